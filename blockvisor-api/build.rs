@@ -19,6 +19,7 @@ fn main() -> Result<()> {
         .collect();
     builder
         .build_server(true)
+        .codec_path("crate::grpc::codec::AuditCodec")
         .enum_attribute("command", "#[allow(clippy::large_enum_variant)]")
         .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
         .type_attribute(".", "#[serde(deny_unknown_fields)]")
@@ -0,0 +1,3 @@
+drop table org_transfers;
+drop table audit_logs;
+drop type enum_audit_event;
@@ -0,0 +1,31 @@
+create type enum_audit_event as enum (
+  'org_ownership_transfer_requested',
+  'org_ownership_transfer_confirmed',
+  'org_ownership_transferred'
+);
+
+create table audit_logs (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid references orgs (id) on delete cascade,
+  event enum_audit_event not null,
+  event_data jsonb,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_audit_logs_org_id on audit_logs using btree (org_id);
+
+create table org_transfers (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  old_owner_id uuid not null references users (id) on delete cascade,
+  new_owner_id uuid not null references users (id) on delete cascade,
+  old_owner_confirmed_at timestamp with time zone,
+  new_owner_confirmed_at timestamp with time zone,
+  completed_at timestamp with time zone,
+  created_at timestamp with time zone default now() not null
+);
+
+create unique index idx_org_transfers_pending on org_transfers using btree (org_id)
+where completed_at is null;
@@ -0,0 +1,3 @@
+drop index if exists idx_orgs_deleted_at;
+
+alter table orgs drop column purged_at;
@@ -0,0 +1,3 @@
+alter table orgs add column purged_at timestamp with time zone;
+
+create index idx_orgs_deleted_at on orgs using btree (deleted_at) where deleted_at is not null and purged_at is null;
@@ -0,0 +1,3 @@
+drop table user_export_requests;
+
+drop type enum_user_export_status;
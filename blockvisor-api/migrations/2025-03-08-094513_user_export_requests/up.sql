@@ -0,0 +1,15 @@
+create type enum_user_export_status as enum ('pending', 'done', 'failed');
+
+create table user_export_requests (
+  id uuid primary key default uuid_generate_v4 (),
+  user_id uuid not null references users (id) on delete cascade,
+  status enum_user_export_status not null default 'pending',
+  attempts integer not null default 0,
+  last_error text,
+  claimed_until timestamp with time zone,
+  created_at timestamp with time zone default now() not null,
+  completed_at timestamp with time zone
+);
+
+create index idx_user_export_requests_pending on user_export_requests (created_at)
+where status = 'pending';
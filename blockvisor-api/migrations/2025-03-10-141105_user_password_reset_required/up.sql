@@ -0,0 +1 @@
+alter table users add column password_reset_required boolean not null default false;
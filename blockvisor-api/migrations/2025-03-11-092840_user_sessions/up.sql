@@ -0,0 +1,11 @@
+create table user_sessions (
+  id uuid primary key default uuid_generate_v4 (),
+  user_id uuid not null references users (id) on delete cascade,
+  device text,
+  ip_address text,
+  created_at timestamp with time zone default now() not null,
+  last_seen_at timestamp with time zone default now() not null,
+  revoked_at timestamp with time zone
+);
+
+create index idx_user_sessions_user_id on user_sessions using btree (user_id);
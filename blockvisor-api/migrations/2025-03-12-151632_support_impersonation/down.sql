@@ -0,0 +1,2 @@
+-- we cannot drop values from an enum (without creating a new type)
+alter table users drop column support_access_until;
@@ -0,0 +1,4 @@
+alter table users add column support_access_until timestamp with time zone;
+
+alter type enum_audit_event add value if not exists 'user_impersonated';
+alter type enum_audit_event add value if not exists 'impersonated_request';
@@ -0,0 +1,6 @@
+alter table protocols drop column lifecycle;
+alter table protocols drop column networks;
+alter table protocols drop column logo_url;
+alter table protocols drop column docs_url;
+
+drop type enum_protocol_lifecycle;
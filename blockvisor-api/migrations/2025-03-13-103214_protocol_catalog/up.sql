@@ -0,0 +1,6 @@
+create type enum_protocol_lifecycle as enum ('active', 'beta', 'deprecated', 'retired');
+
+alter table protocols add column docs_url text;
+alter table protocols add column logo_url text;
+alter table protocols add column networks text[] not null default '{}';
+alter table protocols add column lifecycle enum_protocol_lifecycle not null default 'active';
@@ -0,0 +1 @@
+drop table protocol_grants;
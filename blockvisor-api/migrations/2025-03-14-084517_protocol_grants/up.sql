@@ -0,0 +1,13 @@
+create table protocol_grants (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  protocol_id uuid not null references protocols (id) on delete cascade,
+  protocol_version_id uuid references protocol_versions (id) on delete cascade,
+  created_at timestamp with time zone default now() not null
+);
+
+create unique index idx_protocol_grants_org_protocol on protocol_grants using btree (org_id, protocol_id)
+where protocol_version_id is null;
+create unique index idx_protocol_grants_org_version on protocol_grants using btree (org_id, protocol_version_id)
+where protocol_version_id is not null;
+create index idx_protocol_grants_protocol_id on protocol_grants using btree (protocol_id);
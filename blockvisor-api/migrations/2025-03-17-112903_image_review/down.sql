@@ -0,0 +1,3 @@
+alter table images drop column review_status;
+
+drop type enum_image_review_status;
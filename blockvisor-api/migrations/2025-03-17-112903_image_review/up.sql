@@ -0,0 +1,3 @@
+create type enum_image_review_status as enum ('pending_review', 'approved', 'rejected');
+
+alter table images add column review_status enum_image_review_status not null default 'approved';
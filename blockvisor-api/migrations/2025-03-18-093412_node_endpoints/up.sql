@@ -0,0 +1,19 @@
+create table node_endpoints (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  node_id uuid references nodes (id) on delete cascade,
+  label text not null,
+  token_hash text not null,
+  token_salt text not null,
+  allowed_methods text[] not null default '{}',
+  rate_limit_rps integer,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  revoked_at timestamp with time zone
+);
+
+create index idx_node_endpoints_org_id on node_endpoints using btree (org_id)
+where revoked_at is null;
+create index idx_node_endpoints_node_id on node_endpoints using btree (node_id)
+where revoked_at is null;
@@ -0,0 +1,4 @@
+drop table endpoint_usage;
+
+alter table node_endpoints drop column monthly_quota;
+alter table node_endpoints drop column stripe_item_id;
@@ -0,0 +1,12 @@
+alter table node_endpoints add column monthly_quota bigint;
+alter table node_endpoints add column stripe_item_id text;
+
+create table endpoint_usage (
+  endpoint_id uuid not null references node_endpoints (id) on delete cascade,
+  hour timestamp with time zone not null,
+  request_count bigint not null default 0,
+  error_count bigint not null default 0,
+  latency_ms_total bigint not null default 0,
+  latency_ms_max bigint not null default 0,
+  primary key (endpoint_id, hour)
+);
@@ -0,0 +1,2 @@
+drop table status_pages;
+drop table node_health_history;
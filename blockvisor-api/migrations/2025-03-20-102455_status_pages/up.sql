@@ -0,0 +1,25 @@
+create table node_health_history (
+  node_id uuid not null references nodes (id) on delete cascade,
+  hour timestamp with time zone not null,
+  samples integer not null default 0,
+  healthy_samples integer not null default 0,
+  primary key (node_id, hour)
+);
+
+create table status_pages (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  name text not null,
+  slug text not null,
+  is_public boolean not null default false,
+  custom_domain text,
+  domain_token text,
+  domain_verified_at timestamp with time zone,
+  node_ids uuid[] not null default '{}',
+  node_tags text[] not null default '{}',
+  created_at timestamp with time zone default now() not null
+);
+
+create unique index idx_status_pages_slug on status_pages using btree (slug);
+create unique index idx_status_pages_custom_domain on status_pages using btree (custom_domain);
+create index idx_status_pages_org_id on status_pages using btree (org_id);
@@ -0,0 +1,3 @@
+drop table org_slo_reports;
+drop table node_slo;
+drop table node_downtimes;
@@ -0,0 +1,30 @@
+create table node_downtimes (
+  id uuid primary key default uuid_generate_v4 (),
+  node_id uuid not null references nodes (id) on delete cascade,
+  node_state enum_node_state not null,
+  started_at timestamp with time zone default now() not null,
+  ended_at timestamp with time zone
+);
+
+create index idx_node_downtimes_node_id on node_downtimes using btree (node_id, started_at);
+create unique index idx_node_downtimes_open on node_downtimes using btree (node_id)
+where ended_at is null;
+
+create table node_slo (
+  node_id uuid not null references nodes (id) on delete cascade,
+  org_id uuid not null references orgs (id) on delete cascade,
+  month date not null,
+  total_seconds bigint not null,
+  downtime_seconds bigint not null,
+  updated_at timestamp with time zone default now() not null,
+  primary key (node_id, month)
+);
+
+create index idx_node_slo_org_id on node_slo using btree (org_id, month);
+
+create table org_slo_reports (
+  org_id uuid not null references orgs (id) on delete cascade,
+  month date not null,
+  sent_at timestamp with time zone default now() not null,
+  primary key (org_id, month)
+);
@@ -0,0 +1,6 @@
+drop table incident_links;
+drop table incident_comments;
+drop table incidents;
+
+drop type enum_incident_link_type;
+drop type enum_incident_status;
@@ -0,0 +1,43 @@
+create type enum_incident_status as enum ('open', 'acknowledged', 'resolved');
+create type enum_incident_link_type as enum ('command', 'downtime');
+
+create table incidents (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  node_id uuid not null references nodes (id) on delete cascade,
+  report_id uuid references node_reports (id) on delete set null,
+  title text not null,
+  status enum_incident_status not null default 'open',
+  assignee_id uuid references users (id) on delete set null,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  acknowledged_at timestamp with time zone,
+  resolved_at timestamp with time zone
+);
+
+create unique index idx_incidents_report_id on incidents using btree (report_id);
+create index idx_incidents_org_id on incidents using btree (org_id, status);
+create index idx_incidents_node_id on incidents using btree (node_id);
+create index idx_incidents_assignee_id on incidents using btree (assignee_id)
+where resolved_at is null;
+
+create table incident_comments (
+  id uuid primary key default uuid_generate_v4 (),
+  incident_id uuid not null references incidents (id) on delete cascade,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  message text not null,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_incident_comments_incident_id on incident_comments using btree (incident_id, created_at);
+
+create table incident_links (
+  incident_id uuid not null references incidents (id) on delete cascade,
+  link_type enum_incident_link_type not null,
+  link_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  primary key (incident_id, link_type, link_id)
+);
@@ -0,0 +1,4 @@
+drop table tickets;
+drop table ticket_integrations;
+
+drop type enum_ticket_provider;
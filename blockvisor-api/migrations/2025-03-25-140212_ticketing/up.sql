@@ -0,0 +1,32 @@
+create type enum_ticket_provider as enum ('zendesk', 'jira');
+
+create table ticket_integrations (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  provider enum_ticket_provider not null,
+  base_url text not null,
+  username text not null,
+  api_token text not null,
+  project_key text,
+  webhook_hash text not null,
+  webhook_salt text not null,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null
+);
+
+create unique index idx_ticket_integrations_org_id on ticket_integrations using btree (org_id);
+
+create table tickets (
+  id uuid primary key default uuid_generate_v4 (),
+  incident_id uuid not null references incidents (id) on delete cascade,
+  integration_id uuid not null references ticket_integrations (id) on delete cascade,
+  external_id text not null,
+  url text not null,
+  status enum_incident_status not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null
+);
+
+create unique index idx_tickets_incident_id on tickets using btree (incident_id);
+create unique index idx_tickets_external_id on tickets using btree (integration_id, external_id);
@@ -0,0 +1,3 @@
+alter table audit_logs drop column request_id;
+
+drop table request_logs;
@@ -0,0 +1,16 @@
+create table request_logs (
+  id uuid primary key default uuid_generate_v4 (),
+  request_id text not null,
+  method text not null,
+  grpc_code integer not null,
+  message text,
+  duration_ms bigint not null,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_request_logs_request_id on request_logs using btree (request_id);
+create index idx_request_logs_created_at on request_logs using btree (created_at);
+
+alter table audit_logs add column request_id text;
+
+create index idx_audit_logs_request_id on audit_logs using btree (request_id);
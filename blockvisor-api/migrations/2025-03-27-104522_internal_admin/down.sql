@@ -0,0 +1 @@
+-- we cannot drop values from an enum (without creating a new type)
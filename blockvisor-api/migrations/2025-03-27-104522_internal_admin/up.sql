@@ -0,0 +1 @@
+alter type enum_audit_event add value if not exists 'node_state_overridden';
@@ -0,0 +1,8 @@
+drop index idx_nodes_pending_cleanup;
+
+alter table nodes drop column cleanup_claimed_until;
+alter table nodes drop column cleanup_error;
+alter table nodes drop column cleanup_attempts;
+alter table nodes drop column cleanup;
+
+drop type enum_node_cleanup;
@@ -0,0 +1,12 @@
+create type enum_node_cleanup as enum ('remove_dns', 'remove_stripe', 'complete');
+
+alter table nodes add column cleanup enum_node_cleanup;
+alter table nodes add column cleanup_attempts integer not null default 0;
+alter table nodes add column cleanup_error text;
+alter table nodes add column cleanup_claimed_until timestamptz;
+
+-- nodes deleted before now had their external state removed synchronously
+update nodes set cleanup = 'complete' where deleted_at is not null;
+
+create index idx_nodes_pending_cleanup on nodes (deleted_at)
+where cleanup is not null and cleanup != 'complete';
@@ -0,0 +1,2 @@
+-- we cannot drop values from an enum (without creating a new type)
+drop table feature_flags;
@@ -0,0 +1,15 @@
+create table feature_flags (
+  id uuid primary key default uuid_generate_v4 (),
+  name text not null,
+  description text,
+  enabled boolean not null default false,
+  rollout_percent integer not null default 0 check (rollout_percent between 0 and 100),
+  org_ids uuid[] not null default '{}',
+  user_ids uuid[] not null default '{}',
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null
+);
+
+create unique index idx_feature_flags_name on feature_flags using btree (name);
+
+alter type enum_audit_event add value if not exists 'feature_flag_set';
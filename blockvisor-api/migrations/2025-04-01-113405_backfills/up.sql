@@ -0,0 +1,9 @@
+create table backfills (
+  name text primary key,
+  cursor uuid,
+  processed bigint not null default 0,
+  error text,
+  started_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  completed_at timestamp with time zone
+);
@@ -0,0 +1,2 @@
+alter table nodes drop column peer_count;
+alter table images drop column health_rules;
@@ -0,0 +1,2 @@
+alter table images add column health_rules jsonb;
+alter table nodes add column peer_count int;
@@ -0,0 +1,3 @@
+-- we cannot drop values from an enum (without creating a new type)
+drop table network_heads;
+drop type enum_head_source;
@@ -0,0 +1,15 @@
+create type enum_head_source as enum ('rpc', 'reference_node');
+
+create table network_heads (
+  protocol_id uuid not null references protocols on delete cascade,
+  variant_key text not null,
+  rpc_url text,
+  block_height bigint,
+  source enum_head_source,
+  reference_node_id uuid references nodes on delete set null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  primary key (protocol_id, variant_key)
+);
+
+alter type enum_audit_event add value if not exists 'network_head_set';
@@ -0,0 +1,2 @@
+-- we cannot drop values from an enum (without creating a new type)
+delete from commands where command_type = 'host_upgrade';
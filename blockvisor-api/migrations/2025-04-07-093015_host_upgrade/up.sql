@@ -0,0 +1 @@
+alter type enum_command_type add value if not exists 'host_upgrade';
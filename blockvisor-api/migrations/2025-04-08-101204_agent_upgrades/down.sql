@@ -0,0 +1,8 @@
+alter table orgs drop column agent_upgrade_policy;
+
+drop table agent_upgrade_hosts;
+drop table agent_upgrades;
+
+drop type enum_agent_upgrade_policy;
+drop type enum_agent_upgrade_host_status;
+drop type enum_agent_upgrade_status;
@@ -0,0 +1,39 @@
+create type enum_agent_upgrade_status as enum ('running', 'halted', 'completed');
+create type enum_agent_upgrade_host_status as enum ('pending', 'sent', 'acked', 'succeeded', 'failed');
+create type enum_agent_upgrade_policy as enum ('automatic', 'manual');
+
+create table agent_upgrades (
+  id uuid primary key default uuid_generate_v4(),
+  version text not null,
+  wave_size integer not null,
+  wave_interval_seconds integer not null,
+  max_failures integer not null,
+  status enum_agent_upgrade_status not null default 'running',
+  current_wave integer not null default 0,
+  halted_reason text,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  wave_sent_at timestamp with time zone,
+  completed_at timestamp with time zone
+);
+
+create index idx_agent_upgrades_status on agent_upgrades (status);
+
+create table agent_upgrade_hosts (
+  upgrade_id uuid not null references agent_upgrades on delete cascade,
+  host_id uuid not null references hosts on delete cascade,
+  wave integer not null,
+  status enum_agent_upgrade_host_status not null default 'pending',
+  command_id uuid references commands on delete set null,
+  error text,
+  sent_at timestamp with time zone,
+  acked_at timestamp with time zone,
+  completed_at timestamp with time zone,
+  primary key (upgrade_id, host_id)
+);
+
+create index idx_agent_upgrade_hosts_command_id on agent_upgrade_hosts (command_id);
+
+alter table orgs add column agent_upgrade_policy enum_agent_upgrade_policy not null default 'automatic';
@@ -0,0 +1,9 @@
+alter table nodes drop column scheduler_pool_id;
+alter table nodes drop column scheduler_placement;
+
+drop index idx_hosts_pool_id;
+alter table hosts drop column pool_id;
+
+drop table host_pools;
+
+drop type enum_node_placement;
@@ -0,0 +1,17 @@
+create type enum_node_placement as enum ('any', 'private', 'public');
+
+create table host_pools (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  name text not null,
+  description text,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  unique (org_id, name)
+);
+
+alter table hosts add column pool_id uuid references host_pools on delete set null;
+create index idx_hosts_pool_id on hosts (pool_id);
+
+alter table nodes add column scheduler_placement enum_node_placement not null default 'any';
+alter table nodes add column scheduler_pool_id uuid references host_pools on delete set null;
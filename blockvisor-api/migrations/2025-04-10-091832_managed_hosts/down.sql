@@ -0,0 +1,4 @@
+drop table managed_hosts;
+
+drop type enum_managed_host_status;
+drop type enum_cloud_provider;
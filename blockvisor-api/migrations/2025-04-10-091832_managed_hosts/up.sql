@@ -0,0 +1,33 @@
+create type enum_cloud_provider as enum ('aws', 'hetzner', 'ovh');
+create type enum_managed_host_status as enum (
+  'creating',
+  'bootstrapping',
+  'ready',
+  'failed',
+  'deleting',
+  'deleted'
+);
+
+create table managed_hosts (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid not null references orgs (id) on delete cascade,
+  region_id uuid not null references regions (id),
+  provider enum_cloud_provider not null,
+  provider_region text not null,
+  machine_type text not null,
+  machine_image text not null,
+  name text not null,
+  status enum_managed_host_status not null default 'creating',
+  external_id text,
+  ip_address inet,
+  host_id uuid references hosts (id) on delete set null,
+  error text,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  deleted_at timestamp with time zone
+);
+
+create index idx_managed_hosts_org_id on managed_hosts using btree (org_id);
+create index idx_managed_hosts_status on managed_hosts using btree (status);
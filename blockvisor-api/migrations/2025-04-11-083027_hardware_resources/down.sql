@@ -0,0 +1,12 @@
+alter table nodes drop column gpu_count;
+
+alter table images drop column min_network_bandwidth_mbps;
+alter table images drop column min_nvme_iops;
+alter table images drop column gpu_model;
+alter table images drop column min_gpu_count;
+
+alter table hosts drop column node_gpu_count;
+alter table hosts drop column network_bandwidth_mbps;
+alter table hosts drop column nvme_iops;
+alter table hosts drop column gpu_model;
+alter table hosts drop column gpu_count;
@@ -0,0 +1,12 @@
+alter table hosts add column gpu_count bigint not null default 0;
+alter table hosts add column gpu_model text;
+alter table hosts add column nvme_iops bigint;
+alter table hosts add column network_bandwidth_mbps bigint;
+alter table hosts add column node_gpu_count bigint not null default 0;
+
+alter table images add column min_gpu_count bigint not null default 0;
+alter table images add column gpu_model text;
+alter table images add column min_nvme_iops bigint;
+alter table images add column min_network_bandwidth_mbps bigint;
+
+alter table nodes add column gpu_count bigint not null default 0;
@@ -0,0 +1,9 @@
+alter table orgs drop column disk_auto_approve_bytes;
+alter table orgs drop column disk_quota_bytes;
+
+drop table disk_resizes;
+
+drop type enum_disk_resize_status;
+
+-- we cannot drop values from an enum (without creating a new type)
+delete from commands where command_type = 'node_resize';
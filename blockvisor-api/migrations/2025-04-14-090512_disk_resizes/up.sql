@@ -0,0 +1,26 @@
+alter type enum_command_type add value if not exists 'node_resize';
+
+create type enum_disk_resize_status as enum ('pending', 'approved', 'rejected');
+
+create table disk_resizes (
+  id uuid primary key default uuid_generate_v4(),
+  node_id uuid not null references nodes on delete cascade,
+  org_id uuid not null references orgs on delete cascade,
+  old_disk_bytes bigint not null,
+  new_disk_bytes bigint not null,
+  used_disk_bytes bigint,
+  status enum_disk_resize_status not null default 'pending',
+  command_id uuid references commands on delete set null,
+  requested_by_type enum_resource_type not null,
+  requested_by_id uuid not null,
+  reviewed_by_type enum_resource_type,
+  reviewed_by_id uuid,
+  created_at timestamp with time zone default now() not null,
+  reviewed_at timestamp with time zone
+);
+
+create index idx_disk_resizes_org_id on disk_resizes (org_id);
+create unique index idx_disk_resizes_pending_node_id on disk_resizes (node_id) where status = 'pending';
+
+alter table orgs add column disk_quota_bytes bigint;
+alter table orgs add column disk_auto_approve_bytes bigint;
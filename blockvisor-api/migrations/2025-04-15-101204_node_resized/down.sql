@@ -0,0 +1,2 @@
+-- we cannot drop values from an enum (without creating a new type)
+delete from node_logs where event = 'resized';
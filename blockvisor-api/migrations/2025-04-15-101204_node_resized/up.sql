@@ -0,0 +1 @@
+alter type enum_node_event add value if not exists 'resized';
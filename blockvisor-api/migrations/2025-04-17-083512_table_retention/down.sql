@@ -0,0 +1,72 @@
+alter table node_health_history rename to node_health_history_partitioned;
+alter table node_health_history_partitioned rename constraint node_health_history_pkey to node_health_history_partitioned_pkey;
+
+create table node_health_history (
+  node_id uuid not null references nodes (id) on delete cascade,
+  hour timestamp with time zone not null,
+  samples integer not null default 0,
+  healthy_samples integer not null default 0,
+  primary key (node_id, hour)
+);
+
+insert into node_health_history (node_id, hour, samples, healthy_samples)
+select node_id, hour, samples, healthy_samples
+from node_health_history_partitioned;
+
+drop table node_health_history_partitioned;
+
+alter table audit_logs rename to audit_logs_partitioned;
+alter table audit_logs_partitioned rename constraint audit_logs_pkey to audit_logs_partitioned_pkey;
+alter index idx_audit_logs_org_id rename to idx_audit_logs_partitioned_org_id;
+alter index idx_audit_logs_request_id rename to idx_audit_logs_partitioned_request_id;
+
+create table audit_logs (
+  id uuid primary key default uuid_generate_v4 (),
+  org_id uuid references orgs (id) on delete cascade,
+  event enum_audit_event not null,
+  event_data jsonb,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  request_id text
+);
+
+insert into audit_logs (id, org_id, event, event_data, created_by_type, created_by_id, created_at, request_id)
+select id, org_id, event, event_data, created_by_type, created_by_id, created_at, request_id
+from audit_logs_partitioned;
+
+drop table audit_logs_partitioned;
+
+create index idx_audit_logs_org_id on audit_logs using btree (org_id);
+
+create index idx_audit_logs_request_id on audit_logs using btree (request_id);
+
+alter table node_logs rename to node_logs_partitioned;
+alter table node_logs_partitioned rename constraint node_logs_pkey to node_logs_partitioned_pkey;
+alter index idx_nodes_logs_node_id rename to idx_nodes_logs_partitioned_node_id;
+alter index idx_nodes_logs_host_id rename to idx_nodes_logs_partitioned_host_id;
+
+create table node_logs (
+  id uuid primary key default uuid_generate_v4 (),
+  node_id uuid not null references nodes (id),
+  host_id uuid not null references hosts (id),
+  event enum_node_event not null,
+  event_data jsonb,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null
+);
+
+insert into node_logs (id, node_id, host_id, event, event_data, created_by_type, created_by_id, created_at)
+select id, node_id, host_id, event, event_data, created_by_type, created_by_id, created_at
+from node_logs_partitioned;
+
+drop table node_logs_partitioned;
+
+create index idx_nodes_logs_node_id on node_logs using btree (node_id);
+
+create index idx_nodes_logs_host_id on node_logs using btree (host_id);
+
+drop function create_monthly_partitions (text, timestamp with time zone);
+
+drop function create_monthly_partition (text, date);
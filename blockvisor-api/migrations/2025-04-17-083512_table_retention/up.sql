@@ -0,0 +1,112 @@
+-- creates the partition of `parent` covering the month of `month`, named
+-- `{parent}_pYYYYMM`, unless it already exists
+create or replace function create_monthly_partition (parent text, month date)
+  returns void
+  language plpgsql
+  as $$
+declare
+  start_at date := date_trunc('month', month)::date;
+  end_at date := (date_trunc('month', month) + interval '1 month')::date;
+begin
+  execute format('create table if not exists %I partition of %I for values from (%L) to (%L)',
+    parent || '_p' || to_char(start_at, 'YYYYMM'), parent, start_at, end_at);
+end;
+$$;
+
+-- creates the monthly partitions of `parent` from `first` until next month
+create or replace function create_monthly_partitions (parent text, first timestamp with time zone)
+  returns void
+  language plpgsql
+  as $$
+declare
+  month date := date_trunc('month', coalesce(first, now()))::date;
+begin
+  while month <= date_trunc('month', now() + interval '1 month') loop
+    perform create_monthly_partition (parent, month);
+    month := (month + interval '1 month')::date;
+  end loop;
+end;
+$$;
+
+alter table node_logs rename to node_logs_unpartitioned;
+alter table node_logs_unpartitioned rename constraint node_logs_pkey to node_logs_unpartitioned_pkey;
+alter index idx_nodes_logs_node_id rename to idx_nodes_logs_unpartitioned_node_id;
+alter index idx_nodes_logs_host_id rename to idx_nodes_logs_unpartitioned_host_id;
+
+create table node_logs (
+  id uuid not null default uuid_generate_v4 (),
+  node_id uuid not null references nodes (id),
+  host_id uuid not null references hosts (id),
+  event enum_node_event not null,
+  event_data jsonb,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  primary key (id, created_at)
+) partition by range (created_at);
+
+create table node_logs_default partition of node_logs default;
+
+select create_monthly_partitions ('node_logs', (select min(created_at) from node_logs_unpartitioned));
+
+insert into node_logs (id, node_id, host_id, event, event_data, created_by_type, created_by_id, created_at)
+select id, node_id, host_id, event, event_data, created_by_type, created_by_id, created_at
+from node_logs_unpartitioned;
+
+drop table node_logs_unpartitioned;
+
+create index idx_nodes_logs_node_id on node_logs using btree (node_id);
+
+create index idx_nodes_logs_host_id on node_logs using btree (host_id);
+
+alter table audit_logs rename to audit_logs_unpartitioned;
+alter table audit_logs_unpartitioned rename constraint audit_logs_pkey to audit_logs_unpartitioned_pkey;
+alter index idx_audit_logs_org_id rename to idx_audit_logs_unpartitioned_org_id;
+alter index idx_audit_logs_request_id rename to idx_audit_logs_unpartitioned_request_id;
+
+create table audit_logs (
+  id uuid not null default uuid_generate_v4 (),
+  org_id uuid references orgs (id) on delete cascade,
+  event enum_audit_event not null,
+  event_data jsonb,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  request_id text,
+  primary key (id, created_at)
+) partition by range (created_at);
+
+create table audit_logs_default partition of audit_logs default;
+
+select create_monthly_partitions ('audit_logs', (select min(created_at) from audit_logs_unpartitioned));
+
+insert into audit_logs (id, org_id, event, event_data, created_by_type, created_by_id, created_at, request_id)
+select id, org_id, event, event_data, created_by_type, created_by_id, created_at, request_id
+from audit_logs_unpartitioned;
+
+drop table audit_logs_unpartitioned;
+
+create index idx_audit_logs_org_id on audit_logs using btree (org_id);
+
+create index idx_audit_logs_request_id on audit_logs using btree (request_id);
+
+alter table node_health_history rename to node_health_history_unpartitioned;
+alter table node_health_history_unpartitioned rename constraint node_health_history_pkey to node_health_history_unpartitioned_pkey;
+
+create table node_health_history (
+  node_id uuid not null references nodes (id) on delete cascade,
+  hour timestamp with time zone not null,
+  samples integer not null default 0,
+  healthy_samples integer not null default 0,
+  primary key (node_id, hour)
+) partition by range (hour);
+
+create table node_health_history_default partition of node_health_history default;
+
+select create_monthly_partitions ('node_health_history', (select min(hour) from node_health_history_unpartitioned));
+
+insert into node_health_history (node_id, hour, samples, healthy_samples)
+select node_id, hour, samples, healthy_samples
+from node_health_history_unpartitioned;
+
+drop table node_health_history_unpartitioned;
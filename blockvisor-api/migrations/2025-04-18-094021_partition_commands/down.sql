@@ -0,0 +1,52 @@
+drop index idx_nodes_logs_node_id_created_at;
+
+create index idx_nodes_logs_node_id on node_logs using btree (node_id);
+
+drop trigger trg_commands_clear_refs on commands;
+
+drop function clear_command_refs ();
+
+alter table commands rename to commands_partitioned;
+alter table commands_partitioned rename constraint commands_pkey to commands_partitioned_pkey;
+alter table commands_partitioned rename constraint fk_commands_host_id to fk_commands_partitioned_host_id;
+alter table commands_partitioned rename constraint fk_commands_node_id to fk_commands_partitioned_node_id;
+alter index idx_commands_id rename to idx_commands_partitioned_id;
+alter index idx_commands_completed_at rename to idx_commands_partitioned_completed_at;
+alter index idx_commands_host_id_created_at rename to idx_commands_partitioned_host_id_created_at;
+alter index idx_commands_node_id_created_at rename to idx_commands_partitioned_node_id_created_at;
+
+create table commands (
+  like commands_partitioned including defaults,
+  primary key (id)
+);
+
+insert into commands
+select * from commands_partitioned;
+
+drop table commands_partitioned;
+
+alter table commands
+  add constraint fk_commands_host_id foreign key (host_id) references hosts (id);
+
+alter table commands
+  add constraint fk_commands_node_id foreign key (node_id) references nodes (id);
+
+create index idx_commands_completed_at on commands using btree (completed_at);
+
+create index idx_commands_created_at on commands using btree (created_at);
+
+create index idx_commands_host_id on commands using btree (host_id);
+
+create index idx_commands_node_id on commands using btree (node_id);
+
+update agent_upgrade_hosts set command_id = null
+where command_id not in (select id from commands);
+
+update disk_resizes set command_id = null
+where command_id not in (select id from commands);
+
+alter table agent_upgrade_hosts
+  add constraint agent_upgrade_hosts_command_id_fkey foreign key (command_id) references commands on delete set null;
+
+alter table disk_resizes
+  add constraint disk_resizes_command_id_fkey foreign key (command_id) references commands on delete set null;
@@ -0,0 +1,64 @@
+-- a partitioned table can only be referenced through its partition key, so the
+-- `on delete set null` of commands is replaced by a trigger
+alter table agent_upgrade_hosts
+  drop constraint agent_upgrade_hosts_command_id_fkey;
+
+alter table disk_resizes
+  drop constraint disk_resizes_command_id_fkey;
+
+alter table commands rename to commands_unpartitioned;
+alter table commands_unpartitioned rename constraint commands_pkey to commands_unpartitioned_pkey;
+alter table commands_unpartitioned rename constraint fk_commands_host_id to fk_commands_unpartitioned_host_id;
+alter table commands_unpartitioned rename constraint fk_commands_node_id to fk_commands_unpartitioned_node_id;
+alter index idx_commands_completed_at rename to idx_commands_unpartitioned_completed_at;
+alter index idx_commands_created_at rename to idx_commands_unpartitioned_created_at;
+alter index idx_commands_host_id rename to idx_commands_unpartitioned_host_id;
+alter index idx_commands_node_id rename to idx_commands_unpartitioned_node_id;
+
+create table commands (
+  like commands_unpartitioned including defaults,
+  primary key (id, created_at)
+) partition by range (created_at);
+
+create table commands_default partition of commands default;
+
+select create_monthly_partitions ('commands', (select min(created_at) from commands_unpartitioned));
+
+insert into commands
+select * from commands_unpartitioned;
+
+drop table commands_unpartitioned;
+
+alter table commands
+  add constraint fk_commands_host_id foreign key (host_id) references hosts (id);
+
+alter table commands
+  add constraint fk_commands_node_id foreign key (node_id) references nodes (id);
+
+create index idx_commands_id on commands using btree (id);
+
+create index idx_commands_completed_at on commands using btree (completed_at);
+
+create index idx_commands_host_id_created_at on commands using btree (host_id, created_at);
+
+create index idx_commands_node_id_created_at on commands using btree (node_id, created_at);
+
+create or replace function clear_command_refs ()
+  returns trigger
+  language plpgsql
+  as $$
+begin
+  update agent_upgrade_hosts set command_id = null where command_id = old.id;
+  update disk_resizes set command_id = null where command_id = old.id;
+  return old;
+end;
+$$;
+
+create trigger trg_commands_clear_refs
+  after delete on commands
+  for each row
+  execute function clear_command_refs ();
+
+drop index idx_nodes_logs_node_id;
+
+create index idx_nodes_logs_node_id_created_at on node_logs using btree (node_id, created_at);
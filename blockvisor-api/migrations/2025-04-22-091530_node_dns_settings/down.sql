@@ -0,0 +1,2 @@
+alter table nodes drop column dns_record_ids;
+alter table images drop column dns_settings;
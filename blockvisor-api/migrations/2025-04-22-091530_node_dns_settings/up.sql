@@ -0,0 +1,2 @@
+alter table images add column dns_settings jsonb;
+alter table nodes add column dns_record_ids text[] not null default '{}';
@@ -0,0 +1,2 @@
+drop table node_dns_aliases;
+drop type enum_dns_alias_type;
@@ -0,0 +1,15 @@
+create type enum_dns_alias_type as enum ('cname', 'address');
+
+create table node_dns_aliases (
+  id uuid primary key default uuid_generate_v4(),
+  node_id uuid not null references nodes on delete cascade,
+  name text not null,
+  alias_type enum_dns_alias_type not null,
+  dns_id text not null,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null
+);
+
+create unique index idx_node_dns_aliases_name on node_dns_aliases (name);
+create index idx_node_dns_aliases_node_id on node_dns_aliases (node_id);
@@ -0,0 +1,6 @@
+drop table node_certificates;
+
+drop type enum_certificate_status;
+
+-- we cannot drop values from an enum (without creating a new type)
+delete from commands where command_type = 'node_certificate';
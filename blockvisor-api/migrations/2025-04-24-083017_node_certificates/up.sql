@@ -0,0 +1,18 @@
+create type enum_certificate_status as enum ('pending', 'issued', 'failed');
+
+create table node_certificates (
+  id uuid primary key default uuid_generate_v4(),
+  node_id uuid not null references nodes on delete cascade,
+  names text[] not null,
+  status enum_certificate_status not null default 'pending',
+  not_after timestamp with time zone,
+  last_error text,
+  alerted_at timestamp with time zone,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null
+);
+
+create unique index idx_node_certificates_node_id on node_certificates (node_id);
+create index idx_node_certificates_not_after on node_certificates (not_after);
+
+alter type enum_command_type add value if not exists 'node_certificate';
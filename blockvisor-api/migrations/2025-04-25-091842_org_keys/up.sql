@@ -0,0 +1,48 @@
+create type enum_org_key_type as enum ('ssh', 'wireguard');
+create type enum_org_key_host_status as enum ('sent', 'applied', 'failed');
+
+create table org_keys (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  name text not null,
+  key_type enum_org_key_type not null,
+  public_key text not null,
+  fingerprint text not null,
+  replaces_id uuid references org_keys on delete set null,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  revoked_at timestamp with time zone
+);
+
+create index idx_org_keys_org_id on org_keys (org_id);
+create unique index idx_org_keys_org_id_fingerprint on org_keys (org_id, fingerprint) where revoked_at is null;
+
+create table org_key_hosts (
+  key_id uuid not null references org_keys on delete cascade,
+  host_id uuid not null references hosts on delete cascade,
+  status enum_org_key_host_status not null default 'sent',
+  command_id uuid,
+  error text,
+  sent_at timestamp with time zone default now() not null,
+  applied_at timestamp with time zone,
+  primary key (key_id, host_id)
+);
+
+create index idx_org_key_hosts_host_id on org_key_hosts (host_id);
+create index idx_org_key_hosts_command_id on org_key_hosts (command_id);
+
+-- commands is partitioned, so `command_id` is cleared by the trigger instead
+create or replace function clear_command_refs ()
+  returns trigger
+  language plpgsql
+  as $$
+begin
+  update agent_upgrade_hosts set command_id = null where command_id = old.id;
+  update disk_resizes set command_id = null where command_id = old.id;
+  update org_key_hosts set command_id = null where command_id = old.id;
+  return old;
+end;
+$$;
+
+alter type enum_command_type add value if not exists 'host_keys';
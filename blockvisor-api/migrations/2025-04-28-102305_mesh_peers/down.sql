@@ -0,0 +1,5 @@
+drop table mesh_peers;
+drop table mesh_networks;
+
+-- we cannot drop values from an enum (without creating a new type)
+delete from commands where command_type = 'host_mesh';
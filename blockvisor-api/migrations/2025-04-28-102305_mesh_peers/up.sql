@@ -0,0 +1,22 @@
+create table mesh_networks (
+  org_id uuid primary key references orgs on delete cascade,
+  subnet cidr not null unique,
+  created_at timestamp with time zone default now() not null
+);
+
+create table mesh_peers (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references mesh_networks on delete cascade,
+  host_id uuid not null references hosts on delete cascade,
+  node_id uuid references nodes on delete cascade,
+  public_key text not null unique,
+  ip inet not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null
+);
+
+create unique index idx_mesh_peers_org_id_ip on mesh_peers (org_id, ip);
+create unique index idx_mesh_peers_host_id on mesh_peers (host_id) where node_id is null;
+create unique index idx_mesh_peers_node_id on mesh_peers (node_id);
+
+alter type enum_command_type add value if not exists 'host_mesh';
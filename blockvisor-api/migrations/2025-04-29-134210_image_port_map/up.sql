@@ -0,0 +1 @@
+alter table images add column port_map jsonb;
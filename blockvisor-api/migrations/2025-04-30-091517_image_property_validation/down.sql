@@ -0,0 +1 @@
+alter table image_properties drop column validation;
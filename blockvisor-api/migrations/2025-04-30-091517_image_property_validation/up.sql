@@ -0,0 +1 @@
+alter table image_properties add column validation jsonb;
@@ -0,0 +1,10 @@
+-- we cannot drop values from an enum (without creating a new type), and the
+-- values of secret properties are only kept in the secret store, so rewriting
+-- them to 'password' would show nodes their secret paths as passwords
+do $$
+begin
+  if exists (select 1 from image_properties where ui_type = 'secret') then
+    raise exception 'remove image properties with ui_type secret before reverting';
+  end if;
+end
+$$;
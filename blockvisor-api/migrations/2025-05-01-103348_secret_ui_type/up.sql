@@ -0,0 +1 @@
+alter type enum_ui_type add value if not exists 'secret';
@@ -0,0 +1,7 @@
+drop index idx_nodes_config_drift;
+
+alter table nodes drop column config_reported_at;
+alter table nodes drop column config_drift;
+
+-- we cannot drop values from an enum (without creating a new type)
+delete from commands where command_type = 'node_report_config';
@@ -0,0 +1,6 @@
+alter type enum_command_type add value if not exists 'node_report_config';
+
+alter table nodes add column config_drift boolean not null default false;
+alter table nodes add column config_reported_at timestamp with time zone;
+
+create index idx_nodes_config_drift on nodes (config_drift) where config_drift;
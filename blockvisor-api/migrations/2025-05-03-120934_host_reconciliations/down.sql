@@ -0,0 +1,3 @@
+drop table host_reconciliations;
+
+drop type enum_reconciliation_kind;
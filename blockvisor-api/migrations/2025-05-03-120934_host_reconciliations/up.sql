@@ -0,0 +1,12 @@
+create type enum_reconciliation_kind as enum ('unknown_vm', 'missing_node');
+
+create table host_reconciliations (
+  id uuid primary key default uuid_generate_v4(),
+  host_id uuid not null references hosts on delete cascade,
+  vm_id text not null,
+  kind enum_reconciliation_kind not null,
+  created_at timestamp with time zone default now() not null,
+  resolved_at timestamp with time zone
+);
+
+create unique index idx_host_reconciliations_open on host_reconciliations (host_id, kind, vm_id) where resolved_at is null;
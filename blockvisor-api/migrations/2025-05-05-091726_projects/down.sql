@@ -0,0 +1,5 @@
+drop index idx_nodes_project_id;
+alter table nodes drop column project_id;
+
+drop table project_members;
+drop table projects;
@@ -0,0 +1,22 @@
+create table projects (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  name text not null,
+  description text,
+  node_quota bigint,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  unique (org_id, name)
+);
+
+create table project_members (
+  project_id uuid not null references projects on delete cascade,
+  user_id uuid not null references users on delete cascade,
+  created_at timestamp with time zone default now() not null,
+  primary key (project_id, user_id)
+);
+
+create index idx_project_members_user_id on project_members (user_id);
+
+alter table nodes add column project_id uuid references projects on delete set null;
+create index idx_nodes_project_id on nodes (project_id) where deleted_at is null;
@@ -0,0 +1,4 @@
+drop index if exists idx_commands_execute_at;
+
+alter table commands drop column if exists dispatched_at;
+alter table commands drop column if exists execute_at;
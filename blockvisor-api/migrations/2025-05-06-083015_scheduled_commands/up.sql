@@ -0,0 +1,5 @@
+alter table commands add column execute_at timestamp with time zone;
+alter table commands add column dispatched_at timestamp with time zone;
+
+create index idx_commands_execute_at on commands (execute_at)
+  where execute_at is not null and dispatched_at is null;
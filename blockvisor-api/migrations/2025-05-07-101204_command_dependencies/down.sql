@@ -0,0 +1,3 @@
+drop index if exists idx_commands_depends_on;
+
+alter table commands drop column if exists depends_on;
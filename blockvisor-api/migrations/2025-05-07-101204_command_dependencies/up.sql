@@ -0,0 +1,3 @@
+alter table commands add column depends_on uuid;
+
+create index idx_commands_depends_on on commands (depends_on) where depends_on is not null;
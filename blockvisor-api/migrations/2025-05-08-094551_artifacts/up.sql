@@ -0,0 +1,15 @@
+create table artifacts (
+  id uuid primary key default uuid_generate_v4(),
+  command_id uuid not null,
+  host_id uuid not null references hosts on delete cascade,
+  node_id uuid references nodes on delete cascade,
+  name text not null,
+  store_key text not null unique,
+  created_at timestamp with time zone default now() not null,
+  uploaded_at timestamp with time zone,
+  expires_at timestamp with time zone not null
+);
+
+create index idx_artifacts_command_id on artifacts (command_id);
+create index idx_artifacts_node_id on artifacts (node_id) where node_id is not null;
+create index idx_artifacts_expires_at on artifacts (expires_at);
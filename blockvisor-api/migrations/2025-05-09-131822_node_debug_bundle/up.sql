@@ -0,0 +1 @@
+alter type enum_command_type add value if not exists 'node_debug_bundle';
@@ -0,0 +1,7 @@
+alter table orgs drop column stripe_plan_item_id;
+alter table orgs drop column plan_tier;
+
+drop table plans;
+
+drop type enum_support_level;
+drop type enum_plan_tier;
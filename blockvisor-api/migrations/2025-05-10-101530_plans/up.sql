@@ -0,0 +1,27 @@
+create type enum_plan_tier as enum ('free', 'pro', 'enterprise');
+create type enum_support_level as enum ('community', 'standard', 'priority');
+
+create table plans (
+  tier enum_plan_tier primary key,
+  name text not null,
+  max_nodes bigint,
+  max_endpoints bigint,
+  protocol_ids uuid[],
+  private_hosts boolean not null,
+  support_level enum_support_level not null,
+  stripe_sku text,
+  created_at timestamp with time zone not null default now(),
+  updated_at timestamp with time zone not null default now()
+);
+
+insert into plans (tier, name, max_nodes, max_endpoints, protocol_ids, private_hosts, support_level, stripe_sku)
+values
+  ('free', 'Free', 3, 1, null, false, 'community', null),
+  ('pro', 'Pro', 50, 10, null, true, 'standard', 'plan-pro'),
+  ('enterprise', 'Enterprise', null, null, null, true, 'priority', 'plan-enterprise');
+
+alter table orgs add column plan_tier enum_plan_tier not null default 'free' references plans (tier);
+alter table orgs add column stripe_plan_item_id text;
+
+-- existing orgs keep their current access
+update orgs set plan_tier = 'enterprise';
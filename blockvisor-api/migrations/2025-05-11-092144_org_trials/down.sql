@@ -0,0 +1,6 @@
+drop index idx_orgs_trial_ends_at;
+
+alter table orgs drop column hibernated_at;
+alter table orgs drop column trial_reminded_at;
+alter table orgs drop column trial_max_nodes;
+alter table orgs drop column trial_ends_at;
@@ -0,0 +1,6 @@
+alter table orgs add column trial_ends_at timestamp with time zone;
+alter table orgs add column trial_max_nodes bigint;
+alter table orgs add column trial_reminded_at timestamp with time zone;
+alter table orgs add column hibernated_at timestamp with time zone;
+
+create index idx_orgs_trial_ends_at on orgs (trial_ends_at) where trial_ends_at is not null;
@@ -0,0 +1,6 @@
+drop index idx_orgs_partner_id;
+
+alter table orgs drop column referred_at;
+alter table orgs drop column partner_id;
+
+drop table partners;
@@ -0,0 +1,11 @@
+create table partners (
+  id uuid primary key default uuid_generate_v4(),
+  name text not null,
+  referral_code text not null unique,
+  created_at timestamp with time zone default now() not null
+);
+
+alter table orgs add column partner_id uuid references partners on delete set null;
+alter table orgs add column referred_at timestamp with time zone;
+
+create index idx_orgs_partner_id on orgs (partner_id) where partner_id is not null;
@@ -0,0 +1,8 @@
+create table user_signups (
+  id uuid primary key default uuid_generate_v4(),
+  user_id uuid not null references users on delete cascade,
+  ip_address text not null,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_user_signups_ip_address on user_signups (ip_address, created_at);
@@ -0,0 +1,9 @@
+alter table nodes drop column cpu_pegged_since;
+
+drop table node_abuse_reports;
+
+drop type enum_abuse_resolution;
+drop type enum_abuse_reason;
+
+-- we cannot drop values from an enum (without creating a new type)
+update nodes set node_state = 'stopped' where node_state = 'suspended';
@@ -0,0 +1,22 @@
+alter type enum_node_state add value if not exists 'suspended';
+
+create type enum_abuse_reason as enum ('cpu_no_progress', 'mining_port');
+create type enum_abuse_resolution as enum ('dismissed', 'confirmed');
+
+create table node_abuse_reports (
+  id uuid primary key default uuid_generate_v4(),
+  node_id uuid not null references nodes on delete cascade,
+  org_id uuid not null references orgs on delete cascade,
+  reason enum_abuse_reason not null,
+  details text not null,
+  suspended boolean not null,
+  created_at timestamp with time zone not null default now(),
+  reviewed_at timestamp with time zone,
+  reviewed_by uuid references users on delete set null,
+  resolution enum_abuse_resolution
+);
+
+create index idx_node_abuse_reports_node_id on node_abuse_reports (node_id);
+create index idx_node_abuse_reports_unreviewed on node_abuse_reports (created_at) where reviewed_at is null;
+
+alter table nodes add column cpu_pegged_since timestamp with time zone;
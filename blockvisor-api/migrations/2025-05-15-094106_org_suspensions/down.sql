@@ -0,0 +1,7 @@
+drop table org_suspended_nodes;
+
+alter table orgs drop column suspension_banner;
+alter table orgs drop column suspension_reason;
+alter table orgs drop column suspended_at;
+
+drop type enum_suspension_reason;
@@ -0,0 +1,13 @@
+create type enum_suspension_reason as enum ('billing', 'abuse');
+
+alter table orgs add column suspended_at timestamp with time zone;
+alter table orgs add column suspension_reason enum_suspension_reason;
+alter table orgs add column suspension_banner text;
+
+create table org_suspended_nodes (
+  node_id uuid primary key references nodes on delete cascade,
+  org_id uuid not null references orgs on delete cascade,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_org_suspended_nodes_org_id on org_suspended_nodes (org_id);
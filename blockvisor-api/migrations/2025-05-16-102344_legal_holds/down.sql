@@ -0,0 +1,3 @@
+drop table legal_holds;
+
+-- we cannot drop values from an enum (without creating a new type)
@@ -0,0 +1,17 @@
+create table legal_holds (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid references orgs on delete cascade,
+  node_id uuid references nodes on delete cascade,
+  reason text not null,
+  created_by uuid references users on delete set null,
+  created_at timestamp with time zone default now() not null,
+  cleared_at timestamp with time zone,
+  cleared_by uuid references users on delete set null,
+  check ((org_id is null) <> (node_id is null))
+);
+
+create unique index idx_legal_holds_org_id on legal_holds (org_id) where cleared_at is null and org_id is not null;
+create unique index idx_legal_holds_node_id on legal_holds (node_id) where cleared_at is null and node_id is not null;
+
+alter type enum_audit_event add value if not exists 'legal_hold_set';
+alter type enum_audit_event add value if not exists 'legal_hold_cleared';
@@ -0,0 +1,4 @@
+drop table node_share_accesses;
+drop table node_shares;
+
+-- we cannot drop values from an enum (without creating a new type)
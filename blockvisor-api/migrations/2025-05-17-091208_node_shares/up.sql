@@ -0,0 +1,30 @@
+create table node_shares (
+  id uuid primary key default uuid_generate_v4(),
+  node_id uuid not null references nodes on delete cascade,
+  org_id uuid not null references orgs on delete cascade,
+  label text not null,
+  token_hash text not null,
+  token_salt text not null,
+  expires_at timestamp with time zone not null,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  revoked_at timestamp with time zone
+);
+
+create index idx_node_shares_node_id on node_shares (node_id) where revoked_at is null;
+
+create table node_share_accesses (
+  id uuid primary key default uuid_generate_v4(),
+  share_id uuid not null references node_shares on delete cascade,
+  ip_address text,
+  user_agent text,
+  accessed_at timestamp with time zone default now() not null,
+  accessed_minute timestamp with time zone not null
+);
+
+create index idx_node_share_accesses_share_id on node_share_accesses (share_id, accessed_at);
+create unique index idx_node_share_accesses_minute on node_share_accesses (share_id, ip_address, accessed_minute) nulls not distinct;
+
+alter type enum_audit_event add value if not exists 'node_share_created';
+alter type enum_audit_event add value if not exists 'node_share_revoked';
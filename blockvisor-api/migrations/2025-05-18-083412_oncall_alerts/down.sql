@@ -0,0 +1,4 @@
+drop table escalation_policies;
+drop table oncall_schedules;
+drop table alerts;
+drop type enum_alert_kind;
@@ -0,0 +1,39 @@
+create type enum_alert_kind as enum ('certificate_expiring', 'endpoint_quota');
+
+create table alerts (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  node_id uuid references nodes on delete cascade,
+  kind enum_alert_kind not null,
+  message text not null,
+  escalation_level integer not null default 0,
+  notified_at timestamp with time zone default now() not null,
+  acknowledged_at timestamp with time zone,
+  acknowledged_by uuid references users on delete set null,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_alerts_org_id on alerts (org_id, created_at);
+create index idx_alerts_unacknowledged on alerts (notified_at) where acknowledged_at is null;
+
+create table oncall_schedules (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  name text not null,
+  user_ids uuid[] not null,
+  rotation_hours integer not null,
+  starts_at timestamp with time zone not null,
+  created_at timestamp with time zone default now() not null,
+  check (rotation_hours > 0),
+  check (cardinality(user_ids) > 0)
+);
+
+create index idx_oncall_schedules_org_id on oncall_schedules (org_id);
+
+create table escalation_policies (
+  org_id uuid primary key references orgs on delete cascade,
+  schedule_ids uuid[] not null,
+  ack_timeout_minutes integer not null,
+  updated_at timestamp with time zone default now() not null,
+  check (ack_timeout_minutes > 0)
+);
@@ -0,0 +1,5 @@
+alter table node_downtimes drop column pushed_at;
+
+drop table user_notification_preferences;
+drop table user_devices;
+drop type enum_push_platform;
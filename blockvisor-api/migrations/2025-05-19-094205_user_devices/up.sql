@@ -0,0 +1,25 @@
+create type enum_push_platform as enum ('fcm', 'apns');
+
+create table user_devices (
+  id uuid primary key default uuid_generate_v4(),
+  user_id uuid not null references users on delete cascade,
+  platform enum_push_platform not null,
+  push_token text not null unique,
+  name text not null,
+  created_at timestamp with time zone default now() not null,
+  last_seen_at timestamp with time zone default now() not null
+);
+
+create index idx_user_devices_user_id on user_devices (user_id);
+
+create table user_notification_preferences (
+  user_id uuid primary key references users on delete cascade,
+  node_down boolean not null default true,
+  payment_failed boolean not null default true,
+  updated_at timestamp with time zone default now() not null
+);
+
+alter table node_downtimes add column pushed_at timestamp with time zone;
+
+-- don't push downtimes that were already open before push notifications
+update node_downtimes set pushed_at = now() where ended_at is null;
@@ -0,0 +1,3 @@
+drop table org_brandings;
+
+alter table plans drop column custom_branding;
@@ -0,0 +1,11 @@
+alter table plans add column custom_branding boolean not null default false;
+update plans set custom_branding = true where tier = 'enterprise';
+
+create table org_brandings (
+  org_id uuid primary key references orgs on delete cascade,
+  sender_name text,
+  primary_color text,
+  accent_color text,
+  logo_key text,
+  updated_at timestamp with time zone default now() not null
+);
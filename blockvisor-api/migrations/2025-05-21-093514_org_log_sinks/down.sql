@@ -0,0 +1,20 @@
+create or replace function clear_command_refs ()
+  returns trigger
+  language plpgsql
+  as $$
+begin
+  update agent_upgrade_hosts set command_id = null where command_id = old.id;
+  update disk_resizes set command_id = null where command_id = old.id;
+  update org_key_hosts set command_id = null where command_id = old.id;
+  return old;
+end;
+$$;
+
+drop table org_log_sink_hosts;
+drop table org_log_sinks;
+
+drop type enum_org_log_sink_host_status;
+drop type enum_log_sink_kind;
+
+-- we cannot drop values from an enum (without creating a new type)
+delete from commands where command_type = 'host_log_sink';
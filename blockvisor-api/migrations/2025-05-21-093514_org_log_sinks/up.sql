@@ -0,0 +1,43 @@
+create type enum_log_sink_kind as enum ('loki', 'datadog', 's3');
+create type enum_org_log_sink_host_status as enum ('sent', 'applied', 'failed');
+
+create table org_log_sinks (
+  org_id uuid primary key references orgs on delete cascade,
+  kind enum_log_sink_kind not null,
+  endpoint text not null,
+  has_credentials boolean not null default false,
+  revision bigint not null default 1,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  deleted_at timestamp with time zone
+);
+
+create table org_log_sink_hosts (
+  host_id uuid primary key references hosts on delete cascade,
+  org_id uuid not null references orgs on delete cascade,
+  revision bigint not null,
+  status enum_org_log_sink_host_status not null default 'sent',
+  command_id uuid,
+  error text,
+  sent_at timestamp with time zone default now() not null,
+  applied_at timestamp with time zone
+);
+
+create index idx_org_log_sink_hosts_org_id on org_log_sink_hosts (org_id);
+create index idx_org_log_sink_hosts_command_id on org_log_sink_hosts (command_id);
+
+-- commands is partitioned, so `command_id` is cleared by the trigger instead
+create or replace function clear_command_refs ()
+  returns trigger
+  language plpgsql
+  as $$
+begin
+  update agent_upgrade_hosts set command_id = null where command_id = old.id;
+  update disk_resizes set command_id = null where command_id = old.id;
+  update org_key_hosts set command_id = null where command_id = old.id;
+  update org_log_sink_hosts set command_id = null where command_id = old.id;
+  return old;
+end;
+$$;
+
+alter type enum_command_type add value if not exists 'host_log_sink';
@@ -0,0 +1 @@
+alter type enum_audit_event add value if not exists 'request_payload_sampled';
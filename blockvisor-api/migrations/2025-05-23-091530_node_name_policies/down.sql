@@ -0,0 +1,8 @@
+drop index idx_nodes_org_id_display_name;
+
+alter table orgs drop column node_display_name_charset;
+alter table orgs drop column node_display_name_unique;
+alter table orgs drop column node_note_max_len;
+alter table orgs drop column node_display_name_max_len;
+
+drop type enum_node_name_charset;
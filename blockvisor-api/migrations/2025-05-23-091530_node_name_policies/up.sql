@@ -0,0 +1,8 @@
+create type enum_node_name_charset as enum ('any', 'alphanumeric', 'dns_label');
+
+alter table orgs add column node_display_name_max_len integer;
+alter table orgs add column node_note_max_len integer;
+alter table orgs add column node_display_name_unique boolean not null default false;
+alter table orgs add column node_display_name_charset enum_node_name_charset not null default 'any';
+
+create index idx_nodes_org_id_display_name on nodes (org_id, display_name) where deleted_at is null;
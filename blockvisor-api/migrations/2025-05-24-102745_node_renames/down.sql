@@ -0,0 +1,3 @@
+drop index idx_node_dns_aliases_expires_at;
+
+alter table node_dns_aliases drop column expires_at;
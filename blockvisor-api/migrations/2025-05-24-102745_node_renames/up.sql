@@ -0,0 +1,3 @@
+alter table node_dns_aliases add column expires_at timestamp with time zone;
+
+create index idx_node_dns_aliases_expires_at on node_dns_aliases (expires_at) where expires_at is not null;
@@ -0,0 +1,4 @@
+drop table project_teams;
+drop table team_roles;
+drop table team_members;
+drop table teams;
@@ -0,0 +1,33 @@
+create table teams (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  name text not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone default now() not null,
+  unique (org_id, name)
+);
+
+create table team_members (
+  team_id uuid not null references teams on delete cascade,
+  user_id uuid not null references users on delete cascade,
+  created_at timestamp with time zone default now() not null,
+  primary key (team_id, user_id)
+);
+
+create index idx_team_members_user_id on team_members (user_id);
+
+create table team_roles (
+  team_id uuid not null references teams on delete cascade,
+  role text not null references roles (name) on delete cascade,
+  created_at timestamp with time zone default now() not null,
+  primary key (team_id, role)
+);
+
+create table project_teams (
+  project_id uuid not null references projects on delete cascade,
+  team_id uuid not null references teams on delete cascade,
+  created_at timestamp with time zone default now() not null,
+  primary key (project_id, team_id)
+);
+
+create index idx_project_teams_team_id on project_teams (team_id);
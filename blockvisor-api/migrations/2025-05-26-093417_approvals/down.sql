@@ -0,0 +1,10 @@
+alter table orgs drop column require_approval_org_transfer;
+alter table orgs drop column require_approval_node_upgrade;
+alter table orgs drop column require_approval_node_delete;
+
+drop table approvals;
+
+drop type enum_approval_status;
+drop type enum_approval_action;
+
+-- we cannot drop values from an enum (without creating a new type)
@@ -0,0 +1,26 @@
+create type enum_approval_action as enum ('node_delete', 'node_upgrade', 'org_transfer');
+create type enum_approval_status as enum ('pending', 'approved', 'rejected', 'cancelled');
+
+create table approvals (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  action enum_approval_action not null,
+  request bytea not null,
+  status enum_approval_status not null default 'pending',
+  requested_by_type enum_resource_type not null,
+  requested_by_id uuid not null,
+  reviewed_by_type enum_resource_type,
+  reviewed_by_id uuid,
+  created_at timestamp with time zone default now() not null,
+  expires_at timestamp with time zone not null,
+  reviewed_at timestamp with time zone
+);
+
+create index idx_approvals_org_id on approvals (org_id);
+
+alter table orgs add column require_approval_node_delete boolean not null default false;
+alter table orgs add column require_approval_node_upgrade boolean not null default false;
+alter table orgs add column require_approval_org_transfer boolean not null default false;
+
+alter type enum_audit_event add value if not exists 'approval_requested';
+alter type enum_audit_event add value if not exists 'approval_reviewed';
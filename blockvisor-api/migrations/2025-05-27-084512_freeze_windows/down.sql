@@ -0,0 +1,3 @@
+drop table freeze_windows;
+
+-- we cannot drop values from an enum (without creating a new type)
@@ -0,0 +1,15 @@
+create table freeze_windows (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  start_minute integer not null check (start_minute >= 0 and start_minute < 10080),
+  end_minute integer not null check (end_minute >= 0 and end_minute < 10080),
+  reason text,
+  created_by uuid references users on delete set null,
+  created_at timestamp with time zone default now() not null,
+  check (start_minute <> end_minute)
+);
+
+create index idx_freeze_windows_org_id on freeze_windows (org_id);
+
+alter type enum_audit_event add value if not exists 'freeze_window_created';
+alter type enum_audit_event add value if not exists 'freeze_window_deleted';
@@ -0,0 +1,3 @@
+drop table backorders;
+
+drop type enum_backorder_status;
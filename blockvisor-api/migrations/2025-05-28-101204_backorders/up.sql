@@ -0,0 +1,16 @@
+create type enum_backorder_status as enum ('pending', 'fulfilled', 'expired');
+
+create table backorders (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  user_id uuid not null references users on delete cascade,
+  request bytea not null,
+  status enum_backorder_status not null default 'pending',
+  last_error text,
+  created_at timestamp with time zone default now() not null,
+  expires_at timestamp with time zone not null,
+  resolved_at timestamp with time zone
+);
+
+create index idx_backorders_org_id on backorders (org_id);
+create index idx_backorders_pending on backorders (created_at) where status = 'pending';
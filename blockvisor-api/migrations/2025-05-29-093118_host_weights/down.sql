@@ -0,0 +1,3 @@
+drop table host_weights;
+
+drop type enum_host_ranking;
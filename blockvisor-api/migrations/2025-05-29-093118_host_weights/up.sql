@@ -0,0 +1,12 @@
+create type enum_host_ranking as enum ('active', 'shadow');
+
+create table host_weights (
+  ranking enum_host_ranking primary key,
+  headroom double precision not null,
+  similarity double precision not null,
+  age double precision not null,
+  cost double precision not null,
+  updated_at timestamp with time zone default now() not null
+);
+
+alter type enum_audit_event add value if not exists 'host_weights_set';
@@ -0,0 +1,3 @@
+drop table endpoint_pool_members;
+
+drop table endpoint_pools;
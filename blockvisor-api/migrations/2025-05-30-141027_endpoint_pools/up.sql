@@ -0,0 +1,23 @@
+create table endpoint_pools (
+  id uuid primary key default uuid_generate_v4(),
+  org_id uuid not null references orgs on delete cascade,
+  name text not null,
+  created_by_type enum_resource_type not null,
+  created_by_id uuid not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone
+);
+
+create unique index idx_endpoint_pools_name on endpoint_pools (name);
+create index idx_endpoint_pools_org_id on endpoint_pools (org_id);
+
+create table endpoint_pool_members (
+  pool_id uuid not null references endpoint_pools on delete cascade,
+  node_id uuid not null references nodes on delete cascade,
+  dns_id text,
+  rotated_at timestamp with time zone,
+  created_at timestamp with time zone default now() not null,
+  primary key (pool_id, node_id)
+);
+
+create index idx_endpoint_pool_members_node_id on endpoint_pool_members (node_id);
@@ -0,0 +1,9 @@
+drop table pool_autoscale_events;
+drop table pool_autoscalers;
+
+drop type enum_autoscale_action;
+drop type enum_autoscale_metric;
+
+alter table endpoint_pool_members drop column autoscaled;
+
+alter table nodes drop column cpu_percent;
@@ -0,0 +1,35 @@
+alter table nodes add column cpu_percent int;
+
+alter table endpoint_pool_members add column autoscaled boolean not null default false;
+
+create type enum_autoscale_metric as enum ('requests_per_second', 'cpu_percent');
+create type enum_autoscale_action as enum ('scale_up', 'scale_down');
+
+create table pool_autoscalers (
+  pool_id uuid primary key references endpoint_pools on delete cascade,
+  user_id uuid not null references users on delete cascade,
+  image_id uuid not null references images on delete cascade,
+  region_id uuid not null references regions on delete cascade,
+  min_nodes int not null,
+  max_nodes int not null,
+  metric enum_autoscale_metric not null,
+  scale_up_above float8 not null,
+  scale_down_below float8 not null,
+  cooldown_seconds int not null,
+  last_scaled_at timestamp with time zone,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone
+);
+
+create table pool_autoscale_events (
+  id uuid primary key default uuid_generate_v4(),
+  pool_id uuid not null references endpoint_pools on delete cascade,
+  action enum_autoscale_action not null,
+  node_id uuid,
+  metric_value float8,
+  reason text not null,
+  error text,
+  created_at timestamp with time zone default now() not null
+);
+
+create index idx_pool_autoscale_events_pool_id on pool_autoscale_events (pool_id, created_at);
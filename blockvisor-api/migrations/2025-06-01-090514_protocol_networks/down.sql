@@ -0,0 +1,6 @@
+alter table nodes drop column network_id;
+alter table images drop column network_id;
+
+drop table protocol_networks;
+
+drop type enum_network_kind;
@@ -0,0 +1,32 @@
+create type enum_network_kind as enum ('mainnet', 'testnet', 'devnet');
+
+create table protocol_networks (
+  id uuid primary key default uuid_generate_v4(),
+  protocol_id uuid not null references protocols on delete cascade,
+  key text not null,
+  kind enum_network_kind not null,
+  chain_id text,
+  genesis_hash text,
+  explorer_url text,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone
+);
+
+create unique index idx_protocol_networks_key on protocol_networks (protocol_id, key);
+
+-- register the free-form network names of existing protocols
+insert into protocol_networks (protocol_id, key, kind)
+select distinct id, network,
+  case
+    when network ilike '%main%' then 'mainnet'::enum_network_kind
+    when network ilike '%dev%' then 'devnet'::enum_network_kind
+    else 'testnet'::enum_network_kind
+  end
+from protocols, unnest(networks) as network
+where network is not null and network <> '';
+
+alter table images add column network_id uuid references protocol_networks on delete set null;
+alter table nodes add column network_id uuid references protocol_networks on delete set null;
+
+create index idx_images_network_id on images (network_id);
+create index idx_nodes_network_id on nodes (network_id);
@@ -0,0 +1,3 @@
+drop index idx_orgs_stripe_customer_index;
+
+alter table orgs drop column stripe_customer_index;
@@ -0,0 +1,3 @@
+alter table orgs add column stripe_customer_index text;
+
+create index idx_orgs_stripe_customer_index on orgs (stripe_customer_index);
@@ -0,0 +1,4 @@
+-- enum values can't be dropped, and the secrets are gone with the table
+update nodes set cleanup = 'remove_stripe' where cleanup = 'remove_secrets';
+
+drop table secrets;
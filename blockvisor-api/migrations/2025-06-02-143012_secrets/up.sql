@@ -0,0 +1,11 @@
+create table secrets (
+  resource_type enum_resource_type not null,
+  resource_id uuid not null,
+  key text not null,
+  value text not null,
+  created_at timestamp with time zone default now() not null,
+  updated_at timestamp with time zone,
+  primary key (resource_type, resource_id, key)
+);
+
+alter type enum_node_cleanup add value if not exists 'remove_secrets' before 'remove_stripe';
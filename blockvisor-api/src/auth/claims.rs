@@ -47,7 +47,8 @@ impl From<Error> for Status {
         match err {
             EnsureAnyPerms(_) | EnsureHost(..) | EnsureNode(..) | EnsureOrg(..)
             | EnsureUser(..) => Status::forbidden("Access denied."),
-            MissingPerm(perm, _) => Status::forbidden(format!("Missing permission: {perm}")),
+            MissingPerm(perm, _) => Status::forbidden(format!("Missing permission: {perm}"))
+                .with_reason("MISSING_PERMISSION"),
             Host(err) => err.into(),
             Node(err) => err.into(),
             Org(err) => err.into(),
@@ -68,6 +69,9 @@ pub struct Claims {
     pub access: Access,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<HashMap<String, String>>,
+    /// The support user acting as the claims resource, if impersonated.
+    #[serde(rename = "imp", default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<UserId>,
 }
 
 impl Claims {
@@ -77,6 +81,7 @@ impl Claims {
             expirable,
             access,
             data: None,
+            impersonator: None,
         }
     }
 
@@ -95,6 +100,12 @@ impl Claims {
         self
     }
 
+    #[must_use]
+    pub const fn with_impersonator(mut self, impersonator: UserId) -> Self {
+        self.impersonator = Some(impersonator);
+        self
+    }
+
     pub fn insert_data<K, V>(&mut self, key: K, value: V)
     where
         K: Into<String>,
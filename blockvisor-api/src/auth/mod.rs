@@ -7,15 +7,20 @@ use std::sync::Arc;
 
 use chrono::Duration;
 use displaydoc::Display;
+use serde_json::json;
 use thiserror::Error;
 
 use crate::config::token::Config;
 use crate::database::Conn;
+use crate::encryption::Keyring;
+use crate::grpc::middleware::PayloadSample;
 use crate::grpc::{Metadata, Status};
+use crate::model::audit::{AuditEvent, NewAuditLog};
+use crate::model::user::User;
 
 use self::claims::{Claims, Granted};
 use self::rbac::{Perm, Perms};
-use self::resource::{Resource, Resources};
+use self::resource::{Resource, Resources, UserId};
 use self::token::api_key::Validated;
 use self::token::refresh::{self, Refresh, RequestCookie};
 use self::token::{Cipher, RequestToken};
@@ -139,6 +144,8 @@ pub(crate) trait Authorize {
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Failed to audit impersonated request: {0}
+    Audit(crate::model::audit::Error),
     /// Auth Claims error: {0}
     Claims(#[from] self::claims::Error),
     /// Database error: {0}
@@ -151,10 +158,14 @@ pub enum Error {
     ExpiredJwt(Option<Resource>),
     /// Refresh token for resource {0} has expired.
     ExpiredRefresh(String),
+    /// User `{0}` no longer grants support access.
+    NoSupportAccess(UserId),
     /// Failed to parse RequestToken: {0}
     ParseRequestToken(token::Error),
     /// Failed to parse refresh header: {0}
     RefreshHeader(refresh::Error),
+    /// Failed to load impersonated user: {0}
+    User(crate::model::user::Error),
     /// Failed to validate api key: {0}
     ValidateApiKey(token::api_key::Error),
 }
@@ -163,12 +174,21 @@ impl From<Error> for Status {
     fn from(err: Error) -> Self {
         use Error::*;
         match err {
-            Database(_) => Status::internal("Internal error."),
-            DecodeJwt(_) => Status::forbidden("Invalid JWT token."),
-            DecodeRefresh(_) | RefreshHeader(_) => Status::forbidden("Invalid refresh token."),
-            ExpiredJwt(_) => Status::unauthorized(TOKEN_EXPIRED),
-            ExpiredRefresh(_) => Status::unauthorized(TOKEN_EXPIRED),
-            ValidateApiKey(_) => Status::forbidden("Invalid API key."),
+            Audit(_) | Database(_) | User(_) => Status::internal("Internal error."),
+            DecodeJwt(_) => Status::forbidden("Invalid JWT token.").with_reason("INVALID_JWT"),
+            DecodeRefresh(_) | RefreshHeader(_) => {
+                Status::forbidden("Invalid refresh token.").with_reason("INVALID_REFRESH_TOKEN")
+            }
+            ExpiredJwt(_) => Status::unauthorized(TOKEN_EXPIRED).with_reason("TOKEN_EXPIRED"),
+            ExpiredRefresh(_) => Status::unauthorized(TOKEN_EXPIRED).with_reason("TOKEN_EXPIRED"),
+            NoSupportAccess(_) => Status::unauthorized("Support access has been revoked.")
+                .with_reason("SUPPORT_ACCESS_REVOKED"),
+            ValidateApiKey(token::api_key::Error::OpenSalt(_)) => {
+                Status::internal("Internal error.")
+            }
+            ValidateApiKey(_) => {
+                Status::forbidden("Invalid API key.").with_reason("INVALID_API_KEY")
+            }
             Claims(err) => err.into(),
             ParseRequestToken(err) => err.into(),
         }
@@ -177,16 +197,18 @@ impl From<Error> for Status {
 
 pub struct Auth {
     pub cipher: Arc<Cipher>,
+    pub keyring: Arc<Keyring>,
     pub token_expires: Duration,
 }
 
 impl Auth {
-    pub fn new(config: &Config) -> Self {
+    pub fn new(config: &Config, keyring: Arc<Keyring>) -> Self {
         let cipher = Arc::new(Cipher::new(&config.secret));
         let token_expires = config.expire.token;
 
         Auth {
             cipher,
+            keyring,
             token_expires,
         }
     }
@@ -210,6 +232,13 @@ impl Auth {
         conn: &mut Conn<'_>,
     ) -> Result<AuthZ, Error> {
         let claims = self.claims(token, conn).await?;
+        if claims.impersonator.is_some() {
+            ensure_support_access(&claims, conn).await?;
+        }
+        let org_id = match &resources {
+            Resources::One(resource) => resource.org(),
+            Resources::All | Resources::Many(_) => None,
+        };
 
         // first ensure that claims can access the requested resource
         let extra = claims.ensure_resources(resources, conn).await?;
@@ -231,6 +260,21 @@ impl Auth {
             _ => granted,
         };
 
+        // record every request made by a support user acting as another user
+        let audit = claims.impersonator.map(|impersonator| {
+            let perms: Vec<_> = match &perms {
+                Perms::One(perm) => vec![perm.to_string()],
+                Perms::All(perms) | Perms::Any(perms) => {
+                    perms.iter().map(ToString::to_string).collect()
+                }
+            };
+            NewAuditLog::new(None, impersonator, AuditEvent::ImpersonatedRequest).with_data(json!({
+                "impersonator_id": impersonator.to_string(),
+                "user_id": resource.id().to_string(),
+                "perms": perms,
+            }))
+        });
+
         // finally check that the requested permissions exist
         match perms {
             Perms::One(perm) => granted.ensure_perm(perm, resource).map(|_| ())?,
@@ -238,12 +282,20 @@ impl Auth {
             Perms::Any(perms) => granted.ensure_any_perms(perms, resource).map(|_| ())?,
         }
 
+        if let Some(audit) = audit {
+            audit.create(conn).await.map_err(Error::Audit)?;
+        }
+
+        if let Some(sample) = PayloadSample::current() {
+            sample.set_caller(resource, org_id.or_else(|| resource.org()));
+        }
+
         Ok(AuthZ { claims, granted })
     }
 
     pub async fn claims(&self, token: &RequestToken, conn: &mut Conn<'_>) -> Result<Claims, Error> {
         match token {
-            RequestToken::ApiKey(token) => Validated::from_token(token, conn)
+            RequestToken::ApiKey(token) => Validated::from_token(token, &self.keyring, conn)
                 .await
                 .map_err(Error::ValidateApiKey)
                 .map(|v| v.claims(self.token_expires)),
@@ -282,6 +334,22 @@ impl Auth {
     }
 }
 
+/// Ensure that an impersonated user still consents to support access.
+///
+/// This is checked on every request so that revoking support access also stops
+/// any impersonation token that is already live.
+async fn ensure_support_access(claims: &Claims, conn: &mut Conn<'_>) -> Result<(), Error> {
+    let Resource::User(user_id) = claims.resource() else {
+        return Ok(());
+    };
+    let user = User::by_id(user_id, conn).await.map_err(Error::User)?;
+    if user.has_support_access() {
+        Ok(())
+    } else {
+        Err(Error::NoSupportAccess(user_id))
+    }
+}
+
 /// Authorized `Claims` along with the set of `Granted` permissions.
 #[derive(Debug)]
 pub struct AuthZ {
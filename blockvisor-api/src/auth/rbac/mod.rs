@@ -18,6 +18,7 @@ define_roles! {
 
     Email => {
         Invitation,
+        OrgTransfer,
         RegistrationConfirmation,
         ResetPassword,
     }
@@ -40,6 +41,20 @@ define_roles! {
 }
 
 define_perms! {
+    AbuseAdmin => {
+        List,
+        Review,
+    }
+
+    Alert => {
+        Acknowledge,
+        CreateSchedule,
+        DeleteSchedule,
+        GetOncall,
+        List,
+        SetPolicy,
+    }
+
     ApiKey => {
         Create,
         List,
@@ -48,6 +63,13 @@ define_perms! {
         Delete,
     }
 
+    Approval => {
+        Cancel,
+        Get,
+        List,
+        Review,
+    }
+
     Archive => {
         GetDownloadMetadata,
         GetDownloadChunks,
@@ -62,11 +84,24 @@ define_perms! {
         PutDownloadManifest,
     }
 
+    Artifact => {
+        CreateUpload,
+        Download,
+        List,
+    }
+
+    ArtifactAdmin => {
+        Download,
+        List,
+    }
+
     Auth => {
         Confirm,
         ListPermissions,
+        ListSessions,
         Refresh,
         ResetPassword,
+        RevokeSession,
         UpdatePassword,
         UpdateUiPassword,
     }
@@ -76,6 +111,7 @@ define_perms! {
     }
 
     Billing => {
+        Estimate,
         Exempt,
     }
 
@@ -86,15 +122,19 @@ define_perms! {
 
     Command => {
         Ack,
+        Cancel,
         Create,
         Get,
         List,
+        ListUpcoming,
         Pending,
         Update,
     }
 
     CommandAdmin => {
+        Cancel,
         List,
+        ListUpcoming,
         Pending,
     }
 
@@ -107,28 +147,62 @@ define_perms! {
         Services,
     }
 
+    Endpoint => {
+        Create,
+        List,
+        Revoke,
+        Usage,
+    }
+
+    EndpointAdmin => {
+        ReportUsage,
+        Usage,
+    }
+
     Host => {
+        CreatePool,
         DeleteHost,
+        DeletePool,
         GetHost,
+        GetPool,
         ListHosts,
+        ListPools,
+        ListReconciliations,
         ListRegions,
+        RegionAvailability,
+        ReportInventory,
         Restart,
+        SetHostPool,
         Start,
         Stop,
         UpdateHost,
+        UpdatePool,
     }
 
     HostAdmin => {
+        CreatePool,
         CreateRegion,
         DeleteHost,
+        DeletePool,
+        GetAgentUpgrade,
         GetHost,
+        GetPool,
+        HaltAgentUpgrade,
         ListHosts,
+        ListPools,
+        ListReconciliations,
         ListRegions,
+        RegionAvailability,
         Restart,
+        SetHostPool,
         Start,
         Stop,
         UpdateHost,
+        UpdatePool,
         UpdateRegion,
+        UpgradeAgent,
+        UpgradeOutdated,
+        VersionReport,
         ViewCost,
     }
 
@@ -144,16 +218,53 @@ define_perms! {
     Image => {
         Get,
         ListArchives,
+        PushVersion,
     }
 
     ImageAdmin => {
         Add,
         Get,
         ListArchives,
+        ReviewVersion,
         UpdateArchive,
         UpdateImage,
     }
 
+    Incident => {
+        Comment,
+        Get,
+        List,
+    }
+
+    IncidentAdmin => {
+        Comment,
+        Create,
+        Get,
+        Link,
+        List,
+        Update,
+    }
+
+    InternalAdmin => {
+        ClearLegalHold,
+        CleanupDns,
+        CleanupStripe,
+        GetHostWeights,
+        ListBackfills,
+        ListFeatureFlags,
+        ListLegalHolds,
+        ListNetworkHeads,
+        ListTableRetention,
+        OverrideNodeState,
+        RunBackfill,
+        SearchHosts,
+        SearchNodes,
+        SetFeatureFlag,
+        SetHostWeights,
+        SetLegalHold,
+        SetNetworkHead,
+    }
+
     Invitation => {
         Create,
         List,
@@ -168,6 +279,21 @@ define_perms! {
         Revoke,
     }
 
+    ManagedHost => {
+        Deprovision,
+        Get,
+        List,
+        Provision,
+        SetCredentials,
+    }
+
+    Mesh => {
+        GetConfig,
+        Join,
+        Leave,
+        ListPeers,
+    }
+
     Metrics => {
         Host,
         Node,
@@ -182,13 +308,24 @@ define_perms! {
     }
 
     Node => {
+        AddDnsAlias,
+        BulkUpdate,
+        CollectDebugBundle,
         Create,
         Delete,
         Get,
+        GetCertificate,
         List,
+        ListDiskResizes,
+        RemoveDnsAlias,
+        Rename,
         ReportError,
         ReportStatus,
+        RequestCertificate,
+        Resize,
+        ResizeDisk,
         Restart,
+        ReviewDiskResize,
         Start,
         Stop,
         UpdateConfig,
@@ -196,13 +333,24 @@ define_perms! {
     }
 
     NodeAdmin => {
+        AddDnsAlias,
+        BulkUpdate,
+        CollectDebugBundle,
         Create,
         Delete,
         Get,
+        GetCertificate,
         List,
+        ListDiskResizes,
+        RemoveDnsAlias,
+        Rename,
         ReportError,
         ReportStatus,
+        RequestCertificate,
+        Resize,
+        ResizeDisk,
         Restart,
+        ReviewDiskResize,
         Start,
         Stop,
         Transfer,
@@ -211,20 +359,46 @@ define_perms! {
         ViewCost,
     }
 
+    NodeShare => {
+        Accesses,
+        Create,
+        List,
+        Revoke,
+    }
+
     Org => {
         Create,
         Get,
         List,
         Update,
         Delete,
+        Restore,
         RemoveMember,
         RemoveSelf,
+        TransferOwnership,
+        ConfirmTransfer,
+        ExportConfiguration,
+        ImportConfiguration,
+        CreateProject,
+        GetProject,
+        ListProjects,
+        UpdateProject,
+        DeleteProject,
+        AddProjectMember,
+        RemoveProjectMember,
+        CreateFreezeWindow,
+        ListFreezeWindows,
+        DeleteFreezeWindow,
+        FreezeOverride,
     }
 
     OrgAdmin => {
         Get,
         List,
         Update,
+        StartTrial,
+        Suspend,
+        Reinstate,
     }
 
     OrgProvision => {
@@ -236,6 +410,8 @@ define_perms! {
         GetBillingDetails,
         InitCard,
         ListPaymentMethods,
+        ListPlans,
+        UpdatePlan,
     }
 
     OrgAddress => {
@@ -244,44 +420,137 @@ define_perms! {
         Delete,
     }
 
+    OrgBranding => {
+        Get,
+        Update,
+    }
+
+    OrgKey => {
+        Create,
+        List,
+        Revoke,
+        Rotate,
+    }
+
+    OrgLogSink => {
+        Delete,
+        Get,
+        Set,
+    }
+
+    PartnerAdmin => {
+        Create,
+        List,
+        Revenue,
+    }
+
+    Pool => {
+        Create,
+        Delete,
+        Get,
+        List,
+        Update,
+    }
+
     Protocol => {
         GetPricing,
         GetProtocol,
         GetLatest,
         GetStats,
+        GrantAccess,
+        ListGrants,
+        ListNetworks,
         ListProtocols,
         ListVariants,
         ListVersions,
+        RevokeAccess,
         ViewDevelopment,
         ViewPublic,
     }
 
     ProtocolAdmin => {
+        AddNetwork,
         AddProtocol,
         AddVersion,
+        DeleteProtocol,
         GetPricing,
         GetProtocol,
         GetLatest,
+        GrantAccess,
+        ListGrants,
+        ListNetworks,
         ListProtocols,
         ListVariants,
         ListVersions,
+        RevokeAccess,
         UpdateProtocol,
         UpdateVersion,
         ViewAllStats,
         ViewPrivate,
     }
 
+    Report => {
+        Uptime,
+    }
+
+    ReportAdmin => {
+        Uptime,
+    }
+
+    StatusPage => {
+        Create,
+        Delete,
+        Get,
+        List,
+        VerifyDomain,
+    }
+
+    SupportAdmin => {
+        Impersonate,
+        LookupRequest,
+    }
+
+    Team => {
+        AddMember,
+        AddProject,
+        Create,
+        Delete,
+        Get,
+        GrantRole,
+        List,
+        RemoveMember,
+        RemoveProject,
+        RevokeRole,
+        Update,
+    }
+
+    Ticket => {
+        CreateIntegration,
+        DeleteIntegration,
+        GetIntegration,
+    }
+
     User => {
         Create,
         Filter,
         Get,
         Update,
         Delete,
+        DeleteAccount,
+        ExportData,
+        GrantSupportAccess,
+        RevokeSupportAccess,
+        ListDevices,
+        RegisterDevice,
+        RemoveDevice,
+        GetNotificationPreferences,
+        UpdateNotificationPreferences,
     }
 
     UserAdmin => {
         Filter,
         Get,
+        RequirePasswordReset,
         Update,
     }
 
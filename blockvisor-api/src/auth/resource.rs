@@ -315,6 +315,8 @@ impl From<HostId> for ResourceId {
     PartialOrd,
     Ord,
     DieselNewType,
+    Serialize,
+    Deserialize,
 )]
 pub struct UserId(Uuid);
 
@@ -11,13 +11,16 @@
 //! `{secret}` is a base64-encoded representation of the secret bytes, which
 //! when hashed together with the database `key_salt` field should equal the
 //! database `key_hash` field.
+//!
+//! The `key_salt` is sealed at rest, so it also acts as a pepper: the hashes
+//! can't be checked against a guessed secret without the `Keyring`.
 
 use std::str::FromStr;
 
 use base64::engine::{Engine as _, general_purpose::STANDARD_NO_PAD};
-use derive_more::{Deref, Into};
+use derive_more::{Deref, Display, From, Into};
 use diesel_derive_newtype::DieselNewType;
-use displaydoc::Display;
+use displaydoc::Display as DisplayDoc;
 use rand::RngCore;
 use thiserror::Error;
 use uuid::Uuid;
@@ -28,6 +31,7 @@ use crate::auth::rbac::Perms;
 use crate::auth::resource::Resource;
 use crate::auth::token::ApiToken;
 use crate::database::Conn;
+use crate::encryption::Keyring;
 use crate::model::ApiKey;
 
 pub(super) const TOKEN_PREFIX: &str = "api_";
@@ -38,7 +42,7 @@ const SALT_BYTES: usize = 16;
 const SECRET_BYTES: usize = 20;
 
 /// Internal errors. Note that these are not safe for external display.
-#[derive(Debug, Display, Error)]
+#[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
     /// Invalid decoded secret length.
     BadSecretLen,
@@ -52,6 +56,8 @@ pub enum Error {
     FindKeyId(crate::model::api_key::Error),
     /// Key hash mismatch.
     HashMismatch,
+    /// Failed to open the key salt: {0}
+    OpenSalt(crate::encryption::Error),
     /// Failed to parse KeyId: {0}
     ParseKeyId(uuid::Error),
 }
@@ -61,12 +67,17 @@ pub enum Error {
 pub struct Validated(ApiKey);
 
 impl Validated {
-    pub async fn from_token(token: &ApiToken, conn: &mut Conn<'_>) -> Result<Self, Error> {
+    pub async fn from_token(
+        token: &ApiToken,
+        keyring: &Keyring,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
         let api_key = ApiKey::by_id(token.key_id, conn)
             .await
             .map_err(Error::FindKeyId)?;
 
-        let key_hash = KeyHash::from(&api_key.key_salt, &token.secret);
+        let salt = api_key.key_salt.open(keyring).map_err(Error::OpenSalt)?;
+        let key_hash = KeyHash::from(&salt, &token.secret);
         if key_hash != api_key.key_hash {
             return Err(Error::HashMismatch);
         }
@@ -113,7 +124,7 @@ impl FromStr for KeyId {
 }
 
 /// A base64-encoded representation of the hash of the salt and secret.
-#[derive(Debug, PartialEq, Eq, DieselNewType)]
+#[derive(Debug, PartialEq, Eq, DieselNewType, Deref)]
 pub struct KeyHash(String);
 
 impl KeyHash {
@@ -130,7 +141,7 @@ impl KeyHash {
 }
 
 /// A newtype wrapping the database `salt` text.
-#[derive(Debug, DieselNewType)]
+#[derive(Debug, Display, DieselNewType, Deref, From)]
 pub struct Salt(String);
 
 impl Salt {
@@ -168,6 +179,24 @@ impl Secret {
 
         Ok(Secret(secret))
     }
+
+    /// Parse a secret previously returned from `Secret::encode`.
+    pub fn decode(encoded: &str) -> Result<Self, Error> {
+        let secret: [u8; SECRET_BYTES] = STANDARD_NO_PAD
+            .decode(encoded)
+            .map_err(Error::DecodeSecret)?
+            .try_into()
+            .map_err(|_| Error::BadSecretLen)?;
+
+        Ok(Secret(secret))
+    }
+
+    /// A base64-encoded representation of the secret bytes.
+    ///
+    /// This must not be stored and is only returned once on creation.
+    pub fn encode(&self) -> String {
+        STANDARD_NO_PAD.encode(self.0)
+    }
 }
 
 /// An encoded string representation of the API key.
@@ -115,6 +115,7 @@ mod tests {
             expirable: Expirable::from_now(expires),
             access: Access::Perms(Perms::One(NodePerm::Create.into())),
             data: None,
+            impersonator: None,
         };
 
         let encoded = ctx.auth.cipher.jwt.encode(&claims).unwrap();
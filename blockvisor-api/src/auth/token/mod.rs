@@ -4,6 +4,7 @@ pub mod refresh;
 
 use std::str::FromStr;
 
+use axum::http::HeaderValue;
 use derive_more::{Deref, From};
 use displaydoc::Display;
 use thiserror::Error;
@@ -76,6 +77,20 @@ impl TryFrom<&Metadata> for RequestToken {
     }
 }
 
+/// Request metadata authenticated as the holder of a JWT, for acting on their
+/// behalf outside of a request.
+impl From<&jwt::Jwt> for Metadata {
+    fn from(jwt: &jwt::Jwt) -> Self {
+        let bearer = format!("{AUTH_HEADER_PREFIX}{}", jwt.as_str());
+        let mut meta = Metadata::new();
+        meta.insert_http(
+            AUTH_HEADER,
+            HeaderValue::try_from(bearer).expect("always ascii"),
+        );
+        meta
+    }
+}
+
 impl FromStr for RequestToken {
     type Err = Error;
 
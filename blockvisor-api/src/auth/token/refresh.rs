@@ -14,6 +14,7 @@ use crate::auth::claims::Expirable;
 use crate::auth::resource::{ClaimsResource, Resource};
 use crate::config::token::{RefreshSecret, RefreshSecrets};
 use crate::grpc::{Metadata, Status};
+use crate::model::user::session::UserSessionId;
 
 const ALGORITHM: Algorithm = Algorithm::HS512;
 const COOKIE_HEADER: &str = "cookie";
@@ -157,6 +158,9 @@ pub struct Refresh {
     resource: ClaimsResource,
     #[serde(flatten)]
     expirable: Expirable,
+    /// The user session this refresh token was issued for.
+    #[serde(rename = "sid", default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<UserSessionId>,
 }
 
 impl Refresh {
@@ -164,9 +168,20 @@ impl Refresh {
         Refresh {
             resource: ClaimsResource::from(resource.into()),
             expirable: Expirable::from_now(expires),
+            session_id: None,
         }
     }
 
+    #[must_use]
+    pub const fn with_session(mut self, session_id: Option<UserSessionId>) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    pub const fn session_id(&self) -> Option<UserSessionId> {
+        self.session_id
+    }
+
     pub fn resource(&self) -> Resource {
         Resource::new(self.resource.resource_type, self.resource.resource_id)
     }
@@ -0,0 +1,460 @@
+//! Load test an api server against a seeded database.
+//!
+//! `bench` starts a server on a new test database (just like the integration
+//! tests), seeds the `large-fleet` scenario as background data, and adds a
+//! `bench` org for the workloads to run against. Each workload is driven by
+//! concurrent gRPC clients, and reports the P50 and P99 latency of its calls
+//! and how many database queries each call made.
+//!
+//! With `--baseline <file>` the results are checked against a recorded
+//! baseline and `bench` fails on any regression. With `--update-baseline` the
+//! results are written to the baseline file instead.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context as _, Result, bail};
+use argh::FromArgs;
+use serde::{Deserialize, Serialize};
+use strum::{EnumIter, EnumString, IntoEnumIterator, IntoStaticStr};
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use tonic::Request;
+use tonic::transport::Channel;
+
+use blockvisor_api::auth::claims::Claims;
+use blockvisor_api::auth::rbac::{MetricsPerm, OrgRole, Perms};
+use blockvisor_api::auth::resource::{HostId, NodeId, OrgId};
+use blockvisor_api::config::Context;
+use blockvisor_api::database::seed::fixture::OrgFixture;
+use blockvisor_api::database::seed::scenario::Scenario;
+use blockvisor_api::database::seed::{IMAGE_ID, Seed};
+use blockvisor_api::database::{Database, query_log};
+use blockvisor_api::grpc::api::metrics_service_client::MetricsServiceClient;
+use blockvisor_api::grpc::api::node_service_client::NodeServiceClient;
+use blockvisor_api::grpc::{api, common};
+use blockvisor_api::server;
+
+const BENCH_ORG: &str = "bench";
+const BENCH_HOSTS: usize = 4;
+const BENCH_NODES: usize = 200;
+
+const DEFAULT_LATENCY_TOLERANCE_PERCENT: f64 = 50.0;
+const DEFAULT_QUERY_TOLERANCE: f64 = 0.5;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Args = argh::from_env();
+    if args.update_baseline && args.baseline.is_none() {
+        bail!("`--update-baseline` requires a `--baseline` file");
+    }
+
+    let (context, db) = Context::with_mocked().await?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server_context = context.clone();
+    tokio::spawn(async move {
+        if let Err(err) = server::start_with_listener(server_context, listener).await {
+            eprintln!("server failed: {err}");
+        }
+    });
+
+    let channel = Channel::from_shared(format!("http://{addr}"))?
+        .connect()
+        .await
+        .context("connect to server")?;
+    let target = Target::new(&context, &db.seed, channel, args.iterations).await?;
+    let target = Arc::new(target);
+
+    let workloads: Vec<Workload> = if args.workload.is_empty() {
+        Workload::iter().collect()
+    } else {
+        args.workload
+    };
+
+    println!(
+        "{:<12} {:>8} {:>10} {:>10} {:>14}",
+        "workload", "calls", "p50 ms", "p99 ms", "queries/call"
+    );
+    let mut results = BTreeMap::new();
+    for workload in workloads {
+        let stats = workload
+            .run(&target, args.iterations, args.concurrency)
+            .await
+            .with_context(|| format!("run workload `{}`", workload.name()))?;
+        println!(
+            "{:<12} {:>8} {:>10.2} {:>10.2} {:>14.2}",
+            workload.name(),
+            args.iterations,
+            stats.p50_ms,
+            stats.p99_ms,
+            stats.queries_per_call
+        );
+        results.insert(workload.name().to_string(), stats);
+    }
+
+    let Some(path) = args.baseline.as_deref().map(Path::new) else {
+        return Ok(());
+    };
+
+    if args.update_baseline {
+        let mut baseline = if path.exists() {
+            Baseline::read_file(path)?
+        } else {
+            Baseline::default()
+        };
+        baseline.workloads.extend(results);
+        baseline.write_file(path)?;
+        println!("updated baseline {}", path.display());
+        return Ok(());
+    }
+
+    let regressions = Baseline::read_file(path)?.regressions(&results);
+    if regressions.is_empty() {
+        println!("no regressions from baseline {}", path.display());
+        Ok(())
+    } else {
+        for regression in &regressions {
+            eprintln!("regression: {regression}");
+        }
+        bail!("{} regressions from baseline", regressions.len())
+    }
+}
+
+/// `bench` load tests the api against a seeded database
+#[derive(Debug, FromArgs)]
+struct Args {
+    /// calls made by each workload (default 200)
+    #[argh(option, short = 'n', default = "200")]
+    iterations: u32,
+    /// concurrent clients of each workload (default 8)
+    #[argh(option, short = 'c', default = "8")]
+    concurrency: u32,
+    /// run only this workload (metrics, node-list or node-create)
+    #[argh(option, short = 'w')]
+    workload: Vec<Workload>,
+    /// path to the baseline to check the results against
+    #[argh(option, short = 'b')]
+    baseline: Option<String>,
+    /// write the results to the baseline instead of checking them
+    #[argh(switch)]
+    update_baseline: bool,
+}
+
+/// A kind of gRPC call made repeatedly against the `Target`.
+#[derive(Clone, Copy, Debug, EnumIter, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+enum Workload {
+    /// Report the metrics of every node on a host, as each host does.
+    Metrics,
+    /// List a page of the nodes of the org.
+    NodeList,
+    /// Create a new node on a host.
+    NodeCreate,
+}
+
+impl Workload {
+    fn name(self) -> &'static str {
+        self.into()
+    }
+
+    async fn run(self, target: &Arc<Target>, iterations: u32, concurrency: u32) -> Result<Stats> {
+        let queries = query_log::query_count();
+
+        let mut clients = JoinSet::new();
+        for client in 0..concurrency.max(1) {
+            let target = target.clone();
+            clients.spawn(async move {
+                let calls = (client..iterations).step_by(concurrency.max(1) as usize);
+                let mut latencies = Vec::with_capacity(calls.len());
+                for call in calls {
+                    let started = Instant::now();
+                    self.call(&target, call).await?;
+                    latencies.push(started.elapsed());
+                }
+                Ok::<_, anyhow::Error>(latencies)
+            });
+        }
+
+        let mut latencies = Vec::with_capacity(iterations as usize);
+        while let Some(result) = clients.join_next().await {
+            latencies.extend(result??);
+        }
+
+        let queries = query_log::query_count() - queries;
+        Ok(Stats::new(latencies, queries))
+    }
+
+    async fn call(self, target: &Target, call: u32) -> Result<()> {
+        let (host_id, node_ids) = &target.hosts[call as usize % target.hosts.len()];
+
+        match self {
+            Workload::Metrics => {
+                let metrics = node_ids
+                    .iter()
+                    .map(|node_id| node_metrics(*node_id, call))
+                    .collect();
+                let req = api::MetricsServiceNodeRequest { metrics };
+                let mut client = MetricsServiceClient::new(target.channel.clone());
+                client.node(request(req, &target.metrics_jwt)?).await?;
+            }
+
+            Workload::NodeList => {
+                let req = api::NodeServiceListRequest {
+                    org_ids: vec![target.org_id.to_string()],
+                    limit: 50,
+                    ..Default::default()
+                };
+                let mut client = NodeServiceClient::new(target.channel.clone());
+                client.list(request(req, &target.admin_jwt)?).await?;
+            }
+
+            Workload::NodeCreate => {
+                let launch = common::node_launcher::Launch::ByHost(common::ByHost {
+                    host_counts: vec![common::HostCount {
+                        host_id: host_id.to_string(),
+                        node_count: 1,
+                    }],
+                });
+                let req = api::NodeServiceCreateRequest {
+                    org_id: target.org_id.to_string(),
+                    image_id: IMAGE_ID.to_string(),
+                    launcher: Some(common::NodeLauncher {
+                        launch: Some(launch),
+                    }),
+                    ..Default::default()
+                };
+                let mut client = NodeServiceClient::new(target.channel.clone());
+                client.create(request(req, &target.admin_jwt)?).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `bench` org that every workload runs against.
+struct Target {
+    channel: Channel,
+    org_id: OrgId,
+    hosts: Vec<(HostId, Vec<NodeId>)>,
+    admin_jwt: String,
+    metrics_jwt: String,
+}
+
+impl Target {
+    /// Seed the data to run against, with room for `creates` new nodes.
+    async fn new(context: &Context, seed: &Seed, channel: Channel, creates: u32) -> Result<Self> {
+        let mut conn = context.conn().await?;
+        Scenario::LargeFleet
+            .seed(&mut conn)
+            .await
+            .context("seed large-fleet")?;
+
+        let spare_ips = (creates as usize).div_ceil(BENCH_HOSTS);
+        let fixtures = OrgFixture::new(BENCH_ORG)
+            .hosts(BENCH_HOSTS)
+            .nodes(BENCH_NODES)
+            .spare_ips(spare_ips)
+            .create(&seed.region, &seed.protocol, &seed.image, &mut conn)
+            .await
+            .context("seed bench org")?;
+
+        let hosts = fixtures
+            .hosts
+            .iter()
+            .map(|host| {
+                let node_ids = fixtures
+                    .nodes
+                    .iter()
+                    .filter(|node| node.host_id == host.id)
+                    .map(|node| node.id)
+                    .collect();
+                (host.id, node_ids)
+            })
+            .collect();
+
+        let org_id = fixtures.org.id;
+        let expires = chrono::Duration::hours(1);
+        let admin = Claims::from_now(expires, org_id, OrgRole::Admin);
+        let metrics = Claims::from_now(expires, org_id, Perms::from(MetricsPerm::Node));
+        let jwt = &context.auth.cipher.jwt;
+
+        Ok(Target {
+            channel,
+            org_id,
+            hosts,
+            admin_jwt: jwt.encode(&admin)?.to_string(),
+            metrics_jwt: jwt.encode(&metrics)?.to_string(),
+        })
+    }
+}
+
+/// The latency and database queries of the calls of a `Workload`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct Stats {
+    p50_ms: f64,
+    p99_ms: f64,
+    queries_per_call: f64,
+}
+
+impl Stats {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(mut latencies: Vec<Duration>, queries: u64) -> Self {
+        latencies.sort_unstable();
+        let percentile = |percent: usize| {
+            let index = (latencies.len() * percent / 100).min(latencies.len().saturating_sub(1));
+            latencies
+                .get(index)
+                .map_or(0.0, |latency| latency.as_secs_f64() * 1000.0)
+        };
+
+        Stats {
+            p50_ms: percentile(50),
+            p99_ms: percentile(99),
+            queries_per_call: queries as f64 / latencies.len().max(1) as f64,
+        }
+    }
+}
+
+/// The recorded `Stats` of each workload, and how far they may regress.
+#[derive(Debug, Serialize, Deserialize)]
+struct Baseline {
+    /// How much slower than the baseline a latency may be.
+    latency_tolerance_percent: f64,
+    /// How many more queries per call than the baseline are allowed.
+    query_tolerance: f64,
+    workloads: BTreeMap<String, Stats>,
+}
+
+impl Default for Baseline {
+    fn default() -> Self {
+        Baseline {
+            latency_tolerance_percent: DEFAULT_LATENCY_TOLERANCE_PERCENT,
+            query_tolerance: DEFAULT_QUERY_TOLERANCE,
+            workloads: BTreeMap::new(),
+        }
+    }
+}
+
+impl Baseline {
+    fn read_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| {
+            let path = path.display();
+            format!("read baseline `{path}` (record one with `--update-baseline`)")
+        })?;
+        toml::from_str(&text).context("parse baseline file")
+    }
+
+    fn write_file(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string_pretty(self).context("serialize baseline")?;
+        fs::write(path, text).context("write baseline file")
+    }
+
+    /// Describe each result that regressed from this baseline.
+    fn regressions(&self, results: &BTreeMap<String, Stats>) -> Vec<String> {
+        let latency_limit = 1.0 + self.latency_tolerance_percent / 100.0;
+
+        let mut regressions = Vec::new();
+        for (name, stats) in results {
+            let Some(baseline) = self.workloads.get(name) else {
+                eprintln!("no baseline for workload `{name}`");
+                continue;
+            };
+
+            if stats.p50_ms > baseline.p50_ms * latency_limit {
+                regressions.push(format!(
+                    "{name} p50 is {:.2}ms (baseline {:.2}ms)",
+                    stats.p50_ms, baseline.p50_ms
+                ));
+            }
+            if stats.p99_ms > baseline.p99_ms * latency_limit {
+                regressions.push(format!(
+                    "{name} p99 is {:.2}ms (baseline {:.2}ms)",
+                    stats.p99_ms, baseline.p99_ms
+                ));
+            }
+            if stats.queries_per_call > baseline.queries_per_call + self.query_tolerance {
+                regressions.push(format!(
+                    "{name} makes {:.2} queries per call (baseline {:.2})",
+                    stats.queries_per_call, baseline.queries_per_call
+                ));
+            }
+        }
+
+        regressions
+    }
+}
+
+fn request<T>(message: T, jwt: &str) -> Result<Request<T>> {
+    let mut req = Request::new(message);
+    let auth = format!("Bearer {jwt}").parse()?;
+    req.metadata_mut().insert("authorization", auth);
+    Ok(req)
+}
+
+fn node_metrics(node_id: NodeId, call: u32) -> api::NodeMetrics {
+    api::NodeMetrics {
+        node_id: node_id.to_string(),
+        node_status: Some(common::NodeStatus {
+            state: common::NodeState::Running as i32,
+            next: None,
+            protocol: Some(common::ProtocolStatus {
+                state: "synced".into(),
+                health: common::NodeHealth::Healthy as i32,
+            }),
+        }),
+        height: Some(call.into()),
+        block_age: Some(5),
+        consensus: Some(true),
+        peer_count: Some(20),
+        jobs: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_take_percentiles_of_latencies() {
+        let latencies = (1..=100).map(Duration::from_millis).collect();
+        let stats = Stats::new(latencies, 250);
+        assert!((stats.p50_ms - 51.0).abs() < 1e-9);
+        assert!((stats.p99_ms - 100.0).abs() < 1e-9);
+        assert!((stats.queries_per_call - 2.5).abs() < 1e-9);
+
+        let empty = Stats::new(vec![], 0);
+        assert!(empty.p50_ms.abs() < 1e-9);
+    }
+
+    #[test]
+    fn regressions_allow_the_tolerance() {
+        let stats = |p50_ms, p99_ms, queries_per_call| Stats {
+            p50_ms,
+            p99_ms,
+            queries_per_call,
+        };
+        let baseline = Baseline {
+            latency_tolerance_percent: 20.0,
+            query_tolerance: 0.5,
+            workloads: BTreeMap::from([("node-list".to_string(), stats(10.0, 20.0, 3.0))]),
+        };
+
+        let within = BTreeMap::from([("node-list".to_string(), stats(11.9, 23.9, 3.5))]);
+        assert!(baseline.regressions(&within).is_empty());
+
+        let slower = BTreeMap::from([("node-list".to_string(), stats(12.1, 20.0, 4.0))]);
+        let regressions = baseline.regressions(&slower);
+        assert_eq!(regressions.len(), 2);
+        assert!(regressions[0].starts_with("node-list p50"));
+        assert!(regressions[1].starts_with("node-list makes 4.00 queries"));
+
+        // workloads without a baseline are not regressions
+        let unknown = BTreeMap::from([("node-create".to_string(), stats(99.0, 99.0, 9.0))]);
+        assert!(baseline.regressions(&unknown).is_empty());
+    }
+}
@@ -1,11 +1,12 @@
-use anyhow::{Context as _, Result, anyhow};
+use anyhow::{Context as _, Result, anyhow, bail};
 use diesel::{Connection, PgConnection};
 use diesel_migrations::MigrationHarness;
 use tracing::info;
 
 use blockvisor_api::config::{Config, Context};
 use blockvisor_api::database::{self, Database, MIGRATIONS, Pool};
-use blockvisor_api::server;
+use blockvisor_api::model::backfill::{self, Task};
+use blockvisor_api::{job, server};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -15,6 +16,18 @@ async fn main() -> Result<()> {
     run_migrations(&context.config)?;
     setup_rbac(&context.pool).await?;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        match command.as_str() {
+            "backfill" => run_backfill(&context, &args[1..]).await?,
+            _ => bail!("unknown command `{command}`"),
+        }
+        context.log.shutdown()?;
+        return Ok(());
+    }
+
+    job::spawn(&context);
+
     info!("Starting server...");
     server::start(context.clone()).await?;
 
@@ -37,3 +50,21 @@ async fn setup_rbac(pool: &Pool) -> Result<()> {
         .await
         .map_err(Into::into)
 }
+
+/// Run a backfill to completion: `blockvisor-api backfill <name> [batch-size]`.
+async fn run_backfill(context: &Context, args: &[String]) -> Result<()> {
+    let Some(name) = args.first() else {
+        bail!("usage: blockvisor-api backfill <name> [batch-size]");
+    };
+    let task = Task::from_name(name)?;
+    let batch_size = match args.get(1) {
+        Some(size) => size.parse().context("invalid batch size")?,
+        None => backfill::DEFAULT_BATCH_SIZE,
+    };
+
+    info!("Running backfill {name} in batches of {batch_size}...");
+    let backfill = job::backfill(context, task, batch_size).await?;
+    info!("Backfill {name} processed {} rows", backfill.processed);
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Fault injection for the `integration-test` feature.
+//!
+//! End-to-end tests of retry logic (such as node cleanup after a delete) need
+//! an external service to fail at a known point. `Faults` are shared by the
+//! MQTT notifier and the mocked cloudflare and stripe clients of a `Context`,
+//! and are armed either directly through `Context::faults` or through the
+//! `/v1/chaos` http endpoint.
+//!
+//! Each armed failure or drop applies to exactly one later call, so a test can
+//! fail the first attempt and let the retry succeed.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default)]
+pub struct Faults {
+    dns_failures: AtomicU32,
+    stripe_delay_ms: AtomicU64,
+    mqtt_drops: AtomicU32,
+}
+
+/// A snapshot of the armed `Faults`, or the faults to arm.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultState {
+    /// How many of the next DNS calls fail.
+    pub fail_dns: Option<u32>,
+    /// How long each stripe call is delayed.
+    pub stripe_delay_ms: Option<u64>,
+    /// How many of the next MQTT messages are dropped.
+    pub drop_mqtt: Option<u32>,
+}
+
+impl Faults {
+    /// Fail the next `count` DNS calls.
+    pub fn fail_dns(&self, count: u32) {
+        self.dns_failures.store(count, Ordering::SeqCst);
+    }
+
+    /// Delay every stripe call by `delay` until reset.
+    pub fn delay_stripe(&self, delay: Duration) {
+        let millis = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX);
+        self.stripe_delay_ms.store(millis, Ordering::SeqCst);
+    }
+
+    /// Silently drop the next `count` MQTT messages.
+    pub fn drop_mqtt(&self, count: u32) {
+        self.mqtt_drops.store(count, Ordering::SeqCst);
+    }
+
+    /// Arm each fault that is set in `state`, leaving the others alone.
+    pub fn arm(&self, state: FaultState) {
+        if let Some(count) = state.fail_dns {
+            self.fail_dns(count);
+        }
+        if let Some(millis) = state.stripe_delay_ms {
+            self.delay_stripe(Duration::from_millis(millis));
+        }
+        if let Some(count) = state.drop_mqtt {
+            self.drop_mqtt(count);
+        }
+    }
+
+    /// Disarm every fault.
+    pub fn reset(&self) {
+        self.arm(FaultState {
+            fail_dns: Some(0),
+            stripe_delay_ms: Some(0),
+            drop_mqtt: Some(0),
+        });
+    }
+
+    pub fn state(&self) -> FaultState {
+        FaultState {
+            fail_dns: Some(self.dns_failures.load(Ordering::SeqCst)),
+            stripe_delay_ms: Some(self.stripe_delay_ms.load(Ordering::SeqCst)),
+            drop_mqtt: Some(self.mqtt_drops.load(Ordering::SeqCst)),
+        }
+    }
+
+    /// Whether this DNS call should fail, using up one armed failure.
+    pub fn take_dns_failure(&self) -> bool {
+        take_one(&self.dns_failures)
+    }
+
+    /// The delay to add to this stripe call, if any.
+    pub fn stripe_delay(&self) -> Option<Duration> {
+        match self.stripe_delay_ms.load(Ordering::SeqCst) {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+
+    /// Whether this MQTT message should be dropped, using up one armed drop.
+    pub fn take_mqtt_drop(&self) -> bool {
+        take_one(&self.mqtt_drops)
+    }
+}
+
+fn take_one(count: &AtomicU32) -> bool {
+    count
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn armed_faults_apply_once_each() {
+        let faults = Faults::default();
+        assert!(!faults.take_dns_failure());
+
+        faults.arm(FaultState {
+            fail_dns: Some(2),
+            stripe_delay_ms: Some(50),
+            drop_mqtt: None,
+        });
+        assert!(faults.take_dns_failure());
+        assert!(faults.take_dns_failure());
+        assert!(!faults.take_dns_failure());
+        assert!(!faults.take_mqtt_drop());
+        assert_eq!(faults.stripe_delay(), Some(Duration::from_millis(50)));
+        assert_eq!(faults.stripe_delay(), Some(Duration::from_millis(50)));
+
+        faults.reset();
+        assert_eq!(faults.stripe_delay(), None);
+    }
+}
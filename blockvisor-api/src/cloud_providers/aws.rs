@@ -0,0 +1,150 @@
+//! AWS EC2 instances, created through the EC2 API.
+//!
+//! The machine type is an instance type (e.g. `m6i.2xlarge`), the image is an
+//! AMI id, and the region is an AWS region (e.g. `eu-central-1`). Instances are
+//! launched into the default VPC of the region.
+
+use aws_sdk_ec2::config::{Credentials as AwsCredentials, Region};
+use aws_sdk_ec2::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_ec2::operation::describe_instances::DescribeInstancesError;
+use aws_sdk_ec2::operation::run_instances::RunInstancesError;
+use aws_sdk_ec2::operation::terminate_instances::TerminateInstancesError;
+use aws_sdk_ec2::types::{InstanceStateName, InstanceType, ResourceType, Tag, TagSpecification};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{Machine, MachineSpec, MachineState};
+
+const CREDENTIALS: &str = "cloud-credentials-provider";
+const INSTANCE_NOT_FOUND: &str = "InvalidInstanceID.NotFound";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to describe instance `{0}`: {1:?}
+    Describe(String, SdkError<DescribeInstancesError>),
+    /// No instance was launched.
+    NoInstance,
+    /// Failed to run instance: {0:?}
+    Run(SdkError<RunInstancesError>),
+    /// Failed to terminate instance `{0}`: {1:?}
+    Terminate(String, SdkError<TerminateInstancesError>),
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+pub(super) async fn create(
+    credentials: &Credentials,
+    spec: &MachineSpec<'_>,
+) -> Result<String, Error> {
+    let name = Tag::builder().key("Name").value(spec.name).build();
+    let tags = TagSpecification::builder()
+        .resource_type(ResourceType::Instance)
+        .tags(name)
+        .build();
+
+    let output = client(credentials, spec.region)
+        .run_instances()
+        .image_id(spec.machine_image)
+        .instance_type(InstanceType::from(spec.machine_type))
+        .min_count(1)
+        .max_count(1)
+        .user_data(STANDARD.encode(spec.user_data))
+        .tag_specifications(tags)
+        .send()
+        .await
+        .map_err(Error::Run)?;
+
+    output
+        .instances()
+        .first()
+        .and_then(|instance| instance.instance_id())
+        .map(ToString::to_string)
+        .ok_or(Error::NoInstance)
+}
+
+pub(super) async fn get(
+    credentials: &Credentials,
+    region: &str,
+    external_id: &str,
+) -> Result<Machine, Error> {
+    let deleted = Machine {
+        external_id: external_id.to_string(),
+        state: MachineState::Deleted,
+        ip_address: None,
+    };
+
+    let output = match client(credentials, region)
+        .describe_instances()
+        .instance_ids(external_id)
+        .send()
+        .await
+    {
+        Ok(output) => output,
+        Err(err) if err.code() == Some(INSTANCE_NOT_FOUND) => return Ok(deleted),
+        Err(err) => return Err(Error::Describe(external_id.to_string(), err)),
+    };
+
+    let Some(instance) = output
+        .reservations()
+        .iter()
+        .flat_map(|reservation| reservation.instances())
+        .next()
+    else {
+        return Ok(deleted);
+    };
+
+    let state = match instance.state().and_then(|state| state.name()) {
+        Some(InstanceStateName::Running) => MachineState::Running,
+        Some(InstanceStateName::ShuttingDown | InstanceStateName::Terminated) => {
+            MachineState::Deleted
+        }
+        Some(InstanceStateName::Stopping | InstanceStateName::Stopped) => MachineState::Failed,
+        _ => MachineState::Pending,
+    };
+
+    Ok(Machine {
+        external_id: external_id.to_string(),
+        state,
+        ip_address: instance.public_ip_address().and_then(|ip| ip.parse().ok()),
+    })
+}
+
+pub(super) async fn delete(
+    credentials: &Credentials,
+    region: &str,
+    external_id: &str,
+) -> Result<(), Error> {
+    match client(credentials, region)
+        .terminate_instances()
+        .instance_ids(external_id)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) if err.code() == Some(INSTANCE_NOT_FOUND) => Ok(()),
+        Err(err) => Err(Error::Terminate(external_id.to_string(), err)),
+    }
+}
+
+fn client(credentials: &Credentials, region: &str) -> aws_sdk_ec2::Client {
+    let credentials = AwsCredentials::new(
+        &credentials.access_key_id,
+        &credentials.secret_access_key,
+        None,
+        None,
+        CREDENTIALS,
+    );
+    let config = aws_sdk_ec2::Config::builder()
+        .region(Region::new(region.to_string()))
+        .credentials_provider(credentials)
+        .build();
+
+    aws_sdk_ec2::Client::from_conf(config)
+}
@@ -0,0 +1,123 @@
+//! Hetzner Cloud servers, created through the v1 servers API.
+//!
+//! The machine type is a server type (e.g. `ccx33`), the image is an image name
+//! or id (e.g. `ubuntu-24.04`), and the region is a location (e.g. `fsn1`).
+
+use serde::{Deserialize, Serialize};
+
+use super::{Client, Error, Machine, MachineSpec, MachineState, ignore_not_found, send};
+
+const API_URL: &str = "https://api.hetzner.cloud/v1";
+
+#[derive(Deserialize, Serialize)]
+pub struct Credentials {
+    pub api_token: String,
+}
+
+#[derive(Serialize)]
+struct CreateServer<'a> {
+    name: &'a str,
+    server_type: &'a str,
+    image: &'a str,
+    location: &'a str,
+    user_data: &'a str,
+    start_after_create: bool,
+}
+
+#[derive(Deserialize)]
+struct ServerResponse {
+    server: Server,
+}
+
+#[derive(Deserialize)]
+struct Server {
+    id: u64,
+    status: String,
+    public_net: PublicNet,
+}
+
+#[derive(Deserialize)]
+struct PublicNet {
+    ipv4: Option<Ipv4>,
+}
+
+#[derive(Deserialize)]
+struct Ipv4 {
+    ip: String,
+}
+
+pub(super) async fn create(
+    client: &Client,
+    credentials: &Credentials,
+    spec: &MachineSpec<'_>,
+) -> Result<String, Error> {
+    let body = CreateServer {
+        name: spec.name,
+        server_type: spec.machine_type,
+        image: spec.machine_image,
+        location: spec.region,
+        user_data: spec.user_data,
+        start_after_create: true,
+    };
+
+    let request = client
+        .inner
+        .post(format!("{API_URL}/servers"))
+        .bearer_auth(&credentials.api_token)
+        .json(&body);
+    let response: ServerResponse = send(request)
+        .await?
+        .json()
+        .await
+        .map_err(Error::ParseResponse)?;
+
+    Ok(response.server.id.to_string())
+}
+
+pub(super) async fn get(
+    client: &Client,
+    credentials: &Credentials,
+    external_id: &str,
+) -> Result<Machine, Error> {
+    let request = client
+        .inner
+        .get(format!("{API_URL}/servers/{external_id}"))
+        .bearer_auth(&credentials.api_token);
+    let response: ServerResponse = match send(request).await {
+        Ok(response) => response.json().await.map_err(Error::ParseResponse)?,
+        Err(Error::ResponseError(reqwest::StatusCode::NOT_FOUND, _)) => {
+            return Ok(Machine {
+                external_id: external_id.to_string(),
+                state: MachineState::Deleted,
+                ip_address: None,
+            });
+        }
+        Err(err) => return Err(err),
+    };
+
+    let server = response.server;
+    let state = match server.status.as_str() {
+        "running" => MachineState::Running,
+        "deleting" => MachineState::Deleted,
+        _ => MachineState::Pending,
+    };
+
+    Ok(Machine {
+        external_id: server.id.to_string(),
+        state,
+        ip_address: server.public_net.ipv4.and_then(|ipv4| ipv4.ip.parse().ok()),
+    })
+}
+
+pub(super) async fn delete(
+    client: &Client,
+    credentials: &Credentials,
+    external_id: &str,
+) -> Result<(), Error> {
+    let request = client
+        .inner
+        .delete(format!("{API_URL}/servers/{external_id}"))
+        .bearer_auth(&credentials.api_token);
+
+    ignore_not_found(send(request).await)
+}
@@ -0,0 +1,261 @@
+//! Clients for the cloud providers that orgs provision their own hosts on.
+//!
+//! Each org stores the `Credentials` of its cloud account in the secret store,
+//! one per provider, so a single client is shared across orgs and every request
+//! is built against the credentials of the org. Secrets are only persisted once
+//! vault is integrated with the secret store.
+//!
+//! Machines boot with a cloud-init script that installs blockvisord, which then
+//! registers the machine as a private host of the org using the host provision
+//! token of the user that provisioned it.
+
+pub mod aws;
+pub mod hetzner;
+pub mod ovh;
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use displaydoc::Display;
+use reqwest::{RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::auth::resource::{OrgId, Resource};
+use crate::config::cloud::Config;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::managed_host::CloudProvider;
+use crate::model::region::RegionKey;
+use crate::store::Secret;
+use crate::store::secret::SecretKey;
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// AWS error: {0}
+    Aws(#[from] aws::Error),
+    /// Failed to build cloud provider Client: {0}
+    BuildClient(reqwest::Error),
+    /// Missing cloud provider credentials.
+    MissingCredentials,
+    /// Failed to parse cloud provider credentials: {0}
+    ParseCredentials(serde_json::Error),
+    /// Failed to parse cloud provider response: {0}
+    ParseResponse(reqwest::Error),
+    /// Error code {0} from cloud provider: {1}
+    ResponseError(reqwest::StatusCode, String),
+    /// Cloud provider credentials secret: {0}
+    Secret(#[from] crate::store::secret::Error),
+    /// Failed to send cloud provider request: {0}
+    SendRequest(reqwest::Error),
+    /// Failed to serialize cloud provider credentials: {0}
+    SerializeCredentials(serde_json::Error),
+    /// Failed to serialize cloud provider request: {0}
+    SerializeRequest(serde_json::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            MissingCredentials => Status::invalid_argument("credentials"),
+            Secret(err) => err.into(),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// The credentials of an org's account with a cloud provider.
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum Credentials {
+    Aws(aws::Credentials),
+    Hetzner(hetzner::Credentials),
+    Ovh(ovh::Credentials),
+}
+
+impl Credentials {
+    pub const fn provider(&self) -> CloudProvider {
+        match self {
+            Credentials::Aws(_) => CloudProvider::Aws,
+            Credentials::Hetzner(_) => CloudProvider::Hetzner,
+            Credentials::Ovh(_) => CloudProvider::Ovh,
+        }
+    }
+
+    /// Read the credentials of an org for `provider` from the secret store.
+    pub async fn read(
+        org_id: OrgId,
+        provider: CloudProvider,
+        secret: &Secret,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let key = Self::secret_key(provider)?;
+        let data = secret.get(Resource::from(org_id), &key, conn).await?;
+        serde_json::from_slice(&data).map_err(Error::ParseCredentials)
+    }
+
+    /// Replace the credentials of an org for this provider in the secret store.
+    pub async fn write(
+        &self,
+        org_id: OrgId,
+        secret: &Secret,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let data = serde_json::to_vec(self).map_err(Error::SerializeCredentials)?;
+        let key = Self::secret_key(self.provider())?;
+        secret
+            .put(Resource::from(org_id), &key, &data, conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    fn secret_key(provider: CloudProvider) -> Result<SecretKey, Error> {
+        SecretKey::new(format!("cloud-{}-credentials", provider.name())).map_err(Into::into)
+    }
+}
+
+impl TryFrom<api::CloudCredentials> for Credentials {
+    type Error = Error;
+
+    fn try_from(credentials: api::CloudCredentials) -> Result<Self, Self::Error> {
+        use api::cloud_credentials::Credentials as Creds;
+
+        match credentials.credentials.ok_or(Error::MissingCredentials)? {
+            Creds::Aws(creds) => Ok(Credentials::Aws(aws::Credentials {
+                access_key_id: creds.access_key_id,
+                secret_access_key: creds.secret_access_key,
+            })),
+            Creds::Hetzner(creds) => Ok(Credentials::Hetzner(hetzner::Credentials {
+                api_token: creds.api_token,
+            })),
+            Creds::Ovh(creds) => Ok(Credentials::Ovh(ovh::Credentials {
+                endpoint: creds.endpoint,
+                application_key: creds.application_key,
+                application_secret: creds.application_secret,
+                consumer_key: creds.consumer_key,
+                project_id: creds.project_id,
+            })),
+        }
+    }
+}
+
+/// The machine to create with a provider.
+pub struct MachineSpec<'a> {
+    pub name: &'a str,
+    /// The region of the provider, e.g. `eu-central-1` or `fsn1`.
+    pub region: &'a str,
+    pub machine_type: &'a str,
+    pub machine_image: &'a str,
+    /// The cloud-init script run on first boot.
+    pub user_data: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MachineState {
+    Pending,
+    Running,
+    Failed,
+    Deleted,
+}
+
+/// A machine as reported by its provider.
+pub struct Machine {
+    pub external_id: String,
+    pub state: MachineState,
+    pub ip_address: Option<IpAddr>,
+}
+
+pub struct Client {
+    inner: reqwest::Client,
+}
+
+impl Client {
+    pub fn new() -> Result<Self, Error> {
+        let inner = reqwest::Client::builder()
+            .timeout(CLIENT_TIMEOUT)
+            .build()
+            .map_err(Error::BuildClient)?;
+
+        Ok(Client { inner })
+    }
+
+    /// Create a machine, returning its id with the provider.
+    pub async fn create(
+        &self,
+        credentials: &Credentials,
+        spec: &MachineSpec<'_>,
+    ) -> Result<String, Error> {
+        match credentials {
+            Credentials::Aws(creds) => aws::create(creds, spec).await.map_err(Into::into),
+            Credentials::Hetzner(creds) => hetzner::create(self, creds, spec).await,
+            Credentials::Ovh(creds) => ovh::create(self, creds, spec).await,
+        }
+    }
+
+    pub async fn get(
+        &self,
+        credentials: &Credentials,
+        region: &str,
+        external_id: &str,
+    ) -> Result<Machine, Error> {
+        match credentials {
+            Credentials::Aws(creds) => aws::get(creds, region, external_id)
+                .await
+                .map_err(Into::into),
+            Credentials::Hetzner(creds) => hetzner::get(self, creds, external_id).await,
+            Credentials::Ovh(creds) => ovh::get(self, creds, external_id).await,
+        }
+    }
+
+    /// Delete a machine, which succeeds if it no longer exists.
+    pub async fn delete(
+        &self,
+        credentials: &Credentials,
+        region: &str,
+        external_id: &str,
+    ) -> Result<(), Error> {
+        match credentials {
+            Credentials::Aws(creds) => aws::delete(creds, region, external_id)
+                .await
+                .map_err(Into::into),
+            Credentials::Hetzner(creds) => hetzner::delete(self, creds, external_id).await,
+            Credentials::Ovh(creds) => ovh::delete(self, creds, external_id).await,
+        }
+    }
+}
+
+async fn send(request: RequestBuilder) -> Result<Response, Error> {
+    let response = request.send().await.map_err(Error::SendRequest)?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        let body = response.text().await.unwrap_or_default();
+        Err(Error::ResponseError(status, body))
+    }
+}
+
+/// Treat a machine that the provider no longer knows about as deleted.
+fn ignore_not_found(result: Result<Response, Error>) -> Result<(), Error> {
+    match result {
+        Ok(_) | Err(Error::ResponseError(reqwest::StatusCode::NOT_FOUND, _)) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// The cloud-init script that installs blockvisord and registers the machine
+/// as a private host of the org that owns `provision_token`.
+pub fn bootstrap_script(config: &Config, provision_token: &str, region: &RegionKey) -> String {
+    format!(
+        "#!/bin/bash\n\
+         set -euo pipefail\n\
+         curl -fsSL {bvup_url} -o /usr/local/bin/bvup\n\
+         chmod +x /usr/local/bin/bvup\n\
+         /usr/local/bin/bvup {provision_token} --api {api_url} --region {region} --private --yes\n",
+        bvup_url = config.bvup_url,
+        api_url = config.api_url,
+    )
+}
@@ -0,0 +1,164 @@
+//! OVHcloud public cloud instances, created through the `/cloud/project` API.
+//!
+//! The machine type is a flavor id, the image is an image id, and the region is
+//! an OpenStack region (e.g. `GRA11`). Requests are signed with the application
+//! secret and consumer key of the org's credentials.
+
+use chrono::Utc;
+use reqwest::{Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+use super::{Client, Error, Machine, MachineSpec, MachineState, ignore_not_found, send};
+
+#[derive(Deserialize, Serialize)]
+pub struct Credentials {
+    /// The OVH API endpoint of the account, e.g. `https://eu.api.ovh.com/1.0`.
+    pub endpoint: String,
+    pub application_key: String,
+    pub application_secret: String,
+    pub consumer_key: String,
+    /// The public cloud project that machines are created in.
+    pub project_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateInstance<'a> {
+    name: &'a str,
+    flavor_id: &'a str,
+    image_id: &'a str,
+    region: &'a str,
+    user_data: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Instance {
+    id: String,
+    status: String,
+    #[serde(default)]
+    ip_addresses: Vec<IpAddress>,
+}
+
+#[derive(Deserialize)]
+struct IpAddress {
+    ip: String,
+    #[serde(rename = "type")]
+    ip_type: String,
+    version: u8,
+}
+
+pub(super) async fn create(
+    client: &Client,
+    credentials: &Credentials,
+    spec: &MachineSpec<'_>,
+) -> Result<String, Error> {
+    let body = CreateInstance {
+        name: spec.name,
+        flavor_id: spec.machine_type,
+        image_id: spec.machine_image,
+        region: spec.region,
+        user_data: spec.user_data,
+    };
+    let body = serde_json::to_string(&body).map_err(Error::SerializeRequest)?;
+
+    let request = signed(client, credentials, Method::POST, "/instance", body);
+    let instance: Instance = send(request)
+        .await?
+        .json()
+        .await
+        .map_err(Error::ParseResponse)?;
+
+    Ok(instance.id)
+}
+
+pub(super) async fn get(
+    client: &Client,
+    credentials: &Credentials,
+    external_id: &str,
+) -> Result<Machine, Error> {
+    let path = format!("/instance/{external_id}");
+    let request = signed(client, credentials, Method::GET, &path, String::new());
+    let instance: Instance = match send(request).await {
+        Ok(response) => response.json().await.map_err(Error::ParseResponse)?,
+        Err(Error::ResponseError(reqwest::StatusCode::NOT_FOUND, _)) => {
+            return Ok(Machine {
+                external_id: external_id.to_string(),
+                state: MachineState::Deleted,
+                ip_address: None,
+            });
+        }
+        Err(err) => return Err(err),
+    };
+
+    let state = match instance.status.as_str() {
+        "ACTIVE" => MachineState::Running,
+        "ERROR" => MachineState::Failed,
+        "DELETED" | "DELETING" => MachineState::Deleted,
+        _ => MachineState::Pending,
+    };
+    let ip_address = instance
+        .ip_addresses
+        .iter()
+        .find(|ip| ip.ip_type == "public" && ip.version == 4)
+        .and_then(|ip| ip.ip.parse().ok());
+
+    Ok(Machine {
+        external_id: instance.id,
+        state,
+        ip_address,
+    })
+}
+
+pub(super) async fn delete(
+    client: &Client,
+    credentials: &Credentials,
+    external_id: &str,
+) -> Result<(), Error> {
+    let path = format!("/instance/{external_id}");
+    let request = signed(client, credentials, Method::DELETE, &path, String::new());
+
+    ignore_not_found(send(request).await)
+}
+
+/// Build a request to `path` under the public cloud project of `credentials`.
+///
+/// OVH signs each request with the sha1 of the application secret, consumer
+/// key, method, url, body and timestamp joined by `+`.
+fn signed(
+    client: &Client,
+    credentials: &Credentials,
+    method: Method,
+    path: &str,
+    body: String,
+) -> RequestBuilder {
+    let Credentials {
+        endpoint,
+        application_key,
+        application_secret,
+        consumer_key,
+        project_id,
+    } = credentials;
+
+    let url = format!(
+        "{}/cloud/project/{project_id}{path}",
+        endpoint.trim_end_matches('/')
+    );
+    let timestamp = Utc::now().timestamp().to_string();
+    let payload = format!("{application_secret}+{consumer_key}+{method}+{url}+{body}+{timestamp}");
+    let hash: String = Sha1::digest(payload.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    client
+        .inner
+        .request(method, url)
+        .header("Content-Type", "application/json")
+        .header("X-Ovh-Application", application_key)
+        .header("X-Ovh-Consumer", consumer_key)
+        .header("X-Ovh-Timestamp", timestamp)
+        .header("X-Ovh-Signature", format!("$1${hash}"))
+        .body(body)
+}
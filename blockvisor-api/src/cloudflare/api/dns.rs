@@ -50,13 +50,41 @@ pub struct Meta {
 #[serde(tag = "type")]
 #[allow(clippy::upper_case_acronyms)]
 pub enum DnsContent {
-    A { content: Ipv4Addr },
-    AAAA { content: Ipv6Addr },
-    CNAME { content: String },
-    NS { content: String },
-    MX { content: String, priority: u16 },
-    TXT { content: String },
-    SRV { content: String },
+    A {
+        content: Ipv4Addr,
+    },
+    AAAA {
+        content: Ipv6Addr,
+    },
+    CNAME {
+        content: String,
+    },
+    NS {
+        content: String,
+    },
+    MX {
+        content: String,
+        priority: u16,
+    },
+    TXT {
+        content: String,
+    },
+    SRV {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<SrvData>,
+    },
+}
+
+/// The fields of an SRV record, as its content is derived from them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SrvData {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// The DNS name of the host serving the record.
+    pub target: String,
 }
 
 /// Create DNS Record
@@ -1,10 +1,15 @@
+#[cfg(any(test, feature = "integration-test"))]
+use std::sync::Arc;
 use std::time::Duration;
 
 use displaydoc::Display;
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
 use thiserror::Error;
 use url::Url;
 
+#[cfg(any(test, feature = "integration-test"))]
+use crate::chaos::Faults;
 use crate::config::Redacted;
 
 use super::api::{ApiErrors, ApiSuccess, Endpoint};
@@ -12,6 +17,9 @@ use super::api::{ApiErrors, ApiSuccess, Endpoint};
 const API_URL: &str = "https://api.cloudflare.com/client/v4/";
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
 const CONTENT_JSON: &str = "application/json";
+const RESOLVER_URL: &str = "https://cloudflare-dns.com/dns-query";
+const CONTENT_DNS_JSON: &str = "application/dns-json";
+const TYPE_TXT: u16 = 16;
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -23,12 +31,16 @@ pub enum Error {
     JoinEndpoint(url::ParseError),
     /// Failed to parse cloudflare API endpoint: {0}
     ParseEndpoint(url::ParseError),
+    /// Failed to parse cloudflare resolver answer: {0}
+    ParseAnswer(reqwest::Error),
     /// Failed to parse cloudflare response errors: {0}
     ParseErrors(reqwest::Error),
     /// Failed to parse cloudflare response with error `{0}`. Response body was: {1}
     ParseResponse(serde_json::Error, String),
     /// Error code {0} from cloudflare: {1:?}
     ResponseErrors(reqwest::StatusCode, ApiErrors),
+    /// Failed to resolve with cloudflare: {0}
+    Resolve(reqwest::Error),
     /// Failed to send cloudflare request: {0}
     SendRequest(reqwest::Error),
 }
@@ -36,7 +48,10 @@ pub enum Error {
 pub struct Client {
     inner: reqwest::Client,
     endpoint: Url,
+    resolver: Url,
     bearer: Redacted<String>,
+    #[cfg(any(test, feature = "integration-test"))]
+    faults: Option<Arc<Faults>>,
 }
 
 impl Client {
@@ -46,27 +61,34 @@ impl Client {
             .build()
             .map_err(Error::BuildClient)?;
         let endpoint = API_URL.parse().map_err(Error::ParseEndpoint)?;
+        let resolver = RESOLVER_URL.parse().map_err(Error::ParseEndpoint)?;
         let bearer = format!("Bearer {token}").into();
 
         Ok(Client {
             inner,
             endpoint,
+            resolver,
             bearer,
+            #[cfg(any(test, feature = "integration-test"))]
+            faults: None,
         })
     }
 
     #[cfg(any(test, feature = "integration-test"))]
-    pub fn new_mock(endpoint: Url) -> Result<Self, Error> {
+    pub fn new_mock(endpoint: Url, faults: Arc<Faults>) -> Result<Self, Error> {
         let inner = reqwest::Client::builder()
             .timeout(CLIENT_TIMEOUT)
             .build()
             .map_err(Error::BuildClient)?;
+        let resolver = endpoint.join("dns-query").map_err(Error::JoinEndpoint)?;
         let bearer = "Bearer mock-token".to_string().into();
 
         Ok(Client {
             inner,
             endpoint,
+            resolver,
             bearer,
+            faults: Some(faults),
         })
     }
 
@@ -74,10 +96,23 @@ impl Client {
     where
         E: Endpoint,
     {
-        let url = self
+        #[cfg(any(test, feature = "integration-test"))]
+        if self
+            .faults
+            .as_ref()
+            .is_some_and(|faults| faults.take_dns_failure())
+        {
+            let status = reqwest::StatusCode::SERVICE_UNAVAILABLE;
+            return Err(Error::ResponseErrors(status, ApiErrors::default()));
+        }
+
+        let mut url = self
             .endpoint
             .join(&endpoint.path())
             .map_err(Error::JoinEndpoint)?;
+        if let Some(query) = endpoint.query() {
+            url.set_query(Some(&query));
+        }
 
         let mut request = self.inner.request(endpoint.method(), url);
         request = request.header(AUTHORIZATION, &*self.bearer);
@@ -101,4 +136,50 @@ impl Client {
             Err(Error::ResponseErrors(status, errors))
         }
     }
+
+    /// Look up the public TXT records of `name` with DNS over HTTPS.
+    ///
+    /// Each record is returned as its unquoted text.
+    pub async fn resolve_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        let mut url = self.resolver.clone();
+        url.query_pairs_mut()
+            .append_pair("name", name)
+            .append_pair("type", "TXT");
+
+        let response: ResolveResponse = self
+            .inner
+            .get(url)
+            .header(ACCEPT, CONTENT_DNS_JSON)
+            .send()
+            .await
+            .map_err(Error::SendRequest)?
+            .error_for_status()
+            .map_err(Error::Resolve)?
+            .json()
+            .await
+            .map_err(Error::ParseAnswer)?;
+
+        let records = response
+            .answer
+            .into_iter()
+            .filter(|answer| answer.record_type == TYPE_TXT)
+            .map(|answer| answer.data.trim_matches('"').replace("\" \"", ""))
+            .collect();
+
+        Ok(records)
+    }
+}
+
+/// The JSON answer of a DNS over HTTPS query.
+#[derive(Deserialize)]
+struct ResolveResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<ResolveAnswer>,
+}
+
+#[derive(Deserialize)]
+struct ResolveAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
 }
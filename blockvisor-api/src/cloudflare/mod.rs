@@ -7,19 +7,77 @@ use std::net::IpAddr;
 use std::sync::Arc;
 
 use displaydoc::Display;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::config::cloudflare::Config;
 
 use self::api::dns::{
-    CreateDnsRecord, CreateDnsRecordParams, DeleteDnsRecord, DnsContent, DnsRecord,
+    CreateDnsRecord, CreateDnsRecordParams, DeleteDnsRecord, DnsContent, DnsRecord, ListDnsRecords,
+    ListDnsRecordsParams, SrvData, UpdateDnsRecord, UpdateDnsRecordParams,
 };
 
+/// The number of records fetched per page when listing DNS records.
+const LIST_PAGE_SIZE: u32 = 1000;
+
+/// The TTL that cloudflare requires of proxied records, meaning 'automatic'.
+const AUTOMATIC_TTL: u32 = 1;
+
+/// The TTL of ACME challenge records, which only live until validated.
+const CHALLENGE_TTL: u32 = 60;
+
 #[tonic::async_trait]
 pub trait Dns {
-    async fn create(&self, name: &str, ip: IpAddr) -> Result<DnsRecord, Error>;
+    /// Create the address record of a node `name` under the base domain.
+    async fn create(
+        &self,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error>;
+
+    /// Create a record of node `name` besides its address record.
+    async fn create_extra(
+        &self,
+        name: &str,
+        record: &ExtraRecord,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error>;
+
+    /// Point the existing address record `id` of node `name` at `ip`.
+    async fn update(
+        &self,
+        id: &str,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error>;
+
+    /// Create an alias record `name`, which is a full DNS name outside of the
+    /// base domain.
+    async fn create_alias(&self, name: &str, target: AliasTarget<'_>) -> Result<DnsRecord, Error>;
+
+    /// Point the existing alias record `id` at `target`.
+    async fn update_alias(
+        &self,
+        id: &str,
+        name: &str,
+        target: AliasTarget<'_>,
+    ) -> Result<DnsRecord, Error>;
+
+    /// Create the ACME challenge TXT record `_acme-challenge.{name}` for the
+    /// full DNS name `name`.
+    async fn create_challenge(&self, name: &str, content: &str) -> Result<DnsRecord, Error>;
 
     async fn delete(&self, id: &str) -> Result<(), Error>;
+
+    /// List the address records of node DNS names under the base domain.
+    async fn list(&self) -> Result<Vec<DnsRecord>, Error>;
+
+    /// Look up the public TXT records of the full DNS name `name`, which may
+    /// be outside of any zone managed by the api.
+    async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error>;
 }
 
 #[derive(Debug, Display, Error)]
@@ -30,6 +88,57 @@ pub enum Error {
     CreateDns(String, client::Error),
     /// Failed to delete cloudflare DNS record `{0}`: {1}
     DeleteDns(String, client::Error),
+    /// Failed to list cloudflare DNS records: {0}
+    ListDns(client::Error),
+    /// Failed to look up TXT records of `{0}`: {1}
+    LookupTxt(String, client::Error),
+    /// Failed to update cloudflare DNS record `{0}`: {1}
+    UpdateDns(String, client::Error),
+}
+
+impl Error {
+    /// Whether a DNS record was already deleted.
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Error::DeleteDns(_, client::Error::ResponseErrors(status, _))
+                if *status == StatusCode::NOT_FOUND
+        )
+    }
+}
+
+/// How the records of a node are created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecordOptions {
+    /// Whether traffic to the address record is proxied through cloudflare.
+    pub proxied: bool,
+    /// The TTL in seconds, or `None` for `cloudflare.dns.ttl`.
+    pub ttl: Option<u32>,
+}
+
+/// A record of a node besides its address record.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraRecord {
+    /// An SRV record `_{service}._{protocol}.{name}` for a port of the node.
+    Srv {
+        service: String,
+        protocol: String,
+        port: u16,
+        priority: u16,
+        weight: u16,
+    },
+    /// A TXT record on the name of the node.
+    Txt { content: String },
+}
+
+/// What an alias of a node points at.
+#[derive(Clone, Copy, Debug)]
+pub enum AliasTarget<'a> {
+    /// A CNAME record for the DNS name of node `{0}`.
+    Node(&'a str),
+    /// An address record for the IP of the node.
+    Address(IpAddr),
 }
 
 pub struct Cloudflare {
@@ -45,24 +154,83 @@ impl Cloudflare {
     }
 
     #[cfg(any(test, feature = "integration-test"))]
-    pub fn new_mock(config: Arc<Config>, server_url: url::Url) -> Result<Self, Error> {
-        let client = Client::new_mock(server_url).map_err(Error::CreateClient)?;
+    pub fn new_mock(
+        config: Arc<Config>,
+        server_url: url::Url,
+        faults: Arc<crate::chaos::Faults>,
+    ) -> Result<Self, Error> {
+        let client = Client::new_mock(server_url, faults).map_err(Error::CreateClient)?;
 
         Ok(Cloudflare { config, client })
     }
 
-    pub async fn create_dns(&self, name: &str, ip: IpAddr) -> Result<DnsRecord, Error> {
-        let name = format!("{name}.{}", self.config.dns.base);
-        let content = match ip {
-            IpAddr::V4(ip) => DnsContent::A { content: ip },
-            IpAddr::V6(ip) => DnsContent::AAAA { content: ip },
+    pub async fn create_dns(
+        &self,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error> {
+        let name = self.record_name(name);
+        let endpoint = CreateDnsRecord {
+            zone_identifier: &self.config.api.zone_id,
+            params: CreateDnsRecordParams {
+                ttl: Some(self.ttl(options)),
+                priority: Some(10),
+                proxied: Some(options.proxied),
+                name: &name,
+                content: address(ip),
+            },
+        };
+
+        self.client
+            .request(&endpoint)
+            .await
+            .map_err(|err| Error::CreateDns(name, err))
+    }
+
+    /// Extra records are never proxied, as cloudflare only proxies address
+    /// records.
+    pub async fn create_extra_dns(
+        &self,
+        name: &str,
+        record: &ExtraRecord,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error> {
+        let target = self.record_name(name);
+        let (name, content) = match record {
+            ExtraRecord::Srv {
+                service,
+                protocol,
+                port,
+                priority,
+                weight,
+            } => {
+                let name = self.record_name(&format!("_{service}._{protocol}.{name}"));
+                let data = SrvData {
+                    priority: *priority,
+                    weight: *weight,
+                    port: *port,
+                    target,
+                };
+                let content = DnsContent::SRV {
+                    content: None,
+                    data: Some(data),
+                };
+                (name, content)
+            }
+            ExtraRecord::Txt { content } => {
+                let content = DnsContent::TXT {
+                    content: content.clone(),
+                };
+                (target, content)
+            }
         };
 
         let endpoint = CreateDnsRecord {
             zone_identifier: &self.config.api.zone_id,
             params: CreateDnsRecordParams {
-                ttl: Some(self.config.dns.ttl),
-                priority: Some(10),
+                ttl: Some(options.ttl.unwrap_or(self.config.dns.ttl)),
+                priority: None,
                 proxied: Some(false),
                 name: &name,
                 content,
@@ -75,6 +243,101 @@ impl Cloudflare {
             .map_err(|err| Error::CreateDns(name, err))
     }
 
+    pub async fn update_dns(
+        &self,
+        id: &str,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error> {
+        let name = self.record_name(name);
+        let endpoint = UpdateDnsRecord {
+            zone_identifier: &self.config.api.zone_id,
+            identifier: id,
+            params: UpdateDnsRecordParams {
+                ttl: Some(self.ttl(options)),
+                proxied: Some(options.proxied),
+                name: &name,
+                content: address(ip),
+            },
+        };
+
+        self.client
+            .request(&endpoint)
+            .await
+            .map_err(|err| Error::UpdateDns(id.to_string(), err))
+    }
+
+    pub async fn create_alias_dns(
+        &self,
+        name: &str,
+        target: AliasTarget<'_>,
+    ) -> Result<DnsRecord, Error> {
+        let endpoint = CreateDnsRecord {
+            zone_identifier: &self.config.api.zone_id,
+            params: CreateDnsRecordParams {
+                ttl: Some(self.config.dns.ttl),
+                priority: None,
+                proxied: Some(false),
+                name,
+                content: self.alias_content(target),
+            },
+        };
+
+        self.client
+            .request(&endpoint)
+            .await
+            .map_err(|err| Error::CreateDns(name.to_string(), err))
+    }
+
+    pub async fn update_alias_dns(
+        &self,
+        id: &str,
+        name: &str,
+        target: AliasTarget<'_>,
+    ) -> Result<DnsRecord, Error> {
+        let endpoint = UpdateDnsRecord {
+            zone_identifier: &self.config.api.zone_id,
+            identifier: id,
+            params: UpdateDnsRecordParams {
+                ttl: Some(self.config.dns.ttl),
+                proxied: Some(false),
+                name,
+                content: self.alias_content(target),
+            },
+        };
+
+        self.client
+            .request(&endpoint)
+            .await
+            .map_err(|err| Error::UpdateDns(id.to_string(), err))
+    }
+
+    pub async fn create_challenge_dns(
+        &self,
+        name: &str,
+        content: &str,
+    ) -> Result<DnsRecord, Error> {
+        let name = format!("_acme-challenge.{name}");
+        let endpoint = CreateDnsRecord {
+            zone_identifier: &self.config.api.zone_id,
+            params: CreateDnsRecordParams {
+                ttl: Some(CHALLENGE_TTL),
+                priority: None,
+                proxied: Some(false),
+                name: &name,
+                content: DnsContent::TXT {
+                    content: content.to_string(),
+                },
+            },
+        };
+
+        self.client
+            .request(&endpoint)
+            .await
+            .map_err(|err| Error::CreateDns(name, err))
+    }
+
     pub async fn delete_dns(&self, id: &str) -> Result<(), Error> {
         let endpoint = DeleteDnsRecord {
             zone_identifier: &self.config.api.zone_id,
@@ -87,17 +350,141 @@ impl Cloudflare {
             .map(|_resp| ())
             .map_err(|err| Error::DeleteDns(id.to_string(), err))
     }
+
+    pub async fn list_dns(&self) -> Result<Vec<DnsRecord>, Error> {
+        let suffix = format!(".{}", self.config.dns.base);
+        let mut records = Vec::new();
+
+        for page in 1.. {
+            let endpoint = ListDnsRecords {
+                zone_identifier: &self.config.api.zone_id,
+                params: ListDnsRecordsParams {
+                    page: Some(page),
+                    per_page: Some(LIST_PAGE_SIZE),
+                    ..Default::default()
+                },
+            };
+            let batch = self
+                .client
+                .request(&endpoint)
+                .await
+                .map_err(Error::ListDns)?;
+            let last_page = batch.len() < LIST_PAGE_SIZE as usize;
+
+            records.extend(batch.into_iter().filter(|record| {
+                let address = matches!(
+                    record.content,
+                    DnsContent::A { .. } | DnsContent::AAAA { .. }
+                );
+                let node_name = record
+                    .name
+                    .strip_suffix(&suffix)
+                    .is_some_and(|name| !name.is_empty() && !name.contains('.'));
+                address && node_name
+            }));
+
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(records)
+    }
+
+    pub async fn lookup_txt_dns(&self, name: &str) -> Result<Vec<String>, Error> {
+        self.client
+            .resolve_txt(name)
+            .await
+            .map_err(|err| Error::LookupTxt(name.to_string(), err))
+    }
+
+    fn record_name(&self, name: &str) -> String {
+        format!("{name}.{}", self.config.dns.base)
+    }
+
+    fn alias_content(&self, target: AliasTarget<'_>) -> DnsContent {
+        match target {
+            AliasTarget::Node(name) => DnsContent::CNAME {
+                content: self.record_name(name),
+            },
+            AliasTarget::Address(ip) => address(ip),
+        }
+    }
+
+    fn ttl(&self, options: &RecordOptions) -> u32 {
+        if options.proxied {
+            AUTOMATIC_TTL
+        } else {
+            options.ttl.unwrap_or(self.config.dns.ttl)
+        }
+    }
+}
+
+const fn address(ip: IpAddr) -> DnsContent {
+    match ip {
+        IpAddr::V4(ip) => DnsContent::A { content: ip },
+        IpAddr::V6(ip) => DnsContent::AAAA { content: ip },
+    }
 }
 
 #[tonic::async_trait]
 impl Dns for Cloudflare {
-    async fn create(&self, name: &str, ip: IpAddr) -> Result<DnsRecord, Error> {
-        self.create_dns(name, ip).await
+    async fn create(
+        &self,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error> {
+        self.create_dns(name, ip, options).await
+    }
+
+    async fn create_extra(
+        &self,
+        name: &str,
+        record: &ExtraRecord,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error> {
+        self.create_extra_dns(name, record, options).await
+    }
+
+    async fn update(
+        &self,
+        id: &str,
+        name: &str,
+        ip: IpAddr,
+        options: &RecordOptions,
+    ) -> Result<DnsRecord, Error> {
+        self.update_dns(id, name, ip, options).await
+    }
+
+    async fn create_alias(&self, name: &str, target: AliasTarget<'_>) -> Result<DnsRecord, Error> {
+        self.create_alias_dns(name, target).await
+    }
+
+    async fn update_alias(
+        &self,
+        id: &str,
+        name: &str,
+        target: AliasTarget<'_>,
+    ) -> Result<DnsRecord, Error> {
+        self.update_alias_dns(id, name, target).await
+    }
+
+    async fn create_challenge(&self, name: &str, content: &str) -> Result<DnsRecord, Error> {
+        self.create_challenge_dns(name, content).await
     }
 
     async fn delete(&self, id: &str) -> Result<(), Error> {
         self.delete_dns(id).await
     }
+
+    async fn list(&self) -> Result<Vec<DnsRecord>, Error> {
+        self.list_dns().await
+    }
+
+    async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        self.lookup_txt_dns(name).await
+    }
 }
 
 #[cfg(any(test, feature = "integration-test"))]
@@ -110,6 +497,7 @@ pub mod tests {
     use rand::Rng;
     use rand::rngs::OsRng;
 
+    use crate::chaos::Faults;
     use crate::config::cloudflare::{ApiConfig, Config, DnsConfig};
 
     use super::api::ApiSuccess;
@@ -122,12 +510,12 @@ pub mod tests {
     }
 
     impl MockCloudflare {
-        pub async fn new(rng: &mut OsRng) -> Self {
+        pub async fn new(rng: &mut OsRng, faults: Arc<Faults>) -> Self {
             let id = rng.gen_range(200_000..5_000_000);
             let server = mock_server(id).await;
             let server_url = server.url().parse().unwrap();
             let config = Arc::new(mock_config(&server));
-            let cloudflare = Cloudflare::new_mock(config, server_url).unwrap();
+            let cloudflare = Cloudflare::new_mock(config, server_url, faults).unwrap();
 
             MockCloudflare { server, cloudflare }
         }
@@ -135,13 +523,68 @@ pub mod tests {
 
     #[tonic::async_trait]
     impl Dns for MockCloudflare {
-        async fn create(&self, name: &str, ip: IpAddr) -> Result<DnsRecord, Error> {
-            self.cloudflare.create_dns(name, ip).await
+        async fn create(
+            &self,
+            name: &str,
+            ip: IpAddr,
+            options: &RecordOptions,
+        ) -> Result<DnsRecord, Error> {
+            self.cloudflare.create_dns(name, ip, options).await
+        }
+
+        async fn create_extra(
+            &self,
+            name: &str,
+            record: &ExtraRecord,
+            options: &RecordOptions,
+        ) -> Result<DnsRecord, Error> {
+            self.cloudflare
+                .create_extra_dns(name, record, options)
+                .await
+        }
+
+        async fn update(
+            &self,
+            id: &str,
+            name: &str,
+            ip: IpAddr,
+            options: &RecordOptions,
+        ) -> Result<DnsRecord, Error> {
+            self.cloudflare.update_dns(id, name, ip, options).await
+        }
+
+        async fn create_alias(
+            &self,
+            name: &str,
+            target: AliasTarget<'_>,
+        ) -> Result<DnsRecord, Error> {
+            self.cloudflare.create_alias_dns(name, target).await
+        }
+
+        async fn update_alias(
+            &self,
+            id: &str,
+            name: &str,
+            target: AliasTarget<'_>,
+        ) -> Result<DnsRecord, Error> {
+            self.cloudflare.update_alias_dns(id, name, target).await
+        }
+
+        async fn create_challenge(&self, name: &str, content: &str) -> Result<DnsRecord, Error> {
+            self.cloudflare.create_challenge_dns(name, content).await
         }
 
         async fn delete(&self, id: &str) -> Result<(), Error> {
             self.cloudflare.delete_dns(id).await
         }
+
+        async fn list(&self) -> Result<Vec<DnsRecord>, Error> {
+            self.cloudflare.list_dns().await
+        }
+
+        async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, Error> {
+            self.cloudflare.lookup_txt_dns(name).await
+        }
     }
 
     async fn mock_server(id: u32) -> ServerGuard {
@@ -154,6 +597,20 @@ pub mod tests {
             .create_async()
             .await;
 
+        server
+            .mock("GET", Matcher::Regex(r"^/zones/.*/dns_records\?.*$".into()))
+            .with_status(200)
+            .with_body(serde_json::to_string(&mock_dns_records(id)).unwrap())
+            .create_async()
+            .await;
+
+        server
+            .mock("PUT", Matcher::Regex(r"^/zones/.*/dns_records/.*$".into()))
+            .with_status(200)
+            .with_body(serde_json::to_string(&mock_dns_record(id)).unwrap())
+            .create_async()
+            .await;
+
         server
             .mock(
                 "DELETE",
@@ -163,6 +620,14 @@ pub mod tests {
             .create_async()
             .await;
 
+        // no public TXT records exist for any name
+        server
+            .mock("GET", Matcher::Regex(r"^/dns-query\?.*$".into()))
+            .with_status(200)
+            .with_body(r#"{"Status":3}"#)
+            .create_async()
+            .await;
+
         server
     }
 
@@ -175,36 +640,54 @@ pub mod tests {
             dns: DnsConfig {
                 base: "base".into(),
                 ttl: 3600,
+                alias_zones: "example.com".parse().unwrap(),
             },
         }
     }
 
     fn mock_dns_record(id: u32) -> ApiSuccess<DnsRecord> {
         ApiSuccess {
-            result: DnsRecord {
-                meta: Meta {
-                    auto_added: Some(false),
-                },
-                locked: None,
-                name: "test".into(),
-                ttl: 1,
-                zone_id: Some("zone_id".into()),
-                modified_on: Utc::now(),
-                created_on: Utc::now(),
-                proxiable: false,
-                content: DnsContent::A {
-                    content: Ipv4Addr::LOCALHOST,
-                },
-                id: format!("{id:x}"),
-                proxied: false,
-                zone_name: Some("zone".into()),
-            },
+            result: dns_record(id, "test"),
             result_info: None,
             messages: serde_json::Value::Null,
             errors: vec![],
         }
     }
 
+    /// A day old node record that doesn't belong to any node.
+    fn mock_dns_records(id: u32) -> ApiSuccess<Vec<DnsRecord>> {
+        let mut orphan = dns_record(id + 1, "orphaned-node.base");
+        orphan.created_on = Utc::now() - chrono::TimeDelta::days(1);
+
+        ApiSuccess {
+            result: vec![orphan],
+            result_info: None,
+            messages: serde_json::Value::Null,
+            errors: vec![],
+        }
+    }
+
+    fn dns_record(id: u32, name: &str) -> DnsRecord {
+        DnsRecord {
+            meta: Meta {
+                auto_added: Some(false),
+            },
+            locked: None,
+            name: name.into(),
+            ttl: 1,
+            zone_id: Some("zone_id".into()),
+            modified_on: Utc::now(),
+            created_on: Utc::now(),
+            proxiable: false,
+            content: DnsContent::A {
+                content: Ipv4Addr::LOCALHOST,
+            },
+            id: format!("{id:x}"),
+            proxied: false,
+            zone_name: Some("zone".into()),
+        }
+    }
+
     #[tokio::test]
     async fn test_parse_dns() {
         let test1 = r#"{"result":{"id":"45afecb529c9029d909e1a2ca863fd9d","name":"formally-knowing-eel.n0des.xyz","type":"A","content":"127.0.0.8","proxiable":false,"proxied":false,"ttl":300,"settings":{},"meta":{"auto_added":false,"managed_by_apps":false,"managed_by_argo_tunnel":false},"comment":null,"tags":[],"created_on":"2025-01-27T16:29:07.984046Z","modified_on":"2025-01-27T16:29:07.984046Z"},"success":true,"errors":[],"messages":[]}"#;
@@ -219,9 +702,34 @@ pub mod tests {
         let (ctx, _db) = crate::config::Context::with_mocked().await.unwrap();
         let name = petname::petname(3, "-").unwrap();
 
+        let options = RecordOptions::default();
+
+        let record = ctx
+            .dns
+            .create(&name, Ipv4Addr::LOCALHOST.into(), &options)
+            .await
+            .unwrap();
         ctx.dns
-            .create(&name, Ipv4Addr::LOCALHOST.into())
+            .update(&record.id, &name, Ipv4Addr::BROADCAST.into(), &options)
             .await
             .unwrap();
+
+        let srv = ExtraRecord::Srv {
+            service: "p2p".into(),
+            protocol: "tcp".into(),
+            port: 30303,
+            priority: 10,
+            weight: 5,
+        };
+        ctx.dns.create_extra(&name, &srv, &options).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn list_node_dns_records() {
+        let (ctx, _db) = crate::config::Context::with_mocked().await.unwrap();
+
+        let records = ctx.dns.list().await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "orphaned-node.base");
     }
 }
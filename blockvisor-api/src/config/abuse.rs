@@ -0,0 +1,96 @@
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use derive_more::Deref;
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::HumanTime;
+use super::provider::{self, Provider};
+
+const CPU_PERCENT_VAR: &str = "ABUSE_CPU_PERCENT";
+const CPU_PERCENT_ENTRY: &str = "abuse.cpu_percent";
+const CPU_PERCENT_DEFAULT: u32 = 95;
+const PEGGED_FOR_VAR: &str = "ABUSE_PEGGED_FOR";
+const PEGGED_FOR_ENTRY: &str = "abuse.pegged_for";
+const PEGGED_FOR_DEFAULT: &str = "30m";
+const MINING_PORTS_VAR: &str = "ABUSE_MINING_PORTS";
+const MINING_PORTS_ENTRY: &str = "abuse.mining_ports";
+const MINING_PORTS_DEFAULT: &str = "3333,4444,5555,7777,14433,14444,45700";
+const AUTO_SUSPEND_VAR: &str = "ABUSE_AUTO_SUSPEND";
+const AUTO_SUSPEND_ENTRY: &str = "abuse.auto_suspend";
+const AUTO_SUSPEND_DEFAULT: bool = true;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {AUTO_SUSPEND_ENTRY:?}: {0}
+    AutoSuspend(provider::Error),
+    /// Failed to parse {CPU_PERCENT_ENTRY:?}: {0}
+    CpuPercent(provider::Error),
+    /// Failed to parse {MINING_PORTS_ENTRY:?}: {0}
+    MiningPorts(provider::Error),
+    /// Failed to parse {PEGGED_FOR_ENTRY:?}: {0}
+    PeggedFor(provider::Error),
+}
+
+/// Heuristics that flag nodes on free resources for suspected abuse.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The CPU usage percent above which a node counts as pegged.
+    pub cpu_percent: u32,
+    /// How long a node may stay pegged without protocol progress.
+    pub pegged_for: HumanTime,
+    /// Outbound ports used by mining pools.
+    pub mining_ports: Ports,
+    /// Whether flagged nodes are suspended before an admin reviews them.
+    pub auto_suspend: bool,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            cpu_percent: provider
+                .read_or(CPU_PERCENT_DEFAULT, CPU_PERCENT_VAR, CPU_PERCENT_ENTRY)
+                .map_err(Error::CpuPercent)?,
+            pegged_for: provider
+                .read_or_else(
+                    || PEGGED_FOR_DEFAULT.parse::<HumanTime>(),
+                    PEGGED_FOR_VAR,
+                    PEGGED_FOR_ENTRY,
+                )
+                .map_err(Error::PeggedFor)?,
+            mining_ports: provider
+                .read_or_else(
+                    || MINING_PORTS_DEFAULT.parse::<Ports>(),
+                    MINING_PORTS_VAR,
+                    MINING_PORTS_ENTRY,
+                )
+                .map_err(Error::MiningPorts)?,
+            auto_suspend: provider
+                .read_or(AUTO_SUSPEND_DEFAULT, AUTO_SUSPEND_VAR, AUTO_SUSPEND_ENTRY)
+                .map_err(Error::AutoSuspend)?,
+        })
+    }
+}
+
+/// A comma separated list of ports.
+#[derive(Debug, Default, Deref, Deserialize)]
+#[deref(forward)]
+pub struct Ports(Vec<u32>);
+
+impl FromStr for Ports {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|port| !port.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
@@ -0,0 +1,82 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use super::HumanTime;
+use super::provider::{self, Provider};
+
+const DIRECTORY_URL_VAR: &str = "ACME_DIRECTORY_URL";
+const DIRECTORY_URL_ENTRY: &str = "acme.directory_url";
+const DIRECTORY_URL_DEFAULT: &str = "https://acme-v02.api.letsencrypt.org/directory";
+const CONTACT_VAR: &str = "ACME_CONTACT";
+const CONTACT_ENTRY: &str = "acme.contact";
+const RENEW_BEFORE_VAR: &str = "ACME_RENEW_BEFORE";
+const RENEW_BEFORE_ENTRY: &str = "acme.renew_before";
+const RENEW_BEFORE_DEFAULT: &str = "30d";
+const ALERT_BEFORE_VAR: &str = "ACME_ALERT_BEFORE";
+const ALERT_BEFORE_ENTRY: &str = "acme.alert_before";
+const ALERT_BEFORE_DEFAULT: &str = "14d";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {ALERT_BEFORE_ENTRY:?}: {0}
+    AlertBefore(provider::Error),
+    /// Failed to parse {CONTACT_ENTRY:?}: {0}
+    Contact(provider::Error),
+    /// Failed to parse {DIRECTORY_URL_ENTRY:?}: {0}
+    DirectoryUrl(provider::Error),
+    /// Failed to parse {RENEW_BEFORE_ENTRY:?}: {0}
+    RenewBefore(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The directory of the ACME server that issues node certificates.
+    pub directory_url: Url,
+    /// The email address the ACME account is registered with.
+    pub contact: Option<String>,
+    /// How long before expiry a certificate is renewed.
+    pub renew_before: HumanTime,
+    /// How long before expiry a certificate that failed to renew is alerted.
+    pub alert_before: HumanTime,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let directory_url = provider
+            .read_or(
+                Url::parse(DIRECTORY_URL_DEFAULT).expect("DIRECTORY_URL_DEFAULT not parseable"),
+                DIRECTORY_URL_VAR,
+                DIRECTORY_URL_ENTRY,
+            )
+            .map_err(Error::DirectoryUrl)?;
+        let contact = provider
+            .maybe_read(CONTACT_VAR, CONTACT_ENTRY)
+            .map_err(Error::Contact)?;
+        let renew_before = provider
+            .read_or_else(
+                || RENEW_BEFORE_DEFAULT.parse::<HumanTime>(),
+                RENEW_BEFORE_VAR,
+                RENEW_BEFORE_ENTRY,
+            )
+            .map_err(Error::RenewBefore)?;
+        let alert_before = provider
+            .read_or_else(
+                || ALERT_BEFORE_DEFAULT.parse::<HumanTime>(),
+                ALERT_BEFORE_VAR,
+                ALERT_BEFORE_ENTRY,
+            )
+            .map_err(Error::AlertBefore)?;
+
+        Ok(Config {
+            directory_url,
+            contact,
+            renew_before,
+            alert_before,
+        })
+    }
+}
@@ -0,0 +1,53 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use super::provider::{self, Provider};
+
+const API_URL_VAR: &str = "CLOUD_API_URL";
+const API_URL_ENTRY: &str = "cloud.api_url";
+const API_URL_DEFAULT: &str = "https://api.example.com";
+const BVUP_URL_VAR: &str = "CLOUD_BVUP_URL";
+const BVUP_URL_ENTRY: &str = "cloud.bvup_url";
+const BVUP_URL_DEFAULT: &str = "https://example.com/bvup";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {API_URL_ENTRY:?}: {0}
+    ParseApiUrl(provider::Error),
+    /// Failed to parse {BVUP_URL_ENTRY:?}: {0}
+    ParseBvupUrl(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The url of this API that bootstrapped hosts register with.
+    pub api_url: Url,
+    /// The url that bootstrapped hosts download the `bvup` installer from.
+    pub bvup_url: Url,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            api_url: provider
+                .read_or(
+                    Url::parse(API_URL_DEFAULT).expect("API_URL_DEFAULT not parseable"),
+                    API_URL_VAR,
+                    API_URL_ENTRY,
+                )
+                .map_err(Error::ParseApiUrl)?,
+            bvup_url: provider
+                .read_or(
+                    Url::parse(BVUP_URL_DEFAULT).expect("BVUP_URL_DEFAULT not parseable"),
+                    BVUP_URL_VAR,
+                    BVUP_URL_ENTRY,
+                )
+                .map_err(Error::ParseBvupUrl)?,
+        })
+    }
+}
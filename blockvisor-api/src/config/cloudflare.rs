@@ -1,4 +1,7 @@
-use derive_more::{Deref, FromStr};
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use derive_more::Deref;
 use displaydoc::Display;
 use serde::Deserialize;
 use thiserror::Error;
@@ -10,6 +13,8 @@ const DNS_BASE_VAR: &str = "CF_DNS_BASE";
 const DNS_BASE_ENTRY: &str = "cloudflare.dns.base";
 const DNS_TTL_VAR: &str = "CF_TTL";
 const DNS_TTL_ENTRY: &str = "cloudflare.dns.ttl";
+const DNS_ALIAS_ZONES_VAR: &str = "CF_ALIAS_ZONES";
+const DNS_ALIAS_ZONES_ENTRY: &str = "cloudflare.dns.alias_zones";
 
 const API_ZONE_ID_VAR: &str = "CF_ZONE";
 const API_ZONE_ID_ENTRY: &str = "cloudflare.api.zone_id";
@@ -22,6 +27,8 @@ pub enum Error {
     ParseApiToken(provider::Error),
     /// Failed to parse {API_ZONE_ID_ENTRY:?}: {0}
     ParseApiZoneId(provider::Error),
+    /// Failed to parse {DNS_ALIAS_ZONES_ENTRY:?}: {0}
+    ParseDnsAliasZones(provider::Error),
     /// Failed to parse {DNS_BASE_ENTRY:?}: {0}
     ParseDnsBase(provider::Error),
     /// Failed to parse {DNS_TTL_ENTRY:?}: {0}
@@ -51,6 +58,22 @@ impl TryFrom<&Provider> for Config {
 pub struct DnsConfig {
     pub base: String,
     pub ttl: u32,
+    /// The zones that node aliases may be created in, besides `base`.
+    #[serde(default)]
+    pub alias_zones: AliasZones,
+}
+
+impl DnsConfig {
+    /// Whether `name` is a subdomain of `base` or one of the `alias_zones`.
+    pub fn allows_alias(&self, name: &str) -> bool {
+        std::iter::once(&self.base)
+            .chain(self.alias_zones.iter())
+            .any(|zone| {
+                name.strip_suffix(zone.as_str())
+                    .and_then(|prefix| prefix.strip_suffix('.'))
+                    .is_some_and(|prefix| !prefix.is_empty())
+            })
+    }
 }
 
 impl TryFrom<&Provider> for DnsConfig {
@@ -64,11 +87,32 @@ impl TryFrom<&Provider> for DnsConfig {
             ttl: provider
                 .read(DNS_TTL_VAR, DNS_TTL_ENTRY)
                 .map_err(Error::ParseDnsTtl)?,
+            alias_zones: provider
+                .read_or_default(DNS_ALIAS_ZONES_VAR, DNS_ALIAS_ZONES_ENTRY)
+                .map_err(Error::ParseDnsAliasZones)?,
         })
     }
 }
 
-#[derive(Debug, Deref, Deserialize, FromStr)]
+#[derive(Debug, Default, Deref, Deserialize)]
+#[deref(forward)]
+pub struct AliasZones(Vec<String>);
+
+impl FromStr for AliasZones {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let zones = s
+            .split(',')
+            .map(str::trim)
+            .filter(|zone| !zone.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Ok(Self(zones))
+    }
+}
+
+#[derive(Debug, Deref, Deserialize, derive_more::FromStr)]
 #[deref(forward)]
 pub struct ApiToken(Redacted<String>);
 
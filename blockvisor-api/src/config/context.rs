@@ -5,11 +5,16 @@ use rand::rngs::OsRng;
 use thiserror::Error;
 use tokio::sync::Mutex;
 
+use crate::acme::{self, Acme};
 use crate::auth::Auth;
 use crate::cloudflare::{Cloudflare, Dns};
 use crate::database::Pool;
 use crate::email::Email;
+use crate::encryption::Keyring;
+use crate::model::feature_flag::Flags;
 use crate::mqtt::Notifier;
+use crate::push::Push;
+use crate::server::Shutdown;
 use crate::store::{Secret, Store};
 use crate::stripe::{Stripe, Subscription};
 
@@ -24,6 +29,10 @@ pub enum Error {
     Cloudflare(crate::cloudflare::Error),
     /// Failed to create Email: {0}
     Email(crate::email::Error),
+    /// Failed to create encryption Keyring: {0}
+    Keyring(crate::encryption::Error),
+    /// Builder is missing Acme.
+    MissingAcme,
     /// Builder is missing Auth.
     MissingAuth,
     /// Builder is missing Config.
@@ -32,6 +41,8 @@ pub enum Error {
     MissingDns,
     /// Builder is missing Email.
     MissingEmail,
+    /// Builder is missing Keyring.
+    MissingKeyring,
     /// Builder is missing Log.
     MissingLog,
     /// Builder is missing Notifier.
@@ -48,6 +59,8 @@ pub enum Error {
     Notifier(crate::mqtt::notifier::Error),
     /// Failed to create database Pool: {0}
     Pool(crate::database::Error),
+    /// Failed to create Push: {0}
+    Push(crate::push::Error),
     /// Failed to create Stripe: {0}
     Stripe(crate::stripe::Error),
 }
@@ -58,19 +71,33 @@ pub enum Error {
 /// their own internal reference.
 #[derive(Clone)]
 pub struct Context {
+    pub acme: Arc<Box<dyn Acme + Send + Sync + 'static>>,
     pub auth: Arc<Auth>,
     pub config: Arc<Config>,
     pub dns: Arc<Box<dyn Dns + Send + Sync + 'static>>,
     pub email: Option<Arc<Email>>,
+    #[cfg(any(test, feature = "integration-test"))]
+    pub faults: Arc<crate::chaos::Faults>,
+    pub flags: Arc<Flags>,
+    pub keyring: Arc<Keyring>,
     pub log: Arc<Log>,
     pub notifier: Arc<Notifier>,
     pub pool: Pool,
+    pub push: Option<Arc<Push>>,
     pub rng: Arc<Mutex<OsRng>>,
     pub secret: Arc<Secret>,
+    pub shutdown: Arc<Shutdown>,
     pub store: Arc<Store>,
     pub stripe: Option<Arc<Box<dyn Subscription + Send + Sync + 'static>>>,
 }
 
+/// Lets background jobs run `Transaction`s without a wrapping server type.
+impl AsRef<Context> for Context {
+    fn as_ref(&self) -> &Context {
+        self
+    }
+}
+
 impl Context {
     pub async fn new() -> Result<Arc<Self>, Error> {
         let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
@@ -85,7 +112,11 @@ impl Context {
     }
 
     pub async fn builder_from(config: Config) -> Result<Builder, Error> {
-        let auth = Auth::new(&config.token);
+        let acme = acme::Client::new(config.acme.clone());
+        let keyring = Keyring::new(&config.encryption)
+            .map(Arc::new)
+            .map_err(Error::Keyring)?;
+        let auth = Auth::new(&config.token, keyring.clone());
         let dns = Cloudflare::new(config.cloudflare.clone()).map_err(Error::Cloudflare)?;
         let email = Email::new(&config, auth.cipher.clone()).map_err(Error::Email)?;
         let log = Log::new(&config.log);
@@ -93,13 +124,16 @@ impl Context {
         let notifier = Notifier::new(config.mqtt.options()?, pool.clone())
             .await
             .map_err(Error::Notifier)?;
-        let secret = Secret::new(config.secret.clone());
+        let push = Push::new(&config.push).map_err(Error::Push)?;
+        let secret = Secret::new(config.secret.clone(), keyring.clone());
         let store = Store::new(&config.store);
         let stripe = Stripe::new(config.stripe.clone()).map_err(Error::Stripe)?;
 
         let mut builder = Builder::default()
+            .acme(acme)
             .auth(auth)
             .dns(dns)
+            .keyring(keyring)
             .log(log)
             .notifier(notifier)
             .pool(pool)
@@ -110,6 +144,9 @@ impl Context {
         if let Some(email) = email {
             builder = builder.email(email);
         }
+        if let Some(push) = push {
+            builder = builder.push(push);
+        }
         if let Some(stripe) = stripe {
             builder = builder.stripe(stripe);
         }
@@ -118,6 +155,7 @@ impl Context {
 
     #[cfg(any(test, feature = "integration-test"))]
     pub async fn with_mocked() -> Result<(Arc<Self>, crate::database::tests::TestDb), Error> {
+        use crate::acme::tests::MockAcme;
         use crate::cloudflare::tests::MockCloudflare;
         use crate::database::tests::TestDb;
         use crate::stripe::tests::MockStripe;
@@ -127,25 +165,31 @@ impl Context {
         let mut rng = OsRng;
         let db = TestDb::new(&config.database, &mut rng).await;
 
-        let auth = Auth::new(&config.token);
-        let dns = MockCloudflare::new(&mut rng).await;
+        let keyring = Keyring::new(&config.encryption)
+            .map(Arc::new)
+            .map_err(Error::Keyring)?;
+        let auth = Auth::new(&config.token, keyring.clone());
         let email = Email::new_mocked(&config, auth.cipher.clone()).map_err(Error::Email)?;
         let log = Log::new(&config.log);
         let pool = db.pool();
         let notifier = Notifier::new(config.mqtt.options()?, pool.clone())
             .await
             .map_err(Error::Notifier)?;
-        let secret = Secret::new(config.secret.clone());
+        let dns = MockCloudflare::new(&mut rng, notifier.faults()).await;
+        let secret = Secret::new(config.secret.clone(), keyring.clone());
         let store = Store::new(&config.store);
-        let stripe = MockStripe::new().await;
+        let stripe = MockStripe::new(notifier.faults()).await;
 
         Builder::default()
+            .acme(MockAcme)
             .auth(auth)
             .dns(dns)
             .email(email)
+            .keyring(keyring)
             .log(log)
             .notifier(notifier)
             .pool(pool)
+            .push(Push::new_mocked())
             .rng(rng)
             .secret(secret)
             .store(store)
@@ -159,13 +203,16 @@ impl Context {
 /// Incrementally build a new `Context` from constituent parts.
 #[derive(Default)]
 pub struct Builder {
+    acme: Option<Box<dyn Acme + Send + Sync + 'static>>,
     auth: Option<Auth>,
     config: Option<Config>,
     dns: Option<Box<dyn Dns + Send + Sync + 'static>>,
     email: Option<Email>,
+    keyring: Option<Arc<Keyring>>,
     log: Option<Arc<Log>>,
     notifier: Option<Arc<Notifier>>,
     pool: Option<Pool>,
+    push: Option<Push>,
     rng: Option<OsRng>,
     secret: Option<Secret>,
     store: Option<Store>,
@@ -174,21 +221,42 @@ pub struct Builder {
 
 impl Builder {
     pub fn build(self) -> Result<Arc<Context>, Error> {
+        let config = self.config.ok_or(Error::MissingConfig)?;
+        let pool = self.pool.ok_or(Error::MissingPool)?;
+        let flags = Flags::new(pool.clone(), &config.flag);
+        let notifier = self.notifier.ok_or(Error::MissingNotifier)?;
+
         Ok(Arc::new(Context {
+            acme: self.acme.ok_or(Error::MissingAcme).map(Arc::new)?,
             auth: self.auth.ok_or(Error::MissingAuth).map(Arc::new)?,
-            config: self.config.ok_or(Error::MissingConfig).map(Arc::new)?,
+            config: Arc::new(config),
             dns: self.dns.ok_or(Error::MissingDns).map(Arc::new)?,
             email: self.email.map(Arc::new),
+            #[cfg(any(test, feature = "integration-test"))]
+            faults: notifier.faults(),
+            flags: Arc::new(flags),
+            keyring: self.keyring.ok_or(Error::MissingKeyring)?,
             log: self.log.ok_or(Error::MissingLog)?,
-            notifier: self.notifier.ok_or(Error::MissingNotifier)?,
-            pool: self.pool.ok_or(Error::MissingPool)?,
+            notifier,
+            pool,
+            push: self.push.map(Arc::new),
             rng: Arc::new(Mutex::new(self.rng.unwrap_or_default())),
             secret: self.secret.ok_or(Error::MissingSecret).map(Arc::new)?,
+            shutdown: Arc::new(Shutdown::default()),
             store: self.store.ok_or(Error::MissingStore).map(Arc::new)?,
             stripe: self.stripe.map(Arc::new),
         }))
     }
 
+    #[must_use]
+    pub fn acme<A>(mut self, acme: A) -> Self
+    where
+        A: Acme + Send + Sync + 'static,
+    {
+        self.acme = Some(Box::new(acme));
+        self
+    }
+
     #[must_use]
     pub fn auth(mut self, auth: Auth) -> Self {
         self.auth = Some(auth);
@@ -216,6 +284,12 @@ impl Builder {
         self
     }
 
+    #[must_use]
+    pub fn keyring(mut self, keyring: Arc<Keyring>) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
     #[must_use]
     pub fn log(mut self, log: Arc<Log>) -> Self {
         self.log = Some(log);
@@ -234,6 +308,12 @@ impl Builder {
         self
     }
 
+    #[must_use]
+    pub fn push(mut self, push: Push) -> Self {
+        self.push = Some(push);
+        self
+    }
+
     #[must_use]
     pub const fn rng(mut self, rng: OsRng) -> Self {
         self.rng = Some(rng);
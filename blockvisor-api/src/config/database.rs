@@ -20,9 +20,34 @@ const MAX_LIFETIME_DEFAULT: &str = "1d";
 const IDLE_TIMEOUT_VAR: &str = "DB_IDLE_TIMEOUT";
 const IDLE_TIMEOUT_ENTRY: &str = "database.idle_timeout";
 const IDLE_TIMEOUT_DEFAULT: &str = "2m";
+const ACQUIRE_TIMEOUT_VAR: &str = "DB_ACQUIRE_TIMEOUT";
+const ACQUIRE_TIMEOUT_ENTRY: &str = "database.acquire_timeout";
+const ACQUIRE_TIMEOUT_DEFAULT: &str = "5s";
+const SLOW_ACQUIRE_VAR: &str = "DB_SLOW_ACQUIRE";
+const SLOW_ACQUIRE_ENTRY: &str = "database.slow_acquire";
+const SLOW_ACQUIRE_DEFAULT: &str = "100ms";
+const SLOW_QUERY_VAR: &str = "DB_SLOW_QUERY";
+const SLOW_QUERY_ENTRY: &str = "database.slow_query";
+const SLOW_QUERY_DEFAULT: &str = "500ms";
+const WRITE_ATTEMPTS_VAR: &str = "DB_WRITE_ATTEMPTS";
+const WRITE_ATTEMPTS_ENTRY: &str = "database.write_attempts";
+const WRITE_ATTEMPTS_DEFAULT: u32 = 3;
+const WRITE_BACKOFF_VAR: &str = "DB_WRITE_BACKOFF";
+const WRITE_BACKOFF_ENTRY: &str = "database.write_backoff";
+const WRITE_BACKOFF_DEFAULT: &str = "25ms";
+const CACHE_TTL_VAR: &str = "DB_CACHE_TTL";
+const CACHE_TTL_ENTRY: &str = "database.cache_ttl";
+const CACHE_TTL_DEFAULT: &str = "1m";
+const ROLES_CACHE_TTL_VAR: &str = "DB_ROLES_CACHE_TTL";
+const ROLES_CACHE_TTL_ENTRY: &str = "database.roles_cache_ttl";
+const ROLES_CACHE_TTL_DEFAULT: &str = "10s";
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Failed to parse {ACQUIRE_TIMEOUT_ENTRY:?}: {0}
+    AcquireTimeout(provider::Error),
+    /// Failed to parse {CACHE_TTL_ENTRY:?}: {0}
+    CacheTtl(provider::Error),
     /// Failed to parse {IDLE_TIMEOUT_ENTRY:?}: {0}
     IdleTimeout(provider::Error),
     /// Failed to parse {MAX_CONNS_ENTRY:?}: {0}
@@ -31,8 +56,20 @@ pub enum Error {
     MaxLifetime(provider::Error),
     /// Failed to parse {MIN_CONNS_ENTRY:?}: {0}
     MinConns(provider::Error),
+    /// Failed to parse {ROLES_CACHE_TTL_ENTRY:?}: {0}
+    RolesCacheTtl(provider::Error),
+    /// Failed to parse {SLOW_ACQUIRE_ENTRY:?}: {0}
+    SlowAcquire(provider::Error),
+    /// Failed to parse {SLOW_QUERY_ENTRY:?}: {0}
+    SlowQuery(provider::Error),
     /// Failed to parse {URL_ENTRY:?}: {0}
     Url(provider::Error),
+    /// Failed to parse {WRITE_ATTEMPTS_ENTRY:?}: {0}
+    WriteAttempts(provider::Error),
+    /// Failed to parse {WRITE_BACKOFF_ENTRY:?}: {0}
+    WriteBackoff(provider::Error),
+    /// {WRITE_ATTEMPTS_ENTRY:?} must be at least 1.
+    ZeroWriteAttempts,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,6 +80,20 @@ pub struct Config {
     pub min_conns: u32,
     pub max_lifetime: HumanTime,
     pub idle_timeout: HumanTime,
+    /// How long to wait for a connection before the request fails.
+    pub acquire_timeout: HumanTime,
+    /// Waiting longer than this for a connection is logged.
+    pub slow_acquire: HumanTime,
+    /// Queries taking longer than this are logged.
+    pub slow_query: HumanTime,
+    /// How long rarely changing rows are cached before being read again.
+    pub cache_ttl: HumanTime,
+    /// How long the roles of a user are cached before being read again.
+    pub roles_cache_ttl: HumanTime,
+    /// How many times a write is attempted when its transaction conflicts.
+    pub write_attempts: u32,
+    /// The delay before the first retry of a write, doubling for each retry.
+    pub write_backoff: HumanTime,
 }
 
 impl TryFrom<&Provider> for Config {
@@ -70,6 +121,58 @@ impl TryFrom<&Provider> for Config {
                 IDLE_TIMEOUT_ENTRY,
             )
             .map_err(Error::IdleTimeout)?;
+        let acquire_timeout = provider
+            .read_or_else(
+                || ACQUIRE_TIMEOUT_DEFAULT.parse::<HumanTime>(),
+                ACQUIRE_TIMEOUT_VAR,
+                ACQUIRE_TIMEOUT_ENTRY,
+            )
+            .map_err(Error::AcquireTimeout)?;
+        let slow_acquire = provider
+            .read_or_else(
+                || SLOW_ACQUIRE_DEFAULT.parse::<HumanTime>(),
+                SLOW_ACQUIRE_VAR,
+                SLOW_ACQUIRE_ENTRY,
+            )
+            .map_err(Error::SlowAcquire)?;
+        let slow_query = provider
+            .read_or_else(
+                || SLOW_QUERY_DEFAULT.parse::<HumanTime>(),
+                SLOW_QUERY_VAR,
+                SLOW_QUERY_ENTRY,
+            )
+            .map_err(Error::SlowQuery)?;
+        let cache_ttl = provider
+            .read_or_else(
+                || CACHE_TTL_DEFAULT.parse::<HumanTime>(),
+                CACHE_TTL_VAR,
+                CACHE_TTL_ENTRY,
+            )
+            .map_err(Error::CacheTtl)?;
+        let roles_cache_ttl = provider
+            .read_or_else(
+                || ROLES_CACHE_TTL_DEFAULT.parse::<HumanTime>(),
+                ROLES_CACHE_TTL_VAR,
+                ROLES_CACHE_TTL_ENTRY,
+            )
+            .map_err(Error::RolesCacheTtl)?;
+        let write_attempts = provider
+            .read_or(
+                WRITE_ATTEMPTS_DEFAULT,
+                WRITE_ATTEMPTS_VAR,
+                WRITE_ATTEMPTS_ENTRY,
+            )
+            .map_err(Error::WriteAttempts)?;
+        if write_attempts == 0 {
+            return Err(Error::ZeroWriteAttempts);
+        }
+        let write_backoff = provider
+            .read_or_else(
+                || WRITE_BACKOFF_DEFAULT.parse::<HumanTime>(),
+                WRITE_BACKOFF_VAR,
+                WRITE_BACKOFF_ENTRY,
+            )
+            .map_err(Error::WriteBackoff)?;
 
         Ok(Config {
             url,
@@ -77,6 +180,13 @@ impl TryFrom<&Provider> for Config {
             min_conns,
             max_lifetime,
             idle_timeout,
+            acquire_timeout,
+            slow_acquire,
+            slow_query,
+            cache_ttl,
+            roles_cache_ttl,
+            write_attempts,
+            write_backoff,
         })
     }
 }
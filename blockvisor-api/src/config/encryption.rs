@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use base64::engine::{Engine as _, general_purpose::STANDARD};
+use derive_more::Deref;
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::Redacted;
+use super::provider::{self, Provider};
+
+const KEYS_VAR: &str = "ENCRYPTION_KEYS";
+const KEYS_ENTRY: &str = "encryption.keys";
+const CURRENT_KEY_VAR: &str = "ENCRYPTION_CURRENT_KEY";
+const CURRENT_KEY_ENTRY: &str = "encryption.current_key";
+const INDEX_KEY_VAR: &str = "ENCRYPTION_INDEX_KEY";
+const INDEX_KEY_ENTRY: &str = "encryption.index_key";
+
+/// The length in bytes of each AES-256 key encryption key.
+pub const KEY_BYTES: usize = 32;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {CURRENT_KEY_ENTRY:?}: {0}
+    CurrentKey(provider::Error),
+    /// Failed to decode the blind index key: {0}
+    DecodeIndexKey(base64::DecodeError),
+    /// Failed to decode encryption key `{0}`: {1}
+    DecodeKey(String, base64::DecodeError),
+    /// Failed to parse {INDEX_KEY_ENTRY:?}: {0}
+    IndexKey(provider::Error),
+    /// The blind index key must be 32 bytes, not {0}.
+    IndexKeyLen(usize),
+    /// Encryption key `{0}` must be 32 bytes, not {1}.
+    KeyLen(String, usize),
+    /// Encryption key ids must be alphanumeric or `-`: {0}
+    KeyId(String),
+    /// Failed to parse {KEYS_ENTRY:?}: {0}
+    Keys(provider::Error),
+    /// Encryption key entry is not of the form `id=base64`: {0}
+    KeyEntry(String),
+    /// The current encryption key `{0}` is not one of the keys.
+    UnknownCurrentKey(String),
+}
+
+/// The key encryption keys of sensitive database columns.
+///
+/// Values are sealed with `current_key`, and may be opened with any of `keys`,
+/// so a rotated key must be kept until the re-encryption backfills complete.
+///
+/// Columns that are looked up by value also store a keyed hash of the value
+/// under `index_key`, which can't be rotated without rewriting those hashes.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub keys: Redacted<Keys>,
+    pub current_key: String,
+    pub index_key: Redacted<IndexKey>,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let keys: Redacted<Keys> = provider.read(KEYS_VAR, KEYS_ENTRY).map_err(Error::Keys)?;
+        let current_key: String = provider
+            .read(CURRENT_KEY_VAR, CURRENT_KEY_ENTRY)
+            .map_err(Error::CurrentKey)?;
+        let index_key: Redacted<IndexKey> = provider
+            .read(INDEX_KEY_VAR, INDEX_KEY_ENTRY)
+            .map_err(Error::IndexKey)?;
+
+        if !keys.contains_key(&current_key) {
+            return Err(Error::UnknownCurrentKey(current_key));
+        }
+
+        Ok(Config {
+            keys,
+            current_key,
+            index_key,
+        })
+    }
+}
+
+/// A comma separated list of `id=base64` keys.
+#[derive(Debug, Default, Deref, Deserialize)]
+pub struct Keys(HashMap<String, Vec<u8>>);
+
+impl FromStr for Keys {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (id, key) = entry
+                    .split_once('=')
+                    .ok_or_else(|| Error::KeyEntry(entry.to_string()))?;
+                let id = id.trim();
+                if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    return Err(Error::KeyId(id.to_string()));
+                }
+
+                let key = STANDARD
+                    .decode(key.trim())
+                    .map_err(|err| Error::DecodeKey(id.to_string(), err))?;
+                if key.len() != KEY_BYTES {
+                    return Err(Error::KeyLen(id.to_string(), key.len()));
+                }
+
+                Ok((id.to_string(), key))
+            })
+            .collect::<Result<_, _>>()
+            .map(Keys)
+    }
+}
+
+/// A base64 HMAC-SHA256 key for the blind indexes of encrypted columns.
+#[derive(Debug, Default, Deref, Deserialize)]
+pub struct IndexKey(Vec<u8>);
+
+impl FromStr for IndexKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = STANDARD.decode(s.trim()).map_err(Error::DecodeIndexKey)?;
+        if key.len() == KEY_BYTES {
+            Ok(IndexKey(key))
+        } else {
+            Err(Error::IndexKeyLen(key.len()))
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use super::provider::{self, Provider};
+
+const BASE_URL_VAR: &str = "ENDPOINT_BASE_URL";
+const BASE_URL_ENTRY: &str = "endpoint.base_url";
+const BASE_URL_DEFAULT: &str = "https://rpc.example.com";
+const METERED_SKU_VAR: &str = "ENDPOINT_METERED_SKU";
+const METERED_SKU_ENTRY: &str = "endpoint.metered_sku";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {BASE_URL_ENTRY:?}: {0}
+    BaseUrl(provider::Error),
+    /// Failed to parse {METERED_SKU_ENTRY:?}: {0}
+    MeteredSku(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The base url of the RPC gateway that serves node endpoints.
+    pub base_url: Url,
+    /// The stripe sku of the metered price for endpoint requests.
+    pub metered_sku: Option<String>,
+}
+
+impl Config {
+    /// The public url of the endpoint with id `endpoint_id`.
+    pub fn url<T: std::fmt::Display>(&self, endpoint_id: T) -> String {
+        format!(
+            "{}/{endpoint_id}",
+            self.base_url.as_str().trim_end_matches('/')
+        )
+    }
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            base_url: provider
+                .read_or(
+                    Url::parse(BASE_URL_DEFAULT).expect("BASE_URL_DEFAULT not parseable"),
+                    BASE_URL_VAR,
+                    BASE_URL_ENTRY,
+                )
+                .map_err(Error::BaseUrl)?,
+            metered_sku: provider
+                .maybe_read(METERED_SKU_VAR, METERED_SKU_ENTRY)
+                .map_err(Error::MeteredSku)?,
+        })
+    }
+}
@@ -0,0 +1,39 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::HumanTime;
+use super::provider::{self, Provider};
+
+const REFRESH_VAR: &str = "FEATURE_FLAG_REFRESH";
+const REFRESH_ENTRY: &str = "flag.refresh";
+const REFRESH_DEFAULT: &str = "30s";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {REFRESH_ENTRY:?}: {0}
+    Refresh(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// How long feature flags are cached before being reloaded.
+    pub refresh: HumanTime,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let refresh = provider
+            .read_or_else(
+                || REFRESH_DEFAULT.parse::<HumanTime>(),
+                REFRESH_VAR,
+                REFRESH_ENTRY,
+            )
+            .map_err(Error::Refresh)?;
+
+        Ok(Config { refresh })
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::NaiveDate;
 use displaydoc::Display;
 use serde::Deserialize;
 use thiserror::Error;
@@ -8,16 +9,34 @@ const REQUEST_CONCURRENCY_LIMIT_VAR: &str = "REQUEST_CONCURRENCY_LIMIT";
 const REQUEST_CONCURRENCY_LIMIT_ENTRY: &str = "grpc.request_concurrency_limit";
 const REQUEST_CONCURRENCY_LIMIT_DEFAULT: usize = 32;
 
+const V1_SUNSET_VAR: &str = "GRPC_V1_SUNSET";
+const V1_SUNSET_ENTRY: &str = "grpc.v1_sunset";
+
+const PAYLOAD_SAMPLE_PERCENT_VAR: &str = "GRPC_PAYLOAD_SAMPLE_PERCENT";
+const PAYLOAD_SAMPLE_PERCENT_ENTRY: &str = "grpc.payload_sample_percent";
+const PAYLOAD_SAMPLE_PERCENT_DEFAULT: f64 = 0.0;
+
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// {PAYLOAD_SAMPLE_PERCENT_ENTRY:?} of {0} is not between 0 and 100.
+    PayloadSampleOutOfRange(f64),
+    /// Failed to parse {PAYLOAD_SAMPLE_PERCENT_ENTRY:?}: {0}
+    PayloadSamplePercent(provider::Error),
     /// Failed to parse {REQUEST_CONCURRENCY_LIMIT_ENTRY:?}: {0}
     RequestConcurrencyLimit(provider::Error),
+    /// Failed to parse {V1_SUNSET_ENTRY:?}: {0}
+    V1Sunset(provider::Error),
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub request_concurrency_limit: usize,
+    /// The date after which deprecated v1 services may be removed.
+    pub v1_sunset: Option<NaiveDate>,
+    /// The percentage of authenticated calls whose redacted payloads are
+    /// recorded in the audit log.
+    pub payload_sample_percent: f64,
 }
 
 impl TryFrom<&Provider> for Config {
@@ -31,9 +50,24 @@ impl TryFrom<&Provider> for Config {
                 REQUEST_CONCURRENCY_LIMIT_ENTRY,
             )
             .map_err(Error::RequestConcurrencyLimit)?;
+        let v1_sunset = provider
+            .maybe_read(V1_SUNSET_VAR, V1_SUNSET_ENTRY)
+            .map_err(Error::V1Sunset)?;
+        let payload_sample_percent = provider
+            .read_or(
+                PAYLOAD_SAMPLE_PERCENT_DEFAULT,
+                PAYLOAD_SAMPLE_PERCENT_VAR,
+                PAYLOAD_SAMPLE_PERCENT_ENTRY,
+            )
+            .map_err(Error::PayloadSamplePercent)?;
+        if !(0.0..=100.0).contains(&payload_sample_percent) {
+            return Err(Error::PayloadSampleOutOfRange(payload_sample_percent));
+        }
 
         Ok(Config {
             request_concurrency_limit,
+            v1_sunset,
+            payload_sample_percent,
         })
     }
 }
@@ -1,21 +1,27 @@
-use std::sync::{Arc, OnceLock};
+use std::env;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
 use std::time::Duration;
 
+use derive_more::Deref;
 use displaydoc::Display;
-use opentelemetry::global;
-use opentelemetry::trace::TraceError;
+use opentelemetry::trace::{Link, SamplingResult, SpanKind, TraceError, TraceId};
+use opentelemetry::{KeyValue, global};
 use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
 use opentelemetry_otlp::{LogExporter, MetricExporter, SpanExporter, WithExportConfig};
 use opentelemetry_sdk::Resource;
 use opentelemetry_sdk::logs::{LogError, SdkLoggerProvider};
 use opentelemetry_sdk::metrics::{MetricError, PeriodicReader, SdkMeterProvider};
-use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider, ShouldSample};
 use serde::Deserialize;
 use strum::{EnumString, IntoStaticStr};
 use thiserror::Error;
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{info, warn};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::{EnvFilter, fmt};
+use tracing_subscriber::{EnvFilter, Registry, fmt, reload};
 use url::Url;
 
 use super::HumanTime;
@@ -37,6 +43,14 @@ const OPENTELEMETRY_EXPORT_INTERVAL_VAR: &str = "OPENTELEMETRY_EXPORT_INTERVAL";
 const OPENTELEMETRY_EXPORT_INTERVAL_ENTRY: &str = "log.opentelemetry.export_interval";
 const OPENTELEMETRY_EXPORT_INTERVAL_DEFAULT: Duration = Duration::from_secs(5);
 
+const TRACING_ENDPOINT_VAR: &str = "TRACING_ENDPOINT";
+const TRACING_ENDPOINT_ENTRY: &str = "log.tracing.endpoint";
+const TRACING_SAMPLE_RATIO_VAR: &str = "TRACING_SAMPLE_RATIO";
+const TRACING_SAMPLE_RATIO_ENTRY: &str = "log.tracing.sample_ratio";
+const TRACING_SAMPLE_RATIO_DEFAULT: SampleRatio = SampleRatio(1.0);
+const TRACING_SAMPLE_OVERRIDES_VAR: &str = "TRACING_SAMPLE_OVERRIDES";
+const TRACING_SAMPLE_OVERRIDES_ENTRY: &str = "log.tracing.sample_overrides";
+
 static INIT_LOG: OnceLock<Arc<Log>> = OnceLock::new();
 
 #[derive(Debug, Display, Error)]
@@ -55,8 +69,14 @@ pub enum Error {
     ParseEnvironment(provider::Error),
     /// Failed to parse {LOG_FILTER_ENTRY:?}: {0}
     ParseFilter(provider::Error),
+    /// Failed to reload log filter: {0}
+    ReloadFilter(#[from] reload::Error),
+    /// Failed to build span exporter: {0}
+    SpanExporter(String),
     /// Trace error: {0}
     TraceError(#[from] TraceError),
+    /// Failed to parse TracingConfig: {0}
+    Tracing(#[from] TracingError),
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, EnumString, IntoStaticStr)]
@@ -71,9 +91,18 @@ pub enum Environment {
 pub struct Log {
     pub logger: SdkLoggerProvider,
     pub meter: SdkMeterProvider,
-    pub tracer: SdkTracerProvider,
     pub filter: String,
     pub interval: Duration,
+    resource: Resource,
+    sampler: RatioSampler,
+    tracer: RwLock<Tracer>,
+    reload: OnceLock<reload::Handle<EnvFilter, Registry>>,
+}
+
+/// The tracer provider exporting spans to `endpoint`.
+struct Tracer {
+    provider: SdkTracerProvider,
+    endpoint: Url,
 }
 
 impl Log {
@@ -108,22 +137,19 @@ impl Log {
                 .with_reader(reader)
                 .build();
 
-            let exporter = SpanExporter::builder()
-                .with_tonic()
-                .with_endpoint(config.opentelemetry.endpoint.clone())
-                .build()
-                .expect("span exporter");
-            let tracer = SdkTracerProvider::builder()
-                .with_resource(resource)
-                .with_batch_exporter(exporter)
-                .build();
+            let sampler = RatioSampler::new(&config.tracing);
+            let endpoint = config.trace_endpoint().clone();
+            let provider = tracer_provider(&endpoint, &resource, &sampler).expect("span exporter");
 
             Arc::new(Log {
                 logger,
                 meter,
-                tracer,
                 filter: config.filter.clone(),
                 interval,
+                resource,
+                sampler,
+                tracer: RwLock::new(Tracer { provider, endpoint }),
+                reload: OnceLock::new(),
             })
         });
 
@@ -131,16 +157,14 @@ impl Log {
     }
 
     pub fn init(&self) -> Result<(), Error> {
-        global::set_tracer_provider(self.tracer.clone());
+        global::set_tracer_provider(self.tracer_provider());
         global::set_meter_provider(self.meter.clone());
+        global::set_text_map_propagator(TraceContextPropagator::new());
 
-        // https://github.com/open-telemetry/opentelemetry-rust/issues/761
         let filter = EnvFilter::try_from_default_env()
-            .unwrap_or_else(|_| EnvFilter::new(self.filter.clone()))
-            .add_directive("h2=error".parse()?)
-            .add_directive("hyper=error".parse()?)
-            .add_directive("tonic=error".parse()?)
-            .add_directive("reqwest=error".parse()?);
+            .unwrap_or_else(|_| EnvFilter::new(self.filter.clone()));
+        let (filter, handle) = reload::Layer::new(with_quiet_deps(filter)?);
+        let _ = self.reload.set(handle);
 
         tracing_subscriber::registry()
             .with(filter)
@@ -153,8 +177,9 @@ impl Log {
 
     #[cfg(any(test, feature = "integration-test"))]
     pub fn test_init(&self) {
-        global::set_tracer_provider(self.tracer.clone());
+        global::set_tracer_provider(self.tracer_provider());
         global::set_meter_provider(self.meter.clone());
+        global::set_text_map_propagator(TraceContextPropagator::new());
 
         let filter = EnvFilter::default()
             .add_directive("debug".parse().unwrap())
@@ -171,9 +196,85 @@ impl Log {
             .init();
     }
 
+    /// Apply the filter, sampling and trace endpoint of `config` at runtime.
+    ///
+    /// The filter is left alone when set by `RUST_LOG`. Spans still buffered
+    /// for a previous trace endpoint are flushed there before it is dropped.
+    pub fn reload(&self, config: &Config) -> Result<(), Error> {
+        if let Some(handle) = self.reload.get() {
+            if env::var_os(EnvFilter::DEFAULT_ENV).is_none() {
+                let filter = with_quiet_deps(EnvFilter::try_new(&config.filter)?)?;
+                handle.reload(filter)?;
+            }
+        }
+
+        self.sampler.update(&config.tracing);
+
+        let endpoint = config.trace_endpoint();
+        let mut tracer = self.tracer.write().unwrap_or_else(PoisonError::into_inner);
+        if tracer.endpoint == *endpoint {
+            return Ok(());
+        }
+
+        let provider = tracer_provider(endpoint, &self.resource, &self.sampler)?;
+        global::set_tracer_provider(provider.clone());
+        let old = std::mem::replace(
+            &mut *tracer,
+            Tracer {
+                provider,
+                endpoint: endpoint.clone(),
+            },
+        );
+        drop(tracer);
+
+        if let Err(err) = old.provider.shutdown() {
+            warn!("Failed to shut down tracer for {}: {err}", old.endpoint);
+        }
+
+        Ok(())
+    }
+
+    /// Reload the log config on each SIGHUP.
+    ///
+    /// Only the config file and secrets are read again, as the environment of
+    /// a running process can't change.
+    pub fn on_reload_signal(self: &Arc<Self>) -> Result<(), std::io::Error> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        let log = self.clone();
+
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading log config...");
+                let log = log.clone();
+                let reloaded = tokio::task::spawn_blocking(move || log.reload_config()).await;
+                if let Err(err) = reloaded {
+                    warn!("Log config reload task failed: {err}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    fn reload_config(&self) {
+        let provider = match super::Config::provider() {
+            Ok(provider) => provider,
+            Err(err) => {
+                warn!("Failed to read config to reload: {err}");
+                return;
+            }
+        };
+
+        match Config::try_from(&provider).and_then(|config| self.reload(&config)) {
+            Ok(()) => info!("Reloaded log config."),
+            Err(err) => warn!("Failed to reload log config: {err}"),
+        }
+    }
+
     pub fn shutdown(&self) -> Result<(), Error> {
-        self.tracer.force_flush()?;
-        self.tracer.shutdown()?;
+        let tracer = self.tracer_provider();
+        tracer.force_flush()?;
+        tracer.shutdown()?;
 
         self.meter.force_flush()?;
         self.meter.shutdown()?;
@@ -183,6 +284,89 @@ impl Log {
 
         Ok(())
     }
+
+    fn tracer_provider(&self) -> SdkTracerProvider {
+        let tracer = self.tracer.read().unwrap_or_else(PoisonError::into_inner);
+        tracer.provider.clone()
+    }
+}
+
+fn tracer_provider(
+    endpoint: &Url,
+    resource: &Resource,
+    sampler: &RatioSampler,
+) -> Result<SdkTracerProvider, Error> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint.clone())
+        .build()
+        .map_err(|err| Error::SpanExporter(err.to_string()))?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_resource(resource.clone())
+        .with_sampler(Sampler::ParentBased(Box::new(sampler.clone())))
+        .with_batch_exporter(exporter)
+        .build())
+}
+
+// https://github.com/open-telemetry/opentelemetry-rust/issues/761
+fn with_quiet_deps(filter: EnvFilter) -> Result<EnvFilter, Error> {
+    Ok(filter
+        .add_directive("h2=error".parse()?)
+        .add_directive("hyper=error".parse()?)
+        .add_directive("tonic=error".parse()?)
+        .add_directive("reqwest=error".parse()?))
+}
+
+/// Samples new traces by the ratio of their root span name.
+///
+/// The ratios are shared between clones, so they can be updated on reload
+/// without rebuilding the tracer provider.
+#[derive(Clone, Debug)]
+pub struct RatioSampler {
+    ratios: Arc<RwLock<(SampleRatio, SampleOverrides)>>,
+}
+
+impl RatioSampler {
+    pub fn new(config: &TracingConfig) -> Self {
+        let ratios = (config.sample_ratio, config.sample_overrides.clone());
+        RatioSampler {
+            ratios: Arc::new(RwLock::new(ratios)),
+        }
+    }
+
+    pub fn update(&self, config: &TracingConfig) {
+        let mut ratios = self.ratios.write().unwrap_or_else(PoisonError::into_inner);
+        *ratios = (config.sample_ratio, config.sample_overrides.clone());
+    }
+
+    /// The sample ratio for spans named `name`.
+    pub fn ratio(&self, name: &str) -> f64 {
+        let ratios = self.ratios.read().unwrap_or_else(PoisonError::into_inner);
+        let (ratio, overrides) = &*ratios;
+        *overrides.ratio(name).unwrap_or(*ratio)
+    }
+}
+
+impl ShouldSample for RatioSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&opentelemetry::Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        Sampler::TraceIdRatioBased(self.ratio(name)).should_sample(
+            parent_context,
+            trace_id,
+            name,
+            span_kind,
+            attributes,
+            links,
+        )
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -191,6 +375,7 @@ pub struct Config {
     pub environment: Environment,
     pub filter: String,
     pub opentelemetry: OpentelemetryConfig,
+    pub tracing: TracingConfig,
 }
 
 impl Config {
@@ -201,6 +386,14 @@ impl Config {
             Environment::Production => SERVICE_NAME_PRODUCTION,
         }
     }
+
+    /// Where spans are exported, which defaults to the opentelemetry endpoint.
+    pub fn trace_endpoint(&self) -> &Url {
+        self.tracing
+            .endpoint
+            .as_ref()
+            .unwrap_or(&self.opentelemetry.endpoint)
+    }
 }
 
 impl TryFrom<&Provider> for Config {
@@ -218,6 +411,7 @@ impl TryFrom<&Provider> for Config {
             environment,
             filter,
             opentelemetry: provider.try_into()?,
+            tracing: provider.try_into()?,
         })
     }
 }
@@ -258,3 +452,180 @@ impl TryFrom<&Provider> for OpentelemetryConfig {
         })
     }
 }
+
+#[derive(Debug, Display, Error)]
+pub enum TracingError {
+    /// Failed to parse {TRACING_ENDPOINT_ENTRY:?}: {0}
+    ParseEndpoint(provider::Error),
+    /// Failed to parse {TRACING_SAMPLE_OVERRIDES_ENTRY:?}: {0}
+    ParseSampleOverrides(provider::Error),
+    /// Failed to parse {TRACING_SAMPLE_RATIO_ENTRY:?}: {0}
+    ParseSampleRatio(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TracingConfig {
+    pub endpoint: Option<Url>,
+    pub sample_ratio: SampleRatio,
+    #[serde(default)]
+    pub sample_overrides: SampleOverrides,
+}
+
+impl TryFrom<&Provider> for TracingConfig {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let endpoint = provider
+            .maybe_read(TRACING_ENDPOINT_VAR, TRACING_ENDPOINT_ENTRY)
+            .map_err(TracingError::ParseEndpoint)?;
+        let sample_ratio = provider
+            .read_or(
+                TRACING_SAMPLE_RATIO_DEFAULT,
+                TRACING_SAMPLE_RATIO_VAR,
+                TRACING_SAMPLE_RATIO_ENTRY,
+            )
+            .map_err(TracingError::ParseSampleRatio)?;
+        let sample_overrides = provider
+            .read_or_default(TRACING_SAMPLE_OVERRIDES_VAR, TRACING_SAMPLE_OVERRIDES_ENTRY)
+            .map_err(TracingError::ParseSampleOverrides)?;
+
+        Ok(TracingConfig {
+            endpoint,
+            sample_ratio,
+            sample_overrides,
+        })
+    }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum SampleError {
+    /// Sample override `{0}` is not of the form `prefix=ratio`.
+    Override(String),
+    /// Failed to parse sample ratio: {0}
+    ParseRatio(std::num::ParseFloatError),
+    /// Sample ratio {0} is not between 0 and 1.
+    Ratio(f64),
+}
+
+/// The share of new traces to sample, from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Deref, Deserialize)]
+#[serde(try_from = "f64")]
+pub struct SampleRatio(f64);
+
+impl TryFrom<f64> for SampleRatio {
+    type Error = SampleError;
+
+    fn try_from(ratio: f64) -> Result<Self, Self::Error> {
+        if (0.0..=1.0).contains(&ratio) {
+            Ok(SampleRatio(ratio))
+        } else {
+            Err(SampleError::Ratio(ratio))
+        }
+    }
+}
+
+impl FromStr for SampleRatio {
+    type Err = SampleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim()
+            .parse::<f64>()
+            .map_err(SampleError::ParseRatio)?
+            .try_into()
+    }
+}
+
+/// Sample ratios by span name prefix, parsed from `prefix=ratio,...`.
+///
+/// The longest matching prefix wins, so `blockvisor.v1.NodeService=0.1` can
+/// be narrowed down by `blockvisor.v1.NodeService/Create=1`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SampleOverrides(Vec<(String, SampleRatio)>);
+
+impl SampleOverrides {
+    pub fn ratio(&self, name: &str) -> Option<SampleRatio> {
+        self.0
+            .iter()
+            .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, ratio)| *ratio)
+    }
+}
+
+impl TryFrom<String> for SampleOverrides {
+    type Error = SampleError;
+
+    fn try_from(overrides: String) -> Result<Self, Self::Error> {
+        overrides.parse()
+    }
+}
+
+impl FromStr for SampleOverrides {
+    type Err = SampleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (prefix, ratio) = entry
+                    .split_once('=')
+                    .ok_or_else(|| SampleError::Override(entry.to_string()))?;
+                Ok((prefix.trim().to_string(), ratio.parse()?))
+            })
+            .collect::<Result<_, _>>()
+            .map(SampleOverrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_sample_override_wins() {
+        let overrides: SampleOverrides =
+            "blockvisor.v1.NodeService=0.1, blockvisor.v1.NodeService/Create=1,"
+                .parse()
+                .unwrap();
+
+        let ratio = |name| overrides.ratio(name).map(|ratio| *ratio);
+        assert_eq!(ratio("blockvisor.v1.NodeService/Create"), Some(1.0));
+        assert_eq!(ratio("blockvisor.v1.NodeService/List"), Some(0.1));
+        assert_eq!(ratio("blockvisor.v1.HostService/List"), None);
+
+        assert!(
+            "blockvisor.v1.NodeService"
+                .parse::<SampleOverrides>()
+                .is_err()
+        );
+        assert!(
+            "blockvisor.v1.NodeService=2"
+                .parse::<SampleOverrides>()
+                .is_err()
+        );
+        assert_eq!(
+            "".parse::<SampleOverrides>().unwrap(),
+            SampleOverrides::default()
+        );
+    }
+
+    #[test]
+    fn sampler_ratios_can_be_reloaded() {
+        let config = |sample_overrides: &str| TracingConfig {
+            endpoint: None,
+            sample_ratio: SampleRatio(0.5),
+            sample_overrides: sample_overrides.parse().unwrap(),
+        };
+
+        let sampler = RatioSampler::new(&config(""));
+        let clone = sampler.clone();
+        assert!((clone.ratio("blockvisor.v1.NodeService/List") - 0.5).abs() < f64::EPSILON);
+
+        sampler.update(&config("blockvisor.v1.NodeService=0"));
+        assert!(clone.ratio("blockvisor.v1.NodeService/List").abs() < f64::EPSILON);
+        assert!((clone.ratio("blockvisor.v1.HostService/List") - 0.5).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,75 @@
+use displaydoc::Display;
+use ipnetwork::Ipv4Network;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const NETWORK_VAR: &str = "MESH_NETWORK";
+const NETWORK_ENTRY: &str = "mesh.network";
+const NETWORK_DEFAULT: &str = "10.200.0.0/16";
+const SUBNET_PREFIX_VAR: &str = "MESH_SUBNET_PREFIX";
+const SUBNET_PREFIX_ENTRY: &str = "mesh.subnet_prefix";
+const SUBNET_PREFIX_DEFAULT: u8 = 24;
+const LISTEN_PORT_VAR: &str = "MESH_LISTEN_PORT";
+const LISTEN_PORT_ENTRY: &str = "mesh.listen_port";
+const LISTEN_PORT_DEFAULT: u16 = 51820;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {LISTEN_PORT_ENTRY:?}: {0}
+    ListenPort(provider::Error),
+    /// Failed to parse {NETWORK_ENTRY:?}: {0}
+    Network(provider::Error),
+    /// Failed to parse {SUBNET_PREFIX_ENTRY:?}: {0}
+    SubnetPrefix(provider::Error),
+    /// {SUBNET_PREFIX_ENTRY:?} of {0} must be between the network prefix and 30.
+    SubnetPrefixRange(u8),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The network that the WireGuard subnet of each org is allocated from.
+    pub network: Ipv4Network,
+    /// The prefix length of the subnet allocated to each org.
+    pub subnet_prefix: u8,
+    /// The port that each mesh peer listens on.
+    pub listen_port: u16,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let network = provider
+            .read_or(
+                NETWORK_DEFAULT
+                    .parse::<Ipv4Network>()
+                    .expect("NETWORK_DEFAULT not parseable"),
+                NETWORK_VAR,
+                NETWORK_ENTRY,
+            )
+            .map_err(Error::Network)?;
+        let subnet_prefix = provider
+            .read_or(
+                SUBNET_PREFIX_DEFAULT,
+                SUBNET_PREFIX_VAR,
+                SUBNET_PREFIX_ENTRY,
+            )
+            .map_err(Error::SubnetPrefix)?;
+        let listen_port = provider
+            .read_or(LISTEN_PORT_DEFAULT, LISTEN_PORT_VAR, LISTEN_PORT_ENTRY)
+            .map_err(Error::ListenPort)?;
+
+        if subnet_prefix < network.prefix() || subnet_prefix > 30 {
+            return Err(Error::SubnetPrefixRange(subnet_prefix));
+        }
+
+        Ok(Config {
+            network,
+            subnet_prefix,
+            listen_port,
+        })
+    }
+}
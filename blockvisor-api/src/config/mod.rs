@@ -1,14 +1,29 @@
+pub mod abuse;
+pub mod acme;
+pub mod cloud;
 pub mod cloudflare;
 pub mod database;
 pub mod email;
+pub mod encryption;
+pub mod endpoint;
+pub mod flag;
 pub mod grpc;
 pub mod log;
+pub mod mesh;
 pub mod mqtt;
+pub mod password;
+pub mod push;
+pub mod reconcile;
+pub mod retention;
+pub mod scheduler;
 pub mod secret;
 pub mod server;
+pub mod signup;
+pub mod slo;
 pub mod store;
 pub mod stripe;
 pub mod token;
+pub mod trial;
 
 mod context;
 pub use context::Context;
@@ -34,62 +49,112 @@ const CONFIG_FILE_DEFAULT: &str = "config.toml";
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Failed to parse abuse Config: {0}
+    Abuse(abuse::Error),
+    /// Failed to parse ACME Config: {0}
+    Acme(acme::Error),
     /// Failed to convert to chrono::Duration: {0}
     ChronoDuration(chrono::OutOfRangeError),
+    /// Failed to parse cloud Config: {0}
+    Cloud(cloud::Error),
     /// Failed to parse Cloudflare Config: {0}
     Cloudflare(cloudflare::Error),
     /// Failed to parse database Config: {0}
     Database(database::Error),
     /// Failed to parse email Config: {0}
     Email(email::Error),
+    /// Failed to parse encryption Config: {0}
+    Encryption(encryption::Error),
+    /// Failed to parse endpoint Config: {0}
+    Endpoint(endpoint::Error),
+    /// Failed to parse feature flag Config: {0}
+    Flag(flag::Error),
     /// Failed to parse gRPC Config: {0}
     Grpc(grpc::Error),
     /// Failed to parse HumanTime: {0}
     HumanTime(serde_json::Error),
     /// Failed to parse Log Config: {0}
     Log(log::Error),
+    /// Failed to parse mesh Config: {0}
+    Mesh(mesh::Error),
     /// Failed to parse MQTT Config: {0}
     Mqtt(mqtt::Error),
     /// No config file at path: {0}
     NoConfigFile(String),
+    /// Failed to parse password Config: {0}
+    Password(password::Error),
     /// Failed to create Provider: {0}
     Provider(provider::Error),
+    /// Failed to parse push Config: {0}
+    Push(push::Error),
+    /// Failed to parse reconcile Config: {0}
+    Reconcile(reconcile::Error),
+    /// Failed to parse retention Config: {0}
+    Retention(retention::Error),
     /// Failed to parse Redacted<{0}>: {1}
     Redacted(
         &'static str,
         Box<dyn std::error::Error + Send + Sync + 'static>,
     ),
+    /// Failed to parse scheduler Config: {0}
+    Scheduler(scheduler::Error),
     /// Failed to parse secret Config: {0}
     Secret(secret::Error),
     /// Failed to parse server Config: {0}
     Server(server::Error),
+    /// Failed to parse signup Config: {0}
+    Signup(signup::Error),
+    /// Failed to parse SLO Config: {0}
+    Slo(slo::Error),
     /// Failed to parse store Config: {0}
     Store(store::Error),
     /// Failed to parse stripe Config: {0}
     Stripe(stripe::Error),
     /// Failed to parse token Config: {0}
     Token(token::Error),
+    /// Failed to parse trial Config: {0}
+    Trial(trial::Error),
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
+    pub abuse: Arc<abuse::Config>,
+    pub acme: Arc<acme::Config>,
+    pub cloud: Arc<cloud::Config>,
     pub cloudflare: Arc<cloudflare::Config>,
     pub database: Arc<database::Config>,
     pub email: Arc<email::Config>,
+    pub encryption: Arc<encryption::Config>,
+    pub endpoint: Arc<endpoint::Config>,
+    pub flag: Arc<flag::Config>,
     pub grpc: Arc<grpc::Config>,
     pub log: Arc<log::Config>,
+    pub mesh: Arc<mesh::Config>,
     pub mqtt: Arc<mqtt::Config>,
+    pub password: Arc<password::Config>,
+    pub push: Arc<push::Config>,
+    pub reconcile: Arc<reconcile::Config>,
+    pub retention: Arc<retention::Config>,
+    pub scheduler: Arc<scheduler::Config>,
     pub secret: Arc<secret::Config>,
     pub server: Arc<server::Config>,
+    pub signup: Arc<signup::Config>,
+    pub slo: Arc<slo::Config>,
     pub store: Arc<store::Config>,
     pub stripe: Arc<stripe::Config>,
     pub token: Arc<token::Config>,
+    pub trial: Arc<trial::Config>,
 }
 
 impl Config {
     pub fn new() -> Result<Self, Error> {
-        let provider = if let Ok(file) = env::var(CONFIG_FILE_ENV) {
+        TryInto::try_into(&Self::provider()?)
+    }
+
+    /// The provider for the config file at `CONFIG_FILE`, or `config.toml`.
+    pub fn provider() -> Result<Provider, Error> {
+        if let Ok(file) = env::var(CONFIG_FILE_ENV) {
             let path = Path::new(&file);
             if path.exists() {
                 Provider::new(Some(path)).map_err(Error::Provider)
@@ -100,9 +165,7 @@ impl Config {
             let path = Path::new(CONFIG_FILE_DEFAULT);
             let toml = if path.exists() { Some(path) } else { None };
             Provider::new(toml).map_err(Error::Provider)
-        }?;
-
-        TryInto::try_into(&provider)
+        }
     }
 
     pub fn from_toml<P: AsRef<std::path::Path>>(toml: P) -> Result<Self, Error> {
@@ -120,6 +183,15 @@ impl TryFrom<&Provider> for Config {
     type Error = Error;
 
     fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let abuse = abuse::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Abuse)?;
+        let acme = acme::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Acme)?;
+        let cloud = cloud::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Cloud)?;
         let cloudflare = cloudflare::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Cloudflare)?;
@@ -129,21 +201,54 @@ impl TryFrom<&Provider> for Config {
         let email = email::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Email)?;
+        let encryption = encryption::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Encryption)?;
+        let endpoint = endpoint::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Endpoint)?;
+        let flag = flag::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Flag)?;
         let grpc = grpc::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Grpc)?;
         let log = log::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Log)?;
+        let mesh = mesh::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Mesh)?;
         let mqtt = mqtt::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Mqtt)?;
+        let password = password::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Password)?;
+        let push = push::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Push)?;
+        let reconcile = reconcile::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Reconcile)?;
+        let retention = retention::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Retention)?;
+        let scheduler = scheduler::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Scheduler)?;
         let secret = secret::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Secret)?;
         let server = server::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Server)?;
+        let signup = signup::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Signup)?;
+        let slo = slo::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Slo)?;
         let store = store::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Store)?;
@@ -153,19 +258,37 @@ impl TryFrom<&Provider> for Config {
         let token = token::Config::try_from(provider)
             .map(Arc::new)
             .map_err(Error::Token)?;
+        let trial = trial::Config::try_from(provider)
+            .map(Arc::new)
+            .map_err(Error::Trial)?;
 
         Ok(Config {
+            abuse,
+            acme,
+            cloud,
             cloudflare,
             database,
             email,
+            encryption,
+            endpoint,
+            flag,
             grpc,
             log,
+            mesh,
             mqtt,
+            password,
+            push,
+            reconcile,
+            retention,
+            scheduler,
             secret,
             server,
+            signup,
+            slo,
             store,
             stripe,
             token,
+            trial,
         })
     }
 }
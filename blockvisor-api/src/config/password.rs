@@ -0,0 +1,75 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const MIN_LENGTH_VAR: &str = "PASSWORD_MIN_LENGTH";
+const MIN_LENGTH_ENTRY: &str = "password.min_length";
+const MIN_LENGTH_DEFAULT: usize = 8;
+const MIN_SCORE_VAR: &str = "PASSWORD_MIN_SCORE";
+const MIN_SCORE_ENTRY: &str = "password.min_score";
+const MIN_SCORE_DEFAULT: u8 = 3;
+const BREACH_CHECK_VAR: &str = "PASSWORD_BREACH_CHECK";
+const BREACH_CHECK_ENTRY: &str = "password.breach_check";
+const BREACH_CHECK_DEFAULT: bool = false;
+const BREACH_URL_VAR: &str = "PASSWORD_BREACH_URL";
+const BREACH_URL_ENTRY: &str = "password.breach_url";
+const BREACH_URL_DEFAULT: &str = "https://api.pwnedpasswords.com";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {BREACH_CHECK_ENTRY:?}: {0}
+    BreachCheck(provider::Error),
+    /// Failed to parse {BREACH_URL_ENTRY:?}: {0}
+    BreachUrl(provider::Error),
+    /// Failed to parse {MIN_LENGTH_ENTRY:?}: {0}
+    MinLength(provider::Error),
+    /// Failed to parse {MIN_SCORE_ENTRY:?}: {0}
+    MinScore(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The minimum number of characters in a password.
+    pub min_length: usize,
+    /// The minimum zxcvbn strength score (from 0 to 4) of a password.
+    pub min_score: u8,
+    /// Whether to check new passwords against the HaveIBeenPwned database.
+    pub breach_check: bool,
+    /// The base url of the HaveIBeenPwned range api.
+    pub breach_url: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            min_length: MIN_LENGTH_DEFAULT,
+            min_score: MIN_SCORE_DEFAULT,
+            breach_check: BREACH_CHECK_DEFAULT,
+            breach_url: BREACH_URL_DEFAULT.to_string(),
+        }
+    }
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            min_length: provider
+                .read_or(MIN_LENGTH_DEFAULT, MIN_LENGTH_VAR, MIN_LENGTH_ENTRY)
+                .map_err(Error::MinLength)?,
+            min_score: provider
+                .read_or(MIN_SCORE_DEFAULT, MIN_SCORE_VAR, MIN_SCORE_ENTRY)
+                .map_err(Error::MinScore)?,
+            breach_check: provider
+                .read_or(BREACH_CHECK_DEFAULT, BREACH_CHECK_VAR, BREACH_CHECK_ENTRY)
+                .map_err(Error::BreachCheck)?,
+            breach_url: provider
+                .read_or(BREACH_URL_DEFAULT, BREACH_URL_VAR, BREACH_URL_ENTRY)
+                .map_err(Error::BreachUrl)?,
+        })
+    }
+}
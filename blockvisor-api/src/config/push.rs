@@ -0,0 +1,138 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+use url::Url;
+
+use super::Redacted;
+use super::provider::{self, Provider};
+
+const FCM_PROJECT_ID_VAR: &str = "PUSH_FCM_PROJECT_ID";
+const FCM_PROJECT_ID_ENTRY: &str = "push.fcm.project_id";
+const FCM_CLIENT_EMAIL_VAR: &str = "PUSH_FCM_CLIENT_EMAIL";
+const FCM_CLIENT_EMAIL_ENTRY: &str = "push.fcm.client_email";
+const FCM_PRIVATE_KEY_VAR: &str = "PUSH_FCM_PRIVATE_KEY";
+const FCM_PRIVATE_KEY_ENTRY: &str = "push.fcm.private_key";
+const APNS_KEY_ID_VAR: &str = "PUSH_APNS_KEY_ID";
+const APNS_KEY_ID_ENTRY: &str = "push.apns.key_id";
+const APNS_TEAM_ID_VAR: &str = "PUSH_APNS_TEAM_ID";
+const APNS_TEAM_ID_ENTRY: &str = "push.apns.team_id";
+const APNS_PRIVATE_KEY_VAR: &str = "PUSH_APNS_PRIVATE_KEY";
+const APNS_PRIVATE_KEY_ENTRY: &str = "push.apns.private_key";
+const APNS_TOPIC_VAR: &str = "PUSH_APNS_TOPIC";
+const APNS_TOPIC_ENTRY: &str = "push.apns.topic";
+const APNS_URL_VAR: &str = "PUSH_APNS_URL";
+const APNS_URL_ENTRY: &str = "push.apns.url";
+const APNS_URL_DEFAULT: &str = "https://api.push.apple.com";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {APNS_KEY_ID_ENTRY:?}: {0}
+    ApnsKeyId(provider::Error),
+    /// Failed to parse {APNS_PRIVATE_KEY_ENTRY:?}: {0}
+    ApnsPrivateKey(provider::Error),
+    /// Failed to parse {APNS_TEAM_ID_ENTRY:?}: {0}
+    ApnsTeamId(provider::Error),
+    /// Failed to parse {APNS_TOPIC_ENTRY:?}: {0}
+    ApnsTopic(provider::Error),
+    /// Failed to parse {APNS_URL_ENTRY:?}: {0}
+    ApnsUrl(provider::Error),
+    /// Failed to parse {FCM_CLIENT_EMAIL_ENTRY:?}: {0}
+    FcmClientEmail(provider::Error),
+    /// Failed to parse {FCM_PRIVATE_KEY_ENTRY:?}: {0}
+    FcmPrivateKey(provider::Error),
+    /// Failed to parse {FCM_PROJECT_ID_ENTRY:?}: {0}
+    FcmProjectId(provider::Error),
+    /// Push provider `{0}` is only partially configured.
+    Partial(&'static str),
+}
+
+/// Credentials of the push providers, each of which is optional.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub fcm: Option<FcmConfig>,
+    pub apns: Option<ApnsConfig>,
+}
+
+/// The Firebase service account used to send to Android devices.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FcmConfig {
+    pub project_id: String,
+    pub client_email: String,
+    /// The PEM encoded RSA key of the service account.
+    pub private_key: Redacted<String>,
+}
+
+/// The token based APNs key used to send to iOS devices.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApnsConfig {
+    pub key_id: String,
+    pub team_id: String,
+    /// The PEM encoded `.p8` signing key.
+    pub private_key: Redacted<String>,
+    /// The bundle id of the app.
+    pub topic: String,
+    pub url: Url,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        let project_id: Option<String> = provider
+            .maybe_read(FCM_PROJECT_ID_VAR, FCM_PROJECT_ID_ENTRY)
+            .map_err(Error::FcmProjectId)?;
+        let client_email: Option<String> = provider
+            .maybe_read(FCM_CLIENT_EMAIL_VAR, FCM_CLIENT_EMAIL_ENTRY)
+            .map_err(Error::FcmClientEmail)?;
+        let fcm_key: Option<Redacted<String>> = provider
+            .maybe_read(FCM_PRIVATE_KEY_VAR, FCM_PRIVATE_KEY_ENTRY)
+            .map_err(Error::FcmPrivateKey)?;
+
+        let fcm = match (project_id, client_email, fcm_key) {
+            (Some(project_id), Some(client_email), Some(private_key)) => Some(FcmConfig {
+                project_id,
+                client_email,
+                private_key,
+            }),
+            (None, None, None) => None,
+            _ => return Err(Error::Partial("fcm")),
+        };
+
+        let key_id: Option<String> = provider
+            .maybe_read(APNS_KEY_ID_VAR, APNS_KEY_ID_ENTRY)
+            .map_err(Error::ApnsKeyId)?;
+        let team_id: Option<String> = provider
+            .maybe_read(APNS_TEAM_ID_VAR, APNS_TEAM_ID_ENTRY)
+            .map_err(Error::ApnsTeamId)?;
+        let apns_key: Option<Redacted<String>> = provider
+            .maybe_read(APNS_PRIVATE_KEY_VAR, APNS_PRIVATE_KEY_ENTRY)
+            .map_err(Error::ApnsPrivateKey)?;
+        let topic: Option<String> = provider
+            .maybe_read(APNS_TOPIC_VAR, APNS_TOPIC_ENTRY)
+            .map_err(Error::ApnsTopic)?;
+        let url = provider
+            .read_or(
+                Url::parse(APNS_URL_DEFAULT).expect("APNS_URL_DEFAULT not parseable"),
+                APNS_URL_VAR,
+                APNS_URL_ENTRY,
+            )
+            .map_err(Error::ApnsUrl)?;
+
+        let apns = match (key_id, team_id, apns_key, topic) {
+            (Some(key_id), Some(team_id), Some(private_key), Some(topic)) => Some(ApnsConfig {
+                key_id,
+                team_id,
+                private_key,
+                topic,
+                url,
+            }),
+            (None, None, None, None) => None,
+            _ => return Err(Error::Partial("apns")),
+        };
+
+        Ok(Config { fcm, apns })
+    }
+}
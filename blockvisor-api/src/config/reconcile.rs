@@ -0,0 +1,38 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const DELETE_ORPHANS_VAR: &str = "RECONCILE_DELETE_ORPHANS";
+const DELETE_ORPHANS_ENTRY: &str = "reconcile.delete_orphans";
+const DELETE_ORPHANS_DEFAULT: bool = false;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {DELETE_ORPHANS_ENTRY:?}: {0}
+    DeleteOrphans(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Whether the reconciler deletes the orphans it finds, or only reports them.
+    pub delete_orphans: bool,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            delete_orphans: provider
+                .read_or(
+                    DELETE_ORPHANS_DEFAULT,
+                    DELETE_ORPHANS_VAR,
+                    DELETE_ORPHANS_ENTRY,
+                )
+                .map_err(Error::DeleteOrphans)?,
+        })
+    }
+}
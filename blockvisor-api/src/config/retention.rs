@@ -0,0 +1,95 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const NODE_LOGS_DAYS_VAR: &str = "RETENTION_NODE_LOGS_DAYS";
+const NODE_LOGS_DAYS_ENTRY: &str = "retention.node_logs_days";
+const NODE_LOGS_DAYS_DEFAULT: u32 = 365;
+const COMMANDS_DAYS_VAR: &str = "RETENTION_COMMANDS_DAYS";
+const COMMANDS_DAYS_ENTRY: &str = "retention.commands_days";
+const COMMANDS_DAYS_DEFAULT: u32 = 90;
+const NODE_HEALTH_DAYS_VAR: &str = "RETENTION_NODE_HEALTH_DAYS";
+const NODE_HEALTH_DAYS_ENTRY: &str = "retention.node_health_days";
+const NODE_HEALTH_DAYS_DEFAULT: u32 = 400;
+const AUDIT_LOGS_DAYS_VAR: &str = "RETENTION_AUDIT_LOGS_DAYS";
+const AUDIT_LOGS_DAYS_ENTRY: &str = "retention.audit_logs_days";
+const AUDIT_LOGS_DAYS_DEFAULT: u32 = 730;
+const ARTIFACTS_DAYS_VAR: &str = "RETENTION_ARTIFACTS_DAYS";
+const ARTIFACTS_DAYS_ENTRY: &str = "retention.artifacts_days";
+const ARTIFACTS_DAYS_DEFAULT: u32 = 30;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {ARTIFACTS_DAYS_ENTRY:?}: {0}
+    ArtifactsDays(provider::Error),
+    /// Failed to parse {AUDIT_LOGS_DAYS_ENTRY:?}: {0}
+    AuditLogsDays(provider::Error),
+    /// Failed to parse {COMMANDS_DAYS_ENTRY:?}: {0}
+    CommandsDays(provider::Error),
+    /// Failed to parse {NODE_HEALTH_DAYS_ENTRY:?}: {0}
+    NodeHealthDays(provider::Error),
+    /// Failed to parse {NODE_LOGS_DAYS_ENTRY:?}: {0}
+    NodeLogsDays(provider::Error),
+}
+
+/// How many days rows are kept in each table that grows without bound.
+///
+/// Partitioned tables drop a whole month once its last day is older than the
+/// retention, so rows may be kept for up to a month longer.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub node_logs_days: u32,
+    pub commands_days: u32,
+    /// The hourly health history that uptime and SLO reports are built from.
+    pub node_health_days: u32,
+    pub audit_logs_days: u32,
+    /// The command artifacts uploaded by hosts, such as logs and debug bundles.
+    pub artifacts_days: u32,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            node_logs_days: provider
+                .read_or(
+                    NODE_LOGS_DAYS_DEFAULT,
+                    NODE_LOGS_DAYS_VAR,
+                    NODE_LOGS_DAYS_ENTRY,
+                )
+                .map_err(Error::NodeLogsDays)?,
+            commands_days: provider
+                .read_or(
+                    COMMANDS_DAYS_DEFAULT,
+                    COMMANDS_DAYS_VAR,
+                    COMMANDS_DAYS_ENTRY,
+                )
+                .map_err(Error::CommandsDays)?,
+            node_health_days: provider
+                .read_or(
+                    NODE_HEALTH_DAYS_DEFAULT,
+                    NODE_HEALTH_DAYS_VAR,
+                    NODE_HEALTH_DAYS_ENTRY,
+                )
+                .map_err(Error::NodeHealthDays)?,
+            audit_logs_days: provider
+                .read_or(
+                    AUDIT_LOGS_DAYS_DEFAULT,
+                    AUDIT_LOGS_DAYS_VAR,
+                    AUDIT_LOGS_DAYS_ENTRY,
+                )
+                .map_err(Error::AuditLogsDays)?,
+            artifacts_days: provider
+                .read_or(
+                    ARTIFACTS_DAYS_DEFAULT,
+                    ARTIFACTS_DAYS_VAR,
+                    ARTIFACTS_DAYS_ENTRY,
+                )
+                .map_err(Error::ArtifactsDays)?,
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const HEADROOM_WEIGHT_VAR: &str = "SCHEDULER_HEADROOM_WEIGHT";
+const HEADROOM_WEIGHT_ENTRY: &str = "scheduler.headroom_weight";
+const HEADROOM_WEIGHT_DEFAULT: f64 = 1.0;
+const SIMILARITY_WEIGHT_VAR: &str = "SCHEDULER_SIMILARITY_WEIGHT";
+const SIMILARITY_WEIGHT_ENTRY: &str = "scheduler.similarity_weight";
+const SIMILARITY_WEIGHT_DEFAULT: f64 = 1.0;
+const AGE_WEIGHT_VAR: &str = "SCHEDULER_AGE_WEIGHT";
+const AGE_WEIGHT_ENTRY: &str = "scheduler.age_weight";
+const AGE_WEIGHT_DEFAULT: f64 = 0.0;
+const COST_WEIGHT_VAR: &str = "SCHEDULER_COST_WEIGHT";
+const COST_WEIGHT_ENTRY: &str = "scheduler.cost_weight";
+const COST_WEIGHT_DEFAULT: f64 = 0.0;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {AGE_WEIGHT_ENTRY:?}: {0}
+    AgeWeight(provider::Error),
+    /// Failed to parse {COST_WEIGHT_ENTRY:?}: {0}
+    CostWeight(provider::Error),
+    /// Failed to parse {HEADROOM_WEIGHT_ENTRY:?}: {0}
+    HeadroomWeight(provider::Error),
+    /// Failed to parse {SIMILARITY_WEIGHT_ENTRY:?}: {0}
+    SimilarityWeight(provider::Error),
+}
+
+/// The default weights for scoring candidate hosts, until overridden at runtime.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The weight of the free resource fraction of a host.
+    pub headroom_weight: f64,
+    /// The weight of the count of nodes of the same protocol on a host.
+    pub similarity_weight: f64,
+    /// The weight of the age of a host in days.
+    pub age_weight: f64,
+    /// The weight of the monthly cost of a host.
+    pub cost_weight: f64,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            headroom_weight: provider
+                .read_or(
+                    HEADROOM_WEIGHT_DEFAULT,
+                    HEADROOM_WEIGHT_VAR,
+                    HEADROOM_WEIGHT_ENTRY,
+                )
+                .map_err(Error::HeadroomWeight)?,
+            similarity_weight: provider
+                .read_or(
+                    SIMILARITY_WEIGHT_DEFAULT,
+                    SIMILARITY_WEIGHT_VAR,
+                    SIMILARITY_WEIGHT_ENTRY,
+                )
+                .map_err(Error::SimilarityWeight)?,
+            age_weight: provider
+                .read_or(AGE_WEIGHT_DEFAULT, AGE_WEIGHT_VAR, AGE_WEIGHT_ENTRY)
+                .map_err(Error::AgeWeight)?,
+            cost_weight: provider
+                .read_or(COST_WEIGHT_DEFAULT, COST_WEIGHT_VAR, COST_WEIGHT_ENTRY)
+                .map_err(Error::CostWeight)?,
+        })
+    }
+}
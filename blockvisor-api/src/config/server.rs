@@ -4,6 +4,7 @@ use displaydoc::Display;
 use serde::Deserialize;
 use thiserror::Error;
 
+use super::HumanTime;
 use super::provider::{self, Provider};
 
 const IP_VAR: &str = "BIND_IP";
@@ -12,9 +13,19 @@ const IP_DEFAULT: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
 const PORT_VAR: &str = "PORT";
 const PORT_ENTRY: &str = "server.port";
 const PORT_DEFAULT: u16 = 8080;
+const DRAIN_TIMEOUT_VAR: &str = "SERVER_DRAIN_TIMEOUT";
+const DRAIN_TIMEOUT_ENTRY: &str = "server.drain_timeout";
+const DRAIN_TIMEOUT_DEFAULT: &str = "30s";
+const DEREGISTRATION_DELAY_VAR: &str = "SERVER_DEREGISTRATION_DELAY";
+const DEREGISTRATION_DELAY_ENTRY: &str = "server.deregistration_delay";
+const DEREGISTRATION_DELAY_DEFAULT: &str = "10s";
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Failed to parse {DEREGISTRATION_DELAY_ENTRY:?}: {0}
+    DeregistrationDelay(provider::Error),
+    /// Failed to parse {DRAIN_TIMEOUT_ENTRY:?}: {0}
+    DrainTimeout(provider::Error),
     /// Failed to parse {IP_ENTRY:?}: {0}
     Ip(provider::Error),
     /// Failed to parse {PORT_ENTRY:?}: {0}
@@ -26,6 +37,11 @@ pub enum Error {
 pub struct Config {
     pub ip: IpAddr,
     pub port: u16,
+    /// How long to wait for in-flight requests to finish on shutdown.
+    pub drain_timeout: HumanTime,
+    /// How long to keep accepting requests after `/health` starts failing on
+    /// shutdown, so that load balancers stop routing to the server first.
+    pub deregistration_delay: HumanTime,
 }
 
 impl Config {
@@ -44,7 +60,26 @@ impl TryFrom<&Provider> for Config {
         let port = provider
             .read_or(PORT_DEFAULT, PORT_VAR, PORT_ENTRY)
             .map_err(Error::Port)?;
+        let drain_timeout = provider
+            .read_or_else(
+                || DRAIN_TIMEOUT_DEFAULT.parse::<HumanTime>(),
+                DRAIN_TIMEOUT_VAR,
+                DRAIN_TIMEOUT_ENTRY,
+            )
+            .map_err(Error::DrainTimeout)?;
+        let deregistration_delay = provider
+            .read_or_else(
+                || DEREGISTRATION_DELAY_DEFAULT.parse::<HumanTime>(),
+                DEREGISTRATION_DELAY_VAR,
+                DEREGISTRATION_DELAY_ENTRY,
+            )
+            .map_err(Error::DeregistrationDelay)?;
 
-        Ok(Config { ip, port })
+        Ok(Config {
+            ip,
+            port,
+            drain_timeout,
+            deregistration_delay,
+        })
     }
 }
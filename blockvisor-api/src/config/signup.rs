@@ -0,0 +1,175 @@
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use derive_more::Deref;
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+use super::{HumanTime, Redacted};
+
+const CAPTCHA_PROVIDER_VAR: &str = "SIGNUP_CAPTCHA_PROVIDER";
+const CAPTCHA_PROVIDER_ENTRY: &str = "signup.captcha_provider";
+const CAPTCHA_SECRET_VAR: &str = "SIGNUP_CAPTCHA_SECRET";
+const CAPTCHA_SECRET_ENTRY: &str = "signup.captcha_secret";
+const BLOCKED_DOMAINS_VAR: &str = "SIGNUP_BLOCKED_DOMAINS";
+const BLOCKED_DOMAINS_ENTRY: &str = "signup.blocked_domains";
+const MX_CHECK_VAR: &str = "SIGNUP_MX_CHECK";
+const MX_CHECK_ENTRY: &str = "signup.mx_check";
+const MX_CHECK_DEFAULT: bool = false;
+const DNS_URL_VAR: &str = "SIGNUP_DNS_URL";
+const DNS_URL_ENTRY: &str = "signup.dns_url";
+const DNS_URL_DEFAULT: &str = "https://cloudflare-dns.com/dns-query";
+const MAX_PER_IP_VAR: &str = "SIGNUP_MAX_PER_IP";
+const MAX_PER_IP_ENTRY: &str = "signup.max_per_ip";
+const MAX_PER_IP_DEFAULT: u32 = 5;
+const IP_WINDOW_VAR: &str = "SIGNUP_IP_WINDOW";
+const IP_WINDOW_ENTRY: &str = "signup.ip_window";
+const IP_WINDOW_DEFAULT: &str = "1h";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {BLOCKED_DOMAINS_ENTRY:?}: {0}
+    BlockedDomains(provider::Error),
+    /// Failed to parse {CAPTCHA_PROVIDER_ENTRY:?}: {0}
+    CaptchaProvider(provider::Error),
+    /// Failed to parse {CAPTCHA_SECRET_ENTRY:?}: {0}
+    CaptchaSecret(provider::Error),
+    /// Failed to parse {DNS_URL_ENTRY:?}: {0}
+    DnsUrl(provider::Error),
+    /// Failed to parse {IP_WINDOW_ENTRY:?}: {0}
+    IpWindow(provider::Error),
+    /// Failed to parse {MAX_PER_IP_ENTRY:?}: {0}
+    MaxPerIp(provider::Error),
+    /// Failed to parse {MX_CHECK_ENTRY:?}: {0}
+    MxCheck(provider::Error),
+    /// Unknown captcha provider `{0}`.
+    UnknownCaptcha(String),
+}
+
+/// Abuse checks of public sign-ups through `UserService.Create`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The service verifying captcha tokens, if sign-ups need a captcha.
+    pub captcha_provider: Option<CaptchaProvider>,
+    /// The secret key of the `captcha_provider`.
+    pub captcha_secret: Option<Redacted<String>>,
+    /// Email domains that may not sign up, such as disposable email services.
+    #[serde(default)]
+    pub blocked_domains: Domains,
+    /// Whether the email domain of a new user must have an MX record.
+    pub mx_check: bool,
+    /// The DNS-over-HTTPS JSON api used to look up MX records.
+    pub dns_url: String,
+    /// The most sign-ups from one IP address within `ip_window`, or 0 for no
+    /// limit.
+    pub max_per_ip: u32,
+    pub ip_window: HumanTime,
+}
+
+impl Config {
+    /// The captcha provider and secret, if sign-ups need a captcha.
+    pub fn captcha(&self) -> Option<(CaptchaProvider, &str)> {
+        self.captcha_provider
+            .zip(self.captcha_secret.as_ref())
+            .map(|(provider, secret)| (provider, secret.as_str()))
+    }
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            captcha_provider: provider
+                .maybe_read(CAPTCHA_PROVIDER_VAR, CAPTCHA_PROVIDER_ENTRY)
+                .map_err(Error::CaptchaProvider)?,
+            captcha_secret: provider
+                .maybe_read(CAPTCHA_SECRET_VAR, CAPTCHA_SECRET_ENTRY)
+                .map_err(Error::CaptchaSecret)?,
+            blocked_domains: provider
+                .read_or_default(BLOCKED_DOMAINS_VAR, BLOCKED_DOMAINS_ENTRY)
+                .map_err(Error::BlockedDomains)?,
+            mx_check: provider
+                .read_or(MX_CHECK_DEFAULT, MX_CHECK_VAR, MX_CHECK_ENTRY)
+                .map_err(Error::MxCheck)?,
+            dns_url: provider
+                .read_or(DNS_URL_DEFAULT, DNS_URL_VAR, DNS_URL_ENTRY)
+                .map_err(Error::DnsUrl)?,
+            max_per_ip: provider
+                .read_or(MAX_PER_IP_DEFAULT, MAX_PER_IP_VAR, MAX_PER_IP_ENTRY)
+                .map_err(Error::MaxPerIp)?,
+            ip_window: provider
+                .read_or_else(
+                    || IP_WINDOW_DEFAULT.parse::<HumanTime>(),
+                    IP_WINDOW_VAR,
+                    IP_WINDOW_ENTRY,
+                )
+                .map_err(Error::IpWindow)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptchaProvider {
+    Hcaptcha,
+    Turnstile,
+}
+
+impl CaptchaProvider {
+    /// The url that captcha tokens are verified against.
+    pub const fn verify_url(self) -> &'static str {
+        match self {
+            CaptchaProvider::Hcaptcha => "https://api.hcaptcha.com/siteverify",
+            CaptchaProvider::Turnstile => {
+                "https://challenges.cloudflare.com/turnstile/v0/siteverify"
+            }
+        }
+    }
+}
+
+impl FromStr for CaptchaProvider {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "hcaptcha" => Ok(CaptchaProvider::Hcaptcha),
+            "turnstile" => Ok(CaptchaProvider::Turnstile),
+            _ => Err(Error::UnknownCaptcha(s.to_string())),
+        }
+    }
+}
+
+/// A comma separated list of lowercase domains.
+#[derive(Debug, Default, Deref, Deserialize)]
+#[deref(forward)]
+pub struct Domains(Vec<String>);
+
+impl Domains {
+    /// Whether `domain` is one of these domains or a subdomain of one.
+    pub fn matches(&self, domain: &str) -> bool {
+        self.iter().any(|blocked| {
+            domain == blocked
+                || domain
+                    .strip_suffix(blocked.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+impl FromStr for Domains {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let domains = s
+            .split(',')
+            .map(str::trim)
+            .filter(|domain| !domain.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Ok(Self(domains))
+    }
+}
@@ -0,0 +1,38 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const TARGET_PERCENT_VAR: &str = "SLO_TARGET_PERCENT";
+const TARGET_PERCENT_ENTRY: &str = "slo.target_percent";
+const TARGET_PERCENT_DEFAULT: f64 = 99.9;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {TARGET_PERCENT_ENTRY:?}: {0}
+    TargetPercent(provider::Error),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The monthly uptime percentage that each node is expected to meet.
+    pub target_percent: f64,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            target_percent: provider
+                .read_or(
+                    TARGET_PERCENT_DEFAULT,
+                    TARGET_PERCENT_VAR,
+                    TARGET_PERCENT_ENTRY,
+                )
+                .map_err(Error::TargetPercent)?,
+        })
+    }
+}
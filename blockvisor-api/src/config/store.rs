@@ -21,8 +21,14 @@ const STORE_URL_ENTRY: &str = "store.url";
 
 const ARCHIVE_BUCKET_VAR: &str = "ARCHIVE_BUCKET";
 const ARCHIVE_BUCKET_ENTRY: &str = "store.bucket.archive";
+const ARTIFACT_BUCKET_VAR: &str = "ARTIFACT_BUCKET";
+const ARTIFACT_BUCKET_ENTRY: &str = "store.bucket.artifact";
+const BRANDING_BUCKET_VAR: &str = "BRANDING_BUCKET";
+const BRANDING_BUCKET_ENTRY: &str = "store.bucket.branding";
 const BUNDLE_BUCKET_VAR: &str = "BUNDLE_BUCKET";
 const BUNDLE_BUCKET_ENTRY: &str = "store.bucket.bundle";
+const EXPORT_BUCKET_VAR: &str = "EXPORT_BUCKET";
+const EXPORT_BUCKET_ENTRY: &str = "store.bucket.export";
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -86,15 +92,24 @@ impl TryFrom<&Provider> for Config {
 pub enum BucketError {
     /// Failed to read {ARCHIVE_BUCKET_VAR:?}: {0}
     ReadArchive(provider::Error),
+    /// Failed to read {ARTIFACT_BUCKET_VAR:?}: {0}
+    ReadArtifact(provider::Error),
+    /// Failed to read {BRANDING_BUCKET_VAR:?}: {0}
+    ReadBranding(provider::Error),
     /// Failed to read {BUNDLE_BUCKET_VAR:?}: {0}
     ReadBundle(provider::Error),
+    /// Failed to read {EXPORT_BUCKET_VAR:?}: {0}
+    ReadExport(provider::Error),
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct BucketConfig {
     pub archive: String,
+    pub artifact: String,
+    pub branding: String,
     pub bundle: String,
+    pub export: String,
 }
 
 impl TryFrom<&Provider> for BucketConfig {
@@ -105,9 +120,18 @@ impl TryFrom<&Provider> for BucketConfig {
             archive: provider
                 .read(ARCHIVE_BUCKET_VAR, ARCHIVE_BUCKET_ENTRY)
                 .map_err(BucketError::ReadArchive)?,
+            artifact: provider
+                .read(ARTIFACT_BUCKET_VAR, ARTIFACT_BUCKET_ENTRY)
+                .map_err(BucketError::ReadArtifact)?,
+            branding: provider
+                .read(BRANDING_BUCKET_VAR, BRANDING_BUCKET_ENTRY)
+                .map_err(BucketError::ReadBranding)?,
             bundle: provider
                 .read(BUNDLE_BUCKET_VAR, BUNDLE_BUCKET_ENTRY)
                 .map_err(BucketError::ReadBundle)?,
+            export: provider
+                .read(EXPORT_BUCKET_VAR, EXPORT_BUCKET_ENTRY)
+                .map_err(BucketError::ReadExport)?,
         })
     }
 }
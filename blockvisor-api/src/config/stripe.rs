@@ -12,12 +12,22 @@ const STRIPE_URL_VAR: &str = "STRIPE_URL";
 const STRIPE_URL_ENTRY: &str = "stripe.url";
 const STRIPE_URL_DEFAULT: &str = "https://api.stripe.com/v1";
 
+const STRIPE_DISK_SKU_VAR: &str = "STRIPE_DISK_SKU";
+const STRIPE_DISK_SKU_ENTRY: &str = "stripe.disk_sku";
+
+const STRIPE_WEBHOOK_SECRET_VAR: &str = "STRIPE_WEBHOOK_SECRET";
+const STRIPE_WEBHOOK_SECRET_ENTRY: &str = "stripe.webhook_secret";
+
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Failed to read {STRIPE_DISK_SKU_VAR:?}: {0}
+    ReadDiskSku(provider::Error),
     /// Failed to read {STRIPE_SECRET_VAR:?}: {0}
     ReadSecret(provider::Error),
     /// Failed to read {STRIPE_URL_VAR:?}: {0}
     ReadUrl(provider::Error),
+    /// Failed to read {STRIPE_WEBHOOK_SECRET_VAR:?}: {0}
+    ReadWebhookSecret(provider::Error),
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,6 +35,11 @@ pub enum Error {
 pub struct Config {
     pub secret: Option<Redacted<String>>,
     pub base_url: String,
+    /// The stripe sku of the metered price for disk added by node resizes.
+    pub disk_sku: Option<String>,
+    /// The signing secret of the webhook endpoint, without which webhook
+    /// events are rejected.
+    pub webhook_secret: Option<Redacted<String>>,
 }
 
 impl TryFrom<&provider::Provider> for Config {
@@ -38,6 +53,12 @@ impl TryFrom<&provider::Provider> for Config {
             base_url: provider
                 .read_or(STRIPE_URL_DEFAULT, STRIPE_URL_VAR, STRIPE_URL_ENTRY)
                 .map_err(Error::ReadUrl)?,
+            disk_sku: provider
+                .maybe_read(STRIPE_DISK_SKU_VAR, STRIPE_DISK_SKU_ENTRY)
+                .map_err(Error::ReadDiskSku)?,
+            webhook_secret: provider
+                .maybe_read(STRIPE_WEBHOOK_SECRET_VAR, STRIPE_WEBHOOK_SECRET_ENTRY)
+                .map_err(Error::ReadWebhookSecret)?,
         })
     }
 }
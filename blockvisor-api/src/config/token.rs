@@ -46,6 +46,9 @@ const INVITATION_EXPIRE_VAR: &str = "INVITATION_EXPIRE";
 const INVITATION_EXPIRE_MINS: &str = "INVITATION_MINS";
 const INVITATION_EXPIRE_ENTRY: &str = "token.expire.invitation";
 const INVITATION_EXPIRE_DEFAULT: &str = "168m";
+const ORG_TRANSFER_EXPIRE_VAR: &str = "ORG_TRANSFER_EXPIRE";
+const ORG_TRANSFER_EXPIRE_ENTRY: &str = "token.expire.org_transfer";
+const ORG_TRANSFER_EXPIRE_DEFAULT: &str = "1d";
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -170,6 +173,8 @@ pub enum ExpireError {
     RegistrationConfirmation(provider::Error),
     /// Failed to parse {INVITATION_EXPIRE_ENTRY:?}: {0}
     Invitation(provider::Error),
+    /// Failed to parse {ORG_TRANSFER_EXPIRE_ENTRY:?}: {0}
+    OrgTransfer(provider::Error),
 }
 
 #[derive(Clone, Copy, Debug, Deserialize)]
@@ -181,6 +186,7 @@ pub struct ExpireConfig {
     pub password_reset: HumanTime,
     pub registration_confirmation: HumanTime,
     pub invitation: HumanTime,
+    pub org_transfer: HumanTime,
 }
 
 impl TryFrom<&Provider> for ExpireConfig {
@@ -267,6 +273,13 @@ impl TryFrom<&Provider> for ExpireConfig {
                 INVITATION_EXPIRE_ENTRY,
             )
             .map_err(ExpireError::Invitation)?;
+        let org_transfer = provider
+            .read_or_else(
+                || ORG_TRANSFER_EXPIRE_DEFAULT.parse::<HumanTime>(),
+                ORG_TRANSFER_EXPIRE_VAR,
+                ORG_TRANSFER_EXPIRE_ENTRY,
+            )
+            .map_err(ExpireError::OrgTransfer)?;
 
         Ok(ExpireConfig {
             token,
@@ -275,6 +288,7 @@ impl TryFrom<&Provider> for ExpireConfig {
             password_reset,
             registration_confirmation,
             invitation,
+            org_transfer,
         })
     }
 }
@@ -294,6 +308,8 @@ pub struct ExpireChrono {
     pub registration_confirmation: chrono::Duration,
     #[serde_as(as = "DurationSeconds<i64>")]
     pub invitation: chrono::Duration,
+    #[serde_as(as = "DurationSeconds<i64>")]
+    pub org_transfer: chrono::Duration,
 }
 
 impl TryFrom<ExpireConfig> for ExpireChrono {
@@ -313,6 +329,8 @@ impl TryFrom<ExpireConfig> for ExpireChrono {
             )
             .map_err(Error::Chrono)?,
             invitation: chrono::Duration::from_std(*config.invitation).map_err(Error::Chrono)?,
+            org_transfer: chrono::Duration::from_std(*config.org_transfer)
+                .map_err(Error::Chrono)?,
         })
     }
 }
@@ -0,0 +1,55 @@
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::provider::{self, Provider};
+
+const DAYS_VAR: &str = "TRIAL_DAYS";
+const DAYS_ENTRY: &str = "trial.days";
+const DAYS_DEFAULT: u32 = 14;
+const MAX_NODES_VAR: &str = "TRIAL_MAX_NODES";
+const MAX_NODES_ENTRY: &str = "trial.max_nodes";
+const MAX_NODES_DEFAULT: u32 = 5;
+const REMIND_DAYS_VAR: &str = "TRIAL_REMIND_DAYS";
+const REMIND_DAYS_ENTRY: &str = "trial.remind_days";
+const REMIND_DAYS_DEFAULT: u32 = 3;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to parse {DAYS_ENTRY:?}: {0}
+    Days(provider::Error),
+    /// Failed to parse {MAX_NODES_ENTRY:?}: {0}
+    MaxNodes(provider::Error),
+    /// Failed to parse {REMIND_DAYS_ENTRY:?}: {0}
+    RemindDays(provider::Error),
+}
+
+/// The defaults of a new org trial.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// How many days a trial lasts before the org is hibernated.
+    pub days: u32,
+    /// The most live nodes an org may have during its trial.
+    pub max_nodes: u32,
+    /// How many days before the end of a trial to remind the org owner.
+    pub remind_days: u32,
+}
+
+impl TryFrom<&Provider> for Config {
+    type Error = Error;
+
+    fn try_from(provider: &Provider) -> Result<Self, Self::Error> {
+        Ok(Config {
+            days: provider
+                .read_or(DAYS_DEFAULT, DAYS_VAR, DAYS_ENTRY)
+                .map_err(Error::Days)?,
+            max_nodes: provider
+                .read_or(MAX_NODES_DEFAULT, MAX_NODES_VAR, MAX_NODES_ENTRY)
+                .map_err(Error::MaxNodes)?,
+            remind_days: provider
+                .read_or(REMIND_DAYS_DEFAULT, REMIND_DAYS_VAR, REMIND_DAYS_ENTRY)
+                .map_err(Error::RemindDays)?,
+        })
+    }
+}
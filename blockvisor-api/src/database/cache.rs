@@ -0,0 +1,240 @@
+//! An in-process cache of rarely changing rows that are read on most requests.
+//!
+//! Each `Conn` shares the cache of its `Pool`, so that model lookups can check
+//! it before querying the database. Entries expire after `database.cache_ttl`,
+//! which bounds how long a change made by another api instance goes unseen,
+//! while changes made by this instance remove the entry once committed.
+//!
+//! Rows read within a transaction may not be committed yet, so only reads
+//! outside of one fill the cache (see `Conn::fill_cache`).
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use tokio::sync::RwLock;
+
+use crate::auth::rbac::{Perm, Role};
+use crate::auth::resource::{OrgId, UserId};
+use crate::model::image::{Config, ConfigId};
+use crate::model::protocol::{Protocol, ProtocolId};
+use crate::model::{Region, RegionId};
+
+/// The most entries kept of each kind before expired entries are evicted.
+const MAX_ENTRIES: usize = 10_000;
+
+pub struct Cache {
+    pub regions: Entries<RegionId, Region>,
+    /// Image configs are never updated, only replaced by a new revision.
+    pub configs: Entries<ConfigId, Config>,
+    /// Protocols as stored, before checking their visibility to a caller.
+    pub protocols: Entries<ProtocolId, Protocol>,
+    pub role_perms: Entries<Role, HashSet<Perm>>,
+    /// The roles of a user within an org, kept for `roles_ttl`.
+    pub org_roles: Entries<(UserId, OrgId), HashSet<Role>>,
+    /// The roles of a user that apply to every org, kept for `roles_ttl`.
+    pub user_roles: Entries<UserId, HashSet<Role>>,
+}
+
+impl Cache {
+    pub fn new(ttl: Duration, roles_ttl: Duration) -> Self {
+        Cache {
+            regions: Entries::new(ttl),
+            configs: Entries::new(ttl),
+            protocols: Entries::new(ttl),
+            role_perms: Entries::new(ttl),
+            org_roles: Entries::new(roles_ttl),
+            user_roles: Entries::new(roles_ttl),
+        }
+    }
+}
+
+/// An entry of the cache that a write invalidates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheKey {
+    Config(ConfigId),
+    OrgRoles(UserId, OrgId),
+    Protocol(ProtocolId),
+    Region(RegionId),
+    RolePerms(Role),
+    UserRoles(UserId),
+}
+
+impl Cache {
+    pub async fn remove(&self, key: CacheKey) {
+        match key {
+            CacheKey::Config(id) => self.configs.remove(&id).await,
+            CacheKey::OrgRoles(user_id, org_id) => self.org_roles.remove(&(user_id, org_id)).await,
+            CacheKey::Protocol(id) => self.protocols.remove(&id).await,
+            CacheKey::Region(id) => self.regions.remove(&id).await,
+            CacheKey::RolePerms(role) => self.role_perms.remove(&role).await,
+            CacheKey::UserRoles(user_id) => self.user_roles.remove(&user_id).await,
+        }
+    }
+}
+
+pub struct Entries<K, V> {
+    ttl: Duration,
+    entries: RwLock<HashMap<K, (V, Instant)>>,
+}
+
+impl<K, V> Entries<K, V>
+where
+    K: Copy + Eq + Hash,
+    V: Clone,
+{
+    fn new(ttl: Duration) -> Self {
+        Entries {
+            ttl,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, key: &K) -> Option<V> {
+        self.entries
+            .read()
+            .await
+            .get(key)
+            .filter(|(_, cached_at)| cached_at.elapsed() < self.ttl)
+            .map(|(value, _)| value.clone())
+    }
+
+    /// The cached values of `keys`, and the keys that were not cached.
+    pub async fn get_many<'k, I>(&self, keys: I) -> (Vec<V>, HashSet<K>)
+    where
+        I: IntoIterator<Item = &'k K>,
+        K: 'k,
+    {
+        let entries = self.entries.read().await;
+        let mut values = vec![];
+        let mut missing = HashSet::new();
+
+        for key in keys {
+            match entries.get(key) {
+                Some((value, cached_at)) if cached_at.elapsed() < self.ttl => {
+                    values.push(value.clone());
+                }
+                _ => {
+                    missing.insert(*key);
+                }
+            }
+        }
+
+        (values, missing)
+    }
+
+    pub async fn insert(&self, key: K, value: V) {
+        self.insert_many([(key, value)]).await;
+    }
+
+    pub async fn insert_many<I>(&self, values: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut entries = self.entries.write().await;
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, (_, cached_at)| cached_at.elapsed() < self.ttl);
+        }
+
+        let now = Instant::now();
+        for (key, value) in values {
+            if entries.len() < MAX_ENTRIES {
+                entries.insert(key, (value, now));
+            }
+        }
+    }
+
+    pub async fn remove(&self, key: &K) {
+        self.entries.write().await.remove(key);
+    }
+
+    pub async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::result::Error::NotFound;
+    use diesel_async::scoped_futures::ScopedFutureExt;
+    use uuid::Uuid;
+
+    use crate::config::Context;
+    use crate::database::Transaction;
+    use crate::grpc::host;
+    use crate::model::region::UpdateRegion;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn entries_expire_after_the_ttl() {
+        let entries: Entries<Uuid, u32> = Entries::new(Duration::from_millis(50));
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        entries.insert(a, 1).await;
+        assert_eq!(entries.get(&a).await, Some(1));
+
+        let (values, missing) = entries.get_many(&[a, b]).await;
+        assert_eq!(values, vec![1]);
+        assert_eq!(missing, HashSet::from([b]));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(entries.get(&a).await, None);
+
+        entries.insert(a, 2).await;
+        entries.remove(&a).await;
+        assert_eq!(entries.get(&a).await, None);
+    }
+
+    #[tokio::test]
+    async fn writes_only_change_the_cache_once_committed() {
+        let (ctx, db) = Context::with_mocked().await.unwrap();
+        let region_id = db.seed.region.id;
+        let region = Region::by_id(region_id, &mut db.conn().await)
+            .await
+            .unwrap();
+
+        // a write reads its own update, which is neither cached nor evicts the
+        // committed region when rolled back
+        let result: Result<tonic::Response<()>, tonic::Status> = ctx
+            .write(|mut write| {
+                async move {
+                    let update = UpdateRegion {
+                        id: region_id,
+                        display_name: Some("uncommitted"),
+                        sku_code: None,
+                    };
+                    update.apply(&mut write).await?;
+                    let updated = Region::by_id(region_id, &mut write).await?;
+                    assert_eq!(updated.display_name, "uncommitted");
+                    Err(host::Error::Diesel(NotFound))
+                }
+                .scope_boxed()
+            })
+            .await;
+        assert!(result.is_err());
+        let cached = db.pool.cache.regions.get(&region_id).await.unwrap();
+        assert_eq!(cached.display_name, region.display_name);
+
+        // a committed update is read back rather than the cached region
+        let _: tonic::Response<()> = ctx
+            .write(|mut write| {
+                async move {
+                    let update = UpdateRegion {
+                        id: region_id,
+                        display_name: Some("committed"),
+                        sku_code: None,
+                    };
+                    update.apply(&mut write).await?;
+                    Ok::<_, host::Error>(())
+                }
+                .scope_boxed()
+            })
+            .await
+            .unwrap();
+        let region = Region::by_id(region_id, &mut db.conn().await)
+            .await
+            .unwrap();
+        assert_eq!(region.display_name, "committed");
+    }
+}
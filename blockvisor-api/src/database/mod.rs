@@ -1,8 +1,18 @@
+pub mod cache;
+pub use cache::{Cache, CacheKey};
+
+pub mod query_log;
+pub use query_log::QueryLog;
+
 #[cfg(any(test, feature = "integration-test"))]
 pub mod seed;
 
+use std::collections::HashSet;
 use std::future::Future;
+use std::hash::Hash;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use derive_more::{Deref, DerefMut};
 use diesel::{ConnectionError, ConnectionResult};
@@ -14,6 +24,8 @@ use diesel_migrations::EmbeddedMigrations;
 use displaydoc::Display;
 use futures_util::FutureExt;
 use futures_util::future::BoxFuture;
+use opentelemetry::global;
+use opentelemetry::metrics::{Histogram, ObservableGauge};
 use rustls::client::WebPkiServerVerifier;
 use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
 use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
@@ -30,10 +42,13 @@ use crate::auth::resource::Resources;
 use crate::auth::{self, AuthZ, Authorize};
 use crate::config::Context;
 use crate::config::database::Config;
+use crate::grpc::middleware::RpcName;
 use crate::grpc::{self, Metadata, ResponseMessage, Status};
 use crate::model::rbac::{RbacPerm, RbacRole};
 use crate::mqtt::Message;
 
+use self::cache::Entries;
+
 pub const MIGRATIONS: EmbeddedMigrations = diesel_migrations::embed_migrations!();
 
 // returns `impl Future` for async trait with `pub` visibility
@@ -64,6 +79,9 @@ pub(crate) trait Transaction {
         ErrOuter: From<Status> + From<Error>;
 
     /// Run a transactional closure to write to the database.
+    ///
+    /// The closure is run once, so it may call external services. Prefer
+    /// `WriteConn::after_commit` for calls that must not outlive a rollback.
     async fn write<'a, F, Response, ResponseInner, ErrInner, ErrOuter>(
         &'a self,
         f: F,
@@ -72,6 +90,31 @@ pub(crate) trait Transaction {
         F: for<'c> FnOnce(
                 WriteConn<'c, 'a>,
             ) -> ScopedBoxFuture<'a, 'c, Result<ResponseInner, ErrInner>>
+            + Clone
+            + Send
+            + 'a,
+        Response: ResponseMessage<ResponseInner>,
+        ResponseInner: Send + 'a,
+        ErrInner: std::error::Error + From<diesel::result::Error> + Send + 'a,
+        Status: From<ErrInner>,
+        ErrOuter: From<Status> + From<Error>;
+
+    /// Run a transactional closure without side effects outside of the
+    /// database, retrying it on a serialization failure or deadlock.
+    ///
+    /// A conflicting transaction is run again with a new clone of `f`, up to
+    /// `database.write_attempts` times in total. Any MQTT messages, metadata or
+    /// `after_commit` work of a failed attempt is dropped, so `f` must not call
+    /// external services directly.
+    async fn retry_write<'a, F, Response, ResponseInner, ErrInner, ErrOuter>(
+        &'a self,
+        f: F,
+    ) -> Result<Response, ErrOuter>
+    where
+        F: for<'c> FnOnce(
+                WriteConn<'c, 'a>,
+            ) -> ScopedBoxFuture<'a, 'c, Result<ResponseInner, ErrInner>>
+            + Clone
             + Send
             + 'a,
         Response: ResponseMessage<ResponseInner>,
@@ -83,6 +126,8 @@ pub(crate) trait Transaction {
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Timed out after {0:?} waiting for a database connection.
+    AcquireTimeout(Duration),
     /// Failed to build database pool: {0}
     BuildPool(diesel_async::pooled_connection::PoolError),
     /// Failed to create RBAC perms: {0}
@@ -98,33 +143,108 @@ impl From<Error> for Status {
         use Error::*;
         match err {
             BuildPool(_) | PoolConnection(_) => Status::internal("Internal error."),
+            AcquireTimeout(_) => {
+                Status::unavailable("Database is busy.").with_reason("DATABASE_BUSY")
+            }
             CreatePerms(err) | CreateRoles(err) => err.into(),
         }
     }
 }
 
 impl From<Error> for tonic::Status {
-    fn from(value: Error) -> Self {
-        match value {
-            Error::BuildPool(_err) => tonic::Status::internal("Failed to create db pool"),
-            Error::CreatePerms(err) => {
-                let status: Status = err.into();
-                status.into()
-            }
-            Error::CreateRoles(err) => {
-                let status: Status = err.into();
-                status.into()
-            }
-            Error::PoolConnection(_err) => {
-                tonic::Status::internal("Failed to create db connection")
-            }
-        }
+    fn from(err: Error) -> Self {
+        Status::from(err).into()
     }
 }
 
 /// A `Conn` is an open connection to the database from the `Pool`.
 #[derive(Deref, DerefMut)]
-pub struct Conn<'c>(PooledConnection<'c, AsyncPgConnection>);
+pub struct Conn<'c> {
+    #[deref]
+    #[deref_mut]
+    conn: PooledConnection<'c, AsyncPgConnection>,
+    cache: &'c Cache,
+    conflict: Arc<AtomicBool>,
+    /// The cache entries written within `Transaction::write`, which are only
+    /// invalidated once it commits. `None` outside of a write transaction.
+    invalidated: Option<Vec<CacheKey>>,
+}
+
+impl<'c> Conn<'c> {
+    /// The cache shared by all connections of the pool.
+    pub const fn cache(&self) -> &'c Cache {
+        self.cache
+    }
+
+    /// The cached value of `key`.
+    ///
+    /// A write transaction that has invalidated an entry skips the cache, so
+    /// that it reads its own writes rather than the committed rows.
+    pub async fn cached<K, V>(&self, entries: fn(&Cache) -> &Entries<K, V>, key: &K) -> Option<V>
+    where
+        K: Copy + Eq + Hash,
+        V: Clone,
+    {
+        if self.reads_own_writes() {
+            return None;
+        }
+        entries(self.cache).get(key).await
+    }
+
+    /// The cached values of `keys`, and the keys that were not cached.
+    pub async fn cached_many<'k, K, V, I>(
+        &self,
+        entries: fn(&Cache) -> &Entries<K, V>,
+        keys: I,
+    ) -> (Vec<V>, HashSet<K>)
+    where
+        I: IntoIterator<Item = &'k K>,
+        K: Copy + Eq + Hash + 'k,
+        V: Clone,
+    {
+        if self.reads_own_writes() {
+            return (vec![], keys.into_iter().copied().collect());
+        }
+        entries(self.cache).get_many(keys).await
+    }
+
+    /// Cache rows read from the database, unless they were read within a write
+    /// transaction that may yet roll back.
+    pub async fn fill_cache<K, V, I>(&self, entries: fn(&Cache) -> &Entries<K, V>, values: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Copy + Eq + Hash,
+        V: Clone,
+    {
+        if self.invalidated.is_none() {
+            entries(self.cache).insert_many(values).await;
+        }
+    }
+
+    /// Whether this is a write transaction that has invalidated a cache entry.
+    fn reads_own_writes(&self) -> bool {
+        self.invalidated
+            .as_ref()
+            .is_some_and(|keys| !keys.is_empty())
+    }
+
+    /// Invalidate a cache entry after writing its rows.
+    ///
+    /// Within `Transaction::write` the entry is removed once committed, so that
+    /// no other connection can cache the old rows in the meantime.
+    pub async fn invalidate(&mut self, key: CacheKey) {
+        match self.invalidated.as_mut() {
+            Some(keys) => keys.push(key),
+            None => self.cache.remove(key).await,
+        }
+    }
+
+    /// Whether a query failed with a serialization failure since this was
+    /// last called.
+    pub fn take_conflict(&self) -> bool {
+        self.conflict.swap(false, Ordering::SeqCst)
+    }
+}
 
 /// A `ReadConn` is an open, non-transaction connection to the database.
 #[derive(Deref, DerefMut)]
@@ -149,10 +269,14 @@ impl Authorize for ReadConn<'_, '_> {
     }
 }
 
+/// Work with side effects outside of the database, run once committed.
+pub type AfterCommit = BoxFuture<'static, ()>;
+
 /// A `WriteConn` is an open transactional connection to the database.
 ///
-/// Any messages sent over `mqtt_tx` will be forwared to MQTT only after the
-/// database transaction has been committed.
+/// Any messages sent over `mqtt_tx` will be forwared to MQTT, and any work
+/// sent over `commit_tx` will be run, only after the database transaction has
+/// been committed.
 #[derive(Deref, DerefMut)]
 pub struct WriteConn<'c, 't> {
     #[deref]
@@ -162,6 +286,7 @@ pub struct WriteConn<'c, 't> {
 
     pub meta_tx: UnboundedSender<(&'static str, AsciiMetadataValue)>,
     pub mqtt_tx: UnboundedSender<Message>,
+    pub commit_tx: UnboundedSender<AfterCommit>,
 }
 
 impl Authorize for WriteConn<'_, '_> {
@@ -178,7 +303,19 @@ impl Authorize for WriteConn<'_, '_> {
     }
 }
 
-impl WriteConn<'_, '_> {
+impl<'t> WriteConn<'_, 't> {
+    /// Borrow this connection as a new `WriteConn` in the same transaction,
+    /// for calling another handler that takes one by value.
+    pub fn reborrow(&mut self) -> WriteConn<'_, 't> {
+        WriteConn {
+            conn: &mut *self.conn,
+            ctx: self.ctx,
+            meta_tx: self.meta_tx.clone(),
+            mqtt_tx: self.mqtt_tx.clone(),
+            commit_tx: self.commit_tx.clone(),
+        }
+    }
+
     pub fn meta(&mut self, key: &'static str, val: AsciiMetadataValue) {
         // safety: meta_rx is open for the lifetime of WriteConn
         self.meta_tx.send((key, val)).expect("meta_rx");
@@ -191,10 +328,27 @@ impl WriteConn<'_, '_> {
         // safety: mqtt_rx is open for the lifetime of WriteConn
         self.mqtt_tx.send(message.into()).expect("mqtt_rx");
     }
+
+    /// Run `work` once the transaction has committed, so that calls to
+    /// external services are neither made for a rolled back write nor
+    /// repeated when a write is retried.
+    pub fn after_commit<F>(&mut self, work: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // safety: commit_rx is open for the lifetime of WriteConn
+        self.commit_tx.send(work.boxed()).expect("commit_rx");
+    }
 }
 
 #[derive(Clone, Deref, DerefMut)]
-pub struct Pool(bb8::Pool<AsyncPgConnection>);
+pub struct Pool {
+    #[deref]
+    #[deref_mut]
+    pool: bb8::Pool<AsyncPgConnection>,
+    cache: Arc<Cache>,
+    metrics: Arc<PoolMetrics>,
+}
 
 impl Pool {
     pub async fn new(config: &Config) -> Result<Self, Error> {
@@ -205,15 +359,48 @@ impl Pool {
             manager_config,
         );
 
-        bb8::Pool::builder()
+        let pool = bb8::Pool::builder()
             .max_size(config.max_conns)
             .min_idle(Some(config.min_conns))
             .max_lifetime(Some(*config.max_lifetime))
             .idle_timeout(Some(*config.idle_timeout))
+            .connection_timeout(*config.acquire_timeout)
             .build(manager)
             .await
-            .map(Self)
-            .map_err(Error::BuildPool)
+            .map_err(Error::BuildPool)?;
+
+        let gauges = PoolMetrics::observe(&pool);
+        Ok(Pool::from_bb8(pool, gauges, config))
+    }
+
+    fn from_bb8(
+        pool: bb8::Pool<AsyncPgConnection>,
+        gauges: Vec<ObservableGauge<u64>>,
+        config: &Config,
+    ) -> Self {
+        let wait_time = global::meter("database")
+            .f64_histogram("db.pool.wait_time")
+            .with_description("Time spent waiting for a connection from the pool.")
+            .with_unit("s")
+            .build();
+        let query_duration = global::meter("database")
+            .f64_histogram("db.query.duration")
+            .with_description("Time spent running each query, by the rpc that made it.")
+            .with_unit("s")
+            .build();
+
+        Pool {
+            pool,
+            cache: Arc::new(Cache::new(*config.cache_ttl, *config.roles_cache_ttl)),
+            metrics: Arc::new(PoolMetrics {
+                acquire_timeout: *config.acquire_timeout,
+                slow_acquire: *config.slow_acquire,
+                slow_query: *config.slow_query,
+                wait_time,
+                query_duration,
+                _gauges: gauges,
+            }),
+        }
     }
 
     pub fn is_open(&self) -> bool {
@@ -221,9 +408,74 @@ impl Pool {
     }
 }
 
+/// Instrumentation of the connections acquired from a `Pool`.
+struct PoolMetrics {
+    acquire_timeout: Duration,
+    slow_acquire: Duration,
+    slow_query: Duration,
+    wait_time: Histogram<f64>,
+    query_duration: Histogram<f64>,
+    /// Kept alive so that the state of the pool keeps being observed.
+    _gauges: Vec<ObservableGauge<u64>>,
+}
+
+impl PoolMetrics {
+    /// Observe the connections of `pool` that are in use and idle.
+    fn observe(pool: &bb8::Pool<AsyncPgConnection>) -> Vec<ObservableGauge<u64>> {
+        let meter = global::meter("database");
+
+        let in_use = pool.clone();
+        let idle = pool.clone();
+        vec![
+            meter
+                .u64_observable_gauge("db.pool.connections.in_use")
+                .with_description("Connections checked out of the pool.")
+                .with_callback(move |observer| {
+                    let state = in_use.state();
+                    let used = state.connections.saturating_sub(state.idle_connections);
+                    observer.observe(u64::from(used), &[]);
+                })
+                .build(),
+            meter
+                .u64_observable_gauge("db.pool.connections.idle")
+                .with_description("Open connections waiting in the pool.")
+                .with_callback(move |observer| {
+                    observer.observe(u64::from(idle.state().idle_connections), &[]);
+                })
+                .build(),
+        ]
+    }
+}
+
 impl Database for Pool {
     async fn conn(&self) -> Result<Conn<'_>, Error> {
-        self.get().await.map(Conn).map_err(Error::PoolConnection)
+        let started = Instant::now();
+        let conn = self.get().await;
+
+        let waited = started.elapsed();
+        let rpc = RpcName::current();
+        self.metrics.wait_time.record(waited.as_secs_f64(), &[]);
+        if waited >= self.metrics.slow_acquire {
+            let rpc = rpc.as_deref().unwrap_or("unknown");
+            warn!("Waited {waited:?} for a database connection in {rpc}");
+        }
+
+        let mut conn = conn.map_err(|err| match err {
+            bb8::RunError::TimedOut => Error::AcquireTimeout(self.metrics.acquire_timeout),
+            err @ bb8::RunError::User(_) => Error::PoolConnection(err),
+        })?;
+
+        let metrics = &self.metrics;
+        let log = QueryLog::new(rpc, metrics.slow_query, metrics.query_duration.clone());
+        let conflict = log.conflict();
+        conn.set_instrumentation(log);
+
+        Ok(Conn {
+            conn,
+            cache: &self.cache,
+            conflict,
+            invalidated: None,
+        })
     }
 }
 
@@ -268,6 +520,7 @@ where
         F: for<'c> FnOnce(
                 WriteConn<'c, 'a>,
             ) -> ScopedBoxFuture<'a, 'c, Result<ResponseInner, ErrInner>>
+            + Clone
             + Send
             + 'a,
         Response: ResponseMessage<ResponseInner>,
@@ -276,38 +529,117 @@ where
         Status: From<ErrInner>,
         ErrOuter: From<Status> + From<Error>,
     {
-        let ctx = self.as_ref();
-        let conn = &mut ctx.conn().await?;
+        run_write(self.as_ref(), f, 1).await
+    }
 
-        let (meta_tx, mut meta_rx) = mpsc::unbounded_channel();
-        let (mqtt_tx, mut mqtt_rx) = mpsc::unbounded_channel();
+    async fn retry_write<'a, F, Response, ResponseInner, ErrInner, ErrOuter>(
+        &'a self,
+        f: F,
+    ) -> Result<Response, ErrOuter>
+    where
+        F: for<'c> FnOnce(
+                WriteConn<'c, 'a>,
+            ) -> ScopedBoxFuture<'a, 'c, Result<ResponseInner, ErrInner>>
+            + Clone
+            + Send
+            + 'a,
+        Response: ResponseMessage<ResponseInner>,
+        ResponseInner: Send + 'a,
+        ErrInner: std::error::Error + From<diesel::result::Error> + Send + 'a,
+        Status: From<ErrInner>,
+        ErrOuter: From<Status> + From<Error>,
+    {
+        let ctx = self.as_ref();
+        run_write(ctx, f, ctx.config.database.write_attempts).await
+    }
+}
 
-        let response = conn
+/// Run `f` in a transaction, up to `attempts` times while it conflicts.
+async fn run_write<'a, F, Response, ResponseInner, ErrInner, ErrOuter>(
+    ctx: &'a Context,
+    f: F,
+    attempts: u32,
+) -> Result<Response, ErrOuter>
+where
+    F: for<'c> FnOnce(
+            WriteConn<'c, 'a>,
+        ) -> ScopedBoxFuture<'a, 'c, Result<ResponseInner, ErrInner>>
+        + Clone
+        + Send
+        + 'a,
+    Response: ResponseMessage<ResponseInner>,
+    ResponseInner: Send + 'a,
+    ErrInner: std::error::Error + From<diesel::result::Error> + Send + 'a,
+    Status: From<ErrInner>,
+    ErrOuter: From<Status> + From<Error>,
+{
+    let conn = &mut ctx.conn().await?;
+    let backoff = *ctx.config.database.write_backoff;
+    conn.take_conflict();
+
+    let mut attempt = 1;
+    let (response, mut meta_rx, mut mqtt_rx, mut commit_rx) = loop {
+        let (meta_tx, meta_rx) = mpsc::unbounded_channel();
+        let (mqtt_tx, mqtt_rx) = mpsc::unbounded_channel();
+        let (commit_tx, commit_rx) = mpsc::unbounded_channel();
+
+        let f = f.clone();
+        conn.invalidated = Some(vec![]);
+        let result = conn
             .transaction(|conn| {
                 let write = WriteConn {
                     conn,
                     ctx,
                     meta_tx,
                     mqtt_tx,
+                    commit_tx,
                 };
                 f(write).scope_boxed()
             })
-            .await
-            .map_err(Status::from)?;
-
-        while let Some(msg) = mqtt_rx.recv().await {
-            if let Err(err) = ctx.notifier.send(msg).await {
-                warn!("Failed to send MQTT message: {err}");
+            .await;
+
+        match result {
+            Ok(response) => break (response, meta_rx, mqtt_rx, commit_rx),
+            Err(err) if attempt < attempts && conn.take_conflict() => {
+                let backoff = write_backoff(backoff, attempt);
+                warn!("Retrying write in {backoff:?} after a conflict: {err}");
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
             }
+            // a write that still conflicts may succeed if the client retries
+            Err(err) if conn.take_conflict() => {
+                return Err(Status::from(err).with_retryable(true).into());
+            }
+            Err(err) => return Err(Status::from(err).into()),
         }
+    };
+
+    for key in conn.invalidated.take().into_iter().flatten() {
+        conn.cache().remove(key).await;
+    }
+
+    while let Some(work) = commit_rx.recv().await {
+        work.await;
+    }
 
-        let mut meta = Metadata::new();
-        while let Some((key, val)) = meta_rx.recv().await {
-            meta.insert_grpc(key, val);
+    while let Some(msg) = mqtt_rx.recv().await {
+        if let Err(err) = ctx.notifier.send(msg).await {
+            warn!("Failed to send MQTT message: {err}");
         }
+    }
 
-        Ok(Response::construct(response, meta))
+    let mut meta = Metadata::new();
+    while let Some((key, val)) = meta_rx.recv().await {
+        meta.insert_grpc(key, val);
     }
+
+    Ok(Response::construct(response, meta))
+}
+
+/// The jittered delay before retrying a conflicting write for `attempt`.
+fn write_backoff(base: Duration, attempt: u32) -> Duration {
+    let backoff = base.saturating_mul(2_u32.saturating_pow(attempt - 1));
+    backoff.mul_f64(rand::random::<f64>().mul_add(0.5, 0.5))
 }
 
 /// A custom establish function for a new `AsyncPgConnection` that requires TLS.
@@ -329,6 +661,11 @@ fn establish_connection(config: &str) -> BoxFuture<'_, ConnectionResult<AsyncPgC
             }
         });
 
+        // untranslated messages let `query_log::is_conflict` detect deadlocks
+        if let Err(err) = client.batch_execute(query_log::UNTRANSLATED_MESSAGES).await {
+            warn!("Failed to set untranslated database messages: {err}");
+        }
+
         AsyncPgConnection::try_from(client).await
     };
 
@@ -469,9 +806,10 @@ pub mod tests {
                 AsyncDieselConnectionManager::<AsyncPgConnection>::new(test_db_url.clone());
             let pool = bb8::Pool::builder()
                 .max_size(config.max_conns)
+                .connection_timeout(*config.acquire_timeout)
                 .build(manager)
                 .await
-                .map(Pool)
+                .map(|pool| Pool::from_bb8(pool, vec![], config))
                 .unwrap();
 
             // Finally we seed the new database with test data.
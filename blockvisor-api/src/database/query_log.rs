@@ -0,0 +1,177 @@
+//! Attribute each database query to the RPC that made it.
+//!
+//! Every connection taken from the `Pool` gets a new `QueryLog` as its diesel
+//! instrumentation, tagged with the RPC being served by the current request
+//! (or `background` outside of one). Each query on the connection is then:
+//!
+//! - Recorded in the `db.query.duration` histogram, by `rpc`.
+//! - Logged with its RPC if it takes at least `database.slow_query`.
+//! - Checked for a serialization failure or deadlock, which marks the
+//!   connection as having a conflict so that a write can be retried.
+//!
+//! Logged queries never include their bind parameters, and any string literals
+//! in the SQL itself are redacted.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use diesel::connection::{Instrumentation, InstrumentationEvent};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::Histogram;
+use regex::{NoExpand, Regex};
+use tracing::warn;
+
+use crate::util::secrets::REDACTED;
+
+/// The RPC name of queries made outside of a request.
+pub const BACKGROUND_RPC: &str = "background";
+
+/// Run on each new connection so that server messages are not localized.
+///
+/// Setting `lc_messages` needs a superuser or a granted `SET` privilege, and
+/// deadlocks are not detected on connections where it fails.
+pub const UNTRANSLATED_MESSAGES: &str = "SET lc_messages = 'C'";
+
+/// The untranslated message of a deadlock (SQLSTATE `40P01`).
+const DEADLOCK_DETECTED: &str = "deadlock detected";
+
+/// Separates the SQL of a formatted diesel query from its bind parameters.
+const BINDS_SEPARATOR: &str = " -- binds: ";
+
+static STRING_LITERAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("'(?:[^']|'')*'").expect("valid regex"));
+
+/// Every query started on any connection with a `QueryLog`.
+static QUERIES: AtomicU64 = AtomicU64::new(0);
+
+/// The number of queries made so far by this process.
+pub fn query_count() -> u64 {
+    QUERIES.load(Ordering::SeqCst)
+}
+
+pub struct QueryLog {
+    rpc: String,
+    slow_query: Duration,
+    duration: Histogram<f64>,
+    started: Option<Instant>,
+    conflict: Arc<AtomicBool>,
+}
+
+impl QueryLog {
+    pub fn new(rpc: Option<String>, slow_query: Duration, duration: Histogram<f64>) -> Self {
+        QueryLog {
+            rpc: rpc.unwrap_or_else(|| BACKGROUND_RPC.to_string()),
+            slow_query,
+            duration,
+            started: None,
+            conflict: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Set once a query on this connection fails with a conflict.
+    pub fn conflict(&self) -> Arc<AtomicBool> {
+        self.conflict.clone()
+    }
+
+    fn finish(&mut self, sql: impl FnOnce() -> String, failed: bool) {
+        let Some(started) = self.started.take() else {
+            return;
+        };
+
+        let elapsed = started.elapsed();
+        let rpc = KeyValue::new("rpc", self.rpc.clone());
+        self.duration.record(elapsed.as_secs_f64(), &[rpc]);
+
+        if elapsed >= self.slow_query {
+            let rpc = &self.rpc;
+            let sql = redact(&sql());
+            if failed {
+                warn!("Slow query failed in {rpc} after {elapsed:?}: {sql}");
+            } else {
+                warn!("Slow query in {rpc} took {elapsed:?}: {sql}");
+            }
+        }
+    }
+}
+
+impl Instrumentation for QueryLog {
+    fn on_connection_event(&mut self, event: InstrumentationEvent<'_>) {
+        match event {
+            InstrumentationEvent::StartQuery { .. } => {
+                QUERIES.fetch_add(1, Ordering::SeqCst);
+                self.started = Some(Instant::now());
+            }
+            InstrumentationEvent::FinishQuery { query, error, .. } => {
+                if error.is_some_and(is_conflict) {
+                    self.conflict.store(true, Ordering::SeqCst);
+                }
+                self.finish(|| query.to_string(), error.is_some());
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Whether `err` aborted a transaction that may succeed if run again.
+///
+/// The driver maps SQLSTATE `40001` to `SerializationFailure`. Deadlocks
+/// (`40P01`) reach diesel as `Unknown` without their SQLSTATE, so they are
+/// matched on the message that `UNTRANSLATED_MESSAGES` keeps in English.
+pub fn is_conflict(err: &DieselError) -> bool {
+    match err {
+        DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, _) => true,
+        DieselError::DatabaseError(DatabaseErrorKind::Unknown, info) => {
+            info.message() == DEADLOCK_DETECTED
+        }
+        _ => false,
+    }
+}
+
+/// The SQL of a formatted query, without its binds or string literals.
+fn redact(query: &str) -> String {
+    let sql = query
+        .split_once(BINDS_SEPARATOR)
+        .map_or(query, |(sql, _binds)| sql);
+    let literal = format!("'{REDACTED}'");
+    STRING_LITERAL
+        .replace_all(sql.trim(), NoExpand(&literal))
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_queries_are_redacted() {
+        let query = r#"SELECT "nodes"."id" FROM "nodes" WHERE "nodes"."note" = 'it''s secret' AND "nodes"."org_id" = $1 -- binds: [08dede71-b97d-47c1-a91d-6ba0997b3cdd]"#;
+        assert_eq!(
+            redact(query),
+            r#"SELECT "nodes"."id" FROM "nodes" WHERE "nodes"."note" = '[REDACTED]' AND "nodes"."org_id" = $1"#
+        );
+    }
+
+    #[test]
+    fn serialization_failures_and_deadlocks_conflict() {
+        let error =
+            |kind, message: &str| DieselError::DatabaseError(kind, Box::new(message.into()));
+
+        let serialize = "could not serialize access due to concurrent update";
+        assert!(is_conflict(&error(
+            DatabaseErrorKind::SerializationFailure,
+            serialize
+        )));
+        let deadlock = "deadlock detected";
+        assert!(is_conflict(&error(DatabaseErrorKind::Unknown, deadlock)));
+        let other = "canceling statement due to statement timeout";
+        assert!(!is_conflict(&error(DatabaseErrorKind::Unknown, other)));
+        let unique = "duplicate key value violates unique constraint";
+        assert!(!is_conflict(&error(
+            DatabaseErrorKind::UniqueViolation,
+            unique
+        )));
+        assert!(!is_conflict(&DieselError::NotFound));
+    }
+}
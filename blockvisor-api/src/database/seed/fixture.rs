@@ -0,0 +1,503 @@
+//! Composable fixtures on top of the `Seed` data, for load and UI testing.
+//!
+//! Every id and name is derived from the name of its fixture, so seeding the
+//! same fixture again creates the same rows. A fixture can only be created
+//! once per database, and `delete` removes it again.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::rbac::OrgRole;
+use crate::auth::rbac::access::tests::view_authz;
+use crate::auth::resource::{HostId, NodeId, OrgId, ResourceType, UserId};
+use crate::database::Conn;
+use crate::model::host::{Host, NewHost, ScheduleType};
+use crate::model::image::config::ConfigType;
+use crate::model::image::{Image, ImageId, ImageProperty, NewConfig, NodeConfig};
+use crate::model::ip_address::NewIpAddress;
+use crate::model::node::{Node, NodeState, ResourceAffinity};
+use crate::model::plan::PlanTier;
+use crate::model::protocol::version::VersionId;
+use crate::model::region::Region;
+use crate::model::schema::{
+    commands, configs, hosts, image_properties, images, ip_addresses, node_logs, node_reports,
+    nodes, orgs,
+};
+use crate::model::sql::{IpNetwork, Tag};
+use crate::model::{Org, Protocol};
+
+use super::{ARCHIVE_ID_1, DISK_BYTES, MEMORY_BYTES, PROTOCOL_KEY, SEMANTIC_VERSION};
+
+/// Each host has its own `/24`, so it can hold at most this many nodes.
+pub const MAX_NODES_PER_HOST: usize = 250;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to add fixture host to org: {0}
+    AddHost(crate::model::org::Error),
+    /// Failed to add fixture node to host: {0}
+    AddHostNode(crate::model::host::Error),
+    /// Failed to add fixture node to org: {0}
+    AddOrgNode(crate::model::org::Error),
+    /// Failed to add fixture user to org: {0}
+    AddUser(crate::model::org::Error),
+    /// Failed to create fixture config: {0}
+    Config(crate::model::image::config::Error),
+    /// Failed to create fixture `{0}`: {1}
+    Create(String, diesel::result::Error),
+    /// Failed to delete fixture `{0}`: {1}
+    Delete(String, diesel::result::Error),
+    /// Failed to create fixture ip addresses: {0}
+    IpAddress(crate::model::ip_address::Error),
+    /// Fixture `{0}` has nodes but no hosts.
+    NoHosts(String),
+    /// Failed to read fixture org: {0}
+    Org(crate::model::org::Error),
+    /// Failed to read fixture image properties: {0}
+    Property(crate::model::image::property::Error),
+    /// Fixture `{0}` needs more than {MAX_NODES_PER_HOST} ip addresses per host.
+    TooManyNodes(String),
+}
+
+/// A deterministic id for the `index`th `kind` of fixture `name`.
+pub fn fixture_id(name: &str, kind: &str, index: usize) -> Uuid {
+    let mut hasher = Sha1::new();
+    hasher.update(name.as_bytes());
+    hasher.update(kind.as_bytes());
+    hasher.update(index.to_be_bytes());
+    let hash = hasher.finalize();
+
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    Uuid::from_bytes(bytes)
+}
+
+/// An org with `nodes` spread evenly across `hosts` private hosts.
+pub struct OrgFixture<'a> {
+    name: &'a str,
+    hosts: usize,
+    nodes: usize,
+    spare_ips: usize,
+    members: Vec<(UserId, OrgRole)>,
+}
+
+/// The rows created by an `OrgFixture`.
+pub struct OrgFixtures {
+    pub org: Org,
+    pub hosts: Vec<Host>,
+    pub nodes: Vec<Node>,
+}
+
+impl<'a> OrgFixture<'a> {
+    pub const fn new(name: &'a str) -> Self {
+        OrgFixture {
+            name,
+            hosts: 0,
+            nodes: 0,
+            spare_ips: 0,
+            members: Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub const fn hosts(mut self, hosts: usize) -> Self {
+        self.hosts = hosts;
+        self
+    }
+
+    #[must_use]
+    pub const fn nodes(mut self, nodes: usize) -> Self {
+        self.nodes = nodes;
+        self
+    }
+
+    /// Free ip addresses on each host, for nodes created after seeding.
+    #[must_use]
+    pub const fn spare_ips(mut self, spare_ips: usize) -> Self {
+        self.spare_ips = spare_ips;
+        self
+    }
+
+    #[must_use]
+    pub fn member(mut self, user_id: UserId, role: OrgRole) -> Self {
+        self.members.push((user_id, role));
+        self
+    }
+
+    pub fn org_id(&self) -> OrgId {
+        fixture_id(self.name, "org", 0).into()
+    }
+
+    fn host_id(&self, index: usize) -> HostId {
+        fixture_id(self.name, "host", index).into()
+    }
+
+    fn node_id(&self, index: usize) -> NodeId {
+        fixture_id(self.name, "node", index).into()
+    }
+
+    /// The ip addresses of each host: its share of `nodes` and `spare_ips`.
+    const fn ips_per_host(&self) -> usize {
+        self.nodes.div_ceil(self.hosts) + self.spare_ips
+    }
+
+    /// The `/24` of the `index`th host, derived from its id.
+    fn host_subnet(&self, index: usize) -> [u8; 2] {
+        let id = self.host_id(index);
+        let bytes = id.as_bytes();
+        [bytes[0], bytes[1]]
+    }
+
+    fn host_ip(&self, index: usize, last: u8) -> IpNetwork {
+        let [a, b] = self.host_subnet(index);
+        let ip = IpAddr::V4(Ipv4Addr::new(10, a, b, last));
+        ipnetwork::IpNetwork::from(ip).into()
+    }
+
+    /// Create the org, its hosts and nodes running `image` in `region`.
+    pub async fn create(
+        &self,
+        region: &Region,
+        protocol: &Protocol,
+        image: &Image,
+        conn: &mut Conn<'_>,
+    ) -> Result<OrgFixtures, Error> {
+        if self.nodes > 0 && self.hosts == 0 {
+            return Err(Error::NoHosts(self.name.to_string()));
+        }
+        if self.hosts > 0 && self.ips_per_host() > MAX_NODES_PER_HOST {
+            return Err(Error::TooManyNodes(self.name.to_string()));
+        }
+
+        let org = self.create_org(conn).await?;
+
+        let mut hosts = Vec::with_capacity(self.hosts);
+        for index in 0..self.hosts {
+            hosts.push(self.create_host(index, region, conn).await?);
+        }
+
+        let mut nodes = Vec::with_capacity(self.nodes);
+        for index in 0..self.nodes {
+            let host = &hosts[index % hosts.len()];
+            let ip_index = index / hosts.len();
+            let node = self
+                .create_node(index, host, ip_index, protocol, image, conn)
+                .await?;
+            nodes.push(node);
+        }
+
+        let org = Org::by_id(org.id, conn).await.map_err(Error::Org)?;
+        Ok(OrgFixtures { org, hosts, nodes })
+    }
+
+    /// Delete everything created by this fixture, if it exists.
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let org_id = self.org_id();
+        let host_ids: Vec<HostId> = (0..self.hosts).map(|i| self.host_id(i)).collect();
+        let node_ids: Vec<NodeId> = (0..self.nodes).map(|i| self.node_id(i)).collect();
+        let err = |err| Error::Delete(self.name.to_string(), err);
+
+        diesel::delete(commands::table.filter(commands::node_id.eq_any(&node_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+        diesel::delete(commands::table.filter(commands::host_id.eq_any(&host_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+        diesel::delete(node_reports::table.filter(node_reports::node_id.eq_any(&node_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+        diesel::delete(node_logs::table.filter(node_logs::host_id.eq_any(&host_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+
+        let config_ids: Vec<Uuid> =
+            diesel::delete(nodes::table.filter(nodes::id.eq_any(&node_ids)))
+                .returning(nodes::config_id)
+                .get_results(conn)
+                .await
+                .map_err(err)?;
+        diesel::delete(configs::table.filter(configs::id.eq_any(config_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+
+        diesel::delete(ip_addresses::table.filter(ip_addresses::host_id.eq_any(&host_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+        diesel::delete(hosts::table.filter(hosts::id.eq_any(&host_ids)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+        diesel::delete(orgs::table.find(org_id))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+
+        Ok(())
+    }
+
+    async fn create_org(&self, conn: &mut Conn<'_>) -> Result<Org, Error> {
+        let org_id = self.org_id();
+        diesel::insert_into(orgs::table)
+            .values((
+                orgs::id.eq(org_id),
+                orgs::name.eq(self.name),
+                orgs::is_personal.eq(false),
+                orgs::plan_tier.eq(PlanTier::Enterprise),
+            ))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Create(self.name.to_string(), err))?;
+
+        for &(user_id, role) in &self.members {
+            Org::add_user(user_id, org_id, role, conn)
+                .await
+                .map_err(Error::AddUser)?;
+        }
+
+        Org::by_id(org_id, conn).await.map_err(Error::Org)
+    }
+
+    async fn create_host(
+        &self,
+        index: usize,
+        region: &Region,
+        conn: &mut Conn<'_>,
+    ) -> Result<Host, Error> {
+        let org_id = self.org_id();
+        let host_id = self.host_id(index);
+        let network_name = format!("{}-host-{index}", self.name);
+        let bv_version = "0.1.0".parse().expect("valid version");
+        let gateway = self.host_ip(index, 1);
+
+        let new_host = NewHost {
+            org_id: Some(org_id),
+            region_id: region.id,
+            network_name: &network_name,
+            display_name: None,
+            schedule_type: ScheduleType::Automatic,
+            os: "FixtureOS",
+            os_version: "1",
+            bv_version: &bv_version,
+            ip_address: gateway,
+            ip_gateway: gateway,
+            cpu_cores: 64,
+            memory_bytes: 256 * MEMORY_BYTES,
+            disk_bytes: 64 * DISK_BYTES,
+            gpu_count: 0,
+            gpu_model: None,
+            nvme_iops: None,
+            network_bandwidth_mbps: None,
+            tags: vec![Tag::new(PROTOCOL_KEY.to_string()).expect("valid tag")].into(),
+            created_by_type: ResourceType::Org,
+            created_by_id: org_id.into(),
+        };
+
+        Org::add_host(org_id, conn).await.map_err(Error::AddHost)?;
+        let host: Host = diesel::insert_into(hosts::table)
+            .values((hosts::id.eq(host_id), new_host))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Create(network_name, err))?;
+
+        let ips = (0..self.ips_per_host())
+            .map(|i| NewIpAddress::new(self.host_ip(index, node_octet(i)), host.id))
+            .collect();
+        NewIpAddress::bulk_create(ips, conn)
+            .await
+            .map_err(Error::IpAddress)?;
+
+        Ok(host)
+    }
+
+    async fn create_node(
+        &self,
+        index: usize,
+        host: &Host,
+        ip_index: usize,
+        protocol: &Protocol,
+        image: &Image,
+        conn: &mut Conn<'_>,
+    ) -> Result<Node, Error> {
+        let org_id = self.org_id();
+        let node_id = self.node_id(index);
+        let node_name = format!("{}-node-{index}", self.name);
+        let host_index = index % self.hosts;
+
+        let (node_config, _) = NodeConfig::new(image.clone(), Some(org_id), vec![], vec![], conn)
+            .await
+            .map_err(Error::Config)?;
+        let new_config = NewConfig {
+            image_id: image.id,
+            archive_id: ARCHIVE_ID_1.parse().expect("valid archive id"),
+            config_type: ConfigType::Node,
+            config: node_config.into(),
+        };
+        let config = new_config
+            .create(&view_authz(node_id), conn)
+            .await
+            .map_err(Error::Config)?;
+
+        let node: Node = diesel::insert_into(nodes::table)
+            .values((
+                nodes::id.eq(node_id),
+                nodes::node_name.eq(&node_name),
+                nodes::display_name.eq(&node_name),
+                nodes::org_id.eq(org_id),
+                nodes::host_id.eq(host.id),
+                nodes::image_id.eq(image.id),
+                nodes::config_id.eq(config.id),
+                nodes::protocol_id.eq(protocol.id),
+                nodes::protocol_version_id.eq(image.protocol_version_id),
+                nodes::semantic_version.eq(SEMANTIC_VERSION),
+                nodes::auto_upgrade.eq(true),
+                nodes::node_state.eq(NodeState::Running),
+                nodes::ip_address.eq(self.host_ip(host_index, node_octet(ip_index))),
+                nodes::ip_gateway.eq(host.ip_gateway),
+                nodes::dns_id.eq(format!("{node_name}.dns.id")),
+                nodes::dns_name.eq(&node_name),
+                nodes::cpu_cores.eq(1),
+                nodes::memory_bytes.eq(MEMORY_BYTES),
+                nodes::disk_bytes.eq(DISK_BYTES),
+                nodes::scheduler_resource.eq(ResourceAffinity::LeastResources),
+                nodes::created_by_type.eq(ResourceType::Org),
+                nodes::created_by_id.eq(org_id),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Create(node_name, err))?;
+
+        Host::add_node(&node, conn)
+            .await
+            .map_err(Error::AddHostNode)?;
+        Org::add_node(org_id, conn)
+            .await
+            .map_err(Error::AddOrgNode)?;
+
+        Ok(node)
+    }
+}
+
+/// The last octet of the `index`th node ip of a host, after its gateway.
+fn node_octet(index: usize) -> u8 {
+    u8::try_from(index + 2).expect("at most MAX_NODES_PER_HOST nodes")
+}
+
+/// An image of `version` with its properties in groups of alternatives.
+pub struct ImageFixture<'a> {
+    name: &'a str,
+    version_id: VersionId,
+    build_version: i64,
+    groups: Vec<(&'a str, Vec<&'a str>)>,
+}
+
+/// The rows created by an `ImageFixture`.
+pub struct ImageFixtures {
+    pub image: ImageId,
+    pub properties: Vec<ImageProperty>,
+}
+
+impl<'a> ImageFixture<'a> {
+    pub const fn new(name: &'a str, version_id: VersionId, build_version: i64) -> Self {
+        ImageFixture {
+            name,
+            version_id,
+            build_version,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Add a group of property `keys`, of which the first is the default.
+    #[must_use]
+    pub fn property_group(mut self, group: &'a str, keys: Vec<&'a str>) -> Self {
+        self.groups.push((group, keys));
+        self
+    }
+
+    pub fn image_id(&self) -> ImageId {
+        fixture_id(self.name, "image", 0).into()
+    }
+
+    pub async fn create(&self, conn: &mut Conn<'_>) -> Result<ImageFixtures, Error> {
+        let image_id = self.image_id();
+        let mut queries = vec![format!(
+            "INSERT INTO images (id, org_id, protocol_version_id, image_uri, build_version, min_cpu_cores, min_memory_bytes, min_disk_bytes, default_firewall_in, default_firewall_out, visibility)
+            VALUES ('{image_id}', null, '{}', 'docker:{}', {}, 1, {MEMORY_BYTES}, {DISK_BYTES}, 'drop', 'allow', 'public');",
+            self.version_id, self.name, self.build_version,
+        )];
+
+        let mut index = 0;
+        for (group, keys) in &self.groups {
+            for (position, key) in keys.iter().enumerate() {
+                let property_id = fixture_id(self.name, "property", index);
+                let is_default = position == 0;
+                queries.push(format!(
+                    "INSERT INTO image_properties (id, image_id, key, key_group, is_group_default, new_archive, default_value, ui_type)
+                    VALUES ('{property_id}', '{image_id}', '{key}', '{group}', {is_default}, false, '{is_default}', 'switch');"
+                ));
+                index += 1;
+            }
+        }
+
+        for query in queries {
+            diesel::sql_query(query)
+                .execute(conn)
+                .await
+                .map_err(|err| Error::Create(self.name.to_string(), err))?;
+        }
+
+        let properties = ImageProperty::by_image_id(image_id, conn)
+            .await
+            .map_err(Error::Property)?;
+        Ok(ImageFixtures {
+            image: image_id,
+            properties,
+        })
+    }
+
+    /// Delete the image and its properties, if they exist.
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let image_id = self.image_id();
+        let err = |err| Error::Delete(self.name.to_string(), err);
+
+        diesel::delete(image_properties::table.filter(image_properties::image_id.eq(image_id)))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+        diesel::delete(images::table.find(image_id))
+            .execute(conn)
+            .await
+            .map_err(err)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixture_ids_are_stable_and_distinct() {
+        let fleet = OrgFixture::new("fleet").hosts(3).nodes(10);
+        assert_eq!(fleet.org_id(), OrgFixture::new("fleet").org_id());
+        assert_ne!(fleet.org_id(), OrgFixture::new("other").org_id());
+        assert_ne!(fleet.host_id(0), fleet.host_id(1));
+        assert_ne!(*fleet.host_id(0), *fleet.node_id(0));
+
+        assert_eq!(fleet.host_ip(0, 1), fleet.host_ip(0, 1));
+        assert_eq!(node_octet(0), 2);
+        assert_eq!(node_octet(MAX_NODES_PER_HOST - 1), 251);
+    }
+}
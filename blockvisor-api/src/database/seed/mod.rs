@@ -1,4 +1,11 @@
 //! Seed new test databases with data for integration testing.
+//!
+//! Every test database gets the same `Seed`. Larger data sets for load and UI
+//! testing are built from the `fixture` builders, and seeded by name as a
+//! `scenario`.
+
+pub mod fixture;
+pub mod scenario;
 
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -6,11 +13,13 @@ use diesel_async::RunQueryDsl;
 use crate::auth::rbac::access::tests::view_authz;
 use crate::auth::rbac::{BlockjoyRole, OrgRole, ViewRole};
 use crate::auth::resource::{NodeId, OrgId, ResourceType, UserId};
+use crate::config::password::Config as PasswordConfig;
 use crate::model::host::{Host, NewHost, ScheduleType};
 use crate::model::image::config::ConfigType;
 use crate::model::image::{Config, Image, ImageId, NewConfig, NodeConfig};
 use crate::model::ip_address::NewIpAddress;
 use crate::model::node::{Node, NodeState, ResourceAffinity};
+use crate::model::plan::PlanTier;
 use crate::model::protocol::version::{ProtocolVersion, VersionId};
 use crate::model::protocol::{Protocol, ProtocolId};
 use crate::model::rbac::RbacUser;
@@ -27,7 +36,7 @@ pub const ADMIN_EMAIL: &str = "admin@org.com";
 pub const MEMBER_EMAIL: &str = "member@org.com";
 pub const UNKNOWN_EMAIL: &str = "unknown@other.com";
 pub const UNCONFIRMED_EMAIL: &str = "unconfirmed@org.com";
-pub const LOGIN_PASSWORD: &str = "hunter2";
+pub const LOGIN_PASSWORD: &str = "correct-horse-battery-staple";
 
 pub const PROTOCOL_ID: &str = "ab5d8cfc-77b1-4265-9fee-ba71ba9de092";
 pub const PROTOCOL_KEY: &str = "ethereum";
@@ -61,6 +70,8 @@ pub const ARCHIVE_ID_2: &str = "e54dab10-5c62-4778-8aba-dc5162b48025";
 pub const STORE_KEY_1: &str = "store-1";
 pub const STORE_KEY_2: &str = "store-2";
 
+pub const REGION_KEY: &str = "the-moon";
+
 pub const HOST_1: &str = "host-1";
 pub const HOST_2: &str = "host-2";
 
@@ -182,6 +193,7 @@ async fn create_orgs(conn: &mut Conn<'_>) -> Org {
             orgs::name.eq("the blockboys"),
             orgs::is_personal.eq(false),
             orgs::stripe_customer_id.eq("testing testing, is thing thing even on?"),
+            orgs::plan_tier.eq(PlanTier::Enterprise),
         ))
         .execute(conn)
         .await
@@ -191,7 +203,9 @@ async fn create_orgs(conn: &mut Conn<'_>) -> Org {
 }
 
 async fn create_users(org_id: OrgId, conn: &mut Conn<'_>) -> (User, User, User) {
-    let new_user = |email, first, last| NewUser::new(email, first, last, LOGIN_PASSWORD).unwrap();
+    let policy = PasswordConfig::default();
+    let new_user =
+        |email, first, last| NewUser::new(email, first, last, LOGIN_PASSWORD, &policy).unwrap();
     let root = new_user(SUPER_EMAIL, "Super", "User")
         .create(conn)
         .await
@@ -241,7 +255,7 @@ async fn create_users(org_id: OrgId, conn: &mut Conn<'_>) -> (User, User, User)
 
 async fn create_region(conn: &mut Conn<'_>) -> Region {
     let region = NewRegion {
-        key: RegionKey::new("the-moon".into()).unwrap(),
+        key: RegionKey::new(REGION_KEY.into()).unwrap(),
         display_name: "to the moon",
         sku_code: None,
     };
@@ -270,6 +284,10 @@ async fn create_hosts(
         cpu_cores: 100,
         memory_bytes: 100 * MEMORY_BYTES,
         disk_bytes: 100 * DISK_BYTES,
+        gpu_count: 0,
+        gpu_model: None,
+        nvme_iops: None,
+        network_bandwidth_mbps: None,
         tags: vec![Tag::new(PROTOCOL_KEY.to_string()).unwrap()].into(),
         created_by_type: ResourceType::User,
         created_by_id: created_by_id.into(),
@@ -293,6 +311,10 @@ async fn create_hosts(
         cpu_cores: 1,
         memory_bytes: MEMORY_BYTES,
         disk_bytes: DISK_BYTES,
+        gpu_count: 0,
+        gpu_model: None,
+        nvme_iops: None,
+        network_bandwidth_mbps: None,
         tags: vec![Tag::new(PROTOCOL_KEY.to_string()).unwrap()].into(),
         created_by_type: ResourceType::User,
         created_by_id: created_by_id.into(),
@@ -334,7 +356,7 @@ async fn create_node(
     let image_id: ImageId = IMAGE_ID.parse().unwrap();
     let version_id: VersionId = PROTOCOL_VERSION_ID.parse().unwrap();
 
-    let node_config = NodeConfig::new(image.clone(), Some(org_id), vec![], vec![], conn)
+    let (node_config, _) = NodeConfig::new(image.clone(), Some(org_id), vec![], vec![], conn)
         .await
         .unwrap();
     let new_config = NewConfig {
@@ -384,37 +406,94 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         insert into role_permissions (role, permission)
         values
         -- blockjoy-admin --
+        ('blockjoy-admin', 'abuse-admin-list'),
+        ('blockjoy-admin', 'abuse-admin-review'),
+        ('blockjoy-admin', 'artifact-admin-download'),
+        ('blockjoy-admin', 'artifact-admin-list'),
         ('blockjoy-admin', 'auth-admin-list-permissions'),
+        ('blockjoy-admin', 'billing-estimate'),
         ('blockjoy-admin', 'billing-exempt'),
+        ('blockjoy-admin', 'command-admin-cancel'),
         ('blockjoy-admin', 'command-admin-list'),
+        ('blockjoy-admin', 'command-admin-list-upcoming'),
         ('blockjoy-admin', 'command-admin-pending'),
+        ('blockjoy-admin', 'endpoint-admin-report-usage'),
+        ('blockjoy-admin', 'endpoint-admin-usage'),
+        ('blockjoy-admin', 'host-admin-create-pool'),
         ('blockjoy-admin', 'host-admin-create-region'),
         ('blockjoy-admin', 'host-admin-delete-host'),
+        ('blockjoy-admin', 'host-admin-delete-pool'),
+        ('blockjoy-admin', 'host-admin-get-agent-upgrade'),
         ('blockjoy-admin', 'host-admin-get-host'),
+        ('blockjoy-admin', 'host-admin-get-pool'),
+        ('blockjoy-admin', 'host-admin-halt-agent-upgrade'),
         ('blockjoy-admin', 'host-admin-list-hosts'),
+        ('blockjoy-admin', 'host-admin-list-pools'),
+        ('blockjoy-admin', 'host-admin-list-reconciliations'),
         ('blockjoy-admin', 'host-admin-list-regions'),
+        ('blockjoy-admin', 'host-admin-region-availability'),
         ('blockjoy-admin', 'host-admin-restart'),
+        ('blockjoy-admin', 'host-admin-set-host-pool'),
         ('blockjoy-admin', 'host-admin-start'),
         ('blockjoy-admin', 'host-admin-stop'),
         ('blockjoy-admin', 'host-admin-update-host'),
+        ('blockjoy-admin', 'host-admin-update-pool'),
         ('blockjoy-admin', 'host-admin-update-region'),
+        ('blockjoy-admin', 'host-admin-upgrade-agent'),
+        ('blockjoy-admin', 'host-admin-upgrade-outdated'),
+        ('blockjoy-admin', 'host-admin-version-report'),
         ('blockjoy-admin', 'host-admin-view-cost'),
         ('blockjoy-admin', 'image-admin-add'),
         ('blockjoy-admin', 'image-admin-get'),
         ('blockjoy-admin', 'image-admin-list-archives'),
+        ('blockjoy-admin', 'image-admin-review-version'),
         ('blockjoy-admin', 'image-admin-update-archive'),
         ('blockjoy-admin', 'image-admin-update-image'),
+        ('blockjoy-admin', 'incident-admin-comment'),
+        ('blockjoy-admin', 'incident-admin-create'),
+        ('blockjoy-admin', 'incident-admin-get'),
+        ('blockjoy-admin', 'incident-admin-link'),
+        ('blockjoy-admin', 'incident-admin-list'),
+        ('blockjoy-admin', 'incident-admin-update'),
+        ('blockjoy-admin', 'internal-admin-cleanup-dns'),
+        ('blockjoy-admin', 'internal-admin-cleanup-stripe'),
+        ('blockjoy-admin', 'internal-admin-clear-legal-hold'),
+        ('blockjoy-admin', 'internal-admin-get-host-weights'),
+        ('blockjoy-admin', 'internal-admin-list-backfills'),
+        ('blockjoy-admin', 'internal-admin-list-feature-flags'),
+        ('blockjoy-admin', 'internal-admin-list-legal-holds'),
+        ('blockjoy-admin', 'internal-admin-list-network-heads'),
+        ('blockjoy-admin', 'internal-admin-list-table-retention'),
+        ('blockjoy-admin', 'internal-admin-override-node-state'),
+        ('blockjoy-admin', 'internal-admin-run-backfill'),
+        ('blockjoy-admin', 'internal-admin-search-hosts'),
+        ('blockjoy-admin', 'internal-admin-search-nodes'),
+        ('blockjoy-admin', 'internal-admin-set-feature-flag'),
+        ('blockjoy-admin', 'internal-admin-set-host-weights'),
+        ('blockjoy-admin', 'internal-admin-set-legal-hold'),
+        ('blockjoy-admin', 'internal-admin-set-network-head'),
         ('blockjoy-admin', 'invitation-admin-create'),
         ('blockjoy-admin', 'invitation-admin-list'),
         ('blockjoy-admin', 'invitation-admin-revoke'),
         ('blockjoy-admin', 'mqtt-admin-acl'),
+        ('blockjoy-admin', 'node-admin-add-dns-alias'),
+        ('blockjoy-admin', 'node-admin-bulk-update'),
+        ('blockjoy-admin', 'node-admin-collect-debug-bundle'),
         ('blockjoy-admin', 'node-admin-create'),
         ('blockjoy-admin', 'node-admin-delete'),
         ('blockjoy-admin', 'node-admin-get'),
+        ('blockjoy-admin', 'node-admin-get-certificate'),
         ('blockjoy-admin', 'node-admin-list'),
+        ('blockjoy-admin', 'node-admin-list-disk-resizes'),
+        ('blockjoy-admin', 'node-admin-remove-dns-alias'),
+        ('blockjoy-admin', 'node-admin-rename'),
         ('blockjoy-admin', 'node-admin-report-error'),
         ('blockjoy-admin', 'node-admin-report-status'),
+        ('blockjoy-admin', 'node-admin-request-certificate'),
+        ('blockjoy-admin', 'node-admin-resize'),
+        ('blockjoy-admin', 'node-admin-resize-disk'),
         ('blockjoy-admin', 'node-admin-restart'),
+        ('blockjoy-admin', 'node-admin-review-disk-resize'),
         ('blockjoy-admin', 'node-admin-start'),
         ('blockjoy-admin', 'node-admin-stop'),
         ('blockjoy-admin', 'node-admin-transfer'),
@@ -426,25 +505,44 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('blockjoy-admin', 'org-address-set'),
         ('blockjoy-admin', 'org-admin-get'),
         ('blockjoy-admin', 'org-admin-list'),
+        ('blockjoy-admin', 'org-admin-reinstate'),
+        ('blockjoy-admin', 'org-admin-start-trial'),
+        ('blockjoy-admin', 'org-admin-suspend'),
         ('blockjoy-admin', 'org-admin-update'),
         ('blockjoy-admin', 'org-billing-get-billing-details'),
         ('blockjoy-admin', 'org-billing-init-card'),
         ('blockjoy-admin', 'org-billing-list-payment-methods'),
+        ('blockjoy-admin', 'org-billing-list-plans'),
+        ('blockjoy-admin', 'org-billing-update-plan'),
+        ('blockjoy-admin', 'org-freeze-override'),
+        ('blockjoy-admin', 'partner-admin-create'),
+        ('blockjoy-admin', 'partner-admin-list'),
+        ('blockjoy-admin', 'partner-admin-revenue'),
+        ('blockjoy-admin', 'protocol-admin-add-network'),
         ('blockjoy-admin', 'protocol-admin-add-protocol'),
         ('blockjoy-admin', 'protocol-admin-add-version'),
+        ('blockjoy-admin', 'protocol-admin-delete-protocol'),
         ('blockjoy-admin', 'protocol-admin-get-pricing'),
         ('blockjoy-admin', 'protocol-admin-get-protocol'),
         ('blockjoy-admin', 'protocol-admin-get-latest'),
+        ('blockjoy-admin', 'protocol-admin-grant-access'),
+        ('blockjoy-admin', 'protocol-admin-list-grants'),
+        ('blockjoy-admin', 'protocol-admin-list-networks'),
         ('blockjoy-admin', 'protocol-admin-list-protocols'),
         ('blockjoy-admin', 'protocol-admin-list-variants'),
         ('blockjoy-admin', 'protocol-admin-list-versions'),
+        ('blockjoy-admin', 'protocol-admin-revoke-access'),
         ('blockjoy-admin', 'protocol-admin-update-protocol'),
         ('blockjoy-admin', 'protocol-admin-update-version'),
         ('blockjoy-admin', 'protocol-admin-view-all-stats'),
         ('blockjoy-admin', 'protocol-admin-view-private'),
         ('blockjoy-admin', 'protocol-get-pricing'),
+        ('blockjoy-admin', 'report-admin-uptime'),
+        ('blockjoy-admin', 'support-admin-impersonate'),
+        ('blockjoy-admin', 'support-admin-lookup-request'),
         ('blockjoy-admin', 'user-admin-filter'),
         ('blockjoy-admin', 'user-admin-get'),
+        ('blockjoy-admin', 'user-admin-require-password-reset'),
         ('blockjoy-admin', 'user-admin-update'),
         ('blockjoy-admin', 'user-settings-admin-delete'),
         ('blockjoy-admin', 'user-settings-admin-get'),
@@ -453,6 +551,8 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('email-invitation', 'invitation-accept'),
         ('email-invitation', 'invitation-decline'),
         ('email-invitation', 'user-create'),
+        -- email-org-transfer --
+        ('email-org-transfer', 'org-confirm-transfer'),
         -- email-registration-confirmation --
         ('email-registration-confirmation', 'auth-confirm'),
         -- email-reset-password --
@@ -462,7 +562,9 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('grpc-login', 'api-key-delete'),
         ('grpc-login', 'api-key-list'),
         ('grpc-login', 'auth-list-permissions'),
+        ('grpc-login', 'auth-list-sessions'),
         ('grpc-login', 'auth-refresh'),
+        ('grpc-login', 'auth-revoke-session'),
         ('grpc-login', 'auth-update-ui-password'),
         ('grpc-login', 'bundle-list-versions'),
         ('grpc-login', 'bundle-retrieve'),
@@ -489,23 +591,34 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('grpc-login', 'protocol-get-protocol'),
         ('grpc-login', 'protocol-get-latest'),
         ('grpc-login', 'protocol-get-pricing'),
+        ('grpc-login', 'protocol-list-networks'),
         ('grpc-login', 'protocol-list-protocols'),
         ('grpc-login', 'protocol-list-variants'),
         ('grpc-login', 'protocol-list-versions'),
         ('grpc-login', 'protocol-view-public'),
         ('grpc-login', 'user-create'),
         ('grpc-login', 'user-delete'),
+        ('grpc-login', 'user-delete-account'),
+        ('grpc-login', 'user-export-data'),
         ('grpc-login', 'user-filter'),
         ('grpc-login', 'user-get'),
+        ('grpc-login', 'user-get-notification-preferences'),
+        ('grpc-login', 'user-grant-support-access'),
+        ('grpc-login', 'user-list-devices'),
+        ('grpc-login', 'user-register-device'),
+        ('grpc-login', 'user-remove-device'),
+        ('grpc-login', 'user-revoke-support-access'),
         ('grpc-login', 'user-settings-delete'),
         ('grpc-login', 'user-settings-get'),
         ('grpc-login', 'user-settings-update'),
         ('grpc-login', 'user-update'),
+        ('grpc-login', 'user-update-notification-preferences'),
         -- grpc-new-host --
         ('grpc-new-host', 'archive-get-download-chunks'),
         ('grpc-new-host', 'archive-get-download-metadata'),
         ('grpc-new-host', 'archive-get-upload-slots'),
         ('grpc-new-host', 'archive-put-download-manifest'),
+        ('grpc-new-host', 'artifact-create-upload'),
         ('grpc-new-host', 'auth-refresh'),
         ('grpc-new-host', 'bundle-list-versions'),
         ('grpc-new-host', 'bundle-retrieve'),
@@ -521,9 +634,13 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('grpc-new-host', 'host-get-host'),
         ('grpc-new-host', 'host-list-hosts'),
         ('grpc-new-host', 'host-list-regions'),
+        ('grpc-new-host', 'host-report-inventory'),
         ('grpc-new-host', 'host-update-host'),
         ('grpc-new-host', 'image-get'),
         ('grpc-new-host', 'image-list-archives'),
+        ('grpc-new-host', 'mesh-get-config'),
+        ('grpc-new-host', 'mesh-join'),
+        ('grpc-new-host', 'mesh-leave'),
         ('grpc-new-host', 'metrics-host'),
         ('grpc-new-host', 'metrics-node'),
         ('grpc-new-host', 'mqtt-acl'),
@@ -533,6 +650,7 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('grpc-new-host', 'node-list'),
         ('grpc-new-host', 'node-report-error'),
         ('grpc-new-host', 'node-report-status'),
+        ('grpc-new-host', 'node-resize-disk'),
         ('grpc-new-host', 'node-restart'),
         ('grpc-new-host', 'node-start'),
         ('grpc-new-host', 'node-stop'),
@@ -540,6 +658,7 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('grpc-new-host', 'node-upgrade'),
         ('grpc-new-host', 'protocol-get-protocol'),
         ('grpc-new-host', 'protocol-get-latest'),
+        ('grpc-new-host', 'protocol-list-networks'),
         ('grpc-new-host', 'protocol-list-protocols'),
         ('grpc-new-host', 'protocol-list-variants'),
         ('grpc-new-host', 'protocol-list-versions'),
@@ -551,67 +670,228 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('org-owner', 'org-billing-get-billing-details'),
         ('org-owner', 'org-billing-init-card'),
         ('org-owner', 'org-billing-list-payment-methods'),
+        ('org-owner', 'org-billing-update-plan'),
         ('org-owner', 'org-delete'),
+        ('org-owner', 'org-freeze-override'),
+        ('org-owner', 'org-restore'),
+        ('org-owner', 'org-transfer-ownership'),
         -- org-admin --
+        ('org-admin', 'alert-create-schedule'),
+        ('org-admin', 'alert-delete-schedule'),
+        ('org-admin', 'alert-set-policy'),
+        ('org-admin', 'approval-review'),
+        ('org-admin', 'billing-estimate'),
         ('org-admin', 'crypt-get-secret'),
         ('org-admin', 'crypt-put-secret'),
+        ('org-admin', 'endpoint-create'),
+        ('org-admin', 'endpoint-revoke'),
         ('org-admin', 'host-billing-get'),
+        ('org-admin', 'host-create-pool'),
         ('org-admin', 'host-delete-host'),
+        ('org-admin', 'host-delete-pool'),
         ('org-admin', 'host-provision-create'),
         ('org-admin', 'host-provision-get'),
+        ('org-admin', 'host-set-host-pool'),
+        ('org-admin', 'host-update-pool'),
+        ('org-admin', 'image-push-version'),
         ('org-admin', 'invitation-create'),
         ('org-admin', 'invitation-revoke'),
+        ('org-admin', 'managed-host-deprovision'),
+        ('org-admin', 'managed-host-provision'),
+        ('org-admin', 'managed-host-set-credentials'),
+        ('org-admin', 'mesh-leave'),
+        ('org-admin', 'node-add-dns-alias'),
+        ('org-admin', 'node-bulk-update'),
         ('org-admin', 'node-create'),
         ('org-admin', 'node-delete'),
+        ('org-admin', 'node-remove-dns-alias'),
+        ('org-admin', 'node-rename'),
+        ('org-admin', 'node-request-certificate'),
+        ('org-admin', 'node-resize'),
+        ('org-admin', 'node-resize-disk'),
+        ('org-admin', 'node-review-disk-resize'),
+        ('org-admin', 'node-share-create'),
+        ('org-admin', 'node-share-revoke'),
+        ('org-admin', 'org-add-project-member'),
         ('org-admin', 'org-address-delete'),
         ('org-admin', 'org-address-get'),
         ('org-admin', 'org-address-set'),
         ('org-admin', 'org-billing-get-billing-details'),
         ('org-admin', 'org-billing-init-card'),
         ('org-admin', 'org-billing-list-payment-methods'),
+        ('org-admin', 'org-billing-update-plan'),
+        ('org-admin', 'org-branding-update'),
+        ('org-admin', 'org-create-freeze-window'),
+        ('org-admin', 'org-create-project'),
+        ('org-admin', 'org-delete-freeze-window'),
+        ('org-admin', 'org-delete-project'),
+        ('org-admin', 'org-export-configuration'),
+        ('org-admin', 'org-import-configuration'),
+        ('org-admin', 'org-key-create'),
+        ('org-admin', 'org-key-revoke'),
+        ('org-admin', 'org-key-rotate'),
+        ('org-admin', 'org-log-sink-delete'),
+        ('org-admin', 'org-log-sink-set'),
         ('org-admin', 'org-remove-member'),
+        ('org-admin', 'org-remove-project-member'),
         ('org-admin', 'org-update'),
+        ('org-admin', 'org-update-project'),
+        ('org-admin', 'pool-create'),
+        ('org-admin', 'pool-delete'),
+        ('org-admin', 'pool-update'),
         ('org-admin', 'protocol-get-pricing'),
+        ('org-admin', 'protocol-grant-access'),
+        ('org-admin', 'protocol-list-grants'),
+        ('org-admin', 'protocol-revoke-access'),
+        ('org-admin', 'status-page-create'),
+        ('org-admin', 'status-page-delete'),
+        ('org-admin', 'status-page-verify-domain'),
+        ('org-admin', 'team-add-member'),
+        ('org-admin', 'team-add-project'),
+        ('org-admin', 'team-create'),
+        ('org-admin', 'team-delete'),
+        ('org-admin', 'team-get'),
+        ('org-admin', 'team-grant-role'),
+        ('org-admin', 'team-list'),
+        ('org-admin', 'team-remove-member'),
+        ('org-admin', 'team-remove-project'),
+        ('org-admin', 'team-revoke-role'),
+        ('org-admin', 'team-update'),
+        ('org-admin', 'ticket-create-integration'),
+        ('org-admin', 'ticket-delete-integration'),
+        ('org-admin', 'ticket-get-integration'),
         -- org-member --
+        ('org-member', 'alert-acknowledge'),
+        ('org-member', 'alert-get-oncall'),
+        ('org-member', 'alert-list'),
+        ('org-member', 'approval-cancel'),
+        ('org-member', 'approval-get'),
+        ('org-member', 'approval-list'),
+        ('org-member', 'artifact-download'),
+        ('org-member', 'artifact-list'),
+        ('org-member', 'command-cancel'),
+        ('org-member', 'command-list-upcoming'),
+        ('org-member', 'endpoint-list'),
+        ('org-member', 'endpoint-usage'),
         ('org-member', 'host-get-host'),
+        ('org-member', 'host-get-pool'),
         ('org-member', 'host-list-hosts'),
+        ('org-member', 'host-list-pools'),
+        ('org-member', 'host-list-reconciliations'),
         ('org-member', 'host-list-regions'),
+        ('org-member', 'host-region-availability'),
         ('org-member', 'host-restart'),
         ('org-member', 'host-start'),
         ('org-member', 'host-stop'),
+        ('org-member', 'incident-comment'),
+        ('org-member', 'incident-get'),
+        ('org-member', 'incident-list'),
+        ('org-member', 'managed-host-get'),
+        ('org-member', 'managed-host-list'),
+        ('org-member', 'mesh-list-peers'),
+        ('org-member', 'node-collect-debug-bundle'),
         ('org-member', 'node-get'),
+        ('org-member', 'node-get-certificate'),
         ('org-member', 'node-list'),
+        ('org-member', 'node-list-disk-resizes'),
         ('org-member', 'node-report-error'),
         ('org-member', 'node-restart'),
+        ('org-member', 'node-share-accesses'),
+        ('org-member', 'node-share-list'),
         ('org-member', 'node-start'),
         ('org-member', 'node-stop'),
         ('org-member', 'node-update-config'),
+        ('org-member', 'org-billing-list-plans'),
+        ('org-member', 'org-branding-get'),
         ('org-member', 'org-create'),
         ('org-member', 'org-get'),
+        ('org-member', 'org-get-project'),
+        ('org-member', 'org-key-list'),
+        ('org-member', 'org-log-sink-get'),
         ('org-member', 'org-list'),
+        ('org-member', 'org-list-freeze-windows'),
+        ('org-member', 'org-list-projects'),
         ('org-member', 'org-provision-get-token'),
         ('org-member', 'org-provision-reset-token'),
         ('org-member', 'org-remove-self'),
+        ('org-member', 'pool-get'),
+        ('org-member', 'pool-list'),
+        ('org-member', 'report-uptime'),
+        ('org-member', 'status-page-get'),
+        ('org-member', 'status-page-list'),
+        ('org-member', 'team-get'),
+        ('org-member', 'team-list'),
         -- org-personal --
+        ('org-personal', 'alert-acknowledge'),
+        ('org-personal', 'alert-create-schedule'),
+        ('org-personal', 'alert-delete-schedule'),
+        ('org-personal', 'alert-get-oncall'),
+        ('org-personal', 'alert-list'),
+        ('org-personal', 'alert-set-policy'),
+        ('org-personal', 'approval-cancel'),
+        ('org-personal', 'approval-get'),
+        ('org-personal', 'approval-list'),
+        ('org-personal', 'artifact-download'),
+        ('org-personal', 'artifact-list'),
+        ('org-personal', 'billing-estimate'),
+        ('org-personal', 'command-cancel'),
+        ('org-personal', 'command-list-upcoming'),
         ('org-personal', 'crypt-get-secret'),
         ('org-personal', 'crypt-put-secret'),
+        ('org-personal', 'endpoint-create'),
+        ('org-personal', 'endpoint-list'),
+        ('org-personal', 'endpoint-revoke'),
+        ('org-personal', 'endpoint-usage'),
         ('org-personal', 'host-billing-get'),
+        ('org-personal', 'host-create-pool'),
         ('org-personal', 'host-delete-host'),
+        ('org-personal', 'host-delete-pool'),
         ('org-personal', 'host-get-host'),
+        ('org-personal', 'host-get-pool'),
         ('org-personal', 'host-list-hosts'),
+        ('org-personal', 'host-list-pools'),
+        ('org-personal', 'host-list-reconciliations'),
         ('org-personal', 'host-list-regions'),
+        ('org-personal', 'host-region-availability'),
         ('org-personal', 'host-provision-create'),
         ('org-personal', 'host-provision-get'),
         ('org-personal', 'host-restart'),
+        ('org-personal', 'host-set-host-pool'),
         ('org-personal', 'host-start'),
         ('org-personal', 'host-stop'),
+        ('org-personal', 'host-update-pool'),
+        ('org-personal', 'incident-comment'),
+        ('org-personal', 'incident-get'),
+        ('org-personal', 'incident-list'),
+        ('org-personal', 'managed-host-deprovision'),
+        ('org-personal', 'managed-host-get'),
+        ('org-personal', 'managed-host-list'),
+        ('org-personal', 'managed-host-provision'),
+        ('org-personal', 'managed-host-set-credentials'),
+        ('org-personal', 'mesh-leave'),
+        ('org-personal', 'mesh-list-peers'),
+        ('org-personal', 'node-add-dns-alias'),
+        ('org-personal', 'node-bulk-update'),
+        ('org-personal', 'node-collect-debug-bundle'),
         ('org-personal', 'node-create'),
         ('org-personal', 'node-delete'),
         ('org-personal', 'node-get'),
+        ('org-personal', 'node-get-certificate'),
         ('org-personal', 'node-list'),
+        ('org-personal', 'node-list-disk-resizes'),
+        ('org-personal', 'node-remove-dns-alias'),
+        ('org-personal', 'node-rename'),
         ('org-personal', 'node-report-error'),
         ('org-personal', 'node-report-status'),
+        ('org-personal', 'node-request-certificate'),
+        ('org-personal', 'node-resize'),
+        ('org-personal', 'node-resize-disk'),
         ('org-personal', 'node-restart'),
+        ('org-personal', 'node-review-disk-resize'),
+        ('org-personal', 'node-share-accesses'),
+        ('org-personal', 'node-share-create'),
+        ('org-personal', 'node-share-list'),
+        ('org-personal', 'node-share-revoke'),
         ('org-personal', 'node-start'),
         ('org-personal', 'node-stop'),
         ('org-personal', 'node-update-config'),
@@ -621,13 +901,49 @@ async fn setup_rbac(conn: &mut Conn<'_>) {
         ('org-personal', 'org-billing-get-billing-details'),
         ('org-personal', 'org-billing-init-card'),
         ('org-personal', 'org-billing-list-payment-methods'),
+        ('org-personal', 'org-billing-list-plans'),
+        ('org-personal', 'org-billing-update-plan'),
+        ('org-personal', 'org-branding-get'),
+        ('org-personal', 'org-branding-update'),
         ('org-personal', 'org-create'),
+        ('org-personal', 'org-create-freeze-window'),
+        ('org-personal', 'org-create-project'),
+        ('org-personal', 'org-delete-freeze-window'),
+        ('org-personal', 'org-delete-project'),
+        ('org-personal', 'org-freeze-override'),
         ('org-personal', 'org-get'),
+        ('org-personal', 'org-get-project'),
+        ('org-personal', 'org-key-create'),
+        ('org-personal', 'org-key-list'),
+        ('org-personal', 'org-key-revoke'),
+        ('org-personal', 'org-key-rotate'),
+        ('org-personal', 'org-log-sink-delete'),
+        ('org-personal', 'org-log-sink-get'),
+        ('org-personal', 'org-log-sink-set'),
         ('org-personal', 'org-list'),
+        ('org-personal', 'org-list-freeze-windows'),
+        ('org-personal', 'org-list-projects'),
         ('org-personal', 'org-provision-get-token'),
         ('org-personal', 'org-provision-reset-token'),
         ('org-personal', 'org-update'),
+        ('org-personal', 'org-update-project'),
+        ('org-personal', 'pool-create'),
+        ('org-personal', 'pool-delete'),
+        ('org-personal', 'pool-get'),
+        ('org-personal', 'pool-list'),
+        ('org-personal', 'pool-update'),
         ('org-personal', 'protocol-get-pricing'),
+        ('org-personal', 'report-uptime'),
+        ('org-personal', 'status-page-create'),
+        ('org-personal', 'status-page-delete'),
+        ('org-personal', 'status-page-get'),
+        ('org-personal', 'status-page-list'),
+        ('org-personal', 'status-page-verify-domain'),
+        ('org-personal', 'team-get'),
+        ('org-personal', 'team-list'),
+        ('org-personal', 'ticket-create-integration'),
+        ('org-personal', 'ticket-delete-integration'),
+        ('org-personal', 'ticket-get-integration'),
         -- view-developer-preview --
         ('view-developer-preview', 'protocol-view-development');
         ";
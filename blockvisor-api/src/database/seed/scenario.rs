@@ -0,0 +1,186 @@
+//! Named sets of fixtures that can be seeded into a running test api.
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use strum::{EnumIter, EnumString, IntoStaticStr};
+use thiserror::Error;
+
+use crate::auth::rbac::OrgRole;
+use crate::auth::resource::{HostId, NodeId, OrgId};
+use crate::database::Conn;
+use crate::grpc::Status;
+use crate::model::image::{Image, ImageId};
+use crate::model::protocol::version::VersionId;
+use crate::model::region::{Region, RegionKey};
+use crate::model::schema::{images, protocols};
+use crate::model::{Protocol, ProtocolId, User};
+
+use super::fixture::{self, ImageFixture, OrgFixture};
+use super::{ADMIN_EMAIL, IMAGE_ID, PROTOCOL_ID, PROTOCOL_VERSION_ID, REGION_KEY};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Scenario fixture error: {0}
+    Fixture(#[from] fixture::Error),
+    /// Failed to find seed image: {0}
+    Image(diesel::result::Error),
+    /// Failed to find seed protocol: {0}
+    Protocol(diesel::result::Error),
+    /// Failed to find seed region: {0}
+    Region(crate::model::region::Error),
+    /// Unknown scenario: {0}
+    UnknownScenario(String),
+    /// Failed to find seed user: {0}
+    User(crate::model::user::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            UnknownScenario(_) => Status::invalid_argument("scenario"),
+            Fixture(_) | Image(_) | Protocol(_) | Region(_) | User(_) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+/// A set of fixtures, identified by its kebab-case name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Scenario {
+    /// One org with 30 nodes on 3 hosts, administered by the seed admin.
+    SmallFleet,
+    /// Five orgs with 200 nodes on 10 hosts each.
+    LargeFleet,
+    /// An image of the seed protocol version with grouped properties.
+    ImageGroups,
+}
+
+/// The rows created by a `Scenario`.
+#[derive(Debug, Default)]
+pub struct Seeded {
+    pub org_ids: Vec<OrgId>,
+    pub host_ids: Vec<HostId>,
+    pub node_ids: Vec<NodeId>,
+    pub image_ids: Vec<ImageId>,
+}
+
+impl Scenario {
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        name.parse()
+            .map_err(|_| Error::UnknownScenario(name.to_string()))
+    }
+
+    /// Create the fixtures of this scenario.
+    pub async fn seed(self, conn: &mut Conn<'_>) -> Result<Seeded, Error> {
+        let mut seeded = Seeded::default();
+
+        let (orgs, images) = self.fixtures(conn).await?;
+        if !orgs.is_empty() {
+            let (region, protocol, image) = seed_image(conn).await?;
+            for org in orgs {
+                let created = org.create(&region, &protocol, &image, conn).await?;
+                seeded.org_ids.push(created.org.id);
+                seeded
+                    .host_ids
+                    .extend(created.hosts.iter().map(|host| host.id));
+                seeded
+                    .node_ids
+                    .extend(created.nodes.iter().map(|node| node.id));
+            }
+        }
+        for image in images {
+            seeded.image_ids.push(image.create(conn).await?.image);
+        }
+
+        Ok(seeded)
+    }
+
+    /// Delete the fixtures of this scenario, including any changes to them.
+    pub async fn delete(self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let (orgs, images) = self.fixtures(conn).await?;
+        for org in orgs {
+            org.delete(conn).await?;
+        }
+        for image in images {
+            image.delete(conn).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete and seed this scenario again, back to its initial state.
+    pub async fn reset(self, conn: &mut Conn<'_>) -> Result<Seeded, Error> {
+        self.delete(conn).await?;
+        self.seed(conn).await
+    }
+
+    async fn fixtures(
+        self,
+        conn: &mut Conn<'_>,
+    ) -> Result<(Vec<OrgFixture<'static>>, Vec<ImageFixture<'static>>), Error> {
+        const LARGE_FLEET: [&str; 5] = [
+            "large-fleet-1",
+            "large-fleet-2",
+            "large-fleet-3",
+            "large-fleet-4",
+            "large-fleet-5",
+        ];
+
+        match self {
+            Scenario::SmallFleet => {
+                let admin = User::by_email(ADMIN_EMAIL, conn)
+                    .await
+                    .map_err(Error::User)?;
+                let org = OrgFixture::new("small-fleet")
+                    .hosts(3)
+                    .nodes(30)
+                    .member(admin.id, OrgRole::Admin);
+                Ok((vec![org], vec![]))
+            }
+            Scenario::LargeFleet => {
+                let orgs = LARGE_FLEET
+                    .into_iter()
+                    .map(|name| OrgFixture::new(name).hosts(10).nodes(200))
+                    .collect();
+                Ok((orgs, vec![]))
+            }
+            Scenario::ImageGroups => {
+                let version_id: VersionId = PROTOCOL_VERSION_ID.parse().expect("valid id");
+                let image = ImageFixture::new("image-groups", version_id, 100)
+                    .property_group("client", vec!["geth", "erigon", "reth"])
+                    .property_group("mode", vec!["full", "archive"]);
+                Ok((vec![], vec![image]))
+            }
+        }
+    }
+}
+
+/// The region, protocol and image of `Seed` that fixture nodes run on.
+async fn seed_image(conn: &mut Conn<'_>) -> Result<(Region, Protocol, Image), Error> {
+    let key = RegionKey::new(REGION_KEY.into()).expect("valid key");
+    let region = Region::by_key(&key, conn).await.map_err(Error::Region)?;
+
+    let protocol_id: ProtocolId = PROTOCOL_ID.parse().expect("valid id");
+    let protocol = protocols::table
+        .find(protocol_id)
+        .get_result(conn)
+        .await
+        .map_err(Error::Protocol)?;
+
+    let image_id: ImageId = IMAGE_ID.parse().expect("valid id");
+    let image = images::table
+        .find(image_id)
+        .get_result(conn)
+        .await
+        .map_err(Error::Image)?;
+
+    Ok((region, protocol, image))
+}
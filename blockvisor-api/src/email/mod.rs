@@ -4,6 +4,7 @@ pub use template::{Kind, Language, Templates};
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use chrono::NaiveDate;
 use displaydoc::Display;
 use sendgrid::v3;
 use thiserror::Error;
@@ -16,10 +17,18 @@ use crate::auth::resource::Resource;
 use crate::auth::token::Cipher;
 use crate::config::Config;
 use crate::config::token::ExpireChrono;
-use crate::model::{Invitation, User};
+use crate::model::node::NodeSlo;
+use crate::model::user::setting::UserLocale;
+use crate::model::{
+    Alert, Approval, Artifact, Backorder, Invitation, Node, Org, OrgBranding, OrgTransfer, User,
+};
 
 const FROM_EMAIL: &str = "no-reply@blockjoy.com";
 const FROM_NAME: &str = "BlockJoy";
+const LOGO_URL: &str =
+    "https://cdn.jsdelivr.net/npm/blockjoy-assets@1.0.3/images/blockjoy_transparent.png";
+const PRIMARY_COLOR: &str = "#bff589";
+const ACCENT_COLOR: &str = "#212423";
 
 #[tonic::async_trait]
 pub trait Sender {
@@ -41,6 +50,7 @@ pub struct Email {
     templates: Templates,
     cipher: Arc<Cipher>,
     base_url: Url,
+    api_url: Url,
     expires: ExpireChrono,
 }
 
@@ -58,6 +68,7 @@ impl Email {
         let sender = Box::new(v3::Sender::new(sendgrid_api_key.clone(), None));
         let templates = Templates::new(template_dir)?;
         let base_url = config.email.ui_base_url.clone();
+        let api_url = config.cloud.api_url.clone();
         let expires = config.token.expire;
 
         Ok(Some(Email {
@@ -65,6 +76,7 @@ impl Email {
             templates,
             cipher,
             base_url,
+            api_url,
             expires,
         }))
     }
@@ -74,6 +86,7 @@ impl Email {
         let sender = Box::new(tests::MockEmail {});
         let templates = Templates::new(config.email.template_dir.as_deref().unwrap())?;
         let base_url = config.email.ui_base_url.clone();
+        let api_url = config.cloud.api_url.clone();
         let expires = config.token.expire;
 
         Ok(Email {
@@ -81,18 +94,21 @@ impl Email {
             templates,
             cipher,
             base_url,
+            api_url,
             expires,
         })
     }
 
     /// Sends a confirmation if the user has updated their password.
-    pub async fn update_password(&self, user: &User) -> Result<(), Error> {
-        self.send(Kind::UpdatePassword, user, None).await
+    pub async fn update_password(&self, user: &User, locale: &UserLocale) -> Result<(), Error> {
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::UpdatePassword, recipient, None, None).await
     }
 
     pub async fn registration_confirmation(
         &self,
         user: &User,
+        locale: &UserLocale,
         invitation_id: Option<Uuid>,
     ) -> Result<(), Error> {
         let expires = self.expires.registration_confirmation;
@@ -108,8 +124,14 @@ impl Email {
             "link" => format!("{base}/verified?token={}", *token),
         };
 
-        self.send(Kind::RegistrationConfirmation, user, Some(context))
-            .await
+        let recipient = Recipient::localized(user, locale);
+        self.send(
+            Kind::RegistrationConfirmation,
+            recipient,
+            Some(context),
+            None,
+        )
+        .await
     }
 
     pub async fn invitation_for_registered<S>(
@@ -117,6 +139,7 @@ impl Email {
         invitation: &Invitation,
         inviter: String,
         invitee: &User,
+        locale: &UserLocale,
         expiration: S,
     ) -> Result<(), Error>
     where
@@ -130,7 +153,8 @@ impl Email {
             "expiration" => expiration.to_string()
         };
 
-        self.send(Kind::InviteRegistered, invitee, Some(context))
+        let recipient = Recipient::localized(invitee, locale);
+        self.send(Kind::InviteRegistered, recipient, Some(context), None)
             .await
     }
 
@@ -161,12 +185,13 @@ impl Email {
             "expiration" => expiration.to_string(),
         };
 
-        self.send(Kind::InviteUser, invitee, Some(context)).await
+        self.send(Kind::InviteUser, invitee, Some(context), None)
+            .await
     }
 
     /// Sends a password reset email to the specified user containing a JWT that
     /// they can use to authenticate themselves to reset their password.
-    pub async fn reset_password(&self, user: &User) -> Result<(), Error> {
+    pub async fn reset_password(&self, user: &User, locale: &UserLocale) -> Result<(), Error> {
         let expires = self.expires.password_reset;
         let mut claims = Claims::from_now(expires, user.id, EmailRole::ResetPassword);
         claims.data = Some(hashmap! {
@@ -179,25 +204,271 @@ impl Email {
             "link" => format!("{base}/password-reset?token={}", *token)
         };
 
-        self.send(Kind::ResetPassword, user, Some(context)).await
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::ResetPassword, recipient, Some(context), None)
+            .await
+    }
+
+    /// Asks one party of an org ownership transfer to confirm it.
+    ///
+    /// Both the current owner and the new owner receive their own token.
+    pub async fn org_transfer(
+        &self,
+        transfer: &OrgTransfer,
+        org: &Org,
+        requester: &User,
+        new_owner: &User,
+        recipient: &User,
+        locale: &UserLocale,
+    ) -> Result<(), Error> {
+        let expires = self.expires.org_transfer;
+        let data = hashmap! {
+            "transfer_id".to_string() => transfer.id.to_string(),
+        };
+        let claims =
+            Claims::from_now(expires, recipient.id, EmailRole::OrgTransfer).with_data(data);
+        let token = self.cipher.jwt.encode(&claims).map_err(Error::EncodeJwt)?;
+
+        let base = &self.base_url;
+        let context = hashmap! {
+            "requester" => requester.name(),
+            "new_owner" => new_owner.name(),
+            "org_name" => org.name.clone(),
+            "link" => format!("{base}/confirm-org-transfer?token={}", *token),
+        };
+
+        let recipient = Recipient::localized(recipient, locale);
+        self.send(Kind::OrgTransfer, recipient, Some(context), None)
+            .await
+    }
+
+    /// Asks an org admin to review an action that needs a second approval.
+    pub async fn approval_requested(
+        &self,
+        approval: &Approval,
+        org: &Org,
+        requester: &str,
+        recipient: &User,
+        locale: &UserLocale,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let context = hashmap! {
+            "requester" => requester.to_string(),
+            "action" => approval.action.describe().to_string(),
+            "org_name" => org.name.clone(),
+            "expires_at" => locale.date_time(approval.expires_at),
+            "link" => format!("{base}/orgs/{}/approvals?approval_id={}", org.id, approval.id),
+        };
+
+        let recipient = Recipient::localized(recipient, locale);
+        self.send(Kind::ApprovalRequested, recipient, Some(context), None)
+            .await
+    }
+
+    /// Tells a user that the nodes they backordered have been created.
+    pub async fn backorder_fulfilled(
+        &self,
+        org: &Org,
+        node_count: usize,
+        user: &User,
+        locale: &UserLocale,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let context = hashmap! {
+            "org_name" => org.name.clone(),
+            "node_count" => node_count.to_string(),
+            "link" => format!("{base}/orgs/{}/nodes", org.id),
+        };
+
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::BackorderFulfilled, recipient, Some(context), None)
+            .await
+    }
+
+    /// Tells a user that no capacity freed up for their backorder in time.
+    pub async fn backorder_expired(
+        &self,
+        backorder: &Backorder,
+        org: &Org,
+        user: &User,
+        locale: &UserLocale,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let context = hashmap! {
+            "org_name" => org.name.clone(),
+            "created_at" => locale.date_time(backorder.created_at),
+            "link" => format!("{base}/orgs/{}/nodes", org.id),
+        };
+
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::BackorderExpired, recipient, Some(context), None)
+            .await
+    }
+
+    /// Confirms to a user that their account has been deleted.
+    ///
+    /// This must be sent before the user's email address is anonymized.
+    pub async fn account_deleted(&self, user: &User, locale: &UserLocale) -> Result<(), Error> {
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::AccountDeleted, recipient, None, None).await
+    }
+
+    /// Sends an org owner the uptime of the org nodes over a past month.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn uptime_report(
+        &self,
+        owner: &User,
+        locale: &UserLocale,
+        org: &Org,
+        branding: Option<&OrgBranding>,
+        month: NaiveDate,
+        slos: &[NodeSlo],
+        target_percent: f64,
+    ) -> Result<(), Error> {
+        let uptime_percent = NodeSlo::combined_percent(slos).unwrap_or(100.0);
+        let below_target = slos
+            .iter()
+            .filter(|slo| slo.uptime_percent() < target_percent)
+            .count();
+
+        let base = &self.base_url;
+        let context = hashmap! {
+            "org_name" => org.name.clone(),
+            "month" => month.format("%B %Y").to_string(),
+            "node_count" => slos.len().to_string(),
+            "uptime_percent" => format!("{uptime_percent:.2}"),
+            "target_percent" => target_percent.to_string(),
+            "nodes_below_target" => below_target.to_string(),
+            "link" => format!("{base}/reports/uptime?org_id={}&month={}", org.id, month.format("%Y-%m")),
+        };
+
+        let recipient = Recipient::localized(owner, locale);
+        self.send(Kind::UptimeReport, recipient, Some(context), branding)
+            .await
+    }
+
+    /// Reminds the owner of an org on trial to choose a plan before it ends.
+    pub async fn trial_ending(
+        &self,
+        owner: &User,
+        locale: &UserLocale,
+        org: &Org,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let ends_at = org
+            .trial_ends_at
+            .map(|at| locale.date(at))
+            .unwrap_or_default();
+        let context = hashmap! {
+            "org_name" => org.name.clone(),
+            "ends_at" => ends_at,
+            "link" => format!("{base}/orgs/{}/billing", org.id),
+        };
+
+        let recipient = Recipient::localized(owner, locale);
+        self.send(Kind::TrialEnding, recipient, Some(context), None)
+            .await
+    }
+
+    /// Tells the owner of an org that it was hibernated at the end of its trial.
+    pub async fn trial_ended(
+        &self,
+        owner: &User,
+        locale: &UserLocale,
+        org: &Org,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let context = hashmap! {
+            "org_name" => org.name.clone(),
+            "link" => format!("{base}/orgs/{}/billing", org.id),
+        };
+
+        let recipient = Recipient::localized(owner, locale);
+        self.send(Kind::TrialEnded, recipient, Some(context), None)
+            .await
+    }
+
+    /// Pages the on-call user of an org about an alert nobody acknowledged.
+    pub async fn oncall_alert(
+        &self,
+        user: &User,
+        locale: &UserLocale,
+        org: &Org,
+        branding: Option<&OrgBranding>,
+        alert: &Alert,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let context = hashmap! {
+            "org_name" => org.name.clone(),
+            "message" => alert.message.clone(),
+            "raised_at" => locale.date_time(alert.created_at),
+            "link" => format!("{base}/orgs/{}/alerts?alert_id={}", org.id, alert.id),
+        };
+
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::OncallAlert, recipient, Some(context), branding)
+            .await
+    }
+
+    /// Tells a user that the debug bundle they requested for a node is ready.
+    pub async fn debug_bundle(
+        &self,
+        user: &User,
+        locale: &UserLocale,
+        node: &Node,
+        artifact: &Artifact,
+    ) -> Result<(), Error> {
+        let base = &self.base_url;
+        let context = hashmap! {
+            "node_name" => node.display_name.clone(),
+            "link" => format!("{base}/nodes/{}/artifacts/{}", node.id, artifact.id),
+        };
+
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::DebugBundle, recipient, Some(context), None)
+            .await
     }
 
-    async fn send<'r, R>(
+    /// Sends a user a presigned link to download their data export.
+    pub async fn user_export(
+        &self,
+        user: &User,
+        locale: &UserLocale,
+        link: &Url,
+    ) -> Result<(), Error> {
+        let context = hashmap! {
+            "link" => link.to_string(),
+        };
+
+        let recipient = Recipient::localized(user, locale);
+        self.send(Kind::UserExport, recipient, Some(context), None)
+            .await
+    }
+
+    /// Send an email, branded by the org that it is about if given.
+    async fn send(
         &self,
         kind: Kind,
-        recipient: R,
+        recipient: Recipient<'_>,
         context: Option<HashMap<&'static str, String>>,
-    ) -> Result<(), Error>
-    where
-        R: Into<Recipient<'r>> + Send,
-    {
-        let recipient = recipient.into();
+        branding: Option<&OrgBranding>,
+    ) -> Result<(), Error> {
         let name = recipient.name();
         let lang = recipient.preferred_language.unwrap_or(Language::En);
-        let template = self.templates.render(kind, lang, context)?;
+        let mut context = context.unwrap_or_default();
+        context.extend(self.brand(branding));
+        let template = self.templates.render(kind, lang, Some(context))?;
+
+        let sender_name = branding
+            .and_then(|branding| branding.sender_name.as_ref())
+            .map(|name| name.as_str());
+        let subject = sender_name.map_or_else(
+            || kind.subject().to_string(),
+            |sender| kind.subject().replacen(FROM_NAME, sender, 1),
+        );
 
         let to = v3::Email::new(recipient.email).set_name(name);
-        let from = v3::Email::new(FROM_EMAIL).set_name(FROM_NAME);
+        let from = v3::Email::new(FROM_EMAIL).set_name(sender_name.unwrap_or(FROM_NAME));
         let text = v3::Content::new()
             .set_content_type("text/plain")
             .set_value(template.text);
@@ -206,7 +477,7 @@ impl Email {
             .set_value(template.html);
         let mail = v3::Message::new(from)
             .add_personalization(v3::Personalization::new(to))
-            .set_subject(kind.subject())
+            .set_subject(&subject)
             .add_content(text)
             .add_content(html)
             .set_tracking_settings(Self::tracking_settings());
@@ -214,6 +485,21 @@ impl Email {
         self.sender.send_mail(mail).await
     }
 
+    /// The template context of the brand, where unset parts fall back to ours.
+    fn brand(&self, branding: Option<&OrgBranding>) -> HashMap<&'static str, String> {
+        let name = branding.and_then(|branding| branding.sender_name.clone());
+        let logo_url = branding.and_then(|branding| branding.logo_url(&self.api_url));
+        let primary = branding.and_then(|branding| branding.primary_color.clone());
+        let accent = branding.and_then(|branding| branding.accent_color.clone());
+
+        hashmap! {
+            "brand_name" => name.map_or_else(|| FROM_NAME.to_string(), Into::into),
+            "logo_url" => logo_url.unwrap_or_else(|| LOGO_URL.to_string()),
+            "primary_color" => primary.map_or_else(|| PRIMARY_COLOR.to_string(), Into::into),
+            "accent_color" => accent.map_or_else(|| ACCENT_COLOR.to_string(), Into::into),
+        }
+    }
+
     const fn tracking_settings() -> v3::TrackingSettings {
         v3::TrackingSettings {
             click_tracking: Some(v3::ClickTrackingSetting {
@@ -240,21 +526,20 @@ pub struct Recipient<'r> {
     pub preferred_language: Option<Language>,
 }
 
-impl Recipient<'_> {
-    pub fn name(&self) -> String {
-        format!("{} {}", self.first_name, self.last_name)
-    }
-}
-
-impl<'r> From<&'r User> for Recipient<'r> {
-    fn from(user: &'r User) -> Self {
+impl<'r> Recipient<'r> {
+    /// A registered user, in the language of their locale.
+    pub fn localized(user: &'r User, locale: &UserLocale) -> Self {
         Recipient {
             first_name: &user.first_name,
             last_name: &user.last_name,
             email: &user.email,
-            preferred_language: Some(Language::En),
+            preferred_language: Some(locale.language()),
         }
     }
+
+    pub fn name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name)
+    }
 }
 
 #[cfg(any(test, feature = "integration-test"))]
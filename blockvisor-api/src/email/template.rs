@@ -8,11 +8,22 @@ use handlebars::{Context, Handlebars};
 use serde::Deserialize;
 use thiserror::Error;
 
+const ACCOUNT_DELETED: &str = "account_deleted.toml";
+const APPROVAL_REQUESTED: &str = "approval_requested.toml";
+const BACKORDER_EXPIRED: &str = "backorder_expired.toml";
+const BACKORDER_FULFILLED: &str = "backorder_fulfilled.toml";
+const DEBUG_BUNDLE: &str = "debug_bundle.toml";
 const INVITE_USER: &str = "invite_user.toml";
 const INVITE_REGISTERED: &str = "invite_registered_user.toml";
+const ONCALL_ALERT: &str = "oncall_alert.toml";
+const ORG_TRANSFER: &str = "org_transfer.toml";
 const REGISTRATION_CONFIRMATION: &str = "register.toml";
 const RESET_PASSWORD: &str = "reset_password.toml";
+const TRIAL_ENDED: &str = "trial_ended.toml";
+const TRIAL_ENDING: &str = "trial_ending.toml";
 const UPDATE_PASSWORD: &str = "update_password.toml";
+const UPTIME_REPORT: &str = "uptime_report.toml";
+const USER_EXPORT: &str = "user_export.toml";
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -46,21 +57,43 @@ pub enum Language {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Kind {
+    AccountDeleted,
+    ApprovalRequested,
+    BackorderExpired,
+    BackorderFulfilled,
+    DebugBundle,
     InviteUser,
     InviteRegistered,
+    OncallAlert,
+    OrgTransfer,
     RegistrationConfirmation,
     ResetPassword,
+    TrialEnded,
+    TrialEnding,
     UpdatePassword,
+    UptimeReport,
+    UserExport,
 }
 
 impl Kind {
     pub const fn subject(self) -> &'static str {
         match self {
+            Kind::AccountDeleted => "[BlockJoy] Account Deleted",
+            Kind::ApprovalRequested => "[BlockJoy] Approval Requested",
+            Kind::BackorderExpired => "[BlockJoy] Your Backorder Has Expired",
+            Kind::BackorderFulfilled => "[BlockJoy] Your Backordered Nodes Are Ready",
+            Kind::DebugBundle => "[BlockJoy] Node Debug Bundle Ready",
             Kind::InviteUser => "[BlockJoy] Organization Invite",
             Kind::InviteRegistered => "[BlockJoy] Organization Invite",
+            Kind::OncallAlert => "[BlockJoy] Unacknowledged Alert",
+            Kind::OrgTransfer => "[BlockJoy] Confirm Organization Transfer",
             Kind::RegistrationConfirmation => "[BlockJoy] Verify Your Account",
             Kind::ResetPassword => "[BlockJoy] Reset Password",
+            Kind::TrialEnded => "[BlockJoy] Your Trial Has Ended",
+            Kind::TrialEnding => "[BlockJoy] Your Trial Ends Soon",
             Kind::UpdatePassword => "[BlockJoy] Password Updated",
+            Kind::UptimeReport => "[BlockJoy] Monthly Uptime Report",
+            Kind::UserExport => "[BlockJoy] Your Data Export",
         }
     }
 }
@@ -90,11 +123,22 @@ impl Templates {
         }
 
         let kinds = [
+            (Kind::AccountDeleted, ACCOUNT_DELETED),
+            (Kind::ApprovalRequested, APPROVAL_REQUESTED),
+            (Kind::BackorderExpired, BACKORDER_EXPIRED),
+            (Kind::BackorderFulfilled, BACKORDER_FULFILLED),
+            (Kind::DebugBundle, DEBUG_BUNDLE),
             (Kind::InviteUser, INVITE_USER),
             (Kind::InviteRegistered, INVITE_REGISTERED),
+            (Kind::OncallAlert, ONCALL_ALERT),
+            (Kind::OrgTransfer, ORG_TRANSFER),
             (Kind::RegistrationConfirmation, REGISTRATION_CONFIRMATION),
             (Kind::ResetPassword, RESET_PASSWORD),
+            (Kind::TrialEnded, TRIAL_ENDED),
+            (Kind::TrialEnding, TRIAL_ENDING),
             (Kind::UpdatePassword, UPDATE_PASSWORD),
+            (Kind::UptimeReport, UPTIME_REPORT),
+            (Kind::UserExport, USER_EXPORT),
         ];
 
         let templates = kinds
@@ -161,6 +205,8 @@ impl Templates {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
     use chrono::DateTime;
     use uuid::Uuid;
 
@@ -169,19 +215,29 @@ mod test {
     use crate::config::Config;
     use crate::email::tests::MockEmail;
     use crate::email::{Email, Recipient};
-    use crate::model::{Invitation, User};
+    use crate::encryption::Keyring;
+    use crate::model::agent_upgrade::AgentUpgradePolicy;
+    use crate::model::approval::{Approval, ApprovalAction, ApprovalStatus};
+    use crate::model::backorder::{Backorder, BackorderStatus};
+    use crate::model::node::{NodeNameCharset, NodeSlo};
+    use crate::model::org_branding::{HexColor, OrgBranding, SenderName};
+    use crate::model::plan::PlanTier;
+    use crate::model::user::setting::UserLocale;
+    use crate::model::{Invitation, Org, OrgTransfer, User};
 
     use super::*;
 
     #[tokio::test]
     async fn test_render_emails() {
         let config = Config::new().unwrap();
-        let auth = Auth::new(&config.token);
+        let keyring = Keyring::new(&config.encryption).unwrap();
+        let auth = Auth::new(&config.token, Arc::new(keyring));
         let email = Email {
             sender: Box::new(MockEmail {}),
             templates: Templates::new(config.email.template_dir.as_ref().unwrap()).unwrap(),
             cipher: auth.cipher,
             base_url: config.email.ui_base_url.clone(),
+            api_url: config.cloud.api_url.clone(),
             expires: config.token.expire,
         };
 
@@ -195,8 +251,11 @@ mod test {
             last_name: "Tester".to_string(),
             confirmed_at: None,
             deleted_at: None,
+            password_reset_required: false,
+            support_access_until: None,
         };
-        let recipient = Recipient::from(&user);
+        let locale = UserLocale::default();
+        let recipient = Recipient::localized(&user, &locale);
         let invitation = Invitation {
             id: Uuid::new_v4().into(),
             org_id: Uuid::new_v4().into(),
@@ -208,17 +267,135 @@ mod test {
             declined_at: None,
         };
         let inviter = "Mahatma Gandhi".to_string();
+        let org = Org {
+            id: Uuid::new_v4().into(),
+            name: "Test Org".to_string(),
+            is_personal: false,
+            created_at: DateTime::default(),
+            updated_at: DateTime::default(),
+            deleted_at: None,
+            host_count: 0,
+            node_count: 0,
+            member_count: 2,
+            stripe_customer_id: None,
+            address_id: None,
+            purged_at: None,
+            agent_upgrade_policy: AgentUpgradePolicy::default(),
+            disk_quota_bytes: None,
+            disk_auto_approve_bytes: None,
+            plan_tier: PlanTier::default(),
+            stripe_plan_item_id: None,
+            trial_ends_at: None,
+            trial_max_nodes: None,
+            trial_reminded_at: None,
+            hibernated_at: None,
+            partner_id: None,
+            referred_at: None,
+            suspended_at: None,
+            suspension_reason: None,
+            suspension_banner: None,
+            node_display_name_max_len: None,
+            node_note_max_len: None,
+            node_display_name_unique: false,
+            node_display_name_charset: NodeNameCharset::default(),
+            require_approval_node_delete: false,
+            require_approval_node_upgrade: false,
+            require_approval_org_transfer: false,
+            stripe_customer_index: None,
+        };
+        let transfer = OrgTransfer {
+            id: Uuid::new_v4().into(),
+            org_id: org.id,
+            old_owner_id: user.id,
+            new_owner_id: user.id,
+            old_owner_confirmed_at: None,
+            new_owner_confirmed_at: None,
+            completed_at: None,
+            created_at: DateTime::default(),
+        };
 
-        email.update_password(&user).await.unwrap();
-        email.registration_confirmation(&user, None).await.unwrap();
+        email.update_password(&user, &locale).await.unwrap();
+        email
+            .registration_confirmation(&user, &locale, None)
+            .await
+            .unwrap();
         email
-            .invitation_for_registered(&invitation, inviter.clone(), &user, "tomorrow")
+            .invitation_for_registered(&invitation, inviter.clone(), &user, &locale, "tomorrow")
             .await
             .unwrap();
         email
             .invitation(&invitation, inviter, recipient, "yesterday")
             .await
             .unwrap();
-        email.reset_password(&user).await.unwrap();
+        email.reset_password(&user, &locale).await.unwrap();
+        email
+            .org_transfer(&transfer, &org, &user, &user, &user, &locale)
+            .await
+            .unwrap();
+        let approval = Approval {
+            id: Uuid::new_v4().into(),
+            org_id: org.id,
+            action: ApprovalAction::NodeDelete,
+            request: vec![],
+            status: ApprovalStatus::Pending,
+            requested_by_type: ResourceType::User,
+            requested_by_id: Uuid::new_v4().into(),
+            reviewed_by_type: None,
+            reviewed_by_id: None,
+            created_at: DateTime::default(),
+            expires_at: DateTime::default(),
+            reviewed_at: None,
+        };
+        email
+            .approval_requested(&approval, &org, "Luuk Tester", &user, &locale)
+            .await
+            .unwrap();
+        let backorder = Backorder {
+            id: Uuid::new_v4().into(),
+            org_id: org.id,
+            user_id: user.id,
+            request: vec![],
+            status: BackorderStatus::Pending,
+            last_error: None,
+            created_at: DateTime::default(),
+            expires_at: DateTime::default(),
+            resolved_at: None,
+        };
+        email
+            .backorder_fulfilled(&org, 2, &user, &locale)
+            .await
+            .unwrap();
+        email
+            .backorder_expired(&backorder, &org, &user, &locale)
+            .await
+            .unwrap();
+        let link = "https://example.com/user-export.json".parse().unwrap();
+        email.user_export(&user, &locale, &link).await.unwrap();
+        let month = chrono::NaiveDate::from_ymd_opt(2025, 2, 1).unwrap();
+        let slos = [NodeSlo {
+            node_id: Uuid::new_v4().into(),
+            org_id: org.id,
+            month,
+            total_seconds: 1000,
+            downtime_seconds: 2,
+            updated_at: DateTime::default(),
+        }];
+        email
+            .uptime_report(&user, &locale, &org, None, month, &slos, 99.9)
+            .await
+            .unwrap();
+        let branding = OrgBranding {
+            org_id: org.id,
+            sender_name: Some(SenderName::new("Acme Nodes").unwrap()),
+            primary_color: Some(HexColor::new("#0055ff").unwrap()),
+            accent_color: None,
+            logo_key: Some(format!("{}/1.png", org.id)),
+            updated_at: DateTime::default(),
+        };
+        email
+            .uptime_report(&user, &locale, &org, Some(&branding), month, &slos, 99.9)
+            .await
+            .unwrap();
+        email.account_deleted(&user, &locale).await.unwrap();
     }
 }
@@ -0,0 +1,236 @@
+//! Application-level envelope encryption of sensitive database columns.
+//!
+//! Each value is encrypted with its own random data key, which is in turn
+//! encrypted with a key encryption key from `config::encryption`. The sealed
+//! value records the id of that key, so keys are rotated by adding a new
+//! `current_key` and running the `encrypt-*` backfills to re-seal existing rows
+//! before the old key is removed.
+//!
+//! The `Keyring` is built from config along with the `Context`. Encrypted
+//! columns are read and written as `model::sql::Encrypted` values that stay
+//! sealed in memory until they are opened with the `Keyring` of the context.
+//!
+//! Values written before a column was encrypted are read back as plaintext
+//! until a backfill re-seals them.
+//!
+//! As sealed values can't be compared in a query, a column that is looked up
+//! by value also stores its `blind_index`, a keyed hash of the plaintext.
+
+use std::collections::HashMap;
+use std::string::FromUtf8Error;
+
+use base64::engine::{Engine as _, general_purpose::STANDARD_NO_PAD};
+use displaydoc::Display;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::error::Unspecified;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use thiserror::Error;
+use zeroize::Zeroizing;
+
+use crate::config::encryption::{Config, KEY_BYTES};
+
+/// The prefix of every sealed value, followed by `{key_id}:{data_key}:{value}`.
+const SEALED_PREFIX: &str = "enc:v1:";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to decode sealed value: {0}
+    Decode(base64::DecodeError),
+    /// Failed to decrypt sealed value.
+    Decrypt,
+    /// Failed to encrypt value.
+    Encrypt,
+    /// Encryption key `{0}` is not a valid AES-256 key.
+    InvalidKey(String),
+    /// Sealed value is malformed.
+    Malformed,
+    /// Decrypted value is not utf8: {0}
+    Utf8(FromUtf8Error),
+    /// Unknown encryption key `{0}`.
+    UnknownKey(String),
+}
+
+/// The key encryption keys that seal and open sensitive column values.
+pub struct Keyring {
+    keys: HashMap<String, LessSafeKey>,
+    current_key: String,
+    index_key: hmac::Key,
+    rng: SystemRandom,
+}
+
+impl Keyring {
+    pub fn new(config: &Config) -> Result<Self, Error> {
+        let keys = config
+            .keys
+            .iter()
+            .map(|(id, key)| {
+                new_key(key)
+                    .map(|key| (id.clone(), key))
+                    .map_err(|_| Error::InvalidKey(id.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Keyring {
+            keys,
+            current_key: config.current_key.clone(),
+            index_key: hmac::Key::new(hmac::HMAC_SHA256, &config.index_key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Encrypt `plaintext` with a new data key sealed by the current key.
+    pub fn seal(&self, plaintext: &str) -> Result<String, Error> {
+        let key = self
+            .keys
+            .get(&self.current_key)
+            .ok_or_else(|| Error::UnknownKey(self.current_key.clone()))?;
+
+        let mut data_key = Zeroizing::new([0; KEY_BYTES]);
+        self.rng
+            .fill(&mut data_key[..])
+            .map_err(|_| Error::Encrypt)?;
+        let value_key = new_key(&data_key[..]).map_err(|_| Error::Encrypt)?;
+        let value = encrypt(&value_key, &self.rng, plaintext.as_bytes())?;
+        let wrapped = encrypt(key, &self.rng, &data_key[..])?;
+
+        Ok(format!(
+            "{SEALED_PREFIX}{}:{}:{}",
+            self.current_key,
+            STANDARD_NO_PAD.encode(wrapped),
+            STANDARD_NO_PAD.encode(value),
+        ))
+    }
+
+    /// Decrypt a value returned by `seal` with any of the configured keys.
+    ///
+    /// A value that was never sealed is returned as is.
+    pub fn open(&self, sealed: &str) -> Result<String, Error> {
+        let Some(sealed) = sealed.strip_prefix(SEALED_PREFIX) else {
+            return Ok(sealed.to_string());
+        };
+
+        let mut parts = sealed.splitn(3, ':');
+        let (Some(key_id), Some(wrapped), Some(value)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::Malformed);
+        };
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| Error::UnknownKey(key_id.to_string()))?;
+
+        let wrapped = STANDARD_NO_PAD.decode(wrapped).map_err(Error::Decode)?;
+        let data_key = Zeroizing::new(decrypt(key, &wrapped)?);
+        let value = STANDARD_NO_PAD.decode(value).map_err(Error::Decode)?;
+        let value_key = new_key(&data_key).map_err(|_| Error::Malformed)?;
+        let plaintext = decrypt(&value_key, &value)?;
+
+        String::from_utf8(plaintext).map_err(Error::Utf8)
+    }
+
+    /// A keyed hash of `plaintext` that equal values can be looked up by.
+    pub fn blind_index(&self, plaintext: &str) -> String {
+        let tag = hmac::sign(&self.index_key, plaintext.as_bytes());
+        STANDARD_NO_PAD.encode(tag.as_ref())
+    }
+}
+
+fn new_key(bytes: &[u8]) -> Result<LessSafeKey, Unspecified> {
+    UnboundKey::new(&AES_256_GCM, bytes).map(LessSafeKey::new)
+}
+
+/// Encrypt `plaintext` under a random nonce, returning the nonce followed by
+/// the ciphertext and tag.
+fn encrypt(key: &LessSafeKey, rng: &SystemRandom, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut nonce = [0; NONCE_LEN];
+    rng.fill(&mut nonce).map_err(|_| Error::Encrypt)?;
+
+    let mut in_out = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut in_out,
+    )
+    .map_err(|_| Error::Encrypt)?;
+
+    Ok([&nonce[..], &in_out].concat())
+}
+
+fn decrypt(key: &LessSafeKey, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    if bytes.len() < NONCE_LEN {
+        return Err(Error::Malformed);
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| Error::Malformed)?;
+
+    let mut in_out = ciphertext.to_vec();
+    key.open_in_place(nonce, Aad::empty(), &mut in_out)
+        .map(|plaintext| plaintext.to_vec())
+        .map_err(|_| Error::Decrypt)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+
+    use crate::config::Redacted;
+    use crate::config::encryption::{IndexKey, Keys};
+
+    use super::*;
+
+    fn keyring(keys: &[(&str, u8)], current_key: &str) -> Keyring {
+        let keys = keys
+            .iter()
+            .map(|(id, byte)| format!("{id}={}", STANDARD.encode([*byte; KEY_BYTES])))
+            .collect::<Vec<_>>()
+            .join(",");
+        let index_key = STANDARD.encode([0; KEY_BYTES]);
+        let config = Config {
+            keys: Redacted::from(keys.parse::<Keys>().unwrap()),
+            current_key: current_key.to_string(),
+            index_key: Redacted::from(index_key.parse::<IndexKey>().unwrap()),
+        };
+        Keyring::new(&config).unwrap()
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let keyring = keyring(&[("one", 1)], "one");
+        let sealed = keyring.seal("cus_123").unwrap();
+        assert!(sealed.starts_with("enc:v1:one:"));
+        assert!(!sealed.contains("cus_123"));
+        assert_ne!(sealed, keyring.seal("cus_123").unwrap());
+        assert_eq!(keyring.open(&sealed).unwrap(), "cus_123");
+
+        // values written before encryption are read as is
+        assert_eq!(keyring.open("cus_123").unwrap(), "cus_123");
+    }
+
+    #[test]
+    fn rotated_keys_still_open() {
+        let old = keyring(&[("one", 1)], "one");
+        let sealed = old.seal("secret").unwrap();
+
+        let rotated = keyring(&[("one", 1), ("two", 2)], "two");
+        assert_eq!(rotated.open(&sealed).unwrap(), "secret");
+        assert!(rotated.seal("secret").unwrap().starts_with("enc:v1:two:"));
+
+        let removed = keyring(&[("two", 2)], "two");
+        assert!(matches!(removed.open(&sealed), Err(Error::UnknownKey(_))));
+
+        let wrong = keyring(&[("one", 3)], "one");
+        assert!(matches!(wrong.open(&sealed), Err(Error::Decrypt)));
+    }
+
+    #[test]
+    fn blind_index_survives_rotation() {
+        let old = keyring(&[("one", 1)], "one");
+        let rotated = keyring(&[("one", 1), ("two", 2)], "two");
+
+        let index = old.blind_index("cus_123");
+        assert!(!index.contains("cus_123"));
+        assert_eq!(index, rotated.blind_index("cus_123"));
+        assert_ne!(index, old.blind_index("cus_456"));
+    }
+}
@@ -0,0 +1,132 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::AbuseAdminPerm;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::Node;
+use crate::model::node::abuse::{self, AbuseReport, AbuseResolution};
+use crate::model::node::{NodeState, UpdateNodeState};
+
+use super::api::abuse_service_server::AbuseService;
+use super::{Grpc, Metadata, Status, api, common};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Abuse model error: {0}
+    Abuse(#[from] crate::model::node::abuse::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Abuse review must be made by a user.
+    ClaimsNotUser,
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Abuse node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Abuse node grpc error: {0}
+    NodeGrpc(#[from] crate::grpc::node::Error),
+    /// Failed to parse AbuseReportId: {0}
+    ParseId(uuid::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ClaimsNotUser => Status::forbidden("Access denied."),
+            ParseId(_) => Status::invalid_argument("report_id"),
+            Abuse(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Node(err) => err.into(),
+            NodeGrpc(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl AbuseService for Grpc {
+    async fn list(
+        &self,
+        req: Request<api::AbuseServiceListRequest>,
+    ) -> Result<Response<api::AbuseServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn review(
+        &self,
+        req: Request<api::AbuseServiceReviewRequest>,
+    ) -> Result<Response<api::AbuseServiceReviewResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| review(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+/// The queue of abuse reports waiting for review, oldest first.
+pub async fn list(
+    req: api::AbuseServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::AbuseServiceListResponse, Error> {
+    read.auth(&meta, AbuseAdminPerm::List).await?;
+
+    let reports = AbuseReport::list(req.include_reviewed, &mut read).await?;
+
+    Ok(api::AbuseServiceListResponse {
+        reports: reports.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Resolve an abuse report.
+///
+/// Dismissing a report lifts the suspension of its node, which stays stopped
+/// until it is started again.
+pub async fn review(
+    req: api::AbuseServiceReviewRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AbuseServiceReviewResponse, Error> {
+    let authz = write.auth(&meta, AbuseAdminPerm::Review).await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let id = req.report_id.parse().map_err(Error::ParseId)?;
+    let resolution = AbuseResolution::try_from(req.resolution())?;
+    let report = AbuseReport::by_id(id, &mut write)
+        .await?
+        .review(user_id, resolution, &mut write)
+        .await?;
+
+    if resolution == AbuseResolution::Dismissed {
+        let node = Node::by_id(report.node_id, &mut write).await?;
+        abuse::set_pegged_since(node.id, None, &mut write).await?;
+
+        if node.node_state == NodeState::Suspended {
+            let update = UpdateNodeState {
+                node_state: Some(NodeState::Stopped),
+                next_state: Some(None),
+                protocol_state: None,
+                protocol_health: None,
+                p2p_address: None,
+            };
+            let node = update.force(node.id, &mut write).await?;
+
+            let node = api::Node::from_model(node, &authz, &mut write).await?;
+            let updated_by = common::Resource::from(&authz);
+            write.mqtt(api::NodeMessage::updated(node, updated_by));
+        }
+    }
+
+    Ok(api::AbuseServiceReviewResponse {
+        report: Some(report.into()),
+    })
+}
@@ -0,0 +1,79 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::SupportAdminPerm;
+use crate::database::{ReadConn, Transaction};
+use crate::model::{AuditLog, RequestLog};
+
+use super::api::admin_service_server::AdminService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Admin audit log error: {0}
+    Audit(#[from] crate::model::audit::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Missing request_id.
+    MissingRequestId,
+    /// Admin request log error: {0}
+    RequestLog(#[from] crate::model::request_log::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            MissingRequestId => Status::invalid_argument("request_id"),
+            Audit(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            RequestLog(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl AdminService for Grpc {
+    async fn lookup_request(
+        &self,
+        req: Request<api::AdminServiceLookupRequestRequest>,
+    ) -> Result<Response<api::AdminServiceLookupRequestResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| lookup_request(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+/// Find what happened during a request from the id returned to the client.
+pub async fn lookup_request(
+    req: api::AdminServiceLookupRequestRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::AdminServiceLookupRequestResponse, Error> {
+    read.auth(&meta, SupportAdminPerm::LookupRequest).await?;
+
+    let request_id = req.request_id.trim();
+    if request_id.is_empty() {
+        return Err(Error::MissingRequestId);
+    }
+
+    let outcomes = RequestLog::by_request_id(request_id, &mut read).await?;
+    let audit_events = AuditLog::by_request_id(request_id, &mut read).await?;
+
+    Ok(api::AdminServiceLookupRequestResponse {
+        request_id: request_id.to_string(),
+        outcomes: outcomes.into_iter().map(Into::into).collect(),
+        audit_events: audit_events.into_iter().map(Into::into).collect(),
+    })
+}
@@ -0,0 +1,286 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::AlertPerm;
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::oncall::NewOncallSchedule;
+use crate::model::{Alert, AlertId, EscalationPolicy, OncallSchedule, OncallScheduleId, Org};
+use crate::util::NanosUtc;
+
+use super::api::alert_service_server::AlertService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Alert model error: {0}
+    Alert(#[from] crate::model::alert::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Claims Resource is not a user.
+    ClaimsNotUser,
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// On-call schedule name must not be empty.
+    NoName,
+    /// Alert on-call error: {0}
+    Oncall(#[from] crate::model::oncall::Error),
+    /// Alert org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Failed to parse AlertId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse OncallScheduleId: {0}
+    ParseScheduleId(uuid::Error),
+    /// Failed to parse starts_at: {0}
+    ParseStartsAt(crate::util::timestamp::Error),
+    /// Failed to parse UserId: {0}
+    ParseUserId(uuid::Error),
+    /// On-call schedule `{0}` does not belong to org `{1}`.
+    ScheduleOrg(OncallScheduleId, OrgId),
+    /// User `{0}` is not a member of org `{1}`.
+    UserOrg(UserId, OrgId),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ClaimsNotUser => Status::forbidden("Access denied."),
+            NoName => Status::invalid_argument("name"),
+            ParseId(_) => Status::invalid_argument("alert_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseScheduleId(_) | ScheduleOrg(..) => Status::invalid_argument("schedule_ids"),
+            ParseStartsAt(_) => Status::invalid_argument("starts_at"),
+            ParseUserId(_) | UserOrg(..) => Status::invalid_argument("user_ids"),
+            Alert(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Oncall(err) => err.into(),
+            Org(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl AlertService for Grpc {
+    async fn list(
+        &self,
+        req: Request<api::AlertServiceListRequest>,
+    ) -> Result<Response<api::AlertServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn acknowledge(
+        &self,
+        req: Request<api::AlertServiceAcknowledgeRequest>,
+    ) -> Result<Response<api::AlertServiceAcknowledgeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| acknowledge(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_oncall(
+        &self,
+        req: Request<api::AlertServiceGetOncallRequest>,
+    ) -> Result<Response<api::AlertServiceGetOncallResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_oncall(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn create_schedule(
+        &self,
+        req: Request<api::AlertServiceCreateScheduleRequest>,
+    ) -> Result<Response<api::AlertServiceCreateScheduleResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create_schedule(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn delete_schedule(
+        &self,
+        req: Request<api::AlertServiceDeleteScheduleRequest>,
+    ) -> Result<Response<api::AlertServiceDeleteScheduleResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_schedule(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn set_policy(
+        &self,
+        req: Request<api::AlertServiceSetPolicyRequest>,
+    ) -> Result<Response<api::AlertServiceSetPolicyResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_policy(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn list(
+    req: api::AlertServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::AlertServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, AlertPerm::List, org_id).await?;
+
+    let alerts = Alert::by_org(org_id, req.unacknowledged_only, &mut read)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(api::AlertServiceListResponse { alerts })
+}
+
+pub async fn acknowledge(
+    req: api::AlertServiceAcknowledgeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AlertServiceAcknowledgeResponse, Error> {
+    let id: AlertId = req.alert_id.parse().map_err(Error::ParseId)?;
+    let alert = Alert::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, AlertPerm::Acknowledge, alert.org_id)
+        .await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let alert = alert.acknowledge(user_id, &mut write).await?;
+
+    Ok(api::AlertServiceAcknowledgeResponse {
+        alert: Some(alert.into()),
+    })
+}
+
+pub async fn get_oncall(
+    req: api::AlertServiceGetOncallRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::AlertServiceGetOncallResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, AlertPerm::GetOncall, org_id).await?;
+
+    let schedules = OncallSchedule::by_org(org_id, &mut read)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let policy = match EscalationPolicy::by_org(org_id, &mut read).await {
+        Ok(policy) => Some(policy.into()),
+        Err(crate::model::oncall::Error::Policy(_, diesel::result::Error::NotFound)) => None,
+        Err(err) => return Err(err.into()),
+    };
+
+    Ok(api::AlertServiceGetOncallResponse { schedules, policy })
+}
+
+pub async fn create_schedule(
+    req: api::AlertServiceCreateScheduleRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AlertServiceCreateScheduleResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_for(&meta, AlertPerm::CreateSchedule, org_id)
+        .await?;
+
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err(Error::NoName);
+    }
+
+    // rotation order is kept, so the same user may appear more than once
+    let user_ids = req
+        .user_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseUserId))
+        .collect::<Result<Vec<UserId>, _>>()?;
+    for user_id in user_ids.iter().collect::<HashSet<_>>() {
+        if !Org::has_user(org_id, *user_id, &mut write).await? {
+            return Err(Error::UserOrg(*user_id, org_id));
+        }
+    }
+
+    let starts_at = match req.starts_at {
+        Some(starts_at) => *NanosUtc::try_from(starts_at).map_err(Error::ParseStartsAt)?,
+        None => Utc::now(),
+    };
+
+    let new_schedule = NewOncallSchedule {
+        org_id,
+        name,
+        user_ids: user_ids.into_iter().map(Some).collect(),
+        rotation_hours: i32::try_from(req.rotation_hours).unwrap_or(i32::MAX),
+        starts_at,
+    };
+    let schedule = new_schedule.create(&mut write).await?;
+
+    Ok(api::AlertServiceCreateScheduleResponse {
+        schedule: Some(schedule.into()),
+    })
+}
+
+pub async fn delete_schedule(
+    req: api::AlertServiceDeleteScheduleRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AlertServiceDeleteScheduleResponse, Error> {
+    let id: OncallScheduleId = req.schedule_id.parse().map_err(Error::ParseScheduleId)?;
+    let schedule = OncallSchedule::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, AlertPerm::DeleteSchedule, schedule.org_id)
+        .await?;
+
+    schedule.delete(&mut write).await?;
+
+    Ok(api::AlertServiceDeleteScheduleResponse {})
+}
+
+pub async fn set_policy(
+    req: api::AlertServiceSetPolicyRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AlertServiceSetPolicyResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write.auth_for(&meta, AlertPerm::SetPolicy, org_id).await?;
+
+    let schedule_ids = req
+        .schedule_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseScheduleId))
+        .collect::<Result<Vec<OncallScheduleId>, _>>()?;
+    let unique = schedule_ids.iter().copied().collect::<HashSet<_>>();
+    let schedules = OncallSchedule::by_ids(&unique, &mut write).await?;
+    for schedule_id in &unique {
+        match schedules
+            .iter()
+            .find(|schedule| schedule.id == *schedule_id)
+        {
+            Some(schedule) if schedule.org_id == org_id => (),
+            _ => return Err(Error::ScheduleOrg(*schedule_id, org_id)),
+        }
+    }
+
+    let ack_timeout_minutes = i32::try_from(req.ack_timeout_minutes).unwrap_or(i32::MAX);
+    let policy =
+        EscalationPolicy::set(org_id, schedule_ids, ack_timeout_minutes, &mut write).await?;
+
+    Ok(api::AlertServiceSetPolicyResponse {
+        policy: Some(policy.into()),
+    })
+}
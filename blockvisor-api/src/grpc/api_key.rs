@@ -16,6 +16,7 @@ use crate::model::sql::Permissions;
 use crate::util::NanosUtc;
 
 use super::api::api_key_service_server::ApiKeyService;
+use super::validate::{Validate, Validator};
 use super::{Grpc, Metadata, Status, api};
 
 #[derive(Debug, Display, Error)]
@@ -28,6 +29,8 @@ pub enum Error {
     ClaimsNotUser,
     /// Diesel failure: {0}
     Diesel(#[from] diesel::result::Error),
+    /// API keys can't be created by an impersonated user.
+    Impersonated,
     /// Request is missing the resource.
     MissingResource,
     /// Database model error: {0}
@@ -40,6 +43,8 @@ pub enum Error {
     Resource(#[from] crate::auth::resource::Error),
     /// API key sql type error: {0}
     Sql(#[from] crate::model::sql::Error),
+    /// API key request validation failed: {0}
+    Validate(#[from] crate::grpc::validate::Error),
 }
 
 impl From<Error> for Status {
@@ -48,7 +53,7 @@ impl From<Error> for Status {
         error!("{err}");
         match err {
             Diesel(_) => Status::internal("Internal error."),
-            ClaimsNotUser => Status::forbidden("Access denied."),
+            ClaimsNotUser | Impersonated => Status::forbidden("Access denied."),
             MissingResource => Status::invalid_argument("resource"),
             ParseId(_) => Status::invalid_argument("api_key_id"),
             ParsePerm(_) => Status::invalid_argument("permission"),
@@ -57,6 +62,7 @@ impl From<Error> for Status {
             Model(err) => err.into(),
             Resource(err) => err.into(),
             Sql(err) => err.into(),
+            Validate(err) => err.into(),
         }
     }
 }
@@ -96,9 +102,14 @@ pub async fn create(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::ApiKeyServiceCreateResponse, Error> {
+    req.validate()?;
     let resource = req.resource.ok_or(Error::MissingResource)?;
     let resource = Resource::try_from(&resource)?;
     let authz = write.auth_for(&meta, ApiKeyPerm::Create, resource).await?;
+    // an impersonated user can't mint long-lived credentials
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
 
     let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
     let org_id = resource.org_id(&mut write).await?;
@@ -143,6 +154,7 @@ pub async fn delete(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::ApiKeyServiceDeleteResponse, Error> {
+    req.validate()?;
     let key_id = req.api_key_id.parse().map_err(Error::ParseId)?;
     let existing = ApiKey::by_id(key_id, &mut write).await?;
     write
@@ -153,3 +165,16 @@ pub async fn delete(
 
     Ok(api::ApiKeyServiceDeleteResponse {})
 }
+
+impl Validate for api::ApiKeyServiceCreateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.no_secrets("label", &self.label);
+        v.check(self.resource.is_some(), "resource", "Must be set.");
+    }
+}
+
+impl Validate for api::ApiKeyServiceDeleteRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("api_key_id", &self.api_key_id);
+    }
+}
@@ -0,0 +1,340 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use prost::Message;
+use serde_json::json;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::{error, warn};
+
+use crate::auth::rbac::ApprovalPerm;
+use crate::auth::resource::{OrgId, Resource};
+use crate::auth::{AuthZ, Authorize};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::approval::{Approval, ApprovalAction, ApprovalId, ApprovalStatus, NewApproval};
+use crate::model::audit::NewAuditLog;
+use crate::model::user::setting::UserLocale;
+use crate::model::{AuditEvent, Org, User};
+
+use super::api::approval_service_server::ApprovalService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Approval model error: {0}
+    Approval(#[from] crate::model::approval::Error),
+    /// Approval audit error: {0}
+    Audit(#[from] crate::model::audit::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Failed to decode the request of approval `{0}`: {1}
+    Decode(ApprovalId, prost::DecodeError),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Approved node action error: {0}
+    Node(Box<crate::grpc::node::Error>),
+    /// Only the requester may cancel approval `{0}`.
+    NotRequester(ApprovalId),
+    /// Approved org action error: {0}
+    Org(Box<crate::grpc::org::Error>),
+    /// Approval org model error: {0}
+    OrgModel(#[from] crate::model::org::Error),
+    /// Failed to parse ApprovalId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Approval `{0}` may not be reviewed by its requester.
+    SelfReview(ApprovalId),
+    /// Approval user error: {0}
+    User(#[from] crate::model::user::Error),
+    /// Approval user settings error: {0}
+    UserSettings(#[from] crate::model::user::setting::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Decode(..) | Diesel(_) => Status::internal("Internal error."),
+            NotRequester(_) => Status::forbidden("Access denied."),
+            ParseId(_) => Status::invalid_argument("approval_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            SelfReview(_) => {
+                Status::failed_precondition("Approval must be reviewed by another member.")
+            }
+            Approval(err) => err.into(),
+            Audit(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Node(err) => (*err).into(),
+            Org(err) => (*err).into(),
+            OrgModel(err) => err.into(),
+            User(err) => err.into(),
+            UserSettings(err) => err.into(),
+        }
+    }
+}
+
+impl From<crate::grpc::node::Error> for Error {
+    fn from(err: crate::grpc::node::Error) -> Self {
+        Error::Node(Box::new(err))
+    }
+}
+
+impl From<crate::grpc::org::Error> for Error {
+    fn from(err: crate::grpc::org::Error) -> Self {
+        Error::Org(Box::new(err))
+    }
+}
+
+#[tonic::async_trait]
+impl ApprovalService for Grpc {
+    async fn list(
+        &self,
+        req: Request<api::ApprovalServiceListRequest>,
+    ) -> Result<Response<api::ApprovalServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn get(
+        &self,
+        req: Request<api::ApprovalServiceGetRequest>,
+    ) -> Result<Response<api::ApprovalServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn review(
+        &self,
+        req: Request<api::ApprovalServiceReviewRequest>,
+    ) -> Result<Response<api::ApprovalServiceReviewResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| review(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn cancel(
+        &self,
+        req: Request<api::ApprovalServiceCancelRequest>,
+    ) -> Result<Response<api::ApprovalServiceCancelResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| cancel(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn list(
+    req: api::ApprovalServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ApprovalServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, ApprovalPerm::List, org_id).await?;
+
+    let status = ApprovalStatus::from_api(req.status());
+    let approvals = Approval::by_org_id(org_id, status, &mut read).await?;
+
+    Ok(api::ApprovalServiceListResponse {
+        approvals: approvals.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn get(
+    req: api::ApprovalServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ApprovalServiceGetResponse, Error> {
+    let id: ApprovalId = req.approval_id.parse().map_err(Error::ParseId)?;
+    let approval = Approval::by_id(id, &mut read).await?;
+    read.auth_for(&meta, ApprovalPerm::Get, approval.org_id)
+        .await?;
+
+    Ok(api::ApprovalServiceGetResponse {
+        approval: Some(approval.into()),
+    })
+}
+
+/// Approve or reject a pending action.
+///
+/// An approved action runs straight away as the reviewer, who must be another
+/// org admin than its requester.
+pub async fn review(
+    req: api::ApprovalServiceReviewRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ApprovalServiceReviewResponse, Error> {
+    let id: ApprovalId = req.approval_id.parse().map_err(Error::ParseId)?;
+    let approval = Approval::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, ApprovalPerm::Review, approval.org_id)
+        .await?;
+
+    let reviewed_by = authz.resource();
+    if reviewed_by == approval.requested_by() {
+        return Err(Error::SelfReview(id));
+    }
+
+    let approval = if req.approve {
+        let approval = approval.approve(reviewed_by, &mut write).await?;
+        execute(&approval, &authz, &meta, &mut write).await?;
+        approval
+    } else {
+        approval.reject(reviewed_by, &mut write).await?
+    };
+
+    NewAuditLog::new(Some(approval.org_id), &authz, AuditEvent::ApprovalReviewed)
+        .with_data(json!({
+            "approval_id": approval.id.to_string(),
+            "approved": req.approve,
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::ApprovalServiceReviewResponse {
+        approval: Some(approval.into()),
+    })
+}
+
+pub async fn cancel(
+    req: api::ApprovalServiceCancelRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ApprovalServiceCancelResponse, Error> {
+    let id: ApprovalId = req.approval_id.parse().map_err(Error::ParseId)?;
+    let approval = Approval::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, ApprovalPerm::Cancel, approval.org_id)
+        .await?;
+
+    let cancelled_by = authz.resource();
+    if cancelled_by != approval.requested_by() {
+        return Err(Error::NotRequester(id));
+    }
+    let approval = approval.cancel(cancelled_by, &mut write).await?;
+
+    Ok(api::ApprovalServiceCancelResponse {
+        approval: Some(approval.into()),
+    })
+}
+
+/// Hold back `action` for approval if the org of `org_id` requires it.
+///
+/// Returns the pending approval, whose org admins are emailed to review it, or
+/// `None` if the action may run straight away.
+pub(crate) async fn request<M>(
+    action: ApprovalAction,
+    org_id: OrgId,
+    req: &M,
+    authz: &AuthZ,
+    meta: &Metadata,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<Option<Approval>, Error>
+where
+    M: Message,
+{
+    let org = Org::by_id(org_id, write).await?;
+    if !action.is_required(&org) {
+        return Ok(None);
+    }
+
+    let requested_by = authz.resource();
+    let approval = NewApproval::new(org_id, action, req, requested_by)
+        .create(write)
+        .await?;
+
+    NewAuditLog::new(Some(org_id), authz, AuditEvent::ApprovalRequested)
+        .with_data(json!({
+            "approval_id": approval.id.to_string(),
+            "action": action.describe(),
+        }))
+        .with_request_id(meta.request_id())
+        .create(write)
+        .await?;
+
+    notify(&approval, &org, requested_by, write).await?;
+
+    Ok(Some(approval))
+}
+
+/// Email each org admin other than the requester about a new approval, once
+/// the approval has been committed.
+async fn notify(
+    approval: &Approval,
+    org: &Org,
+    requested_by: Resource,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let Some(email) = write.ctx.email.clone() else {
+        warn!("Unable to send approval emails without email configured");
+        return Ok(());
+    };
+
+    let requester = match requested_by {
+        Resource::User(user_id) => User::by_id(user_id, write).await?.name(),
+        Resource::Host(_) => "A host".to_string(),
+        Resource::Org(_) | Resource::Node(_) => "An API client".to_string(),
+    };
+
+    let mut recipients = vec![];
+    for user_id in Approval::approver_ids(org.id, write).await? {
+        if requested_by.user() == Some(user_id) {
+            continue;
+        }
+        let user = User::by_id(user_id, write).await?;
+        let locale = UserLocale::by_user(user_id, write).await?;
+        recipients.push((user, locale));
+    }
+
+    let (approval, org) = (approval.clone(), org.clone());
+    write.after_commit(async move {
+        for (user, locale) in recipients {
+            if let Err(err) = email
+                .approval_requested(&approval, &org, &requester, &user, &locale)
+                .await
+            {
+                warn!(
+                    "Failed to email approval {} to {}: {err}",
+                    approval.id, user.id
+                );
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Run the action of an approval, as the reviewer that approved it.
+async fn execute(
+    approval: &Approval,
+    authz: &AuthZ,
+    meta: &Metadata,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let decode_err = |err| Error::Decode(approval.id, err);
+    match approval.action {
+        ApprovalAction::NodeDelete => {
+            let req =
+                api::NodeServiceDeleteRequest::decode(&*approval.request).map_err(decode_err)?;
+            super::node::approved_delete(req, approval.org_id, authz, write).await?;
+        }
+        ApprovalAction::NodeUpgrade => {
+            let req = api::NodeServiceUpgradeImageRequest::decode(&*approval.request)
+                .map_err(decode_err)?;
+            super::node::approved_upgrade(req, approval.org_id, authz, write).await?;
+        }
+        ApprovalAction::OrgTransfer => {
+            let req = api::OrgServiceTransferOwnershipRequest::decode(&*approval.request)
+                .map_err(decode_err)?;
+            let requested_by = approval.requested_by();
+            super::org::approved_transfer(req, requested_by, authz, meta, write).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::{ArtifactAdminPerm, ArtifactPerm};
+use crate::auth::resource::Resource;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::artifact::{Artifact, NewArtifact};
+use crate::model::command::Command;
+
+use super::api::artifact_service_server::ArtifactService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Artifact model error: {0}
+    Artifact(#[from] crate::model::artifact::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Artifact command error: {0}
+    Command(#[from] crate::model::command::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// List artifacts is missing a command_id or node_id.
+    ListMissingCommandOrNode,
+    /// Failed to parse ArtifactId: {0}
+    ParseArtifactId(uuid::Error),
+    /// Failed to parse CommandId: {0}
+    ParseCommandId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Artifact store error: {0}
+    Store(#[from] crate::store::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ListMissingCommandOrNode => Status::invalid_argument("command_id or node_id"),
+            ParseArtifactId(_) => Status::invalid_argument("artifact_id"),
+            ParseCommandId(_) => Status::invalid_argument("command_id"),
+            ParseNodeId(_) => Status::invalid_argument("node_id"),
+            Artifact(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Command(err) => err.into(),
+            Store(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ArtifactService for Grpc {
+    async fn create_upload(
+        &self,
+        req: Request<api::ArtifactServiceCreateUploadRequest>,
+    ) -> Result<Response<api::ArtifactServiceCreateUploadResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create_upload(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::ArtifactServiceListRequest>,
+    ) -> Result<Response<api::ArtifactServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn download(
+        &self,
+        req: Request<api::ArtifactServiceDownloadRequest>,
+    ) -> Result<Response<api::ArtifactServiceDownloadResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| download(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+/// Reserve an artifact of a command and return a presigned url to upload it.
+pub async fn create_upload(
+    req: api::ArtifactServiceCreateUploadRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ArtifactServiceCreateUploadResponse, Error> {
+    let command_id = req.command_id.parse().map_err(Error::ParseCommandId)?;
+    let command = Command::by_id(command_id, &mut write).await?;
+    write
+        .auth_for(&meta, ArtifactPerm::CreateUpload, command.host_id)
+        .await?;
+
+    let retention_days = write.ctx.config.retention.artifacts_days;
+    let artifact = NewArtifact::new(&command, req.name, retention_days)?
+        .create(&mut write)
+        .await?;
+    let upload_url = write
+        .ctx
+        .store
+        .artifact_upload_url(&artifact.store_key)
+        .await?;
+
+    Ok(api::ArtifactServiceCreateUploadResponse {
+        artifact: Some(artifact.into()),
+        upload_url: upload_url.to_string(),
+    })
+}
+
+pub async fn list(
+    req: api::ArtifactServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ArtifactServiceListResponse, Error> {
+    let artifacts = match (req.command_id, req.node_id) {
+        (Some(command_id), _) => {
+            let command_id = command_id.parse().map_err(Error::ParseCommandId)?;
+            let command = Command::by_id(command_id, &mut read).await?;
+            let resource = command
+                .node_id
+                .map_or_else(|| Resource::from(command.host_id), Resource::from);
+            read.auth_or_for(&meta, ArtifactAdminPerm::List, ArtifactPerm::List, resource)
+                .await?;
+            Artifact::by_command_id(command_id, &mut read).await?
+        }
+        (None, Some(node_id)) => {
+            let node_id = node_id.parse().map_err(Error::ParseNodeId)?;
+            read.auth_or_for(&meta, ArtifactAdminPerm::List, ArtifactPerm::List, node_id)
+                .await?;
+            Artifact::by_node_id(node_id, &mut read).await?
+        }
+        (None, None) => return Err(Error::ListMissingCommandOrNode),
+    };
+
+    Ok(api::ArtifactServiceListResponse {
+        artifacts: artifacts.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Return a presigned url to download an uploaded artifact.
+pub async fn download(
+    req: api::ArtifactServiceDownloadRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ArtifactServiceDownloadResponse, Error> {
+    let id = req.artifact_id.parse().map_err(Error::ParseArtifactId)?;
+    let artifact = Artifact::by_id(id, &mut read).await?;
+    let resource = artifact
+        .node_id
+        .map_or_else(|| Resource::from(artifact.host_id), Resource::from);
+    read.auth_or_for(
+        &meta,
+        ArtifactAdminPerm::Download,
+        ArtifactPerm::Download,
+        resource,
+    )
+    .await?;
+
+    artifact.check_uploaded()?;
+    let url = read
+        .ctx
+        .store
+        .artifact_download_url(&artifact.store_key)
+        .await?;
+
+    Ok(api::ArtifactServiceDownloadResponse {
+        url: url.to_string(),
+    })
+}
@@ -1,16 +1,23 @@
+use chrono::Utc;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
+use serde_json::json;
 use thiserror::Error;
 use tonic::{Request, Response};
 use tracing::{error, warn};
 
 use crate::auth::Authorize;
 use crate::auth::claims::{Claims, Expirable, Granted};
-use crate::auth::rbac::{AuthAdminPerm, AuthPerm, GrpcRole, Perm};
+use crate::auth::rbac::{AuthAdminPerm, AuthPerm, GrpcRole, Perm, SupportAdminPerm};
+use crate::auth::resource::UserId;
 use crate::auth::token::RequestToken;
 use crate::auth::token::refresh::Refresh;
 use crate::database::{Transaction, WriteConn};
 use crate::model::User;
+use crate::model::audit::{AuditEvent, NewAuditLog};
+use crate::model::user::password;
+use crate::model::user::session::{NewUserSession, UserSession, UserSessionId};
+use crate::model::user::setting::UserLocale;
 
 use super::api::auth_service_server::AuthService;
 use super::{Grpc, Metadata, Status, api};
@@ -27,12 +34,18 @@ pub enum Error {
     Diesel(#[from] diesel::result::Error),
     /// Failed to send email: {0}
     Email(#[from] crate::email::Error),
+    /// Failed to audit impersonation: {0}
+    Audit(#[from] crate::model::audit::Error),
     /// Host auth error: {0}
     Host(#[from] crate::model::host::Error),
     /// JWT token failure: {0}
     Jwt(#[from] crate::auth::token::jwt::Error),
+    /// Credentials can't be changed by an impersonated user.
+    Impersonated,
     /// Node auth error: {0}
     Node(#[from] crate::model::node::Error),
+    /// User `{0}` has not granted support access.
+    NoSupportAccess(UserId),
     /// Not JWT Token.
     NotJwt,
     /// Requested to send an email, but no email service is configured.
@@ -43,20 +56,32 @@ pub enum Error {
     Org(#[from] crate::model::org::Error),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
+    /// Failed to parse UserSessionId: {0}
+    ParseSessionId(uuid::Error),
     /// Failed to parse RequestToken: {0}
     ParseToken(crate::auth::token::Error),
     /// Failed to parse UserId: {0}
     ParseUserId(uuid::Error),
+    /// Password check failed: {0}
+    Password(#[from] crate::model::user::password::Error),
     /// User RBAC error: {0}
     Rbac(#[from] crate::model::rbac::Error),
     /// Refresh token failure: {0}
     Refresh(#[from] crate::auth::token::refresh::Error),
+    /// Impersonation tokens can't be refreshed.
+    RefreshImpersonation,
     /// Refresh token doesn't match JWT Resource.
     RefreshResource,
     /// Auth resource error: {0}
     Resource(#[from] crate::auth::resource::Error),
+    /// User session error: {0}
+    Session(#[from] crate::model::user::session::Error),
+    /// User session `{0}` does not belong to the caller.
+    SessionNotOwned(UserSessionId),
     /// User auth error: {0}
     User(#[from] crate::model::user::Error),
+    /// User settings error: {0}
+    UserSettings(#[from] crate::model::user::setting::Error),
 }
 
 impl From<Error> for Status {
@@ -64,24 +89,33 @@ impl From<Error> for Status {
         use Error::*;
         error!("{err}");
         match err {
-            Jwt(_) | NotJwt | ParseToken(_) | RefreshResource => {
+            Jwt(_) | NotJwt | ParseToken(_) | RefreshImpersonation | RefreshResource => {
                 Status::unauthorized("Access denied.")
             }
-            Diesel(_) | Email(_) => Status::internal("Internal error."),
+            Audit(_) | Diesel(_) | Email(_) => Status::internal("Internal error."),
             NoEmail => Status::failed_precondition("No email configured."),
-            ClaimsNotUser => Status::forbidden("Access denied."),
+            NoSupportAccess(_) => {
+                Status::failed_precondition("User has not granted support access.")
+            }
+            ClaimsNotUser | Impersonated | SessionNotOwned(_) => {
+                Status::forbidden("Access denied.")
+            }
             NoRefresh => Status::invalid_argument("No refresh token."),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseSessionId(_) => Status::invalid_argument("session_id"),
             ParseUserId(_) => Status::invalid_argument("user_id"),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
             Host(err) => err.into(),
             Node(err) => err.into(),
             Org(err) => err.into(),
+            Password(err) => err.into(),
             Rbac(err) => err.into(),
             Refresh(err) => err.into(),
             Resource(err) => err.into(),
+            Session(err) => err.into(),
             User(err) => err.into(),
+            UserSettings(err) => err.into(),
         }
     }
 }
@@ -150,21 +184,49 @@ impl AuthService for Grpc {
         self.write(|write| list_permissions(req, meta.into(), write).scope_boxed())
             .await
     }
+
+    async fn impersonate(
+        &self,
+        req: Request<api::AuthServiceImpersonateRequest>,
+    ) -> Result<Response<api::AuthServiceImpersonateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| impersonate(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_sessions(
+        &self,
+        req: Request<api::AuthServiceListSessionsRequest>,
+    ) -> Result<Response<api::AuthServiceListSessionsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| list_sessions(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn revoke_session(
+        &self,
+        req: Request<api::AuthServiceRevokeSessionRequest>,
+    ) -> Result<Response<api::AuthServiceRevokeSessionResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke_session(req, meta.into(), write).scope_boxed())
+            .await
+    }
 }
 
 pub async fn login(
     req: api::AuthServiceLoginRequest,
-    _: Metadata,
+    meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::AuthServiceLoginResponse, Error> {
     // No auth claims are required as the password is checked instead.
     let user = User::login(&req.email, &req.password, &mut write).await?;
+    let session = new_session(user.id, &meta).create(&mut write).await?;
 
     let expires = write.ctx.config.token.expire.token;
     let claims = Claims::from_now(expires, user.id, GrpcRole::Login);
 
     let expires = write.ctx.config.token.expire.refresh_user;
-    let refresh = Refresh::from_now(expires, user.id);
+    let refresh = Refresh::from_now(expires, user.id).with_session(Some(session.id));
     let cookie = write.ctx.auth.cipher.refresh.cookie(&refresh)?;
     write.meta("set-cookie", cookie.header()?);
 
@@ -186,8 +248,9 @@ pub async fn confirm(
     let claims = Claims::from_now(expire.token, user_id, GrpcRole::Login);
 
     User::confirm(user_id, &mut write).await?;
+    let session = new_session(user_id, &meta).create(&mut write).await?;
 
-    let refresh = Refresh::from_now(expire.refresh_user, user_id);
+    let refresh = Refresh::from_now(expire.refresh_user, user_id).with_session(Some(session.id));
     let cookie = write.ctx.auth.cipher.refresh.cookie(&refresh)?;
     write.meta("set-cookie", cookie.header()?);
 
@@ -206,6 +269,9 @@ pub async fn refresh(
         RequestToken::ApiKey(_) => Err(Error::NotJwt)?,
         RequestToken::Jwt(token) => write.ctx.auth.cipher.jwt.decode_expired(&token)?,
     };
+    if claims.impersonator.is_some() {
+        return Err(Error::RefreshImpersonation);
+    }
 
     let refresh = if let Some(refresh) = req.refresh {
         write.ctx.auth.cipher.refresh.decode(&refresh.into())?
@@ -221,6 +287,12 @@ pub async fn refresh(
         return Err(Error::RefreshResource);
     }
 
+    // Refresh tokens tied to a revoked session can no longer be used
+    let session_id = refresh.session_id();
+    if let Some(session_id) = session_id {
+        UserSession::touch(session_id, &mut write).await?;
+    }
+
     let expirable = Expirable::from_now(write.ctx.config.token.expire.token);
     let new_claims = if let Some(data) = claims.data {
         Claims::new(resource, expirable, claims.access).with_data(data)
@@ -230,7 +302,7 @@ pub async fn refresh(
     let token = write.ctx.auth.cipher.jwt.encode(&new_claims)?;
 
     let expires = refresh.expirable().duration();
-    let refresh = Refresh::from_now(expires, resource);
+    let refresh = Refresh::from_now(expires, resource).with_session(session_id);
 
     let encoded = write.ctx.auth.cipher.refresh.encode(&refresh)?;
     let cookie = write.ctx.auth.cipher.refresh.cookie(&refresh)?;
@@ -251,8 +323,9 @@ pub async fn reset_password(
     // always return ok to caller to hide whether the user exists
     match User::by_email(&req.email, &mut write).await {
         Ok(user) => {
+            let locale = UserLocale::by_user(user.id, &mut write).await?;
             if let Some(email) = write.ctx.email.as_ref() {
-                if let Err(err) = email.reset_password(&user).await {
+                if let Err(err) = email.reset_password(&user, &locale).await {
                     warn!("Failed to reset password: {err}");
                 }
             } else {
@@ -271,10 +344,17 @@ pub async fn update_password(
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::AuthServiceUpdatePasswordResponse, Error> {
     let authz = write.auth(&meta, AuthPerm::UpdatePassword).await?;
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
     let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
 
+    let policy = &write.ctx.config.password;
+    password::check_breached(&req.password, policy).await?;
+
     let user = User::by_id(user_id, &mut write).await?;
-    user.update_password(&req.password, &mut write).await?;
+    user.update_password(&req.password, policy, &mut write)
+        .await?;
 
     write
         .ctx
@@ -293,16 +373,24 @@ pub async fn update_ui_password(
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::AuthServiceUpdateUiPasswordResponse, Error> {
     let user_id = req.user_id.parse().map_err(Error::ParseUserId)?;
-    write
+    let authz = write
         .auth_for(&meta, AuthPerm::UpdateUiPassword, user_id)
         .await?;
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
 
     let user = User::by_id(user_id, &mut write).await?;
     user.verify_password(&req.old_password)?;
-    user.update_password(&req.new_password, &mut write).await?;
 
+    let policy = &write.ctx.config.password;
+    password::check_breached(&req.new_password, policy).await?;
+    user.update_password(&req.new_password, policy, &mut write)
+        .await?;
+
+    let locale = UserLocale::by_user(user.id, &mut write).await?;
     if let Some(email) = write.ctx.email.as_ref() {
-        email.update_password(&user).await?;
+        email.update_password(&user, &locale).await?;
     } else {
         warn!("Cannot send update password email without email configured");
     }
@@ -336,3 +424,89 @@ pub async fn list_permissions(
 
     Ok(api::AuthServiceListPermissionsResponse { permissions })
 }
+
+/// Mint a short-lived token for a support user to act as another user.
+///
+/// The target user must have granted support access, and every request made
+/// with the token is recorded in the audit log against the support user.
+pub async fn impersonate(
+    req: api::AuthServiceImpersonateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AuthServiceImpersonateResponse, Error> {
+    let authz = write.auth(&meta, SupportAdminPerm::Impersonate).await?;
+    // an impersonation token can't be used to chain into another user
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
+    let support_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
+    let user = User::by_id(user_id, &mut write).await?;
+    if !user.has_support_access() {
+        return Err(Error::NoSupportAccess(user_id));
+    }
+
+    let expires = write.ctx.config.token.expire.token;
+    let claims = Claims::from_now(expires, user_id, GrpcRole::Login).with_impersonator(support_id);
+
+    NewAuditLog::new(None, support_id, AuditEvent::UserImpersonated)
+        .with_data(json!({
+            "user_id": user_id.to_string(),
+            "reason": req.reason,
+            "expires_at": claims.expirable.expires_at.to_rfc3339(),
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::AuthServiceImpersonateResponse {
+        token: write.ctx.auth.cipher.jwt.encode(&claims)?.into(),
+    })
+}
+
+/// List the active login sessions of a user.
+pub async fn list_sessions(
+    req: api::AuthServiceListSessionsRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AuthServiceListSessionsResponse, Error> {
+    let user_id = req.user_id.parse().map_err(Error::ParseUserId)?;
+    write
+        .auth_for(&meta, AuthPerm::ListSessions, user_id)
+        .await?;
+
+    let since = Utc::now() - write.ctx.config.token.expire.refresh_user;
+    let sessions = UserSession::active_by_user(user_id, since, &mut write).await?;
+
+    Ok(api::AuthServiceListSessionsResponse {
+        sessions: sessions.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Revoke a login session so that its refresh token can no longer be used.
+pub async fn revoke_session(
+    req: api::AuthServiceRevokeSessionRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::AuthServiceRevokeSessionResponse, Error> {
+    let authz = write.auth(&meta, AuthPerm::RevokeSession).await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let session_id = req.session_id.parse().map_err(Error::ParseSessionId)?;
+    let session = UserSession::by_id(session_id, &mut write).await?;
+    if session.user_id != user_id {
+        return Err(Error::SessionNotOwned(session_id));
+    }
+    session.revoke(&mut write).await?;
+
+    Ok(api::AuthServiceRevokeSessionResponse {})
+}
+
+fn new_session(user_id: UserId, meta: &Metadata) -> NewUserSession {
+    NewUserSession {
+        user_id,
+        device: meta.user_agent(),
+        ip_address: meta.client_ip(),
+    }
+}
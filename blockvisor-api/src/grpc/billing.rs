@@ -0,0 +1,187 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::BillingPerm;
+use crate::auth::resource::OrgId;
+use crate::database::{ReadConn, Transaction};
+use crate::model::image::config::NodeConfig;
+use crate::model::sql::{Amount, Currency, Period};
+use crate::model::{Image, ImageId, ProtocolVersion, Region, RegionId};
+use crate::stripe::Subscription;
+
+use super::api::billing_service_server::BillingService;
+use super::{Grpc, Metadata, Status, api, common};
+
+/// The number of bytes per GiB of metered disk.
+const GIB_BYTES: u64 = 1 << 30;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Estimate image error: {0}
+    Image(#[from] crate::model::image::Error),
+    /// Estimate image config error: {0}
+    ImageConfig(#[from] crate::model::image::config::Error),
+    /// Estimate image property error: {0}
+    ImageProperty(#[from] crate::model::image::property::Error),
+    /// Stripe is not configured.
+    NoStripe,
+    /// The estimated cost does not fit in an i64.
+    Overflow,
+    /// Failed to parse ImageId: {0}
+    ParseImageId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse RegionId: {0}
+    ParseRegionId(uuid::Error),
+    /// The stripe price of SKU `{0}` has no unit amount.
+    PriceWithoutAmount(String),
+    /// Estimate protocol version error: {0}
+    ProtocolVersion(#[from] crate::model::protocol::version::Error),
+    /// Estimate region error: {0}
+    Region(#[from] crate::model::region::Error),
+    /// The region `{0}` has no pricing set.
+    RegionMissingPrice(RegionId),
+    /// The SKU `{0}` has no stripe price.
+    SkuMissingPrice(String),
+    /// Stripe error: {0}
+    Stripe(#[from] crate::stripe::Error),
+    /// At least one node must be estimated.
+    ZeroNodes,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) | Overflow | PriceWithoutAmount(_) | Stripe(_) => {
+                Status::internal("Internal error.")
+            }
+            NoStripe => Status::failed_precondition("Stripe is not configured."),
+            ParseImageId(_) => Status::invalid_argument("image_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseRegionId(_) => Status::invalid_argument("region_id"),
+            RegionMissingPrice(_) | SkuMissingPrice(_) => Status::not_found("Not found."),
+            ZeroNodes => Status::invalid_argument("node_count"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Image(err) => err.into(),
+            ImageConfig(err) => err.into(),
+            ImageProperty(err) => err.into(),
+            ProtocolVersion(err) => err.into(),
+            Region(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl BillingService for Grpc {
+    async fn estimate(
+        &self,
+        req: Request<api::BillingServiceEstimateRequest>,
+    ) -> Result<Response<api::BillingServiceEstimateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| estimate(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+/// Estimate the monthly cost of launching nodes of an image in a region.
+///
+/// Each node costs the price of its SKU, plus the metered disk price for any
+/// disk that the chosen property values add to the image minimum. These are
+/// list prices, so the estimate ignores billing exemptions and self-hosted
+/// placement.
+pub async fn estimate(
+    req: api::BillingServiceEstimateRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::BillingServiceEstimateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = read.auth_for(&meta, BillingPerm::Estimate, org_id).await?;
+
+    if req.node_count == 0 {
+        return Err(Error::ZeroNodes);
+    }
+
+    let image_id: ImageId = req.image_id.parse().map_err(Error::ParseImageId)?;
+    let image = Image::by_id(image_id, Some(org_id), &authz, &mut read).await?;
+    let version =
+        ProtocolVersion::by_id(image.protocol_version_id, Some(org_id), &authz, &mut read).await?;
+
+    let region_id: RegionId = req.region_id.parse().map_err(Error::ParseRegionId)?;
+    let region = Region::by_id(region_id, &mut read).await?;
+    let sku = version
+        .sku(&region)
+        .ok_or(Error::RegionMissingPrice(region.id))?;
+
+    let ctx = read.ctx;
+    let Some(stripe) = ctx.stripe.as_ref() else {
+        return Err(Error::NoStripe);
+    };
+
+    let min_disk_bytes = u64::try_from(image.min_disk_bytes).unwrap_or_default();
+    let new_values = req
+        .new_values
+        .into_iter()
+        .map(TryFrom::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    let (config, _secrets) =
+        NodeConfig::new(image, Some(org_id), new_values, vec![], &mut read).await?;
+
+    let base = unit_amount(&sku, stripe.as_ref().as_ref()).await?;
+    let added_bytes = config.vm.disk_bytes.saturating_sub(min_disk_bytes);
+    let added_disk = match &ctx.config.stripe.disk_sku {
+        Some(disk_sku) if added_bytes > 0 => {
+            let gib =
+                i64::try_from(added_bytes.div_ceil(GIB_BYTES)).map_err(|_| Error::Overflow)?;
+            let per_gib = unit_amount(disk_sku, stripe.as_ref().as_ref()).await?;
+            per_gib.checked_mul(gib).ok_or(Error::Overflow)?
+        }
+        _ => 0,
+    };
+
+    let per_node = base.checked_add(added_disk).ok_or(Error::Overflow)?;
+    let total = per_node
+        .checked_mul(i64::from(req.node_count))
+        .ok_or(Error::Overflow)?;
+
+    Ok(api::BillingServiceEstimateResponse {
+        sku,
+        base: Some(monthly(base)),
+        added_disk: Some(monthly(added_disk)),
+        per_node: Some(monthly(per_node)),
+        total: Some(monthly(total)),
+    })
+}
+
+async fn unit_amount(sku: &str, stripe: &(dyn Subscription + Send + Sync)) -> Result<i64, Error> {
+    let price = match stripe.get_price(sku).await {
+        Ok(price) => Ok(price),
+        Err(crate::stripe::Error::NoPrice(_)) => Err(Error::SkuMissingPrice(sku.to_string())),
+        Err(err) => Err(err.into()),
+    }?;
+
+    price
+        .unit_amount
+        .ok_or_else(|| Error::PriceWithoutAmount(sku.to_string()))
+}
+
+fn monthly(amount: i64) -> common::BillingAmount {
+    Amount {
+        amount,
+        currency: Currency::Usd,
+        period: Period::Monthly,
+    }
+    .into()
+}
@@ -0,0 +1,105 @@
+//! The protobuf codec of every service.
+//!
+//! This encodes and decodes messages the same way as `tonic::codec::ProstCodec`,
+//! but also hands the messages of sampled calls to their `PayloadSample`.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use prost::Message;
+use prost::bytes::Buf;
+use serde::Serialize;
+use tonic::Status;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+
+use super::middleware::PayloadSample;
+
+#[derive(Debug)]
+pub struct AuditCodec<T, U> {
+    _messages: PhantomData<(T, U)>,
+}
+
+impl<T, U> Default for AuditCodec<T, U> {
+    fn default() -> Self {
+        AuditCodec {
+            _messages: PhantomData,
+        }
+    }
+}
+
+impl<T, U> Codec for AuditCodec<T, U>
+where
+    T: Message + Serialize + Send + 'static,
+    U: Message + Default + Serialize + Send + 'static,
+{
+    type Encode = T;
+    type Decode = U;
+
+    type Encoder = AuditEncoder<T>;
+    type Decoder = AuditDecoder<U>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        AuditEncoder {
+            sample: PayloadSample::current(),
+            _message: PhantomData,
+        }
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        AuditDecoder {
+            sample: PayloadSample::current(),
+            _message: PhantomData,
+        }
+    }
+}
+
+pub struct AuditEncoder<T> {
+    sample: Option<Arc<PayloadSample>>,
+    _message: PhantomData<T>,
+}
+
+impl<T> Encoder for AuditEncoder<T>
+where
+    T: Message + Serialize,
+{
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        if let Some(sample) = &self.sample {
+            sample.set_response(&item);
+        }
+
+        item.encode(dst)
+            .expect("Message only errors if not enough space");
+
+        Ok(())
+    }
+}
+
+pub struct AuditDecoder<U> {
+    sample: Option<Arc<PayloadSample>>,
+    _message: PhantomData<U>,
+}
+
+impl<U> Decoder for AuditDecoder<U>
+where
+    U: Message + Default + Serialize,
+{
+    type Item = U;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let decoded = if let Some(sample) = &self.sample {
+            let bytes = src.copy_to_bytes(src.remaining());
+            sample.set_request::<U>(bytes.clone());
+            U::decode(bytes)
+        } else {
+            U::decode(src)
+        };
+
+        decoded
+            .map(Some)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+}
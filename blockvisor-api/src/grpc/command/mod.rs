@@ -9,20 +9,28 @@ use tonic::{Request, Response};
 use tracing::{error, warn};
 
 use crate::auth::rbac::{CommandAdminPerm, CommandPerm};
-use crate::auth::resource::Resource;
+use crate::auth::resource::{NodeId, Resource};
 use crate::auth::{AuthZ, Authorize};
 use crate::database::{Conn, ReadConn, Transaction, WriteConn};
 use crate::grpc::api::command_service_server::CommandService;
 use crate::grpc::{Grpc, Metadata, Status, api, common};
+use crate::model::agent_upgrade::AgentUpgradeHost;
+use crate::model::artifact::{Artifact, ArtifactId};
 use crate::model::command::{
-    Command, CommandFilter, CommandId, CommandType, ExitCode, UpdateCommand,
+    Command, CommandFilter, CommandId, CommandType, ExitCode, MAX_LIST_LIMIT, UpdateCommand,
 };
 use crate::model::node::{NextState, NodeState, UpdateNodeState};
+use crate::model::org_log_sink;
 use crate::model::{Host, Node};
+use crate::store::secret::{Secret, SecretKey};
 use crate::util::NanosUtc;
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Command agent upgrade error: {0}
+    AgentUpgrade(#[from] crate::model::agent_upgrade::Error),
+    /// Command artifact error: {0}
+    Artifact(#[from] crate::model::artifact::Error),
     /// Auth check failed: {0}
     Auth(#[from] crate::auth::Error),
     /// Claims check failed: {0}
@@ -37,14 +45,50 @@ pub enum Error {
     GrpcHost(Box<crate::grpc::node::Error>),
     /// Command host error: {0}
     Host(#[from] crate::model::host::Error),
+    /// HostKeys command is missing expected protobuf bytes.
+    HostKeysMissingProtobuf,
+    /// Failed to decode HostKeys protobuf: {0}
+    HostKeysDecode(prost::DecodeError),
+    /// HostLogSink command is missing expected protobuf bytes.
+    HostLogSinkMissingProtobuf,
+    /// Failed to decode HostLogSink protobuf: {0}
+    HostLogSinkDecode(prost::DecodeError),
+    /// HostMesh command is missing expected protobuf bytes.
+    HostMeshMissingProtobuf,
+    /// Failed to decode HostMesh protobuf: {0}
+    HostMeshDecode(prost::DecodeError),
+    /// HostUpgrade command is missing expected protobuf bytes.
+    HostUpgradeMissingProtobuf,
+    /// Failed to decode HostUpgrade protobuf: {0}
+    HostUpgradeDecode(prost::DecodeError),
+    /// Failed to parse list limit: {0}
+    Limit(std::num::TryFromIntError),
     /// List commands is missing a node_id or host_id.
     ListMissingNodeOrHost,
+    /// Command log sink error: {0}
+    LogSink(#[from] crate::model::org_log_sink::Error),
     /// Missing `command.node_id`.
     MissingNodeId,
+    /// NodeCertificate command is missing expected protobuf bytes.
+    NodeCertificateMissingProtobuf,
+    /// Failed to decode NodeCertificate protobuf: {0}
+    NodeCertificateDecode(prost::DecodeError),
+    /// NodeDebugBundle command is missing expected protobuf bytes.
+    NodeDebugBundleMissingProtobuf,
+    /// Failed to decode NodeDebugBundle protobuf: {0}
+    NodeDebugBundleDecode(prost::DecodeError),
+    /// NodeReportConfig command is missing expected protobuf bytes.
+    NodeReportConfigMissingProtobuf,
+    /// Failed to decode NodeReportConfig protobuf: {0}
+    NodeReportConfigDecode(prost::DecodeError),
     /// Command node error: {0}
     Node(#[from] crate::model::node::Error),
     /// Command node response error: {0}
     NodeResponse(Box<crate::grpc::node::Error>),
+    /// NodeResize command is missing expected protobuf bytes.
+    NodeResizeMissingProtobuf,
+    /// Failed to decode NodeResize protobuf: {0}
+    NodeResizeDecode(prost::DecodeError),
     /// NodeUpdate command is missing expected protobuf bytes.
     NodeUpdateMissingProtobuf,
     /// Failed to decode NodeUpdate protobuf: {0}
@@ -57,6 +101,8 @@ pub enum Error {
     NotNodeCommand(CommandId),
     /// No visibility of command to update.
     NoUpdateVisibility,
+    /// Failed to parse ArtifactId: {0}
+    ParseArtifactId(uuid::Error),
     /// Failed to parse HostId: {0}
     ParseHostId(uuid::Error),
     /// Failed to parse NodeId: {0}
@@ -65,6 +111,8 @@ pub enum Error {
     ParseCommandId(uuid::Error),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
+    /// Failed to parse created_after: {0}
+    ParseCreatedAfter(crate::util::timestamp::Error),
     /// Command protocol error: {0}
     Protocol(#[from] crate::model::protocol::Error),
     /// Command protocol version error: {0}
@@ -75,6 +123,10 @@ pub enum Error {
     RetryHint(std::num::TryFromIntError),
     /// Resource error: {0}
     Resource(#[from] crate::auth::resource::Error),
+    /// Command secret error: {0}
+    Secret(#[from] crate::store::secret::Error),
+    /// Secret property value is not UTF-8: {0}
+    SecretUtf8(std::string::FromUtf8Error),
     /// Command success error: {0}
     Success(#[from] self::success::Error),
     /// Unknown ExitCode.
@@ -88,30 +140,54 @@ impl From<Error> for Status {
         match err {
             Diesel(_)
             | GrpcHost(_)
+            | HostKeysMissingProtobuf
+            | HostKeysDecode(_)
+            | HostLogSinkMissingProtobuf
+            | HostLogSinkDecode(_)
+            | HostMeshMissingProtobuf
+            | HostMeshDecode(_)
+            | HostUpgradeMissingProtobuf
+            | HostUpgradeDecode(_)
+            | NodeCertificateMissingProtobuf
+            | NodeCertificateDecode(_)
+            | NodeDebugBundleMissingProtobuf
+            | NodeDebugBundleDecode(_)
+            | NodeReportConfigMissingProtobuf
+            | NodeReportConfigDecode(_)
+            | NodeResizeMissingProtobuf
+            | NodeResizeDecode(_)
             | NodeUpdateMissingProtobuf
             | NodeUpdateDecode(_)
             | NotHostCommand(_)
-            | NotNodeCommand(_) => Status::internal("Internal error."),
+            | NotNodeCommand(_)
+            | SecretUtf8(_) => Status::internal("Internal error."),
+            Limit(_) => Status::invalid_argument("limit"),
             ListMissingNodeOrHost => Status::invalid_argument("node_id or host_id"),
             MissingNodeId => Status::invalid_argument("command.node_id"),
             NotHostToken | NoUpdateVisibility => Status::forbidden("Access denied."),
+            ParseArtifactId(_) => Status::invalid_argument("artifact_ids"),
             ParseNodeId(_) => Status::invalid_argument("node_id"),
             ParseHostId(_) => Status::invalid_argument("host_id"),
             ParseCommandId(_) => Status::invalid_argument("command_id"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseCreatedAfter(_) => Status::invalid_argument("created_after"),
             RetryHint(_) => Status::invalid_argument("retry_hint_seconds"),
             UnknownExitCode => Status::invalid_argument("exit_code"),
+            AgentUpgrade(err) => err.into(),
+            Artifact(err) => err.into(),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
             Command(err) => err.into(),
             Config(err) => err.into(),
             Host(err) => err.into(),
+            LogSink(err) => err.into(),
             Node(err) => err.into(),
             NodeResponse(err) => (*err).into(),
             Protocol(err) => err.into(),
             ProtocolVersion(err) => err.into(),
             Recover(err) => err.into(),
             Resource(err) => err.into(),
+            Secret(err) => err.into(),
             Success(err) => err.into(),
         }
     }
@@ -128,6 +204,15 @@ impl CommandService for Grpc {
             .await
     }
 
+    async fn cancel(
+        &self,
+        req: Request<api::CommandServiceCancelRequest>,
+    ) -> Result<Response<api::CommandServiceCancelResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| cancel(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn list(
         &self,
         req: Request<api::CommandServiceListRequest>,
@@ -137,6 +222,15 @@ impl CommandService for Grpc {
             .await
     }
 
+    async fn list_upcoming(
+        &self,
+        req: Request<api::CommandServiceListUpcomingRequest>,
+    ) -> Result<Response<api::CommandServiceListUpcomingResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_upcoming(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
     async fn pending(
         &self,
         req: Request<api::CommandServicePendingRequest>,
@@ -182,6 +276,10 @@ async fn ack(
         warn!("Duplicate ack for command id: {0}", command.id);
     }
 
+    if command.command_type == CommandType::HostUpgrade {
+        AgentUpgradeHost::acked(command.id, &mut write).await?;
+    }
+
     if let Some(node) = command.node(&mut write).await? {
         ack_node_state(node, &command, &authz, &mut write).await?;
     }
@@ -274,10 +372,24 @@ async fn list(
         .map(|_| Option::from(req.exit_code()).ok_or(Error::UnknownExitCode))
         .transpose()?;
 
+    let created_after = req
+        .created_after
+        .map(|at| NanosUtc::try_from(at).map(|at| *at))
+        .transpose()
+        .map_err(Error::ParseCreatedAfter)?;
+    let limit = req
+        .limit
+        .map(i64::try_from)
+        .transpose()
+        .map_err(Error::Limit)?
+        .unwrap_or(MAX_LIST_LIMIT);
+
     let filter = CommandFilter {
         node_id,
         host_id,
         exit_code,
+        created_after,
+        limit,
     };
     let filtered = Command::list(filter, &mut read).await?;
 
@@ -291,6 +403,96 @@ async fn list(
     Ok(api::CommandServiceListResponse { commands })
 }
 
+async fn cancel(
+    req: api::CommandServiceCancelRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::CommandServiceCancelResponse, Error> {
+    let id = req.command_id.parse().map_err(Error::ParseCommandId)?;
+    let command = Command::by_id(id, &mut write).await?;
+
+    if let Some(node_id) = command.node_id {
+        write
+            .auth_or_for(
+                &meta,
+                CommandAdminPerm::Cancel,
+                CommandPerm::Cancel,
+                node_id,
+            )
+            .await?;
+    } else {
+        let host_id = command.host_id;
+        let authz = write
+            .auth_or_for(
+                &meta,
+                CommandAdminPerm::Cancel,
+                CommandPerm::Cancel,
+                host_id,
+            )
+            .await?;
+        let is_public = Host::deleted_org_id(host_id, &mut write).await?.is_none();
+        if is_public && authz.resource().host().is_none() {
+            return Err(Error::NotHostToken);
+        }
+    }
+
+    Command::cancel(id, &mut write).await?;
+
+    Ok(api::CommandServiceCancelResponse {})
+}
+
+async fn list_upcoming(
+    req: api::CommandServiceListUpcomingRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::CommandServiceListUpcomingResponse, Error> {
+    let node_id = req
+        .node_id
+        .as_deref()
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .transpose()?;
+    let host_id = req
+        .host_id
+        .as_deref()
+        .map(|id| id.parse().map_err(Error::ParseHostId))
+        .transpose()?;
+
+    if (node_id, host_id) == (None, None) {
+        return Err(Error::ListMissingNodeOrHost);
+    }
+
+    let resources: Vec<_> = node_id
+        .map(Resource::from)
+        .into_iter()
+        .chain(host_id.map(Resource::from))
+        .collect();
+    let authz = read
+        .auth_or_for(
+            &meta,
+            CommandAdminPerm::ListUpcoming,
+            CommandPerm::ListUpcoming,
+            &resources,
+        )
+        .await?;
+
+    if let Some(host_id) = host_id {
+        let is_public = Host::deleted_org_id(host_id, &mut read).await?.is_none();
+        if is_public && authz.resource().host().is_none() {
+            return Err(Error::NotHostToken);
+        }
+    }
+
+    let upcoming = Command::upcoming(host_id, node_id, &mut read).await?;
+    let mut commands = Vec::with_capacity(upcoming.len());
+    for command in upcoming {
+        if let Some(cmd) = api::Command::from(&command, &authz, &mut read).await? {
+            commands.push(cmd);
+        }
+    }
+
+    Ok(api::CommandServiceListUpcomingResponse { commands })
+}
+
 async fn pending(
     req: api::CommandServicePendingRequest,
     meta: Metadata,
@@ -306,11 +508,18 @@ async fn pending(
         )
         .await?;
 
+    let is_host = authz.resource().host().is_some();
     let pending = Command::host_pending(host_id, &mut read).await?;
     let mut commands = Vec::with_capacity(pending.len());
     for command in pending {
         match api::Command::from(&command, &authz, &mut read).await {
-            Ok(Some(cmd)) => commands.push(cmd),
+            Ok(Some(mut cmd)) => {
+                if is_host {
+                    let ctx = read.ctx;
+                    reveal_secrets(&mut cmd, &ctx.secret, &mut read).await?;
+                }
+                commands.push(cmd);
+            }
             Ok(None) => (),
             Err(err) => return Err(err),
         }
@@ -352,17 +561,42 @@ async fn update(
     };
     let updated = update.apply(id, &mut write).await?;
 
+    let artifact_ids = req
+        .artifact_ids
+        .iter()
+        .map(|id| id.parse().map(ArtifactId::from))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::ParseArtifactId)?;
+    Artifact::uploaded(&artifact_ids, &updated, &mut write).await?;
+
     let cmd = api::Command::from(&updated, &authz, &mut write)
         .await?
         .ok_or(Error::NoUpdateVisibility)?;
     write.mqtt(cmd.clone());
 
     match updated.exit_code {
-        Some(ExitCode::Ok) => success::confirm(&updated, &authz, &mut write).await?,
-        Some(_) => recover::recover(&updated, org_id, &authz, &mut write)
-            .await?
-            .into_iter()
-            .for_each(|cmd| write.mqtt(cmd)),
+        Some(ExitCode::Ok) => {
+            let config_hash = req.config_hash.as_deref();
+            success::confirm(&updated, config_hash, &authz, &mut write).await?;
+
+            for released in updated.release_dependents(&mut write).await? {
+                if let Some(cmd) = api::Command::from(&released, &authz, &mut write).await? {
+                    write.mqtt(cmd);
+                }
+            }
+        }
+        Some(_) => {
+            for failed in updated.fail_dependents(&mut write).await? {
+                if let Some(cmd) = api::Command::from(&failed, &authz, &mut write).await? {
+                    write.mqtt(cmd);
+                }
+            }
+
+            recover::recover(&updated, org_id, &authz, &mut write)
+                .await?
+                .into_iter()
+                .for_each(|cmd| write.mqtt(cmd));
+        }
         None => (),
     };
 
@@ -379,14 +613,22 @@ impl api::Command {
             CommandType::HostStart
             | CommandType::HostStop
             | CommandType::HostRestart
-            | CommandType::HostPending => Self::from_host(command),
+            | CommandType::HostPending
+            | CommandType::HostUpgrade
+            | CommandType::HostKeys
+            | CommandType::HostMesh
+            | CommandType::HostLogSink => Self::from_host(command),
             CommandType::NodeCreate
             | CommandType::NodeStart
             | CommandType::NodeStop
             | CommandType::NodeRestart
             | CommandType::NodeUpdate
             | CommandType::NodeUpgrade
-            | CommandType::NodeDelete => Self::from_node(command, authz, conn).await,
+            | CommandType::NodeDelete
+            | CommandType::NodeResize
+            | CommandType::NodeCertificate
+            | CommandType::NodeReportConfig
+            | CommandType::NodeDebugBundle => Self::from_node(command, authz, conn).await,
         }
     }
 
@@ -396,6 +638,10 @@ impl api::Command {
             CommandType::HostStop => host_stop(command).map(Some),
             CommandType::HostRestart => host_restart(command).map(Some),
             CommandType::HostPending => host_pending(command).map(Some),
+            CommandType::HostUpgrade => host_upgrade(command).map(Some),
+            CommandType::HostKeys => host_keys(command).map(Some),
+            CommandType::HostMesh => host_mesh(command).map(Some),
+            CommandType::HostLogSink => host_log_sink(command).map(Some),
             _ => Err(Error::NotHostCommand(command.id)),
         }
     }
@@ -413,11 +659,93 @@ impl api::Command {
             CommandType::NodeUpdate => node_update(command, conn).await.map(Some),
             CommandType::NodeUpgrade => node_upgrade(command, authz, conn).await,
             CommandType::NodeDelete => node_delete(command, conn).await.map(Some),
+            CommandType::NodeResize => node_resize(command, conn).await.map(Some),
+            CommandType::NodeCertificate => node_certificate(command, conn).await.map(Some),
+            CommandType::NodeReportConfig => node_report_config(command, conn).await.map(Some),
+            CommandType::NodeDebugBundle => node_debug_bundle(command, conn).await.map(Some),
             _ => Err(Error::NotNodeCommand(command.id)),
         }
     }
 }
 
+/// The message to release a scheduled command to its host once it is due.
+///
+/// Commands that carry a full node are only visible through an `AuthZ`, so
+/// those are left for the host to fetch as pending.
+pub async fn scheduled(
+    command: &Command,
+    conn: &mut Conn<'_>,
+) -> Result<Option<api::Command>, Error> {
+    match command.command_type {
+        CommandType::NodeCreate | CommandType::NodeUpgrade => Ok(None),
+        CommandType::NodeStart => node_start(command, conn).await.map(Some),
+        CommandType::NodeStop => node_stop(command, conn).await.map(Some),
+        CommandType::NodeRestart => node_restart(command, conn).await.map(Some),
+        CommandType::NodeUpdate => node_update(command, conn).await.map(Some),
+        CommandType::NodeDelete => node_delete(command, conn).await.map(Some),
+        CommandType::NodeResize => node_resize(command, conn).await.map(Some),
+        CommandType::NodeCertificate => node_certificate(command, conn).await.map(Some),
+        CommandType::NodeReportConfig => node_report_config(command, conn).await.map(Some),
+        CommandType::NodeDebugBundle => node_debug_bundle(command, conn).await.map(Some),
+        CommandType::HostStart
+        | CommandType::HostStop
+        | CommandType::HostRestart
+        | CommandType::HostPending
+        | CommandType::HostUpgrade
+        | CommandType::HostKeys
+        | CommandType::HostMesh
+        | CommandType::HostLogSink => api::Command::from_host(command),
+    }
+}
+
+/// Fill in the secret property values of a `NodeCreate` or `NodeUpgrade`
+/// command, or the credentials of a `HostLogSink` command, from the stored
+/// secrets of the node or org.
+///
+/// Node configs only hold the `secret_path` of each secret value, so this is
+/// only called for the pending commands of a host fetched with its own token.
+async fn reveal_secrets(
+    command: &mut api::Command,
+    secret: &Secret,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let node_cmd = match &mut command.command {
+        Some(api::command::Command::Node(node_cmd)) => node_cmd,
+        Some(api::command::Command::Host(api::HostCommand {
+            command: Some(api::host_command::Command::LogSink(sink)),
+            ..
+        })) => {
+            return org_log_sink::reveal_credentials(sink, secret, conn)
+                .await
+                .map_err(Into::into);
+        }
+        _ => return Ok(()),
+    };
+    let node = match &mut node_cmd.command {
+        Some(
+            api::node_command::Command::Create(api::NodeCreate { node: Some(node) })
+            | api::node_command::Command::Upgrade(api::NodeUpgrade { node: Some(node) }),
+        ) => node,
+        _ => return Ok(()),
+    };
+
+    let node_id: NodeId = node.node_id.parse().map_err(Error::ParseNodeId)?;
+    let values = node
+        .config
+        .as_mut()
+        .and_then(|config| config.image.as_mut())
+        .map(|image| &mut image.values);
+    for value in values.into_iter().flatten() {
+        if let Some(path) = &value.secret_path {
+            let key = SecretKey::new(path.clone())?;
+            let data = secret.get(Resource::from(node_id), &key, conn).await?;
+            value.value = String::from_utf8(data).map_err(Error::SecretUtf8)?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Create a new `api::HostCommand` from a `Command`.
 fn host_command(
     command: &Command,
@@ -438,6 +766,8 @@ fn host_command(
         retry_hint_seconds,
         created_at: Some(NanosUtc::from(command.created_at).into()),
         acked_at: command.acked_at.map(NanosUtc::from).map(Into::into),
+        execute_at: command.execute_at.map(NanosUtc::from).map(Into::into),
+        depends_on: command.depends_on.map(|id| id.to_string()),
         command: Some(api::command::Command::Host(api::HostCommand {
             host_id: command.host_id.to_string(),
             command: Some(host_cmd),
@@ -465,6 +795,51 @@ pub fn host_pending(command: &Command) -> Result<api::Command, Error> {
     host_command(command, host_cmd)
 }
 
+fn host_upgrade(command: &Command) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::HostUpgradeMissingProtobuf)?;
+    let upgrade: api::HostUpgrade =
+        Message::decode(&bytes[..]).map_err(Error::HostUpgradeDecode)?;
+
+    let host_cmd = api::host_command::Command::Upgrade(upgrade);
+    host_command(command, host_cmd)
+}
+
+fn host_keys(command: &Command) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::HostKeysMissingProtobuf)?;
+    let keys: api::HostKeys = Message::decode(&bytes[..]).map_err(Error::HostKeysDecode)?;
+
+    let host_cmd = api::host_command::Command::Keys(keys);
+    host_command(command, host_cmd)
+}
+
+fn host_mesh(command: &Command) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::HostMeshMissingProtobuf)?;
+    let mesh: api::HostMesh = Message::decode(&bytes[..]).map_err(Error::HostMeshDecode)?;
+
+    let host_cmd = api::host_command::Command::Mesh(mesh);
+    host_command(command, host_cmd)
+}
+
+fn host_log_sink(command: &Command) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::HostLogSinkMissingProtobuf)?;
+    let sink: api::HostLogSink = Message::decode(&bytes[..]).map_err(Error::HostLogSinkDecode)?;
+
+    let host_cmd = api::host_command::Command::LogSink(sink);
+    host_command(command, host_cmd)
+}
+
 /// Create a new `api::NodeCommand` from a `Command`.
 fn node_command(
     command: &Command,
@@ -486,6 +861,8 @@ fn node_command(
         retry_hint_seconds,
         created_at: Some(NanosUtc::from(command.created_at).into()),
         acked_at: command.acked_at.map(NanosUtc::from).map(Into::into),
+        execute_at: command.execute_at.map(NanosUtc::from).map(Into::into),
+        depends_on: command.depends_on.map(|id| id.to_string()),
         command: Some(api::command::Command::Node(api::NodeCommand {
             host_id: command.host_id.to_string(),
             node_id: node.id.to_string(),
@@ -522,7 +899,7 @@ async fn node_start(command: &Command, conn: &mut Conn<'_>) -> Result<api::Comma
     node_command(command, node, node_cmd)
 }
 
-async fn node_stop(command: &Command, conn: &mut Conn<'_>) -> Result<api::Command, Error> {
+pub async fn node_stop(command: &Command, conn: &mut Conn<'_>) -> Result<api::Command, Error> {
     let node_id = command.node_id.ok_or(Error::MissingNodeId)?;
     let node = Node::by_id(node_id, conn).await?;
     let node_cmd = api::node_command::Command::Stop(api::NodeStop {});
@@ -575,3 +952,72 @@ pub async fn node_delete(command: &Command, conn: &mut Conn<'_>) -> Result<api::
     let node_cmd = api::node_command::Command::Delete(api::NodeDelete {});
     node_command(command, node, node_cmd)
 }
+
+pub async fn node_resize(command: &Command, conn: &mut Conn<'_>) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::NodeResizeMissingProtobuf)?;
+    let resize: api::NodeResize = Message::decode(&bytes[..]).map_err(Error::NodeResizeDecode)?;
+
+    let node_id = command.node_id.ok_or(Error::MissingNodeId)?;
+    let node = Node::by_id(node_id, conn).await?;
+    let node_cmd = api::node_command::Command::Resize(resize);
+    node_command(command, node, node_cmd)
+}
+
+/// The certificate chain of a node, whose private key the host reads from the
+/// secret store rather than from the command.
+pub async fn node_certificate(
+    command: &Command,
+    conn: &mut Conn<'_>,
+) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::NodeCertificateMissingProtobuf)?;
+    let certificate: api::NodeCertificate =
+        Message::decode(&bytes[..]).map_err(Error::NodeCertificateDecode)?;
+
+    let node_id = command.node_id.ok_or(Error::MissingNodeId)?;
+    let node = Node::by_id(node_id, conn).await?;
+    let node_cmd = api::node_command::Command::Certificate(certificate);
+    node_command(command, node, node_cmd)
+}
+
+/// A request for the hash of the config that the host runs for a node.
+pub async fn node_report_config(
+    command: &Command,
+    conn: &mut Conn<'_>,
+) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::NodeReportConfigMissingProtobuf)?;
+    let report: api::NodeReportConfig =
+        Message::decode(&bytes[..]).map_err(Error::NodeReportConfigDecode)?;
+
+    let node_id = command.node_id.ok_or(Error::MissingNodeId)?;
+    let node = Node::by_id(node_id, conn).await?;
+    let node_cmd = api::node_command::Command::ReportConfig(report);
+    node_command(command, node, node_cmd)
+}
+
+/// A request for the host to archive the logs, process state and config of a
+/// node, and upload the archive as an artifact of the command.
+pub async fn node_debug_bundle(
+    command: &Command,
+    conn: &mut Conn<'_>,
+) -> Result<api::Command, Error> {
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::NodeDebugBundleMissingProtobuf)?;
+    let bundle: api::NodeDebugBundle =
+        Message::decode(&bytes[..]).map_err(Error::NodeDebugBundleDecode)?;
+
+    let node_id = command.node_id.ok_or(Error::MissingNodeId)?;
+    let node = Node::by_id(node_id, conn).await?;
+    let node_cmd = api::node_command::Command::DebugBundle(bundle);
+    node_command(command, node, node_cmd)
+}
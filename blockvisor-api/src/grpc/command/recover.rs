@@ -2,18 +2,22 @@
 
 use displaydoc::Display;
 use thiserror::Error;
-use tracing::warn;
 
 use crate::auth::AuthZ;
 use crate::auth::resource::{HostId, OrgId};
 use crate::database::WriteConn;
 use crate::grpc::{Status, api};
+use crate::model::agent_upgrade::AgentUpgradeHost;
 use crate::model::command::NewCommand;
 use crate::model::node::{LogEvent, NewNodeLog, UpdateNode};
+use crate::model::org_key::OrgKeyHost;
+use crate::model::org_log_sink::OrgLogSinkHost;
 use crate::model::{Command, CommandType, Host, IpAddress, Node, Protocol};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Command recovery agent upgrade error: {0}
+    AgentUpgrade(#[from] crate::model::agent_upgrade::Error),
     /// Failed to create cancelled log: {0}
     CancelledLog(crate::model::node::log::Error),
     /// Command recovery error: {0}
@@ -23,25 +27,25 @@ pub enum Error {
     /// Recovery of `CreateNode` command has no node id.
     CreateNodeId,
     /// Command recovery dns error: {0}
-    Dns(#[from] crate::cloudflare::Error),
+    Dns(#[from] crate::model::node::dns::Error),
     /// Failed to create deployment log: {0}
     DeploymentLog(crate::model::node::log::Error),
     /// Command recovery host error: {0}
     Host(#[from] crate::model::host::Error),
     /// Command recovery ip address: {0}
     IpAddress(#[from] crate::model::ip_address::Error),
+    /// Command recovery log sink error: {0}
+    LogSink(#[from] crate::model::org_log_sink::Error),
     /// Command recovery node error: {0}
     Node(#[from] crate::model::node::Error),
     /// No IP addresses available for host: {0}
     NoIps(HostId),
     /// No recovery visibilitiy of NodeCreate command.
     NoNodeCreate,
-    /// No recovery visibilitiy of NodeStart command.
-    NoNodeStart,
+    /// Command recovery org key error: {0}
+    OrgKey(#[from] crate::model::org_key::Error),
     /// Command protocol error: {0}
     Protocol(#[from] crate::model::protocol::Error),
-    /// Command recovery failed to build a new NodeStart command: {0}
-    StartCommand(Box<crate::grpc::command::Error>),
     /// Command recovery failed to update node: {0}
     UpdateNode(crate::model::node::Error),
 }
@@ -50,19 +54,21 @@ impl From<Error> for Status {
     fn from(err: Error) -> Self {
         use Error::*;
         match err {
-            Dns(_) => Status::internal("Internal error."),
             CreateNodeId => Status::invalid_argument("node_id"),
             NoIps(_) => Status::failed_precondition("No host IPs."),
-            NoNodeCreate | NoNodeStart => Status::forbidden("Access denied."),
+            NoNodeCreate => Status::forbidden("Access denied."),
+            AgentUpgrade(err) => err.into(),
             CancelledLog(err) => err.into(),
             Command(err) => err.into(),
             CreateCommand(err) => (*err).into(),
             DeploymentLog(err) => err.into(),
+            Dns(err) => err.into(),
             Host(err) => err.into(),
             IpAddress(err) => err.into(),
+            LogSink(err) => err.into(),
             Node(err) | UpdateNode(err) => err.into(),
+            OrgKey(err) => err.into(),
             Protocol(err) => err.into(),
-            StartCommand(err) => (*err).into(),
         }
     }
 }
@@ -75,11 +81,53 @@ pub(super) async fn recover(
     write: &mut WriteConn<'_, '_>,
 ) -> Result<Vec<api::Command>, Error> {
     match failed.command_type {
+        CommandType::HostUpgrade => host_upgrade_failed(failed, write).await,
+        CommandType::HostKeys => host_keys_failed(failed, write).await,
+        CommandType::HostLogSink => host_log_sink_failed(failed, write).await,
         CommandType::NodeCreate => node_create_failed(failed, org_id, authz, write).await,
         _ => Ok(vec![]),
     }
 }
 
+/// Record a failed host upgrade, which may halt its agent upgrade.
+async fn host_upgrade_failed(
+    failed: &Command,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<Vec<api::Command>, Error> {
+    let error = failed.exit_message.as_deref().unwrap_or("Upgrade failed.");
+    AgentUpgradeHost::finished(failed.id, Some(error), write).await?;
+
+    Ok(vec![])
+}
+
+/// Record that a host failed to apply its org keys.
+async fn host_keys_failed(
+    failed: &Command,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<Vec<api::Command>, Error> {
+    let error = failed
+        .exit_message
+        .as_deref()
+        .unwrap_or("Applying keys failed.");
+    OrgKeyHost::finished(failed.id, Some(error), write).await?;
+
+    Ok(vec![])
+}
+
+/// Record that a host failed to apply its org log sink.
+async fn host_log_sink_failed(
+    failed: &Command,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<Vec<api::Command>, Error> {
+    let error = failed
+        .exit_message
+        .as_deref()
+        .unwrap_or("Applying log sink failed.");
+    OrgLogSinkHost::finished(failed.id, Some(error), write).await?;
+
+    Ok(vec![])
+}
+
 /// Recover from a failed node creation.
 async fn node_create_failed(
     failed: &Command,
@@ -97,13 +145,10 @@ async fn node_create_failed(
         .await
         .map_err(Error::DeploymentLog)?;
 
-    if let Err(err) = write.ctx.dns.delete(&node.dns_id).await {
-        warn!("Failed to remove node dns for node {}: {err}", node.id);
-    }
-
     // find the next host to assign the node to
     let protocol = Protocol::by_id(node.protocol_id, org_id, authz, write).await?;
     let Some(host) = node.next_host(&protocol, write).await? else {
+        node.remove_dns(&**write.ctx.dns).await;
         return NewNodeLog::from(&node, authz, LogEvent::CreateCancelled)
             .create(write)
             .await
@@ -132,28 +177,23 @@ async fn node_create_failed(
         .map_err(Error::UpdateNode)?;
 
     Host::add_node(&node, write).await?;
-    write.ctx.dns.create(&node.dns_name, ip.ip.ip()).await?;
+    node.update_dns(write).await?;
 
     // notify blockvisor to create the new node
-    let mut commands = vec![];
     let create_cmd = NewCommand::node(&node, CommandType::NodeCreate)?
         .create(write)
         .await?;
-    let create_cmd = api::Command::from(&create_cmd, authz, write)
-        .await
-        .map_err(|err| Error::CreateCommand(Box::new(err)))?
-        .ok_or(Error::NoNodeCreate)?;
-    commands.push(create_cmd);
 
-    // and to start it
-    let start_cmd = NewCommand::node(&node, CommandType::NodeStart)?
+    // and to start it once created
+    NewCommand::node(&node, CommandType::NodeStart)?
+        .after(&create_cmd)
         .create(write)
         .await?;
-    let start_cmd = api::Command::from(&start_cmd, authz, write)
+
+    let create_cmd = api::Command::from(&create_cmd, authz, write)
         .await
-        .map_err(|err| Error::StartCommand(Box::new(err)))?
-        .ok_or(Error::NoNodeStart)?;
-    commands.push(start_cmd);
+        .map_err(|err| Error::CreateCommand(Box::new(err)))?
+        .ok_or(Error::NoNodeCreate)?;
 
-    Ok(commands)
+    Ok(vec![create_cmd])
 }
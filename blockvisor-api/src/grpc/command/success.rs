@@ -1,49 +1,84 @@
 use displaydoc::Display;
+use prost::Message;
 use thiserror::Error;
 use tracing::warn;
 
 use crate::auth::AuthZ;
-use crate::auth::resource::NodeId;
+use crate::auth::resource::{NodeId, Resource};
 use crate::database::WriteConn;
 use crate::grpc::{Status, api};
-use crate::model::CommandId;
-use crate::model::command::{Command, CommandType, NewCommand};
+use crate::model::agent_upgrade::AgentUpgradeHost;
+use crate::model::command::{Command, CommandType};
 use crate::model::node::{
-    LogEvent, NewNodeLog, Node, NodeJobs, NodeState, UpdateNodeMetrics, UpdateNodeState,
+    ConfigReport, LogEvent, NewNodeLog, Node, NodeJobs, NodeState, UpdateNodeMetrics,
+    UpdateNodeState, drift,
 };
+use crate::model::org_key::OrgKeyHost;
+use crate::model::org_log_sink::OrgLogSinkHost;
+use crate::model::user::setting::UserLocale;
+use crate::model::{Artifact, CommandId, User};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Command success agent upgrade error: {0}
+    AgentUpgrade(#[from] crate::model::agent_upgrade::Error),
+    /// Command success artifact error: {0}
+    Artifact(#[from] crate::model::artifact::Error),
     /// Command success model error: {0}
     Command(#[from] crate::model::command::Error),
+    /// Failed to decode NodeDebugBundle protobuf: {0}
+    DebugBundleDecode(prost::DecodeError),
     /// Command `{0}` failedto delete node `{1}`: {2}
     DeleteNode(CommandId, NodeId, crate::model::node::Error),
+    /// Command success config drift error: {0}
+    Drift(#[from] crate::model::node::drift::Error),
+    /// Command success email error: {0}
+    Email(#[from] crate::email::Error),
     /// Failed to serialize JSON: {0}
     Json(serde_json::Error),
+    /// Command success log sink error: {0}
+    LogSink(#[from] crate::model::org_log_sink::Error),
+    /// Command `{0}` is missing the reported `config_hash`.
+    MissingConfigHash(CommandId),
+    /// NodeDebugBundle command `{0}` is missing expected protobuf bytes.
+    MissingDebugBundle(CommandId),
     /// Command `{0}` is missing the `NodeId`.
     MissingNodeId(CommandId),
-    /// Failed to write a NodeStart command to MQTT: {0}
-    MqttStart(Box<super::Error>),
     /// Command success node error: {0}
     Node(#[from] crate::model::node::Error),
     /// Command success node log error: {0}
     NodeLog(#[from] crate::model::node::log::Error),
-    /// No success visibility of NodeStart command.
-    NoNodeStart,
+    /// Command success org key error: {0}
+    OrgKey(#[from] crate::model::org_key::Error),
+    /// Command success resource error: {0}
+    Resource(#[from] crate::auth::resource::Error),
+    /// Command success user error: {0}
+    User(#[from] crate::model::user::Error),
+    /// Command success user settings error: {0}
+    UserSettings(#[from] crate::model::user::setting::Error),
 }
 
 impl From<Error> for Status {
     fn from(err: Error) -> Self {
         use Error::*;
         match err {
-            Json(_) => Status::internal("Internal error."),
+            DebugBundleDecode(_) | Email(_) | Json(_) | MissingDebugBundle(_) => {
+                Status::internal("Internal error.")
+            }
+            MissingConfigHash(_) => Status::invalid_argument("config_hash"),
             MissingNodeId(_) => Status::invalid_argument("node_id"),
-            NoNodeStart => Status::forbidden("Access denied."),
             DeleteNode(_, _, err) => err.into(),
-            MqttStart(err) => (*err).into(),
+            AgentUpgrade(err) => err.into(),
+            Artifact(err) => err.into(),
             Command(err) => err.into(),
+            Drift(err) => err.into(),
+            LogSink(err) => err.into(),
             Node(err) => err.into(),
             NodeLog(err) => err.into(),
+            OrgKey(err) => err.into(),
+            Resource(err) => err.into(),
+            User(err) => err.into(),
+            UserSettings(err) => err.into(),
         }
     }
 }
@@ -51,18 +86,47 @@ impl From<Error> for Status {
 /// Confirm success and take additional action after receiving `ExitCode::Ok`.
 pub(super) async fn confirm(
     cmd: &Command,
+    config_hash: Option<&str>,
     authz: &AuthZ,
     write: &mut WriteConn<'_, '_>,
 ) -> Result<(), Error> {
     match cmd.command_type {
+        CommandType::HostUpgrade => host_upgraded(cmd, write).await,
+        CommandType::HostKeys => host_keys_applied(cmd, write).await,
+        CommandType::HostLogSink => host_log_sink_applied(cmd, write).await,
         CommandType::NodeCreate => node_created(cmd, authz, write).await,
         CommandType::NodeUpgrade => node_upgraded(cmd, write).await,
         CommandType::NodeDelete => node_deleted(cmd, write).await,
+        CommandType::NodeReportConfig => config_reported(cmd, config_hash, write).await,
+        CommandType::NodeDebugBundle => debug_bundle_collected(cmd, write).await,
         _ => Ok(()),
     }
 }
 
-/// After NodeCreate, write a log and send a start command.
+/// After HostUpgrade, record the host as upgraded by its agent upgrade.
+async fn host_upgraded(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    AgentUpgradeHost::finished(cmd.id, None, write)
+        .await
+        .map_err(Into::into)
+}
+
+/// After HostKeys, record the org keys as applied to the host.
+async fn host_keys_applied(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    OrgKeyHost::finished(cmd.id, None, write)
+        .await
+        .map_err(Into::into)
+}
+
+/// After HostLogSink, record the org log sink as applied to the host.
+async fn host_log_sink_applied(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    OrgLogSinkHost::finished(cmd.id, None, write)
+        .await
+        .map_err(Into::into)
+}
+
+/// After NodeCreate, write a log.
+///
+/// The NodeStart command created alongside it is released as a dependent.
 async fn node_created(
     cmd: &Command,
     authz: &AuthZ,
@@ -75,15 +139,6 @@ async fn node_created(
         .create(write)
         .await?;
 
-    let start_cmd = NewCommand::node(&node, CommandType::NodeStart)?
-        .create(write)
-        .await?;
-    let start_cmd = api::Command::from(&start_cmd, authz, write)
-        .await
-        .map_err(|err| Error::MqttStart(Box::new(err)))?
-        .ok_or(Error::NoNodeStart)?;
-    write.mqtt(start_cmd);
-
     Ok(())
 }
 
@@ -98,13 +153,42 @@ async fn node_upgraded(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<(
         block_height: None,
         block_age: None,
         consensus: None,
+        peer_count: None,
         jobs: Some(NodeJobs(vec![])),
+        cpu_percent: None,
     };
     let _updated = update.apply(write).await?;
 
     Ok(())
 }
 
+/// After NodeReportConfig, compare the running config of the host against the
+/// stored config, and alert once when the node starts drifting.
+async fn config_reported(
+    cmd: &Command,
+    config_hash: Option<&str>,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let config_hash = config_hash.ok_or_else(|| Error::MissingConfigHash(cmd.id))?;
+    let report = drift::report(cmd, config_hash, write).await?;
+
+    if let ConfigReport::Drifted {
+        config_id,
+        expected_hash,
+        started: true,
+    } = report
+    {
+        let node_id = cmd.node_id.ok_or_else(|| Error::MissingNodeId(cmd.id))?;
+        let node = Node::by_id(node_id, write).await?;
+        warn!("Node {node_id} is not running its config {config_id}");
+
+        let alert = api::NodeMessage::config_drift(&node, config_id, expected_hash, config_hash);
+        write.mqtt(alert);
+    }
+
+    Ok(())
+}
+
 /// After NodeDelete, set the node status to deleted.
 async fn node_deleted(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
     let node = cmd
@@ -127,3 +211,42 @@ async fn node_deleted(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<()
 
     Ok(())
 }
+
+/// After NodeDebugBundle, email the requesting user a link to the uploaded
+/// bundle.
+async fn debug_bundle_collected(cmd: &Command, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    let bytes = cmd
+        .protobuf
+        .as_ref()
+        .ok_or_else(|| Error::MissingDebugBundle(cmd.id))?;
+    let bundle: api::NodeDebugBundle =
+        Message::decode(&bytes[..]).map_err(Error::DebugBundleDecode)?;
+    let requested_by = bundle
+        .requested_by
+        .as_ref()
+        .map(Resource::try_from)
+        .transpose()?;
+    let Some(user_id) = requested_by.and_then(Resource::user) else {
+        return Ok(());
+    };
+
+    let Some(artifact) = Artifact::by_command_id(cmd.id, write).await?.pop() else {
+        warn!(
+            "Debug bundle command `{}` succeeded without an artifact",
+            cmd.id
+        );
+        return Ok(());
+    };
+    let node_id = cmd.node_id.ok_or_else(|| Error::MissingNodeId(cmd.id))?;
+    let node = Node::by_id(node_id, write).await?;
+    let user = User::by_id(user_id, write).await?;
+    let locale = UserLocale::by_user(user_id, write).await?;
+
+    if let Some(email) = write.ctx.email.as_ref() {
+        email.debug_bundle(&user, &locale, &node, &artifact).await?;
+    } else {
+        warn!("Unable to send debug bundle email without email configured");
+    }
+
+    Ok(())
+}
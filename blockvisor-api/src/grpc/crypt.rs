@@ -75,7 +75,8 @@ async fn get_secret(
     let _id = resource.id_exists(&mut read).await?;
 
     let key = SecretKey::new(req.key)?;
-    let data = read.ctx.secret.get(resource, &key)?;
+    let ctx = read.ctx;
+    let data = ctx.secret.get(resource, &key, &mut read).await?;
 
     Ok(api::CryptServiceGetSecretResponse { value: data })
 }
@@ -93,7 +94,10 @@ async fn put_secret(
     let _id = resource.id_exists(&mut write).await?;
 
     let key = SecretKey::new(req.key)?;
-    write.ctx.secret.put(resource, &key, &req.value)?;
+    let ctx = write.ctx;
+    ctx.secret
+        .put(resource, &key, &req.value, &mut write)
+        .await?;
 
     Ok(api::CryptServicePutSecretResponse {})
 }
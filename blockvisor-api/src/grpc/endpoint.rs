@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Days, NaiveTime, TimeDelta, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::{error, warn};
+
+use crate::auth::Authorize;
+use crate::auth::rbac::{BillingPerm, EndpointAdminPerm, EndpointPerm};
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::alert::{AlertKind, NewAlert};
+use crate::model::endpoint::{Endpoint, EndpointId, EndpointUsage, NewEndpoint, UsageReport};
+use crate::model::plan::Entitlements;
+use crate::model::{Node, Org};
+use crate::stripe::{Payment, Subscription};
+use crate::util::NanosUtc;
+
+use super::api::endpoint_service_server::EndpointService;
+use super::{Grpc, Metadata, Status, api, common};
+
+/// Percentages of the monthly quota at which an alert is sent.
+const QUOTA_ALERT_PERCENTS: [i64; 2] = [80, 100];
+/// The default period of usage returned when no start is given.
+const USAGE_DEFAULT_PERIOD: TimeDelta = TimeDelta::days(1);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Endpoint alert error: {0}
+    Alert(#[from] crate::model::alert::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Usage report for unknown endpoint `{0}`.
+    MissingEndpoint(EndpointId),
+    /// Endpoint model error: {0}
+    Model(#[from] crate::model::endpoint::Error),
+    /// Monthly quota of {0} requests is too large.
+    MonthlyQuota(u64),
+    /// Endpoint node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Node `{0}` does not belong to org `{1}`.
+    NodeOrg(NodeId, OrgId),
+    /// Endpoint org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Failed to parse EndpointId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse timestamp: {0}
+    ParseTimestamp(crate::util::timestamp::Error),
+    /// Endpoint plan error: {0}
+    Plan(#[from] crate::model::plan::Error),
+    /// Rate limit of {0} requests per second is too large.
+    RateLimit(u32),
+    /// Usage report has too many {0}.
+    ReportOverflow(&'static str),
+    /// Usage report is missing a timestamp.
+    ReportTimestamp,
+    /// Endpoint stripe error: {0}
+    Stripe(#[from] crate::stripe::Error),
+    /// Endpoint usage error: {0}
+    Usage(#[from] crate::model::endpoint::usage::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) | Stripe(_) => Status::internal("Internal error."),
+            MissingEndpoint(_) | ParseId(_) => Status::invalid_argument("endpoint_id"),
+            MonthlyQuota(_) => Status::invalid_argument("monthly_quota"),
+            NodeOrg(..) | ParseNodeId(_) => Status::invalid_argument("node_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseTimestamp(_) | ReportTimestamp => Status::invalid_argument("timestamp"),
+            RateLimit(_) => Status::invalid_argument("rate_limit_rps"),
+            ReportOverflow(field) => Status::invalid_argument(field),
+            Alert(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+            Org(err) => err.into(),
+            Plan(err) => err.into(),
+            Usage(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl EndpointService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::EndpointServiceCreateRequest>,
+    ) -> Result<Response<api::EndpointServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::EndpointServiceListRequest>,
+    ) -> Result<Response<api::EndpointServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn revoke(
+        &self,
+        req: Request<api::EndpointServiceRevokeRequest>,
+    ) -> Result<Response<api::EndpointServiceRevokeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn usage(
+        &self,
+        req: Request<api::EndpointServiceUsageRequest>,
+    ) -> Result<Response<api::EndpointServiceUsageResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| usage(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn report_usage(
+        &self,
+        req: Request<api::EndpointServiceReportUsageRequest>,
+    ) -> Result<Response<api::EndpointServiceReportUsageResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| report_usage(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::EndpointServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::EndpointServiceCreateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth_for(&meta, EndpointPerm::Create, org_id).await?;
+    let entitlements = Entitlements::for_org(org_id, &mut write).await?;
+    entitlements.check_awake()?;
+
+    let node_id = if let Some(id) = &req.node_id {
+        let node_id: NodeId = id.parse().map_err(Error::ParseNodeId)?;
+        let node = Node::by_id(node_id, &mut write).await?;
+        if node.org_id != org_id {
+            return Err(Error::NodeOrg(node_id, org_id));
+        }
+        Some(node_id)
+    } else {
+        None
+    };
+
+    let rate_limit_rps = req
+        .rate_limit_rps
+        .map(|rps| i32::try_from(rps).map_err(|_| Error::RateLimit(rps)))
+        .transpose()?;
+    let monthly_quota = req
+        .monthly_quota
+        .map(|quota| i64::try_from(quota).map_err(|_| Error::MonthlyQuota(quota)))
+        .transpose()?;
+
+    // Endpoint requests are only charged for when billing has added the
+    // metered price to the subscription of the org.
+    let ctx = write.ctx;
+    let stripe_item_id = match (&ctx.config.endpoint.metered_sku, &ctx.stripe) {
+        (Some(sku), Some(stripe)) if !authz.has_perm(BillingPerm::Exempt) => {
+            let org = Org::by_id(org_id, &mut write).await?;
+            stripe
+                .find_metered_item(&org, &ctx.keyring, sku)
+                .await?
+                .map(|item| item.id)
+        }
+        _ => None,
+    };
+
+    let new_endpoint = NewEndpoint {
+        org_id,
+        node_id,
+        label: req.label,
+        allowed_methods: req.allowed_methods,
+        rate_limit_rps,
+        monthly_quota,
+        stripe_item_id,
+        created_by: authz.resource(),
+    };
+    let created = new_endpoint.create(&mut write).await?;
+    entitlements.check_endpoints(&mut write).await?;
+
+    let created_by = common::Resource::from(&authz);
+    write.mqtt(api::EndpointMessage::created(&created.endpoint, created_by));
+
+    let url = write.ctx.config.endpoint.url(created.endpoint.id);
+    Ok(api::EndpointServiceCreateResponse {
+        endpoint: Some(created.endpoint.into_api(url)),
+        token: created.token,
+    })
+}
+
+pub async fn list(
+    req: api::EndpointServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::EndpointServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let node_id: Option<NodeId> = req
+        .node_id
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .transpose()?;
+    read.auth_for(&meta, EndpointPerm::List, org_id).await?;
+
+    let config = &read.ctx.config.endpoint;
+    let endpoints = Endpoint::by_org(org_id, node_id, &mut read)
+        .await?
+        .into_iter()
+        .map(|endpoint| {
+            let url = config.url(endpoint.id);
+            endpoint.into_api(url)
+        })
+        .collect();
+
+    Ok(api::EndpointServiceListResponse { endpoints })
+}
+
+pub async fn revoke(
+    req: api::EndpointServiceRevokeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::EndpointServiceRevokeResponse, Error> {
+    let id: EndpointId = req.endpoint_id.parse().map_err(Error::ParseId)?;
+    let endpoint = Endpoint::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, EndpointPerm::Revoke, endpoint.org_id)
+        .await?;
+
+    let revoked = endpoint.revoke(&mut write).await?;
+    let revoked_by = common::Resource::from(&authz);
+    write.mqtt(api::EndpointMessage::revoked(&revoked, revoked_by));
+
+    Ok(api::EndpointServiceRevokeResponse {})
+}
+
+pub async fn usage(
+    req: api::EndpointServiceUsageRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::EndpointServiceUsageResponse, Error> {
+    let id: EndpointId = req.endpoint_id.parse().map_err(Error::ParseId)?;
+    let endpoint = Endpoint::by_id(id, &mut read).await?;
+    read.auth_or_for(
+        &meta,
+        EndpointAdminPerm::Usage,
+        EndpointPerm::Usage,
+        endpoint.org_id,
+    )
+    .await?;
+
+    let end = match req.end {
+        Some(end) => *NanosUtc::try_from(end).map_err(Error::ParseTimestamp)?,
+        None => Utc::now(),
+    };
+    let start = match req.start {
+        Some(start) => *NanosUtc::try_from(start).map_err(Error::ParseTimestamp)?,
+        None => end - USAGE_DEFAULT_PERIOD,
+    };
+
+    let usage = EndpointUsage::by_endpoint(id, start, end, &mut read).await?;
+    let quota_used = EndpointUsage::requests_since(id, month_start(Utc::now()), &mut read).await?;
+
+    Ok(api::EndpointServiceUsageResponse {
+        usage: usage.into_iter().map(Into::into).collect(),
+        monthly_quota: endpoint
+            .monthly_quota
+            .and_then(|quota| u64::try_from(quota).ok()),
+        quota_used: u64::try_from(quota_used).unwrap_or_default(),
+    })
+}
+
+/// Record the usage of endpoints as reported by the gateway.
+///
+/// Each report is added to the hourly rollup of its endpoint, alerts the org
+/// as the monthly quota is approached, and is sent on to stripe for metered
+/// endpoints.
+pub async fn report_usage(
+    req: api::EndpointServiceReportUsageRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::EndpointServiceReportUsageResponse, Error> {
+    write.auth(&meta, EndpointAdminPerm::ReportUsage).await?;
+    let ctx = write.ctx;
+
+    let ids = req
+        .reports
+        .iter()
+        .map(|report| report.endpoint_id.parse().map_err(Error::ParseId))
+        .collect::<Result<Vec<EndpointId>, _>>()?;
+    let endpoints: HashMap<EndpointId, Endpoint> = Endpoint::by_ids(ids.clone(), &mut write)
+        .await?
+        .into_iter()
+        .map(|endpoint| (endpoint.id, endpoint))
+        .collect();
+
+    for (id, report) in ids.into_iter().zip(req.reports) {
+        let endpoint = endpoints.get(&id).ok_or(Error::MissingEndpoint(id))?;
+        let timestamp = report.timestamp.ok_or(Error::ReportTimestamp)?;
+        let at = *NanosUtc::try_from(timestamp).map_err(Error::ParseTimestamp)?;
+
+        let count =
+            |value: u64, field| i64::try_from(value).map_err(|_| Error::ReportOverflow(field));
+        let report = UsageReport::new(
+            id,
+            at,
+            count(report.request_count, "request_count")?,
+            count(report.error_count, "error_count")?,
+            count(report.latency_ms_total, "latency_ms_total")?,
+            count(report.latency_ms_max, "latency_ms_max")?,
+        )?;
+        let (hour, requests) = (report.hour, report.request_count);
+
+        let before = EndpointUsage::requests_since(id, month_start(hour), &mut write).await?;
+        report.record(&mut write).await?;
+
+        if let Some(quota) = endpoint.monthly_quota {
+            let after = before + requests;
+            for percent in QUOTA_ALERT_PERCENTS {
+                let limit = quota.saturating_mul(percent) / 100;
+                if before < limit && after >= limit {
+                    let alert = api::EndpointMessage::quota_alert(endpoint, after, percent);
+                    write.mqtt(alert);
+                    let new_alert = NewAlert {
+                        org_id: endpoint.org_id,
+                        node_id: endpoint.node_id,
+                        kind: AlertKind::EndpointQuota,
+                        message: format!(
+                            "Endpoint {} reached {percent}% of its monthly quota.",
+                            endpoint.label
+                        ),
+                    };
+                    new_alert.create(&mut write).await?;
+                }
+            }
+        }
+
+        if let (Some(item_id), Some(stripe)) = (&endpoint.stripe_item_id, &ctx.stripe) {
+            // a failed usage record shouldn't make the gateway report it twice
+            if let Err(err) = stripe
+                .create_usage_record(item_id, requests.unsigned_abs(), hour)
+                .await
+            {
+                warn!("Failed to report usage of endpoint `{id}` to stripe: {err}");
+            }
+        }
+    }
+
+    Ok(api::EndpointServiceReportUsageResponse {})
+}
+
+/// The start of the calendar month containing `at`.
+fn month_start(at: DateTime<Utc>) -> DateTime<Utc> {
+    let first = at.date_naive() - Days::new(u64::from(at.day0()));
+    first.and_time(NaiveTime::MIN).and_utc()
+}
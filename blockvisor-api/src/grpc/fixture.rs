@@ -0,0 +1,89 @@
+//! Seed named fixture scenarios into a running test api.
+//!
+//! Only built with the `integration-test` feature, so it has no auth.
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::database::WriteConn;
+use crate::database::seed::scenario::Scenario;
+
+use super::api::fixture_service_server::FixtureService;
+use super::{Grpc, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Fixture scenario error: {0}
+    Scenario(#[from] crate::database::seed::scenario::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            Scenario(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl FixtureService for Grpc {
+    async fn seed(
+        &self,
+        req: Request<api::FixtureServiceSeedRequest>,
+    ) -> Result<Response<api::FixtureServiceSeedResponse>, tonic::Status> {
+        let (_, _, req) = req.into_parts();
+        self.write(|write| seed(req, write).scope_boxed()).await
+    }
+
+    async fn reset(
+        &self,
+        req: Request<api::FixtureServiceResetRequest>,
+    ) -> Result<Response<api::FixtureServiceResetResponse>, tonic::Status> {
+        let (_, _, req) = req.into_parts();
+        self.write(|write| reset(req, write).scope_boxed()).await
+    }
+}
+
+/// Create the fixtures of a scenario that has not been seeded yet.
+pub async fn seed(
+    req: api::FixtureServiceSeedRequest,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::FixtureServiceSeedResponse, Error> {
+    let scenario = Scenario::from_name(&req.scenario)?;
+    let seeded = scenario.seed(&mut write).await?;
+
+    Ok(api::FixtureServiceSeedResponse {
+        org_ids: ids(&seeded.org_ids),
+        host_ids: ids(&seeded.host_ids),
+        node_ids: ids(&seeded.node_ids),
+        image_ids: ids(&seeded.image_ids),
+    })
+}
+
+/// Delete any fixtures of a scenario, then seed it again.
+pub async fn reset(
+    req: api::FixtureServiceResetRequest,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::FixtureServiceResetResponse, Error> {
+    let scenario = Scenario::from_name(&req.scenario)?;
+    let seeded = scenario.reset(&mut write).await?;
+
+    Ok(api::FixtureServiceResetResponse {
+        org_ids: ids(&seeded.org_ids),
+        host_ids: ids(&seeded.host_ids),
+        node_ids: ids(&seeded.node_ids),
+        image_ids: ids(&seeded.image_ids),
+    })
+}
+
+fn ids<T: ToString>(ids: &[T]) -> Vec<String> {
+    ids.iter().map(ToString::to_string).collect()
+}
@@ -1,11 +1,11 @@
 use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
 use thiserror::Error;
 use tonic::{Request, Response};
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::auth::claims::Claims;
 use crate::auth::rbac::{GrpcRole, HostAdminPerm, HostPerm};
@@ -13,23 +13,42 @@ use crate::auth::resource::{HostId, OrgId, Resource};
 use crate::auth::token::refresh::Refresh;
 use crate::auth::{AuthZ, Authorize};
 use crate::database::{Conn, ReadConn, Transaction, WriteConn};
-use crate::model::command::NewCommand;
+use crate::model::agent_upgrade::{
+    AgentUpgrade, AgentUpgradeHost, AgentUpgradePolicy, NewAgentUpgrade,
+};
+use crate::model::command::{Command, NewCommand};
 use crate::model::host::{
-    Host, HostFilter, HostRequirements, HostSearch, HostSort, NewHost, UpdateHost,
+    ConnectionStatus, Host, HostFilter, HostRequirements, HostSearch, HostSort, NewHost, UpdateHost,
 };
+use crate::model::host_pool::{HostPool, HostPoolId, NewHostPool, PoolCapacity, UpdateHostPool};
+use crate::model::host_weights::HostWeights;
+use crate::model::mesh::MeshPeer;
 use crate::model::node::NodeScheduler;
+use crate::model::plan::Entitlements;
+use crate::model::reconciliation::Reconciliation;
 use crate::model::region::{NewRegion, RegionKey, UpdateRegion};
-use crate::model::sql::{IpNetwork, Tag, Version};
+use crate::model::sql::{Tag, Version};
 use crate::model::{
-    CommandType, Image, IpAddress, Node, Org, Protocol, ProtocolVersion, Region, RegionId, Token,
+    CommandType, Image, ManagedHost, Node, OrgKey, OrgLogSink, Protocol, ProtocolId,
+    ProtocolVersion, Region, Token,
 };
-use crate::util::{HashVec, NanosUtc};
+use crate::util::NanosUtc;
 
+use super::api::host_service_region_availability_request::Spec;
 use super::api::host_service_server::HostService;
+use super::relations::NodeRelations;
+use super::update_mask::UpdateMask;
 use super::{Grpc, Metadata, Status, api, common};
 
+/// The number of hosts upgraded at once when not requested.
+const DEFAULT_WAVE_SIZE: u32 = 10;
+/// How long to wait between waves when not requested.
+const DEFAULT_WAVE_INTERVAL_SECONDS: u32 = 5 * 60;
+
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Host agent upgrade error: {0}
+    AgentUpgrade(#[from] crate::model::agent_upgrade::Error),
     /// Host amount error: {0}
     Amount(#[from] crate::model::sql::amount::Error),
     /// Auth check failed: {0}
@@ -50,10 +69,14 @@ pub enum Error {
     FilterLimit(std::num::TryFromIntError),
     /// Failed to parse filter offset as i64: {0}
     FilterOffset(std::num::TryFromIntError),
+    /// Failed to parse gpu count: {0}
+    GpuCount(std::num::TryFromIntError),
     /// This host cannot be deleted because it still has nodes.
     HasNodes,
     /// Host model error: {0}
     Host(#[from] crate::model::host::Error),
+    /// Host pool error: {0}
+    HostPool(#[from] crate::model::host_pool::Error),
     /// Host token error: {0}
     HostProvisionByToken(crate::model::token::Error),
     /// Host image error: {0}
@@ -64,10 +87,24 @@ pub enum Error {
     Jwt(#[from] crate::auth::token::jwt::Error),
     /// Lookup missing Region. This should not happen.
     LookupMissingRegion,
+    /// Host managed host error: {0}
+    ManagedHost(#[from] crate::model::managed_host::Error),
     /// Failed to parse memory bytes: {0}
     MemoryBytes(std::num::TryFromIntError),
+    /// Failed to parse max_failures: {0}
+    MaxFailures(std::num::TryFromIntError),
+    /// Host mesh error: {0}
+    Mesh(#[from] crate::grpc::mesh::Error),
+    /// Host mesh peer error: {0}
+    MeshPeer(#[from] crate::model::mesh::Error),
     /// Missing the region to get info for.
     MissingRegion,
+    /// Missing the image or resources to check availability for.
+    MissingSpec,
+    /// Missing the hosts to upgrade.
+    MissingTarget,
+    /// Failed to parse network bandwidth: {0}
+    NetworkBandwidth(std::num::TryFromIntError),
     /// Node model error: {0}
     Node(#[from] crate::model::node::Error),
     /// No visibility of HostRestart command.
@@ -76,10 +113,20 @@ pub enum Error {
     NoHostStart,
     /// No visibility of HostStop command.
     NoHostStop,
+    /// No visibility of HostUpgrade command.
+    NoHostUpgrade,
+    /// Failed to parse nvme iops: {0}
+    NvmeIops(std::num::TryFromIntError),
     /// Host org error: {0}
     Org(#[from] crate::model::org::Error),
+    /// Host org key error: {0}
+    OrgKey(#[from] crate::model::org_key::Error),
+    /// Host org log sink error: {0}
+    OrgLogSink(#[from] crate::model::org_log_sink::Error),
     /// Failed to parse bv_version: {0}
     ParseBvVersion(crate::model::sql::Error),
+    /// Failed to parse AgentUpgradeId: {0}
+    ParseAgentUpgradeId(uuid::Error),
     /// Failed to parse HostId: {0}
     ParseId(uuid::Error),
     /// Failed to parse ImageId: {0}
@@ -94,16 +141,32 @@ pub enum Error {
     ParseNodeCount(std::num::TryFromIntError),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
+    /// Failed to parse HostPoolId: {0}
+    ParsePoolId(uuid::Error),
+    /// Failed to parse ProtocolId: {0}
+    ParseProtocolId(uuid::Error),
     /// Failed to parse RegionId: {0}
     ParseRegionId(uuid::Error),
+    /// Failed to parse upgrade version: {0}
+    ParseUpgradeVersion(crate::model::sql::Error),
+    /// Host plan error: {0}
+    Plan(#[from] crate::model::plan::Error),
+    /// Host pool `{0}` belongs to another org.
+    PoolOrg(HostPoolId),
     /// Host protocol error: {0}
     Protocol(#[from] crate::model::protocol::Error),
     /// Host protocol version error: {0}
     ProtocolVersion(#[from] crate::model::protocol::version::Error),
+    /// Public host `{0}` cannot be added to a pool.
+    PublicHostPool(HostId),
+    /// Host reconciliation error: {0}
+    Reconciliation(#[from] crate::model::reconciliation::Error),
     /// Host Refresh token failure: {0}
     Refresh(#[from] crate::auth::token::refresh::Error),
     /// Host region error: {0}
     Region(#[from] crate::model::region::Error),
+    /// Host relations error: {0}
+    Relations(#[from] crate::grpc::relations::Error),
     /// Host search failed: {0}
     SearchOperator(crate::util::search::Error),
     /// Sort order: {0}
@@ -114,6 +177,12 @@ pub enum Error {
     Store(#[from] crate::store::Error),
     /// The requested sort field is unknown.
     UnknownSortField,
+    /// Host update mask: {0}
+    UpdateMask(#[from] crate::grpc::update_mask::Error),
+    /// Failed to parse wave_interval_seconds: {0}
+    WaveInterval(std::num::TryFromIntError),
+    /// Failed to parse wave_size: {0}
+    WaveSize(std::num::TryFromIntError),
 }
 
 impl From<Error> for Status {
@@ -128,11 +197,20 @@ impl From<Error> for Status {
             DiskBytes(_) => Status::out_of_range("disk_bytes"),
             FilterLimit(_) => Status::invalid_argument("limit"),
             FilterOffset(_) => Status::invalid_argument("offset"),
+            GpuCount(_) => Status::out_of_range("gpu_count"),
             HasNodes => Status::failed_precondition("This host still has nodes."),
             HostProvisionByToken(_) => Status::forbidden("Invalid token."),
+            MaxFailures(_) => Status::out_of_range("max_failures"),
             MemoryBytes(_) => Status::out_of_range("memory_bytes"),
             MissingRegion => Status::out_of_range("region"),
-            NoHostRestart | NoHostStart | NoHostStop => Status::forbidden("Access denied."),
+            MissingSpec => Status::invalid_argument("spec"),
+            MissingTarget => Status::invalid_argument("target"),
+            NetworkBandwidth(_) => Status::out_of_range("network_bandwidth_mbps"),
+            NoHostRestart | NoHostStart | NoHostStop | NoHostUpgrade => {
+                Status::forbidden("Access denied.")
+            }
+            NvmeIops(_) => Status::out_of_range("nvme_iops"),
+            ParseAgentUpgradeId(_) => Status::invalid_argument("agent_upgrade_id"),
             ParseBvVersion(_) => Status::invalid_argument("bv_version"),
             ParseId(_) => Status::invalid_argument("host_id"),
             ParseImageId(_) => Status::invalid_argument("image_id"),
@@ -140,29 +218,67 @@ impl From<Error> for Status {
             ParseIpAddress(_) => Status::invalid_argument("ip_address"),
             ParseIpGateway(_) => Status::invalid_argument("ip_gateway"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParsePoolId(_) => Status::invalid_argument("pool_id"),
+            ParseProtocolId(_) => Status::invalid_argument("protocol_id"),
             ParseRegionId(_) => Status::invalid_argument("region_id"),
+            ParseUpgradeVersion(_) => Status::invalid_argument("version"),
+            PoolOrg(_) => Status::not_found("Host pool not found."),
+            PublicHostPool(_) => {
+                Status::failed_precondition("Only private hosts can be added to a pool.")
+            }
             SearchOperator(_) => Status::invalid_argument("search.operator"),
             SortOrder(_) => Status::invalid_argument("sort.order"),
             UnknownSortField => Status::invalid_argument("sort.field"),
+            WaveInterval(_) => Status::out_of_range("wave_interval_seconds"),
+            WaveSize(_) => Status::out_of_range("wave_size"),
+            AgentUpgrade(err) => err.into(),
             Amount(err) => err.into(),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
             Command(err) => err.into(),
             CommandApi(err) => err.into(),
             Host(err) => err.into(),
+            HostPool(err) => err.into(),
             Image(err) => err.into(),
             IpAddress(err) => err.into(),
+            ManagedHost(err) => err.into(),
+            Mesh(err) => err.into(),
+            MeshPeer(err) => err.into(),
             Node(err) => err.into(),
             Org(err) => err.into(),
+            OrgKey(err) => err.into(),
+            OrgLogSink(err) => err.into(),
+            Plan(err) => err.into(),
             Protocol(err) => err.into(),
             ProtocolVersion(err) => err.into(),
+            Reconciliation(err) => err.into(),
             Region(err) => err.into(),
+            Relations(err) => err.into(),
             Sql(err) => err.into(),
             Store(err) => err.into(),
+            UpdateMask(err) => err.into(),
         }
     }
 }
 
+/// The fields of `HostServiceUpdateHostRequest` that an update mask may name.
+const UPDATE_HOST_FIELDS: &[&str] = &[
+    "network_name",
+    "display_name",
+    "region_id",
+    "schedule_type",
+    "os",
+    "os_version",
+    "bv_version",
+    "disk_bytes",
+    "gpu_count",
+    "gpu_model",
+    "nvme_iops",
+    "network_bandwidth_mbps",
+    "update_tags",
+    "cost",
+];
+
 #[tonic::async_trait]
 impl HostService for Grpc {
     async fn create_host(
@@ -272,6 +388,132 @@ impl HostService for Grpc {
         self.write(|write| restart(req, meta.into(), write).scope_boxed())
             .await
     }
+
+    async fn version_report(
+        &self,
+        req: Request<api::HostServiceVersionReportRequest>,
+    ) -> Result<Response<api::HostServiceVersionReportResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| version_report(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn upgrade_agent(
+        &self,
+        req: Request<api::HostServiceUpgradeAgentRequest>,
+    ) -> Result<Response<api::HostServiceUpgradeAgentResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| upgrade_agent(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_agent_upgrade(
+        &self,
+        req: Request<api::HostServiceGetAgentUpgradeRequest>,
+    ) -> Result<Response<api::HostServiceGetAgentUpgradeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_agent_upgrade(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn halt_agent_upgrade(
+        &self,
+        req: Request<api::HostServiceHaltAgentUpgradeRequest>,
+    ) -> Result<Response<api::HostServiceHaltAgentUpgradeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| halt_agent_upgrade(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn upgrade_outdated(
+        &self,
+        req: Request<api::HostServiceUpgradeOutdatedRequest>,
+    ) -> Result<Response<api::HostServiceUpgradeOutdatedResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| upgrade_outdated(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn create_pool(
+        &self,
+        req: Request<api::HostServiceCreatePoolRequest>,
+    ) -> Result<Response<api::HostServiceCreatePoolResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create_pool(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_pool(
+        &self,
+        req: Request<api::HostServiceGetPoolRequest>,
+    ) -> Result<Response<api::HostServiceGetPoolResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_pool(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list_pools(
+        &self,
+        req: Request<api::HostServiceListPoolsRequest>,
+    ) -> Result<Response<api::HostServiceListPoolsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_pools(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update_pool(
+        &self,
+        req: Request<api::HostServiceUpdatePoolRequest>,
+    ) -> Result<Response<api::HostServiceUpdatePoolResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update_pool(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn delete_pool(
+        &self,
+        req: Request<api::HostServiceDeletePoolRequest>,
+    ) -> Result<Response<api::HostServiceDeletePoolResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_pool(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn set_host_pool(
+        &self,
+        req: Request<api::HostServiceSetHostPoolRequest>,
+    ) -> Result<Response<api::HostServiceSetHostPoolResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_host_pool(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn report_inventory(
+        &self,
+        req: Request<api::HostServiceReportInventoryRequest>,
+    ) -> Result<Response<api::HostServiceReportInventoryResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| report_inventory(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_reconciliations(
+        &self,
+        req: Request<api::HostServiceListReconciliationsRequest>,
+    ) -> Result<Response<api::HostServiceListReconciliationsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_reconciliations(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn region_availability(
+        &self,
+        req: Request<api::HostServiceRegionAvailabilityRequest>,
+    ) -> Result<Response<api::HostServiceRegionAvailabilityResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| region_availability(req, meta.into(), read).scope_boxed())
+            .await
+    }
 }
 
 pub async fn create_host(
@@ -283,6 +525,11 @@ pub async fn create_host(
         .await
         .map_err(Error::HostProvisionByToken)?;
     let org_id = req.is_private.then_some(token.org_id);
+    if let Some(org_id) = org_id {
+        Entitlements::for_org(org_id, &mut write)
+            .await?
+            .check_private_hosts()?;
+    }
     let region_id = req.region_id.parse().map_err(Error::ParseRegionId)?;
 
     let host_ips: Vec<_> = req
@@ -315,11 +562,27 @@ pub async fn create_host(
         cpu_cores: req.cpu_cores.try_into().map_err(Error::CpuCores)?,
         memory_bytes: req.memory_bytes.try_into().map_err(Error::MemoryBytes)?,
         disk_bytes: req.disk_bytes.try_into().map_err(Error::DiskBytes)?,
+        gpu_count: req.gpu_count.try_into().map_err(Error::GpuCount)?,
+        gpu_model: req.gpu_model.as_deref(),
+        nvme_iops: req
+            .nvme_iops
+            .map(|iops| iops.try_into().map_err(Error::NvmeIops))
+            .transpose()?,
+        network_bandwidth_mbps: req
+            .network_bandwidth_mbps
+            .map(|mbps| mbps.try_into().map_err(Error::NetworkBandwidth))
+            .transpose()?,
         tags,
         created_by_type: token.created_by_type,
         created_by_id: token.created_by_id,
     };
     let host = new_host.create(&host_ips, &mut write).await?;
+    if let Some(org_id) = org_id {
+        ManagedHost::registered(org_id, host.ip_address, host.id, &mut write).await?;
+        // left pending for the host to fetch once it connects
+        let _keys = OrgKey::send_to_host(org_id, host.id, &mut write).await?;
+        let _sink = OrgLogSink::send_to_host(org_id, host.id, &mut write).await?;
+    }
 
     let expire_token = write.ctx.config.token.expire.token;
     let expire_refresh = write.ctx.config.token.expire.refresh_host;
@@ -465,6 +728,11 @@ pub async fn list_regions(
         cpu_cores: image.min_cpu_cores,
         memory_bytes: image.min_memory_bytes,
         disk_bytes: image.min_disk_bytes,
+        gpu_count: image.min_gpu_count,
+        gpu_model: image.gpu_model.as_deref(),
+        nvme_iops: image.min_nvme_iops,
+        network_bandwidth_mbps: image.min_network_bandwidth_mbps,
+        weights: HostWeights::from(&*read.ctx.config.scheduler),
     };
 
     let mut region_ids = HashSet::new();
@@ -497,7 +765,7 @@ pub async fn list_regions(
 }
 
 pub async fn update_host(
-    req: api::HostServiceUpdateHostRequest,
+    mut req: api::HostServiceUpdateHostRequest,
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::HostServiceUpdateHostResponse, Error> {
@@ -509,8 +777,11 @@ pub async fn update_host(
         resources.push(Resource::from(org_id));
     };
 
+    let mask = UpdateMask::new(req.update_mask.take(), UPDATE_HOST_FIELDS)?;
+    let cost = mask.value("cost", req.cost.take())?;
+
     // for public hosts, only a host api token has the update perm
-    let authz = if req.cost.is_some() {
+    let authz = if cost.is_some() {
         // Only admins can update the cost of a host.
         write
             .auth_for(
@@ -531,44 +802,59 @@ pub async fn update_host(
     };
     let host = Host::by_id(id, org_id, &mut write).await?;
 
-    let region_id = req
-        .region_id
-        .as_ref()
+    let region_id = mask
+        .value("region_id", req.region_id.as_ref())?
         .map(|id| id.parse().map_err(Error::ParseRegionId))
         .transpose()?;
-    let bv_version = req
-        .bv_version
-        .as_ref()
+    let bv_version = mask
+        .value("bv_version", req.bv_version.as_ref())?
         .map(|bv| bv.parse::<Version>().map_err(Error::ParseBvVersion))
         .transpose()?;
-    let disk_bytes = req
-        .disk_bytes
+    let disk_bytes = mask
+        .value("disk_bytes", req.disk_bytes)?
         .map(|space| space.try_into().map_err(Error::DiskBytes))
         .transpose()?;
+    let gpu_count = mask
+        .value("gpu_count", req.gpu_count)?
+        .map(|count| count.try_into().map_err(Error::GpuCount))
+        .transpose()?;
+    let nvme_iops = mask
+        .value("nvme_iops", req.nvme_iops)?
+        .map(|iops| iops.try_into().map_err(Error::NvmeIops))
+        .transpose()?;
+    let network_bandwidth_mbps = mask
+        .value("network_bandwidth_mbps", req.network_bandwidth_mbps)?
+        .map(|mbps| mbps.try_into().map_err(Error::NetworkBandwidth))
+        .transpose()?;
+    let schedule_type = mask
+        .value("schedule_type", req.schedule_type)?
+        .map(|_| req.schedule_type().try_into())
+        .transpose()?;
 
     let update = UpdateHost {
-        network_name: req.network_name.as_deref(),
-        display_name: req.display_name.as_deref(),
+        network_name: mask.value("network_name", req.network_name.as_deref())?,
+        display_name: mask.nullable("display_name", req.display_name.as_deref()),
         region_id,
-        schedule_type: req
-            .schedule_type
-            .map(|_| req.schedule_type().try_into())
-            .transpose()?,
+        schedule_type,
         connection_status: None,
-        os: req.os.as_deref(),
-        os_version: req.os_version.as_deref(),
+        os: mask.value("os", req.os.as_deref())?,
+        os_version: mask.value("os_version", req.os_version.as_deref())?,
         bv_version: bv_version.as_ref(),
         ip_address: None,
         ip_gateway: None,
         cpu_cores: None,
         memory_bytes: None,
         disk_bytes,
-        tags: req
-            .update_tags
+        gpu_count,
+        gpu_model: mask.nullable("gpu_model", req.gpu_model.as_deref()),
+        nvme_iops,
+        network_bandwidth_mbps,
+        tags: mask
+            .value("update_tags", req.update_tags)?
             .map(|tags| tags.into_update(host.tags))
             .transpose()?
             .flatten(),
-        cost: req.cost.map(TryInto::try_into).transpose()?,
+        cost: cost.map(TryInto::try_into).transpose()?,
     };
     let host = update.apply(id, &mut write).await?;
     let host = api::Host::from_host(host, Some(&authz), &mut write).await?;
@@ -625,6 +911,12 @@ pub async fn delete_host(
     Host::delete(id, org_id, &mut write).await?;
     IpAddress::delete_for_host(id, &mut write).await?;
 
+    let peers = MeshPeer::delete_for_host(id, &mut write).await?;
+    if !peers.is_empty() {
+        let org_ids = peers.iter().map(|peer| peer.org_id).collect();
+        super::mesh::send(&org_ids, &HashSet::new(), &mut write).await?;
+    }
+
     Ok(api::HostServiceDeleteHostResponse {})
 }
 
@@ -709,14 +1001,571 @@ pub async fn restart(
     Ok(api::HostServiceRestartResponse {})
 }
 
+/// Count the hosts running each version of blockvisord on each OS.
+pub async fn version_report(
+    req: api::HostServiceVersionReportRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::HostServiceVersionReportResponse, Error> {
+    read.auth(&meta, HostAdminPerm::VersionReport).await?;
+
+    let org_id: Option<OrgId> = req
+        .org_id
+        .map(|id| id.parse().map_err(Error::ParseOrgId))
+        .transpose()?;
+    let hosts = Host::versions(org_id, &mut read).await?;
+    let total = u64::try_from(hosts.len()).unwrap_or_default();
+
+    let mut counts: BTreeMap<(Version, String, String), (u64, u64)> = BTreeMap::new();
+    for host in hosts {
+        let (count, online) = counts
+            .entry((host.bv_version, host.os, host.os_version))
+            .or_default();
+        *count += 1;
+        if host.connection_status == ConnectionStatus::Online {
+            *online += 1;
+        }
+    }
+
+    let versions = counts
+        .into_iter()
+        .rev()
+        .map(
+            |((bv_version, os, os_version), (count, online))| api::HostVersionCount {
+                bv_version: bv_version.to_string(),
+                os,
+                os_version,
+                count,
+                online,
+            },
+        )
+        .collect();
+
+    Ok(api::HostServiceVersionReportResponse { versions, total })
+}
+
+/// Send an upgrade command to the online hosts running a blockvisord version
+/// below the requested one.
+///
+/// The oldest hosts are upgraded first, and `limit` caps the number of hosts
+/// upgraded per request so a new version can be rolled out in stages. Hosts
+/// that are still upgrading are skipped, so repeating the request moves on to
+/// the next stage.
+pub async fn upgrade_outdated(
+    req: api::HostServiceUpgradeOutdatedRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceUpgradeOutdatedResponse, Error> {
+    write.auth(&meta, HostAdminPerm::UpgradeOutdated).await?;
+
+    let version: Version = req.version.parse().map_err(Error::ParseUpgradeVersion)?;
+    let org_id: Option<OrgId> = req
+        .org_id
+        .map(|id| id.parse().map_err(Error::ParseOrgId))
+        .transpose()?;
+    let limit = req.limit.map_or(usize::MAX, |limit| {
+        usize::try_from(limit).unwrap_or(usize::MAX)
+    });
+
+    let upgrading = Command::host_upgrade_pending(&mut write).await?;
+    let mut outdated: Vec<_> = Host::versions(org_id, &mut write)
+        .await?
+        .into_iter()
+        .filter(|host| host.connection_status == ConnectionStatus::Online)
+        .filter(|host| host.bv_version < version && !upgrading.contains(&host.id))
+        .collect();
+    outdated.sort_by(|a, b| a.bv_version.cmp(&b.bv_version));
+
+    let upgrade = api::HostUpgrade {
+        version: version.to_string(),
+    };
+    let mut host_ids = Vec::new();
+    for host in outdated.into_iter().take(limit) {
+        let command = NewCommand::host(host.id, CommandType::HostUpgrade)?
+            .with_protobuf(&upgrade)
+            .create(&mut write)
+            .await?;
+        let command = api::Command::from_host(&command)?.ok_or(Error::NoHostUpgrade)?;
+        write.mqtt(command);
+        host_ids.push(host.id.to_string());
+    }
+
+    Ok(api::HostServiceUpgradeOutdatedResponse { host_ids })
+}
+
+/// Start a staged upgrade of blockvisord on either the given hosts, or the
+/// online hosts matching a filter that run an older version.
+///
+/// The filter skips the hosts of orgs with a `Manual` agent upgrade policy,
+/// and hosts already part of a running upgrade are always skipped.
+pub async fn upgrade_agent(
+    req: api::HostServiceUpgradeAgentRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceUpgradeAgentResponse, Error> {
+    use api::host_service_upgrade_agent_request::Target;
+
+    let authz = write.auth(&meta, HostAdminPerm::UpgradeAgent).await?;
+
+    let version: Version = req.version.parse().map_err(Error::ParseUpgradeVersion)?;
+    let wave_size = req
+        .wave_size
+        .unwrap_or(DEFAULT_WAVE_SIZE)
+        .try_into()
+        .map_err(Error::WaveSize)?;
+    let wave_interval_seconds = req
+        .wave_interval_seconds
+        .unwrap_or(DEFAULT_WAVE_INTERVAL_SECONDS)
+        .try_into()
+        .map_err(Error::WaveInterval)?;
+    let max_failures = req
+        .max_failures
+        .unwrap_or_default()
+        .try_into()
+        .map_err(Error::MaxFailures)?;
+
+    let in_progress = AgentUpgradeHost::in_progress(&mut write).await?;
+    let hosts = Host::versions(None, &mut write).await?;
+    let mut targets: Vec<_> = match req.target.ok_or(Error::MissingTarget)? {
+        Target::HostIds(ids) => {
+            let host_ids: HashSet<HostId> = ids
+                .host_ids
+                .iter()
+                .map(|id| id.parse().map_err(Error::ParseId))
+                .collect::<Result<_, _>>()?;
+            hosts
+                .into_iter()
+                .filter(|host| host_ids.contains(&host.id))
+                .collect()
+        }
+        Target::Filter(filter) => {
+            let org_ids: HashSet<OrgId> = filter
+                .org_ids
+                .iter()
+                .map(|id| id.parse().map_err(Error::ParseOrgId))
+                .collect::<Result<_, _>>()?;
+            let region_ids: HashSet<RegionId> = filter
+                .region_ids
+                .iter()
+                .map(|id| id.parse().map_err(Error::ParseRegionId))
+                .collect::<Result<_, _>>()?;
+            let manual = AgentUpgradePolicy::manual_org_ids(&mut write).await?;
+
+            hosts
+                .into_iter()
+                .filter(|host| host.connection_status == ConnectionStatus::Online)
+                .filter(|host| host.bv_version < version)
+                .filter(|host| host.org_id.is_none_or(|org_id| !manual.contains(&org_id)))
+                .filter(|host| {
+                    org_ids.is_empty() || host.org_id.is_some_and(|id| org_ids.contains(&id))
+                })
+                .filter(|host| region_ids.is_empty() || region_ids.contains(&host.region_id))
+                .collect()
+        }
+    };
+    targets.retain(|host| !in_progress.contains(&host.id));
+    targets.sort_by(|a, b| a.bv_version.cmp(&b.bv_version));
+    let host_ids: Vec<_> = targets.into_iter().map(|host| host.id).collect();
+
+    let new_upgrade = NewAgentUpgrade {
+        version,
+        wave_size,
+        wave_interval_seconds,
+        max_failures,
+        created_by: authz.resource(),
+    };
+    let (upgrade, commands) = new_upgrade.create(&host_ids, &mut write).await?;
+    for command in commands {
+        let command = api::Command::from_host(&command)?.ok_or(Error::NoHostUpgrade)?;
+        write.mqtt(command);
+    }
+
+    Ok(api::HostServiceUpgradeAgentResponse {
+        agent_upgrade: Some(upgrade.into()),
+    })
+}
+
+pub async fn get_agent_upgrade(
+    req: api::HostServiceGetAgentUpgradeRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::HostServiceGetAgentUpgradeResponse, Error> {
+    read.auth(&meta, HostAdminPerm::GetAgentUpgrade).await?;
+
+    let id = req
+        .agent_upgrade_id
+        .parse()
+        .map_err(Error::ParseAgentUpgradeId)?;
+    let upgrade = AgentUpgrade::by_id(id, &mut read).await?;
+    let hosts = upgrade.hosts(&mut read).await?;
+
+    Ok(api::HostServiceGetAgentUpgradeResponse {
+        agent_upgrade: Some(upgrade.into()),
+        hosts: hosts.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn halt_agent_upgrade(
+    req: api::HostServiceHaltAgentUpgradeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceHaltAgentUpgradeResponse, Error> {
+    write.auth(&meta, HostAdminPerm::HaltAgentUpgrade).await?;
+
+    let id = req
+        .agent_upgrade_id
+        .parse()
+        .map_err(Error::ParseAgentUpgradeId)?;
+    let upgrade = AgentUpgrade::halt(id, "Halted by request.", &mut write).await?;
+
+    Ok(api::HostServiceHaltAgentUpgradeResponse {
+        agent_upgrade: Some(upgrade.into()),
+    })
+}
+
+pub async fn create_pool(
+    req: api::HostServiceCreatePoolRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceCreatePoolResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_or_for(
+            &meta,
+            HostAdminPerm::CreatePool,
+            HostPerm::CreatePool,
+            org_id,
+        )
+        .await?;
+
+    let new_pool = NewHostPool {
+        org_id,
+        name: &req.name,
+        description: req.description.as_deref(),
+    };
+    let pool = new_pool.create(&mut write).await?;
+
+    Ok(api::HostServiceCreatePoolResponse {
+        pool: Some(pool.into()),
+    })
+}
+
+pub async fn get_pool(
+    req: api::HostServiceGetPoolRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::HostServiceGetPoolResponse, Error> {
+    let id: HostPoolId = req.pool_id.parse().map_err(Error::ParsePoolId)?;
+    let pool = HostPool::by_id(id, &mut read).await?;
+    read.auth_or_for(
+        &meta,
+        HostAdminPerm::GetPool,
+        HostPerm::GetPool,
+        pool.org_id,
+    )
+    .await?;
+
+    let capacities = PoolCapacity::by_pool_ids(&hashset! {id}, &mut read).await?;
+    let capacity = capacities.get(&id).copied().unwrap_or_default();
+
+    Ok(api::HostServiceGetPoolResponse {
+        pool: Some(api::HostPool {
+            capacity: Some(capacity.into()),
+            ..pool.into()
+        }),
+    })
+}
+
+pub async fn list_pools(
+    req: api::HostServiceListPoolsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::HostServiceListPoolsResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_or_for(&meta, HostAdminPerm::ListPools, HostPerm::ListPools, org_id)
+        .await?;
+
+    let pools = HostPool::by_org_id(org_id, &mut read).await?;
+    let pool_ids = pools.iter().map(|pool| pool.id).collect();
+    let capacities = PoolCapacity::by_pool_ids(&pool_ids, &mut read).await?;
+
+    let pools = pools
+        .into_iter()
+        .map(|pool| {
+            let capacity = capacities.get(&pool.id).copied().unwrap_or_default();
+            api::HostPool {
+                capacity: Some(capacity.into()),
+                ..pool.into()
+            }
+        })
+        .collect();
+
+    Ok(api::HostServiceListPoolsResponse { pools })
+}
+
+pub async fn update_pool(
+    req: api::HostServiceUpdatePoolRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceUpdatePoolResponse, Error> {
+    let id: HostPoolId = req.pool_id.parse().map_err(Error::ParsePoolId)?;
+    let pool = HostPool::by_id(id, &mut write).await?;
+    write
+        .auth_or_for(
+            &meta,
+            HostAdminPerm::UpdatePool,
+            HostPerm::UpdatePool,
+            pool.org_id,
+        )
+        .await?;
+
+    let update = UpdateHostPool {
+        name: req.name.as_deref(),
+        description: req.description.as_deref(),
+    };
+    let pool = update.apply(id, &mut write).await?;
+
+    Ok(api::HostServiceUpdatePoolResponse {
+        pool: Some(pool.into()),
+    })
+}
+
+pub async fn delete_pool(
+    req: api::HostServiceDeletePoolRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceDeletePoolResponse, Error> {
+    let id: HostPoolId = req.pool_id.parse().map_err(Error::ParsePoolId)?;
+    let pool = HostPool::by_id(id, &mut write).await?;
+    write
+        .auth_or_for(
+            &meta,
+            HostAdminPerm::DeletePool,
+            HostPerm::DeletePool,
+            pool.org_id,
+        )
+        .await?;
+
+    HostPool::delete(id, &mut write).await?;
+
+    Ok(api::HostServiceDeletePoolResponse {})
+}
+
+/// Add a private host to a pool of its org, or remove it from its pool.
+pub async fn set_host_pool(
+    req: api::HostServiceSetHostPoolRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceSetHostPoolResponse, Error> {
+    let id: HostId = req.host_id.parse().map_err(Error::ParseId)?;
+    let org_id = Host::org_id(id, &mut write)
+        .await?
+        .ok_or(Error::PublicHostPool(id))?;
+    let authz = write
+        .auth_or_for(
+            &meta,
+            HostAdminPerm::SetHostPool,
+            HostPerm::SetHostPool,
+            [Resource::from(id), Resource::from(org_id)],
+        )
+        .await?;
+
+    let pool_id = req
+        .pool_id
+        .as_ref()
+        .map(|id| id.parse().map_err(Error::ParsePoolId))
+        .transpose()?;
+    if let Some(pool_id) = pool_id {
+        let pool = HostPool::by_id(pool_id, &mut write).await?;
+        if pool.org_id != org_id {
+            return Err(Error::PoolOrg(pool_id));
+        }
+    }
+
+    let host = Host::set_pool(id, org_id, pool_id, &mut write).await?;
+    let host = api::Host::from_host(host, Some(&authz), &mut write).await?;
+
+    Ok(api::HostServiceSetHostPoolResponse { host: Some(host) })
+}
+
+/// Compare the VMs that blockvisord runs against the nodes of its host, and
+/// record each difference as a reconciliation.
+pub async fn report_inventory(
+    req: api::HostServiceReportInventoryRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::HostServiceReportInventoryResponse, Error> {
+    let id: HostId = req.host_id.parse().map_err(Error::ParseId)?;
+    write.auth_for(&meta, HostPerm::ReportInventory, id).await?;
+
+    let vm_ids: HashSet<String> = req.vm_ids.into_iter().collect();
+    let (open, created) = Reconciliation::report(id, &vm_ids, &mut write).await?;
+    for rec in created {
+        warn!(
+            "Host {id} needs reconciliation of {:?} {}",
+            rec.kind, rec.vm_id
+        );
+    }
+
+    Ok(api::HostServiceReportInventoryResponse {
+        reconciliations: open.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// The reconciliations of a host, newest first.
+pub async fn list_reconciliations(
+    req: api::HostServiceListReconciliationsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::HostServiceListReconciliationsResponse, Error> {
+    let id: HostId = req.host_id.parse().map_err(Error::ParseId)?;
+    let mut resources = vec![Resource::from(id)];
+    if let Some(org_id) = Host::org_id(id, &mut read).await? {
+        resources.push(Resource::from(org_id));
+        read.auth_or_for(
+            &meta,
+            HostAdminPerm::ListReconciliations,
+            HostPerm::ListReconciliations,
+            &resources,
+        )
+        .await?;
+    } else {
+        read.auth(&meta, HostAdminPerm::ListReconciliations).await?;
+    }
+
+    let reconciliations = Reconciliation::by_host_id(id, req.include_resolved, &mut read).await?;
+
+    Ok(api::HostServiceListReconciliationsResponse {
+        reconciliations: reconciliations.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// The resources that a node needs from its host.
+struct NodeSpec {
+    protocol_id: ProtocolId,
+    cpu_cores: i64,
+    memory_bytes: i64,
+    disk_bytes: i64,
+    gpu_count: i64,
+    gpu_model: Option<String>,
+    nvme_iops: Option<i64>,
+    network_bandwidth_mbps: Option<i64>,
+}
+
+/// Count how many nodes of an image or resource spec could currently be
+/// scheduled in each region.
+///
+/// Every region is returned, so that regions without capacity have a count of
+/// zero rather than being left out.
+pub async fn region_availability(
+    req: api::HostServiceRegionAvailabilityRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::HostServiceRegionAvailabilityResponse, Error> {
+    let org_id = req
+        .org_id
+        .as_ref()
+        .map(|id| id.parse().map_err(Error::ParseOrgId))
+        .transpose()?;
+
+    let authz = if let Some(org_id) = org_id {
+        read.auth_or_for(
+            &meta,
+            HostAdminPerm::RegionAvailability,
+            HostPerm::RegionAvailability,
+            org_id,
+        )
+        .await?
+    } else {
+        read.auth(&meta, HostAdminPerm::RegionAvailability).await?
+    };
+
+    let spec = match req.spec.ok_or(Error::MissingSpec)? {
+        Spec::ImageId(image_id) => {
+            let image_id = image_id.parse().map_err(Error::ParseImageId)?;
+            let image = Image::by_id(image_id, org_id, &authz, &mut read).await?;
+            let version =
+                ProtocolVersion::by_id(image.protocol_version_id, org_id, &authz, &mut read)
+                    .await?;
+            NodeSpec {
+                protocol_id: version.protocol_id,
+                cpu_cores: image.min_cpu_cores,
+                memory_bytes: image.min_memory_bytes,
+                disk_bytes: image.min_disk_bytes,
+                gpu_count: image.min_gpu_count,
+                gpu_model: image.gpu_model,
+                nvme_iops: image.min_nvme_iops,
+                network_bandwidth_mbps: image.min_network_bandwidth_mbps,
+            }
+        }
+        Spec::Resources(resources) => NodeSpec {
+            protocol_id: resources
+                .protocol_id
+                .parse()
+                .map_err(Error::ParseProtocolId)?,
+            cpu_cores: resources.cpu_cores.try_into().map_err(Error::CpuCores)?,
+            memory_bytes: resources
+                .memory_bytes
+                .try_into()
+                .map_err(Error::MemoryBytes)?,
+            disk_bytes: resources.disk_bytes.try_into().map_err(Error::DiskBytes)?,
+            gpu_count: resources.gpu_count.try_into().map_err(Error::GpuCount)?,
+            gpu_model: None,
+            nvme_iops: None,
+            network_bandwidth_mbps: None,
+        },
+    };
+    let protocol = Protocol::by_id(spec.protocol_id, org_id, &authz, &mut read).await?;
+
+    let requirements = HostRequirements {
+        scheduler: &NodeScheduler::least_resources(),
+        protocol: &protocol,
+        org_id,
+        cpu_cores: spec.cpu_cores,
+        memory_bytes: spec.memory_bytes,
+        disk_bytes: spec.disk_bytes,
+        gpu_count: spec.gpu_count,
+        gpu_model: spec.gpu_model.as_deref(),
+        nvme_iops: spec.nvme_iops,
+        network_bandwidth_mbps: spec.network_bandwidth_mbps,
+        weights: HostWeights::from(&*read.ctx.config.scheduler),
+    };
+
+    let mut region_hosts = HashMap::new();
+    let mut region_nodes = HashMap::new();
+    let candidates = Host::candidates(requirements, None, &mut read).await?;
+    for candidate in candidates {
+        let region_id = candidate.host.region_id;
+        let nodes = u64::from(candidate.capacity(&requirements));
+        *region_hosts.entry(region_id).or_insert(0) += 1;
+        *region_nodes.entry(region_id).or_insert(0) += nodes;
+    }
+
+    let regions = Region::all(&mut read)
+        .await?
+        .into_iter()
+        .map(|region| {
+            let valid_hosts = region_hosts.get(&region.id).copied().unwrap_or(0);
+            let node_capacity = region_nodes.get(&region.id).copied().unwrap_or(0);
+            api::RegionAvailability {
+                region: Some(region.into()),
+                valid_hosts,
+                node_capacity,
+            }
+        })
+        .collect();
+
+    Ok(api::HostServiceRegionAvailabilityResponse { regions })
+}
+
 impl api::Host {
     pub async fn from_host(
         host: Host,
         authz: Option<&AuthZ>,
         conn: &mut Conn<'_>,
     ) -> Result<Self, Error> {
-        let lookup = Lookup::from_host(&host, conn).await?;
-        Self::from_model(host, &lookup, authz)
+        let relations = NodeRelations::for_hosts(std::slice::from_ref(&host), conn).await?;
+        Self::from_model(host, &relations, authz)
     }
 
     pub async fn from_hosts(
@@ -724,27 +1573,31 @@ impl api::Host {
         authz: &AuthZ,
         conn: &mut Conn<'_>,
     ) -> Result<Vec<Self>, Error> {
-        let lookup = Lookup::from_hosts(&hosts, conn).await?;
+        let relations = NodeRelations::for_hosts(&hosts, conn).await?;
 
         hosts
             .into_iter()
-            .map(|host| Self::from_model(host, &lookup, Some(authz)))
+            .map(|host| Self::from_model(host, &relations, Some(authz)))
             .collect()
     }
 
-    fn from_model(host: Host, lookup: &Lookup, authz: Option<&AuthZ>) -> Result<Self, Error> {
+    fn from_model(
+        host: Host,
+        relations: &NodeRelations,
+        authz: Option<&AuthZ>,
+    ) -> Result<Self, Error> {
         let created_by = host.created_by();
-        let org = host.org_id.and_then(|id| lookup.orgs.get(&id));
+        let org = host.org_id.and_then(|id| relations.orgs.get(&id));
         let org_name = org.map(|org| org.name.clone());
-        let region = lookup
+        let region = relations
             .regions
             .get(&host.region_id)
             .ok_or(Error::LookupMissingRegion)?;
         let cost = authz.and_then(|authz| common::BillingAmount::from_host(&host, authz));
 
         let no_ips = vec![];
-        let ips = lookup.host_ips.get(&host.id).unwrap_or(&no_ips);
-        let assigned = lookup.assigned_ips.get(&host.id).unwrap_or(&no_ips);
+        let ips = relations.host_ips.get(&host.id).unwrap_or(&no_ips);
+        let assigned = relations.assigned_ips.get(&host.id).unwrap_or(&no_ips);
         let ip_addresses = ips
             .iter()
             .map(|ip| common::HostIpAddress {
@@ -771,52 +1624,22 @@ impl api::Host {
             memory_bytes: host.memory_bytes.try_into().map_err(Error::MemoryBytes)?,
             disk_bytes: host.disk_bytes.try_into().map_err(Error::DiskBytes)?,
             node_count: u64::try_from(max(0, host.node_count)).map_err(Error::ParseNodeCount)?,
+            gpu_count: host.gpu_count.try_into().map_err(Error::GpuCount)?,
+            gpu_model: host.gpu_model,
+            nvme_iops: host
+                .nvme_iops
+                .map(|iops| iops.try_into().map_err(Error::NvmeIops))
+                .transpose()?,
+            network_bandwidth_mbps: host
+                .network_bandwidth_mbps
+                .map(|mbps| mbps.try_into().map_err(Error::NetworkBandwidth))
+                .transpose()?,
             tags: Some(host.tags.into()),
             created_by: Some(common::Resource::from(created_by)),
             created_at: Some(NanosUtc::from(host.created_at).into()),
             updated_at: host.updated_at.map(|at| NanosUtc::from(at).into()),
             cost,
-        })
-    }
-}
-
-struct Lookup {
-    orgs: HashMap<OrgId, Org>,
-    regions: HashMap<RegionId, Region>,
-    host_ips: HashMap<HostId, Vec<IpNetwork>>,
-    assigned_ips: HashMap<HostId, Vec<IpNetwork>>,
-}
-
-impl Lookup {
-    async fn from_host(host: &Host, conn: &mut Conn<'_>) -> Result<Lookup, Error> {
-        Self::from_hosts(std::slice::from_ref(host), conn).await
-    }
-
-    async fn from_hosts(hosts: &[Host], conn: &mut Conn<'_>) -> Result<Lookup, Error> {
-        let host_ids: HashSet<HostId> = hosts.iter().map(|host| host.id).collect();
-
-        let org_ids = hosts.iter().filter_map(|host| host.org_id).collect();
-        let orgs = Org::by_ids(&org_ids, conn)
-            .await?
-            .to_map_keep_last(|org| (org.id, org));
-
-        let region_ids = hosts.iter().map(|host| host.region_id).collect();
-        let regions = Region::by_ids(&region_ids, conn)
-            .await?
-            .to_map_keep_last(|region| (region.id, region));
-
-        let host_ips = IpAddress::for_hosts(&host_ids, conn)
-            .await?
-            .to_map_keep_all(|ip| (ip.host_id, ip.ip));
-        let assigned_ips = IpAddress::assigned_for_hosts(&host_ids, conn)
-            .await?
-            .to_map_keep_all(|ip| (ip.host_id, ip.ip));
-
-        Ok(Lookup {
-            orgs,
-            regions,
-            host_ips,
-            assigned_ips,
+            pool_id: host.pool_id.map(|id| id.to_string()),
         })
     }
 }
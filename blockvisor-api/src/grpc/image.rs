@@ -9,19 +9,26 @@ use tracing::error;
 
 use crate::auth::Authorize;
 use crate::auth::rbac::{ImageAdminPerm, ImagePerm, Perm};
-use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::auth::resource::OrgId;
+use crate::database::{Conn, ReadConn, Transaction, WriteConn};
 use crate::model::image::archive::{NewArchive, UpdateArchive};
 use crate::model::image::config::Ramdisks;
 use crate::model::image::property::ImagePropertyKey;
 use crate::model::image::rule::{ImageRule, NewImageRule};
-use crate::model::image::{Archive, Image, ImageProperty, NewImage, NewProperty, UpdateImage};
-use crate::model::protocol::VersionKey;
+use crate::model::image::{
+    Archive, HealthRules, Image, ImageId, ImageProperty, ImageReviewStatus, NewImage, NewProperty,
+    PortMap, UpdateImage,
+};
+use crate::model::protocol::{
+    Protocol, ProtocolId, ProtocolNetwork, ProtocolNetworkId, VersionKey,
+};
 use crate::model::sql::Version;
 use crate::model::{Node, ProtocolVersion};
 use crate::store::StoreKey;
 use crate::util::{HashVec, NanosUtc};
 
 use super::api::image_service_server::ImageService;
+use super::validate::{Validate, Validator};
 use super::{Grpc, Metadata, Status, api, common};
 
 #[derive(Debug, Display, Error)]
@@ -36,8 +43,12 @@ pub enum Error {
     Claims(#[from] crate::auth::claims::Error),
     /// Diesel failure: {0}
     Diesel(#[from] diesel::result::Error),
+    /// Image dns settings error: {0}
+    Dns(#[from] crate::model::image::dns::Error),
     /// Failed to find semantic version: {0}
     FindVersion(Version),
+    /// Image health rule error: {0}
+    Health(#[from] crate::model::image::health::Error),
     /// Image model error: {0}
     Image(#[from] crate::model::image::Error),
     /// Invalid new_archive_keys combination: {0:?}
@@ -48,8 +59,14 @@ pub enum Error {
     MinCpu(std::num::TryFromIntError),
     /// Failed to parse minimum disk space: {0}
     MinDisk(std::num::TryFromIntError),
+    /// Failed to parse minimum gpu count: {0}
+    MinGpu(std::num::TryFromIntError),
     /// Failed to parse minimum memory: {0}
     MinMemory(std::num::TryFromIntError),
+    /// Failed to parse minimum network bandwidth: {0}
+    MinNetworkBandwidth(std::num::TryFromIntError),
+    /// Failed to parse minimum nvme iops: {0}
+    MinNvmeIops(std::num::TryFromIntError),
     /// Missing firewall config.
     MissingFirewallConfig,
     /// Missing expected new_archive key combos: {0:?}
@@ -60,16 +77,22 @@ pub enum Error {
     MissingStorePointer,
     /// Missing version key.
     MissingVersionKey,
+    /// Image network error: {0}
+    Network(#[from] crate::model::protocol::network::Error),
     /// No builds found.
     NoBuilds,
     /// Node error: {0}
     Node(#[from] crate::model::node::Error),
     /// No versions found.
     NoVersions,
+    /// Org `{0}` does not publish protocol `{1}`.
+    NotPublisher(OrgId, ProtocolId),
     /// Failed to parse ArchiveId: {0}
     ParseArchiveId(uuid::Error),
     /// Failed to parse ImageId: {0}
     ParseImageId(uuid::Error),
+    /// Failed to parse ProtocolNetworkId: {0}
+    ParseNetworkId(uuid::Error),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
     /// Failed to parse ProtocolId: {0}
@@ -78,6 +101,8 @@ pub enum Error {
     ParseVersion(crate::model::sql::Error),
     /// Failed to parse VersionId: {0}
     ParseVersionId(uuid::Error),
+    /// Image port map error: {0}
+    Port(#[from] crate::model::image::port::Error),
     /// Image property error: {0}
     Property(#[from] crate::model::image::property::Error),
     /// Image protocol error: {0}
@@ -86,6 +111,8 @@ pub enum Error {
     Rule(#[from] crate::model::image::rule::Error),
     /// Image store error: {0}
     Store(#[from] crate::store::Error),
+    /// Image request validation failed: {0}
+    Validate(#[from] crate::grpc::validate::Error),
     /// Image protocol version error: {0}
     Version(#[from] crate::model::protocol::version::Error),
 }
@@ -97,6 +124,7 @@ impl From<Error> for Status {
         match err {
             Diesel(_) => Status::internal("Internal error."),
             FindVersion(_) | NoBuilds | NoVersions => Status::not_found("Not found."),
+            NotPublisher(..) => Status::forbidden("Org does not publish this protocol."),
             BuildVersion(_) => Status::invalid_argument("build_version"),
             InvalidKeyCombo(set) => {
                 // safety: keys are from the client
@@ -105,7 +133,10 @@ impl From<Error> for Status {
             MinBabel(_) => Status::invalid_argument("min_babel_version"),
             MinCpu(_) => Status::invalid_argument("min_cpu_cores"),
             MinDisk(_) => Status::invalid_argument("min_disk_bytes"),
+            MinGpu(_) => Status::invalid_argument("min_gpu_count"),
             MinMemory(_) => Status::invalid_argument("min_memory_bytes"),
+            MinNetworkBandwidth(_) => Status::invalid_argument("min_network_bandwidth_mbps"),
+            MinNvmeIops(_) => Status::invalid_argument("min_nvme_iops"),
             MissingFirewallConfig => Status::invalid_argument("firewall"),
             MissingKeyCombos(set) => {
                 // safety: keys are from properties the client provided
@@ -119,6 +150,7 @@ impl From<Error> for Status {
             MissingVersionKey => Status::invalid_argument("version_key"),
             ParseArchiveId(_) => Status::invalid_argument("id"),
             ParseImageId(_) => Status::invalid_argument("image_id"),
+            ParseNetworkId(_) => Status::invalid_argument("network_id"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
             ParseProtocolId(_) => Status::invalid_argument("protocol_id"),
             ParseVersion(_) => Status::invalid_argument("protocol_version"),
@@ -126,12 +158,17 @@ impl From<Error> for Status {
             Archive(err) => err.into(),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
+            Dns(err) => err.into(),
+            Health(err) => err.into(),
             Image(err) => err.into(),
+            Network(err) => err.into(),
             Node(err) => err.into(),
+            Port(err) => err.into(),
             Property(err) => err.into(),
             Protocol(err) => err.into(),
             Rule(err) => err.into(),
             Store(err) => err.into(),
+            Validate(err) => err.into(),
             Version(err) => err.into(),
         }
     }
@@ -157,6 +194,15 @@ impl ImageService for Grpc {
             .await
     }
 
+    async fn get_ports(
+        &self,
+        req: Request<api::ImageServiceGetPortsRequest>,
+    ) -> Result<Response<api::ImageServiceGetPortsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_ports(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
     async fn list_archives(
         &self,
         req: Request<api::ImageServiceListArchivesRequest>,
@@ -166,6 +212,24 @@ impl ImageService for Grpc {
             .await
     }
 
+    async fn push_version(
+        &self,
+        req: Request<api::ImageServicePushVersionRequest>,
+    ) -> Result<Response<api::ImageServicePushVersionResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| push_version(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn review_version(
+        &self,
+        req: Request<api::ImageServiceReviewVersionRequest>,
+    ) -> Result<Response<api::ImageServiceReviewVersionResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| review_version(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn update_archive(
         &self,
         req: Request<api::ImageServiceUpdateArchiveRequest>,
@@ -190,6 +254,7 @@ async fn add_image(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::ImageServiceAddImageResponse, Error> {
+    req.validate()?;
     let authz = write.auth(&meta, ImageAdminPerm::Add).await?;
 
     let version_id = req
@@ -203,14 +268,15 @@ async fn add_image(
         .transpose()?;
 
     let version = ProtocolVersion::by_id(version_id, org_id, &authz, &mut write).await?;
-    let latest = Image::latest_build(version_id, org_id, &authz, &mut write).await?;
+    let max_build = Image::max_build(version_id, &mut write).await?;
+    let network_id = network_id(req.network_id.as_deref(), &version, &mut write).await?;
 
     let firewall = req.firewall.ok_or(Error::MissingFirewallConfig)?;
     let new_image = NewImage {
         protocol_version_id: version.id,
         org_id: version.org_id.or(org_id),
         image_uri: req.image_uri,
-        build_version: latest.as_ref().map_or(1, |image| image.build_version + 1),
+        build_version: max_build + 1,
         description: req.description,
         min_cpu_cores: i64::try_from(req.min_cpu_cores).map_err(Error::MinCpu)?,
         min_memory_bytes: i64::try_from(req.min_memory_bytes).map_err(Error::MinMemory)?,
@@ -220,22 +286,74 @@ async fn add_image(
         default_firewall_in: firewall.default_in().try_into()?,
         default_firewall_out: firewall.default_out().try_into()?,
         dns_scheme: req.dns_scheme,
+        review_status: ImageReviewStatus::Approved,
+        health_rules: health_rules(req.health_rules)?,
+        dns_settings: req.dns_settings.map(TryInto::try_into).transpose()?,
+        port_map: PortMap::from_api(req.ports)?,
+        min_gpu_count: i64::try_from(req.min_gpu_count).map_err(Error::MinGpu)?,
+        gpu_model: req.gpu_model,
+        min_nvme_iops: req
+            .min_nvme_iops
+            .map(|iops| i64::try_from(iops).map_err(Error::MinNvmeIops))
+            .transpose()?,
+        min_network_bandwidth_mbps: req
+            .min_network_bandwidth_mbps
+            .map(|mbps| i64::try_from(mbps).map_err(Error::MinNetworkBandwidth))
+            .transpose()?,
+        network_id,
+    };
+    let (image, properties, rules, archives) = create_image(
+        new_image,
+        firewall.rules,
+        req.properties,
+        req.archive_pointers,
+        &mut write,
+    )
+    .await?;
+
+    Node::notify_auto_upgrades(&image, &version, org_id, &authz, &mut write).await?;
+
+    Ok(api::ImageServiceAddImageResponse {
+        image: Some(api::Image::from(image, properties, rules)?),
+        archives: archives.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Parse the network of a new image, which must be a network of its protocol.
+async fn network_id(
+    network_id: Option<&str>,
+    version: &ProtocolVersion,
+    conn: &mut Conn<'_>,
+) -> Result<Option<ProtocolNetworkId>, Error> {
+    let Some(id) = network_id else {
+        return Ok(None);
     };
-    let image = new_image.create(&mut write).await?;
+    let id = id.parse().map_err(Error::ParseNetworkId)?;
+    let network = ProtocolNetwork::for_protocol(id, version.protocol_id, conn).await?;
+    Ok(Some(network.id))
+}
 
-    let new_rules = firewall
-        .rules
+/// Create an image with its firewall rules, properties and archives.
+async fn create_image(
+    new_image: NewImage,
+    firewall_rules: Vec<common::FirewallRule>,
+    properties: Vec<api::AddImageProperty>,
+    archive_pointers: Vec<api::ArchivePointer>,
+    conn: &mut Conn<'_>,
+) -> Result<(Image, Vec<ImageProperty>, Vec<ImageRule>, Vec<Archive>), Error> {
+    let image = new_image.create(conn).await?;
+
+    let new_rules = firewall_rules
         .into_iter()
         .map(|rule| NewImageRule::from_api(image.id, rule))
         .collect::<Result<_, _>>()?;
-    let rules = NewImageRule::bulk_create(new_rules, &mut write).await?;
+    let rules = NewImageRule::bulk_create(new_rules, conn).await?;
 
-    let new_properties = req
-        .properties
+    let new_properties = properties
         .into_iter()
         .map(|prop| NewProperty::from(image.id, prop))
         .collect::<Result<_, _>>()?;
-    let properties = NewProperty::bulk_create(new_properties, &mut write).await?;
+    let properties = NewProperty::bulk_create(new_properties, conn).await?;
     let key_to_property_id = properties
         .iter()
         .to_map_keep_last(|prop| (prop.key.clone(), prop.id));
@@ -256,8 +374,7 @@ async fn add_image(
         });
 
     // get the store pointers for all requested new_archive combinations
-    let archive_pointers = req
-        .archive_pointers
+    let archive_pointers = archive_pointers
         .into_iter()
         .map(|pointer| {
             let keys = pointer
@@ -304,17 +421,25 @@ async fn add_image(
 
     // ensure all possible new_archive key combinations are provided
     let archives = if new_archive_powerset.is_empty() {
-        NewArchive::bulk_create(new_archives, &mut write).await?
+        NewArchive::bulk_create(new_archives, conn).await?
     } else {
         return Err(Error::MissingKeyCombos(new_archive_powerset));
     };
 
-    Node::notify_auto_upgrades(&image, &version, org_id, &authz, &mut write).await?;
+    Ok((image, properties, rules, archives))
+}
 
-    Ok(api::ImageServiceAddImageResponse {
-        image: Some(api::Image::from(image, properties, rules)?),
-        archives: archives.into_iter().map(Into::into).collect(),
-    })
+/// The health rules of a new image, or `None` to trust the reported health.
+fn health_rules(rules: Vec<common::HealthRule>) -> Result<Option<HealthRules>, Error> {
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    let rules = rules
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_, _>>()?;
+    Ok(Some(HealthRules(rules)))
 }
 
 async fn get_image(
@@ -322,6 +447,7 @@ async fn get_image(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::ImageServiceGetImageResponse, Error> {
+    req.validate()?;
     let admin_perm: Perm = ImageAdminPerm::Get.into();
     let user_perm: Perm = ImagePerm::Get.into();
 
@@ -367,11 +493,43 @@ async fn get_image(
     })
 }
 
+/// The declared ports of an image, so that clients can show them rather than
+/// hardcoding them per protocol.
+async fn get_ports(
+    req: api::ImageServiceGetPortsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ImageServiceGetPortsResponse, Error> {
+    req.validate()?;
+    let admin_perm: Perm = ImageAdminPerm::Get.into();
+    let user_perm: Perm = ImagePerm::Get.into();
+
+    let (org_id, authz) = if let Some(ref org_id) = req.org_id {
+        let org_id = org_id.parse().map_err(Error::ParseOrgId)?;
+        let authz = read
+            .auth_or_for(&meta, admin_perm, user_perm, org_id)
+            .await?;
+        (Some(org_id), authz)
+    } else {
+        let authz = read.auth_any(&meta, [admin_perm, user_perm]).await?;
+        (None, authz)
+    };
+
+    let image_id: ImageId = req.image_id.parse().map_err(Error::ParseImageId)?;
+    let image = Image::by_id(image_id, org_id, &authz, &mut read).await?;
+
+    Ok(api::ImageServiceGetPortsResponse {
+        image_id: image.id.to_string(),
+        ports: image.port_map.map(Into::into).unwrap_or_default(),
+    })
+}
+
 async fn list_archives(
     req: api::ImageServiceListArchivesRequest,
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::ImageServiceListArchivesResponse, Error> {
+    req.validate()?;
     let admin_perm: Perm = ImageAdminPerm::ListArchives.into();
     let user_perm: Perm = ImagePerm::ListArchives.into();
 
@@ -395,11 +553,111 @@ async fn list_archives(
     })
 }
 
+async fn push_version(
+    req: api::ImageServicePushVersionRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ImageServicePushVersionResponse, Error> {
+    req.validate()?;
+    let org_id = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write
+        .auth_for(&meta, ImagePerm::PushVersion, org_id)
+        .await?;
+
+    let version_id = req
+        .protocol_version_id
+        .parse()
+        .map_err(Error::ParseVersionId)?;
+    let version = ProtocolVersion::by_id(version_id, Some(org_id), &authz, &mut write).await?;
+    if Protocol::owner(version.protocol_id, &mut write).await? != Some(org_id) {
+        return Err(Error::NotPublisher(org_id, version.protocol_id));
+    }
+    let max_build = Image::max_build(version_id, &mut write).await?;
+    let network_id = network_id(req.network_id.as_deref(), &version, &mut write).await?;
+
+    let firewall = req.firewall.ok_or(Error::MissingFirewallConfig)?;
+    let new_image = NewImage {
+        protocol_version_id: version.id,
+        org_id: version.org_id,
+        image_uri: req.image_uri,
+        build_version: max_build + 1,
+        description: req.description,
+        min_cpu_cores: i64::try_from(req.min_cpu_cores).map_err(Error::MinCpu)?,
+        min_memory_bytes: i64::try_from(req.min_memory_bytes).map_err(Error::MinMemory)?,
+        min_disk_bytes: i64::try_from(req.min_disk_bytes).map_err(Error::MinDisk)?,
+        min_babel_version: req.min_babel_version.parse().map_err(Error::MinBabel)?,
+        ramdisks: Ramdisks(req.ramdisks.into_iter().map(Into::into).collect()),
+        default_firewall_in: firewall.default_in().try_into()?,
+        default_firewall_out: firewall.default_out().try_into()?,
+        dns_scheme: req.dns_scheme,
+        review_status: ImageReviewStatus::PendingReview,
+        health_rules: health_rules(req.health_rules)?,
+        dns_settings: req.dns_settings.map(TryInto::try_into).transpose()?,
+        port_map: PortMap::from_api(req.ports)?,
+        min_gpu_count: i64::try_from(req.min_gpu_count).map_err(Error::MinGpu)?,
+        gpu_model: req.gpu_model,
+        min_nvme_iops: req
+            .min_nvme_iops
+            .map(|iops| i64::try_from(iops).map_err(Error::MinNvmeIops))
+            .transpose()?,
+        min_network_bandwidth_mbps: req
+            .min_network_bandwidth_mbps
+            .map(|mbps| i64::try_from(mbps).map_err(Error::MinNetworkBandwidth))
+            .transpose()?,
+        network_id,
+    };
+    let (image, properties, rules, archives) = create_image(
+        new_image,
+        firewall.rules,
+        req.properties,
+        req.archive_pointers,
+        &mut write,
+    )
+    .await?;
+
+    Ok(api::ImageServicePushVersionResponse {
+        image: Some(api::Image::from(image, properties, rules)?),
+        archives: archives.into_iter().map(Into::into).collect(),
+    })
+}
+
+async fn review_version(
+    req: api::ImageServiceReviewVersionRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ImageServiceReviewVersionResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, ImageAdminPerm::ReviewVersion).await?;
+
+    let id = req.image_id.parse().map_err(Error::ParseImageId)?;
+    let status = if req.approve {
+        ImageReviewStatus::Approved
+    } else {
+        ImageReviewStatus::Rejected
+    };
+    let image = Image::review(id, status, &mut write).await?;
+
+    if status == ImageReviewStatus::Approved {
+        let version =
+            ProtocolVersion::by_id(image.protocol_version_id, image.org_id, &authz, &mut write)
+                .await?;
+        Node::notify_auto_upgrades(&image, &version, image.org_id, &authz, &mut write).await?;
+    }
+
+    let properties = ImageProperty::by_image_id(image.id, &mut write).await?;
+    let rules = ImageRule::by_image_id(image.id, &mut write).await?;
+
+    Ok(api::ImageServiceReviewVersionResponse {
+        image: Some(api::Image::from(image, properties, rules)?),
+    })
+}
+
 async fn update_archive(
     req: api::ImageServiceUpdateArchiveRequest,
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::ImageServiceUpdateArchiveResponse, Error> {
+    req.validate()?;
     let _authz = write.auth(&meta, ImageAdminPerm::UpdateArchive).await?;
 
     let id = req.archive_id.parse().map_err(Error::ParseArchiveId)?;
@@ -418,6 +676,7 @@ async fn update_image(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::ImageServiceUpdateImageResponse, Error> {
+    req.validate()?;
     let _authz = write.auth(&meta, ImageAdminPerm::UpdateImage).await?;
 
     let id = req.image_id.parse().map_err(Error::ParseImageId)?;
@@ -459,12 +718,89 @@ impl api::Image {
             min_cpu_cores: u64::try_from(image.min_cpu_cores).map_err(Error::MinCpu)?,
             min_memory_bytes: u64::try_from(image.min_memory_bytes).map_err(Error::MinMemory)?,
             min_disk_bytes: u64::try_from(image.min_disk_bytes).map_err(Error::MinDisk)?,
+            min_gpu_count: u64::try_from(image.min_gpu_count).map_err(Error::MinGpu)?,
+            gpu_model: image.gpu_model,
+            min_nvme_iops: image
+                .min_nvme_iops
+                .map(|iops| u64::try_from(iops).map_err(Error::MinNvmeIops))
+                .transpose()?,
+            min_network_bandwidth_mbps: image
+                .min_network_bandwidth_mbps
+                .map(|mbps| u64::try_from(mbps).map_err(Error::MinNetworkBandwidth))
+                .transpose()?,
             min_babel_version: image.min_babel_version.to_string(),
             ramdisks: image.ramdisks.into_iter().map(Into::into).collect(),
             visibility: common::Visibility::from(image.visibility).into(),
             created_at: Some(NanosUtc::from(image.created_at).into()),
             updated_at: image.updated_at.map(NanosUtc::from).map(Into::into),
             dns_scheme: image.dns_scheme,
+            review_status: api::ImageReviewStatus::from(image.review_status).into(),
+            health_rules: image
+                .health_rules
+                .into_iter()
+                .flatten()
+                .map(Into::into)
+                .collect(),
+            dns_settings: image.dns_settings.map(Into::into),
+            ports: image.port_map.map(Into::into).unwrap_or_default(),
+            network_id: image.network_id.map(|id| id.to_string()),
         })
     }
 }
+
+impl Validate for api::ImageServiceAddImageRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("protocol_version_id", &self.protocol_version_id);
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+        v.maybe_uuid("network_id", self.network_id.as_deref());
+        v.maybe_no_secrets("description", self.description.as_deref());
+    }
+}
+
+impl Validate for api::ImageServiceGetImageRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+    }
+}
+
+impl Validate for api::ImageServiceGetPortsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("image_id", &self.image_id);
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+    }
+}
+
+impl Validate for api::ImageServiceListArchivesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("image_id", &self.image_id);
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+    }
+}
+
+impl Validate for api::ImageServicePushVersionRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("protocol_version_id", &self.protocol_version_id);
+        v.maybe_uuid("network_id", self.network_id.as_deref());
+        v.maybe_no_secrets("description", self.description.as_deref());
+    }
+}
+
+impl Validate for api::ImageServiceReviewVersionRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("image_id", &self.image_id);
+    }
+}
+
+impl Validate for api::ImageServiceUpdateArchiveRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("archive_id", &self.archive_id);
+    }
+}
+
+impl Validate for api::ImageServiceUpdateImageRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("image_id", &self.image_id);
+        v.maybe_enum_value::<common::Visibility>("visibility", self.visibility);
+    }
+}
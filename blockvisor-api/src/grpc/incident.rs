@@ -0,0 +1,389 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::{IncidentAdminPerm, IncidentPerm};
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::command::{Command, CommandId};
+use crate::model::incident::{
+    Incident, IncidentComment, IncidentFilter, IncidentId, IncidentLink, IncidentLinkType,
+    IncidentStatus, NewIncident,
+};
+use crate::model::node::report::NodeReportId;
+use crate::model::{Node, NodeDowntime, NodeReport, Ticket, User};
+
+use super::api::incident_service_server::IncidentService;
+use super::validate::{Validate, Validator};
+use super::{Grpc, Metadata, Status, api, common};
+
+/// The maximum number of characters in a comment message.
+const MAX_MESSAGE_LEN: usize = 4096;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Incident command error: {0}
+    Command(#[from] crate::model::command::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Incident downtime error: {0}
+    Downtime(#[from] crate::model::node::slo::Error),
+    /// Linked {0:?} `{1}` does not belong to the incident node.
+    LinkNode(IncidentLinkType, uuid::Uuid),
+    /// Incident model error: {0}
+    Model(#[from] crate::model::incident::Error),
+    /// Incident node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Failed to parse UserId: {0}
+    ParseAssigneeId(uuid::Error),
+    /// Failed to parse IncidentId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse link id: {0}
+    ParseLinkId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse NodeReportId: {0}
+    ParseReportId(uuid::Error),
+    /// Incident report error: {0}
+    Report(#[from] crate::model::node::report::Error),
+    /// Incident ticket error: {0}
+    Ticket(#[from] crate::model::ticket::Error),
+    /// Incident assignee error: {0}
+    User(#[from] crate::model::user::Error),
+    /// Incident request validation failed: {0}
+    Validate(#[from] crate::grpc::validate::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            LinkNode(..) | ParseLinkId(_) => Status::invalid_argument("link_id"),
+            ParseAssigneeId(_) => Status::invalid_argument("assignee_id"),
+            ParseId(_) => Status::invalid_argument("incident_id"),
+            ParseNodeId(_) => Status::invalid_argument("node_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseReportId(_) => Status::invalid_argument("report_id"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Command(err) => err.into(),
+            Downtime(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+            Report(err) => err.into(),
+            Ticket(err) => err.into(),
+            User(err) => err.into(),
+            Validate(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl IncidentService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::IncidentServiceCreateRequest>,
+    ) -> Result<Response<api::IncidentServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get(
+        &self,
+        req: Request<api::IncidentServiceGetRequest>,
+    ) -> Result<Response<api::IncidentServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::IncidentServiceListRequest>,
+    ) -> Result<Response<api::IncidentServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update(
+        &self,
+        req: Request<api::IncidentServiceUpdateRequest>,
+    ) -> Result<Response<api::IncidentServiceUpdateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn comment(
+        &self,
+        req: Request<api::IncidentServiceCommentRequest>,
+    ) -> Result<Response<api::IncidentServiceCommentResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| comment(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn link(
+        &self,
+        req: Request<api::IncidentServiceLinkRequest>,
+    ) -> Result<Response<api::IncidentServiceLinkResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| link(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::IncidentServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::IncidentServiceCreateResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, IncidentAdminPerm::Create).await?;
+
+    let report_id: NodeReportId = req.report_id.parse().map_err(Error::ParseReportId)?;
+    let report = NodeReport::by_id(report_id, &mut write).await?;
+    let node = Node::by_id(report.node_id, &mut write).await?;
+
+    let incident = NewIncident::from_report(&report, node.org_id)
+        .create(&mut write)
+        .await?;
+
+    let created_by = common::Resource::from(&authz);
+    write.mqtt(api::IncidentMessage::created(&incident, created_by));
+
+    Ok(api::IncidentServiceCreateResponse {
+        incident: Some(incident.into()),
+    })
+}
+
+pub async fn get(
+    req: api::IncidentServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::IncidentServiceGetResponse, Error> {
+    req.validate()?;
+    let id: IncidentId = req.incident_id.parse().map_err(Error::ParseId)?;
+    let incident = Incident::by_id(id, &mut read).await?;
+    read.auth_or_for(
+        &meta,
+        IncidentAdminPerm::Get,
+        IncidentPerm::Get,
+        incident.org_id,
+    )
+    .await?;
+
+    let comments = incident.comments(&mut read).await?;
+    let links = incident.links(&mut read).await?;
+    let ticket = Ticket::by_incident(id, &mut read).await?;
+
+    Ok(api::IncidentServiceGetResponse {
+        incident: Some(incident.into()),
+        comments: comments.into_iter().map(Into::into).collect(),
+        links: links.into_iter().map(Into::into).collect(),
+        ticket: ticket.map(Into::into),
+    })
+}
+
+pub async fn list(
+    req: api::IncidentServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::IncidentServiceListResponse, Error> {
+    req.validate()?;
+    let org_id: Option<OrgId> = req
+        .org_id
+        .as_deref()
+        .map(|id| id.parse().map_err(Error::ParseOrgId))
+        .transpose()?;
+
+    if let Some(org_id) = org_id {
+        read.auth_or_for(&meta, IncidentAdminPerm::List, IncidentPerm::List, org_id)
+            .await?;
+    } else {
+        read.auth(&meta, IncidentAdminPerm::List).await?;
+    }
+
+    let filter = IncidentFilter {
+        org_id,
+        node_id: req
+            .node_id
+            .as_deref()
+            .map(|id| id.parse().map_err(Error::ParseNodeId))
+            .transpose()?,
+        assignee_id: req
+            .assignee_id
+            .as_deref()
+            .map(|id| id.parse().map_err(Error::ParseAssigneeId))
+            .transpose()?,
+        statuses: req
+            .statuses()
+            .map(IncidentStatus::try_from)
+            .collect::<Result<_, _>>()?,
+    };
+
+    let incidents = Incident::list(filter, &mut read).await?;
+
+    Ok(api::IncidentServiceListResponse {
+        incidents: incidents.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn update(
+    req: api::IncidentServiceUpdateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::IncidentServiceUpdateResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, IncidentAdminPerm::Update).await?;
+
+    let id: IncidentId = req.incident_id.parse().map_err(Error::ParseId)?;
+    let mut incident = Incident::by_id(id, &mut write).await?;
+
+    if req.status.is_some() {
+        let status = IncidentStatus::try_from(req.status())?;
+        incident = incident.set_status(status, &mut write).await?;
+    }
+
+    if req.unassign {
+        incident = incident.assign(None, &mut write).await?;
+    } else if let Some(assignee_id) = &req.assignee_id {
+        let assignee_id: UserId = assignee_id.parse().map_err(Error::ParseAssigneeId)?;
+        let assignee = User::by_id(assignee_id, &mut write).await?;
+        incident = incident.assign(Some(assignee.id), &mut write).await?;
+    }
+
+    let updated_by = common::Resource::from(&authz);
+    write.mqtt(api::IncidentMessage::updated(&incident, updated_by));
+
+    Ok(api::IncidentServiceUpdateResponse {
+        incident: Some(incident.into()),
+    })
+}
+
+pub async fn comment(
+    req: api::IncidentServiceCommentRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::IncidentServiceCommentResponse, Error> {
+    req.validate()?;
+    let id: IncidentId = req.incident_id.parse().map_err(Error::ParseId)?;
+    let incident = Incident::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_or_for(
+            &meta,
+            IncidentAdminPerm::Comment,
+            IncidentPerm::Comment,
+            incident.org_id,
+        )
+        .await?;
+
+    let message = req.message.trim().to_string();
+    let comment = IncidentComment::create(id, authz.resource(), message, &mut write).await?;
+    write.mqtt(api::IncidentMessage::commented(&incident, &comment));
+
+    Ok(api::IncidentServiceCommentResponse {
+        comment: Some(comment.into()),
+    })
+}
+
+pub async fn link(
+    req: api::IncidentServiceLinkRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::IncidentServiceLinkResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, IncidentAdminPerm::Link).await?;
+
+    let id: IncidentId = req.incident_id.parse().map_err(Error::ParseId)?;
+    let incident = Incident::by_id(id, &mut write).await?;
+
+    let link_type = IncidentLinkType::try_from(req.link_type())?;
+    let link_id: uuid::Uuid = req.link_id.parse().map_err(Error::ParseLinkId)?;
+    let node_id = match link_type {
+        IncidentLinkType::Command => {
+            let command = Command::by_id(CommandId::from(link_id), &mut write).await?;
+            command.node_id
+        }
+        IncidentLinkType::Downtime => {
+            let downtime = NodeDowntime::by_id(link_id, &mut write).await?;
+            Some(downtime.node_id)
+        }
+    };
+    if node_id != Some(incident.node_id) {
+        return Err(Error::LinkNode(link_type, link_id));
+    }
+
+    IncidentLink::create(id, link_type, link_id, &mut write).await?;
+
+    let links = incident.links(&mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    write.mqtt(api::IncidentMessage::updated(&incident, updated_by));
+
+    Ok(api::IncidentServiceLinkResponse {
+        links: links.into_iter().map(Into::into).collect(),
+    })
+}
+
+impl Validate for api::IncidentServiceCreateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("report_id", &self.report_id);
+    }
+}
+
+impl Validate for api::IncidentServiceGetRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("incident_id", &self.incident_id);
+    }
+}
+
+impl Validate for api::IncidentServiceListRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+        v.maybe_uuid("node_id", self.node_id.as_deref());
+        v.maybe_uuid("assignee_id", self.assignee_id.as_deref());
+        v.enum_values::<api::IncidentStatus>("statuses", &self.statuses);
+    }
+}
+
+impl Validate for api::IncidentServiceUpdateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("incident_id", &self.incident_id);
+        v.maybe_enum_value::<api::IncidentStatus>("status", self.status);
+        v.maybe_uuid("assignee_id", self.assignee_id.as_deref());
+        v.check(
+            !(self.unassign && self.assignee_id.is_some()),
+            "unassign",
+            "Can't be set together with `assignee_id`.",
+        );
+    }
+}
+
+impl Validate for api::IncidentServiceCommentRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("incident_id", &self.incident_id);
+        v.length("message", &self.message, 1..=MAX_MESSAGE_LEN);
+        v.no_secrets("message", &self.message);
+    }
+}
+
+impl Validate for api::IncidentServiceLinkRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("incident_id", &self.incident_id);
+        v.enum_value::<api::IncidentLinkType>("link_type", self.link_type);
+        v.uuid("link_id", &self.link_id);
+    }
+}
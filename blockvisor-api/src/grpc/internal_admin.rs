@@ -0,0 +1,986 @@
+//! Cross-org operations for blockjoy staff.
+//!
+//! These endpoints are not scoped to any org, so every one of them requires an
+//! `internal-admin-*` permission that is only granted to `blockjoy-admin`.
+//!
+//! The cleanup endpoints find the external resources in `model::orphan` that
+//! are no longer referenced by any node or endpoint. A dry run only reports
+//! them without deleting anything.
+//!
+//! Backfills are started here and then run in the background, so their
+//! progress is polled with `list_backfills`.
+//!
+//! Feature flags are read through `Context::flags`, so a changed flag is
+//! reloaded right away by this instance and within `flag.refresh` by others.
+//!
+//! Host weights rank the candidate hosts of new nodes. Shadow weights only log
+//! the host that they would have chosen instead, so new weights can be compared
+//! against live placements before they are made active.
+//!
+//! Network heads are refreshed in the background, so setting the rpc provider
+//! of a network only takes effect on the next refresh.
+//!
+//! Legal holds are set and cleared here for compliance requests, and each
+//! change is recorded in the audit log along with its reason.
+
+use diesel::result::Error::NotFound;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use serde_json::json;
+use strum::IntoEnumIterator;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+use url::Url;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::InternalAdminPerm;
+use crate::auth::resource::{NodeId, OrgId, UserId};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::job;
+use crate::model::audit::{AuditEvent, NewAuditLog};
+use crate::model::backfill::{self, Backfill, Task};
+use crate::model::feature_flag::{self, FeatureFlag, UpsertFeatureFlag};
+use crate::model::host::{HostFilter, HostSearch};
+use crate::model::host_weights::{HostRanking, HostWeights};
+use crate::model::legal_hold::{LegalHold, LegalHoldId, NewLegalHold};
+use crate::model::node::{NextState, Node, NodeFilter, NodeSearch, NodeState, UpdateNodeState};
+use crate::model::protocol::NetworkHead;
+use crate::model::protocol::version::{self, VariantKey};
+use crate::model::retention::RetainedTable;
+use crate::model::{Org, orphan};
+use crate::util::{LOWER_KEBAB_CASE, NanosUtc, SearchOperator};
+
+use super::api::internal_admin_service_server::InternalAdminService;
+use super::validate::{Validate, Validator};
+use super::{Grpc, Metadata, Status, api, common};
+
+/// The largest page of search results.
+const MAX_SEARCH_LIMIT: u64 = 1000;
+
+/// The longest override reason stored in the audit log.
+const MAX_REASON_LEN: usize = 1024;
+
+/// The largest number of rows a backfill rewrites per transaction.
+const MAX_BATCH_SIZE: u32 = 10_000;
+
+/// The longest feature flag description.
+const MAX_DESCRIPTION_LEN: usize = 1024;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Internal admin audit log error: {0}
+    Audit(#[from] crate::model::audit::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Internal admin backfill error: {0}
+    Backfill(#[from] crate::model::backfill::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Legal holds must be changed by a user.
+    ClaimsNotUser,
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Internal admin feature flag error: {0}
+    FeatureFlag(#[from] crate::model::feature_flag::Error),
+    /// Internal admin host error: {0}
+    Host(#[from] crate::model::host::Error),
+    /// Internal admin host grpc error: {0}
+    HostGrpc(#[from] crate::grpc::host::Error),
+    /// Internal admin host weights error: {0}
+    HostWeights(#[from] crate::model::host_weights::Error),
+    /// Internal admin legal hold error: {0}
+    LegalHold(#[from] crate::model::legal_hold::Error),
+    /// Internal admin network head error: {0}
+    NetworkHead(#[from] crate::model::protocol::head::Error),
+    /// Failed to parse search limit: {0}
+    Limit(std::num::TryFromIntError),
+    /// Internal admin node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Internal admin node grpc error: {0}
+    NodeGrpc(#[from] crate::grpc::node::Error),
+    /// Internal admin node status error: {0}
+    NodeStatus(#[from] crate::model::node::status::Error),
+    /// Failed to parse search offset: {0}
+    Offset(std::num::TryFromIntError),
+    /// Internal admin org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Internal admin orphan error: {0}
+    Orphan(#[from] crate::model::orphan::Error),
+    /// Failed to parse LegalHoldId: {0}
+    ParseHoldId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse ProtocolId: {0}
+    ParseProtocolId(uuid::Error),
+    /// Failed to parse UserId: {0}
+    ParseUserId(uuid::Error),
+    /// Failed to parse variant key: {0}
+    ParseVariantKey(version::Error),
+    /// Internal admin retention error: {0}
+    Retention(#[from] crate::model::retention::Error),
+    /// Stripe is not configured.
+    StripeNotConfigured,
+    /// Unknown host ranking.
+    UnknownRanking,
+    /// Internal admin request validation: {0}
+    Validate(#[from] crate::grpc::validate::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            ClaimsNotUser => Status::forbidden("Access denied."),
+            Diesel(_) => Status::internal("Internal error."),
+            Limit(_) => Status::invalid_argument("limit"),
+            Offset(_) => Status::invalid_argument("offset"),
+            ParseHoldId(_) => Status::invalid_argument("hold_id"),
+            ParseNodeId(_) => Status::invalid_argument("node_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_ids"),
+            ParseProtocolId(_) => Status::invalid_argument("protocol_id"),
+            ParseUserId(_) => Status::invalid_argument("user_ids"),
+            ParseVariantKey(_) => Status::invalid_argument("variant_key"),
+            StripeNotConfigured => Status::failed_precondition("Stripe is not configured."),
+            UnknownRanking => Status::invalid_argument("ranking"),
+            Audit(err) => err.into(),
+            Auth(err) => err.into(),
+            Backfill(err) => err.into(),
+            Claims(err) => err.into(),
+            FeatureFlag(err) => err.into(),
+            Host(err) => err.into(),
+            HostGrpc(err) => err.into(),
+            HostWeights(err) => err.into(),
+            LegalHold(err) => err.into(),
+            NetworkHead(err) => err.into(),
+            Node(err) => err.into(),
+            NodeGrpc(err) => err.into(),
+            NodeStatus(err) => err.into(),
+            Org(err) => err.into(),
+            Orphan(err) => err.into(),
+            Retention(err) => err.into(),
+            Validate(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl InternalAdminService for Grpc {
+    async fn search_nodes(
+        &self,
+        req: Request<api::InternalAdminServiceSearchNodesRequest>,
+    ) -> Result<Response<api::InternalAdminServiceSearchNodesResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| search_nodes(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn search_hosts(
+        &self,
+        req: Request<api::InternalAdminServiceSearchHostsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceSearchHostsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| search_hosts(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn override_node_state(
+        &self,
+        req: Request<api::InternalAdminServiceOverrideNodeStateRequest>,
+    ) -> Result<Response<api::InternalAdminServiceOverrideNodeStateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| override_node_state(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn cleanup_dns(
+        &self,
+        req: Request<api::InternalAdminServiceCleanupDnsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceCleanupDnsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| cleanup_dns(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn cleanup_stripe(
+        &self,
+        req: Request<api::InternalAdminServiceCleanupStripeRequest>,
+    ) -> Result<Response<api::InternalAdminServiceCleanupStripeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| cleanup_stripe(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list_feature_flags(
+        &self,
+        req: Request<api::InternalAdminServiceListFeatureFlagsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceListFeatureFlagsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_feature_flags(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn set_feature_flag(
+        &self,
+        req: Request<api::InternalAdminServiceSetFeatureFlagRequest>,
+    ) -> Result<Response<api::InternalAdminServiceSetFeatureFlagResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        let resp = self
+            .write(|write| set_feature_flag(req, meta.into(), write).scope_boxed())
+            .await?;
+
+        // the flags are only reloaded once the change has committed
+        self.context.flags.invalidate().await;
+
+        Ok(resp)
+    }
+
+    async fn get_host_weights(
+        &self,
+        req: Request<api::InternalAdminServiceGetHostWeightsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceGetHostWeightsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_host_weights(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn set_host_weights(
+        &self,
+        req: Request<api::InternalAdminServiceSetHostWeightsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceSetHostWeightsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_host_weights(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_backfills(
+        &self,
+        req: Request<api::InternalAdminServiceListBackfillsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceListBackfillsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_backfills(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn run_backfill(
+        &self,
+        req: Request<api::InternalAdminServiceRunBackfillRequest>,
+    ) -> Result<Response<api::InternalAdminServiceRunBackfillResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        let task = Task::from_name(&req.name).ok();
+        let batch_size = batch_size(&req);
+        let resp = self
+            .write(|write| run_backfill(req, meta.into(), write).scope_boxed())
+            .await?;
+
+        // the backfill row is only visible to the runner once committed
+        if let Some(task) = task {
+            job::spawn_backfill(&self.context, task, batch_size);
+        }
+
+        Ok(resp)
+    }
+
+    async fn list_network_heads(
+        &self,
+        req: Request<api::InternalAdminServiceListNetworkHeadsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceListNetworkHeadsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_network_heads(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn set_network_head(
+        &self,
+        req: Request<api::InternalAdminServiceSetNetworkHeadRequest>,
+    ) -> Result<Response<api::InternalAdminServiceSetNetworkHeadResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_network_head(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_table_retention(
+        &self,
+        req: Request<api::InternalAdminServiceListTableRetentionRequest>,
+    ) -> Result<Response<api::InternalAdminServiceListTableRetentionResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_table_retention(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list_legal_holds(
+        &self,
+        req: Request<api::InternalAdminServiceListLegalHoldsRequest>,
+    ) -> Result<Response<api::InternalAdminServiceListLegalHoldsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_legal_holds(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn set_legal_hold(
+        &self,
+        req: Request<api::InternalAdminServiceSetLegalHoldRequest>,
+    ) -> Result<Response<api::InternalAdminServiceSetLegalHoldResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_legal_hold(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn clear_legal_hold(
+        &self,
+        req: Request<api::InternalAdminServiceClearLegalHoldRequest>,
+    ) -> Result<Response<api::InternalAdminServiceClearLegalHoldResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| clear_legal_hold(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+impl Validate for api::InternalAdminServiceSearchNodesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.length("search", &self.search, 1..=256);
+        v.check(
+            (1..=MAX_SEARCH_LIMIT).contains(&self.limit),
+            "limit",
+            format!("Must be between 1 and {MAX_SEARCH_LIMIT}."),
+        );
+    }
+}
+
+impl Validate for api::InternalAdminServiceSearchHostsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.length("search", &self.search, 1..=256);
+        v.check(
+            (1..=MAX_SEARCH_LIMIT).contains(&self.limit),
+            "limit",
+            format!("Must be between 1 and {MAX_SEARCH_LIMIT}."),
+        );
+    }
+}
+
+impl Validate for api::InternalAdminServiceOverrideNodeStateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+        v.maybe_enum_value::<common::NodeState>("node_state", self.node_state);
+        v.maybe_enum_value::<common::NextState>("next_state", self.next_state);
+        v.check(
+            !(self.clear_next_state && self.next_state.is_some()),
+            "clear_next_state",
+            "Can't be set with `next_state`.",
+        );
+        v.check(
+            self.node_state.is_some() || self.next_state.is_some() || self.clear_next_state,
+            "node_state",
+            "One of `node_state`, `next_state` or `clear_next_state` is required.",
+        );
+        v.length("reason", &self.reason, 1..=MAX_REASON_LEN);
+    }
+}
+
+impl Validate for api::InternalAdminServiceSetFeatureFlagRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.length("name", &self.name, 1..=64);
+        v.check(
+            self.name.chars().all(|c| LOWER_KEBAB_CASE.contains(c)),
+            "name",
+            "Must be lower-kebab-case.",
+        );
+        if let Some(description) = &self.description {
+            v.length("description", description, 0..=MAX_DESCRIPTION_LEN);
+        }
+        v.check(
+            self.rollout_percent <= 100,
+            "rollout_percent",
+            "Must be between 0 and 100.",
+        );
+        for org_id in &self.org_ids {
+            v.uuid("org_ids", org_id);
+        }
+        for user_id in &self.user_ids {
+            v.uuid("user_ids", user_id);
+        }
+    }
+}
+
+impl Validate for api::InternalAdminServiceSetNetworkHeadRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("protocol_id", &self.protocol_id);
+        v.length("variant_key", &self.variant_key, 3..=64);
+        if let Some(rpc_url) = &self.rpc_url {
+            v.check(
+                Url::parse(rpc_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")),
+                "rpc_url",
+                "Must be an http or https url.",
+            );
+        }
+    }
+}
+
+impl Validate for api::InternalAdminServiceSetLegalHoldRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.check(
+            self.org_id.is_some() != self.node_id.is_some(),
+            "org_id",
+            "Exactly one of `org_id` or `node_id` is required.",
+        );
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+        v.maybe_uuid("node_id", self.node_id.as_deref());
+        v.length("reason", &self.reason, 1..=MAX_REASON_LEN);
+    }
+}
+
+impl Validate for api::InternalAdminServiceClearLegalHoldRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("hold_id", &self.hold_id);
+        v.length("reason", &self.reason, 1..=MAX_REASON_LEN);
+    }
+}
+
+impl Validate for api::InternalAdminServiceRunBackfillRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.length("name", &self.name, 1..=64);
+        if let Some(batch_size) = self.batch_size {
+            v.check(
+                (1..=MAX_BATCH_SIZE).contains(&batch_size),
+                "batch_size",
+                format!("Must be between 1 and {MAX_BATCH_SIZE}."),
+            );
+        }
+    }
+}
+
+/// Find nodes in any org by id, name, display name, DNS name or IP.
+pub async fn search_nodes(
+    req: api::InternalAdminServiceSearchNodesRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceSearchNodesResponse, Error> {
+    req.validate()?;
+    let authz = read.auth(&meta, InternalAdminPerm::SearchNodes).await?;
+
+    let pattern = search_pattern(&req.search);
+    let filter = NodeFilter {
+        protocol_ids: vec![],
+        version_keys: vec![],
+        semantic_versions: vec![],
+        org_ids: vec![],
+        host_ids: vec![],
+        user_ids: vec![],
+        ip_addresses: vec![],
+        node_states: vec![],
+        next_states: vec![],
+        min_blocks_behind: None,
+        config_drift: None,
+        project_ids: vec![],
+        network_ids: vec![],
+        network_kinds: vec![],
+        search: Some(NodeSearch {
+            operator: SearchOperator::Or,
+            id: Some(pattern.clone()),
+            node_name: Some(pattern.clone()),
+            display_name: Some(pattern.clone()),
+            dns_name: Some(pattern.clone()),
+            ip: Some(pattern),
+        }),
+        sort: Default::default(),
+        limit: req.limit.try_into().map_err(Error::Limit)?,
+        offset: req.offset.try_into().map_err(Error::Offset)?,
+    };
+
+    let (nodes, total) = filter.query(&mut read).await?;
+    let nodes = api::Node::from_models(nodes, &authz, &mut read).await?;
+
+    Ok(api::InternalAdminServiceSearchNodesResponse { nodes, total })
+}
+
+/// Find hosts in any org by id, name, version, OS or IP.
+pub async fn search_hosts(
+    req: api::InternalAdminServiceSearchHostsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceSearchHostsResponse, Error> {
+    req.validate()?;
+    let authz = read.auth(&meta, InternalAdminPerm::SearchHosts).await?;
+
+    let pattern = search_pattern(&req.search);
+    let filter = HostFilter {
+        org_ids: vec![],
+        versions: vec![],
+        search: Some(HostSearch {
+            operator: SearchOperator::Or,
+            id: Some(pattern.clone()),
+            network_name: Some(pattern.clone()),
+            display_name: Some(pattern.clone()),
+            bv_version: Some(pattern.clone()),
+            os: Some(pattern.clone()),
+            ip: Some(pattern),
+        }),
+        sort: Default::default(),
+        limit: req.limit.try_into().map_err(Error::Limit)?,
+        offset: req.offset.try_into().map_err(Error::Offset)?,
+    };
+
+    let (hosts, total) = filter.query(&mut read).await?;
+    let hosts = api::Host::from_hosts(hosts, &authz, &mut read).await?;
+
+    Ok(api::InternalAdminServiceSearchHostsResponse { hosts, total })
+}
+
+/// Force the state of a node that is stuck, e.g. after a lost host.
+///
+/// The override is recorded in the audit log along with the given reason.
+pub async fn override_node_state(
+    req: api::InternalAdminServiceOverrideNodeStateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceOverrideNodeStateResponse, Error> {
+    req.validate()?;
+    let authz = write
+        .auth(&meta, InternalAdminPerm::OverrideNodeState)
+        .await?;
+
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseNodeId)?;
+    let node = Node::by_id(node_id, &mut write).await?;
+
+    let node_state: Option<NodeState> = req
+        .node_state
+        .map(|_| req.node_state().try_into())
+        .transpose()?;
+    let next_state: Option<Option<NextState>> = if req.clear_next_state {
+        Some(None)
+    } else {
+        req.next_state
+            .map(|_| req.next_state().try_into().map(Some))
+            .transpose()?
+    };
+
+    let update = UpdateNodeState {
+        node_state,
+        next_state,
+        protocol_state: None,
+        protocol_health: None,
+        p2p_address: None,
+    };
+    let updated = update.force(node_id, &mut write).await?;
+
+    NewAuditLog::new(Some(node.org_id), &authz, AuditEvent::NodeStateOverridden)
+        .with_data(json!({
+            "node_id": node_id.to_string(),
+            "old_node_state": format!("{:?}", node.node_state),
+            "new_node_state": format!("{:?}", updated.node_state),
+            "old_next_state": node.next_state.map(|state| format!("{state:?}")),
+            "new_next_state": updated.next_state.map(|state| format!("{state:?}")),
+            "reason": req.reason.trim(),
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    let node = api::Node::from_model(updated, &authz, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    write.mqtt(api::NodeMessage::updated(node.clone(), updated_by));
+
+    Ok(api::InternalAdminServiceOverrideNodeStateResponse { node: Some(node) })
+}
+
+/// Find node DNS records that no node refers to, and delete them unless this
+/// is a dry run.
+pub async fn cleanup_dns(
+    req: api::InternalAdminServiceCleanupDnsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceCleanupDnsResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::CleanupDns).await?;
+
+    let dns = read.ctx.dns.clone();
+    let orphans = orphan::dns_records(dns.as_ref().as_ref(), &mut read).await?;
+    let deleted = if req.dry_run {
+        0
+    } else {
+        orphan::delete_dns_records(dns.as_ref().as_ref(), &orphans).await
+    };
+
+    Ok(api::InternalAdminServiceCleanupDnsResponse {
+        orphans: orphans
+            .into_iter()
+            .map(|record| api::OrphanedDnsRecord {
+                record_id: record.id,
+                name: record.name,
+                created_at: Some(NanosUtc::from(record.created_on).into()),
+            })
+            .collect(),
+        deleted,
+        dry_run: req.dry_run,
+    })
+}
+
+/// Find stripe subscription items that no node or endpoint refers to, and
+/// delete them unless this is a dry run.
+pub async fn cleanup_stripe(
+    req: api::InternalAdminServiceCleanupStripeRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceCleanupStripeResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::CleanupStripe).await?;
+    let stripe = read.ctx.stripe.clone().ok_or(Error::StripeNotConfigured)?;
+
+    let ctx = read.ctx;
+    let orphans = orphan::stripe_items(stripe.as_ref().as_ref(), &ctx.keyring, &mut read).await?;
+    let deleted = if req.dry_run {
+        0
+    } else {
+        orphan::delete_stripe_items(stripe.as_ref().as_ref(), &orphans).await
+    };
+
+    Ok(api::InternalAdminServiceCleanupStripeResponse {
+        orphans: orphans
+            .into_iter()
+            .map(|orphan| api::OrphanedStripeItem {
+                org_id: orphan.org_id.to_string(),
+                item_id: orphan.item.id.to_string(),
+                price_id: orphan.item.price.map(|price| price.id.0),
+                quantity: orphan.item.quantity,
+            })
+            .collect(),
+        deleted,
+        dry_run: req.dry_run,
+    })
+}
+
+pub async fn list_feature_flags(
+    _: api::InternalAdminServiceListFeatureFlagsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceListFeatureFlagsResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::ListFeatureFlags)
+        .await?;
+
+    let flags = FeatureFlag::all(&mut read).await?;
+
+    Ok(api::InternalAdminServiceListFeatureFlagsResponse {
+        flags: flags.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Create or replace a feature flag by name.
+///
+/// The previous settings are recorded in the audit log.
+pub async fn set_feature_flag(
+    req: api::InternalAdminServiceSetFeatureFlagRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceSetFeatureFlagResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, InternalAdminPerm::SetFeatureFlag).await?;
+
+    let org_ids = req
+        .org_ids
+        .iter()
+        .map(|id| id.parse().map(Some).map_err(Error::ParseOrgId))
+        .collect::<Result<Vec<Option<OrgId>>, _>>()?;
+    let user_ids = req
+        .user_ids
+        .iter()
+        .map(|id| id.parse().map(Some).map_err(Error::ParseUserId))
+        .collect::<Result<Vec<Option<UserId>>, _>>()?;
+
+    let name = req.name.trim().to_string();
+    let previous = match FeatureFlag::by_name(&name, &mut write).await {
+        Ok(flag) => Some(flag),
+        Err(feature_flag::Error::ByName(_, NotFound)) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let upsert = UpsertFeatureFlag {
+        name,
+        description: req.description.map(|desc| desc.trim().to_string()),
+        enabled: req.enabled,
+        rollout_percent: i32::try_from(req.rollout_percent).unwrap_or(100),
+        org_ids,
+        user_ids,
+    };
+    let flag = upsert.upsert(&mut write).await?;
+
+    NewAuditLog::new(None, &authz, AuditEvent::FeatureFlagSet)
+        .with_data(json!({
+            "name": flag.name,
+            "old_enabled": previous.as_ref().map(|flag| flag.enabled),
+            "new_enabled": flag.enabled,
+            "old_rollout_percent": previous.as_ref().map(|flag| flag.rollout_percent),
+            "new_rollout_percent": flag.rollout_percent,
+            "org_ids": req.org_ids,
+            "user_ids": req.user_ids,
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::InternalAdminServiceSetFeatureFlagResponse {
+        flag: Some(flag.into()),
+    })
+}
+
+/// The active and shadow host weights, along with the configured defaults that
+/// the active weights fall back to.
+pub async fn get_host_weights(
+    _: api::InternalAdminServiceGetHostWeightsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceGetHostWeightsResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::GetHostWeights).await?;
+
+    let defaults = HostWeights::from(&*read.ctx.config.scheduler);
+    let active = HostWeights::by_ranking(HostRanking::Active, &mut read).await?;
+    let shadow = HostWeights::by_ranking(HostRanking::Shadow, &mut read).await?;
+
+    Ok(api::InternalAdminServiceGetHostWeightsResponse {
+        active: Some(active.unwrap_or(defaults).into()),
+        shadow: shadow.map(Into::into),
+        defaults: Some(defaults.into()),
+    })
+}
+
+/// Set the host weights of a ranking, or unset them if no weights are given.
+///
+/// Unset active weights fall back to the configured defaults, and unset shadow
+/// weights stop the shadow ranking.
+pub async fn set_host_weights(
+    req: api::InternalAdminServiceSetHostWeightsRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceSetHostWeightsResponse, Error> {
+    let authz = write.auth(&meta, InternalAdminPerm::SetHostWeights).await?;
+
+    let ranking = HostRanking::from_api(req.ranking()).ok_or(Error::UnknownRanking)?;
+    let previous = HostWeights::by_ranking(ranking, &mut write).await?;
+    let weights = match req.weights {
+        Some(weights) => Some(HostWeights::from(weights).set(ranking, &mut write).await?),
+        None => {
+            HostWeights::unset(ranking, &mut write).await?;
+            None
+        }
+    };
+
+    NewAuditLog::new(None, &authz, AuditEvent::HostWeightsSet)
+        .with_data(json!({
+            "ranking": ranking,
+            "old_weights": previous,
+            "new_weights": weights,
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::InternalAdminServiceSetHostWeightsResponse {
+        weights: weights.map(Into::into),
+    })
+}
+
+/// List the progress of each backfill that was started, and the names of all
+/// backfills that can be run.
+pub async fn list_backfills(
+    _: api::InternalAdminServiceListBackfillsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceListBackfillsResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::ListBackfills).await?;
+
+    let backfills = Backfill::all(&mut read).await?;
+
+    Ok(api::InternalAdminServiceListBackfillsResponse {
+        backfills: backfills.into_iter().map(Into::into).collect(),
+        tasks: Task::iter().map(|task| task.name().to_string()).collect(),
+    })
+}
+
+/// Start or resume a backfill, which then runs in the background.
+pub async fn run_backfill(
+    req: api::InternalAdminServiceRunBackfillRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceRunBackfillResponse, Error> {
+    req.validate()?;
+    write.auth(&meta, InternalAdminPerm::RunBackfill).await?;
+
+    let task = Task::from_name(&req.name)?;
+    let backfill = Backfill::start(task, &mut write).await?;
+
+    Ok(api::InternalAdminServiceRunBackfillResponse {
+        backfill: Some(backfill.into()),
+    })
+}
+
+/// The requested backfill batch size, or the default.
+pub fn batch_size(req: &api::InternalAdminServiceRunBackfillRequest) -> i64 {
+    req.batch_size
+        .map_or(backfill::DEFAULT_BATCH_SIZE, i64::from)
+}
+
+pub async fn list_network_heads(
+    _: api::InternalAdminServiceListNetworkHeadsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceListNetworkHeadsResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::ListNetworkHeads)
+        .await?;
+
+    let heads = NetworkHead::all(&mut read).await?;
+
+    Ok(api::InternalAdminServiceListNetworkHeadsResponse {
+        heads: heads.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Set or clear the rpc provider used for the head of a network.
+///
+/// Networks without an rpc provider follow their reference node.
+pub async fn set_network_head(
+    req: api::InternalAdminServiceSetNetworkHeadRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceSetNetworkHeadResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, InternalAdminPerm::SetNetworkHead).await?;
+
+    let protocol_id = req.protocol_id.parse().map_err(Error::ParseProtocolId)?;
+    let variant_key = VariantKey::new(req.variant_key).map_err(Error::ParseVariantKey)?;
+    let rpc_url = req.rpc_url.as_deref().map(str::trim);
+    let head = NetworkHead::set_rpc_url(protocol_id, &variant_key, rpc_url, &mut write).await?;
+
+    NewAuditLog::new(None, &authz, AuditEvent::NetworkHeadSet)
+        .with_data(json!({
+            "protocol_id": head.protocol_id,
+            "variant_key": head.variant_key.to_string(),
+            "rpc_url": head.rpc_url,
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::InternalAdminServiceSetNetworkHeadResponse {
+        head: Some(head.into()),
+    })
+}
+
+/// List the size of each table with a retention policy, and when its oldest
+/// rows are next purged by the retention job.
+pub async fn list_table_retention(
+    _: api::InternalAdminServiceListTableRetentionRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceListTableRetentionResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::ListTableRetention)
+        .await?;
+
+    let config = read.ctx.config.retention.clone();
+    let mut tables = Vec::with_capacity(RetainedTable::ALL.len());
+    for table in RetainedTable::ALL {
+        tables.push(table.usage(&config, &mut read).await?.into());
+    }
+
+    Ok(api::InternalAdminServiceListTableRetentionResponse { tables })
+}
+
+/// List the active legal holds, or all of them with `include_cleared`.
+pub async fn list_legal_holds(
+    req: api::InternalAdminServiceListLegalHoldsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::InternalAdminServiceListLegalHoldsResponse, Error> {
+    read.auth(&meta, InternalAdminPerm::ListLegalHolds).await?;
+
+    let holds = LegalHold::list(req.include_cleared, &mut read).await?;
+
+    Ok(api::InternalAdminServiceListLegalHoldsResponse {
+        holds: holds.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Put an org or node under legal hold, which exempts its data from deletes
+/// and retention purges until the hold is cleared.
+pub async fn set_legal_hold(
+    req: api::InternalAdminServiceSetLegalHoldRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceSetLegalHoldResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, InternalAdminPerm::SetLegalHold).await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let (org_id, node_id) = if let Some(org_id) = &req.org_id {
+        let org_id: OrgId = org_id.parse().map_err(Error::ParseOrgId)?;
+        let org = Org::by_id(org_id, &mut write).await?;
+        (org.id, None)
+    } else {
+        let node_id = req.node_id.as_deref().unwrap_or_default();
+        let node_id: NodeId = node_id.parse().map_err(Error::ParseNodeId)?;
+        let node = Node::by_id(node_id, &mut write).await?;
+        (node.org_id, Some(node.id))
+    };
+
+    let hold = NewLegalHold {
+        org_id: node_id.is_none().then_some(org_id),
+        node_id,
+        reason: req.reason.trim().to_string(),
+        created_by: user_id,
+    };
+    let hold = hold.create(&mut write).await?;
+
+    NewAuditLog::new(Some(org_id), &authz, AuditEvent::LegalHoldSet)
+        .with_data(json!({
+            "hold_id": hold.id.to_string(),
+            "node_id": hold.node_id.map(|id| id.to_string()),
+            "reason": hold.reason,
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::InternalAdminServiceSetLegalHoldResponse {
+        hold: Some(hold.into()),
+    })
+}
+
+/// Clear a legal hold, so that its org or node may be deleted and purged
+/// again.
+pub async fn clear_legal_hold(
+    req: api::InternalAdminServiceClearLegalHoldRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::InternalAdminServiceClearLegalHoldResponse, Error> {
+    req.validate()?;
+    let authz = write.auth(&meta, InternalAdminPerm::ClearLegalHold).await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let hold_id: LegalHoldId = req.hold_id.parse().map_err(Error::ParseHoldId)?;
+    let hold = LegalHold::by_id(hold_id, &mut write)
+        .await?
+        .clear(user_id, &mut write)
+        .await?;
+
+    let org_id = match (hold.org_id, hold.node_id) {
+        (Some(org_id), _) => Some(org_id),
+        (None, Some(node_id)) => Some(Node::deleted_org_id(node_id, &mut write).await?),
+        (None, None) => None,
+    };
+    NewAuditLog::new(org_id, &authz, AuditEvent::LegalHoldCleared)
+        .with_data(json!({
+            "hold_id": hold.id.to_string(),
+            "node_id": hold.node_id.map(|id| id.to_string()),
+            "reason": req.reason.trim(),
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::InternalAdminServiceClearLegalHoldResponse {
+        hold: Some(hold.into()),
+    })
+}
+
+/// Match `search` anywhere in a field, ignoring case.
+fn search_pattern(search: &str) -> String {
+    format!("%{}%", search.trim().to_lowercase())
+}
@@ -13,6 +13,7 @@ use crate::email::Recipient;
 use crate::model::invitation::{Invitation, InvitationFilter, NewInvitation};
 use crate::model::org::Org;
 use crate::model::user::User;
+use crate::model::user::setting::UserLocale;
 use crate::util::{HashVec, NanosUtc};
 
 use super::api::invitation_service_server::InvitationService;
@@ -64,6 +65,8 @@ pub enum Error {
     Resource(#[from] crate::auth::resource::Error),
     /// Invitation user error: {0}
     User(#[from] crate::model::user::Error),
+    /// Invitation user settings error: {0}
+    UserSettings(#[from] crate::model::user::setting::Error),
     /// Wrong email for invitation.
     WrongEmail,
     /// Wrong org for invitation.
@@ -91,6 +94,7 @@ impl From<Error> for Status {
             Org(err) => err.into(),
             Resource(err) => err.into(),
             User(err) => err.into(),
+            UserSettings(err) => err.into(),
         }
     }
 }
@@ -183,9 +187,10 @@ pub async fn create(
                 return Err(Error::AlreadyMember);
             }
 
+            let locale = UserLocale::by_user(invitee.id, &mut write).await?;
             if let Some(email) = write.ctx.email.as_ref() {
                 email
-                    .invitation_for_registered(&invitation, invitor, &invitee, "1 week")
+                    .invitation_for_registered(&invitation, invitor, &invitee, &locale, "1 week")
                     .await?;
             } else {
                 warn!("Unable to send invite email to user without email configured");
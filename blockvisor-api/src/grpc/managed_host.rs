@@ -0,0 +1,247 @@
+use diesel::result::Error::NotFound;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::ManagedHostPerm;
+use crate::auth::resource::OrgId;
+use crate::cloud_providers::Credentials;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::managed_host::{CloudProvider, ManagedHostId, NewManagedHost};
+use crate::model::{Host, IpAddress, ManagedHost, Node, Region, Token};
+
+use super::api::managed_host_service_server::ManagedHostService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Claims Resource is not a user.
+    ClaimsNotUser,
+    /// Cloud provider error: {0}
+    CloudProvider(#[from] crate::cloud_providers::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// The host of this managed host still has nodes.
+    HasNodes,
+    /// Managed host host error: {0}
+    Host(#[from] crate::model::host::Error),
+    /// Managed host ip address error: {0}
+    IpAddress(#[from] crate::model::ip_address::Error),
+    /// Missing cloud provider credentials.
+    MissingCredentials,
+    /// Managed host model error: {0}
+    Model(#[from] crate::model::managed_host::Error),
+    /// Managed host node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Failed to parse ManagedHostId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse RegionId: {0}
+    ParseRegionId(uuid::Error),
+    /// Managed host region error: {0}
+    Region(#[from] crate::model::region::Error),
+    /// Managed host token error: {0}
+    Token(#[from] crate::model::token::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ClaimsNotUser => Status::forbidden("Access denied."),
+            HasNodes => Status::failed_precondition("This host still has nodes."),
+            MissingCredentials => Status::invalid_argument("credentials"),
+            ParseId(_) => Status::invalid_argument("managed_host_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseRegionId(_) => Status::invalid_argument("region_id"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            CloudProvider(err) => err.into(),
+            Host(err) => err.into(),
+            IpAddress(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+            Region(err) => err.into(),
+            Token(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ManagedHostService for Grpc {
+    async fn set_credentials(
+        &self,
+        req: Request<api::ManagedHostServiceSetCredentialsRequest>,
+    ) -> Result<Response<api::ManagedHostServiceSetCredentialsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_credentials(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn provision(
+        &self,
+        req: Request<api::ManagedHostServiceProvisionRequest>,
+    ) -> Result<Response<api::ManagedHostServiceProvisionResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| provision(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get(
+        &self,
+        req: Request<api::ManagedHostServiceGetRequest>,
+    ) -> Result<Response<api::ManagedHostServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::ManagedHostServiceListRequest>,
+    ) -> Result<Response<api::ManagedHostServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn deprovision(
+        &self,
+        req: Request<api::ManagedHostServiceDeprovisionRequest>,
+    ) -> Result<Response<api::ManagedHostServiceDeprovisionResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| deprovision(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn set_credentials(
+    req: api::ManagedHostServiceSetCredentialsRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ManagedHostServiceSetCredentialsResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_for(&meta, ManagedHostPerm::SetCredentials, org_id)
+        .await?;
+
+    let credentials = req.credentials.ok_or(Error::MissingCredentials)?;
+    let ctx = write.ctx;
+    Credentials::try_from(credentials)?
+        .write(org_id, &ctx.secret, &mut write)
+        .await?;
+
+    Ok(api::ManagedHostServiceSetCredentialsResponse {})
+}
+
+/// Record a new machine to be created by the provision job.
+///
+/// The machine registers with the host provision token of the calling user, so
+/// only users may provision hosts.
+pub async fn provision(
+    req: api::ManagedHostServiceProvisionRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ManagedHostServiceProvisionResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write
+        .auth_for(&meta, ManagedHostPerm::Provision, org_id)
+        .await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let region_id = req.region_id.parse().map_err(Error::ParseRegionId)?;
+    let region = Region::by_id(region_id, &mut write).await?;
+    let _token = Token::host_provision_by_user(user_id, org_id, &mut write).await?;
+
+    let new_host = NewManagedHost {
+        org_id,
+        region_id: region.id,
+        provider: CloudProvider::try_from(req.provider())?,
+        provider_region: &req.provider_region,
+        machine_type: &req.machine_type,
+        machine_image: &req.machine_image,
+        name: &req.name,
+        created_by_type: authz.resource().typ(),
+        created_by_id: authz.resource().id(),
+    };
+    let managed_host = new_host.create(&mut write).await?;
+
+    Ok(api::ManagedHostServiceProvisionResponse {
+        managed_host: Some(managed_host.into()),
+    })
+}
+
+pub async fn get(
+    req: api::ManagedHostServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ManagedHostServiceGetResponse, Error> {
+    let id: ManagedHostId = req.managed_host_id.parse().map_err(Error::ParseId)?;
+    let managed_host = ManagedHost::by_id(id, &mut read).await?;
+    read.auth_for(&meta, ManagedHostPerm::Get, managed_host.org_id)
+        .await?;
+
+    Ok(api::ManagedHostServiceGetResponse {
+        managed_host: Some(managed_host.into()),
+    })
+}
+
+pub async fn list(
+    req: api::ManagedHostServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ManagedHostServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, ManagedHostPerm::List, org_id).await?;
+
+    let managed_hosts = ManagedHost::by_org_id(org_id, &mut read).await?;
+
+    Ok(api::ManagedHostServiceListResponse {
+        managed_hosts: managed_hosts.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Delete the registered host of a managed host, and mark its machine to be
+/// deleted with the provider by the provision job.
+pub async fn deprovision(
+    req: api::ManagedHostServiceDeprovisionRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ManagedHostServiceDeprovisionResponse, Error> {
+    let id: ManagedHostId = req.managed_host_id.parse().map_err(Error::ParseId)?;
+    let managed_host = ManagedHost::by_id(id, &mut write).await?;
+    let org_id = managed_host.org_id;
+    write
+        .auth_for(&meta, ManagedHostPerm::Deprovision, org_id)
+        .await?;
+
+    if let Some(host_id) = managed_host.host_id {
+        match Host::by_id(host_id, Some(org_id), &mut write).await {
+            Ok(host) => {
+                if Node::host_has_nodes(host.id, &mut write).await? {
+                    return Err(Error::HasNodes);
+                }
+                Host::delete(host.id, host.org_id, &mut write).await?;
+                IpAddress::delete_for_host(host.id, &mut write).await?;
+            }
+            Err(crate::model::host::Error::FindById(_, NotFound)) => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let managed_host = managed_host.deprovision(&mut write).await?;
+
+    Ok(api::ManagedHostServiceDeprovisionResponse {
+        managed_host: Some(managed_host.into()),
+    })
+}
@@ -0,0 +1,223 @@
+use std::collections::HashSet;
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::MeshPerm;
+use crate::auth::resource::{HostId, NodeId, OrgId, Resource};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::mesh::{MeshPeer, MeshPeerId, NewMeshPeer};
+use crate::model::{Host, Node};
+
+use super::api::mesh_service_server::MeshService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Mesh command error: {0}
+    Command(#[from] crate::grpc::command::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Mesh host error: {0}
+    Host(#[from] crate::model::host::Error),
+    /// Mesh model error: {0}
+    Model(#[from] crate::model::mesh::Error),
+    /// Mesh node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Node `{0}` is not on host `{1}`.
+    NodeHost(NodeId, HostId),
+    /// No visibility of HostMesh command.
+    NoHostMesh,
+    /// Failed to parse HostId: {0}
+    ParseHostId(uuid::Error),
+    /// Failed to parse MeshPeerId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Public host `{0}` can only join a mesh through its nodes.
+    PublicHost(HostId),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            NoHostMesh => Status::forbidden("Access denied."),
+            NodeHost(..) => Status::invalid_argument("node_id"),
+            ParseHostId(_) => Status::invalid_argument("host_id"),
+            ParseId(_) => Status::invalid_argument("peer_id"),
+            ParseNodeId(_) => Status::invalid_argument("node_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            PublicHost(_) => Status::failed_precondition("Public hosts join through nodes."),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Command(err) => err.into(),
+            Host(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl MeshService for Grpc {
+    async fn join(
+        &self,
+        req: Request<api::MeshServiceJoinRequest>,
+    ) -> Result<Response<api::MeshServiceJoinResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| join(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn leave(
+        &self,
+        req: Request<api::MeshServiceLeaveRequest>,
+    ) -> Result<Response<api::MeshServiceLeaveResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| leave(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_peers(
+        &self,
+        req: Request<api::MeshServiceListPeersRequest>,
+    ) -> Result<Response<api::MeshServiceListPeersResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_peers(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn get_config(
+        &self,
+        req: Request<api::MeshServiceGetConfigRequest>,
+    ) -> Result<Response<api::MeshServiceGetConfigResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_config(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+/// Join a host, or one of its nodes, to the mesh of its org.
+///
+/// A node joins the mesh of the org that owns it, so the nodes of a public
+/// host may each join a different mesh.
+pub async fn join(
+    req: api::MeshServiceJoinRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::MeshServiceJoinResponse, Error> {
+    let host_id: HostId = req.host_id.parse().map_err(Error::ParseHostId)?;
+    let node_id: Option<NodeId> = req
+        .node_id
+        .as_deref()
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .transpose()?;
+
+    let org_id = if let Some(node_id) = node_id {
+        write.auth_for(&meta, MeshPerm::Join, node_id).await?;
+        let node = Node::by_id(node_id, &mut write).await?;
+        if node.host_id != host_id {
+            return Err(Error::NodeHost(node_id, host_id));
+        }
+        node.org_id
+    } else {
+        write.auth_for(&meta, MeshPerm::Join, host_id).await?;
+        Host::org_id(host_id, &mut write)
+            .await?
+            .ok_or(Error::PublicHost(host_id))?
+    };
+
+    let new_peer = NewMeshPeer {
+        org_id,
+        host_id,
+        node_id,
+        public_key: &req.public_key,
+    };
+    let config = write.ctx.config.mesh.clone();
+    let peer = MeshPeer::join(new_peer, &config, &mut write).await?;
+    send(&HashSet::from([org_id]), &HashSet::new(), &mut write).await?;
+
+    Ok(api::MeshServiceJoinResponse {
+        peer: Some(peer.into()),
+    })
+}
+
+pub async fn leave(
+    req: api::MeshServiceLeaveRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::MeshServiceLeaveResponse, Error> {
+    let id: MeshPeerId = req.peer_id.parse().map_err(Error::ParseId)?;
+    let peer = MeshPeer::by_id(id, &mut write).await?;
+
+    let resource = peer
+        .node_id
+        .map_or_else(|| Resource::from(peer.host_id), Resource::from);
+    write.auth_for(&meta, MeshPerm::Leave, resource).await?;
+
+    peer.delete(&mut write).await?;
+    let org_ids = HashSet::from([peer.org_id]);
+    let removed_from = HashSet::from([peer.host_id]);
+    send(&org_ids, &removed_from, &mut write).await?;
+
+    Ok(api::MeshServiceLeaveResponse {})
+}
+
+pub async fn list_peers(
+    req: api::MeshServiceListPeersRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::MeshServiceListPeersResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, MeshPerm::ListPeers, org_id).await?;
+
+    let peers = MeshPeer::by_org_id(org_id, &mut read).await?;
+
+    Ok(api::MeshServiceListPeersResponse {
+        peers: peers.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// The current interfaces of a host, as last sent in a `HostMesh` command.
+pub async fn get_config(
+    req: api::MeshServiceGetConfigRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::MeshServiceGetConfigResponse, Error> {
+    let host_id: HostId = req.host_id.parse().map_err(Error::ParseHostId)?;
+    read.auth_for(&meta, MeshPerm::GetConfig, host_id).await?;
+
+    let config = read.ctx.config.mesh.clone();
+    let mesh = MeshPeer::host_mesh(host_id, &config, &mut read).await?;
+
+    Ok(api::MeshServiceGetConfigResponse { mesh: Some(mesh) })
+}
+
+/// Send the hosts with a peer in `org_ids` their interfaces, along with the
+/// hosts in `removed_from`, once the transaction commits.
+pub(crate) async fn send(
+    org_ids: &HashSet<OrgId>,
+    removed_from: &HashSet<HostId>,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let config = write.ctx.config.mesh.clone();
+    for command in MeshPeer::send(org_ids, removed_from, &config, write).await? {
+        let command = api::Command::from_host(&command)?.ok_or(Error::NoHostMesh)?;
+        write.mqtt(command);
+    }
+
+    Ok(())
+}
@@ -1,20 +1,29 @@
 //! The metrics service handles metrics updates for hosts and nodes.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use chrono::Utc;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
 use itertools::Itertools;
 use thiserror::Error;
 use tonic::{Request, Response};
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::auth::Authorize;
 use crate::auth::rbac::MetricsPerm;
 use crate::auth::resource::{HostId, NodeId, Resource};
-use crate::database::{Transaction, WriteConn};
+use crate::database::{Conn, Transaction, WriteConn};
+use crate::model::command::{CommandType, NewCommand};
 use crate::model::host::{Host, UpdateHostMetrics};
-use crate::model::node::{Node, NodeJobs, NodeStatus, UpdateNodeMetrics};
+use crate::model::image::HealthRules;
+use crate::model::image::health::HealthMetrics;
+use crate::model::node::abuse::{self, NewAbuseReport};
+use crate::model::node::{
+    AbuseReport, Node, NodeHealthHistory, NodeJobs, NodeState, NodeStatus, NodeUsage,
+    UpdateNodeMetrics, UpdateNodeState,
+};
+use crate::model::protocol::NetworkHead;
 use crate::util::HashVec;
 
 use super::api::metrics_service_server::MetricsService;
@@ -22,6 +31,8 @@ use super::{Grpc, Metadata, Status, api, common};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Metrics abuse error: {0}
+    Abuse(#[from] crate::model::node::abuse::Error),
     /// Auth check failed: {0}
     Auth(#[from] crate::auth::Error),
     /// Failed to parse block age: {0}
@@ -30,12 +41,20 @@ pub enum Error {
     BlockHeight(std::num::TryFromIntError),
     /// Claims check failed: {0}
     Claims(#[from] crate::auth::claims::Error),
+    /// Metrics command error: {0}
+    Command(#[from] crate::model::command::Error),
+    /// Metrics command grpc error: {0}
+    CommandGrpc(#[from] crate::grpc::command::Error),
+    /// Node health rule error: {0}
+    Health(#[from] crate::model::image::health::Error),
     /// Diesel failure: {0}
     Diesel(#[from] diesel::result::Error),
     /// Metrics host error: {0}
     Host(#[from] crate::model::host::Error),
     /// Metrics host grpc error: {0}
     HostGrpc(#[from] crate::grpc::host::Error),
+    /// Node health history error: {0}
+    History(#[from] crate::model::node::history::Error),
     /// Attempt to update the metrics for node `{node_id}`, which doesn't exist HostId: {host_id:?}
     MetricsForMissingNode {
         node_id: NodeId,
@@ -48,6 +67,8 @@ pub enum Error {
     },
     /// Missing HostMetrics.
     MissingHostMetrics,
+    /// Metrics network head error: {0}
+    NetworkHead(#[from] crate::model::protocol::head::Error),
     /// Failed to parse network received: {0}
     NetworkReceived(std::num::TryFromIntError),
     /// Failed to parse network sent: {0}
@@ -64,6 +85,8 @@ pub enum Error {
     ParseHostId(uuid::Error),
     /// Failed to parse NodeId: {0}
     ParseNodeId(uuid::Error),
+    /// Failed to parse peer count: {0}
+    PeerCount(std::num::TryFromIntError),
     /// Metrics resource error: {0}
     Resource(#[from] crate::auth::resource::Error),
     /// Failed to parse current data sync progress: {0}
@@ -74,6 +97,8 @@ pub enum Error {
     Uptime(std::num::TryFromIntError),
     /// Failed to parse used cpu hundreths: {0}
     UsedCpu(std::num::TryFromIntError),
+    /// Failed to parse used cpu percent: {0}
+    UsedCpuPercent(std::num::TryFromIntError),
     /// Failed to parse used disk space: {0}
     UsedDisk(std::num::TryFromIntError),
     /// Failed to parse used memory: {0}
@@ -96,16 +121,24 @@ impl From<Error> for Status {
             NotHostToken => Status::forbidden("Access denied."),
             ParseHostId(_) => Status::invalid_argument("metrics.host_id"),
             ParseNodeId(_) => Status::invalid_argument("metrics.node_id"),
+            PeerCount(_) => Status::invalid_argument("peer_count"),
             SyncCurrent(_) => Status::invalid_argument("data_sync_progress_current"),
             SyncTotal(_) => Status::invalid_argument("data_sync_progress_total"),
             Uptime(_) => Status::invalid_argument("uptime_seconds"),
             UsedCpu(_) => Status::invalid_argument("used_cpu_hundreths"),
+            UsedCpuPercent(_) => Status::invalid_argument("used_cpu_percent"),
             UsedDisk(_) => Status::invalid_argument("used_disk_bytes"),
             UsedMemory(_) => Status::invalid_argument("used_memory_bytes"),
+            Abuse(err) => err.into(),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
+            Command(err) => err.into(),
+            CommandGrpc(err) => err.into(),
+            Health(err) => err.into(),
             Host(err) => err.into(),
             HostGrpc(err) => err.into(),
+            History(err) => err.into(),
+            NetworkHead(err) => err.into(),
             Node(err) => err.into(),
             NodeGrpc(err) => err.into(),
             NodeStatus(err) => err.into(),
@@ -171,7 +204,12 @@ pub async fn node(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<AfterCommit<api::MetricsServiceNodeResponse>, Error> {
-    let updates = req
+    let usage = req
+        .metrics
+        .iter()
+        .map(api::NodeMetrics::usage)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut updates = req
         .metrics
         .into_iter()
         .map(api::NodeMetrics::into_update)
@@ -185,8 +223,11 @@ pub async fn node(
     let authz = write.auth_for(&meta, MetricsPerm::Node, &resources).await?;
 
     let nodes_map = nodes.iter().to_map_keep_last(|node| (node.id, node));
+    evaluate_health(&mut updates, &nodes_map, &mut write).await?;
+    detect_abuse(usage, &mut updates, &nodes_map, &mut write).await?;
 
     let nodes = UpdateNodeMetrics::apply_all(updates, &mut write).await?;
+    NodeHealthHistory::record(&nodes, &mut write).await?;
     let nodes = api::Node::from_models(nodes, &authz, &mut write).await?;
 
     let updated_by = common::Resource::from(&authz);
@@ -215,6 +256,154 @@ pub async fn node(
     }
 }
 
+/// Replace the reported protocol health of each node with the health evaluated
+/// from the health rules of its image, if it has any.
+async fn evaluate_health(
+    updates: &mut [UpdateNodeMetrics],
+    nodes: &HashMap<NodeId, &Node>,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let image_ids = nodes.values().map(|node| node.image_id).collect();
+    let rules = HealthRules::by_image_ids(&image_ids, conn).await?;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let version_ids = nodes
+        .values()
+        .filter(|node| rules.contains_key(&node.image_id))
+        .map(|node| node.protocol_version_id)
+        .collect();
+    let heights = NetworkHead::by_version_ids(&version_ids, conn).await?;
+
+    for update in updates {
+        let Some(node) = nodes.get(&update.id) else {
+            continue;
+        };
+        let Some(rules) = rules.get(&node.image_id) else {
+            continue;
+        };
+
+        let metrics = HealthMetrics {
+            block_height: update.block_height.or(node.block_height),
+            block_age: update.block_age.or(node.block_age),
+            peer_count: update.peer_count.or(node.peer_count),
+            consensus: update.consensus.or(node.consensus),
+            network_height: heights
+                .get(&node.protocol_version_id)
+                .copied()
+                .max(update.block_height),
+        };
+        if let Some(health) = rules.evaluate(&metrics) {
+            update.protocol_health = Some(health);
+        }
+    }
+
+    Ok(())
+}
+
+/// Flag the nodes on free resources whose usage looks like abuse, and suspend
+/// them unless disabled in config.
+///
+/// Suspended nodes keep their state whatever their host reports.
+async fn detect_abuse(
+    usage: Vec<NodeUsage>,
+    updates: &mut [UpdateNodeMetrics],
+    nodes: &HashMap<NodeId, &Node>,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let is_suspended = |id| {
+        nodes
+            .get(&id)
+            .is_some_and(|node| node.node_state == NodeState::Suspended)
+    };
+    for update in updates.iter_mut().filter(|update| is_suspended(update.id)) {
+        update.node_state = None;
+    }
+
+    let org_ids = nodes.values().map(|node| node.org_id).collect();
+    let free_orgs = abuse::free_org_ids(&org_ids, write).await?;
+    if free_orgs.is_empty() {
+        return Ok(());
+    }
+
+    let config = write.ctx.config.abuse.clone();
+    let now = Utc::now();
+    let mut flagged = Vec::new();
+    for usage in usage {
+        let Some(node) = nodes.get(&usage.node_id) else {
+            continue;
+        };
+        if !free_orgs.contains(&node.org_id) || is_suspended(node.id) {
+            continue;
+        }
+
+        let height = updates
+            .iter()
+            .find(|update| update.id == node.id)
+            .and_then(|update| update.block_height);
+        let progressed = height > node.block_height;
+        let verdict = usage.evaluate(progressed, node.cpu_pegged_since, &config, now)?;
+        if verdict.pegged_since != node.cpu_pegged_since {
+            abuse::set_pegged_since(node.id, verdict.pegged_since, write).await?;
+        }
+        if let Some((reason, details)) = verdict.flag {
+            flagged.push((*node, reason, details));
+        }
+    }
+    if flagged.is_empty() {
+        return Ok(());
+    }
+
+    let node_ids = flagged.iter().map(|(node, _, _)| node.id).collect();
+    let open = AbuseReport::open_node_ids(&node_ids, write).await?;
+    for (node, reason, details) in flagged {
+        if open.contains(&node.id) {
+            continue;
+        }
+
+        warn!("Flagged node {} for suspected abuse: {details}", node.id);
+        NewAbuseReport {
+            node_id: node.id,
+            org_id: node.org_id,
+            reason,
+            details,
+            suspended: config.auto_suspend,
+        }
+        .create(write)
+        .await?;
+
+        if config.auto_suspend {
+            suspend(node, write).await?;
+            if let Some(update) = updates.iter_mut().find(|update| update.id == node.id) {
+                update.node_state = None;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stop a node and keep it suspended until an admin reviews it.
+async fn suspend(node: &Node, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    let update = UpdateNodeState {
+        node_state: Some(NodeState::Suspended),
+        next_state: Some(None),
+        protocol_state: None,
+        protocol_health: None,
+        p2p_address: None,
+    };
+    let node = update.force(node.id, write).await?;
+
+    let command = NewCommand::node(&node, CommandType::NodeStop)?
+        .create(write)
+        .await?;
+    let command = crate::grpc::command::node_stop(&command, write).await?;
+    write.mqtt(command);
+
+    Ok(())
+}
+
 /// The response to send over gRPC after committing the transaction.
 pub enum AfterCommit<T> {
     Ok(T),
@@ -260,6 +449,14 @@ impl api::HostMetrics {
 }
 
 impl api::NodeMetrics {
+    pub fn usage(&self) -> Result<NodeUsage, Error> {
+        Ok(NodeUsage {
+            node_id: self.node_id.parse().map_err(Error::ParseNodeId)?,
+            cpu_percent: self.used_cpu_percent,
+            outbound_ports: self.outbound_ports.clone(),
+        })
+    }
+
     pub fn into_update(self) -> Result<UpdateNodeMetrics, Error> {
         let id = self.node_id.parse().map_err(Error::ParseNodeId)?;
 
@@ -298,7 +495,17 @@ impl api::NodeMetrics {
             block_height,
             block_age,
             consensus: self.consensus,
+            peer_count: self
+                .peer_count
+                .map(i32::try_from)
+                .transpose()
+                .map_err(Error::PeerCount)?,
             jobs: Some(jobs),
+            cpu_percent: self
+                .used_cpu_percent
+                .map(i32::try_from)
+                .transpose()
+                .map_err(Error::UsedCpuPercent)?,
         })
     }
 }
@@ -0,0 +1,144 @@
+//! Mark responses from v1 methods that have a v2 successor as deprecated.
+//!
+//! Clients see a `deprecation` header naming the successor in a `link`
+//! header, plus a `sunset` header once a removal date is configured.
+
+use std::task::{Context, Poll};
+
+use chrono::NaiveDate;
+use futures::future::BoxFuture;
+use hyper::header::{HeaderName, HeaderValue, LINK};
+use hyper::{Request, Response};
+use tower::{Layer, Service};
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// The v1 methods replaced by a v2 method, keyed by their full request path.
+///
+/// Only list methods here once their v2 replacement is implemented.
+const SUCCESSORS: &[(&str, &str)] = &[
+    (
+        "/blockjoy.v1.NodeService/Get",
+        "/blockjoy.v2.NodeService/Get",
+    ),
+    (
+        "/blockjoy.v1.NodeService/List",
+        "/blockjoy.v2.NodeService/List",
+    ),
+];
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeprecationLayer {
+    sunset: Option<NaiveDate>,
+}
+
+impl DeprecationLayer {
+    pub const fn new(sunset: Option<NaiveDate>) -> Self {
+        DeprecationLayer { sunset }
+    }
+}
+
+impl<S> Layer<S> for DeprecationLayer {
+    type Service = DeprecationService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        DeprecationService {
+            service,
+            sunset: self.sunset,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeprecationService<S> {
+    service: S,
+    sunset: Option<NaiveDate>,
+}
+
+impl<B, R, S> Service<Request<B>> for DeprecationService<S>
+where
+    S: Service<Request<B>, Response = Response<R>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let service = self.service.clone();
+        let mut service = std::mem::replace(&mut self.service, service);
+
+        let successor = successor(request.uri().path());
+        let sunset = self.sunset;
+
+        Box::pin(async move {
+            let mut response = service.call(request).await?;
+            if let Some(successor) = successor {
+                let headers = response.headers_mut();
+                headers.insert(DEPRECATION, HeaderValue::from_static("true"));
+                if let Ok(link) = format!("<{successor}>; rel=\"successor-version\"").parse() {
+                    headers.insert(LINK, link);
+                }
+                if let Some(sunset) = sunset.and_then(|date| http_date(date).parse().ok()) {
+                    headers.insert(SUNSET, sunset);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// The v2 method replacing the v1 method at `path`.
+fn successor(path: &str) -> Option<&'static str> {
+    SUCCESSORS
+        .iter()
+        .find(|(method, _)| path == *method)
+        .map(|(_, successor)| *successor)
+}
+
+/// Format a date as an RFC 7231 HTTP-date, as used by the `sunset` header.
+fn http_date(date: NaiveDate) -> String {
+    date.format("%a, %d %b %Y 00:00:00 GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_methods_with_a_successor_are_deprecated() {
+        let path = "/blockjoy.v1.NodeService/List";
+        assert_eq!(successor(path), Some("/blockjoy.v2.NodeService/List"));
+        assert_eq!(successor("/blockjoy.v2.NodeService/List"), None);
+        assert_eq!(successor("/blockjoy.v1.HostService/List"), None);
+
+        let date = NaiveDate::from_ymd_opt(2025, 12, 1).unwrap();
+        assert_eq!(http_date(date), "Mon, 01 Dec 2025 00:00:00 GMT");
+    }
+
+    async fn deprecation_header(path: &str) -> Option<HeaderValue> {
+        let inner = tower::service_fn(|_: Request<()>| async {
+            Ok::<_, std::convert::Infallible>(Response::new(()))
+        });
+        let mut service = DeprecationLayer::new(None).layer(inner);
+        let request = Request::builder().uri(path).body(()).unwrap();
+        let response = service.call(request).await.unwrap();
+        response.headers().get(DEPRECATION).cloned()
+    }
+
+    #[tokio::test]
+    async fn v1_methods_without_a_successor_are_not_deprecated() {
+        let path = "/blockjoy.v1.NodeService/List";
+        assert!(deprecation_header(path).await.is_some());
+
+        for method in ["Create", "Delete", "ReportStatus", "Watch"] {
+            let path = format!("/blockjoy.v1.NodeService/{method}");
+            assert!(deprecation_header(&path).await.is_none(), "{path}");
+        }
+    }
+}
@@ -1,15 +1,39 @@
+pub mod deprecation;
+pub use deprecation::DeprecationLayer;
+
+pub mod payload_audit;
+pub use payload_audit::{PayloadAuditLayer, PayloadSample};
+
+pub mod request_id;
+pub use request_id::RequestIdLayer;
+
 use std::task::{Context, Poll};
 
 use futures::future::BoxFuture;
 use hyper::body::Body;
 use hyper::{Request, Response};
-use opentelemetry::trace::{FutureExt, Status, TraceContextExt, Tracer};
+use opentelemetry::trace::{FutureExt, Span, Status, TraceContextExt, Tracer};
 use opentelemetry::{KeyValue, global};
 use opentelemetry_semantic_conventions::trace::{HTTP_RESPONSE_STATUS_CODE, RPC_GRPC_STATUS_CODE};
 use tonic::Code;
 use tonic::body::BoxBody;
 use tower::{Layer, Service};
 
+use crate::util::trace;
+
+/// The gRPC method being served, attached to the context of each request.
+#[derive(Clone, Debug)]
+pub struct RpcName(pub String);
+
+impl RpcName {
+    /// The method served by the current request, if any.
+    pub fn current() -> Option<String> {
+        opentelemetry::Context::current()
+            .get::<Self>()
+            .map(|rpc| rpc.0.clone())
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 pub struct MetricsLayer;
 
@@ -50,8 +74,14 @@ where
         let path = request.uri().path();
         let path = path.strip_prefix('/').unwrap_or(path).to_string();
 
-        let span = tracer.start(path);
-        let ctx = opentelemetry::Context::current_with_span(span);
+        // continue the trace of callers that send a `traceparent` header
+        let parent = trace::extract(request.headers());
+        let mut span = tracer.start_with_context(path.clone(), &parent);
+        if let Some(id) = request.headers().get(&request_id::REQUEST_ID_HEADER) {
+            let id = id.to_str().unwrap_or_default().to_string();
+            span.set_attribute(KeyValue::new("request.id", id));
+        }
+        let ctx = parent.with_span(span).with_value(RpcName(path));
 
         Box::pin(async move {
             match service.call(request).with_context(ctx.clone()).await {
@@ -0,0 +1,153 @@
+//! Identify each request by its `x-request-id` for support debugging.
+//!
+//! An incoming `x-request-id` is kept as is, otherwise a new one is generated.
+//! The id is added to the request metadata for handlers, recorded on the
+//! tracing span of the request, and returned in the response metadata.
+//!
+//! Failed requests also store their outcome as a `RequestLog` so that support
+//! can look them up by the id a customer reports.
+
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{Request, Response};
+use tonic::Code;
+use tower::{Layer, Service};
+use tracing::{Instrument, info_span, warn};
+use uuid::Uuid;
+
+use crate::config::Context;
+use crate::database::Database;
+use crate::model::request_log::NewRequestLog;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The longest client-provided request id that is kept.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+#[derive(Clone)]
+pub struct RequestIdLayer {
+    context: Arc<Context>,
+}
+
+impl RequestIdLayer {
+    pub const fn new(context: Arc<Context>) -> Self {
+        RequestIdLayer { context }
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RequestIdService {
+            service,
+            context: self.context.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestIdService<S> {
+    service: S,
+    context: Arc<Context>,
+}
+
+impl<B, R, S> Service<Request<B>> for RequestIdService<S>
+where
+    B: Send + 'static,
+    S: Service<Request<B>, Response = Response<R>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let service = self.service.clone();
+        let mut service = std::mem::replace(&mut self.service, service);
+
+        let request_id = request_id(request.headers().get(&REQUEST_ID_HEADER));
+        request
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER, request_id.clone());
+
+        let method = request.uri().path().to_string();
+        let id = request_id.to_str().unwrap_or_default().to_string();
+        let span = info_span!("request", request_id = %id);
+        let context = self.context.clone();
+
+        Box::pin(
+            async move {
+                let started = Instant::now();
+                let mut response = service.call(request).await?;
+                response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+
+                if let Some(status) = tonic::Status::from_header_map(response.headers()) {
+                    if status.code() != Code::Ok {
+                        let log = NewRequestLog {
+                            request_id: id,
+                            method,
+                            grpc_code: status.code() as i32,
+                            message: Some(status.message().to_string()),
+                            duration_ms: i64::try_from(started.elapsed().as_millis())
+                                .unwrap_or(i64::MAX),
+                        };
+                        tokio::spawn(record(context, log));
+                    }
+                }
+
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Keep a valid incoming request id, or generate a new one.
+fn request_id(incoming: Option<&HeaderValue>) -> HeaderValue {
+    incoming
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.to_str().is_ok())
+        .cloned()
+        .unwrap_or_else(|| {
+            let id = Uuid::new_v4().to_string();
+            HeaderValue::from_str(&id).expect("uuid is a valid header")
+        })
+}
+
+async fn record(context: Arc<Context>, log: NewRequestLog) {
+    match context.conn().await {
+        Ok(mut conn) => {
+            if let Err(err) = log.create(&mut conn).await {
+                warn!("Failed to record request outcome: {err}");
+            }
+        }
+        Err(err) => warn!("Failed to record request outcome: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incoming_request_ids_are_kept_when_valid() {
+        let incoming = HeaderValue::from_static("abc-123");
+        assert_eq!(request_id(Some(&incoming)), "abc-123");
+
+        let empty = HeaderValue::from_static("");
+        let generated = request_id(Some(&empty));
+        assert!(Uuid::try_parse(generated.to_str().unwrap()).is_ok());
+
+        let long = HeaderValue::from_str(&"a".repeat(MAX_REQUEST_ID_LEN + 1)).unwrap();
+        assert_ne!(request_id(Some(&long)), long);
+    }
+}
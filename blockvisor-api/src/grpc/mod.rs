@@ -1,27 +1,67 @@
+pub mod abuse;
+pub mod admin;
+pub mod alert;
 pub mod api_key;
+pub mod approval;
 pub mod archive;
+pub mod artifact;
 pub mod auth;
+pub mod billing;
 pub mod bundle;
+pub mod codec;
 pub mod command;
 pub mod crypt;
 pub mod discovery;
+pub mod endpoint;
+#[cfg(any(test, feature = "integration-test"))]
+pub mod fixture;
 pub mod host;
 pub mod image;
+pub mod incident;
+pub mod internal_admin;
 pub mod invitation;
+pub mod managed_host;
+pub mod mesh;
 pub mod metrics;
 pub mod middleware;
 pub mod node;
+pub mod node_share;
 pub mod org;
+pub mod org_branding;
+pub mod org_key;
+pub mod org_log_sink;
+pub mod partner;
+pub mod pool;
 pub mod protocol;
+pub mod relations;
+pub mod report;
+pub mod status_page;
+pub mod team;
+pub mod ticket;
+pub mod update_mask;
 pub mod user;
+pub mod v2;
+pub mod validate;
 
 const MAX_ARCHIVE_MESSAGE_SIZE: usize = 150 * 1024 * 1024;
+const USER_AGENT_HEADER: &str = "user-agent";
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
 
 #[allow(clippy::nursery, clippy::pedantic)]
 pub mod api {
     tonic::include_proto!("blockjoy.v1");
 }
 
+#[allow(clippy::nursery, clippy::pedantic)]
+pub mod api_v2 {
+    tonic::include_proto!("blockjoy.v2");
+}
+
+/// Resolves the v1 messages that v2 messages refer to.
+mod v1 {
+    pub use super::api::*;
+}
+
 #[allow(clippy::nursery, clippy::pedantic)]
 pub mod common {
     tonic::include_proto!("blockjoy.common.v1");
@@ -41,27 +81,54 @@ use tonic::codec::CompressionEncoding;
 use tonic::metadata::AsciiMetadataValue;
 use tonic::transport::Server;
 use tonic::transport::server::Router;
+use tonic_types::{ErrorDetails, StatusExt};
 use tower_http::cors::{self, CorsLayer};
 use tower_http::trace::TraceLayer;
 
 use crate::config::Context;
 
+use self::api::abuse_service_server::AbuseServiceServer;
+use self::api::admin_service_server::AdminServiceServer;
+use self::api::alert_service_server::AlertServiceServer;
 use self::api::api_key_service_server::ApiKeyServiceServer;
+use self::api::approval_service_server::ApprovalServiceServer;
 use self::api::archive_service_server::ArchiveServiceServer;
+use self::api::artifact_service_server::ArtifactServiceServer;
 use self::api::auth_service_server::AuthServiceServer;
+use self::api::billing_service_server::BillingServiceServer;
 use self::api::bundle_service_server::BundleServiceServer;
 use self::api::command_service_server::CommandServiceServer;
 use self::api::crypt_service_server::CryptServiceServer;
 use self::api::discovery_service_server::DiscoveryServiceServer;
+use self::api::endpoint_service_server::EndpointServiceServer;
+#[cfg(any(test, feature = "integration-test"))]
+use self::api::fixture_service_server::FixtureServiceServer;
 use self::api::host_service_server::HostServiceServer;
 use self::api::image_service_server::ImageServiceServer;
+use self::api::incident_service_server::IncidentServiceServer;
+use self::api::internal_admin_service_server::InternalAdminServiceServer;
 use self::api::invitation_service_server::InvitationServiceServer;
+use self::api::managed_host_service_server::ManagedHostServiceServer;
+use self::api::mesh_service_server::MeshServiceServer;
 use self::api::metrics_service_server::MetricsServiceServer;
 use self::api::node_service_server::NodeServiceServer;
+use self::api::node_share_service_server::NodeShareServiceServer;
+use self::api::org_branding_service_server::OrgBrandingServiceServer;
+use self::api::org_key_service_server::OrgKeyServiceServer;
+use self::api::org_log_sink_service_server::OrgLogSinkServiceServer;
 use self::api::org_service_server::OrgServiceServer;
+use self::api::partner_service_server::PartnerServiceServer;
+use self::api::pool_service_server::PoolServiceServer;
 use self::api::protocol_service_server::ProtocolServiceServer;
+use self::api::report_service_server::ReportServiceServer;
+use self::api::status_page_service_server::StatusPageServiceServer;
+use self::api::team_service_server::TeamServiceServer;
+use self::api::ticket_service_server::TicketServiceServer;
 use self::api::user_service_server::UserServiceServer;
-use self::middleware::MetricsLayer;
+use self::api_v2::node_service_server::NodeServiceServer as NodeServiceV2Server;
+use self::middleware::request_id::REQUEST_ID_HEADER;
+use self::middleware::{DeprecationLayer, MetricsLayer, PayloadAuditLayer, RequestIdLayer};
+use self::validate::FieldViolation;
 
 #[derive(Clone, Deref)]
 struct Grpc {
@@ -76,6 +143,7 @@ impl Grpc {
 }
 
 /// Metadata from gRPC or HTTP request headers.
+#[derive(Clone)]
 pub struct Metadata {
     headers: axum::http::HeaderMap,
 }
@@ -100,6 +168,28 @@ impl Metadata {
     pub fn get_http(&self, k: &str) -> Option<&HeaderValue> {
         self.headers.get(k)
     }
+
+    /// The `user-agent` of the client making the request.
+    pub fn user_agent(&self) -> Option<String> {
+        self.get_http(USER_AGENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+    }
+
+    /// The client IP address, as forwarded by the load balancer.
+    pub fn client_ip(&self) -> Option<String> {
+        self.get_http(FORWARDED_FOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty())
+    }
+
+    /// The id of this request, as set by the `RequestIdLayer`.
+    pub fn request_id(&self) -> Option<&str> {
+        self.get_http(REQUEST_ID_HEADER.as_str())
+            .and_then(|value| value.to_str().ok())
+    }
 }
 
 impl Default for Metadata {
@@ -128,89 +218,230 @@ impl From<axum::http::header::HeaderMap> for Metadata {
     }
 }
 
+/// The `google.rpc.ErrorInfo` domain of errors returned by this service.
+const ERROR_DOMAIN: &str = "api.blockjoy.com";
+
 /// Response status codes returned from both gRPC and http handlers.
-pub(crate) enum Status {
-    NotFound(Cow<'static, str>),
-    AlreadyExists(Cow<'static, str>),
-    Forbidden(Cow<'static, str>),
-    Unauthorized(Cow<'static, str>),
-    FailedPrecondition(Cow<'static, str>),
-    InvalidArgument(Cow<'static, str>),
-    UnparseableRequest(Cow<'static, str>),
-    OutOfRange(Cow<'static, str>),
-    Internal(Cow<'static, str>),
+///
+/// Each status is sent with a `google.rpc.ErrorInfo` detail containing a
+/// stable `reason` code and whether the request may be retried, so that
+/// clients don't have to parse the English message. Invalid fields are also
+/// sent as a `google.rpc.BadRequest` detail.
+pub(crate) struct Status {
+    code: Code,
+    message: Cow<'static, str>,
+    reason: Option<&'static str>,
+    retryable: Option<bool>,
+    violations: Vec<FieldViolation>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Code {
+    NotFound,
+    AlreadyExists,
+    Forbidden,
+    Unauthorized,
+    FailedPrecondition,
+    InvalidArgument,
+    UnparseableRequest,
+    OutOfRange,
+    Unavailable,
+    Internal,
+}
+
+impl Code {
+    const fn grpc(self) -> tonic::Code {
+        match self {
+            Code::NotFound => tonic::Code::NotFound,
+            Code::AlreadyExists => tonic::Code::AlreadyExists,
+            Code::Forbidden => tonic::Code::PermissionDenied,
+            Code::Unauthorized => tonic::Code::Unauthenticated,
+            Code::FailedPrecondition => tonic::Code::FailedPrecondition,
+            Code::InvalidArgument | Code::UnparseableRequest => tonic::Code::InvalidArgument,
+            Code::OutOfRange => tonic::Code::OutOfRange,
+            Code::Unavailable => tonic::Code::Unavailable,
+            Code::Internal => tonic::Code::Internal,
+        }
+    }
+
+    const fn http(self) -> hyper::StatusCode {
+        match self {
+            Code::NotFound => hyper::StatusCode::NOT_FOUND,
+            Code::AlreadyExists => hyper::StatusCode::CONFLICT,
+            Code::Forbidden => hyper::StatusCode::FORBIDDEN,
+            Code::Unauthorized => hyper::StatusCode::UNAUTHORIZED,
+            Code::FailedPrecondition => hyper::StatusCode::PRECONDITION_FAILED,
+            Code::InvalidArgument => hyper::StatusCode::BAD_REQUEST,
+            Code::UnparseableRequest => hyper::StatusCode::UNPROCESSABLE_ENTITY,
+            Code::OutOfRange => hyper::StatusCode::RANGE_NOT_SATISFIABLE,
+            Code::Unavailable => hyper::StatusCode::SERVICE_UNAVAILABLE,
+            Code::Internal => hyper::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The reason code used when the error doesn't set a more specific one.
+    const fn reason(self) -> &'static str {
+        match self {
+            Code::NotFound => "NOT_FOUND",
+            Code::AlreadyExists => "ALREADY_EXISTS",
+            Code::Forbidden => "PERMISSION_DENIED",
+            Code::Unauthorized => "UNAUTHENTICATED",
+            Code::FailedPrecondition => "FAILED_PRECONDITION",
+            Code::InvalidArgument => "INVALID_ARGUMENT",
+            Code::UnparseableRequest => "UNPARSEABLE_REQUEST",
+            Code::OutOfRange => "OUT_OF_RANGE",
+            Code::Unavailable => "UNAVAILABLE",
+            Code::Internal => "INTERNAL",
+        }
+    }
+
+    /// Whether the same request may succeed when sent again, unless the
+    /// error says otherwise.
+    ///
+    /// Internal errors are mostly permanent (such as missing config or a
+    /// failed decode), so only the transient ones are marked as retryable.
+    const fn retryable(self) -> bool {
+        matches!(self, Code::Unavailable)
+    }
 }
 
 impl Status {
+    fn new(code: Code, message: impl Into<Cow<'static, str>>) -> Self {
+        Status {
+            code,
+            message: message.into(),
+            reason: None,
+            retryable: None,
+            violations: vec![],
+        }
+    }
+
     pub fn not_found(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::NotFound(message.into())
+        Self::new(Code::NotFound, message)
     }
 
     pub fn already_exists(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::AlreadyExists(message.into())
+        Self::new(Code::AlreadyExists, message)
     }
 
     pub fn forbidden(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::Forbidden(message.into())
+        Self::new(Code::Forbidden, message)
     }
 
     pub fn unauthorized(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::Unauthorized(message.into())
+        Self::new(Code::Unauthorized, message)
     }
 
     pub fn failed_precondition(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::FailedPrecondition(message.into())
+        Self::new(Code::FailedPrecondition, message)
     }
 
+    /// An invalid argument, where `message` is usually the offending field.
+    ///
+    /// Messages that look like a field name are also sent as a field
+    /// violation.
     pub fn invalid_argument(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::InvalidArgument(message.into())
+        let mut status = Self::new(Code::InvalidArgument, message);
+        if is_field_name(&status.message) {
+            status.violations.push(FieldViolation {
+                field: status.message.to_string(),
+                description: "Invalid value.".to_string(),
+            });
+        }
+        status
+    }
+
+    pub fn bad_request(violations: Vec<FieldViolation>) -> Self {
+        let mut status = Self::new(Code::InvalidArgument, "Invalid request fields.");
+        status.violations = violations;
+        status.with_reason("INVALID_FIELDS")
     }
 
     pub fn unparseable_request(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::UnparseableRequest(message.into())
+        Self::new(Code::UnparseableRequest, message)
     }
 
     pub fn out_of_range(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::OutOfRange(message.into())
+        Self::new(Code::OutOfRange, message)
+    }
+
+    /// A temporary failure, such as the database being too busy to serve the
+    /// request.
+    pub fn unavailable(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(Code::Unavailable, message)
     }
 
     pub fn internal(message: impl Into<Cow<'static, str>>) -> Self {
-        Self::Internal(message.into())
+        Self::new(Code::Internal, message)
+    }
+
+    /// Set a machine-readable reason code in `SCREAMING_SNAKE_CASE`.
+    pub const fn with_reason(mut self, reason: &'static str) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    /// Set whether the same request may succeed when sent again, for errors
+    /// that are transient regardless of their code.
+    pub const fn with_retryable(mut self, retryable: bool) -> Self {
+        self.retryable = Some(retryable);
+        self
+    }
+
+    fn reason(&self) -> &'static str {
+        self.reason.unwrap_or_else(|| self.code.reason())
+    }
+
+    fn retryable(&self) -> bool {
+        self.retryable.unwrap_or_else(|| self.code.retryable())
     }
 
     fn error_grpc(self) -> tonic::Status {
-        use Status::*;
-        match self {
-            NotFound(message) => tonic::Status::not_found(message.into_owned()),
-            AlreadyExists(message) => tonic::Status::already_exists(message.into_owned()),
-            Forbidden(message) => tonic::Status::permission_denied(message.into_owned()),
-            Unauthorized(message) => tonic::Status::unauthenticated(message.into_owned()),
-            FailedPrecondition(message) => tonic::Status::failed_precondition(message.into_owned()),
-            InvalidArgument(message) => tonic::Status::invalid_argument(message.into_owned()),
-            UnparseableRequest(message) => tonic::Status::invalid_argument(message.into_owned()),
-            OutOfRange(message) => tonic::Status::out_of_range(message.into_owned()),
-            Internal(message) => tonic::Status::internal(message.into_owned()),
+        let retryable = self.retryable();
+        let metadata = [("retryable".to_string(), retryable.to_string())].into();
+
+        let mut details = ErrorDetails::new();
+        details.set_error_info(self.reason(), ERROR_DOMAIN, metadata);
+        if !self.violations.is_empty() {
+            let violations = self
+                .violations
+                .into_iter()
+                .map(|v| tonic_types::FieldViolation::new(v.field, v.description))
+                .collect::<Vec<_>>();
+            details.set_bad_request(violations);
         }
+
+        tonic::Status::with_error_details(self.code.grpc(), self.message, details)
     }
 
     fn error_http(self) -> (hyper::StatusCode, serde_json::Value) {
-        use Status::*;
-        let body =
-            |message: Cow<'static, str>| serde_json::json!({"message": message.into_owned()});
-        match self {
-            NotFound(message) => (hyper::StatusCode::NOT_FOUND, body(message)),
-            AlreadyExists(message) => (hyper::StatusCode::CONFLICT, body(message)),
-            Forbidden(message) => (hyper::StatusCode::FORBIDDEN, body(message)),
-            Unauthorized(message) => (hyper::StatusCode::UNAUTHORIZED, body(message)),
-            FailedPrecondition(message) => (hyper::StatusCode::PRECONDITION_FAILED, body(message)),
-            InvalidArgument(message) => (hyper::StatusCode::BAD_REQUEST, body(message)),
-            UnparseableRequest(message) => (hyper::StatusCode::UNPROCESSABLE_ENTITY, body(message)),
-            OutOfRange(message) => (hyper::StatusCode::RANGE_NOT_SATISFIABLE, body(message)),
-            Internal(message) => (hyper::StatusCode::INTERNAL_SERVER_ERROR, body(message)),
+        let mut body = serde_json::json!({
+            "message": self.message,
+            "reason": self.reason(),
+            "domain": ERROR_DOMAIN,
+            "retryable": self.retryable(),
+        });
+        if !self.violations.is_empty() {
+            let violations = self
+                .violations
+                .into_iter()
+                .map(|v| serde_json::json!({"field": v.field, "description": v.description}))
+                .collect::<Vec<_>>();
+            body["violations"] = violations.into();
         }
+
+        (self.code.http(), body)
     }
 }
 
+/// Whether an invalid argument message names a request field, like `org_id`.
+fn is_field_name(message: &str) -> bool {
+    !message.is_empty()
+        && message
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '.')
+}
+
 impl From<Status> for tonic::Status {
     fn from(value: Status) -> Self {
         value.error_grpc()
@@ -257,33 +488,79 @@ pub fn server(context: &Arc<Context>) -> Router<impl Clone> {
         .allow_origin(cors::Any);
 
     let middleware = tower::ServiceBuilder::new()
+        .layer(RequestIdLayer::new(context.clone()))
         .layer(TraceLayer::new_for_grpc())
         .layer(MetricsLayer)
+        .layer(DeprecationLayer::new(context.config.grpc.v1_sunset))
+        .layer(PayloadAuditLayer::new(context.clone()))
         .layer(Extension(context.pool.clone()))
         .layer(cors_rules)
         .into_inner();
 
-    Server::builder()
+    let router = Server::builder()
         .layer(middleware)
         .concurrency_limit_per_connection(context.config.grpc.request_concurrency_limit)
+        .add_service(gzip_service!(AbuseServiceServer, grpc.clone()))
+        .add_service(gzip_service!(AdminServiceServer, grpc.clone()))
+        .add_service(gzip_service!(AlertServiceServer, grpc.clone()))
         .add_service(gzip_service!(ApiKeyServiceServer, grpc.clone()))
+        .add_service(gzip_service!(ApprovalServiceServer, grpc.clone()))
         .add_service(
             ArchiveServiceServer::new(grpc.clone())
                 .accept_compressed(CompressionEncoding::Gzip)
                 .send_compressed(CompressionEncoding::Gzip)
                 .max_decoding_message_size(MAX_ARCHIVE_MESSAGE_SIZE),
         )
+        .add_service(gzip_service!(ArtifactServiceServer, grpc.clone()))
         .add_service(gzip_service!(AuthServiceServer, grpc.clone()))
+        .add_service(gzip_service!(BillingServiceServer, grpc.clone()))
         .add_service(gzip_service!(BundleServiceServer, grpc.clone()))
         .add_service(gzip_service!(CommandServiceServer, grpc.clone()))
         .add_service(gzip_service!(CryptServiceServer, grpc.clone()))
         .add_service(gzip_service!(DiscoveryServiceServer, grpc.clone()))
+        .add_service(gzip_service!(EndpointServiceServer, grpc.clone()))
         .add_service(gzip_service!(HostServiceServer, grpc.clone()))
         .add_service(gzip_service!(ImageServiceServer, grpc.clone()))
+        .add_service(gzip_service!(IncidentServiceServer, grpc.clone()))
+        .add_service(gzip_service!(InternalAdminServiceServer, grpc.clone()))
         .add_service(gzip_service!(InvitationServiceServer, grpc.clone()))
+        .add_service(gzip_service!(ManagedHostServiceServer, grpc.clone()))
+        .add_service(gzip_service!(MeshServiceServer, grpc.clone()))
         .add_service(gzip_service!(MetricsServiceServer, grpc.clone()))
         .add_service(gzip_service!(NodeServiceServer, grpc.clone()))
+        .add_service(gzip_service!(NodeShareServiceServer, grpc.clone()))
         .add_service(gzip_service!(OrgServiceServer, grpc.clone()))
+        .add_service(gzip_service!(OrgBrandingServiceServer, grpc.clone()))
+        .add_service(gzip_service!(OrgKeyServiceServer, grpc.clone()))
+        .add_service(gzip_service!(OrgLogSinkServiceServer, grpc.clone()))
+        .add_service(gzip_service!(PartnerServiceServer, grpc.clone()))
+        .add_service(gzip_service!(PoolServiceServer, grpc.clone()))
         .add_service(gzip_service!(ProtocolServiceServer, grpc.clone()))
-        .add_service(gzip_service!(UserServiceServer, grpc))
+        .add_service(gzip_service!(ReportServiceServer, grpc.clone()))
+        .add_service(gzip_service!(StatusPageServiceServer, grpc.clone()))
+        .add_service(gzip_service!(TeamServiceServer, grpc.clone()))
+        .add_service(gzip_service!(TicketServiceServer, grpc.clone()))
+        .add_service(gzip_service!(UserServiceServer, grpc.clone()));
+
+    #[cfg(any(test, feature = "integration-test"))]
+    let router = router.add_service(gzip_service!(FixtureServiceServer, grpc.clone()));
+
+    router.add_service(gzip_service!(NodeServiceV2Server, grpc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_transient_errors_are_retryable() {
+        assert!(!Status::internal("Internal error.").retryable());
+        assert!(!Status::not_found("Node not found.").retryable());
+        assert!(Status::unavailable("Server is shutting down.").retryable());
+
+        let conflict = Status::internal("Internal error.").with_retryable(true);
+        assert!(conflict.retryable());
+        let body = conflict.error_http().1;
+        assert_eq!(body["retryable"], true);
+    }
 }
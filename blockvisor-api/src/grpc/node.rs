@@ -1,81 +1,162 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Arc;
 
-use diesel::result::Error::NotFound;
+use chrono::Utc;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
+use futures_util::Stream;
+use prost::Message;
 use thiserror::Error;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response};
-use tracing::error;
+use tracing::{error, warn};
 
 use crate::auth::rbac::{CryptPerm, NodeAdminPerm, NodePerm, Perm};
-use crate::auth::resource::{NodeId, OrgId, Resource};
+use crate::auth::resource::{HostId, NodeId, OrgId, Resource};
 use crate::auth::{AuthZ, Authorize};
-use crate::database::{Conn, ReadConn, Transaction, WriteConn};
+use crate::config::Context;
+use crate::database::{Conn, Database, ReadConn, Transaction, WriteConn};
+use crate::model::approval::{Approval, ApprovalAction};
+use crate::model::backorder::{Backorder, BackorderId, NewBackorder};
 use crate::model::command::NewCommand;
-use crate::model::image::ConfigId;
 use crate::model::image::config::{Config, ConfigType, NewConfig, NodeConfig};
+use crate::model::image::{ConfigId, ImageId};
+use crate::model::incident::NewIncident;
+use crate::model::mesh::MeshPeer;
+use crate::model::node::bulk::{self, MAX_BULK_NODES};
 use crate::model::node::{
-    HostCount, Launch, NewNode, NextState, Node, NodeFilter, NodeReport, NodeSearch, NodeSort,
-    NodeState, NodeStatus, RegionCount, UpdateNode, UpdateNodeConfig, UpdateNodeState,
+    BulkUpdate, DiskResize, DiskResizeId, DiskResizeStatus, DnsAliasType, Error as NodeError,
+    HostCount, Launch, LogEvent, NameTemplate, NewDiskResize, NewNode, NewNodeDnsAlias, NewNodeLog,
+    NextState, Node, NodeCertificate, NodeDnsAlias, NodeFilter, NodeReport, NodeResources,
+    NodeSearch, NodeSort, NodeState, NodeStatus, PlacementPolicy, RegionCount, StateRejected,
+    UpdateNode, UpdateNodeConfig, UpdateNodeState,
 };
-use crate::model::protocol::ProtocolVersion;
+use crate::model::plan::Entitlements;
+use crate::model::project::{Project, ProjectId};
+use crate::model::protocol::{NetworkKind, ProtocolVersion};
 use crate::model::sql::Tag;
-use crate::model::{CommandType, Host, Image, Org, Protocol, Region};
-use crate::util::{HashVec, NanosUtc};
+use crate::model::{
+    CommandType, FreezeWindow, Host, HostPool, HostPoolId, Image, LegalHold, Org, Protocol, Region,
+};
+use crate::stripe::Subscription;
+use crate::util::NanosUtc;
 
 use super::api::node_service_server::NodeService;
-use super::command::node_update;
+use super::approval;
+use super::command::{node_resize, node_update};
 use super::common::node_launcher;
+use super::relations::NodeRelations;
+use super::update_mask::UpdateMask;
+use super::validate::{Validate, Validator};
 use super::{Grpc, Metadata, Status, api, common};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Node alias error: {0}
+    Alias(#[from] crate::model::node::alias::Error),
     /// Node amount error: {0}
     Amount(#[from] crate::model::sql::amount::Error),
+    /// Node approval error: {0}
+    Approval(#[from] crate::grpc::approval::Error),
+    /// The approved nodes are no longer in org `{0}`.
+    ApprovalOrg(OrgId),
     /// Auth check failed: {0}
     Auth(#[from] crate::auth::Error),
     /// Auth token parsing failed: {0}
     AuthToken(#[from] crate::auth::token::Error),
+    /// Node backorder error: {0}
+    Backorder(#[from] crate::model::backorder::Error),
+    /// Only users may backorder nodes.
+    BackorderUser,
     /// Failed to parse block age: {0}
     BlockAge(std::num::TryFromIntError),
     /// Failed to parse block height: {0}
     BlockHeight(std::num::TryFromIntError),
+    /// Node bulk update error: {0}
+    Bulk(#[from] crate::model::node::bulk::Error),
+    /// Failed to parse cpu cores: {0}
+    CpuCores(std::num::TryFromIntError),
+    /// Node certificate error: {0}
+    Certificate(#[from] crate::model::node::certificate::Error),
     /// Claims check failed: {0}
     Claims(#[from] crate::auth::claims::Error),
+    /// Node cloudflare error: {0}
+    Cloudflare(#[from] crate::cloudflare::Error),
     /// Node command error: {0}
     Command(#[from] crate::model::command::Error),
     /// Node grpc command error: {0}
     CommandGrpc(#[from] crate::grpc::command::Error),
+    /// Node database error: {0}
+    Database(#[from] crate::database::Error),
+    /// Failed to decode the request of backorder `{0}`: {1}
+    DecodeBackorder(BackorderId, prost::DecodeError),
     /// Diesel failure: {0}
     Diesel(#[from] diesel::result::Error),
+    /// Failed to parse disk bytes: {0}
+    DiskBytes(std::num::TryFromIntError),
+    /// Node disk resize error: {0}
+    DiskResize(#[from] crate::model::node::resize::Error),
     /// Failed to parse filter limit as i64: {0}
     FilterLimit(std::num::TryFromIntError),
     /// Failed to parse filter offset as i64: {0}
     FilterOffset(std::num::TryFromIntError),
+    /// Node freeze window error: {0}
+    FreezeWindow(#[from] crate::model::freeze_window::Error),
+    /// Node `{0}` has a protocol or version that is not visible.
+    HiddenProtocol(NodeId),
     /// Node host error: {0}
     Host(#[from] crate::model::host::Error),
+    /// Node host pool error: {0}
+    HostPool(#[from] crate::model::host_pool::Error),
     /// Node image error: {0}
     Image(#[from] crate::model::image::Error),
     /// Node image config error: {0}
     ImageConfig(#[from] crate::model::image::config::Error),
     /// Node image property error: {0}
     ImageProperty(#[from] crate::model::image::property::Error),
+    /// Node incident error: {0}
+    Incident(#[from] crate::model::incident::Error),
     /// Node ip address error: {0}
     IpAddress(#[from] crate::model::ip_address::Error),
     /// Node launch error: {0}
     Launch(#[from] crate::model::node::launch::Error),
+    /// Node legal hold error: {0}
+    LegalHold(#[from] crate::model::legal_hold::Error),
+    /// Node `{0}` is missing its {1}.
+    LookupMissing(NodeId, &'static str),
+    /// Failed to parse memory bytes: {0}
+    MemoryBytes(std::num::TryFromIntError),
+    /// Node mesh error: {0}
+    Mesh(#[from] crate::grpc::mesh::Error),
+    /// Node mesh peer error: {0}
+    MeshPeer(#[from] crate::model::mesh::Error),
+    /// Failed to parse min blocks behind: {0}
+    MinBlocksBehind(std::num::TryFromIntError),
+    /// Missing alias type.
+    MissingAliasType,
+    /// Missing node filter.
+    MissingFilter,
     /// No node ids given.
     MissingIds,
     /// Missing launch type.
     MissingLaunch,
     /// Missing NodeLauncher.
     MissingLauncher,
+    /// Node network error: {0}
+    Network(#[from] crate::model::protocol::network::Error),
+    /// Node network head error: {0}
+    NetworkHead(#[from] crate::model::protocol::head::Error),
     /// Node model error: {0}
     Node(#[from] crate::model::node::Error),
     /// Node model status error: {0}
     NodeStatus(#[from] crate::model::node::status::Error),
     /// No visiblity of NodeCreate command.
     NoNodeCreate,
+    /// No visiblity of NodeDebugBundle command.
+    NoNodeDebugBundle,
     /// No visiblity of NodeDelete command.
     NoNodeDelete,
     /// No visiblity of NodeRestart command.
@@ -84,10 +165,22 @@ pub enum Error {
     NoNodeStart,
     /// No visiblity of NodeStop command.
     NoNodeStop,
+    /// A resize needs new cpu cores or memory bytes.
+    NoResize,
     /// Node org error: {0}
     Org(#[from] crate::model::org::Error),
+    /// Node plan error: {0}
+    Plan(#[from] crate::model::plan::Error),
+    /// Host pool `{0}` belongs to another org.
+    PoolOrg(HostPoolId),
+    /// Node project error: {0}
+    Project(#[from] crate::model::project::Error),
+    /// Project `{0}` belongs to another org.
+    ProjectOrg(ProjectId),
     /// Failed to parse ConfigId: {0}
     ParseConfigId(uuid::Error),
+    /// Failed to parse execute_at: {0}
+    ParseExecuteAt(crate::util::timestamp::Error),
     /// Failed to parse HostId: {0}
     ParseHostId(uuid::Error),
     /// Failed to parse NodeId: {0}
@@ -96,12 +189,20 @@ pub enum Error {
     ParseImageId(uuid::Error),
     /// Failed to parse ip: {0}
     ParseIp(crate::model::sql::Error),
+    /// Failed to parse ProtocolNetworkId: {0}
+    ParseNetworkId(uuid::Error),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
+    /// Failed to parse HostPoolId: {0}
+    ParsePoolId(uuid::Error),
+    /// Failed to parse ProjectId: {0}
+    ParseProjectId(uuid::Error),
     /// Failed to parse ProtocolId: {0}
     ParseProtocolId(uuid::Error),
     /// Failed to parse RegionId: {0}
     ParseRegionId(uuid::Error),
+    /// Failed to parse DiskResizeId: {0}
+    ParseResizeId(uuid::Error),
     /// Failed to parse UserId: {0}
     ParseUserId(uuid::Error),
     /// Node protocol error: {0}
@@ -110,6 +211,10 @@ pub enum Error {
     ProtocolVersion(#[from] crate::model::protocol::version::Error),
     /// Node region error: {0}
     Region(#[from] crate::model::region::Error),
+    /// Node relations error: {0}
+    Relations(#[from] crate::grpc::relations::Error),
+    /// Node rename error: {0}
+    Rename(#[from] crate::model::node::rename::Error),
     /// Node report error: {0}
     Report(#[from] crate::model::node::report::Error),
     /// Report config id `{0}` does not match node config id `{1}`.
@@ -128,10 +233,20 @@ pub enum Error {
     Sql(#[from] crate::model::sql::Error),
     /// Node store error: {0}
     Store(#[from] crate::store::Error),
+    /// Node stripe error: {0}
+    Stripe(#[from] crate::stripe::Error),
     /// The requested sort field is unknown.
     UnknownSortField,
+    /// Nodes of org `{0}` require approval to be upgraded on their own.
+    UpgradeApprovalOrgs(OrgId),
+    /// Node update mask: {0}
+    UpdateMask(#[from] crate::grpc::update_mask::Error),
+    /// Failed to parse used disk bytes: {0}
+    UsedDiskBytes(std::num::TryFromIntError),
     /// Node user error: {0}
     User(#[from] crate::model::user::Error),
+    /// Node request validation: {0}
+    Validate(#[from] crate::grpc::validate::Error),
 }
 
 impl From<Error> for Status {
@@ -139,66 +254,132 @@ impl From<Error> for Status {
         use Error::*;
         error!("{err}");
         match err {
-            Diesel(_) | Store(_) => Status::internal("Internal error."),
+            Cloudflare(_) | DecodeBackorder(..) | Diesel(_) | LookupMissing(..) | Store(_)
+            | Stripe(_) => Status::internal("Internal error."),
+            BackorderUser => Status::failed_precondition("Only users may backorder nodes."),
             BlockAge(_) => Status::invalid_argument("block_age"),
             BlockHeight(_) => Status::invalid_argument("block_height"),
+            CpuCores(_) => Status::invalid_argument("cpu_cores"),
+            DiskBytes(_) => Status::invalid_argument("disk_bytes"),
             FilterLimit(_) => Status::invalid_argument("limit"),
             FilterOffset(_) => Status::invalid_argument("offset"),
+            MemoryBytes(_) => Status::invalid_argument("memory_bytes"),
+            MinBlocksBehind(_) => Status::invalid_argument("min_blocks_behind"),
+            MissingAliasType => Status::invalid_argument("alias_type"),
+            MissingFilter => Status::invalid_argument("filter"),
             MissingIds => Status::invalid_argument("ids"),
             MissingLaunch => Status::invalid_argument("launch"),
             MissingLauncher => Status::invalid_argument("launcher"),
-            NoNodeCreate | NoNodeDelete | NoNodeRestart | NoNodeStart | NoNodeStop => {
-                Status::forbidden("Access denied.")
-            }
+            NoNodeCreate | NoNodeDebugBundle | NoNodeDelete | NoNodeRestart | NoNodeStart
+            | NoNodeStop => Status::forbidden("Access denied."),
+            NoResize => Status::invalid_argument("cpu_cores or memory_bytes"),
             ParseConfigId(_) => Status::invalid_argument("config_id"),
+            ParseExecuteAt(_) => Status::invalid_argument("execute_at"),
             ParseHostId(_) => Status::invalid_argument("host_id"),
             ParseId(_) => Status::invalid_argument("node_id"),
             ParseImageId(_) => Status::invalid_argument("image_id"),
             ParseIp(_) => Status::invalid_argument("ip_addresses"),
+            ParseNetworkId(_) => Status::invalid_argument("network_ids"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParsePoolId(_) => Status::invalid_argument("pool_id"),
+            ParseProjectId(_) => Status::invalid_argument("project_id"),
             ParseProtocolId(_) => Status::invalid_argument("protocol_id"),
             ParseRegionId(_) => Status::invalid_argument("region_id"),
+            ParseResizeId(_) => Status::invalid_argument("resize_id"),
             ParseUserId(_) => Status::invalid_argument("user_id"),
+            HiddenProtocol(_) => Status::not_found("Not found."),
+            PoolOrg(_) => Status::not_found("Host pool not found."),
+            ProjectOrg(_) => Status::not_found("Project not found."),
             ReportConfigId(_, _) => Status::failed_precondition("config_id"),
             ReportNextState => Status::invalid_argument("status.next"),
             SearchOperator(_) => Status::invalid_argument("search.operator"),
             SortOrder(_) => Status::invalid_argument("sort.order"),
             UnknownSortField => Status::invalid_argument("sort.field"),
+            UsedDiskBytes(_) => Status::invalid_argument("used_disk_bytes"),
+            ApprovalOrg(_) => Status::failed_precondition("Nodes have moved to another org."),
+            UpgradeApprovalOrgs(_) => Status::failed_precondition(
+                "Nodes of an org that requires approval must be upgraded on their own.",
+            ),
+            Alias(err) => err.into(),
             Amount(err) => err.into(),
+            Approval(err) => err.into(),
             Auth(err) => err.into(),
+            Backorder(err) => err.into(),
+            Bulk(err) => err.into(),
+            Certificate(err) => err.into(),
             AuthToken(err) => err.into(),
             Claims(err) => err.into(),
             Command(err) => err.into(),
             CommandGrpc(err) => err.into(),
+            Database(err) => err.into(),
+            DiskResize(err) => err.into(),
+            FreezeWindow(err) => err.into(),
             Host(err) => err.into(),
+            HostPool(err) => err.into(),
             Image(err) => err.into(),
             ImageConfig(err) => err.into(),
             ImageProperty(err) => err.into(),
+            Incident(err) => err.into(),
             IpAddress(err) => err.into(),
             Launch(err) => err.into(),
+            LegalHold(err) => err.into(),
+            Mesh(err) => err.into(),
+            MeshPeer(err) => err.into(),
+            Network(err) => err.into(),
+            NetworkHead(err) => err.into(),
             Node(err) => err.into(),
             NodeStatus(err) => err.into(),
             Org(err) => err.into(),
+            Plan(err) => err.into(),
+            Project(err) => err.into(),
             Protocol(err) => err.into(),
             ProtocolVersion(err) => err.into(),
             Region(err) => err.into(),
+            Relations(err) => err.into(),
+            Rename(err) => err.into(),
             Report(err) => err.into(),
             Resource(err) => err.into(),
             Rule(err) => err.into(),
             Sql(err) => err.into(),
+            UpdateMask(err) => err.into(),
             User(err) => err.into(),
+            Validate(err) => err.into(),
         }
     }
 }
 
+/// The fields of `NodeServiceUpdateConfigRequest` that an update mask may name.
+const UPDATE_CONFIG_FIELDS: &[&str] = &[
+    "new_org_id",
+    "new_display_name",
+    "new_note",
+    "auto_upgrade",
+    "update_tags",
+    "cost",
+];
+
+/// How many watch events are buffered for a slow client.
+const WATCH_BUFFER: usize = 64;
+
+/// How many recent log lines of a node a debug bundle holds by default.
+const DEBUG_BUNDLE_LOG_LINES: u32 = 10_000;
+/// The most log lines of a node that a debug bundle may request.
+const MAX_DEBUG_BUNDLE_LOG_LINES: u32 = 100_000;
+
+/// A stream of the changes to the nodes matching a watch.
+type WatchStream =
+    Pin<Box<dyn Stream<Item = Result<api::NodeServiceWatchResponse, tonic::Status>> + Send>>;
+
 #[tonic::async_trait]
 impl NodeService for Grpc {
+    type WatchStream = WatchStream;
+
     async fn create(
         &self,
         req: Request<api::NodeServiceCreateRequest>,
     ) -> Result<Response<api::NodeServiceCreateResponse>, tonic::Status> {
         let (meta, _, req) = req.into_parts();
-        self.write(|write| create(req, meta.into(), write).scope_boxed())
+        self.retry_write(|write| create(req, meta.into(), write).scope_boxed())
             .await
     }
 
@@ -220,12 +401,37 @@ impl NodeService for Grpc {
             .await
     }
 
+    async fn bulk_update(
+        &self,
+        req: Request<api::NodeServiceBulkUpdateRequest>,
+    ) -> Result<Response<api::NodeServiceBulkUpdateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| bulk_update(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn watch(
+        &self,
+        req: Request<api::NodeServiceWatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        // subscribe before authorizing so no change after the check is missed
+        let messages = self.context.notifier.watch_nodes();
+        let watch: Response<(NodeWatch, AuthZ)> = self
+            .read(|read| watch(req, meta.into(), read).scope_boxed())
+            .await?;
+
+        let (watch, authz) = watch.into_inner();
+        let context = self.context.clone();
+        Ok(Response::new(watch.stream(authz, messages, context)))
+    }
+
     async fn report_status(
         &self,
         req: Request<api::NodeServiceReportStatusRequest>,
     ) -> Result<Response<api::NodeServiceReportStatusResponse>, tonic::Status> {
         let (meta, _, req) = req.into_parts();
-        self.write(|write| report_status(req, meta.into(), write).scope_boxed())
+        self.retry_write(|write| report_status(req, meta.into(), write).scope_boxed())
             .await
     }
 
@@ -283,12 +489,102 @@ impl NodeService for Grpc {
             .await
     }
 
+    async fn resize(
+        &self,
+        req: Request<api::NodeServiceResizeRequest>,
+    ) -> Result<Response<api::NodeServiceResizeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| resize(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn resize_disk(
+        &self,
+        req: Request<api::NodeServiceResizeDiskRequest>,
+    ) -> Result<Response<api::NodeServiceResizeDiskResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| resize_disk(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_disk_resizes(
+        &self,
+        req: Request<api::NodeServiceListDiskResizesRequest>,
+    ) -> Result<Response<api::NodeServiceListDiskResizesResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_disk_resizes(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn review_disk_resize(
+        &self,
+        req: Request<api::NodeServiceReviewDiskResizeRequest>,
+    ) -> Result<Response<api::NodeServiceReviewDiskResizeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| review_disk_resize(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn add_dns_alias(
+        &self,
+        req: Request<api::NodeServiceAddDnsAliasRequest>,
+    ) -> Result<Response<api::NodeServiceAddDnsAliasResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| add_dns_alias(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn remove_dns_alias(
+        &self,
+        req: Request<api::NodeServiceRemoveDnsAliasRequest>,
+    ) -> Result<Response<api::NodeServiceRemoveDnsAliasResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| remove_dns_alias(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn rename(
+        &self,
+        req: Request<api::NodeServiceRenameRequest>,
+    ) -> Result<Response<api::NodeServiceRenameResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| rename(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn request_certificate(
+        &self,
+        req: Request<api::NodeServiceRequestCertificateRequest>,
+    ) -> Result<Response<api::NodeServiceRequestCertificateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| request_certificate(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_certificate(
+        &self,
+        req: Request<api::NodeServiceGetCertificateRequest>,
+    ) -> Result<Response<api::NodeServiceGetCertificateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_certificate(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn collect_debug_bundle(
+        &self,
+        req: Request<api::NodeServiceCollectDebugBundleRequest>,
+    ) -> Result<Response<api::NodeServiceCollectDebugBundleResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| collect_debug_bundle(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn delete(
         &self,
         req: Request<api::NodeServiceDeleteRequest>,
     ) -> Result<Response<api::NodeServiceDeleteResponse>, tonic::Status> {
         let (meta, _, req) = req.into_parts();
-        self.write(|write| delete(req, meta.into(), write).scope_boxed())
+        self.retry_write(|write| delete(req, meta.into(), write).scope_boxed())
             .await
     }
 }
@@ -296,9 +592,34 @@ impl NodeService for Grpc {
 pub async fn create(
     req: api::NodeServiceCreateRequest,
     meta: Metadata,
+    write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceCreateResponse, Error> {
+    req.validate()?;
+    create_nodes(req, meta, None, write).await
+}
+
+/// Retry creating the nodes of a pending backorder.
+///
+/// The backorder is fulfilled along with its created nodes, or left pending
+/// and returned without any nodes while its regions are still full.
+pub async fn retry_backorder(
+    backorder: Backorder,
+    meta: Metadata,
+    write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceCreateResponse, Error> {
+    let req = api::NodeServiceCreateRequest::decode(&*backorder.request)
+        .map_err(|err| Error::DecodeBackorder(backorder.id, err))?;
+    create_nodes(req, meta, Some(backorder), write).await
+}
+
+async fn create_nodes(
+    req: api::NodeServiceCreateRequest,
+    meta: Metadata,
+    backorder: Option<Backorder>,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceCreateResponse, Error> {
     let org_id = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let backorder_req = req.backorder.then(|| req.clone());
     let mut perms = vec![Perm::from(NodePerm::Create)];
     let mut resources = vec![Resource::from(org_id)];
 
@@ -312,6 +633,13 @@ pub async fn create(
         resources.push(Resource::from(old_id));
     };
 
+    let placement = PlacementPolicy::from(req.placement_policy());
+    let pool_id = req
+        .pool_id
+        .as_ref()
+        .map(|id| id.parse().map_err(Error::ParsePoolId))
+        .transpose()?;
+
     let launch = req
         .launcher
         .ok_or(Error::MissingLauncher)?
@@ -347,11 +675,33 @@ pub async fn create(
         }
     };
 
+    if let Some(pool_id) = pool_id {
+        let pool = HostPool::by_id(pool_id, &mut write).await?;
+        if pool.org_id != org_id {
+            return Err(Error::PoolOrg(pool_id));
+        }
+    }
+
+    let project = if let Some(id) = &req.project_id {
+        let project_id = id.parse().map_err(Error::ParseProjectId)?;
+        let project = Project::by_id(project_id, &mut write).await?;
+        if project.org_id != org_id {
+            return Err(Error::ProjectOrg(project_id));
+        }
+        project.authorize(&authz, &mut write).await?;
+        Some(project)
+    } else {
+        None
+    };
+
     let image_id = req.image_id.parse().map_err(Error::ParseImageId)?;
     let image = Image::by_id(image_id, Some(org_id), &authz, &mut write).await?;
 
     let version =
         ProtocolVersion::by_id(image.protocol_version_id, Some(org_id), &authz, &mut write).await?;
+    let entitlements = Entitlements::for_org(org_id, &mut write).await?;
+    entitlements.check_awake()?;
+    entitlements.check_protocol(version.protocol_id)?;
 
     let new_values = req
         .new_values
@@ -363,7 +713,53 @@ pub async fn create(
         .into_iter()
         .map(TryFrom::try_from)
         .collect::<Result<Vec<_>, _>>()?;
-    let config = NodeConfig::new(image, Some(org_id), new_values, add_rules, &mut write).await?;
+    let (config, secrets) =
+        NodeConfig::new(image, Some(org_id), new_values, add_rules, &mut write).await?;
+
+    // preflight the launch without storing or creating anything
+    if req.validate_only {
+        let planned = launch
+            .plan(
+                org_id, placement, pool_id, &version, &config, &authz, &mut write,
+            )
+            .await?;
+        let added = i64::try_from(planned.len()).unwrap_or(i64::MAX);
+        if let Some(project) = &project {
+            project.check_added_quota(added, &mut write).await?;
+        }
+        entitlements.check_added_nodes(added, &mut write).await?;
+
+        return Ok(api::NodeServiceCreateResponse {
+            nodes: vec![],
+            planned: planned.into_iter().map(Into::into).collect(),
+            backorder: None,
+        });
+    }
+
+    // wait for capacity instead of failing to find a host
+    let full = backorder_req.is_some()
+        && !launch
+            .has_capacity(
+                org_id, placement, pool_id, &version, &config, &authz, &mut write,
+            )
+            .await?;
+    if let Some(req) = backorder_req.filter(|_| full) {
+        let backorder = match backorder {
+            Some(backorder) => backorder,
+            None => {
+                let user_id = authz.resource().user().ok_or(Error::BackorderUser)?;
+                NewBackorder::new(org_id, user_id, &req)
+                    .create(&mut write)
+                    .await?
+            }
+        };
+
+        return Ok(api::NodeServiceCreateResponse {
+            nodes: vec![],
+            planned: vec![],
+            backorder: Some(backorder.into()),
+        });
+    }
 
     let new_config = NewConfig {
         image_id,
@@ -394,11 +790,20 @@ pub async fn create(
         semantic_version: version.semantic_version,
         auto_upgrade: true,
         tags,
+        scheduler_placement: placement,
+        scheduler_pool_id: pool_id,
+        project_id: project.as_ref().map(|project| project.id),
     };
 
     let created = new_node
         .create(launch, dns_base, &authz, &mut write)
         .await?;
+    let node_ids = created.iter().map(|node| node.id).collect();
+    secrets.store_after_commit(node_ids, &mut write);
+    if let Some(project) = &project {
+        project.check_quota(&mut write).await?;
+    }
+    entitlements.check_nodes(&mut write).await?;
 
     let mut nodes = Vec::with_capacity(created.len());
     for node in created {
@@ -407,6 +812,10 @@ pub async fn create(
         let create_cmd = NewCommand::node(&node, CommandType::NodeCreate)?
             .create(&mut write)
             .await?;
+        NewCommand::node(&node, CommandType::NodeStart)?
+            .after(&create_cmd)
+            .create(&mut write)
+            .await?;
         let create_cmd = api::Command::from(&create_cmd, &authz, &mut write)
             .await?
             .ok_or(Error::NoNodeCreate)?;
@@ -419,7 +828,16 @@ pub async fn create(
         nodes.push(api_node);
     }
 
-    Ok(api::NodeServiceCreateResponse { nodes })
+    let backorder = match backorder {
+        Some(backorder) => Some(backorder.fulfil(&mut write).await?.into()),
+        None => None,
+    };
+
+    Ok(api::NodeServiceCreateResponse {
+        nodes,
+        planned: vec![],
+        backorder,
+    })
 }
 
 pub async fn get(
@@ -427,6 +845,7 @@ pub async fn get(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::NodeServiceGetResponse, Error> {
+    req.validate()?;
     let node_id = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = read
         .auth_or_for(&meta, NodeAdminPerm::Get, NodePerm::Get, node_id)
@@ -444,6 +863,7 @@ pub async fn list(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::NodeServiceListResponse, Error> {
+    req.validate()?;
     let filter = req.into_filter()?;
     let authz = if filter.org_ids.is_empty() {
         read.auth(&meta, NodeAdminPerm::List).await?
@@ -463,11 +883,144 @@ pub async fn list(
     Ok(api::NodeServiceListResponse { nodes, total })
 }
 
+/// Update every node matching the filter of `req`, returning the outcome for
+/// each node.
+///
+/// As with `list`, updating nodes without an org filter requires admin access.
+/// A node rejected by the display name or note policy of its org is reported
+/// in its result without stopping the others, but any other error fails the
+/// whole update.
+pub async fn bulk_update(
+    req: api::NodeServiceBulkUpdateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceBulkUpdateResponse, Error> {
+    req.validate()?;
+    let mut filter = req.filter.ok_or(Error::MissingFilter)?.into_filter()?;
+    filter.limit = MAX_BULK_NODES;
+    filter.offset = 0;
+
+    let authz = if filter.org_ids.is_empty() {
+        write.auth(&meta, NodeAdminPerm::BulkUpdate).await?
+    } else {
+        write
+            .auth_or_for(
+                &meta,
+                NodeAdminPerm::BulkUpdate,
+                NodePerm::BulkUpdate,
+                &filter.org_ids[..],
+            )
+            .await?
+    };
+
+    let parse_tags = |tags: Vec<String>| {
+        tags.into_iter()
+            .map(|tag| Tag::new(tag).map_err(Error::from))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let update = BulkUpdate {
+        add_tags: parse_tags(req.add_tags)?,
+        remove_tags: parse_tags(req.remove_tags)?,
+        note: req.new_note,
+        auto_upgrade: req.auto_upgrade,
+        display_name: req
+            .display_name_template
+            .as_deref()
+            .map(NameTemplate::parse)
+            .transpose()?,
+    };
+    update.check()?;
+
+    let (nodes, total) = filter.query(&mut write).await?;
+    if total > nodes.len() as u64 {
+        return Err(bulk::Error::TooManyNodes(total, MAX_BULK_NODES).into());
+    }
+
+    let mut updated = Vec::with_capacity(nodes.len());
+    let mut rejected = HashMap::new();
+    for (index, node) in nodes.iter().enumerate() {
+        match update.apply(node, index + 1, &authz, &mut write).await {
+            Ok(node) => updated.push(node),
+            Err(NodeError::Policy(err)) => {
+                rejected.insert(node.id, err.to_string());
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    if update.updates_host() {
+        for node in &updated {
+            let api_update = api::NodeUpdate {
+                node_id: node.id.to_string(),
+                config_id: node.config_id.to_string(),
+                auto_upgrade: update.auto_upgrade,
+                new_org_id: None,
+                new_org_name: None,
+                new_display_name: update
+                    .display_name
+                    .as_ref()
+                    .map(|_| node.display_name.clone()),
+                new_note: update.note.clone(),
+                new_values: vec![],
+                new_firewall: None,
+            };
+            let node_cmd = NewCommand::node(node, CommandType::NodeUpdate)?
+                .with_protobuf(&api_update)
+                .create(&mut write)
+                .await?;
+            let update_cmd = node_update(&node_cmd, &mut write).await?;
+            write.mqtt(update_cmd);
+        }
+    }
+
+    let api_nodes = api::Node::from_models(updated, &authz, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    let mut api_nodes: HashMap<_, _> = api_nodes
+        .into_iter()
+        .map(|node| (node.node_id.clone(), node))
+        .collect();
+    for node in api_nodes.values() {
+        write.mqtt(api::NodeMessage::updated(node.clone(), updated_by.clone()));
+    }
+
+    let results = nodes
+        .iter()
+        .map(|node| api::NodeBulkUpdateResult {
+            node_id: node.id.to_string(),
+            node: api_nodes.remove(&node.id.to_string()),
+            error: rejected.remove(&node.id),
+        })
+        .collect();
+
+    Ok(api::NodeServiceBulkUpdateResponse { results })
+}
+
+/// Authorize a watch of the nodes matching the filter of `req`.
+///
+/// As with `list`, watching nodes without an org filter requires admin access.
+pub async fn watch(
+    req: api::NodeServiceWatchRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<(NodeWatch, AuthZ), Error> {
+    let watch = NodeWatch::try_from(req)?;
+    let org_ids: Vec<OrgId> = watch.org_ids.iter().copied().collect();
+    let authz = if org_ids.is_empty() {
+        read.auth(&meta, NodeAdminPerm::List).await?
+    } else {
+        read.auth_or_for(&meta, NodeAdminPerm::List, NodePerm::List, &org_ids[..])
+            .await?
+    };
+
+    Ok((watch, authz))
+}
+
 pub async fn report_status(
     req: api::NodeServiceReportStatusRequest,
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceReportStatusResponse, Error> {
+    req.validate()?;
     let node_id = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = write
         .auth_or_for(
@@ -488,8 +1041,8 @@ pub async fn report_status(
         return Err(Error::ReportNextState);
     }
 
-    let update = UpdateNodeState {
-        node_state: status.as_ref().map(|status| status.state),
+    let update = |node_state| UpdateNodeState {
+        node_state,
         next_state: None,
         protocol_state: status
             .as_ref()
@@ -502,7 +1055,21 @@ pub async fn report_status(
         p2p_address: req.p2p_address.as_deref(),
     };
 
-    let node = update.apply(node_id, &mut write).await?;
+    let node_state = status.as_ref().map(|status| status.state);
+    let node = match update(node_state).apply(node_id, &mut write).await {
+        Ok(node) => node,
+        // keep the rest of the report but log the rejected state
+        Err(NodeError::InvalidTransition(_, from, to)) => {
+            warn!("Rejected state report for node {node_id} from {from:?} to {to:?}");
+            let event = LogEvent::StateRejected(StateRejected { from, to });
+            NewNodeLog::from(&node, &authz, event)
+                .create(&mut write)
+                .await
+                .map_err(NodeError::NodeLog)?;
+            update(None).apply(node_id, &mut write).await?
+        }
+        Err(err) => return Err(err.into()),
+    };
     let node = api::Node::from_model(node, &authz, &mut write).await?;
 
     let updated_by = common::Resource::from(&authz);
@@ -518,6 +1085,7 @@ pub async fn report_error(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceReportErrorResponse, Error> {
+    req.validate()?;
     let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = write
         .auth_or_for(
@@ -532,6 +1100,12 @@ pub async fn report_error(
     let resource = authz.resource();
     let report = node.report(resource, req.message, &mut write).await?;
 
+    let incident = NewIncident::from_report(&report, node.org_id)
+        .create(&mut write)
+        .await?;
+    let created_by = common::Resource::from(&authz);
+    write.mqtt(api::IncidentMessage::created(&incident, created_by));
+
     Ok(api::NodeServiceReportErrorResponse {
         report_id: report.id.to_string(),
     })
@@ -542,18 +1116,24 @@ pub async fn update_config(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceUpdateConfigResponse, Error> {
+    req.validate()?;
     let node_id = req.node_id.parse().map_err(Error::ParseId)?;
 
-    let new_org_id = req
-        .new_org_id
-        .as_deref()
+    let mask = UpdateMask::new(req.update_mask, UPDATE_CONFIG_FIELDS)?;
+    let new_org_id = mask
+        .value("new_org_id", req.new_org_id)?
         .map(|id| id.parse().map_err(Error::ParseOrgId))
         .transpose()?;
+    let new_display_name = mask.nullable("new_display_name", req.new_display_name);
+    let new_note = mask.nullable("new_note", req.new_note);
+    let auto_upgrade = mask.value("auto_upgrade", req.auto_upgrade)?;
+    let update_tags = mask.value("update_tags", req.update_tags)?;
+    let cost = mask.value("cost", req.cost)?;
 
-    let authz = if req.new_org_id.is_some() {
+    let authz = if new_org_id.is_some() {
         let perms = [NodeAdminPerm::UpdateConfig, NodeAdminPerm::Transfer];
         write.auth_all(&meta, perms).await?
-    } else if req.cost.is_some() {
+    } else if cost.is_some() {
         let perms = [NodeAdminPerm::UpdateConfig, NodeAdminPerm::ViewCost];
         write.auth(&meta, perms).await?
     } else {
@@ -568,20 +1148,22 @@ pub async fn update_config(
     };
 
     let node = Node::by_id(node_id, &mut write).await?;
+    // a cleared display name falls back to the generated node name
+    let new_display_name =
+        new_display_name.map(|name| name.unwrap_or_else(|| node.node_name.clone()));
     let update = UpdateNode {
         org_id: new_org_id,
         host_id: None,
-        display_name: req.new_display_name.as_deref(),
-        auto_upgrade: req.auto_upgrade,
+        display_name: new_display_name.as_deref(),
+        auto_upgrade,
         ip_address: None,
         ip_gateway: None,
-        note: req.new_note.as_deref(),
-        tags: req
-            .update_tags
+        note: new_note.as_ref().map(Option::as_deref),
+        tags: update_tags
             .map(|tags| tags.into_update(node.tags))
             .transpose()?
             .flatten(),
-        cost: req.cost.map(common::BillingAmount::try_into).transpose()?,
+        cost: cost.map(common::BillingAmount::try_into).transpose()?,
     };
     update.apply(node_id, &authz, &mut write).await?;
 
@@ -610,6 +1192,8 @@ pub async fn update_config(
             .image
             .values
             .into_iter()
+            // secret values are only sent with create and upgrade
+            .filter(|value| !value.is_secret)
             .map(Into::into)
             .collect()
     } else {
@@ -619,11 +1203,12 @@ pub async fn update_config(
     let api_update = api::NodeUpdate {
         node_id: node.id.to_string(),
         config_id: node.config_id.to_string(),
-        auto_upgrade: req.auto_upgrade,
+        auto_upgrade,
         new_org_id: new_org_id.map(|id| id.to_string()),
         new_org_name: None,
-        new_display_name: req.new_display_name,
-        new_note: req.new_note,
+        new_display_name,
+        // a cleared note is sent as empty
+        new_note: new_note.map(Option::unwrap_or_default),
         new_values,
         new_firewall: req.new_firewall,
     };
@@ -647,6 +1232,7 @@ pub async fn upgrade_image(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceUpgradeImageResponse, Error> {
+    req.validate()?;
     let ids = req
         .node_ids
         .iter()
@@ -672,16 +1258,102 @@ pub async fn upgrade_image(
         .await?;
 
     let nodes = Node::by_ids(&ids, &mut write).await?;
+    check_freeze(&nodes, &authz, &mut write).await?;
 
-    let image = Image::by_id(image_id, org_id, &authz, &mut write).await?;
-    let version =
-        ProtocolVersion::by_id(image.protocol_version_id, org_id, &authz, &mut write).await?;
+    if nodes.len() > 1 && !authz.has_perm(NodeAdminPerm::Upgrade) {
+        if let Some(approval) = upgrade_approval(&nodes, &req, &authz, &meta, &mut write).await? {
+            return Ok(api::NodeServiceUpgradeImageResponse {
+                approval: Some(approval.into()),
+            });
+        }
+    }
+
+    upgrade_nodes(nodes, image_id, org_id, &authz, &mut write).await?;
+
+    Ok(api::NodeServiceUpgradeImageResponse { approval: None })
+}
+
+/// Hold back an upgrade of several nodes if their org requires approval.
+///
+/// Nodes of an org that requires approval can't be upgraded together with the
+/// nodes of other orgs, as each approval belongs to a single org.
+async fn upgrade_approval(
+    nodes: &[Node],
+    req: &api::NodeServiceUpgradeImageRequest,
+    authz: &AuthZ,
+    meta: &Metadata,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<Option<Approval>, Error> {
+    let action = ApprovalAction::NodeUpgrade;
+    let org_ids: HashSet<_> = nodes.iter().map(|node| node.org_id).collect();
+    if let [org_id] = org_ids.iter().collect::<Vec<_>>()[..] {
+        return Ok(approval::request(action, *org_id, req, authz, meta, write).await?);
+    }
+
+    for org_id in org_ids {
+        let org = Org::by_id(org_id, write).await?;
+        if action.is_required(&org) {
+            return Err(Error::UpgradeApprovalOrgs(org_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Run an approved upgrade of the nodes of `approved_org_id`.
+pub(super) async fn approved_upgrade(
+    req: api::NodeServiceUpgradeImageRequest,
+    approved_org_id: OrgId,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let ids = req
+        .node_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseId))
+        .collect::<Result<HashSet<_>, _>>()?;
+    let image_id = req.image_id.parse().map_err(Error::ParseImageId)?;
+    let org_id = req
+        .org_id
+        .map(|id| id.parse::<OrgId>().map_err(Error::ParseOrgId))
+        .transpose()?;
+
+    let nodes = Node::by_ids(&ids, write).await?;
+    if nodes.iter().any(|node| node.org_id != approved_org_id) {
+        return Err(Error::ApprovalOrg(approved_org_id));
+    }
+    check_freeze(&nodes, authz, write).await?;
+
+    upgrade_nodes(nodes, image_id, org_id, authz, write).await
+}
+
+/// Returns an error if the org of any of `nodes` is in a freeze window.
+async fn check_freeze(
+    nodes: &[Node],
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let org_ids: HashSet<_> = nodes.iter().map(|node| node.org_id).collect();
+    for org_id in org_ids {
+        FreezeWindow::check(org_id, authz, write).await?;
+    }
+    Ok(())
+}
+
+async fn upgrade_nodes(
+    nodes: Vec<Node>,
+    image_id: ImageId,
+    org_id: Option<OrgId>,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let image = Image::by_id(image_id, org_id, authz, write).await?;
+    let version = ProtocolVersion::by_id(image.protocol_version_id, org_id, authz, write).await?;
     for node in nodes {
-        node.notify_upgrade(&image, &version, org_id, &authz, &mut write)
+        node.notify_upgrade(&image, &version, org_id, authz, write)
             .await?;
     }
 
-    Ok(api::NodeServiceUpgradeImageResponse {})
+    Ok(())
 }
 
 pub async fn start(
@@ -689,19 +1361,30 @@ pub async fn start(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceStartResponse, Error> {
+    req.validate()?;
     let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = write
         .auth_or_for(&meta, NodeAdminPerm::Start, NodePerm::Start, node_id)
         .await?;
 
+    let execute_at = req
+        .execute_at
+        .map(|at| NanosUtc::try_from(at).map(|at| *at))
+        .transpose()
+        .map_err(Error::ParseExecuteAt)?;
+
     let node = Node::by_id(node_id, &mut write).await?;
+    node.check_not_suspended()?;
     let start_cmd = NewCommand::node(&node, CommandType::NodeStart)?
+        .with_execute_at(execute_at)
         .create(&mut write)
         .await?;
-    let start_cmd = api::Command::from(&start_cmd, &authz, &mut write)
-        .await?
-        .ok_or(Error::NoNodeStart)?;
-    write.mqtt(start_cmd);
+    if !start_cmd.is_held() {
+        let start_cmd = api::Command::from(&start_cmd, &authz, &mut write)
+            .await?
+            .ok_or(Error::NoNodeStart)?;
+        write.mqtt(start_cmd);
+    }
 
     Ok(api::NodeServiceStartResponse {})
 }
@@ -711,19 +1394,29 @@ pub async fn stop(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceStopResponse, Error> {
+    req.validate()?;
     let node_id = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = write
         .auth_or_for(&meta, NodeAdminPerm::Stop, NodePerm::Stop, node_id)
         .await?;
 
+    let execute_at = req
+        .execute_at
+        .map(|at| NanosUtc::try_from(at).map(|at| *at))
+        .transpose()
+        .map_err(Error::ParseExecuteAt)?;
+
     let node = Node::by_id(node_id, &mut write).await?;
     let stop_cmd = NewCommand::node(&node, CommandType::NodeStop)?
+        .with_execute_at(execute_at)
         .create(&mut write)
         .await?;
-    let stop_cmd = api::Command::from(&stop_cmd, &authz, &mut write)
-        .await?
-        .ok_or(Error::NoNodeStop)?;
-    write.mqtt(stop_cmd);
+    if !stop_cmd.is_held() {
+        let stop_cmd = api::Command::from(&stop_cmd, &authz, &mut write)
+            .await?
+            .ok_or(Error::NoNodeStop)?;
+        write.mqtt(stop_cmd);
+    }
 
     Ok(api::NodeServiceStopResponse {})
 }
@@ -733,19 +1426,30 @@ pub async fn restart(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceRestartResponse, Error> {
+    req.validate()?;
     let node_id = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = write
         .auth_or_for(&meta, NodeAdminPerm::Restart, NodePerm::Restart, node_id)
         .await?;
 
+    let execute_at = req
+        .execute_at
+        .map(|at| NanosUtc::try_from(at).map(|at| *at))
+        .transpose()
+        .map_err(Error::ParseExecuteAt)?;
+
     let node = Node::by_id(node_id, &mut write).await?;
+    node.check_not_suspended()?;
     let restart_cmd = NewCommand::node(&node, CommandType::NodeRestart)?
+        .with_execute_at(execute_at)
         .create(&mut write)
         .await?;
-    let restart_cmd = api::Command::from(&restart_cmd, &authz, &mut write)
-        .await?
-        .ok_or(Error::NoNodeRestart)?;
-    write.mqtt(restart_cmd);
+    if !restart_cmd.is_held() {
+        let restart_cmd = api::Command::from(&restart_cmd, &authz, &mut write)
+            .await?
+            .ok_or(Error::NoNodeRestart)?;
+        write.mqtt(restart_cmd);
+    }
 
     Ok(api::NodeServiceRestartResponse {})
 }
@@ -755,122 +1459,581 @@ pub async fn delete(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::NodeServiceDeleteResponse, Error> {
+    req.validate()?;
     let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
     let authz = write
         .auth_or_for(&meta, NodeAdminPerm::Delete, NodePerm::Delete, node_id)
         .await?;
 
-    let node = Node::delete(node_id, &mut write).await?;
+    let node = Node::by_id(node_id, &mut write).await?;
+    if let Some(project_id) = node.project_id {
+        let project = Project::by_id(project_id, &mut write).await?;
+        project.authorize(&authz, &mut write).await?;
+    }
+    LegalHold::check_node(node.id, node.org_id, &mut write).await?;
+    FreezeWindow::check(node.org_id, &authz, &mut write).await?;
+
+    if !authz.has_perm(NodeAdminPerm::Delete) {
+        let action = ApprovalAction::NodeDelete;
+        let org_id = node.org_id;
+        if let Some(approval) =
+            approval::request(action, org_id, &req, &authz, &meta, &mut write).await?
+        {
+            return Ok(api::NodeServiceDeleteResponse {
+                approval: Some(approval.into()),
+            });
+        }
+    }
+
+    delete_node(node_id, &authz, &mut write).await?;
+
+    Ok(api::NodeServiceDeleteResponse { approval: None })
+}
+
+/// Run an approved delete of a node of `approved_org_id`.
+///
+/// The node may have been put on legal hold since the delete was requested.
+pub(super) async fn approved_delete(
+    req: api::NodeServiceDeleteRequest,
+    approved_org_id: OrgId,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    let node = Node::by_id(node_id, write).await?;
+    if node.org_id != approved_org_id {
+        return Err(Error::ApprovalOrg(approved_org_id));
+    }
+    LegalHold::check_node(node.id, node.org_id, write).await?;
+    FreezeWindow::check(node.org_id, authz, write).await?;
+
+    delete_node(node_id, authz, write).await
+}
+
+async fn delete_node(
+    node_id: NodeId,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let node = Node::delete(node_id, write).await?;
     let delete_cmd = NewCommand::node(&node, CommandType::NodeDelete)?
-        .create(&mut write)
+        .create(write)
         .await?;
-    let delete_cmd = api::Command::from(&delete_cmd, &authz, &mut write)
+    let delete_cmd = api::Command::from(&delete_cmd, authz, write)
         .await?
         .ok_or(Error::NoNodeDelete)?;
     write.mqtt(delete_cmd);
 
-    let deleted_by = common::Resource::from(&authz);
+    if let Some(peer) = MeshPeer::delete_for_node(node_id, write).await? {
+        let org_ids = HashSet::from([peer.org_id]);
+        let removed_from = HashSet::from([peer.host_id]);
+        super::mesh::send(&org_ids, &removed_from, write).await?;
+    }
+
+    let deleted_by = common::Resource::from(authz);
     let deleted = api::NodeMessage::deleted(&node, Some(deleted_by));
     write.mqtt(deleted);
 
-    Ok(api::NodeServiceDeleteResponse {})
+    Ok(())
 }
 
-impl api::Node {
-    pub async fn maybe_from_model(
-        node: Node,
-        authz: &AuthZ,
-        conn: &mut Conn<'_>,
-    ) -> Result<Option<Self>, Error> {
-        use crate::model::protocol::Error as ProtocolError;
-        use crate::model::protocol::version::Error as VersionError;
-
-        match Self::from_model(node, authz, conn).await {
-            Ok(node) => Ok(Some(node)),
-            Err(Error::Protocol(
-                ProtocolError::ById(_, NotFound) | ProtocolError::ByIds(_, NotFound),
-            )) => Ok(None),
-            Err(Error::ProtocolVersion(
-                VersionError::ById(_, NotFound) | VersionError::ByIds(_, NotFound),
-            )) => Ok(None),
-            Err(err) => Err(err),
-        }
-    }
-
-    pub async fn from_model(node: Node, authz: &AuthZ, conn: &mut Conn<'_>) -> Result<Self, Error> {
-        let config = Config::by_id(node.config_id, conn).await?;
-        let org = Org::by_id(node.org_id, conn).await?;
-
-        let host = Host::by_id(node.host_id, Some(node.org_id), conn).await?;
-        let region = Region::by_id(host.region_id, conn).await?;
-
-        let protocol = Protocol::by_id(node.protocol_id, Some(org.id), authz, conn).await?;
-        let version =
-            ProtocolVersion::by_id(node.protocol_version_id, Some(org.id), authz, conn).await?;
-        let reports = NodeReport::by_node(node.id, conn).await?;
+/// Change the cpu cores or memory of a node.
+pub async fn resize(
+    req: api::NodeServiceResizeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceResizeResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_or_for(&meta, NodeAdminPerm::Resize, NodePerm::Resize, node_id)
+        .await?;
 
-        api::Node::new(
-            node, &config, &org, &host, &region, &protocol, &version, reports, authz,
-        )
+    if req.cpu_cores.is_none() && req.memory_bytes.is_none() {
+        return Err(Error::NoResize);
     }
+    let cpu_cores = req
+        .cpu_cores
+        .map(|cores| i64::try_from(cores).map_err(Error::CpuCores))
+        .transpose()?;
+    let memory_bytes = req
+        .memory_bytes
+        .map(|bytes| i64::try_from(bytes).map_err(Error::MemoryBytes))
+        .transpose()?;
 
-    pub async fn from_models(
-        nodes: Vec<Node>,
-        authz: &AuthZ,
-        conn: &mut Conn<'_>,
-    ) -> Result<Vec<Self>, Error> {
-        let node_ids = nodes.iter().map(|n| n.id).collect();
-
-        let config_ids = nodes.iter().map(|n| n.config_id).collect();
-        let configs = Config::by_ids(&config_ids, conn)
-            .await?
-            .to_map_keep_last(|config| (config.id, config));
+    let node = Node::by_id(node_id, &mut write).await?;
+    let current = NodeResources::from(&node);
+    let resources = NodeResources {
+        cpu_cores: cpu_cores.unwrap_or(current.cpu_cores),
+        memory_bytes: memory_bytes.unwrap_or(current.memory_bytes),
+        disk_bytes: current.disk_bytes,
+    };
+    resources.check_host(&node, &mut write).await?;
+    let resized = resources.apply(&node, &authz, &mut write).await?;
 
-        let org_ids = nodes.iter().map(|n| n.org_id).collect();
-        let orgs = Org::by_ids(&org_ids, conn)
-            .await?
-            .to_map_keep_last(|org| (org.id, org));
+    let api_resize = api::NodeResize::from(resources);
+    let node_cmd = NewCommand::node(&resized, CommandType::NodeResize)?
+        .with_protobuf(&api_resize)
+        .create(&mut write)
+        .await?;
+    let resize_cmd = node_resize(&node_cmd, &mut write).await?;
+    write.mqtt(resize_cmd);
 
-        let host_ids = nodes.iter().map(|n| n.host_id).collect();
-        let hosts = Host::by_ids(&host_ids, &org_ids, conn).await?;
-        let region_ids = hosts.iter().map(|h| h.region_id).collect();
-        let regions = Region::by_ids(&region_ids, conn)
-            .await?
-            .to_map_keep_last(|region| (region.id, region));
-        let hosts = hosts.to_map_keep_last(|host| (host.id, host));
+    let api_node = api::Node::from_model(resized, &authz, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    write.mqtt(api::NodeMessage::updated(api_node.clone(), updated_by));
 
-        let protocol_ids = nodes.iter().map(|n| n.protocol_id).collect();
-        let protocol = Protocol::by_ids(&protocol_ids, &org_ids, authz, conn)
-            .await?
-            .to_map_keep_last(|chain| (chain.id, chain));
+    Ok(api::NodeServiceResizeResponse {
+        node: Some(api_node),
+    })
+}
 
-        let version_ids = nodes.iter().map(|n| n.protocol_version_id).collect();
-        let versions = ProtocolVersion::by_ids(&version_ids, &org_ids, authz, conn)
-            .await?
-            .to_map_keep_last(|version| (version.id, version));
+/// Request a larger disk for a node.
+///
+/// The resize is applied straight away when the new size is within the auto
+/// approve cap of the org, and otherwise waits for `review_disk_resize`.
+pub async fn resize_disk(
+    req: api::NodeServiceResizeDiskRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceResizeDiskResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_or_for(
+            &meta,
+            NodeAdminPerm::ResizeDisk,
+            NodePerm::ResizeDisk,
+            node_id,
+        )
+        .await?;
+
+    let disk_bytes = i64::try_from(req.disk_bytes).map_err(Error::DiskBytes)?;
+    let used_disk_bytes = req
+        .used_disk_bytes
+        .map(|bytes| i64::try_from(bytes).map_err(Error::UsedDiskBytes))
+        .transpose()?;
+
+    let node = Node::by_id(node_id, &mut write).await?;
+    let org = DiskResize::check(&node, disk_bytes, &mut write).await?;
+    let resize = NewDiskResize::new(&node, disk_bytes, used_disk_bytes, authz.resource())
+        .create(&mut write)
+        .await?;
+
+    let auto_approve = org
+        .disk_auto_approve_bytes
+        .is_some_and(|cap| disk_bytes <= cap);
+    let resize = if auto_approve {
+        apply_disk_resize(resize, node, None, &authz, &mut write).await?
+    } else {
+        resize
+    };
+
+    Ok(api::NodeServiceResizeDiskResponse {
+        resize: Some(resize.into()),
+    })
+}
+
+pub async fn list_disk_resizes(
+    req: api::NodeServiceListDiskResizesRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::NodeServiceListDiskResizesResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_or_for(
+        &meta,
+        NodeAdminPerm::ListDiskResizes,
+        NodePerm::ListDiskResizes,
+        org_id,
+    )
+    .await?;
+
+    let status = DiskResizeStatus::from_api(req.status());
+    let resizes = DiskResize::by_org_id(org_id, status, &mut read).await?;
+
+    Ok(api::NodeServiceListDiskResizesResponse {
+        resizes: resizes.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn review_disk_resize(
+    req: api::NodeServiceReviewDiskResizeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceReviewDiskResizeResponse, Error> {
+    req.validate()?;
+    let resize_id: DiskResizeId = req.resize_id.parse().map_err(Error::ParseResizeId)?;
+    let resize = DiskResize::by_id(resize_id, &mut write).await?;
+    let authz = write
+        .auth_or_for(
+            &meta,
+            NodeAdminPerm::ReviewDiskResize,
+            NodePerm::ReviewDiskResize,
+            resize.node_id,
+        )
+        .await?;
+
+    let reviewed_by = authz.resource();
+    let resize = if req.approve {
+        // the node or its host may have changed since the resize was requested
+        let node = Node::by_id(resize.node_id, &mut write).await?;
+        DiskResize::check(&node, resize.new_disk_bytes, &mut write).await?;
+        apply_disk_resize(resize, node, Some(reviewed_by), &authz, &mut write).await?
+    } else {
+        resize.reject(reviewed_by, &mut write).await?
+    };
+
+    Ok(api::NodeServiceReviewDiskResizeResponse {
+        resize: Some(resize.into()),
+    })
+}
+
+/// Grow the disk of `node`, send blockvisord a `NodeResize` command and charge
+/// the org for the added disk.
+async fn apply_disk_resize(
+    resize: DiskResize,
+    node: Node,
+    reviewed_by: Option<Resource>,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<DiskResize, Error> {
+    let added_bytes = resize.new_disk_bytes - node.disk_bytes;
+    let resized = resize.resize_node(&node, authz, write).await?;
+
+    let api_resize = api::NodeResize::from(NodeResources::from(&resized));
+    let node_cmd = NewCommand::node(&resized, CommandType::NodeResize)?
+        .with_protobuf(&api_resize)
+        .create(write)
+        .await?;
+    let resize_cmd = node_resize(&node_cmd, write).await?;
+    write.mqtt(resize_cmd);
+
+    let resize = resize.approve(node_cmd.id, reviewed_by, write).await?;
+    bill_added_disk(&resized, added_bytes, write).await?;
+
+    let api_node = api::Node::from_model(resized, authz, write).await?;
+    let updated_by = common::Resource::from(authz);
+    write.mqtt(api::NodeMessage::updated(api_node, updated_by));
+
+    Ok(resize)
+}
+
+/// Report the added disk of a billed node as metered usage in GiB.
+///
+/// Disk is only charged for when the node has a subscription item and billing
+/// has added the metered disk price to the subscription of the org.
+async fn bill_added_disk(
+    node: &Node,
+    added_bytes: i64,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let ctx = write.ctx;
+    let (Some(_), Some(sku), Some(stripe)) = (
+        &node.stripe_item_id,
+        &ctx.config.stripe.disk_sku,
+        &ctx.stripe,
+    ) else {
+        return Ok(());
+    };
+
+    let org = Org::by_id(node.org_id, write).await?;
+    if let Some(item) = stripe.find_metered_item(&org, &ctx.keyring, sku).await? {
+        let gib = added_bytes.unsigned_abs().div_ceil(1 << 30);
+        stripe
+            .create_usage_record(&item.id, gib, Utc::now())
+            .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn add_dns_alias(
+    req: api::NodeServiceAddDnsAliasRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceAddDnsAliasResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_or_for(
+            &meta,
+            NodeAdminPerm::AddDnsAlias,
+            NodePerm::AddDnsAlias,
+            node_id,
+        )
+        .await?;
+
+    let alias_type = DnsAliasType::from_api(req.alias_type()).ok_or(Error::MissingAliasType)?;
+    let dns_config = &write.ctx.config.cloudflare.dns;
+    let name = NodeDnsAlias::parse_name(&req.name, |name| dns_config.allows_alias(name))?;
+
+    let node = Node::by_id(node_id, &mut write).await?;
+    let dns_id = write
+        .ctx
+        .dns
+        .create_alias(&name, alias_type.target(&node))
+        .await?
+        .id;
+
+    let new_alias = NewNodeDnsAlias::new(&node, name, alias_type, dns_id, authz.resource());
+    let dns_id = new_alias.dns_id.clone();
+    let alias = match new_alias.create(&mut write).await {
+        Ok(alias) => alias,
+        Err(err) => {
+            if let Err(err) = write.ctx.dns.delete(&dns_id).await {
+                warn!("Failed to delete DNS record {dns_id}: {err}");
+            }
+            return Err(err.into());
+        }
+    };
+
+    Ok(api::NodeServiceAddDnsAliasResponse {
+        alias: Some(alias.into()),
+    })
+}
+
+pub async fn remove_dns_alias(
+    req: api::NodeServiceRemoveDnsAliasRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceRemoveDnsAliasResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    write
+        .auth_or_for(
+            &meta,
+            NodeAdminPerm::RemoveDnsAlias,
+            NodePerm::RemoveDnsAlias,
+            node_id,
+        )
+        .await?;
+
+    let name = req.name.trim().trim_end_matches('.').to_lowercase();
+    let alias = NodeDnsAlias::by_name(&name, node_id, &mut write).await?;
+    alias.delete(&mut write).await?;
+
+    match write.ctx.dns.delete(&alias.dns_id).await {
+        Ok(()) => (),
+        Err(err) if err.is_not_found() => (),
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(api::NodeServiceRemoveDnsAliasResponse {})
+}
+
+/// Rename a node to a new or generated name. The old DNS name stays as an
+/// alias of the node until it expires.
+pub async fn rename(
+    req: api::NodeServiceRenameRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceRenameResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_or_for(&meta, NodeAdminPerm::Rename, NodePerm::Rename, node_id)
+        .await?;
+
+    let node = Node::by_id(node_id, &mut write).await?;
+    let renamed = node
+        .rename(req.new_node_name.as_deref(), authz.resource(), &mut write)
+        .await?;
+
+    let api_node = api::Node::from_model(renamed, &authz, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    write.mqtt(api::NodeMessage::updated(api_node.clone(), updated_by));
+
+    Ok(api::NodeServiceRenameResponse {
+        node: Some(api_node),
+    })
+}
+
+/// Request a TLS certificate for the DNS name and aliases of a node, which is
+/// issued in the background and sent to its host.
+pub async fn request_certificate(
+    req: api::NodeServiceRequestCertificateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceRequestCertificateResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    write
+        .auth_or_for(
+            &meta,
+            NodeAdminPerm::RequestCertificate,
+            NodePerm::RequestCertificate,
+            node_id,
+        )
+        .await?;
+
+    let node = Node::by_id(node_id, &mut write).await?;
+    let base = &write.ctx.config.cloudflare.dns.base;
+    let aliases = NodeDnsAlias::by_node_id(node_id, &mut write).await?;
+    let names = std::iter::once(format!("{}.{base}", node.node_name))
+        .chain(aliases.into_iter().map(|alias| alias.name))
+        .collect();
+
+    let certificate = NodeCertificate::request(&node, names, &mut write).await?;
+
+    Ok(api::NodeServiceRequestCertificateResponse {
+        certificate: Some(certificate.into()),
+    })
+}
+
+pub async fn get_certificate(
+    req: api::NodeServiceGetCertificateRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::NodeServiceGetCertificateResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    read.auth_or_for(
+        &meta,
+        NodeAdminPerm::GetCertificate,
+        NodePerm::GetCertificate,
+        node_id,
+    )
+    .await?;
+
+    let certificate = NodeCertificate::by_node_id(node_id, &mut read).await?;
+
+    Ok(api::NodeServiceGetCertificateResponse {
+        certificate: Some(certificate.into()),
+    })
+}
+
+/// Ask the host of a node to upload an archive of its logs, process state and
+/// config as an artifact of the returned command.
+///
+/// The requester is emailed a link once the host reports the upload.
+pub async fn collect_debug_bundle(
+    req: api::NodeServiceCollectDebugBundleRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeServiceCollectDebugBundleResponse, Error> {
+    req.validate()?;
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_or_for(
+            &meta,
+            NodeAdminPerm::CollectDebugBundle,
+            NodePerm::CollectDebugBundle,
+            node_id,
+        )
+        .await?;
+
+    let node = Node::by_id(node_id, &mut write).await?;
+    let bundle = api::NodeDebugBundle {
+        log_lines: req.log_lines.map_or(DEBUG_BUNDLE_LOG_LINES, |lines| {
+            lines.min(MAX_DEBUG_BUNDLE_LOG_LINES)
+        }),
+        requested_by: Some(common::Resource::from(&authz)),
+    };
+    let bundle_cmd = NewCommand::node(&node, CommandType::NodeDebugBundle)?
+        .with_protobuf(&bundle)
+        .create(&mut write)
+        .await?;
+
+    let cmd = api::Command::from(&bundle_cmd, &authz, &mut write)
+        .await?
+        .ok_or(Error::NoNodeDebugBundle)?;
+    write.mqtt(cmd);
+
+    Ok(api::NodeServiceCollectDebugBundleResponse {
+        command_id: bundle_cmd.id.to_string(),
+    })
+}
+
+impl api::Node {
+    /// Converts a node, or returns `None` if its protocol or version is not
+    /// visible to `authz`.
+    pub async fn maybe_from_model(
+        node: Node,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Self>, Error> {
+        let mut relations =
+            NodeRelations::for_nodes(std::slice::from_ref(&node), authz, conn).await?;
+        Self::from_relations(node, &mut relations, authz)
+    }
 
-        let mut reports = NodeReport::by_node_ids(&node_ids, conn)
+    pub async fn from_model(node: Node, authz: &AuthZ, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let node_id = node.id;
+        Self::maybe_from_model(node, authz, conn)
             .await?
-            .to_map_keep_all(|report| (report.node_id, report));
+            .ok_or(Error::HiddenProtocol(node_id))
+    }
+
+    /// Converts a list of `nodes`, skipping those with a protocol or version
+    /// that is not visible to `authz`.
+    ///
+    /// Performs O(1) database queries irrespective of the number of nodes.
+    pub async fn from_models(
+        nodes: Vec<Node>,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut relations = NodeRelations::for_nodes(&nodes, authz, conn).await?;
 
         nodes
             .into_iter()
-            .filter_map(|node| {
-                let config = configs.get(&node.config_id)?;
-                let org = orgs.get(&node.org_id)?;
-                let host = hosts.get(&node.host_id)?;
-                let region = regions.get(&host.region_id)?;
-                let protocol = protocol.get(&node.protocol_id)?;
-                let version = versions.get(&node.protocol_version_id)?;
-                let reports = reports.remove(&node.id).unwrap_or_default();
-
-                Some(api::Node::new(
-                    node, config, org, host, region, protocol, version, reports, authz,
-                ))
-            })
+            .filter_map(|node| Self::from_relations(node, &mut relations, authz).transpose())
             .collect()
     }
 
+    fn from_relations(
+        node: Node,
+        relations: &mut NodeRelations,
+        authz: &AuthZ,
+    ) -> Result<Option<Self>, Error> {
+        let reports = relations.reports.remove(&node.id).unwrap_or_default();
+        let aliases = relations.aliases.remove(&node.id).unwrap_or_default();
+        let network_height = relations.heads.get(&node.protocol_version_id).copied();
+
+        // the protocol may be hidden from the caller, but any other missing row
+        // means the node itself is inconsistent
+        let (Some(protocol), Some(version)) = (
+            relations.protocols.get(&node.protocol_id),
+            relations.versions.get(&node.protocol_version_id),
+        ) else {
+            return Ok(None);
+        };
+        let node_id = node.id;
+        let missing = |relation| Error::LookupMissing(node_id, relation);
+        let config = relations
+            .configs
+            .get(&node.config_id)
+            .ok_or_else(|| missing("config"))?;
+        let org = relations
+            .orgs
+            .get(&node.org_id)
+            .ok_or_else(|| missing("org"))?;
+        let host = relations
+            .hosts
+            .get(&node.host_id)
+            .ok_or_else(|| missing("host"))?;
+        let region = relations
+            .regions
+            .get(&host.region_id)
+            .ok_or_else(|| missing("region"))?;
+
+        api::Node::new(
+            node,
+            config,
+            org,
+            host,
+            region,
+            protocol,
+            version,
+            reports,
+            aliases,
+            network_height,
+            authz,
+        )
+        .map(Some)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         node: Node,
@@ -881,6 +2044,8 @@ impl api::Node {
         protocol: &Protocol,
         version: &ProtocolVersion,
         reports: Vec<NodeReport>,
+        aliases: Vec<NodeDnsAlias>,
+        network_height: Option<i64>,
         authz: &AuthZ,
     ) -> Result<Self, Error> {
         let config = config.node_config()?;
@@ -896,6 +2061,10 @@ impl api::Node {
             .block_height
             .map(|age| u64::try_from(age).map_err(Error::BlockAge))
             .transpose()?;
+        // how many blocks the node is behind the head of its network
+        let sync_lag = network_height
+            .zip(node.block_height)
+            .map(|(head, height)| u64::try_from(head - height).unwrap_or_default());
 
         let jobs = node
             .jobs
@@ -928,6 +2097,8 @@ impl api::Node {
             host_org_id: host.org_id.map(|id| id.to_string()),
             host_network_name: host.network_name.clone(),
             host_display_name: host.display_name.clone(),
+            host_pool_id: host.pool_id.map(|id| id.to_string()),
+            placement_policy: common::PlacementPolicy::from(node.scheduler_placement).into(),
             region_id: region.id.to_string(),
             region_key: region.key.clone().into(),
             region_name: region.display_name.clone(),
@@ -943,10 +2114,12 @@ impl api::Node {
             ip_address: node.ip_address.to_string(),
             ip_gateway: node.ip_gateway.to_string(),
             dns_name: node.dns_name,
+            dns_aliases: aliases.into_iter().map(Into::into).collect(),
             p2p_address: node.p2p_address,
             dns_url: node.dns_url,
             block_height,
             block_age,
+            sync_lag,
             note: node.note,
             node_status: Some(status.into()),
             jobs,
@@ -956,6 +2129,9 @@ impl api::Node {
             created_at: Some(NanosUtc::from(node.created_at).into()),
             updated_at: node.updated_at.map(NanosUtc::from).map(Into::into),
             cost,
+            config_drift: node.config_drift,
+            project_id: node.project_id.map(|id| id.to_string()),
+            network_id: node.network_id.map(|id| id.to_string()),
             version_metadata: version
                 .metadata
                 .as_ref()
@@ -966,6 +2142,234 @@ impl api::Node {
     }
 }
 
+/// The nodes that a `watch` stream is filtered to.
+///
+/// An empty set matches any node.
+pub struct NodeWatch {
+    org_ids: HashSet<OrgId>,
+    host_ids: HashSet<HostId>,
+    node_ids: HashSet<NodeId>,
+    node_states: HashSet<i32>,
+}
+
+impl NodeWatch {
+    /// Forward matching messages visible to `authz` until the client
+    /// disconnects or the server shuts down.
+    ///
+    /// A watch that falls behind ends with an error, so that the client can
+    /// list the nodes again rather than miss a change. A watch that is closed
+    /// by a shutdown ends with a retryable error, so that the client can watch
+    /// again on another server.
+    fn stream(
+        self,
+        authz: AuthZ,
+        mut messages: broadcast::Receiver<api::NodeMessage>,
+        context: Arc<Context>,
+    ) -> WatchStream {
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+
+        tokio::spawn(async move {
+            // the last state of each node, to report state transitions
+            let mut states = HashMap::new();
+            // whether each node is visible to the watch
+            let mut visible = HashMap::new();
+            loop {
+                let message = tokio::select! {
+                    () = tx.closed() => break,
+                    () = context.shutdown.wait() => {
+                        let status = Status::unavailable("Server is shutting down.")
+                            .with_reason("SHUTTING_DOWN");
+                        let _ = tx.send(Err(status.into())).await;
+                        break;
+                    }
+                    message = messages.recv() => message,
+                };
+
+                let resp = match message {
+                    Ok(message) => {
+                        let Some(node_id) = self.matches(&message) else {
+                            continue;
+                        };
+                        if !is_visible(node_id, &authz, &mut visible, &context).await {
+                            continue;
+                        }
+                        match event(message, &mut states) {
+                            Some(resp) => Ok(resp),
+                            None => continue,
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Node watch skipped {skipped} messages");
+                        let status =
+                            Status::internal("Node watch fell behind.").with_reason("WATCH_LAGGED");
+                        let _ = tx.send(Err(status.into())).await;
+                        break;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if tx.send(resp).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Box::pin(ReceiverStream::new(rx))
+    }
+
+    /// The id of the node of `message`, if it matches the filter of this watch.
+    fn matches(&self, message: &api::NodeMessage) -> Option<NodeId> {
+        let (node_id, org_id, host_id, state) = watched_fields(message)?;
+
+        let matches = |ids: &HashSet<_>, id: &str| {
+            ids.is_empty() || id.parse().is_ok_and(|id| ids.contains(&id))
+        };
+        if !matches(&self.org_ids, org_id)
+            || !matches(&self.host_ids, host_id)
+            || !matches(&self.node_ids, node_id)
+            || !(self.node_states.is_empty()
+                || state.is_some_and(|state| self.node_states.contains(&state)))
+        {
+            return None;
+        }
+
+        node_id.parse().ok()
+    }
+}
+
+/// The event for a matching node message.
+fn event(
+    message: api::NodeMessage,
+    states: &mut HashMap<String, i32>,
+) -> Option<api::NodeServiceWatchResponse> {
+    let (node_id, _, _, state) = watched_fields(&message)?;
+    let node_id = node_id.to_string();
+
+    let deleted = matches!(
+        message.message,
+        Some(api::node_message::Message::Deleted(_))
+    );
+    let previous_state = match state {
+        Some(state) => states
+            .insert(node_id, state)
+            .filter(|previous| *previous != state),
+        None if deleted => states.remove(&node_id),
+        None => None,
+    };
+
+    Some(api::NodeServiceWatchResponse {
+        event: Some(message),
+        previous_state,
+    })
+}
+
+/// The node id, org id, host id and state of a node message.
+fn watched_fields(message: &api::NodeMessage) -> Option<(&str, &str, &str, Option<i32>)> {
+    use api::node_message::Message::{CertificateExpiring, ConfigDrift, Created, Deleted, Updated};
+
+    match message.message.as_ref()? {
+        Created(api::NodeCreated { node, .. }) | Updated(api::NodeUpdated { node, .. }) => {
+            let node = node.as_ref()?;
+            let state = node.node_status.as_ref().map(|status| status.state);
+            Some((&node.node_id, &node.org_id, &node.host_id, state))
+        }
+        Deleted(api::NodeDeleted {
+            node_id,
+            org_id,
+            host_id,
+            ..
+        })
+        | CertificateExpiring(api::NodeCertificateExpiring {
+            node_id,
+            org_id,
+            host_id,
+            ..
+        })
+        | ConfigDrift(api::NodeConfigDrift {
+            node_id,
+            org_id,
+            host_id,
+            ..
+        }) => Some((node_id, org_id, host_id, None)),
+    }
+}
+
+/// Whether a watch by `authz` may see node `node_id`.
+///
+/// As with `list`, a node is hidden if its protocol or version is not visible
+/// to the caller. A node of a project with members or teams is also hidden
+/// from anyone who may not change its nodes, as for the MQTT topics of the
+/// project. Each node is only checked once per watch, and a failed check hides
+/// the node without being remembered.
+async fn is_visible(
+    node_id: NodeId,
+    authz: &AuthZ,
+    visible: &mut HashMap<NodeId, bool>,
+    context: &Context,
+) -> bool {
+    if let Some(visible) = visible.get(&node_id) {
+        return *visible;
+    }
+
+    match check_visible(node_id, authz, context).await {
+        Ok(is_visible) => {
+            visible.insert(node_id, is_visible);
+            is_visible
+        }
+        Err(err) => {
+            warn!("Failed to check if node {node_id} is visible to a watch: {err}");
+            false
+        }
+    }
+}
+
+async fn check_visible(node_id: NodeId, authz: &AuthZ, context: &Context) -> Result<bool, Error> {
+    let mut conn = context.conn().await?;
+    let node = Node::deleted_by_id(node_id, &mut conn).await?;
+
+    if let Some(project_id) = node.project_id {
+        let project = Project::by_id(project_id, &mut conn).await?;
+        match project.authorize(authz, &mut conn).await {
+            Ok(()) => (),
+            Err(crate::model::project::Error::NotMember(_)) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let node = api::Node::maybe_from_model(node, authz, &mut conn).await?;
+    Ok(node.is_some())
+}
+
+impl TryFrom<api::NodeServiceWatchRequest> for NodeWatch {
+    type Error = Error;
+
+    fn try_from(req: api::NodeServiceWatchRequest) -> Result<Self, Self::Error> {
+        let org_ids = req
+            .org_ids
+            .iter()
+            .map(|id| id.parse().map_err(Error::ParseOrgId))
+            .collect::<Result<_, _>>()?;
+        let host_ids = req
+            .host_ids
+            .iter()
+            .map(|id| id.parse().map_err(Error::ParseHostId))
+            .collect::<Result<_, _>>()?;
+        let node_ids = req
+            .node_ids
+            .iter()
+            .map(|id| id.parse().map_err(Error::ParseId))
+            .collect::<Result<_, _>>()?;
+        let node_states = req.node_states().map(|state| state as i32).collect();
+
+        Ok(NodeWatch {
+            org_ids,
+            host_ids,
+            node_ids,
+            node_states,
+        })
+    }
+}
+
 impl api::NodeServiceListRequest {
     fn into_filter(self) -> Result<NodeFilter, Error> {
         let node_states = self
@@ -1002,6 +2406,20 @@ impl api::NodeServiceListRequest {
             .iter()
             .map(|id| id.parse().map_err(Error::ParseUserId))
             .collect::<Result<_, _>>()?;
+        let project_ids = self
+            .project_ids
+            .iter()
+            .map(|id| id.parse().map_err(Error::ParseProjectId))
+            .collect::<Result<_, _>>()?;
+        let network_ids = self
+            .network_ids
+            .iter()
+            .map(|id| id.parse().map_err(Error::ParseNetworkId))
+            .collect::<Result<_, _>>()?;
+        let network_kinds = self
+            .network_kinds()
+            .map(NetworkKind::try_from)
+            .collect::<Result<_, _>>()?;
 
         let search = self
             .search
@@ -1034,6 +2452,7 @@ impl api::NodeServiceListRequest {
                     api::NodeSortField::ProtocolState => Ok(NodeSort::ProtocolState(order)),
                     api::NodeSortField::ProtocolHealth => Ok(NodeSort::ProtocolHealth(order)),
                     api::NodeSortField::BlockHeight => Ok(NodeSort::BlockHeight(order)),
+                    api::NodeSortField::BlocksBehind => Ok(NodeSort::BlocksBehind(order)),
                     api::NodeSortField::CreatedAt => Ok(NodeSort::CreatedAt(order)),
                     api::NodeSortField::UpdatedAt => Ok(NodeSort::UpdatedAt(order)),
                 }
@@ -1045,6 +2464,10 @@ impl api::NodeServiceListRequest {
             .iter()
             .map(|ip| ip.parse().map_err(Error::ParseIp))
             .collect::<Result<_, _>>()?;
+        let min_blocks_behind = self
+            .min_blocks_behind
+            .map(|min| i64::try_from(min).map_err(Error::MinBlocksBehind))
+            .transpose()?;
 
         Ok(NodeFilter {
             protocol_ids,
@@ -1056,6 +2479,11 @@ impl api::NodeServiceListRequest {
             ip_addresses,
             node_states,
             next_states,
+            min_blocks_behind,
+            config_drift: self.config_drift,
+            project_ids,
+            network_ids,
+            network_kinds,
             search,
             sort,
             limit: i64::try_from(self.limit).map_err(Error::FilterLimit)?,
@@ -1063,3 +2491,154 @@ impl api::NodeServiceListRequest {
         })
     }
 }
+
+impl Validate for api::NodeServiceCreateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("image_id", &self.image_id);
+        v.maybe_uuid("old_node_id", self.old_node_id.as_deref());
+        v.maybe_uuid("pool_id", self.pool_id.as_deref());
+        v.maybe_uuid("project_id", self.project_id.as_deref());
+    }
+}
+
+impl Validate for api::NodeServiceGetRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceListRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuids("protocol_ids", &self.protocol_ids);
+        v.uuids("org_ids", &self.org_ids);
+        v.uuids("host_ids", &self.host_ids);
+        v.uuids("user_ids", &self.user_ids);
+        v.uuids("project_ids", &self.project_ids);
+        v.uuids("network_ids", &self.network_ids);
+    }
+}
+
+impl Validate for api::NodeServiceBulkUpdateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.maybe_no_secrets("new_note", self.new_note.as_deref());
+        v.maybe_no_secrets(
+            "display_name_template",
+            self.display_name_template.as_deref(),
+        );
+    }
+}
+
+impl Validate for api::NodeServiceReportStatusRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+        v.uuid("config_id", &self.config_id);
+    }
+}
+
+impl Validate for api::NodeServiceReportErrorRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceUpdateConfigRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+        v.maybe_uuid("new_org_id", self.new_org_id.as_deref());
+        v.maybe_no_secrets("new_display_name", self.new_display_name.as_deref());
+        v.maybe_no_secrets("new_note", self.new_note.as_deref());
+    }
+}
+
+impl Validate for api::NodeServiceUpgradeImageRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuids("node_ids", &self.node_ids);
+        v.uuid("image_id", &self.image_id);
+        v.maybe_uuid("org_id", self.org_id.as_deref());
+    }
+}
+
+impl Validate for api::NodeServiceStartRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceStopRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceRestartRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceDeleteRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceResizeRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceResizeDiskRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceListDiskResizesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::NodeServiceReviewDiskResizeRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("resize_id", &self.resize_id);
+    }
+}
+
+impl Validate for api::NodeServiceAddDnsAliasRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceRemoveDnsAliasRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceRenameRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceRequestCertificateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceGetCertificateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
+
+impl Validate for api::NodeServiceCollectDebugBundleRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("node_id", &self.node_id);
+    }
+}
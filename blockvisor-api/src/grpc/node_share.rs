@@ -0,0 +1,299 @@
+use chrono::{TimeDelta, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use serde_json::json;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+use url::Url;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::NodeSharePerm;
+use crate::auth::resource::NodeId;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::audit::{AuditEvent, NewAuditLog};
+use crate::model::node::NodeHealthHistory;
+use crate::model::node_share::{NewNodeShare, NodeShareAccess};
+use crate::model::{Node, NodeShare, NodeShareId};
+use crate::util::NanosUtc;
+
+use super::api::node_share_service_server::NodeShareService;
+use super::{Grpc, Metadata, Status, api, common};
+
+/// How long a share link is valid for when no expiry is given.
+const DEFAULT_SHARE_EXPIRY: TimeDelta = TimeDelta::days(7);
+/// The longest a share link may be valid for.
+const MAX_SHARE_EXPIRY: TimeDelta = TimeDelta::days(90);
+/// How many of the most recent accesses of a share are returned.
+const ACCESSES_SHOWN: i64 = 100;
+/// The number of days of uptime history shown to share viewers.
+const UPTIME_HISTORY_DAYS: i64 = 30;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Node share audit error: {0}
+    Audit(#[from] crate::model::audit::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Share expiry must be in the future and within 90 days.
+    Expiry,
+    /// Node share health history error: {0}
+    History(#[from] crate::model::node::history::Error),
+    /// Node share model error: {0}
+    Model(#[from] crate::model::node_share::Error),
+    /// Node share node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Failed to parse expiry: {0}
+    ParseExpiry(crate::util::timestamp::Error),
+    /// Failed to parse NodeShareId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to build share url: {0}
+    ShareUrl(url::ParseError),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) | ShareUrl(_) => Status::internal("Internal error."),
+            Expiry | ParseExpiry(_) => Status::invalid_argument("expires_at"),
+            ParseId(_) => Status::invalid_argument("node_share_id"),
+            ParseNodeId(_) => Status::invalid_argument("node_id"),
+            Audit(err) => err.into(),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            History(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl NodeShareService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::NodeShareServiceCreateRequest>,
+    ) -> Result<Response<api::NodeShareServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::NodeShareServiceListRequest>,
+    ) -> Result<Response<api::NodeShareServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn revoke(
+        &self,
+        req: Request<api::NodeShareServiceRevokeRequest>,
+    ) -> Result<Response<api::NodeShareServiceRevokeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn accesses(
+        &self,
+        req: Request<api::NodeShareServiceAccessesRequest>,
+    ) -> Result<Response<api::NodeShareServiceAccessesResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| accesses(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::NodeShareServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeShareServiceCreateResponse, Error> {
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseNodeId)?;
+    let authz = write
+        .auth_for(&meta, NodeSharePerm::Create, node_id)
+        .await?;
+    let node = Node::by_id(node_id, &mut write).await?;
+
+    let now = Utc::now();
+    let expires_at = match req.expires_at {
+        Some(expires_at) => *NanosUtc::try_from(expires_at).map_err(Error::ParseExpiry)?,
+        None => now + DEFAULT_SHARE_EXPIRY,
+    };
+    if expires_at <= now || expires_at > now + MAX_SHARE_EXPIRY {
+        return Err(Error::Expiry);
+    }
+
+    let new_share = NewNodeShare {
+        node_id,
+        org_id: node.org_id,
+        label: req.label,
+        expires_at,
+        created_by: authz.resource(),
+    };
+    let created = new_share.create(&mut write).await?;
+
+    NewAuditLog::new(Some(node.org_id), &authz, AuditEvent::NodeShareCreated)
+        .with_data(json!({
+            "node_share_id": created.share.id.to_string(),
+            "node_id": node_id.to_string(),
+            "expires_at": expires_at.to_rfc3339(),
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    let url = share_url(
+        &write.ctx.config.email.ui_base_url,
+        created.share.id,
+        &created.token,
+    )?;
+    Ok(api::NodeShareServiceCreateResponse {
+        node_share: Some(created.share.into()),
+        token: created.token,
+        url,
+    })
+}
+
+pub async fn list(
+    req: api::NodeShareServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::NodeShareServiceListResponse, Error> {
+    let node_id: NodeId = req.node_id.parse().map_err(Error::ParseNodeId)?;
+    read.auth_for(&meta, NodeSharePerm::List, node_id).await?;
+
+    let node_shares = NodeShare::by_node(node_id, &mut read)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(api::NodeShareServiceListResponse { node_shares })
+}
+
+pub async fn revoke(
+    req: api::NodeShareServiceRevokeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeShareServiceRevokeResponse, Error> {
+    let id: NodeShareId = req.node_share_id.parse().map_err(Error::ParseId)?;
+    let share = NodeShare::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, NodeSharePerm::Revoke, share.node_id)
+        .await?;
+
+    let revoked = share.revoke(&mut write).await?;
+
+    NewAuditLog::new(Some(revoked.org_id), &authz, AuditEvent::NodeShareRevoked)
+        .with_data(json!({
+            "node_share_id": revoked.id.to_string(),
+            "node_id": revoked.node_id.to_string(),
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::NodeShareServiceRevokeResponse {})
+}
+
+pub async fn accesses(
+    req: api::NodeShareServiceAccessesRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::NodeShareServiceAccessesResponse, Error> {
+    let id: NodeShareId = req.node_share_id.parse().map_err(Error::ParseId)?;
+    let share = NodeShare::by_id(id, &mut read).await?;
+    read.auth_for(&meta, NodeSharePerm::Accesses, share.node_id)
+        .await?;
+
+    let accesses = NodeShareAccess::by_share(id, ACCESSES_SHOWN, &mut read)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(api::NodeShareServiceAccessesResponse { accesses })
+}
+
+/// Render a shared node without auth, recording the access.
+///
+/// Only the status and metrics of the node are shown, as the viewer is not a
+/// member of the org.
+pub async fn view(
+    share_id: &str,
+    token: &str,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::NodeShareView, Error> {
+    let id: NodeShareId = share_id.parse().map_err(Error::ParseId)?;
+    let share = NodeShare::by_id(id, &mut write).await?;
+    share.verify(token)?;
+    share
+        .record_access(meta.client_ip(), meta.user_agent(), &mut write)
+        .await?;
+
+    let node = Node::by_id(share.node_id, &mut write).await?;
+    let since = Utc::now() - TimeDelta::days(UPTIME_HISTORY_DAYS);
+    let history = NodeHealthHistory::by_nodes(&[node.id], since, &mut write).await?;
+    let days = NodeHealthHistory::daily_uptime(history)
+        .remove(&node.id)
+        .unwrap_or_default();
+
+    Ok(api::NodeShareView {
+        display_name: node.display_name,
+        state: common::NodeState::from(node.node_state).into(),
+        health: node
+            .protocol_health
+            .map(|health| common::NodeHealth::from(health).into()),
+        block_height: node
+            .block_height
+            .and_then(|height| u64::try_from(height).ok()),
+        block_age: node.block_age.and_then(|age| u64::try_from(age).ok()),
+        peer_count: node.peer_count.and_then(|count| u32::try_from(count).ok()),
+        daily_uptime: days
+            .into_iter()
+            .map(|(day, uptime_percent)| api::StatusPageUptime {
+                date: day.to_string(),
+                uptime_percent,
+            })
+            .collect(),
+        expires_at: Some(NanosUtc::from(share.expires_at).into()),
+        updated_at: Some(NanosUtc::from(Utc::now()).into()),
+    })
+}
+
+/// The UI link to a share, carrying its token as a query parameter.
+fn share_url(base_url: &Url, share_id: NodeShareId, token: &str) -> Result<String, Error> {
+    let base = base_url.as_str().trim_end_matches('/');
+    let mut url = Url::parse(&format!("{base}/share/{share_id}")).map_err(Error::ShareUrl)?;
+    url.query_pairs_mut().append_pair("token", token);
+    Ok(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn share_url_escapes_the_token() {
+        let base = Url::parse("https://app.example.com/").unwrap();
+        let id = NodeShareId::from(uuid::Uuid::nil());
+        let url = share_url(&base, id, "ab+c/d").unwrap();
+        assert_eq!(
+            url,
+            "https://app.example.com/share/00000000-0000-0000-0000-000000000000?token=ab%2Bc%2Fd"
+        );
+    }
+}
@@ -4,27 +4,54 @@ use std::collections::HashSet;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
 use futures::future::OptionFuture;
+use serde_json::json;
 use thiserror::Error;
 use tonic::{Request, Response};
-use tracing::error;
-
-use crate::auth::Authorize;
-use crate::auth::rbac::{OrgAddressPerm, OrgAdminPerm, OrgBillingPerm, OrgPerm, OrgProvisionPerm};
-use crate::auth::resource::{OrgId, UserId};
+use tracing::{error, warn};
+
+use crate::auth::rbac::{
+    BillingPerm, NodeAdminPerm, NodePerm, OrgAddressPerm, OrgAdminPerm, OrgBillingPerm, OrgPerm,
+    OrgProvisionPerm,
+};
+use crate::auth::resource::{OrgId, Resource, UserId};
+use crate::auth::{AuthZ, Authorize};
 use crate::database::{Conn, ReadConn, Transaction, WriteConn};
 use crate::model::address::NewAddress;
+use crate::model::approval::ApprovalAction;
+use crate::model::audit::{AuditEvent, NewAuditLog};
+use crate::model::command::NewCommand;
+use crate::model::freeze_window::NewFreezeWindow;
+use crate::model::node::{NodeSummary, UpdateNode};
 use crate::model::org::{NewOrg, OrgFilter, OrgSearch, OrgSort, UpdateOrg};
+use crate::model::org_suspension::{self, SuspensionReason};
+use crate::model::org_transfer::{NewOrgTransfer, OrgTransfer, OrgTransferId};
+use crate::model::org_trial;
+use crate::model::plan::{Entitlements, Plan, PlanTier};
+use crate::model::project::{NewProject, Project, ProjectId, UpdateProject};
 use crate::model::rbac::{OrgUsers, RbacUser};
-use crate::model::{Address, Invitation, Org, Token, User};
+use crate::model::user::setting::UserLocale;
+use crate::model::{
+    Address, CommandType, FreezeWindow, Invitation, LegalHold, Node, Org, OrgSnapshot, Partner,
+    Protocol, Token, User,
+};
 use crate::util::{HashVec, NanosUtc};
 
 use super::api::org_service_server::OrgService;
+use super::approval;
+use super::relations::NodeRelations;
+use super::validate::{Validate, Validator};
 use super::{Grpc, Metadata, Status, api, common};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
     /// Address error: {0}
     Address(#[from] crate::model::address::Error),
+    /// Org approval error: {0}
+    Approval(#[from] crate::grpc::approval::Error),
+    /// Org agent upgrade error: {0}
+    AgentUpgrade(#[from] crate::model::agent_upgrade::Error),
+    /// Org audit log error: {0}
+    Audit(#[from] crate::model::audit::Error),
     /// Auth check failed: {0}
     Auth(#[from] crate::auth::Error),
     /// No org found after conversion.
@@ -33,36 +60,104 @@ pub enum Error {
     Claims(#[from] crate::auth::claims::Error),
     /// Claims Resource is not a user.
     ClaimsNotUser,
+    /// Org command error: {0}
+    Command(#[from] crate::model::command::Error),
+    /// Org grpc command error: {0}
+    CommandGrpc(#[from] crate::grpc::command::Error),
     /// Can't delete personal org.
     DeletePersonal,
+    /// Can't delete an org with {0} running nodes without `force`.
+    DeleteRunningNodes(usize),
     /// Diesel failure: {0}
     Diesel(#[from] diesel::result::Error),
+    /// Failed to parse disk_auto_approve_bytes: {0}
+    DiskAutoApprove(std::num::TryFromIntError),
+    /// Failed to parse disk_quota_bytes: {0}
+    DiskQuota(std::num::TryFromIntError),
+    /// Org email error: {0}
+    Email(#[from] crate::email::Error),
     /// Failed to parse filter limit as i64: {0}
     FilterLimit(std::num::TryFromIntError),
     /// Failed to parse filter offset as i64: {0}
     FilterOffset(std::num::TryFromIntError),
+    /// Org freeze window error: {0}
+    FreezeWindow(#[from] crate::model::freeze_window::Error),
     /// Org invitation error: {0}
     Invitation(#[from] crate::model::invitation::Error),
+    /// Org legal hold error: {0}
+    LegalHold(#[from] crate::model::legal_hold::Error),
+    /// Provisioning tokens can't be reset by an impersonated user.
+    Impersonated,
+    /// Failed to create imported node: {0}
+    ImportNode(Box<crate::grpc::node::Error>),
     /// The request is missing the `address` fields.
     MissingAddress,
+    /// Failed to parse node_display_name_max_len: {0}
+    NodeDisplayNameMaxLen(std::num::TryFromIntError),
+    /// Failed to parse node_note_max_len: {0}
+    NodeNoteMaxLen(std::num::TryFromIntError),
+    /// Org node policy error: {0}
+    NodePolicy(#[from] crate::model::node::policy::Error),
+    /// Failed to parse node_quota: {0}
+    NodeQuota(std::num::TryFromIntError),
+    /// Failed to build a NodeStart command for visibility.
+    NoNodeStart,
+    /// Failed to build a NodeStop command for visibility.
+    NoNodeStop,
     /// Stripe is not configured.
     NoStripe,
+    /// Failed to build a HostKeys command for imported keys.
+    NoHostKeys,
     /// No customer exists in stripe for org `{0}`.
     NoStripeCustomer(OrgId),
     /// No subscription exists in stripe for org `{0}`.
     NoStripeSubscription(OrgId),
+    /// Org host error: {0}
+    Host(#[from] crate::model::host::Error),
+    /// Org node error: {0}
+    Node(#[from] crate::model::node::Error),
     /// Org model error: {0}
     Org(#[from] crate::model::org::Error),
+    /// Org snapshot error: {0}
+    OrgSnapshot(#[from] crate::model::org_snapshot::Error),
+    /// Org suspension error: {0}
+    OrgSuspension(#[from] crate::model::org_suspension::Error),
+    /// Org transfer error: {0}
+    OrgTransfer(#[from] crate::model::org_transfer::Error),
+    /// Org trial error: {0}
+    OrgTrial(#[from] crate::model::org_trial::Error),
+    /// Failed to parse FreezeWindowId: {0}
+    ParseFreezeWindowId(uuid::Error),
     /// Failed to parse `id` as OrgId: {0}
     ParseId(uuid::Error),
+    /// Org partner error: {0}
+    Partner(#[from] crate::model::partner::Error),
+    /// Org plan error: {0}
+    Plan(#[from] crate::model::plan::Error),
     /// Failed to parse non-zero count as u64: {0}
     ParseMax(std::num::TryFromIntError),
+    /// Failed to parse imported NodeId: {0}
+    ParseNodeId(uuid::Error),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
+    /// Failed to parse ProjectId: {0}
+    ParseProjectId(uuid::Error),
+    /// Failed to parse OrgTransferId: {0}
+    ParseTransferId(uuid::Error),
     /// Failed to parse UserId: {0}
     ParseUserId(uuid::Error),
+    /// Org project error: {0}
+    Project(#[from] crate::model::project::Error),
+    /// The user to add is not a member of the org.
+    ProjectNotMember,
+    /// Org protocol error: {0}
+    Protocol(#[from] crate::model::protocol::Error),
     /// Org rbac error: {0}
     Rbac(#[from] crate::model::rbac::Error),
+    /// Org region error: {0}
+    Region(#[from] crate::model::region::Error),
+    /// Org relations error: {0}
+    Relations(#[from] crate::grpc::relations::Error),
     /// Org resource error: {0}
     Resource(#[from] crate::auth::resource::Error),
     /// Cannot remove last owner from an org.
@@ -81,10 +176,26 @@ pub enum Error {
     StripeInvoice(#[from] crate::stripe::api::invoice::Error),
     /// Org token error: {0}
     Token(#[from] crate::model::token::Error),
+    /// Org transfer has already completed.
+    TransferCompleted,
+    /// The new owner is not a member of the org.
+    TransferNotMember,
+    /// The user requesting the transfer is no longer an org owner.
+    TransferOwnerChanged,
+    /// Can't transfer ownership of a personal org.
+    TransferPersonal,
+    /// Can't transfer ownership to the current owner.
+    TransferToSelf,
     /// The requested sort field is unknown.
     UnknownSortField,
     /// Org user error: {0}
     User(#[from] crate::model::user::Error),
+    /// Org user settings error: {0}
+    UserSettings(#[from] crate::model::user::setting::Error),
+    /// Org request validation failed: {0}
+    Validate(#[from] crate::grpc::validate::Error),
+    /// Token is not valid for this org transfer.
+    WrongTransfer,
 }
 
 impl From<Error> for Status {
@@ -92,31 +203,71 @@ impl From<Error> for Status {
         use Error::*;
         error!("{err}");
         match err {
-            ClaimsNotUser | DeletePersonal | RemoveNotSelf => Status::forbidden("Access denied."),
-            ConvertNoOrg | Diesel(_) | ParseMax(_) | Stripe(_) | StripeCurrency(_)
-            | StripeInvoice(_) => Status::internal("Internal error."),
+            ClaimsNotUser | DeletePersonal | Impersonated | RemoveNotSelf | TransferPersonal
+            | WrongTransfer => Status::forbidden("Access denied."),
+            ConvertNoOrg | Diesel(_) | Email(_) | NoHostKeys | NoNodeStart | NoNodeStop
+            | ParseMax(_) | ParseNodeId(_) | Stripe(_) | StripeCurrency(_) | StripeInvoice(_) => {
+                Status::internal("Internal error.")
+            }
+            DeleteRunningNodes(_) => Status::failed_precondition("Org has running nodes."),
+            DiskAutoApprove(_) => Status::invalid_argument("disk_auto_approve_bytes"),
+            DiskQuota(_) => Status::invalid_argument("disk_quota_bytes"),
             FilterLimit(_) => Status::invalid_argument("limit"),
             FilterOffset(_) => Status::invalid_argument("offset"),
+            NodeDisplayNameMaxLen(_) => Status::invalid_argument("node_display_name_max_len"),
+            NodeNoteMaxLen(_) => Status::invalid_argument("node_note_max_len"),
+            NodeQuota(_) => Status::invalid_argument("node_quota"),
             MissingAddress => Status::failed_precondition("User has no address."),
             NoStripe => Status::failed_precondition("Stripe is not configured."),
             NoStripeCustomer(_) => Status::failed_precondition("No customer for that org."),
             NoStripeSubscription(_) => Status::failed_precondition("No subscription for that org."),
+            ParseFreezeWindowId(_) => Status::invalid_argument("window_id"),
             ParseId(_) => Status::invalid_argument("id"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseProjectId(_) => Status::invalid_argument("project_id"),
+            ParseTransferId(_) => Status::invalid_argument("transfer_id"),
+            ProjectNotMember => Status::failed_precondition("User is not an org member."),
             ParseUserId(_) => Status::invalid_argument("user_id"),
             RemoveLastOwner => Status::failed_precondition("Can't remove last org owner."),
             SearchOperator(_) => Status::invalid_argument("search.operator"),
             SortOrder(_) => Status::invalid_argument("sort.order"),
+            TransferCompleted => Status::failed_precondition("Transfer already completed."),
+            TransferNotMember => Status::failed_precondition("New owner is not an org member."),
+            TransferOwnerChanged => Status::failed_precondition("Org owner has changed."),
+            TransferToSelf => Status::invalid_argument("new_owner_id"),
             UnknownSortField => Status::invalid_argument("sort.field"),
             Address(err) => err.into(),
+            AgentUpgrade(err) => err.into(),
+            Approval(err) => err.into(),
+            Audit(err) => err.into(),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
+            Command(err) => err.into(),
+            CommandGrpc(err) => err.into(),
+            FreezeWindow(err) => err.into(),
+            Host(err) => err.into(),
+            ImportNode(err) => (*err).into(),
             Invitation(err) => err.into(),
+            LegalHold(err) => err.into(),
+            Node(err) => err.into(),
+            NodePolicy(err) => err.into(),
             Org(err) => err.into(),
+            OrgSnapshot(err) => err.into(),
+            OrgSuspension(err) => err.into(),
+            OrgTransfer(err) => err.into(),
+            OrgTrial(err) => err.into(),
+            Partner(err) => err.into(),
+            Plan(err) => err.into(),
+            Project(err) => err.into(),
+            Protocol(err) => err.into(),
             Rbac(err) => err.into(),
+            Region(err) => err.into(),
+            Relations(err) => err.into(),
             Resource(err) => err.into(),
             Token(err) => err.into(),
             User(err) => err.into(),
+            UserSettings(err) => err.into(),
+            Validate(err) => err.into(),
         }
     }
 }
@@ -168,6 +319,15 @@ impl OrgService for Grpc {
             .await
     }
 
+    async fn restore_deleted(
+        &self,
+        req: Request<api::OrgServiceRestoreDeletedRequest>,
+    ) -> Result<Response<api::OrgServiceRestoreDeletedResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| restore_deleted(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn remove_member(
         &self,
         req: Request<api::OrgServiceRemoveMemberRequest>,
@@ -177,6 +337,24 @@ impl OrgService for Grpc {
             .await
     }
 
+    async fn transfer_ownership(
+        &self,
+        req: Request<api::OrgServiceTransferOwnershipRequest>,
+    ) -> Result<Response<api::OrgServiceTransferOwnershipResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| transfer_ownership(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn confirm_transfer(
+        &self,
+        req: Request<api::OrgServiceConfirmTransferRequest>,
+    ) -> Result<Response<api::OrgServiceConfirmTransferResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| confirm_transfer(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn get_provision_token(
         &self,
         req: Request<api::OrgServiceGetProvisionTokenRequest>,
@@ -222,6 +400,51 @@ impl OrgService for Grpc {
             .await
     }
 
+    async fn list_plans(
+        &self,
+        req: Request<api::OrgServiceListPlansRequest>,
+    ) -> Result<Response<api::OrgServiceListPlansResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_plans(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update_plan(
+        &self,
+        req: Request<api::OrgServiceUpdatePlanRequest>,
+    ) -> Result<Response<api::OrgServiceUpdatePlanResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update_plan(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn start_trial(
+        &self,
+        req: Request<api::OrgServiceStartTrialRequest>,
+    ) -> Result<Response<api::OrgServiceStartTrialResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| start_trial(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn suspend(
+        &self,
+        req: Request<api::OrgServiceSuspendRequest>,
+    ) -> Result<Response<api::OrgServiceSuspendResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| suspend(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn reinstate(
+        &self,
+        req: Request<api::OrgServiceReinstateRequest>,
+    ) -> Result<Response<api::OrgServiceReinstateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| reinstate(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn get_address(
         &self,
         req: Request<api::OrgServiceGetAddressRequest>,
@@ -257,6 +480,123 @@ impl OrgService for Grpc {
         self.read(|read| get_invoices(req, meta.into(), read).scope_boxed())
             .await
     }
+
+    async fn summary(
+        &self,
+        req: Request<api::OrgServiceSummaryRequest>,
+    ) -> Result<Response<api::OrgServiceSummaryResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| summary(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn export_configuration(
+        &self,
+        req: Request<api::OrgServiceExportConfigurationRequest>,
+    ) -> Result<Response<api::OrgServiceExportConfigurationResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| export_configuration(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn import_configuration(
+        &self,
+        req: Request<api::OrgServiceImportConfigurationRequest>,
+    ) -> Result<Response<api::OrgServiceImportConfigurationResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| import_configuration(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn create_project(
+        &self,
+        req: Request<api::OrgServiceCreateProjectRequest>,
+    ) -> Result<Response<api::OrgServiceCreateProjectResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create_project(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_project(
+        &self,
+        req: Request<api::OrgServiceGetProjectRequest>,
+    ) -> Result<Response<api::OrgServiceGetProjectResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_project(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list_projects(
+        &self,
+        req: Request<api::OrgServiceListProjectsRequest>,
+    ) -> Result<Response<api::OrgServiceListProjectsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_projects(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update_project(
+        &self,
+        req: Request<api::OrgServiceUpdateProjectRequest>,
+    ) -> Result<Response<api::OrgServiceUpdateProjectResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update_project(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn delete_project(
+        &self,
+        req: Request<api::OrgServiceDeleteProjectRequest>,
+    ) -> Result<Response<api::OrgServiceDeleteProjectResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_project(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn add_project_member(
+        &self,
+        req: Request<api::OrgServiceAddProjectMemberRequest>,
+    ) -> Result<Response<api::OrgServiceAddProjectMemberResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| add_project_member(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn remove_project_member(
+        &self,
+        req: Request<api::OrgServiceRemoveProjectMemberRequest>,
+    ) -> Result<Response<api::OrgServiceRemoveProjectMemberResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| remove_project_member(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn create_freeze_window(
+        &self,
+        req: Request<api::OrgServiceCreateFreezeWindowRequest>,
+    ) -> Result<Response<api::OrgServiceCreateFreezeWindowResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create_freeze_window(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_freeze_windows(
+        &self,
+        req: Request<api::OrgServiceListFreezeWindowsRequest>,
+    ) -> Result<Response<api::OrgServiceListFreezeWindowsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_freeze_windows(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn delete_freeze_window(
+        &self,
+        req: Request<api::OrgServiceDeleteFreezeWindowRequest>,
+    ) -> Result<Response<api::OrgServiceDeleteFreezeWindowResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_freeze_window(req, meta.into(), write).scope_boxed())
+            .await
+    }
 }
 
 pub async fn create(
@@ -264,6 +604,7 @@ pub async fn create(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::OrgServiceCreateResponse, Error> {
+    req.validate()?;
     let authz = write.auth(&meta, OrgPerm::Create).await?;
     let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
     let user = User::by_id(user_id, &mut write).await?;
@@ -272,7 +613,15 @@ pub async fn create(
         name: &req.name,
         is_personal: false,
     };
-    let org = new_org.create(user.id, &mut write).await?;
+    let mut org = new_org.create(user.id, &mut write).await?;
+
+    // orgs created by a referred user are attributed to the same partner
+    let personal = Org::find_personal(user.id, &mut write).await?;
+    if let Some(partner_id) = personal.partner_id {
+        let partner = Partner::by_id(partner_id, &mut write).await?;
+        partner.attribute(org.id, &mut write).await?;
+        org = Org::by_id(org.id, &mut write).await?;
+    }
     let org = api::Org::from_model(&org, &mut write).await?;
 
     let created_by = common::Resource::from(user.id);
@@ -287,6 +636,7 @@ pub async fn get(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceGetResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseId)?;
     read.auth_or_for(&meta, OrgAdminPerm::Get, OrgPerm::Get, org_id)
         .await?;
@@ -302,6 +652,7 @@ pub async fn list(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceListResponse, Error> {
+    req.validate()?;
     let filter = req.into_filter()?;
     if let Some(user_id) = filter.member_id {
         read.auth_for(&meta, OrgPerm::List, user_id).await?
@@ -320,15 +671,62 @@ pub async fn update(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::OrgServiceUpdateResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseId)?;
-    let authz = write
-        .auth_or_for(&meta, OrgAdminPerm::Update, OrgPerm::Update, org_id)
-        .await?;
+    // the disk quota of an org is only set by blockjoy
+    let authz = if req.disk_quota_bytes.is_some() {
+        write.auth(&meta, OrgAdminPerm::Update).await?
+    } else {
+        write
+            .auth_or_for(&meta, OrgAdminPerm::Update, OrgPerm::Update, org_id)
+            .await?
+    };
+
+    let agent_upgrade_policy = req
+        .agent_upgrade_policy
+        .map(|_| req.agent_upgrade_policy().try_into())
+        .transpose()?;
+
+    // a disk limit of zero removes it
+    let disk_limit = |bytes: u64| (bytes > 0).then(|| i64::try_from(bytes)).transpose();
+    let disk_quota_bytes = req
+        .disk_quota_bytes
+        .map(|bytes| disk_limit(bytes).map_err(Error::DiskQuota))
+        .transpose()?;
+    let disk_auto_approve_bytes = req
+        .disk_auto_approve_bytes
+        .map(|bytes| disk_limit(bytes).map_err(Error::DiskAutoApprove))
+        .transpose()?;
+
+    // a node name or note length of zero removes the limit
+    let len_limit = |len: u32| (len > 0).then(|| i32::try_from(len)).transpose();
+    let node_display_name_max_len = req
+        .node_display_name_max_len
+        .map(|len| len_limit(len).map_err(Error::NodeDisplayNameMaxLen))
+        .transpose()?;
+    let node_note_max_len = req
+        .node_note_max_len
+        .map(|len| len_limit(len).map_err(Error::NodeNoteMaxLen))
+        .transpose()?;
+    let node_display_name_charset = req
+        .node_display_name_charset
+        .map(|_| req.node_display_name_charset().try_into())
+        .transpose()?;
 
     let update = UpdateOrg {
         id: org_id,
         name: req.name.as_deref(),
         address_id: None,
+        agent_upgrade_policy,
+        disk_quota_bytes,
+        disk_auto_approve_bytes,
+        node_display_name_max_len,
+        node_note_max_len,
+        node_display_name_unique: req.node_display_name_unique,
+        node_display_name_charset,
+        require_approval_node_delete: req.require_approval_node_delete,
+        require_approval_node_upgrade: req.require_approval_node_upgrade,
+        require_approval_org_transfer: req.require_approval_org_transfer,
     };
     let org = update.update(&mut write).await?;
     let org = api::Org::from_model(&org, &mut write).await?;
@@ -345,13 +743,46 @@ pub async fn delete(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::OrgServiceDeleteResponse, Error> {
-    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseId)?;
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     let authz = write.auth_for(&meta, OrgPerm::Delete, org_id).await?;
 
     let org = Org::by_id(org_id, &mut write).await?;
     if org.is_personal {
         return Err(Error::DeletePersonal);
     }
+    LegalHold::check_org(org_id, &mut write).await?;
+    FreezeWindow::check(org_id, &authz, &mut write).await?;
+
+    let running = Node::running_by_org_id(org_id, &mut write).await?;
+    if !running.is_empty() && !req.force {
+        return Err(Error::DeleteRunningNodes(running.len()));
+    }
+    for node in &running {
+        let stop_cmd = NewCommand::node(node, CommandType::NodeStop)?
+            .create(&mut write)
+            .await?;
+        let stop_cmd = api::Command::from(&stop_cmd, &authz, &mut write)
+            .await?
+            .ok_or(Error::NoNodeStop)?;
+        write.mqtt(stop_cmd);
+    }
+
+    // the subscription is only cancelled once the delete has committed
+    if let Some(customer_id) = org.customer_id(&write.ctx.keyring)? {
+        let stripe = write.ctx.stripe.clone().ok_or(Error::NoStripe)?;
+        write.after_commit(async move {
+            match stripe.get_subscription_by_customer(&customer_id).await {
+                Ok(Some(subscription)) => {
+                    if let Err(err) = stripe.cancel_subscription(&subscription.id).await {
+                        warn!("Failed to cancel subscription of org {org_id}: {err}");
+                    }
+                }
+                Ok(None) => (),
+                Err(err) => warn!("Failed to find subscription of org {org_id}: {err}"),
+            }
+        });
+    }
 
     org.delete(&mut write).await?;
 
@@ -363,7 +794,33 @@ pub async fn delete(
     let msg = api::OrgMessage::deleted(&org, deleted_by);
     write.mqtt(msg);
 
-    Ok(api::OrgServiceDeleteResponse {})
+    let org = Org::deleted_by_id(org_id, &mut write).await?;
+    Ok(api::OrgServiceDeleteResponse {
+        purge_at: org.purge_after().map(|time| NanosUtc::from(time).into()),
+    })
+}
+
+/// Restore an org that was deleted within the last `DELETE_GRACE_PERIOD`.
+///
+/// Cancelled stripe subscriptions are not restored and will be recreated as
+/// new nodes are launched.
+pub async fn restore_deleted(
+    req: api::OrgServiceRestoreDeletedRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceRestoreDeletedResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth_for(&meta, OrgPerm::Restore, org_id).await?;
+
+    let org = Org::deleted_by_id(org_id, &mut write).await?;
+    let org = org.restore(&mut write).await?;
+
+    let org = api::Org::from_model(&org, &mut write).await?;
+    let msg = api::OrgMessage::created(org.clone(), common::Resource::from(&authz));
+    write.mqtt(msg);
+
+    Ok(api::OrgServiceRestoreDeletedResponse { org: Some(org) })
 }
 
 pub async fn remove_member(
@@ -371,6 +828,7 @@ pub async fn remove_member(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::OrgServiceRemoveMemberResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     let user_id = req.user_id.parse().map_err(Error::ParseUserId)?;
 
@@ -408,11 +866,233 @@ pub async fn remove_member(
     Ok(api::OrgServiceRemoveMemberResponse {})
 }
 
+/// Start transferring org ownership to another member of the org.
+///
+/// Both the current and the new owner are emailed a confirmation link, and
+/// the transfer is only applied once both have called `confirm_transfer`. If
+/// the org requires transfers to be approved, a pending approval is returned
+/// instead and the transfer only starts once another org admin approves it.
+pub async fn transfer_ownership(
+    req: api::OrgServiceTransferOwnershipRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceTransferOwnershipResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let new_owner_id: UserId = req.new_owner_id.parse().map_err(Error::ParseUserId)?;
+    let authz = write
+        .auth_for(&meta, OrgPerm::TransferOwnership, org_id)
+        .await?;
+    let old_owner_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let org = Org::by_id(org_id, &mut write).await?;
+    if org.is_personal {
+        return Err(Error::TransferPersonal);
+    } else if old_owner_id == new_owner_id {
+        return Err(Error::TransferToSelf);
+    } else if !Org::has_user(org_id, new_owner_id, &mut write).await? {
+        return Err(Error::TransferNotMember);
+    }
+
+    let action = ApprovalAction::OrgTransfer;
+    if let Some(approval) =
+        approval::request(action, org_id, &req, &authz, &meta, &mut write).await?
+    {
+        return Ok(api::OrgServiceTransferOwnershipResponse {
+            transfer_id: String::new(),
+            approval: Some(approval.into()),
+        });
+    }
+
+    let transfer =
+        start_transfer(&org, old_owner_id, new_owner_id, &authz, &meta, &mut write).await?;
+
+    Ok(api::OrgServiceTransferOwnershipResponse {
+        transfer_id: transfer.id.to_string(),
+        approval: None,
+    })
+}
+
+/// Start an approved transfer of org ownership.
+///
+/// The requester must still be an owner, and the new owner still a member.
+pub(super) async fn approved_transfer(
+    req: api::OrgServiceTransferOwnershipRequest,
+    requested_by: Resource,
+    authz: &AuthZ,
+    meta: &Metadata,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<(), Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let new_owner_id: UserId = req.new_owner_id.parse().map_err(Error::ParseUserId)?;
+    let old_owner_id = requested_by.user().ok_or(Error::ClaimsNotUser)?;
+
+    let owners = RbacUser::org_owners(org_id, write).await?;
+    if !owners.contains(&old_owner_id) {
+        return Err(Error::TransferOwnerChanged);
+    } else if !Org::has_user(org_id, new_owner_id, write).await? {
+        return Err(Error::TransferNotMember);
+    }
+
+    let org = Org::by_id(org_id, write).await?;
+    start_transfer(&org, old_owner_id, new_owner_id, authz, meta, write).await?;
+
+    Ok(())
+}
+
+async fn start_transfer(
+    org: &Org,
+    old_owner_id: UserId,
+    new_owner_id: UserId,
+    authz: &AuthZ,
+    meta: &Metadata,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<OrgTransfer, Error> {
+    OrgTransfer::cancel_pending(org.id, write).await?;
+    let new_transfer = NewOrgTransfer {
+        org_id: org.id,
+        old_owner_id,
+        new_owner_id,
+    };
+    let transfer = new_transfer.create(write).await?;
+
+    // the confirmation links are only emailed once the transfer has committed
+    if let Some(email) = write.ctx.email.clone() {
+        let old_owner = User::by_id(old_owner_id, write).await?;
+        let new_owner = User::by_id(new_owner_id, write).await?;
+        let old_locale = UserLocale::by_user(old_owner_id, write).await?;
+        let new_locale = UserLocale::by_user(new_owner_id, write).await?;
+        let (transfer, org) = (transfer.clone(), org.clone());
+        write.after_commit(async move {
+            for (recipient, locale) in [(&old_owner, &old_locale), (&new_owner, &new_locale)] {
+                if let Err(err) = email
+                    .org_transfer(&transfer, &org, &old_owner, &new_owner, recipient, locale)
+                    .await
+                {
+                    warn!(
+                        "Failed to email org transfer {} to {}: {err}",
+                        transfer.id, recipient.id
+                    );
+                }
+            }
+        });
+    } else {
+        warn!("Unable to send org transfer emails without email configured");
+    }
+
+    NewAuditLog::new(
+        Some(org.id),
+        authz,
+        AuditEvent::OrgOwnershipTransferRequested,
+    )
+    .with_data(json!({
+        "transfer_id": transfer.id.to_string(),
+        "old_owner_id": old_owner_id.to_string(),
+        "new_owner_id": new_owner_id.to_string(),
+    }))
+    .with_request_id(meta.request_id())
+    .create(write)
+    .await?;
+
+    Ok(transfer)
+}
+
+/// Confirm an org transfer using the token emailed to one of its parties.
+///
+/// Once both parties have confirmed, the owner role is moved to the new owner
+/// and the billing contact is updated to their email.
+pub async fn confirm_transfer(
+    req: api::OrgServiceConfirmTransferRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceConfirmTransferResponse, Error> {
+    req.validate()?;
+    let transfer_id: OrgTransferId = req.transfer_id.parse().map_err(Error::ParseTransferId)?;
+    let authz = write.auth(&meta, OrgPerm::ConfirmTransfer).await?;
+    let user_id = authz.resource().user().ok_or(Error::ClaimsNotUser)?;
+
+    let token_transfer_id = authz
+        .get_data("transfer_id")
+        .and_then(|id| id.parse::<OrgTransferId>().ok());
+    if token_transfer_id != Some(transfer_id) {
+        return Err(Error::WrongTransfer);
+    }
+
+    let transfer = OrgTransfer::by_id(transfer_id, &mut write).await?;
+    if transfer.is_completed() {
+        return Err(Error::TransferCompleted);
+    }
+
+    let transfer = transfer.confirm(user_id, &mut write).await?;
+    let org_id = transfer.org_id;
+    let data = json!({
+        "transfer_id": transfer.id.to_string(),
+        "old_owner_id": transfer.old_owner_id.to_string(),
+        "new_owner_id": transfer.new_owner_id.to_string(),
+    });
+
+    NewAuditLog::new(
+        Some(org_id),
+        &authz,
+        AuditEvent::OrgOwnershipTransferConfirmed,
+    )
+    .with_data(data.clone())
+    .with_request_id(meta.request_id())
+    .create(&mut write)
+    .await?;
+
+    if !transfer.is_confirmed() {
+        return Ok(api::OrgServiceConfirmTransferResponse { completed: false });
+    }
+
+    let owners = RbacUser::org_owners(org_id, &mut write).await?;
+    if !owners.contains(&transfer.old_owner_id) {
+        return Err(Error::TransferOwnerChanged);
+    } else if !Org::has_user(org_id, transfer.new_owner_id, &mut write).await? {
+        return Err(Error::TransferNotMember);
+    }
+
+    Org::transfer_owner(
+        org_id,
+        transfer.old_owner_id,
+        transfer.new_owner_id,
+        &mut write,
+    )
+    .await?;
+    let transfer = transfer.complete(&mut write).await?;
+
+    // the billing contact is only updated once the transfer has committed
+    let org = Org::by_id(org_id, &mut write).await?;
+    if let Some(customer_id) = org.customer_id(&write.ctx.keyring)? {
+        let stripe = write.ctx.stripe.clone().ok_or(Error::NoStripe)?;
+        let new_owner = User::by_id(transfer.new_owner_id, &mut write).await?;
+        write.after_commit(async move {
+            if let Err(err) = stripe.set_email(&customer_id, &new_owner.email).await {
+                warn!("Failed to set billing email of org {org_id}: {err}");
+            }
+        });
+    }
+
+    NewAuditLog::new(Some(org_id), &authz, AuditEvent::OrgOwnershipTransferred)
+        .with_data(data)
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    let org = api::Org::from_model(&org, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    let msg = api::OrgMessage::updated(org, updated_by);
+    write.mqtt(msg);
+
+    Ok(api::OrgServiceConfirmTransferResponse { completed: true })
+}
+
 pub async fn get_provision_token(
     req: api::OrgServiceGetProvisionTokenRequest,
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceGetProvisionTokenResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     read.auth_for(&meta, OrgProvisionPerm::GetToken, org_id)
         .await?;
@@ -430,10 +1110,15 @@ pub async fn reset_provision_token(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::OrgServiceResetProvisionTokenResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
-    write
+    let authz = write
         .auth_for(&meta, OrgProvisionPerm::ResetToken, org_id)
         .await?;
+    // an impersonated user can't mint host provisioning credentials
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
 
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
     let new_token = Token::reset_host_provision(user_id, org_id, &mut write).await?;
@@ -448,6 +1133,7 @@ pub async fn init_card(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::OrgServiceInitCardResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseUserId)?;
     write
@@ -471,17 +1157,18 @@ pub async fn list_payment_methods(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceListPaymentMethodsResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     read.auth_for(&meta, OrgBillingPerm::ListPaymentMethods, org_id)
         .await?;
 
     let org = Org::by_id(org_id, &mut read).await?;
-    let payment_methods = if let Some(customer_id) = &org.stripe_customer_id {
+    let payment_methods = if let Some(customer_id) = org.customer_id(&read.ctx.keyring)? {
         read.ctx
             .stripe
             .as_ref()
             .ok_or(Error::NoStripe)?
-            .list_payment_methods(customer_id)
+            .list_payment_methods(&customer_id)
             .await?
     } else {
         vec![]
@@ -518,13 +1205,14 @@ pub async fn billing_details(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceBillingDetailsResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     read.auth_for(&meta, OrgBillingPerm::GetBillingDetails, org_id)
         .await?;
 
     let org = Org::by_id(org_id, &mut read).await?;
 
-    let Some(customer_id) = org.stripe_customer_id.as_deref() else {
+    let Some(customer_id) = org.customer_id(&read.ctx.keyring)? else {
         return Ok(Default::default());
     };
     let subscription = read
@@ -532,7 +1220,7 @@ pub async fn billing_details(
         .stripe
         .as_ref()
         .ok_or(Error::NoStripe)?
-        .get_subscription_by_customer(customer_id)
+        .get_subscription_by_customer(&customer_id)
         .await?
         .ok_or_else(|| Error::NoStripeSubscription(org_id))?;
 
@@ -565,84 +1253,290 @@ pub async fn billing_details(
     })
 }
 
-pub async fn get_address(
-    req: api::OrgServiceGetAddressRequest,
+pub async fn list_plans(
+    req: api::OrgServiceListPlansRequest,
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
-) -> Result<api::OrgServiceGetAddressResponse, Error> {
+) -> Result<api::OrgServiceListPlansResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
-    read.auth_for(&meta, OrgAddressPerm::Get, org_id).await?;
+    read.auth_for(&meta, OrgBillingPerm::ListPlans, org_id)
+        .await?;
 
     let org = Org::by_id(org_id, &mut read).await?;
-    let Some(customer_id) = org.stripe_customer_id.as_ref() else {
-        return Ok(Default::default());
-    };
-    let address = read
-        .ctx
-        .stripe
-        .as_ref()
-        .ok_or(Error::NoStripe)?
-        .get_address(customer_id)
-        .await?;
+    let plans = Plan::all(&mut read).await?;
 
-    Ok(api::OrgServiceGetAddressResponse {
-        address: address.map(Into::into),
+    Ok(api::OrgServiceListPlansResponse {
+        plans: plans.into_iter().map(Into::into).collect(),
+        current_tier: api::PlanTier::from(org.plan_tier).into(),
     })
 }
 
-pub async fn set_address(
-    req: api::OrgServiceSetAddressRequest,
+/// Move an org to another plan, once everything it runs fits in that plan.
+///
+/// The subscription item of the old plan is replaced by one for the price of
+/// the new plan, unless the new plan is free or the caller is billing exempt.
+/// This converts the trial of an org on one, even to the plan of its trial.
+pub async fn update_plan(
+    req: api::OrgServiceUpdatePlanRequest,
     meta: Metadata,
-    mut write: ReadConn<'_, '_>,
-) -> Result<api::OrgServiceSetAddressResponse, Error> {
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceUpdatePlanResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
-    write.auth_for(&meta, OrgAddressPerm::Set, org_id).await?;
+    let authz = write
+        .auth_for(&meta, OrgBillingPerm::UpdatePlan, org_id)
+        .await?;
+    let tier = PlanTier::try_from(req.tier())?;
 
-    let stripe = write.ctx.stripe.as_ref().ok_or(Error::NoStripe)?;
     let org = Org::by_id(org_id, &mut write).await?;
-    let address = req.address.ok_or(Error::MissingAddress)?;
-    let (org, customer_id) = if let Some(customer_id) = org.stripe_customer_id.clone() {
-        (org, customer_id)
-    } else {
-        let owner = User::owner(org_id, &mut write).await?;
-        let customer_id = stripe.create_customer(&org, &owner, None).await?.id;
-        let org = org.set_customer_id(&customer_id, &mut write).await?;
-        (org, customer_id)
-    };
-    let address = stripe.set_address(&customer_id, &address.into()).await?;
-    let maybe_address = org.address_id.map(|a_id| Address::by_id(a_id, &mut write));
-    match OptionFuture::from(maybe_address).await {
-        Some(Ok(mut existing)) => {
-            existing.city = address.city;
-            existing.country = address.country;
-            existing.line1 = address.line1;
-            existing.line2 = address.line2;
-            existing.postal_code = address.postal_code;
-            existing.state = address.state;
-            existing.update(&mut write).await?;
-        }
-        None
-        | Some(Err(crate::model::address::Error::FindById(_, diesel::result::Error::NotFound))) => {
-            let new_address = NewAddress {
-                city: address.city.as_deref(),
-                country: address.country.as_deref(),
-                line1: address.line1.as_deref(),
-                line2: address.line2.as_deref(),
-                postal_code: address.postal_code.as_deref(),
-                state: address.state.as_deref(),
-            };
-            let address = new_address.create(&mut write).await?;
-            let update_org = UpdateOrg {
-                id: org.id,
-                name: None,
-                address_id: Some(address.id),
-            };
-            update_org.update(&mut write).await?;
+    let plan = Plan::by_tier(tier, &mut write).await?;
+    if org.plan_tier == tier && org.trial_ends_at.is_none() {
+        return Ok(api::OrgServiceUpdatePlanResponse {
+            org: Some(api::Org::from_model(&org, &mut write).await?),
+            plan: Some(plan.into()),
+        });
+    }
+
+    let entitlements = Entitlements::new(org_id, plan);
+    entitlements.check_usage(&mut write).await?;
+
+    let ctx = write.ctx;
+    let billed = !authz.has_perm(BillingPerm::Exempt);
+    let item_id = match (&entitlements.plan.stripe_sku, billed) {
+        (Some(sku), true) => {
+            let stripe = ctx.stripe.as_ref().ok_or(Error::NoStripe)?;
+            if org.stripe_customer_id.is_none() {
+                return Err(Error::NoStripeCustomer(org_id));
+            }
+            Some(stripe.add_subscription(&org, &ctx.keyring, sku).await?.id)
         }
-        Some(Err(err)) => return Err(err.into()),
+        _ => None,
     };
+    if let (Some(old_item_id), Some(stripe)) = (&org.stripe_plan_item_id, &ctx.stripe) {
+        stripe.remove_subscription(old_item_id).await?;
+    }
 
-    Ok(api::OrgServiceSetAddressResponse {})
+    let mut org = org.set_plan(tier, item_id.as_ref(), &mut write).await?;
+    if org.trial_ends_at.is_some() {
+        org = org_trial::convert(org_id, &mut write).await?;
+    }
+    let org = api::Org::from_model(&org, &mut write).await?;
+
+    let updated_by = common::Resource::from(&authz);
+    let msg = api::OrgMessage::updated(org.clone(), updated_by);
+    write.mqtt(msg);
+
+    Ok(api::OrgServiceUpdatePlanResponse {
+        org: Some(org),
+        plan: Some(entitlements.plan.into()),
+    })
+}
+
+/// Put an org on a trial of a plan, limited to `trial.max_nodes` live nodes
+/// by default.
+pub async fn start_trial(
+    req: api::OrgServiceStartTrialRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceStartTrialResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth(&meta, OrgAdminPerm::StartTrial).await?;
+    let tier = PlanTier::try_from(req.tier())?;
+
+    let config = &write.ctx.config.trial;
+    let days = req.days.unwrap_or(config.days);
+    let max_nodes = req.max_nodes.unwrap_or(config.max_nodes);
+
+    let org = Org::by_id(org_id, &mut write).await?;
+    let org = org_trial::start(org.id, tier, days, max_nodes, &mut write).await?;
+    let org = api::Org::from_model(&org, &mut write).await?;
+
+    let updated_by = common::Resource::from(&authz);
+    let msg = api::OrgMessage::updated(org.clone(), updated_by);
+    write.mqtt(msg);
+
+    Ok(api::OrgServiceStartTrialResponse { org: Some(org) })
+}
+
+/// Suspend an org for billing or abuse reasons, showing `banner` to its
+/// members.
+///
+/// With `stop_nodes`, the running nodes of the org are stopped until the org
+/// is reinstated.
+pub async fn suspend(
+    req: api::OrgServiceSuspendRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceSuspendResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth(&meta, OrgAdminPerm::Suspend).await?;
+    let reason = SuspensionReason::try_from(req.reason())?;
+
+    let org = Org::by_id(org_id, &mut write).await?;
+    let org = org_suspension::suspend(&org, reason, req.banner.as_deref(), &mut write).await?;
+
+    if req.stop_nodes {
+        let running = Node::running_by_org_id(org_id, &mut write).await?;
+        let node_ids: Vec<_> = running.iter().map(|node| node.id).collect();
+        org_suspension::stopped_nodes(org_id, &node_ids, &mut write).await?;
+
+        for node in &running {
+            let stop_cmd = NewCommand::node(node, CommandType::NodeStop)?
+                .create(&mut write)
+                .await?;
+            let stop_cmd = api::Command::from(&stop_cmd, &authz, &mut write)
+                .await?
+                .ok_or(Error::NoNodeStop)?;
+            write.mqtt(stop_cmd);
+        }
+    }
+
+    let org = api::Org::from_model(&org, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    let msg = api::OrgMessage::updated(org.clone(), updated_by);
+    write.mqtt(msg);
+
+    Ok(api::OrgServiceSuspendResponse { org: Some(org) })
+}
+
+/// Reinstate a suspended org, and start the nodes that its suspension stopped.
+///
+/// Nodes that were deleted or suspended for abuse in the meantime are left
+/// alone.
+pub async fn reinstate(
+    req: api::OrgServiceReinstateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceReinstateResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth(&meta, OrgAdminPerm::Reinstate).await?;
+
+    let org = Org::by_id(org_id, &mut write).await?;
+    let org = org_suspension::reinstate(&org, &mut write).await?;
+
+    let node_ids = org_suspension::take_stopped_nodes(org_id, &mut write)
+        .await?
+        .into_iter()
+        .collect();
+    let nodes = Node::by_ids(&node_ids, &mut write).await?;
+    for node in nodes
+        .iter()
+        .filter(|node| node.org_id == org_id && node.check_not_suspended().is_ok())
+    {
+        let start_cmd = NewCommand::node(node, CommandType::NodeStart)?
+            .create(&mut write)
+            .await?;
+        let start_cmd = api::Command::from(&start_cmd, &authz, &mut write)
+            .await?
+            .ok_or(Error::NoNodeStart)?;
+        write.mqtt(start_cmd);
+    }
+
+    let org = api::Org::from_model(&org, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    let msg = api::OrgMessage::updated(org.clone(), updated_by);
+    write.mqtt(msg);
+
+    Ok(api::OrgServiceReinstateResponse { org: Some(org) })
+}
+
+pub async fn get_address(
+    req: api::OrgServiceGetAddressRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceGetAddressResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, OrgAddressPerm::Get, org_id).await?;
+
+    let org = Org::by_id(org_id, &mut read).await?;
+    let Some(customer_id) = org.customer_id(&read.ctx.keyring)? else {
+        return Ok(Default::default());
+    };
+    let address = read
+        .ctx
+        .stripe
+        .as_ref()
+        .ok_or(Error::NoStripe)?
+        .get_address(&customer_id)
+        .await?;
+
+    Ok(api::OrgServiceGetAddressResponse {
+        address: address.map(Into::into),
+    })
+}
+
+pub async fn set_address(
+    req: api::OrgServiceSetAddressRequest,
+    meta: Metadata,
+    mut write: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceSetAddressResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write.auth_for(&meta, OrgAddressPerm::Set, org_id).await?;
+
+    let ctx = write.ctx;
+    let stripe = ctx.stripe.as_ref().ok_or(Error::NoStripe)?;
+    let org = Org::by_id(org_id, &mut write).await?;
+    let address = req.address.ok_or(Error::MissingAddress)?;
+    let (org, customer_id) = if let Some(customer_id) = org.customer_id(&ctx.keyring)? {
+        (org, customer_id)
+    } else {
+        let owner = User::owner(org_id, &mut write).await?;
+        let customer_id = stripe.create_customer(&org, &owner, None).await?.id;
+        let org = org
+            .set_customer_id(&customer_id, &ctx.keyring, &mut write)
+            .await?;
+        (org, customer_id)
+    };
+    let address = stripe.set_address(&customer_id, &address.into()).await?;
+    let maybe_address = org.address_id.map(|a_id| Address::by_id(a_id, &mut write));
+    match OptionFuture::from(maybe_address).await {
+        Some(Ok(mut existing)) => {
+            existing.city = address.city;
+            existing.country = address.country;
+            existing.line1 = address.line1;
+            existing.line2 = address.line2;
+            existing.postal_code = address.postal_code;
+            existing.state = address.state;
+            existing.update(&mut write).await?;
+        }
+        None
+        | Some(Err(crate::model::address::Error::FindById(_, diesel::result::Error::NotFound))) => {
+            let new_address = NewAddress {
+                city: address.city.as_deref(),
+                country: address.country.as_deref(),
+                line1: address.line1.as_deref(),
+                line2: address.line2.as_deref(),
+                postal_code: address.postal_code.as_deref(),
+                state: address.state.as_deref(),
+            };
+            let address = new_address.create(&mut write).await?;
+            let update_org = UpdateOrg {
+                id: org.id,
+                name: None,
+                address_id: Some(address.id),
+                agent_upgrade_policy: None,
+                disk_quota_bytes: None,
+                disk_auto_approve_bytes: None,
+                node_display_name_max_len: None,
+                node_note_max_len: None,
+                node_display_name_unique: None,
+                node_display_name_charset: None,
+                require_approval_node_delete: None,
+                require_approval_node_upgrade: None,
+                require_approval_org_transfer: None,
+            };
+            update_org.update(&mut write).await?;
+        }
+        Some(Err(err)) => return Err(err.into()),
+    };
+
+    Ok(api::OrgServiceSetAddressResponse {})
 }
 
 pub async fn delete_address(
@@ -650,6 +1544,7 @@ pub async fn delete_address(
     meta: Metadata,
     mut write: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceDeleteAddressResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     write
         .auth_for(&meta, OrgAddressPerm::Delete, org_id)
@@ -657,15 +1552,14 @@ pub async fn delete_address(
 
     let org = Org::by_id(org_id, &mut write).await?;
     let customer_id = org
-        .stripe_customer_id
-        .as_deref()
+        .customer_id(&write.ctx.keyring)?
         .ok_or(Error::NoStripeCustomer(org_id))?;
     write
         .ctx
         .stripe
         .as_ref()
         .ok_or(Error::NoStripe)?
-        .delete_address(customer_id)
+        .delete_address(&customer_id)
         .await?;
 
     Ok(api::OrgServiceDeleteAddressResponse {})
@@ -676,13 +1570,14 @@ pub async fn get_invoices(
     meta: Metadata,
     mut write: ReadConn<'_, '_>,
 ) -> Result<api::OrgServiceGetInvoicesResponse, Error> {
+    req.validate()?;
     let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
     let _authz = write
         .auth_for(&meta, OrgAddressPerm::Delete, org_id)
         .await?;
 
     let org = Org::by_id(org_id, &mut write).await?;
-    let Some(customer_id) = org.stripe_customer_id.as_deref() else {
+    let Some(customer_id) = org.customer_id(&write.ctx.keyring)? else {
         return Ok(Default::default());
     };
     let invoices = write
@@ -690,7 +1585,7 @@ pub async fn get_invoices(
         .stripe
         .as_ref()
         .ok_or(Error::NoStripe)?
-        .get_invoices(customer_id)
+        .get_invoices(&customer_id)
         .await?;
     let invoices = invoices
         .into_iter()
@@ -700,6 +1595,438 @@ pub async fn get_invoices(
     Ok(api::OrgServiceGetInvoicesResponse { invoices })
 }
 
+/// Count the nodes of an org by state, protocol, region and host, along with
+/// their total resources and monthly cost.
+pub async fn summary(
+    req: api::OrgServiceSummaryRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceSummaryResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = read
+        .auth_or_for(&meta, NodeAdminPerm::List, NodePerm::List, org_id)
+        .await?;
+
+    let nodes = Node::by_org_id(org_id, &mut read).await?;
+    let summary = NodeSummary::new(&nodes);
+    let org_ids = HashSet::from([org_id]);
+
+    let protocol_ids = summary.protocols.keys().copied().collect();
+    let protocols = Protocol::by_ids(&protocol_ids, &org_ids, &authz, &mut read)
+        .await?
+        .to_map_keep_last(|protocol| (protocol.id, protocol));
+
+    let host_ids = summary.hosts.keys().copied().collect();
+    let relations = NodeRelations::default()
+        .with_hosts(&host_ids, &org_ids, &mut read)
+        .await?;
+    let host_regions = relations
+        .hosts
+        .values()
+        .map(|host| (host.id, host.region_id))
+        .collect();
+
+    let region_counts = summary.regions(&host_regions);
+    let region_ids = region_counts.keys().copied().collect();
+    let NodeRelations { hosts, regions, .. } =
+        relations.with_regions(&region_ids, &mut read).await?;
+
+    let count = |id: String, name: Option<String>, count: u64| api::OrgSummaryCount {
+        id,
+        name: name.unwrap_or_default(),
+        count,
+    };
+
+    let mut node_states: Vec<_> = summary
+        .states
+        .iter()
+        .map(|(state, count)| api::NodeStateCount {
+            state: common::NodeState::from(*state).into(),
+            count: *count,
+        })
+        .collect();
+    node_states.sort_by_key(|count| count.state);
+
+    let mut protocol_counts: Vec<_> = summary
+        .protocols
+        .iter()
+        .map(|(id, n)| {
+            let name = protocols.get(id).map(|protocol| protocol.name.clone());
+            count(id.to_string(), name, *n)
+        })
+        .collect();
+    let mut region_counts: Vec<_> = region_counts
+        .iter()
+        .map(|(id, n)| {
+            let name = regions.get(id).map(|region| region.display_name.clone());
+            count(id.to_string(), name, *n)
+        })
+        .collect();
+    let mut host_counts: Vec<_> = summary
+        .hosts
+        .iter()
+        .map(|(id, n)| {
+            let name = hosts.get(id).map(|host| {
+                host.display_name
+                    .clone()
+                    .unwrap_or_else(|| host.network_name.clone())
+            });
+            count(id.to_string(), name, *n)
+        })
+        .collect();
+    for counts in [&mut protocol_counts, &mut region_counts, &mut host_counts] {
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    }
+
+    let monthly_cost = summary
+        .monthly_cost
+        .filter(|_| authz.has_perm(NodeAdminPerm::ViewCost))
+        .map(Into::into);
+
+    Ok(api::OrgServiceSummaryResponse {
+        summary: Some(api::OrgSummary {
+            org_id: org_id.to_string(),
+            node_count: summary.node_count,
+            node_states,
+            protocols: protocol_counts,
+            regions: region_counts,
+            hosts: host_counts,
+            cpu_cores: u64::try_from(summary.cpu_cores).unwrap_or_default(),
+            memory_bytes: u64::try_from(summary.memory_bytes).unwrap_or_default(),
+            disk_bytes: u64::try_from(summary.disk_bytes).unwrap_or_default(),
+            monthly_cost,
+        }),
+    })
+}
+
+pub async fn export_configuration(
+    req: api::OrgServiceExportConfigurationRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceExportConfigurationResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, OrgPerm::ExportConfiguration, org_id)
+        .await?;
+
+    let org = Org::by_id(org_id, &mut read).await?;
+    let snapshot = OrgSnapshot::export(&org, &mut read).await?;
+
+    Ok(api::OrgServiceExportConfigurationResponse {
+        version: snapshot.version,
+        snapshot: snapshot.to_json()?,
+    })
+}
+
+/// Restore a snapshot from `ExportConfiguration` into an org, which may be in
+/// another environment.
+///
+/// Nodes are created through `NodeService.Create`, so the caller also needs
+/// permission to create nodes in the org.
+pub async fn import_configuration(
+    req: api::OrgServiceImportConfigurationRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceImportConfigurationResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write
+        .auth_for(&meta, OrgPerm::ImportConfiguration, org_id)
+        .await?;
+
+    let snapshot = OrgSnapshot::parse(&req.snapshot)?;
+    let (pool_ids, commands) = snapshot
+        .restore(org_id, authz.resource(), &mut write)
+        .await?;
+    for command in commands {
+        let command = api::Command::from_host(&command)?.ok_or(Error::NoHostKeys)?;
+        write.mqtt(command);
+    }
+
+    let mut nodes = vec![];
+    for node in &snapshot.nodes {
+        let create = node
+            .create_request(org_id, &pool_ids, &authz, &mut write)
+            .await?;
+        let created = super::node::create(create, meta.clone(), write.reborrow())
+            .await
+            .map_err(|err| Error::ImportNode(Box::new(err)))?;
+
+        for created in created.nodes {
+            let node_id = created.node_id.parse().map_err(Error::ParseNodeId)?;
+            let update = UpdateNode {
+                org_id: None,
+                host_id: None,
+                display_name: Some(&node.display_name),
+                auto_upgrade: Some(node.auto_upgrade),
+                ip_address: None,
+                ip_gateway: None,
+                note: node.note.as_deref().map(Some),
+                tags: None,
+                cost: None,
+            };
+            let updated = update.apply(node_id, &authz, &mut write).await?;
+            let updated = api::Node::from_model(updated, &authz, &mut write)
+                .await
+                .map_err(|err| Error::ImportNode(Box::new(err)))?;
+            nodes.push(updated);
+        }
+    }
+
+    let org = Org::by_id(org_id, &mut write).await?;
+    let org = api::Org::from_model(&org, &mut write).await?;
+    let updated_by = common::Resource::from(&authz);
+    write.mqtt(api::OrgMessage::updated(org, updated_by));
+
+    Ok(api::OrgServiceImportConfigurationResponse { nodes })
+}
+
+pub async fn create_project(
+    req: api::OrgServiceCreateProjectRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceCreateProjectResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_for(&meta, OrgPerm::CreateProject, org_id)
+        .await?;
+
+    let new_project = NewProject {
+        org_id,
+        name: &req.name,
+        description: req.description.as_deref(),
+        node_quota: req
+            .node_quota
+            .map(|quota| i64::try_from(quota).map_err(Error::NodeQuota))
+            .transpose()?,
+    };
+    let project = new_project.create(&mut write).await?;
+
+    Ok(api::OrgServiceCreateProjectResponse {
+        project: Some(project.into_api(&[])),
+    })
+}
+
+pub async fn get_project(
+    req: api::OrgServiceGetProjectRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceGetProjectResponse, Error> {
+    req.validate()?;
+    let id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let project = Project::by_id(id, &mut read).await?;
+    read.auth_or_for(
+        &meta,
+        OrgAdminPerm::Get,
+        OrgPerm::GetProject,
+        project.org_id,
+    )
+    .await?;
+
+    let member_ids = Project::member_ids(&HashSet::from([id]), &mut read).await?;
+    let member_ids = member_ids.get(&id).map(Vec::as_slice).unwrap_or_default();
+
+    Ok(api::OrgServiceGetProjectResponse {
+        project: Some(project.into_api(member_ids)),
+    })
+}
+
+pub async fn list_projects(
+    req: api::OrgServiceListProjectsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceListProjectsResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_or_for(&meta, OrgAdminPerm::Get, OrgPerm::ListProjects, org_id)
+        .await?;
+
+    let projects = Project::by_org_id(org_id, &mut read).await?;
+    let project_ids = projects.iter().map(|project| project.id).collect();
+    let member_ids = Project::member_ids(&project_ids, &mut read).await?;
+
+    let projects = projects
+        .into_iter()
+        .map(|project| {
+            let members = member_ids.get(&project.id).map(Vec::as_slice);
+            project.into_api(members.unwrap_or_default())
+        })
+        .collect();
+
+    Ok(api::OrgServiceListProjectsResponse { projects })
+}
+
+pub async fn update_project(
+    req: api::OrgServiceUpdateProjectRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceUpdateProjectResponse, Error> {
+    req.validate()?;
+    let id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let project = Project::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, OrgPerm::UpdateProject, project.org_id)
+        .await?;
+
+    // a node quota of zero removes it
+    let node_quota = req
+        .node_quota
+        .map(|quota| (quota > 0).then(|| i64::try_from(quota)).transpose())
+        .transpose()
+        .map_err(Error::NodeQuota)?;
+
+    let update = UpdateProject {
+        name: req.name.as_deref(),
+        description: req.description.as_deref(),
+        node_quota,
+    };
+    let project = update.apply(id, &mut write).await?;
+    let member_ids = Project::member_ids(&HashSet::from([id]), &mut write).await?;
+    let member_ids = member_ids.get(&id).map(Vec::as_slice).unwrap_or_default();
+
+    Ok(api::OrgServiceUpdateProjectResponse {
+        project: Some(project.into_api(member_ids)),
+    })
+}
+
+pub async fn delete_project(
+    req: api::OrgServiceDeleteProjectRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceDeleteProjectResponse, Error> {
+    req.validate()?;
+    let id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let project = Project::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, OrgPerm::DeleteProject, project.org_id)
+        .await?;
+
+    Project::delete(id, &mut write).await?;
+
+    Ok(api::OrgServiceDeleteProjectResponse {})
+}
+
+/// Bind an org member to a project, which restricts changes to the nodes of
+/// the project to its members.
+pub async fn add_project_member(
+    req: api::OrgServiceAddProjectMemberRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceAddProjectMemberResponse, Error> {
+    req.validate()?;
+    let id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
+    let project = Project::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, OrgPerm::AddProjectMember, project.org_id)
+        .await?;
+
+    if !Org::has_user(project.org_id, user_id, &mut write).await? {
+        return Err(Error::ProjectNotMember);
+    }
+    Project::add_member(id, user_id, &mut write).await?;
+
+    Ok(api::OrgServiceAddProjectMemberResponse {})
+}
+
+pub async fn remove_project_member(
+    req: api::OrgServiceRemoveProjectMemberRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceRemoveProjectMemberResponse, Error> {
+    req.validate()?;
+    let id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
+    let project = Project::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, OrgPerm::RemoveProjectMember, project.org_id)
+        .await?;
+
+    Project::remove_member(id, user_id, &mut write).await?;
+
+    Ok(api::OrgServiceRemoveProjectMemberResponse {})
+}
+
+/// Add a weekly window during which destructive or upgrade operations on the
+/// org are rejected, unless the caller may override it.
+pub async fn create_freeze_window(
+    req: api::OrgServiceCreateFreezeWindowRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceCreateFreezeWindowResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write
+        .auth_for(&meta, OrgPerm::CreateFreezeWindow, org_id)
+        .await?;
+
+    let window = NewFreezeWindow::new(
+        org_id,
+        (req.start_day, req.start_minute),
+        (req.end_day, req.end_minute),
+        req.reason,
+        authz.resource().user(),
+    )?
+    .create(&mut write)
+    .await?;
+
+    NewAuditLog::new(Some(org_id), &authz, AuditEvent::FreezeWindowCreated)
+        .with_data(json!({
+            "window_id": window.id.to_string(),
+            "start_minute": window.start_minute,
+            "end_minute": window.end_minute,
+        }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::OrgServiceCreateFreezeWindowResponse {
+        window: Some(window.into()),
+    })
+}
+
+pub async fn list_freeze_windows(
+    req: api::OrgServiceListFreezeWindowsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgServiceListFreezeWindowsResponse, Error> {
+    req.validate()?;
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_or_for(&meta, OrgAdminPerm::Get, OrgPerm::ListFreezeWindows, org_id)
+        .await?;
+
+    let windows = FreezeWindow::by_org(org_id, &mut read).await?;
+
+    Ok(api::OrgServiceListFreezeWindowsResponse {
+        windows: windows.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn delete_freeze_window(
+    req: api::OrgServiceDeleteFreezeWindowRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgServiceDeleteFreezeWindowResponse, Error> {
+    req.validate()?;
+    let id = req.window_id.parse().map_err(Error::ParseFreezeWindowId)?;
+    let window = FreezeWindow::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, OrgPerm::DeleteFreezeWindow, window.org_id)
+        .await?;
+
+    window.delete(&mut write).await?;
+
+    NewAuditLog::new(Some(window.org_id), &authz, AuditEvent::FreezeWindowDeleted)
+        .with_data(json!({ "window_id": window.id.to_string() }))
+        .with_request_id(meta.request_id())
+        .create(&mut write)
+        .await?;
+
+    Ok(api::OrgServiceDeleteFreezeWindowResponse {})
+}
+
 impl api::Org {
     /// Converts a list of `orgs` into a list of `api::Org`.
     ///
@@ -720,9 +2047,8 @@ impl api::Org {
             .values()
             .flat_map(|ou| ou.user_roles.keys().copied())
             .collect();
-        let users = User::by_ids(&user_ids, conn)
-            .await?
-            .to_map_keep_last(|u| (u.id, u));
+        let NodeRelations { users, .. } =
+            NodeRelations::default().with_users(&user_ids, conn).await?;
 
         orgs.iter()
             .map(|org| {
@@ -770,6 +2096,36 @@ impl api::Org {
                     member_count: u64::try_from(max(0, org.member_count))
                         .map_err(Error::ParseMax)?,
                     members,
+                    agent_upgrade_policy: api::AgentUpgradePolicy::from(org.agent_upgrade_policy)
+                        .into(),
+                    disk_quota_bytes: org
+                        .disk_quota_bytes
+                        .map(|bytes| u64::try_from(bytes).map_err(Error::ParseMax))
+                        .transpose()?,
+                    disk_auto_approve_bytes: org
+                        .disk_auto_approve_bytes
+                        .map(|bytes| u64::try_from(bytes).map_err(Error::ParseMax))
+                        .transpose()?,
+                    node_display_name_max_len: org
+                        .node_display_name_max_len
+                        .map(|len| u32::try_from(len).map_err(Error::ParseMax))
+                        .transpose()?,
+                    node_note_max_len: org
+                        .node_note_max_len
+                        .map(|len| u32::try_from(len).map_err(Error::ParseMax))
+                        .transpose()?,
+                    node_display_name_unique: org.node_display_name_unique,
+                    node_display_name_charset: api::NodeNameCharset::from(
+                        org.node_display_name_charset,
+                    )
+                    .into(),
+                    require_approval_node_delete: org.require_approval_node_delete,
+                    require_approval_node_upgrade: org.require_approval_node_upgrade,
+                    require_approval_org_transfer: org.require_approval_org_transfer,
+                    plan_tier: api::PlanTier::from(org.plan_tier).into(),
+                    trial_ends_at: org.trial_ends_at.map(NanosUtc::from).map(Into::into),
+                    hibernated_at: org.hibernated_at.map(NanosUtc::from).map(Into::into),
+                    suspension: org_suspension::to_api(org),
                 })
             })
             .collect()
@@ -829,3 +2185,233 @@ impl api::OrgServiceListRequest {
         })
     }
 }
+
+impl Validate for api::OrgServiceCreateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.no_secrets("name", &self.name);
+    }
+}
+
+impl Validate for api::OrgServiceGetRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceListRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.maybe_uuid("member_id", self.member_id.as_deref());
+    }
+}
+
+impl Validate for api::OrgServiceUpdateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.maybe_no_secrets("name", self.name.as_deref());
+    }
+}
+
+impl Validate for api::OrgServiceDeleteRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceRestoreDeletedRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceRemoveMemberRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::OrgServiceTransferOwnershipRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("new_owner_id", &self.new_owner_id);
+    }
+}
+
+impl Validate for api::OrgServiceConfirmTransferRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("transfer_id", &self.transfer_id);
+    }
+}
+
+impl Validate for api::OrgServiceGetProvisionTokenRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::OrgServiceResetProvisionTokenRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::OrgServiceInitCardRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::OrgServiceListPaymentMethodsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceBillingDetailsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceListPlansRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceUpdatePlanRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceStartTrialRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceSuspendRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.maybe_no_secrets("banner", self.banner.as_deref());
+    }
+}
+
+impl Validate for api::OrgServiceReinstateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceGetAddressRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceSetAddressRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceDeleteAddressRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceGetInvoicesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceSummaryRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceExportConfigurationRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceImportConfigurationRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceCreateProjectRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.no_secrets("name", &self.name);
+        v.maybe_no_secrets("description", self.description.as_deref());
+    }
+}
+
+impl Validate for api::OrgServiceGetProjectRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("project_id", &self.project_id);
+    }
+}
+
+impl Validate for api::OrgServiceListProjectsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceUpdateProjectRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("project_id", &self.project_id);
+        v.maybe_no_secrets("name", self.name.as_deref());
+        v.maybe_no_secrets("description", self.description.as_deref());
+    }
+}
+
+impl Validate for api::OrgServiceDeleteProjectRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("project_id", &self.project_id);
+    }
+}
+
+impl Validate for api::OrgServiceAddProjectMemberRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("project_id", &self.project_id);
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::OrgServiceRemoveProjectMemberRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("project_id", &self.project_id);
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::OrgServiceCreateFreezeWindowRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+        v.maybe_no_secrets("reason", self.reason.as_deref());
+    }
+}
+
+impl Validate for api::OrgServiceListFreezeWindowsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("org_id", &self.org_id);
+    }
+}
+
+impl Validate for api::OrgServiceDeleteFreezeWindowRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("window_id", &self.window_id);
+    }
+}
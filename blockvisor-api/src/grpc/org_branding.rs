@@ -0,0 +1,181 @@
+use chrono::Utc;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::{error, warn};
+use url::Url;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::OrgBrandingPerm;
+use crate::auth::resource::OrgId;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::org_branding::{HexColor, Logo, OrgBranding, SenderName};
+use crate::model::plan::Entitlements;
+
+use super::api::org_branding_service_server::OrgBrandingService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Org branding model error: {0}
+    Model(#[from] crate::model::org_branding::Error),
+    /// Org `{0}` has no branded logo.
+    NoLogo(OrgId),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Org branding plan error: {0}
+    Plan(#[from] crate::model::plan::Error),
+    /// Org branding store error: {0}
+    Store(#[from] crate::store::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            NoLogo(_) => Status::not_found("Logo not found."),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            Store(_) => Status::internal("Internal error."),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Model(err) => err.into(),
+            Plan(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl OrgBrandingService for Grpc {
+    async fn get(
+        &self,
+        req: Request<api::OrgBrandingServiceGetRequest>,
+    ) -> Result<Response<api::OrgBrandingServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update(
+        &self,
+        req: Request<api::OrgBrandingServiceUpdateRequest>,
+    ) -> Result<Response<api::OrgBrandingServiceUpdateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn get(
+    req: api::OrgBrandingServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgBrandingServiceGetResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, OrgBrandingPerm::Get, org_id).await?;
+
+    let entitlements = Entitlements::for_org(org_id, &mut read).await?;
+    let branding = OrgBranding::by_org(org_id, &mut read)
+        .await?
+        .unwrap_or_else(|| OrgBranding::empty(org_id));
+
+    Ok(api::OrgBrandingServiceGetResponse {
+        branding: Some(branding.into_api(&read.ctx.config.cloud.api_url)),
+        enabled: entitlements.plan.custom_branding,
+    })
+}
+
+/// Update the branding of an org, where an empty string clears that field.
+pub async fn update(
+    req: api::OrgBrandingServiceUpdateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgBrandingServiceUpdateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_for(&meta, OrgBrandingPerm::Update, org_id)
+        .await?;
+    Entitlements::for_org(org_id, &mut write)
+        .await?
+        .check_branding()?;
+
+    let mut branding = OrgBranding::by_org(org_id, &mut write)
+        .await?
+        .unwrap_or_else(|| OrgBranding::empty(org_id));
+
+    if let Some(name) = req.sender_name {
+        branding.sender_name = clearable(&name, SenderName::new)?;
+    }
+    if let Some(color) = req.primary_color {
+        branding.primary_color = clearable(&color, HexColor::new)?;
+    }
+    if let Some(color) = req.accent_color {
+        branding.accent_color = clearable(&color, HexColor::new)?;
+    }
+
+    // validate everything before the logo is stored
+    let logo = req.logo.map(Logo::new).transpose()?;
+    let old_key = if let Some(logo) = logo {
+        let key = write
+            .ctx
+            .store
+            .save_logo(
+                org_id,
+                logo.data,
+                logo.format.content_type(),
+                logo.format.extension(),
+            )
+            .await?;
+        branding.logo_key.replace(key)
+    } else if req.remove_logo {
+        branding.logo_key.take()
+    } else {
+        None
+    };
+
+    branding.updated_at = Utc::now();
+    let branding = branding.upsert(&mut write).await?;
+
+    if let Some(key) = old_key {
+        let deleted = write.ctx.store.delete_logo(&key).await;
+        if let Err(err) = deleted {
+            warn!("Failed to delete old logo `{key}` of org {org_id}: {err}");
+        }
+    }
+
+    Ok(api::OrgBrandingServiceUpdateResponse {
+        branding: Some(branding.into_api(&write.ctx.config.cloud.api_url)),
+    })
+}
+
+/// Return a presigned url to the logo of an org, without auth.
+///
+/// The logo is only served while the org plan includes custom branding.
+pub async fn logo_url(org_id: &str, mut read: ReadConn<'_, '_>) -> Result<Url, Error> {
+    let org_id: OrgId = org_id.parse().map_err(Error::ParseOrgId)?;
+    let key = OrgBranding::active(org_id, &mut read)
+        .await?
+        .and_then(|branding| branding.logo_key)
+        .ok_or(Error::NoLogo(org_id))?;
+
+    read.ctx.store.logo_url(&key).await.map_err(Into::into)
+}
+
+fn clearable<T, F>(value: &str, parse: F) -> Result<Option<T>, Error>
+where
+    F: FnOnce(&str) -> Result<T, crate::model::org_branding::Error>,
+{
+    if value.trim().is_empty() {
+        Ok(None)
+    } else {
+        parse(value).map(Some).map_err(Into::into)
+    }
+}
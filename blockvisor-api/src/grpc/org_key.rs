@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::OrgKeyPerm;
+use crate::auth::resource::OrgId;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::org_key::{NewOrgKey, OrgKey, OrgKeyHost, OrgKeyId, OrgKeyType};
+
+use super::api::org_key_service_server::OrgKeyService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Org key command error: {0}
+    Command(#[from] crate::grpc::command::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Org key model error: {0}
+    Model(#[from] crate::model::org_key::Error),
+    /// No visibility of HostKeys command.
+    NoHostKeys,
+    /// Failed to parse OrgKeyId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            NoHostKeys => Status::forbidden("Access denied."),
+            ParseId(_) => Status::invalid_argument("key_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Command(err) => err.into(),
+            Model(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl OrgKeyService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::OrgKeyServiceCreateRequest>,
+    ) -> Result<Response<api::OrgKeyServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::OrgKeyServiceListRequest>,
+    ) -> Result<Response<api::OrgKeyServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn rotate(
+        &self,
+        req: Request<api::OrgKeyServiceRotateRequest>,
+    ) -> Result<Response<api::OrgKeyServiceRotateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| rotate(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn revoke(
+        &self,
+        req: Request<api::OrgKeyServiceRevokeRequest>,
+    ) -> Result<Response<api::OrgKeyServiceRevokeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::OrgKeyServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgKeyServiceCreateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth_for(&meta, OrgKeyPerm::Create, org_id).await?;
+
+    let new_key = NewOrgKey {
+        org_id,
+        name: &req.name,
+        key_type: OrgKeyType::try_from(req.key_type())?,
+        public_key: &req.public_key,
+        replaces_id: None,
+        created_by: authz.resource(),
+    };
+    let key = new_key.create(&mut write).await?;
+    send_to_org(org_id, &mut write).await?;
+
+    Ok(api::OrgKeyServiceCreateResponse {
+        key: Some(with_hosts(key, &mut write).await?),
+    })
+}
+
+pub async fn list(
+    req: api::OrgKeyServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgKeyServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, OrgKeyPerm::List, org_id).await?;
+
+    let keys = OrgKey::by_org_id(org_id, req.include_revoked, &mut read).await?;
+    let key_ids: Vec<_> = keys.iter().map(|key| key.id).collect();
+    let mut hosts: HashMap<OrgKeyId, Vec<OrgKeyHost>> = HashMap::new();
+    for host in OrgKeyHost::by_key_ids(&key_ids, &mut read).await? {
+        hosts.entry(host.key_id).or_default().push(host);
+    }
+
+    let keys = keys
+        .into_iter()
+        .map(|key| {
+            let hosts = hosts.remove(&key.id).unwrap_or_default();
+            key.into_api(hosts)
+        })
+        .collect();
+
+    Ok(api::OrgKeyServiceListResponse { keys })
+}
+
+/// Replace a key with a new public key, revoking the old key in the same send
+/// so that hosts never hold both.
+pub async fn rotate(
+    req: api::OrgKeyServiceRotateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgKeyServiceRotateResponse, Error> {
+    let id: OrgKeyId = req.key_id.parse().map_err(Error::ParseId)?;
+    let old_key = OrgKey::by_id(id, &mut write).await?;
+    let authz = write
+        .auth_for(&meta, OrgKeyPerm::Rotate, old_key.org_id)
+        .await?;
+
+    let old_key = old_key.revoke(&mut write).await?;
+    let new_key = NewOrgKey {
+        org_id: old_key.org_id,
+        name: req.name.as_deref().unwrap_or(&old_key.name),
+        key_type: old_key.key_type,
+        public_key: &req.public_key,
+        replaces_id: Some(old_key.id),
+        created_by: authz.resource(),
+    };
+    let key = new_key.create(&mut write).await?;
+    send_to_org(key.org_id, &mut write).await?;
+
+    Ok(api::OrgKeyServiceRotateResponse {
+        key: Some(with_hosts(key, &mut write).await?),
+    })
+}
+
+pub async fn revoke(
+    req: api::OrgKeyServiceRevokeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgKeyServiceRevokeResponse, Error> {
+    let id: OrgKeyId = req.key_id.parse().map_err(Error::ParseId)?;
+    let key = OrgKey::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, OrgKeyPerm::Revoke, key.org_id)
+        .await?;
+
+    let key = key.revoke(&mut write).await?;
+    send_to_org(key.org_id, &mut write).await?;
+
+    Ok(api::OrgKeyServiceRevokeResponse {
+        key: Some(with_hosts(key, &mut write).await?),
+    })
+}
+
+/// Send the active keys of an org to its private hosts once the transaction
+/// commits.
+async fn send_to_org(org_id: OrgId, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    for command in OrgKey::send_to_org(org_id, write).await? {
+        let command = api::Command::from_host(&command)?.ok_or(Error::NoHostKeys)?;
+        write.mqtt(command);
+    }
+
+    Ok(())
+}
+
+async fn with_hosts(key: OrgKey, write: &mut WriteConn<'_, '_>) -> Result<api::OrgKey, Error> {
+    let hosts = OrgKeyHost::by_key_ids([&key.id], write).await?;
+    Ok(key.into_api(hosts))
+}
@@ -0,0 +1,176 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::OrgLogSinkPerm;
+use crate::auth::resource::OrgId;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::org_log_sink::{
+    LogSinkCredentials, LogSinkKind, NewOrgLogSink, OrgLogSink, OrgLogSinkHost,
+};
+
+use super::api::org_log_sink_service_server::OrgLogSinkService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Org log sink command error: {0}
+    Command(#[from] crate::grpc::command::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Org log sink model error: {0}
+    Model(#[from] crate::model::org_log_sink::Error),
+    /// No visibility of HostLogSink command.
+    NoHostLogSink,
+    /// Org `{0}` has no log sink.
+    NoLogSink(OrgId),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            NoHostLogSink => Status::forbidden("Access denied."),
+            NoLogSink(_) => Status::not_found("Log sink not found."),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Command(err) => err.into(),
+            Model(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl OrgLogSinkService for Grpc {
+    async fn get(
+        &self,
+        req: Request<api::OrgLogSinkServiceGetRequest>,
+    ) -> Result<Response<api::OrgLogSinkServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn set(
+        &self,
+        req: Request<api::OrgLogSinkServiceSetRequest>,
+    ) -> Result<Response<api::OrgLogSinkServiceSetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn delete(
+        &self,
+        req: Request<api::OrgLogSinkServiceDeleteRequest>,
+    ) -> Result<Response<api::OrgLogSinkServiceDeleteResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn get(
+    req: api::OrgLogSinkServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::OrgLogSinkServiceGetResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, OrgLogSinkPerm::Get, org_id).await?;
+
+    let sink = OrgLogSink::by_org(org_id, &mut read).await?;
+    let hosts = OrgLogSinkHost::by_org(org_id, &mut read).await?;
+
+    Ok(api::OrgLogSinkServiceGetResponse {
+        log_sink: sink.map(OrgLogSink::into_api),
+        hosts: hosts.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Replace the log sink of an org and send it to each of its private hosts.
+///
+/// Credentials may be left out to keep those stored for the same kind of sink.
+pub async fn set(
+    req: api::OrgLogSinkServiceSetRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgLogSinkServiceSetResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write.auth_for(&meta, OrgLogSinkPerm::Set, org_id).await?;
+
+    let kind = LogSinkKind::try_from(req.kind())?;
+    let credentials = req
+        .credentials
+        .map(LogSinkCredentials::try_from)
+        .transpose()?;
+    let existing = OrgLogSink::by_org(org_id, &mut write).await?;
+    let new_sink = NewOrgLogSink::new(
+        org_id,
+        kind,
+        &req.endpoint,
+        credentials.as_ref(),
+        existing.as_ref(),
+    )?;
+
+    if let Some(credentials) = credentials {
+        let ctx = write.ctx;
+        credentials.write(org_id, &ctx.secret, &mut write).await?;
+    }
+    let sink = new_sink.upsert(&mut write).await?;
+    send_to_org(&sink, &mut write).await?;
+
+    let hosts = OrgLogSinkHost::by_org(org_id, &mut write).await?;
+    Ok(api::OrgLogSinkServiceSetResponse {
+        log_sink: Some(sink.into_api()),
+        hosts: hosts.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Remove the log sink of an org, so its private hosts stop shipping logs.
+pub async fn delete(
+    req: api::OrgLogSinkServiceDeleteRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::OrgLogSinkServiceDeleteResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_for(&meta, OrgLogSinkPerm::Delete, org_id)
+        .await?;
+
+    let sink = OrgLogSink::by_org(org_id, &mut write)
+        .await?
+        .ok_or(Error::NoLogSink(org_id))?;
+    let sink = sink.delete(&mut write).await?;
+    send_to_org(&sink, &mut write).await?;
+
+    let hosts = OrgLogSinkHost::by_org(org_id, &mut write).await?;
+    Ok(api::OrgLogSinkServiceDeleteResponse {
+        hosts: hosts.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Send the log sink of an org to its private hosts once the transaction
+/// commits.
+///
+/// These commands never carry credentials, which hosts only receive when they
+/// fetch their pending commands.
+async fn send_to_org(sink: &OrgLogSink, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+    for command in sink.send_to_org(write).await? {
+        let command = api::Command::from_host(&command)?.ok_or(Error::NoHostLogSink)?;
+        write.mqtt(command);
+    }
+
+    Ok(())
+}
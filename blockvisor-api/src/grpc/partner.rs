@@ -0,0 +1,146 @@
+use chrono::{NaiveTime, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::PartnerAdminPerm;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::node::slo::month_start;
+use crate::model::partner::{NewPartner, Partner, PartnerId, usd};
+use crate::util::NanosUtc;
+
+use super::api::partner_service_server::PartnerService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Partner name must not be empty.
+    NoName,
+    /// Failed to parse PartnerId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse month: {0}
+    ParseMonth(crate::util::timestamp::Error),
+    /// Partner model error: {0}
+    Partner(#[from] crate::model::partner::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            NoName => Status::invalid_argument("name"),
+            ParseId(_) => Status::invalid_argument("partner_id"),
+            ParseMonth(_) => Status::invalid_argument("month"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Partner(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl PartnerService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::PartnerServiceCreateRequest>,
+    ) -> Result<Response<api::PartnerServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::PartnerServiceListRequest>,
+    ) -> Result<Response<api::PartnerServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn revenue(
+        &self,
+        req: Request<api::PartnerServiceRevenueRequest>,
+    ) -> Result<Response<api::PartnerServiceRevenueResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| revenue(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::PartnerServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::PartnerServiceCreateResponse, Error> {
+    write.auth(&meta, PartnerAdminPerm::Create).await?;
+
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(Error::NoName);
+    }
+    let partner = NewPartner::new(name.to_string(), &req.referral_code)?
+        .create(&mut write)
+        .await?;
+
+    Ok(api::PartnerServiceCreateResponse {
+        partner: Some(partner.into()),
+    })
+}
+
+pub async fn list(
+    _: api::PartnerServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::PartnerServiceListResponse, Error> {
+    read.auth(&meta, PartnerAdminPerm::List).await?;
+
+    let partners = Partner::list(&mut read).await?;
+
+    Ok(api::PartnerServiceListResponse {
+        partners: partners.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// The revenue of the orgs attributed to a partner over the month containing
+/// `req.month`.
+///
+/// Defaults to the current month, measured up until now.
+pub async fn revenue(
+    req: api::PartnerServiceRevenueRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::PartnerServiceRevenueResponse, Error> {
+    read.auth(&meta, PartnerAdminPerm::Revenue).await?;
+
+    let partner_id: PartnerId = req.partner_id.parse().map_err(Error::ParseId)?;
+    let month = match req.month {
+        Some(month) => NanosUtc::try_from(month).map_err(Error::ParseMonth)?,
+        None => NanosUtc::from(Utc::now()),
+    };
+    let month = month_start(month.date_naive());
+
+    let partner = Partner::by_id(partner_id, &mut read).await?;
+    let orgs = partner.revenue(month, &mut read).await?;
+    let total = orgs
+        .iter()
+        .fold(0_i64, |total, org| total.saturating_add(org.amount));
+
+    Ok(api::PartnerServiceRevenueResponse {
+        partner: Some(partner.into()),
+        month: Some(NanosUtc::from(month.and_time(NaiveTime::MIN).and_utc()).into()),
+        total: Some(usd(total)),
+        orgs: orgs.into_iter().map(Into::into).collect(),
+    })
+}
@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::PoolPerm;
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::endpoint::autoscaler::{AutoscaleMetric, NewPoolAutoscaler};
+use crate::model::endpoint::pool::NewEndpointPool;
+use crate::model::endpoint::{
+    AutoscaleEvent, EndpointPool, EndpointPoolId, PoolAutoscaler, PoolMember,
+};
+use crate::model::node::NodeDnsAlias;
+use crate::model::{Image, ImageId, Node, Region, RegionId};
+
+use super::api::pool_service_server::PoolService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Pool alias error: {0}
+    Alias(#[from] crate::model::node::alias::Error),
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Pool autoscaler error: {0}
+    Autoscaler(#[from] crate::model::endpoint::autoscaler::Error),
+    /// Only users may set up an autoscaler.
+    AutoscalerUser,
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Pool cloudflare error: {0}
+    Cloudflare(#[from] crate::cloudflare::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Pool image error: {0}
+    Image(#[from] crate::model::image::Error),
+    /// Missing autoscale metric.
+    MissingMetric,
+    /// Pool model error: {0}
+    Model(#[from] crate::model::endpoint::pool::Error),
+    /// Pool node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Node `{0}` does not belong to org `{1}`.
+    NodeOrg(NodeId, OrgId),
+    /// Failed to parse EndpointPoolId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse ImageId: {0}
+    ParseImageId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse RegionId: {0}
+    ParseRegionId(uuid::Error),
+    /// Pool region error: {0}
+    Region(#[from] crate::model::region::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            AutoscalerUser => Status::failed_precondition("Only users may set up an autoscaler."),
+            Cloudflare(_) | Diesel(_) => Status::internal("Internal error."),
+            MissingMetric => Status::invalid_argument("metric"),
+            NodeOrg(..) | ParseNodeId(_) => Status::invalid_argument("node_ids"),
+            ParseId(_) => Status::invalid_argument("pool_id"),
+            ParseImageId(_) => Status::invalid_argument("image_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseRegionId(_) => Status::invalid_argument("region_id"),
+            Alias(err) => err.into(),
+            Auth(err) => err.into(),
+            Autoscaler(err) => err.into(),
+            Claims(err) => err.into(),
+            Image(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+            Region(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl PoolService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::PoolServiceCreateRequest>,
+    ) -> Result<Response<api::PoolServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get(
+        &self,
+        req: Request<api::PoolServiceGetRequest>,
+    ) -> Result<Response<api::PoolServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::PoolServiceListRequest>,
+    ) -> Result<Response<api::PoolServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update(
+        &self,
+        req: Request<api::PoolServiceUpdateRequest>,
+    ) -> Result<Response<api::PoolServiceUpdateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn delete(
+        &self,
+        req: Request<api::PoolServiceDeleteRequest>,
+    ) -> Result<Response<api::PoolServiceDeleteResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn set_autoscaler(
+        &self,
+        req: Request<api::PoolServiceSetAutoscalerRequest>,
+    ) -> Result<Response<api::PoolServiceSetAutoscalerResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| set_autoscaler(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_autoscaler(
+        &self,
+        req: Request<api::PoolServiceGetAutoscalerRequest>,
+    ) -> Result<Response<api::PoolServiceGetAutoscalerResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_autoscaler(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn remove_autoscaler(
+        &self,
+        req: Request<api::PoolServiceRemoveAutoscalerRequest>,
+    ) -> Result<Response<api::PoolServiceRemoveAutoscalerResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| remove_autoscaler(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+/// Create a pool with a DNS name for the healthy nodes among `node_ids`.
+pub async fn create(
+    req: api::PoolServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::PoolServiceCreateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write.auth_for(&meta, PoolPerm::Create, org_id).await?;
+
+    let ctx = write.ctx;
+    let dns_config = &ctx.config.cloudflare.dns;
+    let name = NodeDnsAlias::parse_name(&req.name, |name| dns_config.allows_alias(name))?;
+    let node_ids = org_nodes(&req.node_ids, org_id, &mut write).await?;
+
+    let pool = NewEndpointPool::new(org_id, name, authz.resource())
+        .create(&mut write)
+        .await?;
+    PoolMember::add(pool.id, &node_ids, &mut write).await?;
+    pool.rotate(ctx.dns.as_ref().as_ref(), &mut write).await?;
+
+    let members = PoolMember::by_pool_id(pool.id, &mut write).await?;
+    Ok(api::PoolServiceCreateResponse {
+        pool: Some(pool.into_api(members)),
+    })
+}
+
+pub async fn get(
+    req: api::PoolServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::PoolServiceGetResponse, Error> {
+    let id: EndpointPoolId = req.pool_id.parse().map_err(Error::ParseId)?;
+    let pool = EndpointPool::by_id(id, &mut read).await?;
+    read.auth_for(&meta, PoolPerm::Get, pool.org_id).await?;
+
+    let members = PoolMember::by_pool_id(pool.id, &mut read).await?;
+    Ok(api::PoolServiceGetResponse {
+        pool: Some(pool.into_api(members)),
+    })
+}
+
+pub async fn list(
+    req: api::PoolServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::PoolServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, PoolPerm::List, org_id).await?;
+
+    let pools = EndpointPool::by_org_id(org_id, &mut read).await?;
+    let pool_ids: Vec<_> = pools.iter().map(|pool| pool.id).collect();
+    let mut members: HashMap<EndpointPoolId, Vec<PoolMember>> = HashMap::new();
+    for member in PoolMember::by_pool_ids(&pool_ids, &mut read).await? {
+        members.entry(member.pool_id).or_default().push(member);
+    }
+
+    let pools = pools
+        .into_iter()
+        .map(|pool| {
+            let members = members.remove(&pool.id).unwrap_or_default();
+            pool.into_api(members)
+        })
+        .collect();
+
+    Ok(api::PoolServiceListResponse { pools })
+}
+
+/// Add and remove pool members, taking removed members out of rotation.
+pub async fn update(
+    req: api::PoolServiceUpdateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::PoolServiceUpdateResponse, Error> {
+    let id: EndpointPoolId = req.pool_id.parse().map_err(Error::ParseId)?;
+    let pool = EndpointPool::by_id(id, &mut write).await?;
+    write.auth_for(&meta, PoolPerm::Update, pool.org_id).await?;
+
+    let add_ids = org_nodes(&req.add_node_ids, pool.org_id, &mut write).await?;
+    let remove_ids = req
+        .remove_node_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .collect::<Result<Vec<NodeId>, _>>()?;
+
+    // members are added and rotated in before others are removed
+    let ctx = write.ctx;
+    let dns = ctx.dns.as_ref().as_ref();
+    PoolMember::add(pool.id, &add_ids, &mut write).await?;
+    pool.rotate(dns, &mut write).await?;
+
+    let removed = PoolMember::remove(pool.id, &remove_ids, &mut write).await?;
+    for dns_id in removed.iter().filter_map(|member| member.dns_id.as_ref()) {
+        match dns.delete(dns_id).await {
+            Ok(()) => (),
+            Err(err) if err.is_not_found() => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    pool.rotate(dns, &mut write).await?;
+
+    let pool = pool.touch(&mut write).await?;
+    let members = PoolMember::by_pool_id(pool.id, &mut write).await?;
+    Ok(api::PoolServiceUpdateResponse {
+        pool: Some(pool.into_api(members)),
+    })
+}
+
+/// Delete a pool along with its DNS records.
+pub async fn delete(
+    req: api::PoolServiceDeleteRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::PoolServiceDeleteResponse, Error> {
+    let id: EndpointPoolId = req.pool_id.parse().map_err(Error::ParseId)?;
+    let pool = EndpointPool::by_id(id, &mut write).await?;
+    write.auth_for(&meta, PoolPerm::Delete, pool.org_id).await?;
+
+    let ctx = write.ctx;
+    pool.remove(ctx.dns.as_ref().as_ref(), &mut write).await?;
+
+    Ok(api::PoolServiceDeleteResponse {})
+}
+
+/// Scale the nodes of a pool by their usage, as the calling user.
+pub async fn set_autoscaler(
+    req: api::PoolServiceSetAutoscalerRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::PoolServiceSetAutoscalerResponse, Error> {
+    let pool_id: EndpointPoolId = req.pool_id.parse().map_err(Error::ParseId)?;
+    let pool = EndpointPool::by_id(pool_id, &mut write).await?;
+    let authz = write.auth_for(&meta, PoolPerm::Update, pool.org_id).await?;
+    let user_id = authz.resource().user().ok_or(Error::AutoscalerUser)?;
+
+    let metric = AutoscaleMetric::from_api(req.metric()).ok_or(Error::MissingMetric)?;
+    let image_id: ImageId = req.image_id.parse().map_err(Error::ParseImageId)?;
+    let image = Image::by_id(image_id, Some(pool.org_id), &authz, &mut write).await?;
+    let region_id: RegionId = req.region_id.parse().map_err(Error::ParseRegionId)?;
+    let region = Region::by_id(region_id, &mut write).await?;
+
+    let count = |count: u32| i32::try_from(count).unwrap_or(i32::MAX);
+    let autoscaler = NewPoolAutoscaler {
+        pool_id: pool.id,
+        user_id,
+        image_id: image.id,
+        region_id: region.id,
+        min_nodes: count(req.min_nodes),
+        max_nodes: count(req.max_nodes),
+        metric,
+        scale_up_above: req.scale_up_above,
+        scale_down_below: req.scale_down_below,
+        cooldown_seconds: count(req.cooldown_seconds),
+    }
+    .set(&mut write)
+    .await?;
+
+    Ok(api::PoolServiceSetAutoscalerResponse {
+        autoscaler: Some(autoscaler.into()),
+    })
+}
+
+pub async fn get_autoscaler(
+    req: api::PoolServiceGetAutoscalerRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::PoolServiceGetAutoscalerResponse, Error> {
+    let pool_id: EndpointPoolId = req.pool_id.parse().map_err(Error::ParseId)?;
+    let pool = EndpointPool::by_id(pool_id, &mut read).await?;
+    read.auth_for(&meta, PoolPerm::Get, pool.org_id).await?;
+
+    let autoscaler = PoolAutoscaler::by_pool_id(pool.id, &mut read).await?;
+    let events = AutoscaleEvent::recent(pool.id, &mut read).await?;
+
+    Ok(api::PoolServiceGetAutoscalerResponse {
+        autoscaler: Some(autoscaler.into()),
+        events: events.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Stop scaling a pool, keeping the nodes it has.
+pub async fn remove_autoscaler(
+    req: api::PoolServiceRemoveAutoscalerRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::PoolServiceRemoveAutoscalerResponse, Error> {
+    let pool_id: EndpointPoolId = req.pool_id.parse().map_err(Error::ParseId)?;
+    let pool = EndpointPool::by_id(pool_id, &mut write).await?;
+    write.auth_for(&meta, PoolPerm::Update, pool.org_id).await?;
+
+    PoolAutoscaler::remove(pool.id, &mut write).await?;
+
+    Ok(api::PoolServiceRemoveAutoscalerResponse {})
+}
+
+/// Parse `node_ids`, checking that each node belongs to `org_id`.
+async fn org_nodes(
+    node_ids: &[String],
+    org_id: OrgId,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<Vec<NodeId>, Error> {
+    let node_ids = node_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .collect::<Result<HashSet<NodeId>, _>>()?;
+
+    let nodes = Node::by_ids(&node_ids, write).await?;
+    for node_id in &node_ids {
+        match nodes.iter().find(|node| node.id == *node_id) {
+            Some(node) if node.org_id == org_id => (),
+            _ => return Err(Error::NodeOrg(*node_id, org_id)),
+        }
+    }
+
+    Ok(node_ids.into_iter().collect())
+}
@@ -1,5 +1,6 @@
 use std::collections::HashSet;
 
+use diesel::result::Error::NotFound;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
 use prost_wkt_types::Empty;
@@ -10,14 +11,17 @@ use tracing::error;
 use crate::auth::rbac::{Perm, ProtocolAdminPerm, ProtocolPerm};
 use crate::auth::{AuthZ, Authorize};
 use crate::database::{Conn, ReadConn, Transaction, WriteConn};
+use crate::model::protocol::network::NewProtocolNetwork;
 use crate::model::protocol::stats::NodeStats;
 use crate::model::protocol::version::{
-    NewVersion, ProtocolKey, ProtocolVersion, UpdateVersion, VersionKey, VersionMetadata,
+    NewVersion, ProtocolKey, ProtocolVersion, UpdateVersion, VersionId, VersionKey, VersionMetadata,
 };
 use crate::model::protocol::{
-    NewProtocol, Protocol, ProtocolFilter, ProtocolSearch, ProtocolSort, UpdateProtocol,
+    Error as ProtocolError, NetworkKind, NewProtocol, NewProtocolGrant, Protocol, ProtocolFilter,
+    ProtocolGrant, ProtocolId, ProtocolLifecycle, ProtocolNetwork, ProtocolSearch, ProtocolSort,
+    UpdateProtocol,
 };
-use crate::model::{Region, RegionId};
+use crate::model::{Org, Region, RegionId};
 use crate::util::{HashVec, NanosUtc};
 
 use super::api::protocol_service_server::ProtocolService;
@@ -47,12 +51,18 @@ pub enum Error {
     MissingStatsFor,
     /// Missing version key.
     MissingVersionKey,
+    /// Protocol network error: {0}
+    Network(#[from] crate::model::protocol::network::Error),
     /// Stripe is not configured.
     NoStripe,
     /// Protocol node error: {0}
     Node(#[from] crate::model::node::Error),
     /// Protocol node log error: {0}
     NodeLog(#[from] crate::model::node::log::Error),
+    /// Protocol org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Failed to parse ProtocolGrantId: {0}
+    ParseGrantId(uuid::Error),
     /// Failed to parse ProtocolId: {0}
     ParseId(uuid::Error),
     /// Failed to parse OrgId: {0}
@@ -65,6 +75,8 @@ pub enum Error {
     ParseVersionId(uuid::Error),
     /// Protocol model error: {0}
     Protocol(#[from] crate::model::protocol::Error),
+    /// Protocol grant error: {0}
+    ProtocolGrant(#[from] crate::model::protocol::grant::Error),
     /// Protocol stats error: {0}
     ProtocolStats(#[from] crate::model::protocol::stats::Error),
     /// Protocol version error: {0}
@@ -87,6 +99,8 @@ pub enum Error {
     StripePrice(#[from] crate::stripe::api::price::Error),
     /// The requested sort field is unknown.
     UnknownSortField,
+    /// Protocol version `{0}` does not belong to protocol `{1}`.
+    VersionProtocol(VersionId, ProtocolId),
 }
 
 impl From<Error> for Status {
@@ -103,6 +117,7 @@ impl From<Error> for Status {
             MissingStatsFor => Status::invalid_argument("stats_for"),
             MissingVersionKey => Status::invalid_argument("version_key"),
             NoStripe => Status::failed_precondition("Stripe is not configured."),
+            ParseGrantId(_) => Status::invalid_argument("grant_id"),
             ParseId(_) => Status::invalid_argument("protocol_id"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
             ParseRegionId(_) => Status::invalid_argument("region_id"),
@@ -112,13 +127,17 @@ impl From<Error> for Status {
             SearchOperator(_) => Status::invalid_argument("search.operator"),
             SortOrder(_) => Status::invalid_argument("sort.order"),
             UnknownSortField => Status::invalid_argument("sort.field"),
+            VersionProtocol(..) => Status::invalid_argument("protocol_version_id"),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
             Command(err) => err.into(),
             CommandGrpc(err) => err.into(),
+            Network(err) => err.into(),
             Node(err) => err.into(),
             NodeLog(err) => err.into(),
+            Org(err) => err.into(),
             Protocol(err) => err.into(),
+            ProtocolGrant(err) => err.into(),
             ProtocolStats(err) => err.into(),
             ProtocolVersion(err) => err.into(),
             Region(err) => err.into(),
@@ -128,6 +147,15 @@ impl From<Error> for Status {
 
 #[tonic::async_trait]
 impl ProtocolService for Grpc {
+    async fn add_network(
+        &self,
+        req: Request<api::ProtocolServiceAddNetworkRequest>,
+    ) -> Result<Response<api::ProtocolServiceAddNetworkResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| add_network(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn add_protocol(
         &self,
         req: Request<api::ProtocolServiceAddProtocolRequest>,
@@ -146,6 +174,15 @@ impl ProtocolService for Grpc {
             .await
     }
 
+    async fn delete_protocol(
+        &self,
+        req: Request<api::ProtocolServiceDeleteProtocolRequest>,
+    ) -> Result<Response<api::ProtocolServiceDeleteProtocolResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_protocol(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn get_latest(
         &self,
         req: Request<api::ProtocolServiceGetLatestRequest>,
@@ -182,6 +219,42 @@ impl ProtocolService for Grpc {
             .await
     }
 
+    async fn grant_access(
+        &self,
+        req: Request<api::ProtocolServiceGrantAccessRequest>,
+    ) -> Result<Response<api::ProtocolServiceGrantAccessResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| grant_access(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_catalog(
+        &self,
+        req: Request<api::ProtocolServiceListCatalogRequest>,
+    ) -> Result<Response<api::ProtocolServiceListCatalogResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_catalog(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list_grants(
+        &self,
+        req: Request<api::ProtocolServiceListGrantsRequest>,
+    ) -> Result<Response<api::ProtocolServiceListGrantsResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_grants(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list_networks(
+        &self,
+        req: Request<api::ProtocolServiceListNetworksRequest>,
+    ) -> Result<Response<api::ProtocolServiceListNetworksResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_networks(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
     async fn list_protocols(
         &self,
         req: Request<api::ProtocolServiceListProtocolsRequest>,
@@ -209,6 +282,15 @@ impl ProtocolService for Grpc {
             .await
     }
 
+    async fn revoke_access(
+        &self,
+        req: Request<api::ProtocolServiceRevokeAccessRequest>,
+    ) -> Result<Response<api::ProtocolServiceRevokeAccessResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke_access(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn update_protocol(
         &self,
         req: Request<api::ProtocolServiceUpdateProtocolRequest>,
@@ -228,6 +310,31 @@ impl ProtocolService for Grpc {
     }
 }
 
+pub async fn add_network(
+    req: api::ProtocolServiceAddNetworkRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ProtocolServiceAddNetworkResponse, Error> {
+    let authz = write.auth(&meta, ProtocolAdminPerm::AddNetwork).await?;
+
+    let protocol_id = req.protocol_id.parse().map_err(Error::ParseId)?;
+    let protocol = Protocol::by_id(protocol_id, None, &authz, &mut write).await?;
+
+    let new_network = NewProtocolNetwork {
+        protocol_id: protocol.id,
+        key: req.network_key,
+        kind: req.kind().try_into()?,
+        chain_id: req.chain_id,
+        genesis_hash: req.genesis_hash,
+        explorer_url: req.explorer_url,
+    };
+    let network = new_network.create(&mut write).await?;
+
+    Ok(api::ProtocolServiceAddNetworkResponse {
+        network: Some(network.into()),
+    })
+}
+
 pub async fn add_protocol(
     req: api::ProtocolServiceAddProtocolRequest,
     meta: Metadata,
@@ -241,12 +348,22 @@ pub async fn add_protocol(
         .map(|id| id.parse().map_err(Error::ParseOrgId))
         .transpose()?;
 
+    let lifecycle = req
+        .lifecycle
+        .map(|_| req.lifecycle().try_into())
+        .transpose()?
+        .unwrap_or(ProtocolLifecycle::Active);
+
     let new_protocol = NewProtocol {
         org_id,
         key: ProtocolKey::new(req.key)?,
         name: req.name,
         description: req.description,
         ticker: req.ticker,
+        docs_url: req.docs_url,
+        logo_url: req.logo_url,
+        networks: req.networks.into_iter().map(Some).collect(),
+        lifecycle,
     };
 
     let protocol = new_protocol.create(&mut write).await?;
@@ -296,6 +413,19 @@ pub async fn add_version(
     })
 }
 
+pub async fn delete_protocol(
+    req: api::ProtocolServiceDeleteProtocolRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ProtocolServiceDeleteProtocolResponse, Error> {
+    let _authz = write.auth(&meta, ProtocolAdminPerm::DeleteProtocol).await?;
+
+    let id = req.protocol_id.parse().map_err(Error::ParseId)?;
+    Protocol::delete(id, &mut write).await?;
+
+    Ok(api::ProtocolServiceDeleteProtocolResponse {})
+}
+
 pub async fn get_latest(
     req: api::ProtocolServiceGetLatestRequest,
     meta: Metadata,
@@ -466,6 +596,133 @@ pub async fn get_stats(
     }
 }
 
+pub async fn grant_access(
+    req: api::ProtocolServiceGrantAccessRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ProtocolServiceGrantAccessResponse, Error> {
+    let protocol_id = req.protocol_id.parse().map_err(Error::ParseId)?;
+    let owner_id = Protocol::owner(protocol_id, &mut write).await?;
+    let authz = if let Some(owner_id) = owner_id {
+        write
+            .auth_or_for(
+                &meta,
+                ProtocolAdminPerm::GrantAccess,
+                ProtocolPerm::GrantAccess,
+                owner_id,
+            )
+            .await
+    } else {
+        write.auth(&meta, ProtocolAdminPerm::GrantAccess).await
+    }
+    .map_err(denied_as_not_found(protocol_id))?;
+
+    let protocol_version_id = if let Some(ref id) = req.protocol_version_id {
+        let id = id.parse().map_err(Error::ParseVersionId)?;
+        let version = ProtocolVersion::by_id(id, owner_id, &authz, &mut write).await?;
+        if version.protocol_id != protocol_id {
+            return Err(Error::VersionProtocol(id, protocol_id));
+        }
+        Some(id)
+    } else {
+        None
+    };
+
+    let org_id = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let org = Org::by_id(org_id, &mut write).await?;
+
+    let new_grant = NewProtocolGrant {
+        org_id: org.id,
+        protocol_id,
+        protocol_version_id,
+    };
+    let grant = new_grant.create(&mut write).await?;
+
+    Ok(api::ProtocolServiceGrantAccessResponse {
+        grant: Some(grant.into()),
+    })
+}
+
+/// Hide whether a protocol exists from a caller denied access to it, so that
+/// the protocols of other orgs can't be probed for by id.
+fn denied_as_not_found(protocol_id: ProtocolId) -> impl FnOnce(crate::auth::Error) -> Error {
+    move |err| match err {
+        crate::auth::Error::Claims(_) => ProtocolError::ById(protocol_id, NotFound).into(),
+        err => err.into(),
+    }
+}
+
+pub async fn list_catalog(
+    _req: api::ProtocolServiceListCatalogRequest,
+    _meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ProtocolServiceListCatalogResponse, Error> {
+    // No auth claims are required as the catalog only contains public protocols.
+    let protocols = Protocol::catalog(&mut read).await?;
+
+    Ok(api::ProtocolServiceListCatalogResponse {
+        protocols: protocols.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn list_grants(
+    req: api::ProtocolServiceListGrantsRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ProtocolServiceListGrantsResponse, Error> {
+    let protocol_id = req.protocol_id.parse().map_err(Error::ParseId)?;
+    if let Some(owner_id) = Protocol::owner(protocol_id, &mut read).await? {
+        read.auth_or_for(
+            &meta,
+            ProtocolAdminPerm::ListGrants,
+            ProtocolPerm::ListGrants,
+            owner_id,
+        )
+        .await
+    } else {
+        read.auth(&meta, ProtocolAdminPerm::ListGrants).await
+    }
+    .map_err(denied_as_not_found(protocol_id))?;
+
+    let grants = ProtocolGrant::by_protocol(protocol_id, &mut read).await?;
+
+    Ok(api::ProtocolServiceListGrantsResponse {
+        grants: grants.into_iter().map(Into::into).collect(),
+    })
+}
+
+pub async fn list_networks(
+    req: api::ProtocolServiceListNetworksRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::ProtocolServiceListNetworksResponse, Error> {
+    let admin_perm: Perm = ProtocolAdminPerm::ListNetworks.into();
+    let user_perm: Perm = ProtocolPerm::ListNetworks.into();
+
+    let (org_id, authz) = if let Some(ref org_id) = req.org_id {
+        let org_id = org_id.parse().map_err(Error::ParseOrgId)?;
+        let authz = read
+            .auth_or_for(&meta, admin_perm, user_perm, org_id)
+            .await?;
+        (Some(org_id), authz)
+    } else {
+        let authz = read.auth_any(&meta, [admin_perm, user_perm]).await?;
+        (None, authz)
+    };
+
+    let protocol_id = req.protocol_id.parse().map_err(Error::ParseId)?;
+    let protocol = Protocol::by_id(protocol_id, org_id, &authz, &mut read).await?;
+    let kinds = req
+        .kinds()
+        .map(NetworkKind::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    let networks = ProtocolNetwork::by_protocol_id(protocol.id, &kinds, &mut read).await?;
+
+    Ok(api::ProtocolServiceListNetworksResponse {
+        networks: networks.into_iter().map(Into::into).collect(),
+    })
+}
+
 pub async fn list_protocols(
     req: api::ProtocolServiceListProtocolsRequest,
     meta: Metadata,
@@ -583,6 +840,32 @@ pub async fn list_versions(
     })
 }
 
+pub async fn revoke_access(
+    req: api::ProtocolServiceRevokeAccessRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ProtocolServiceRevokeAccessResponse, Error> {
+    let id = req.grant_id.parse().map_err(Error::ParseGrantId)?;
+    let grant = ProtocolGrant::by_id(id, &mut write).await?;
+    if let Some(owner_id) = Protocol::owner(grant.protocol_id, &mut write).await? {
+        write
+            .auth_or_for(
+                &meta,
+                ProtocolAdminPerm::RevokeAccess,
+                ProtocolPerm::RevokeAccess,
+                owner_id,
+            )
+            .await
+    } else {
+        write.auth(&meta, ProtocolAdminPerm::RevokeAccess).await
+    }
+    .map_err(denied_as_not_found(grant.protocol_id))?;
+
+    grant.delete(&mut write).await?;
+
+    Ok(api::ProtocolServiceRevokeAccessResponse {})
+}
+
 pub async fn update_protocol(
     req: api::ProtocolServiceUpdateProtocolRequest,
     meta: Metadata,
@@ -598,6 +881,15 @@ pub async fn update_protocol(
             .visibility
             .map(|_| req.visibility().try_into())
             .transpose()?,
+        docs_url: req.docs_url.as_deref(),
+        logo_url: req.logo_url.as_deref(),
+        lifecycle: req
+            .lifecycle
+            .map(|_| req.lifecycle().try_into())
+            .transpose()?,
+        networks: req
+            .networks
+            .map(|networks| networks.networks.into_iter().map(Some).collect()),
     };
 
     let protocol = update.apply(&mut write).await?;
@@ -664,6 +956,10 @@ impl api::Protocol {
                     visibility: common::Visibility::from(protocol.visibility).into(),
                     created_at: Some(NanosUtc::from(protocol.created_at).into()),
                     updated_at: protocol.updated_at.map(|at| NanosUtc::from(at).into()),
+                    docs_url: protocol.docs_url,
+                    logo_url: protocol.logo_url,
+                    networks: protocol.networks.into_iter().flatten().collect(),
+                    lifecycle: api::ProtocolLifecycle::from(protocol.lifecycle).into(),
                     versions: versions
                         .remove(&protocol.id)
                         .map(|versions| versions.into_iter().map(Into::into).collect())
@@ -688,3 +984,19 @@ impl api::Protocol {
         }
     }
 }
+
+impl From<Protocol> for api::CatalogProtocol {
+    fn from(protocol: Protocol) -> Self {
+        api::CatalogProtocol {
+            protocol_id: protocol.id.to_string(),
+            key: protocol.key.into(),
+            name: protocol.name,
+            description: protocol.description,
+            ticker: protocol.ticker,
+            docs_url: protocol.docs_url,
+            logo_url: protocol.logo_url,
+            networks: protocol.networks.into_iter().flatten().collect(),
+            lifecycle: api::ProtocolLifecycle::from(protocol.lifecycle).into(),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Preloading of the rows that api nodes, hosts and orgs are built from.
+//!
+//! Each relation is read with one query for every model being converted, so
+//! that converting a single model costs as many queries as converting a page.
+
+use std::collections::{HashMap, HashSet};
+
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::AuthZ;
+use crate::auth::resource::{HostId, NodeId, OrgId, UserId};
+use crate::database::Conn;
+use crate::model::image::ConfigId;
+use crate::model::image::config::Config;
+use crate::model::node::{NodeDnsAlias, NodeReport};
+use crate::model::protocol::NetworkHead;
+use crate::model::sql::IpNetwork;
+use crate::model::{
+    Host, IpAddress, Node, Org, Protocol, ProtocolId, ProtocolVersion, Region, RegionId, User,
+    VersionId,
+};
+use crate::util::HashVec;
+
+use super::Status;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Relations node alias error: {0}
+    Alias(#[from] crate::model::node::alias::Error),
+    /// Relations host error: {0}
+    Host(#[from] crate::model::host::Error),
+    /// Relations image config error: {0}
+    ImageConfig(#[from] crate::model::image::config::Error),
+    /// Relations ip address error: {0}
+    IpAddress(#[from] crate::model::ip_address::Error),
+    /// Relations network head error: {0}
+    NetworkHead(#[from] crate::model::protocol::head::Error),
+    /// Relations org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Relations protocol error: {0}
+    Protocol(#[from] crate::model::protocol::Error),
+    /// Relations protocol version error: {0}
+    ProtocolVersion(#[from] crate::model::protocol::version::Error),
+    /// Relations region error: {0}
+    Region(#[from] crate::model::region::Error),
+    /// Relations node report error: {0}
+    Report(#[from] crate::model::node::report::Error),
+    /// Relations user error: {0}
+    User(#[from] crate::model::user::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Alias(err) => err.into(),
+            Host(err) => err.into(),
+            ImageConfig(err) => err.into(),
+            IpAddress(err) => err.into(),
+            NetworkHead(err) => err.into(),
+            Org(err) => err.into(),
+            Protocol(err) => err.into(),
+            ProtocolVersion(err) => err.into(),
+            Region(err) => err.into(),
+            Report(err) => err.into(),
+            User(err) => err.into(),
+        }
+    }
+}
+
+/// The related rows of a batch of nodes, hosts or orgs, keyed by id.
+///
+/// A row that is missing from a map was either not loaded, or is not visible
+/// to the caller.
+#[derive(Default)]
+pub struct NodeRelations {
+    pub configs: HashMap<ConfigId, Config>,
+    pub orgs: HashMap<OrgId, Org>,
+    pub hosts: HashMap<HostId, Host>,
+    pub regions: HashMap<RegionId, Region>,
+    pub protocols: HashMap<ProtocolId, Protocol>,
+    pub versions: HashMap<VersionId, ProtocolVersion>,
+    /// The height of the network head of each protocol version.
+    pub heads: HashMap<VersionId, i64>,
+    pub reports: HashMap<NodeId, Vec<NodeReport>>,
+    pub aliases: HashMap<NodeId, Vec<NodeDnsAlias>>,
+    pub host_ips: HashMap<HostId, Vec<IpNetwork>>,
+    pub assigned_ips: HashMap<HostId, Vec<IpNetwork>>,
+    pub users: HashMap<UserId, User>,
+}
+
+impl NodeRelations {
+    /// Load every relation of `nodes` that an `api::Node` is built from.
+    pub async fn for_nodes(
+        nodes: &[Node],
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let node_ids = nodes.iter().map(|node| node.id).collect();
+        let config_ids = nodes.iter().map(|node| node.config_id).collect();
+        let org_ids = nodes.iter().map(|node| node.org_id).collect();
+        let host_ids = nodes.iter().map(|node| node.host_id).collect();
+        let protocol_ids = nodes.iter().map(|node| node.protocol_id).collect();
+        let version_ids = nodes.iter().map(|node| node.protocol_version_id).collect();
+
+        let relations = Self::default()
+            .with_configs(&config_ids, conn)
+            .await?
+            .with_orgs(&org_ids, conn)
+            .await?
+            .with_hosts(&host_ids, &org_ids, conn)
+            .await?;
+
+        let region_ids = relations
+            .hosts
+            .values()
+            .map(|host| host.region_id)
+            .collect();
+        relations
+            .with_regions(&region_ids, conn)
+            .await?
+            .with_protocols(&protocol_ids, &version_ids, &org_ids, authz, conn)
+            .await?
+            .with_reports(&node_ids, conn)
+            .await?
+            .with_aliases(&node_ids, conn)
+            .await
+    }
+
+    /// Load every relation of `hosts` that an `api::Host` is built from.
+    pub async fn for_hosts(hosts: &[Host], conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let host_ids = hosts.iter().map(|host| host.id).collect();
+        let org_ids = hosts.iter().filter_map(|host| host.org_id).collect();
+        let region_ids = hosts.iter().map(|host| host.region_id).collect();
+
+        Self::default()
+            .with_orgs(&org_ids, conn)
+            .await?
+            .with_regions(&region_ids, conn)
+            .await?
+            .with_host_ips(&host_ids, conn)
+            .await
+    }
+
+    pub async fn with_configs(
+        mut self,
+        config_ids: &HashSet<ConfigId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let configs = Config::by_ids(config_ids, conn).await?;
+        self.configs
+            .extend(configs.into_iter().map(|config| (config.id, config)));
+        Ok(self)
+    }
+
+    pub async fn with_orgs(
+        mut self,
+        org_ids: &HashSet<OrgId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let orgs = Org::by_ids(org_ids, conn).await?;
+        self.orgs.extend(orgs.into_iter().map(|org| (org.id, org)));
+        Ok(self)
+    }
+
+    /// Load the hosts of `host_ids` that are visible to `org_ids`.
+    pub async fn with_hosts(
+        mut self,
+        host_ids: &HashSet<HostId>,
+        org_ids: &HashSet<OrgId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let hosts = Host::by_ids(host_ids, org_ids, conn).await?;
+        self.hosts
+            .extend(hosts.into_iter().map(|host| (host.id, host)));
+        Ok(self)
+    }
+
+    pub async fn with_host_ips(
+        mut self,
+        host_ids: &HashSet<HostId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        self.host_ips = IpAddress::for_hosts(host_ids, conn)
+            .await?
+            .to_map_keep_all(|ip| (ip.host_id, ip.ip));
+        self.assigned_ips = IpAddress::assigned_for_hosts(host_ids, conn)
+            .await?
+            .to_map_keep_all(|ip| (ip.host_id, ip.ip));
+        Ok(self)
+    }
+
+    pub async fn with_regions(
+        mut self,
+        region_ids: &HashSet<RegionId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let regions = Region::by_ids(region_ids, conn).await?;
+        self.regions
+            .extend(regions.into_iter().map(|region| (region.id, region)));
+        Ok(self)
+    }
+
+    /// Load the protocols and versions visible to `org_ids`, with the network
+    /// head of each version.
+    pub async fn with_protocols(
+        mut self,
+        protocol_ids: &HashSet<ProtocolId>,
+        version_ids: &HashSet<VersionId>,
+        org_ids: &HashSet<OrgId>,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let protocols = Protocol::by_ids(protocol_ids, org_ids, authz, conn).await?;
+        self.protocols.extend(
+            protocols
+                .into_iter()
+                .map(|protocol| (protocol.id, protocol)),
+        );
+
+        let versions = ProtocolVersion::by_ids(version_ids, org_ids, authz, conn).await?;
+        self.versions
+            .extend(versions.into_iter().map(|version| (version.id, version)));
+
+        self.heads
+            .extend(NetworkHead::by_version_ids(version_ids, conn).await?);
+        Ok(self)
+    }
+
+    pub async fn with_reports(
+        mut self,
+        node_ids: &HashSet<NodeId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        self.reports = NodeReport::by_node_ids(node_ids, conn)
+            .await?
+            .to_map_keep_all(|report| (report.node_id, report));
+        Ok(self)
+    }
+
+    pub async fn with_aliases(
+        mut self,
+        node_ids: &HashSet<NodeId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        self.aliases = NodeDnsAlias::by_node_ids(node_ids, conn)
+            .await?
+            .to_map_keep_all(|alias| (alias.node_id, alias));
+        Ok(self)
+    }
+
+    pub async fn with_users(
+        mut self,
+        user_ids: &HashSet<UserId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let users = User::by_ids(user_ids, conn).await?;
+        self.users
+            .extend(users.into_iter().map(|user| (user.id, user)));
+        Ok(self)
+    }
+}
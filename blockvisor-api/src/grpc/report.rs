@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use chrono::{NaiveTime, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::{ReportAdminPerm, ReportPerm};
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::{Transaction, WriteConn};
+use crate::model::node::NodeSlo;
+use crate::model::node::slo::month_start;
+use crate::util::NanosUtc;
+
+use super::api::report_service_server::ReportService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Failed to parse month: {0}
+    ParseMonth(crate::util::timestamp::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Report uptime error: {0}
+    Slo(#[from] crate::model::node::slo::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ParseMonth(_) => Status::invalid_argument("month"),
+            ParseNodeId(_) => Status::invalid_argument("node_ids"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Slo(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl ReportService for Grpc {
+    async fn uptime(
+        &self,
+        req: Request<api::ReportServiceUptimeRequest>,
+    ) -> Result<Response<api::ReportServiceUptimeResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| uptime(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+/// The uptime of the org nodes over the month containing `req.month`.
+///
+/// Defaults to the current month, measured up until now.
+pub async fn uptime(
+    req: api::ReportServiceUptimeRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::ReportServiceUptimeResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_or_for(&meta, ReportAdminPerm::Uptime, ReportPerm::Uptime, org_id)
+        .await?;
+
+    let month = match req.month {
+        Some(month) => NanosUtc::try_from(month).map_err(Error::ParseMonth)?,
+        None => NanosUtc::from(Utc::now()),
+    };
+    let month = month_start(month.date_naive());
+    let node_ids = req
+        .node_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .collect::<Result<HashSet<NodeId>, _>>()?;
+
+    let mut slos = NodeSlo::compute(org_id, month, &mut write).await?;
+    if !node_ids.is_empty() {
+        slos.retain(|slo| node_ids.contains(&slo.node_id));
+    }
+
+    let target_percent = write.ctx.config.slo.target_percent;
+    Ok(api::ReportServiceUptimeResponse {
+        month: Some(NanosUtc::from(month.and_time(NaiveTime::MIN).and_utc()).into()),
+        target_percent,
+        uptime_percent: NodeSlo::combined_percent(&slos),
+        nodes: slos
+            .into_iter()
+            .map(|slo| slo.into_api(target_percent))
+            .collect(),
+    })
+}
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+
+use chrono::{TimeDelta, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+use url::Url;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::StatusPagePerm;
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::{Conn, ReadConn, Transaction, WriteConn};
+use crate::model::node::NodeHealthHistory;
+use crate::model::status_page::{
+    CustomDomain, NewStatusPage, StatusPage, StatusPageId, StatusPageSlug,
+};
+use crate::model::{Node, OrgBranding};
+use crate::util::NanosUtc;
+
+use super::api::status_page_service_server::StatusPageService;
+use super::{Grpc, Metadata, Status, api, common};
+
+/// The number of days of uptime history shown on a status page.
+const UPTIME_HISTORY_DAYS: i64 = 90;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Status page health history error: {0}
+    History(#[from] crate::model::node::history::Error),
+    /// Status page branding error: {0}
+    Branding(#[from] crate::model::org_branding::Error),
+    /// Status page model error: {0}
+    Model(#[from] crate::model::status_page::Error),
+    /// Status page name must not be empty.
+    NoName,
+    /// Status page node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Node `{0}` does not belong to org `{1}`.
+    NodeOrg(NodeId, OrgId),
+    /// Failed to parse StatusPageId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse NodeId: {0}
+    ParseNodeId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            NoName => Status::invalid_argument("name"),
+            NodeOrg(..) | ParseNodeId(_) => Status::invalid_argument("node_ids"),
+            ParseId(_) => Status::invalid_argument("status_page_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            Auth(err) => err.into(),
+            Branding(err) => err.into(),
+            Claims(err) => err.into(),
+            History(err) => err.into(),
+            Model(err) => err.into(),
+            Node(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl StatusPageService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::StatusPageServiceCreateRequest>,
+    ) -> Result<Response<api::StatusPageServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get(
+        &self,
+        req: Request<api::StatusPageServiceGetRequest>,
+    ) -> Result<Response<api::StatusPageServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::StatusPageServiceListRequest>,
+    ) -> Result<Response<api::StatusPageServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn delete(
+        &self,
+        req: Request<api::StatusPageServiceDeleteRequest>,
+    ) -> Result<Response<api::StatusPageServiceDeleteResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn verify_domain(
+        &self,
+        req: Request<api::StatusPageServiceVerifyDomainRequest>,
+    ) -> Result<Response<api::StatusPageServiceVerifyDomainResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| verify_domain(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::StatusPageServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::StatusPageServiceCreateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write
+        .auth_for(&meta, StatusPagePerm::Create, org_id)
+        .await?;
+
+    let name = req.name.trim().to_string();
+    if name.is_empty() {
+        return Err(Error::NoName);
+    }
+    let slug = StatusPageSlug::new(req.slug)?;
+    let custom_domain = req
+        .custom_domain
+        .as_deref()
+        .map(CustomDomain::new)
+        .transpose()?;
+    let domain_token = custom_domain.is_some().then(NewStatusPage::domain_token);
+
+    let node_ids = req
+        .node_ids
+        .iter()
+        .map(|id| id.parse().map_err(Error::ParseNodeId))
+        .collect::<Result<HashSet<NodeId>, _>>()?;
+    let nodes = Node::by_ids(&node_ids, &mut write).await?;
+    for node_id in &node_ids {
+        match nodes.iter().find(|node| node.id == *node_id) {
+            Some(node) if node.org_id == org_id => (),
+            _ => return Err(Error::NodeOrg(*node_id, org_id)),
+        }
+    }
+
+    let new_page = NewStatusPage {
+        org_id,
+        name,
+        slug,
+        is_public: req.is_public,
+        custom_domain,
+        domain_token,
+        node_ids: node_ids.into_iter().map(Some).collect(),
+        node_tags: req.node_tags.into_iter().map(Some).collect(),
+    };
+    let page = new_page.create(&mut write).await?;
+
+    Ok(api::StatusPageServiceCreateResponse {
+        status_page: Some(page.into()),
+    })
+}
+
+pub async fn get(
+    req: api::StatusPageServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::StatusPageServiceGetResponse, Error> {
+    let id: StatusPageId = req.status_page_id.parse().map_err(Error::ParseId)?;
+    let page = StatusPage::by_id(id, &mut read).await?;
+    read.auth_for(&meta, StatusPagePerm::Get, page.org_id)
+        .await?;
+
+    let ctx = read.ctx;
+    let view = render(&page, &ctx.config.cloud.api_url, &mut read).await?;
+    Ok(api::StatusPageServiceGetResponse {
+        status_page: Some(page.into()),
+        view: Some(view),
+    })
+}
+
+pub async fn list(
+    req: api::StatusPageServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::StatusPageServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, StatusPagePerm::List, org_id).await?;
+
+    let status_pages = StatusPage::by_org(org_id, &mut read)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(api::StatusPageServiceListResponse { status_pages })
+}
+
+pub async fn delete(
+    req: api::StatusPageServiceDeleteRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::StatusPageServiceDeleteResponse, Error> {
+    let id: StatusPageId = req.status_page_id.parse().map_err(Error::ParseId)?;
+    let page = StatusPage::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, StatusPagePerm::Delete, page.org_id)
+        .await?;
+
+    page.delete(&mut write).await?;
+
+    Ok(api::StatusPageServiceDeleteResponse {})
+}
+
+/// Verify the custom domain of a page from the TXT record holding its token.
+pub async fn verify_domain(
+    req: api::StatusPageServiceVerifyDomainRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::StatusPageServiceVerifyDomainResponse, Error> {
+    let id: StatusPageId = req.status_page_id.parse().map_err(Error::ParseId)?;
+    let page = StatusPage::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, StatusPagePerm::VerifyDomain, page.org_id)
+        .await?;
+
+    let dns = write.ctx.dns.clone();
+    let page = page.verify_domain(&**dns, &mut write).await?;
+
+    Ok(api::StatusPageServiceVerifyDomainResponse {
+        status_page: Some(page.into()),
+    })
+}
+
+/// Render a public status page without auth, found by its slug.
+pub async fn public_by_slug(
+    slug: &str,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::StatusPageView, Error> {
+    let page = StatusPage::public_by_slug(slug, &mut read).await?;
+    let ctx = read.ctx;
+    render(&page, &ctx.config.cloud.api_url, &mut read).await
+}
+
+/// Render a public status page without auth, found by its custom domain.
+pub async fn public_by_domain(
+    domain: &str,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::StatusPageView, Error> {
+    let page = StatusPage::public_by_domain(domain, &mut read).await?;
+    let ctx = read.ctx;
+    render(&page, &ctx.config.cloud.api_url, &mut read).await
+}
+
+/// Render the current status and daily uptime history of the page nodes, with
+/// the branding of the org if its plan includes it.
+///
+/// Node ids are left out as the view may be shown publicly.
+async fn render(
+    page: &StatusPage,
+    api_url: &Url,
+    conn: &mut Conn<'_>,
+) -> Result<api::StatusPageView, Error> {
+    let nodes = page.nodes(conn).await?;
+    let node_ids: Vec<NodeId> = nodes.iter().map(|node| node.id).collect();
+    let since = Utc::now() - TimeDelta::days(UPTIME_HISTORY_DAYS);
+    let history = NodeHealthHistory::by_nodes(&node_ids, since, conn).await?;
+    let mut uptime = NodeHealthHistory::daily_uptime(history);
+
+    let nodes = nodes
+        .into_iter()
+        .map(|node| {
+            let days = uptime.remove(&node.id).unwrap_or_default();
+            #[allow(clippy::cast_precision_loss)]
+            let uptime_percent = if days.is_empty() {
+                None
+            } else {
+                Some(days.values().sum::<f64>() / days.len() as f64)
+            };
+
+            api::StatusPageNode {
+                display_name: node.display_name,
+                state: common::NodeState::from(node.node_state).into(),
+                health: node
+                    .protocol_health
+                    .map(|health| common::NodeHealth::from(health).into()),
+                uptime_percent,
+                daily_uptime: days
+                    .into_iter()
+                    .map(|(day, uptime_percent)| api::StatusPageUptime {
+                        date: day.to_string(),
+                        uptime_percent,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let branding = OrgBranding::active(page.org_id, conn)
+        .await?
+        .map(|branding| api::StatusPageBranding {
+            logo_url: branding.logo_url(api_url),
+            name: branding.sender_name.map(Into::into),
+            primary_color: branding.primary_color.map(Into::into),
+            accent_color: branding.accent_color.map(Into::into),
+        });
+
+    Ok(api::StatusPageView {
+        name: page.name.clone(),
+        nodes,
+        branding,
+        updated_at: Some(NanosUtc::from(Utc::now()).into()),
+    })
+}
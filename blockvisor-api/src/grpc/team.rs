@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::error;
+
+use crate::auth::Authorize;
+use crate::auth::rbac::{Role, TeamPerm};
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::{Conn, ReadConn, Transaction, WriteConn};
+use crate::model::Org;
+use crate::model::project::{Project, ProjectId};
+use crate::model::team::{NewTeam, Team, TeamId, UpdateTeam};
+
+use super::api::team_service_server::TeamService;
+use super::{Grpc, Metadata, Status, api};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Team org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Failed to parse TeamId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse ProjectId: {0}
+    ParseProjectId(uuid::Error),
+    /// Failed to parse role: {0}
+    ParseRole(String),
+    /// Failed to parse UserId: {0}
+    ParseUserId(uuid::Error),
+    /// Team project error: {0}
+    Project(#[from] crate::model::project::Error),
+    /// Project `{0}` belongs to another org.
+    ProjectOrg(ProjectId),
+    /// Team model error: {0}
+    Team(#[from] crate::model::team::Error),
+    /// The user to add is not a member of the org.
+    UserNotMember,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ParseId(_) => Status::invalid_argument("team_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseProjectId(_) => Status::invalid_argument("project_id"),
+            ParseRole(_) => Status::invalid_argument("role"),
+            ParseUserId(_) => Status::invalid_argument("user_id"),
+            ProjectOrg(_) => Status::not_found("Project not found."),
+            UserNotMember => Status::failed_precondition("User is not an org member."),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Org(err) => err.into(),
+            Project(err) => err.into(),
+            Team(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl TeamService for Grpc {
+    async fn create(
+        &self,
+        req: Request<api::TeamServiceCreateRequest>,
+    ) -> Result<Response<api::TeamServiceCreateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get(
+        &self,
+        req: Request<api::TeamServiceGetRequest>,
+    ) -> Result<Response<api::TeamServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::TeamServiceListRequest>,
+    ) -> Result<Response<api::TeamServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update(
+        &self,
+        req: Request<api::TeamServiceUpdateRequest>,
+    ) -> Result<Response<api::TeamServiceUpdateResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn delete(
+        &self,
+        req: Request<api::TeamServiceDeleteRequest>,
+    ) -> Result<Response<api::TeamServiceDeleteResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn add_member(
+        &self,
+        req: Request<api::TeamServiceAddMemberRequest>,
+    ) -> Result<Response<api::TeamServiceAddMemberResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| add_member(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn remove_member(
+        &self,
+        req: Request<api::TeamServiceRemoveMemberRequest>,
+    ) -> Result<Response<api::TeamServiceRemoveMemberResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| remove_member(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn grant_role(
+        &self,
+        req: Request<api::TeamServiceGrantRoleRequest>,
+    ) -> Result<Response<api::TeamServiceGrantRoleResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| grant_role(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn revoke_role(
+        &self,
+        req: Request<api::TeamServiceRevokeRoleRequest>,
+    ) -> Result<Response<api::TeamServiceRevokeRoleResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke_role(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn add_project(
+        &self,
+        req: Request<api::TeamServiceAddProjectRequest>,
+    ) -> Result<Response<api::TeamServiceAddProjectResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| add_project(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn remove_project(
+        &self,
+        req: Request<api::TeamServiceRemoveProjectRequest>,
+    ) -> Result<Response<api::TeamServiceRemoveProjectResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| remove_project(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create(
+    req: api::TeamServiceCreateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceCreateResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    write.auth_for(&meta, TeamPerm::Create, org_id).await?;
+
+    let new_team = NewTeam {
+        org_id,
+        name: req.name.trim(),
+    };
+    let team = new_team.create(&mut write).await?;
+
+    Ok(api::TeamServiceCreateResponse {
+        team: Some(team.into_api(&[], &[], &[])),
+    })
+}
+
+pub async fn get(
+    req: api::TeamServiceGetRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::TeamServiceGetResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let team = Team::by_id(id, &mut read).await?;
+    read.auth_for(&meta, TeamPerm::Get, team.org_id).await?;
+
+    let mut teams = into_api(vec![team], &mut read).await?;
+
+    Ok(api::TeamServiceGetResponse { team: teams.pop() })
+}
+
+pub async fn list(
+    req: api::TeamServiceListRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::TeamServiceListResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, TeamPerm::List, org_id).await?;
+
+    let teams = Team::by_org_id(org_id, &mut read).await?;
+    let teams = into_api(teams, &mut read).await?;
+
+    Ok(api::TeamServiceListResponse { teams })
+}
+
+pub async fn update(
+    req: api::TeamServiceUpdateRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceUpdateResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write.auth_for(&meta, TeamPerm::Update, team.org_id).await?;
+
+    let update = UpdateTeam {
+        name: req.name.as_deref().map(str::trim),
+    };
+    let team = update.apply(id, &mut write).await?;
+    let mut teams = into_api(vec![team], &mut write).await?;
+
+    Ok(api::TeamServiceUpdateResponse { team: teams.pop() })
+}
+
+/// Delete a team, which removes the roles and project access it gave to each
+/// of its members.
+pub async fn delete(
+    req: api::TeamServiceDeleteRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceDeleteResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write.auth_for(&meta, TeamPerm::Delete, team.org_id).await?;
+
+    team.delete(&mut write).await?;
+
+    Ok(api::TeamServiceDeleteResponse {})
+}
+
+pub async fn add_member(
+    req: api::TeamServiceAddMemberRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceAddMemberResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TeamPerm::AddMember, team.org_id)
+        .await?;
+
+    if !Org::has_user(team.org_id, user_id, &mut write).await? {
+        return Err(Error::UserNotMember);
+    }
+    team.add_member(user_id, &mut write).await?;
+
+    Ok(api::TeamServiceAddMemberResponse {})
+}
+
+pub async fn remove_member(
+    req: api::TeamServiceRemoveMemberRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceRemoveMemberResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseUserId)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TeamPerm::RemoveMember, team.org_id)
+        .await?;
+
+    team.remove_member(user_id, &mut write).await?;
+
+    Ok(api::TeamServiceRemoveMemberResponse {})
+}
+
+/// Grant an org role, like `org-admin`, to each member of a team.
+pub async fn grant_role(
+    req: api::TeamServiceGrantRoleRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceGrantRoleResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let role: Role = req.role.parse().map_err(Error::ParseRole)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TeamPerm::GrantRole, team.org_id)
+        .await?;
+
+    team.grant_role(role, &mut write).await?;
+
+    Ok(api::TeamServiceGrantRoleResponse {})
+}
+
+pub async fn revoke_role(
+    req: api::TeamServiceRevokeRoleRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceRevokeRoleResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let role: Role = req.role.parse().map_err(Error::ParseRole)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TeamPerm::RevokeRole, team.org_id)
+        .await?;
+
+    team.revoke_role(role, &mut write).await?;
+
+    Ok(api::TeamServiceRevokeRoleResponse {})
+}
+
+/// Bind a team to a project, which restricts changes to the nodes of the
+/// project to its members and the members of its teams.
+pub async fn add_project(
+    req: api::TeamServiceAddProjectRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceAddProjectResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let project_id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TeamPerm::AddProject, team.org_id)
+        .await?;
+
+    let project = Project::by_id(project_id, &mut write).await?;
+    if project.org_id != team.org_id {
+        return Err(Error::ProjectOrg(project_id));
+    }
+    team.add_project(project_id, &mut write).await?;
+
+    Ok(api::TeamServiceAddProjectResponse {})
+}
+
+pub async fn remove_project(
+    req: api::TeamServiceRemoveProjectRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TeamServiceRemoveProjectResponse, Error> {
+    let id: TeamId = req.team_id.parse().map_err(Error::ParseId)?;
+    let project_id: ProjectId = req.project_id.parse().map_err(Error::ParseProjectId)?;
+    let team = Team::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TeamPerm::RemoveProject, team.org_id)
+        .await?;
+
+    team.remove_project(project_id, &mut write).await?;
+
+    Ok(api::TeamServiceRemoveProjectResponse {})
+}
+
+/// Convert `teams` with their members, roles and projects.
+async fn into_api(teams: Vec<Team>, conn: &mut Conn<'_>) -> Result<Vec<api::Team>, Error> {
+    let team_ids: HashSet<_> = teams.iter().map(|team| team.id).collect();
+    let member_ids = Team::member_ids(&team_ids, conn).await?;
+    let roles = Team::roles(&team_ids, conn).await?;
+    let project_ids = Team::project_ids(&team_ids, conn).await?;
+
+    Ok(teams
+        .into_iter()
+        .map(|team| {
+            let id = team.id;
+            team.into_api(
+                member_ids.get(&id).map(Vec::as_slice).unwrap_or_default(),
+                roles.get(&id).map(Vec::as_slice).unwrap_or_default(),
+                project_ids.get(&id).map(Vec::as_slice).unwrap_or_default(),
+            )
+        })
+        .collect())
+}
@@ -0,0 +1,206 @@
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tonic::{Request, Response};
+use tracing::{debug, error};
+
+use crate::auth::Authorize;
+use crate::auth::rbac::TicketPerm;
+use crate::auth::resource::OrgId;
+use crate::database::{ReadConn, Transaction, WriteConn};
+use crate::model::ticket::{NewTicketIntegration, TicketIntegrationId, TicketProvider};
+use crate::model::{Incident, Ticket, TicketIntegration};
+use crate::ticket::{jira, zendesk};
+
+use super::api::ticket_service_server::TicketService;
+use super::{Grpc, Metadata, Status, api, common};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Auth check failed: {0}
+    Auth(#[from] crate::auth::Error),
+    /// Claims check failed: {0}
+    Claims(#[from] crate::auth::claims::Error),
+    /// Diesel failure: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Ticket incident error: {0}
+    Incident(#[from] crate::model::incident::Error),
+    /// Ticket model error: {0}
+    Model(#[from] crate::model::ticket::Error),
+    /// Failed to parse TicketIntegrationId: {0}
+    ParseId(uuid::Error),
+    /// Failed to parse OrgId: {0}
+    ParseOrgId(uuid::Error),
+    /// Failed to parse webhook body: {0}
+    ParseWebhook(serde_json::Error),
+    /// Invalid webhook token.
+    WebhookToken,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        error!("{err}");
+        match err {
+            Diesel(_) => Status::internal("Internal error."),
+            ParseId(_) => Status::invalid_argument("integration_id"),
+            ParseOrgId(_) => Status::invalid_argument("org_id"),
+            ParseWebhook(_) => Status::invalid_argument("Unparseable request"),
+            WebhookToken => Status::unauthorized("Invalid webhook token."),
+            Auth(err) => err.into(),
+            Claims(err) => err.into(),
+            Incident(err) => err.into(),
+            Model(err) => err.into(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl TicketService for Grpc {
+    async fn create_integration(
+        &self,
+        req: Request<api::TicketServiceCreateIntegrationRequest>,
+    ) -> Result<Response<api::TicketServiceCreateIntegrationResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| create_integration(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_integration(
+        &self,
+        req: Request<api::TicketServiceGetIntegrationRequest>,
+    ) -> Result<Response<api::TicketServiceGetIntegrationResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_integration(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn delete_integration(
+        &self,
+        req: Request<api::TicketServiceDeleteIntegrationRequest>,
+    ) -> Result<Response<api::TicketServiceDeleteIntegrationResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_integration(req, meta.into(), write).scope_boxed())
+            .await
+    }
+}
+
+pub async fn create_integration(
+    req: api::TicketServiceCreateIntegrationRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TicketServiceCreateIntegrationResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    let authz = write
+        .auth_for(&meta, TicketPerm::CreateIntegration, org_id)
+        .await?;
+
+    let new_integration = NewTicketIntegration {
+        org_id,
+        provider: TicketProvider::try_from(req.provider())?,
+        base_url: req.base_url,
+        username: req.username,
+        api_token: req.api_token,
+        project_key: req.project_key,
+        created_by: authz.resource(),
+    };
+    let created = new_integration.create(&mut write).await?;
+
+    Ok(api::TicketServiceCreateIntegrationResponse {
+        integration: Some(created.integration.into()),
+        webhook_token: created.webhook_token,
+    })
+}
+
+pub async fn get_integration(
+    req: api::TicketServiceGetIntegrationRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::TicketServiceGetIntegrationResponse, Error> {
+    let org_id: OrgId = req.org_id.parse().map_err(Error::ParseOrgId)?;
+    read.auth_for(&meta, TicketPerm::GetIntegration, org_id)
+        .await?;
+
+    let integration = TicketIntegration::by_org(org_id, &mut read).await?;
+
+    Ok(api::TicketServiceGetIntegrationResponse {
+        integration: Some(integration.into()),
+    })
+}
+
+pub async fn delete_integration(
+    req: api::TicketServiceDeleteIntegrationRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::TicketServiceDeleteIntegrationResponse, Error> {
+    let id: TicketIntegrationId = req.integration_id.parse().map_err(Error::ParseId)?;
+    let integration = TicketIntegration::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, TicketPerm::DeleteIntegration, integration.org_id)
+        .await?;
+
+    integration.delete(&mut write).await?;
+
+    Ok(api::TicketServiceDeleteIntegrationResponse {})
+}
+
+/// Apply a ticket status change sent by the provider of an integration.
+///
+/// Unknown tickets and statuses are ignored so that the provider doesn't keep
+/// retrying events unrelated to an incident.
+pub async fn webhook(
+    integration_id: &str,
+    token: &str,
+    body: &str,
+    mut write: WriteConn<'_, '_>,
+) -> Result<serde_json::Value, Error> {
+    let id: TicketIntegrationId = integration_id.parse().map_err(Error::ParseId)?;
+    let integration = TicketIntegration::by_id(id, &mut write).await?;
+    if !integration.verify_webhook(token, &write.ctx.keyring)? {
+        return Err(Error::WebhookToken);
+    }
+
+    let (external_id, status) = match integration.provider {
+        TicketProvider::Zendesk => {
+            let event: zendesk::WebhookEvent =
+                serde_json::from_str(body).map_err(Error::ParseWebhook)?;
+            (event.ticket_id, zendesk::incident_status(&event.status))
+        }
+        TicketProvider::Jira => {
+            let event: jira::WebhookEvent =
+                serde_json::from_str(body).map_err(Error::ParseWebhook)?;
+            let status = jira::incident_status(&event.issue.fields.status.status_category);
+            (event.issue.key, status)
+        }
+    };
+
+    let Some(status) = status else {
+        debug!("Ignoring unknown status for ticket {external_id}");
+        return Ok(serde_json::json!({"message": "event ignored"}));
+    };
+    let ticket = match Ticket::by_external(id, &external_id, &mut write).await {
+        Ok(ticket) => ticket,
+        Err(crate::model::ticket::Error::ByExternal(_, diesel::result::Error::NotFound)) => {
+            debug!("Ignoring unknown ticket {external_id}");
+            return Ok(serde_json::json!({"message": "event ignored"}));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if status == ticket.status {
+        return Ok(serde_json::json!({"message": "ticket unchanged"}));
+    }
+
+    let incident = Incident::by_id(ticket.incident_id, &mut write).await?;
+    let incident = if incident.status == status {
+        incident
+    } else {
+        incident.set_status(status, &mut write).await?
+    };
+    ticket.set_status(status, &mut write).await?;
+
+    // changes made in the provider are attributed to the integration owner
+    let updated_by = common::Resource::from(integration.created_by());
+    write.mqtt(api::IncidentMessage::updated(&incident, updated_by));
+
+    Ok(serde_json::json!({"message": "ticket synced"}))
+}
@@ -0,0 +1,118 @@
+//! Partial updates selected by a `google.protobuf.FieldMask`.
+//!
+//! Without a mask, each optional field of an update request is changed only
+//! when it is set. That can't express clearing a field, so update requests also
+//! take an `update_mask`. When it is given:
+//!
+//! - Only the fields named in its paths are changed, whether set or not.
+//! - A named nullable field that is unset is cleared.
+//! - A named non-nullable field must be set.
+
+use std::collections::HashSet;
+
+use displaydoc::Display;
+use prost_wkt_types::FieldMask;
+use thiserror::Error;
+
+use super::Status;
+use super::validate::FieldViolation;
+
+const MASK_FIELD: &str = "update_mask";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Field `{0}` can't be cleared.
+    NotNullable(&'static str),
+    /// Field `{0}` can't be updated.
+    UnknownPath(String),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        Status::bad_request(vec![FieldViolation {
+            field: MASK_FIELD.to_string(),
+            description: err.to_string(),
+        }])
+    }
+}
+
+/// The fields of an update request that should be changed.
+#[derive(Debug, Default)]
+pub struct UpdateMask {
+    paths: Option<HashSet<String>>,
+}
+
+impl UpdateMask {
+    /// Check that each path of `mask` is one of the updatable `fields`.
+    pub fn new(mask: Option<FieldMask>, fields: &[&str]) -> Result<Self, Error> {
+        let Some(mask) = mask else {
+            return Ok(UpdateMask::default());
+        };
+
+        if let Some(path) = mask
+            .paths
+            .iter()
+            .find(|path| !fields.contains(&path.as_str()))
+        {
+            return Err(Error::UnknownPath(path.clone()));
+        }
+
+        Ok(UpdateMask {
+            paths: Some(mask.paths.into_iter().collect()),
+        })
+    }
+
+    /// The new `value` of a non-nullable `field`, or `None` if unchanged.
+    pub fn value<T>(&self, field: &'static str, value: Option<T>) -> Result<Option<T>, Error> {
+        match &self.paths {
+            Some(paths) if paths.contains(field) => {
+                value.ok_or(Error::NotNullable(field)).map(Some)
+            }
+            Some(_) => Ok(None),
+            None => Ok(value),
+        }
+    }
+
+    /// The new `value` of a nullable `field`, or `None` if unchanged.
+    ///
+    /// A `Some(None)` clears the field.
+    pub fn nullable<T>(&self, field: &str, value: Option<T>) -> Option<Option<T>> {
+        match &self.paths {
+            Some(paths) => paths.contains(field).then_some(value),
+            None => value.map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &[&str] = &["display_name", "note"];
+
+    fn mask(paths: &[&str]) -> Option<FieldMask> {
+        Some(FieldMask {
+            paths: paths.iter().map(ToString::to_string).collect(),
+        })
+    }
+
+    #[test]
+    fn masked_fields_are_set_or_cleared() {
+        let unmasked = UpdateMask::new(None, FIELDS).unwrap();
+        assert_eq!(unmasked.nullable("note", Some("note")), Some(Some("note")));
+        assert_eq!(unmasked.nullable::<&str>("note", None), None);
+        assert_eq!(
+            unmasked.value("display_name", Some("name")).unwrap(),
+            Some("name")
+        );
+
+        let masked = UpdateMask::new(mask(&["note"]), FIELDS).unwrap();
+        assert_eq!(masked.nullable::<&str>("note", None), Some(None));
+        assert_eq!(masked.value("display_name", Some("name")).unwrap(), None);
+
+        let masked = UpdateMask::new(mask(&["display_name"]), FIELDS).unwrap();
+        assert!(masked.value::<&str>("display_name", None).is_err());
+
+        assert!(UpdateMask::new(mask(&["node_id"]), FIELDS).is_err());
+    }
+}
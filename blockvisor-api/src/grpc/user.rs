@@ -1,3 +1,4 @@
+use chrono::Utc;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
 use thiserror::Error;
@@ -5,45 +6,94 @@ use tonic::{Request, Response};
 use tracing::{error, warn};
 
 use crate::auth::rbac::{UserAdminPerm, UserPerm, UserSettingsAdminPerm, UserSettingsPerm};
-use crate::auth::resource::{Resource, UserId};
+use crate::auth::resource::{OrgId, Resource, UserId};
 use crate::auth::{self, Authorize, token};
 use crate::database::{ReadConn, Transaction, WriteConn};
-use crate::model::user::setting::{NewUserSetting, UserSetting};
-use crate::model::user::{NewUser, UpdateUser, User, UserFilter, UserSearch, UserSort};
+use crate::model::rbac::RbacUser;
+use crate::model::user::device::{NewUserDevice, PushPlatform, UserDevice, UserDeviceId};
+use crate::model::user::export::NewUserExportRequest;
+use crate::model::user::notification::NotificationPreferences;
+use crate::model::user::password;
+use crate::model::user::session::UserSession;
+use crate::model::user::setting::{NewUserSetting, UserLocale, UserSetting};
+use crate::model::user::signup::{self, NewUserSignup};
+use crate::model::user::{
+    NewUser, SUPPORT_ACCESS_DURATION, UpdateUser, User, UserFilter, UserSearch, UserSort,
+};
+use crate::model::{ApiKey, AuditLog, Node, Org, Partner};
+use crate::util::NanosUtc;
 
 use super::api::user_service_server::UserService;
+use super::update_mask::UpdateMask;
+use super::validate::{Validate, Validator};
 use super::{Grpc, Metadata, Status, api};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// User api key error: {0}
+    ApiKey(#[from] crate::model::api_key::Error),
+    /// User audit log error: {0}
+    Audit(#[from] crate::model::audit::Error),
     /// Auth check failed: {0}
     Auth(#[from] crate::auth::Error),
     /// Claims check failed: {0}
     Claims(#[from] crate::auth::claims::Error),
+    /// User device error: {0}
+    Device(#[from] crate::model::user::device::Error),
     /// Diesel failure: {0}
     Diesel(#[from] diesel::result::Error),
     /// User email error: {0}
     Email(#[from] crate::email::Error),
+    /// User export error: {0}
+    Export(#[from] crate::model::user::export::Error),
     /// Failed to parse filter limit as i64: {0}
     FilterLimit(std::num::TryFromIntError),
     /// Failed to parse filter offset as i64: {0}
     FilterOffset(std::num::TryFromIntError),
+    /// Not allowed for an impersonated user.
+    Impersonated,
+    /// User node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Email is not configured.
+    NoEmail,
+    /// User notification preferences error: {0}
+    Notification(#[from] crate::model::user::notification::Error),
+    /// User org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// User owns org `{0}` which still has nodes.
+    OwnsActiveOrg(OrgId),
+    /// User password error: {0}
+    Password(#[from] crate::model::user::password::Error),
     /// Failed to parse UserId: {0}
     ParseId(uuid::Error),
+    /// Failed to parse UserDeviceId: {0}
+    ParseDeviceId(uuid::Error),
     /// Failed to parse invitation id: {0}
     ParseInvitationId(uuid::Error),
     /// Failed to parse OrgId: {0}
     ParseOrgId(uuid::Error),
+    /// User partner error: {0}
+    Partner(#[from] crate::model::partner::Error),
+    /// User rbac error: {0}
+    Rbac(#[from] crate::model::rbac::Error),
     /// User search failed: {0}
     SearchOperator(crate::util::search::Error),
+    /// User session error: {0}
+    Session(#[from] crate::model::user::session::Error),
+    /// User signup error: {0}
+    Signup(#[from] crate::model::user::signup::Error),
     /// Sort order: {0}
     SortOrder(crate::util::search::Error),
     /// The requested sort field is unknown.
     UnknownSortField,
+    /// User update mask: {0}
+    UpdateMask(#[from] crate::grpc::update_mask::Error),
     /// User model error: {0}
     User(#[from] crate::model::user::Error),
     /// User settings error: {0}
     UserSettings(#[from] crate::model::user::setting::Error),
+    /// User request validation failed: {0}
+    Validate(#[from] crate::grpc::validate::Error),
 }
 
 impl From<Error> for Status {
@@ -51,22 +101,44 @@ impl From<Error> for Status {
         use Error::*;
         error!("{err}");
         match err {
-            Diesel(_) | Email(_) | ParseInvitationId(_) => Status::internal("Internal error."),
+            Diesel(_) | Email(_) | Export(_) | ParseInvitationId(_) => {
+                Status::internal("Internal error.")
+            }
             FilterLimit(_) => Status::invalid_argument("limit"),
             FilterOffset(_) => Status::invalid_argument("offset"),
+            Impersonated => Status::forbidden("Access denied."),
+            NoEmail => Status::failed_precondition("Email is not configured."),
+            OwnsActiveOrg(_) => Status::failed_precondition("User owns an org with nodes."),
             ParseId(_) => Status::invalid_argument("user_id"),
+            ParseDeviceId(_) => Status::invalid_argument("device_id"),
             ParseOrgId(_) => Status::invalid_argument("org_id"),
             SearchOperator(_) => Status::invalid_argument("search.operator"),
             SortOrder(_) => Status::invalid_argument("sort.order"),
             UnknownSortField => Status::invalid_argument("sort.field"),
+            ApiKey(err) => err.into(),
+            Audit(err) => err.into(),
             Auth(err) => err.into(),
             Claims(err) => err.into(),
+            Device(err) => err.into(),
+            Node(err) => err.into(),
+            Notification(err) => err.into(),
+            Org(err) => err.into(),
+            Partner(err) => err.into(),
+            Password(err) => err.into(),
+            Rbac(err) => err.into(),
+            Session(err) => err.into(),
+            Signup(err) => err.into(),
+            UpdateMask(err) => err.into(),
             User(err) => err.into(),
-            UserSettings(_) => err.into(),
+            UserSettings(err) => err.into(),
+            Validate(err) => err.into(),
         }
     }
 }
 
+/// The fields of `UserServiceUpdateRequest` that an update mask may name.
+const UPDATE_FIELDS: &[&str] = &["first_name", "last_name"];
+
 #[tonic::async_trait]
 impl UserService for Grpc {
     async fn create(
@@ -114,6 +186,51 @@ impl UserService for Grpc {
             .await
     }
 
+    async fn delete_account(
+        &self,
+        req: Request<api::UserServiceDeleteAccountRequest>,
+    ) -> Result<Response<api::UserServiceDeleteAccountResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| delete_account(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn export_data(
+        &self,
+        req: Request<api::UserServiceExportDataRequest>,
+    ) -> Result<Response<api::UserServiceExportDataResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| export_data(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn grant_support_access(
+        &self,
+        req: Request<api::UserServiceGrantSupportAccessRequest>,
+    ) -> Result<Response<api::UserServiceGrantSupportAccessResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| grant_support_access(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn revoke_support_access(
+        &self,
+        req: Request<api::UserServiceRevokeSupportAccessRequest>,
+    ) -> Result<Response<api::UserServiceRevokeSupportAccessResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| revoke_support_access(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn require_password_reset(
+        &self,
+        req: Request<api::UserServiceRequirePasswordResetRequest>,
+    ) -> Result<Response<api::UserServiceRequirePasswordResetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| require_password_reset(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
     async fn get_settings(
         &self,
         req: Request<api::UserServiceGetSettingsRequest>,
@@ -140,6 +257,52 @@ impl UserService for Grpc {
         self.write(|write| delete_settings(req, meta.into(), write).scope_boxed())
             .await
     }
+
+    async fn register_device(
+        &self,
+        req: Request<api::UserServiceRegisterDeviceRequest>,
+    ) -> Result<Response<api::UserServiceRegisterDeviceResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| register_device(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn list_devices(
+        &self,
+        req: Request<api::UserServiceListDevicesRequest>,
+    ) -> Result<Response<api::UserServiceListDevicesResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list_devices(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn remove_device(
+        &self,
+        req: Request<api::UserServiceRemoveDeviceRequest>,
+    ) -> Result<Response<api::UserServiceRemoveDeviceResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| remove_device(req, meta.into(), write).scope_boxed())
+            .await
+    }
+
+    async fn get_notification_preferences(
+        &self,
+        req: Request<api::UserServiceGetNotificationPreferencesRequest>,
+    ) -> Result<Response<api::UserServiceGetNotificationPreferencesResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get_notification_preferences(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn update_notification_preferences(
+        &self,
+        req: Request<api::UserServiceUpdateNotificationPreferencesRequest>,
+    ) -> Result<Response<api::UserServiceUpdateNotificationPreferencesResponse>, tonic::Status>
+    {
+        let (meta, _, req) = req.into_parts();
+        self.write(|write| update_notification_preferences(req, meta.into(), write).scope_boxed())
+            .await
+    }
 }
 
 pub async fn create(
@@ -147,6 +310,7 @@ pub async fn create(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::UserServiceCreateResponse, Error> {
+    req.validate()?;
     // A successful authz is not needed when users first register
     let invitation_id = match write.auth(&meta, UserPerm::Create).await {
         // If there is a successful authorization, then get the invitation id
@@ -160,12 +324,48 @@ pub async fn create(
         Err(err) => return Err(err.into()),
     };
 
-    let new_user = NewUser::new(&req.email, &req.first_name, &req.last_name, &req.password)?;
+    let policy = &write.ctx.config.password;
+    let new_user = NewUser::new(
+        &req.email,
+        &req.first_name,
+        &req.last_name,
+        &req.password,
+        policy,
+    )?;
+    password::check_breached(&req.password, policy).await?;
+
+    // Invited users were vetted by the org that invited them
+    let client_ip = meta.client_ip();
+    if invitation_id.is_none() {
+        let config = write.ctx.config.signup.clone();
+        signup::check_captcha(req.captcha_token.as_deref(), client_ip.as_deref(), &config).await?;
+        signup::check_email(&req.email, &config).await?;
+        signup::check_ip(client_ip.as_deref(), &config, &mut write).await?;
+    }
+
+    let partner = match req.referral_code.as_deref() {
+        Some(code) => Some(Partner::by_referral_code(code, &mut write).await?),
+        None => None,
+    };
+
     let user = new_user.create(&mut write).await?;
+    if let Some(ip_address) = client_ip.as_deref() {
+        NewUserSignup {
+            user_id: user.id,
+            ip_address,
+        }
+        .create(&mut write)
+        .await?;
+    }
+    if let Some(partner) = partner {
+        let org = Org::find_personal(user.id, &mut write).await?;
+        partner.attribute(org.id, &mut write).await?;
+    }
 
     if let Some(email) = write.ctx.email.as_ref() {
+        // a new user has no settings yet
         email
-            .registration_confirmation(&user, invitation_id)
+            .registration_confirmation(&user, &UserLocale::default(), invitation_id)
             .await?;
     } else {
         warn!("Can't send registration confirmation email, not configured");
@@ -181,14 +381,18 @@ pub async fn get(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::UserServiceGetResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
     read.auth_or_for(&meta, UserAdminPerm::Get, UserPerm::Get, user_id)
         .await?;
 
     let user = User::by_id(user_id, &mut read).await?;
+    let locale = UserLocale::by_user(user.id, &mut read).await?;
 
     Ok(api::UserServiceGetResponse {
         user: Some(user.into()),
+        locale: locale.locale,
+        timezone: locale.timezone.name().to_string(),
     })
 }
 
@@ -197,6 +401,7 @@ pub async fn list(
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::UserServiceListResponse, Error> {
+    req.validate()?;
     let filter = req.into_filter()?;
 
     let users = filter.user_ids.iter().map(Resource::from);
@@ -220,15 +425,17 @@ pub async fn update(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::UserServiceUpdateResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
     write
         .auth_or_for(&meta, UserAdminPerm::Update, UserPerm::Update, user_id)
         .await?;
 
+    let mask = UpdateMask::new(req.update_mask, UPDATE_FIELDS)?;
     let update = UpdateUser {
         id: user_id,
-        first_name: req.first_name.as_deref(),
-        last_name: req.last_name.as_deref(),
+        first_name: mask.value("first_name", req.first_name.as_deref())?,
+        last_name: mask.value("last_name", req.last_name.as_deref())?,
     };
     let user = update.apply(&mut write).await?;
 
@@ -242,19 +449,162 @@ pub async fn delete(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::UserServiceDeleteResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
-    write.auth_for(&meta, UserPerm::Delete, user_id).await?;
+    let authz = write.auth_for(&meta, UserPerm::Delete, user_id).await?;
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
 
     User::delete(user_id, &mut write).await?;
 
     Ok(api::UserServiceDeleteResponse {})
 }
 
+/// Permanently delete a user account.
+///
+/// Unlike `delete`, this removes the user from all orgs, revokes their api keys
+/// and provisioning tokens, and anonymizes the personal information held in
+/// historical records.
+pub async fn delete_account(
+    req: api::UserServiceDeleteAccountRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceDeleteAccountResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_for(&meta, UserPerm::DeleteAccount, user_id)
+        .await?;
+    // an impersonated user can't delete the account they are acting as
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
+
+    let user = User::by_id(user_id, &mut write).await?;
+    let orgs = Org::memberships(user_id, &mut write).await?;
+    for org in &orgs {
+        if org.deleted_at.is_some() || org.node_count == 0 {
+            continue;
+        }
+        // a personal org is deleted along with its user, so must have no nodes
+        let owners = RbacUser::org_owners(org.id, &mut write).await?;
+        if org.is_personal || owners.contains(&user_id) {
+            return Err(Error::OwnsActiveOrg(org.id));
+        }
+    }
+
+    for org in &orgs {
+        if org.is_personal {
+            org.delete_personal(&mut write).await?;
+        }
+        Org::remove_user(user_id, org.id, &mut write).await?;
+    }
+
+    ApiKey::delete_by_user(user_id, &mut write).await?;
+    UserSession::revoke_for_user(user_id, &mut write).await?;
+    AuditLog::anonymize_creator(Resource::User(user_id), &mut write).await?;
+    Node::anonymize_creator(user_id, &mut write).await?;
+
+    let locale = UserLocale::by_user(user_id, &mut write).await?;
+    if let Some(email) = write.ctx.email.as_ref() {
+        email.account_deleted(&user, &locale).await?;
+    } else {
+        warn!("Unable to send account deletion email without email configured");
+    }
+    User::anonymize(user_id, &mut write).await?;
+
+    Ok(api::UserServiceDeleteAccountResponse {})
+}
+
+/// Request an export of all data held about a user.
+///
+/// The request is stored as pending, then assembled by a background job and a
+/// download link is emailed to the user once it is ready.
+pub async fn export_data(
+    req: api::UserServiceExportDataRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceExportDataResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    write.auth_for(&meta, UserPerm::ExportData, user_id).await?;
+
+    let user = User::by_id(user_id, &mut write).await?;
+    if write.ctx.email.is_none() {
+        return Err(Error::NoEmail);
+    }
+    NewUserExportRequest::new(user.id)
+        .create(&mut write)
+        .await?;
+
+    Ok(api::UserServiceExportDataResponse {})
+}
+
+/// Allow support staff to impersonate the user for a limited time.
+pub async fn grant_support_access(
+    req: api::UserServiceGrantSupportAccessRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceGrantSupportAccessResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    let authz = write
+        .auth_for(&meta, UserPerm::GrantSupportAccess, user_id)
+        .await?;
+    // an impersonated user can't extend their own consent
+    if authz.claims.impersonator.is_some() {
+        return Err(Error::Impersonated);
+    }
+
+    let until = Utc::now() + SUPPORT_ACCESS_DURATION;
+    User::set_support_access(user_id, Some(until), &mut write).await?;
+
+    Ok(api::UserServiceGrantSupportAccessResponse {
+        expires_at: Some(NanosUtc::from(until).into()),
+    })
+}
+
+/// Withdraw any support access previously granted by the user.
+pub async fn revoke_support_access(
+    req: api::UserServiceRevokeSupportAccessRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceRevokeSupportAccessResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    write
+        .auth_for(&meta, UserPerm::RevokeSupportAccess, user_id)
+        .await?;
+
+    User::set_support_access(user_id, None, &mut write).await?;
+
+    Ok(api::UserServiceRevokeSupportAccessResponse {})
+}
+
+/// Require a user to set a new password before they can next log in.
+pub async fn require_password_reset(
+    req: api::UserServiceRequirePasswordResetRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceRequirePasswordResetResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    write
+        .auth(&meta, UserAdminPerm::RequirePasswordReset)
+        .await?;
+
+    User::require_password_reset(user_id, &mut write).await?;
+
+    Ok(api::UserServiceRequirePasswordResetResponse {})
+}
+
 pub async fn get_settings(
     req: api::UserServiceGetSettingsRequest,
     meta: Metadata,
     mut read: ReadConn<'_, '_>,
 ) -> Result<api::UserServiceGetSettingsResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
     read.auth_or_for(
         &meta,
@@ -279,6 +629,7 @@ pub async fn update_settings(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::UserServiceUpdateSettingsResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
     write
         .auth_or_for(
@@ -290,7 +641,7 @@ pub async fn update_settings(
         .await?;
 
     let user = User::by_id(user_id, &mut write).await?;
-    let setting = NewUserSetting::new(user.id, req.key, &req.value)
+    let setting = NewUserSetting::validated(user.id, req.key, &req.value)?
         .create_or_update(&mut write)
         .await?;
 
@@ -305,6 +656,7 @@ pub async fn delete_settings(
     meta: Metadata,
     mut write: WriteConn<'_, '_>,
 ) -> Result<api::UserServiceDeleteSettingsResponse, Error> {
+    req.validate()?;
     let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
     write
         .auth_or_for(
@@ -321,6 +673,110 @@ pub async fn delete_settings(
     Ok(api::UserServiceDeleteSettingsResponse {})
 }
 
+/// Register a mobile device to receive push notifications for the user.
+pub async fn register_device(
+    req: api::UserServiceRegisterDeviceRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceRegisterDeviceResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    write
+        .auth_for(&meta, UserPerm::RegisterDevice, user_id)
+        .await?;
+
+    let new_device = NewUserDevice {
+        user_id,
+        platform: PushPlatform::try_from(req.platform())?,
+        push_token: req.push_token,
+        name: req.name.trim().to_string(),
+    };
+    let device = new_device.register(&mut write).await?;
+
+    Ok(api::UserServiceRegisterDeviceResponse {
+        device: Some(device.into()),
+    })
+}
+
+pub async fn list_devices(
+    req: api::UserServiceListDevicesRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::UserServiceListDevicesResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    read.auth_for(&meta, UserPerm::ListDevices, user_id).await?;
+
+    let devices = UserDevice::by_user(user_id, &mut read)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok(api::UserServiceListDevicesResponse { devices })
+}
+
+pub async fn remove_device(
+    req: api::UserServiceRemoveDeviceRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceRemoveDeviceResponse, Error> {
+    req.validate()?;
+    let id: UserDeviceId = req.device_id.parse().map_err(Error::ParseDeviceId)?;
+    let device = UserDevice::by_id(id, &mut write).await?;
+    write
+        .auth_for(&meta, UserPerm::RemoveDevice, device.user_id)
+        .await?;
+
+    device.delete(&mut write).await?;
+
+    Ok(api::UserServiceRemoveDeviceResponse {})
+}
+
+pub async fn get_notification_preferences(
+    req: api::UserServiceGetNotificationPreferencesRequest,
+    meta: Metadata,
+    mut read: ReadConn<'_, '_>,
+) -> Result<api::UserServiceGetNotificationPreferencesResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    read.auth_for(&meta, UserPerm::GetNotificationPreferences, user_id)
+        .await?;
+
+    let preferences = NotificationPreferences::by_user(user_id, &mut read).await?;
+
+    Ok(api::UserServiceGetNotificationPreferencesResponse {
+        preferences: Some(preferences.into()),
+    })
+}
+
+/// Update the notification preferences, leaving unset fields unchanged.
+pub async fn update_notification_preferences(
+    req: api::UserServiceUpdateNotificationPreferencesRequest,
+    meta: Metadata,
+    mut write: WriteConn<'_, '_>,
+) -> Result<api::UserServiceUpdateNotificationPreferencesResponse, Error> {
+    req.validate()?;
+    let user_id: UserId = req.user_id.parse().map_err(Error::ParseId)?;
+    write
+        .auth_for(&meta, UserPerm::UpdateNotificationPreferences, user_id)
+        .await?;
+
+    let mut preferences = NotificationPreferences::by_user(user_id, &mut write).await?;
+    if let Some(node_down) = req.node_down {
+        preferences.node_down = node_down;
+    }
+    if let Some(payment_failed) = req.payment_failed {
+        preferences.payment_failed = payment_failed;
+    }
+    preferences.updated_at = Utc::now();
+    let preferences = preferences.upsert(&mut write).await?;
+
+    Ok(api::UserServiceUpdateNotificationPreferencesResponse {
+        preferences: Some(preferences.into()),
+    })
+}
+
 impl api::UserServiceListRequest {
     fn into_filter(self) -> Result<UserFilter, Error> {
         let user_ids = self
@@ -372,3 +828,116 @@ impl api::UserServiceListRequest {
         })
     }
 }
+
+impl Validate for api::UserServiceCreateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.no_secrets("first_name", &self.first_name);
+        v.no_secrets("last_name", &self.last_name);
+    }
+}
+
+impl Validate for api::UserServiceGetRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceListRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuids("user_ids", &self.user_ids);
+        v.uuids("org_ids", &self.org_ids);
+    }
+}
+
+impl Validate for api::UserServiceUpdateRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+        v.maybe_no_secrets("first_name", self.first_name.as_deref());
+        v.maybe_no_secrets("last_name", self.last_name.as_deref());
+    }
+}
+
+impl Validate for api::UserServiceDeleteRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceDeleteAccountRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceExportDataRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceGrantSupportAccessRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceRevokeSupportAccessRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceRequirePasswordResetRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceGetSettingsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceUpdateSettingsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceDeleteSettingsRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceRegisterDeviceRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+        v.no_secrets("name", &self.name);
+    }
+}
+
+impl Validate for api::UserServiceListDevicesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceRemoveDeviceRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("device_id", &self.device_id);
+    }
+}
+
+impl Validate for api::UserServiceGetNotificationPreferencesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
+
+impl Validate for api::UserServiceUpdateNotificationPreferencesRequest {
+    fn constraints(&self, v: &mut Validator) {
+        v.uuid("user_id", &self.user_id);
+    }
+}
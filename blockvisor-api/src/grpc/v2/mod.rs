@@ -0,0 +1,10 @@
+//! Services of the `blockjoy.v2` proto package.
+//!
+//! Each v2 service is a compatibility shim over its v1 handlers: requests are
+//! converted to their v1 form, handled as before, and the v1 responses are
+//! reshaped into v2 messages. This lets messages evolve without breaking older
+//! clients like blockvisord, which keep talking to the v1 services until their
+//! sunset. Messages that don't change between generations are imported from
+//! the v1 package rather than copied.
+
+pub mod node;
@@ -0,0 +1,167 @@
+//! The v2 node service, which splits a node into its desired `spec` and its
+//! observed `status`.
+
+use diesel_async::scoped_futures::ScopedFutureExt;
+use tonic::{Request, Response};
+
+use crate::database::{ReadConn, Transaction};
+use crate::grpc::api_v2::node_service_server::NodeService;
+use crate::grpc::node::{self, Error};
+use crate::grpc::{Grpc, Metadata, api, api_v2};
+
+#[tonic::async_trait]
+impl NodeService for Grpc {
+    async fn get(
+        &self,
+        req: Request<api_v2::NodeServiceGetRequest>,
+    ) -> Result<Response<api_v2::NodeServiceGetResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| get(req, meta.into(), read).scope_boxed())
+            .await
+    }
+
+    async fn list(
+        &self,
+        req: Request<api::NodeServiceListRequest>,
+    ) -> Result<Response<api_v2::NodeServiceListResponse>, tonic::Status> {
+        let (meta, _, req) = req.into_parts();
+        self.read(|read| list(req, meta.into(), read).scope_boxed())
+            .await
+    }
+}
+
+pub async fn get(
+    req: api_v2::NodeServiceGetRequest,
+    meta: Metadata,
+    read: ReadConn<'_, '_>,
+) -> Result<api_v2::NodeServiceGetResponse, Error> {
+    let req = api::NodeServiceGetRequest {
+        node_id: req.node_id,
+    };
+    let resp = node::get(req, meta, read).await?;
+
+    Ok(api_v2::NodeServiceGetResponse {
+        node: resp.node.map(Into::into),
+    })
+}
+
+pub async fn list(
+    req: api::NodeServiceListRequest,
+    meta: Metadata,
+    read: ReadConn<'_, '_>,
+) -> Result<api_v2::NodeServiceListResponse, Error> {
+    let resp = node::list(req, meta, read).await?;
+
+    Ok(api_v2::NodeServiceListResponse {
+        nodes: resp.nodes.into_iter().map(Into::into).collect(),
+        total: resp.total,
+    })
+}
+
+/// Every field is destructured so that a field added to `api::Node` fails to
+/// compile until it is mapped into the `spec` or `status` of a v2 node.
+impl From<api::Node> for api_v2::Node {
+    fn from(node: api::Node) -> Self {
+        let api::Node {
+            node_id,
+            org_id,
+            org_name,
+            node_name,
+            display_name,
+            old_node_id,
+            image_id,
+            config_id,
+            config,
+            host_id,
+            host_org_id,
+            host_network_name,
+            host_display_name,
+            host_pool_id,
+            placement_policy,
+            region_id,
+            region_key,
+            region_name,
+            protocol_id,
+            protocol_name,
+            protocol_version_id,
+            version_key,
+            semantic_version,
+            auto_upgrade,
+            ip_address,
+            ip_gateway,
+            dns_name,
+            dns_aliases,
+            p2p_address,
+            dns_url,
+            block_height,
+            block_age,
+            sync_lag,
+            note,
+            node_status,
+            jobs,
+            reports,
+            tags,
+            created_by,
+            created_at,
+            updated_at,
+            cost,
+            config_drift,
+            project_id,
+            network_id,
+            version_metadata,
+        } = node;
+
+        api_v2::Node {
+            node_id,
+            org_id,
+            org_name,
+            node_name,
+            display_name,
+            spec: Some(api_v2::NodeSpec {
+                old_node_id,
+                image_id,
+                config_id,
+                config,
+                host_id,
+                host_org_id,
+                host_network_name,
+                host_display_name,
+                host_pool_id,
+                placement_policy,
+                region_id,
+                region_key,
+                region_name,
+                protocol_id,
+                protocol_name,
+                protocol_version_id,
+                version_key,
+                version_metadata,
+                semantic_version,
+                auto_upgrade,
+                tags,
+                note,
+                cost,
+                project_id,
+                network_id,
+            }),
+            status: Some(api_v2::NodeStatus {
+                node_status,
+                ip_address,
+                ip_gateway,
+                dns_name,
+                dns_url,
+                dns_aliases,
+                p2p_address,
+                block_height,
+                block_age,
+                sync_lag,
+                jobs,
+                reports,
+                config_drift,
+            }),
+            created_by,
+            created_at,
+            updated_at,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Field-level validation of incoming requests.
+//!
+//! Request types implement `Validate` to describe their constraints, and
+//! handlers call `req.validate()?` before doing any work. All violations are
+//! collected rather than stopping at the first one, and are returned to the
+//! client as a `google.rpc.BadRequest` detail with one entry per field.
+
+use std::ops::RangeInclusive;
+
+use displaydoc::Display;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::util::secrets;
+
+use super::Status;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Request has invalid fields: {0:?}
+    Invalid(Vec<FieldViolation>),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Invalid(violations) => Status::bad_request(violations),
+        }
+    }
+}
+
+/// A single invalid request field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub description: String,
+}
+
+/// Describes the constraints of a request type.
+pub trait Validate {
+    fn constraints(&self, validator: &mut Validator);
+
+    fn validate(&self) -> Result<(), Error> {
+        let mut validator = Validator::default();
+        self.constraints(&mut validator);
+        validator.finish()
+    }
+}
+
+/// Collects the violations of each request field.
+#[derive(Debug, Default)]
+pub struct Validator {
+    violations: Vec<FieldViolation>,
+}
+
+impl Validator {
+    /// Add a violation for `field` unless `valid` holds.
+    ///
+    /// This is also used for constraints spanning several fields.
+    pub fn check(&mut self, valid: bool, field: &str, description: impl Into<String>) {
+        if !valid {
+            self.violations.push(FieldViolation {
+                field: field.to_string(),
+                description: description.into(),
+            });
+        }
+    }
+
+    /// Require `value` to be a UUID.
+    pub fn uuid(&mut self, field: &str, value: &str) {
+        let valid = Uuid::try_parse(value).is_ok();
+        self.check(valid, field, "Must be a UUID.");
+    }
+
+    /// Require `value` to be a UUID when set.
+    pub fn maybe_uuid(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.uuid(field, value);
+        }
+    }
+
+    /// Require every one of `values` to be a UUID.
+    pub fn uuids(&mut self, field: &str, values: &[String]) {
+        for value in values {
+            self.uuid(field, value);
+        }
+    }
+
+    /// Require the trimmed length of `value` to be within `chars`.
+    pub fn length(&mut self, field: &str, value: &str, chars: RangeInclusive<usize>) {
+        let len = value.trim().chars().count();
+        let description = format!(
+            "Must be between {} and {} characters.",
+            chars.start(),
+            chars.end()
+        );
+        self.check(chars.contains(&len), field, description);
+    }
+
+    /// Require free-text `value` not to contain a private key or seed phrase.
+    pub fn no_secrets(&mut self, field: &str, value: &str) {
+        let valid = !secrets::contains_secret(value);
+        self.check(
+            valid,
+            field,
+            "Must not contain a private key or seed phrase.",
+        );
+    }
+
+    /// Require free-text `value` not to contain secrets when set.
+    pub fn maybe_no_secrets(&mut self, field: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            self.no_secrets(field, value);
+        }
+    }
+
+    /// Require `value` to be a known, specified variant of the proto enum `E`.
+    pub fn enum_value<E: TryFrom<i32>>(&mut self, field: &str, value: i32) {
+        let valid = value != 0 && E::try_from(value).is_ok();
+        self.check(valid, field, "Must be a known enum value.");
+    }
+
+    /// Require `value` to be a known, specified variant of `E` when set.
+    pub fn maybe_enum_value<E: TryFrom<i32>>(&mut self, field: &str, value: Option<i32>) {
+        if let Some(value) = value {
+            self.enum_value::<E>(field, value);
+        }
+    }
+
+    /// Require every one of `values` to be a known variant of `E`.
+    pub fn enum_values<E: TryFrom<i32>>(&mut self, field: &str, values: &[i32]) {
+        for value in values {
+            self.enum_value::<E>(field, *value);
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if self.violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid(self.violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Request {
+        id: String,
+        name: String,
+        assignee_id: Option<String>,
+        unassign: bool,
+    }
+
+    impl Validate for Request {
+        fn constraints(&self, v: &mut Validator) {
+            v.uuid("id", &self.id);
+            v.length("name", &self.name, 1..=8);
+            v.maybe_uuid("assignee_id", self.assignee_id.as_deref());
+            v.check(
+                !(self.unassign && self.assignee_id.is_some()),
+                "unassign",
+                "Can't be set with `assignee_id`.",
+            );
+        }
+    }
+
+    #[test]
+    fn valid_request_passes() {
+        let req = Request {
+            id: Uuid::new_v4().to_string(),
+            name: "node".into(),
+            assignee_id: None,
+            unassign: true,
+        };
+        req.validate().unwrap();
+    }
+
+    #[test]
+    fn every_violation_is_reported() {
+        let req = Request {
+            id: "not-a-uuid".into(),
+            name: "   ".into(),
+            assignee_id: Some(Uuid::new_v4().to_string()),
+            unassign: true,
+        };
+        let Error::Invalid(violations) = req.validate().unwrap_err();
+        let fields: Vec<_> = violations.iter().map(|v| v.field.as_str()).collect();
+        assert_eq!(fields, ["id", "name", "unassign"]);
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/request/:request_id", routing::get(lookup_request))
+        .with_state(context)
+}
+
+async fn lookup_request(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((request_id,)): Path<(String,)>,
+) -> Result<Json<api::AdminServiceLookupRequestResponse>, Error> {
+    let req = api::AdminServiceLookupRequestRequest { request_id };
+    ctx.read(|read| grpc::admin::lookup_request(req, headers.into(), read).scope_boxed())
+        .await
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::get(list))
+        .route("/acknowledge", routing::post(acknowledge))
+        .route("/oncall", routing::get(get_oncall))
+        .route("/schedule", routing::post(create_schedule))
+        .route("/schedule", routing::delete(delete_schedule))
+        .route("/policy", routing::put(set_policy))
+        .with_state(context)
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::AlertServiceListRequest>,
+) -> Result<Json<api::AlertServiceListResponse>, Error> {
+    ctx.read(|read| grpc::alert::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn acknowledge(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::AlertServiceAcknowledgeRequest>,
+) -> Result<Json<api::AlertServiceAcknowledgeResponse>, Error> {
+    ctx.write(|write| grpc::alert::acknowledge(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_oncall(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::AlertServiceGetOncallRequest>,
+) -> Result<Json<api::AlertServiceGetOncallResponse>, Error> {
+    ctx.read(|read| grpc::alert::get_oncall(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn create_schedule(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::AlertServiceCreateScheduleRequest>,
+) -> Result<Json<api::AlertServiceCreateScheduleResponse>, Error> {
+    ctx.write(|write| grpc::alert::create_schedule(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn delete_schedule(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::AlertServiceDeleteScheduleRequest>,
+) -> Result<Json<api::AlertServiceDeleteScheduleResponse>, Error> {
+    ctx.write(|write| grpc::alert::delete_schedule(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn set_policy(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::AlertServiceSetPolicyRequest>,
+) -> Result<Json<api::AlertServiceSetPolicyResponse>, Error> {
+    ctx.write(|write| grpc::alert::set_policy(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::get(list))
+        .route("/:id", routing::get(get))
+        .route("/:id/review", routing::post(review))
+        .route("/:id/cancel", routing::post(cancel))
+        .with_state(context)
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::ApprovalServiceListRequest>,
+) -> Result<Json<api::ApprovalServiceListResponse>, Error> {
+    ctx.read(|read| grpc::approval::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((approval_id,)): Path<(String,)>,
+) -> Result<Json<api::ApprovalServiceGetResponse>, Error> {
+    let req = api::ApprovalServiceGetRequest { approval_id };
+    ctx.read(|read| grpc::approval::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApprovalServiceReviewRequest {
+    approve: bool,
+}
+
+async fn review(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((approval_id,)): Path<(String,)>,
+    Json(req): Json<ApprovalServiceReviewRequest>,
+) -> Result<Json<api::ApprovalServiceReviewResponse>, Error> {
+    let req = api::ApprovalServiceReviewRequest {
+        approval_id,
+        approve: req.approve,
+    };
+    ctx.write(|write| grpc::approval::review(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn cancel(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((approval_id,)): Path<(String,)>,
+) -> Result<Json<api::ApprovalServiceCancelResponse>, Error> {
+    let req = api::ApprovalServiceCancelRequest { approval_id };
+    ctx.write(|write| grpc::approval::cancel(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -22,6 +22,9 @@ where
         .route("/password", routing::put(update_password))
         .route("/ui_password", routing::put(update_ui_password))
         .route("/permissions", routing::get(list_permissions))
+        .route("/impersonate", routing::post(impersonate))
+        .route("/sessions", routing::get(list_sessions))
+        .route("/sessions/revoke", routing::post(revoke_session))
         .with_state(context)
 }
 
@@ -87,3 +90,30 @@ async fn list_permissions(
     ctx.write(|write| grpc::auth::list_permissions(req, headers.into(), write).scope_boxed())
         .await
 }
+
+async fn impersonate(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::AuthServiceImpersonateRequest>,
+) -> Result<Json<api::AuthServiceImpersonateResponse>, super::Error> {
+    ctx.write(|write| grpc::auth::impersonate(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list_sessions(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::AuthServiceListSessionsRequest>,
+) -> Result<Json<api::AuthServiceListSessionsResponse>, super::Error> {
+    ctx.write(|write| grpc::auth::list_sessions(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn revoke_session(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::AuthServiceRevokeSessionRequest>,
+) -> Result<Json<api::AuthServiceRevokeSessionResponse>, super::Error> {
+    ctx.write(|write| grpc::auth::revoke_session(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/estimate", routing::post(estimate))
+        .with_state(context)
+}
+
+async fn estimate(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::BillingServiceEstimateRequest>,
+) -> Result<Json<api::BillingServiceEstimateResponse>, Error> {
+    ctx.read(|read| grpc::billing::estimate(req, headers.into(), read).scope_boxed())
+        .await
+}
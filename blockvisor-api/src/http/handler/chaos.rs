@@ -0,0 +1,42 @@
+//! Arm the injected faults of a mocked `Context` over http.
+//!
+//! Only built with the `integration-test` feature, so it has no auth:
+//!
+//! - `GET /v1/chaos` returns the armed faults.
+//! - `PUT /v1/chaos` arms each fault set in the body.
+//! - `DELETE /v1/chaos` disarms every fault.
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::State;
+use axum::routing::{Router, get};
+
+use crate::chaos::FaultState;
+use crate::config::Context;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", get(state).put(arm).delete(reset))
+        .with_state(context)
+}
+
+#[allow(clippy::unused_async)]
+async fn state(State(ctx): State<Arc<Context>>) -> Json<FaultState> {
+    Json(ctx.faults.state())
+}
+
+#[allow(clippy::unused_async)]
+async fn arm(State(ctx): State<Arc<Context>>, Json(faults): Json<FaultState>) -> Json<FaultState> {
+    ctx.faults.arm(faults);
+    Json(ctx.faults.state())
+}
+
+#[allow(clippy::unused_async)]
+async fn reset(State(ctx): State<Arc<Context>>) -> Json<FaultState> {
+    ctx.faults.reset();
+    Json(ctx.faults.state())
+}
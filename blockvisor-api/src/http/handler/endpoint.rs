@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/:id", routing::delete(revoke))
+        .route("/usage", routing::get(usage))
+        .route("/usage", routing::post(report_usage))
+        .with_state(context)
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::EndpointServiceCreateRequest>,
+) -> Result<Json<api::EndpointServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::endpoint::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::EndpointServiceListRequest>,
+) -> Result<Json<api::EndpointServiceListResponse>, Error> {
+    ctx.read(|read| grpc::endpoint::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn revoke(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((endpoint_id,)): Path<(String,)>,
+) -> Result<Json<api::EndpointServiceRevokeResponse>, Error> {
+    let req = api::EndpointServiceRevokeRequest { endpoint_id };
+    ctx.write(|write| grpc::endpoint::revoke(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn usage(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::EndpointServiceUsageRequest>,
+) -> Result<Json<api::EndpointServiceUsageResponse>, Error> {
+    ctx.read(|read| grpc::endpoint::usage(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn report_usage(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::EndpointServiceReportUsageRequest>,
+) -> Result<Json<api::EndpointServiceReportUsageResponse>, Error> {
+    ctx.write(|write| grpc::endpoint::report_usage(req, headers.into(), write).scope_boxed())
+        .await
+}
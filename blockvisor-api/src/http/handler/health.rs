@@ -1,28 +1,258 @@
-//! Health handler used to indicate system status.
+//! Health handlers used to indicate system status.
+//!
+//! `/healthz` is a liveness probe that only checks the process is serving
+//! requests, while `/readyz` is a readiness probe that checks each dependency
+//! and reports its status. Dependency checks are cached for `READY_CACHE` so
+//! that frequent probes don't load the database or stripe.
+//!
+//! Only the database and MQTT broker are required to be ready. Stripe is
+//! reported for dashboards but a stripe outage does not take the api out of
+//! the load balancer. The secret store is probed by reading each served secret
+//! and is likewise reported without being required.
 
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use axum::Json;
 use axum::extract::State;
+use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::routing::{Router, get};
+use diesel_async::RunQueryDsl;
+use serde::Serialize;
+use tokio::sync::Mutex;
 
 use crate::config::Context;
+use crate::database::Database;
 use crate::http::response;
+use crate::stripe::Payment;
+
+/// How long the results of the readiness checks are reused.
+const READY_CACHE: Duration = Duration::from_secs(10);
+/// How long each dependency has to respond.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 
 pub fn router<S>(context: Arc<Context>) -> Router<S>
 where
     S: Clone + Send + Sync,
 {
+    let readiness = Arc::new(Readiness::new(context.clone()));
+
     Router::new()
         .route("/health", get(health))
+        .route("/healthz", get(healthz))
         .with_state(context)
+        .merge(
+            Router::new()
+                .route("/readyz", get(readyz))
+                .with_state(readiness),
+        )
 }
 
 #[allow(clippy::unused_async)]
 async fn health(State(ctx): State<Arc<Context>>) -> Response {
-    if ctx.pool.is_open() {
+    if ctx.shutdown.is_draining() {
+        response::draining().into_response()
+    } else if ctx.pool.is_open() {
         response::ok().into_response()
     } else {
         response::db_closed().into_response()
     }
 }
+
+#[allow(clippy::unused_async)]
+async fn healthz() -> Response {
+    response::ok().into_response()
+}
+
+async fn readyz(State(readiness): State<Arc<Readiness>>) -> Response {
+    let report = readiness.report().await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report)).into_response()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CheckStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    latency_ms: u64,
+}
+
+impl Check {
+    async fn run<F, E>(name: &'static str, required: bool, check: F) -> Self
+    where
+        F: Future<Output = Result<(), E>>,
+        E: std::fmt::Display,
+    {
+        let started = Instant::now();
+        let (status, message) = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+            Ok(Ok(())) => (CheckStatus::Ok, None),
+            Ok(Err(err)) => (CheckStatus::Failed, Some(err.to_string())),
+            Err(_) => (CheckStatus::Failed, Some("Timed out.".to_string())),
+        };
+
+        Check {
+            name,
+            status,
+            required,
+            message,
+            latency_ms: u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        }
+    }
+
+    fn skipped(name: &'static str, message: &str) -> Self {
+        Check {
+            name,
+            status: CheckStatus::Skipped,
+            required: false,
+            message: Some(message.to_string()),
+            latency_ms: 0,
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        !self.required || self.status == CheckStatus::Ok
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Report {
+    ready: bool,
+    draining: bool,
+    checks: Vec<Check>,
+}
+
+struct Readiness {
+    context: Arc<Context>,
+    cache: Mutex<Option<(Instant, Report)>>,
+}
+
+impl Readiness {
+    fn new(context: Arc<Context>) -> Self {
+        Readiness {
+            context,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// The cached report, or a new one once the cache has expired.
+    ///
+    /// Concurrent probes wait on the same checks rather than running their own.
+    async fn report(&self) -> Report {
+        let draining = self.context.shutdown.is_draining();
+        let mut cache = self.cache.lock().await;
+
+        let mut report = match cache.as_ref() {
+            Some((checked_at, report)) if checked_at.elapsed() < READY_CACHE => report.clone(),
+            _ => {
+                let report = self.check().await;
+                *cache = Some((Instant::now(), report.clone()));
+                report
+            }
+        };
+
+        // draining is never cached so the load balancer reacts right away
+        report.draining = draining;
+        report.ready &= !draining;
+        report
+    }
+
+    async fn check(&self) -> Report {
+        let ctx = &self.context;
+
+        let database = Check::run("database", true, async {
+            let mut conn = ctx.pool.conn().await.map_err(|err| err.to_string())?;
+            diesel::sql_query("select 1")
+                .execute(&mut conn)
+                .await
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        });
+
+        let mqtt = Check::run("mqtt", true, async {
+            if ctx.notifier.is_connected() {
+                Ok(())
+            } else {
+                Err("Not connected to the broker.")
+            }
+        });
+
+        let stripe = async {
+            match &ctx.stripe {
+                Some(stripe) => {
+                    Check::run("stripe", false, async {
+                        stripe.get_account().await.map(|_| ())
+                    })
+                    .await
+                }
+                None => Check::skipped("stripe", "Stripe is not configured."),
+            }
+        };
+
+        let vault = Check::run("vault", false, async { ctx.secret.probe() });
+
+        let (database, mqtt, stripe, vault) = tokio::join!(database, mqtt, stripe, vault);
+
+        let checks = vec![database, mqtt, stripe, vault];
+        Report {
+            ready: checks.iter().all(Check::is_ready),
+            draining: false,
+            checks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(report: &Report, name: &str) -> CheckStatus {
+        report
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .map(|check| check.status)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn readiness_checks_each_dependency() {
+        let (ctx, _db) = Context::with_mocked().await.unwrap();
+        let readiness = Readiness::new(ctx);
+
+        let report = readiness.report().await;
+        assert!(report.ready);
+        assert!(!report.draining);
+        assert_eq!(status(&report, "database"), CheckStatus::Ok);
+        assert_eq!(status(&report, "mqtt"), CheckStatus::Ok);
+        assert_eq!(status(&report, "vault"), CheckStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn draining_is_not_ready() {
+        let (ctx, _db) = Context::with_mocked().await.unwrap();
+        let readiness = Readiness::new(ctx.clone());
+        assert!(readiness.report().await.ready);
+
+        ctx.shutdown.start();
+        let report = readiness.report().await;
+        assert!(!report.ready);
+        assert!(report.draining);
+    }
+}
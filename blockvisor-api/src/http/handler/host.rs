@@ -5,6 +5,7 @@ use axum::extract::{Path, Query, State};
 use axum::http::header::HeaderMap;
 use axum::routing::{self, Router};
 use diesel_async::scoped_futures::ScopedFutureExt;
+use prost_wkt_types::FieldMask;
 
 use crate::config::Context;
 use crate::database::Transaction;
@@ -23,12 +24,24 @@ where
         .route("/region/:id", routing::get(get_region))
         .route("/", routing::get(list_hosts))
         .route("/regions", routing::get(list_regions))
+        .route("/regions/availability", routing::post(region_availability))
         .route("/:id", routing::put(update_host))
         .route("/region/:id", routing::put(update_region))
         .route("/:id", routing::delete(delete_host))
         .route("/:id/start", routing::put(start))
         .route("/:id/stop", routing::put(stop))
         .route("/:id/restart", routing::put(restart))
+        .route("/versions", routing::get(version_report))
+        .route("/versions/upgrade", routing::post(upgrade_outdated))
+        .route("/agent-upgrade", routing::post(upgrade_agent))
+        .route("/agent-upgrade/:id", routing::get(get_agent_upgrade))
+        .route("/agent-upgrade/:id/halt", routing::put(halt_agent_upgrade))
+        .route("/pool", routing::post(create_pool))
+        .route("/pool/:id", routing::get(get_pool))
+        .route("/pools", routing::get(list_pools))
+        .route("/pool/:id", routing::put(update_pool))
+        .route("/pool/:id", routing::delete(delete_pool))
+        .route("/:id/pool", routing::put(set_host_pool))
         .with_state(context)
 }
 
@@ -92,6 +105,15 @@ async fn list_regions(
         .await
 }
 
+async fn region_availability(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::HostServiceRegionAvailabilityRequest>,
+) -> Result<Json<api::HostServiceRegionAvailabilityResponse>, Error> {
+    ctx.read(|read| grpc::host::region_availability(req, headers.into(), read).scope_boxed())
+        .await
+}
+
 #[derive(serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 struct HostServiceUpdateHostRequest {
@@ -104,9 +126,14 @@ struct HostServiceUpdateHostRequest {
     cpu_cores: Option<u64>,
     memory_bytes: Option<u64>,
     disk_bytes: Option<u64>,
+    gpu_count: Option<u64>,
+    gpu_model: Option<String>,
+    nvme_iops: Option<u64>,
+    network_bandwidth_mbps: Option<u64>,
     schedule_type: Option<i32>,
     update_tags: Option<common::UpdateTags>,
     cost: Option<common::BillingAmount>,
+    update_mask: Option<FieldMask>,
 }
 
 async fn update_host(
@@ -126,9 +153,14 @@ async fn update_host(
         cpu_cores: req.cpu_cores,
         memory_bytes: req.memory_bytes,
         disk_bytes: req.disk_bytes,
+        gpu_count: req.gpu_count,
+        gpu_model: req.gpu_model,
+        nvme_iops: req.nvme_iops,
+        network_bandwidth_mbps: req.network_bandwidth_mbps,
         schedule_type: req.schedule_type,
         update_tags: req.update_tags,
         cost: req.cost,
+        update_mask: req.update_mask,
     };
     ctx.write(|write| grpc::host::update_host(req, headers.into(), write).scope_boxed())
         .await
@@ -195,3 +227,130 @@ async fn restart(
     ctx.write(|write| grpc::host::restart(req, headers.into(), write).scope_boxed())
         .await
 }
+
+async fn version_report(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::HostServiceVersionReportRequest>,
+) -> Result<Json<api::HostServiceVersionReportResponse>, Error> {
+    ctx.read(|read| grpc::host::version_report(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn upgrade_outdated(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::HostServiceUpgradeOutdatedRequest>,
+) -> Result<Json<api::HostServiceUpgradeOutdatedResponse>, Error> {
+    ctx.write(|write| grpc::host::upgrade_outdated(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn upgrade_agent(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::HostServiceUpgradeAgentRequest>,
+) -> Result<Json<api::HostServiceUpgradeAgentResponse>, Error> {
+    ctx.write(|write| grpc::host::upgrade_agent(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_agent_upgrade(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((agent_upgrade_id,)): Path<(String,)>,
+) -> Result<Json<api::HostServiceGetAgentUpgradeResponse>, Error> {
+    let req = api::HostServiceGetAgentUpgradeRequest { agent_upgrade_id };
+    ctx.read(|read| grpc::host::get_agent_upgrade(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn halt_agent_upgrade(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((agent_upgrade_id,)): Path<(String,)>,
+) -> Result<Json<api::HostServiceHaltAgentUpgradeResponse>, Error> {
+    let req = api::HostServiceHaltAgentUpgradeRequest { agent_upgrade_id };
+    ctx.write(|write| grpc::host::halt_agent_upgrade(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn create_pool(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::HostServiceCreatePoolRequest>,
+) -> Result<Json<api::HostServiceCreatePoolResponse>, Error> {
+    ctx.write(|write| grpc::host::create_pool(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_pool(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+) -> Result<Json<api::HostServiceGetPoolResponse>, Error> {
+    let req = api::HostServiceGetPoolRequest { pool_id };
+    ctx.read(|read| grpc::host::get_pool(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list_pools(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::HostServiceListPoolsRequest>,
+) -> Result<Json<api::HostServiceListPoolsResponse>, Error> {
+    ctx.read(|read| grpc::host::list_pools(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HostServiceUpdatePoolRequest {
+    name: Option<String>,
+    description: Option<String>,
+}
+
+async fn update_pool(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+    Json(req): Json<HostServiceUpdatePoolRequest>,
+) -> Result<Json<api::HostServiceUpdatePoolResponse>, Error> {
+    let req = api::HostServiceUpdatePoolRequest {
+        pool_id,
+        name: req.name,
+        description: req.description,
+    };
+    ctx.write(|write| grpc::host::update_pool(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn delete_pool(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+) -> Result<Json<api::HostServiceDeletePoolResponse>, Error> {
+    let req = api::HostServiceDeletePoolRequest { pool_id };
+    ctx.write(|write| grpc::host::delete_pool(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HostServiceSetHostPoolRequest {
+    pool_id: Option<String>,
+}
+
+async fn set_host_pool(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((host_id,)): Path<(String,)>,
+    Json(req): Json<HostServiceSetHostPoolRequest>,
+) -> Result<Json<api::HostServiceSetHostPoolResponse>, Error> {
+    let req = api::HostServiceSetHostPoolRequest {
+        host_id,
+        pool_id: req.pool_id,
+    };
+    ctx.write(|write| grpc::host::set_host_pool(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/", routing::put(update))
+        .route("/:id", routing::get(get))
+        .route("/comment", routing::post(comment))
+        .route("/link", routing::post(link))
+        .with_state(context)
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::IncidentServiceCreateRequest>,
+) -> Result<Json<api::IncidentServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::incident::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((incident_id,)): Path<(String,)>,
+) -> Result<Json<api::IncidentServiceGetResponse>, Error> {
+    let req = api::IncidentServiceGetRequest { incident_id };
+    ctx.read(|read| grpc::incident::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::IncidentServiceListRequest>,
+) -> Result<Json<api::IncidentServiceListResponse>, Error> {
+    ctx.read(|read| grpc::incident::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn update(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::IncidentServiceUpdateRequest>,
+) -> Result<Json<api::IncidentServiceUpdateResponse>, Error> {
+    ctx.write(|write| grpc::incident::update(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn comment(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::IncidentServiceCommentRequest>,
+) -> Result<Json<api::IncidentServiceCommentResponse>, Error> {
+    ctx.write(|write| grpc::incident::comment(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn link(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::IncidentServiceLinkRequest>,
+) -> Result<Json<api::IncidentServiceLinkResponse>, Error> {
+    ctx.write(|write| grpc::incident::link(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+use crate::job;
+use crate::model::backfill::Task;
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/node", routing::get(search_nodes))
+        .route("/node/state", routing::put(override_node_state))
+        .route("/host", routing::get(search_hosts))
+        .route("/cleanup/dns", routing::post(cleanup_dns))
+        .route("/cleanup/stripe", routing::post(cleanup_stripe))
+        .route("/flag", routing::get(list_feature_flags))
+        .route("/flag", routing::put(set_feature_flag))
+        .route("/host-weights", routing::get(get_host_weights))
+        .route("/host-weights", routing::put(set_host_weights))
+        .route("/backfill", routing::get(list_backfills))
+        .route("/backfill", routing::post(run_backfill))
+        .route("/network/head", routing::get(list_network_heads))
+        .route("/network/head", routing::put(set_network_head))
+        .route("/retention", routing::get(list_table_retention))
+        .with_state(context)
+}
+
+async fn search_nodes(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceSearchNodesRequest>,
+) -> Result<Json<api::InternalAdminServiceSearchNodesResponse>, Error> {
+    ctx.read(|read| grpc::internal_admin::search_nodes(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn search_hosts(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceSearchHostsRequest>,
+) -> Result<Json<api::InternalAdminServiceSearchHostsResponse>, Error> {
+    ctx.read(|read| grpc::internal_admin::search_hosts(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn override_node_state(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceOverrideNodeStateRequest>,
+) -> Result<Json<api::InternalAdminServiceOverrideNodeStateResponse>, Error> {
+    ctx.write(|write| {
+        grpc::internal_admin::override_node_state(req, headers.into(), write).scope_boxed()
+    })
+    .await
+}
+
+async fn cleanup_dns(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceCleanupDnsRequest>,
+) -> Result<Json<api::InternalAdminServiceCleanupDnsResponse>, Error> {
+    ctx.read(|read| grpc::internal_admin::cleanup_dns(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn cleanup_stripe(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceCleanupStripeRequest>,
+) -> Result<Json<api::InternalAdminServiceCleanupStripeResponse>, Error> {
+    ctx.read(|read| grpc::internal_admin::cleanup_stripe(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list_feature_flags(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceListFeatureFlagsRequest>,
+) -> Result<Json<api::InternalAdminServiceListFeatureFlagsResponse>, Error> {
+    ctx.read(|read| {
+        grpc::internal_admin::list_feature_flags(req, headers.into(), read).scope_boxed()
+    })
+    .await
+}
+
+async fn set_feature_flag(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceSetFeatureFlagRequest>,
+) -> Result<Json<api::InternalAdminServiceSetFeatureFlagResponse>, Error> {
+    let resp = ctx
+        .write(|write| {
+            grpc::internal_admin::set_feature_flag(req, headers.into(), write).scope_boxed()
+        })
+        .await?;
+
+    // the flags are only reloaded once the change has committed
+    ctx.flags.invalidate().await;
+
+    Ok(resp)
+}
+
+async fn get_host_weights(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceGetHostWeightsRequest>,
+) -> Result<Json<api::InternalAdminServiceGetHostWeightsResponse>, Error> {
+    ctx.read(|read| grpc::internal_admin::get_host_weights(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn set_host_weights(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceSetHostWeightsRequest>,
+) -> Result<Json<api::InternalAdminServiceSetHostWeightsResponse>, Error> {
+    ctx.write(|write| {
+        grpc::internal_admin::set_host_weights(req, headers.into(), write).scope_boxed()
+    })
+    .await
+}
+
+async fn list_backfills(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceListBackfillsRequest>,
+) -> Result<Json<api::InternalAdminServiceListBackfillsResponse>, Error> {
+    ctx.read(|read| grpc::internal_admin::list_backfills(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn run_backfill(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceRunBackfillRequest>,
+) -> Result<Json<api::InternalAdminServiceRunBackfillResponse>, Error> {
+    let task = Task::from_name(&req.name).ok();
+    let batch_size = grpc::internal_admin::batch_size(&req);
+    let resp = ctx
+        .write(|write| grpc::internal_admin::run_backfill(req, headers.into(), write).scope_boxed())
+        .await?;
+
+    // the backfill row is only visible to the runner once committed
+    if let Some(task) = task {
+        job::spawn_backfill(&ctx, task, batch_size);
+    }
+
+    Ok(resp)
+}
+
+async fn list_network_heads(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceListNetworkHeadsRequest>,
+) -> Result<Json<api::InternalAdminServiceListNetworkHeadsResponse>, Error> {
+    ctx.read(|read| {
+        grpc::internal_admin::list_network_heads(req, headers.into(), read).scope_boxed()
+    })
+    .await
+}
+
+async fn set_network_head(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::InternalAdminServiceSetNetworkHeadRequest>,
+) -> Result<Json<api::InternalAdminServiceSetNetworkHeadResponse>, Error> {
+    ctx.write(|write| {
+        grpc::internal_admin::set_network_head(req, headers.into(), write).scope_boxed()
+    })
+    .await
+}
+
+async fn list_table_retention(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::InternalAdminServiceListTableRetentionRequest>,
+) -> Result<Json<api::InternalAdminServiceListTableRetentionResponse>, Error> {
+    ctx.read(|read| {
+        grpc::internal_admin::list_table_retention(req, headers.into(), read).scope_boxed()
+    })
+    .await
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/credentials", routing::put(set_credentials))
+        .route("/", routing::post(provision))
+        .route("/", routing::get(list))
+        .route("/:id", routing::get(get))
+        .route("/:id", routing::delete(deprovision))
+        .with_state(context)
+}
+
+async fn set_credentials(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::ManagedHostServiceSetCredentialsRequest>,
+) -> Result<Json<api::ManagedHostServiceSetCredentialsResponse>, Error> {
+    ctx.write(|write| grpc::managed_host::set_credentials(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn provision(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::ManagedHostServiceProvisionRequest>,
+) -> Result<Json<api::ManagedHostServiceProvisionResponse>, Error> {
+    ctx.write(|write| grpc::managed_host::provision(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((managed_host_id,)): Path<(String,)>,
+) -> Result<Json<api::ManagedHostServiceGetResponse>, Error> {
+    let req = api::ManagedHostServiceGetRequest { managed_host_id };
+    ctx.read(|read| grpc::managed_host::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::ManagedHostServiceListRequest>,
+) -> Result<Json<api::ManagedHostServiceListResponse>, Error> {
+    ctx.read(|read| grpc::managed_host::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn deprovision(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((managed_host_id,)): Path<(String,)>,
+) -> Result<Json<api::ManagedHostServiceDeprovisionResponse>, Error> {
+    let req = api::ManagedHostServiceDeprovisionRequest { managed_host_id };
+    ctx.write(|write| grpc::managed_host::deprovision(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(join))
+        .route("/", routing::get(list_peers))
+        .route("/:id", routing::delete(leave))
+        .route("/host/:id/config", routing::get(get_config))
+        .with_state(context)
+}
+
+async fn join(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::MeshServiceJoinRequest>,
+) -> Result<Json<api::MeshServiceJoinResponse>, Error> {
+    ctx.write(|write| grpc::mesh::join(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list_peers(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::MeshServiceListPeersRequest>,
+) -> Result<Json<api::MeshServiceListPeersResponse>, Error> {
+    ctx.read(|read| grpc::mesh::list_peers(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn leave(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((peer_id,)): Path<(String,)>,
+) -> Result<Json<api::MeshServiceLeaveResponse>, Error> {
+    let req = api::MeshServiceLeaveRequest { peer_id };
+    ctx.write(|write| grpc::mesh::leave(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_config(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((host_id,)): Path<(String,)>,
+) -> Result<Json<api::MeshServiceGetConfigResponse>, Error> {
+    let req = api::MeshServiceGetConfigRequest { host_id };
+    ctx.read(|read| grpc::mesh::get_config(req, headers.into(), read).scope_boxed())
+        .await
+}
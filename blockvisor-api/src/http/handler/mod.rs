@@ -5,20 +5,40 @@ use serde_json::Value;
 use crate::database;
 use crate::grpc::Status;
 
+pub mod admin;
+pub mod alert;
 pub mod api_key;
+pub mod approval;
 pub mod archive;
 pub mod auth;
+pub mod billing;
 pub mod bundle;
+#[cfg(any(test, feature = "integration-test"))]
+pub mod chaos;
 pub mod discovery;
+pub mod endpoint;
 pub mod health;
 pub mod host;
+pub mod incident;
+pub mod internal_admin;
 pub mod invitation;
+pub mod managed_host;
+pub mod mesh;
 pub mod metrics;
 pub mod mqtt;
 pub mod node;
+pub mod node_share;
 pub mod org;
+pub mod org_branding;
+pub mod org_key;
+pub mod org_log_sink;
+pub mod pool;
 pub mod protocol;
+pub mod report;
+pub mod status_page;
 pub mod stripe;
+pub mod team;
+pub mod ticket;
 pub mod user;
 
 pub(crate) struct Error {
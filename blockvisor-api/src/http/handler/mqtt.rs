@@ -11,12 +11,13 @@ use thiserror::Error;
 use tracing::{debug, error};
 
 use crate::auth::rbac::{MqttAdminPerm, MqttPerm};
-use crate::auth::resource::{Resource, Resources};
+use crate::auth::resource::{OrgId, Resource, Resources};
 use crate::config::Context;
 use crate::database::Database;
 use crate::grpc::Status;
 use crate::http::response;
-use crate::mqtt::handler::{self, AclRequest, Topic};
+use crate::model::project::{Project, ProjectId};
+use crate::mqtt::handler::{self, AclRequest, OrgTopic, Topic};
 
 use super::ErrorWrapper;
 
@@ -32,6 +33,10 @@ pub enum Error {
     ParseJson(#[from] JsonRejection),
     /// Failed to parse RequestToken: {0}
     ParseRequestToken(crate::auth::token::Error),
+    /// MQTT project error: {0}
+    Project(#[from] crate::model::project::Error),
+    /// Project `{0}` is not in org `{1}`.
+    ProjectOrg(ProjectId, OrgId),
     /// Wildcard topic subscribe without `mqtt-admin-acl`: {0}
     WildcardTopic(String),
 }
@@ -47,7 +52,13 @@ impl From<Error> for Status {
             Auth(_)
             | Handler(handler::Error::Claims(_))
             | ParseRequestToken(_)
+            | Project(crate::model::project::Error::NotMember(_))
+            | ProjectOrg(..)
             | WildcardTopic(_) => Status::unauthorized("Unauthorized"),
+            Project(crate::model::project::Error::ById(_, diesel::result::Error::NotFound)) => {
+                Status::unauthorized("Unauthorized")
+            }
+            Project(_) => Status::internal("Database error"),
             Database(_) => Status::internal("Database error"),
             Handler(_) => Status::invalid_argument("Invalid arguments"),
             ParseJson(rejection) => Status::unparseable_request(rejection.body_text()),
@@ -99,16 +110,32 @@ async fn acl(
     }
 
     let resources: Resources = match req.topic {
-        Topic::Orgs(org_id) => Resource::from(org_id).into(),
+        Topic::Orgs(org_id, _) => Resource::from(org_id).into(),
         Topic::Hosts(host_id) => Resource::from(host_id).into(),
         Topic::Nodes(node_id) => Resource::from(node_id).into(),
         Topic::BvHostsStatus(host_id) => Resource::from(host_id).into(),
         Topic::Wildcard(topic) => return Err(Status::from(Error::WildcardTopic(topic)).into()),
     };
 
-    ctx.auth
+    let authz = ctx
+        .auth
         .authorize_token(&token, MqttPerm::Acl.into(), resources, &mut conn)
         .await
-        .map(|_authz| response::ok())
-        .map_err(|err| Status::from(err).into())
+        .map_err(Status::from)?;
+
+    // the nodes of a project with members are only visible to them
+    if let Topic::Orgs(org_id, OrgTopic::ProjectNodes(project_id)) = req.topic {
+        let project = Project::by_id(project_id, &mut conn)
+            .await
+            .map_err(|err| Status::from(Error::from(err)))?;
+        if project.org_id != org_id {
+            return Err(Status::from(Error::ProjectOrg(project_id, org_id)).into());
+        }
+        project
+            .authorize(&authz, &mut conn)
+            .await
+            .map_err(|err| Status::from(Error::from(err)))?;
+    }
+
+    Ok(response::ok())
 }
@@ -23,11 +23,21 @@ where
         .route("/:id/report", routing::post(report_error))
         .route("/status", routing::post(report_status))
         .route("/config", routing::put(update_config))
+        .route("/bulk", routing::put(bulk_update))
         .route("/image", routing::put(upgrade_image))
         .route("/:id/start", routing::put(start))
         .route("/:id/stop", routing::put(stop))
         .route("/:id/restart", routing::put(restart))
         .route("/:id", routing::delete(delete))
+        .route("/resize", routing::put(resize))
+        .route("/disk-resize", routing::post(resize_disk))
+        .route("/disk-resize", routing::get(list_disk_resizes))
+        .route("/disk-resize/:id", routing::put(review_disk_resize))
+        .route("/dns-alias", routing::post(add_dns_alias))
+        .route("/dns-alias", routing::delete(remove_dns_alias))
+        .route("/rename", routing::put(rename))
+        .route("/:id/certificate", routing::post(request_certificate))
+        .route("/:id/certificate", routing::get(get_certificate))
         .with_state(context)
 }
 
@@ -98,6 +108,15 @@ async fn update_config(
         .await
 }
 
+async fn bulk_update(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeServiceBulkUpdateRequest>,
+) -> Result<Json<api::NodeServiceBulkUpdateResponse>, Error> {
+    ctx.write(|write| grpc::node::bulk_update(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 async fn upgrade_image(
     State(ctx): State<Arc<Context>>,
     headers: HeaderMap,
@@ -142,3 +161,97 @@ async fn delete(
     ctx.write(|write| grpc::node::delete(req, headers.into(), write).scope_boxed())
         .await
 }
+
+async fn resize(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeServiceResizeRequest>,
+) -> Result<Json<api::NodeServiceResizeResponse>, Error> {
+    ctx.write(|write| grpc::node::resize(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn resize_disk(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeServiceResizeDiskRequest>,
+) -> Result<Json<api::NodeServiceResizeDiskResponse>, Error> {
+    ctx.write(|write| grpc::node::resize_disk(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list_disk_resizes(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::NodeServiceListDiskResizesRequest>,
+) -> Result<Json<api::NodeServiceListDiskResizesResponse>, Error> {
+    ctx.read(|read| grpc::node::list_disk_resizes(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeServiceReviewDiskResizeRequest {
+    approve: bool,
+}
+
+async fn review_disk_resize(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((resize_id,)): Path<(String,)>,
+    Json(req): Json<NodeServiceReviewDiskResizeRequest>,
+) -> Result<Json<api::NodeServiceReviewDiskResizeResponse>, Error> {
+    let req = api::NodeServiceReviewDiskResizeRequest {
+        resize_id,
+        approve: req.approve,
+    };
+    ctx.write(|write| grpc::node::review_disk_resize(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn add_dns_alias(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeServiceAddDnsAliasRequest>,
+) -> Result<Json<api::NodeServiceAddDnsAliasResponse>, Error> {
+    ctx.write(|write| grpc::node::add_dns_alias(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn remove_dns_alias(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeServiceRemoveDnsAliasRequest>,
+) -> Result<Json<api::NodeServiceRemoveDnsAliasResponse>, Error> {
+    ctx.write(|write| grpc::node::remove_dns_alias(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn rename(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeServiceRenameRequest>,
+) -> Result<Json<api::NodeServiceRenameResponse>, Error> {
+    ctx.write(|write| grpc::node::rename(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn request_certificate(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((node_id,)): Path<(String,)>,
+) -> Result<Json<api::NodeServiceRequestCertificateResponse>, Error> {
+    let req = api::NodeServiceRequestCertificateRequest { node_id };
+    ctx.write(|write| grpc::node::request_certificate(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_certificate(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((node_id,)): Path<(String,)>,
+) -> Result<Json<api::NodeServiceGetCertificateResponse>, Error> {
+    let req = api::NodeServiceGetCertificateRequest { node_id };
+    ctx.read(|read| grpc::node::get_certificate(req, headers.into(), read).scope_boxed())
+        .await
+}
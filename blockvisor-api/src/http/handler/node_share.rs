@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use serde::Deserialize;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/:id", routing::delete(revoke))
+        .route("/:id/accesses", routing::get(accesses))
+        .with_state(context)
+}
+
+/// Shared nodes, served without auth to holders of the share token.
+pub fn public_router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/:id", routing::get(view))
+        .with_state(context)
+}
+
+#[derive(Deserialize)]
+struct ViewQuery {
+    token: String,
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::NodeShareServiceCreateRequest>,
+) -> Result<Json<api::NodeShareServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::node_share::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::NodeShareServiceListRequest>,
+) -> Result<Json<api::NodeShareServiceListResponse>, Error> {
+    ctx.read(|read| grpc::node_share::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn revoke(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((node_share_id,)): Path<(String,)>,
+) -> Result<Json<api::NodeShareServiceRevokeResponse>, Error> {
+    let req = api::NodeShareServiceRevokeRequest { node_share_id };
+    ctx.write(|write| grpc::node_share::revoke(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn accesses(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((node_share_id,)): Path<(String,)>,
+) -> Result<Json<api::NodeShareServiceAccessesResponse>, Error> {
+    let req = api::NodeShareServiceAccessesRequest { node_share_id };
+    ctx.read(|read| grpc::node_share::accesses(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn view(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((node_share_id,)): Path<(String,)>,
+    Query(query): Query<ViewQuery>,
+) -> Result<Json<api::NodeShareView>, Error> {
+    ctx.write(|write| {
+        grpc::node_share::view(&node_share_id, &query.token, headers.into(), write).scope_boxed()
+    })
+    .await
+}
@@ -22,7 +22,10 @@ where
         .route("/", routing::get(list))
         .route("/:id", routing::put(update))
         .route("/:id", routing::delete(delete))
+        .route("/:id/restore", routing::post(restore_deleted))
         .route("/:id/member", routing::delete(remove_member))
+        .route("/:id/transfer", routing::post(transfer_ownership))
+        .route("/transfer/:id/confirm", routing::post(confirm_transfer))
         .route("/:id/provision-token", routing::get(get_provision_token))
         .route("/:id/provision-token", routing::post(reset_provision_token))
         .route("/:id/init-card", routing::post(init_card))
@@ -32,6 +35,7 @@ where
         .route("/:id/address", routing::post(set_address))
         .route("/:id/address", routing::delete(delete_address))
         .route("/:id/invoices", routing::get(get_invoices))
+        .route("/:id/summary", routing::get(summary))
         .with_state(context)
 }
 
@@ -67,6 +71,16 @@ async fn list(
 #[serde(deny_unknown_fields)]
 struct OrgServiceUpdateRequest {
     name: Option<String>,
+    agent_upgrade_policy: Option<i32>,
+    disk_quota_bytes: Option<u64>,
+    disk_auto_approve_bytes: Option<u64>,
+    node_display_name_max_len: Option<u32>,
+    node_note_max_len: Option<u32>,
+    node_display_name_unique: Option<bool>,
+    node_display_name_charset: Option<i32>,
+    require_approval_node_delete: Option<bool>,
+    require_approval_node_upgrade: Option<bool>,
+    require_approval_org_transfer: Option<bool>,
 }
 
 async fn update(
@@ -78,21 +92,52 @@ async fn update(
     let req = api::OrgServiceUpdateRequest {
         org_id,
         name: req.name,
+        agent_upgrade_policy: req.agent_upgrade_policy,
+        disk_quota_bytes: req.disk_quota_bytes,
+        disk_auto_approve_bytes: req.disk_auto_approve_bytes,
+        node_display_name_max_len: req.node_display_name_max_len,
+        node_note_max_len: req.node_note_max_len,
+        node_display_name_unique: req.node_display_name_unique,
+        node_display_name_charset: req.node_display_name_charset,
+        require_approval_node_delete: req.require_approval_node_delete,
+        require_approval_node_upgrade: req.require_approval_node_upgrade,
+        require_approval_org_transfer: req.require_approval_org_transfer,
     };
     ctx.write(|write| grpc::org::update(req, headers.into(), write).scope_boxed())
         .await
 }
 
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrgServiceDeleteQuery {
+    #[serde(default)]
+    force: bool,
+}
+
 async fn delete(
     State(ctx): State<Arc<Context>>,
     headers: HeaderMap,
     Path((org_id,)): Path<(String,)>,
+    Query(query): Query<OrgServiceDeleteQuery>,
 ) -> Result<Json<api::OrgServiceDeleteResponse>, Error> {
-    let req = api::OrgServiceDeleteRequest { org_id };
+    let req = api::OrgServiceDeleteRequest {
+        org_id,
+        force: query.force,
+    };
     ctx.write(|write| grpc::org::delete(req, headers.into(), write).scope_boxed())
         .await
 }
 
+async fn restore_deleted(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgServiceRestoreDeletedResponse>, Error> {
+    let req = api::OrgServiceRestoreDeletedRequest { org_id };
+    ctx.write(|write| grpc::org::restore_deleted(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 #[derive(serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 struct OrgServiceRemoveMemberRequest {
@@ -113,6 +158,36 @@ async fn remove_member(
         .await
 }
 
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrgServiceTransferOwnershipRequest {
+    new_owner_id: String,
+}
+
+async fn transfer_ownership(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+    Json(req): Json<OrgServiceTransferOwnershipRequest>,
+) -> Result<Json<api::OrgServiceTransferOwnershipResponse>, Error> {
+    let req = api::OrgServiceTransferOwnershipRequest {
+        org_id,
+        new_owner_id: req.new_owner_id,
+    };
+    ctx.write(|write| grpc::org::transfer_ownership(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn confirm_transfer(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((transfer_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgServiceConfirmTransferResponse>, Error> {
+    let req = api::OrgServiceConfirmTransferRequest { transfer_id };
+    ctx.write(|write| grpc::org::confirm_transfer(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 #[derive(serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 struct OrgServiceGetProvisionTokenRequest {
@@ -242,3 +317,13 @@ async fn get_invoices(
     ctx.read(|read| grpc::org::get_invoices(req, headers.into(), read).scope_boxed())
         .await
 }
+
+async fn summary(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgServiceSummaryResponse>, Error> {
+    let req = api::OrgServiceSummaryRequest { org_id };
+    ctx.read(|read| grpc::org::summary(req, headers.into(), read).scope_boxed())
+        .await
+}
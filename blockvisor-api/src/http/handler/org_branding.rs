@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::header::HeaderMap;
+use axum::response::Redirect;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use url::Url;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/:org_id", routing::get(get))
+        .route("/:org_id", routing::put(update))
+        .with_state(context)
+}
+
+/// Org logos, served without auth for emails and public status pages.
+pub fn public_router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/:org_id/logo", routing::get(logo))
+        .with_state(context)
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgBrandingServiceGetResponse>, Error> {
+    let req = api::OrgBrandingServiceGetRequest { org_id };
+    ctx.read(|read| grpc::org_branding::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrgBrandingServiceUpdateRequest {
+    sender_name: Option<String>,
+    primary_color: Option<String>,
+    accent_color: Option<String>,
+    logo: Option<Vec<u8>>,
+    #[serde(default)]
+    remove_logo: bool,
+}
+
+async fn update(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+    Json(req): Json<OrgBrandingServiceUpdateRequest>,
+) -> Result<Json<api::OrgBrandingServiceUpdateResponse>, Error> {
+    let req = api::OrgBrandingServiceUpdateRequest {
+        org_id,
+        sender_name: req.sender_name,
+        primary_color: req.primary_color,
+        accent_color: req.accent_color,
+        logo: req.logo,
+        remove_logo: req.remove_logo,
+    };
+    ctx.write(|write| grpc::org_branding::update(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn logo(
+    State(ctx): State<Arc<Context>>,
+    Path((org_id,)): Path<(String,)>,
+) -> Result<Redirect, Error> {
+    let Json(url): Json<Url> = ctx
+        .read(|read| grpc::org_branding::logo_url(&org_id, read).scope_boxed())
+        .await?;
+    Ok(Redirect::temporary(url.as_str()))
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/:id/rotate", routing::post(rotate))
+        .route("/:id", routing::delete(revoke))
+        .with_state(context)
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::OrgKeyServiceCreateRequest>,
+) -> Result<Json<api::OrgKeyServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::org_key::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::OrgKeyServiceListRequest>,
+) -> Result<Json<api::OrgKeyServiceListResponse>, Error> {
+    ctx.read(|read| grpc::org_key::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrgKeyServiceRotateRequest {
+    name: Option<String>,
+    public_key: String,
+}
+
+async fn rotate(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((key_id,)): Path<(String,)>,
+    Json(req): Json<OrgKeyServiceRotateRequest>,
+) -> Result<Json<api::OrgKeyServiceRotateResponse>, Error> {
+    let req = api::OrgKeyServiceRotateRequest {
+        key_id,
+        name: req.name,
+        public_key: req.public_key,
+    };
+    ctx.write(|write| grpc::org_key::rotate(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn revoke(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((key_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgKeyServiceRevokeResponse>, Error> {
+    let req = api::OrgKeyServiceRevokeRequest { key_id };
+    ctx.write(|write| grpc::org_key::revoke(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/:org_id", routing::get(get))
+        .route("/:org_id", routing::put(set))
+        .route("/:org_id", routing::delete(delete))
+        .with_state(context)
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgLogSinkServiceGetResponse>, Error> {
+    let req = api::OrgLogSinkServiceGetRequest { org_id };
+    ctx.read(|read| grpc::org_log_sink::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OrgLogSinkServiceSetRequest {
+    kind: i32,
+    endpoint: String,
+    credentials: Option<api::LogSinkCredentials>,
+}
+
+async fn set(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+    Json(req): Json<OrgLogSinkServiceSetRequest>,
+) -> Result<Json<api::OrgLogSinkServiceSetResponse>, Error> {
+    let req = api::OrgLogSinkServiceSetRequest {
+        org_id,
+        kind: req.kind,
+        endpoint: req.endpoint,
+        credentials: req.credentials,
+    };
+    ctx.write(|write| grpc::org_log_sink::set(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn delete(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((org_id,)): Path<(String,)>,
+) -> Result<Json<api::OrgLogSinkServiceDeleteResponse>, Error> {
+    let req = api::OrgLogSinkServiceDeleteRequest { org_id };
+    ctx.write(|write| grpc::org_log_sink::delete(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/:id", routing::get(get))
+        .route("/:id", routing::put(update))
+        .route("/:id", routing::delete(delete))
+        .route("/:id/autoscaler", routing::get(get_autoscaler))
+        .route("/:id/autoscaler", routing::put(set_autoscaler))
+        .route("/:id/autoscaler", routing::delete(remove_autoscaler))
+        .with_state(context)
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::PoolServiceCreateRequest>,
+) -> Result<Json<api::PoolServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::pool::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::PoolServiceListRequest>,
+) -> Result<Json<api::PoolServiceListResponse>, Error> {
+    ctx.read(|read| grpc::pool::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+) -> Result<Json<api::PoolServiceGetResponse>, Error> {
+    let req = api::PoolServiceGetRequest { pool_id };
+    ctx.read(|read| grpc::pool::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoolServiceUpdateRequest {
+    #[serde(default)]
+    add_node_ids: Vec<String>,
+    #[serde(default)]
+    remove_node_ids: Vec<String>,
+}
+
+async fn update(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+    Json(req): Json<PoolServiceUpdateRequest>,
+) -> Result<Json<api::PoolServiceUpdateResponse>, Error> {
+    let req = api::PoolServiceUpdateRequest {
+        pool_id,
+        add_node_ids: req.add_node_ids,
+        remove_node_ids: req.remove_node_ids,
+    };
+    ctx.write(|write| grpc::pool::update(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn delete(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+) -> Result<Json<api::PoolServiceDeleteResponse>, Error> {
+    let req = api::PoolServiceDeleteRequest { pool_id };
+    ctx.write(|write| grpc::pool::delete(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoolServiceSetAutoscalerRequest {
+    image_id: String,
+    region_id: String,
+    min_nodes: u32,
+    max_nodes: u32,
+    metric: i32,
+    scale_up_above: f64,
+    scale_down_below: f64,
+    cooldown_seconds: u32,
+}
+
+async fn set_autoscaler(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+    Json(req): Json<PoolServiceSetAutoscalerRequest>,
+) -> Result<Json<api::PoolServiceSetAutoscalerResponse>, Error> {
+    let req = api::PoolServiceSetAutoscalerRequest {
+        pool_id,
+        image_id: req.image_id,
+        region_id: req.region_id,
+        min_nodes: req.min_nodes,
+        max_nodes: req.max_nodes,
+        metric: req.metric,
+        scale_up_above: req.scale_up_above,
+        scale_down_below: req.scale_down_below,
+        cooldown_seconds: req.cooldown_seconds,
+    };
+    ctx.write(|write| grpc::pool::set_autoscaler(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_autoscaler(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+) -> Result<Json<api::PoolServiceGetAutoscalerResponse>, Error> {
+    let req = api::PoolServiceGetAutoscalerRequest { pool_id };
+    ctx.read(|read| grpc::pool::get_autoscaler(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn remove_autoscaler(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((pool_id,)): Path<(String,)>,
+) -> Result<Json<api::PoolServiceRemoveAutoscalerResponse>, Error> {
+    let req = api::PoolServiceRemoveAutoscalerRequest { pool_id };
+    ctx.write(|write| grpc::pool::remove_autoscaler(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -23,15 +23,31 @@ where
         .route("/:id", routing::get(get_protocol))
         .route("/key/:key", routing::get(get_protocol_by_key))
         .route("/:id", routing::put(update_protocol))
+        .route("/:id", routing::delete(delete_protocol))
+        .route("/catalog", routing::get(list_catalog))
         .route("/version", routing::post(add_version))
         .route("/version", routing::get(list_versions))
         .route("/version/:id", routing::put(update_version))
+        .route("/grant", routing::post(grant_access))
+        .route("/grant", routing::get(list_grants))
+        .route("/grant/:id", routing::delete(revoke_access))
+        .route("/network", routing::post(add_network))
+        .route("/network", routing::get(list_networks))
         .route("/latest", routing::get(get_latest))
         .route("/pricing", routing::get(get_pricing))
         .route("/stats", routing::get(get_stats))
         .with_state(context)
 }
 
+async fn add_network(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::ProtocolServiceAddNetworkRequest>,
+) -> Result<Json<api::ProtocolServiceAddNetworkResponse>, Error> {
+    ctx.write(|write| grpc::protocol::add_network(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 async fn add_protocol(
     State(ctx): State<Arc<Context>>,
     headers: HeaderMap,
@@ -50,6 +66,16 @@ async fn add_version(
         .await
 }
 
+async fn delete_protocol(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((protocol_id,)): Path<(String,)>,
+) -> Result<Json<api::ProtocolServiceDeleteProtocolResponse>, Error> {
+    let req = api::ProtocolServiceDeleteProtocolRequest { protocol_id };
+    ctx.write(|write| grpc::protocol::delete_protocol(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 async fn get_latest(
     State(ctx): State<Arc<Context>>,
     headers: HeaderMap,
@@ -113,6 +139,42 @@ async fn get_stats(
         .await
 }
 
+async fn grant_access(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::ProtocolServiceGrantAccessRequest>,
+) -> Result<Json<api::ProtocolServiceGrantAccessResponse>, Error> {
+    ctx.write(|write| grpc::protocol::grant_access(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list_catalog(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+) -> Result<Json<api::ProtocolServiceListCatalogResponse>, Error> {
+    let req = api::ProtocolServiceListCatalogRequest {};
+    ctx.read(|read| grpc::protocol::list_catalog(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list_grants(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::ProtocolServiceListGrantsRequest>,
+) -> Result<Json<api::ProtocolServiceListGrantsResponse>, Error> {
+    ctx.read(|read| grpc::protocol::list_grants(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list_networks(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::ProtocolServiceListNetworksRequest>,
+) -> Result<Json<api::ProtocolServiceListNetworksResponse>, Error> {
+    ctx.read(|read| grpc::protocol::list_networks(req, headers.into(), read).scope_boxed())
+        .await
+}
+
 async fn list_protocols(
     State(ctx): State<Arc<Context>>,
     headers: HeaderMap,
@@ -131,12 +193,27 @@ async fn list_versions(
         .await
 }
 
+async fn revoke_access(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((grant_id,)): Path<(String,)>,
+) -> Result<Json<api::ProtocolServiceRevokeAccessResponse>, Error> {
+    let req = api::ProtocolServiceRevokeAccessRequest { grant_id };
+    ctx.write(|write| grpc::protocol::revoke_access(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 #[derive(serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 struct ProtocolServiceUpdateProtocolRequest {
     name: Option<String>,
     description: Option<String>,
     visibility: Option<i32>,
+    docs_url: Option<String>,
+    logo_url: Option<String>,
+    /// A comma-separated list of network names.
+    networks: Option<String>,
+    lifecycle: Option<i32>,
 }
 
 async fn update_protocol(
@@ -150,6 +227,17 @@ async fn update_protocol(
         name: req.name,
         description: req.description,
         visibility: req.visibility,
+        docs_url: req.docs_url,
+        logo_url: req.logo_url,
+        networks: req.networks.map(|networks| api::ProtocolNetworks {
+            networks: networks
+                .split(',')
+                .map(str::trim)
+                .filter(|network| !network.is_empty())
+                .map(String::from)
+                .collect(),
+        }),
+        lifecycle: req.lifecycle,
     };
     ctx.write(|write| grpc::protocol::update_protocol(req, headers.into(), write).scope_boxed())
         .await
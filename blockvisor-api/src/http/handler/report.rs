@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/uptime", routing::get(uptime))
+        .with_state(context)
+}
+
+async fn uptime(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::ReportServiceUptimeRequest>,
+) -> Result<Json<api::ReportServiceUptimeResponse>, Error> {
+    ctx.write(|write| grpc::report::uptime(req, headers.into(), write).scope_boxed())
+        .await
+}
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::{self, HeaderMap};
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, Status, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/:id", routing::get(get))
+        .route("/:id", routing::delete(delete))
+        .route("/:id/verify-domain", routing::post(verify_domain))
+        .with_state(context)
+}
+
+/// Public status pages, served without auth at their slug or custom domain.
+pub fn public_router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::get(public_by_domain))
+        .route("/:slug", routing::get(public_by_slug))
+        .with_state(context)
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::StatusPageServiceCreateRequest>,
+) -> Result<Json<api::StatusPageServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::status_page::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((status_page_id,)): Path<(String,)>,
+) -> Result<Json<api::StatusPageServiceGetResponse>, Error> {
+    let req = api::StatusPageServiceGetRequest { status_page_id };
+    ctx.read(|read| grpc::status_page::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::StatusPageServiceListRequest>,
+) -> Result<Json<api::StatusPageServiceListResponse>, Error> {
+    ctx.read(|read| grpc::status_page::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn delete(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((status_page_id,)): Path<(String,)>,
+) -> Result<Json<api::StatusPageServiceDeleteResponse>, Error> {
+    let req = api::StatusPageServiceDeleteRequest { status_page_id };
+    ctx.write(|write| grpc::status_page::delete(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn verify_domain(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((status_page_id,)): Path<(String,)>,
+) -> Result<Json<api::StatusPageServiceVerifyDomainResponse>, Error> {
+    let req = api::StatusPageServiceVerifyDomainRequest { status_page_id };
+    ctx.write(|write| grpc::status_page::verify_domain(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn public_by_slug(
+    State(ctx): State<Arc<Context>>,
+    Path((slug,)): Path<(String,)>,
+) -> Result<Json<api::StatusPageView>, Error> {
+    ctx.read(|read| grpc::status_page::public_by_slug(&slug, read).scope_boxed())
+        .await
+}
+
+async fn public_by_domain(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+) -> Result<Json<api::StatusPageView>, Error> {
+    let Some(domain) = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(|host| host.split(':').next().unwrap_or(host).to_string())
+    else {
+        return Err(Status::invalid_argument("host").into());
+    };
+
+    ctx.read(|read| grpc::status_page::public_by_domain(&domain, read).scope_boxed())
+        .await
+}
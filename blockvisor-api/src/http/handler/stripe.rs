@@ -1,12 +1,16 @@
 //! Handlers for incoming stripe webhook events.
 //!
-//! These are currently only used for follow-up actions after the cancellation
-//! of a subscription.
+//! These are used for follow-up actions after the cancellation of a
+//! subscription, and to notify org owners of failed payments. Each event must
+//! be signed with the configured `stripe.webhook_secret`.
 
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use axum::extract::State;
+use axum::http::header::HeaderMap;
 use axum::routing::{Router, post};
+use chrono::Utc;
 use diesel_async::scoped_futures::ScopedFutureExt;
 use displaydoc::Display;
 use thiserror::Error;
@@ -16,13 +20,20 @@ use crate::auth::resource::OrgId;
 use crate::config::Context;
 use crate::database::{Transaction, WriteConn};
 use crate::grpc::Status;
+use crate::model::user::notification::NotificationKind;
 use crate::model::{Org, User};
+use crate::push::Message;
+use crate::stripe::api::IdOrObject;
 use crate::stripe::api::event::{Event, EventObject, SetupIntent};
+use crate::stripe::api::invoice::Invoice;
+use crate::stripe::webhook;
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
     /// Stripe database error: {0}
     Database(#[from] diesel::result::Error),
+    /// Stripe invoice is missing a customer.
+    MissingCustomer,
     /// Stripe event is missing the metadata field.
     MissingMetadata,
     /// Stripe event is missing a org_id in its metadata.
@@ -31,10 +42,16 @@ pub enum Error {
     NoOwner(OrgId),
     /// Stripe is not configured.
     NoStripe,
+    /// Stripe webhook secret is not configured.
+    NoWebhookSecret,
     /// Stripe org: {0}
     Org(#[from] crate::model::org::Error),
     /// Stripe event has an unparsable org_id in its metadata.
     ParseOrgId(uuid::Error),
+    /// Stripe push: {0}
+    Push(#[from] crate::push::Error),
+    /// Stripe webhook signature: {0}
+    Signature(webhook::Error),
     /// Stripe handler: {0}
     Stripe(#[from] crate::stripe::Error),
     /// Could not parse stripe body: {0}
@@ -48,12 +65,17 @@ impl From<Error> for Status {
         use Error::*;
         error!("Stripe webhook: {err:?}");
         match err {
-            Database(_) | Org(_) | Stripe(_) | User(_) => Status::internal("Internal error."),
+            Database(_) | Org(_) | Push(_) | Stripe(_) | User(_) => {
+                Status::internal("Internal error.")
+            }
+            MissingCustomer => Status::invalid_argument("Customer missing from invoice"),
             MissingMetadata => Status::invalid_argument("Metadata field not set"),
             MissingOrgId => Status::invalid_argument("Org id missing from metadata"),
             NoOwner(_) => Status::failed_precondition("Org has no owner"),
             NoStripe => Status::failed_precondition("Stripe is not configured."),
+            NoWebhookSecret => Status::failed_precondition("Stripe webhooks are not configured."),
             ParseOrgId(_) => Status::invalid_argument("Could not parse org id"),
+            Signature(_) => Status::unauthorized("Invalid stripe signature."),
             UnparseableStripeBody(_) => Status::invalid_argument("Unparseable request"),
         }
     }
@@ -65,14 +87,16 @@ where
 {
     Router::new()
         .route("/setup_intent_succeeded", post(setup_intent_succeeded))
+        .route("/invoice_payment_failed", post(invoice_payment_failed))
         .with_state(context)
 }
 
 async fn setup_intent_succeeded(
     State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
     body: String,
 ) -> Result<axum::Json<serde_json::Value>, super::Error> {
-    // FIXME: this bastard needs auth.
+    verify(&ctx, &headers, &body).map_err(Status::from)?;
 
     let event: Event = match serde_json::from_str(&body) {
         Ok(body) => body,
@@ -86,13 +110,28 @@ async fn setup_intent_succeeded(
             ctx.write(|c| setup_intent_succeeded_handler(data, c).scope_boxed())
                 .await
         }
-        EventObject::Other => {
+        EventObject::Invoice(_) | EventObject::Other => {
             debug!("Skipping chargebee callback event: {body}");
             Ok(axum::Json(serde_json::json!({"message": "event ignored"})))
         }
     }
 }
 
+/// Check that `body` was sent by stripe before acting on it.
+fn verify(ctx: &Context, headers: &HeaderMap, body: &str) -> Result<(), Error> {
+    let secret = ctx
+        .config
+        .stripe
+        .webhook_secret
+        .as_ref()
+        .ok_or(Error::NoWebhookSecret)?;
+    let header = headers
+        .get(webhook::SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    webhook::verify(secret, header, body, Utc::now()).map_err(Error::Signature)
+}
+
 async fn setup_intent_succeeded_handler(
     setup_intent: SetupIntent,
     mut write: WriteConn<'_, '_>,
@@ -105,13 +144,14 @@ async fn setup_intent_succeeded_handler(
         .parse()
         .map_err(Error::ParseOrgId)?;
     let org = Org::by_id(org_id, &mut write).await?;
-    let Some(stripe) = write.ctx.stripe.as_ref() else {
+    let ctx = write.ctx;
+    let Some(stripe) = ctx.stripe.as_ref() else {
         return Err(Error::NoStripe);
     };
 
-    if let Some(stripe_customer_id) = org.stripe_customer_id.as_ref() {
+    if let Some(stripe_customer_id) = org.customer_id(&ctx.keyring)? {
         stripe
-            .attach_payment_method(&setup_intent.payment_method, stripe_customer_id)
+            .attach_payment_method(&setup_intent.payment_method, &stripe_customer_id)
             .await?;
     } else {
         let owner = User::owner(org_id, &mut write).await?;
@@ -119,8 +159,74 @@ async fn setup_intent_succeeded_handler(
             .create_customer(&org, &owner, Some(&setup_intent.payment_method))
             .await?
             .id;
-        org.set_customer_id(&customer_id, &mut write).await?;
+        org.set_customer_id(&customer_id, &ctx.keyring, &mut write)
+            .await?;
     };
 
     Ok(serde_json::json!({"message": "subscription created"}))
 }
+
+async fn invoice_payment_failed(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<axum::Json<serde_json::Value>, super::Error> {
+    verify(&ctx, &headers, &body).map_err(Status::from)?;
+
+    let event: Event = match serde_json::from_str(&body) {
+        Ok(body) => body,
+        Err(err) => {
+            return Err(Status::from(Error::UnparseableStripeBody(err)).into());
+        }
+    };
+
+    match event.data.object {
+        EventObject::Invoice(invoice) => {
+            ctx.write(|c| invoice_payment_failed_handler(invoice, c).scope_boxed())
+                .await
+        }
+        EventObject::SetupIntent(_) | EventObject::Other => {
+            debug!("Skipping stripe callback event: {body}");
+            Ok(axum::Json(serde_json::json!({"message": "event ignored"})))
+        }
+    }
+}
+
+/// Push a notification to the owner of the org whose payment failed.
+async fn invoice_payment_failed_handler(
+    invoice: Invoice,
+    mut write: WriteConn<'_, '_>,
+) -> Result<serde_json::Value, Error> {
+    let customer_id = match invoice.customer {
+        Some(IdOrObject::Id(id)) => id,
+        Some(IdOrObject::Object(customer)) => customer.id.to_string(),
+        None => return Err(Error::MissingCustomer),
+    };
+    let ctx = write.ctx;
+    let Some(org) = Org::by_customer_id(&customer_id, &ctx.keyring, &mut write).await? else {
+        debug!("Skipping payment failure of unknown customer: {customer_id}");
+        return Ok(serde_json::json!({"message": "event ignored"}));
+    };
+    let Some(push) = ctx.push.clone() else {
+        return Ok(serde_json::json!({"message": "event ignored"}));
+    };
+
+    let owner = User::owner(org.id, &mut write).await?;
+    let message = Message {
+        title: "Payment failed".to_string(),
+        body: format!(
+            "The latest payment for {} could not be processed. Please update your payment method.",
+            org.name
+        ),
+    };
+    let user_ids = HashSet::from([owner.id]);
+    push.notify(
+        &user_ids,
+        NotificationKind::PaymentFailed,
+        &message,
+        &mut write,
+    )
+    .await?;
+
+    Ok(serde_json::json!({"message": "payment failure notified"}))
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/", routing::post(create))
+        .route("/", routing::get(list))
+        .route("/:id", routing::get(get))
+        .route("/:id", routing::put(update))
+        .route("/:id", routing::delete(delete))
+        .route("/:id/member", routing::post(add_member))
+        .route("/:id/member/:user_id", routing::delete(remove_member))
+        .route("/:id/role", routing::post(grant_role))
+        .route("/:id/role/:role", routing::delete(revoke_role))
+        .route("/:id/project", routing::post(add_project))
+        .route("/:id/project/:project_id", routing::delete(remove_project))
+        .with_state(context)
+}
+
+async fn create(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::TeamServiceCreateRequest>,
+) -> Result<Json<api::TeamServiceCreateResponse>, Error> {
+    ctx.write(|write| grpc::team::create(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::TeamServiceListRequest>,
+) -> Result<Json<api::TeamServiceListResponse>, Error> {
+    ctx.read(|read| grpc::team::list(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn get(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id,)): Path<(String,)>,
+) -> Result<Json<api::TeamServiceGetResponse>, Error> {
+    let req = api::TeamServiceGetRequest { team_id };
+    ctx.read(|read| grpc::team::get(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TeamServiceUpdateRequest {
+    name: Option<String>,
+}
+
+async fn update(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id,)): Path<(String,)>,
+    Json(req): Json<TeamServiceUpdateRequest>,
+) -> Result<Json<api::TeamServiceUpdateResponse>, Error> {
+    let req = api::TeamServiceUpdateRequest {
+        team_id,
+        name: req.name,
+    };
+    ctx.write(|write| grpc::team::update(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn delete(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id,)): Path<(String,)>,
+) -> Result<Json<api::TeamServiceDeleteResponse>, Error> {
+    let req = api::TeamServiceDeleteRequest { team_id };
+    ctx.write(|write| grpc::team::delete(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TeamServiceAddMemberRequest {
+    user_id: String,
+}
+
+async fn add_member(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id,)): Path<(String,)>,
+    Json(req): Json<TeamServiceAddMemberRequest>,
+) -> Result<Json<api::TeamServiceAddMemberResponse>, Error> {
+    let req = api::TeamServiceAddMemberRequest {
+        team_id,
+        user_id: req.user_id,
+    };
+    ctx.write(|write| grpc::team::add_member(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn remove_member(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id, user_id)): Path<(String, String)>,
+) -> Result<Json<api::TeamServiceRemoveMemberResponse>, Error> {
+    let req = api::TeamServiceRemoveMemberRequest { team_id, user_id };
+    ctx.write(|write| grpc::team::remove_member(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TeamServiceGrantRoleRequest {
+    role: String,
+}
+
+async fn grant_role(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id,)): Path<(String,)>,
+    Json(req): Json<TeamServiceGrantRoleRequest>,
+) -> Result<Json<api::TeamServiceGrantRoleResponse>, Error> {
+    let req = api::TeamServiceGrantRoleRequest {
+        team_id,
+        role: req.role,
+    };
+    ctx.write(|write| grpc::team::grant_role(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn revoke_role(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id, role)): Path<(String, String)>,
+) -> Result<Json<api::TeamServiceRevokeRoleResponse>, Error> {
+    let req = api::TeamServiceRevokeRoleRequest { team_id, role };
+    ctx.write(|write| grpc::team::revoke_role(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TeamServiceAddProjectRequest {
+    project_id: String,
+}
+
+async fn add_project(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id,)): Path<(String,)>,
+    Json(req): Json<TeamServiceAddProjectRequest>,
+) -> Result<Json<api::TeamServiceAddProjectResponse>, Error> {
+    let req = api::TeamServiceAddProjectRequest {
+        team_id,
+        project_id: req.project_id,
+    };
+    ctx.write(|write| grpc::team::add_project(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn remove_project(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((team_id, project_id)): Path<(String, String)>,
+) -> Result<Json<api::TeamServiceRemoveProjectResponse>, Error> {
+    let req = api::TeamServiceRemoveProjectRequest {
+        team_id,
+        project_id,
+    };
+    ctx.write(|write| grpc::team::remove_project(req, headers.into(), write).scope_boxed())
+        .await
+}
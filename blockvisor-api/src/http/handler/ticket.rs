@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::header::HeaderMap;
+use axum::routing::{self, Router};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use serde::Deserialize;
+
+use crate::config::Context;
+use crate::database::Transaction;
+use crate::grpc::{self, api};
+
+use super::Error;
+
+pub fn router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/integration", routing::post(create_integration))
+        .route("/integration", routing::get(get_integration))
+        .route("/integration/:id", routing::delete(delete_integration))
+        .with_state(context)
+}
+
+/// Incoming ticket status changes, authenticated by the integration token.
+pub fn webhook_router<S>(context: Arc<Context>) -> Router<S>
+where
+    S: Clone + Send + Sync,
+{
+    Router::new()
+        .route("/:id", routing::post(webhook))
+        .with_state(context)
+}
+
+async fn create_integration(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Json(req): Json<api::TicketServiceCreateIntegrationRequest>,
+) -> Result<Json<api::TicketServiceCreateIntegrationResponse>, Error> {
+    ctx.write(|write| grpc::ticket::create_integration(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_integration(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Query(req): Query<api::TicketServiceGetIntegrationRequest>,
+) -> Result<Json<api::TicketServiceGetIntegrationResponse>, Error> {
+    ctx.read(|read| grpc::ticket::get_integration(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn delete_integration(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((integration_id,)): Path<(String,)>,
+) -> Result<Json<api::TicketServiceDeleteIntegrationResponse>, Error> {
+    let req = api::TicketServiceDeleteIntegrationRequest { integration_id };
+    ctx.write(|write| grpc::ticket::delete_integration(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+#[derive(Deserialize)]
+struct WebhookQuery {
+    token: String,
+}
+
+async fn webhook(
+    State(ctx): State<Arc<Context>>,
+    Path((integration_id,)): Path<(String,)>,
+    Query(query): Query<WebhookQuery>,
+    body: String,
+) -> Result<Json<serde_json::Value>, Error> {
+    ctx.write(|write| {
+        grpc::ticket::webhook(&integration_id, &query.token, &body, write).scope_boxed()
+    })
+    .await
+}
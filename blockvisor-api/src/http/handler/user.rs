@@ -22,9 +22,34 @@ where
         .route("/", routing::get(list))
         .route("/", routing::put(update))
         .route("/:user_id", routing::delete(delete))
+        .route("/:user_id/account", routing::delete(delete_account))
+        .route("/:user_id/export", routing::post(export_data))
+        .route(
+            "/:user_id/support-access",
+            routing::post(grant_support_access),
+        )
+        .route(
+            "/:user_id/support-access",
+            routing::delete(revoke_support_access),
+        )
+        .route(
+            "/:user_id/require-password-reset",
+            routing::post(require_password_reset),
+        )
         .route("/:user_id/settings", routing::get(get_settings))
         .route("/:user_id/settings", routing::put(update_settings))
         .route("/:user_id/settings", routing::delete(delete_settings))
+        .route("/:user_id/devices", routing::post(register_device))
+        .route("/:user_id/devices", routing::get(list_devices))
+        .route("/devices/:device_id", routing::delete(remove_device))
+        .route(
+            "/:user_id/notifications",
+            routing::get(get_notification_preferences),
+        )
+        .route(
+            "/:user_id/notifications",
+            routing::put(update_notification_preferences),
+        )
         .with_state(context)
 }
 
@@ -75,6 +100,56 @@ async fn delete(
         .await
 }
 
+async fn delete_account(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceDeleteAccountResponse>, Error> {
+    let req = api::UserServiceDeleteAccountRequest { user_id };
+    ctx.write(|write| grpc::user::delete_account(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn export_data(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceExportDataResponse>, Error> {
+    let req = api::UserServiceExportDataRequest { user_id };
+    ctx.write(|write| grpc::user::export_data(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn grant_support_access(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceGrantSupportAccessResponse>, Error> {
+    let req = api::UserServiceGrantSupportAccessRequest { user_id };
+    ctx.write(|write| grpc::user::grant_support_access(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn revoke_support_access(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceRevokeSupportAccessResponse>, Error> {
+    let req = api::UserServiceRevokeSupportAccessRequest { user_id };
+    ctx.write(|write| grpc::user::revoke_support_access(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn require_password_reset(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceRequirePasswordResetResponse>, Error> {
+    let req = api::UserServiceRequirePasswordResetRequest { user_id };
+    ctx.write(|write| grpc::user::require_password_reset(req, headers.into(), write).scope_boxed())
+        .await
+}
+
 async fn get_settings(
     State(ctx): State<Arc<Context>>,
     headers: HeaderMap,
@@ -126,3 +201,83 @@ async fn delete_settings(
     ctx.write(|write| grpc::user::delete_settings(req, headers.into(), write).scope_boxed())
         .await
 }
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserServiceRegisterDeviceRequest {
+    platform: i32,
+    push_token: String,
+    name: String,
+}
+
+async fn register_device(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+    Json(req): Json<UserServiceRegisterDeviceRequest>,
+) -> Result<Json<api::UserServiceRegisterDeviceResponse>, Error> {
+    let req = api::UserServiceRegisterDeviceRequest {
+        user_id,
+        platform: req.platform,
+        push_token: req.push_token,
+        name: req.name,
+    };
+    ctx.write(|write| grpc::user::register_device(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn list_devices(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceListDevicesResponse>, Error> {
+    let req = api::UserServiceListDevicesRequest { user_id };
+    ctx.read(|read| grpc::user::list_devices(req, headers.into(), read).scope_boxed())
+        .await
+}
+
+async fn remove_device(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((device_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceRemoveDeviceResponse>, Error> {
+    let req = api::UserServiceRemoveDeviceRequest { device_id };
+    ctx.write(|write| grpc::user::remove_device(req, headers.into(), write).scope_boxed())
+        .await
+}
+
+async fn get_notification_preferences(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+) -> Result<Json<api::UserServiceGetNotificationPreferencesResponse>, Error> {
+    let req = api::UserServiceGetNotificationPreferencesRequest { user_id };
+    ctx.read(|read| {
+        grpc::user::get_notification_preferences(req, headers.into(), read).scope_boxed()
+    })
+    .await
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserServiceUpdateNotificationPreferencesRequest {
+    node_down: Option<bool>,
+    payment_failed: Option<bool>,
+}
+
+async fn update_notification_preferences(
+    State(ctx): State<Arc<Context>>,
+    headers: HeaderMap,
+    Path((user_id,)): Path<(String,)>,
+    Json(req): Json<UserServiceUpdateNotificationPreferencesRequest>,
+) -> Result<Json<api::UserServiceUpdateNotificationPreferencesResponse>, Error> {
+    let req = api::UserServiceUpdateNotificationPreferencesRequest {
+        user_id,
+        node_down: req.node_down,
+        payment_failed: req.payment_failed,
+    };
+    ctx.write(|write| {
+        grpc::user::update_notification_preferences(req, headers.into(), write).scope_boxed()
+    })
+    .await
+}
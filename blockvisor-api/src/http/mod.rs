@@ -12,36 +12,69 @@ use tower_http::trace::TraceLayer;
 use crate::config::Context;
 
 use self::handler::{
-    api_key, archive, auth, bundle, discovery, health, host, invitation, metrics, mqtt, node, org,
-    protocol, stripe, user,
+    admin, alert, api_key, approval, archive, auth, billing, bundle, discovery, endpoint, health,
+    host, incident, internal_admin, invitation, managed_host, mesh, metrics, mqtt, node,
+    node_share, org, org_branding, org_key, org_log_sink, pool, protocol, report, status_page,
+    stripe, team, ticket, user,
 };
 
+#[allow(clippy::let_and_return)] // without integration-test
 pub fn router(context: &Arc<Context>) -> Router {
     let cors = CorsLayer::new()
         .allow_headers(Any)
         .allow_methods(Any)
         .allow_origin(Any);
 
-    Router::new()
+    let router = Router::new()
         .layer(cors)
         .layer(CompressionLayer::new())
         .layer(TraceLayer::new_for_http())
         .layer(OtelAxumLayer::default())
         // These are the endpoints that are also gRPC handlers
+        .nest("/v1/admin", admin::router(context.clone()))
+        .nest("/v1/alert", alert::router(context.clone()))
         .nest("/v1/api-key", api_key::router(context.clone()))
+        .nest("/v1/approval", approval::router(context.clone()))
         .nest("/v1/archive", archive::router(context.clone()))
         .nest("/v1/auth", auth::router(context.clone()))
+        .nest("/v1/billing", billing::router(context.clone()))
         .nest("/v1/bundle", bundle::router(context.clone()))
         .nest("/v1/discovery", discovery::router(context.clone()))
+        .nest("/v1/endpoint", endpoint::router(context.clone()))
         .nest("/v1/host", host::router(context.clone()))
+        .nest("/v1/incident", incident::router(context.clone()))
+        .nest(
+            "/v1/internal-admin",
+            internal_admin::router(context.clone()),
+        )
         .nest("/v1/invitation", invitation::router(context.clone()))
+        .nest("/v1/managed-host", managed_host::router(context.clone()))
+        .nest("/v1/mesh", mesh::router(context.clone()))
         .nest("/v1/metrics", metrics::router(context.clone()))
         .nest("/v1/node", node::router(context.clone()))
+        .nest("/v1/node-share", node_share::router(context.clone()))
         .nest("/v1/org", org::router(context.clone()))
+        .nest("/v1/org-branding", org_branding::router(context.clone()))
+        .nest("/v1/org-key", org_key::router(context.clone()))
+        .nest("/v1/org-log-sink", org_log_sink::router(context.clone()))
+        .nest("/v1/pool", pool::router(context.clone()))
         .nest("/v1/protocol", protocol::router(context.clone()))
+        .nest("/v1/report", report::router(context.clone()))
+        .nest("/v1/status-page", status_page::router(context.clone()))
+        .nest("/v1/team", team::router(context.clone()))
+        .nest("/v1/ticket", ticket::router(context.clone()))
         .nest("/v1/user", user::router(context.clone()))
         // These are utility endpoints that are not accessible through the gRPC API
         .nest("/v1/stripe", stripe::router(context.clone()))
+        .nest("/branding", org_branding::public_router(context.clone()))
+        .nest("/share", node_share::public_router(context.clone()))
+        .nest("/status", status_page::public_router(context.clone()))
+        .nest("/ticketing", ticket::webhook_router(context.clone()))
         .nest("/mqtt", mqtt::router(context.clone()))
-        .merge(health::router(context.clone()))
+        .merge(health::router(context.clone()));
+
+    #[cfg(any(test, feature = "integration-test"))]
+    let router = router.nest("/v1/chaos", handler::chaos::router(context.clone()));
+
+    router
 }
@@ -11,11 +11,13 @@ const BAD_REQUEST: StatusCode = StatusCode::BAD_REQUEST;
 const UNAUTHORIZED: StatusCode = StatusCode::UNAUTHORIZED;
 const INTERNAL_SERVER_ERROR: StatusCode = StatusCode::INTERNAL_SERVER_ERROR;
 const NOT_FOUND: StatusCode = StatusCode::NOT_FOUND;
+const SERVICE_UNAVAILABLE: StatusCode = StatusCode::SERVICE_UNAVAILABLE;
 
 #[derive(Clone, Copy)]
 pub enum Message {
     BadParams,
     DbClosed,
+    Draining,
     Empty,
     Failed,
     Unauthorized,
@@ -29,6 +31,7 @@ impl From<Message> for &'static str {
         match message {
             BadParams => "Bad params.",
             DbClosed => "DB connection is closed.",
+            Draining => "Server is shutting down.",
             Empty => "",
             Failed => "Failed.",
             Unauthorized => "Unauthorized.",
@@ -66,6 +69,10 @@ pub fn db_closed() -> Response {
     (INTERNAL_SERVER_ERROR, Body::json(Message::DbClosed)).into_response()
 }
 
+pub fn draining() -> Response {
+    (SERVICE_UNAVAILABLE, Body::json(Message::Draining)).into_response()
+}
+
 #[derive(Serialize)]
 struct Body {
     pub message: &'static str,
@@ -0,0 +1,1615 @@
+//! Background jobs that run periodically alongside the server.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{NaiveDate, Utc};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use displaydoc::Display;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::auth::claims::Claims;
+use crate::auth::rbac::GrpcRole;
+use crate::auth::resource::{NodeId, OrgId, Resource, UserId};
+use crate::cloud_providers::{self, Credentials, MachineSpec, MachineState};
+use crate::config::Context;
+use crate::database::{Conn, Database, Transaction};
+use crate::email::Email;
+use crate::grpc::{Metadata, api, common};
+use crate::model::alert::{AlertKind, NewAlert};
+use crate::model::artifact::Artifact;
+use crate::model::backfill::{Backfill, Task};
+use crate::model::backorder::Backorder;
+use crate::model::command::{Command, CommandType, NewCommand};
+use crate::model::endpoint::autoscaler::{AutoscaleAction, NewAutoscaleEvent};
+use crate::model::endpoint::{EndpointPool, PoolAutoscaler, PoolMember};
+use crate::model::managed_host::{ManagedHostId, ManagedHostStatus};
+use crate::model::node::certificate::{CERTIFICATE_SECRET, PRIVATE_KEY_SECRET};
+use crate::model::node::slo::{self, NodeDowntime, OrgSloReport};
+use crate::model::node::{NodeCertificate, NodeCleanup, NodeDnsAlias, NodeSlo, NodeState, drift};
+use crate::model::protocol::NetworkHead;
+use crate::model::rbac::OrgUsers;
+use crate::model::retention::RetainedTable;
+use crate::model::sql::IpNetwork;
+use crate::model::ticket::NewTicket;
+use crate::model::user::export::{UserExport, UserExportRequest};
+use crate::model::user::notification::NotificationKind;
+use crate::model::user::setting::UserLocale;
+use crate::model::{
+    AgentUpgrade, AgentUpgradeId, Alert, EscalationPolicy, Incident, LegalHold, ManagedHost, Node,
+    OncallSchedule, OncallScheduleId, Org, OrgBranding, Region, RequestLog, Ticket,
+    TicketIntegration, Token, User,
+};
+use crate::model::{org_trial, orphan};
+use crate::push::Message;
+use crate::store::secret::SecretKey;
+use crate::ticket;
+use crate::util::NanosUtc;
+
+/// How often to check for deleted orgs whose grace period has expired.
+const PURGE_ORGS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often to assemble pending user data exports.
+const USER_EXPORTS_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to check for orgs pending last month's uptime report.
+const UPTIME_REPORTS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often to push new incidents and status changes to ticketing providers.
+const SYNC_TICKETS_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to delete request logs past `REQUEST_LOG_RETENTION`.
+const PURGE_REQUEST_LOGS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long the outcome of a failed request is kept for support.
+const REQUEST_LOG_RETENTION: chrono::TimeDelta = chrono::TimeDelta::days(30);
+/// How often to retry the cleanup of deleted nodes that failed or crashed.
+const NODE_CLEANUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How often to check external services for resources left by failed deletes.
+const RECONCILE_ORPHANS_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// How often to refresh the head block of each network.
+const NETWORK_HEADS_INTERVAL: Duration = Duration::from_secs(60);
+/// How long an rpc provider has to return the head of a network.
+const NETWORK_HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to check running agent upgrades for their next wave.
+const AGENT_UPGRADES_INTERVAL: Duration = Duration::from_secs(30);
+/// How often to create, poll and delete the machines of managed hosts.
+const MANAGED_HOSTS_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a managed host has to register once its machine is created.
+const MANAGED_HOST_BOOTSTRAP_TIMEOUT: chrono::TimeDelta = chrono::TimeDelta::minutes(30);
+/// How often to create upcoming partitions and purge rows past their retention.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// How often to issue pending node certificates and renew expiring ones.
+const CERTIFICATES_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How often to ask hosts for the config their running nodes use.
+const CONFIG_DRIFT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often to release scheduled commands whose time has come.
+const SCHEDULED_COMMANDS_INTERVAL: Duration = Duration::from_secs(30);
+/// How often to remind orgs of ending trials and hibernate expired ones.
+const TRIALS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often to page the on-call users of unacknowledged alerts.
+const ESCALATE_ALERTS_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to push notifications for nodes that went down.
+const PUSH_DOWNTIMES_INTERVAL: Duration = Duration::from_secs(60);
+/// How often to remove the old DNS names of renamed nodes once they expire.
+const EXPIRE_DNS_ALIASES_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often to retry the placement of backordered nodes.
+const BACKORDERS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How long the token used to create backordered nodes is valid for.
+const BACKORDER_TOKEN_EXPIRY: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+/// How often to rotate endpoint pool members in or out by node health.
+const ROTATE_ENDPOINT_POOLS_INTERVAL: Duration = Duration::from_secs(30);
+/// How often to scale endpoint pools by the usage of their nodes.
+const AUTOSCALE_POOLS_INTERVAL: Duration = Duration::from_secs(60);
+/// How long the token used to scale endpoint pools is valid for.
+const AUTOSCALE_TOKEN_EXPIRY: chrono::TimeDelta = chrono::TimeDelta::minutes(5);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Job ACME error: {0}
+    Acme(#[from] crate::acme::Error),
+    /// Failed to parse ACME duration: {0}
+    AcmeDuration(crate::config::Error),
+    /// Job agent upgrade error: {0}
+    AgentUpgrade(#[from] crate::model::agent_upgrade::Error),
+    /// Job alert error: {0}
+    Alert(#[from] crate::model::alert::Error),
+    /// Job node alias error: {0}
+    Alias(#[from] crate::model::node::alias::Error),
+    /// Job artifact error: {0}
+    Artifact(#[from] crate::model::artifact::Error),
+    /// Deleting node `{0}` is awaiting approval.
+    AutoscaleApproval(NodeId),
+    /// Failed to scale the nodes of an endpoint pool: {0}
+    AutoscaleNode(tonic::Status),
+    /// Creating a node returned no node.
+    AutoscaleNoNode,
+    /// Failed to parse the NodeId of an autoscaled node: {0}
+    AutoscaleNodeId(uuid::Error),
+    /// Job autoscaler error: {0}
+    Autoscaler(#[from] crate::model::endpoint::autoscaler::Error),
+    /// Job backfill error: {0}
+    Backfill(#[from] crate::model::backfill::Error),
+    /// Job backorder error: {0}
+    Backorder(#[from] crate::model::backorder::Error),
+    /// Job certificate error: {0}
+    Certificate(#[from] crate::model::node::certificate::Error),
+    /// Job cloud provider error: {0}
+    CloudProvider(#[from] crate::cloud_providers::Error),
+    /// Job command error: {0}
+    Command(#[from] crate::model::command::Error),
+    /// Job database error: {0}
+    Database(#[from] crate::database::Error),
+    /// Job config drift error: {0}
+    Drift(#[from] crate::model::node::drift::Error),
+    /// Job email error: {0}
+    Email(#[from] crate::email::Error),
+    /// Job endpoint pool error: {0}
+    EndpointPool(#[from] crate::model::endpoint::pool::Error),
+    /// Failed to create the nodes of a backorder: {0}
+    FulfilBackorder(tonic::Status),
+    /// Job command error: {0}
+    GrpcCommand(#[from] crate::grpc::command::Error),
+    /// Job JWT error: {0}
+    Jwt(#[from] crate::auth::token::jwt::Error),
+    /// Job legal hold error: {0}
+    LegalHold(#[from] crate::model::legal_hold::Error),
+    /// Job managed host error: {0}
+    ManagedHost(#[from] crate::model::managed_host::Error),
+    /// Managed host `{0}` was not provisioned by a user.
+    ManagedHostCreator(ManagedHostId),
+    /// Job network head error: {0}
+    NetworkHead(#[from] crate::model::protocol::head::Error),
+    /// Email is not configured.
+    NoEmail,
+    /// Job node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Job node cleanup error: {0}
+    NodeCleanup(#[from] crate::model::node::cleanup::Error),
+    /// Job on-call error: {0}
+    Oncall(#[from] crate::model::oncall::Error),
+    /// Job org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Job org branding error: {0}
+    OrgBranding(#[from] crate::model::org_branding::Error),
+    /// Job orphan error: {0}
+    Orphan(#[from] crate::model::orphan::Error),
+    /// Job region error: {0}
+    Region(#[from] crate::model::region::Error),
+    /// Job request log error: {0}
+    RequestLog(#[from] crate::model::request_log::Error),
+    /// Job retention error: {0}
+    Retention(#[from] crate::model::retention::Error),
+    /// Job secret error: {0}
+    Secret(#[from] crate::store::secret::Error),
+    /// Job uptime error: {0}
+    Slo(#[from] crate::model::node::slo::Error),
+    /// Job store error: {0}
+    Store(#[from] crate::store::Error),
+    /// Job ticket error: {0}
+    Ticket(#[from] crate::model::ticket::Error),
+    /// Job ticketing error: {0}
+    Ticketing(#[from] crate::ticket::Error),
+    /// Job token error: {0}
+    Token(#[from] crate::model::token::Error),
+    /// Job push error: {0}
+    Push(#[from] crate::push::Error),
+    /// Job rbac error: {0}
+    Rbac(#[from] crate::model::rbac::Error),
+    /// Job org trial error: {0}
+    Trial(#[from] crate::model::org_trial::Error),
+    /// Job user error: {0}
+    User(#[from] crate::model::user::Error),
+    /// Job user export error: {0}
+    UserExport(#[from] crate::model::user::export::Error),
+    /// Job user settings error: {0}
+    UserSettings(#[from] crate::model::user::setting::Error),
+}
+
+/// Spawn all background jobs onto the current tokio runtime.
+pub fn spawn(context: &Arc<Context>) {
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_ORGS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = purge_orgs(&ctx).await {
+                warn!("Failed to purge deleted orgs: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(USER_EXPORTS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = run_user_exports(&ctx).await {
+                warn!("Failed to run user exports: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPTIME_REPORTS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = send_uptime_reports(&ctx).await {
+                warn!("Failed to send uptime reports: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let client = match ticket::Client::new(ctx.keyring.clone()) {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Failed to create ticketing client: {err}");
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(SYNC_TICKETS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = sync_tickets(&ctx, &client).await {
+                warn!("Failed to sync tickets: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_REQUEST_LOGS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = purge_request_logs(&ctx).await {
+                warn!("Failed to purge request logs: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(NODE_CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = resume_node_cleanups(&ctx).await {
+                warn!("Failed to resume node cleanups: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RECONCILE_ORPHANS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = reconcile_orphans(&ctx).await {
+                warn!("Failed to reconcile orphaned resources: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder()
+            .timeout(NETWORK_HEAD_TIMEOUT)
+            .build()
+        {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Failed to create network head client: {err}");
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(NETWORK_HEADS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = refresh_network_heads(&ctx, &client).await {
+                warn!("Failed to refresh network heads: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(AGENT_UPGRADES_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = advance_agent_upgrades(&ctx).await {
+                warn!("Failed to advance agent upgrades: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let client = match cloud_providers::Client::new() {
+            Ok(client) => client,
+            Err(err) => {
+                warn!("Failed to create cloud provider client: {err}");
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(MANAGED_HOSTS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = sync_managed_hosts(&ctx, &client).await {
+                warn!("Failed to sync managed hosts: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = enforce_retention(&ctx).await {
+                warn!("Failed to enforce table retention: {err}");
+            }
+            if let Err(err) = purge_artifacts(&ctx).await {
+                warn!("Failed to purge expired artifacts: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CERTIFICATES_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = renew_certificates(&ctx).await {
+                warn!("Failed to renew node certificates: {err}");
+            }
+            if let Err(err) = alert_expiring_certificates(&ctx).await {
+                warn!("Failed to alert expiring node certificates: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CONFIG_DRIFT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = request_config_reports(&ctx).await {
+                warn!("Failed to request node config reports: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULED_COMMANDS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = dispatch_scheduled_commands(&ctx).await {
+                warn!("Failed to dispatch scheduled commands: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TRIALS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = remind_trials(&ctx).await {
+                warn!("Failed to remind ending trials: {err}");
+            }
+            if let Err(err) = hibernate_trials(&ctx).await {
+                warn!("Failed to hibernate expired trials: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ESCALATE_ALERTS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = escalate_alerts(&ctx).await {
+                warn!("Failed to escalate unacknowledged alerts: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PUSH_DOWNTIMES_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = push_node_downtimes(&ctx).await {
+                warn!("Failed to push node downtimes: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRE_DNS_ALIASES_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = expire_dns_aliases(&ctx).await {
+                warn!("Failed to expire node DNS aliases: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(BACKORDERS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = fulfil_backorders(&ctx).await {
+                warn!("Failed to fulfil backorders: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ROTATE_ENDPOINT_POOLS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = rotate_endpoint_pools(&ctx).await {
+                warn!("Failed to rotate endpoint pools: {err}");
+            }
+        }
+    });
+
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(AUTOSCALE_POOLS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = autoscale_endpoint_pools(&ctx).await {
+                warn!("Failed to autoscale endpoint pools: {err}");
+            }
+        }
+    });
+}
+
+/// Purge the data of deleted orgs that are past `DELETE_GRACE_PERIOD`.
+///
+/// Each org is purged independently so that one failure doesn't block the
+/// others, and a partially purged org is retried on the next run. Orgs under
+/// legal hold are kept until the hold is cleared.
+pub async fn purge_orgs(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let held = LegalHold::held_org_ids(&mut conn).await?;
+    for org_id in Org::expired(&mut conn).await? {
+        if held.contains(&org_id) {
+            info!("Skipped purging org {org_id} under legal hold");
+            continue;
+        }
+        match Org::purge(org_id, &mut conn).await {
+            Ok(()) => info!("Purged deleted org {org_id}"),
+            Err(err) => warn!("Failed to purge org {org_id}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the request logs older than `REQUEST_LOG_RETENTION`.
+pub async fn purge_request_logs(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let cutoff = Utc::now() - REQUEST_LOG_RETENTION;
+    let purged = RequestLog::purge(cutoff, &mut conn).await?;
+    if purged > 0 {
+        info!("Purged {purged} request logs");
+    }
+
+    Ok(())
+}
+
+/// Delete the command artifacts past their retention from the store.
+///
+/// A row is only deleted once its artifact is gone from the store, so that a
+/// failed delete is retried on the next run. Artifacts of nodes under legal
+/// hold are kept until the hold is cleared.
+pub async fn purge_artifacts(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let held = LegalHold::held_node_ids(&mut conn).await?;
+    for artifact in Artifact::expired(&mut conn).await? {
+        if artifact
+            .node_id
+            .is_some_and(|node_id| held.contains(&node_id))
+        {
+            continue;
+        }
+        if let Err(err) = context.store.delete_artifact(&artifact.store_key).await {
+            warn!("Failed to delete artifact {}: {err}", artifact.id);
+            continue;
+        }
+        Artifact::delete(artifact.id, &mut conn).await?;
+    }
+
+    Ok(())
+}
+
+/// Create the partitions of each retained table for this month and the next,
+/// then drop the partitions past its configured retention.
+///
+/// Each table is handled independently so that one failure doesn't block the
+/// others.
+pub async fn enforce_retention(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let config = &context.config.retention;
+    let now = Utc::now();
+
+    for table in RetainedTable::ALL {
+        if let Err(err) = table.ensure_partitions(now, &mut conn).await {
+            warn!("Failed to create partitions of {table}: {err}");
+        }
+
+        let cutoff = now - table.retention(config);
+        match table.purge(cutoff, &mut conn).await {
+            Ok(0) => (),
+            Ok(dropped) => info!("Dropped {dropped} expired partitions of {table}"),
+            Err(err) => warn!("Failed to purge {table}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Refresh the head block of each network from its rpc provider or reference
+/// node, which the sync lag of its nodes is measured against.
+pub async fn refresh_network_heads(
+    context: &Context,
+    client: &reqwest::Client,
+) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    NetworkHead::refresh(client, &mut conn)
+        .await
+        .map(|_| ())
+        .map_err(Into::into)
+}
+
+/// Send the next wave of each running agent upgrade whose current wave has
+/// finished, or halt it once too many of its hosts have failed.
+///
+/// Each upgrade is advanced independently so that one failure doesn't block
+/// the others.
+pub async fn advance_agent_upgrades(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for id in AgentUpgrade::running(&mut conn).await? {
+        match advance_agent_upgrade(context, id, &mut conn).await {
+            Ok(0) => (),
+            Ok(sent) => info!("Sent the next wave of agent upgrade {id} to {sent} hosts"),
+            Err(err) => warn!("Failed to advance agent upgrade {id}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn advance_agent_upgrade(
+    context: &Context,
+    id: AgentUpgradeId,
+    conn: &mut Conn<'_>,
+) -> Result<usize, Error> {
+    let commands = AgentUpgrade::advance(id, conn).await?;
+    for command in &commands {
+        if let Some(command) = api::Command::from_host(command)? {
+            if let Err(err) = context.notifier.send(command).await {
+                warn!("Failed to send MQTT message: {err}");
+            }
+        }
+    }
+
+    Ok(commands.len())
+}
+
+/// Create, poll and delete the machines of managed hosts with their provider.
+///
+/// Each managed host is synced independently so that one org's broken
+/// credentials don't block the others, and a failed sync is retried on the next
+/// run. A machine that failed to create is not retried, as the provider may
+/// have created it regardless.
+pub async fn sync_managed_hosts(
+    context: &Context,
+    client: &cloud_providers::Client,
+) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for managed_host in ManagedHost::pending(&mut conn).await? {
+        let id = managed_host.id;
+        if let Err(err) = sync_managed_host(context, client, &managed_host, &mut conn).await {
+            warn!("Failed to sync managed host {id}: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn sync_managed_host(
+    context: &Context,
+    client: &cloud_providers::Client,
+    managed_host: &ManagedHost,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let id = managed_host.id;
+    let (org_id, provider) = (managed_host.org_id, managed_host.provider);
+
+    match (managed_host.status, managed_host.external_id.as_deref()) {
+        (ManagedHostStatus::Creating, _) => {
+            match create_machine(context, client, managed_host, conn).await {
+                Ok(external_id) => {
+                    managed_host.created(&external_id, conn).await?;
+                    info!("Created machine {external_id} for managed host {id}");
+                }
+                Err(err) => {
+                    managed_host.failed(&err.to_string(), conn).await?;
+                    warn!("Failed to create machine for managed host {id}: {err}");
+                }
+            }
+        }
+
+        (ManagedHostStatus::Bootstrapping, Some(external_id)) => {
+            let credentials = Credentials::read(org_id, provider, &context.secret, conn).await?;
+            let machine = client
+                .get(&credentials, &managed_host.provider_region, external_id)
+                .await?;
+            let expired = managed_host.created_at + MANAGED_HOST_BOOTSTRAP_TIMEOUT < Utc::now();
+
+            if matches!(machine.state, MachineState::Failed | MachineState::Deleted) {
+                managed_host
+                    .failed(&format!("Machine is {:?}.", machine.state), conn)
+                    .await?;
+            } else if expired {
+                managed_host
+                    .failed("Machine did not register as a host in time.", conn)
+                    .await?;
+            } else if let Some(ip) = machine.ip_address {
+                let ip_address = IpNetwork::from(ipnetwork::IpNetwork::from(ip));
+                if managed_host.ip_address != Some(ip_address) {
+                    let managed_host = managed_host.set_ip_address(ip_address, conn).await?;
+                    managed_host.link_host(conn).await?;
+                }
+            }
+        }
+
+        (ManagedHostStatus::Deleting, external_id) => {
+            if let Some(external_id) = external_id {
+                let credentials =
+                    Credentials::read(org_id, provider, &context.secret, conn).await?;
+                client
+                    .delete(&credentials, &managed_host.provider_region, external_id)
+                    .await?;
+            }
+            managed_host.deleted(conn).await?;
+            info!("Deleted the machine of managed host {id}");
+        }
+
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Create the machine of a managed host, which bootstraps blockvisord with the
+/// host provision token of the user that provisioned it.
+async fn create_machine(
+    context: &Context,
+    client: &cloud_providers::Client,
+    managed_host: &ManagedHost,
+    conn: &mut Conn<'_>,
+) -> Result<String, Error> {
+    let credentials = Credentials::read(
+        managed_host.org_id,
+        managed_host.provider,
+        &context.secret,
+        conn,
+    )
+    .await?;
+    let user_id = managed_host
+        .created_by()
+        .user()
+        .ok_or(Error::ManagedHostCreator(managed_host.id))?;
+    let token = Token::host_provision_by_user(user_id, managed_host.org_id, conn).await?;
+    let region = Region::by_id(managed_host.region_id, conn).await?;
+
+    let user_data =
+        cloud_providers::bootstrap_script(&context.config.cloud, &token.token.take(), &region.key);
+    let spec = MachineSpec {
+        name: &managed_host.name,
+        region: &managed_host.provider_region,
+        machine_type: &managed_host.machine_type,
+        machine_image: &managed_host.machine_image,
+        user_data: &user_data,
+    };
+
+    client.create(&credentials, &spec).await.map_err(Into::into)
+}
+
+/// Issue the node certificates that are pending, expiring within
+/// `acme.renew_before`, or due a retry after failing.
+///
+/// Each certificate is issued independently so that one failed validation
+/// doesn't block the others, and records its error until the next retry.
+pub async fn renew_certificates(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let renew_before = chrono::Duration::try_from(context.config.acme.renew_before)
+        .map_err(Error::AcmeDuration)?;
+
+    for certificate in NodeCertificate::due(renew_before, &mut conn).await? {
+        let node_id = certificate.node_id;
+        match issue_certificate(context, &certificate, &mut conn).await {
+            Ok(issued) => info!(
+                "Issued certificate for node {node_id} until {}",
+                issued.not_after.unwrap_or_default()
+            ),
+            Err(err) => {
+                warn!("Failed to issue certificate for node {node_id}: {err}");
+                certificate.failed(&err.to_string(), &mut conn).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Issue a certificate, store it in the secret store, and send its chain to
+/// the host of the node.
+async fn issue_certificate(
+    context: &Context,
+    certificate: &NodeCertificate,
+    conn: &mut Conn<'_>,
+) -> Result<NodeCertificate, Error> {
+    let node = Node::by_id(certificate.node_id, conn).await?;
+    let names = certificate.names();
+    let issued = context.acme.issue(&names, &**context.dns).await?;
+
+    let resource = Resource::from(node.id);
+    let chain_key = SecretKey::new(CERTIFICATE_SECRET.to_string())?;
+    let private_key = SecretKey::new(PRIVATE_KEY_SECRET.to_string())?;
+    context
+        .secret
+        .put(resource, &chain_key, issued.chain_pem.as_bytes(), conn)
+        .await?;
+    context
+        .secret
+        .put(resource, &private_key, issued.key_pem.as_bytes(), conn)
+        .await?;
+
+    let certificate = certificate.issued(issued.not_after, conn).await?;
+    let command = NewCommand::node(&node, CommandType::NodeCertificate)?
+        .with_protobuf(&api::NodeCertificate {
+            names,
+            certificate_pem: issued.chain_pem,
+            private_key_secret: PRIVATE_KEY_SECRET.to_string(),
+            not_after: Some(NanosUtc::from(issued.not_after).into()),
+        })
+        .create(conn)
+        .await?;
+
+    let command = crate::grpc::command::node_certificate(&command, conn).await?;
+    if let Err(err) = context.notifier.send(command).await {
+        warn!("Failed to send MQTT message: {err}");
+    }
+
+    Ok(certificate)
+}
+
+/// Alert the org and host of each node whose certificate expires within
+/// `acme.alert_before` without having been renewed.
+///
+/// Each certificate is only alerted once until it is issued again.
+pub async fn alert_expiring_certificates(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let alert_before = chrono::Duration::try_from(context.config.acme.alert_before)
+        .map_err(Error::AcmeDuration)?;
+
+    for (certificate, node) in NodeCertificate::expiring(alert_before, &mut conn).await? {
+        warn!(
+            "Certificate for node {} expires at {}: {}",
+            node.id,
+            certificate.not_after.unwrap_or_default(),
+            certificate.last_error.as_deref().unwrap_or("not renewed")
+        );
+
+        let alert = api::NodeMessage::certificate_expiring(&node, &certificate);
+        if let Err(err) = context.notifier.send(alert).await {
+            warn!("Failed to send MQTT message: {err}");
+        }
+        let new_alert = NewAlert {
+            org_id: node.org_id,
+            node_id: Some(node.id),
+            kind: AlertKind::CertificateExpiring,
+            message: format!("The certificate of node {} is expiring.", node.display_name),
+        };
+        new_alert.create(&mut conn).await?;
+        certificate.alerted(&mut conn).await?;
+    }
+
+    Ok(())
+}
+
+/// Ask the host of each running node for a hash of the config it runs, which
+/// is compared against the stored config when the command succeeds.
+///
+/// Nodes with an unanswered report are skipped until their host responds.
+pub async fn request_config_reports(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for node in drift::due(&mut conn).await? {
+        if let Err(err) = request_config_report(context, &node, &mut conn).await {
+            warn!(
+                "Failed to request config report for node {}: {err}",
+                node.id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn request_config_report(
+    context: &Context,
+    node: &Node,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let command = NewCommand::node(node, CommandType::NodeReportConfig)?
+        .with_protobuf(&drift::payload(node))
+        .create(conn)
+        .await?;
+
+    let command = crate::grpc::command::node_report_config(&command, conn).await?;
+    if let Err(err) = context.notifier.send(command).await {
+        warn!("Failed to send MQTT message: {err}");
+    }
+
+    Ok(())
+}
+
+/// Release the scheduled commands that are due to their hosts.
+///
+/// Each command is marked as dispatched before it is sent, so that a failed
+/// send is left for the host to fetch as pending rather than sent twice.
+pub async fn dispatch_scheduled_commands(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for command in Command::due(&mut conn).await? {
+        if let Err(err) = dispatch_scheduled_command(context, &command, &mut conn).await {
+            warn!("Failed to dispatch scheduled command {}: {err}", command.id);
+        }
+    }
+
+    Ok(())
+}
+
+async fn dispatch_scheduled_command(
+    context: &Context,
+    command: &Command,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    command.dispatched(conn).await?;
+
+    if let Some(command) = crate::grpc::command::scheduled(command, conn).await? {
+        if let Err(err) = context.notifier.send(command).await {
+            warn!("Failed to send MQTT message: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Email the owner of each org whose trial ends within `trial.remind_days`.
+///
+/// Each org is only reminded once per trial, and a failed org is retried on
+/// the next run.
+pub async fn remind_trials(context: &Context) -> Result<(), Error> {
+    let Some(email) = context.email.as_ref() else {
+        return Ok(());
+    };
+    let mut conn = context.conn().await?;
+
+    let remind_before = chrono::TimeDelta::days(context.config.trial.remind_days.into());
+    for org in org_trial::ending(remind_before, &mut conn).await? {
+        match remind_trial(email, &org, &mut conn).await {
+            Ok(()) => info!("Reminded org {} that its trial ends soon", org.id),
+            Err(err) => warn!("Failed to remind org {} of its trial: {err}", org.id),
+        }
+    }
+
+    Ok(())
+}
+
+async fn remind_trial(email: &Email, org: &Org, conn: &mut Conn<'_>) -> Result<(), Error> {
+    let owner = User::owner(org.id, conn).await?;
+    let locale = UserLocale::by_user(owner.id, conn).await?;
+    email.trial_ending(&owner, &locale, org).await?;
+    org_trial::reminded(org.id, conn).await.map_err(Into::into)
+}
+
+/// Hibernate each org whose trial has ended by stopping its running nodes, then
+/// tell its owner to choose a plan.
+///
+/// Nodes are stopped before the org is marked as hibernated, so that a failed
+/// stop is retried on the next run.
+pub async fn hibernate_trials(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for org in org_trial::expired(&mut conn).await? {
+        match hibernate_trial(context, &org, &mut conn).await {
+            Ok(stopped) => info!("Hibernated org {} and stopped {stopped} nodes", org.id),
+            Err(err) => warn!("Failed to hibernate org {}: {err}", org.id),
+        }
+    }
+
+    Ok(())
+}
+
+async fn hibernate_trial(
+    context: &Context,
+    org: &Org,
+    conn: &mut Conn<'_>,
+) -> Result<usize, Error> {
+    let running = Node::running_by_org_id(org.id, conn).await?;
+    for node in &running {
+        let command = NewCommand::node(node, CommandType::NodeStop)?
+            .create(conn)
+            .await?;
+        let command = crate::grpc::command::node_stop(&command, conn).await?;
+        if let Err(err) = context.notifier.send(command).await {
+            warn!("Failed to send MQTT message: {err}");
+        }
+    }
+
+    let org = org_trial::hibernate(org.id, conn).await?;
+    if let Some(email) = context.email.as_ref() {
+        let owner = User::owner(org.id, conn).await?;
+        let locale = UserLocale::by_user(owner.id, conn).await?;
+        if let Err(err) = email.trial_ended(&owner, &locale, &org).await {
+            warn!(
+                "Failed to email the end of the trial of org {}: {err}",
+                org.id
+            );
+        }
+    }
+
+    Ok(running.len())
+}
+
+/// Page the next on-call schedule of each alert not acknowledged within the
+/// `ack_timeout_minutes` of its org's escalation policy.
+///
+/// Alerts of orgs without a policy are never escalated. An alert only moves to
+/// the next level once its page is sent, so a failed page is retried on the
+/// next run.
+pub async fn escalate_alerts(context: &Context) -> Result<(), Error> {
+    let Some(email) = context.email.as_ref() else {
+        return Ok(());
+    };
+    let mut conn = context.conn().await?;
+
+    let org_ids = Alert::unacknowledged_orgs(&mut conn).await?;
+    let policies: HashMap<OrgId, EscalationPolicy> = EscalationPolicy::by_orgs(&org_ids, &mut conn)
+        .await?
+        .into_iter()
+        .map(|policy| (policy.org_id, policy))
+        .collect();
+    let org_ids = policies.keys().copied().collect();
+
+    let now = Utc::now();
+    for alert in Alert::unacknowledged(&org_ids, &mut conn).await? {
+        let Some(schedule_id) = policies
+            .get(&alert.org_id)
+            .and_then(|policy| policy.next_schedule(&alert, now))
+        else {
+            continue;
+        };
+
+        match escalate_alert(email, &alert, schedule_id, &mut conn).await {
+            Ok(()) => info!("Escalated alert {} to schedule {schedule_id}", alert.id),
+            Err(err) => warn!("Failed to escalate alert {}: {err}", alert.id),
+        }
+    }
+
+    Ok(())
+}
+
+/// Email the user on call for `schedule_id`, skipping schedules that were
+/// deleted since the policy was set.
+async fn escalate_alert(
+    email: &Email,
+    alert: &Alert,
+    schedule_id: OncallScheduleId,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let schedules = OncallSchedule::by_ids(&HashSet::from([schedule_id]), conn).await?;
+    let on_call = schedules
+        .first()
+        .and_then(|schedule| schedule.on_call(Utc::now()));
+
+    if let Some(user_id) = on_call {
+        let user = User::by_id(user_id, conn).await?;
+        let locale = UserLocale::by_user(user_id, conn).await?;
+        let org = Org::by_id(alert.org_id, conn).await?;
+        let branding = OrgBranding::active(org.id, conn).await?;
+        email
+            .oncall_alert(&user, &locale, &org, branding.as_ref(), alert)
+            .await?;
+    }
+
+    alert.escalated(conn).await?;
+    Ok(())
+}
+
+/// Push a notification to the org members of each node that went down.
+///
+/// A downtime is pushed at most once, and downtimes of deleted nodes are
+/// marked as pushed without notifying anyone.
+pub async fn push_node_downtimes(context: &Context) -> Result<(), Error> {
+    let Some(push) = context.push.as_ref() else {
+        return Ok(());
+    };
+    let mut conn = context.conn().await?;
+
+    let downtimes = NodeDowntime::unpushed(&mut conn).await?;
+    let node_ids = downtimes.iter().map(|downtime| downtime.node_id).collect();
+    let nodes: HashMap<NodeId, Node> = Node::by_ids(&node_ids, &mut conn)
+        .await?
+        .into_iter()
+        .map(|node| (node.id, node))
+        .collect();
+    let org_ids = nodes.values().map(|node| node.org_id).collect();
+    let org_users = OrgUsers::for_org_ids(&org_ids, &mut conn).await?;
+
+    for downtime in downtimes {
+        if let Some(node) = nodes.get(&downtime.node_id) {
+            let user_ids = org_users
+                .get(&node.org_id)
+                .map(|users| users.user_roles.keys().copied().collect())
+                .unwrap_or_default();
+            let message = Message {
+                title: format!("Node {} is down", node.display_name),
+                body: format!(
+                    "The node has been down since {}.",
+                    downtime.started_at.format("%Y-%m-%d %H:%M UTC")
+                ),
+            };
+
+            match push
+                .notify(&user_ids, NotificationKind::NodeDown, &message, &mut conn)
+                .await
+            {
+                Ok(sent) => info!("Pushed downtime of node {} to {sent} devices", node.id),
+                Err(err) => {
+                    warn!("Failed to push downtime of node {}: {err}", node.id);
+                    continue;
+                }
+            }
+        }
+
+        downtime.pushed(&mut conn).await?;
+    }
+
+    Ok(())
+}
+
+/// Resume the cleanup of deleted nodes that failed or were interrupted.
+///
+/// Each node is claimed and resumed independently, from its last completed
+/// step. A node that fails again stays claimed until it may be retried.
+pub async fn resume_node_cleanups(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    while let Some(node) = NodeCleanup::claim_next(&mut conn).await? {
+        let node_id = node.id;
+        match NodeCleanup::resume(&node, context, &mut conn).await {
+            Ok(()) => info!("Cleaned up deleted node {node_id}"),
+            Err(err) => warn!("Failed to clean up deleted node {node_id}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the node DNS aliases past their expiry, such as the old names of
+/// renamed nodes.
+///
+/// An alias is only deleted once its DNS record is gone, so that a failed
+/// delete is retried on the next run.
+pub async fn expire_dns_aliases(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for alias in NodeDnsAlias::expired(&mut conn).await? {
+        match context.dns.delete(&alias.dns_id).await {
+            Ok(()) => (),
+            Err(err) if err.is_not_found() => (),
+            Err(err) => {
+                warn!("Failed to delete DNS alias {}: {err}", alias.name);
+                continue;
+            }
+        }
+        alias.delete(&mut conn).await?;
+        info!("Expired DNS alias {} of node {}", alias.name, alias.node_id);
+    }
+
+    Ok(())
+}
+
+/// Retry creating the nodes of each pending backorder, oldest first.
+///
+/// Nodes are created as the user that placed the backorder, so they are
+/// checked against the same permissions and quotas as the original request.
+/// A backorder whose regions are still full stays pending until it expires,
+/// and its user is emailed once it is either fulfilled or expired.
+pub async fn fulfil_backorders(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for backorder in Backorder::pending(&mut conn).await? {
+        let id = backorder.id;
+        if backorder.is_expired() {
+            match expire_backorder(context, backorder, &mut conn).await {
+                Ok(()) => info!("Expired backorder {id}"),
+                Err(err) => warn!("Failed to expire backorder {id}: {err}"),
+            }
+            continue;
+        }
+
+        match fulfil_backorder(context, &backorder, &mut conn).await {
+            Ok(0) => (),
+            Ok(created) => info!("Fulfilled backorder {id} with {created} nodes"),
+            Err(err) => {
+                warn!("Failed to fulfil backorder {id}: {err}");
+                backorder.set_error(&err.to_string(), &mut conn).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn fulfil_backorder(
+    context: &Context,
+    backorder: &Backorder,
+    conn: &mut Conn<'_>,
+) -> Result<usize, Error> {
+    let claims = Claims::from_now(BACKORDER_TOKEN_EXPIRY, backorder.user_id, GrpcRole::Login);
+    let jwt = context.auth.cipher.jwt.encode(&claims)?;
+    let meta = Metadata::from(&jwt);
+
+    let response: tonic::Response<api::NodeServiceCreateResponse> = context
+        .write(|write| {
+            crate::grpc::node::retry_backorder(backorder.clone(), meta.clone(), write).scope_boxed()
+        })
+        .await
+        .map_err(Error::FulfilBackorder)?;
+    let created = response.get_ref().nodes.len();
+    if created == 0 {
+        return Ok(0);
+    }
+
+    if let Some(email) = context.email.as_ref() {
+        let org = Org::by_id(backorder.org_id, conn).await?;
+        let user = User::by_id(backorder.user_id, conn).await?;
+        let locale = UserLocale::by_user(user.id, conn).await?;
+        if let Err(err) = email
+            .backorder_fulfilled(&org, created, &user, &locale)
+            .await
+        {
+            warn!(
+                "Failed to email fulfilled backorder {}: {err}",
+                backorder.id
+            );
+        }
+    }
+
+    Ok(created)
+}
+
+async fn expire_backorder(
+    context: &Context,
+    backorder: Backorder,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let backorder = backorder.expire(conn).await?;
+    let Some(email) = context.email.as_ref() else {
+        return Ok(());
+    };
+
+    let org = Org::by_id(backorder.org_id, conn).await?;
+    let user = User::by_id(backorder.user_id, conn).await?;
+    let locale = UserLocale::by_user(user.id, conn).await?;
+    email
+        .backorder_expired(&backorder, &org, &user, &locale)
+        .await
+        .map_err(Into::into)
+}
+
+/// Bring the DNS records of each endpoint pool in line with member health.
+///
+/// Each pool is rotated independently, so that a failed DNS call only delays
+/// the pool it was for until the next run.
+pub async fn rotate_endpoint_pools(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let dns = context.dns.as_ref().as_ref();
+    for pool in EndpointPool::all(&mut conn).await? {
+        match pool.rotate(dns, &mut conn).await {
+            Ok(rotation) if rotation.is_empty() => (),
+            Ok(rotation) => info!(
+                "Rotated endpoint pool {}: added {:?}, removed {:?}",
+                pool.name, rotation.added, rotation.removed
+            ),
+            Err(err) => warn!("Failed to rotate endpoint pool {}: {err}", pool.name),
+        }
+    }
+
+    Ok(())
+}
+
+/// Scale each endpoint pool with an autoscaler by the usage of its nodes.
+///
+/// A pool changes by at most one node per run. Failed changes are kept as
+/// events and start the cooldown like successful ones, so that a failing pool
+/// isn't retried on every run.
+pub async fn autoscale_endpoint_pools(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    for autoscaler in PoolAutoscaler::all(&mut conn).await? {
+        let pool_id = autoscaler.pool_id;
+        if let Err(err) = autoscale_pool(context, &autoscaler, &mut conn).await {
+            warn!("Failed to autoscale endpoint pool {pool_id}: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn autoscale_pool(
+    context: &Context,
+    autoscaler: &PoolAutoscaler,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let members = PoolMember::by_pool_id(autoscaler.pool_id, conn).await?;
+    let member_ids = members.iter().map(|member| member.node_id).collect();
+    let nodes = Node::by_ids(&member_ids, conn).await?;
+    let running: Vec<_> = nodes
+        .iter()
+        .filter(|node| node.node_state == NodeState::Running)
+        .collect();
+
+    let usage = autoscaler.usage(&running, now, conn).await?;
+    let Some(decision) = autoscaler.decide(nodes.len(), usage, now) else {
+        return Ok(());
+    };
+
+    let (node_id, result) = match decision.action {
+        AutoscaleAction::ScaleUp => match scale_up(context, autoscaler, conn).await {
+            Ok(node_id) => (Some(node_id), Ok(())),
+            Err(err) => (None, Err(err)),
+        },
+        AutoscaleAction::ScaleDown => {
+            // only nodes created by the autoscaler are deleted, newest first
+            let newest = members
+                .iter()
+                .filter(|member| member.autoscaled)
+                .filter(|member| nodes.iter().any(|node| node.id == member.node_id))
+                .max_by_key(|member| member.created_at);
+            let Some(member) = newest else {
+                return Ok(());
+            };
+            let result = scale_down(context, autoscaler, member.node_id, conn).await;
+            (Some(member.node_id), result)
+        }
+    };
+
+    let event = NewAutoscaleEvent {
+        pool_id: autoscaler.pool_id,
+        action: decision.action,
+        node_id,
+        metric_value: usage,
+        reason: decision.reason,
+        error: result.as_ref().err().map(ToString::to_string),
+    }
+    .create(conn)
+    .await?;
+    autoscaler.scaled(conn).await?;
+
+    match (&event.error, event.node_id) {
+        (None, Some(node_id)) => info!(
+            "Autoscaled endpoint pool {} with {:?} of node {node_id}: {}",
+            event.pool_id, event.action, event.reason
+        ),
+        _ => warn!(
+            "Failed to autoscale endpoint pool {} with {:?}: {}",
+            event.pool_id,
+            event.action,
+            event.error.as_deref().unwrap_or_default()
+        ),
+    }
+
+    Ok(())
+}
+
+/// Create a node through the usual node request and add it to the pool.
+async fn scale_up(
+    context: &Context,
+    autoscaler: &PoolAutoscaler,
+    conn: &mut Conn<'_>,
+) -> Result<NodeId, Error> {
+    let pool = EndpointPool::by_id(autoscaler.pool_id, conn).await?;
+    let meta = autoscale_meta(context, autoscaler.user_id)?;
+    let req = api::NodeServiceCreateRequest {
+        org_id: pool.org_id.to_string(),
+        image_id: autoscaler.image_id.to_string(),
+        old_node_id: None,
+        launcher: Some(common::NodeLauncher {
+            launch: Some(common::node_launcher::Launch::ByRegion(common::ByRegion {
+                region_counts: vec![common::RegionCount {
+                    region_id: autoscaler.region_id.to_string(),
+                    node_count: 1,
+                    resource: None,
+                    similarity: None,
+                }],
+            })),
+        }),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+
+    let response: tonic::Response<api::NodeServiceCreateResponse> = context
+        .write(|write| crate::grpc::node::create(req.clone(), meta.clone(), write).scope_boxed())
+        .await
+        .map_err(Error::AutoscaleNode)?;
+    let node = response
+        .into_inner()
+        .nodes
+        .pop()
+        .ok_or(Error::AutoscaleNoNode)?;
+    let node_id: NodeId = node.node_id.parse().map_err(Error::AutoscaleNodeId)?;
+
+    PoolMember::add_autoscaled(pool.id, node_id, conn).await?;
+    Ok(node_id)
+}
+
+/// Delete a node through the usual node request, taking it out of rotation
+/// without waiting for its cleanup.
+async fn scale_down(
+    context: &Context,
+    autoscaler: &PoolAutoscaler,
+    node_id: NodeId,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let meta = autoscale_meta(context, autoscaler.user_id)?;
+    let req = api::NodeServiceDeleteRequest {
+        node_id: node_id.to_string(),
+    };
+
+    let response: tonic::Response<api::NodeServiceDeleteResponse> = context
+        .write(|write| crate::grpc::node::delete(req.clone(), meta.clone(), write).scope_boxed())
+        .await
+        .map_err(Error::AutoscaleNode)?;
+    if response.get_ref().approval.is_some() {
+        return Err(Error::AutoscaleApproval(node_id));
+    }
+
+    let removed = PoolMember::remove(autoscaler.pool_id, &[node_id], conn).await?;
+    for dns_id in removed.iter().filter_map(|member| member.dns_id.as_ref()) {
+        match context.dns.delete(dns_id).await {
+            Ok(()) => (),
+            Err(err) if err.is_not_found() => (),
+            Err(err) => warn!("Failed to delete DNS record {dns_id}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// The metadata of a short-lived token for the user of an autoscaler.
+fn autoscale_meta(context: &Context, user_id: UserId) -> Result<Metadata, Error> {
+    let claims = Claims::from_now(AUTOSCALE_TOKEN_EXPIRY, user_id, GrpcRole::Login);
+    let jwt = context.auth.cipher.jwt.encode(&claims)?;
+    Ok(Metadata::from(&jwt))
+}
+
+/// Report the DNS records and stripe items that no live node refers to.
+///
+/// Orphans are only deleted when `reconcile.delete_orphans` is enabled, so
+/// that new environments can check the report before trusting it.
+pub async fn reconcile_orphans(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    let delete = context.config.reconcile.delete_orphans;
+
+    let dns = context.dns.as_ref().as_ref();
+    let records = orphan::dns_records(dns, &mut conn).await?;
+    for record in &records {
+        warn!("Orphaned DNS record {} for {}", record.id, record.name);
+    }
+    if delete && !records.is_empty() {
+        let deleted = orphan::delete_dns_records(dns, &records).await;
+        info!(
+            "Deleted {deleted} of {} orphaned DNS records",
+            records.len()
+        );
+    }
+
+    let Some(stripe) = context.stripe.as_ref() else {
+        return Ok(());
+    };
+    let items = orphan::stripe_items(stripe.as_ref().as_ref(), &context.keyring, &mut conn).await?;
+    for orphan in &items {
+        warn!(
+            "Orphaned stripe item {} for org {}",
+            orphan.item.id, orphan.org_id
+        );
+    }
+    if delete && !items.is_empty() {
+        let deleted = orphan::delete_stripe_items(stripe.as_ref().as_ref(), &items).await;
+        info!("Deleted {deleted} of {} orphaned stripe items", items.len());
+    }
+
+    Ok(())
+}
+
+/// Email each org owner the uptime of their nodes over the previous month.
+///
+/// Each org is only sent its report once, so that restarts don't send
+/// duplicates, and a failed org is retried on the next run.
+pub async fn send_uptime_reports(context: &Context) -> Result<(), Error> {
+    let Some(email) = context.email.as_ref() else {
+        return Ok(());
+    };
+    let mut conn = context.conn().await?;
+
+    let month = slo::previous_month(Utc::now().date_naive());
+    for org_id in OrgSloReport::pending(month, &mut conn).await? {
+        match send_uptime_report(context, email, org_id, month, &mut conn).await {
+            Ok(()) => info!("Sent {month} uptime report to org {org_id}"),
+            Err(err) => warn!("Failed to send uptime report to org {org_id}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn send_uptime_report(
+    context: &Context,
+    email: &Email,
+    org_id: OrgId,
+    month: NaiveDate,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let org = Org::by_id(org_id, conn).await?;
+    let owner = User::owner(org_id, conn).await?;
+    let locale = UserLocale::by_user(owner.id, conn).await?;
+    let branding = OrgBranding::active(org_id, conn).await?;
+    let slos = NodeSlo::compute(org_id, month, conn).await?;
+    let target_percent = context.config.slo.target_percent;
+
+    email
+        .uptime_report(
+            &owner,
+            &locale,
+            &org,
+            branding.as_ref(),
+            month,
+            &slos,
+            target_percent,
+        )
+        .await?;
+    OrgSloReport::mark_sent(org_id, month, conn)
+        .await
+        .map_err(Into::into)
+}
+
+/// Open tickets for new incidents and push incident status changes.
+///
+/// Each ticket is synced independently so that one org's broken credentials
+/// don't block the others, and a failed ticket is retried on the next run.
+pub async fn sync_tickets(context: &Context, client: &ticket::Client) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+
+    for (incident, integration) in Ticket::pending(&mut conn).await? {
+        let incident_id = incident.id;
+        match open_ticket(client, &incident, &integration, &mut conn).await {
+            Ok(ticket) => info!("Opened ticket {} for incident {incident_id}", ticket.url),
+            Err(err) => warn!("Failed to open ticket for incident {incident_id}: {err}"),
+        }
+    }
+
+    for (ticket, incident, integration) in Ticket::out_of_sync(&mut conn).await? {
+        let external_id = &ticket.external_id;
+        match push_ticket_status(client, &ticket, &incident, &integration, &mut conn).await {
+            Ok(()) => info!("Synced ticket {external_id} to {:?}", incident.status),
+            Err(err) => warn!("Failed to sync ticket {external_id}: {err}"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn open_ticket(
+    client: &ticket::Client,
+    incident: &Incident,
+    integration: &TicketIntegration,
+    conn: &mut Conn<'_>,
+) -> Result<Ticket, Error> {
+    let external = client.create(integration, incident).await?;
+    let new_ticket = NewTicket {
+        incident_id: incident.id,
+        integration_id: integration.id,
+        external_id: external.external_id,
+        url: external.url,
+        status: incident.status,
+    };
+
+    new_ticket.create(conn).await.map_err(Into::into)
+}
+
+async fn push_ticket_status(
+    client: &ticket::Client,
+    ticket: &Ticket,
+    incident: &Incident,
+    integration: &TicketIntegration,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    client
+        .update_status(integration, &ticket.external_id, incident.status)
+        .await?;
+    ticket
+        .set_status(incident.status, conn)
+        .await
+        .map(|_| ())
+        .map_err(Into::into)
+}
+
+/// Run the remaining batches of a started backfill in the background.
+pub fn spawn_backfill(context: &Context, task: Task, batch_size: i64) {
+    let ctx = context.clone();
+    tokio::spawn(async move {
+        let name = task.name();
+        match backfill(&ctx, task, batch_size).await {
+            Ok(backfill) => info!("Backfill {name} processed {} rows", backfill.processed),
+            Err(err) => warn!("Failed to run backfill {name}: {err}"),
+        }
+    });
+}
+
+pub async fn backfill(context: &Context, task: Task, batch_size: i64) -> Result<Backfill, Error> {
+    let mut conn = context.conn().await?;
+    Backfill::run(task, batch_size, &context.keyring, &mut conn)
+        .await
+        .map_err(Into::into)
+}
+
+/// Assemble each pending user data export then email a download link.
+///
+/// A failed export is recorded against its request and retried later.
+pub async fn run_user_exports(context: &Context) -> Result<(), Error> {
+    let mut conn = context.conn().await?;
+    while let Some(request) = UserExportRequest::claim_next(&mut conn).await? {
+        let user_id = request.user_id;
+        match user_export(context, user_id).await {
+            Ok(()) => {
+                request.done(&mut conn).await?;
+                info!("Exported data for user {user_id}");
+            }
+            Err(err) => {
+                warn!("Failed to export data for user {user_id}: {err}");
+                request.failed(&err.to_string(), &mut conn).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn user_export(context: &Context, user_id: UserId) -> Result<(), Error> {
+    let email = context.email.as_ref().ok_or(Error::NoEmail)?;
+    let mut conn = context.conn().await?;
+
+    let user = User::by_id(user_id, &mut conn).await?;
+    let locale = UserLocale::by_user(user_id, &mut conn).await?;
+    let data = UserExport::collect(&user, &mut conn).await?.to_json()?;
+    let link = context.store.save_user_export(user_id, data).await?;
+
+    email
+        .user_export(&user, &locale, &link)
+        .await
+        .map_err(Into::into)
+}
@@ -33,16 +33,24 @@
 #[macro_use]
 extern crate maplit;
 
+pub mod acme;
 pub mod auth;
+#[cfg(any(test, feature = "integration-test"))]
+pub mod chaos;
+pub mod cloud_providers;
 pub mod cloudflare;
 pub mod config;
 pub mod database;
 pub mod email;
+pub mod encryption;
 pub mod grpc;
 pub mod http;
+pub mod job;
 pub mod model;
 pub mod mqtt;
+pub mod push;
 pub mod server;
 pub mod store;
 pub mod stripe;
+pub mod ticket;
 pub mod util;
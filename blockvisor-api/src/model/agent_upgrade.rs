@@ -0,0 +1,595 @@
+//! Staged rollouts of a blockvisord version to a set of hosts.
+//!
+//! An `AgentUpgrade` splits its hosts into waves of `wave_size`. The first wave
+//! is sent when the upgrade is created, and each later wave is sent by a
+//! periodic job once every host of the current wave has finished and
+//! `wave_interval_seconds` have passed since it was sent.
+//!
+//! Each host tracks its `HostUpgrade` command through ack to success or
+//! failure, where a host that doesn't finish within `HOST_TIMEOUT` has failed.
+//! An upgrade halts once more than `max_failures` of its hosts have failed,
+//! leaving the remaining waves unsent.
+//!
+//! The private hosts of an org with a `Manual` agent upgrade policy are only
+//! upgraded when targeted by id.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{HostId, OrgId, Resource, ResourceId, ResourceType};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::command::{Command, CommandId, CommandType, NewCommand};
+use super::schema::{agent_upgrade_hosts, agent_upgrades, orgs, sql_types};
+use super::sql::Version;
+
+/// How long a host has to finish its upgrade before it has failed.
+const HOST_TIMEOUT: TimeDelta = TimeDelta::minutes(30);
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find agent upgrade `{0}`: {1}
+    ById(AgentUpgradeId, diesel::result::Error),
+    /// Agent upgrade command error: {0}
+    Command(#[from] super::command::Error),
+    /// Failed to create agent upgrade: {0}
+    Create(diesel::result::Error),
+    /// Agent upgrade database error: {0}
+    Diesel(#[from] diesel::result::Error),
+    /// Failed to halt agent upgrade `{0}`: {1}
+    Halt(AgentUpgradeId, diesel::result::Error),
+    /// Failed to find hosts of agent upgrade `{0}`: {1}
+    Hosts(AgentUpgradeId, diesel::result::Error),
+    /// Failed to find hosts with an upgrade in progress: {0}
+    InProgress(diesel::result::Error),
+    /// Failed to find orgs with a manual agent upgrade policy: {0}
+    ManualOrgIds(diesel::result::Error),
+    /// An agent upgrade must have at least one host.
+    NoHosts,
+    /// Failed to find running agent upgrades: {0}
+    Running(diesel::result::Error),
+    /// Unknown AgentUpgradePolicy.
+    UnknownPolicy,
+    /// Failed to update agent upgrade host: {0}
+    UpdateHost(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Halt(_, NotFound) => Status::not_found("Agent upgrade not found."),
+            NoHosts => Status::failed_precondition("No hosts to upgrade."),
+            UnknownPolicy => Status::invalid_argument("agent_upgrade_policy"),
+            Command(err) => err.into(),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct AgentUpgradeId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAgentUpgradeStatus"]
+pub enum AgentUpgradeStatus {
+    Running,
+    Halted,
+    Completed,
+}
+
+impl From<AgentUpgradeStatus> for api::AgentUpgradeStatus {
+    fn from(status: AgentUpgradeStatus) -> Self {
+        match status {
+            AgentUpgradeStatus::Running => api::AgentUpgradeStatus::Running,
+            AgentUpgradeStatus::Halted => api::AgentUpgradeStatus::Halted,
+            AgentUpgradeStatus::Completed => api::AgentUpgradeStatus::Completed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAgentUpgradeHostStatus"]
+pub enum AgentUpgradeHostStatus {
+    Pending,
+    Sent,
+    Acked,
+    Succeeded,
+    Failed,
+}
+
+impl AgentUpgradeHostStatus {
+    const fn is_in_flight(self) -> bool {
+        matches!(
+            self,
+            AgentUpgradeHostStatus::Sent | AgentUpgradeHostStatus::Acked
+        )
+    }
+}
+
+impl From<AgentUpgradeHostStatus> for api::AgentUpgradeHostStatus {
+    fn from(status: AgentUpgradeHostStatus) -> Self {
+        match status {
+            AgentUpgradeHostStatus::Pending => api::AgentUpgradeHostStatus::Pending,
+            AgentUpgradeHostStatus::Sent => api::AgentUpgradeHostStatus::Sent,
+            AgentUpgradeHostStatus::Acked => api::AgentUpgradeHostStatus::Acked,
+            AgentUpgradeHostStatus::Succeeded => api::AgentUpgradeHostStatus::Succeeded,
+            AgentUpgradeHostStatus::Failed => api::AgentUpgradeHostStatus::Failed,
+        }
+    }
+}
+
+/// Whether the private hosts of an org are included in fleet agent upgrades.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
+#[ExistingTypePath = "sql_types::EnumAgentUpgradePolicy"]
+#[serde(rename_all = "snake_case")]
+pub enum AgentUpgradePolicy {
+    #[default]
+    Automatic,
+    Manual,
+}
+
+impl AgentUpgradePolicy {
+    /// The orgs whose hosts are only upgraded when targeted by id.
+    pub async fn manual_org_ids(conn: &mut Conn<'_>) -> Result<HashSet<OrgId>, Error> {
+        orgs::table
+            .filter(orgs::agent_upgrade_policy.eq(AgentUpgradePolicy::Manual))
+            .select(orgs::id)
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<OrgId>| ids.into_iter().collect())
+            .map_err(Error::ManualOrgIds)
+    }
+}
+
+impl From<AgentUpgradePolicy> for api::AgentUpgradePolicy {
+    fn from(policy: AgentUpgradePolicy) -> Self {
+        match policy {
+            AgentUpgradePolicy::Automatic => api::AgentUpgradePolicy::Automatic,
+            AgentUpgradePolicy::Manual => api::AgentUpgradePolicy::Manual,
+        }
+    }
+}
+
+impl TryFrom<api::AgentUpgradePolicy> for AgentUpgradePolicy {
+    type Error = Error;
+
+    fn try_from(policy: api::AgentUpgradePolicy) -> Result<Self, Self::Error> {
+        match policy {
+            api::AgentUpgradePolicy::Unspecified => Err(Error::UnknownPolicy),
+            api::AgentUpgradePolicy::Automatic => Ok(AgentUpgradePolicy::Automatic),
+            api::AgentUpgradePolicy::Manual => Ok(AgentUpgradePolicy::Manual),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct AgentUpgrade {
+    pub id: AgentUpgradeId,
+    pub version: Version,
+    pub wave_size: i32,
+    pub wave_interval_seconds: i32,
+    pub max_failures: i32,
+    pub status: AgentUpgradeStatus,
+    pub current_wave: i32,
+    pub halted_reason: Option<String>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub wave_sent_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl AgentUpgrade {
+    pub async fn by_id(id: AgentUpgradeId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        agent_upgrades::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn running(conn: &mut Conn<'_>) -> Result<Vec<AgentUpgradeId>, Error> {
+        agent_upgrades::table
+            .filter(agent_upgrades::status.eq(AgentUpgradeStatus::Running))
+            .order_by(agent_upgrades::created_at.asc())
+            .select(agent_upgrades::id)
+            .get_results(conn)
+            .await
+            .map_err(Error::Running)
+    }
+
+    pub async fn hosts(&self, conn: &mut Conn<'_>) -> Result<Vec<AgentUpgradeHost>, Error> {
+        agent_upgrade_hosts::table
+            .filter(agent_upgrade_hosts::upgrade_id.eq(self.id))
+            .order_by((agent_upgrade_hosts::wave, agent_upgrade_hosts::host_id))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Hosts(self.id, err))
+    }
+
+    /// Stop sending new waves of a running upgrade.
+    pub async fn halt(
+        id: AgentUpgradeId,
+        reason: &str,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let running = agent_upgrades::table
+            .find(id)
+            .filter(agent_upgrades::status.eq(AgentUpgradeStatus::Running));
+
+        let halted = diesel::update(running)
+            .set((
+                agent_upgrades::status.eq(AgentUpgradeStatus::Halted),
+                agent_upgrades::halted_reason.eq(reason),
+                agent_upgrades::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::Halt(id, err))?;
+
+        match halted {
+            Some(upgrade) => Ok(upgrade),
+            None => Self::by_id(id, conn).await,
+        }
+    }
+
+    /// Time out the stuck hosts of a running upgrade, halt it when too many
+    /// hosts have failed, or otherwise send its next wave once the current
+    /// one has finished.
+    ///
+    /// The upgrade is locked so that only one api instance advances it, and
+    /// the returned commands must be sent once the transaction commits.
+    pub async fn advance(id: AgentUpgradeId, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        conn.transaction(|conn| {
+            async move {
+                let upgrade: Self = agent_upgrades::table
+                    .find(id)
+                    .for_update()
+                    .get_result(conn)
+                    .await?;
+                if upgrade.status != AgentUpgradeStatus::Running {
+                    return Ok(vec![]);
+                }
+
+                let timed_out = agent_upgrade_hosts::table
+                    .filter(agent_upgrade_hosts::upgrade_id.eq(id))
+                    .filter(
+                        agent_upgrade_hosts::status
+                            .eq_any([AgentUpgradeHostStatus::Sent, AgentUpgradeHostStatus::Acked]),
+                    )
+                    .filter(agent_upgrade_hosts::sent_at.lt(Utc::now() - HOST_TIMEOUT));
+                diesel::update(timed_out)
+                    .set((
+                        agent_upgrade_hosts::status.eq(AgentUpgradeHostStatus::Failed),
+                        agent_upgrade_hosts::error.eq("Timed out."),
+                        agent_upgrade_hosts::completed_at.eq(Utc::now()),
+                    ))
+                    .execute(conn)
+                    .await?;
+
+                let hosts: Vec<AgentUpgradeHost> = agent_upgrade_hosts::table
+                    .filter(agent_upgrade_hosts::upgrade_id.eq(id))
+                    .get_results(conn)
+                    .await?;
+                if upgrade.halt_on_failures(&hosts, conn).await? {
+                    return Ok(vec![]);
+                }
+
+                let in_flight = hosts
+                    .iter()
+                    .any(|host| host.wave == upgrade.current_wave && host.status.is_in_flight());
+                let next_at = upgrade.wave_sent_at.unwrap_or(upgrade.created_at)
+                    + TimeDelta::seconds(upgrade.wave_interval_seconds.into());
+                if in_flight || next_at > Utc::now() {
+                    return Ok(vec![]);
+                }
+
+                let next_wave = hosts
+                    .iter()
+                    .filter(|host| host.status == AgentUpgradeHostStatus::Pending)
+                    .map(|host| host.wave)
+                    .filter(|wave| *wave > upgrade.current_wave)
+                    .min();
+                let Some(next_wave) = next_wave else {
+                    diesel::update(agent_upgrades::table.find(id))
+                        .set((
+                            agent_upgrades::status.eq(AgentUpgradeStatus::Completed),
+                            agent_upgrades::updated_at.eq(Utc::now()),
+                            agent_upgrades::completed_at.eq(Utc::now()),
+                        ))
+                        .execute(conn)
+                        .await?;
+                    return Ok(vec![]);
+                };
+
+                upgrade.send_wave(next_wave, conn).await
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Halt the upgrade if more than `max_failures` hosts have failed.
+    async fn halt_on_failures(
+        &self,
+        hosts: &[AgentUpgradeHost],
+        conn: &mut Conn<'_>,
+    ) -> Result<bool, Error> {
+        let failed = hosts
+            .iter()
+            .filter(|host| host.status == AgentUpgradeHostStatus::Failed)
+            .count();
+        if failed <= usize::try_from(self.max_failures).unwrap_or_default() {
+            return Ok(false);
+        }
+
+        diesel::update(agent_upgrades::table.find(self.id))
+            .set((
+                agent_upgrades::status.eq(AgentUpgradeStatus::Halted),
+                agent_upgrades::halted_reason.eq(format!("{failed} hosts failed to upgrade.")),
+                agent_upgrades::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Halt(self.id, err))?;
+
+        Ok(true)
+    }
+
+    /// Create an upgrade command for each pending host of a wave.
+    async fn send_wave(&self, wave: i32, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        let host_ids: Vec<HostId> = agent_upgrade_hosts::table
+            .filter(agent_upgrade_hosts::upgrade_id.eq(self.id))
+            .filter(agent_upgrade_hosts::wave.eq(wave))
+            .filter(agent_upgrade_hosts::status.eq(AgentUpgradeHostStatus::Pending))
+            .select(agent_upgrade_hosts::host_id)
+            .get_results(conn)
+            .await?;
+
+        let upgrade = api::HostUpgrade {
+            version: self.version.to_string(),
+        };
+        let mut commands = Vec::with_capacity(host_ids.len());
+        for host_id in host_ids {
+            let command = NewCommand::host(host_id, CommandType::HostUpgrade)?
+                .with_protobuf(&upgrade)
+                .create(conn)
+                .await?;
+
+            let row = agent_upgrade_hosts::table.find((self.id, host_id));
+            diesel::update(row)
+                .set((
+                    agent_upgrade_hosts::status.eq(AgentUpgradeHostStatus::Sent),
+                    agent_upgrade_hosts::command_id.eq(command.id),
+                    agent_upgrade_hosts::sent_at.eq(Utc::now()),
+                ))
+                .execute(conn)
+                .await?;
+            commands.push(command);
+        }
+
+        diesel::update(agent_upgrades::table.find(self.id))
+            .set((
+                agent_upgrades::current_wave.eq(wave),
+                agent_upgrades::wave_sent_at.eq(Utc::now()),
+                agent_upgrades::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await?;
+
+        Ok(commands)
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<AgentUpgrade> for api::AgentUpgrade {
+    fn from(upgrade: AgentUpgrade) -> Self {
+        let created_by = common::Resource::from(upgrade.created_by());
+        api::AgentUpgrade {
+            agent_upgrade_id: upgrade.id.to_string(),
+            version: upgrade.version.to_string(),
+            status: api::AgentUpgradeStatus::from(upgrade.status).into(),
+            wave_size: u32::try_from(upgrade.wave_size).unwrap_or_default(),
+            wave_interval_seconds: u32::try_from(upgrade.wave_interval_seconds).unwrap_or_default(),
+            max_failures: u32::try_from(upgrade.max_failures).unwrap_or_default(),
+            current_wave: u32::try_from(upgrade.current_wave).unwrap_or_default(),
+            halted_reason: upgrade.halted_reason,
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(upgrade.created_at).into()),
+            updated_at: Some(NanosUtc::from(upgrade.updated_at).into()),
+            completed_at: upgrade.completed_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+/// The progress of a single host of an agent upgrade.
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct AgentUpgradeHost {
+    pub upgrade_id: AgentUpgradeId,
+    pub host_id: HostId,
+    pub wave: i32,
+    pub status: AgentUpgradeHostStatus,
+    pub command_id: Option<CommandId>,
+    pub error: Option<String>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub acked_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl AgentUpgradeHost {
+    /// The hosts that are part of a running upgrade and not yet finished.
+    pub async fn in_progress(conn: &mut Conn<'_>) -> Result<HashSet<HostId>, Error> {
+        agent_upgrade_hosts::table
+            .inner_join(agent_upgrades::table)
+            .filter(agent_upgrades::status.eq(AgentUpgradeStatus::Running))
+            .filter(agent_upgrade_hosts::status.eq_any([
+                AgentUpgradeHostStatus::Pending,
+                AgentUpgradeHostStatus::Sent,
+                AgentUpgradeHostStatus::Acked,
+            ]))
+            .select(agent_upgrade_hosts::host_id)
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<HostId>| ids.into_iter().collect())
+            .map_err(Error::InProgress)
+    }
+
+    /// Record that a host has received its upgrade command.
+    pub async fn acked(command_id: CommandId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let sent = agent_upgrade_hosts::table
+            .filter(agent_upgrade_hosts::command_id.eq(command_id))
+            .filter(agent_upgrade_hosts::status.eq(AgentUpgradeHostStatus::Sent));
+
+        diesel::update(sent)
+            .set((
+                agent_upgrade_hosts::status.eq(AgentUpgradeHostStatus::Acked),
+                agent_upgrade_hosts::acked_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(Error::UpdateHost)
+    }
+
+    /// Record that a host has finished its upgrade command.
+    ///
+    /// A failed host halts its upgrade right away once too many have failed,
+    /// rather than waiting for the next wave.
+    pub async fn finished(
+        command_id: CommandId,
+        error: Option<&str>,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let status = if error.is_some() {
+            AgentUpgradeHostStatus::Failed
+        } else {
+            AgentUpgradeHostStatus::Succeeded
+        };
+        let in_flight = agent_upgrade_hosts::table
+            .filter(agent_upgrade_hosts::command_id.eq(command_id))
+            .filter(
+                agent_upgrade_hosts::status
+                    .eq_any([AgentUpgradeHostStatus::Sent, AgentUpgradeHostStatus::Acked]),
+            );
+
+        let updated: Option<Self> = diesel::update(in_flight)
+            .set((
+                agent_upgrade_hosts::status.eq(status),
+                agent_upgrade_hosts::error.eq(error),
+                agent_upgrade_hosts::completed_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(Error::UpdateHost)?;
+
+        let Some(host) = updated else {
+            return Ok(());
+        };
+        if status == AgentUpgradeHostStatus::Failed {
+            let upgrade = AgentUpgrade::by_id(host.upgrade_id, conn).await?;
+            if upgrade.status == AgentUpgradeStatus::Running {
+                let hosts = upgrade.hosts(conn).await?;
+                upgrade.halt_on_failures(&hosts, conn).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<AgentUpgradeHost> for api::AgentUpgradeHost {
+    fn from(host: AgentUpgradeHost) -> Self {
+        api::AgentUpgradeHost {
+            host_id: host.host_id.to_string(),
+            wave: u32::try_from(host.wave).unwrap_or_default(),
+            status: api::AgentUpgradeHostStatus::from(host.status).into(),
+            command_id: host.command_id.map(|id| id.to_string()),
+            error: host.error,
+            sent_at: host.sent_at.map(NanosUtc::from).map(Into::into),
+            acked_at: host.acked_at.map(NanosUtc::from).map(Into::into),
+            completed_at: host.completed_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+pub struct NewAgentUpgrade {
+    pub version: Version,
+    pub wave_size: i32,
+    pub wave_interval_seconds: i32,
+    pub max_failures: i32,
+    pub created_by: Resource,
+}
+
+impl NewAgentUpgrade {
+    /// Create an upgrade of the hosts in order, and send its first wave.
+    ///
+    /// Returns the commands of the first wave, which must be sent once the
+    /// transaction commits.
+    pub async fn create(
+        self,
+        host_ids: &[HostId],
+        conn: &mut Conn<'_>,
+    ) -> Result<(AgentUpgrade, Vec<Command>), Error> {
+        if host_ids.is_empty() {
+            return Err(Error::NoHosts);
+        }
+        let wave_size = usize::try_from(self.wave_size.max(1)).unwrap_or(1);
+
+        let upgrade: AgentUpgrade = diesel::insert_into(agent_upgrades::table)
+            .values((
+                agent_upgrades::version.eq(&self.version),
+                agent_upgrades::wave_size.eq(self.wave_size),
+                agent_upgrades::wave_interval_seconds.eq(self.wave_interval_seconds),
+                agent_upgrades::max_failures.eq(self.max_failures),
+                agent_upgrades::created_by_type.eq(self.created_by.typ()),
+                agent_upgrades::created_by_id.eq(self.created_by.id()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)?;
+
+        let rows: Vec<_> = host_ids
+            .iter()
+            .enumerate()
+            .map(|(index, host_id)| {
+                (
+                    agent_upgrade_hosts::upgrade_id.eq(upgrade.id),
+                    agent_upgrade_hosts::host_id.eq(*host_id),
+                    agent_upgrade_hosts::wave
+                        .eq(i32::try_from(index / wave_size).unwrap_or(i32::MAX)),
+                )
+            })
+            .collect();
+        diesel::insert_into(agent_upgrade_hosts::table)
+            .values(rows)
+            .execute(conn)
+            .await
+            .map_err(Error::Create)?;
+
+        let commands = upgrade.send_wave(0, conn).await?;
+        let upgrade = AgentUpgrade::by_id(upgrade.id, conn).await?;
+
+        Ok((upgrade, commands))
+    }
+}
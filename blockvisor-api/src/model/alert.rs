@@ -0,0 +1,206 @@
+//! Alerts raised for an org that need acknowledging by a person.
+//!
+//! An alert is first delivered over the usual channels (mqtt, webhooks,
+//! email). Until someone acknowledges it, the escalation policy of the org
+//! pages each of its on-call schedules in turn, tracked by `escalation_level`.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::{alerts, sql_types};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to acknowledge alert `{0}`: {1}
+    Acknowledge(AlertId, diesel::result::Error),
+    /// Failed to find alert `{0}`: {1}
+    ById(AlertId, diesel::result::Error),
+    /// Failed to find alerts of org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Failed to create alert: {0}
+    Create(diesel::result::Error),
+    /// Failed to escalate alert `{0}`: {1}
+    Escalate(AlertId, diesel::result::Error),
+    /// Failed to find unacknowledged alerts: {0}
+    Unacknowledged(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) => Status::not_found("Alert not found."),
+            Acknowledge(_, NotFound) => Status::not_found("Alert is already acknowledged."),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct AlertId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAlertKind"]
+pub enum AlertKind {
+    CertificateExpiring,
+    EndpointQuota,
+}
+
+impl From<AlertKind> for api::AlertKind {
+    fn from(kind: AlertKind) -> Self {
+        match kind {
+            AlertKind::CertificateExpiring => api::AlertKind::CertificateExpiring,
+            AlertKind::EndpointQuota => api::AlertKind::EndpointQuota,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Alert {
+    pub id: AlertId,
+    pub org_id: OrgId,
+    pub node_id: Option<NodeId>,
+    pub kind: AlertKind,
+    pub message: String,
+    pub escalation_level: i32,
+    pub notified_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub acknowledged_by: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Alert {
+    pub async fn by_id(id: AlertId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        alerts::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The alerts of an org, most recent first.
+    pub async fn by_org(
+        org_id: OrgId,
+        unacknowledged_only: bool,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = alerts::table.filter(alerts::org_id.eq(org_id)).into_boxed();
+        if unacknowledged_only {
+            query = query.filter(alerts::acknowledged_at.is_null());
+        }
+
+        query
+            .order_by(alerts::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    /// All unacknowledged alerts of the given orgs.
+    pub async fn unacknowledged(
+        org_ids: &HashSet<OrgId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        alerts::table
+            .filter(alerts::org_id.eq_any(org_ids))
+            .filter(alerts::acknowledged_at.is_null())
+            .order_by(alerts::notified_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::Unacknowledged)
+    }
+
+    /// The orgs with unacknowledged alerts.
+    pub async fn unacknowledged_orgs(conn: &mut Conn<'_>) -> Result<HashSet<OrgId>, Error> {
+        alerts::table
+            .filter(alerts::acknowledged_at.is_null())
+            .select(alerts::org_id)
+            .distinct()
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<OrgId>| ids.into_iter().collect())
+            .map_err(Error::Unacknowledged)
+    }
+
+    /// Acknowledge the alert, which stops any further escalation.
+    ///
+    /// Returns `NotFound` when the alert was already acknowledged.
+    pub async fn acknowledge(&self, user_id: UserId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        let pending = alerts::table
+            .find(id)
+            .filter(alerts::acknowledged_at.is_null());
+
+        diesel::update(pending)
+            .set((
+                alerts::acknowledged_at.eq(Utc::now()),
+                alerts::acknowledged_by.eq(user_id),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Acknowledge(id, err))
+    }
+
+    /// Record that the next escalation level has been paged.
+    pub async fn escalated(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        diesel::update(alerts::table.find(self.id))
+            .set((
+                alerts::escalation_level.eq(alerts::escalation_level + 1),
+                alerts::notified_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Escalate(self.id, err))
+    }
+}
+
+impl From<Alert> for api::Alert {
+    fn from(alert: Alert) -> Self {
+        api::Alert {
+            alert_id: alert.id.to_string(),
+            org_id: alert.org_id.to_string(),
+            node_id: alert.node_id.map(|id| id.to_string()),
+            kind: api::AlertKind::from(alert.kind).into(),
+            message: alert.message,
+            escalation_level: u32::try_from(alert.escalation_level).unwrap_or_default(),
+            notified_at: Some(NanosUtc::from(alert.notified_at).into()),
+            acknowledged_at: alert.acknowledged_at.map(NanosUtc::from).map(Into::into),
+            acknowledged_by: alert.acknowledged_by.map(|id| id.to_string()),
+            created_at: Some(NanosUtc::from(alert.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = alerts)]
+pub struct NewAlert {
+    pub org_id: OrgId,
+    pub node_id: Option<NodeId>,
+    pub kind: AlertKind,
+    pub message: String,
+}
+
+impl NewAlert {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Alert, Error> {
+        diesel::insert_into(alerts::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -10,7 +10,7 @@ use crate::auth::resource::{Resource, ResourceId, ResourceType, UserId};
 use crate::auth::token::api_key::{BearerSecret, KeyHash, KeyId, Salt, Secret};
 use crate::database::{Conn, WriteConn};
 use crate::grpc::{Status, api, common};
-use crate::model::sql::Permissions;
+use crate::model::sql::{Encrypted, Permissions};
 use crate::util::NanosUtc;
 
 use super::schema::api_keys;
@@ -21,6 +21,10 @@ pub enum Error {
     CreateNew(diesel::result::Error),
     /// Failed to delete api key: {0}
     DeleteKey(diesel::result::Error),
+    /// Failed to delete api keys for user `{0}`: {1}
+    DeleteByUser(UserId, diesel::result::Error),
+    /// Failed to seal the api key salt: {0}
+    Encryption(crate::encryption::Error),
     /// Failed to find api key by id: {0}
     FindById(diesel::result::Error),
     /// Failed to find api keys by user_id: {0}
@@ -41,9 +45,13 @@ impl From<Error> for Status {
             DeleteKey(NotFound) | FindById(NotFound) | FindByUser(NotFound) | NoKeysDeleted => {
                 Status::not_found("Api key not found.")
             }
-            CreateNew(_) | DeleteKey(_) | FindById(_) | FindByUser(_) | MultipleKeysDeleted(_) => {
-                Status::internal("Internal error.")
-            }
+            CreateNew(_)
+            | DeleteKey(_)
+            | DeleteByUser(..)
+            | Encryption(_)
+            | FindById(_)
+            | FindByUser(_)
+            | MultipleKeysDeleted(_) => Status::internal("Internal error."),
         }
     }
 }
@@ -54,7 +62,8 @@ pub struct ApiKey {
     pub user_id: UserId,
     pub label: String,
     pub key_hash: KeyHash,
-    pub key_salt: Salt,
+    /// Encrypted at rest, see `crate::encryption`.
+    pub key_salt: Encrypted<Salt>,
     pub resource: ResourceType,
     pub resource_id: ResourceId,
     pub permissions: Permissions,
@@ -90,6 +99,14 @@ impl ApiKey {
             })
     }
 
+    /// Delete all api keys belonging to a user.
+    pub async fn delete_by_user(user_id: UserId, conn: &mut Conn<'_>) -> Result<usize, Error> {
+        diesel::delete(api_keys::table.filter(api_keys::user_id.eq(user_id)))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::DeleteByUser(user_id, err))
+    }
+
     pub fn resource(&self) -> Resource {
         Resource::new(self.resource, self.resource_id)
     }
@@ -107,7 +124,7 @@ pub struct NewApiKey {
     user_id: UserId,
     label: String,
     key_hash: KeyHash,
-    key_salt: Salt,
+    key_salt: Encrypted<Salt>,
     resource: ResourceType,
     resource_id: ResourceId,
     permissions: Permissions,
@@ -127,11 +144,12 @@ impl NewApiKey {
         drop(rng);
 
         let key_hash = KeyHash::from(&salt, &secret);
+        let key_salt = Encrypted::seal(&salt, &write.ctx.keyring).map_err(Error::Encryption)?;
         let new_api_key = NewApiKey {
             user_id,
             label,
             key_hash,
-            key_salt: salt,
+            key_salt,
             resource: resource.typ(),
             resource_id: resource.id(),
             permissions,
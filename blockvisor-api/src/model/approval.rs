@@ -0,0 +1,329 @@
+//! Approval by a second member of destructive org operations.
+//!
+//! An org may require that deleting a node, upgrading several nodes at once or
+//! transferring its ownership is approved by another member. The RPC starting
+//! such an operation then records a pending `Approval` holding its encoded
+//! request instead, and the operation only runs once an org admin other than
+//! the requester approves it. A pending approval that nobody reviews expires
+//! after `APPROVAL_EXPIRY`.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use prost::Message;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::rbac::OrgRole;
+use crate::auth::resource::{OrgId, Resource, ResourceId, ResourceType, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::model::Org;
+use crate::model::schema::{approvals, sql_types, team_members, team_roles, teams, user_roles};
+use crate::util::NanosUtc;
+
+/// How long a pending approval waits for a review.
+pub const APPROVAL_EXPIRY: TimeDelta = TimeDelta::days(3);
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find approvers for org `{0}`: {1}
+    Approvers(OrgId, diesel::result::Error),
+    /// Failed to find approval `{0}`: {1}
+    ById(ApprovalId, diesel::result::Error),
+    /// Failed to find approvals for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Failed to create approval: {0}
+    Create(diesel::result::Error),
+    /// Approval `{0}` has expired.
+    Expired(ApprovalId),
+    /// Approval `{0}` is not pending.
+    NotPending(ApprovalId),
+    /// Failed to review approval `{0}`: {1}
+    Review(ApprovalId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Review(_, NotFound) => Status::not_found("Approval not found."),
+            Expired(_) => Status::failed_precondition("Approval has expired."),
+            NotPending(_) => Status::failed_precondition("Approval is not pending."),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ApprovalId(Uuid);
+
+/// An operation that an org may require a second approval for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumApprovalAction"]
+pub enum ApprovalAction {
+    NodeDelete,
+    /// Upgrading the image of more than one node at once.
+    NodeUpgrade,
+    OrgTransfer,
+}
+
+impl ApprovalAction {
+    /// Whether `org` requires this action to be approved.
+    pub const fn is_required(self, org: &Org) -> bool {
+        match self {
+            ApprovalAction::NodeDelete => org.require_approval_node_delete,
+            ApprovalAction::NodeUpgrade => org.require_approval_node_upgrade,
+            ApprovalAction::OrgTransfer => org.require_approval_org_transfer,
+        }
+    }
+
+    /// A description of the action for notifications.
+    pub const fn describe(self) -> &'static str {
+        match self {
+            ApprovalAction::NodeDelete => "delete a node",
+            ApprovalAction::NodeUpgrade => "upgrade several nodes",
+            ApprovalAction::OrgTransfer => "transfer the organization",
+        }
+    }
+}
+
+impl From<ApprovalAction> for api::ApprovalAction {
+    fn from(action: ApprovalAction) -> Self {
+        match action {
+            ApprovalAction::NodeDelete => api::ApprovalAction::NodeDelete,
+            ApprovalAction::NodeUpgrade => api::ApprovalAction::NodeUpgrade,
+            ApprovalAction::OrgTransfer => api::ApprovalAction::OrgTransfer,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumApprovalStatus"]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Cancelled,
+}
+
+impl From<ApprovalStatus> for api::ApprovalStatus {
+    fn from(status: ApprovalStatus) -> Self {
+        match status {
+            ApprovalStatus::Pending => api::ApprovalStatus::Pending,
+            ApprovalStatus::Approved => api::ApprovalStatus::Approved,
+            ApprovalStatus::Rejected => api::ApprovalStatus::Rejected,
+            ApprovalStatus::Cancelled => api::ApprovalStatus::Cancelled,
+        }
+    }
+}
+
+impl ApprovalStatus {
+    pub const fn from_api(status: api::ApprovalStatus) -> Option<Self> {
+        match status {
+            api::ApprovalStatus::Unspecified => None,
+            api::ApprovalStatus::Pending => Some(ApprovalStatus::Pending),
+            api::ApprovalStatus::Approved => Some(ApprovalStatus::Approved),
+            api::ApprovalStatus::Rejected => Some(ApprovalStatus::Rejected),
+            api::ApprovalStatus::Cancelled => Some(ApprovalStatus::Cancelled),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Approval {
+    pub id: ApprovalId,
+    pub org_id: OrgId,
+    pub action: ApprovalAction,
+    /// The protobuf encoded request of the action.
+    pub request: Vec<u8>,
+    pub status: ApprovalStatus,
+    pub requested_by_type: ResourceType,
+    pub requested_by_id: ResourceId,
+    pub reviewed_by_type: Option<ResourceType>,
+    pub reviewed_by_id: Option<ResourceId>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl Approval {
+    pub async fn by_id(id: ApprovalId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        approvals::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(
+        org_id: OrgId,
+        status: Option<ApprovalStatus>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = approvals::table
+            .filter(approvals::org_id.eq(org_id))
+            .order_by(approvals::created_at.desc())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(approvals::status.eq(status));
+        }
+
+        query
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// The users that may approve the actions of `org_id`.
+    ///
+    /// These are its admins, whether granted directly or through a team.
+    pub async fn approver_ids(
+        org_id: OrgId,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<UserId>, Error> {
+        let admin = OrgRole::Admin.to_string();
+        let mut user_ids: HashSet<UserId> = user_roles::table
+            .filter(user_roles::org_id.eq(org_id))
+            .filter(user_roles::role.eq(&admin))
+            .select(user_roles::user_id)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Approvers(org_id, err))?
+            .into_iter()
+            .collect();
+
+        let team_admins: Vec<UserId> = team_members::table
+            .inner_join(teams::table.inner_join(team_roles::table))
+            .filter(teams::org_id.eq(org_id))
+            .filter(team_roles::role.eq(&admin))
+            .select(team_members::user_id)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Approvers(org_id, err))?;
+        user_ids.extend(team_admins);
+
+        Ok(user_ids)
+    }
+
+    pub async fn approve(self, reviewed_by: Resource, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        if self.is_expired() {
+            return Err(Error::Expired(self.id));
+        }
+        self.review(ApprovalStatus::Approved, reviewed_by, conn)
+            .await
+    }
+
+    pub async fn reject(self, reviewed_by: Resource, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        self.review(ApprovalStatus::Rejected, reviewed_by, conn)
+            .await
+    }
+
+    pub async fn cancel(self, cancelled_by: Resource, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        self.review(ApprovalStatus::Cancelled, cancelled_by, conn)
+            .await
+    }
+
+    async fn review(
+        self,
+        status: ApprovalStatus,
+        reviewed_by: Resource,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        if self.status != ApprovalStatus::Pending {
+            return Err(Error::NotPending(self.id));
+        }
+
+        let row = approvals::table
+            .find(self.id)
+            .filter(approvals::status.eq(ApprovalStatus::Pending));
+        diesel::update(row)
+            .set((
+                approvals::status.eq(status),
+                approvals::reviewed_by_type.eq(reviewed_by.typ()),
+                approvals::reviewed_by_id.eq(reviewed_by.id()),
+                approvals::reviewed_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Review(self.id, err))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    pub fn requested_by(&self) -> Resource {
+        Resource::new(self.requested_by_type, self.requested_by_id)
+    }
+
+    pub fn reviewed_by(&self) -> Option<Resource> {
+        self.reviewed_by_type
+            .zip(self.reviewed_by_id)
+            .map(|(typ, id)| Resource::new(typ, id))
+    }
+}
+
+impl From<Approval> for api::Approval {
+    fn from(approval: Approval) -> Self {
+        let requested_by = common::Resource::from(approval.requested_by());
+        api::Approval {
+            approval_id: approval.id.to_string(),
+            org_id: approval.org_id.to_string(),
+            action: api::ApprovalAction::from(approval.action).into(),
+            status: api::ApprovalStatus::from(approval.status).into(),
+            requested_by: Some(requested_by),
+            reviewed_by: approval.reviewed_by().map(common::Resource::from),
+            created_at: Some(NanosUtc::from(approval.created_at).into()),
+            expires_at: Some(NanosUtc::from(approval.expires_at).into()),
+            reviewed_at: approval.reviewed_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = approvals)]
+pub struct NewApproval {
+    pub org_id: OrgId,
+    pub action: ApprovalAction,
+    pub request: Vec<u8>,
+    pub requested_by_type: ResourceType,
+    pub requested_by_id: ResourceId,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewApproval {
+    pub fn new<M>(
+        org_id: OrgId,
+        action: ApprovalAction,
+        request: &M,
+        requested_by: Resource,
+    ) -> Self
+    where
+        M: Message,
+    {
+        NewApproval {
+            org_id,
+            action,
+            request: request.encode_to_vec(),
+            requested_by_type: requested_by.typ(),
+            requested_by_id: requested_by.id(),
+            expires_at: Utc::now() + APPROVAL_EXPIRY,
+        }
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Approval, Error> {
+        diesel::insert_into(approvals::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -0,0 +1,241 @@
+//! Files that hosts upload for a command, such as logs and debug bundles.
+//!
+//! A host asks for a presigned upload url for each artifact of a command, then
+//! references the uploaded artifacts when it reports the command result. Only
+//! referenced artifacts can be downloaded.
+//!
+//! Artifacts expire after `retention.artifacts_days`, when they are purged from
+//! the store along with their rows.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{HostId, NodeId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::command::{Command, CommandId};
+use super::schema::artifacts;
+
+/// The most characters in an artifact name.
+const MAX_NAME_LEN: usize = 128;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find artifact `{0}`: {1}
+    ById(ArtifactId, diesel::result::Error),
+    /// Failed to find artifacts of command `{0}`: {1}
+    ByCommandId(CommandId, diesel::result::Error),
+    /// Failed to find artifacts of node `{0}`: {1}
+    ByNodeId(NodeId, diesel::result::Error),
+    /// Failed to create artifact: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete artifact `{0}`: {1}
+    Delete(ArtifactId, diesel::result::Error),
+    /// Failed to find expired artifacts: {0}
+    Expired(diesel::result::Error),
+    /// Artifact name `{0}` must be 1 to 128 letters, digits, `.`, `_` or `-`.
+    Name(String),
+    /// Artifact `{0}` has not been uploaded.
+    NotUploaded(ArtifactId),
+    /// Failed to mark artifacts as uploaded: {0}
+    Uploaded(diesel::result::Error),
+    /// Artifacts {0:?} do not belong to command `{1}`.
+    WrongCommand(Vec<ArtifactId>, CommandId),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) => Status::not_found("Artifact not found."),
+            Name(_) => Status::invalid_argument("name"),
+            NotUploaded(_) => Status::failed_precondition("Artifact is not available."),
+            WrongCommand(..) => Status::invalid_argument("artifact_ids"),
+            ById(..) | ByCommandId(..) | ByNodeId(..) | Create(_) | Delete(..) | Expired(_)
+            | Uploaded(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ArtifactId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Artifact {
+    pub id: ArtifactId,
+    pub command_id: CommandId,
+    pub host_id: HostId,
+    pub node_id: Option<NodeId>,
+    pub name: String,
+    pub store_key: String,
+    pub created_at: DateTime<Utc>,
+    /// When the host referenced the artifact in its command result.
+    pub uploaded_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Artifact {
+    pub async fn by_id(id: ArtifactId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        artifacts::table
+            .find(id)
+            .filter(artifacts::expires_at.gt(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The uploaded artifacts of a command.
+    pub async fn by_command_id(
+        command_id: CommandId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        artifacts::table
+            .filter(artifacts::command_id.eq(command_id))
+            .filter(artifacts::uploaded_at.is_not_null())
+            .filter(artifacts::expires_at.gt(Utc::now()))
+            .order_by(artifacts::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByCommandId(command_id, err))
+    }
+
+    /// The uploaded artifacts of a node, newest first.
+    pub async fn by_node_id(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        artifacts::table
+            .filter(artifacts::node_id.eq(node_id))
+            .filter(artifacts::uploaded_at.is_not_null())
+            .filter(artifacts::expires_at.gt(Utc::now()))
+            .order_by(artifacts::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByNodeId(node_id, err))
+    }
+
+    /// Mark the artifacts referenced by the result of `command` as uploaded.
+    pub async fn uploaded(
+        ids: &[ArtifactId],
+        command: &Command,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let updated: Vec<ArtifactId> = diesel::update(artifacts::table)
+            .filter(artifacts::id.eq_any(ids))
+            .filter(artifacts::command_id.eq(command.id))
+            .set(artifacts::uploaded_at.eq(Utc::now()))
+            .returning(artifacts::id)
+            .get_results(conn)
+            .await
+            .map_err(Error::Uploaded)?;
+
+        let unknown: Vec<_> = ids
+            .iter()
+            .filter(|id| !updated.contains(id))
+            .copied()
+            .collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::WrongCommand(unknown, command.id))
+        }
+    }
+
+    /// Artifacts past their retention, which may no longer be in the store.
+    pub async fn expired(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        artifacts::table
+            .filter(artifacts::expires_at.le(Utc::now()))
+            .get_results(conn)
+            .await
+            .map_err(Error::Expired)
+    }
+
+    pub async fn delete(id: ArtifactId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(artifacts::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+
+    /// Returns an error unless the artifact was referenced by a command result.
+    pub const fn check_uploaded(&self) -> Result<(), Error> {
+        if self.uploaded_at.is_some() {
+            Ok(())
+        } else {
+            Err(Error::NotUploaded(self.id))
+        }
+    }
+}
+
+impl From<Artifact> for api::Artifact {
+    fn from(artifact: Artifact) -> Self {
+        api::Artifact {
+            artifact_id: artifact.id.to_string(),
+            command_id: artifact.command_id.to_string(),
+            host_id: artifact.host_id.to_string(),
+            node_id: artifact.node_id.map(|id| id.to_string()),
+            name: artifact.name,
+            created_at: Some(NanosUtc::from(artifact.created_at).into()),
+            uploaded_at: artifact.uploaded_at.map(NanosUtc::from).map(Into::into),
+            expires_at: Some(NanosUtc::from(artifact.expires_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = artifacts)]
+pub struct NewArtifact {
+    command_id: CommandId,
+    host_id: HostId,
+    node_id: Option<NodeId>,
+    name: String,
+    store_key: String,
+    expires_at: DateTime<Utc>,
+}
+
+impl NewArtifact {
+    /// A new artifact of `command`, kept for `retention_days`.
+    pub fn new(command: &Command, name: String, retention_days: u32) -> Result<Self, Error> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-');
+        if name.is_empty() || name.len() > MAX_NAME_LEN || !name.chars().all(valid) {
+            return Err(Error::Name(name));
+        }
+
+        let store_key = format!(
+            "{}/{}/{}-{}",
+            command.host_id,
+            command.id,
+            Uuid::new_v4(),
+            name.to_lowercase()
+        );
+        let expires_at = Utc::now() + TimeDelta::days(retention_days.into());
+
+        Ok(NewArtifact {
+            command_id: command.id,
+            host_id: command.host_id,
+            node_id: command.node_id,
+            name,
+            store_key,
+            expires_at,
+        })
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Artifact, Error> {
+        diesel::insert_into(artifacts::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
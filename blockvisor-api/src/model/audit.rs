@@ -0,0 +1,215 @@
+//! An append-only audit log of security-relevant actions.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use serde::Serialize;
+use strum::IntoStaticStr;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{OrgId, Resource, ResourceId, ResourceType};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::schema::{audit_logs, sql_types};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to anonymize audit logs created by `{0:?}`: {1}
+    Anonymize(Resource, diesel::result::Error),
+    /// Failed to create audit log: {0}
+    Create(diesel::result::Error),
+    /// Failed to find audit logs created by `{0:?}`: {1}
+    ByCreator(Resource, diesel::result::Error),
+    /// Failed to find audit logs for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Failed to find audit logs for request `{0}`: {1}
+    ByRequestId(String, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Anonymize(..) | Create(_) | ByCreator(..) | ByOrgId(..) | ByRequestId(..) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Display,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    DieselNewType,
+    Deref,
+    From,
+    FromStr,
+)]
+pub struct AuditLogId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, DbEnum, IntoStaticStr)]
+#[ExistingTypePath = "sql_types::EnumAuditEvent"]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditEvent {
+    OrgOwnershipTransferRequested,
+    OrgOwnershipTransferConfirmed,
+    OrgOwnershipTransferred,
+    UserImpersonated,
+    ImpersonatedRequest,
+    NodeStateOverridden,
+    FeatureFlagSet,
+    NetworkHeadSet,
+    LegalHoldSet,
+    LegalHoldCleared,
+    NodeShareCreated,
+    NodeShareRevoked,
+    RequestPayloadSampled,
+    ApprovalRequested,
+    ApprovalReviewed,
+    FreezeWindowCreated,
+    FreezeWindowDeleted,
+    HostWeightsSet,
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = audit_logs)]
+pub struct AuditLog {
+    pub id: AuditLogId,
+    pub org_id: Option<OrgId>,
+    pub event: AuditEvent,
+    pub event_data: Option<serde_json::Value>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub request_id: Option<String>,
+}
+
+impl AuditLog {
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        audit_logs::table
+            .filter(audit_logs::org_id.eq(org_id))
+            .order_by(audit_logs::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    pub async fn by_request_id(request_id: &str, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        audit_logs::table
+            .filter(audit_logs::request_id.eq(request_id))
+            .order_by(audit_logs::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByRequestId(request_id.to_string(), err))
+    }
+
+    pub async fn by_creator(created_by: Resource, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        audit_logs::table
+            .filter(audit_logs::created_by_type.eq(created_by.typ()))
+            .filter(audit_logs::created_by_id.eq(created_by.id()))
+            .order_by(audit_logs::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByCreator(created_by, err))
+    }
+
+    /// Remove the identity of `created_by` from the audit logs they created.
+    ///
+    /// The events themselves are kept, but are attributed to the nil id and
+    /// their event data is cleared.
+    pub async fn anonymize_creator(created_by: Resource, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let logs = audit_logs::table
+            .filter(audit_logs::created_by_type.eq(created_by.typ()))
+            .filter(audit_logs::created_by_id.eq(created_by.id()));
+
+        diesel::update(logs)
+            .set((
+                audit_logs::created_by_id.eq(ResourceId::from(Uuid::nil())),
+                audit_logs::event_data.eq(None::<serde_json::Value>),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Anonymize(created_by, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<AuditLog> for api::RequestAuditEvent {
+    fn from(log: AuditLog) -> Self {
+        api::RequestAuditEvent {
+            event: <&str>::from(log.event).to_string(),
+            org_id: log.org_id.map(|id| id.to_string()),
+            event_data: log.event_data.map(|data| data.to_string()),
+            created_by: Some(common::Resource::from(log.created_by())),
+            created_at: Some(NanosUtc::from(log.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = audit_logs)]
+pub struct NewAuditLog {
+    pub org_id: Option<OrgId>,
+    pub event: AuditEvent,
+    pub event_data: Option<serde_json::Value>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub request_id: Option<String>,
+}
+
+impl NewAuditLog {
+    pub fn new<R>(org_id: Option<OrgId>, created_by: R, event: AuditEvent) -> Self
+    where
+        R: Into<Resource>,
+    {
+        let created_by = created_by.into();
+        NewAuditLog {
+            org_id,
+            event,
+            event_data: None,
+            created_by_type: created_by.typ(),
+            created_by_id: created_by.id(),
+            request_id: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.event_data = Some(data);
+        self
+    }
+
+    /// Link the audit log to the request that caused it.
+    #[must_use]
+    pub fn with_request_id(mut self, request_id: Option<&str>) -> Self {
+        self.request_id = request_id.map(ToString::to_string);
+        self
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<AuditLog, Error> {
+        diesel::insert_into(audit_logs::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -0,0 +1,430 @@
+//! Online data backfills that rewrite existing rows in small batches.
+//!
+//! A migration that rewrites every row of a large table (e.g. when splitting
+//! `nodes` columns) holds its locks until the whole update commits. Instead,
+//! the migration should only change the schema, and a `Task` then rewrites
+//! the existing rows in batches ordered by id, each in its own transaction.
+//!
+//! The progress of each task is tracked in `backfills` along with its batch,
+//! so a backfill that failed or was interrupted resumes after the last batch
+//! that committed. Tasks must be idempotent, as a completed task may be run
+//! again from the start.
+//!
+//! Backfills are run with the `backfill` subcommand of the server binary, or
+//! with the internal admin api.
+//!
+//! The `encrypt-*` backfills re-seal the encrypted columns of a table with the
+//! current key, so that a rotated key may be removed once they complete.
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error::SerializationError;
+use diesel::sql_types::{BigInt, Nullable, Uuid as SqlUuid};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use displaydoc::Display;
+use strum::{EnumIter, EnumString, IntoStaticStr};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::token::api_key::Salt;
+use crate::database::Conn;
+use crate::encryption::Keyring;
+use crate::grpc::{Status, api};
+use crate::stripe::api::customer::CustomerId;
+use crate::util::NanosUtc;
+
+use super::schema::{api_keys, backfills, orgs, ticket_integrations};
+use super::sql::Encrypted;
+
+/// The default number of rows rewritten per transaction.
+pub const DEFAULT_BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find backfills: {0}
+    All(diesel::result::Error),
+    /// Failed to run a batch of backfill `{0}`: {1}
+    Batch(&'static str, diesel::result::Error),
+    /// Failed to record the failure of backfill `{0}`: {1}
+    Failed(&'static str, diesel::result::Error),
+    /// Failed to start backfill `{0}`: {1}
+    Start(&'static str, diesel::result::Error),
+    /// Unknown backfill task: {0}
+    UnknownTask(String),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            UnknownTask(_) => Status::not_found("Unknown backfill."),
+            All(_) | Batch(..) | Failed(..) | Start(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// A backfill that can be run, identified by its kebab-case name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Task {
+    /// Recount the cached `node_count` and `host_count` of each org.
+    OrgCounts,
+    /// Re-seal the encrypted columns of `orgs` with the current key and set
+    /// their blind indexes.
+    EncryptOrgs,
+    /// Re-seal the encrypted columns of `ticket_integrations` with the current
+    /// key.
+    EncryptTicketIntegrations,
+    /// Re-seal the `key_salt` of each api key with the current key.
+    EncryptApiKeys,
+}
+
+impl Task {
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        name.parse()
+            .map_err(|_| Error::UnknownTask(name.to_string()))
+    }
+
+    /// Rewrite up to `limit` rows with an id after `cursor`, returning the ids
+    /// of the rows in order.
+    async fn batch(
+        self,
+        cursor: Option<Uuid>,
+        limit: i64,
+        keyring: &Keyring,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Uuid>, diesel::result::Error> {
+        match self {
+            Task::OrgCounts => sql_batch(ORG_COUNTS_QUERY, cursor, limit, conn).await,
+            Task::EncryptOrgs => encrypt_orgs(cursor, limit, keyring, conn).await,
+            Task::EncryptTicketIntegrations => {
+                encrypt_ticket_integrations(cursor, limit, keyring, conn).await
+            }
+            Task::EncryptApiKeys => encrypt_api_keys(cursor, limit, keyring, conn).await,
+        }
+    }
+}
+
+const ORG_COUNTS_QUERY: &str = "
+    with batch as (
+        select id from orgs
+        where $1::uuid is null or id > $1
+        order by id
+        limit $2
+    )
+    update orgs set
+        node_count = (
+            select count(*) from nodes
+            where nodes.org_id = orgs.id and nodes.deleted_at is null
+        ),
+        host_count = (
+            select count(*) from hosts
+            where hosts.org_id = orgs.id and hosts.deleted_at is null
+        )
+    where id in (select id from batch)
+    returning id
+";
+
+/// Run a batch `query` that binds the cursor as `$1` and the limit as `$2`.
+async fn sql_batch(
+    query: &str,
+    cursor: Option<Uuid>,
+    limit: i64,
+    conn: &mut Conn<'_>,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut rows: Vec<BatchRow> = diesel::sql_query(query)
+        .bind::<Nullable<SqlUuid>, _>(cursor)
+        .bind::<BigInt, _>(limit)
+        .load(conn)
+        .await?;
+    rows.sort_unstable_by_key(|row| row.id);
+
+    Ok(rows.into_iter().map(|row| row.id).collect())
+}
+
+/// Re-seal the `stripe_customer_id` of each org with the current key, and
+/// set its `stripe_customer_index`.
+async fn encrypt_orgs(
+    cursor: Option<Uuid>,
+    limit: i64,
+    keyring: &Keyring,
+    conn: &mut Conn<'_>,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut query = orgs::table
+        .select((orgs::id, orgs::stripe_customer_id))
+        .order_by(orgs::id)
+        .limit(limit)
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        query = query.filter(orgs::id.gt(cursor));
+    }
+    let rows: Vec<(Uuid, Option<Encrypted<CustomerId>>)> = query.get_results(conn).await?;
+
+    for (id, customer_id) in &rows {
+        if let Some(customer_id) = customer_id {
+            let plaintext = customer_id.open(keyring).map_err(sealing)?;
+            let sealed = Encrypted::seal(&plaintext, keyring).map_err(sealing)?;
+            diesel::update(orgs::table.find(id))
+                .set((
+                    orgs::stripe_customer_id.eq(sealed),
+                    orgs::stripe_customer_index.eq(keyring.blind_index(&plaintext)),
+                ))
+                .execute(conn)
+                .await?;
+        }
+    }
+
+    Ok(rows.into_iter().map(|(id, _)| id).collect())
+}
+
+/// Re-seal the `api_token` and `webhook_salt` of each ticket integration with
+/// the current key.
+async fn encrypt_ticket_integrations(
+    cursor: Option<Uuid>,
+    limit: i64,
+    keyring: &Keyring,
+    conn: &mut Conn<'_>,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut query = ticket_integrations::table
+        .select((
+            ticket_integrations::id,
+            ticket_integrations::api_token,
+            ticket_integrations::webhook_salt,
+        ))
+        .order_by(ticket_integrations::id)
+        .limit(limit)
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        query = query.filter(ticket_integrations::id.gt(cursor));
+    }
+    let rows: Vec<(Uuid, Encrypted<String>, Encrypted<Salt>)> = query.get_results(conn).await?;
+
+    for (id, api_token, webhook_salt) in &rows {
+        diesel::update(ticket_integrations::table.find(id))
+            .set((
+                ticket_integrations::api_token.eq(reseal(api_token, keyring)?),
+                ticket_integrations::webhook_salt.eq(reseal(webhook_salt, keyring)?),
+            ))
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(rows.into_iter().map(|(id, ..)| id).collect())
+}
+
+/// Re-seal the `key_salt` of each api key with the current key.
+async fn encrypt_api_keys(
+    cursor: Option<Uuid>,
+    limit: i64,
+    keyring: &Keyring,
+    conn: &mut Conn<'_>,
+) -> Result<Vec<Uuid>, diesel::result::Error> {
+    let mut query = api_keys::table
+        .select((api_keys::id, api_keys::key_salt))
+        .order_by(api_keys::id)
+        .limit(limit)
+        .into_boxed();
+    if let Some(cursor) = cursor {
+        query = query.filter(api_keys::id.gt(cursor));
+    }
+    let rows: Vec<(Uuid, Encrypted<Salt>)> = query.get_results(conn).await?;
+
+    for (id, key_salt) in &rows {
+        diesel::update(api_keys::table.find(id))
+            .set(api_keys::key_salt.eq(reseal(key_salt, keyring)?))
+            .execute(conn)
+            .await?;
+    }
+
+    Ok(rows.into_iter().map(|(id, _)| id).collect())
+}
+
+/// Open `value` with any key and seal it again with the current key.
+fn reseal<T>(value: &Encrypted<T>, keyring: &Keyring) -> Result<Encrypted<T>, diesel::result::Error>
+where
+    T: From<String> + std::fmt::Display,
+{
+    let plaintext = value.open(keyring).map_err(sealing)?;
+    Encrypted::seal(&plaintext, keyring).map_err(sealing)
+}
+
+/// A keyring error fails a batch the same way as a failed column write.
+fn sealing(err: crate::encryption::Error) -> diesel::result::Error {
+    SerializationError(Box::new(err))
+}
+
+#[derive(QueryableByName)]
+struct BatchRow {
+    #[diesel(sql_type = SqlUuid)]
+    id: Uuid,
+}
+
+/// The progress of a backfill task.
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Backfill {
+    pub name: String,
+    pub cursor: Option<Uuid>,
+    pub processed: i64,
+    pub error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl Backfill {
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        backfills::table
+            .order_by(backfills::started_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(Error::All)
+    }
+
+    pub const fn is_complete(&self) -> bool {
+        self.completed_at.is_some()
+    }
+
+    /// Start a new backfill, resume an incomplete one, or restart a completed
+    /// one from the beginning.
+    pub async fn start(task: Task, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let name = task.name();
+        let existing = backfills::table
+            .find(name)
+            .get_result::<Self>(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::Start(name, err))?;
+
+        match existing {
+            Some(backfill) if !backfill.is_complete() => {
+                diesel::update(backfills::table.find(name))
+                    .set((
+                        backfills::error.eq(None::<String>),
+                        backfills::updated_at.eq(Utc::now()),
+                    ))
+                    .get_result(conn)
+                    .await
+                    .map_err(|err| Error::Start(name, err))
+            }
+            Some(_) => diesel::update(backfills::table.find(name))
+                .set((
+                    backfills::cursor.eq(None::<Uuid>),
+                    backfills::processed.eq(0),
+                    backfills::error.eq(None::<String>),
+                    backfills::started_at.eq(Utc::now()),
+                    backfills::updated_at.eq(Utc::now()),
+                    backfills::completed_at.eq(None::<DateTime<Utc>>),
+                ))
+                .get_result(conn)
+                .await
+                .map_err(|err| Error::Start(name, err)),
+            None => diesel::insert_into(backfills::table)
+                .values(backfills::name.eq(name))
+                .get_result(conn)
+                .await
+                .map_err(|err| Error::Start(name, err)),
+        }
+    }
+
+    /// Run the remaining batches of a started backfill until it completes.
+    ///
+    /// A failed batch is recorded against the backfill and stops the run.
+    pub async fn run(
+        task: Task,
+        batch_size: i64,
+        keyring: &Keyring,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let batch_size = batch_size.max(1);
+        loop {
+            let backfill = match Self::next_batch(task, batch_size, keyring, conn).await {
+                Ok(backfill) => backfill,
+                Err(err) => {
+                    Self::failed(task, &err, conn).await?;
+                    return Err(err);
+                }
+            };
+
+            if backfill.is_complete() {
+                return Ok(backfill);
+            }
+        }
+    }
+
+    /// Run a single batch and record its progress in the same transaction.
+    ///
+    /// The backfill row is locked first so concurrent runs of the same task
+    /// wait for each other rather than rewriting the same rows.
+    async fn next_batch(
+        task: Task,
+        batch_size: i64,
+        keyring: &Keyring,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let name = task.name();
+        conn.transaction(|conn| {
+            async move {
+                let backfill: Self = backfills::table
+                    .find(name)
+                    .for_update()
+                    .get_result(conn)
+                    .await?;
+                if backfill.is_complete() {
+                    return Ok(backfill);
+                }
+
+                let ids = task
+                    .batch(backfill.cursor, batch_size, keyring, conn)
+                    .await?;
+                let count = i64::try_from(ids.len()).unwrap_or(i64::MAX);
+                let completed_at = (count < batch_size).then(Utc::now);
+
+                diesel::update(backfills::table.find(name))
+                    .set((
+                        backfills::cursor.eq(ids.last().copied().or(backfill.cursor)),
+                        backfills::processed.eq(backfills::processed + count),
+                        backfills::updated_at.eq(Utc::now()),
+                        backfills::completed_at.eq(completed_at),
+                    ))
+                    .get_result(conn)
+                    .await
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(|err| Error::Batch(name, err))
+    }
+
+    async fn failed(task: Task, err: &Error, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let name = task.name();
+        diesel::update(backfills::table.find(name))
+            .set((
+                backfills::error.eq(err.to_string()),
+                backfills::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Failed(name, err))
+    }
+}
+
+impl From<Backfill> for api::Backfill {
+    fn from(backfill: Backfill) -> Self {
+        api::Backfill {
+            name: backfill.name,
+            processed: u64::try_from(backfill.processed).unwrap_or_default(),
+            error: backfill.error,
+            started_at: Some(NanosUtc::from(backfill.started_at).into()),
+            updated_at: Some(NanosUtc::from(backfill.updated_at).into()),
+            completed_at: backfill
+                .completed_at
+                .map(|completed_at| NanosUtc::from(completed_at).into()),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Backorders of nodes that no host currently has room for.
+//!
+//! A node create request launching into regions without enough free capacity
+//! may ask to be backordered instead of failing. The encoded request is then
+//! kept as a pending `Backorder`, which a background job retries as capacity
+//! frees up until its nodes are created or it expires after
+//! `BACKORDER_EXPIRY`.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use prost::Message;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{backorders, sql_types};
+use crate::util::NanosUtc;
+
+/// How long a pending backorder waits for capacity.
+pub const BACKORDER_EXPIRY: TimeDelta = TimeDelta::days(7);
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to create backorder: {0}
+    Create(diesel::result::Error),
+    /// Failed to find pending backorders: {0}
+    Pending(diesel::result::Error),
+    /// Failed to resolve backorder `{0}`: {1}
+    Resolve(BackorderId, diesel::result::Error),
+    /// Failed to set the error of backorder `{0}`: {1}
+    SetError(BackorderId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Resolve(_, NotFound) => Status::failed_precondition("Backorder is not pending."),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct BackorderId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumBackorderStatus"]
+pub enum BackorderStatus {
+    Pending,
+    Fulfilled,
+    Expired,
+}
+
+impl From<BackorderStatus> for api::BackorderStatus {
+    fn from(status: BackorderStatus) -> Self {
+        match status {
+            BackorderStatus::Pending => api::BackorderStatus::Pending,
+            BackorderStatus::Fulfilled => api::BackorderStatus::Fulfilled,
+            BackorderStatus::Expired => api::BackorderStatus::Expired,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Backorder {
+    pub id: BackorderId,
+    pub org_id: OrgId,
+    /// The user that the nodes are created for once capacity frees up.
+    pub user_id: UserId,
+    /// The protobuf encoded `NodeServiceCreateRequest`.
+    pub request: Vec<u8>,
+    pub status: BackorderStatus,
+    /// Why the last attempt to fulfil the backorder failed.
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Backorder {
+    /// All pending backorders, oldest first.
+    pub async fn pending(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        backorders::table
+            .filter(backorders::status.eq(BackorderStatus::Pending))
+            .order_by(backorders::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::Pending)
+    }
+
+    pub async fn fulfil(self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        self.resolve(BackorderStatus::Fulfilled, conn).await
+    }
+
+    pub async fn expire(self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        self.resolve(BackorderStatus::Expired, conn).await
+    }
+
+    async fn resolve(self, status: BackorderStatus, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let row = backorders::table
+            .find(self.id)
+            .filter(backorders::status.eq(BackorderStatus::Pending));
+        diesel::update(row)
+            .set((
+                backorders::status.eq(status),
+                backorders::resolved_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Resolve(self.id, err))
+    }
+
+    pub async fn set_error(&self, error: &str, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(backorders::table.find(self.id))
+            .set(backorders::last_error.eq(error))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::SetError(self.id, err))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+impl From<Backorder> for api::Backorder {
+    fn from(backorder: Backorder) -> Self {
+        api::Backorder {
+            backorder_id: backorder.id.to_string(),
+            org_id: backorder.org_id.to_string(),
+            user_id: backorder.user_id.to_string(),
+            status: api::BackorderStatus::from(backorder.status).into(),
+            last_error: backorder.last_error,
+            created_at: Some(NanosUtc::from(backorder.created_at).into()),
+            expires_at: Some(NanosUtc::from(backorder.expires_at).into()),
+            resolved_at: backorder.resolved_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = backorders)]
+pub struct NewBackorder {
+    pub org_id: OrgId,
+    pub user_id: UserId,
+    pub request: Vec<u8>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewBackorder {
+    pub fn new<M>(org_id: OrgId, user_id: UserId, request: &M) -> Self
+    where
+        M: Message,
+    {
+        NewBackorder {
+            org_id,
+            user_id,
+            request: request.encode_to_vec(),
+            expires_at: Utc::now() + BACKORDER_EXPIRY,
+        }
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Backorder, Error> {
+        diesel::insert_into(backorders::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
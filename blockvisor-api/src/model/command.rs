@@ -1,9 +1,13 @@
-use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
 use derive_more::{Deref, Display, From, FromStr};
 use diesel::dsl;
+use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::result::DatabaseErrorKind::UniqueViolation;
 use diesel::result::Error::{DatabaseError, NotFound};
+use diesel::sql_types::{Bool, Nullable};
 use diesel_async::RunQueryDsl;
 use diesel_derive_enum::DbEnum;
 use diesel_derive_newtype::DieselNewType;
@@ -20,32 +24,57 @@ use crate::grpc::api;
 use super::Node;
 use super::schema::{commands, sql_types};
 
+/// How long after a start command completes that its node may report starting.
+const START_WINDOW: TimeDelta = TimeDelta::minutes(5);
+/// The most commands returned by a single list query.
+pub const MAX_LIST_LIMIT: i64 = 1000;
+
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
     /// Failed to ack command: {0}
     Ack(diesel::result::Error),
+    /// Failed to cancel scheduled command `{0}`: {1}
+    Cancel(CommandId, diesel::result::Error),
     /// Failed to create new command: {0}
     Create(diesel::result::Error),
     /// Failed to delete pending host commands: {0}
     DeleteHostPending(diesel::result::Error),
     /// Failed to delete pending node commands: {0}
     DeleteNodePending(diesel::result::Error),
+    /// Failed to cancel the dependents of command `{0}`: {1}
+    DeleteDependents(CommandId, diesel::result::Error),
+    /// Failed to mark command `{0}` as dispatched: {1}
+    Dispatched(CommandId, diesel::result::Error),
+    /// Failed to find due scheduled commands: {0}
+    Due(diesel::result::Error),
+    /// Failed to fail the dependents of command `{0}`: {1}
+    FailDependents(CommandId, diesel::result::Error),
     /// Failed to filter commands: {0}
     Filter(diesel::result::Error),
     /// Failed to find command by id `{0}`: {1}
     FindById(CommandId, diesel::result::Error),
     /// Failed to check for pending host commands: {0}
     HasHostPending(diesel::result::Error),
+    /// Failed to check for node start commands: {0}
+    HasNodeStart(diesel::result::Error),
     /// Command Host error: {0}
     Host(#[from] super::host::Error),
     /// Attempt to create a host command with a node id.
     HostCommandWithNodeId,
     /// Failed to find pending host commands: {0}
     HostPending(diesel::result::Error),
+    /// Failed to find pending host upgrade commands: {0}
+    HostUpgradePending(diesel::result::Error),
     /// Command Node error: {0}
     Node(#[from] super::node::Error),
     /// Attempt to create a node command without a node id.
     NodeCommandWithoutNodeId,
+    /// Command `{0}` is not held for later execution.
+    NotHeld(CommandId),
+    /// Failed to release the dependents of command `{0}`: {1}
+    ReleaseDependents(CommandId, diesel::result::Error),
+    /// Failed to find upcoming commands: {0}
+    Upcoming(diesel::result::Error),
     /// Failed to update command: {0}
     Update(diesel::result::Error),
 }
@@ -61,7 +90,9 @@ impl From<Error> for Status {
             | DeleteNodePending(NotFound)
             | FindById(_, NotFound)
             | HasHostPending(NotFound)
+            | HasNodeStart(NotFound)
             | HostPending(NotFound) => Status::not_found("Not found."),
+            NotHeld(_) => Status::failed_precondition("Command is not held."),
             Host(err) => err.into(),
             Node(err) => err.into(),
             _ => Status::internal("Internal error."),
@@ -76,6 +107,10 @@ pub enum CommandType {
     HostStop,
     HostRestart,
     HostPending,
+    HostUpgrade,
+    HostKeys,
+    HostMesh,
+    HostLogSink,
     NodeCreate,
     NodeStart,
     NodeStop,
@@ -83,12 +118,26 @@ pub enum CommandType {
     NodeUpdate,
     NodeUpgrade,
     NodeDelete,
+    NodeResize,
+    NodeCertificate,
+    NodeReportConfig,
+    NodeDebugBundle,
 }
 
 impl CommandType {
     const fn is_host(self) -> bool {
         use CommandType::*;
-        matches!(self, HostStart | HostStop | HostRestart | HostPending)
+        matches!(
+            self,
+            HostStart
+                | HostStop
+                | HostRestart
+                | HostPending
+                | HostUpgrade
+                | HostKeys
+                | HostMesh
+                | HostLogSink
+        )
     }
 
     const fn is_node(self) -> bool {
@@ -126,12 +175,19 @@ pub struct Command {
     pub exit_code: Option<ExitCode>,
     pub command_type: CommandType,
     pub protobuf: Option<Vec<u8>>,
+    /// When a scheduled command is held until.
+    pub execute_at: Option<DateTime<Utc>>,
+    /// When a scheduled command was released to its host.
+    pub dispatched_at: Option<DateTime<Utc>>,
+    /// A command that must succeed before this one is released.
+    pub depends_on: Option<CommandId>,
 }
 
 impl Command {
+    /// Find a command by id alone, which checks the id index of each partition.
     pub async fn by_id(id: CommandId, conn: &mut Conn<'_>) -> Result<Self, Error> {
         commands::table
-            .find(id)
+            .filter(commands::id.eq(id))
             .get_result(conn)
             .await
             .map_err(|err| Error::FindById(id, err))
@@ -140,7 +196,8 @@ impl Command {
     pub async fn has_host_pending(host_id: HostId, conn: &mut Conn<'_>) -> Result<bool, Error> {
         let pending = commands::table
             .filter(commands::host_id.eq(host_id))
-            .filter(commands::exit_code.is_null());
+            .filter(commands::exit_code.is_null())
+            .filter(released());
 
         diesel::select(dsl::exists(pending))
             .get_result(conn)
@@ -148,16 +205,58 @@ impl Command {
             .map_err(Error::HasHostPending)
     }
 
+    /// Whether a start or restart command for the node is pending or recently
+    /// completed, so the node may start again.
+    ///
+    /// A start that is still held does not count.
+    pub async fn has_node_start(node_id: NodeId, conn: &mut Conn<'_>) -> Result<bool, Error> {
+        let recent = Utc::now() - START_WINDOW;
+        let started = commands::table
+            .filter(commands::node_id.eq(node_id))
+            .filter(
+                commands::command_type.eq_any([CommandType::NodeStart, CommandType::NodeRestart]),
+            )
+            .filter(
+                commands::exit_code
+                    .is_null()
+                    .or(commands::completed_at.gt(recent)),
+            )
+            .filter(released());
+
+        diesel::select(dsl::exists(started))
+            .get_result(conn)
+            .await
+            .map_err(Error::HasNodeStart)
+    }
+
+    /// The incomplete commands of a host, excluding those still held.
     pub async fn host_pending(host_id: HostId, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
         commands::table
             .filter(commands::host_id.eq(host_id))
             .filter(commands::exit_code.is_null())
+            .filter(released())
             .order_by(commands::created_at.asc())
             .get_results(conn)
             .await
             .map_err(Error::HostPending)
     }
 
+    /// The hosts that have not yet completed an upgrade command.
+    pub async fn host_upgrade_pending(conn: &mut Conn<'_>) -> Result<HashSet<HostId>, Error> {
+        commands::table
+            .filter(commands::command_type.eq(CommandType::HostUpgrade))
+            .filter(commands::exit_code.is_null())
+            .select(commands::host_id)
+            .distinct()
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<HostId>| ids.into_iter().collect())
+            .map_err(Error::HostUpgradePending)
+    }
+
+    /// The most recent commands matching `filter`, newest first.
+    ///
+    /// Setting `created_after` skips the monthly partitions before it.
     pub async fn list(filter: CommandFilter, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
         let mut query = commands::table.into_boxed();
 
@@ -170,14 +269,165 @@ impl Command {
         if let Some(exit_code) = filter.exit_code {
             query = query.filter(commands::exit_code.eq(exit_code));
         }
+        if let Some(created_after) = filter.created_after {
+            query = query.filter(commands::created_at.gt(created_after));
+        }
 
         query
             .order_by(commands::created_at.desc())
+            .limit(filter.limit.clamp(1, MAX_LIST_LIMIT))
             .get_results(conn)
             .await
             .map_err(Error::Filter)
     }
 
+    /// The commands held until a later time or an unfinished dependency,
+    /// soonest first.
+    pub async fn upcoming(
+        host_id: Option<HostId>,
+        node_id: Option<NodeId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Command>, Error> {
+        let mut query = commands::table
+            .filter(commands::exit_code.is_null())
+            .filter(dsl::not(released()))
+            .into_boxed();
+
+        if let Some(host_id) = host_id {
+            query = query.filter(commands::host_id.eq(host_id));
+        }
+        if let Some(node_id) = node_id {
+            query = query.filter(commands::node_id.eq(node_id));
+        }
+
+        query
+            .order_by((commands::execute_at.asc(), commands::created_at.asc()))
+            .limit(MAX_LIST_LIMIT)
+            .get_results(conn)
+            .await
+            .map_err(Error::Upcoming)
+    }
+
+    /// Held commands whose time has come and whose dependency succeeded, but
+    /// that were not yet released to their host.
+    pub async fn due(conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        commands::table
+            .filter(commands::exit_code.is_null())
+            .filter(commands::dispatched_at.is_null())
+            .filter(
+                commands::execute_at
+                    .is_not_null()
+                    .or(commands::depends_on.is_not_null()),
+            )
+            .filter(released())
+            .order_by(commands::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::Due)
+    }
+
+    /// Record that a scheduled command was released to its host.
+    pub async fn dispatched(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let command = commands::table
+            .filter(commands::id.eq(self.id))
+            .filter(commands::created_at.eq(self.created_at));
+
+        diesel::update(command)
+            .set(commands::dispatched_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Dispatched(self.id, err))
+    }
+
+    /// Delete a command that is still held, along with the commands that
+    /// depend on it.
+    pub async fn cancel(id: CommandId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let held = commands::table
+            .filter(commands::id.eq(id))
+            .filter(commands::exit_code.is_null())
+            .filter(dsl::not(released()));
+
+        let deleted = diesel::delete(held)
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Cancel(id, err))?;
+        if deleted == 0 {
+            return Err(Error::NotHeld(id));
+        }
+
+        let mut cancelled = vec![id];
+        while let Some(id) = cancelled.pop() {
+            let dependents = commands::table
+                .filter(commands::depends_on.eq(id))
+                .filter(commands::exit_code.is_null());
+            let ids: Vec<CommandId> = diesel::delete(dependents)
+                .returning(commands::id)
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::DeleteDependents(id, err))?;
+            cancelled.extend(ids);
+        }
+
+        Ok(())
+    }
+
+    /// Release the commands waiting on this one after it succeeded, unless
+    /// they are still scheduled for later.
+    pub async fn release_dependents(&self, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        let dependents = commands::table
+            .filter(commands::depends_on.eq(self.id))
+            .filter(commands::exit_code.is_null())
+            .filter(commands::dispatched_at.is_null())
+            .filter(
+                commands::execute_at
+                    .is_null()
+                    .or(commands::execute_at.le(Utc::now())),
+            );
+
+        diesel::update(dependents)
+            .set(commands::dispatched_at.eq(Utc::now()))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ReleaseDependents(self.id, err))
+    }
+
+    /// Fail the commands waiting on this one after it failed, along with the
+    /// commands waiting on those in turn.
+    pub async fn fail_dependents(&self, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        let mut failed = vec![];
+        let mut pending = vec![self.id];
+        while let Some(id) = pending.pop() {
+            let dependents = commands::table
+                .filter(commands::depends_on.eq(id))
+                .filter(commands::exit_code.is_null());
+            let update = UpdateCommand {
+                exit_code: self.exit_code,
+                exit_message: Some(format!("Dependency `{id}` failed.")),
+                retry_hint_seconds: None,
+                completed_at: Some(Utc::now()),
+            };
+            let commands: Vec<Command> = diesel::update(dependents)
+                .set(update)
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::FailDependents(id, err))?;
+
+            pending.extend(commands.iter().map(|command| command.id));
+            failed.extend(commands);
+        }
+
+        Ok(failed)
+    }
+
+    /// Whether the command is held until a later time or until its dependency
+    /// succeeds.
+    pub fn is_held(&self) -> bool {
+        self.exit_code.is_none()
+            && self.dispatched_at.is_none()
+            && (self.execute_at.is_some_and(|at| at > Utc::now()) || self.depends_on.is_some())
+    }
+
     pub async fn delete_host_pending(host_id: HostId, conn: &mut Conn<'_>) -> Result<(), Error> {
         let pending = commands::table
             .filter(commands::host_id.eq(host_id))
@@ -210,7 +460,11 @@ impl Command {
     }
 
     pub async fn ack(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
-        diesel::update(commands::table.find(self.id))
+        let command = commands::table
+            .filter(commands::id.eq(self.id))
+            .filter(commands::created_at.eq(self.created_at));
+
+        diesel::update(command)
             .set(commands::acked_at.eq(Utc::now()))
             .execute(conn)
             .await
@@ -226,6 +480,8 @@ pub struct NewCommand {
     node_id: Option<NodeId>,
     command_type: CommandType,
     protobuf: Option<Vec<u8>>,
+    execute_at: Option<DateTime<Utc>>,
+    depends_on: Option<CommandId>,
 }
 
 impl NewCommand {
@@ -239,6 +495,8 @@ impl NewCommand {
             node_id: None,
             command_type,
             protobuf: None,
+            execute_at: None,
+            depends_on: None,
         })
     }
 
@@ -252,6 +510,8 @@ impl NewCommand {
             node_id: Some(node.id),
             command_type,
             protobuf: None,
+            execute_at: None,
+            depends_on: None,
         })
     }
 
@@ -264,6 +524,21 @@ impl NewCommand {
         self
     }
 
+    /// Hold the command until `execute_at`, if it is in the future.
+    #[must_use]
+    pub fn with_execute_at(mut self, execute_at: Option<DateTime<Utc>>) -> Self {
+        self.execute_at = execute_at.filter(|at| *at > Utc::now());
+        self
+    }
+
+    /// Hold the command until `command` succeeds, or fail it along with
+    /// `command`.
+    #[must_use]
+    pub const fn after(mut self, command: &Command) -> Self {
+        self.depends_on = Some(command.id);
+        self
+    }
+
     pub async fn create(self, conn: &mut Conn<'_>) -> Result<Command, Error> {
         diesel::insert_into(commands::table)
             .values(self)
@@ -273,6 +548,27 @@ impl NewCommand {
     }
 }
 
+/// Commands that are neither scheduled for later nor waiting on a dependency
+/// that has not succeeded yet.
+fn released() -> Box<dyn BoxableExpression<commands::table, Pg, SqlType = Nullable<Bool>>> {
+    let dependencies = diesel::alias!(commands as dependencies);
+    let succeeded = dependencies
+        .filter(
+            dependencies
+                .field(commands::id)
+                .nullable()
+                .eq(commands::depends_on),
+        )
+        .filter(dependencies.field(commands::exit_code).eq(ExitCode::Ok));
+
+    Box::new(
+        commands::execute_at
+            .is_null()
+            .or(commands::execute_at.le(Utc::now()))
+            .and(commands::depends_on.is_null().or(dsl::exists(succeeded))),
+    )
+}
+
 #[derive(Debug, AsChangeset)]
 #[diesel(table_name = commands)]
 pub struct UpdateCommand {
@@ -284,7 +580,7 @@ pub struct UpdateCommand {
 
 impl UpdateCommand {
     pub async fn apply(self, id: CommandId, conn: &mut Conn<'_>) -> Result<Command, Error> {
-        diesel::update(commands::table.find(id))
+        diesel::update(commands::table.filter(commands::id.eq(id)))
             .set(self)
             .get_result(conn)
             .await
@@ -343,4 +639,6 @@ pub struct CommandFilter {
     pub node_id: Option<NodeId>,
     pub host_id: Option<HostId>,
     pub exit_code: Option<ExitCode>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub limit: i64,
 }
@@ -0,0 +1,500 @@
+//! Autoscaling of the nodes of an endpoint pool by their usage.
+//!
+//! An autoscaler keeps the pool between `min_nodes` and `max_nodes` members,
+//! adding a node when the average usage of its running members is above
+//! `scale_up_above` and removing one when it is below `scale_down_below`.
+//! Usage is either the requests per second of the endpoints of the members, or
+//! the percent of their allocated CPU that they last reported using.
+//!
+//! Nodes are created and deleted through the usual node requests, as the user
+//! that set up the autoscaler, so they are checked against the same permissions
+//! and quotas. Only nodes that the autoscaler created are ever deleted, newest
+//! first. After each change the pool waits `cooldown_seconds` before scaling
+//! again, so that new nodes can start serving before usage is measured again.
+//!
+//! Each change, successful or not, is kept as an `AutoscaleEvent`.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{pool_autoscale_events, pool_autoscalers, sql_types};
+use crate::model::{ImageId, Node, RegionId};
+use crate::util::NanosUtc;
+
+use super::{EndpointPoolId, EndpointUsage};
+
+/// The most nodes that an autoscaler may keep in a pool.
+pub const MAX_AUTOSCALE_NODES: i32 = 50;
+/// The shortest wait between two changes to a pool.
+pub const MIN_COOLDOWN_SECONDS: i32 = 60;
+/// The number of recent events returned for an autoscaler.
+const RECENT_EVENTS: i64 = 20;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find all autoscalers: {0}
+    All(diesel::result::Error),
+    /// Failed to find autoscaler of pool `{0}`: {1}
+    ByPoolId(EndpointPoolId, diesel::result::Error),
+    /// Autoscaler cooldown must be at least {MIN_COOLDOWN_SECONDS} seconds.
+    Cooldown,
+    /// Failed to create autoscale event: {0}
+    CreateEvent(diesel::result::Error),
+    /// Failed to find autoscale events of pool `{0}`: {1}
+    Events(EndpointPoolId, diesel::result::Error),
+    /// Autoscaler node counts must satisfy min <= max <= {MAX_AUTOSCALE_NODES}.
+    NodeCount,
+    /// Failed to remove autoscaler of pool `{0}`: {1}
+    Remove(EndpointPoolId, diesel::result::Error),
+    /// Failed to mark autoscaler of pool `{0}` as scaled: {1}
+    Scaled(EndpointPoolId, diesel::result::Error),
+    /// Failed to set autoscaler of pool `{0}`: {1}
+    Set(EndpointPoolId, diesel::result::Error),
+    /// Autoscaler thresholds must be finite, with scale down below scale up.
+    Thresholds,
+    /// Failed to truncate autoscale usage window: {0}
+    Truncate(chrono::RoundingError),
+    /// Autoscale endpoint usage error: {0}
+    Usage(#[from] super::usage::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByPoolId(_, NotFound) | Remove(_, NotFound) => {
+                Status::not_found("Autoscaler not found.")
+            }
+            Cooldown => Status::invalid_argument("cooldown_seconds"),
+            NodeCount => Status::invalid_argument("max_nodes"),
+            Thresholds => Status::invalid_argument("scale_down_below"),
+            Usage(err) => err.into(),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// The usage that a pool is scaled by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAutoscaleMetric"]
+pub enum AutoscaleMetric {
+    /// The average requests per second of the endpoints of each member.
+    RequestsPerSecond,
+    /// The average percent of allocated CPU used by each member.
+    CpuPercent,
+}
+
+impl AutoscaleMetric {
+    pub const fn from_api(metric: api::AutoscaleMetric) -> Option<Self> {
+        match metric {
+            api::AutoscaleMetric::Unspecified => None,
+            api::AutoscaleMetric::RequestsPerSecond => Some(AutoscaleMetric::RequestsPerSecond),
+            api::AutoscaleMetric::CpuPercent => Some(AutoscaleMetric::CpuPercent),
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            AutoscaleMetric::RequestsPerSecond => "requests per second",
+            AutoscaleMetric::CpuPercent => "cpu percent",
+        }
+    }
+}
+
+impl From<AutoscaleMetric> for api::AutoscaleMetric {
+    fn from(metric: AutoscaleMetric) -> Self {
+        match metric {
+            AutoscaleMetric::RequestsPerSecond => api::AutoscaleMetric::RequestsPerSecond,
+            AutoscaleMetric::CpuPercent => api::AutoscaleMetric::CpuPercent,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAutoscaleAction"]
+pub enum AutoscaleAction {
+    ScaleUp,
+    ScaleDown,
+}
+
+impl From<AutoscaleAction> for api::AutoscaleAction {
+    fn from(action: AutoscaleAction) -> Self {
+        match action {
+            AutoscaleAction::ScaleUp => api::AutoscaleAction::ScaleUp,
+            AutoscaleAction::ScaleDown => api::AutoscaleAction::ScaleDown,
+        }
+    }
+}
+
+/// A change that an autoscaler decided to make to its pool.
+#[derive(Debug, PartialEq)]
+pub struct Decision {
+    pub action: AutoscaleAction,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = pool_autoscalers)]
+pub struct PoolAutoscaler {
+    pub pool_id: EndpointPoolId,
+    /// The user that nodes are created and deleted as.
+    pub user_id: UserId,
+    /// The image that new nodes are created from.
+    pub image_id: ImageId,
+    /// The region that new nodes are created in.
+    pub region_id: RegionId,
+    pub min_nodes: i32,
+    pub max_nodes: i32,
+    pub metric: AutoscaleMetric,
+    pub scale_up_above: f64,
+    pub scale_down_below: f64,
+    pub cooldown_seconds: i32,
+    pub last_scaled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl PoolAutoscaler {
+    pub async fn by_pool_id(pool_id: EndpointPoolId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        pool_autoscalers::table
+            .find(pool_id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByPoolId(pool_id, err))
+    }
+
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        pool_autoscalers::table
+            .order_by(pool_autoscalers::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::All)
+    }
+
+    pub async fn remove(pool_id: EndpointPoolId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(pool_autoscalers::table.find(pool_id))
+            .returning(pool_autoscalers::pool_id)
+            .get_result::<EndpointPoolId>(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Remove(pool_id, err))
+    }
+
+    /// Start the cooldown of the pool after a change.
+    pub async fn scaled(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(pool_autoscalers::table.find(self.pool_id))
+            .set(pool_autoscalers::last_scaled_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Scaled(self.pool_id, err))
+    }
+
+    /// The average usage of the `running` members of the pool, if they
+    /// reported any.
+    ///
+    /// Endpoint usage is rolled up hourly, so requests per second are measured
+    /// from the start of the previous hour.
+    pub async fn usage(
+        &self,
+        running: &[&Node],
+        now: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<f64>, Error> {
+        if running.is_empty() {
+            return Ok(None);
+        }
+
+        match self.metric {
+            AutoscaleMetric::CpuPercent => {
+                let percents: Vec<i64> = running
+                    .iter()
+                    .filter_map(|node| node.cpu_percent)
+                    .map(i64::from)
+                    .collect();
+                if percents.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(average(percents.iter().sum(), percents.len(), 1)))
+            }
+            AutoscaleMetric::RequestsPerSecond => {
+                let since = (now - TimeDelta::hours(1))
+                    .duration_trunc(TimeDelta::hours(1))
+                    .map_err(Error::Truncate)?;
+                let node_ids: Vec<_> = running.iter().map(|node| node.id).collect();
+                let requests = EndpointUsage::node_requests_since(&node_ids, since, conn).await?;
+                let seconds = (now - since).num_seconds().max(1);
+                Ok(Some(average(requests, running.len(), seconds)))
+            }
+        }
+    }
+
+    /// Decide whether a pool of `count` live members should change.
+    ///
+    /// `usage` is the average usage of the running members, if any of them
+    /// reported it. The node count limits apply even without usage, while the
+    /// thresholds only apply within them.
+    pub fn decide(&self, count: usize, usage: Option<f64>, now: DateTime<Utc>) -> Option<Decision> {
+        let cooldown = TimeDelta::seconds(i64::from(self.cooldown_seconds));
+        if self.last_scaled_at.is_some_and(|at| now < at + cooldown) {
+            return None;
+        }
+
+        let count = i32::try_from(count).unwrap_or(i32::MAX);
+        let decision = |action, reason| Some(Decision { action, reason });
+        if count < self.min_nodes {
+            return decision(
+                AutoscaleAction::ScaleUp,
+                format!("{count} nodes is below the minimum of {}", self.min_nodes),
+            );
+        }
+        if count > self.max_nodes {
+            return decision(
+                AutoscaleAction::ScaleDown,
+                format!("{count} nodes is above the maximum of {}", self.max_nodes),
+            );
+        }
+
+        let usage = usage?;
+        let metric = self.metric.name();
+        if usage > self.scale_up_above && count < self.max_nodes {
+            decision(
+                AutoscaleAction::ScaleUp,
+                format!("{metric} of {usage:.2} is above {}", self.scale_up_above),
+            )
+        } else if usage < self.scale_down_below && count > self.min_nodes {
+            decision(
+                AutoscaleAction::ScaleDown,
+                format!("{metric} of {usage:.2} is below {}", self.scale_down_below),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+impl From<PoolAutoscaler> for api::PoolAutoscaler {
+    fn from(autoscaler: PoolAutoscaler) -> Self {
+        api::PoolAutoscaler {
+            pool_id: autoscaler.pool_id.to_string(),
+            image_id: autoscaler.image_id.to_string(),
+            region_id: autoscaler.region_id.to_string(),
+            min_nodes: u32::try_from(autoscaler.min_nodes).unwrap_or_default(),
+            max_nodes: u32::try_from(autoscaler.max_nodes).unwrap_or_default(),
+            metric: api::AutoscaleMetric::from(autoscaler.metric).into(),
+            scale_up_above: autoscaler.scale_up_above,
+            scale_down_below: autoscaler.scale_down_below,
+            cooldown_seconds: u32::try_from(autoscaler.cooldown_seconds).unwrap_or_default(),
+            last_scaled_at: autoscaler
+                .last_scaled_at
+                .map(NanosUtc::from)
+                .map(Into::into),
+            created_at: Some(NanosUtc::from(autoscaler.created_at).into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = pool_autoscalers)]
+pub struct NewPoolAutoscaler {
+    pub pool_id: EndpointPoolId,
+    pub user_id: UserId,
+    pub image_id: ImageId,
+    pub region_id: RegionId,
+    pub min_nodes: i32,
+    pub max_nodes: i32,
+    pub metric: AutoscaleMetric,
+    pub scale_up_above: f64,
+    pub scale_down_below: f64,
+    pub cooldown_seconds: i32,
+}
+
+impl NewPoolAutoscaler {
+    /// Create or replace the autoscaler of the pool, keeping its cooldown.
+    pub async fn set(self, conn: &mut Conn<'_>) -> Result<PoolAutoscaler, Error> {
+        self.validate()?;
+
+        let pool_id = self.pool_id;
+        diesel::insert_into(pool_autoscalers::table)
+            .values(self)
+            .on_conflict(pool_autoscalers::pool_id)
+            .do_update()
+            .set((
+                pool_autoscalers::user_id.eq(excluded(pool_autoscalers::user_id)),
+                pool_autoscalers::image_id.eq(excluded(pool_autoscalers::image_id)),
+                pool_autoscalers::region_id.eq(excluded(pool_autoscalers::region_id)),
+                pool_autoscalers::min_nodes.eq(excluded(pool_autoscalers::min_nodes)),
+                pool_autoscalers::max_nodes.eq(excluded(pool_autoscalers::max_nodes)),
+                pool_autoscalers::metric.eq(excluded(pool_autoscalers::metric)),
+                pool_autoscalers::scale_up_above.eq(excluded(pool_autoscalers::scale_up_above)),
+                pool_autoscalers::scale_down_below.eq(excluded(pool_autoscalers::scale_down_below)),
+                pool_autoscalers::cooldown_seconds.eq(excluded(pool_autoscalers::cooldown_seconds)),
+                pool_autoscalers::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Set(pool_id, err))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.min_nodes < 0 || self.min_nodes > self.max_nodes {
+            return Err(Error::NodeCount);
+        }
+        if self.max_nodes > MAX_AUTOSCALE_NODES {
+            return Err(Error::NodeCount);
+        }
+        if self.cooldown_seconds < MIN_COOLDOWN_SECONDS {
+            return Err(Error::Cooldown);
+        }
+
+        let finite = self.scale_up_above.is_finite() && self.scale_down_below.is_finite();
+        if !finite || self.scale_down_below >= self.scale_up_above {
+            return Err(Error::Thresholds);
+        }
+
+        Ok(())
+    }
+}
+
+/// The average of `total` over `count` members and `seconds`.
+#[allow(clippy::cast_precision_loss)]
+fn average(total: i64, count: usize, seconds: i64) -> f64 {
+    total as f64 / count.max(1) as f64 / seconds.max(1) as f64
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct AutoscaleEventId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = pool_autoscale_events)]
+pub struct AutoscaleEvent {
+    pub id: AutoscaleEventId,
+    pub pool_id: EndpointPoolId,
+    pub action: AutoscaleAction,
+    /// The node that was created or deleted, if any.
+    pub node_id: Option<NodeId>,
+    /// The usage of the pool when the change was decided.
+    pub metric_value: Option<f64>,
+    pub reason: String,
+    /// Why the change failed, if it did.
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AutoscaleEvent {
+    /// The most recent events of a pool, newest first.
+    pub async fn recent(pool_id: EndpointPoolId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        pool_autoscale_events::table
+            .filter(pool_autoscale_events::pool_id.eq(pool_id))
+            .order_by(pool_autoscale_events::created_at.desc())
+            .limit(RECENT_EVENTS)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Events(pool_id, err))
+    }
+}
+
+impl From<AutoscaleEvent> for api::AutoscaleEvent {
+    fn from(event: AutoscaleEvent) -> Self {
+        api::AutoscaleEvent {
+            event_id: event.id.to_string(),
+            pool_id: event.pool_id.to_string(),
+            action: api::AutoscaleAction::from(event.action).into(),
+            node_id: event.node_id.map(|id| id.to_string()),
+            metric_value: event.metric_value,
+            reason: event.reason,
+            error: event.error,
+            created_at: Some(NanosUtc::from(event.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = pool_autoscale_events)]
+pub struct NewAutoscaleEvent {
+    pub pool_id: EndpointPoolId,
+    pub action: AutoscaleAction,
+    pub node_id: Option<NodeId>,
+    pub metric_value: Option<f64>,
+    pub reason: String,
+    pub error: Option<String>,
+}
+
+impl NewAutoscaleEvent {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<AutoscaleEvent, Error> {
+        diesel::insert_into(pool_autoscale_events::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::CreateEvent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn autoscaler(last_scaled_at: Option<DateTime<Utc>>) -> PoolAutoscaler {
+        PoolAutoscaler {
+            pool_id: EndpointPoolId::from(Uuid::nil()),
+            user_id: UserId::from(Uuid::nil()),
+            image_id: ImageId::from(Uuid::nil()),
+            region_id: RegionId::from(Uuid::nil()),
+            min_nodes: 1,
+            max_nodes: 3,
+            metric: AutoscaleMetric::CpuPercent,
+            scale_up_above: 80.0,
+            scale_down_below: 20.0,
+            cooldown_seconds: 300,
+            last_scaled_at,
+            created_at: Utc::now(),
+            updated_at: None,
+        }
+    }
+
+    fn action(decision: Option<Decision>) -> Option<AutoscaleAction> {
+        decision.map(|decision| decision.action)
+    }
+
+    #[test]
+    fn decide_keeps_the_pool_within_limits() {
+        let now = Utc::now();
+        let autoscaler = autoscaler(None);
+
+        let up = Some(AutoscaleAction::ScaleUp);
+        let down = Some(AutoscaleAction::ScaleDown);
+        assert_eq!(action(autoscaler.decide(0, None, now)), up);
+        assert_eq!(action(autoscaler.decide(4, Some(99.0), now)), down);
+        assert_eq!(action(autoscaler.decide(2, Some(90.0), now)), up);
+        assert_eq!(action(autoscaler.decide(2, Some(10.0), now)), down);
+        assert_eq!(action(autoscaler.decide(2, Some(50.0), now)), None);
+        assert_eq!(action(autoscaler.decide(2, None, now)), None);
+
+        // the thresholds don't scale past the limits
+        assert_eq!(action(autoscaler.decide(3, Some(90.0), now)), None);
+        assert_eq!(action(autoscaler.decide(1, Some(10.0), now)), None);
+    }
+
+    #[test]
+    fn decide_waits_for_the_cooldown() {
+        let now = Utc::now();
+        let autoscaler = autoscaler(Some(now - TimeDelta::seconds(60)));
+        assert_eq!(action(autoscaler.decide(0, None, now)), None);
+
+        let later = now + TimeDelta::seconds(300);
+        let up = Some(AutoscaleAction::ScaleUp);
+        assert_eq!(action(autoscaler.decide(0, None, later)), up);
+    }
+}
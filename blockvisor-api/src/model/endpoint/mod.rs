@@ -0,0 +1,252 @@
+//! Authenticated RPC endpoints exposing nodes through the edge gateway.
+//!
+//! An endpoint either belongs to a single node, or to a whole org when it has
+//! no `node_id`. Requests to the gateway carry a bearer token of the form
+//! `rpc_{secret}`, where the secret hashed with `token_salt` must equal the
+//! stored `token_hash`. The secret itself is only returned once on creation.
+
+pub mod autoscaler;
+pub use autoscaler::{AutoscaleEvent, PoolAutoscaler};
+
+pub mod pool;
+pub use pool::{EndpointPool, EndpointPoolId, PoolMember};
+
+pub mod usage;
+pub use usage::{EndpointUsage, UsageReport};
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, Resource, ResourceId, ResourceType};
+use crate::auth::token::api_key::{KeyHash, Salt, Secret};
+use crate::database::{Conn, WriteConn};
+use crate::grpc::{Status, api, common};
+use crate::stripe::api::subscription::SubscriptionItemId;
+use crate::util::NanosUtc;
+
+use super::schema::node_endpoints;
+
+pub const TOKEN_PREFIX: &str = "rpc_";
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find endpoint `{0}`: {1}
+    ById(EndpointId, diesel::result::Error),
+    /// Failed to find endpoints `{0:?}`: {1}
+    ByIds(Vec<EndpointId>, diesel::result::Error),
+    /// Failed to find endpoints for org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Failed to create endpoint: {0}
+    Create(diesel::result::Error),
+    /// Failed to revoke endpoint `{0}`: {1}
+    Revoke(EndpointId, diesel::result::Error),
+    /// Failed to find endpoint stripe item ids: {0}
+    StripeItemIds(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Revoke(_, NotFound) => Status::not_found("Endpoint not found."),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct EndpointId(Uuid);
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = node_endpoints)]
+pub struct Endpoint {
+    pub id: EndpointId,
+    pub org_id: OrgId,
+    pub node_id: Option<NodeId>,
+    pub label: String,
+    pub token_hash: KeyHash,
+    pub token_salt: Salt,
+    pub allowed_methods: Vec<Option<String>>,
+    pub rate_limit_rps: Option<i32>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub monthly_quota: Option<i64>,
+    pub stripe_item_id: Option<SubscriptionItemId>,
+}
+
+impl Endpoint {
+    /// Find an unrevoked endpoint by id.
+    pub async fn by_id(id: EndpointId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_endpoints::table
+            .find(id)
+            .filter(node_endpoints::revoked_at.is_null())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// Find endpoints by id, including revoked ones.
+    pub async fn by_ids(ids: Vec<EndpointId>, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        node_endpoints::table
+            .filter(node_endpoints::id.eq_any(ids.clone()))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByIds(ids, err))
+    }
+
+    /// Find the unrevoked endpoints of an org, optionally for a single node.
+    pub async fn by_org(
+        org_id: OrgId,
+        node_id: Option<NodeId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = node_endpoints::table
+            .filter(node_endpoints::org_id.eq(org_id))
+            .filter(node_endpoints::revoked_at.is_null())
+            .into_boxed();
+
+        if let Some(node_id) = node_id {
+            query = query.filter(node_endpoints::node_id.eq(node_id));
+        }
+
+        query
+            .order_by(node_endpoints::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    /// The stripe subscription items of all unrevoked, metered endpoints.
+    pub async fn stripe_item_ids(
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<SubscriptionItemId>, Error> {
+        node_endpoints::table
+            .filter(node_endpoints::revoked_at.is_null())
+            .filter(node_endpoints::stripe_item_id.is_not_null())
+            .select(node_endpoints::stripe_item_id.assume_not_null())
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<SubscriptionItemId>| ids.into_iter().collect())
+            .map_err(Error::StripeItemIds)
+    }
+
+    pub async fn revoke(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        let active = node_endpoints::table
+            .find(id)
+            .filter(node_endpoints::revoked_at.is_null());
+
+        diesel::update(active)
+            .set(node_endpoints::revoked_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Revoke(id, err))
+    }
+
+    pub fn allowed_methods(&self) -> Vec<String> {
+        self.allowed_methods.iter().flatten().cloned().collect()
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+
+    /// Convert to the api representation served at `url`.
+    pub fn into_api(self, url: String) -> api::Endpoint {
+        let allowed_methods = self.allowed_methods();
+        let created_by = common::Resource::from(self.created_by());
+
+        api::Endpoint {
+            endpoint_id: self.id.to_string(),
+            org_id: self.org_id.to_string(),
+            node_id: self.node_id.map(|id| id.to_string()),
+            label: self.label,
+            url,
+            allowed_methods,
+            rate_limit_rps: self.rate_limit_rps.and_then(|rps| u32::try_from(rps).ok()),
+            monthly_quota: self
+                .monthly_quota
+                .and_then(|quota| u64::try_from(quota).ok()),
+            metered: self.stripe_item_id.is_some(),
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(self.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = node_endpoints)]
+struct InsertEndpoint {
+    org_id: OrgId,
+    node_id: Option<NodeId>,
+    label: String,
+    token_hash: KeyHash,
+    token_salt: Salt,
+    allowed_methods: Vec<Option<String>>,
+    rate_limit_rps: Option<i32>,
+    created_by_type: ResourceType,
+    created_by_id: ResourceId,
+    monthly_quota: Option<i64>,
+    stripe_item_id: Option<SubscriptionItemId>,
+}
+
+#[derive(Debug)]
+pub struct NewEndpoint {
+    pub org_id: OrgId,
+    pub node_id: Option<NodeId>,
+    pub label: String,
+    pub allowed_methods: Vec<String>,
+    pub rate_limit_rps: Option<i32>,
+    pub monthly_quota: Option<i64>,
+    pub stripe_item_id: Option<SubscriptionItemId>,
+    pub created_by: Resource,
+}
+
+impl NewEndpoint {
+    pub async fn create(self, write: &mut WriteConn<'_, '_>) -> Result<Created, Error> {
+        let mut rng = write.ctx.rng.lock().await;
+        let salt = Salt::generate(&mut *rng);
+        let secret = Secret::generate(&mut *rng);
+        drop(rng);
+
+        let insert = InsertEndpoint {
+            org_id: self.org_id,
+            node_id: self.node_id,
+            label: self.label,
+            token_hash: KeyHash::from(&salt, &secret),
+            token_salt: salt,
+            allowed_methods: self.allowed_methods.into_iter().map(Some).collect(),
+            rate_limit_rps: self.rate_limit_rps,
+            created_by_type: self.created_by.typ(),
+            created_by_id: self.created_by.id(),
+            monthly_quota: self.monthly_quota,
+            stripe_item_id: self.stripe_item_id,
+        };
+
+        let endpoint = diesel::insert_into(node_endpoints::table)
+            .values(insert)
+            .get_result(write)
+            .await
+            .map_err(Error::Create)?;
+        let token = format!("{TOKEN_PREFIX}{}", secret.encode());
+
+        Ok(Created { endpoint, token })
+    }
+}
+
+/// A new `Endpoint` row plus the gateway token returned once on creation.
+pub struct Created {
+    pub endpoint: Endpoint,
+    pub token: String,
+}
@@ -0,0 +1,434 @@
+//! DNS names that load-balance across the healthy nodes of a pool.
+//!
+//! An endpoint pool is a single DNS name with an address record for each of
+//! its member nodes that is in rotation. A member is in rotation while its node
+//! is running and its protocol does not report itself as unhealthy, and
+//! `EndpointPool::rotate` adds or removes records as node health changes.
+//!
+//! New records are added before stale ones are removed, and the records of
+//! unhealthy members are kept while no member is healthy, so the name keeps
+//! resolving throughout. The records of a deleted node are removed with its
+//! other DNS records.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, Resource, ResourceId, ResourceType};
+use crate::cloudflare::{AliasTarget, Dns};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::model::Node;
+use crate::model::node::{NodeHealth, NodeState};
+use crate::model::schema::{endpoint_pool_members, endpoint_pools};
+use crate::util::NanosUtc;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to add members to pool `{0}`: {1}
+    AddMembers(EndpointPoolId, diesel::result::Error),
+    /// Failed to find all endpoint pools: {0}
+    All(diesel::result::Error),
+    /// Failed to find endpoint pool `{0}`: {1}
+    ById(EndpointPoolId, diesel::result::Error),
+    /// Failed to find endpoint pools for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Endpoint pool cloudflare error: {0}
+    Cloudflare(#[from] crate::cloudflare::Error),
+    /// Failed to create endpoint pool: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete endpoint pool `{0}`: {1}
+    Delete(EndpointPoolId, diesel::result::Error),
+    /// Failed to delete the pool memberships of node `{0}`: {1}
+    DeleteForNode(NodeId, diesel::result::Error),
+    /// Failed to find endpoint pool DNS ids: {0}
+    DnsIds(diesel::result::Error),
+    /// Failed to find the members of endpoint pools: {0}
+    Members(diesel::result::Error),
+    /// Endpoint pool node error: {0}
+    Node(Box<crate::model::node::Error>),
+    /// Failed to find the pool memberships of node `{0}`: {1}
+    NodeMembers(NodeId, diesel::result::Error),
+    /// Failed to remove members from pool `{0}`: {1}
+    RemoveMembers(EndpointPoolId, diesel::result::Error),
+    /// Failed to set the DNS record of node `{1}` in pool `{0}`: {2}
+    SetDnsId(EndpointPoolId, NodeId, diesel::result::Error),
+    /// Failed to update endpoint pool `{0}`: {1}
+    Update(EndpointPoolId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Pool name already exists.")
+            }
+            ById(_, NotFound) | Delete(_, NotFound) | Update(_, NotFound) => {
+                Status::not_found("Endpoint pool not found.")
+            }
+            Node(err) => (*err).into(),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct EndpointPoolId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = endpoint_pools)]
+pub struct EndpointPool {
+    pub id: EndpointPoolId,
+    pub org_id: OrgId,
+    /// The full DNS name of the pool.
+    pub name: String,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl EndpointPool {
+    pub async fn by_id(id: EndpointPoolId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        endpoint_pools::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        endpoint_pools::table
+            .filter(endpoint_pools::org_id.eq(org_id))
+            .order_by(endpoint_pools::name)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        endpoint_pools::table
+            .order_by(endpoint_pools::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::All)
+    }
+
+    /// Mark the members of the pool as changed.
+    pub async fn touch(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        diesel::update(endpoint_pools::table.find(self.id))
+            .set(endpoint_pools::updated_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(self.id, err))
+    }
+
+    /// Delete the pool and its members, whose DNS records must already be
+    /// removed.
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(endpoint_pools::table.find(self.id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(self.id, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+
+    /// Bring the DNS records of the pool in line with the health of its
+    /// members.
+    pub async fn rotate(
+        &self,
+        dns: &(dyn Dns + Send + Sync),
+        conn: &mut Conn<'_>,
+    ) -> Result<Rotation, Error> {
+        let members = PoolMember::by_pool_id(self.id, conn).await?;
+        let node_ids = members.iter().map(|member| member.node_id).collect();
+        let nodes: HashMap<NodeId, Node> = Node::by_ids(&node_ids, conn)
+            .await
+            .map_err(|err| Error::Node(Box::new(err)))?
+            .into_iter()
+            .map(|node| (node.id, node))
+            .collect();
+
+        let healthy = |member: &PoolMember| nodes.get(&member.node_id).is_some_and(is_serving);
+        let any_healthy = members.iter().any(healthy);
+
+        let mut rotation = Rotation::default();
+        for member in members.iter().filter(|m| m.dns_id.is_none() && healthy(m)) {
+            let ip = nodes[&member.node_id].ip_address.ip();
+            let record = dns
+                .create_alias(&self.name, AliasTarget::Address(ip))
+                .await?;
+            if let Err(err) = member.set_dns_id(Some(&record.id), conn).await {
+                if let Err(err) = dns.delete(&record.id).await {
+                    warn!("Failed to delete DNS record {}: {err}", record.id);
+                }
+                return Err(err);
+            }
+            rotation.added.push(member.node_id);
+        }
+
+        for member in &members {
+            let Some(dns_id) = &member.dns_id else {
+                continue;
+            };
+            // a deleted node may have its IP reused, so it is always removed
+            let deleted = !nodes.contains_key(&member.node_id);
+            if deleted || (any_healthy && !healthy(member)) {
+                match dns.delete(dns_id).await {
+                    Ok(()) => (),
+                    Err(err) if err.is_not_found() => (),
+                    Err(err) => return Err(err.into()),
+                }
+                member.set_dns_id(None, conn).await?;
+                rotation.removed.push(member.node_id);
+            }
+        }
+
+        Ok(rotation)
+    }
+
+    /// Remove the DNS records of the pool and delete it.
+    pub async fn remove(
+        &self,
+        dns: &(dyn Dns + Send + Sync),
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let members = PoolMember::by_pool_id(self.id, conn).await?;
+        for dns_id in members.iter().filter_map(|member| member.dns_id.as_ref()) {
+            match dns.delete(dns_id).await {
+                Ok(()) => (),
+                Err(err) if err.is_not_found() => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        self.delete(conn).await
+    }
+
+    pub fn into_api(self, members: Vec<PoolMember>) -> api::EndpointPool {
+        let created_by = common::Resource::from(self.created_by());
+        api::EndpointPool {
+            pool_id: self.id.to_string(),
+            org_id: self.org_id.to_string(),
+            name: self.name,
+            members: members.into_iter().map(Into::into).collect(),
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(self.created_at).into()),
+            updated_at: self.updated_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+/// Whether `node` should receive traffic of the pools it is a member of.
+fn is_serving(node: &Node) -> bool {
+    node.node_state == NodeState::Running && node.protocol_health != Some(NodeHealth::Unhealthy)
+}
+
+/// The members added to and removed from the DNS records of a pool.
+#[derive(Debug, Default)]
+pub struct Rotation {
+    pub added: Vec<NodeId>,
+    pub removed: Vec<NodeId>,
+}
+
+impl Rotation {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = endpoint_pool_members)]
+pub struct PoolMember {
+    pub pool_id: EndpointPoolId,
+    pub node_id: NodeId,
+    /// The DNS record of the node while it is in rotation.
+    pub dns_id: Option<String>,
+    /// When the node last entered or left rotation.
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Whether the node was created by the autoscaler of the pool, which may
+    /// delete it again.
+    pub autoscaled: bool,
+}
+
+impl PoolMember {
+    pub async fn by_pool_id(
+        pool_id: EndpointPoolId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        Self::by_pool_ids(&[pool_id], conn).await
+    }
+
+    pub async fn by_pool_ids(
+        pool_ids: &[EndpointPoolId],
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        endpoint_pool_members::table
+            .filter(endpoint_pool_members::pool_id.eq_any(pool_ids))
+            .order_by(endpoint_pool_members::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::Members)
+    }
+
+    pub async fn by_node_id(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        endpoint_pool_members::table
+            .filter(endpoint_pool_members::node_id.eq(node_id))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::NodeMembers(node_id, err))
+    }
+
+    /// The DNS records of all members in rotation.
+    pub async fn dns_ids(conn: &mut Conn<'_>) -> Result<HashSet<String>, Error> {
+        endpoint_pool_members::table
+            .filter(endpoint_pool_members::dns_id.is_not_null())
+            .select(endpoint_pool_members::dns_id.assume_not_null())
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<String>| ids.into_iter().collect())
+            .map_err(Error::DnsIds)
+    }
+
+    /// Add `node_ids` to a pool, ignoring those that are already members.
+    pub async fn add(
+        pool_id: EndpointPoolId,
+        node_ids: &[NodeId],
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        if node_ids.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<_> = node_ids
+            .iter()
+            .map(|node_id| {
+                (
+                    endpoint_pool_members::pool_id.eq(pool_id),
+                    endpoint_pool_members::node_id.eq(node_id),
+                )
+            })
+            .collect();
+
+        diesel::insert_into(endpoint_pool_members::table)
+            .values(rows)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::AddMembers(pool_id, err))
+    }
+
+    /// Add a node created by the autoscaler of the pool.
+    pub async fn add_autoscaled(
+        pool_id: EndpointPoolId,
+        node_id: NodeId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(endpoint_pool_members::table)
+            .values((
+                endpoint_pool_members::pool_id.eq(pool_id),
+                endpoint_pool_members::node_id.eq(node_id),
+                endpoint_pool_members::autoscaled.eq(true),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::AddMembers(pool_id, err))
+    }
+
+    /// Remove `node_ids` from a pool, returning the removed members so that
+    /// their DNS records can be deleted.
+    pub async fn remove(
+        pool_id: EndpointPoolId,
+        node_ids: &[NodeId],
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let members = endpoint_pool_members::table
+            .filter(endpoint_pool_members::pool_id.eq(pool_id))
+            .filter(endpoint_pool_members::node_id.eq_any(node_ids));
+
+        diesel::delete(members)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::RemoveMembers(pool_id, err))
+    }
+
+    pub async fn delete_for_node(node_id: NodeId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let members =
+            endpoint_pool_members::table.filter(endpoint_pool_members::node_id.eq(node_id));
+        diesel::delete(members)
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::DeleteForNode(node_id, err))
+    }
+
+    async fn set_dns_id(&self, dns_id: Option<&str>, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let member = endpoint_pool_members::table.find((self.pool_id, self.node_id));
+        diesel::update(member)
+            .set((
+                endpoint_pool_members::dns_id.eq(dns_id),
+                endpoint_pool_members::rotated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::SetDnsId(self.pool_id, self.node_id, err))
+    }
+}
+
+impl From<PoolMember> for api::EndpointPoolMember {
+    fn from(member: PoolMember) -> Self {
+        api::EndpointPoolMember {
+            node_id: member.node_id.to_string(),
+            in_rotation: member.dns_id.is_some(),
+            rotated_at: member.rotated_at.map(NanosUtc::from).map(Into::into),
+            autoscaled: member.autoscaled,
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = endpoint_pools)]
+pub struct NewEndpointPool {
+    pub org_id: OrgId,
+    pub name: String,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+}
+
+impl NewEndpointPool {
+    pub fn new(org_id: OrgId, name: String, created_by: Resource) -> Self {
+        NewEndpointPool {
+            org_id,
+            name,
+            created_by_type: created_by.typ(),
+            created_by_id: created_by.id(),
+        }
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<EndpointPool, Error> {
+        diesel::insert_into(endpoint_pools::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
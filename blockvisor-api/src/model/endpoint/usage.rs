@@ -0,0 +1,185 @@
+//! Hourly rollups of the requests served for an endpoint by the gateway.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::resource::NodeId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{endpoint_usage, node_endpoints};
+use crate::model::sql::greatest;
+use crate::util::NanosUtc;
+
+use super::EndpointId;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find usage for endpoint `{0}`: {1}
+    ByEndpoint(EndpointId, diesel::result::Error),
+    /// Failed to find usage for the endpoints of nodes: {0}
+    ByNodes(diesel::result::Error),
+    /// Failed to record usage for endpoint `{0}`: {1}
+    Record(EndpointId, diesel::result::Error),
+    /// Failed to truncate usage timestamp: {0}
+    Truncate(chrono::RoundingError),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Truncate(_) => Status::invalid_argument("timestamp"),
+            ByEndpoint(..) | ByNodes(_) | Record(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = endpoint_usage)]
+pub struct EndpointUsage {
+    pub endpoint_id: EndpointId,
+    pub hour: DateTime<Utc>,
+    pub request_count: i64,
+    pub error_count: i64,
+    pub latency_ms_total: i64,
+    pub latency_ms_max: i64,
+}
+
+impl EndpointUsage {
+    /// Find the hourly usage of an endpoint from `start` until `end`.
+    pub async fn by_endpoint(
+        endpoint_id: EndpointId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        endpoint_usage::table
+            .filter(endpoint_usage::endpoint_id.eq(endpoint_id))
+            .filter(endpoint_usage::hour.ge(start))
+            .filter(endpoint_usage::hour.lt(end))
+            .order_by(endpoint_usage::hour.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByEndpoint(endpoint_id, err))
+    }
+
+    /// The number of requests served for an endpoint since `since`.
+    pub async fn requests_since(
+        endpoint_id: EndpointId,
+        since: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<i64, Error> {
+        let counts: Vec<i64> = endpoint_usage::table
+            .filter(endpoint_usage::endpoint_id.eq(endpoint_id))
+            .filter(endpoint_usage::hour.ge(since))
+            .select(endpoint_usage::request_count)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByEndpoint(endpoint_id, err))?;
+
+        Ok(counts.into_iter().sum())
+    }
+
+    /// The number of requests served since `since` for the endpoints of
+    /// `node_ids`, including revoked ones.
+    pub async fn node_requests_since(
+        node_ids: &[NodeId],
+        since: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<i64, Error> {
+        let endpoint_ids = node_endpoints::table
+            .filter(node_endpoints::node_id.eq_any(node_ids))
+            .select(node_endpoints::id);
+        let counts: Vec<i64> = endpoint_usage::table
+            .filter(endpoint_usage::endpoint_id.eq_any(endpoint_ids))
+            .filter(endpoint_usage::hour.ge(since))
+            .select(endpoint_usage::request_count)
+            .get_results(conn)
+            .await
+            .map_err(Error::ByNodes)?;
+
+        Ok(counts.into_iter().sum())
+    }
+}
+
+impl From<EndpointUsage> for api::EndpointUsage {
+    fn from(usage: EndpointUsage) -> Self {
+        let latency_ms_avg = if usage.request_count > 0 {
+            usage.latency_ms_total / usage.request_count
+        } else {
+            0
+        };
+
+        api::EndpointUsage {
+            hour: Some(NanosUtc::from(usage.hour).into()),
+            request_count: u64::try_from(usage.request_count).unwrap_or_default(),
+            error_count: u64::try_from(usage.error_count).unwrap_or_default(),
+            latency_ms_avg: u64::try_from(latency_ms_avg).unwrap_or_default(),
+            latency_ms_max: u64::try_from(usage.latency_ms_max).unwrap_or_default(),
+        }
+    }
+}
+
+/// Usage reported by the gateway, added to the rollup of its hour.
+#[derive(Debug, Insertable)]
+#[diesel(table_name = endpoint_usage)]
+pub struct UsageReport {
+    pub endpoint_id: EndpointId,
+    pub hour: DateTime<Utc>,
+    pub request_count: i64,
+    pub error_count: i64,
+    pub latency_ms_total: i64,
+    pub latency_ms_max: i64,
+}
+
+impl UsageReport {
+    pub fn new(
+        endpoint_id: EndpointId,
+        at: DateTime<Utc>,
+        request_count: i64,
+        error_count: i64,
+        latency_ms_total: i64,
+        latency_ms_max: i64,
+    ) -> Result<Self, Error> {
+        let hour = at
+            .duration_trunc(TimeDelta::hours(1))
+            .map_err(Error::Truncate)?;
+
+        Ok(UsageReport {
+            endpoint_id,
+            hour,
+            request_count,
+            error_count,
+            latency_ms_total,
+            latency_ms_max,
+        })
+    }
+
+    pub async fn record(self, conn: &mut Conn<'_>) -> Result<EndpointUsage, Error> {
+        let endpoint_id = self.endpoint_id;
+        diesel::insert_into(endpoint_usage::table)
+            .values(self)
+            .on_conflict((endpoint_usage::endpoint_id, endpoint_usage::hour))
+            .do_update()
+            .set((
+                endpoint_usage::request_count
+                    .eq(endpoint_usage::request_count + excluded(endpoint_usage::request_count)),
+                endpoint_usage::error_count
+                    .eq(endpoint_usage::error_count + excluded(endpoint_usage::error_count)),
+                endpoint_usage::latency_ms_total
+                    .eq(endpoint_usage::latency_ms_total
+                        + excluded(endpoint_usage::latency_ms_total)),
+                endpoint_usage::latency_ms_max.eq(greatest(
+                    endpoint_usage::latency_ms_max,
+                    excluded(endpoint_usage::latency_ms_max),
+                )),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Record(endpoint_id, err))
+    }
+}
@@ -0,0 +1,338 @@
+//! Feature flags for rolling out risky features gradually.
+//!
+//! A flag is evaluated for an org and user: it is on for anyone in its org or
+//! user allowlists, and otherwise for `rollout_percent` of orgs (or users when
+//! there is no org). Each subject is hashed with the flag name so that raising
+//! the percentage only ever adds subjects, and different flags roll out to
+//! different subjects.
+//!
+//! A disabled flag is off for everyone, including the allowlists.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::resource::{OrgId, UserId};
+use crate::config::flag::Config;
+use crate::database::{Conn, Database, Pool};
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::feature_flags;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find all feature flags: {0}
+    All(diesel::result::Error),
+    /// Failed to find feature flag `{0}`: {1}
+    ByName(String, diesel::result::Error),
+    /// Feature flag database error: {0}
+    Database(#[from] crate::database::Error),
+    /// Failed to upsert feature flag `{0}`: {1}
+    Upsert(String, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByName(_, diesel::result::Error::NotFound) => {
+                Status::not_found("Feature flag not found.")
+            }
+            All(_) | ByName(..) | Database(_) | Upsert(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Display,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    DieselNewType,
+    Deref,
+    From,
+    FromStr,
+)]
+pub struct FeatureFlagId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = feature_flags)]
+pub struct FeatureFlag {
+    pub id: FeatureFlagId,
+    pub name: String,
+    pub description: Option<String>,
+    pub enabled: bool,
+    pub rollout_percent: i32,
+    pub org_ids: Vec<Option<OrgId>>,
+    pub user_ids: Vec<Option<UserId>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FeatureFlag {
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        feature_flags::table
+            .order_by(feature_flags::name.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::All)
+    }
+
+    pub async fn by_name(name: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        feature_flags::table
+            .filter(feature_flags::name.eq(name))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByName(name.to_string(), err))
+    }
+
+    /// Whether this flag is on for the given org and user.
+    pub fn evaluate(&self, org_id: Option<OrgId>, user_id: Option<UserId>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        let allowed_org = org_id.is_some_and(|id| self.org_ids.contains(&Some(id)));
+        let allowed_user = user_id.is_some_and(|id| self.user_ids.contains(&Some(id)));
+        if allowed_org || allowed_user {
+            return true;
+        }
+
+        match org_id.map(|id| *id).or_else(|| user_id.map(|id| *id)) {
+            Some(subject) => i64::from(self.bucket(subject)) < i64::from(self.rollout_percent),
+            None => self.rollout_percent >= 100,
+        }
+    }
+
+    /// A stable bucket in `0..100` for `subject` under this flag.
+    fn bucket(&self, subject: Uuid) -> u32 {
+        let mut hasher = Sha1::new();
+        hasher.update(self.name.as_bytes());
+        hasher.update(subject.as_bytes());
+        let hash = hasher.finalize();
+        u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]) % 100
+    }
+}
+
+impl From<FeatureFlag> for api::FeatureFlag {
+    fn from(flag: FeatureFlag) -> Self {
+        api::FeatureFlag {
+            name: flag.name,
+            description: flag.description,
+            enabled: flag.enabled,
+            rollout_percent: u32::try_from(flag.rollout_percent).unwrap_or_default(),
+            org_ids: flag
+                .org_ids
+                .iter()
+                .flatten()
+                .map(ToString::to_string)
+                .collect(),
+            user_ids: flag
+                .user_ids
+                .iter()
+                .flatten()
+                .map(ToString::to_string)
+                .collect(),
+            created_at: Some(NanosUtc::from(flag.created_at).into()),
+            updated_at: Some(NanosUtc::from(flag.updated_at).into()),
+        }
+    }
+}
+
+/// Create a flag, or replace the settings of an existing flag by name.
+#[derive(Debug, Insertable)]
+#[diesel(table_name = feature_flags)]
+pub struct UpsertFeatureFlag {
+    pub name: String,
+    pub description: Option<String>,
+    pub enabled: bool,
+    pub rollout_percent: i32,
+    pub org_ids: Vec<Option<OrgId>>,
+    pub user_ids: Vec<Option<UserId>>,
+}
+
+impl UpsertFeatureFlag {
+    pub async fn upsert(self, conn: &mut Conn<'_>) -> Result<FeatureFlag, Error> {
+        let name = self.name.clone();
+        diesel::insert_into(feature_flags::table)
+            .values(self)
+            .on_conflict(feature_flags::name)
+            .do_update()
+            .set((
+                feature_flags::description.eq(excluded(feature_flags::description)),
+                feature_flags::enabled.eq(excluded(feature_flags::enabled)),
+                feature_flags::rollout_percent.eq(excluded(feature_flags::rollout_percent)),
+                feature_flags::org_ids.eq(excluded(feature_flags::org_ids)),
+                feature_flags::user_ids.eq(excluded(feature_flags::user_ids)),
+                feature_flags::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Upsert(name, err))
+    }
+}
+
+/// The feature flags of `Context::flags`, cached for `Config::refresh`.
+///
+/// Flags are loaded with a separate connection, so evaluating a flag does not
+/// depend on the transaction of the caller. If reloading fails, the last known
+/// flags are kept until the next refresh.
+pub struct Flags {
+    pool: Pool,
+    refresh: Duration,
+    cache: RwLock<Cache>,
+}
+
+#[derive(Default)]
+struct Cache {
+    flags: HashMap<String, FeatureFlag>,
+    loaded_at: Option<Instant>,
+}
+
+impl Cache {
+    fn is_fresh(&self, refresh: Duration) -> bool {
+        self.loaded_at
+            .is_some_and(|loaded_at| loaded_at.elapsed() < refresh)
+    }
+}
+
+impl Flags {
+    pub fn new(pool: Pool, config: &Config) -> Self {
+        Flags {
+            pool,
+            refresh: *config.refresh,
+            cache: RwLock::new(Cache::default()),
+        }
+    }
+
+    /// Whether flag `name` is on for the given org and user.
+    ///
+    /// Unknown flags are off.
+    pub async fn enabled(
+        &self,
+        name: &str,
+        org_id: Option<OrgId>,
+        user_id: Option<UserId>,
+    ) -> bool {
+        self.refresh().await;
+        self.cache
+            .read()
+            .await
+            .flags
+            .get(name)
+            .is_some_and(|flag| flag.evaluate(org_id, user_id))
+    }
+
+    /// The cached flag `name`, for evaluating it later without awaiting.
+    pub async fn get(&self, name: &str) -> Option<FeatureFlag> {
+        self.refresh().await;
+        self.cache.read().await.flags.get(name).cloned()
+    }
+
+    /// Reload the flags on next use, e.g. after a flag was changed.
+    pub async fn invalidate(&self) {
+        self.cache.write().await.loaded_at = None;
+    }
+
+    async fn refresh(&self) {
+        if self.cache.read().await.is_fresh(self.refresh) {
+            return;
+        }
+
+        let mut cache = self.cache.write().await;
+        if cache.is_fresh(self.refresh) {
+            return;
+        }
+
+        match self.load().await {
+            Ok(flags) => cache.flags = flags,
+            Err(err) => warn!("Failed to reload feature flags: {err}"),
+        }
+        cache.loaded_at = Some(Instant::now());
+    }
+
+    async fn load(&self) -> Result<HashMap<String, FeatureFlag>, Error> {
+        let mut conn = self.pool.conn().await?;
+        let flags = FeatureFlag::all(&mut conn).await?;
+        Ok(flags
+            .into_iter()
+            .map(|flag| (flag.name.clone(), flag))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag(enabled: bool, rollout_percent: i32) -> FeatureFlag {
+        FeatureFlag {
+            id: Uuid::new_v4().into(),
+            name: "rebalancer".to_string(),
+            description: None,
+            enabled,
+            rollout_percent,
+            org_ids: vec![],
+            user_ids: vec![],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn rollouts_are_stable_and_grow_with_the_percentage() {
+        let orgs: Vec<OrgId> = (0..1000).map(|_| Uuid::new_v4().into()).collect();
+        let count = |flag: &FeatureFlag| {
+            orgs.iter()
+                .filter(|&&org_id| flag.evaluate(Some(org_id), None))
+                .count()
+        };
+
+        assert_eq!(count(&flag(true, 0)), 0);
+        assert_eq!(count(&flag(true, 100)), orgs.len());
+        assert_eq!(count(&flag(false, 100)), 0);
+
+        let half = flag(true, 50);
+        let quarter = flag(true, 25);
+        assert!((400..600).contains(&count(&half)));
+        for &org_id in &orgs {
+            if quarter.evaluate(Some(org_id), None) {
+                assert!(half.evaluate(Some(org_id), None));
+            }
+        }
+    }
+
+    #[test]
+    fn allowlists_override_the_rollout_unless_disabled() {
+        let org_id: OrgId = Uuid::new_v4().into();
+        let user_id: UserId = Uuid::new_v4().into();
+
+        let mut flag = flag(true, 0);
+        flag.org_ids = vec![Some(org_id)];
+        flag.user_ids = vec![Some(user_id)];
+        assert!(flag.evaluate(Some(org_id), None));
+        assert!(flag.evaluate(None, Some(user_id)));
+        assert!(!flag.evaluate(None, None));
+
+        flag.enabled = false;
+        assert!(!flag.evaluate(Some(org_id), Some(user_id)));
+    }
+}
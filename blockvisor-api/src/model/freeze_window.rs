@@ -0,0 +1,277 @@
+//! Change freeze windows of an org.
+//!
+//! A freeze window recurs every week, from `start_minute` to `end_minute` of
+//! the UTC week starting on Monday 00:00. A window ending before it starts
+//! wraps over the end of the week, so Friday 18:00 to Monday 08:00 is a single
+//! window. While a window is open, nodes of the org may not be deleted or
+//! upgraded and the org may not be deleted, unless the caller has the
+//! `OrgPerm::FreezeOverride` permission.
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::AuthZ;
+use crate::auth::rbac::OrgPerm;
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::freeze_windows;
+
+/// The number of minutes in a day.
+const DAY_MINUTES: u32 = 24 * 60;
+
+/// The status reason of an operation that is blocked by a freeze window.
+const CHANGE_FREEZE: &str = "CHANGE_FREEZE";
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find freeze window `{0}`: {1}
+    ById(FreezeWindowId, diesel::result::Error),
+    /// Failed to find freeze windows of org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Failed to create freeze window: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete freeze window `{0}`: {1}
+    Delete(FreezeWindowId, diesel::result::Error),
+    /// Freeze window must not start and end at the same minute.
+    Empty,
+    /// Org `{0}` is in freeze window `{1}`.
+    Frozen(OrgId, FreezeWindowId),
+    /// Freeze window day must be 0 (Monday) to 6 (Sunday), not {0}.
+    InvalidDay(u32),
+    /// Freeze window minute must be less than 1440, not {0}.
+    InvalidMinute(u32),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Delete(_, NotFound) => {
+                Status::not_found("Freeze window not found.")
+            }
+            Empty => Status::invalid_argument("end_minute"),
+            Frozen(..) => {
+                Status::failed_precondition("Org is in a change freeze.").with_reason(CHANGE_FREEZE)
+            }
+            InvalidDay(_) => Status::invalid_argument("day"),
+            InvalidMinute(_) => Status::invalid_argument("minute"),
+            ById(..) | ByOrg(..) | Create(_) | Delete(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct FreezeWindowId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct FreezeWindow {
+    pub id: FreezeWindowId,
+    pub org_id: OrgId,
+    /// The minute of the UTC week that the window opens at.
+    pub start_minute: i32,
+    /// The minute of the UTC week that the window closes at.
+    pub end_minute: i32,
+    pub reason: Option<String>,
+    pub created_by: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl FreezeWindow {
+    pub async fn by_id(id: FreezeWindowId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        freeze_windows::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        freeze_windows::table
+            .filter(freeze_windows::org_id.eq(org_id))
+            .order_by(freeze_windows::start_minute.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let id = self.id;
+        diesel::delete(freeze_windows::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+
+    /// Returns an error if org `org_id` is in a freeze window that `authz` may
+    /// not override.
+    pub async fn check(org_id: OrgId, authz: &AuthZ, conn: &mut Conn<'_>) -> Result<(), Error> {
+        if authz.has_perm(OrgPerm::FreezeOverride) {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let windows = Self::by_org(org_id, conn).await?;
+        match windows.iter().find(|window| window.is_open(now)) {
+            Some(window) => Err(Error::Frozen(org_id, window.id)),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the window is open at `at`.
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        let minute = i32::try_from(week_minute(at)).unwrap_or_default();
+        if self.start_minute < self.end_minute {
+            self.start_minute <= minute && minute < self.end_minute
+        } else {
+            self.start_minute <= minute || minute < self.end_minute
+        }
+    }
+}
+
+impl From<FreezeWindow> for api::FreezeWindow {
+    fn from(window: FreezeWindow) -> Self {
+        let start = u32::try_from(window.start_minute).unwrap_or_default();
+        let end = u32::try_from(window.end_minute).unwrap_or_default();
+        api::FreezeWindow {
+            window_id: window.id.to_string(),
+            org_id: window.org_id.to_string(),
+            start_day: start / DAY_MINUTES,
+            start_minute: start % DAY_MINUTES,
+            end_day: end / DAY_MINUTES,
+            end_minute: end % DAY_MINUTES,
+            reason: window.reason,
+            created_by: window.created_by.map(|id| id.to_string()),
+            created_at: Some(NanosUtc::from(window.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = freeze_windows)]
+pub struct NewFreezeWindow {
+    pub org_id: OrgId,
+    pub start_minute: i32,
+    pub end_minute: i32,
+    pub reason: Option<String>,
+    pub created_by: Option<UserId>,
+}
+
+impl NewFreezeWindow {
+    /// A window from `start_minute` of `start_day` to `end_minute` of
+    /// `end_day`, where days count from 0 for Monday.
+    pub fn new(
+        org_id: OrgId,
+        (start_day, start_minute): (u32, u32),
+        (end_day, end_minute): (u32, u32),
+        reason: Option<String>,
+        created_by: Option<UserId>,
+    ) -> Result<Self, Error> {
+        let start_minute = day_minute(start_day, start_minute)?;
+        let end_minute = day_minute(end_day, end_minute)?;
+        if start_minute == end_minute {
+            return Err(Error::Empty);
+        }
+
+        Ok(NewFreezeWindow {
+            org_id,
+            start_minute,
+            end_minute,
+            reason,
+            created_by,
+        })
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<FreezeWindow, Error> {
+        diesel::insert_into(freeze_windows::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+/// The minute of the UTC week of `minute` on `day`.
+fn day_minute(day: u32, minute: u32) -> Result<i32, Error> {
+    if day > 6 {
+        return Err(Error::InvalidDay(day));
+    } else if minute >= DAY_MINUTES {
+        return Err(Error::InvalidMinute(minute));
+    }
+
+    // less than a week of minutes always fits an i32
+    Ok(i32::try_from(day * DAY_MINUTES + minute).unwrap_or_default())
+}
+
+/// The minute of the UTC week starting on Monday 00:00 for `at`.
+fn week_minute(at: DateTime<Utc>) -> u32 {
+    at.weekday().num_days_from_monday() * DAY_MINUTES + at.hour() * 60 + at.minute()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn window(start: (u32, u32), end: (u32, u32)) -> FreezeWindow {
+        let org_id = Uuid::new_v4().into();
+        let new = NewFreezeWindow::new(org_id, start, end, None, None).unwrap();
+        FreezeWindow {
+            id: Uuid::new_v4().into(),
+            org_id: new.org_id,
+            start_minute: new.start_minute,
+            end_minute: new.end_minute,
+            reason: None,
+            created_by: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn window_wraps_over_the_weekend() {
+        // Friday 18:00 to Monday 08:00
+        let weekend = window((4, 18 * 60), (0, 8 * 60));
+        // Wednesday 12:00 to 14:00
+        let midweek = window((2, 12 * 60), (2, 14 * 60));
+
+        // 2025-05-30 is a Friday
+        let friday_evening = Utc.with_ymd_and_hms(2025, 5, 30, 19, 0, 0).unwrap();
+        let sunday = Utc.with_ymd_and_hms(2025, 6, 1, 12, 0, 0).unwrap();
+        let monday_morning = Utc.with_ymd_and_hms(2025, 6, 2, 7, 59, 0).unwrap();
+        let monday_noon = Utc.with_ymd_and_hms(2025, 6, 2, 12, 0, 0).unwrap();
+        let wednesday = Utc.with_ymd_and_hms(2025, 6, 4, 13, 0, 0).unwrap();
+
+        assert!(weekend.is_open(friday_evening));
+        assert!(weekend.is_open(sunday));
+        assert!(weekend.is_open(monday_morning));
+        assert!(!weekend.is_open(monday_noon));
+        assert!(!weekend.is_open(wednesday));
+
+        assert!(midweek.is_open(wednesday));
+        assert!(!midweek.is_open(sunday));
+    }
+
+    #[test]
+    fn invalid_windows_are_rejected() {
+        let org_id: OrgId = Uuid::new_v4().into();
+        let new = |start, end| NewFreezeWindow::new(org_id, start, end, None, None);
+
+        assert!(matches!(new((7, 0), (0, 60)), Err(Error::InvalidDay(7))));
+        assert!(matches!(
+            new((0, 1440), (1, 0)),
+            Err(Error::InvalidMinute(1440))
+        ));
+        assert!(matches!(new((3, 60), (3, 60)), Err(Error::Empty)));
+    }
+}
@@ -7,7 +7,7 @@ use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::result::DatabaseErrorKind::UniqueViolation;
 use diesel::result::Error::{DatabaseError, NotFound};
-use diesel::sql_types::{Bool, Nullable};
+use diesel::sql_types::{Bool, Double, Nullable};
 use diesel_async::RunQueryDsl;
 use diesel_derive_enum::DbEnum;
 use displaydoc::Display;
@@ -20,9 +20,9 @@ use crate::model::sql::{self, Amount, IpNetwork, Tags, Version, greatest};
 use crate::util::{SearchOperator, SortOrder};
 
 use super::ip_address::NewIpAddress;
-use super::node::{NodeScheduler, ResourceAffinity, SimilarNodeAffinity};
+use super::node::{NodeResources, NodeScheduler, PlacementPolicy};
 use super::schema::{hosts, ip_addresses, nodes, sql_types};
-use super::{Command, Node, Org, Paginate, Protocol, RegionId};
+use super::{Command, HostPoolId, HostWeights, Node, Org, Paginate, Protocol, RegionId};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -72,6 +72,10 @@ pub enum Error {
     ParseIp(std::net::AddrParseError),
     /// Failed to decrement node count for host `{0}`: {1}
     RemoveNode(HostId, diesel::result::Error),
+    /// Failed to resize a node on host `{0}`: {1}
+    ResizeNode(HostId, diesel::result::Error),
+    /// Failed to set the pool of host `{0}`: {1}
+    SetPool(HostId, diesel::result::Error),
     /// Unknown ConnectionStatus.
     UnknownConnectionStatus,
     /// Unknown ScheduleType.
@@ -80,6 +84,8 @@ pub enum Error {
     Update(diesel::result::Error),
     /// Failed to update metrics for host `{0}`: {1}
     UpdateMetrics(HostId, diesel::result::Error),
+    /// Failed to find host versions: {0}
+    Versions(diesel::result::Error),
 }
 
 impl From<Error> for Status {
@@ -87,13 +93,16 @@ impl From<Error> for Status {
         use Error::*;
         match err {
             Create(DatabaseError(UniqueViolation, _)) => {
-                Status::already_exists("Host already exists.")
+                Status::already_exists("Host already exists.").with_reason("HOST_EXISTS")
             }
             Delete(_, NotFound)
             | FindById(_, NotFound)
             | FindByIds(_, NotFound)
             | FindDeletedOrgId(_, NotFound)
-            | FindOrgId(_, NotFound) => Status::not_found("Host not found."),
+            | FindOrgId(_, NotFound)
+            | SetPool(_, NotFound) => {
+                Status::not_found("Host not found.").with_reason("HOST_NOT_FOUND")
+            }
             BillingMissingAmount | BillingCurrencyUnknown | BillingPeriodUnknown => {
                 Status::invalid_argument("billing_amount")
             }
@@ -150,6 +159,12 @@ pub struct Host {
     pub updated_at: Option<DateTime<Utc>>,
     pub deleted_at: Option<DateTime<Utc>>,
     pub cost: Option<Amount>,
+    pub pool_id: Option<HostPoolId>,
+    pub gpu_count: i64,
+    pub gpu_model: Option<String>,
+    pub nvme_iops: Option<i64>,
+    pub network_bandwidth_mbps: Option<i64>,
+    pub node_gpu_count: i64,
 }
 
 impl Host {
@@ -181,6 +196,25 @@ impl Host {
             .map_err(|err| Error::FindByIds(ids.clone(), err))
     }
 
+    /// The agent and OS versions of each host, optionally of a single org.
+    pub async fn versions(
+        org_id: Option<OrgId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<HostVersion>, Error> {
+        let mut query = hosts::table
+            .filter(hosts::deleted_at.is_null())
+            .into_boxed();
+        if let Some(org_id) = org_id {
+            query = query.filter(hosts::org_id.eq(org_id));
+        }
+
+        query
+            .select(HostVersion::as_select())
+            .get_results(conn)
+            .await
+            .map_err(Error::Versions)
+    }
+
     pub async fn org_id(id: HostId, conn: &mut Conn<'_>) -> Result<Option<OrgId>, Error> {
         hosts::table
             .find(id)
@@ -207,12 +241,51 @@ impl Host {
                 hosts::node_cpu_cores.eq(hosts::node_cpu_cores + node.cpu_cores),
                 hosts::node_memory_bytes.eq(hosts::node_memory_bytes + node.memory_bytes),
                 hosts::node_disk_bytes.eq(hosts::node_disk_bytes + node.disk_bytes),
+                hosts::node_gpu_count.eq(hosts::node_gpu_count + node.gpu_count),
             ))
             .get_result(conn)
             .await
             .map_err(|err| Error::AddNode(node.host_id, err))
     }
 
+    /// Account for the resources of `node` changing by `added`.
+    pub async fn resize_node(
+        node: &Node,
+        added: NodeResources,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::update(hosts::table.find(node.host_id))
+            .set((
+                hosts::node_cpu_cores.eq(greatest(0, hosts::node_cpu_cores + added.cpu_cores)),
+                hosts::node_memory_bytes
+                    .eq(greatest(0, hosts::node_memory_bytes + added.memory_bytes)),
+                hosts::node_disk_bytes.eq(greatest(0, hosts::node_disk_bytes + added.disk_bytes)),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ResizeNode(node.host_id, err))
+    }
+
+    /// Add a private host of `org_id` to a pool, or remove it from its pool.
+    ///
+    /// Public hosts and hosts of other orgs are not found.
+    pub async fn set_pool(
+        id: HostId,
+        org_id: OrgId,
+        pool_id: Option<HostPoolId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let row = hosts::table
+            .find(id)
+            .filter(hosts::org_id.eq(org_id))
+            .filter(hosts::deleted_at.is_null());
+        diesel::update(row)
+            .set((hosts::pool_id.eq(pool_id), hosts::updated_at.eq(Utc::now())))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::SetPool(id, err))
+    }
+
     pub async fn remove_node(node: &Node, conn: &mut Conn<'_>) -> Result<Self, Error> {
         diesel::update(hosts::table.find(node.host_id))
             .set((
@@ -221,6 +294,7 @@ impl Host {
                 hosts::node_memory_bytes
                     .eq(greatest(0, hosts::node_memory_bytes - node.memory_bytes)),
                 hosts::node_disk_bytes.eq(greatest(0, hosts::node_disk_bytes - node.disk_bytes)),
+                hosts::node_gpu_count.eq(greatest(0, hosts::node_gpu_count - node.gpu_count)),
             ))
             .get_result(conn)
             .await
@@ -262,6 +336,7 @@ impl Host {
         let free_cpu = hosts::cpu_cores - hosts::node_cpu_cores;
         let free_memory = hosts::memory_bytes - hosts::node_memory_bytes;
         let free_disk = hosts::disk_bytes - hosts::node_disk_bytes;
+        let free_gpu = hosts::gpu_count - hosts::node_gpu_count;
         let free_ips = ip_addresses::table
             .filter(ip_addresses::host_id.eq(hosts::id))
             .filter(not(exists(
@@ -289,39 +364,42 @@ impl Host {
             .order_by(sql::<Bool>(&tag_order).desc())
             .into_boxed();
 
-        if let Some(org_id) = require.org_id {
-            query = query.filter(hosts::org_id.eq(org_id).or(hosts::org_id.is_null()));
-        } else {
-            query = query.filter(hosts::org_id.is_null());
+        query = match (require.org_id, require.scheduler.placement()) {
+            (Some(org_id), PlacementPolicy::Any) => {
+                query.filter(hosts::org_id.eq(org_id).or(hosts::org_id.is_null()))
+            }
+            (Some(org_id), PlacementPolicy::Private) => query.filter(hosts::org_id.eq(org_id)),
+            (None, _) | (_, PlacementPolicy::Public) => query.filter(hosts::org_id.is_null()),
+        };
+
+        if let Some(pool_id) = require.scheduler.pool_id {
+            query = query.filter(hosts::pool_id.eq(pool_id));
         }
 
         if let Some(region_id) = require.scheduler.region.as_ref().map(|region| region.id) {
             query = query.filter(hosts::region_id.eq(region_id));
         }
 
-        if let Some(similarity) = require.scheduler.similarity {
-            let similar = nodes::table
-                .filter(nodes::host_id.eq(hosts::id))
-                .filter(nodes::protocol_id.eq(require.protocol.id))
-                .filter(nodes::deleted_at.is_null())
-                .select(count(nodes::id))
-                .single_value();
-
-            query = match similarity {
-                SimilarNodeAffinity::Cluster => query.then_order_by(similar.desc()),
-                SimilarNodeAffinity::Spread => query.then_order_by(similar),
-            };
+        if require.gpu_count > 0 {
+            query = query.filter(free_gpu.ge(require.gpu_count));
+        } else {
+            // keep hosts with free GPUs for the nodes that need them
+            query = query.then_order_by(free_gpu);
+        }
+        if let Some(gpu_model) = require.gpu_model {
+            query = query.filter(hosts::gpu_model.eq(gpu_model));
+        }
+        if let Some(nvme_iops) = require.nvme_iops {
+            query = query.filter(hosts::nvme_iops.ge(nvme_iops));
+        }
+        if let Some(bandwidth) = require.network_bandwidth_mbps {
+            query = query.filter(hosts::network_bandwidth_mbps.ge(bandwidth));
         }
 
-        query = match require.scheduler.resource {
-            Some(ResourceAffinity::MostResources) => {
-                query.then_order_by((free_cpu.desc(), free_memory.desc(), free_disk.desc()))
-            }
-            Some(ResourceAffinity::LeastResources) => {
-                query.then_order_by((free_cpu, free_memory, free_disk))
-            }
-            None => query,
-        };
+        let score = require
+            .weights
+            .score(require.scheduler, require.protocol.id);
+        query = query.then_order_by(sql::<Double>(&score).desc());
 
         if let Some(limit) = limit {
             query = query.limit(limit);
@@ -347,6 +425,20 @@ impl Host {
     }
 }
 
+/// The versions of the software running on a host.
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = hosts)]
+pub struct HostVersion {
+    pub id: HostId,
+    pub org_id: Option<OrgId>,
+    pub region_id: RegionId,
+    pub connection_status: ConnectionStatus,
+    pub bv_version: Version,
+    pub os: String,
+    pub os_version: String,
+}
+
+#[derive(Clone, Copy)]
 pub struct HostRequirements<'r> {
     pub scheduler: &'r NodeScheduler,
     pub protocol: &'r Protocol,
@@ -354,6 +446,11 @@ pub struct HostRequirements<'r> {
     pub cpu_cores: i64,
     pub memory_bytes: i64,
     pub disk_bytes: i64,
+    pub gpu_count: i64,
+    pub gpu_model: Option<&'r str>,
+    pub nvme_iops: Option<i64>,
+    pub network_bandwidth_mbps: Option<i64>,
+    pub weights: HostWeights,
 }
 
 pub struct HostCandidate {
@@ -361,6 +458,43 @@ pub struct HostCandidate {
     pub free_ips: u32,
 }
 
+impl HostCandidate {
+    /// How many more nodes meeting `require` could be scheduled on this host.
+    ///
+    /// A host remains a candidate only while its free resources exceed those
+    /// of a node, so a node needing exactly the remainder is not counted.
+    pub fn capacity(&self, require: &HostRequirements<'_>) -> u32 {
+        let host = &self.host;
+        let fits = |free: i64, needed: i64| {
+            if needed > 0 {
+                (free - 1).max(0) / needed
+            } else {
+                i64::MAX
+            }
+        };
+        let gpus = if require.gpu_count > 0 {
+            (host.gpu_count - host.node_gpu_count).max(0) / require.gpu_count
+        } else {
+            i64::MAX
+        };
+
+        let nodes = [
+            fits(host.cpu_cores - host.node_cpu_cores, require.cpu_cores),
+            fits(
+                host.memory_bytes - host.node_memory_bytes,
+                require.memory_bytes,
+            ),
+            fits(host.disk_bytes - host.node_disk_bytes, require.disk_bytes),
+            gpus,
+        ]
+        .into_iter()
+        .min()
+        .unwrap_or_default();
+
+        u32::try_from(nodes).unwrap_or(u32::MAX).min(self.free_ips)
+    }
+}
+
 #[derive(Debug, Clone, Insertable)]
 #[diesel(table_name = hosts)]
 pub struct NewHost<'a> {
@@ -377,6 +511,10 @@ pub struct NewHost<'a> {
     pub cpu_cores: i64,
     pub memory_bytes: i64,
     pub disk_bytes: i64,
+    pub gpu_count: i64,
+    pub gpu_model: Option<&'a str>,
+    pub nvme_iops: Option<i64>,
+    pub network_bandwidth_mbps: Option<i64>,
     pub tags: Tags,
     pub created_by_type: ResourceType,
     pub created_by_id: ResourceId,
@@ -408,7 +546,7 @@ impl NewHost<'_> {
 #[diesel(table_name = hosts)]
 pub struct UpdateHost<'a> {
     pub network_name: Option<&'a str>,
-    pub display_name: Option<&'a str>,
+    pub display_name: Option<Option<&'a str>>,
     pub region_id: Option<RegionId>,
     pub schedule_type: Option<ScheduleType>,
     pub connection_status: Option<ConnectionStatus>,
@@ -420,6 +558,10 @@ pub struct UpdateHost<'a> {
     pub cpu_cores: Option<i64>,
     pub memory_bytes: Option<i64>,
     pub disk_bytes: Option<i64>,
+    pub gpu_count: Option<i64>,
+    pub gpu_model: Option<Option<&'a str>>,
+    pub nvme_iops: Option<i64>,
+    pub network_bandwidth_mbps: Option<i64>,
     pub tags: Option<Tags>,
     pub cost: Option<Amount>,
 }
@@ -0,0 +1,236 @@
+//! Pools of private hosts registered by an org.
+//!
+//! A private host belongs to an org, and only nodes of that org are scheduled
+//! onto it. Pools group those hosts so a node can be placed on a specific set
+//! of the org's hardware, and report the capacity that remains on it. Nodes on
+//! private hosts are never billed, so pools report capacity rather than cost.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel::sql_types::{Array, BigInt, Uuid as SqlUuid};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::OrgId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::host_pools;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find host pool `{0}`: {1}
+    ById(HostPoolId, diesel::result::Error),
+    /// Failed to find host pools for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Failed to find host pool capacity: {0}
+    Capacity(diesel::result::Error),
+    /// Failed to create host pool: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete host pool `{0}`: {1}
+    Delete(HostPoolId, diesel::result::Error),
+    /// Host pool name must not be empty.
+    EmptyName,
+    /// Failed to update host pool `{0}`: {1}
+    Update(HostPoolId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _))
+            | Update(_, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Host pool already exists.")
+            }
+            ById(_, NotFound) | Delete(_, NotFound) | Update(_, NotFound) => {
+                Status::not_found("Host pool not found.")
+            }
+            EmptyName => Status::invalid_argument("name"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct HostPoolId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct HostPool {
+    pub id: HostPoolId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl HostPool {
+    pub async fn by_id(id: HostPoolId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        host_pools::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        host_pools::table
+            .filter(host_pools::org_id.eq(org_id))
+            .order_by(host_pools::name)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Delete a pool, which leaves its hosts in the org without a pool.
+    pub async fn delete(id: HostPoolId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let deleted = diesel::delete(host_pools::table.find(id))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Delete(id, err))?;
+
+        if deleted == 0 {
+            Err(Error::Delete(id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl From<HostPool> for api::HostPool {
+    fn from(pool: HostPool) -> Self {
+        api::HostPool {
+            pool_id: pool.id.to_string(),
+            org_id: pool.org_id.to_string(),
+            name: pool.name,
+            description: pool.description,
+            capacity: None,
+            created_at: Some(NanosUtc::from(pool.created_at).into()),
+            updated_at: Some(NanosUtc::from(pool.updated_at).into()),
+        }
+    }
+}
+
+/// The total and scheduled resources of the hosts in a pool.
+#[derive(Clone, Copy, Debug, Default, QueryableByName)]
+pub struct PoolCapacity {
+    #[diesel(sql_type = SqlUuid)]
+    pub pool_id: Uuid,
+    #[diesel(sql_type = BigInt)]
+    pub host_count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub cpu_cores: i64,
+    #[diesel(sql_type = BigInt)]
+    pub node_cpu_cores: i64,
+    #[diesel(sql_type = BigInt)]
+    pub memory_bytes: i64,
+    #[diesel(sql_type = BigInt)]
+    pub node_memory_bytes: i64,
+    #[diesel(sql_type = BigInt)]
+    pub disk_bytes: i64,
+    #[diesel(sql_type = BigInt)]
+    pub node_disk_bytes: i64,
+}
+
+impl PoolCapacity {
+    /// The capacity of each pool with at least one host.
+    pub async fn by_pool_ids(
+        pool_ids: &HashSet<HostPoolId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<HostPoolId, Self>, Error> {
+        let ids: Vec<Uuid> = pool_ids.iter().map(|id| **id).collect();
+        let capacities: Vec<Self> = diesel::sql_query(
+            "
+            select
+                pool_id,
+                count(*) as host_count,
+                sum(cpu_cores)::bigint as cpu_cores,
+                sum(node_cpu_cores)::bigint as node_cpu_cores,
+                sum(memory_bytes)::bigint as memory_bytes,
+                sum(node_memory_bytes)::bigint as node_memory_bytes,
+                sum(disk_bytes)::bigint as disk_bytes,
+                sum(node_disk_bytes)::bigint as node_disk_bytes
+            from hosts
+            where pool_id = any($1) and deleted_at is null
+            group by pool_id
+            ",
+        )
+        .bind::<Array<SqlUuid>, _>(ids)
+        .load(conn)
+        .await
+        .map_err(Error::Capacity)?;
+
+        Ok(capacities
+            .into_iter()
+            .map(|capacity| (HostPoolId(capacity.pool_id), capacity))
+            .collect())
+    }
+}
+
+impl From<PoolCapacity> for api::HostPoolCapacity {
+    fn from(capacity: PoolCapacity) -> Self {
+        let to_u64 = |value: i64| u64::try_from(value).unwrap_or_default();
+        api::HostPoolCapacity {
+            host_count: to_u64(capacity.host_count),
+            cpu_cores: to_u64(capacity.cpu_cores),
+            free_cpu_cores: to_u64(capacity.cpu_cores - capacity.node_cpu_cores),
+            memory_bytes: to_u64(capacity.memory_bytes),
+            free_memory_bytes: to_u64(capacity.memory_bytes - capacity.node_memory_bytes),
+            disk_bytes: to_u64(capacity.disk_bytes),
+            free_disk_bytes: to_u64(capacity.disk_bytes - capacity.node_disk_bytes),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = host_pools)]
+pub struct NewHostPool<'p> {
+    pub org_id: OrgId,
+    pub name: &'p str,
+    pub description: Option<&'p str>,
+}
+
+impl NewHostPool<'_> {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<HostPool, Error> {
+        if self.name.trim().is_empty() {
+            return Err(Error::EmptyName);
+        }
+
+        diesel::insert_into(host_pools::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = host_pools)]
+pub struct UpdateHostPool<'p> {
+    pub name: Option<&'p str>,
+    pub description: Option<&'p str>,
+}
+
+impl UpdateHostPool<'_> {
+    pub async fn apply(self, id: HostPoolId, conn: &mut Conn<'_>) -> Result<HostPool, Error> {
+        if self.name.is_some_and(|name| name.trim().is_empty()) {
+            return Err(Error::EmptyName);
+        }
+
+        diesel::update(host_pools::table.find(id))
+            .set((self, host_pools::updated_at.eq(Utc::now())))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+}
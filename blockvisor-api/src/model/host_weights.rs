@@ -0,0 +1,267 @@
+//! Weights for ranking the candidate hosts of a node.
+//!
+//! Hosts that meet the requirements of a node are ranked by a weighted score of
+//! their free resource headroom, the count of similar nodes on them, their age
+//! and their cost. The active weights default to the `scheduler` config until
+//! they are overridden at runtime.
+//!
+//! Shadow weights, when set, rank the same candidates again so that the host
+//! they would have chosen is logged next to the active choice, without changing
+//! where nodes are placed.
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::config::scheduler::Config;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::ProtocolId;
+use crate::model::node::{NodeScheduler, ResourceAffinity, SimilarNodeAffinity};
+use crate::model::schema::{host_weights, sql_types};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find {0:?} host weights: {1}
+    ByRanking(HostRanking, diesel::result::Error),
+    /// Host weight `{0}` is not a finite number.
+    NotFinite(&'static str),
+    /// Failed to set {0:?} host weights: {1}
+    Set(HostRanking, diesel::result::Error),
+    /// Failed to unset {0:?} host weights: {1}
+    Unset(HostRanking, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            NotFinite(weight) => Status::invalid_argument(weight),
+            ByRanking(..) | Set(..) | Unset(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// Which ranking of candidate hosts a set of weights is for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum, Serialize)]
+#[ExistingTypePath = "sql_types::EnumHostRanking"]
+#[serde(rename_all = "snake_case")]
+pub enum HostRanking {
+    /// The ranking that nodes are placed by.
+    Active,
+    /// An alternative ranking that is only logged.
+    Shadow,
+}
+
+impl HostRanking {
+    pub const fn from_api(ranking: api::HostRanking) -> Option<Self> {
+        match ranking {
+            api::HostRanking::Unspecified => None,
+            api::HostRanking::Active => Some(HostRanking::Active),
+            api::HostRanking::Shadow => Some(HostRanking::Shadow),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Queryable, Selectable, Serialize)]
+#[diesel(table_name = host_weights)]
+pub struct HostWeights {
+    /// The weight of the average fraction of free cpu, memory and disk.
+    ///
+    /// Only applies when the scheduler has a resource affinity, which decides
+    /// whether more or less headroom is preferred.
+    pub headroom: f64,
+    /// The weight of the count of nodes of the same protocol.
+    ///
+    /// Only applies when the scheduler has a similarity affinity, which decides
+    /// whether more or fewer similar nodes are preferred.
+    pub similarity: f64,
+    /// The weight of the age of the host in days.
+    pub age: f64,
+    /// The weight of the cost of the host, which is preferred to be lower.
+    pub cost: f64,
+}
+
+impl HostWeights {
+    pub async fn by_ranking(
+        ranking: HostRanking,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Self>, Error> {
+        host_weights::table
+            .find(ranking)
+            .select(HostWeights::as_select())
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::ByRanking(ranking, err))
+    }
+
+    /// The weights that nodes are placed by, falling back to `config`.
+    pub async fn active(config: &Config, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let active = Self::by_ranking(HostRanking::Active, conn).await?;
+        Ok(active.unwrap_or_else(|| Self::from(config)))
+    }
+
+    pub async fn set(self, ranking: HostRanking, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        self.validate()?;
+
+        diesel::insert_into(host_weights::table)
+            .values((
+                host_weights::ranking.eq(ranking),
+                host_weights::headroom.eq(self.headroom),
+                host_weights::similarity.eq(self.similarity),
+                host_weights::age.eq(self.age),
+                host_weights::cost.eq(self.cost),
+            ))
+            .on_conflict(host_weights::ranking)
+            .do_update()
+            .set((
+                host_weights::headroom.eq(excluded(host_weights::headroom)),
+                host_weights::similarity.eq(excluded(host_weights::similarity)),
+                host_weights::age.eq(excluded(host_weights::age)),
+                host_weights::cost.eq(excluded(host_weights::cost)),
+                host_weights::updated_at.eq(Utc::now()),
+            ))
+            .returning(HostWeights::as_returning())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Set(ranking, err))
+    }
+
+    /// Remove the weights of `ranking`, so the active ranking falls back to
+    /// the config and the shadow ranking stops.
+    pub async fn unset(ranking: HostRanking, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(host_weights::table.find(ranking))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Unset(ranking, err))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let weights = [
+            ("headroom", self.headroom),
+            ("similarity", self.similarity),
+            ("age", self.age),
+            ("cost", self.cost),
+        ];
+        match weights.into_iter().find(|(_, weight)| !weight.is_finite()) {
+            Some((name, _)) => Err(Error::NotFinite(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// A SQL expression scoring a row of `hosts`, where higher is better.
+    ///
+    /// The weights are finite floats and `protocol_id` is a uuid, so the
+    /// formatted expression is injection safe.
+    pub fn score(&self, scheduler: &NodeScheduler, protocol_id: ProtocolId) -> String {
+        let headroom = match scheduler.resource {
+            Some(ResourceAffinity::MostResources) => self.headroom,
+            Some(ResourceAffinity::LeastResources) => -self.headroom,
+            None => 0.0,
+        };
+        let similarity = match scheduler.similarity {
+            Some(SimilarNodeAffinity::Cluster) => self.similarity,
+            Some(SimilarNodeAffinity::Spread) => -self.similarity,
+            None => 0.0,
+        };
+        let HostWeights { age, cost, .. } = self;
+
+        format!(
+            "({headroom:?}) * ( \
+                (hosts.cpu_cores - hosts.node_cpu_cores)::float8 \
+                    / greatest(hosts.cpu_cores, 1) \
+                + (hosts.memory_bytes - hosts.node_memory_bytes)::float8 \
+                    / greatest(hosts.memory_bytes, 1) \
+                + (hosts.disk_bytes - hosts.node_disk_bytes)::float8 \
+                    / greatest(hosts.disk_bytes, 1) \
+            ) / 3 \
+            + ({similarity:?}) * ( \
+                select count(*) from nodes \
+                where nodes.host_id = hosts.id \
+                and nodes.protocol_id = '{protocol_id}' \
+                and nodes.deleted_at is null \
+            ) \
+            + ({age:?}) * extract(epoch from now() - hosts.created_at)::float8 / 86400 \
+            - ({cost:?}) * coalesce((hosts.cost->>'amount')::float8, 0) / 100"
+        )
+    }
+}
+
+impl From<&Config> for HostWeights {
+    fn from(config: &Config) -> Self {
+        HostWeights {
+            headroom: config.headroom_weight,
+            similarity: config.similarity_weight,
+            age: config.age_weight,
+            cost: config.cost_weight,
+        }
+    }
+}
+
+impl From<api::HostWeights> for HostWeights {
+    fn from(weights: api::HostWeights) -> Self {
+        HostWeights {
+            headroom: weights.headroom,
+            similarity: weights.similarity,
+            age: weights.age,
+            cost: weights.cost,
+        }
+    }
+}
+
+impl From<HostWeights> for api::HostWeights {
+    fn from(weights: HostWeights) -> Self {
+        api::HostWeights {
+            headroom: weights.headroom,
+            similarity: weights.similarity,
+            age: weights.age,
+            cost: weights.cost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_follows_scheduler_affinities() {
+        let weights = HostWeights {
+            headroom: 2.0,
+            similarity: 0.5,
+            age: 0.0,
+            cost: 1.0,
+        };
+        let scheduler = NodeScheduler {
+            similarity: Some(SimilarNodeAffinity::Spread),
+            ..NodeScheduler::least_resources()
+        };
+        let score = weights.score(&scheduler, ProtocolId::from(uuid::Uuid::nil()));
+
+        assert!(score.starts_with("(-2.0) * ("));
+        assert!(score.contains("+ (-0.5) * ("));
+        assert!(score.contains("+ (0.0) * extract"));
+        assert!(score.contains("- (1.0) * coalesce"));
+    }
+
+    #[test]
+    fn weights_must_be_finite() {
+        let weights = HostWeights {
+            headroom: 1.0,
+            similarity: f64::NAN,
+            age: 0.0,
+            cost: f64::INFINITY,
+        };
+        assert!(matches!(
+            weights.validate(),
+            Err(Error::NotFinite("similarity"))
+        ));
+    }
+}
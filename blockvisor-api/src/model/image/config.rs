@@ -17,22 +17,26 @@ use displaydoc::Display as DisplayDoc;
 use prost::Message;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::auth::AuthZ;
-use crate::auth::resource::{OrgId, Resource, ResourceId, ResourceType};
-use crate::database::Conn;
+use crate::auth::resource::{NodeId, OrgId, Resource, ResourceId, ResourceType};
+use crate::config::Context;
+use crate::database::{Conn, Database, WriteConn};
 use crate::grpc::{Status, common};
 use crate::model::image::Image;
 use crate::model::image::property::{ImageProperty, ImagePropertyKey};
 use crate::model::schema::{configs, sql_types};
 use crate::model::sql::Version;
 use crate::store::StoreKey;
+use crate::store::secret::SecretKey;
 use crate::util::HashVec;
 
-use super::property::{NewImagePropertyValue, PropertyMap, PropertyValueConfig};
+use super::property::{NewImagePropertyValue, PropertyMap, PropertyValueConfig, UiType};
 use super::rule::{FirewallAction, FirewallRule};
-use super::{Archive, ArchiveId, ImageId, ImageRule};
+use super::validation::validate_values;
+use super::{Archive, ArchiveId, ImageId, ImageRule, PortMap};
 
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
@@ -46,6 +50,8 @@ pub enum Error {
     ChangeProperty(ImagePropertyKey),
     /// Failed to create new image Config: {0}
     Create(diesel::result::Error),
+    /// Image config database error: {0}
+    Database(#[from] crate::database::Error),
     /// Failed to decode NodeConfig proto bytes: {0}
     DecodeNodeConfig(prost::DecodeError),
     /// Missing FirewallConfig. This should not happen.
@@ -60,22 +66,36 @@ pub enum Error {
     ParseImageId(uuid::Error),
     /// Failed to parse min babel version: {0}
     ParseMinBabel(crate::model::sql::Error),
+    /// Image config port map error: {0}
+    Port(#[from] super::port::Error),
     /// Image config property error: {0}
     Property(#[from] super::property::Error),
     /// Image config firewall rule error: {0}
     Rule(#[from] super::rule::Error),
+    /// Image config secret error: {0}
+    Secret(#[from] crate::store::secret::Error),
     /// Image config store error: {0}
     Store(#[from] crate::store::Error),
     /// Update Image Config missing key: {0}
     UpdateKeyMissing(ImagePropertyKey),
     /// Update Image Config key is not dynamic: {0}
     UpdateKeyNotDynamic(ImagePropertyKey),
+    /// Update Image Config key is a secret: {0}
+    UpdateKeySecret(ImagePropertyKey),
+    /// Image config property value error: {0}
+    Validation(#[from] super::validation::Error),
     /// Invalid VM cpu_count: {0}
     VmCpu(std::num::TryFromIntError),
     /// Invalid VM disk bytes: {0}
     VmDisk(std::num::TryFromIntError),
+    /// Invalid VM gpu count: {0}
+    VmGpu(std::num::TryFromIntError),
     /// Invalid VM memory bytes: {0}
     VmMemory(std::num::TryFromIntError),
+    /// Invalid VM network bandwidth: {0}
+    VmNetworkBandwidth(std::num::TryFromIntError),
+    /// Invalid VM nvme iops: {0}
+    VmNvmeIops(std::num::TryFromIntError),
 }
 
 impl From<Error> for Status {
@@ -89,21 +109,30 @@ impl From<Error> for Status {
             ParseArchiveId(_) => Status::invalid_argument("archive_id"),
             ParseImageId(_) => Status::invalid_argument("image_id"),
             ParseMinBabel(_) => Status::invalid_argument("min_babel_version"),
-            UpdateKeyNotDynamic(key) => Status::failed_precondition(format!("property.key: {key}")),
+            UpdateKeyNotDynamic(key) | UpdateKeySecret(key) => {
+                Status::failed_precondition(format!("property.key: {key}"))
+            }
             ById(_, _)
             | ByIds(_, _)
             | Create(_)
+            | Database(_)
             | DecodeNodeConfig(_)
             | MissingImageConfig
             | MissingFirewallConfig
             | MissingVmConfig
             | VmCpu(_)
             | VmDisk(_)
-            | VmMemory(_) => Status::internal("Internal error."),
+            | VmGpu(_)
+            | VmMemory(_)
+            | VmNetworkBandwidth(_)
+            | VmNvmeIops(_) => Status::internal("Internal error."),
             Archive(err) => err.into(),
+            Port(err) => err.into(),
             Property(err) => err.into(),
             Rule(err) => err.into(),
+            Secret(err) => err.into(),
             Store(err) => err.into(),
+            Validation(err) => err.into(),
         }
     }
 }
@@ -147,19 +176,37 @@ pub struct Config {
 
 impl Config {
     pub async fn by_id(id: ConfigId, conn: &mut Conn<'_>) -> Result<Self, Error> {
-        configs::table
+        if let Some(config) = conn.cached(|cache| &cache.configs, &id).await {
+            return Ok(config);
+        }
+
+        let config: Self = configs::table
             .filter(configs::id.eq(id))
             .get_result(conn)
             .await
-            .map_err(|err| Error::ById(id, err))
+            .map_err(|err| Error::ById(id, err))?;
+        conn.fill_cache(|cache| &cache.configs, [(id, config.clone())])
+            .await;
+
+        Ok(config)
     }
 
     pub async fn by_ids(ids: &HashSet<ConfigId>, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
-        configs::table
-            .filter(configs::id.eq_any(ids))
+        let (mut cached, missing) = conn.cached_many(|cache| &cache.configs, ids).await;
+        if missing.is_empty() {
+            return Ok(cached);
+        }
+
+        let configs: Vec<Self> = configs::table
+            .filter(configs::id.eq_any(&missing))
             .get_results(conn)
             .await
-            .map_err(|err| Error::ByIds(ids.clone(), err))
+            .map_err(|err| Error::ByIds(ids.clone(), err))?;
+        let values = configs.iter().map(|config| (config.id, config.clone()));
+        conn.fill_cache(|cache| &cache.configs, values).await;
+
+        cached.extend(configs);
+        Ok(cached)
     }
 
     pub fn node_config(&self) -> Result<NodeConfig, Error> {
@@ -205,15 +252,22 @@ pub struct NodeConfig {
 }
 
 impl NodeConfig {
+    /// Create the config of a new node.
+    ///
+    /// The values of secret properties are replaced by a `SecretKey`, and
+    /// returned as `NodeSecrets` to be written to the secret store.
     pub async fn new(
         image: Image,
         org_id: Option<OrgId>,
         new_values: Vec<NewImagePropertyValue>,
         add_rules: Vec<FirewallRule>,
         conn: &mut Conn<'_>,
-    ) -> Result<Self, Error> {
+    ) -> Result<(Self, NodeSecrets), Error> {
         let properties = ImageProperty::by_image_id(image.id, conn).await?;
-        let values = PropertyMap::new(properties).apply_overrides(new_values);
+        let mut values = PropertyMap::new(properties.clone()).apply_overrides(new_values);
+        validate_values(&properties, &values)?;
+        let secrets = NodeSecrets::take(&properties, &mut values)?;
+        validate_overrides(image.id, &add_rules, conn).await?;
 
         let mut rules = ImageRule::by_image_id(image.id, conn)
             .await?
@@ -224,7 +278,8 @@ impl NodeConfig {
         }
         let rules = rules.into_values().collect();
 
-        Self::generate_from(image, org_id, values, rules, conn).await
+        let config = Self::generate_from(image, org_id, values, rules, conn).await?;
+        Ok((config, secrets))
     }
 
     pub async fn upgrade(
@@ -324,6 +379,14 @@ impl NodeConfig {
             u64::try_from(max(mem, image.min_memory_bytes)).map_err(Error::VmMemory)?,
             u64::try_from(max(disk, image.min_disk_bytes)).map_err(Error::VmDisk)?,
         );
+        let nvme_iops = image
+            .min_nvme_iops
+            .map(|iops| u64::try_from(iops).map_err(Error::VmNvmeIops))
+            .transpose()?;
+        let network_bandwidth_mbps = image
+            .min_network_bandwidth_mbps
+            .map(|mbps| u64::try_from(mbps).map_err(Error::VmNetworkBandwidth))
+            .transpose()?;
 
         Ok(NodeConfig {
             vm: VmConfig {
@@ -331,6 +394,10 @@ impl NodeConfig {
                 memory_bytes,
                 disk_bytes,
                 ramdisks: image.ramdisks,
+                gpu_count: u64::try_from(image.min_gpu_count).map_err(Error::VmGpu)?,
+                gpu_model: image.gpu_model,
+                nvme_iops,
+                network_bandwidth_mbps,
             },
             image: ImageConfig {
                 image_id: image.id,
@@ -357,7 +424,7 @@ impl NodeConfig {
         let properties = ImageProperty::by_image_id(self.image.image_id, conn).await?;
         let property_map = PropertyMap::new(properties.clone());
         let key_to_prop = properties
-            .into_iter()
+            .iter()
             .to_map_keep_last(|prop| (prop.key.clone(), prop));
 
         for value in &new_values {
@@ -366,8 +433,13 @@ impl NodeConfig {
                 .ok_or_else(|| Error::UpdateKeyMissing(value.key.clone()))?;
             if !property.dynamic_value {
                 return Err(Error::UpdateKeyNotDynamic(value.key.clone()));
+            } else if property.ui_type == UiType::Secret {
+                return Err(Error::UpdateKeySecret(value.key.clone()));
             }
         }
+        if let Some(firewall) = &new_firewall {
+            validate_overrides(self.image.image_id, &firewall.rules, conn).await?;
+        }
         let overrides = self
             .image
             .values
@@ -376,6 +448,8 @@ impl NodeConfig {
             .map(NewImagePropertyValue::from)
             .chain(new_values)
             .collect();
+        let values = property_map.apply_overrides(overrides);
+        validate_values(&properties, &values)?;
 
         Ok(NodeConfig {
             vm: self.vm,
@@ -384,7 +458,7 @@ impl NodeConfig {
                 image_uri: self.image.image_uri,
                 archive_id: self.image.archive_id,
                 store_key: self.image.store_key,
-                values: property_map.apply_overrides(overrides),
+                values,
                 min_babel_version: self.image.min_babel_version,
             },
             firewall: if let Some(config) = new_firewall {
@@ -402,6 +476,10 @@ impl NodeConfig {
                 memory_bytes: 0,
                 disk_bytes: 0,
                 ramdisks: Ramdisks(vec![]),
+                gpu_count: 0,
+                gpu_model: None,
+                nvme_iops: None,
+                network_bandwidth_mbps: None,
             },
             image: ImageConfig {
                 image_id: Uuid::nil().into(),
@@ -420,6 +498,97 @@ impl NodeConfig {
     }
 }
 
+/// The values of secret properties, keyed by the `SecretKey` that replaced
+/// them in a `NodeConfig`.
+///
+/// Each key is derived from its property key, and the values are stored for
+/// each node created from the config, so a host finds them by node id alone.
+#[derive(Debug, Default)]
+pub struct NodeSecrets(Vec<(SecretKey, String)>);
+
+impl NodeSecrets {
+    /// Move the set values of secret properties out of `values`.
+    fn take(
+        properties: &[ImageProperty],
+        values: &mut [PropertyValueConfig],
+    ) -> Result<Self, Error> {
+        let secret_keys: HashSet<_> = properties
+            .iter()
+            .filter(|property| property.ui_type == UiType::Secret)
+            .map(|property| &property.key)
+            .collect();
+
+        let mut secrets = vec![];
+        for value in values {
+            if value.is_secret || value.value.is_empty() || !secret_keys.contains(&value.key) {
+                continue;
+            }
+
+            let key = SecretKey::new(format!("property-{}", value.key))?;
+            let secret = std::mem::replace(&mut value.value, key.to_string());
+            value.is_secret = true;
+            secrets.push((key, secret));
+        }
+
+        Ok(NodeSecrets(secrets))
+    }
+
+    /// Write the secret values of each node once the write has committed.
+    ///
+    /// Nothing is stored for a create that rolls back or is retried. A failure
+    /// is only logged, and the host then fails to reveal the values of the node.
+    pub fn store_after_commit(self, node_ids: Vec<NodeId>, write: &mut WriteConn<'_, '_>) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        let ctx = write.ctx.clone();
+        write.after_commit(async move {
+            for node_id in node_ids {
+                if let Err(err) = self.store(node_id, &ctx).await {
+                    warn!("Failed to store secret values of node {node_id}: {err}");
+                }
+            }
+        });
+    }
+
+    async fn store(&self, node_id: NodeId, context: &Context) -> Result<(), Error> {
+        let mut conn = context.conn().await?;
+        for (key, value) in &self.0 {
+            context
+                .secret
+                .put(Resource::from(node_id), key, value.as_bytes(), &mut conn)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the firewall rules of a node that differ from those of its image
+/// against the declared ports of the image, if it has any.
+async fn validate_overrides(
+    image_id: ImageId,
+    rules: &[FirewallRule],
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let Some(ports) = PortMap::by_image_id(image_id, conn).await? else {
+        return Ok(());
+    };
+
+    let image_rules = ImageRule::by_image_id(image_id, conn)
+        .await?
+        .into_iter()
+        .to_map_keep_last(|rule| (rule.key.clone(), FirewallRule::from(rule)));
+    let overrides: Vec<_> = rules
+        .iter()
+        .filter(|rule| image_rules.get(&rule.key) != Some(*rule))
+        .cloned()
+        .collect();
+
+    ports.validate(&overrides).map_err(Into::into)
+}
+
 impl From<NodeConfig> for ConfigBytes {
     fn from(config: NodeConfig) -> Self {
         ConfigBytes(common::NodeConfig::from(config).encode_to_vec())
@@ -467,6 +636,13 @@ pub struct VmConfig {
     pub memory_bytes: u64,
     pub disk_bytes: u64,
     pub ramdisks: Ramdisks,
+    pub gpu_count: u64,
+    /// A specific GPU model the node must run on, if any.
+    pub gpu_model: Option<String>,
+    /// The minimum NVMe IOPS of the host, if any.
+    pub nvme_iops: Option<u64>,
+    /// The minimum network bandwidth of the host, if any.
+    pub network_bandwidth_mbps: Option<u64>,
 }
 
 impl From<VmConfig> for common::VmConfig {
@@ -476,6 +652,10 @@ impl From<VmConfig> for common::VmConfig {
             memory_bytes: config.memory_bytes,
             disk_bytes: config.disk_bytes,
             ramdisks: config.ramdisks.into_iter().map(Into::into).collect(),
+            gpu_count: config.gpu_count,
+            gpu_model: config.gpu_model,
+            nvme_iops: config.nvme_iops,
+            network_bandwidth_mbps: config.network_bandwidth_mbps,
         }
     }
 }
@@ -487,6 +667,10 @@ impl From<common::VmConfig> for VmConfig {
             memory_bytes: config.memory_bytes,
             disk_bytes: config.disk_bytes,
             ramdisks: Ramdisks(config.ramdisks.into_iter().map(Into::into).collect()),
+            gpu_count: config.gpu_count,
+            gpu_model: config.gpu_model,
+            nvme_iops: config.nvme_iops,
+            network_bandwidth_mbps: config.network_bandwidth_mbps,
         }
     }
 }
@@ -0,0 +1,198 @@
+//! DNS settings of the nodes created from an image.
+//!
+//! Each node gets an address record for its name. An image may also ask for
+//! that record to be proxied or to have a custom TTL, and for extra records
+//! such as an SRV record per p2p port, which are created alongside it.
+
+use std::num::TryFromIntError;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::sql_types::Jsonb;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::cloudflare::{ExtraRecord, RecordOptions};
+use crate::database::Conn;
+use crate::grpc::{Status, common};
+use crate::model::schema::images;
+
+use super::ImageId;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find dns settings for image `{0}`: {1}
+    ByImageId(ImageId, diesel::result::Error),
+    /// DNS record is missing a record.
+    MissingRecord,
+    /// Invalid SRV record port: {0}
+    SrvPort(TryFromIntError),
+    /// Invalid SRV record priority: {0}
+    SrvPriority(TryFromIntError),
+    /// Invalid SRV record weight: {0}
+    SrvWeight(TryFromIntError),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByImageId(..) => Status::internal("Internal error."),
+            MissingRecord => Status::invalid_argument("dns_settings.records"),
+            SrvPort(_) => Status::invalid_argument("dns_settings.records.port"),
+            SrvPriority(_) => Status::invalid_argument("dns_settings.records.priority"),
+            SrvWeight(_) => Status::invalid_argument("dns_settings.records.weight"),
+        }
+    }
+}
+
+/// The DNS settings of an image.
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Jsonb)]
+pub struct DnsSettings {
+    #[serde(default)]
+    pub proxied: bool,
+    #[serde(default)]
+    pub ttl: Option<u32>,
+    #[serde(default)]
+    pub records: Vec<ExtraRecord>,
+}
+
+impl DnsSettings {
+    /// The DNS settings of an image, regardless of its visibility.
+    pub async fn by_image_id(id: ImageId, conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        images::table
+            .find(id)
+            .select(images::dns_settings)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByImageId(id, err))
+    }
+
+    pub const fn options(&self) -> RecordOptions {
+        RecordOptions {
+            proxied: self.proxied,
+            ttl: self.ttl,
+        }
+    }
+}
+
+impl From<DnsSettings> for common::DnsSettings {
+    fn from(settings: DnsSettings) -> Self {
+        use common::dns_record::Record;
+
+        let records = settings
+            .records
+            .into_iter()
+            .map(|record| {
+                let record = match record {
+                    ExtraRecord::Srv {
+                        service,
+                        protocol,
+                        port,
+                        priority,
+                        weight,
+                    } => Record::Srv(common::SrvRecord {
+                        service,
+                        protocol,
+                        port: u32::from(port),
+                        priority: u32::from(priority),
+                        weight: u32::from(weight),
+                    }),
+                    ExtraRecord::Txt { content } => Record::Txt(common::TxtRecord { content }),
+                };
+                common::DnsRecord {
+                    record: Some(record),
+                }
+            })
+            .collect();
+
+        common::DnsSettings {
+            proxied: settings.proxied,
+            ttl: settings.ttl,
+            records,
+        }
+    }
+}
+
+impl TryFrom<common::DnsSettings> for DnsSettings {
+    type Error = Error;
+
+    fn try_from(settings: common::DnsSettings) -> Result<Self, Self::Error> {
+        use common::dns_record::Record;
+
+        let records = settings
+            .records
+            .into_iter()
+            .map(|record| match record.record.ok_or(Error::MissingRecord)? {
+                Record::Srv(srv) => Ok(ExtraRecord::Srv {
+                    service: srv.service,
+                    protocol: srv.protocol,
+                    port: u16::try_from(srv.port).map_err(Error::SrvPort)?,
+                    priority: u16::try_from(srv.priority).map_err(Error::SrvPriority)?,
+                    weight: u16::try_from(srv.weight).map_err(Error::SrvWeight)?,
+                }),
+                Record::Txt(txt) => Ok(ExtraRecord::Txt {
+                    content: txt.content,
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(DnsSettings {
+            proxied: settings.proxied,
+            ttl: settings.ttl,
+            records,
+        })
+    }
+}
+
+impl FromSql<Jsonb, Pg> for DnsSettings {
+    fn from_sql(value: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        serde_json::from_value(FromSql::<Jsonb, Pg>::from_sql(value)?).map_err(Into::into)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for DnsSettings {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let json = serde_json::to_value(self).unwrap();
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&json, &mut out.reborrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srv_ports_must_fit_a_u16() {
+        use common::dns_record::Record;
+
+        let srv = |port| common::DnsSettings {
+            proxied: false,
+            ttl: Some(60),
+            records: vec![common::DnsRecord {
+                record: Some(Record::Srv(common::SrvRecord {
+                    service: "p2p".into(),
+                    protocol: "tcp".into(),
+                    port,
+                    priority: 10,
+                    weight: 5,
+                })),
+            }],
+        };
+
+        let settings = DnsSettings::try_from(srv(30303)).unwrap();
+        assert_eq!(settings.options().ttl, Some(60));
+        assert_eq!(common::DnsSettings::from(settings), srv(30303));
+
+        let err = DnsSettings::try_from(srv(70000)).unwrap_err();
+        assert!(matches!(err, Error::SrvPort(_)));
+    }
+}
@@ -0,0 +1,221 @@
+//! Health rules that evaluate the protocol health of a node from its metrics.
+//!
+//! Each image may have a list of `HealthRule`s, so each protocol version can
+//! define what healthy means for its nodes. A node with rules has its health
+//! evaluated from its reported metrics rather than the health reported by its
+//! host, which falls back to the reported health when no rule has the metrics
+//! it needs.
+
+use std::collections::{HashMap, HashSet};
+
+use derive_more::{From, IntoIterator};
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::sql_types::Jsonb;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::database::Conn;
+use crate::grpc::{Status, common};
+use crate::model::node::NodeHealth;
+use crate::model::schema::images;
+
+use super::ImageId;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find health rules for images `{0:?}`: {1}
+    ByImageIds(HashSet<ImageId>, diesel::result::Error),
+    /// Health rule is missing a rule.
+    MissingRule,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            MissingRule => Status::invalid_argument("health_rules"),
+            ByImageIds(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// A single check of the metrics of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthRule {
+    /// Unhealthy when the latest block is older than this many seconds.
+    MaxBlockAge(u64),
+    /// Unhealthy when more than this many blocks behind the network head.
+    MaxHeightLag(u64),
+    /// Unhealthy when connected to fewer than this many peers.
+    MinPeers(u32),
+    /// Unhealthy when not participating in consensus.
+    Consensus,
+}
+
+impl HealthRule {
+    /// The health of a node under this rule, or `None` if the node did not
+    /// report the metrics for it.
+    pub fn evaluate(self, metrics: &HealthMetrics) -> Option<NodeHealth> {
+        let healthy = match self {
+            HealthRule::MaxBlockAge(max) => metrics
+                .block_age
+                .map(|age| u64::try_from(age).unwrap_or_default() <= max)?,
+            HealthRule::MaxHeightLag(max) => {
+                let lag = metrics.network_height? - metrics.block_height?;
+                u64::try_from(lag).unwrap_or_default() <= max
+            }
+            HealthRule::MinPeers(min) => metrics
+                .peer_count
+                .map(|peers| u32::try_from(peers).unwrap_or_default() >= min)?,
+            HealthRule::Consensus => metrics.consensus?,
+        };
+
+        Some(if healthy {
+            NodeHealth::Healthy
+        } else {
+            NodeHealth::Unhealthy
+        })
+    }
+}
+
+impl From<HealthRule> for common::HealthRule {
+    fn from(rule: HealthRule) -> Self {
+        use common::health_rule::Rule;
+
+        let rule = match rule {
+            HealthRule::MaxBlockAge(max) => Rule::MaxBlockAgeSeconds(max),
+            HealthRule::MaxHeightLag(max) => Rule::MaxHeightLag(max),
+            HealthRule::MinPeers(min) => Rule::MinPeers(min),
+            HealthRule::Consensus => Rule::Consensus(true),
+        };
+
+        common::HealthRule { rule: Some(rule) }
+    }
+}
+
+impl TryFrom<common::HealthRule> for HealthRule {
+    type Error = Error;
+
+    fn try_from(rule: common::HealthRule) -> Result<Self, Self::Error> {
+        use common::health_rule::Rule;
+
+        match rule.rule.ok_or(Error::MissingRule)? {
+            Rule::MaxBlockAgeSeconds(max) => Ok(HealthRule::MaxBlockAge(max)),
+            Rule::MaxHeightLag(max) => Ok(HealthRule::MaxHeightLag(max)),
+            Rule::MinPeers(min) => Ok(HealthRule::MinPeers(min)),
+            Rule::Consensus(_) => Ok(HealthRule::Consensus),
+        }
+    }
+}
+
+/// The health rules of an image.
+#[derive(
+    Clone, Debug, Default, AsExpression, From, FromSqlRow, IntoIterator, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Jsonb)]
+pub struct HealthRules(pub Vec<HealthRule>);
+
+impl HealthRules {
+    /// Find the health rules of each image that has any.
+    pub async fn by_image_ids(
+        image_ids: &HashSet<ImageId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<ImageId, Self>, Error> {
+        let rules: Vec<(ImageId, Option<Self>)> = images::table
+            .filter(images::id.eq_any(image_ids))
+            .filter(images::health_rules.is_not_null())
+            .select((images::id, images::health_rules))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByImageIds(image_ids.clone(), err))?;
+
+        Ok(rules
+            .into_iter()
+            .filter_map(|(id, rules)| rules.map(|rules| (id, rules)))
+            .filter(|(_, rules)| !rules.0.is_empty())
+            .collect())
+    }
+
+    /// The worst health of any rule with the metrics it needs.
+    pub fn evaluate(&self, metrics: &HealthMetrics) -> Option<NodeHealth> {
+        self.0
+            .iter()
+            .filter_map(|rule| rule.evaluate(metrics))
+            .max_by_key(|health| match health {
+                NodeHealth::Healthy => 0,
+                NodeHealth::Neutral => 1,
+                NodeHealth::Unhealthy => 2,
+            })
+    }
+}
+
+impl FromSql<Jsonb, Pg> for HealthRules {
+    fn from_sql(value: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        serde_json::from_value(FromSql::<Jsonb, Pg>::from_sql(value)?).map_err(Into::into)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for HealthRules {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let json = serde_json::to_value(self).unwrap();
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&json, &mut out.reborrow())
+    }
+}
+
+/// The metrics of a node that health rules are evaluated against.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HealthMetrics {
+    pub block_height: Option<i64>,
+    pub block_age: Option<i64>,
+    pub peer_count: Option<i32>,
+    pub consensus: Option<bool>,
+    /// The latest block height of the network of the node.
+    pub network_height: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_without_metrics_are_skipped() {
+        let rules = HealthRules(vec![HealthRule::MinPeers(3), HealthRule::Consensus]);
+        assert_eq!(rules.evaluate(&HealthMetrics::default()), None);
+
+        let metrics = HealthMetrics {
+            consensus: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(rules.evaluate(&metrics), Some(NodeHealth::Healthy));
+    }
+
+    #[test]
+    fn the_worst_rule_wins() {
+        let rules = HealthRules(vec![
+            HealthRule::MaxBlockAge(60),
+            HealthRule::MaxHeightLag(10),
+        ]);
+
+        let mut metrics = HealthMetrics {
+            block_height: Some(995),
+            block_age: Some(12),
+            network_height: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(rules.evaluate(&metrics), Some(NodeHealth::Healthy));
+
+        metrics.block_height = Some(900);
+        assert_eq!(rules.evaluate(&metrics), Some(NodeHealth::Unhealthy));
+
+        metrics.block_height = Some(1000);
+        metrics.block_age = Some(600);
+        assert_eq!(rules.evaluate(&metrics), Some(NodeHealth::Unhealthy));
+    }
+}
@@ -5,12 +5,24 @@ pub use archive::{Archive, ArchiveId};
 pub mod config;
 pub use config::{Config, ConfigId, NewConfig, NodeConfig};
 
+pub mod dns;
+pub use dns::DnsSettings;
+
+pub mod health;
+pub use health::{HealthRule, HealthRules};
+
+pub mod port;
+pub use port::PortMap;
+
 pub mod property;
 pub use property::{ImageProperty, ImagePropertyId, NewProperty, UiType};
 
 pub mod rule;
 pub use rule::{FirewallRule, ImageRule, ImageRuleId, NewImageRule};
 
+pub mod validation;
+pub use validation::PropertyValidation;
+
 use std::collections::HashSet;
 
 use chrono::{DateTime, Utc};
@@ -18,6 +30,7 @@ use derive_more::{Deref, Display, From, FromStr};
 use diesel::prelude::*;
 use diesel::result::Error::NotFound;
 use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
 use diesel_derive_newtype::DieselNewType;
 use displaydoc::Display as DisplayDoc;
 use serde::{Deserialize, Serialize};
@@ -27,9 +40,9 @@ use uuid::Uuid;
 use crate::auth::AuthZ;
 use crate::auth::resource::OrgId;
 use crate::database::Conn;
-use crate::grpc::Status;
-use crate::model::protocol::{VersionId, Visibility};
-use crate::model::schema::images;
+use crate::grpc::{Status, api};
+use crate::model::protocol::{ProtocolNetworkId, VersionId, Visibility};
+use crate::model::schema::{images, sql_types};
 use crate::model::sql::Version;
 
 use self::config::Ramdisks;
@@ -49,6 +62,10 @@ pub enum Error {
     Create(diesel::result::Error),
     /// Failed to get the last build for protocol version `{0}`: {1}
     LatestBuild(VersionId, diesel::result::Error),
+    /// Failed to get the highest build for protocol version `{0}`: {1}
+    MaxBuild(VersionId, diesel::result::Error),
+    /// Failed to review image id {0}: {1}
+    Review(ImageId, diesel::result::Error),
     /// Failed to update image id {0}: {1}
     Update(ImageId, diesel::result::Error),
 }
@@ -59,6 +76,7 @@ impl From<Error> for Status {
         match err {
             ById(_, NotFound) => Status::not_found("Image not found."),
             ByBuild(_, _, _, NotFound) => Status::not_found("No image for that build."),
+            Review(_, NotFound) => Status::not_found("No image pending review."),
             Update(_, NotFound) => Status::not_found("No image updated."),
             _ => Status::internal("Internal error."),
         }
@@ -101,6 +119,15 @@ pub struct Image {
     pub updated_at: Option<DateTime<Utc>>,
     pub min_babel_version: Version,
     pub dns_scheme: Option<String>,
+    pub review_status: ImageReviewStatus,
+    pub health_rules: Option<HealthRules>,
+    pub min_gpu_count: i64,
+    pub gpu_model: Option<String>,
+    pub min_nvme_iops: Option<i64>,
+    pub min_network_bandwidth_mbps: Option<i64>,
+    pub dns_settings: Option<DnsSettings>,
+    pub port_map: Option<PortMap>,
+    pub network_id: Option<ProtocolNetworkId>,
 }
 
 impl Image {
@@ -114,6 +141,7 @@ impl Image {
             .find(id)
             .filter(images::org_id.eq(org_id).or(images::org_id.is_null()))
             .filter(images::visibility.eq_any(<&[Visibility]>::from(authz)))
+            .filter(images::review_status.eq(ImageReviewStatus::Approved))
             .get_result(conn)
             .await
             .map_err(|err| Error::ById(id, err))
@@ -129,6 +157,7 @@ impl Image {
             .filter(images::protocol_version_id.eq(version_id))
             .filter(images::org_id.eq(org_id).or(images::org_id.is_null()))
             .filter(images::visibility.eq_any(<&[Visibility]>::from(authz)))
+            .filter(images::review_status.eq(ImageReviewStatus::Approved))
             .order_by(images::build_version.desc())
             .get_results(conn)
             .await
@@ -145,6 +174,7 @@ impl Image {
             .filter(images::protocol_version_id.eq(version_id))
             .filter(images::org_id.eq(org_id).or(images::org_id.is_null()))
             .filter(images::visibility.eq_any(<&[Visibility]>::from(authz)))
+            .filter(images::review_status.eq(ImageReviewStatus::Approved))
             .order_by(images::build_version.desc())
             .first(conn)
             .await
@@ -162,6 +192,7 @@ impl Image {
             .filter(images::protocol_version_id.eq_any(version_ids))
             .filter(images::org_id.eq(org_id).or(images::org_id.is_null()))
             .filter(images::visibility.eq_any(<&[Visibility]>::from(authz)))
+            .filter(images::review_status.eq(ImageReviewStatus::Approved))
             .get_results(conn)
             .await
             .map_err(|err| Error::ByVersions(version_ids.clone(), org_id, err))
@@ -179,10 +210,42 @@ impl Image {
             .filter(images::org_id.eq(org_id).or(images::org_id.is_null()))
             .filter(images::build_version.eq(build))
             .filter(images::visibility.eq_any(<&[Visibility]>::from(authz)))
+            .filter(images::review_status.eq(ImageReviewStatus::Approved))
             .get_result(conn)
             .await
             .map_err(|err| Error::ByBuild(version_id, org_id, build, err))
     }
+
+    /// The highest build number of a protocol version, including unapproved builds.
+    pub async fn max_build(version_id: VersionId, conn: &mut Conn<'_>) -> Result<i64, Error> {
+        images::table
+            .filter(images::protocol_version_id.eq(version_id))
+            .select(diesel::dsl::max(images::build_version))
+            .get_result::<Option<i64>>(conn)
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(|err| Error::MaxBuild(version_id, err))
+    }
+
+    /// Approve or reject an image that is pending review.
+    pub async fn review(
+        id: ImageId,
+        status: ImageReviewStatus,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let pending = images::table
+            .find(id)
+            .filter(images::review_status.eq(ImageReviewStatus::PendingReview));
+
+        diesel::update(pending)
+            .set((
+                images::review_status.eq(status),
+                images::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Review(id, err))
+    }
 }
 
 #[derive(Debug, Insertable)]
@@ -201,6 +264,15 @@ pub struct NewImage {
     pub default_firewall_in: FirewallAction,
     pub default_firewall_out: FirewallAction,
     pub dns_scheme: Option<String>,
+    pub review_status: ImageReviewStatus,
+    pub health_rules: Option<HealthRules>,
+    pub min_gpu_count: i64,
+    pub gpu_model: Option<String>,
+    pub min_nvme_iops: Option<i64>,
+    pub min_network_bandwidth_mbps: Option<i64>,
+    pub dns_settings: Option<DnsSettings>,
+    pub port_map: Option<PortMap>,
+    pub network_id: Option<ProtocolNetworkId>,
 }
 
 impl NewImage {
@@ -230,3 +302,21 @@ impl UpdateImage {
             .map_err(|err| Error::Update(id, err))
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumImageReviewStatus"]
+pub enum ImageReviewStatus {
+    PendingReview,
+    Approved,
+    Rejected,
+}
+
+impl From<ImageReviewStatus> for api::ImageReviewStatus {
+    fn from(status: ImageReviewStatus) -> Self {
+        match status {
+            ImageReviewStatus::PendingReview => api::ImageReviewStatus::PendingReview,
+            ImageReviewStatus::Approved => api::ImageReviewStatus::Approved,
+            ImageReviewStatus::Rejected => api::ImageReviewStatus::Rejected,
+        }
+    }
+}
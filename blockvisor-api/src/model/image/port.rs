@@ -0,0 +1,292 @@
+//! The ports that the nodes of an image listen on.
+//!
+//! An image may declare its p2p, rpc and metrics ports so that clients can show
+//! them, and so that the firewall rules a user adds to a node can be checked
+//! against them. A user rule may only open ports that the image declares, and
+//! may not close a p2p port to every source since the node could then no
+//! longer sync.
+
+use std::collections::HashSet;
+use std::num::TryFromIntError;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::sql_types::Jsonb;
+use diesel::pg::{Pg, PgValue};
+use diesel::prelude::*;
+use diesel::serialize::{Output, ToSql};
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::database::Conn;
+use crate::grpc::{Status, common};
+use crate::model::schema::images;
+
+use super::ImageId;
+use super::rule::{FirewallAction, FirewallDirection, FirewallProtocol, FirewallRule};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find port map for image `{0}`: {1}
+    ByImageId(ImageId, diesel::result::Error),
+    /// Firewall rule `{0}` closes p2p port {1} to all sources.
+    ClosesP2p(String, u16),
+    /// Port {0} is declared more than once.
+    DuplicatePort(u16),
+    /// Invalid port: {0}
+    Port(TryFromIntError),
+    /// Image port protocol error: {0}
+    Protocol(#[from] super::rule::Error),
+    /// Firewall rule `{0}` opens port {1}, which the image does not declare.
+    UndeclaredPort(String, u16),
+    /// Unknown PortKind.
+    UnknownKind,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByImageId(..) => Status::internal("Internal error."),
+            ClosesP2p(key, port) => {
+                // safety: the key and port are from the client
+                Status::failed_precondition(format!("rule {key} closes p2p port {port}"))
+            }
+            DuplicatePort(_) | Port(_) => Status::invalid_argument("ports.port"),
+            UndeclaredPort(key, port) => {
+                // safety: the key and port are from the client
+                Status::failed_precondition(format!("rule {key} opens undeclared port {port}"))
+            }
+            UnknownKind => Status::invalid_argument("ports.kind"),
+            Protocol(err) => err.into(),
+        }
+    }
+}
+
+/// What a port is used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PortKind {
+    P2p,
+    Rpc,
+    Metrics,
+}
+
+impl From<PortKind> for common::PortKind {
+    fn from(kind: PortKind) -> Self {
+        match kind {
+            PortKind::P2p => common::PortKind::P2p,
+            PortKind::Rpc => common::PortKind::Rpc,
+            PortKind::Metrics => common::PortKind::Metrics,
+        }
+    }
+}
+
+impl TryFrom<common::PortKind> for PortKind {
+    type Error = Error;
+
+    fn try_from(kind: common::PortKind) -> Result<Self, Self::Error> {
+        match kind {
+            common::PortKind::Unspecified => Err(Error::UnknownKind),
+            common::PortKind::P2p => Ok(PortKind::P2p),
+            common::PortKind::Rpc => Ok(PortKind::Rpc),
+            common::PortKind::Metrics => Ok(PortKind::Metrics),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImagePort {
+    pub kind: PortKind,
+    pub port: u16,
+    pub protocol: FirewallProtocol,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl ImagePort {
+    /// Whether this port accepts traffic of `protocol`.
+    fn accepts(&self, protocol: FirewallProtocol) -> bool {
+        self.protocol == FirewallProtocol::Both || self.protocol == protocol
+    }
+}
+
+impl From<ImagePort> for common::ImagePort {
+    fn from(port: ImagePort) -> Self {
+        common::ImagePort {
+            kind: common::PortKind::from(port.kind).into(),
+            port: u32::from(port.port),
+            protocol: common::FirewallProtocol::from(port.protocol).into(),
+            description: port.description,
+        }
+    }
+}
+
+impl TryFrom<common::ImagePort> for ImagePort {
+    type Error = Error;
+
+    fn try_from(port: common::ImagePort) -> Result<Self, Self::Error> {
+        Ok(ImagePort {
+            kind: port.kind().try_into()?,
+            port: u16::try_from(port.port).map_err(Error::Port)?,
+            protocol: port.protocol().try_into()?,
+            description: port.description,
+        })
+    }
+}
+
+/// The declared ports of an image.
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Jsonb)]
+pub struct PortMap(pub Vec<ImagePort>);
+
+impl PortMap {
+    /// The port map of an image, regardless of its visibility.
+    pub async fn by_image_id(id: ImageId, conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        images::table
+            .find(id)
+            .select(images::port_map)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByImageId(id, err))
+    }
+
+    /// Parse the ports declared for a new image, returning `None` if there
+    /// are none.
+    pub fn from_api(ports: Vec<common::ImagePort>) -> Result<Option<Self>, Error> {
+        let ports = ports
+            .into_iter()
+            .map(ImagePort::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut seen = HashSet::new();
+        for port in &ports {
+            if !seen.insert((port.port, port.protocol)) {
+                return Err(Error::DuplicatePort(port.port));
+            }
+        }
+
+        Ok((!ports.is_empty()).then_some(PortMap(ports)))
+    }
+
+    /// Check the firewall rules that a user added to a node against the
+    /// declared ports.
+    pub fn validate(&self, rules: &[FirewallRule]) -> Result<(), Error> {
+        let inbound = rules
+            .iter()
+            .filter(|rule| rule.direction == FirewallDirection::Inbound);
+
+        for rule in inbound {
+            let ports = rule.ports.iter().flat_map(|ports| &ports.0);
+            for port in ports.map(|port| port.port) {
+                let declared: Vec<_> = self
+                    .0
+                    .iter()
+                    .filter(|declared| declared.port == port)
+                    .collect();
+
+                match rule.action {
+                    FirewallAction::Allow => {
+                        if !declared
+                            .iter()
+                            .any(|declared| declared.accepts(rule.protocol))
+                        {
+                            return Err(Error::UndeclaredPort(rule.key.0.clone(), port));
+                        }
+                    }
+                    FirewallAction::Drop | FirewallAction::Reject => {
+                        let closes_p2p = rule.ips.is_none()
+                            && declared
+                                .iter()
+                                .any(|declared| declared.kind == PortKind::P2p);
+                        if closes_p2p {
+                            return Err(Error::ClosesP2p(rule.key.0.clone(), port));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<PortMap> for Vec<common::ImagePort> {
+    fn from(ports: PortMap) -> Self {
+        ports.0.into_iter().map(Into::into).collect()
+    }
+}
+
+impl FromSql<Jsonb, Pg> for PortMap {
+    fn from_sql(value: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        serde_json::from_value(FromSql::<Jsonb, Pg>::from_sql(value)?).map_err(Into::into)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for PortMap {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let json = serde_json::to_value(self).unwrap();
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&json, &mut out.reborrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rule::{FirewallRuleKey, IpName, IpNames, PortName, PortNames};
+    use super::*;
+
+    fn rule(action: FirewallAction, port: u16, ips: Option<IpNames>) -> FirewallRule {
+        FirewallRule {
+            key: FirewallRuleKey("user".to_string()),
+            description: None,
+            protocol: FirewallProtocol::Tcp,
+            direction: FirewallDirection::Inbound,
+            action,
+            ips,
+            ports: Some(PortNames(vec![PortName { port, name: None }])),
+        }
+    }
+
+    #[test]
+    fn user_rules_are_checked_against_declared_ports() {
+        let ports = PortMap(vec![
+            ImagePort {
+                kind: PortKind::P2p,
+                port: 30303,
+                protocol: FirewallProtocol::Both,
+                description: None,
+            },
+            ImagePort {
+                kind: PortKind::Rpc,
+                port: 8545,
+                protocol: FirewallProtocol::Tcp,
+                description: None,
+            },
+        ]);
+        let office = IpNames(vec![IpName {
+            ip: "10.0.0.0/8".parse().unwrap(),
+            name: None,
+        }]);
+
+        ports
+            .validate(&[rule(FirewallAction::Allow, 8545, None)])
+            .unwrap();
+        ports
+            .validate(&[rule(FirewallAction::Drop, 30303, Some(office))])
+            .unwrap();
+
+        let err = ports
+            .validate(&[rule(FirewallAction::Allow, 22, None)])
+            .unwrap_err();
+        assert!(matches!(err, Error::UndeclaredPort(_, 22)));
+
+        let err = ports
+            .validate(&[rule(FirewallAction::Reject, 30303, None)])
+            .unwrap_err();
+        assert!(matches!(err, Error::ClosesP2p(_, 30303)));
+    }
+}
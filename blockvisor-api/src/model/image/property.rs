@@ -16,6 +16,7 @@ use crate::model::schema::{image_properties, sql_types};
 use crate::util::LOWER_KEBAB_CASE;
 
 use super::ImageId;
+use super::validation::PropertyValidation;
 
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
@@ -41,8 +42,12 @@ pub enum Error {
     PropertyKeyChars(String),
     /// ImagePropertyKey must be at least 3 characters: {0}
     PropertyKeyLen(String),
+    /// Secret image property `{0}` can't have a default value.
+    SecretDefault(ImagePropertyKey),
     /// Unknown UiType.
     UnknownUiType,
+    /// Image property validation error: {0}
+    Validation(#[from] super::validation::Error),
 }
 
 impl From<Error> for Status {
@@ -53,7 +58,9 @@ impl From<Error> for Status {
             GroupMultipleDefaults(_) | GroupNoDefault(_) => {
                 Status::failed_precondition("is_group_default")
             }
+            SecretDefault(_) => Status::invalid_argument("default_value"),
             UnknownUiType => Status::invalid_argument("ui_type"),
+            Validation(err) => err.into(),
             _ => Status::internal("Internal error."),
         }
     }
@@ -112,6 +119,7 @@ pub struct ImageProperty {
     pub add_disk_bytes: Option<i64>,
     pub display_name: Option<String>,
     pub display_group: Option<String>,
+    pub validation: Option<PropertyValidation>,
 }
 
 impl ImageProperty {
@@ -172,6 +180,7 @@ impl From<ImageProperty> for api::ImageProperty {
             add_cpu_cores: property.add_cpu_cores,
             add_memory_bytes: property.add_memory_bytes,
             add_disk_bytes: property.add_disk_bytes,
+            validation: property.validation.map(Into::into),
         }
     }
 }
@@ -193,6 +202,7 @@ pub struct NewProperty {
     pub add_disk_bytes: Option<i64>,
     pub display_name: Option<String>,
     pub display_group: Option<String>,
+    pub validation: Option<PropertyValidation>,
 }
 
 impl NewProperty {
@@ -212,15 +222,25 @@ impl NewProperty {
             add_disk_bytes: property.add_disk_bytes,
             display_name: property.display_name,
             display_group: property.display_group,
+            validation: property.validation,
         }
     }
 
     pub fn from(image_id: ImageId, property: api::AddImageProperty) -> Result<Self, Error> {
         let ui_type = property.ui_type().try_into()?;
+        let key = ImagePropertyKey::new(property.key)?;
+        if ui_type == UiType::Secret && !property.default_value.is_empty() {
+            return Err(Error::SecretDefault(key));
+        }
+        let validation = property
+            .validation
+            .map(PropertyValidation::from_api)
+            .transpose()?
+            .flatten();
 
         Ok(NewProperty {
             image_id,
-            key: ImagePropertyKey::new(property.key)?,
+            key,
             key_group: property
                 .key_group
                 .map(ImagePropertyGroup::new)
@@ -236,6 +256,7 @@ impl NewProperty {
             add_disk_bytes: property.add_disk_bytes,
             display_name: property.display_name,
             display_group: property.display_group,
+            validation,
         })
     }
 
@@ -273,6 +294,8 @@ pub struct NewImagePropertyValue {
     pub key: ImagePropertyKey,
     pub value: String,
     pub has_changed: bool,
+    /// Whether `value` is the `SecretKey` of a stored secret value.
+    pub is_secret: bool,
 }
 
 impl From<ImageProperty> for NewImagePropertyValue {
@@ -281,6 +304,7 @@ impl From<ImageProperty> for NewImagePropertyValue {
             key: property.key,
             value: property.default_value,
             has_changed: false,
+            is_secret: false,
         }
     }
 }
@@ -302,6 +326,7 @@ impl TryFrom<api::NewImagePropertyValue> for NewImagePropertyValue {
             key: ImagePropertyKey::new(value.key)?,
             value: value.value,
             has_changed: true,
+            is_secret: false,
         })
     }
 }
@@ -312,6 +337,7 @@ impl From<PropertyValueConfig> for NewImagePropertyValue {
             key: property.key,
             value: property.value,
             has_changed: true,
+            is_secret: property.is_secret,
         }
     }
 }
@@ -322,6 +348,8 @@ pub struct PropertyValueConfig {
     pub key_group: Option<ImagePropertyGroup>,
     pub value: String,
     pub has_changed: bool,
+    /// Whether `value` is the `SecretKey` of a stored secret value.
+    pub is_secret: bool,
 }
 
 impl From<ImageProperty> for PropertyValueConfig {
@@ -331,16 +359,26 @@ impl From<ImageProperty> for PropertyValueConfig {
             key_group: property.key_group,
             value: property.default_value,
             has_changed: false,
+            is_secret: false,
         }
     }
 }
 
+/// Secret values are only referenced by their `secret_path`, so the value
+/// itself is never part of a config or response.
 impl From<PropertyValueConfig> for common::PropertyValueConfig {
-    fn from(value: PropertyValueConfig) -> Self {
+    fn from(config: PropertyValueConfig) -> Self {
+        let (value, secret_path) = if config.is_secret {
+            (String::new(), Some(config.value))
+        } else {
+            (config.value, None)
+        };
+
         common::PropertyValueConfig {
-            key: value.key.0,
-            key_group: value.key_group.map(|group| group.0),
-            value: value.value,
+            key: config.key.0,
+            key_group: config.key_group.map(|group| group.0),
+            value,
+            secret_path,
         }
     }
 }
@@ -348,12 +386,18 @@ impl From<PropertyValueConfig> for common::PropertyValueConfig {
 impl TryFrom<common::PropertyValueConfig> for PropertyValueConfig {
     type Error = Error;
 
-    fn try_from(value: common::PropertyValueConfig) -> Result<Self, Self::Error> {
+    fn try_from(config: common::PropertyValueConfig) -> Result<Self, Self::Error> {
+        let (value, is_secret) = match config.secret_path {
+            Some(path) => (path, true),
+            None => (config.value, false),
+        };
+
         Ok(PropertyValueConfig {
-            key: ImagePropertyKey::new(value.key)?,
-            key_group: value.key_group.map(ImagePropertyGroup::new).transpose()?,
-            value: value.value,
+            key: ImagePropertyKey::new(config.key)?,
+            key_group: config.key_group.map(ImagePropertyGroup::new).transpose()?,
+            value,
             has_changed: true,
+            is_secret,
         })
     }
 }
@@ -365,6 +409,7 @@ pub enum UiType {
     Text,
     Password,
     Enum,
+    Secret,
 }
 
 impl From<UiType> for common::UiType {
@@ -374,6 +419,7 @@ impl From<UiType> for common::UiType {
             UiType::Text => common::UiType::Text,
             UiType::Password => common::UiType::Password,
             UiType::Enum => common::UiType::Enum,
+            UiType::Secret => common::UiType::Secret,
         }
     }
 }
@@ -388,6 +434,7 @@ impl TryFrom<common::UiType> for UiType {
             common::UiType::Text => Ok(UiType::Text),
             common::UiType::Password => Ok(UiType::Password),
             common::UiType::Enum => Ok(UiType::Enum),
+            common::UiType::Secret => Ok(UiType::Secret),
         }
     }
 }
@@ -452,6 +499,7 @@ impl PropertyMap {
                     key_group,
                     value: new_value.value,
                     has_changed: new_value.has_changed,
+                    is_secret: new_value.is_secret,
                 }
             })
             .collect()
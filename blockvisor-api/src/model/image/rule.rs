@@ -177,10 +177,12 @@ impl NewImageRule {
     }
 }
 
-#[derive(Clone, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From)]
+#[derive(
+    Clone, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, Serialize, Deserialize,
+)]
 pub struct FirewallRuleKey(pub String);
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FirewallRule {
     pub key: FirewallRuleKey,
     pub description: Option<String>,
@@ -350,8 +352,9 @@ impl TryFrom<common::PortName> for PortName {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
 #[ExistingTypePath = "sql_types::EnumFirewallProtocol"]
+#[serde(rename_all = "snake_case")]
 pub enum FirewallProtocol {
     Tcp,
     Udp,
@@ -381,8 +384,9 @@ impl TryFrom<common::FirewallProtocol> for FirewallProtocol {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
 #[ExistingTypePath = "sql_types::EnumFirewallDirection"]
+#[serde(rename_all = "snake_case")]
 pub enum FirewallDirection {
     Inbound,
     Outbound,
@@ -409,8 +413,9 @@ impl TryFrom<common::FirewallDirection> for FirewallDirection {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
 #[ExistingTypePath = "sql_types::EnumFirewallAction"]
+#[serde(rename_all = "snake_case")]
 pub enum FirewallAction {
     Allow,
     Drop,
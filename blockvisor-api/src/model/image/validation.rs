@@ -0,0 +1,315 @@
+//! Validation rules for the values of image properties.
+//!
+//! An image property may constrain the values a node can set with a regex, a
+//! list of allowed values, an integer range, or by being required when another
+//! property has a given value. The rules are checked when a node is created or
+//! its values are updated, and every broken rule is returned to the client as a
+//! field violation of `new_values.<key>`.
+
+use std::collections::HashMap;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::sql_types::Jsonb;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{Output, ToSql};
+use displaydoc::Display;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::grpc::validate::FieldViolation;
+use crate::grpc::{Status, common};
+
+use super::property::{ImageProperty, ImagePropertyKey, PropertyValueConfig};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Property values are invalid: {0:?}
+    Invalid(Vec<FieldViolation>),
+    /// Validation min `{0}` is greater than max `{1}`.
+    MinMax(i64, i64),
+    /// Validation regex is invalid: {0}
+    Regex(regex::Error),
+    /// Validation required_if key error: {0}
+    RequiredIfKey(Box<super::property::Error>),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Invalid(violations) => Status::bad_request(violations),
+            MinMax(..) => Status::invalid_argument("validation.min"),
+            Regex(_) => Status::invalid_argument("validation.regex"),
+            RequiredIfKey(_) => Status::invalid_argument("validation.required_if.key"),
+        }
+    }
+}
+
+/// The rules that the value of an image property must satisfy.
+///
+/// An empty value is only checked against `required_if`, so optional
+/// properties may be left unset.
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Jsonb)]
+pub struct PropertyValidation {
+    /// A pattern that the whole value must match.
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// The only values that may be set, if not empty.
+    #[serde(default)]
+    pub allowed_values: Vec<String>,
+    /// The inclusive lower bound of an integer value.
+    #[serde(default)]
+    pub min: Option<i64>,
+    /// The inclusive upper bound of an integer value.
+    #[serde(default)]
+    pub max: Option<i64>,
+    /// Another property value that makes this value required.
+    #[serde(default)]
+    pub required_if: Option<RequiredIf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequiredIf {
+    pub key: String,
+    pub value: String,
+}
+
+impl PropertyValidation {
+    /// Parse the validation rules of a new image property, returning `None`
+    /// if there are none.
+    pub fn from_api(validation: common::PropertyValidation) -> Result<Option<Self>, Error> {
+        if let Some(regex) = &validation.regex {
+            anchored(regex).map_err(Error::Regex)?;
+        }
+        if let (Some(min), Some(max)) = (validation.min, validation.max) {
+            if min > max {
+                return Err(Error::MinMax(min, max));
+            }
+        }
+        let required_if = validation
+            .required_if
+            .map(|required| {
+                Ok::<_, Error>(RequiredIf {
+                    key: ImagePropertyKey::new(required.key)
+                        .map_err(|err| Error::RequiredIfKey(Box::new(err)))?
+                        .into(),
+                    value: required.value,
+                })
+            })
+            .transpose()?;
+
+        let validation = PropertyValidation {
+            regex: validation.regex,
+            allowed_values: validation.allowed_values,
+            min: validation.min,
+            max: validation.max,
+            required_if,
+        };
+
+        Ok((validation != PropertyValidation::default()).then_some(validation))
+    }
+
+    /// Describe each rule that `value` breaks.
+    fn check(&self, value: &str, values: &HashMap<&str, &str>) -> Vec<String> {
+        let mut broken = vec![];
+
+        if value.is_empty() {
+            if let Some(required) = &self.required_if {
+                if values.get(required.key.as_str()) == Some(&required.value.as_str()) {
+                    broken.push(format!(
+                        "Required when `{}` is `{}`.",
+                        required.key, required.value
+                    ));
+                }
+            }
+            return broken;
+        }
+
+        if let Some(regex) = &self.regex {
+            if !anchored(regex).is_ok_and(|re| re.is_match(value)) {
+                broken.push(format!("Must match `{regex}`."));
+            }
+        }
+
+        if !self.allowed_values.is_empty() && !self.allowed_values.iter().any(|v| v == value) {
+            broken.push(format!(
+                "Must be one of: {}.",
+                self.allowed_values.join(", ")
+            ));
+        }
+
+        if self.min.is_some() || self.max.is_some() {
+            match value.parse::<i64>() {
+                Ok(int) => {
+                    if let Some(min) = self.min.filter(|min| int < *min) {
+                        broken.push(format!("Must be at least {min}."));
+                    }
+                    if let Some(max) = self.max.filter(|max| int > *max) {
+                        broken.push(format!("Must be at most {max}."));
+                    }
+                }
+                Err(_) => broken.push("Must be an integer.".to_string()),
+            }
+        }
+
+        broken
+    }
+}
+
+impl From<PropertyValidation> for common::PropertyValidation {
+    fn from(validation: PropertyValidation) -> Self {
+        common::PropertyValidation {
+            regex: validation.regex,
+            allowed_values: validation.allowed_values,
+            min: validation.min,
+            max: validation.max,
+            required_if: validation
+                .required_if
+                .map(|required| common::PropertyRequiredIf {
+                    key: required.key,
+                    value: required.value,
+                }),
+        }
+    }
+}
+
+/// Check the resolved values of a node against the validation rules of the
+/// image properties.
+///
+/// Values already moved to the secret store were checked when they were set.
+pub fn validate_values(
+    properties: &[ImageProperty],
+    values: &[PropertyValueConfig],
+) -> Result<(), Error> {
+    let values: HashMap<_, _> = values
+        .iter()
+        .filter(|value| !value.is_secret)
+        .map(|value| (value.key.as_str(), value.value.as_str()))
+        .collect();
+
+    let mut violations = vec![];
+    for property in properties {
+        let (Some(validation), Some(value)) =
+            (&property.validation, values.get(property.key.as_str()))
+        else {
+            continue;
+        };
+
+        for description in validation.check(value, &values) {
+            violations.push(FieldViolation {
+                field: format!("new_values.{}", property.key),
+                description,
+            });
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Invalid(violations))
+    }
+}
+
+fn anchored(regex: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{regex})$"))
+}
+
+impl FromSql<Jsonb, Pg> for PropertyValidation {
+    fn from_sql(value: PgValue<'_>) -> diesel::deserialize::Result<Self> {
+        serde_json::from_value(FromSql::<Jsonb, Pg>::from_sql(value)?).map_err(Into::into)
+    }
+}
+
+impl ToSql<Jsonb, Pg> for PropertyValidation {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> diesel::serialize::Result {
+        let json = serde_json::to_value(self).unwrap();
+        <serde_json::Value as ToSql<Jsonb, Pg>>::to_sql(&json, &mut out.reborrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(key: &str, value: &str) -> PropertyValueConfig {
+        PropertyValueConfig {
+            key: ImagePropertyKey::new(key.to_string()).unwrap(),
+            key_group: None,
+            value: value.to_string(),
+            has_changed: true,
+            is_secret: false,
+        }
+    }
+
+    #[test]
+    fn every_broken_rule_is_reported() {
+        let network = PropertyValidation {
+            allowed_values: vec!["mainnet".into(), "testnet".into()],
+            ..Default::default()
+        };
+        let peers = PropertyValidation {
+            min: Some(1),
+            max: Some(100),
+            ..Default::default()
+        };
+        let fee_address = PropertyValidation {
+            regex: Some("0x[0-9a-f]{40}".into()),
+            required_if: Some(RequiredIf {
+                key: "network".into(),
+                value: "mainnet".into(),
+            }),
+            ..Default::default()
+        };
+
+        let values = [
+            value("network", "mainnet"),
+            value("max-peers", "50"),
+            value("fee-address", ""),
+        ];
+        let values: HashMap<_, _> = values
+            .iter()
+            .map(|value| (value.key.as_str(), value.value.as_str()))
+            .collect();
+
+        assert!(network.check("mainnet", &values).is_empty());
+        assert_eq!(network.check("devnet", &values).len(), 1);
+        assert!(peers.check("50", &values).is_empty());
+        assert_eq!(peers.check("0", &values), ["Must be at least 1."]);
+        assert_eq!(peers.check("many", &values), ["Must be an integer."]);
+        assert_eq!(fee_address.check("", &values).len(), 1);
+        assert_eq!(fee_address.check("0x1234", &values).len(), 1);
+
+        let address = format!("0x{}", "a".repeat(40));
+        assert!(fee_address.check(&address, &values).is_empty());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let regex = common::PropertyValidation {
+            regex: Some("[".into()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            PropertyValidation::from_api(regex),
+            Err(Error::Regex(_))
+        ));
+
+        let range = common::PropertyValidation {
+            min: Some(10),
+            max: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            PropertyValidation::from_api(range),
+            Err(Error::MinMax(10, 1))
+        ));
+
+        let empty = common::PropertyValidation::default();
+        assert!(PropertyValidation::from_api(empty).unwrap().is_none());
+    }
+}
@@ -0,0 +1,399 @@
+//! Incidents track the handling of problems reported for a node.
+//!
+//! Each `NodeReport` opens an incident, which the support team acknowledges,
+//! assigns and resolves. Both the org and the support team may comment on it,
+//! and link the commands or node downtimes that relate to the problem.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, Resource, ResourceId, ResourceType, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::node::NodeReport;
+use super::node::report::NodeReportId;
+use super::schema::{incident_comments, incident_links, incidents, sql_types};
+
+/// The maximum length of an incident title taken from a report message.
+const TITLE_MAX_CHARS: usize = 80;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find incident `{0}`: {1}
+    ById(IncidentId, diesel::result::Error),
+    /// Failed to find comments of incident `{0}`: {1}
+    Comments(IncidentId, diesel::result::Error),
+    /// Failed to create incident: {0}
+    Create(diesel::result::Error),
+    /// Failed to comment on incident `{0}`: {1}
+    CreateComment(IncidentId, diesel::result::Error),
+    /// Failed to link incident `{0}`: {1}
+    Link(IncidentId, diesel::result::Error),
+    /// Failed to find links of incident `{0}`: {1}
+    Links(IncidentId, diesel::result::Error),
+    /// Failed to list incidents: {0}
+    List(diesel::result::Error),
+    /// Unknown IncidentLinkType.
+    UnknownLinkType,
+    /// Unknown IncidentStatus.
+    UnknownStatus,
+    /// Failed to update incident `{0}`: {1}
+    Update(IncidentId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("An incident already exists for this report.")
+            }
+            ById(_, NotFound) | Update(_, NotFound) => Status::not_found("Incident not found."),
+            UnknownLinkType => Status::invalid_argument("link_type"),
+            UnknownStatus => Status::invalid_argument("status"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct IncidentId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumIncidentStatus"]
+pub enum IncidentStatus {
+    Open,
+    Acknowledged,
+    Resolved,
+}
+
+impl From<IncidentStatus> for api::IncidentStatus {
+    fn from(status: IncidentStatus) -> Self {
+        match status {
+            IncidentStatus::Open => api::IncidentStatus::Open,
+            IncidentStatus::Acknowledged => api::IncidentStatus::Acknowledged,
+            IncidentStatus::Resolved => api::IncidentStatus::Resolved,
+        }
+    }
+}
+
+impl TryFrom<api::IncidentStatus> for IncidentStatus {
+    type Error = Error;
+
+    fn try_from(status: api::IncidentStatus) -> Result<Self, Self::Error> {
+        match status {
+            api::IncidentStatus::Unspecified => Err(Error::UnknownStatus),
+            api::IncidentStatus::Open => Ok(IncidentStatus::Open),
+            api::IncidentStatus::Acknowledged => Ok(IncidentStatus::Acknowledged),
+            api::IncidentStatus::Resolved => Ok(IncidentStatus::Resolved),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumIncidentLinkType"]
+pub enum IncidentLinkType {
+    Command,
+    Downtime,
+}
+
+impl From<IncidentLinkType> for api::IncidentLinkType {
+    fn from(link_type: IncidentLinkType) -> Self {
+        match link_type {
+            IncidentLinkType::Command => api::IncidentLinkType::Command,
+            IncidentLinkType::Downtime => api::IncidentLinkType::Downtime,
+        }
+    }
+}
+
+impl TryFrom<api::IncidentLinkType> for IncidentLinkType {
+    type Error = Error;
+
+    fn try_from(link_type: api::IncidentLinkType) -> Result<Self, Self::Error> {
+        match link_type {
+            api::IncidentLinkType::Unspecified => Err(Error::UnknownLinkType),
+            api::IncidentLinkType::Command => Ok(IncidentLinkType::Command),
+            api::IncidentLinkType::Downtime => Ok(IncidentLinkType::Downtime),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Incident {
+    pub id: IncidentId,
+    pub org_id: OrgId,
+    pub node_id: NodeId,
+    pub report_id: Option<NodeReportId>,
+    pub title: String,
+    pub status: IncidentStatus,
+    pub assignee_id: Option<UserId>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Incident {
+    pub async fn by_id(id: IncidentId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        incidents::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// Find incidents matching the filter, most recent first.
+    pub async fn list(filter: IncidentFilter, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let mut query = incidents::table.into_boxed();
+
+        if let Some(org_id) = filter.org_id {
+            query = query.filter(incidents::org_id.eq(org_id));
+        }
+        if let Some(node_id) = filter.node_id {
+            query = query.filter(incidents::node_id.eq(node_id));
+        }
+        if let Some(assignee_id) = filter.assignee_id {
+            query = query.filter(incidents::assignee_id.eq(assignee_id));
+        }
+        if !filter.statuses.is_empty() {
+            query = query.filter(incidents::status.eq_any(filter.statuses));
+        }
+
+        query
+            .order_by(incidents::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(Error::List)
+    }
+
+    /// Move the incident to `status`, reopening it when it was resolved.
+    pub async fn set_status(
+        &self,
+        status: IncidentStatus,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let now = Utc::now();
+        let (acknowledged_at, resolved_at) = match status {
+            IncidentStatus::Open => (self.acknowledged_at, None),
+            IncidentStatus::Acknowledged => (self.acknowledged_at.or(Some(now)), None),
+            IncidentStatus::Resolved => (self.acknowledged_at, self.resolved_at.or(Some(now))),
+        };
+
+        diesel::update(incidents::table.find(self.id))
+            .set((
+                incidents::status.eq(status),
+                incidents::acknowledged_at.eq(acknowledged_at),
+                incidents::resolved_at.eq(resolved_at),
+                incidents::updated_at.eq(now),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(self.id, err))
+    }
+
+    pub async fn assign(
+        &self,
+        assignee_id: Option<UserId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::update(incidents::table.find(self.id))
+            .set((
+                incidents::assignee_id.eq(assignee_id),
+                incidents::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(self.id, err))
+    }
+
+    pub async fn comments(&self, conn: &mut Conn<'_>) -> Result<Vec<IncidentComment>, Error> {
+        incident_comments::table
+            .filter(incident_comments::incident_id.eq(self.id))
+            .order_by(incident_comments::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Comments(self.id, err))
+    }
+
+    pub async fn links(&self, conn: &mut Conn<'_>) -> Result<Vec<IncidentLink>, Error> {
+        incident_links::table
+            .filter(incident_links::incident_id.eq(self.id))
+            .order_by(incident_links::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Links(self.id, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<Incident> for api::Incident {
+    fn from(incident: Incident) -> Self {
+        let created_by = common::Resource::from(incident.created_by());
+        api::Incident {
+            incident_id: incident.id.to_string(),
+            org_id: incident.org_id.to_string(),
+            node_id: incident.node_id.to_string(),
+            report_id: incident.report_id.map(|id| id.to_string()),
+            title: incident.title,
+            status: api::IncidentStatus::from(incident.status).into(),
+            assignee_id: incident.assignee_id.map(|id| id.to_string()),
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(incident.created_at).into()),
+            updated_at: Some(NanosUtc::from(incident.updated_at).into()),
+            acknowledged_at: incident.acknowledged_at.map(NanosUtc::from).map(Into::into),
+            resolved_at: incident.resolved_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IncidentFilter {
+    pub org_id: Option<OrgId>,
+    pub node_id: Option<NodeId>,
+    pub assignee_id: Option<UserId>,
+    pub statuses: Vec<IncidentStatus>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = incidents)]
+pub struct NewIncident {
+    pub org_id: OrgId,
+    pub node_id: NodeId,
+    pub report_id: Option<NodeReportId>,
+    pub title: String,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+}
+
+impl NewIncident {
+    /// Open an incident for a report, titled after its first line.
+    pub fn from_report(report: &NodeReport, org_id: OrgId) -> Self {
+        let line = report.message.lines().next().unwrap_or_default().trim();
+        let title = if line.chars().count() > TITLE_MAX_CHARS {
+            let truncated: String = line.chars().take(TITLE_MAX_CHARS - 3).collect();
+            format!("{truncated}...")
+        } else {
+            line.to_string()
+        };
+
+        NewIncident {
+            org_id,
+            node_id: report.node_id,
+            report_id: Some(report.id),
+            title,
+            created_by_type: report.created_by_type,
+            created_by_id: report.created_by_id,
+        }
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Incident, Error> {
+        diesel::insert_into(incidents::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct IncidentComment {
+    pub id: Uuid,
+    pub incident_id: IncidentId,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl IncidentComment {
+    pub async fn create(
+        incident_id: IncidentId,
+        created_by: Resource,
+        message: String,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::insert_into(incident_comments::table)
+            .values((
+                incident_comments::incident_id.eq(incident_id),
+                incident_comments::created_by_type.eq(created_by.typ()),
+                incident_comments::created_by_id.eq(created_by.id()),
+                incident_comments::message.eq(message),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::CreateComment(incident_id, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<IncidentComment> for api::IncidentComment {
+    fn from(comment: IncidentComment) -> Self {
+        let created_by = common::Resource::from(comment.created_by());
+        api::IncidentComment {
+            comment_id: comment.id.to_string(),
+            incident_id: comment.incident_id.to_string(),
+            created_by: Some(created_by),
+            message: comment.message,
+            created_at: Some(NanosUtc::from(comment.created_at).into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct IncidentLink {
+    pub incident_id: IncidentId,
+    pub link_type: IncidentLinkType,
+    pub link_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl IncidentLink {
+    /// Link a resource to an incident, ignoring links that already exist.
+    pub async fn create(
+        incident_id: IncidentId,
+        link_type: IncidentLinkType,
+        link_id: Uuid,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(incident_links::table)
+            .values((
+                incident_links::incident_id.eq(incident_id),
+                incident_links::link_type.eq(link_type),
+                incident_links::link_id.eq(link_id),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Link(incident_id, err))
+    }
+}
+
+impl From<IncidentLink> for api::IncidentLink {
+    fn from(link: IncidentLink) -> Self {
+        api::IncidentLink {
+            link_type: api::IncidentLinkType::from(link.link_type).into(),
+            link_id: link.link_id.to_string(),
+            created_at: Some(NanosUtc::from(link.created_at).into()),
+        }
+    }
+}
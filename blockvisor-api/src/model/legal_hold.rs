@@ -0,0 +1,245 @@
+//! Legal holds that preserve the data of an org or node for compliance.
+//!
+//! While a hold is active, its org or node may not be deleted, a deleted org is
+//! not purged, and the retention job keeps any partition or artifact holding
+//! its data. Holds are never deleted, only cleared, so that their history is
+//! kept alongside the audit log.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::{CheckViolation, UniqueViolation};
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::{legal_holds, nodes};
+
+/// The status reason of a delete that is blocked by a legal hold.
+const LEGAL_HOLD: &str = "LEGAL_HOLD";
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Legal hold `{0}` was already cleared.
+    AlreadyCleared(LegalHoldId),
+    /// Failed to find legal hold `{0}`: {1}
+    ById(LegalHoldId, diesel::result::Error),
+    /// Failed to check for a legal hold: {0}
+    Check(diesel::result::Error),
+    /// Failed to clear legal hold `{0}`: {1}
+    Clear(LegalHoldId, diesel::result::Error),
+    /// Failed to create legal hold: {0}
+    Create(diesel::result::Error),
+    /// Failed to find held orgs: {0}
+    HeldOrgs(diesel::result::Error),
+    /// Failed to find held nodes: {0}
+    HeldNodes(diesel::result::Error),
+    /// Failed to list legal holds: {0}
+    List(diesel::result::Error),
+    /// Node `{0}` is under legal hold.
+    NodeHeld(NodeId),
+    /// Org `{0}` is under legal hold.
+    OrgHeld(OrgId),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            AlreadyCleared(_) => Status::failed_precondition("Legal hold was already cleared."),
+            ById(_, NotFound) => Status::not_found("Legal hold not found."),
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Legal hold already exists.")
+            }
+            Create(DatabaseError(CheckViolation, _)) => {
+                Status::invalid_argument("Legal hold needs one of `org_id` or `node_id`.")
+            }
+            NodeHeld(_) => {
+                Status::failed_precondition("Node is under legal hold.").with_reason(LEGAL_HOLD)
+            }
+            OrgHeld(_) => {
+                Status::failed_precondition("Org is under legal hold.").with_reason(LEGAL_HOLD)
+            }
+            ById(..) | Check(_) | Clear(..) | Create(_) | HeldOrgs(_) | HeldNodes(_) | List(_) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct LegalHoldId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = legal_holds)]
+pub struct LegalHold {
+    pub id: LegalHoldId,
+    pub org_id: Option<OrgId>,
+    pub node_id: Option<NodeId>,
+    pub reason: String,
+    pub created_by: Option<UserId>,
+    pub created_at: DateTime<Utc>,
+    pub cleared_at: Option<DateTime<Utc>>,
+    pub cleared_by: Option<UserId>,
+}
+
+impl LegalHold {
+    pub async fn by_id(id: LegalHoldId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        legal_holds::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The active holds, newest first, or all holds if `include_cleared`.
+    pub async fn list(include_cleared: bool, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let mut query = legal_holds::table.into_boxed();
+        if !include_cleared {
+            query = query.filter(legal_holds::cleared_at.is_null());
+        }
+
+        query
+            .order_by(legal_holds::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(Error::List)
+    }
+
+    pub async fn clear(self, cleared_by: UserId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        if self.cleared_at.is_some() {
+            return Err(Error::AlreadyCleared(self.id));
+        }
+
+        diesel::update(legal_holds::table.find(self.id))
+            .set((
+                legal_holds::cleared_at.eq(Utc::now()),
+                legal_holds::cleared_by.eq(cleared_by),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Clear(self.id, err))
+    }
+
+    /// Returns an error if org `org_id` is under an active hold.
+    pub async fn check_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let held: bool = diesel::select(exists(
+            legal_holds::table
+                .filter(legal_holds::org_id.eq(org_id))
+                .filter(legal_holds::cleared_at.is_null()),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(Error::Check)?;
+
+        if held {
+            Err(Error::OrgHeld(org_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns an error if node `node_id` or its org is under an active hold.
+    pub async fn check_node(
+        node_id: NodeId,
+        org_id: OrgId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        Self::check_org(org_id, conn).await?;
+
+        let held: bool = diesel::select(exists(
+            legal_holds::table
+                .filter(legal_holds::node_id.eq(node_id))
+                .filter(legal_holds::cleared_at.is_null()),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(Error::Check)?;
+
+        if held {
+            Err(Error::NodeHeld(node_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The orgs under an active hold.
+    pub async fn held_org_ids(conn: &mut Conn<'_>) -> Result<HashSet<OrgId>, Error> {
+        legal_holds::table
+            .filter(legal_holds::cleared_at.is_null())
+            .filter(legal_holds::org_id.is_not_null())
+            .select(legal_holds::org_id.assume_not_null())
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<OrgId>| ids.into_iter().collect())
+            .map_err(Error::HeldOrgs)
+    }
+
+    /// The nodes under an active hold, either directly or through their org.
+    pub async fn held_node_ids(conn: &mut Conn<'_>) -> Result<HashSet<NodeId>, Error> {
+        let mut node_ids: HashSet<NodeId> = legal_holds::table
+            .filter(legal_holds::cleared_at.is_null())
+            .filter(legal_holds::node_id.is_not_null())
+            .select(legal_holds::node_id.assume_not_null())
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<NodeId>| ids.into_iter().collect())
+            .map_err(Error::HeldNodes)?;
+
+        let org_ids = Self::held_org_ids(conn).await?;
+        let org_node_ids: Vec<NodeId> = nodes::table
+            .filter(nodes::org_id.eq_any(org_ids))
+            .select(nodes::id)
+            .get_results(conn)
+            .await
+            .map_err(Error::HeldNodes)?;
+        node_ids.extend(org_node_ids);
+
+        Ok(node_ids)
+    }
+}
+
+impl From<LegalHold> for api::LegalHold {
+    fn from(hold: LegalHold) -> Self {
+        api::LegalHold {
+            hold_id: hold.id.to_string(),
+            org_id: hold.org_id.map(|id| id.to_string()),
+            node_id: hold.node_id.map(|id| id.to_string()),
+            reason: hold.reason,
+            created_by: hold.created_by.map(|id| id.to_string()),
+            created_at: Some(NanosUtc::from(hold.created_at).into()),
+            cleared_at: hold.cleared_at.map(NanosUtc::from).map(Into::into),
+            cleared_by: hold.cleared_by.map(|id| id.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = legal_holds)]
+pub struct NewLegalHold {
+    pub org_id: Option<OrgId>,
+    pub node_id: Option<NodeId>,
+    pub reason: String,
+    pub created_by: UserId,
+}
+
+impl NewLegalHold {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<LegalHold, Error> {
+        diesel::insert_into(legal_holds::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -0,0 +1,378 @@
+//! Hosts provisioned on an org's own cloud account.
+//!
+//! A `ManagedHost` tracks a machine created through the API of a cloud provider
+//! (see `crate::cloud_providers`) until blockvisord registers it as a host:
+//! - `Creating` until the provision job has created the machine,
+//! - `Bootstrapping` while the machine boots and installs blockvisord,
+//! - `Ready` once a host registers from the machine's ip address.
+//!
+//! Deprovisioning marks the machine `Deleting` until the job has deleted it
+//! with the provider. A machine that fails to create or bootstrap is `Failed`
+//! with the reason in `error`, and must be deprovisioned by the org.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use strum::IntoStaticStr;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{HostId, OrgId, Resource, ResourceId, ResourceType};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::RegionId;
+use super::schema::{hosts, managed_hosts, sql_types};
+use super::sql::IpNetwork;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find managed host `{0}`: {1}
+    ById(ManagedHostId, diesel::result::Error),
+    /// Failed to find managed hosts for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Failed to create managed host: {0}
+    Create(diesel::result::Error),
+    /// Managed host `{0}` is already being deprovisioned.
+    Deprovisioning(ManagedHostId),
+    /// Managed host {0} must not be empty.
+    Empty(&'static str),
+    /// Failed to find the registered host of managed host `{0}`: {1}
+    FindHost(ManagedHostId, diesel::result::Error),
+    /// Failed to find pending managed hosts: {0}
+    Pending(diesel::result::Error),
+    /// Failed to register the managed host at `{0}`: {1}
+    Registered(IpNetwork, diesel::result::Error),
+    /// Unknown CloudProvider.
+    UnknownProvider,
+    /// Failed to update managed host `{0}`: {1}
+    Update(ManagedHostId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Update(_, NotFound) => Status::not_found("Managed host not found."),
+            Deprovisioning(_) => Status::failed_precondition("Already deprovisioning."),
+            Empty(field) => Status::invalid_argument(field),
+            UnknownProvider => Status::invalid_argument("provider"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ManagedHostId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum, IntoStaticStr)]
+#[ExistingTypePath = "sql_types::EnumCloudProvider"]
+#[strum(serialize_all = "snake_case")]
+pub enum CloudProvider {
+    Aws,
+    Hetzner,
+    Ovh,
+}
+
+impl CloudProvider {
+    pub fn name(self) -> &'static str {
+        self.into()
+    }
+}
+
+impl From<CloudProvider> for api::CloudProvider {
+    fn from(provider: CloudProvider) -> Self {
+        match provider {
+            CloudProvider::Aws => api::CloudProvider::Aws,
+            CloudProvider::Hetzner => api::CloudProvider::Hetzner,
+            CloudProvider::Ovh => api::CloudProvider::Ovh,
+        }
+    }
+}
+
+impl TryFrom<api::CloudProvider> for CloudProvider {
+    type Error = Error;
+
+    fn try_from(provider: api::CloudProvider) -> Result<Self, Self::Error> {
+        match provider {
+            api::CloudProvider::Unspecified => Err(Error::UnknownProvider),
+            api::CloudProvider::Aws => Ok(CloudProvider::Aws),
+            api::CloudProvider::Hetzner => Ok(CloudProvider::Hetzner),
+            api::CloudProvider::Ovh => Ok(CloudProvider::Ovh),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumManagedHostStatus"]
+pub enum ManagedHostStatus {
+    Creating,
+    Bootstrapping,
+    Ready,
+    Failed,
+    Deleting,
+    Deleted,
+}
+
+impl From<ManagedHostStatus> for api::ManagedHostStatus {
+    fn from(status: ManagedHostStatus) -> Self {
+        match status {
+            ManagedHostStatus::Creating => api::ManagedHostStatus::Creating,
+            ManagedHostStatus::Bootstrapping => api::ManagedHostStatus::Bootstrapping,
+            ManagedHostStatus::Ready => api::ManagedHostStatus::Ready,
+            ManagedHostStatus::Failed => api::ManagedHostStatus::Failed,
+            ManagedHostStatus::Deleting => api::ManagedHostStatus::Deleting,
+            ManagedHostStatus::Deleted => api::ManagedHostStatus::Deleted,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct ManagedHost {
+    pub id: ManagedHostId,
+    pub org_id: OrgId,
+    pub region_id: RegionId,
+    pub provider: CloudProvider,
+    pub provider_region: String,
+    pub machine_type: String,
+    pub machine_image: String,
+    pub name: String,
+    pub status: ManagedHostStatus,
+    pub external_id: Option<String>,
+    pub ip_address: Option<IpNetwork>,
+    pub host_id: Option<HostId>,
+    pub error: Option<String>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl ManagedHost {
+    pub async fn by_id(id: ManagedHostId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        managed_hosts::table
+            .find(id)
+            .filter(managed_hosts::deleted_at.is_null())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        managed_hosts::table
+            .filter(managed_hosts::org_id.eq(org_id))
+            .filter(managed_hosts::deleted_at.is_null())
+            .order_by(managed_hosts::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Managed hosts waiting on the provider to create, boot or delete them.
+    pub async fn pending(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let pending = [
+            ManagedHostStatus::Creating,
+            ManagedHostStatus::Bootstrapping,
+            ManagedHostStatus::Deleting,
+        ];
+
+        managed_hosts::table
+            .filter(managed_hosts::status.eq_any(pending))
+            .order_by(managed_hosts::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::Pending)
+    }
+
+    /// Link the host registered by blockvisord to the bootstrapping managed
+    /// host of the org with the same ip address, if there is one.
+    pub async fn registered(
+        org_id: OrgId,
+        ip_address: IpNetwork,
+        host_id: HostId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Self>, Error> {
+        diesel::update(managed_hosts::table)
+            .filter(managed_hosts::org_id.eq(org_id))
+            .filter(managed_hosts::ip_address.eq(ip_address))
+            .filter(managed_hosts::status.eq(ManagedHostStatus::Bootstrapping))
+            .set((
+                managed_hosts::status.eq(ManagedHostStatus::Ready),
+                managed_hosts::host_id.eq(host_id),
+                managed_hosts::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::Registered(ip_address, err))
+    }
+
+    /// Link a host that registered before the ip address of its machine was
+    /// known to this managed host.
+    pub async fn link_host(&self, conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        let Some(ip_address) = self.ip_address else {
+            return Ok(None);
+        };
+
+        let host_id: Option<HostId> = hosts::table
+            .filter(hosts::org_id.eq(self.org_id))
+            .filter(hosts::ip_address.eq(ip_address))
+            .filter(hosts::deleted_at.is_null())
+            .select(hosts::id)
+            .first(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::FindHost(self.id, err))?;
+
+        match host_id {
+            Some(host_id) => Self::registered(self.org_id, ip_address, host_id, conn).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Record the machine created with the provider, which is now booting.
+    pub async fn created(&self, external_id: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        diesel::update(managed_hosts::table.find(id))
+            .set((
+                managed_hosts::status.eq(ManagedHostStatus::Bootstrapping),
+                managed_hosts::external_id.eq(external_id),
+                managed_hosts::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+
+    pub async fn set_ip_address(
+        &self,
+        ip_address: IpNetwork,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let id = self.id;
+        diesel::update(managed_hosts::table.find(id))
+            .set((
+                managed_hosts::ip_address.eq(ip_address),
+                managed_hosts::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+
+    pub async fn failed(&self, error: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        diesel::update(managed_hosts::table.find(id))
+            .set((
+                managed_hosts::status.eq(ManagedHostStatus::Failed),
+                managed_hosts::error.eq(error),
+                managed_hosts::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+
+    /// Mark the machine for deletion by the provision job.
+    pub async fn deprovision(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        if matches!(
+            self.status,
+            ManagedHostStatus::Deleting | ManagedHostStatus::Deleted
+        ) {
+            return Err(Error::Deprovisioning(id));
+        }
+
+        diesel::update(managed_hosts::table.find(id))
+            .set((
+                managed_hosts::status.eq(ManagedHostStatus::Deleting),
+                managed_hosts::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+
+    /// Record that the machine no longer exists with the provider.
+    pub async fn deleted(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        diesel::update(managed_hosts::table.find(id))
+            .set((
+                managed_hosts::status.eq(ManagedHostStatus::Deleted),
+                managed_hosts::updated_at.eq(Utc::now()),
+                managed_hosts::deleted_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<ManagedHost> for api::ManagedHost {
+    fn from(host: ManagedHost) -> Self {
+        let created_by = common::Resource::from(host.created_by());
+        api::ManagedHost {
+            managed_host_id: host.id.to_string(),
+            org_id: host.org_id.to_string(),
+            region_id: host.region_id.to_string(),
+            provider: api::CloudProvider::from(host.provider).into(),
+            provider_region: host.provider_region,
+            machine_type: host.machine_type,
+            machine_image: host.machine_image,
+            name: host.name,
+            status: api::ManagedHostStatus::from(host.status).into(),
+            external_id: host.external_id,
+            ip_address: host.ip_address.map(|ip| ip.to_string()),
+            host_id: host.host_id.map(|id| id.to_string()),
+            error: host.error,
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(host.created_at).into()),
+            updated_at: Some(NanosUtc::from(host.updated_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = managed_hosts)]
+pub struct NewManagedHost<'a> {
+    pub org_id: OrgId,
+    pub region_id: RegionId,
+    pub provider: CloudProvider,
+    pub provider_region: &'a str,
+    pub machine_type: &'a str,
+    pub machine_image: &'a str,
+    pub name: &'a str,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+}
+
+impl NewManagedHost<'_> {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<ManagedHost, Error> {
+        let required = [
+            ("provider_region", self.provider_region),
+            ("machine_type", self.machine_type),
+            ("machine_image", self.machine_image),
+            ("name", self.name),
+        ];
+        if let Some((field, _)) = required.iter().find(|(_, value)| value.trim().is_empty()) {
+            return Err(Error::Empty(field));
+        }
+
+        diesel::insert_into(managed_hosts::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
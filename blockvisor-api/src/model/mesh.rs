@@ -0,0 +1,455 @@
+//! WireGuard meshes between the hosts and nodes of an org.
+//!
+//! Each org with a mesh is allocated a subnet of `mesh.network`, and each peer
+//! that joins is allocated an address within it. A peer is either a host, or a
+//! node on a host, and generates its own key pair so that only its public key
+//! is ever known here.
+//!
+//! The interfaces of a host and its nodes are sent together in a `HostMesh`
+//! command, with every other live peer of the same org as a WireGuard peer.
+//! Whenever the peers of an org change, each host with a peer in that org is
+//! sent its interfaces again.
+
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use ipnetwork::{IpNetwork as IpNet, Ipv4Network};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{HostId, NodeId, OrgId};
+use crate::config::mesh::Config;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::command::{Command, CommandType, NewCommand};
+use super::schema::{hosts, mesh_networks, mesh_peers, nodes};
+use super::sql::IpNetwork;
+
+/// How often peers send a keepalive, which holds open NAT mappings.
+const PERSISTENT_KEEPALIVE_SECONDS: u32 = 25;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find mesh peer `{0}`: {1}
+    ById(MeshPeerId, diesel::result::Error),
+    /// Failed to find mesh peers of org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Mesh command error: {0}
+    Command(#[from] super::command::Error),
+    /// Failed to delete mesh peer: {0}
+    Delete(diesel::result::Error),
+    /// Failed to find mesh peers of host `{0}`: {1}
+    HostPeers(HostId, diesel::result::Error),
+    /// Mesh peer public key is not a valid WireGuard key.
+    InvalidPublicKey,
+    /// Failed to join mesh: {0}
+    Join(diesel::result::Error),
+    /// Failed to find the mesh network of org `{0}`: {1}
+    Network(OrgId, diesel::result::Error),
+    /// No free addresses left in the mesh of org `{0}`.
+    NoFreeAddress(OrgId),
+    /// No free subnets left in `mesh.network`.
+    NoFreeSubnet,
+    /// Failed to find mesh peers: {0}
+    Peers(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Join(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Public key already in use.")
+            }
+            ById(_, NotFound) => Status::not_found("Mesh peer not found."),
+            InvalidPublicKey => Status::invalid_argument("public_key"),
+            NoFreeAddress(_) => Status::resource_exhausted("No free mesh addresses."),
+            NoFreeSubnet => Status::resource_exhausted("No free mesh subnets."),
+            Command(err) => err.into(),
+            ById(..) | ByOrgId(..) | Delete(_) | HostPeers(..) | Join(_) | Network(..)
+            | Peers(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct MeshPeerId(Uuid);
+
+/// The subnet allocated to the mesh of an org.
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct MeshNetwork {
+    pub org_id: OrgId,
+    pub subnet: IpNet,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MeshNetwork {
+    /// Find or allocate the mesh network of an org, locking it until the
+    /// transaction commits so that peers join one at a time.
+    async fn for_org(org_id: OrgId, config: &Config, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let existing = mesh_networks::table
+            .find(org_id)
+            .for_update()
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::Network(org_id, err))?;
+        if let Some(network) = existing {
+            return Ok(network);
+        }
+
+        let used: HashSet<IpNet> = mesh_networks::table
+            .select(mesh_networks::subnet)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Network(org_id, err))?
+            .into_iter()
+            .collect();
+        let subnet = subnets(config)
+            .map(IpNet::V4)
+            .find(|subnet| !used.contains(subnet))
+            .ok_or(Error::NoFreeSubnet)?;
+
+        diesel::insert_into(mesh_networks::table)
+            .values((
+                mesh_networks::org_id.eq(org_id),
+                mesh_networks::subnet.eq(subnet),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Network(org_id, err))
+    }
+
+    /// The first address of the subnet that no peer of `used` has.
+    fn next_ip(&self, used: &HashSet<IpAddr>) -> Option<IpNetwork> {
+        let IpNet::V4(subnet) = self.subnet else {
+            return None;
+        };
+        let network = u32::from(subnet.network());
+        let broadcast = u32::from(subnet.broadcast());
+
+        ((network + 1)..broadcast)
+            .map(|ip| IpAddr::V4(Ipv4Addr::from(ip)))
+            .find(|ip| !used.contains(ip))
+            .map(|ip| IpNetwork::from(IpNet::from(ip)))
+    }
+}
+
+/// Each subnet of `subnet_prefix` within `mesh.network`.
+fn subnets(config: &Config) -> impl Iterator<Item = Ipv4Network> {
+    let base = u32::from(config.network.network());
+    let size = 1u64 << (32 - u32::from(config.subnet_prefix));
+    let count = 1u64 << u32::from(config.subnet_prefix - config.network.prefix());
+    let prefix = config.subnet_prefix;
+
+    (0..count).filter_map(move |index| {
+        let offset = u32::try_from(index * size).ok()?;
+        Ipv4Network::new(Ipv4Addr::from(base + offset), prefix).ok()
+    })
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct MeshPeer {
+    pub id: MeshPeerId,
+    pub org_id: OrgId,
+    pub host_id: HostId,
+    pub node_id: Option<NodeId>,
+    pub public_key: String,
+    pub ip: IpNetwork,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl MeshPeer {
+    pub async fn by_id(id: MeshPeerId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        mesh_peers::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        mesh_peers::table
+            .filter(mesh_peers::org_id.eq(org_id))
+            .order_by(mesh_peers::ip)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Join a host or node to the mesh of an org.
+    ///
+    /// A peer that has already joined keeps its address and replaces its
+    /// public key, which is how a peer rotates its key.
+    pub async fn join(
+        new_peer: NewMeshPeer<'_>,
+        config: &Config,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let public_key = parse_public_key(new_peer.public_key)?;
+        let network = MeshNetwork::for_org(new_peer.org_id, config, conn).await?;
+
+        let existing = if let Some(node_id) = new_peer.node_id {
+            mesh_peers::table
+                .filter(mesh_peers::node_id.eq(node_id))
+                .into_boxed()
+        } else {
+            mesh_peers::table
+                .filter(mesh_peers::host_id.eq(new_peer.host_id))
+                .filter(mesh_peers::node_id.is_null())
+                .into_boxed()
+        };
+        let existing: Option<Self> = existing
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(Error::Join)?;
+
+        if let Some(peer) = existing {
+            return diesel::update(mesh_peers::table.find(peer.id))
+                .set((
+                    mesh_peers::host_id.eq(new_peer.host_id),
+                    mesh_peers::public_key.eq(public_key),
+                    mesh_peers::updated_at.eq(Utc::now()),
+                ))
+                .get_result(conn)
+                .await
+                .map_err(Error::Join);
+        }
+
+        let used: HashSet<IpAddr> = mesh_peers::table
+            .filter(mesh_peers::org_id.eq(new_peer.org_id))
+            .select(mesh_peers::ip)
+            .get_results(conn)
+            .await
+            .map_err(Error::Join)?
+            .into_iter()
+            .map(|ip: IpNetwork| ip.ip())
+            .collect();
+        let ip = network
+            .next_ip(&used)
+            .ok_or(Error::NoFreeAddress(new_peer.org_id))?;
+
+        diesel::insert_into(mesh_peers::table)
+            .values((
+                mesh_peers::org_id.eq(new_peer.org_id),
+                mesh_peers::host_id.eq(new_peer.host_id),
+                mesh_peers::node_id.eq(new_peer.node_id),
+                mesh_peers::public_key.eq(public_key),
+                mesh_peers::ip.eq(ip),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(Error::Join)
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(mesh_peers::table.find(self.id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(Error::Delete)
+    }
+
+    /// Remove the peer of a deleted node, returning it if the node had one.
+    pub async fn delete_for_node(
+        node_id: NodeId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Self>, Error> {
+        diesel::delete(mesh_peers::table.filter(mesh_peers::node_id.eq(node_id)))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(Error::Delete)
+    }
+
+    /// Remove the peers of a deleted host, returning them.
+    pub async fn delete_for_host(host_id: HostId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        diesel::delete(mesh_peers::table.filter(mesh_peers::host_id.eq(host_id)))
+            .get_results(conn)
+            .await
+            .map_err(Error::Delete)
+    }
+
+    /// Send each host with a peer in `org_ids` its interfaces, along with
+    /// `removed_from` hosts whose peers have left.
+    ///
+    /// Returns the commands to send once the transaction commits.
+    pub async fn send(
+        org_ids: &HashSet<OrgId>,
+        removed_from: &HashSet<HostId>,
+        config: &Config,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Command>, Error> {
+        let mut host_ids: HashSet<HostId> = mesh_peers::table
+            .inner_join(hosts::table)
+            .filter(mesh_peers::org_id.eq_any(org_ids))
+            .filter(hosts::deleted_at.is_null())
+            .select(mesh_peers::host_id)
+            .distinct()
+            .get_results(conn)
+            .await
+            .map_err(Error::Peers)?
+            .into_iter()
+            .collect();
+        host_ids.extend(removed_from);
+
+        let mut commands = Vec::with_capacity(host_ids.len());
+        for host_id in host_ids {
+            let mesh = Self::host_mesh(host_id, config, conn).await?;
+            let command = NewCommand::host(host_id, CommandType::HostMesh)?
+                .with_protobuf(&mesh)
+                .create(conn)
+                .await?;
+            commands.push(command);
+        }
+
+        Ok(commands)
+    }
+
+    /// The interfaces of a host and its nodes, each with every other live peer
+    /// of the same org.
+    pub async fn host_mesh(
+        host_id: HostId,
+        config: &Config,
+        conn: &mut Conn<'_>,
+    ) -> Result<api::HostMesh, Error> {
+        let local: Vec<Self> = mesh_peers::table
+            .filter(mesh_peers::host_id.eq(host_id))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::HostPeers(host_id, err))?;
+        let org_ids: HashSet<OrgId> = local.iter().map(|peer| peer.org_id).collect();
+
+        let live: Vec<(Self, IpNetwork, Option<IpNetwork>)> = mesh_peers::table
+            .inner_join(hosts::table)
+            .left_join(nodes::table)
+            .filter(mesh_peers::org_id.eq_any(&org_ids))
+            .filter(hosts::deleted_at.is_null())
+            .filter(nodes::deleted_at.is_null())
+            .select((
+                MeshPeer::as_select(),
+                hosts::ip_address,
+                nodes::ip_address.nullable(),
+            ))
+            .order_by(mesh_peers::ip)
+            .get_results(conn)
+            .await
+            .map_err(Error::Peers)?;
+
+        let mut by_org: HashMap<OrgId, Vec<api::WireguardPeer>> = HashMap::new();
+        let mut live_ids = HashSet::new();
+        for (peer, host_ip, node_ip) in live {
+            let endpoint = node_ip.unwrap_or(host_ip);
+            by_org
+                .entry(peer.org_id)
+                .or_default()
+                .push(api::WireguardPeer {
+                    peer_id: peer.id.to_string(),
+                    public_key: peer.public_key,
+                    allowed_ips: vec![format!("{}/32", peer.ip)],
+                    endpoint: format!("{endpoint}:{}", config.listen_port),
+                    persistent_keepalive_seconds: PERSISTENT_KEEPALIVE_SECONDS,
+                });
+            live_ids.insert(peer.id);
+        }
+
+        let interfaces = local
+            .into_iter()
+            .filter(|peer| live_ids.contains(&peer.id))
+            .map(|peer| {
+                let peer_id = peer.id.to_string();
+                let peers = by_org
+                    .get(&peer.org_id)
+                    .into_iter()
+                    .flatten()
+                    .filter(|other| other.peer_id != peer_id)
+                    .cloned()
+                    .collect();
+                api::MeshInterface {
+                    peer_id,
+                    node_id: peer.node_id.map(|id| id.to_string()),
+                    address: format!("{}/{}", peer.ip, config.subnet_prefix),
+                    listen_port: u32::from(config.listen_port),
+                    peers,
+                }
+            })
+            .collect();
+
+        Ok(api::HostMesh { interfaces })
+    }
+}
+
+impl From<MeshPeer> for api::MeshPeer {
+    fn from(peer: MeshPeer) -> Self {
+        api::MeshPeer {
+            peer_id: peer.id.to_string(),
+            org_id: peer.org_id.to_string(),
+            host_id: peer.host_id.to_string(),
+            node_id: peer.node_id.map(|id| id.to_string()),
+            public_key: peer.public_key,
+            ip: peer.ip.to_string(),
+            created_at: Some(NanosUtc::from(peer.created_at).into()),
+            updated_at: Some(NanosUtc::from(peer.updated_at).into()),
+        }
+    }
+}
+
+pub struct NewMeshPeer<'a> {
+    pub org_id: OrgId,
+    pub host_id: HostId,
+    pub node_id: Option<NodeId>,
+    pub public_key: &'a str,
+}
+
+/// Check that `public_key` is a base64 encoded WireGuard key.
+fn parse_public_key(public_key: &str) -> Result<String, Error> {
+    let public_key = public_key.trim();
+    super::org_key::OrgKeyType::Wireguard
+        .parse(public_key)
+        .map(|(key, _fingerprint)| key)
+        .map_err(|_| Error::InvalidPublicKey)?;
+    Ok(public_key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocate_subnets_and_addresses() {
+        let config = Config {
+            network: "10.200.0.0/16".parse().unwrap(),
+            subnet_prefix: 24,
+            listen_port: 51820,
+        };
+        let subnets: Vec<_> = subnets(&config).take(2).collect();
+        assert_eq!(subnets[0].to_string(), "10.200.0.0/24");
+        assert_eq!(subnets[1].to_string(), "10.200.1.0/24");
+        assert_eq!(subnets(&config).count(), 256);
+
+        let network = MeshNetwork {
+            org_id: Uuid::new_v4().into(),
+            subnet: IpNet::V4(subnets[1]),
+            created_at: Utc::now(),
+        };
+        let mut used = HashSet::new();
+        let ip = network.next_ip(&used).unwrap();
+        assert_eq!(ip.to_string(), "10.200.1.1");
+
+        used.insert(ip.ip());
+        let ip = network.next_ip(&used).unwrap();
+        assert_eq!(ip.to_string(), "10.200.1.2");
+    }
+}
@@ -3,43 +3,145 @@
 pub mod address;
 pub use address::{Address, AddressId};
 
+pub mod agent_upgrade;
+pub use agent_upgrade::{AgentUpgrade, AgentUpgradeId};
+
+pub mod alert;
+pub use alert::{Alert, AlertId};
+
 pub mod api_key;
 pub use api_key::ApiKey;
 
+pub mod approval;
+pub use approval::{Approval, ApprovalId};
+
+pub mod artifact;
+pub use artifact::{Artifact, ArtifactId};
+
+pub mod audit;
+pub use audit::{AuditEvent, AuditLog};
+
+pub mod backfill;
+pub use backfill::Backfill;
+
+pub mod backorder;
+pub use backorder::{Backorder, BackorderId};
+
 pub mod command;
 pub use command::{Command, CommandId, CommandType};
 
+pub mod endpoint;
+pub use endpoint::{Endpoint, EndpointId};
+
+pub mod feature_flag;
+pub use feature_flag::FeatureFlag;
+
+pub mod freeze_window;
+pub use freeze_window::{FreezeWindow, FreezeWindowId};
+
 pub mod host;
 pub use host::Host;
 
+pub mod host_pool;
+pub use host_pool::{HostPool, HostPoolId};
+
+pub mod host_weights;
+pub use host_weights::{HostRanking, HostWeights};
+
 pub mod image;
 pub use image::{Image, ImageId};
 
+pub mod incident;
+pub use incident::{Incident, IncidentId};
+
 pub mod invitation;
 pub use invitation::{Invitation, InvitationId};
 
 pub mod ip_address;
 pub use ip_address::IpAddress;
 
+pub mod legal_hold;
+pub use legal_hold::{LegalHold, LegalHoldId};
+
+pub mod managed_host;
+pub use managed_host::{ManagedHost, ManagedHostId};
+
+pub mod mesh;
+pub use mesh::{MeshPeer, MeshPeerId};
+
 pub mod node;
 pub use node::Node;
 
+pub mod node_share;
+pub use node_share::{NodeShare, NodeShareId};
+
+pub mod oncall;
+pub use oncall::{EscalationPolicy, OncallSchedule, OncallScheduleId};
+
 pub mod org;
 pub use org::Org;
 
+pub mod org_branding;
+pub use org_branding::OrgBranding;
+
+pub mod org_key;
+pub use org_key::{OrgKey, OrgKeyId};
+
+pub mod org_log_sink;
+pub use org_log_sink::OrgLogSink;
+
+pub mod org_snapshot;
+pub use org_snapshot::OrgSnapshot;
+
+pub mod org_suspension;
+
+pub mod org_transfer;
+pub use org_transfer::{OrgTransfer, OrgTransferId};
+
+pub mod org_trial;
+
+pub mod orphan;
+
 pub mod paginate;
 pub use paginate::Paginate;
 
+pub mod partner;
+pub use partner::{Partner, PartnerId};
+
+pub mod plan;
+pub use plan::{Plan, PlanTier};
+
+pub mod project;
+pub use project::{Project, ProjectId};
+
 pub mod rbac;
 
+pub mod reconciliation;
+pub use reconciliation::{Reconciliation, ReconciliationId};
+
 pub mod region;
 pub use region::{Region, RegionId};
 
+pub mod request_log;
+pub use request_log::RequestLog;
+
+pub mod retention;
+pub use retention::RetainedTable;
+
 #[allow(clippy::wildcard_imports)]
 pub mod schema;
 
 pub mod sql;
 
+pub mod status_page;
+pub use status_page::{StatusPage, StatusPageId};
+
+pub mod team;
+pub use team::{Team, TeamId};
+
+pub mod ticket;
+pub use ticket::{Ticket, TicketIntegration};
+
 pub mod protocol;
 pub use protocol::{Protocol, ProtocolId, ProtocolVersion, VersionId};
 
@@ -0,0 +1,391 @@
+//! Heuristics that flag nodes suspected of abusing free resources.
+//!
+//! The nodes of orgs on the free plan or on trial are checked on each metrics
+//! update. A node is flagged when it connects out to a known mining pool port,
+//! or when its CPU stays pegged for `abuse.pegged_for` while its protocol makes
+//! no progress.
+//!
+//! Each flag opens an `AbuseReport` in the admin review queue. Unless disabled
+//! by `abuse.auto_suspend`, the node is also stopped and moved to
+//! `NodeState::Suspended` until an admin dismisses the report.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, UserId};
+use crate::config::abuse::Config;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::plan::PlanTier;
+use crate::model::schema::{node_abuse_reports, nodes, orgs, sql_types};
+use crate::util::NanosUtc;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Abuse report `{0}` was already reviewed.
+    AlreadyReviewed(AbuseReportId),
+    /// Failed to find abuse report `{0}`: {1}
+    ById(AbuseReportId, diesel::result::Error),
+    /// Failed to create abuse report for node `{0}`: {1}
+    Create(NodeId, diesel::result::Error),
+    /// Failed to find orgs on free resources: {0}
+    FreeOrgs(diesel::result::Error),
+    /// Failed to list abuse reports: {0}
+    List(diesel::result::Error),
+    /// Failed to find open abuse reports: {0}
+    Open(diesel::result::Error),
+    /// Failed to parse the abuse pegged duration: {0}
+    PeggedFor(crate::config::Error),
+    /// Failed to review abuse report `{0}`: {1}
+    Review(AbuseReportId, diesel::result::Error),
+    /// Failed to set when node `{0}` became pegged: {1}
+    SetPegged(NodeId, diesel::result::Error),
+    /// Unknown abuse resolution.
+    UnknownResolution,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            AlreadyReviewed(_) => Status::failed_precondition("Report was already reviewed."),
+            ById(_, NotFound) => Status::not_found("Abuse report not found."),
+            UnknownResolution => Status::invalid_argument("resolution"),
+            ById(..) | Create(..) | FreeOrgs(_) | List(_) | Open(_) | PeggedFor(_) | Review(..)
+            | SetPegged(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct AbuseReportId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAbuseReason"]
+pub enum AbuseReason {
+    /// The CPU stayed pegged while the protocol made no progress.
+    CpuNoProgress,
+    /// The node connected out to a known mining pool port.
+    MiningPort,
+}
+
+impl From<AbuseReason> for api::AbuseReason {
+    fn from(reason: AbuseReason) -> Self {
+        match reason {
+            AbuseReason::CpuNoProgress => api::AbuseReason::CpuNoProgress,
+            AbuseReason::MiningPort => api::AbuseReason::MiningPort,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumAbuseResolution"]
+pub enum AbuseResolution {
+    /// The node was not abusing resources, so it is no longer suspended.
+    Dismissed,
+    /// The node was abusing resources, so it stays suspended.
+    Confirmed,
+}
+
+impl From<AbuseResolution> for api::AbuseResolution {
+    fn from(resolution: AbuseResolution) -> Self {
+        match resolution {
+            AbuseResolution::Dismissed => api::AbuseResolution::Dismissed,
+            AbuseResolution::Confirmed => api::AbuseResolution::Confirmed,
+        }
+    }
+}
+
+impl TryFrom<api::AbuseResolution> for AbuseResolution {
+    type Error = Error;
+
+    fn try_from(resolution: api::AbuseResolution) -> Result<Self, Self::Error> {
+        match resolution {
+            api::AbuseResolution::Unspecified => Err(Error::UnknownResolution),
+            api::AbuseResolution::Dismissed => Ok(AbuseResolution::Dismissed),
+            api::AbuseResolution::Confirmed => Ok(AbuseResolution::Confirmed),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = node_abuse_reports)]
+pub struct AbuseReport {
+    pub id: AbuseReportId,
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub reason: AbuseReason,
+    pub details: String,
+    /// Whether the node was suspended when it was flagged.
+    pub suspended: bool,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<UserId>,
+    pub resolution: Option<AbuseResolution>,
+}
+
+impl AbuseReport {
+    pub async fn by_id(id: AbuseReportId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_abuse_reports::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The reports waiting for review, oldest first, or all reports if
+    /// `include_reviewed`.
+    pub async fn list(include_reviewed: bool, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let mut query = node_abuse_reports::table.into_boxed();
+        if !include_reviewed {
+            query = query.filter(node_abuse_reports::reviewed_at.is_null());
+        }
+
+        query
+            .order_by(node_abuse_reports::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::List)
+    }
+
+    /// The nodes among `node_ids` that have a report waiting for review.
+    pub async fn open_node_ids(
+        node_ids: &HashSet<NodeId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<NodeId>, Error> {
+        node_abuse_reports::table
+            .filter(node_abuse_reports::node_id.eq_any(node_ids))
+            .filter(node_abuse_reports::reviewed_at.is_null())
+            .select(node_abuse_reports::node_id)
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<NodeId>| ids.into_iter().collect())
+            .map_err(Error::Open)
+    }
+
+    pub async fn review(
+        self,
+        reviewed_by: UserId,
+        resolution: AbuseResolution,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        if self.reviewed_at.is_some() {
+            return Err(Error::AlreadyReviewed(self.id));
+        }
+
+        diesel::update(node_abuse_reports::table.find(self.id))
+            .set((
+                node_abuse_reports::reviewed_at.eq(Utc::now()),
+                node_abuse_reports::reviewed_by.eq(reviewed_by),
+                node_abuse_reports::resolution.eq(resolution),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Review(self.id, err))
+    }
+}
+
+impl From<AbuseReport> for api::AbuseReport {
+    fn from(report: AbuseReport) -> Self {
+        api::AbuseReport {
+            report_id: report.id.to_string(),
+            node_id: report.node_id.to_string(),
+            org_id: report.org_id.to_string(),
+            reason: api::AbuseReason::from(report.reason).into(),
+            details: report.details,
+            suspended: report.suspended,
+            created_at: Some(NanosUtc::from(report.created_at).into()),
+            reviewed_at: report.reviewed_at.map(NanosUtc::from).map(Into::into),
+            reviewed_by: report.reviewed_by.map(|id| id.to_string()),
+            resolution: report
+                .resolution
+                .map(|resolution| api::AbuseResolution::from(resolution).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = node_abuse_reports)]
+pub struct NewAbuseReport {
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub reason: AbuseReason,
+    pub details: String,
+    pub suspended: bool,
+}
+
+impl NewAbuseReport {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<AbuseReport, Error> {
+        let node_id = self.node_id;
+        diesel::insert_into(node_abuse_reports::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Create(node_id, err))
+    }
+}
+
+/// What a node used, as reported in its metrics.
+#[derive(Clone, Debug)]
+pub struct NodeUsage {
+    pub node_id: NodeId,
+    /// The percent of its allocated CPU that the node used.
+    pub cpu_percent: Option<u32>,
+    /// The remote ports of the outbound connections of the node.
+    pub outbound_ports: Vec<u32>,
+}
+
+/// The outcome of checking the usage of a node.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Verdict {
+    /// Why the node should be flagged, if it should.
+    pub flag: Option<(AbuseReason, String)>,
+    /// Since when the CPU of the node is pegged without protocol progress.
+    pub pegged_since: Option<DateTime<Utc>>,
+}
+
+impl NodeUsage {
+    /// Check this usage against the abuse heuristics.
+    ///
+    /// `progressed` is whether the protocol advanced since the last metrics,
+    /// and `pegged_since` is when the node was last seen pegged.
+    pub fn evaluate(
+        &self,
+        progressed: bool,
+        pegged_since: Option<DateTime<Utc>>,
+        config: &Config,
+        now: DateTime<Utc>,
+    ) -> Result<Verdict, Error> {
+        let pegged = !progressed
+            && self
+                .cpu_percent
+                .is_some_and(|percent| percent >= config.cpu_percent);
+        let pegged_since = pegged.then(|| pegged_since.unwrap_or(now));
+
+        if let Some(port) = self
+            .outbound_ports
+            .iter()
+            .find(|port| config.mining_ports.contains(port))
+        {
+            let details = format!("Outbound connection to mining port {port}.");
+            return Ok(Verdict {
+                flag: Some((AbuseReason::MiningPort, details)),
+                pegged_since,
+            });
+        }
+
+        let pegged_for = TimeDelta::try_from(config.pegged_for).map_err(Error::PeggedFor)?;
+        let flag = pegged_since
+            .filter(|since| now - *since >= pegged_for)
+            .map(|since| {
+                let percent = self.cpu_percent.unwrap_or_default();
+                let details = format!("CPU at {percent}% with no protocol progress since {since}.");
+                (AbuseReason::CpuNoProgress, details)
+            });
+
+        Ok(Verdict { flag, pegged_since })
+    }
+}
+
+/// The orgs among `org_ids` that run on free resources, either on the free plan
+/// or on trial.
+pub async fn free_org_ids(
+    org_ids: &HashSet<OrgId>,
+    conn: &mut Conn<'_>,
+) -> Result<HashSet<OrgId>, Error> {
+    orgs::table
+        .filter(orgs::id.eq_any(org_ids))
+        .filter(
+            orgs::plan_tier
+                .eq(PlanTier::Free)
+                .or(orgs::trial_ends_at.is_not_null()),
+        )
+        .select(orgs::id)
+        .get_results(conn)
+        .await
+        .map(|ids: Vec<OrgId>| ids.into_iter().collect())
+        .map_err(Error::FreeOrgs)
+}
+
+/// Set since when the CPU of a node is pegged, or clear it with `None`.
+pub async fn set_pegged_since(
+    node_id: NodeId,
+    since: Option<DateTime<Utc>>,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    diesel::update(nodes::table.find(node_id))
+        .set(nodes::cpu_pegged_since.eq(since))
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|err| Error::SetPegged(node_id, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            cpu_percent: 95,
+            pegged_for: "30m".parse().unwrap(),
+            mining_ports: "3333,4444".parse().unwrap(),
+            auto_suspend: true,
+        }
+    }
+
+    fn usage(cpu_percent: u32, outbound_ports: Vec<u32>) -> NodeUsage {
+        NodeUsage {
+            node_id: Uuid::new_v4().into(),
+            cpu_percent: Some(cpu_percent),
+            outbound_ports,
+        }
+    }
+
+    #[test]
+    fn pegged_cpu_without_progress_is_flagged_after_a_while() {
+        let config = config();
+        let now = Utc::now();
+        let usage = usage(99, vec![443]);
+
+        let verdict = usage.evaluate(false, None, &config, now).unwrap();
+        assert_eq!(verdict.flag, None);
+        assert_eq!(verdict.pegged_since, Some(now));
+
+        let since = now - TimeDelta::minutes(10);
+        let verdict = usage.evaluate(false, Some(since), &config, now).unwrap();
+        assert_eq!(verdict.flag, None);
+        assert_eq!(verdict.pegged_since, Some(since));
+
+        let since = now - TimeDelta::minutes(31);
+        let verdict = usage.evaluate(false, Some(since), &config, now).unwrap();
+        assert_eq!(verdict.flag.unwrap().0, AbuseReason::CpuNoProgress);
+
+        let verdict = usage.evaluate(true, Some(since), &config, now).unwrap();
+        assert_eq!(
+            verdict,
+            Verdict {
+                flag: None,
+                pegged_since: None,
+            }
+        );
+    }
+
+    #[test]
+    fn mining_ports_are_flagged_at_once() {
+        let verdict = usage(10, vec![443, 3333])
+            .evaluate(true, None, &config(), Utc::now())
+            .unwrap();
+        assert_eq!(verdict.flag.unwrap().0, AbuseReason::MiningPort);
+    }
+}
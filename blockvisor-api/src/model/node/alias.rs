@@ -0,0 +1,289 @@
+//! Custom DNS names that point at a node.
+//!
+//! An alias is either a CNAME record for the DNS name of the node, or an
+//! address record for its IP, which follows the node to a new IP. Aliases must
+//! be within `cloudflare.dns.base` or one of `cloudflare.dns.alias_zones`, and
+//! are removed with the other DNS records of the node when it is deleted.
+//!
+//! Renaming a node keeps its old address record as an alias that expires after
+//! a grace period, when a background job removes it.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, Resource, ResourceId, ResourceType};
+use crate::cloudflare::AliasTarget;
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::model::schema::{node_dns_aliases, sql_types};
+use crate::util::NanosUtc;
+
+use super::Node;
+
+/// The longest DNS name, excluding the trailing dot.
+const MAX_NAME_LEN: usize = 253;
+/// The longest label of a DNS name.
+const MAX_LABEL_LEN: usize = 63;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find alias `{0}` of node `{1}`: {2}
+    ByName(String, NodeId, diesel::result::Error),
+    /// Failed to find aliases of node `{0}`: {1}
+    ByNodeId(NodeId, diesel::result::Error),
+    /// Failed to find aliases of nodes: {0}
+    ByNodeIds(diesel::result::Error),
+    /// Failed to create node alias: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete node alias `{0}`: {1}
+    Delete(NodeDnsAliasId, diesel::result::Error),
+    /// Failed to delete the aliases of node `{0}`: {1}
+    DeleteForNode(NodeId, diesel::result::Error),
+    /// Failed to find expired node aliases: {0}
+    Expired(diesel::result::Error),
+    /// Alias `{0}` is not a valid DNS name.
+    InvalidName(String),
+    /// Alias `{0}` is outside of the allowed zones.
+    Zone(String),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Alias already exists.")
+            }
+            ByName(_, _, NotFound) => Status::not_found("Alias not found."),
+            InvalidName(_) => Status::invalid_argument("name"),
+            Zone(_) => Status::invalid_argument("name").with_reason("ALIAS_ZONE_NOT_ALLOWED"),
+            ByName(..) | ByNodeId(..) | ByNodeIds(_) | Create(_) | Delete(..)
+            | DeleteForNode(..) | Expired(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct NodeDnsAliasId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumDnsAliasType"]
+pub enum DnsAliasType {
+    Cname,
+    Address,
+}
+
+impl From<DnsAliasType> for api::DnsAliasType {
+    fn from(alias_type: DnsAliasType) -> Self {
+        match alias_type {
+            DnsAliasType::Cname => api::DnsAliasType::Cname,
+            DnsAliasType::Address => api::DnsAliasType::Address,
+        }
+    }
+}
+
+impl DnsAliasType {
+    pub const fn from_api(alias_type: api::DnsAliasType) -> Option<Self> {
+        match alias_type {
+            api::DnsAliasType::Unspecified => None,
+            api::DnsAliasType::Cname => Some(DnsAliasType::Cname),
+            api::DnsAliasType::Address => Some(DnsAliasType::Address),
+        }
+    }
+
+    /// What an alias of this type for `node` points at.
+    pub fn target(self, node: &Node) -> AliasTarget<'_> {
+        match self {
+            DnsAliasType::Cname => AliasTarget::Node(&node.node_name),
+            DnsAliasType::Address => AliasTarget::Address(node.ip_address.ip()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct NodeDnsAlias {
+    pub id: NodeDnsAliasId,
+    pub node_id: NodeId,
+    pub name: String,
+    pub alias_type: DnsAliasType,
+    pub dns_id: String,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    /// When the alias is removed, if it was kept from a rename.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl NodeDnsAlias {
+    pub async fn by_name(name: &str, node_id: NodeId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_dns_aliases::table
+            .filter(node_dns_aliases::node_id.eq(node_id))
+            .filter(node_dns_aliases::name.eq(name))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByName(name.to_string(), node_id, err))
+    }
+
+    pub async fn by_node_id(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        node_dns_aliases::table
+            .filter(node_dns_aliases::node_id.eq(node_id))
+            .order_by(node_dns_aliases::name)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByNodeId(node_id, err))
+    }
+
+    pub async fn by_node_ids<'n, I>(node_ids: I, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error>
+    where
+        I: IntoIterator<Item = &'n NodeId>,
+    {
+        node_dns_aliases::table
+            .filter(node_dns_aliases::node_id.eq_any(node_ids))
+            .order_by(node_dns_aliases::name)
+            .get_results(conn)
+            .await
+            .map_err(Error::ByNodeIds)
+    }
+
+    /// The aliases whose grace period has ended.
+    pub async fn expired(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        node_dns_aliases::table
+            .filter(node_dns_aliases::expires_at.lt(Utc::now()))
+            .get_results(conn)
+            .await
+            .map_err(Error::Expired)
+    }
+
+    /// Check that `name` is a valid DNS name that is `allowed`, returning it in
+    /// lowercase.
+    pub fn parse_name(name: &str, allowed: impl Fn(&str) -> bool) -> Result<String, Error> {
+        let name = name.trim().trim_end_matches('.').to_lowercase();
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+
+        if name.len() > MAX_NAME_LEN || !name.split('.').all(valid_label) {
+            Err(Error::InvalidName(name))
+        } else if !allowed(&name) {
+            Err(Error::Zone(name))
+        } else {
+            Ok(name)
+        }
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(node_dns_aliases::table.find(self.id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(self.id, err))
+    }
+
+    pub async fn delete_for_node(node_id: NodeId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(node_dns_aliases::table.filter(node_dns_aliases::node_id.eq(node_id)))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::DeleteForNode(node_id, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<NodeDnsAlias> for api::NodeDnsAlias {
+    fn from(alias: NodeDnsAlias) -> Self {
+        let created_by = common::Resource::from(alias.created_by());
+        api::NodeDnsAlias {
+            alias_id: alias.id.to_string(),
+            node_id: alias.node_id.to_string(),
+            name: alias.name,
+            alias_type: api::DnsAliasType::from(alias.alias_type).into(),
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(alias.created_at).into()),
+            expires_at: alias.expires_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = node_dns_aliases)]
+pub struct NewNodeDnsAlias {
+    pub node_id: NodeId,
+    pub name: String,
+    pub alias_type: DnsAliasType,
+    pub dns_id: String,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl NewNodeDnsAlias {
+    pub fn new(
+        node: &Node,
+        name: String,
+        alias_type: DnsAliasType,
+        dns_id: String,
+        created_by: Resource,
+    ) -> Self {
+        NewNodeDnsAlias {
+            node_id: node.id,
+            name,
+            alias_type,
+            dns_id,
+            created_by_type: created_by.typ(),
+            created_by_id: created_by.id(),
+            expires_at: None,
+        }
+    }
+
+    /// Remove the alias at `expires_at`.
+    #[must_use]
+    pub const fn expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<NodeDnsAlias, Error> {
+        diesel::insert_into(node_dns_aliases::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alias_names_must_be_in_an_allowed_zone() {
+        let allowed = |name: &str| name.ends_with(".example.com");
+
+        let name = NodeDnsAlias::parse_name(" RPC.Example.com. ", allowed).unwrap();
+        assert_eq!(name, "rpc.example.com");
+
+        let err = NodeDnsAlias::parse_name("rpc.other.com", allowed).unwrap_err();
+        assert!(matches!(err, Error::Zone(_)));
+
+        for invalid in ["-rpc.example.com", "rpc..example.com", "rpc_1.example.com"] {
+            let err = NodeDnsAlias::parse_name(invalid, allowed).unwrap_err();
+            assert!(matches!(err, Error::InvalidName(_)), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Updating every node that matches a filter at once.
+//!
+//! A bulk update may add and remove tags, set the note, toggle auto upgrades
+//! and rename the display name of each node from a `NameTemplate`. Each node
+//! is updated separately so that the display name and note policy of its org
+//! still applies, and a node rejected by its policy doesn't stop the others.
+
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::AuthZ;
+use crate::database::Conn;
+use crate::grpc::Status;
+use crate::model::sql::Tag;
+
+use super::{Node, UpdateNode};
+
+/// The most nodes that a single bulk update may change.
+pub const MAX_BULK_NODES: i64 = 1000;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Display name template is empty.
+    EmptyTemplate,
+    /// Bulk update changes nothing.
+    NoChanges,
+    /// Filter matches {0} nodes, which is more than the limit of {1}.
+    TooManyNodes(u64, i64),
+    /// Display name template has an unclosed placeholder.
+    UnclosedPlaceholder,
+    /// Display name template has an unknown placeholder `{0}`.
+    UnknownPlaceholder(String),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            EmptyTemplate | UnclosedPlaceholder | UnknownPlaceholder(_) => {
+                Status::invalid_argument("display_name_template")
+            }
+            NoChanges => Status::invalid_argument("No changes given."),
+            TooManyNodes(..) => Status::failed_precondition(err.to_string()),
+        }
+    }
+}
+
+/// A part of a display name template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    NodeName,
+    DisplayName,
+    Index,
+}
+
+/// A display name made from each node, like `eth-{index}`.
+///
+/// The placeholders are `{node_name}`, `{display_name}` and `{index}`, which
+/// counts the matching nodes from 1 in the order of the filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameTemplate(Vec<Segment>);
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<Self, Error> {
+        if template.trim().is_empty() {
+            return Err(Error::EmptyTemplate);
+        }
+
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 1..];
+            let end = after.find('}').ok_or(Error::UnclosedPlaceholder)?;
+            segments.push(match &after[..end] {
+                "node_name" => Segment::NodeName,
+                "display_name" => Segment::DisplayName,
+                "index" => Segment::Index,
+                other => return Err(Error::UnknownPlaceholder(other.to_string())),
+            });
+            rest = &after[end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(NameTemplate(segments))
+    }
+
+    /// The display name of `node`, the `index`th matching node.
+    pub fn render(&self, node: &Node, index: usize) -> String {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::NodeName => node.node_name.clone(),
+                Segment::DisplayName => node.display_name.clone(),
+                Segment::Index => index.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// The changes to make to each matching node.
+#[derive(Debug, Default)]
+pub struct BulkUpdate {
+    pub add_tags: Vec<Tag>,
+    pub remove_tags: Vec<Tag>,
+    pub note: Option<String>,
+    pub auto_upgrade: Option<bool>,
+    pub display_name: Option<NameTemplate>,
+}
+
+impl BulkUpdate {
+    /// Check that the update changes something.
+    pub fn check(&self) -> Result<(), Error> {
+        let changes_tags = !self.add_tags.is_empty() || !self.remove_tags.is_empty();
+        if changes_tags
+            || self.note.is_some()
+            || self.auto_upgrade.is_some()
+            || self.display_name.is_some()
+        {
+            Ok(())
+        } else {
+            Err(Error::NoChanges)
+        }
+    }
+
+    /// Whether the host of each node must be sent the update.
+    pub const fn updates_host(&self) -> bool {
+        self.note.is_some() || self.auto_upgrade.is_some() || self.display_name.is_some()
+    }
+
+    /// Apply the update to `node`, the `index`th matching node from 1.
+    pub async fn apply(
+        &self,
+        node: &Node,
+        index: usize,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Node, super::Error> {
+        let display_name = self
+            .display_name
+            .as_ref()
+            .map(|template| template.render(node, index));
+        let tags = (!self.add_tags.is_empty() || !self.remove_tags.is_empty())
+            .then(|| node.tags.clone().edit(&self.add_tags, &self.remove_tags));
+
+        let update = UpdateNode {
+            org_id: None,
+            host_id: None,
+            display_name: display_name.as_deref(),
+            auto_upgrade: self.auto_upgrade,
+            ip_address: None,
+            ip_gateway: None,
+            note: self.note.as_deref().map(Some),
+            tags,
+            cost: None,
+        };
+        update.apply(node.id, authz, conn).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_templates_have_known_placeholders() {
+        let template = NameTemplate::parse("eth-{index} ({node_name})").unwrap();
+        assert_eq!(
+            template.0,
+            vec![
+                Segment::Text("eth-".to_string()),
+                Segment::Index,
+                Segment::Text(" (".to_string()),
+                Segment::NodeName,
+                Segment::Text(")".to_string()),
+            ]
+        );
+
+        assert!(matches!(
+            NameTemplate::parse("{org}"),
+            Err(Error::UnknownPlaceholder(name)) if name == "org"
+        ));
+        assert!(matches!(
+            NameTemplate::parse("eth-{index"),
+            Err(Error::UnclosedPlaceholder)
+        ));
+        assert!(matches!(
+            NameTemplate::parse(" "),
+            Err(Error::EmptyTemplate)
+        ));
+    }
+}
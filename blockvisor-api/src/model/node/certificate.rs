@@ -0,0 +1,235 @@
+//! TLS certificates for the DNS names of a node.
+//!
+//! A certificate covers the DNS name of a node and its aliases. It is requested
+//! as `Pending` and issued in the background, which stores the chain and its
+//! private key in the secret store, then sends the chain to the host in a
+//! `NodeCertificate` command. The host reads the private key with its own
+//! token, so the key is never persisted with the command.
+//!
+//! Issued certificates are renewed within `acme.renew_before` of expiry, and
+//! alerted once within `acme.alert_before` if renewal keeps failing.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::NodeId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{node_certificates, nodes, sql_types};
+use crate::util::NanosUtc;
+
+use super::Node;
+
+/// The secret key of the PEM encoded certificate chain of a node.
+pub const CERTIFICATE_SECRET: &str = "tls-certificate";
+/// The secret key of the PEM encoded private key of a node certificate.
+pub const PRIVATE_KEY_SECRET: &str = "tls-private-key";
+
+/// How long to wait before retrying a certificate that failed to issue.
+const RETRY_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to mark certificate `{0}` as alerted: {1}
+    Alerted(NodeCertificateId, diesel::result::Error),
+    /// Failed to find certificate for node `{0}`: {1}
+    ByNodeId(NodeId, diesel::result::Error),
+    /// Failed to find certificates due for issue: {0}
+    Due(diesel::result::Error),
+    /// Failed to find expiring certificates: {0}
+    Expiring(diesel::result::Error),
+    /// Failed to mark certificate `{0}` as failed: {1}
+    Failed(NodeCertificateId, diesel::result::Error),
+    /// Failed to mark certificate `{0}` as issued: {1}
+    Issued(NodeCertificateId, diesel::result::Error),
+    /// Failed to request certificate for node `{0}`: {1}
+    Request(NodeId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByNodeId(_, diesel::result::Error::NotFound) => {
+                Status::not_found("Certificate not found.")
+            }
+            Alerted(..) | ByNodeId(..) | Due(_) | Expiring(_) | Failed(..) | Issued(..)
+            | Request(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct NodeCertificateId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumCertificateStatus"]
+pub enum CertificateStatus {
+    Pending,
+    Issued,
+    Failed,
+}
+
+impl From<CertificateStatus> for api::CertificateStatus {
+    fn from(status: CertificateStatus) -> Self {
+        match status {
+            CertificateStatus::Pending => api::CertificateStatus::Pending,
+            CertificateStatus::Issued => api::CertificateStatus::Issued,
+            CertificateStatus::Failed => api::CertificateStatus::Failed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct NodeCertificate {
+    pub id: NodeCertificateId,
+    pub node_id: NodeId,
+    pub names: Vec<Option<String>>,
+    pub status: CertificateStatus,
+    pub not_after: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub alerted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NodeCertificate {
+    pub async fn by_node_id(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_certificates::table
+            .filter(node_certificates::node_id.eq(node_id))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByNodeId(node_id, err))
+    }
+
+    /// Request a certificate for `names` of `node`, replacing the names of an
+    /// existing certificate and issuing it again.
+    pub async fn request(
+        node: &Node,
+        names: Vec<String>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let names: Vec<_> = names.into_iter().map(Some).collect();
+        diesel::insert_into(node_certificates::table)
+            .values((
+                node_certificates::node_id.eq(node.id),
+                node_certificates::names.eq(names),
+                node_certificates::status.eq(CertificateStatus::Pending),
+            ))
+            .on_conflict(node_certificates::node_id)
+            .do_update()
+            .set((
+                node_certificates::names.eq(excluded(node_certificates::names)),
+                node_certificates::status.eq(CertificateStatus::Pending),
+                node_certificates::last_error.eq(None::<String>),
+                node_certificates::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Request(node.id, err))
+    }
+
+    /// The certificates of live nodes that are pending, expire within
+    /// `renew_before`, or failed more than `RETRY_INTERVAL` ago.
+    pub async fn due(renew_before: TimeDelta, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let now = Utc::now();
+        let pending = node_certificates::status.eq(CertificateStatus::Pending);
+        let renew = node_certificates::status
+            .eq(CertificateStatus::Issued)
+            .and(node_certificates::not_after.lt(now + renew_before));
+        let retry = node_certificates::status
+            .eq(CertificateStatus::Failed)
+            .and(node_certificates::updated_at.lt(now - RETRY_INTERVAL));
+
+        node_certificates::table
+            .inner_join(nodes::table)
+            .filter(nodes::deleted_at.is_null())
+            .filter(pending.or(renew).or(retry))
+            .select(NodeCertificate::as_select())
+            .order_by(node_certificates::updated_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::Due)
+    }
+
+    /// The certificates of live nodes that expire within `alert_before` and
+    /// have not been alerted since they were last updated.
+    pub async fn expiring(
+        alert_before: TimeDelta,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<(Self, Node)>, Error> {
+        node_certificates::table
+            .inner_join(nodes::table)
+            .filter(nodes::deleted_at.is_null())
+            .filter(node_certificates::not_after.lt(Utc::now() + alert_before))
+            .filter(node_certificates::alerted_at.is_null())
+            .select((NodeCertificate::as_select(), Node::as_select()))
+            .get_results(conn)
+            .await
+            .map_err(Error::Expiring)
+    }
+
+    pub async fn issued(
+        &self,
+        not_after: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::update(node_certificates::table.find(self.id))
+            .set((
+                node_certificates::status.eq(CertificateStatus::Issued),
+                node_certificates::not_after.eq(not_after),
+                node_certificates::last_error.eq(None::<String>),
+                node_certificates::alerted_at.eq(None::<DateTime<Utc>>),
+                node_certificates::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Issued(self.id, err))
+    }
+
+    pub async fn failed(&self, error: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        diesel::update(node_certificates::table.find(self.id))
+            .set((
+                node_certificates::status.eq(CertificateStatus::Failed),
+                node_certificates::last_error.eq(error),
+                node_certificates::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Failed(self.id, err))
+    }
+
+    pub async fn alerted(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(node_certificates::table.find(self.id))
+            .set(node_certificates::alerted_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Alerted(self.id, err))
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.names.iter().flatten().cloned().collect()
+    }
+}
+
+impl From<NodeCertificate> for api::NodeCertificateStatus {
+    fn from(certificate: NodeCertificate) -> Self {
+        api::NodeCertificateStatus {
+            certificate_id: certificate.id.to_string(),
+            node_id: certificate.node_id.to_string(),
+            names: certificate.names(),
+            status: api::CertificateStatus::from(certificate.status).into(),
+            not_after: certificate.not_after.map(NanosUtc::from).map(Into::into),
+            last_error: certificate.last_error,
+        }
+    }
+}
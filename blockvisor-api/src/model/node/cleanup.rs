@@ -0,0 +1,269 @@
+//! Removal of the external state of deleted nodes.
+//!
+//! Deleting a node runs in three phases:
+//! 1. `Node::delete` marks the node as deleted and sets its `cleanup` to the
+//!    first step, within the transaction of the delete request.
+//! 2. Once committed, `NodeCleanup::spawn` runs each remaining step against
+//!    the external services, persisting the next step after each success. A
+//!    failed step is recorded in `cleanup_error` and retried later, so a crash
+//!    mid-delete resumes from the last completed step.
+//! 3. The node is fully deleted once its `cleanup` is `Complete`.
+//!
+//! A node is claimed by setting `cleanup_claimed_until` before running any
+//! step, so that a request and the retry job of each api instance never clean
+//! up the same node at once. A claim that is never released (such as after a
+//! crash) expires after `CLAIM_LEASE`.
+//!
+//! Removing DNS records also removes the aliases of the node, so their names
+//! can be used again, and takes it out of any endpoint pools.
+//!
+//! Removing secrets deletes every value stored for the node, such as its
+//! secret image property values.
+//!
+//! Removing a stripe item decrements its quantity, so it runs last: a crash
+//! between that call and persisting the step would repeat it.
+
+use chrono::{TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::auth::resource::{NodeId, Resource};
+use crate::config::Context;
+use crate::database::{Conn, Database};
+use crate::grpc::Status;
+use crate::model::endpoint::PoolMember;
+use crate::model::schema::{nodes, sql_types};
+
+use super::{Node, NodeDnsAlias};
+
+/// Nodes are no longer retried after this many failed cleanup attempts.
+pub const MAX_ATTEMPTS: i32 = 10;
+
+/// How long after a delete or a failed step to wait before retrying cleanup.
+const RETRY_AFTER: TimeDelta = TimeDelta::minutes(5);
+
+/// How long a claim on the cleanup of a node lasts unless released.
+const CLAIM_LEASE: TimeDelta = TimeDelta::minutes(10);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to advance cleanup of node `{0}`: {1}
+    Advance(NodeId, diesel::result::Error),
+    /// Node cleanup alias error: {0}
+    Alias(#[from] super::alias::Error),
+    /// Failed to claim cleanup of node `{0}`: {1}
+    Claim(NodeId, diesel::result::Error),
+    /// Failed to claim the next node pending cleanup: {0}
+    ClaimNext(diesel::result::Error),
+    /// Node cleanup cloudflare error: {0}
+    Cloudflare(#[from] crate::cloudflare::Error),
+    /// Node cleanup database error: {0}
+    Database(#[from] crate::database::Error),
+    /// Failed to record cleanup failure of node `{0}`: {1}
+    Failed(NodeId, diesel::result::Error),
+    /// Node cleanup endpoint pool error: {0}
+    Pool(#[from] crate::model::endpoint::pool::Error),
+    /// Node cleanup secret error: {0}
+    Secret(#[from] crate::store::secret::Error),
+    /// Node cleanup stripe error: {0}
+    Stripe(#[from] crate::stripe::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Advance(..) | Alias(_) | Claim(..) | ClaimNext(_) | Cloudflare(_) | Database(_)
+            | Failed(..) | Pool(_) | Secret(_) | Stripe(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// The next step in removing the external state of a deleted node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumNodeCleanup"]
+pub enum NodeCleanup {
+    RemoveDns,
+    RemoveSecrets,
+    RemoveStripe,
+    Complete,
+}
+
+impl NodeCleanup {
+    /// The first step of a newly deleted node.
+    pub const FIRST: Self = NodeCleanup::RemoveDns;
+
+    const fn next(self) -> Self {
+        match self {
+            NodeCleanup::RemoveDns => NodeCleanup::RemoveSecrets,
+            NodeCleanup::RemoveSecrets => NodeCleanup::RemoveStripe,
+            NodeCleanup::RemoveStripe | NodeCleanup::Complete => NodeCleanup::Complete,
+        }
+    }
+
+    /// Clean up a deleted node in the background, once its delete committed.
+    ///
+    /// Nothing is run if the node is already claimed, and a failed cleanup is
+    /// retried by `job::resume_node_cleanups`.
+    pub fn spawn(context: &Context, node_id: NodeId) {
+        let context = context.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let mut conn = context.conn().await?;
+                match Self::claim(node_id, &mut conn).await? {
+                    Some(node) => Self::resume(&node, &context, &mut conn).await,
+                    None => Ok(()),
+                }
+            };
+
+            match result.await {
+                Ok(()) => info!("Cleaned up deleted node {node_id}"),
+                Err(err) => warn!("Failed to clean up deleted node {node_id}: {err}"),
+            }
+        });
+    }
+
+    /// Claim the cleanup of deleted node `id`, unless another claim holds it.
+    ///
+    /// The conditional update is atomic, so of two concurrent claims only one
+    /// returns the node.
+    pub async fn claim(id: NodeId, conn: &mut Conn<'_>) -> Result<Option<Node>, Error> {
+        let now = Utc::now();
+        diesel::update(nodes::table.find(id))
+            .filter(nodes::cleanup.ne(NodeCleanup::Complete))
+            .filter(
+                nodes::cleanup_claimed_until
+                    .is_null()
+                    .or(nodes::cleanup_claimed_until.lt(now)),
+            )
+            .set(nodes::cleanup_claimed_until.eq(now + CLAIM_LEASE))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::Claim(id, err))
+    }
+
+    /// Claim the deleted node with remaining cleanup steps that has waited the
+    /// longest, skipping any node locked by a concurrent claim.
+    pub async fn claim_next(conn: &mut Conn<'_>) -> Result<Option<Node>, Error> {
+        conn.transaction(|conn| {
+            async move {
+                let now = Utc::now();
+                let next: Option<NodeId> = nodes::table
+                    .filter(nodes::deleted_at.lt(now - RETRY_AFTER))
+                    .filter(nodes::cleanup.ne(NodeCleanup::Complete))
+                    .filter(nodes::cleanup_attempts.lt(MAX_ATTEMPTS))
+                    .filter(
+                        nodes::cleanup_claimed_until
+                            .is_null()
+                            .or(nodes::cleanup_claimed_until.lt(now)),
+                    )
+                    .order_by(nodes::deleted_at.asc())
+                    .select(nodes::id)
+                    .for_update()
+                    .skip_locked()
+                    .first(conn)
+                    .await
+                    .optional()?;
+
+                let Some(id) = next else {
+                    return Ok(None);
+                };
+                diesel::update(nodes::table.find(id))
+                    .set(nodes::cleanup_claimed_until.eq(now + CLAIM_LEASE))
+                    .get_result(conn)
+                    .await
+                    .map(Some)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(Error::ClaimNext)
+    }
+
+    /// Run the remaining cleanup steps of a claimed node.
+    ///
+    /// Stops at the first failed step, which is recorded against the node and
+    /// keeps it claimed until it may be retried.
+    pub async fn resume(node: &Node, context: &Context, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let Some(mut step) = node.cleanup else {
+            return Ok(());
+        };
+
+        while step != NodeCleanup::Complete {
+            if let Err(err) = step.run(node, context, conn).await {
+                Self::failed(node.id, &err, conn).await?;
+                return Err(err);
+            }
+            step = step.next();
+            Self::advance(node.id, step, conn).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn run(self, node: &Node, context: &Context, conn: &mut Conn<'_>) -> Result<(), Error> {
+        match self {
+            NodeCleanup::RemoveDns => {
+                let aliases = NodeDnsAlias::by_node_id(node.id, conn).await?;
+                let alias_ids = aliases.iter().map(|alias| alias.dns_id.as_str());
+                let members = PoolMember::by_node_id(node.id, conn).await?;
+                let pool_ids = members.iter().filter_map(|member| member.dns_id.as_deref());
+                for id in node.dns_records().chain(alias_ids).chain(pool_ids) {
+                    match context.dns.delete(id).await {
+                        Ok(()) => (),
+                        Err(err) if err.is_not_found() => (),
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                NodeDnsAlias::delete_for_node(node.id, conn).await?;
+                PoolMember::delete_for_node(node.id, conn).await?;
+                Ok(())
+            }
+            NodeCleanup::RemoveSecrets => {
+                let resource = Resource::from(node.id);
+                context.secret.delete_all(resource, conn).await?;
+                Ok(())
+            }
+            NodeCleanup::RemoveStripe => {
+                if let (Some(item_id), Some(stripe)) = (&node.stripe_item_id, &context.stripe) {
+                    stripe.remove_subscription(item_id).await?;
+                }
+                Ok(())
+            }
+            NodeCleanup::Complete => Ok(()),
+        }
+    }
+
+    async fn advance(id: NodeId, step: Self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let claimed_until = (step != NodeCleanup::Complete).then(|| Utc::now() + CLAIM_LEASE);
+        diesel::update(nodes::table.find(id))
+            .set((
+                nodes::cleanup.eq(step),
+                nodes::cleanup_error.eq(None::<String>),
+                nodes::cleanup_claimed_until.eq(claimed_until),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Advance(id, err))
+    }
+
+    async fn failed(id: NodeId, err: &Error, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(nodes::table.find(id))
+            .set((
+                nodes::cleanup_attempts.eq(nodes::cleanup_attempts + 1),
+                nodes::cleanup_error.eq(err.to_string()),
+                nodes::cleanup_claimed_until.eq(Utc::now() + RETRY_AFTER),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Failed(id, err))
+    }
+}
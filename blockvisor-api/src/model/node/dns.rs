@@ -0,0 +1,228 @@
+//! The DNS records of a node.
+//!
+//! Each node has an address record `dns_id` for its name, and the extra records
+//! of its image in `dns_record_ids`. The address record is updated in place
+//! when the node moves to a new IP, while the extra records are replaced when
+//! an upgrade changes the DNS settings of its image. Address aliases of the
+//! node follow it to a new IP as well.
+
+use std::net::IpAddr;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::auth::resource::NodeId;
+use crate::cloudflare::{Dns, ExtraRecord, RecordOptions};
+use crate::database::WriteConn;
+use crate::grpc::Status;
+use crate::model::image::{DnsSettings, ImageId};
+use crate::model::schema::nodes;
+
+use super::{DnsAliasType, Node, NodeDnsAlias};
+
+/// The DNS provider of the `Context`.
+pub type DnsClient = dyn Dns + Send + Sync;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Node dns cloudflare error: {0}
+    Cloudflare(#[from] crate::cloudflare::Error),
+    /// Node dns alias error: {0}
+    Alias(#[from] super::alias::Error),
+    /// Node dns settings error: {0}
+    Settings(#[from] crate::model::image::dns::Error),
+    /// Failed to update the DNS record ids of node `{0}`: {1}
+    UpdateRecordIds(NodeId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Cloudflare(_) | UpdateRecordIds(..) => Status::internal("Internal error."),
+            Alias(err) => err.into(),
+            Settings(err) => err.into(),
+        }
+    }
+}
+
+/// The DNS records created for a new node.
+pub struct NodeRecords {
+    pub dns_id: String,
+    pub record_ids: Vec<String>,
+}
+
+impl NodeRecords {
+    /// Create the address record of node `name` and the extra records of its
+    /// image, removing any that were created if one fails.
+    pub async fn create(
+        name: &str,
+        ip: IpAddr,
+        settings: Option<&DnsSettings>,
+        dns: &DnsClient,
+    ) -> Result<Self, Error> {
+        let options = settings.map(DnsSettings::options).unwrap_or_default();
+        let records = settings.map_or(&[][..], |settings| &settings.records);
+
+        let dns_id = dns.create(name, ip, &options).await?.id;
+        match create_extra(name, records, &options, dns).await {
+            Ok(record_ids) => Ok(NodeRecords { dns_id, record_ids }),
+            Err(err) => {
+                delete_records([dns_id.as_str()], dns).await;
+                Err(err)
+            }
+        }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.dns_id.as_str()).chain(self.record_ids.iter().map(String::as_str))
+    }
+
+    /// The extra record ids as stored in `nodes.dns_record_ids`.
+    pub fn stored_ids(&self) -> Vec<Option<String>> {
+        self.record_ids.iter().cloned().map(Some).collect()
+    }
+}
+
+impl Node {
+    /// The ids of the address record and extra records of this node.
+    ///
+    /// The address record is missing until a new node has been provisioned.
+    pub fn dns_records(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.dns_id.as_str())
+            .filter(|id| !id.is_empty())
+            .chain(self.dns_record_ids.iter().flatten().map(String::as_str))
+    }
+
+    /// Remove the DNS records of this node, logging any that fail.
+    pub async fn remove_dns(&self, dns: &DnsClient) {
+        delete_records(self.dns_records(), dns).await;
+    }
+
+    /// Point the address record and address aliases of this node at its
+    /// current IP.
+    pub async fn update_dns(&self, write: &mut WriteConn<'_, '_>) -> Result<(), Error> {
+        let ctx = write.ctx;
+        let settings = DnsSettings::by_image_id(self.image_id, write).await?;
+        let options = settings
+            .as_ref()
+            .map(DnsSettings::options)
+            .unwrap_or_default();
+
+        ctx.dns
+            .update(
+                &self.dns_id,
+                &self.node_name,
+                self.ip_address.ip(),
+                &options,
+            )
+            .await?;
+
+        let aliases = NodeDnsAlias::by_node_id(self.id, write).await?;
+        for alias in aliases {
+            if alias.alias_type == DnsAliasType::Address {
+                let target = alias.alias_type.target(self);
+                ctx.dns
+                    .update_alias(&alias.dns_id, &alias.name, target)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the DNS settings of the image this node was upgraded to, from the
+    /// settings of image `old_image_id`.
+    ///
+    /// New extra records are created before the old ones are removed, so the
+    /// node keeps its records if creating them fails.
+    pub async fn upgrade_dns(
+        self,
+        old_image_id: ImageId,
+        write: &mut WriteConn<'_, '_>,
+    ) -> Result<Self, Error> {
+        let ctx = write.ctx;
+        let old = DnsSettings::by_image_id(old_image_id, write)
+            .await?
+            .unwrap_or_default();
+        let new = DnsSettings::by_image_id(self.image_id, write)
+            .await?
+            .unwrap_or_default();
+
+        let options = new.options();
+        if old.options() != options {
+            ctx.dns
+                .update(
+                    &self.dns_id,
+                    &self.node_name,
+                    self.ip_address.ip(),
+                    &options,
+                )
+                .await?;
+        }
+
+        if old.records == new.records {
+            return Ok(self);
+        }
+
+        let record_ids = create_extra(&self.node_name, &new.records, &options, &**ctx.dns).await?;
+        let stored_ids: Vec<_> = record_ids.iter().cloned().map(Some).collect();
+        let node: Self = match diesel::update(nodes::table.find(self.id))
+            .set((
+                nodes::dns_record_ids.eq(stored_ids),
+                nodes::updated_at.eq(Utc::now()),
+            ))
+            .get_result(write)
+            .await
+        {
+            Ok(node) => node,
+            Err(err) => {
+                delete_records(record_ids.iter().map(String::as_str), &**ctx.dns).await;
+                return Err(Error::UpdateRecordIds(self.id, err));
+            }
+        };
+
+        let old_ids = self.dns_record_ids.iter().flatten().map(String::as_str);
+        delete_records(old_ids, &**ctx.dns).await;
+
+        Ok(node)
+    }
+}
+
+/// Create the extra records of node `name`, removing any that were created if
+/// one fails.
+async fn create_extra(
+    name: &str,
+    records: &[ExtraRecord],
+    options: &RecordOptions,
+    dns: &DnsClient,
+) -> Result<Vec<String>, Error> {
+    let mut ids = Vec::with_capacity(records.len());
+    for record in records {
+        match dns.create_extra(name, record, options).await {
+            Ok(created) => ids.push(created.id),
+            Err(err) => {
+                delete_records(ids.iter().map(String::as_str), dns).await;
+                return Err(err.into());
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Delete each DNS record, logging any that fail.
+pub async fn delete_records<'a, I>(ids: I, dns: &DnsClient)
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    for id in ids {
+        if let Err(err) = dns.delete(id).await {
+            warn!("Failed to delete DNS record {id}: {err}");
+        }
+    }
+}
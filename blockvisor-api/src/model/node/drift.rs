@@ -0,0 +1,154 @@
+//! Drift between the stored config of a node and the config its host runs.
+//!
+//! Running nodes are periodically sent a `NodeReportConfig` command with the id
+//! of their current config. The host responds with the hash of the config it
+//! actually runs, which is compared against the hash of the stored config
+//! bytes. A mismatch sets `config_drift` on the node until a later report
+//! matches again.
+//!
+//! A report for a config the node has since replaced is ignored, as the host
+//! may not have applied the new config yet.
+
+use chrono::Utc;
+use diesel::dsl::{exists, not};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use prost::Message;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::auth::resource::NodeId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::command::{Command, CommandId, CommandType};
+use crate::model::image::{Config, ConfigId};
+use crate::model::schema::{commands, nodes};
+
+use super::{Node, NodeState};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Config drift image config error: {0}
+    Config(#[from] crate::model::image::config::Error),
+    /// Failed to decode NodeReportConfig protobuf: {0}
+    Decode(prost::DecodeError),
+    /// Failed to find nodes due a config report: {0}
+    Due(diesel::result::Error),
+    /// NodeReportConfig command `{0}` is missing the `NodeId`.
+    MissingNodeId(CommandId),
+    /// NodeReportConfig command `{0}` is missing expected protobuf bytes.
+    MissingProtobuf(CommandId),
+    /// Failed to find config of node `{0}`: {1}
+    NodeConfig(NodeId, diesel::result::Error),
+    /// Failed to parse ConfigId: {0}
+    ParseConfigId(uuid::Error),
+    /// Failed to record config report for node `{0}`: {1}
+    Report(NodeId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            NodeConfig(_, diesel::result::Error::NotFound) => Status::not_found("Node not found."),
+            Decode(_) | Due(_) | MissingNodeId(_) | MissingProtobuf(_) | NodeConfig(..)
+            | ParseConfigId(_) | Report(..) => Status::internal("Internal error."),
+            Config(err) => err.into(),
+        }
+    }
+}
+
+/// The outcome of a config report.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigReport {
+    /// The host runs the stored config.
+    Matches,
+    /// The host runs a different config than the stored one.
+    Drifted {
+        config_id: ConfigId,
+        expected_hash: String,
+        started: bool,
+    },
+    /// The node has moved to another config since the command was sent.
+    Stale,
+}
+
+/// The hex encoded SHA-256 hash of the stored bytes of a config.
+pub fn config_hash(config: &Config) -> String {
+    format!("{:x}", Sha256::digest(&*config.config))
+}
+
+/// Running nodes without a pending `NodeReportConfig` command.
+pub async fn due(conn: &mut Conn<'_>) -> Result<Vec<Node>, Error> {
+    let pending = commands::table
+        .filter(commands::node_id.eq(nodes::id.nullable()))
+        .filter(commands::command_type.eq(CommandType::NodeReportConfig))
+        .filter(commands::exit_code.is_null());
+
+    nodes::table
+        .filter(nodes::deleted_at.is_null())
+        .filter(nodes::node_state.eq(NodeState::Running))
+        .filter(not(exists(pending)))
+        .select(Node::as_select())
+        .get_results(conn)
+        .await
+        .map_err(Error::Due)
+}
+
+/// The payload of a `NodeReportConfig` command for the current config of a
+/// node.
+pub fn payload(node: &Node) -> api::NodeReportConfig {
+    api::NodeReportConfig {
+        config_id: node.config_id.to_string(),
+    }
+}
+
+/// Compare the config hash reported in response to a `NodeReportConfig`
+/// command against the config it was sent for.
+pub async fn report(
+    command: &Command,
+    reported_hash: &str,
+    conn: &mut Conn<'_>,
+) -> Result<ConfigReport, Error> {
+    let node_id = command.node_id.ok_or(Error::MissingNodeId(command.id))?;
+    let bytes = command
+        .protobuf
+        .as_ref()
+        .ok_or(Error::MissingProtobuf(command.id))?;
+    let sent: api::NodeReportConfig = Message::decode(&bytes[..]).map_err(Error::Decode)?;
+    let config_id: ConfigId = sent.config_id.parse().map_err(Error::ParseConfigId)?;
+
+    let (current_id, drifting): (ConfigId, bool) = nodes::table
+        .find(node_id)
+        .select((nodes::config_id, nodes::config_drift))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::NodeConfig(node_id, err))?;
+    if current_id != config_id {
+        return Ok(ConfigReport::Stale);
+    }
+
+    let config = Config::by_id(config_id, conn).await?;
+    let expected_hash = config_hash(&config);
+    let drift = expected_hash != reported_hash;
+
+    diesel::update(nodes::table.find(node_id))
+        .set((
+            nodes::config_drift.eq(drift),
+            nodes::config_reported_at.eq(Utc::now()),
+        ))
+        .execute(conn)
+        .await
+        .map_err(|err| Error::Report(node_id, err))?;
+
+    if drift {
+        Ok(ConfigReport::Drifted {
+            config_id,
+            expected_hash,
+            started: !drifting,
+        })
+    } else {
+        Ok(ConfigReport::Matches)
+    }
+}
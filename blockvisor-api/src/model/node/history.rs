@@ -0,0 +1,131 @@
+//! Hourly rollups of node health, sampled from each metrics update.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, DurationRound, NaiveDate, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::resource::NodeId;
+use crate::database::Conn;
+use crate::grpc::Status;
+use crate::model::schema::node_health_history;
+
+use super::{Node, NodeHealth, NodeState};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find health history for nodes: {0}
+    ByNodes(diesel::result::Error),
+    /// Failed to record health of node `{0}`: {1}
+    Record(NodeId, diesel::result::Error),
+    /// Failed to truncate health sample timestamp: {0}
+    Truncate(chrono::RoundingError),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByNodes(_) | Record(..) | Truncate(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = node_health_history)]
+pub struct NodeHealthHistory {
+    pub node_id: NodeId,
+    pub hour: DateTime<Utc>,
+    pub samples: i32,
+    pub healthy_samples: i32,
+}
+
+impl NodeHealthHistory {
+    /// Record a health sample for each node in the rollup of the current hour.
+    ///
+    /// A node is healthy while it is running and its protocol does not report
+    /// itself as unhealthy.
+    pub async fn record(nodes: &[Node], conn: &mut Conn<'_>) -> Result<(), Error> {
+        let hour = Utc::now()
+            .duration_trunc(TimeDelta::hours(1))
+            .map_err(Error::Truncate)?;
+
+        for node in nodes {
+            let healthy = node.node_state == NodeState::Running
+                && node.protocol_health != Some(NodeHealth::Unhealthy);
+
+            diesel::insert_into(node_health_history::table)
+                .values((
+                    node_health_history::node_id.eq(node.id),
+                    node_health_history::hour.eq(hour),
+                    node_health_history::samples.eq(1),
+                    node_health_history::healthy_samples.eq(i32::from(healthy)),
+                ))
+                .on_conflict((node_health_history::node_id, node_health_history::hour))
+                .do_update()
+                .set((
+                    node_health_history::samples.eq(node_health_history::samples + 1),
+                    node_health_history::healthy_samples.eq(node_health_history::healthy_samples
+                        + excluded(node_health_history::healthy_samples)),
+                ))
+                .execute(conn)
+                .await
+                .map_err(|err| Error::Record(node.id, err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Find the hourly health of the nodes since `since`.
+    pub async fn by_nodes(
+        node_ids: &[NodeId],
+        since: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        node_health_history::table
+            .filter(node_health_history::node_id.eq_any(node_ids))
+            .filter(node_health_history::hour.ge(since))
+            .order_by(node_health_history::hour.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::ByNodes)
+    }
+
+    /// Group hourly rollups into the daily uptime percentage of each node.
+    pub fn daily_uptime(history: Vec<Self>) -> BTreeMap<NodeId, BTreeMap<NaiveDate, f64>> {
+        let mut totals: BTreeMap<NodeId, BTreeMap<NaiveDate, (i64, i64)>> = BTreeMap::new();
+        for hour in history {
+            let (samples, healthy) = totals
+                .entry(hour.node_id)
+                .or_default()
+                .entry(hour.hour.date_naive())
+                .or_default();
+            *samples += i64::from(hour.samples);
+            *healthy += i64::from(hour.healthy_samples);
+        }
+
+        totals
+            .into_iter()
+            .map(|(node_id, days)| {
+                let days = days
+                    .into_iter()
+                    .map(|(day, (samples, healthy))| (day, uptime_percent(samples, healthy)))
+                    .collect();
+                (node_id, days)
+            })
+            .collect()
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn uptime_percent(samples: i64, healthy: i64) -> f64 {
+    if samples == 0 {
+        0.0
+    } else {
+        healthy as f64 * 100.0 / samples as f64
+    }
+}
@@ -2,17 +2,17 @@ use std::collections::HashMap;
 
 use displaydoc::Display;
 use thiserror::Error;
-use tracing::warn;
 
 use crate::auth::AuthZ;
-use crate::auth::resource::{HostId, Resource};
+use crate::auth::resource::{HostId, OrgId, Resource};
 use crate::database::WriteConn;
-use crate::grpc::{Status, common};
+use crate::grpc::{Status, api, common};
 use crate::model::image::NodeConfig;
 use crate::model::region::RegionId;
-use crate::model::{Host, Image, Org, ProtocolVersion, Region};
+use crate::model::sql::{Amount, Currency, Period};
+use crate::model::{Host, HostPoolId, Image, IpAddress, Org, ProtocolVersion, Region};
 
-use super::{NewNode, Node, NodeScheduler, ResourceAffinity, SimilarNodeAffinity};
+use super::{NewNode, Node, NodeScheduler, PlacementPolicy, ResourceAffinity, SimilarNodeAffinity};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -24,6 +24,8 @@ pub enum Error {
     ParseHostId(uuid::Error),
     /// Failed to parse RegionId: {0}
     ParseRegionId(uuid::Error),
+    /// Host `{0}` does not match the placement policy of the node.
+    Placement(HostId),
     /// Launch region error: {0}
     Region(#[from] crate::model::region::Error),
 }
@@ -34,6 +36,9 @@ impl From<Error> for Status {
         match err {
             ParseHostId(_) => Status::invalid_argument("host_id"),
             ParseRegionId(_) => Status::invalid_argument("region_id"),
+            Placement(_) => {
+                Status::failed_precondition("Host does not match the placement policy.")
+            }
             Host(err) => err.into(),
             Node(err) => err.into(),
             Region(err) => err.into(),
@@ -65,10 +70,27 @@ impl Launch {
 
         match self {
             Launch::ByHost(host_counts) => {
+                let scheduler = NodeScheduler {
+                    resource: None,
+                    similarity: None,
+                    region: None,
+                    placement: node.scheduler_placement,
+                    pool_id: node.scheduler_pool_id,
+                };
+
+                // check every host before creating any nodes
+                let mut hosts = Vec::with_capacity(host_counts.len());
                 for count in host_counts {
                     let host = Host::by_id(count.host_id, Some(node.org_id), write).await?;
-                    for _ in 0..count.node_count {
-                        match node
+                    if !scheduler.allows(&host, node.org_id) {
+                        return Err(Error::Placement(host.id));
+                    }
+                    hosts.push((host, count.node_count));
+                }
+
+                for (host, node_count) in hosts {
+                    for _ in 0..node_count {
+                        let created = node
                             .create_node(
                                 &host,
                                 org,
@@ -82,18 +104,8 @@ impl Launch {
                                 write,
                             )
                             .await
-                        {
-                            Ok(node) => launched.push(node),
-                            Err(err) => {
-                                for node in launched {
-                                    if let Err(err) = write.ctx.dns.delete(&node.dns_id).await {
-                                        warn!("Failed to delete DNS record {}: {err}", node.dns_id);
-                                    }
-                                }
-
-                                return Err(Error::Node(err));
-                            }
-                        }
+                            .map_err(Error::Node)?;
+                        launched.push(created);
                     }
                 }
             }
@@ -105,11 +117,21 @@ impl Launch {
                         resource: count.resource,
                         similarity: count.similarity,
                         region: Some(region),
+                        placement: node.scheduler_placement,
+                        pool_id: node.scheduler_pool_id,
                     };
 
                     for _ in 0..count.node_count {
-                        let candidate = node.find_host(&scheduler, authz, write).await?;
-                        match node
+                        let candidate = super::find_host(
+                            &scheduler,
+                            node.org_id,
+                            node.protocol_id,
+                            node_config,
+                            authz,
+                            write,
+                        )
+                        .await?;
+                        let created = node
                             .create_node(
                                 &candidate.host,
                                 org,
@@ -123,18 +145,8 @@ impl Launch {
                                 write,
                             )
                             .await
-                        {
-                            Ok(node) => launched.push(node),
-                            Err(err) => {
-                                for node in launched {
-                                    if let Err(err) = write.ctx.dns.delete(&node.dns_id).await {
-                                        warn!("Failed to delete DNS record {}: {err}", node.dns_id);
-                                    }
-                                }
-
-                                return Err(Error::Node(err));
-                            }
-                        }
+                            .map_err(Error::Node)?;
+                        launched.push(created);
                     }
                 }
             }
@@ -142,6 +154,193 @@ impl Launch {
 
         Ok(launched)
     }
+
+    /// Plan the nodes that `create` would launch, without creating anything.
+    ///
+    /// Hosts are checked and chosen as for `create`, except that the nodes of
+    /// a region count are each planned against the current load of its hosts,
+    /// rather than the load after placing the nodes before it.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn plan(
+        self,
+        org_id: OrgId,
+        placement: PlacementPolicy,
+        pool_id: Option<HostPoolId>,
+        version: &ProtocolVersion,
+        node_config: &NodeConfig,
+        authz: &AuthZ,
+        write: &mut WriteConn<'_, '_>,
+    ) -> Result<Vec<PlannedNode>, Error> {
+        let mut planned = Vec::new();
+
+        match self {
+            Launch::ByHost(host_counts) => {
+                let scheduler = NodeScheduler {
+                    resource: None,
+                    similarity: None,
+                    region: None,
+                    placement,
+                    pool_id,
+                };
+
+                for count in host_counts {
+                    let host = Host::by_id(count.host_id, Some(org_id), write).await?;
+                    if !scheduler.allows(&host, org_id) {
+                        return Err(Error::Placement(host.id));
+                    }
+                    for _ in 0..count.node_count {
+                        let host = host.clone();
+                        let node =
+                            PlannedNode::new(host, org_id, version, node_config, authz, write);
+                        planned.push(node.await?);
+                    }
+                }
+            }
+
+            Launch::ByRegion(region_counts) => {
+                for count in region_counts {
+                    let region = Region::by_id(count.region_id, write).await?;
+                    let scheduler = NodeScheduler {
+                        resource: count.resource,
+                        similarity: count.similarity,
+                        region: Some(region),
+                        placement,
+                        pool_id,
+                    };
+
+                    for _ in 0..count.node_count {
+                        let candidate = super::find_host(
+                            &scheduler,
+                            org_id,
+                            version.protocol_id,
+                            node_config,
+                            authz,
+                            write,
+                        )
+                        .await?;
+                        let host = candidate.host;
+                        let node =
+                            PlannedNode::new(host, org_id, version, node_config, authz, write);
+                        planned.push(node.await?);
+                    }
+                }
+            }
+        }
+
+        Ok(planned)
+    }
+
+    /// Whether the regions of this launch can currently fit all of its nodes.
+    ///
+    /// A launch onto chosen hosts does not wait for capacity, so it is always
+    /// considered to fit.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn has_capacity(
+        &self,
+        org_id: OrgId,
+        placement: PlacementPolicy,
+        pool_id: Option<HostPoolId>,
+        version: &ProtocolVersion,
+        node_config: &NodeConfig,
+        authz: &AuthZ,
+        write: &mut WriteConn<'_, '_>,
+    ) -> Result<bool, Error> {
+        let Launch::ByRegion(region_counts) = self else {
+            return Ok(true);
+        };
+
+        for count in region_counts {
+            let region = Region::by_id(count.region_id, write).await?;
+            let scheduler = NodeScheduler {
+                resource: count.resource,
+                similarity: count.similarity,
+                region: Some(region),
+                placement,
+                pool_id,
+            };
+
+            let capacity = super::host_capacity(
+                &scheduler,
+                org_id,
+                version.protocol_id,
+                node_config,
+                authz,
+                write,
+            )
+            .await?;
+            if capacity < u64::from(count.node_count) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// A node that a `Launch` would create, with its placement and cost.
+pub struct PlannedNode {
+    pub host: Host,
+    pub region: Region,
+    /// The billing SKU of the node, or `None` if it is not charged for.
+    pub sku: Option<String>,
+    /// The monthly cost of the node, if it is charged for and stripe is
+    /// configured.
+    pub cost: Option<Amount>,
+}
+
+impl From<PlannedNode> for api::PlannedNode {
+    fn from(planned: PlannedNode) -> Self {
+        api::PlannedNode {
+            host_id: planned.host.id.to_string(),
+            host_network_name: planned.host.network_name,
+            region: Some(planned.region.into()),
+            sku: planned.sku,
+            cost: planned.cost.map(Into::into),
+        }
+    }
+}
+
+impl PlannedNode {
+    async fn new(
+        host: Host,
+        org_id: OrgId,
+        version: &ProtocolVersion,
+        node_config: &NodeConfig,
+        authz: &AuthZ,
+        write: &mut WriteConn<'_, '_>,
+    ) -> Result<Self, super::Error> {
+        super::check_host_resources(&host, node_config)?;
+        IpAddress::next_for_host(host.id, write)
+            .await?
+            .ok_or_else(|| super::Error::HostFreeIp(host.id))?;
+
+        let region = Region::by_id(host.region_id, write).await?;
+        let sku = if super::is_billed(&host, org_id, authz) {
+            version.sku(&region)
+        } else {
+            None
+        };
+
+        let cost = match (&sku, write.ctx.stripe.as_ref()) {
+            (Some(sku), Some(stripe)) => {
+                let price = stripe.get_price(sku).await?;
+                let amount = price.unit_amount.ok_or(super::Error::PriceWithoutAmount)?;
+                Some(Amount {
+                    amount,
+                    currency: Currency::Usd,
+                    period: Period::Monthly,
+                })
+            }
+            _ => None,
+        };
+
+        Ok(PlannedNode {
+            host,
+            region,
+            sku,
+            cost,
+        })
+    }
 }
 
 pub struct HostCount {
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use diesel::deserialize::{FromSql, FromSqlRow};
 use diesel::expression::AsExpression;
 use diesel::pg::sql_types::Jsonb;
@@ -21,7 +21,8 @@ use crate::grpc::Status;
 use crate::model::ImageId;
 use crate::model::schema::{node_logs, sql_types};
 
-use super::Node;
+use super::resize::Resized;
+use super::{Node, NodeState};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -58,12 +59,20 @@ pub struct NodeLog {
 }
 
 impl NodeLog {
-    pub async fn by_node_id(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+    /// The logs of a node, oldest first.
+    ///
+    /// Logs are partitioned by month, so the partitions from before the node
+    /// was created are skipped. The bound is a day early to allow for clock
+    /// skew between the api and the database.
+    pub async fn by_node(node: &Node, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let since = node.created_at - TimeDelta::days(1);
         node_logs::table
-            .filter(node_logs::node_id.eq(node_id))
+            .filter(node_logs::node_id.eq(node.id))
+            .filter(node_logs::created_at.ge(since))
+            .order_by(node_logs::created_at.asc())
             .get_results(conn)
             .await
-            .map_err(|err| Error::ByNodeId(node_id, err))
+            .map_err(|err| Error::ByNodeId(node.id, err))
     }
 }
 
@@ -134,6 +143,11 @@ pub enum LogEvent {
     UpgradeSucceeded,
     /// Notification that an attempt to upgrade a node failed.
     UpgradeFailed,
+    /// A state reported by blockvisord was rejected as an illegal transition.
+    StateRejected(StateRejected),
+    /// The vm resources of the node were changed and a `NodeResize` message
+    /// has been sent to blockvisord.
+    Resized(Resized),
 }
 
 impl LogEvent {
@@ -153,6 +167,11 @@ impl LogEvent {
             ),
             LogEvent::UpgradeSucceeded => (NodeEvent::UpgradeSucceeded, None),
             LogEvent::UpgradeFailed => (NodeEvent::UpgradeFailed, None),
+            LogEvent::StateRejected(data) => (
+                NodeEvent::StateRejected,
+                Some(NodeEventData::StateRejected(data)),
+            ),
+            LogEvent::Resized(data) => (NodeEvent::Resized, Some(NodeEventData::Resized(data))),
         }
     }
 }
@@ -169,6 +188,12 @@ pub struct UpgradeStarted {
     pub new: ImageId,
 }
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct StateRejected {
+    pub from: NodeState,
+    pub to: NodeState,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
 #[ExistingTypePath = "sql_types::EnumNodeEvent"]
 pub enum NodeEvent {
@@ -180,6 +205,8 @@ pub enum NodeEvent {
     UpgradeStarted,
     UpgradeSucceeded,
     UpgradeFailed,
+    StateRejected,
+    Resized,
 }
 
 #[derive(Clone, Copy, Debug, AsExpression, FromSqlRow, Serialize, Deserialize)]
@@ -187,6 +214,8 @@ pub enum NodeEvent {
 pub enum NodeEventData {
     OrgTransferred(OrgTransferred),
     UpgradeStarted(UpgradeStarted),
+    StateRejected(StateRejected),
+    Resized(Resized),
 }
 
 impl FromSql<Jsonb, Pg> for NodeEventData {
@@ -1,45 +1,88 @@
+pub mod abuse;
+pub use abuse::{AbuseReason, AbuseReport, AbuseReportId, AbuseResolution, NodeUsage};
+
+pub mod alias;
+pub use alias::{DnsAliasType, NewNodeDnsAlias, NodeDnsAlias, NodeDnsAliasId};
+
+pub mod bulk;
+pub use bulk::{BulkUpdate, NameTemplate};
+
+pub mod certificate;
+pub use certificate::{CertificateStatus, NodeCertificate, NodeCertificateId};
+
+pub mod cleanup;
+pub use cleanup::NodeCleanup;
+
+pub mod dns;
+pub use dns::NodeRecords;
+
+pub mod drift;
+pub use drift::ConfigReport;
+
 pub mod job;
 pub use job::{NodeJob, NodeJobProgress, NodeJobStatus, NodeJobs};
 
+pub mod history;
+pub use history::NodeHealthHistory;
+
 pub mod launch;
-pub use launch::{HostCount, Launch, RegionCount};
+pub use launch::{HostCount, Launch, PlannedNode, RegionCount};
 
 pub mod log;
-pub use log::{LogEvent, NewNodeLog, NodeEvent, NodeEventData, NodeLog};
+pub use log::{LogEvent, NewNodeLog, NodeEvent, NodeEventData, NodeLog, StateRejected};
+
+pub mod policy;
+pub use policy::{NodeNameCharset, NodePolicy};
+
+pub mod provision;
+pub use provision::NodeProvision;
+
+pub mod rename;
 
 pub mod report;
 pub use report::{NewNodeReport, NodeReport};
 
+pub mod resize;
+pub use resize::{DiskResize, DiskResizeId, DiskResizeStatus, NewDiskResize, NodeResources};
+
 pub mod scheduler;
-pub use scheduler::{NodeScheduler, ResourceAffinity, SimilarNodeAffinity};
+pub use scheduler::{NodeScheduler, PlacementPolicy, ResourceAffinity, SimilarNodeAffinity};
+
+pub mod slo;
+pub use slo::{NodeDowntime, NodeSlo};
 
 pub mod status;
-pub use status::{NextState, NodeHealth, NodeState, NodeStatus, ProtocolStatus};
+pub use status::{NextState, NodeHealth, NodeState, NodeStatus, ProtocolStatus, Transition};
+
+pub mod summary;
+pub use summary::NodeSummary;
 
 use std::collections::{HashMap, HashSet, VecDeque};
 
 use chrono::{DateTime, Utc};
 use diesel::dsl::{self, InnerJoinQuerySource};
+use diesel::expression::SqlLiteral;
 use diesel::expression::expression_types::NotSelectable;
 use diesel::pg::Pg;
 use diesel::prelude::*;
 use diesel::result::DatabaseErrorKind::UniqueViolation;
 use diesel::result::Error::{DatabaseError, NotFound};
-use diesel::sql_types::Bool;
+use diesel::sql_types::{BigInt, Bool, Nullable};
 use diesel_async::RunQueryDsl;
 use displaydoc::Display;
 use petname::{Generator, Petnames};
 use thiserror::Error;
-use tracing::warn;
+use tracing::{info, warn};
+use uuid::Uuid;
 
 use crate::auth::AuthZ;
 use crate::auth::rbac::{BillingPerm, NodeAdminPerm};
 use crate::auth::resource::{HostId, NodeId, OrgId, Resource, ResourceId, ResourceType, UserId};
 use crate::database::{Conn, WriteConn};
 use crate::grpc::{Status, api};
-use crate::model::sql::{self, Amount, Currency, IpNetwork, Period, Tags, Version};
+use crate::model::sql::{self, Amount, IpNetwork, Tags, Version};
 use crate::stripe::api::subscription::SubscriptionItemId;
-use crate::util::{SearchOperator, SortOrder};
+use crate::util::{SearchOperator, SortOrder, secrets};
 
 use super::command::NewCommand;
 use super::host::{Host, HostCandidate, HostRequirements};
@@ -47,14 +90,19 @@ use super::image::config::{ConfigType, FirewallConfig, NewConfig};
 use super::image::property::NewImagePropertyValue;
 use super::image::{Config, ConfigId, Image, ImageId, NodeConfig};
 use super::protocol::version::{ProtocolVersion, VersionId};
-use super::protocol::{Protocol, ProtocolId, VersionKey};
-use super::schema::{nodes, protocol_versions};
-use super::{Command, CommandType, IpAddress, Org, Paginate, Region, RegionId};
+use super::protocol::{NetworkKind, Protocol, ProtocolId, ProtocolNetworkId, VersionKey};
+use super::schema::{nodes, protocol_networks, protocol_versions};
+use super::{
+    Command, CommandType, HostPoolId, HostRanking, HostWeights, IpAddress, Org, Paginate,
+    ProjectId, Region, RegionId,
+};
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
     /// Cannot delete node `{0}`, it is already deleted.
     AlreadyDeleted(NodeId),
+    /// Failed to anonymize nodes created by user `{0}`: {1}
+    AnonymizeCreator(UserId, diesel::result::Error),
     /// Node Cloudflare error: {0}
     Cloudflare(#[from] crate::cloudflare::Error),
     /// Node Command error: {0}
@@ -65,12 +113,20 @@ pub enum Error {
     Create(diesel::result::Error),
     /// Failed to delete node `{0}`: {1}
     Delete(NodeId, diesel::result::Error),
+    /// Node dns error: {0}
+    Dns(#[from] self::dns::Error),
+    /// Node downtime error: {0}
+    Downtime(#[from] self::slo::Error),
     /// Failed to find deleted node by id `{0}`: {1}
     FindDeletedById(NodeId, diesel::result::Error),
     /// Failed to find node by id `{0}`: {1}
     FindById(NodeId, diesel::result::Error),
     /// Failed to find nodes by ids `{0:?}`: {1}
     FindByIds(HashSet<NodeId>, diesel::result::Error),
+    /// Failed to find nodes for org {0}: {1}
+    FindByOrgId(OrgId, diesel::result::Error),
+    /// Failed to find node DNS ids: {0}
+    FindDnsIds(diesel::result::Error),
     /// Failed to find nodes by version ids `{0:?}`: {1}
     FindByVersionIds(HashSet<VersionId>, diesel::result::Error),
     /// Failed to find host id for possibly deleted node {0}: {1}
@@ -83,6 +139,12 @@ pub enum Error {
     FindHostIds(HashSet<HostId>, diesel::result::Error),
     /// Failed to find org id for node {0}: {1}
     FindOrgId(NodeId, diesel::result::Error),
+    /// Failed to find running nodes for org {0}: {1}
+    FindRunning(OrgId, diesel::result::Error),
+    /// Failed to find node stripe item ids: {0}
+    FindStripeItemIds(diesel::result::Error),
+    /// Failed to find the state of node {0}: {1}
+    FindState(NodeId, diesel::result::Error),
     /// Failed to generate node name. This should not happen.
     GenerateName,
     /// Grpc command error: {0}
@@ -93,18 +155,28 @@ pub enum Error {
     HostFreeCpu(HostId),
     /// Host doesn't have enough free disk: {0}
     HostFreeDisk(HostId),
+    /// Host doesn't have enough free GPUs: {0}
+    HostFreeGpu(HostId),
     /// Host has no free IP addresses: {0}
     HostFreeIp(HostId),
     /// Host doesn't have enough free memory: {0}
     HostFreeMem(HostId),
+    /// Host {0} doesn't have the required GPU model.
+    HostGpuModel(HostId),
     /// Failed to check if host {0} has nodes: {1}
     HostHasNodes(HostId, diesel::result::Error),
+    /// Host doesn't have enough network bandwidth: {0}
+    HostNetworkBandwidth(HostId),
+    /// Host doesn't have enough NVMe IOPS: {0}
+    HostNvmeIops(HostId),
+    /// Node host weights error: {0}
+    HostWeights(#[from] crate::model::host_weights::Error),
     /// Node image error: {0},
     Image(#[from] crate::model::image::Error),
     /// Node ip address error: {0},
     IpAddress(#[from] crate::model::ip_address::Error),
-    /// The stripe `item` for this node doesn't have an associated `price`.
-    ItemWithoutPrice,
+    /// Node `{0}` may not move from {1:?} to {2:?}.
+    InvalidTransition(NodeId, NodeState, NodeState),
     /// Node launch error: {0}
     Launch(#[from] Box<self::launch::Error>),
     /// Missing node-admin-transfer permission.
@@ -119,8 +191,8 @@ pub enum Error {
     Org(#[from] crate::model::org::Error),
     /// Node pagination: {0}
     Paginate(#[from] crate::model::paginate::Error),
-    /// The stripe `price` for this node doesn't have an associated `amount`.
-    PriceWithoutAmount,
+    /// Node policy: {0}
+    Policy(#[from] self::policy::Error),
     /// Node protocol error: {0}
     Protocol(#[from] crate::model::protocol::Error),
     /// Node protocol version error: {0}
@@ -133,6 +205,8 @@ pub enum Error {
     Store(#[from] crate::store::Error),
     /// Node stripe error: {0}
     Stripe(#[from] crate::stripe::Error),
+    /// Node `{0}` is suspended for suspected abuse.
+    Suspended(NodeId),
     /// Failed to update the node config: {0}
     UpdateConfig(diesel::result::Error),
     /// Failed to update the node status: {0}
@@ -151,6 +225,12 @@ pub enum Error {
     VmMemory(std::num::TryFromIntError),
     /// Failed to parse VM disk bytes: {0}
     VmDisk(std::num::TryFromIntError),
+    /// Failed to parse VM gpu count: {0}
+    VmGpu(std::num::TryFromIntError),
+    /// Failed to parse VM network bandwidth: {0}
+    VmNetworkBandwidth(std::num::TryFromIntError),
+    /// Failed to parse VM nvme iops: {0}
+    VmNvmeIops(std::num::TryFromIntError),
 }
 
 impl From<Error> for Status {
@@ -158,7 +238,7 @@ impl From<Error> for Status {
         use Error::*;
         match err {
             Create(DatabaseError(UniqueViolation, _)) => {
-                Status::already_exists("Node already exists.")
+                Status::already_exists("Node already exists.").with_reason("NODE_EXISTS")
             }
             Delete(_, NotFound)
             | FindById(_, NotFound)
@@ -169,24 +249,30 @@ impl From<Error> for Status {
             | FindHostId(_, NotFound)
             | FindHostIds(_, NotFound)
             | FindOrgId(_, NotFound)
-            | FindByVersionIds(_, NotFound) => Status::not_found("Node not found."),
+            | FindState(_, NotFound)
+            | FindByVersionIds(_, NotFound) => {
+                Status::not_found("Node not found.").with_reason("NODE_NOT_FOUND")
+            }
             AlreadyDeleted(_)
             | Cloudflare(_)
             | Create(_)
             | Delete(_, _)
             | FindById(_, _)
             | FindByIds(_, _)
+            | FindByOrgId(_, _)
             | FindDeletedById(_, _)
             | FindDeletedHostId(_, _)
             | FindDeletedOrgId(_, _)
+            | FindDnsIds(_)
             | FindHostId(_, _)
             | FindHostIds(_, _)
             | FindOrgId(_, _)
             | FindByVersionIds(_, _)
+            | FindRunning(_, _)
+            | FindState(_, _)
+            | FindStripeItemIds(_)
             | GenerateName
             | HostHasNodes(_, _)
-            | ItemWithoutPrice
-            | PriceWithoutAmount
             | Stripe(_)
             | UpdateConfig(_)
             | UpdateMetrics(_, _)
@@ -194,26 +280,44 @@ impl From<Error> for Status {
             | Upgrade(_)
             | VmCpu(_)
             | VmDisk(_)
-            | VmMemory(_) => Status::internal("Internal error."),
+            | VmGpu(_)
+            | VmMemory(_)
+            | VmNetworkBandwidth(_)
+            | VmNvmeIops(_) => Status::internal("Internal error."),
             HostFreeCpu(_) => Status::failed_precondition("Host has too little available cpu."),
             HostFreeDisk(_) => Status::failed_precondition("Host has too little available memory."),
+            HostFreeGpu(_) => Status::failed_precondition("Host has too few available GPUs."),
             HostFreeIp(_) => Status::failed_precondition("Host has too few available IPs."),
             HostFreeMem(_) => Status::failed_precondition("Host has too little available disk."),
+            HostGpuModel(_) => Status::failed_precondition("Host has the wrong GPU model."),
+            HostNetworkBandwidth(_) => {
+                Status::failed_precondition("Host has too little network bandwidth.")
+            }
+            HostNvmeIops(_) => Status::failed_precondition("Host has too few NVMe IOPS."),
+            InvalidTransition(..) => Status::failed_precondition("Invalid node state transition.")
+                .with_reason("INVALID_NODE_STATE"),
             MissingTransferPerm => Status::forbidden("Missing permission."),
             NoMatchingHost => Status::failed_precondition("No matching host."),
             NoUpgradeCommand => Status::forbidden("Access denied."),
+            Suspended(_) => {
+                Status::failed_precondition("Node is suspended.").with_reason("NODE_SUSPENDED")
+            }
             UpdateSameOrg => Status::already_exists("new_org_id"),
             UpgradeSameImage => Status::already_exists("image_id"),
             Command(err) => (*err).into(),
             Config(err) => err.into(),
+            Dns(err) => err.into(),
+            Downtime(err) => err.into(),
             Grpc(err) => (*err).into(),
             Host(err) => err.into(),
+            HostWeights(err) => err.into(),
             Image(err) => err.into(),
             IpAddress(err) => err.into(),
             Launch(err) => (*err).into(),
             NodeLog(err) => err.into(),
             Org(err) => err.into(),
             Paginate(err) => err.into(),
+            Policy(err) => err.into(),
             Protocol(err) => err.into(),
             ProtocolVersion(err) => err.into(),
             Region(err) => err.into(),
@@ -266,6 +370,27 @@ pub struct Node {
     pub updated_at: Option<DateTime<Utc>>,
     pub deleted_at: Option<DateTime<Utc>>,
     pub cost: Option<Amount>,
+    pub cleanup: Option<NodeCleanup>,
+    pub cleanup_attempts: i32,
+    pub cleanup_error: Option<String>,
+    /// Until when a cleanup of the deleted node holds its claim.
+    pub cleanup_claimed_until: Option<DateTime<Utc>>,
+    pub peer_count: Option<i32>,
+    pub scheduler_placement: PlacementPolicy,
+    pub scheduler_pool_id: Option<HostPoolId>,
+    pub gpu_count: i64,
+    /// The ids of the DNS records of this node besides `dns_id`.
+    pub dns_record_ids: Vec<Option<String>>,
+    /// Whether the host last reported running a different config.
+    pub config_drift: bool,
+    pub config_reported_at: Option<DateTime<Utc>>,
+    pub project_id: Option<ProjectId>,
+    /// Since when the node has used most of its CPU without protocol progress.
+    pub cpu_pegged_since: Option<DateTime<Utc>>,
+    /// The percent of its allocated CPU that the node last reported using.
+    pub cpu_percent: Option<i32>,
+    /// The protocol network of the node's image.
+    pub network_id: Option<ProtocolNetworkId>,
 }
 
 impl Node {
@@ -295,6 +420,15 @@ impl Node {
             .map_err(|err| Error::FindByIds(ids.clone(), err))
     }
 
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        nodes::table
+            .filter(nodes::org_id.eq(org_id))
+            .filter(nodes::deleted_at.is_null())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::FindByOrgId(org_id, err))
+    }
+
     pub async fn by_host_ids(
         host_ids: &HashSet<HostId>,
         org_ids: &HashSet<OrgId>,
@@ -321,6 +455,36 @@ impl Node {
             .map_err(|err| Error::FindByVersionIds(version_ids.clone(), err))
     }
 
+    /// Attribute the nodes created by a deleted user to the nil id.
+    pub async fn anonymize_creator(user_id: UserId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let created = nodes::table
+            .filter(nodes::created_by_type.eq(ResourceType::User))
+            .filter(nodes::created_by_id.eq(ResourceId::from(user_id)));
+
+        diesel::update(created)
+            .set(nodes::created_by_id.eq(ResourceId::from(Uuid::nil())))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::AnonymizeCreator(user_id, err))
+    }
+
+    /// Find the nodes of an org that are not yet stopped.
+    pub async fn running_by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        let running = [
+            NodeState::Starting,
+            NodeState::Running,
+            NodeState::Upgrading,
+        ];
+        nodes::table
+            .filter(nodes::org_id.eq(org_id))
+            .filter(nodes::node_state.eq_any(running))
+            .filter(nodes::deleted_at.is_null())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::FindRunning(org_id, err))
+    }
+
     pub async fn org_id(id: NodeId, conn: &mut Conn<'_>) -> Result<OrgId, Error> {
         nodes::table
             .find(id)
@@ -370,6 +534,41 @@ impl Node {
             .map_err(|err| Error::HostHasNodes(host_id, err))
     }
 
+    /// The DNS record ids of all nodes that are not deleted or still being
+    /// cleaned up.
+    pub async fn dns_ids(conn: &mut Conn<'_>) -> Result<HashSet<String>, Error> {
+        nodes::table
+            .filter(
+                nodes::deleted_at
+                    .is_null()
+                    .or(nodes::cleanup.ne(NodeCleanup::Complete)),
+            )
+            .select(nodes::dns_id)
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<String>| ids.into_iter().collect())
+            .map_err(Error::FindDnsIds)
+    }
+
+    /// The stripe subscription items of all nodes that are not deleted or
+    /// still being cleaned up.
+    pub async fn stripe_item_ids(
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<SubscriptionItemId>, Error> {
+        nodes::table
+            .filter(
+                nodes::deleted_at
+                    .is_null()
+                    .or(nodes::cleanup.ne(NodeCleanup::Complete)),
+            )
+            .filter(nodes::stripe_item_id.is_not_null())
+            .select(nodes::stripe_item_id.assume_not_null())
+            .get_results(conn)
+            .await
+            .map(|ids: Vec<SubscriptionItemId>| ids.into_iter().collect())
+            .map_err(Error::FindStripeItemIds)
+    }
+
     pub async fn delete(id: NodeId, write: &mut WriteConn<'_, '_>) -> Result<Node, Error> {
         let node = Node::deleted_by_id(id, write).await?;
         if node.deleted_at.is_some() {
@@ -386,36 +585,15 @@ impl Node {
             .set((
                 nodes::next_state.eq(Some(NextState::Deleting)),
                 nodes::deleted_at.eq(Utc::now()),
+                nodes::cleanup.eq(NodeCleanup::FIRST),
             ))
             .get_result(write)
             .await
             .map_err(|err| Error::Delete(id, err))?;
 
-        if let Err(err) = write.ctx.dns.delete(&node.dns_id).await {
-            warn!("Failed to remove node dns: {err}");
-        }
-
-        // FIXME: secrets integration
-        /*
-            let prefix = format!("node/{id}/secret");
-            let secrets = write.ctx.vault.read().await.list_path(&prefix).await?;
-            if let Some(names) = secrets {
-            for name in names {
-            let path = format!("{prefix}/{name}");
-            let result = write.ctx.vault.read().await.delete_path(&path).await;
-            match result {
-            Ok(()) | Err(crate::store::vault::Error::PathNotFound) => (),
-            Err(err) => return Err(err.into()),
-        }
-        }
-        }
-             */
-
-        if let Some(ref item_id) = node.stripe_item_id {
-            if let Some(stripe) = write.ctx.stripe.as_ref() {
-                stripe.remove_subscription(item_id).await?;
-            }
-        }
+        // external state is only removed once the delete has committed
+        let ctx = write.ctx.clone();
+        write.after_commit(async move { NodeCleanup::spawn(&ctx, id) });
 
         Ok(node)
     }
@@ -427,6 +605,9 @@ impl Node {
         write: &mut WriteConn<'_, '_>,
     ) -> Result<Option<Host>, Error> {
         let scheduler = self.scheduler(write).await?;
+        let node_config = Config::by_id(self.config_id, write).await?.node_config()?;
+        let defaults = write.ctx.config.scheduler.clone();
+        let weights = HostWeights::active(&defaults, write).await?;
         let requirements = HostRequirements {
             scheduler: &scheduler,
             protocol,
@@ -434,11 +615,24 @@ impl Node {
             cpu_cores: self.cpu_cores,
             memory_bytes: self.memory_bytes,
             disk_bytes: self.disk_bytes,
+            gpu_count: self.gpu_count,
+            gpu_model: node_config.vm.gpu_model.as_deref(),
+            nvme_iops: node_config
+                .vm
+                .nvme_iops
+                .map(|iops| i64::try_from(iops).map_err(Error::VmNvmeIops))
+                .transpose()?,
+            network_bandwidth_mbps: node_config
+                .vm
+                .network_bandwidth_mbps
+                .map(|mbps| i64::try_from(mbps).map_err(Error::VmNetworkBandwidth))
+                .transpose()?,
+            weights,
         };
         let candidates = Host::candidates(requirements, Some(2), write).await?;
 
         let mut counts: HashMap<HostId, usize> = HashMap::new();
-        for log in NodeLog::by_node_id(self.id, write).await? {
+        for log in NodeLog::by_node(self, write).await? {
             if log.event == NodeEvent::CreateStarted {
                 *counts.entry(log.host_id).or_insert(0) += 1;
             }
@@ -477,6 +671,8 @@ impl Node {
             resource: self.scheduler_resource,
             similarity: self.scheduler_similarity,
             region: self.region(conn).await?,
+            placement: self.scheduler_placement,
+            pool_id: self.scheduler_pool_id,
         })
     }
 
@@ -491,6 +687,15 @@ impl Node {
             .map_err(Error::Region)
     }
 
+    /// Returns an error if the node is suspended for suspected abuse.
+    pub fn check_not_suspended(&self) -> Result<(), Error> {
+        if self.node_state == NodeState::Suspended {
+            Err(Error::Suspended(self.id))
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn status(&self) -> NodeStatus {
         NodeStatus {
             state: self.node_state,
@@ -505,6 +710,7 @@ impl Node {
         }
     }
 
+    /// Private keys and seed phrases in `message` are redacted before it is stored.
     pub async fn report(
         &self,
         created_by: Resource,
@@ -515,7 +721,7 @@ impl Node {
             node_id: self.id,
             created_by_type: created_by.typ(),
             created_by_id: created_by.id(),
-            message,
+            message: secrets::redact_secrets(&message),
         };
         report.create(conn).await.map_err(Error::Report)
     }
@@ -565,6 +771,7 @@ impl Node {
             org_id,
         };
         let upgraded = upgrade.apply(authz, write).await?;
+        let upgraded = upgraded.upgrade_dns(self.image_id, write).await?;
 
         let cmd = NewCommand::node(&upgraded, CommandType::NodeUpgrade)
             .map_err(|err| Error::Command(Box::new(err)))?
@@ -597,6 +804,9 @@ pub struct NewNode {
     pub semantic_version: Version,
     pub auto_upgrade: bool,
     pub tags: Tags,
+    pub scheduler_placement: PlacementPolicy,
+    pub scheduler_pool_id: Option<HostPoolId>,
+    pub project_id: Option<ProjectId>,
 }
 
 impl NewNode {
@@ -675,62 +885,32 @@ impl NewNode {
         let cpu_cores = i64::try_from(node_config.vm.cpu_cores).map_err(Error::VmCpu)?;
         let memory_bytes = i64::try_from(node_config.vm.memory_bytes).map_err(Error::VmMemory)?;
         let disk_bytes = i64::try_from(node_config.vm.disk_bytes).map_err(Error::VmDisk)?;
-
-        if cpu_cores + host.node_cpu_cores > host.cpu_cores {
-            return Err(Error::HostFreeCpu(host.id));
-        } else if memory_bytes + host.node_memory_bytes > host.memory_bytes {
-            return Err(Error::HostFreeMem(host.id));
-        } else if disk_bytes + host.node_disk_bytes > host.disk_bytes {
-            return Err(Error::HostFreeDisk(host.id));
-        }
+        let gpu_count = i64::try_from(node_config.vm.gpu_count).map_err(Error::VmGpu)?;
+        check_host_resources(host, node_config)?;
 
         let ip_address = IpAddress::next_for_host(host.id, write)
             .await?
             .ok_or_else(|| Error::HostFreeIp(host.id))?;
 
-        // Users that have the billing-exempt permission or that are launching a node on their own
-        // host do not need to be charged.
-        let billing_exempt =
-            authz.has_perm(BillingPerm::Exempt) || host.org_id == Some(self.org_id);
-        let (stripe_item_id, price) = if billing_exempt {
-            (None, None)
-        } else {
+        // only the sku is found here, as the item is added after commit
+        let sku = if is_billed(host, self.org_id, authz) {
             let region = Region::by_id(host.region_id, write).await?;
-
-            if let Some(sku) = version.sku(&region) {
-                if let Some(stripe) = write.ctx.stripe.as_ref() {
-                    let item = stripe.add_subscription(org, &sku).await?;
-                    let price = item
-                        .price
-                        .ok_or(Error::ItemWithoutPrice)?
-                        .unit_amount
-                        .ok_or(Error::PriceWithoutAmount)?;
-                    (Some(item.id), Some(price))
-                } else {
-                    warn!("Stripe not configured, cannot charge for node!");
-                    (None, None)
-                }
-            } else {
-                (None, None)
-            }
+            version.sku(&region)
+        } else {
+            None
         };
-        let cost = price.map(|amount| Amount {
-            amount,
-            currency: Currency::Usd,
-            period: Period::Monthly,
-        });
 
-        loop {
+        let node = loop {
             let name = Petnames::small()
                 .generate_one(3, "-")
                 .ok_or(Error::GenerateName)?;
-            let dns_id = write.ctx.dns.create(&name, ip_address.ip.ip()).await?.id;
             let dns_name = if let Some(scheme) = &image.dns_scheme {
                 format!("{scheme}://{name}.{dns_base}")
             } else {
                 format!("{name}.{dns_base}")
             };
 
+            // the dns records are also only added after commit
             match diesel::insert_into(nodes::table)
                 .values((
                     self,
@@ -740,78 +920,197 @@ impl NewNode {
                     nodes::node_state.eq(NodeState::Starting),
                     nodes::ip_address.eq(&ip_address.ip),
                     nodes::ip_gateway.eq(&host.ip_gateway),
-                    nodes::dns_id.eq(&dns_id),
+                    nodes::dns_id.eq(""),
                     nodes::dns_name.eq(&dns_name),
                     nodes::cpu_cores.eq(cpu_cores),
                     nodes::memory_bytes.eq(memory_bytes),
                     nodes::disk_bytes.eq(disk_bytes),
-                    nodes::stripe_item_id.eq(&stripe_item_id),
+                    nodes::gpu_count.eq(gpu_count),
                     nodes::created_by_type.eq(created_by.typ()),
                     nodes::created_by_id.eq(created_by.id()),
                     nodes::created_at.eq(Utc::now()),
-                    nodes::cost.eq(&cost),
+                    nodes::network_id.eq(image.network_id),
                 ))
                 .get_result::<Node>(&mut write)
                 .await
             {
-                Ok(node) => {
-                    Org::add_node(self.org_id, write).await?;
-                    Host::add_node(&node, write).await?;
-
-                    /*
-                        if let Some(secrets) = secrets {
-                        for (name, data) in secrets {
-                        let path = format!("node/{}/secret/{name}", node.id);
-                        let _version =
-                        write.ctx.vault.read().await.set_bytes(&path, data).await?;
-                    }
-                    }
-                         */
-
-                    return Ok(node);
+                Ok(node) => break node,
+                Err(DatabaseError(UniqueViolation, ref info))
+                    if info.column_name() == Some("name") =>
+                {
+                    warn!("Node name {} already taken. Retrying...", name);
                 }
+                Err(err) => return Err(Error::Create(err)),
+            }
+        };
 
-                Err(err) => {
-                    if let Err(err) = write.ctx.dns.delete(&dns_id).await {
-                        warn!("Failed to delete DNS record {dns_id}: {err}");
-                    }
+        Org::add_node(self.org_id, write).await?;
+        Host::add_node(&node, write).await?;
 
-                    if let DatabaseError(UniqueViolation, ref info) = err {
-                        if info.column_name() == Some("name") {
-                            warn!("Node name {} already taken. Retrying...", name);
-                            continue;
-                        }
-                    }
+        /*
+            if let Some(secrets) = secrets {
+            for (name, data) in secrets {
+            let path = format!("node/{}/secret/{name}", node.id);
+            let _version =
+            write.ctx.vault.read().await.set_bytes(&path, data).await?;
+        }
+        }
+             */
 
-                    return Err(Error::Create(err));
-                }
-            }
+        NodeProvision {
+            node_id: node.id,
+            name: node.node_name.clone(),
+            ip: ip_address.ip.ip(),
+            dns_settings: image.dns_settings.clone(),
+            org: org.clone(),
+            sku,
         }
+        .after_commit(write);
+
+        Ok(node)
     }
+}
 
-    /// Finds the most suitable host to place the node on.
-    async fn find_host(
-        &self,
-        scheduler: &NodeScheduler,
-        authz: &AuthZ,
-        conn: &mut Conn<'_>,
-    ) -> Result<HostCandidate, Error> {
-        let config = Config::by_id(self.config_id, conn).await?;
-        let node_config = config.node_config()?;
-        let protocol = Protocol::by_id(self.protocol_id, Some(self.org_id), authz, conn).await?;
+/// Returns an error if `host` lacks the free resources for a node with
+/// `node_config`.
+pub fn check_host_resources(host: &Host, node_config: &NodeConfig) -> Result<(), Error> {
+    let cpu_cores = i64::try_from(node_config.vm.cpu_cores).map_err(Error::VmCpu)?;
+    let memory_bytes = i64::try_from(node_config.vm.memory_bytes).map_err(Error::VmMemory)?;
+    let disk_bytes = i64::try_from(node_config.vm.disk_bytes).map_err(Error::VmDisk)?;
+    let gpu_count = i64::try_from(node_config.vm.gpu_count).map_err(Error::VmGpu)?;
+    let nvme_iops = node_config
+        .vm
+        .nvme_iops
+        .map(|iops| i64::try_from(iops).map_err(Error::VmNvmeIops))
+        .transpose()?;
+    let bandwidth = node_config
+        .vm
+        .network_bandwidth_mbps
+        .map(|mbps| i64::try_from(mbps).map_err(Error::VmNetworkBandwidth))
+        .transpose()?;
+
+    if cpu_cores + host.node_cpu_cores > host.cpu_cores {
+        Err(Error::HostFreeCpu(host.id))
+    } else if memory_bytes + host.node_memory_bytes > host.memory_bytes {
+        Err(Error::HostFreeMem(host.id))
+    } else if disk_bytes + host.node_disk_bytes > host.disk_bytes {
+        Err(Error::HostFreeDisk(host.id))
+    } else if gpu_count + host.node_gpu_count > host.gpu_count {
+        Err(Error::HostFreeGpu(host.id))
+    } else if node_config
+        .vm
+        .gpu_model
+        .as_ref()
+        .is_some_and(|model| host.gpu_model.as_ref() != Some(model))
+    {
+        Err(Error::HostGpuModel(host.id))
+    } else if nvme_iops.is_some_and(|iops| host.nvme_iops.unwrap_or(0) < iops) {
+        Err(Error::HostNvmeIops(host.id))
+    } else if bandwidth.is_some_and(|mbps| host.network_bandwidth_mbps.unwrap_or(0) < mbps) {
+        Err(Error::HostNetworkBandwidth(host.id))
+    } else {
+        Ok(())
+    }
+}
 
+/// Whether a node of `org_id` on `host` is charged for.
+///
+/// Users that have the billing-exempt permission or that are launching a node on their own
+/// private host (in a pool or not) do not need to be charged.
+pub fn is_billed(host: &Host, org_id: OrgId, authz: &AuthZ) -> bool {
+    !authz.has_perm(BillingPerm::Exempt) && host.org_id != Some(org_id)
+}
+
+/// Finds the most suitable host to place a node with `node_config` on.
+///
+/// If shadow host weights are set, the host that they would have chosen instead
+/// is logged.
+pub async fn find_host(
+    scheduler: &NodeScheduler,
+    org_id: OrgId,
+    protocol_id: ProtocolId,
+    node_config: &NodeConfig,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<HostCandidate, Error> {
+    let protocol = Protocol::by_id(protocol_id, Some(org_id), authz, write).await?;
+    let defaults = write.ctx.config.scheduler.clone();
+    let weights = HostWeights::active(&defaults, write).await?;
+    let requirements = host_requirements(scheduler, &protocol, org_id, node_config, weights)?;
+
+    let candidates = Host::candidates(requirements, Some(1), write).await?;
+    let candidate = candidates.into_iter().next().ok_or(Error::NoMatchingHost)?;
+
+    if let Some(shadow) = HostWeights::by_ranking(HostRanking::Shadow, write).await? {
         let requirements = HostRequirements {
-            scheduler,
-            protocol: &protocol,
-            org_id: Some(self.org_id),
-            cpu_cores: i64::try_from(node_config.vm.cpu_cores).map_err(Error::VmCpu)?,
-            memory_bytes: i64::try_from(node_config.vm.memory_bytes).map_err(Error::VmMemory)?,
-            disk_bytes: i64::try_from(node_config.vm.disk_bytes).map_err(Error::VmDisk)?,
+            weights: shadow,
+            ..requirements
         };
-
-        let candidates = Host::candidates(requirements, Some(1), conn).await?;
-        candidates.into_iter().next().ok_or(Error::NoMatchingHost)
+        match Host::candidates(requirements, Some(1), write).await {
+            Ok(shadows) => match shadows.first() {
+                Some(shadow) if shadow.host.id != candidate.host.id => info!(
+                    "Shadow host weights would place node of protocol {protocol_id} on host {} \
+                     instead of {}",
+                    shadow.host.id, candidate.host.id
+                ),
+                _ => (),
+            },
+            Err(err) => warn!("Failed to rank candidates by shadow host weights: {err}"),
+        }
     }
+
+    Ok(candidate)
+}
+
+/// The number of nodes with `node_config` that `scheduler` could still place
+/// across all candidate hosts.
+pub async fn host_capacity(
+    scheduler: &NodeScheduler,
+    org_id: OrgId,
+    protocol_id: ProtocolId,
+    node_config: &NodeConfig,
+    authz: &AuthZ,
+    write: &mut WriteConn<'_, '_>,
+) -> Result<u64, Error> {
+    let protocol = Protocol::by_id(protocol_id, Some(org_id), authz, write).await?;
+    let weights = HostWeights::from(&*write.ctx.config.scheduler);
+    let requirements = host_requirements(scheduler, &protocol, org_id, node_config, weights)?;
+
+    let candidates = Host::candidates(requirements, None, write).await?;
+    Ok(candidates
+        .iter()
+        .map(|candidate| u64::from(candidate.capacity(&requirements)))
+        .sum())
+}
+
+fn host_requirements<'r>(
+    scheduler: &'r NodeScheduler,
+    protocol: &'r Protocol,
+    org_id: OrgId,
+    node_config: &'r NodeConfig,
+    weights: HostWeights,
+) -> Result<HostRequirements<'r>, Error> {
+    Ok(HostRequirements {
+        scheduler,
+        protocol,
+        org_id: Some(org_id),
+        cpu_cores: i64::try_from(node_config.vm.cpu_cores).map_err(Error::VmCpu)?,
+        memory_bytes: i64::try_from(node_config.vm.memory_bytes).map_err(Error::VmMemory)?,
+        disk_bytes: i64::try_from(node_config.vm.disk_bytes).map_err(Error::VmDisk)?,
+        gpu_count: i64::try_from(node_config.vm.gpu_count).map_err(Error::VmGpu)?,
+        gpu_model: node_config.vm.gpu_model.as_deref(),
+        nvme_iops: node_config
+            .vm
+            .nvme_iops
+            .map(|iops| i64::try_from(iops).map_err(Error::VmNvmeIops))
+            .transpose()?,
+        network_bandwidth_mbps: node_config
+            .vm
+            .network_bandwidth_mbps
+            .map(|mbps| i64::try_from(mbps).map_err(Error::VmNetworkBandwidth))
+            .transpose()?,
+        weights,
+    })
 }
 
 #[derive(Debug, AsChangeset)]
@@ -823,7 +1122,7 @@ pub struct UpdateNode<'u> {
     pub auto_upgrade: Option<bool>,
     pub ip_address: Option<IpNetwork>,
     pub ip_gateway: Option<IpNetwork>,
-    pub note: Option<&'u str>,
+    pub note: Option<Option<&'u str>>,
     pub tags: Option<Tags>,
     pub cost: Option<Amount>,
 }
@@ -851,6 +1150,22 @@ impl UpdateNode<'_> {
             NewNodeLog::from(&node, authz, event).create(conn).await?;
         }
 
+        // an unchanged display name is only checked when moving to another org
+        let display_name = self
+            .display_name
+            .filter(|name| *name != node.display_name || self.org_id.is_some());
+        let note = self.note.flatten();
+        if display_name.is_some() || note.is_some() {
+            let org_id = self.org_id.unwrap_or(node.org_id);
+            let policy = NodePolicy::by_org_id(org_id, conn).await?;
+            if let Some(name) = display_name {
+                policy.check_display_name(id, org_id, name, conn).await?;
+            }
+            if let Some(note) = note {
+                policy.check_note(note)?;
+            }
+        }
+
         diesel::update(nodes::table.find(id))
             .set((self, nodes::updated_at.eq(Utc::now())))
             .get_result(conn)
@@ -910,13 +1225,52 @@ pub struct UpdateNodeState<'u> {
 }
 
 impl UpdateNodeState<'_> {
+    /// Apply the update, rejecting an illegal change of `node_state`.
     pub async fn apply(self, id: NodeId, conn: &mut Conn<'_>) -> Result<Node, Error> {
+        if let Some(to) = self.node_state {
+            Self::check_transition(id, to, conn).await?;
+        }
+        self.force(id, conn).await
+    }
+
+    /// Apply the update without checking the `node_state` transition.
+    pub async fn force(self, id: NodeId, conn: &mut Conn<'_>) -> Result<Node, Error> {
+        let tracked = self.node_state.is_some() || self.protocol_health.is_some();
         let row = nodes::table.find(id);
-        diesel::update(row)
+        let node: Node = diesel::update(row)
             .set((self, nodes::updated_at.eq(Utc::now())))
             .get_result(conn)
             .await
-            .map_err(Error::UpdateStatus)
+            .map_err(Error::UpdateStatus)?;
+
+        if tracked {
+            NodeDowntime::track(&node, conn).await?;
+        }
+
+        Ok(node)
+    }
+
+    async fn check_transition(id: NodeId, to: NodeState, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let (from, next): (NodeState, Option<NextState>) = nodes::table
+            .find(id)
+            .select((nodes::node_state, nodes::next_state))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::FindState(id, err))?;
+
+        match from.transition(to, next) {
+            Transition::Allowed => Ok(()),
+            Transition::NeedsStart
+                if Command::has_node_start(id, conn)
+                    .await
+                    .map_err(|err| Error::Command(Box::new(err)))? =>
+            {
+                Ok(())
+            }
+            Transition::NeedsStart | Transition::Invalid => {
+                Err(Error::InvalidTransition(id, from, to))
+            }
+        }
     }
 }
 
@@ -930,7 +1284,9 @@ pub struct UpdateNodeMetrics {
     pub block_height: Option<i64>,
     pub block_age: Option<i64>,
     pub consensus: Option<bool>,
+    pub peer_count: Option<i32>,
     pub jobs: Option<NodeJobs>,
+    pub cpu_percent: Option<i32>,
 }
 
 impl UpdateNodeMetrics {
@@ -939,11 +1295,17 @@ impl UpdateNodeMetrics {
             .find(self.id)
             .filter(nodes::deleted_at.is_null());
 
-        diesel::update(row)
+        let node: Node = diesel::update(row)
             .set(self)
             .get_result(conn)
             .await
-            .map_err(|err| Error::UpdateMetrics(self.id, err))
+            .map_err(|err| Error::UpdateMetrics(self.id, err))?;
+
+        if self.node_state.is_some() || self.protocol_health.is_some() {
+            NodeDowntime::track(&node, conn).await?;
+        }
+
+        Ok(node)
     }
 
     pub async fn apply_all(updates: Vec<Self>, conn: &mut Conn<'_>) -> Result<Vec<Node>, Error> {
@@ -1002,6 +1364,7 @@ impl UpgradeNode<'_> {
                 nodes::protocol_id.eq(self.version.protocol_id),
                 nodes::protocol_version_id.eq(self.version.id),
                 nodes::semantic_version.eq(&self.version.semantic_version),
+                nodes::network_id.eq(self.image.network_id.or(node.network_id)),
                 nodes::next_state.eq(Some(NextState::Upgrading)),
                 nodes::updated_at.eq(Utc::now()),
             ))
@@ -1031,6 +1394,7 @@ pub enum NodeSort {
     ProtocolState(SortOrder),
     ProtocolHealth(SortOrder),
     BlockHeight(SortOrder),
+    BlocksBehind(SortOrder),
     CreatedAt(SortOrder),
     UpdatedAt(SortOrder),
 }
@@ -1077,6 +1441,9 @@ impl NodeSort {
             BlockHeight(Asc) => Box::new(nodes::block_height.asc()),
             BlockHeight(Desc) => Box::new(nodes::block_height.desc()),
 
+            BlocksBehind(Asc) => Box::new(blocks_behind().asc().nulls_last()),
+            BlocksBehind(Desc) => Box::new(blocks_behind().desc().nulls_last()),
+
             CreatedAt(Asc) => Box::new(nodes::created_at.asc()),
             CreatedAt(Desc) => Box::new(nodes::created_at.desc()),
 
@@ -1097,6 +1464,14 @@ pub struct NodeFilter {
     pub ip_addresses: Vec<IpNetwork>,
     pub node_states: Vec<NodeState>,
     pub next_states: Vec<NextState>,
+    /// Only nodes at least this many blocks behind the head of their network.
+    pub min_blocks_behind: Option<i64>,
+    /// Only nodes whose host does, or does not, run a drifted config.
+    pub config_drift: Option<bool>,
+    pub project_ids: Vec<ProjectId>,
+    pub network_ids: Vec<ProtocolNetworkId>,
+    /// Only nodes on a network of one of these kinds.
+    pub network_kinds: Vec<NetworkKind>,
     pub search: Option<NodeSearch>,
     pub sort: VecDeque<NodeSort>,
     pub limit: i64,
@@ -1174,6 +1549,29 @@ impl NodeFilter {
             query = query.filter(nodes::next_state.eq_any(self.next_states));
         }
 
+        if let Some(min) = self.min_blocks_behind {
+            query = query.filter(blocks_behind().ge(min));
+        }
+
+        if let Some(drift) = self.config_drift {
+            query = query.filter(nodes::config_drift.eq(drift));
+        }
+
+        if !self.project_ids.is_empty() {
+            query = query.filter(nodes::project_id.eq_any(self.project_ids));
+        }
+
+        if !self.network_ids.is_empty() {
+            query = query.filter(nodes::network_id.eq_any(self.network_ids));
+        }
+
+        if !self.network_kinds.is_empty() {
+            let networks = protocol_networks::table
+                .filter(protocol_networks::kind.eq_any(self.network_kinds))
+                .select(protocol_networks::id.nullable());
+            query = query.filter(nodes::network_id.eq_any(networks));
+        }
+
         if let Some(sort) = self.sort.pop_front() {
             query = query.order_by(sort.into_expr());
         } else {
@@ -1195,6 +1593,18 @@ impl NodeFilter {
 
 type NodesAndVersions = InnerJoinQuerySource<nodes::table, protocol_versions::table>;
 
+/// How many blocks a node is behind the head of its network, or null when
+/// either is unknown.
+///
+/// This must be used in a query joining `nodes` with `protocol_versions`.
+fn blocks_behind() -> SqlLiteral<Nullable<BigInt>> {
+    dsl::sql(
+        "(select network_heads.block_height - nodes.block_height from network_heads \
+          where network_heads.protocol_id = nodes.protocol_id \
+          and network_heads.variant_key = protocol_versions.variant_key)",
+    )
+}
+
 impl NodeSearch {
     fn into_expression(self) -> Box<dyn BoxableExpression<NodesAndVersions, Pg, SqlType = Bool>> {
         match self.operator {
@@ -1260,11 +1670,13 @@ mod tests {
         let (ctx, db) = Context::with_mocked().await.unwrap();
         let (meta_tx, _meta_rx) = mpsc::unbounded_channel();
         let (mqtt_tx, _mqtt_rx) = mpsc::unbounded_channel();
+        let (commit_tx, _commit_rx) = mpsc::unbounded_channel();
         let mut write = WriteConn {
             conn: &mut db.conn().await,
             ctx: &ctx,
             meta_tx,
             mqtt_tx,
+            commit_tx,
         };
 
         let new_node = NewNode {
@@ -1277,6 +1689,9 @@ mod tests {
             semantic_version: "1.2.3".parse().unwrap(),
             auto_upgrade: false,
             tags: Default::default(),
+            scheduler_placement: PlacementPolicy::Any,
+            scheduler_pool_id: None,
+            project_id: None,
         };
 
         let launch = Launch::ByHost(vec![HostCount::one(db.seed.host1.id)]);
@@ -1300,6 +1715,11 @@ mod tests {
             ip_addresses: vec![],
             node_states: vec![NodeState::Running],
             next_states: vec![],
+            min_blocks_behind: None,
+            config_drift: None,
+            project_ids: vec![],
+            network_ids: vec![],
+            network_kinds: vec![],
             search: None,
             sort: VecDeque::new(),
             offset: 0,
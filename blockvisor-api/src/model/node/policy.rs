@@ -0,0 +1,212 @@
+//! Per-org rules for the display names and notes of nodes.
+//!
+//! An org may limit the length of node display names and notes, restrict the
+//! characters of display names, and require display names to be unique among
+//! its live nodes. The policy is checked whenever an update sets either field.
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::Conn;
+use crate::grpc::validate::FieldViolation;
+use crate::grpc::{Status, api};
+use crate::model::schema::{nodes, orgs, sql_types};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find node policy for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Display name may only contain {0}.
+    DisplayNameCharset(&'static str),
+    /// Display name is longer than {0} characters.
+    DisplayNameLength(i32),
+    /// Display name `{0}` is already used by another node of this org.
+    DisplayNameTaken(String),
+    /// Failed to check if display name is taken: {0}
+    FindDisplayName(diesel::result::Error),
+    /// Note is longer than {0} characters.
+    NoteLength(i32),
+    /// Unknown node name charset.
+    UnknownCharset,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        let field = match err {
+            DisplayNameCharset(_) | DisplayNameLength(_) | DisplayNameTaken(_) => "display_name",
+            NoteLength(_) => "note",
+            UnknownCharset => return Status::invalid_argument("node_display_name_charset"),
+            ByOrgId(..) | FindDisplayName(_) => return Status::internal("Internal error."),
+        };
+
+        Status::bad_request(vec![FieldViolation {
+            field: field.to_string(),
+            description: err.to_string(),
+        }])
+    }
+}
+
+/// The characters allowed in the display names of the nodes of an org.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumNodeNameCharset"]
+pub enum NodeNameCharset {
+    #[default]
+    Any,
+    /// ASCII letters and digits, spaces, `-`, `_` and `.`.
+    Alphanumeric,
+    /// Lowercase ASCII letters, digits and `-`, not at either end.
+    DnsLabel,
+}
+
+impl NodeNameCharset {
+    fn allows(self, name: &str) -> bool {
+        match self {
+            NodeNameCharset::Any => true,
+            NodeNameCharset::Alphanumeric => name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.')),
+            NodeNameCharset::DnsLabel => {
+                !name.starts_with('-')
+                    && !name.ends_with('-')
+                    && name
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            }
+        }
+    }
+
+    const fn description(self) -> &'static str {
+        match self {
+            NodeNameCharset::Any => "any characters",
+            NodeNameCharset::Alphanumeric => "letters, digits, spaces, `-`, `_` and `.`",
+            NodeNameCharset::DnsLabel => "lowercase letters, digits and inner `-`",
+        }
+    }
+}
+
+impl From<NodeNameCharset> for api::NodeNameCharset {
+    fn from(charset: NodeNameCharset) -> Self {
+        match charset {
+            NodeNameCharset::Any => api::NodeNameCharset::Any,
+            NodeNameCharset::Alphanumeric => api::NodeNameCharset::Alphanumeric,
+            NodeNameCharset::DnsLabel => api::NodeNameCharset::DnsLabel,
+        }
+    }
+}
+
+impl TryFrom<api::NodeNameCharset> for NodeNameCharset {
+    type Error = Error;
+
+    fn try_from(charset: api::NodeNameCharset) -> Result<Self, Self::Error> {
+        match charset {
+            api::NodeNameCharset::Unspecified => Err(Error::UnknownCharset),
+            api::NodeNameCharset::Any => Ok(NodeNameCharset::Any),
+            api::NodeNameCharset::Alphanumeric => Ok(NodeNameCharset::Alphanumeric),
+            api::NodeNameCharset::DnsLabel => Ok(NodeNameCharset::DnsLabel),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Queryable, Selectable)]
+#[diesel(table_name = orgs)]
+pub struct NodePolicy {
+    #[diesel(column_name = node_display_name_max_len)]
+    pub display_name_max_len: Option<i32>,
+    #[diesel(column_name = node_note_max_len)]
+    pub note_max_len: Option<i32>,
+    #[diesel(column_name = node_display_name_unique)]
+    pub display_name_unique: bool,
+    #[diesel(column_name = node_display_name_charset)]
+    pub display_name_charset: NodeNameCharset,
+}
+
+impl NodePolicy {
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        orgs::table
+            .find(org_id)
+            .select(NodePolicy::as_select())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Check a new display name of node `node_id` in org `org_id`.
+    pub async fn check_display_name(
+        &self,
+        node_id: NodeId,
+        org_id: OrgId,
+        display_name: &str,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let max_len = self.display_name_max_len;
+        if let Some(max) = max_len.filter(|max| exceeds(display_name, *max)) {
+            return Err(Error::DisplayNameLength(max));
+        }
+
+        let charset = self.display_name_charset;
+        if !charset.allows(display_name) {
+            return Err(Error::DisplayNameCharset(charset.description()));
+        }
+
+        if self.display_name_unique {
+            let taken = diesel::select(diesel::dsl::exists(
+                nodes::table
+                    .filter(nodes::org_id.eq(org_id))
+                    .filter(nodes::display_name.eq(display_name))
+                    .filter(nodes::id.ne(node_id))
+                    .filter(nodes::deleted_at.is_null()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(Error::FindDisplayName)?;
+            if taken {
+                return Err(Error::DisplayNameTaken(display_name.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a new note of a node.
+    pub fn check_note(&self, note: &str) -> Result<(), Error> {
+        match self.note_max_len {
+            Some(max) if exceeds(note, max) => Err(Error::NoteLength(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether `text` has more than `max` characters.
+fn exceeds(text: &str, max: i32) -> bool {
+    usize::try_from(max).is_ok_and(|max| text.chars().count() > max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_name_charsets() {
+        assert!(NodeNameCharset::Any.allows("<script>alert('XSS');</script>"));
+
+        assert!(NodeNameCharset::Alphanumeric.allows("Eth Node_1.prod"));
+        assert!(!NodeNameCharset::Alphanumeric.allows("node/1"));
+        assert!(!NodeNameCharset::Alphanumeric.allows("nöde"));
+
+        assert!(NodeNameCharset::DnsLabel.allows("eth-node-1"));
+        assert!(!NodeNameCharset::DnsLabel.allows("Eth-node"));
+        assert!(!NodeNameCharset::DnsLabel.allows("-eth-node"));
+        assert!(!NodeNameCharset::DnsLabel.allows("eth node"));
+    }
+
+    #[test]
+    fn lengths_count_characters() {
+        assert!(!exceeds("nöde", 4));
+        assert!(exceeds("nodes", 4));
+    }
+}
@@ -0,0 +1,133 @@
+//! The external state of a newly created node.
+//!
+//! A node is inserted without its DNS records or stripe subscription item, so
+//! that its create can be retried on a conflict without repeating any calls to
+//! an external service. Once the create has committed, `NodeProvision` adds
+//! them and stores their ids on the node.
+//!
+//! A failure to provision is only logged and leaves the node without the
+//! failed records or item, in the same way that a failed rename or upgrade of
+//! the DNS records of a node does.
+
+use std::net::IpAddr;
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::auth::resource::NodeId;
+use crate::config::Context;
+use crate::database::{Database, WriteConn};
+use crate::model::Org;
+use crate::model::image::DnsSettings;
+use crate::model::schema::nodes;
+use crate::model::sql::{Amount, Currency, Period};
+
+use super::dns::{self, NodeRecords};
+
+#[derive(Debug, Display, Error)]
+enum Error {
+    /// Node provision database error: {0}
+    Database(#[from] crate::database::Error),
+    /// Node provision dns error: {0}
+    Dns(#[from] self::dns::Error),
+    /// Stripe subscription item has no price.
+    ItemWithoutPrice,
+    /// Stripe price has no unit amount.
+    PriceWithoutAmount,
+    /// Node provision stripe error: {0}
+    Stripe(#[from] crate::stripe::Error),
+    /// Failed to store the DNS records of node `{0}`: {1}
+    UpdateRecords(NodeId, diesel::result::Error),
+    /// Failed to store the stripe item of node `{0}`: {1}
+    UpdateStripe(NodeId, diesel::result::Error),
+}
+
+/// The DNS records and stripe item to add for a node once it is created.
+pub struct NodeProvision {
+    pub node_id: NodeId,
+    pub name: String,
+    pub ip: IpAddr,
+    pub dns_settings: Option<DnsSettings>,
+    pub org: Org,
+    /// The stripe SKU to charge the node to, if it is billed.
+    pub sku: Option<String>,
+}
+
+impl NodeProvision {
+    /// Provision the node once `write` has committed.
+    pub fn after_commit(self, write: &mut WriteConn<'_, '_>) {
+        let ctx = write.ctx.clone();
+        write.after_commit(async move { self.run(&ctx).await });
+    }
+
+    async fn run(self, context: &Context) {
+        let node_id = self.node_id;
+        if let Err(err) = self.add_dns(context).await {
+            warn!("Failed to add DNS records of node {node_id}: {err}");
+        }
+        if let Err(err) = self.add_stripe(context).await {
+            warn!("Failed to add stripe item of node {node_id}: {err}");
+        }
+    }
+
+    async fn add_dns(&self, context: &Context) -> Result<(), Error> {
+        let settings = self.dns_settings.as_ref();
+        let records = NodeRecords::create(&self.name, self.ip, settings, &**context.dns).await?;
+
+        let mut conn = context.conn().await?;
+        let updated = diesel::update(nodes::table.find(self.node_id))
+            .set((
+                nodes::dns_id.eq(&records.dns_id),
+                nodes::dns_record_ids.eq(records.stored_ids()),
+            ))
+            .execute(&mut conn)
+            .await;
+
+        if let Err(err) = updated {
+            dns::delete_records(records.ids(), &**context.dns).await;
+            return Err(Error::UpdateRecords(self.node_id, err));
+        }
+
+        Ok(())
+    }
+
+    async fn add_stripe(&self, context: &Context) -> Result<(), Error> {
+        let Some(sku) = &self.sku else {
+            return Ok(());
+        };
+        let Some(stripe) = context.stripe.as_ref() else {
+            warn!("Stripe not configured, cannot charge for node!");
+            return Ok(());
+        };
+
+        let item = stripe
+            .add_subscription(&self.org, &context.keyring, sku)
+            .await?;
+        let amount = item
+            .price
+            .ok_or(Error::ItemWithoutPrice)?
+            .unit_amount
+            .ok_or(Error::PriceWithoutAmount)?;
+        let stripe_item_id = Some(item.id);
+        let cost = Some(Amount {
+            amount,
+            currency: Currency::Usd,
+            period: Period::Monthly,
+        });
+
+        let mut conn = context.conn().await?;
+        diesel::update(nodes::table.find(self.node_id))
+            .set((
+                nodes::stripe_item_id.eq(&stripe_item_id),
+                nodes::cost.eq(&cost),
+            ))
+            .execute(&mut conn)
+            .await
+            .map_err(|err| Error::UpdateStripe(self.node_id, err))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! Renaming a node, which moves it to a new DNS name.
+//!
+//! The address and extra records of the new name are created before the node
+//! is updated, with a generated name retried until one is free like a new node.
+//! The old address record is kept as an address alias of the node for
+//! `RENAME_GRACE`, so that clients of the old DNS name keep working while they
+//! move over, and the old extra records are removed.
+
+use chrono::{TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use petname::{Generator, Petnames};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::auth::resource::{NodeId, Resource};
+use crate::database::{Conn, WriteConn};
+use crate::grpc::Status;
+use crate::model::image::DnsSettings;
+use crate::model::schema::nodes;
+
+use super::dns::{self, NodeRecords};
+use super::{DnsAliasType, NewNodeDnsAlias, Node};
+
+/// How long the old DNS name of a renamed node keeps resolving.
+pub const RENAME_GRACE: TimeDelta = TimeDelta::days(7);
+
+/// The longest node name, which is a single DNS label.
+const MAX_NAME_LEN: usize = 63;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Node rename alias error: {0}
+    Alias(#[from] super::alias::Error),
+    /// Node rename dns error: {0}
+    Dns(#[from] super::dns::Error),
+    /// Failed to check if node name is taken: {0}
+    FindName(diesel::result::Error),
+    /// Failed to generate node name. This should not happen.
+    GenerateName,
+    /// Node name `{0}` is not a valid DNS label.
+    InvalidName(String),
+    /// Node name `{0}` is already taken.
+    NameTaken(String),
+    /// Node is already named `{0}`.
+    SameName(String),
+    /// Node rename dns settings error: {0}
+    Settings(#[from] crate::model::image::dns::Error),
+    /// Failed to rename node `{0}`: {1}
+    Update(NodeId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            InvalidName(_) | SameName(_) => Status::invalid_argument("new_node_name"),
+            NameTaken(_) => Status::already_exists("Node name is taken."),
+            FindName(_) | GenerateName | Update(..) => Status::internal("Internal error."),
+            Alias(err) => err.into(),
+            Dns(err) => err.into(),
+            Settings(err) => err.into(),
+        }
+    }
+}
+
+impl Node {
+    /// Rename this node to `new_name`, or to a generated name if `None`.
+    ///
+    /// A display name that was left as the old node name follows it.
+    pub async fn rename(
+        self,
+        new_name: Option<&str>,
+        renamed_by: Resource,
+        write: &mut WriteConn<'_, '_>,
+    ) -> Result<Self, Error> {
+        let new_name = new_name.map(parse_name).transpose()?;
+        if new_name.as_ref() == Some(&self.node_name) {
+            return Err(Error::SameName(self.node_name));
+        }
+
+        let ctx = write.ctx;
+        let base = &ctx.config.cloudflare.dns.base;
+        let settings = DnsSettings::by_image_id(self.image_id, write).await?;
+        let scheme = self.dns_name.split_once("://").map(|(scheme, _)| scheme);
+
+        let name = loop {
+            let name = match &new_name {
+                Some(name) => name.clone(),
+                None => Petnames::small()
+                    .generate_one(3, "-")
+                    .ok_or(Error::GenerateName)?,
+            };
+            if !name_taken(&name, write).await? {
+                break name;
+            } else if new_name.is_some() {
+                return Err(Error::NameTaken(name));
+            }
+            warn!("Node name {name} already taken. Retrying...");
+        };
+
+        let records =
+            NodeRecords::create(&name, self.ip_address.ip(), settings.as_ref(), &**ctx.dns).await?;
+        let dns_name = scheme.map_or_else(
+            || format!("{name}.{base}"),
+            |scheme| format!("{scheme}://{name}.{base}"),
+        );
+        let display_name = if self.display_name == self.node_name {
+            &name
+        } else {
+            &self.display_name
+        };
+
+        let node: Self = match diesel::update(nodes::table.find(self.id))
+            .set((
+                nodes::node_name.eq(&name),
+                nodes::display_name.eq(display_name),
+                nodes::dns_id.eq(&records.dns_id),
+                nodes::dns_name.eq(&dns_name),
+                nodes::dns_record_ids.eq(records.stored_ids()),
+                nodes::updated_at.eq(Utc::now()),
+            ))
+            .get_result(write)
+            .await
+        {
+            Ok(node) => node,
+            Err(err) => {
+                dns::delete_records(records.ids(), &**ctx.dns).await;
+                return Err(Error::Update(self.id, err));
+            }
+        };
+
+        let old_name = format!("{}.{base}", self.node_name);
+        NewNodeDnsAlias::new(
+            &node,
+            old_name,
+            DnsAliasType::Address,
+            self.dns_id.clone(),
+            renamed_by,
+        )
+        .expires_at(Utc::now() + RENAME_GRACE)
+        .create(write)
+        .await?;
+
+        let old_ids = self.dns_record_ids.iter().flatten().map(String::as_str);
+        dns::delete_records(old_ids, &**ctx.dns).await;
+
+        Ok(node)
+    }
+}
+
+/// Check that `name` is a valid node name, returning it in lowercase.
+fn parse_name(name: &str) -> Result<String, Error> {
+    let name = name.trim().to_lowercase();
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+    if valid {
+        Ok(name)
+    } else {
+        Err(Error::InvalidName(name))
+    }
+}
+
+async fn name_taken(name: &str, conn: &mut Conn<'_>) -> Result<bool, Error> {
+    diesel::select(diesel::dsl::exists(
+        nodes::table.filter(nodes::node_name.eq(name)),
+    ))
+    .get_result(conn)
+    .await
+    .map_err(Error::FindName)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_names_are_dns_labels() {
+        assert_eq!(
+            parse_name(" Happy-Blue-Whale ").unwrap(),
+            "happy-blue-whale"
+        );
+
+        for invalid in ["", "-whale", "whale-", "blue.whale", "blue_whale"] {
+            let err = parse_name(invalid).unwrap_err();
+            assert!(matches!(err, Error::InvalidName(_)), "{invalid}");
+        }
+        assert!(parse_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+}
@@ -17,6 +17,8 @@ use crate::model::schema::node_reports;
 
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
+    /// Failed to find node report `{0}`: {1}
+    ById(NodeReportId, diesel::result::Error),
     /// Failed to create report: {0}
     Create(diesel::result::Error),
     /// Failed to find node reports by id `{0}`: {1}
@@ -29,7 +31,7 @@ impl From<Error> for Status {
     fn from(err: Error) -> Self {
         use Error::*;
         match err {
-            FindByNode(_, NotFound) | FindByNodes(_, NotFound) => {
+            ById(_, NotFound) | FindByNode(_, NotFound) | FindByNodes(_, NotFound) => {
                 Status::not_found("Report not found.")
             }
             _ => Status::internal("Internal error."),
@@ -65,6 +67,14 @@ pub struct NodeReport {
 }
 
 impl NodeReport {
+    pub async fn by_id(id: NodeReportId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_reports::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
     pub async fn by_node(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
         node_reports::table
             .filter(node_reports::node_id.eq(node_id))
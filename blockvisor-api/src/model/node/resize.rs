@@ -0,0 +1,461 @@
+//! Changes to the vm resources of a node after it was created.
+//!
+//! `NodeResources` applies new CPU, memory or disk sizes to a node, writing a
+//! new config revision and moving the difference on the host of the node.
+//!
+//! Disk can only grow, through a `DiskResize`. A resize is requested by blockvisord when a node runs low on disk, or by a
+//! user of the org. Each request is checked against the free disk of the host
+//! and the disk quota of the org, and is approved straight away when the new
+//! size is within the org's `disk_auto_approve_bytes`. Otherwise it stays
+//! pending until reviewed, and a node has at most one pending resize.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::AuthZ;
+use crate::auth::resource::{HostId, NodeId, OrgId, Resource, ResourceId, ResourceType};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::model::command::CommandId;
+use crate::model::host::Host;
+use crate::model::image::Config;
+use crate::model::image::config::{ConfigType, NewConfig};
+use crate::model::schema::{disk_resizes, nodes, sql_types};
+use crate::model::{Node, Org};
+use crate::util::NanosUtc;
+
+use super::log::{LogEvent, NewNodeLog};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find disk resize `{0}`: {1}
+    ById(DiskResizeId, diesel::result::Error),
+    /// Failed to find disk resizes for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Disk resize config error: {0}
+    Config(#[from] crate::model::image::config::Error),
+    /// Failed to create disk resize: {0}
+    Create(diesel::result::Error),
+    /// New disk size `{0}` is not larger than the current size.
+    DiskNotLarger(i64),
+    /// Disk resize host error: {0}
+    Host(#[from] crate::model::host::Error),
+    /// Host `{0}` has too little free cpu.
+    HostFreeCpu(HostId),
+    /// Host `{0}` has too little free disk.
+    HostFreeDisk(HostId),
+    /// Host `{0}` has too little free memory.
+    HostFreeMem(HostId),
+    /// A node must have at least one cpu core.
+    NoCpu,
+    /// A node must have some memory.
+    NoMemory,
+    /// Disk resize node log error: {0}
+    NodeLog(#[from] super::log::Error),
+    /// Disk resize `{0}` is not pending.
+    NotPending(DiskResizeId),
+    /// Disk resize org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Failed to find the node disk of org `{0}`: {1}
+    OrgDisk(OrgId, diesel::result::Error),
+    /// Org `{0}` would exceed its disk quota.
+    OrgQuota(OrgId),
+    /// Failed to review disk resize `{0}`: {1}
+    Review(DiskResizeId, diesel::result::Error),
+    /// Failed to update the disk of node `{0}`: {1}
+    UpdateNode(NodeId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Node already has a pending disk resize.")
+            }
+            ById(_, NotFound) | Review(_, NotFound) => Status::not_found("Disk resize not found."),
+            DiskNotLarger(_) => Status::invalid_argument("disk_bytes"),
+            HostFreeCpu(_) => Status::failed_precondition("Host has too little available cpu."),
+            HostFreeDisk(_) => Status::failed_precondition("Host has too little available disk."),
+            HostFreeMem(_) => Status::failed_precondition("Host has too little available memory."),
+            NoCpu => Status::invalid_argument("cpu_cores"),
+            NoMemory => Status::invalid_argument("memory_bytes"),
+            NotPending(_) => Status::failed_precondition("Disk resize is not pending."),
+            OrgQuota(_) => Status::failed_precondition("Org disk quota exceeded."),
+            Config(err) => err.into(),
+            Host(err) => err.into(),
+            NodeLog(err) => err.into(),
+            Org(err) => err.into(),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// The vm resources of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeResources {
+    pub cpu_cores: i64,
+    pub memory_bytes: i64,
+    pub disk_bytes: i64,
+}
+
+impl From<&Node> for NodeResources {
+    fn from(node: &Node) -> Self {
+        NodeResources {
+            cpu_cores: node.cpu_cores,
+            memory_bytes: node.memory_bytes,
+            disk_bytes: node.disk_bytes,
+        }
+    }
+}
+
+impl NodeResources {
+    /// Check that the host of `node` has room for these resources.
+    ///
+    /// Only resources that grow need to be free on the host.
+    pub async fn check_host(&self, node: &Node, conn: &mut Conn<'_>) -> Result<(), Error> {
+        if self.cpu_cores < 1 {
+            return Err(Error::NoCpu);
+        } else if self.memory_bytes < 1 {
+            return Err(Error::NoMemory);
+        }
+
+        let host = Host::by_id(node.host_id, None, conn).await?;
+        let added = self.added(node);
+        if added.cpu_cores > 0 && host.node_cpu_cores + added.cpu_cores > host.cpu_cores {
+            Err(Error::HostFreeCpu(host.id))
+        } else if added.memory_bytes > 0
+            && host.node_memory_bytes + added.memory_bytes > host.memory_bytes
+        {
+            Err(Error::HostFreeMem(host.id))
+        } else if added.disk_bytes > 0 && host.node_disk_bytes + added.disk_bytes > host.disk_bytes
+        {
+            Err(Error::HostFreeDisk(host.id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Apply these resources to `node`.
+    ///
+    /// The node gets a new config revision with the new vm resources, the
+    /// difference is moved on its host, and a `Resized` event is logged.
+    pub async fn apply(
+        self,
+        node: &Node,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Node, Error> {
+        let config = Config::by_id(node.config_id, conn).await?;
+        let mut node_config = config.node_config()?;
+        node_config.vm.cpu_cores = u64::try_from(self.cpu_cores).unwrap_or_default();
+        node_config.vm.memory_bytes = u64::try_from(self.memory_bytes).unwrap_or_default();
+        node_config.vm.disk_bytes = u64::try_from(self.disk_bytes).unwrap_or_default();
+        let new_config = NewConfig {
+            image_id: config.image_id,
+            archive_id: config.archive_id,
+            config_type: ConfigType::Node,
+            config: node_config.into(),
+        };
+        let config = new_config.create(authz, conn).await?;
+
+        Host::resize_node(node, self.added(node), conn).await?;
+
+        let event = LogEvent::Resized(Resized {
+            old: NodeResources::from(node),
+            new: self,
+        });
+        NewNodeLog::from(node, authz, event).create(conn).await?;
+
+        diesel::update(nodes::table.find(node.id))
+            .set((
+                nodes::cpu_cores.eq(self.cpu_cores),
+                nodes::memory_bytes.eq(self.memory_bytes),
+                nodes::disk_bytes.eq(self.disk_bytes),
+                nodes::config_id.eq(config.id),
+                nodes::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::UpdateNode(node.id, err))
+    }
+
+    /// The resources added to `node`, where shrunk resources are negative.
+    fn added(&self, node: &Node) -> Self {
+        NodeResources {
+            cpu_cores: self.cpu_cores - node.cpu_cores,
+            memory_bytes: self.memory_bytes - node.memory_bytes,
+            disk_bytes: self.disk_bytes - node.disk_bytes,
+        }
+    }
+}
+
+impl From<NodeResources> for api::NodeResize {
+    fn from(resources: NodeResources) -> Self {
+        let to_u64 = |value: i64| u64::try_from(value).unwrap_or_default();
+        api::NodeResize {
+            cpu_cores: to_u64(resources.cpu_cores),
+            memory_bytes: to_u64(resources.memory_bytes),
+            disk_bytes: to_u64(resources.disk_bytes),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Resized {
+    pub old: NodeResources,
+    pub new: NodeResources,
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct DiskResizeId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumDiskResizeStatus"]
+pub enum DiskResizeStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl From<DiskResizeStatus> for api::DiskResizeStatus {
+    fn from(status: DiskResizeStatus) -> Self {
+        match status {
+            DiskResizeStatus::Pending => api::DiskResizeStatus::Pending,
+            DiskResizeStatus::Approved => api::DiskResizeStatus::Approved,
+            DiskResizeStatus::Rejected => api::DiskResizeStatus::Rejected,
+        }
+    }
+}
+
+impl DiskResizeStatus {
+    pub const fn from_api(status: api::DiskResizeStatus) -> Option<Self> {
+        match status {
+            api::DiskResizeStatus::Unspecified => None,
+            api::DiskResizeStatus::Pending => Some(DiskResizeStatus::Pending),
+            api::DiskResizeStatus::Approved => Some(DiskResizeStatus::Approved),
+            api::DiskResizeStatus::Rejected => Some(DiskResizeStatus::Rejected),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct DiskResize {
+    pub id: DiskResizeId,
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub old_disk_bytes: i64,
+    pub new_disk_bytes: i64,
+    pub used_disk_bytes: Option<i64>,
+    pub status: DiskResizeStatus,
+    pub command_id: Option<CommandId>,
+    pub requested_by_type: ResourceType,
+    pub requested_by_id: ResourceId,
+    pub reviewed_by_type: Option<ResourceType>,
+    pub reviewed_by_id: Option<ResourceId>,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl DiskResize {
+    pub async fn by_id(id: DiskResizeId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        disk_resizes::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(
+        org_id: OrgId,
+        status: Option<DiskResizeStatus>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = disk_resizes::table
+            .filter(disk_resizes::org_id.eq(org_id))
+            .order_by(disk_resizes::created_at.desc())
+            .into_boxed();
+        if let Some(status) = status {
+            query = query.filter(disk_resizes::status.eq(status));
+        }
+
+        query
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Check that the disk of `node` can grow to `disk_bytes`.
+    ///
+    /// Returns the org of the node so the caller can apply its auto-approve
+    /// policy.
+    pub async fn check(node: &Node, disk_bytes: i64, conn: &mut Conn<'_>) -> Result<Org, Error> {
+        if disk_bytes <= node.disk_bytes {
+            return Err(Error::DiskNotLarger(disk_bytes));
+        }
+        let added = disk_bytes - node.disk_bytes;
+
+        let resources = NodeResources {
+            disk_bytes,
+            ..NodeResources::from(node)
+        };
+        resources.check_host(node, conn).await?;
+
+        let org = Org::by_id(node.org_id, conn).await?;
+        if let Some(quota) = org.disk_quota_bytes {
+            let org_disk: Vec<i64> = nodes::table
+                .filter(nodes::org_id.eq(node.org_id))
+                .filter(nodes::deleted_at.is_null())
+                .select(nodes::disk_bytes)
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::OrgDisk(node.org_id, err))?;
+            if org_disk.into_iter().sum::<i64>() + added > quota {
+                return Err(Error::OrgQuota(node.org_id));
+            }
+        }
+
+        Ok(org)
+    }
+
+    /// Grow the disk of `node` to the new size of this resize.
+    pub async fn resize_node(
+        &self,
+        node: &Node,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<Node, Error> {
+        let resources = NodeResources {
+            disk_bytes: self.new_disk_bytes,
+            ..NodeResources::from(node)
+        };
+        resources.apply(node, authz, conn).await
+    }
+
+    pub async fn approve(
+        self,
+        command_id: CommandId,
+        reviewed_by: Option<Resource>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        self.review(
+            DiskResizeStatus::Approved,
+            Some(command_id),
+            reviewed_by,
+            conn,
+        )
+        .await
+    }
+
+    pub async fn reject(self, reviewed_by: Resource, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        self.review(DiskResizeStatus::Rejected, None, Some(reviewed_by), conn)
+            .await
+    }
+
+    async fn review(
+        self,
+        status: DiskResizeStatus,
+        command_id: Option<CommandId>,
+        reviewed_by: Option<Resource>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        if self.status != DiskResizeStatus::Pending {
+            return Err(Error::NotPending(self.id));
+        }
+
+        let row = disk_resizes::table
+            .find(self.id)
+            .filter(disk_resizes::status.eq(DiskResizeStatus::Pending));
+        diesel::update(row)
+            .set((
+                disk_resizes::status.eq(status),
+                disk_resizes::command_id.eq(command_id),
+                disk_resizes::reviewed_by_type.eq(reviewed_by.map(|r| r.typ())),
+                disk_resizes::reviewed_by_id.eq(reviewed_by.map(|r| r.id())),
+                disk_resizes::reviewed_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Review(self.id, err))
+    }
+
+    pub fn requested_by(&self) -> Resource {
+        Resource::new(self.requested_by_type, self.requested_by_id)
+    }
+
+    pub fn reviewed_by(&self) -> Option<Resource> {
+        self.reviewed_by_type
+            .zip(self.reviewed_by_id)
+            .map(|(typ, id)| Resource::new(typ, id))
+    }
+}
+
+impl From<DiskResize> for api::DiskResize {
+    fn from(resize: DiskResize) -> Self {
+        let to_u64 = |value: i64| u64::try_from(value).unwrap_or_default();
+        let requested_by = common::Resource::from(resize.requested_by());
+        api::DiskResize {
+            resize_id: resize.id.to_string(),
+            node_id: resize.node_id.to_string(),
+            org_id: resize.org_id.to_string(),
+            old_disk_bytes: to_u64(resize.old_disk_bytes),
+            new_disk_bytes: to_u64(resize.new_disk_bytes),
+            used_disk_bytes: resize.used_disk_bytes.map(to_u64),
+            status: api::DiskResizeStatus::from(resize.status).into(),
+            command_id: resize.command_id.map(|id| id.to_string()),
+            requested_by: Some(requested_by),
+            reviewed_by: resize.reviewed_by().map(common::Resource::from),
+            created_at: Some(NanosUtc::from(resize.created_at).into()),
+            reviewed_at: resize.reviewed_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[diesel(table_name = disk_resizes)]
+pub struct NewDiskResize {
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub old_disk_bytes: i64,
+    pub new_disk_bytes: i64,
+    pub used_disk_bytes: Option<i64>,
+    pub requested_by_type: ResourceType,
+    pub requested_by_id: ResourceId,
+}
+
+impl NewDiskResize {
+    pub fn new(
+        node: &Node,
+        new_disk_bytes: i64,
+        used_disk_bytes: Option<i64>,
+        requested_by: Resource,
+    ) -> Self {
+        NewDiskResize {
+            node_id: node.id,
+            org_id: node.org_id,
+            old_disk_bytes: node.disk_bytes,
+            new_disk_bytes,
+            used_disk_bytes,
+            requested_by_type: requested_by.typ(),
+            requested_by_id: requested_by.id(),
+        }
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<DiskResize, Error> {
+        diesel::insert_into(disk_resizes::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
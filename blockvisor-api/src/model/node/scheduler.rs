@@ -1,18 +1,25 @@
 use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
 
+use crate::auth::resource::OrgId;
 use crate::grpc::common;
-use crate::model::Region;
 use crate::model::schema::sql_types;
+use crate::model::{Host, HostPoolId, Region};
 
 /// Controls how a node is placed onto an appropriate host.
 #[derive(Debug)]
 pub struct NodeScheduler {
     /// Affinity to scheduling on the most or least heavily utilized hosts.
     pub resource: Option<ResourceAffinity>,
-    /// Affinity to similar nodes on a host. Takes precedence over `resource`.
+    /// Affinity to similar nodes on a host. Weighed against `resource` by the
+    /// `HostWeights` of the deployment.
     pub similarity: Option<SimilarNodeAffinity>,
     /// The region for the node. Takes precedence over `similarity`.
     pub region: Option<Region>,
+    /// Whether the node is placed on private or public hosts.
+    pub placement: PlacementPolicy,
+    /// The private host pool for the node, which implies `Private` placement.
+    pub pool_id: Option<HostPoolId>,
 }
 
 impl NodeScheduler {
@@ -21,6 +28,67 @@ impl NodeScheduler {
             resource: Some(ResourceAffinity::LeastResources),
             similarity: None,
             region: None,
+            placement: PlacementPolicy::Any,
+            pool_id: None,
+        }
+    }
+
+    /// The placement policy after taking the host pool into account.
+    pub const fn placement(&self) -> PlacementPolicy {
+        if self.pool_id.is_some() {
+            PlacementPolicy::Private
+        } else {
+            self.placement
+        }
+    }
+
+    /// Whether a node of `org_id` may be placed on `host`.
+    pub fn allows(&self, host: &Host, org_id: OrgId) -> bool {
+        let placed = match self.placement() {
+            PlacementPolicy::Any => host.org_id.is_none() || host.org_id == Some(org_id),
+            PlacementPolicy::Private => host.org_id == Some(org_id),
+            PlacementPolicy::Public => host.org_id.is_none(),
+        };
+
+        placed
+            && self
+                .pool_id
+                .is_none_or(|pool_id| host.pool_id == Some(pool_id))
+    }
+}
+
+/// Whether a node will be placed on the private hosts of its org.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
+#[ExistingTypePath = "sql_types::EnumNodePlacement"]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementPolicy {
+    /// Place on private hosts of the org or on public hosts.
+    #[default]
+    Any,
+    /// Only place on private hosts of the org.
+    Private,
+    /// Only place on public hosts.
+    Public,
+}
+
+impl From<PlacementPolicy> for common::PlacementPolicy {
+    fn from(policy: PlacementPolicy) -> Self {
+        match policy {
+            PlacementPolicy::Any => common::PlacementPolicy::Any,
+            PlacementPolicy::Private => common::PlacementPolicy::Private,
+            PlacementPolicy::Public => common::PlacementPolicy::Public,
+        }
+    }
+}
+
+impl From<common::PlacementPolicy> for PlacementPolicy {
+    fn from(policy: common::PlacementPolicy) -> Self {
+        match policy {
+            common::PlacementPolicy::Unspecified | common::PlacementPolicy::Any => {
+                PlacementPolicy::Any
+            }
+            common::PlacementPolicy::Private => PlacementPolicy::Private,
+            common::PlacementPolicy::Public => PlacementPolicy::Public,
         }
     }
 }
@@ -0,0 +1,372 @@
+//! Monthly uptime of nodes, measured against the configured SLO target.
+//!
+//! Downtime is tracked as intervals that open when a node fails or reports
+//! itself as unhealthy, and close once it is healthy again. Planned states such
+//! as `Stopped` or `Upgrading` do not count against uptime.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{node_downtimes, node_slo, nodes, org_slo_reports};
+
+use super::{Node, NodeHealth, NodeState};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find node downtime `{0}`: {1}
+    ById(Uuid, diesel::result::Error),
+    /// Failed to close downtime of node `{0}`: {1}
+    CloseDowntime(NodeId, diesel::result::Error),
+    /// Failed to find downtimes of nodes: {0}
+    Downtimes(diesel::result::Error),
+    /// Failed to mark the {1} uptime report of org `{0}` as sent: {2}
+    MarkSent(OrgId, NaiveDate, diesel::result::Error),
+    /// Month `{0}` is out of range.
+    Month(NaiveDate),
+    /// Failed to find nodes of org `{0}`: {1}
+    Nodes(OrgId, diesel::result::Error),
+    /// Failed to open downtime of node `{0}`: {1}
+    OpenDowntime(NodeId, diesel::result::Error),
+    /// Failed to find orgs pending an uptime report: {0}
+    Pending(diesel::result::Error),
+    /// Failed to mark downtime `{0}` as pushed: {1}
+    Pushed(Uuid, diesel::result::Error),
+    /// Failed to find unpushed downtimes: {0}
+    Unpushed(diesel::result::Error),
+    /// Failed to store uptime of node `{0}`: {1}
+    Upsert(NodeId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) => Status::not_found("Downtime not found."),
+            Month(_) => Status::invalid_argument("month"),
+            ById(..) | CloseDowntime(..) | Downtimes(_) | MarkSent(..) | Nodes(..)
+            | OpenDowntime(..) | Pending(_) | Pushed(..) | Unpushed(_) | Upsert(..) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+/// An interval during which a node was down.
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = node_downtimes)]
+pub struct NodeDowntime {
+    pub id: Uuid,
+    pub node_id: NodeId,
+    pub node_state: NodeState,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub pushed_at: Option<DateTime<Utc>>,
+}
+
+impl NodeDowntime {
+    pub async fn by_id(id: Uuid, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_downtimes::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The open downtimes that no push notification was sent for yet.
+    pub async fn unpushed(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        node_downtimes::table
+            .filter(node_downtimes::ended_at.is_null())
+            .filter(node_downtimes::pushed_at.is_null())
+            .order_by(node_downtimes::started_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::Unpushed)
+    }
+
+    pub async fn pushed(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(node_downtimes::table.find(self.id))
+            .set(node_downtimes::pushed_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Pushed(self.id, err))
+    }
+
+    /// Open or close the downtime interval of a node after a state change.
+    pub async fn track(node: &Node, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let is_down = node.node_state == NodeState::Failed
+            || (node.node_state == NodeState::Running
+                && node.protocol_health == Some(NodeHealth::Unhealthy));
+
+        if is_down {
+            // at most one interval is open per node, so this keeps the oldest
+            diesel::insert_into(node_downtimes::table)
+                .values((
+                    node_downtimes::node_id.eq(node.id),
+                    node_downtimes::node_state.eq(node.node_state),
+                ))
+                .on_conflict_do_nothing()
+                .execute(conn)
+                .await
+                .map(|_| ())
+                .map_err(|err| Error::OpenDowntime(node.id, err))
+        } else {
+            let open = node_downtimes::table
+                .filter(node_downtimes::node_id.eq(node.id))
+                .filter(node_downtimes::ended_at.is_null());
+            diesel::update(open)
+                .set(node_downtimes::ended_at.eq(Utc::now()))
+                .execute(conn)
+                .await
+                .map(|_| ())
+                .map_err(|err| Error::CloseDowntime(node.id, err))
+        }
+    }
+
+    /// Find the downtimes of the nodes that overlap `start` until `end`.
+    pub async fn overlapping(
+        node_ids: &[NodeId],
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        node_downtimes::table
+            .filter(node_downtimes::node_id.eq_any(node_ids))
+            .filter(node_downtimes::started_at.lt(end))
+            .filter(
+                node_downtimes::ended_at
+                    .is_null()
+                    .or(node_downtimes::ended_at.gt(start)),
+            )
+            .get_results(conn)
+            .await
+            .map_err(Error::Downtimes)
+    }
+}
+
+/// The uptime of a node over one calendar month.
+#[derive(Debug, Insertable, Queryable, Selectable)]
+#[diesel(table_name = node_slo)]
+pub struct NodeSlo {
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub month: NaiveDate,
+    pub total_seconds: i64,
+    pub downtime_seconds: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NodeSlo {
+    /// Compute and store the uptime of each org node during `month`.
+    ///
+    /// The month is measured up until now while it is still in progress, and
+    /// each node only from its creation until its deletion.
+    pub async fn compute(
+        org_id: OrgId,
+        month: NaiveDate,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let month = month_start(month);
+        let (start, end) = month_bounds(month)?;
+        let now = Utc::now();
+        let end = end.min(now);
+
+        let org_nodes: Vec<(NodeId, DateTime<Utc>, Option<DateTime<Utc>>)> = nodes::table
+            .filter(nodes::org_id.eq(org_id))
+            .filter(nodes::created_at.lt(end))
+            .filter(nodes::deleted_at.is_null().or(nodes::deleted_at.gt(start)))
+            .select((nodes::id, nodes::created_at, nodes::deleted_at))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Nodes(org_id, err))?;
+
+        let node_ids: Vec<NodeId> = org_nodes.iter().map(|(id, ..)| *id).collect();
+        let mut downtimes: HashMap<NodeId, Vec<NodeDowntime>> = HashMap::new();
+        for downtime in NodeDowntime::overlapping(&node_ids, start, end, conn).await? {
+            downtimes
+                .entry(downtime.node_id)
+                .or_default()
+                .push(downtime);
+        }
+
+        let mut slos = Vec::with_capacity(org_nodes.len());
+        for (node_id, created_at, deleted_at) in org_nodes {
+            let from = start.max(created_at);
+            let until = deleted_at.map_or(end, |deleted| deleted.min(end));
+            let downtime_seconds = downtimes
+                .remove(&node_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|down| overlap(down.started_at, down.ended_at.unwrap_or(now), from, until))
+                .sum();
+
+            let slo = NodeSlo {
+                node_id,
+                org_id,
+                month,
+                total_seconds: (until - from).num_seconds().max(0),
+                downtime_seconds,
+                updated_at: now,
+            };
+            slos.push(slo.upsert(conn).await?);
+        }
+
+        Ok(slos)
+    }
+
+    async fn upsert(self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let node_id = self.node_id;
+        diesel::insert_into(node_slo::table)
+            .values(self)
+            .on_conflict((node_slo::node_id, node_slo::month))
+            .do_update()
+            .set((
+                node_slo::total_seconds.eq(excluded(node_slo::total_seconds)),
+                node_slo::downtime_seconds.eq(excluded(node_slo::downtime_seconds)),
+                node_slo::updated_at.eq(excluded(node_slo::updated_at)),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Upsert(node_id, err))
+    }
+
+    pub fn uptime_percent(&self) -> f64 {
+        uptime_percent(self.total_seconds, self.downtime_seconds)
+    }
+
+    /// The uptime of all nodes combined, weighted by how long each existed.
+    pub fn combined_percent(slos: &[Self]) -> Option<f64> {
+        let total: i64 = slos.iter().map(|slo| slo.total_seconds).sum();
+        let downtime: i64 = slos.iter().map(|slo| slo.downtime_seconds).sum();
+        (total > 0).then(|| uptime_percent(total, downtime))
+    }
+
+    pub fn into_api(self, target_percent: f64) -> api::NodeUptime {
+        let uptime_percent = self.uptime_percent();
+        api::NodeUptime {
+            node_id: self.node_id.to_string(),
+            uptime_percent,
+            total_seconds: u64::try_from(self.total_seconds).unwrap_or_default(),
+            downtime_seconds: u64::try_from(self.downtime_seconds).unwrap_or_default(),
+            target_met: uptime_percent >= target_percent,
+        }
+    }
+}
+
+/// Tracks which monthly uptime reports have been emailed to each org.
+pub struct OrgSloReport;
+
+impl OrgSloReport {
+    /// Orgs that had nodes during `month` but were not yet sent its report.
+    pub async fn pending(month: NaiveDate, conn: &mut Conn<'_>) -> Result<Vec<OrgId>, Error> {
+        let (start, end) = month_bounds(month)?;
+        let sent = org_slo_reports::table
+            .filter(org_slo_reports::month.eq(month))
+            .select(org_slo_reports::org_id);
+
+        nodes::table
+            .filter(nodes::created_at.lt(end))
+            .filter(nodes::deleted_at.is_null().or(nodes::deleted_at.gt(start)))
+            .filter(nodes::org_id.ne_all(sent))
+            .select(nodes::org_id)
+            .distinct()
+            .get_results(conn)
+            .await
+            .map_err(Error::Pending)
+    }
+
+    pub async fn mark_sent(
+        org_id: OrgId,
+        month: NaiveDate,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(org_slo_reports::table)
+            .values((
+                org_slo_reports::org_id.eq(org_id),
+                org_slo_reports::month.eq(month),
+            ))
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::MarkSent(org_id, month, err))
+    }
+}
+
+/// The first day of the month containing `date`.
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// The first day of the month before the month containing `date`.
+pub fn previous_month(date: NaiveDate) -> NaiveDate {
+    month_start(date)
+        .checked_sub_months(Months::new(1))
+        .unwrap_or(date)
+}
+
+/// The start of the month containing `month` and the start of the next.
+pub fn month_bounds(month: NaiveDate) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let month = month_start(month);
+    let next = month
+        .checked_add_months(Months::new(1))
+        .ok_or(Error::Month(month))?;
+
+    Ok((
+        month.and_time(NaiveTime::MIN).and_utc(),
+        next.and_time(NaiveTime::MIN).and_utc(),
+    ))
+}
+
+/// The seconds that `start` until `end` overlaps `from` until `until`.
+fn overlap(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> i64 {
+    let seconds = (end.min(until) - start.max(from)).num_seconds();
+    seconds.max(0)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn uptime_percent(total_seconds: i64, downtime_seconds: i64) -> f64 {
+    if total_seconds <= 0 {
+        100.0
+    } else {
+        (total_seconds - downtime_seconds) as f64 * 100.0 / total_seconds as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn downtime_overlaps_the_measured_period() {
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 3, day, hour, 0, 0).unwrap();
+        let (from, until) = (at(1, 0), at(31, 0));
+
+        assert_eq!(overlap(at(2, 0), at(2, 1), from, until), 3600);
+        assert_eq!(overlap(at(30, 23), at(31, 2), from, until), 3600);
+        assert_eq!(overlap(at(31, 1), at(31, 2), from, until), 0);
+        assert!((uptime_percent(1000, 1) - 99.9).abs() < f64::EPSILON);
+        assert_eq!(
+            previous_month(NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 12, 1).unwrap()
+        );
+    }
+}
@@ -55,7 +55,7 @@ impl TryFrom<common::NodeStatus> for NodeStatus {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
 #[ExistingTypePath = "sql_types::EnumNodeState"]
 pub enum NodeState {
     Starting,
@@ -65,6 +65,43 @@ pub enum NodeState {
     Upgrading,
     Deleting,
     Deleted,
+    /// Stopped for suspected abuse until an admin reviews it.
+    Suspended,
+}
+
+impl NodeState {
+    /// Whether a node in this state may move to state `to`, given its pending
+    /// `next` state.
+    ///
+    /// Deleted nodes never change state, and nodes only start deleting once a
+    /// delete is pending. A suspended node may only be deleted. A running or
+    /// stopped node only starts again after a start or restart command.
+    pub fn transition(self, to: NodeState, next: Option<NextState>) -> Transition {
+        use NodeState::*;
+
+        let deleting = next == Some(NextState::Deleting);
+        match (self, to) {
+            (from, to) if from == to => Transition::Allowed,
+            (Deleting, Deleted) => Transition::Allowed,
+            (Deleted | Deleting, _) => Transition::Invalid,
+            (_, Deleting | Deleted) if deleting => Transition::Allowed,
+            (_, Deleting | Deleted) => Transition::Invalid,
+            (Suspended, _) => Transition::Invalid,
+            (Running, Starting) | (Stopped, Starting | Running) => Transition::NeedsStart,
+            _ => Transition::Allowed,
+        }
+    }
+}
+
+/// Whether a node may move from one `NodeState` to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    /// The node may move to the new state.
+    Allowed,
+    /// The node may only move to the new state after a start or restart command.
+    NeedsStart,
+    /// The node may never move to the new state.
+    Invalid,
 }
 
 impl From<NodeState> for common::NodeState {
@@ -77,6 +114,7 @@ impl From<NodeState> for common::NodeState {
             NodeState::Upgrading => Self::Upgrading,
             NodeState::Deleting => Self::Deleting,
             NodeState::Deleted => Self::Deleted,
+            NodeState::Suspended => Self::Suspended,
         }
     }
 }
@@ -94,6 +132,7 @@ impl TryFrom<common::NodeState> for NodeState {
             common::NodeState::Upgrading => Ok(NodeState::Upgrading),
             common::NodeState::Deleting => Ok(NodeState::Deleting),
             common::NodeState::Deleted => Ok(NodeState::Deleted),
+            common::NodeState::Suspended => Ok(NodeState::Suspended),
         }
     }
 }
@@ -185,3 +224,61 @@ impl TryFrom<common::NodeHealth> for NodeHealth {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_nodes_never_change_state() {
+        for to in [NodeState::Starting, NodeState::Running, NodeState::Deleting] {
+            let transition = NodeState::Deleted.transition(to, Some(NextState::Deleting));
+            assert_eq!(transition, Transition::Invalid);
+        }
+        let transition = NodeState::Deleted.transition(NodeState::Deleted, None);
+        assert_eq!(transition, Transition::Allowed);
+    }
+
+    #[test]
+    fn deleting_requires_a_pending_delete() {
+        let transition = NodeState::Running.transition(NodeState::Deleting, None);
+        assert_eq!(transition, Transition::Invalid);
+        let transition = NodeState::Running.transition(NodeState::Deleted, None);
+        assert_eq!(transition, Transition::Invalid);
+
+        let pending = Some(NextState::Deleting);
+        let transition = NodeState::Running.transition(NodeState::Deleting, pending);
+        assert_eq!(transition, Transition::Allowed);
+        let transition = NodeState::Deleting.transition(NodeState::Deleted, None);
+        assert_eq!(transition, Transition::Allowed);
+        let transition = NodeState::Deleting.transition(NodeState::Running, None);
+        assert_eq!(transition, Transition::Invalid);
+    }
+
+    #[test]
+    fn starting_again_requires_a_start_command() {
+        let transition = NodeState::Running.transition(NodeState::Starting, None);
+        assert_eq!(transition, Transition::NeedsStart);
+        let transition = NodeState::Stopped.transition(NodeState::Running, None);
+        assert_eq!(transition, Transition::NeedsStart);
+
+        let transition = NodeState::Starting.transition(NodeState::Running, None);
+        assert_eq!(transition, Transition::Allowed);
+        let transition = NodeState::Failed.transition(NodeState::Starting, None);
+        assert_eq!(transition, Transition::Allowed);
+        let transition = NodeState::Running.transition(NodeState::Stopped, None);
+        assert_eq!(transition, Transition::Allowed);
+    }
+
+    #[test]
+    fn suspended_nodes_may_only_be_deleted() {
+        for to in [NodeState::Starting, NodeState::Running, NodeState::Stopped] {
+            let transition = NodeState::Suspended.transition(to, None);
+            assert_eq!(transition, Transition::Invalid);
+        }
+
+        let pending = Some(NextState::Deleting);
+        let transition = NodeState::Suspended.transition(NodeState::Deleting, pending);
+        assert_eq!(transition, Transition::Allowed);
+    }
+}
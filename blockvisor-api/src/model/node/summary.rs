@@ -0,0 +1,89 @@
+//! A summary of the nodes of an org.
+//!
+//! Each node is counted by its state, protocol, host and the region of its
+//! host, along with the resources and monthly cost of all its nodes.
+
+use std::collections::HashMap;
+
+use crate::auth::resource::HostId;
+use crate::model::protocol::ProtocolId;
+use crate::model::region::RegionId;
+use crate::model::sql::{Amount, Period};
+
+use super::{Node, NodeState};
+
+#[derive(Debug, Default)]
+pub struct NodeSummary {
+    pub node_count: u64,
+    pub states: HashMap<NodeState, u64>,
+    pub protocols: HashMap<ProtocolId, u64>,
+    pub hosts: HashMap<HostId, u64>,
+    pub cpu_cores: i64,
+    pub memory_bytes: i64,
+    pub disk_bytes: i64,
+    /// The monthly cost of the nodes that have one.
+    pub monthly_cost: Option<Amount>,
+}
+
+impl NodeSummary {
+    pub fn new(nodes: &[Node]) -> Self {
+        let mut summary = NodeSummary::default();
+
+        for node in nodes {
+            summary.node_count += 1;
+            *summary.states.entry(node.node_state).or_default() += 1;
+            *summary.protocols.entry(node.protocol_id).or_default() += 1;
+            *summary.hosts.entry(node.host_id).or_default() += 1;
+
+            summary.cpu_cores += node.cpu_cores;
+            summary.memory_bytes += node.memory_bytes;
+            summary.disk_bytes += node.disk_bytes;
+
+            if let Some(cost) = node.cost.filter(|cost| cost.period == Period::Monthly) {
+                summary.monthly_cost = Some(match summary.monthly_cost {
+                    Some(total) => Amount {
+                        amount: total.amount + cost.amount,
+                        ..total
+                    },
+                    None => cost,
+                });
+            }
+        }
+
+        summary
+    }
+
+    /// The number of nodes in each region, given the region of each host.
+    pub fn regions(&self, host_regions: &HashMap<HostId, RegionId>) -> HashMap<RegionId, u64> {
+        let mut regions = HashMap::new();
+        for (host_id, count) in &self.hosts {
+            if let Some(region) = host_regions.get(host_id) {
+                *regions.entry(*region).or_default() += count;
+            }
+        }
+        regions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn regions_sum_their_hosts() {
+        let [host1, host2, host3] = [(); 3].map(|()| HostId::from(Uuid::new_v4()));
+        let [region1, region2] = [(); 2].map(|()| RegionId::from(Uuid::new_v4()));
+
+        let summary = NodeSummary {
+            hosts: HashMap::from([(host1, 2), (host2, 3), (host3, 1)]),
+            ..Default::default()
+        };
+        let host_regions = HashMap::from([(host1, region1), (host2, region1), (host3, region2)]);
+
+        let regions = summary.regions(&host_regions);
+        assert_eq!(regions[&region1], 5);
+        assert_eq!(regions[&region2], 1);
+    }
+}
@@ -0,0 +1,283 @@
+//! Read-only share links of a node's status for viewers outside the org.
+//!
+//! A share link carries a secret that hashed with `token_salt` must equal the
+//! stored `token_hash`. The secret itself is only returned once on creation.
+//! Links stop working once they expire or are revoked, and views of a link are
+//! recorded as a `NodeShareAccess`, at most one per viewer IP and minute.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId, Resource, ResourceId, ResourceType};
+use crate::auth::token::api_key::{KeyHash, Salt, Secret};
+use crate::database::{Conn, WriteConn};
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::schema::{node_share_accesses, node_shares};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find accesses of node share `{0}`: {1}
+    Accesses(NodeShareId, diesel::result::Error),
+    /// Failed to find node share `{0}`: {1}
+    ById(NodeShareId, diesel::result::Error),
+    /// Failed to find shares of node `{0}`: {1}
+    ByNode(NodeId, diesel::result::Error),
+    /// Failed to create node share: {0}
+    Create(diesel::result::Error),
+    /// Failed to record access of node share `{0}`: {1}
+    CreateAccess(NodeShareId, diesel::result::Error),
+    /// Node share `{0}` has expired.
+    Expired(NodeShareId),
+    /// Node share `{0}` token does not match.
+    HashMismatch(NodeShareId),
+    /// Failed to revoke node share `{0}`: {1}
+    Revoke(NodeShareId, diesel::result::Error),
+    /// Node share `{0}` has been revoked.
+    Revoked(NodeShareId),
+    /// Failed to truncate node share access timestamp: {0}
+    Truncate(chrono::RoundingError),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Revoke(_, NotFound) => Status::not_found("Node share not found."),
+            Expired(_) | HashMismatch(_) | Revoked(_) => {
+                Status::not_found("Share link is invalid or has expired.")
+            }
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct NodeShareId(Uuid);
+
+#[derive(Debug, Queryable, Selectable)]
+pub struct NodeShare {
+    pub id: NodeShareId,
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub label: String,
+    pub token_hash: KeyHash,
+    pub token_salt: Salt,
+    pub expires_at: DateTime<Utc>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl NodeShare {
+    pub async fn by_id(id: NodeShareId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        node_shares::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The unrevoked shares of a node, including expired ones.
+    pub async fn by_node(node_id: NodeId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        node_shares::table
+            .filter(node_shares::node_id.eq(node_id))
+            .filter(node_shares::revoked_at.is_null())
+            .order_by(node_shares::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByNode(node_id, err))
+    }
+
+    /// Check that `token` is the secret of an unrevoked, unexpired share.
+    pub fn verify(&self, token: &str) -> Result<(), Error> {
+        let matches = Secret::decode(token)
+            .is_ok_and(|secret| KeyHash::from(&self.token_salt, &secret) == self.token_hash);
+
+        if !matches {
+            Err(Error::HashMismatch(self.id))
+        } else if self.revoked_at.is_some() {
+            Err(Error::Revoked(self.id))
+        } else if self.expires_at <= Utc::now() {
+            Err(Error::Expired(self.id))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn revoke(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        let active = node_shares::table
+            .find(id)
+            .filter(node_shares::revoked_at.is_null());
+
+        diesel::update(active)
+            .set(node_shares::revoked_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Revoke(id, err))
+    }
+
+    /// Record a view of this share by an external viewer.
+    ///
+    /// Repeated views from the same IP within a minute are only recorded once,
+    /// so a viewer polling the link can't flood the access log.
+    pub async fn record_access(
+        &self,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let accessed_minute = Utc::now()
+            .duration_trunc(TimeDelta::minutes(1))
+            .map_err(Error::Truncate)?;
+        let access = NewNodeShareAccess {
+            share_id: self.id,
+            ip_address,
+            user_agent,
+            accessed_minute,
+        };
+
+        diesel::insert_into(node_share_accesses::table)
+            .values(access)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::CreateAccess(self.id, err))
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<NodeShare> for api::NodeShare {
+    fn from(share: NodeShare) -> Self {
+        let created_by = common::Resource::from(share.created_by());
+        api::NodeShare {
+            node_share_id: share.id.to_string(),
+            node_id: share.node_id.to_string(),
+            org_id: share.org_id.to_string(),
+            label: share.label,
+            expires_at: Some(NanosUtc::from(share.expires_at).into()),
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(share.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = node_shares)]
+struct InsertNodeShare {
+    node_id: NodeId,
+    org_id: OrgId,
+    label: String,
+    token_hash: KeyHash,
+    token_salt: Salt,
+    expires_at: DateTime<Utc>,
+    created_by_type: ResourceType,
+    created_by_id: ResourceId,
+}
+
+#[derive(Debug)]
+pub struct NewNodeShare {
+    pub node_id: NodeId,
+    pub org_id: OrgId,
+    pub label: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_by: Resource,
+}
+
+impl NewNodeShare {
+    pub async fn create(self, write: &mut WriteConn<'_, '_>) -> Result<Created, Error> {
+        let mut rng = write.ctx.rng.lock().await;
+        let salt = Salt::generate(&mut *rng);
+        let secret = Secret::generate(&mut *rng);
+        drop(rng);
+
+        let insert = InsertNodeShare {
+            node_id: self.node_id,
+            org_id: self.org_id,
+            label: self.label,
+            token_hash: KeyHash::from(&salt, &secret),
+            token_salt: salt,
+            expires_at: self.expires_at,
+            created_by_type: self.created_by.typ(),
+            created_by_id: self.created_by.id(),
+        };
+
+        let share = diesel::insert_into(node_shares::table)
+            .values(insert)
+            .get_result(write)
+            .await
+            .map_err(Error::Create)?;
+
+        Ok(Created {
+            share,
+            token: secret.encode(),
+        })
+    }
+}
+
+/// A new `NodeShare` plus the token returned once on creation.
+pub struct Created {
+    pub share: NodeShare,
+    pub token: String,
+}
+
+#[derive(Debug, Queryable, Selectable)]
+#[diesel(table_name = node_share_accesses)]
+pub struct NodeShareAccess {
+    pub id: Uuid,
+    pub share_id: NodeShareId,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub accessed_at: DateTime<Utc>,
+    pub accessed_minute: DateTime<Utc>,
+}
+
+impl NodeShareAccess {
+    /// The most recent `limit` accesses of a share.
+    pub async fn by_share(
+        share_id: NodeShareId,
+        limit: i64,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        node_share_accesses::table
+            .filter(node_share_accesses::share_id.eq(share_id))
+            .order_by(node_share_accesses::accessed_at.desc())
+            .limit(limit)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Accesses(share_id, err))
+    }
+}
+
+impl From<NodeShareAccess> for api::NodeShareAccess {
+    fn from(access: NodeShareAccess) -> Self {
+        api::NodeShareAccess {
+            ip_address: access.ip_address,
+            user_agent: access.user_agent,
+            accessed_at: Some(NanosUtc::from(access.accessed_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = node_share_accesses)]
+struct NewNodeShareAccess {
+    share_id: NodeShareId,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    accessed_minute: DateTime<Utc>,
+}
@@ -0,0 +1,358 @@
+//! On-call schedules and the escalation policy of an org.
+//!
+//! A schedule rotates through its users, handing over every `rotation_hours`
+//! from `starts_at`. The escalation policy of an org lists schedules in the
+//! order they are paged when an `Alert` is not acknowledged within
+//! `ack_timeout_minutes` of its last notification.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::Alert;
+use super::schema::{escalation_policies, oncall_schedules};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Escalation policy timeout must be positive, not {0} minutes.
+    AckTimeout(i64),
+    /// Failed to find on-call schedule `{0}`: {1}
+    ById(OncallScheduleId, diesel::result::Error),
+    /// Failed to find on-call schedules of org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Failed to find escalation policies of orgs: {0}
+    ByOrgs(diesel::result::Error),
+    /// Failed to create on-call schedule: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete on-call schedule `{0}`: {1}
+    Delete(OncallScheduleId, diesel::result::Error),
+    /// On-call schedules need at least one user.
+    NoUsers,
+    /// Failed to find escalation policy of org `{0}`: {1}
+    Policy(OrgId, diesel::result::Error),
+    /// Rotation must be a positive number of hours, not {0}.
+    RotationHours(i64),
+    /// Failed to find on-call schedules: {0}
+    Schedules(diesel::result::Error),
+    /// Failed to set escalation policy of org `{0}`: {1}
+    SetPolicy(OrgId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            AckTimeout(_) => Status::invalid_argument("ack_timeout_minutes"),
+            ById(_, NotFound) | Delete(_, NotFound) => {
+                Status::not_found("On-call schedule not found.")
+            }
+            NoUsers => Status::invalid_argument("user_ids"),
+            Policy(_, NotFound) => Status::not_found("Escalation policy not found."),
+            RotationHours(_) => Status::invalid_argument("rotation_hours"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct OncallScheduleId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct OncallSchedule {
+    pub id: OncallScheduleId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub user_ids: Vec<Option<UserId>>,
+    pub rotation_hours: i32,
+    pub starts_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OncallSchedule {
+    pub async fn by_id(id: OncallScheduleId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        oncall_schedules::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_ids(
+        ids: &HashSet<OncallScheduleId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        oncall_schedules::table
+            .filter(oncall_schedules::id.eq_any(ids))
+            .get_results(conn)
+            .await
+            .map_err(Error::Schedules)
+    }
+
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        oncall_schedules::table
+            .filter(oncall_schedules::org_id.eq(org_id))
+            .order_by(oncall_schedules::name.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let id = self.id;
+        diesel::delete(oncall_schedules::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+
+    /// The user on call at `at`, or `None` before the rotation starts.
+    pub fn on_call(&self, at: DateTime<Utc>) -> Option<UserId> {
+        let users: Vec<UserId> = self.user_ids.iter().flatten().copied().collect();
+        if users.is_empty() || self.rotation_hours <= 0 || at < self.starts_at {
+            return None;
+        }
+
+        let rotations = (at - self.starts_at).num_hours() / i64::from(self.rotation_hours);
+        let index = usize::try_from(rotations).ok()? % users.len();
+        Some(users[index])
+    }
+}
+
+impl From<OncallSchedule> for api::OncallSchedule {
+    fn from(schedule: OncallSchedule) -> Self {
+        let on_call = schedule.on_call(Utc::now());
+        api::OncallSchedule {
+            schedule_id: schedule.id.to_string(),
+            org_id: schedule.org_id.to_string(),
+            name: schedule.name,
+            user_ids: schedule
+                .user_ids
+                .iter()
+                .flatten()
+                .map(ToString::to_string)
+                .collect(),
+            rotation_hours: u32::try_from(schedule.rotation_hours).unwrap_or_default(),
+            starts_at: Some(NanosUtc::from(schedule.starts_at).into()),
+            on_call_user_id: on_call.map(|id| id.to_string()),
+            created_at: Some(NanosUtc::from(schedule.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = oncall_schedules)]
+pub struct NewOncallSchedule {
+    pub org_id: OrgId,
+    pub name: String,
+    pub user_ids: Vec<Option<UserId>>,
+    pub rotation_hours: i32,
+    pub starts_at: DateTime<Utc>,
+}
+
+impl NewOncallSchedule {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<OncallSchedule, Error> {
+        if self.user_ids.iter().flatten().next().is_none() {
+            return Err(Error::NoUsers);
+        }
+        if self.rotation_hours <= 0 {
+            return Err(Error::RotationHours(self.rotation_hours.into()));
+        }
+
+        diesel::insert_into(oncall_schedules::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = escalation_policies)]
+pub struct EscalationPolicy {
+    pub org_id: OrgId,
+    pub schedule_ids: Vec<Option<OncallScheduleId>>,
+    pub ack_timeout_minutes: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl EscalationPolicy {
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        escalation_policies::table
+            .find(org_id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Policy(org_id, err))
+    }
+
+    pub async fn by_orgs(
+        org_ids: &HashSet<OrgId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        escalation_policies::table
+            .filter(escalation_policies::org_id.eq_any(org_ids))
+            .get_results(conn)
+            .await
+            .map_err(Error::ByOrgs)
+    }
+
+    /// Create or replace the escalation policy of an org.
+    pub async fn set(
+        org_id: OrgId,
+        schedule_ids: Vec<OncallScheduleId>,
+        ack_timeout_minutes: i32,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        if ack_timeout_minutes <= 0 {
+            return Err(Error::AckTimeout(ack_timeout_minutes.into()));
+        }
+
+        let policy = EscalationPolicy {
+            org_id,
+            schedule_ids: schedule_ids.into_iter().map(Some).collect(),
+            ack_timeout_minutes,
+            updated_at: Utc::now(),
+        };
+
+        diesel::insert_into(escalation_policies::table)
+            .values(&policy)
+            .on_conflict(escalation_policies::org_id)
+            .do_update()
+            .set(&policy)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::SetPolicy(org_id, err))
+    }
+
+    /// The schedule to page next for `alert`, if it is due for escalation at
+    /// `now`.
+    ///
+    /// Each level of the policy pages one schedule, so an alert stops
+    /// escalating once every schedule has been paged.
+    pub fn next_schedule(&self, alert: &Alert, now: DateTime<Utc>) -> Option<OncallScheduleId> {
+        if alert.acknowledged_at.is_some() {
+            return None;
+        }
+
+        let timeout = TimeDelta::minutes(self.ack_timeout_minutes.into());
+        if alert.notified_at + timeout > now {
+            return None;
+        }
+
+        let level = usize::try_from(alert.escalation_level).ok()?;
+        self.schedule_ids.iter().flatten().nth(level).copied()
+    }
+}
+
+impl From<EscalationPolicy> for api::EscalationPolicy {
+    fn from(policy: EscalationPolicy) -> Self {
+        api::EscalationPolicy {
+            org_id: policy.org_id.to_string(),
+            schedule_ids: policy
+                .schedule_ids
+                .iter()
+                .flatten()
+                .map(ToString::to_string)
+                .collect(),
+            ack_timeout_minutes: u32::try_from(policy.ack_timeout_minutes).unwrap_or_default(),
+            updated_at: Some(NanosUtc::from(policy.updated_at).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::alert::AlertKind;
+
+    use super::*;
+
+    fn schedule(users: &[UserId], rotation_hours: i32, starts_at: DateTime<Utc>) -> OncallSchedule {
+        OncallSchedule {
+            id: Uuid::new_v4().into(),
+            org_id: Uuid::new_v4().into(),
+            name: "primary".to_string(),
+            user_ids: users.iter().copied().map(Some).collect(),
+            rotation_hours,
+            starts_at,
+            created_at: starts_at,
+        }
+    }
+
+    #[test]
+    fn rotates_through_users() {
+        let users: Vec<UserId> = (0..3).map(|_| Uuid::new_v4().into()).collect();
+        let start = Utc::now();
+        let schedule = schedule(&users, 24, start);
+
+        assert_eq!(schedule.on_call(start - TimeDelta::hours(1)), None);
+        assert_eq!(schedule.on_call(start), Some(users[0]));
+        assert_eq!(
+            schedule.on_call(start + TimeDelta::hours(23)),
+            Some(users[0])
+        );
+        assert_eq!(
+            schedule.on_call(start + TimeDelta::hours(24)),
+            Some(users[1])
+        );
+        assert_eq!(
+            schedule.on_call(start + TimeDelta::hours(50)),
+            Some(users[2])
+        );
+        assert_eq!(
+            schedule.on_call(start + TimeDelta::hours(72)),
+            Some(users[0])
+        );
+    }
+
+    #[test]
+    fn escalates_unacknowledged_alerts() {
+        let now = Utc::now();
+        let schedules: Vec<OncallScheduleId> = (0..2).map(|_| Uuid::new_v4().into()).collect();
+        let policy = EscalationPolicy {
+            org_id: Uuid::new_v4().into(),
+            schedule_ids: schedules.iter().copied().map(Some).collect(),
+            ack_timeout_minutes: 15,
+            updated_at: now,
+        };
+
+        let mut alert = Alert {
+            id: Uuid::new_v4().into(),
+            org_id: policy.org_id,
+            node_id: None,
+            kind: AlertKind::CertificateExpiring,
+            message: "expiring".to_string(),
+            escalation_level: 0,
+            notified_at: now - TimeDelta::minutes(10),
+            acknowledged_at: None,
+            acknowledged_by: None,
+            created_at: now - TimeDelta::minutes(10),
+        };
+        assert_eq!(policy.next_schedule(&alert, now), None);
+
+        alert.notified_at = now - TimeDelta::minutes(15);
+        assert_eq!(policy.next_schedule(&alert, now), Some(schedules[0]));
+
+        alert.escalation_level = 1;
+        assert_eq!(policy.next_schedule(&alert, now), Some(schedules[1]));
+
+        alert.escalation_level = 2;
+        assert_eq!(policy.next_schedule(&alert, now), None);
+
+        alert.escalation_level = 0;
+        alert.acknowledged_at = Some(now);
+        assert_eq!(policy.next_schedule(&alert, now), None);
+    }
+}
@@ -8,25 +8,38 @@ use diesel::prelude::*;
 use diesel::result::DatabaseErrorKind::UniqueViolation;
 use diesel::result::Error::{DatabaseError, NotFound};
 use diesel::sql_types::Bool;
-use diesel_async::RunQueryDsl;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 use displaydoc::Display;
 use thiserror::Error;
 
 use crate::auth::rbac::OrgRole;
 use crate::auth::rbac::Role;
-use crate::auth::resource::{OrgId, UserId};
+use crate::auth::resource::{OrgId, ResourceId, ResourceType, UserId};
 use crate::database::Conn;
+use crate::encryption::Keyring;
 use crate::grpc::Status;
-use crate::model::sql;
+use crate::model::sql::{self, Encrypted};
 use crate::stripe::api::customer::CustomerId;
+use crate::stripe::api::subscription::SubscriptionItemId;
 use crate::util::{SearchOperator, SortOrder};
 
 use super::address::AddressId;
+use super::agent_upgrade::AgentUpgradePolicy;
+use super::node::NodeNameCharset;
+use super::org_suspension::SuspensionReason;
+use super::partner::PartnerId;
+use super::plan::PlanTier;
 use super::rbac::RbacUser;
-use super::schema::{orgs, user_roles};
+use super::schema::{addresses, api_keys, invitations, orgs, tokens, user_roles};
+use super::team::Team;
 use super::{Paginate, Token};
 
 const PERSONAL_ORG_NAME: &str = "Personal";
+const PURGED_ORG_NAME: &str = "Deleted";
+
+/// How long a deleted org may still be restored before its data is purged.
+pub const DELETE_GRACE_PERIOD: chrono::TimeDelta = chrono::TimeDelta::days(30);
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -40,6 +53,16 @@ pub enum Error {
     Create(diesel::result::Error),
     /// Failed to delete org `{0}`: {1}
     Delete(OrgId, diesel::result::Error),
+    /// Org encryption: {0}
+    Encryption(crate::encryption::Error),
+    /// Failed to find deleted org by id `{0}`: {1}
+    FindDeletedById(OrgId, diesel::result::Error),
+    /// Failed to find orgs with a stripe customer: {0}
+    FindCustomers(diesel::result::Error),
+    /// Failed to find org by stripe customer: {0}
+    FindByCustomer(diesel::result::Error),
+    /// Failed to find orgs due for purging: {0}
+    FindExpired(diesel::result::Error),
     /// Failed to find org by id `{0}`: {1}
     FindById(OrgId, diesel::result::Error),
     /// Failed to find org by ids `{0:?}`: {1}
@@ -52,8 +75,14 @@ pub enum Error {
     HostCounts(diesel::result::Error),
     /// Failed to find org memberships for user `{0}`: {1}
     Memberships(UserId, diesel::result::Error),
+    /// Org `{0}` is not deleted.
+    NotDeleted(OrgId),
     /// Org pagination: {0}
     Paginate(#[from] crate::model::paginate::Error),
+    /// Failed to purge org `{0}`: {1}
+    Purge(OrgId, diesel::result::Error),
+    /// Org `{0}` has already been purged.
+    Purged(OrgId),
     /// Org model RBAC error: {0}
     Rbac(#[from] crate::model::rbac::Error),
     /// Failed to decrement host count for org `{0}`: {1}
@@ -62,8 +91,14 @@ pub enum Error {
     RemoveMember(OrgId, diesel::result::Error),
     /// Failed to decrement node count for org `{0}`: {1}
     RemoveNode(OrgId, diesel::result::Error),
+    /// Failed to restore org `{0}`: {1}
+    Restore(OrgId, diesel::result::Error),
     /// Failed update customer_id for org: {0}
     SetCustomerId(diesel::result::Error),
+    /// Failed to set the plan of org `{0}`: {1}
+    SetPlan(OrgId, diesel::result::Error),
+    /// Org model team error: {0}
+    Team(#[from] crate::model::team::Error),
     /// Org model token error: {0}
     Token(#[from] crate::model::token::Error),
     /// Failed to update org: {0}
@@ -75,14 +110,23 @@ impl From<Error> for Status {
         use Error::*;
         match err {
             Create(DatabaseError(UniqueViolation, _)) => {
-                Status::already_exists("Org already exists.")
+                Status::already_exists("Org already exists.").with_reason("ORG_EXISTS")
             }
             Delete(_, NotFound)
             | FindById(_, NotFound)
             | FindByIds(_, NotFound)
-            | FindPersonal(_, NotFound) => Status::not_found("Org not found."),
+            | FindDeletedById(_, NotFound)
+            | FindPersonal(_, NotFound) => {
+                Status::not_found("Org not found.").with_reason("ORG_NOT_FOUND")
+            }
+            NotDeleted(_) => {
+                Status::failed_precondition("Org is not deleted.").with_reason("ORG_NOT_DELETED")
+            }
+            Purged(_) => Status::failed_precondition("Org grace period has expired.")
+                .with_reason("ORG_PURGED"),
             Paginate(err) => err.into(),
             Rbac(err) => err.into(),
+            Team(err) => err.into(),
             Token(err) => err.into(),
             _ => Status::internal("Internal error."),
         }
@@ -100,8 +144,48 @@ pub struct Org {
     pub host_count: i32,
     pub node_count: i32,
     pub member_count: i32,
-    pub stripe_customer_id: Option<CustomerId>,
+    /// Encrypted at rest, see `crate::encryption`.
+    pub stripe_customer_id: Option<Encrypted<CustomerId>>,
     pub address_id: Option<AddressId>,
+    pub purged_at: Option<DateTime<Utc>>,
+    pub agent_upgrade_policy: AgentUpgradePolicy,
+    /// The maximum total disk of all nodes of the org, if limited.
+    pub disk_quota_bytes: Option<i64>,
+    /// Node disk resizes up to this size are approved without review.
+    pub disk_auto_approve_bytes: Option<i64>,
+    pub plan_tier: PlanTier,
+    /// The stripe subscription item billing a paid plan.
+    pub stripe_plan_item_id: Option<SubscriptionItemId>,
+    /// When the trial of the org ends, if it is on one.
+    pub trial_ends_at: Option<DateTime<Utc>>,
+    /// The most live nodes the org may have during its trial.
+    pub trial_max_nodes: Option<i64>,
+    pub trial_reminded_at: Option<DateTime<Utc>>,
+    /// When the org was hibernated after its trial ended.
+    pub hibernated_at: Option<DateTime<Utc>>,
+    /// The partner that referred the org, if any.
+    pub partner_id: Option<PartnerId>,
+    pub referred_at: Option<DateTime<Utc>>,
+    /// When the org was suspended by an admin, if it is suspended.
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<SuspensionReason>,
+    /// The message shown to members of a suspended org.
+    pub suspension_banner: Option<String>,
+    /// The most characters in the display name of a node, if limited.
+    pub node_display_name_max_len: Option<i32>,
+    /// The most characters in the note of a node, if limited.
+    pub node_note_max_len: Option<i32>,
+    /// Whether the live nodes of the org must have distinct display names.
+    pub node_display_name_unique: bool,
+    pub node_display_name_charset: NodeNameCharset,
+    /// Whether deleting a node needs the approval of a second member.
+    pub require_approval_node_delete: bool,
+    /// Whether upgrading several nodes at once needs a second approval.
+    pub require_approval_node_upgrade: bool,
+    /// Whether transferring the org needs the approval of a second member.
+    pub require_approval_org_transfer: bool,
+    /// A keyed hash of `stripe_customer_id` to look the org up by.
+    pub stripe_customer_index: Option<String>,
 }
 
 impl Org {
@@ -114,6 +198,15 @@ impl Org {
             .map_err(|err| Error::FindById(id, err))
     }
 
+    /// Find an org by id, including an org that has been deleted.
+    pub async fn deleted_by_id(id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        orgs::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::FindDeletedById(id, err))
+    }
+
     pub async fn by_ids(org_ids: &HashSet<OrgId>, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
         orgs::table
             .filter(orgs::id.eq_any(org_ids))
@@ -135,18 +228,52 @@ impl Org {
             .map_err(|err| Error::FindPersonal(user_id, err))
     }
 
+    /// The opened `stripe_customer_id` of the org.
+    pub fn customer_id(&self, keyring: &Keyring) -> Result<Option<CustomerId>, Error> {
+        self.stripe_customer_id
+            .as_ref()
+            .map(|customer_id| customer_id.open(keyring))
+            .transpose()
+            .map_err(Error::Encryption)
+    }
+
     pub async fn set_customer_id(
         self,
         customer_id: &str,
+        keyring: &Keyring,
         conn: &mut Conn<'_>,
     ) -> Result<Org, Error> {
+        let index = keyring.blind_index(customer_id);
+        let customer_id = CustomerId::from(customer_id.to_string());
+        let customer_id = Encrypted::seal(&customer_id, keyring).map_err(Error::Encryption)?;
         diesel::update(orgs::table.filter(orgs::id.eq(self.id)))
-            .set(orgs::stripe_customer_id.eq(customer_id))
+            .set((
+                orgs::stripe_customer_id.eq(customer_id),
+                orgs::stripe_customer_index.eq(index),
+            ))
             .get_result(conn)
             .await
             .map_err(Error::SetCustomerId)
     }
 
+    /// Move the org to the plan of `tier`, billed by `item_id` if it is paid.
+    pub async fn set_plan(
+        &self,
+        tier: PlanTier,
+        item_id: Option<&SubscriptionItemId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Org, Error> {
+        diesel::update(orgs::table.find(self.id))
+            .set((
+                orgs::plan_tier.eq(tier),
+                orgs::stripe_plan_item_id.eq(item_id.cloned()),
+                orgs::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::SetPlan(self.id, err))
+    }
+
     pub async fn add_user(
         user_id: UserId,
         org_id: OrgId,
@@ -187,9 +314,42 @@ impl Org {
     ) -> Result<Self, Error> {
         Token::delete_host_provision(user_id, org_id, conn).await?;
         RbacUser::unlink_role(user_id, org_id, None::<Role>, conn).await?;
+        Team::remove_user(user_id, org_id, conn).await?;
         Org::remove_member(org_id, conn).await
     }
 
+    /// Moves the `Owner` role from `old_owner_id` to `new_owner_id`.
+    ///
+    /// The previous owner keeps their admin and member roles.
+    pub async fn transfer_owner(
+        org_id: OrgId,
+        old_owner_id: UserId,
+        new_owner_id: UserId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let roles = RbacUser::org_roles(new_owner_id, org_id, true, conn).await?;
+        for role in [OrgRole::Owner, OrgRole::Admin] {
+            if !roles.contains(&Role::from(role)) {
+                RbacUser::link_role(new_owner_id, org_id, role, conn).await?;
+            }
+        }
+
+        RbacUser::unlink_role(old_owner_id, org_id, Some(OrgRole::Owner), conn).await?;
+        Ok(())
+    }
+
+    /// Find all orgs that a user has a role in, including deleted orgs.
+    pub async fn memberships(user_id: UserId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        orgs::table
+            .inner_join(user_roles::table)
+            .filter(user_roles::user_id.eq(user_id))
+            .select(Org::as_select())
+            .distinct()
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Memberships(user_id, err))
+    }
+
     /// Marks the the given organization as deleted
     pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
         let org_id = self.id;
@@ -205,6 +365,175 @@ impl Org {
             .map_err(|err| Error::Delete(org_id, err))
     }
 
+    /// Returns the time after which this deleted org will be purged.
+    pub fn purge_after(&self) -> Option<DateTime<Utc>> {
+        self.deleted_at
+            .map(|deleted_at| deleted_at + DELETE_GRACE_PERIOD)
+    }
+
+    /// Undo the deletion of an org that is still within its grace period.
+    pub async fn restore(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let org_id = self.id;
+        match self.purge_after() {
+            None => return Err(Error::NotDeleted(org_id)),
+            Some(_) if self.purged_at.is_some() => return Err(Error::Purged(org_id)),
+            Some(purge_after) if purge_after <= Utc::now() => return Err(Error::Purged(org_id)),
+            Some(_) => (),
+        }
+
+        diesel::update(orgs::table.find(org_id))
+            .set((
+                orgs::deleted_at.eq(None::<DateTime<Utc>>),
+                orgs::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Restore(org_id, err))
+    }
+
+    /// Find all orgs that have a stripe customer.
+    pub async fn customers(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        orgs::table
+            .filter(orgs::stripe_customer_id.is_not_null())
+            .get_results(conn)
+            .await
+            .map_err(Error::FindCustomers)
+    }
+
+    /// Find the org of a stripe customer.
+    ///
+    /// Customer ids are sealed with a random nonce so they are looked up by
+    /// their `stripe_customer_index` instead. Orgs that the `encrypt-orgs`
+    /// backfill hasn't indexed yet are compared once opened.
+    pub async fn by_customer_id(
+        customer_id: &str,
+        keyring: &Keyring,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Self>, Error> {
+        let index = keyring.blind_index(customer_id);
+        let org = orgs::table
+            .filter(orgs::stripe_customer_index.eq(index))
+            .first(conn)
+            .await
+            .optional()
+            .map_err(Error::FindByCustomer)?;
+        if org.is_some() {
+            return Ok(org);
+        }
+
+        let unindexed: Vec<Self> = orgs::table
+            .filter(orgs::stripe_customer_id.is_not_null())
+            .filter(orgs::stripe_customer_index.is_null())
+            .get_results(conn)
+            .await
+            .map_err(Error::FindByCustomer)?;
+        for org in unindexed {
+            if org
+                .customer_id(keyring)?
+                .is_some_and(|id| id.as_str() == customer_id)
+            {
+                return Ok(Some(org));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Find the ids of deleted orgs whose grace period has expired.
+    pub async fn expired(conn: &mut Conn<'_>) -> Result<Vec<OrgId>, Error> {
+        orgs::table
+            .filter(orgs::deleted_at.lt(Utc::now() - DELETE_GRACE_PERIOD))
+            .filter(orgs::purged_at.is_null())
+            .select(orgs::id)
+            .get_results(conn)
+            .await
+            .map_err(Error::FindExpired)
+    }
+
+    /// Remove the data of a deleted org.
+    ///
+    /// The org row itself is kept (with its identifying fields cleared) so that
+    /// historical records such as nodes and commands remain valid. Each step
+    /// runs in one transaction that sets `purged_at` last, so a failed purge
+    /// leaves the org untouched to be retried.
+    pub async fn purge(org_id: OrgId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let user_ids = conn
+            .transaction(|conn| {
+                async move {
+                    let address_id: Option<AddressId> = orgs::table
+                        .find(org_id)
+                        .filter(orgs::purged_at.is_null())
+                        .select(orgs::address_id)
+                        .for_update()
+                        .get_result(conn)
+                        .await?;
+
+                    let user_ids: Vec<UserId> =
+                        diesel::delete(user_roles::table.filter(user_roles::org_id.eq(org_id)))
+                            .returning(user_roles::user_id)
+                            .get_results(conn)
+                            .await?;
+                    diesel::delete(invitations::table.filter(invitations::org_id.eq(org_id)))
+                        .execute(conn)
+                        .await?;
+                    diesel::delete(tokens::table.filter(tokens::org_id.eq(org_id)))
+                        .execute(conn)
+                        .await?;
+                    let org_keys = api_keys::table
+                        .filter(api_keys::resource.eq(ResourceType::Org))
+                        .filter(api_keys::resource_id.eq(ResourceId::from(org_id)));
+                    diesel::delete(org_keys).execute(conn).await?;
+
+                    diesel::update(orgs::table.find(org_id))
+                        .set((
+                            orgs::name.eq(PURGED_ORG_NAME),
+                            orgs::stripe_customer_id.eq(None::<String>),
+                            orgs::stripe_customer_index.eq(None::<String>),
+                            orgs::address_id.eq(None::<AddressId>),
+                            orgs::member_count.eq(0),
+                        ))
+                        .execute(conn)
+                        .await?;
+                    if let Some(address_id) = address_id {
+                        diesel::delete(addresses::table.find(address_id))
+                            .execute(conn)
+                            .await?;
+                    }
+
+                    diesel::update(orgs::table.find(org_id))
+                        .set(orgs::purged_at.eq(Utc::now()))
+                        .execute(conn)
+                        .await?;
+
+                    Ok(user_ids)
+                }
+                .scope_boxed()
+            })
+            .await
+            .map_err(|err| Error::Purge(org_id, err))?;
+
+        for user_id in user_ids {
+            RbacUser::invalidate_roles(user_id, org_id, conn).await;
+        }
+
+        Ok(())
+    }
+
+    /// Marks the personal org of a deleted user as deleted.
+    pub async fn delete_personal(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let org_id = self.id;
+        let to_delete = orgs::table
+            .filter(orgs::id.eq(org_id))
+            .filter(orgs::is_personal);
+
+        diesel::update(to_delete)
+            .set(orgs::deleted_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(org_id, err))
+    }
+
     pub async fn add_host(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
         diesel::update(orgs::table.filter(orgs::id.eq(org_id)))
             .set((
@@ -444,6 +773,16 @@ pub struct UpdateOrg<'a> {
     pub id: OrgId,
     pub name: Option<&'a str>,
     pub address_id: Option<AddressId>,
+    pub agent_upgrade_policy: Option<AgentUpgradePolicy>,
+    pub disk_quota_bytes: Option<Option<i64>>,
+    pub disk_auto_approve_bytes: Option<Option<i64>>,
+    pub node_display_name_max_len: Option<Option<i32>>,
+    pub node_note_max_len: Option<Option<i32>>,
+    pub node_display_name_unique: Option<bool>,
+    pub node_display_name_charset: Option<NodeNameCharset>,
+    pub require_approval_node_delete: Option<bool>,
+    pub require_approval_node_upgrade: Option<bool>,
+    pub require_approval_org_transfer: Option<bool>,
 }
 
 impl UpdateOrg<'_> {
@@ -0,0 +1,276 @@
+//! Custom branding of the notification emails and public status pages of an org.
+//!
+//! Branding is only applied while the plan of the org includes it, so an org
+//! that moves to a smaller plan keeps its branding for when it moves back. The
+//! logo is kept in the branding bucket of the store, and served publicly by a
+//! redirect to a presigned url.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, Into};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use url::Url;
+
+use crate::auth::resource::OrgId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::{org_brandings, orgs, plans};
+
+/// The largest logo that may be uploaded.
+pub const MAX_LOGO_BYTES: usize = 256 * 1024;
+/// The longest sender name shown in the `From` of an email.
+const MAX_SENDER_NAME_LEN: usize = 64;
+/// Characters that could break out of an email header or html attribute.
+const SENDER_NAME_DENIED: &str = "<>\"@\\";
+
+const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+const JPEG_MAGIC: &[u8] = b"\xff\xd8\xff";
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find branding of org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Color `{0}` is not of the form `#rrggbb`.
+    Color(String),
+    /// Logo must not be empty.
+    LogoEmpty,
+    /// Logo is not a PNG or JPEG image.
+    LogoFormat,
+    /// Logo of {0} bytes is larger than the maximum of {MAX_LOGO_BYTES}.
+    LogoSize(usize),
+    /// Sender name `{0}` contains invalid characters.
+    SenderNameChars(String),
+    /// Sender name must be between 1 and {MAX_SENDER_NAME_LEN} characters.
+    SenderNameLen,
+    /// Failed to update branding of org `{0}`: {1}
+    Upsert(OrgId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Color(_) => Status::invalid_argument("color"),
+            LogoEmpty | LogoFormat | LogoSize(_) => Status::invalid_argument("logo"),
+            SenderNameChars(_) | SenderNameLen => Status::invalid_argument("sender_name"),
+            ByOrg(..) | Upsert(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// A brand color of the form `#rrggbb`.
+#[derive(Clone, Debug, Display, PartialEq, Eq, DieselNewType, Deref, Into)]
+pub struct HexColor(String);
+
+impl HexColor {
+    pub fn new(color: &str) -> Result<Self, Error> {
+        let color = color.trim().to_lowercase();
+        let valid = color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+
+        if valid {
+            Ok(HexColor(color))
+        } else {
+            Err(Error::Color(color))
+        }
+    }
+}
+
+/// The name that branded emails are sent from.
+#[derive(Clone, Debug, Display, PartialEq, Eq, DieselNewType, Deref, Into)]
+pub struct SenderName(String);
+
+impl SenderName {
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let name = name.trim();
+        let len = name.chars().count();
+        if len == 0 || len > MAX_SENDER_NAME_LEN {
+            Err(Error::SenderNameLen)
+        } else if name
+            .chars()
+            .any(|c| c.is_control() || SENDER_NAME_DENIED.contains(c))
+        {
+            Err(Error::SenderNameChars(name.to_string()))
+        } else {
+            Ok(SenderName(name.to_string()))
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogoFormat {
+    Png,
+    Jpeg,
+}
+
+impl LogoFormat {
+    /// Detect the format from the leading bytes, ignoring any claimed type.
+    fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(PNG_MAGIC) {
+            Some(LogoFormat::Png)
+        } else if data.starts_with(JPEG_MAGIC) {
+            Some(LogoFormat::Jpeg)
+        } else {
+            None
+        }
+    }
+
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            LogoFormat::Png => "image/png",
+            LogoFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            LogoFormat::Png => "png",
+            LogoFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// An uploaded logo that was checked to be a small PNG or JPEG image.
+///
+/// SVG is not accepted since it may contain scripts.
+#[derive(Debug)]
+pub struct Logo {
+    pub data: Vec<u8>,
+    pub format: LogoFormat,
+}
+
+impl Logo {
+    pub fn new(data: Vec<u8>) -> Result<Self, Error> {
+        if data.is_empty() {
+            return Err(Error::LogoEmpty);
+        } else if data.len() > MAX_LOGO_BYTES {
+            return Err(Error::LogoSize(data.len()));
+        }
+
+        let format = LogoFormat::detect(&data).ok_or(Error::LogoFormat)?;
+        Ok(Logo { data, format })
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = org_brandings, treat_none_as_null = true)]
+pub struct OrgBranding {
+    pub org_id: OrgId,
+    pub sender_name: Option<SenderName>,
+    pub primary_color: Option<HexColor>,
+    pub accent_color: Option<HexColor>,
+    /// The store key of the uploaded logo.
+    pub logo_key: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OrgBranding {
+    /// The stored branding of an org, whether or not its plan includes it.
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        org_brandings::table
+            .find(org_id)
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    /// The branding of an org, if its current plan includes custom branding.
+    pub async fn active(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        org_brandings::table
+            .inner_join(orgs::table.inner_join(plans::table))
+            .filter(org_brandings::org_id.eq(org_id))
+            .filter(plans::custom_branding)
+            .select(OrgBranding::as_select())
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    /// The empty branding of an org, before anything was set.
+    pub fn empty(org_id: OrgId) -> Self {
+        OrgBranding {
+            org_id,
+            sender_name: None,
+            primary_color: None,
+            accent_color: None,
+            logo_key: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub async fn upsert(self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let org_id = self.org_id;
+        diesel::insert_into(org_brandings::table)
+            .values(&self)
+            .on_conflict(org_brandings::org_id)
+            .do_update()
+            .set(&self)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Upsert(org_id, err))
+    }
+
+    /// The public url of the logo, which redirects to the stored image.
+    pub fn logo_url(&self, api_url: &Url) -> Option<String> {
+        self.logo_key.as_ref().map(|_| {
+            let base = api_url.as_str().trim_end_matches('/');
+            format!("{base}/branding/{}/logo", self.org_id)
+        })
+    }
+
+    pub fn into_api(self, api_url: &Url) -> api::OrgBranding {
+        api::OrgBranding {
+            org_id: self.org_id.to_string(),
+            logo_url: self.logo_url(api_url),
+            sender_name: self.sender_name.map(Into::into),
+            primary_color: self.primary_color.map(Into::into),
+            accent_color: self.accent_color.map(Into::into),
+            updated_at: Some(NanosUtc::from(self.updated_at).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_are_validated() {
+        assert_eq!(*HexColor::new(" #A1b2C3 ").unwrap(), "#a1b2c3");
+        assert!(HexColor::new("a1b2c3").is_err());
+        assert!(HexColor::new("#a1b2c").is_err());
+        assert!(HexColor::new("#a1b2cg").is_err());
+        assert!(HexColor::new("red").is_err());
+    }
+
+    #[test]
+    fn sender_names_are_validated() {
+        assert_eq!(*SenderName::new(" Acme Nodes ").unwrap(), "Acme Nodes");
+        assert!(SenderName::new("  ").is_err());
+        assert!(SenderName::new(&"a".repeat(MAX_SENDER_NAME_LEN + 1)).is_err());
+        assert!(SenderName::new("Acme <support@acme.io>").is_err());
+        assert!(SenderName::new("Acme\r\nBcc: someone").is_err());
+    }
+
+    #[test]
+    fn logos_must_be_small_images() {
+        let png = [PNG_MAGIC, b"rest"].concat();
+        assert_eq!(Logo::new(png).unwrap().format, LogoFormat::Png);
+        let jpeg = [JPEG_MAGIC, b"rest"].concat();
+        assert_eq!(Logo::new(jpeg).unwrap().format, LogoFormat::Jpeg);
+
+        let svg = b"<svg><script>alert(1)</script></svg>".to_vec();
+        assert!(matches!(Logo::new(svg), Err(Error::LogoFormat)));
+        assert!(matches!(Logo::new(vec![]), Err(Error::LogoEmpty)));
+        let large = [PNG_MAGIC, &vec![0; MAX_LOGO_BYTES]].concat();
+        assert!(matches!(Logo::new(large), Err(Error::LogoSize(_))));
+    }
+}
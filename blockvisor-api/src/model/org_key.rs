@@ -0,0 +1,529 @@
+//! SSH and WireGuard public keys that an org grants operator access with.
+//!
+//! The active keys of an org are sent as a whole to each of its private hosts
+//! in a `HostKeys` command whenever they change, so a host replaces its
+//! authorized keys rather than applying a diff. Each `OrgKeyHost` tracks the
+//! last command that carried a key to a host, through to success or failure.
+//!
+//! A rotated key is revoked and points at its replacement through
+//! `replaces_id`. The host rows of a revoked key remain until a host confirms
+//! that it has removed the key.
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{HostId, OrgId, Resource, ResourceId, ResourceType};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::command::{Command, CommandId, CommandType, NewCommand};
+use super::schema::{hosts, org_key_hosts, org_keys, sql_types};
+
+/// The longest name of a key.
+const MAX_NAME_LEN: usize = 64;
+/// The length of a decoded WireGuard public key.
+const WIREGUARD_KEY_LEN: usize = 32;
+/// The SSH public key algorithms that may be registered.
+const SSH_ALGORITHMS: &[&str] = &[
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+    "sk-ssh-ed25519@openssh.com",
+    "ssh-ed25519",
+    "ssh-rsa",
+];
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find org key `{0}`: {1}
+    ById(OrgKeyId, diesel::result::Error),
+    /// Failed to find keys of org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Org key command error: {0}
+    Command(#[from] super::command::Error),
+    /// Failed to create org key: {0}
+    Create(diesel::result::Error),
+    /// Failed to find hosts of org keys: {0}
+    Hosts(diesel::result::Error),
+    /// Org key name must be between 1 and {MAX_NAME_LEN} characters.
+    InvalidName,
+    /// Invalid {0:?} public key: {1}
+    InvalidPublicKey(OrgKeyType, &'static str),
+    /// Failed to find private hosts of org `{0}`: {1}
+    OrgHosts(OrgId, diesel::result::Error),
+    /// Failed to revoke org key `{0}`: {1}
+    Revoke(OrgKeyId, diesel::result::Error),
+    /// Org key `{0}` is already revoked.
+    Revoked(OrgKeyId),
+    /// Failed to send org keys to host `{0}`: {1}
+    Send(HostId, diesel::result::Error),
+    /// Unknown OrgKeyType.
+    UnknownKeyType,
+    /// Failed to update org key host: {0}
+    UpdateHost(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Key already exists.")
+            }
+            ById(_, NotFound) => Status::not_found("Key not found."),
+            InvalidName => Status::invalid_argument("name"),
+            InvalidPublicKey(..) => Status::invalid_argument("public_key"),
+            Revoked(_) => Status::failed_precondition("Key is already revoked."),
+            UnknownKeyType => Status::invalid_argument("key_type"),
+            Command(err) => err.into(),
+            ById(..) | ByOrgId(..) | Create(_) | Hosts(_) | OrgHosts(..) | Revoke(..)
+            | Send(..) | UpdateHost(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct OrgKeyId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
+#[ExistingTypePath = "sql_types::EnumOrgKeyType"]
+#[serde(rename_all = "snake_case")]
+pub enum OrgKeyType {
+    Ssh,
+    Wireguard,
+}
+
+impl OrgKeyType {
+    /// Check that `public_key` is a valid key of this type, returning it
+    /// normalized along with its fingerprint.
+    ///
+    /// The fingerprint is the unpadded base64 SHA256 of the decoded key, which
+    /// matches the output of `ssh-keygen -l` for SSH keys.
+    pub fn parse(self, public_key: &str) -> Result<(String, String), Error> {
+        let invalid = |reason| Error::InvalidPublicKey(self, reason);
+        let (normalized, decoded) = match self {
+            OrgKeyType::Ssh => {
+                let mut parts = public_key.split_whitespace();
+                let algorithm = parts.next().ok_or_else(|| invalid("missing algorithm"))?;
+                let encoded = parts.next().ok_or_else(|| invalid("missing key"))?;
+                if !SSH_ALGORITHMS.contains(&algorithm) {
+                    return Err(invalid("unsupported algorithm"));
+                }
+
+                let decoded = STANDARD
+                    .decode(encoded)
+                    .map_err(|_| invalid("not base64"))?;
+                let prefix_len = 4 + algorithm.len();
+                let embedded = decoded
+                    .get(4..prefix_len)
+                    .ok_or_else(|| invalid("too short"))?;
+                if decoded[..4] != u32::try_from(algorithm.len()).unwrap_or(0).to_be_bytes()
+                    || embedded != algorithm.as_bytes()
+                {
+                    return Err(invalid("algorithm mismatch"));
+                }
+
+                let comment: Vec<_> = parts.collect();
+                let normalized = if comment.is_empty() {
+                    format!("{algorithm} {encoded}")
+                } else {
+                    format!("{algorithm} {encoded} {}", comment.join(" "))
+                };
+                (normalized, decoded)
+            }
+            OrgKeyType::Wireguard => {
+                let encoded = public_key.trim();
+                let decoded = STANDARD
+                    .decode(encoded)
+                    .map_err(|_| invalid("not base64"))?;
+                if decoded.len() != WIREGUARD_KEY_LEN {
+                    return Err(invalid("not 32 bytes"));
+                }
+                (encoded.to_string(), decoded)
+            }
+        };
+
+        let fingerprint = format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(decoded)));
+        Ok((normalized, fingerprint))
+    }
+}
+
+impl From<OrgKeyType> for api::OrgKeyType {
+    fn from(key_type: OrgKeyType) -> Self {
+        match key_type {
+            OrgKeyType::Ssh => api::OrgKeyType::Ssh,
+            OrgKeyType::Wireguard => api::OrgKeyType::Wireguard,
+        }
+    }
+}
+
+impl TryFrom<api::OrgKeyType> for OrgKeyType {
+    type Error = Error;
+
+    fn try_from(key_type: api::OrgKeyType) -> Result<Self, Self::Error> {
+        match key_type {
+            api::OrgKeyType::Unspecified => Err(Error::UnknownKeyType),
+            api::OrgKeyType::Ssh => Ok(OrgKeyType::Ssh),
+            api::OrgKeyType::Wireguard => Ok(OrgKeyType::Wireguard),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumOrgKeyHostStatus"]
+pub enum OrgKeyHostStatus {
+    Sent,
+    Applied,
+    Failed,
+}
+
+impl From<OrgKeyHostStatus> for api::OrgKeyHostStatus {
+    fn from(status: OrgKeyHostStatus) -> Self {
+        match status {
+            OrgKeyHostStatus::Sent => api::OrgKeyHostStatus::Sent,
+            OrgKeyHostStatus::Applied => api::OrgKeyHostStatus::Applied,
+            OrgKeyHostStatus::Failed => api::OrgKeyHostStatus::Failed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct OrgKey {
+    pub id: OrgKeyId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub key_type: OrgKeyType,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub replaces_id: Option<OrgKeyId>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl OrgKey {
+    pub async fn by_id(id: OrgKeyId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        org_keys::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// The keys of an org, oldest first, optionally including revoked keys.
+    pub async fn by_org_id(
+        org_id: OrgId,
+        include_revoked: bool,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = org_keys::table
+            .filter(org_keys::org_id.eq(org_id))
+            .into_boxed();
+        if !include_revoked {
+            query = query.filter(org_keys::revoked_at.is_null());
+        }
+
+        query
+            .order_by(org_keys::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Revoke this key, which is removed from hosts on the next send.
+    pub async fn revoke(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let active = org_keys::table
+            .find(self.id)
+            .filter(org_keys::revoked_at.is_null());
+
+        diesel::update(active)
+            .set(org_keys::revoked_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::Revoke(self.id, err))?
+            .ok_or(Error::Revoked(self.id))
+    }
+
+    /// Send the active keys of an org to each of its private hosts.
+    ///
+    /// Returns the commands to send once the transaction commits.
+    pub async fn send_to_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        let host_ids: Vec<HostId> = hosts::table
+            .filter(hosts::org_id.eq(org_id))
+            .filter(hosts::deleted_at.is_null())
+            .select(hosts::id)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::OrgHosts(org_id, err))?;
+        let keys = Self::by_org_id(org_id, false, conn).await?;
+
+        let mut commands = Vec::with_capacity(host_ids.len());
+        for host_id in host_ids {
+            commands.push(Self::send(org_id, host_id, &keys, conn).await?);
+        }
+
+        Ok(commands)
+    }
+
+    /// Send the active keys of an org to a new private host, if it has any.
+    pub async fn send_to_host(
+        org_id: OrgId,
+        host_id: HostId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Command>, Error> {
+        let keys = Self::by_org_id(org_id, false, conn).await?;
+        if keys.is_empty() {
+            return Ok(None);
+        }
+
+        Self::send(org_id, host_id, &keys, conn).await.map(Some)
+    }
+
+    /// Create a `HostKeys` command of `keys`, and record it as the latest
+    /// command of each key of the org on the host.
+    async fn send(
+        org_id: OrgId,
+        host_id: HostId,
+        keys: &[Self],
+        conn: &mut Conn<'_>,
+    ) -> Result<Command, Error> {
+        let host_keys = api::HostKeys {
+            keys: keys.iter().map(api::HostKey::from).collect(),
+        };
+        let command = NewCommand::host(host_id, CommandType::HostKeys)?
+            .with_protobuf(&host_keys)
+            .create(conn)
+            .await?;
+
+        let rows: Vec<_> = keys
+            .iter()
+            .map(|key| {
+                (
+                    org_key_hosts::key_id.eq(key.id),
+                    org_key_hosts::host_id.eq(host_id),
+                )
+            })
+            .collect();
+        diesel::insert_into(org_key_hosts::table)
+            .values(rows)
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Send(host_id, err))?;
+
+        let org_keys = org_keys::table
+            .filter(org_keys::org_id.eq(org_id))
+            .select(org_keys::id);
+        let host_rows = org_key_hosts::table
+            .filter(org_key_hosts::host_id.eq(host_id))
+            .filter(org_key_hosts::key_id.eq_any(org_keys));
+        diesel::update(host_rows)
+            .set((
+                org_key_hosts::status.eq(OrgKeyHostStatus::Sent),
+                org_key_hosts::command_id.eq(command.id),
+                org_key_hosts::error.eq(None::<String>),
+                org_key_hosts::sent_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Send(host_id, err))?;
+
+        Ok(command)
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+
+    pub fn into_api(self, hosts: Vec<OrgKeyHost>) -> api::OrgKey {
+        let created_by = common::Resource::from(self.created_by());
+        api::OrgKey {
+            key_id: self.id.to_string(),
+            org_id: self.org_id.to_string(),
+            name: self.name,
+            key_type: api::OrgKeyType::from(self.key_type).into(),
+            public_key: self.public_key,
+            fingerprint: self.fingerprint,
+            replaces_key_id: self.replaces_id.map(|id| id.to_string()),
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(self.created_at).into()),
+            revoked_at: self.revoked_at.map(NanosUtc::from).map(Into::into),
+            hosts: hosts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<&OrgKey> for api::HostKey {
+    fn from(key: &OrgKey) -> Self {
+        api::HostKey {
+            key_id: key.id.to_string(),
+            key_type: api::OrgKeyType::from(key.key_type).into(),
+            public_key: key.public_key.clone(),
+            fingerprint: key.fingerprint.clone(),
+        }
+    }
+}
+
+/// The state of a key on a single private host.
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct OrgKeyHost {
+    pub key_id: OrgKeyId,
+    pub host_id: HostId,
+    pub status: OrgKeyHostStatus,
+    pub command_id: Option<CommandId>,
+    pub error: Option<String>,
+    pub sent_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+impl OrgKeyHost {
+    /// The live hosts of each key in `key_ids`.
+    pub async fn by_key_ids<'k, I>(key_ids: I, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error>
+    where
+        I: IntoIterator<Item = &'k OrgKeyId>,
+    {
+        org_key_hosts::table
+            .inner_join(hosts::table)
+            .filter(org_key_hosts::key_id.eq_any(key_ids))
+            .filter(hosts::deleted_at.is_null())
+            .select(OrgKeyHost::as_select())
+            .order_by(org_key_hosts::host_id)
+            .get_results(conn)
+            .await
+            .map_err(Error::Hosts)
+    }
+
+    /// Record that a host has finished its `HostKeys` command.
+    ///
+    /// Once a host has applied the keys, the rows of revoked keys are removed
+    /// since the host no longer has them.
+    pub async fn finished(
+        command_id: CommandId,
+        error: Option<&str>,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let sent = org_key_hosts::table
+            .filter(org_key_hosts::command_id.eq(command_id))
+            .filter(org_key_hosts::status.eq(OrgKeyHostStatus::Sent));
+
+        if let Some(error) = error {
+            return diesel::update(sent.clone())
+                .set((
+                    org_key_hosts::status.eq(OrgKeyHostStatus::Failed),
+                    org_key_hosts::error.eq(error),
+                ))
+                .execute(conn)
+                .await
+                .map(|_| ())
+                .map_err(Error::UpdateHost);
+        }
+
+        let revoked = org_keys::table
+            .filter(org_keys::revoked_at.is_not_null())
+            .select(org_keys::id);
+        diesel::delete(sent.clone().filter(org_key_hosts::key_id.eq_any(revoked)))
+            .execute(conn)
+            .await
+            .map_err(Error::UpdateHost)?;
+
+        diesel::update(sent)
+            .set((
+                org_key_hosts::status.eq(OrgKeyHostStatus::Applied),
+                org_key_hosts::applied_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(Error::UpdateHost)
+    }
+}
+
+impl From<OrgKeyHost> for api::OrgKeyHost {
+    fn from(host: OrgKeyHost) -> Self {
+        api::OrgKeyHost {
+            host_id: host.host_id.to_string(),
+            status: api::OrgKeyHostStatus::from(host.status).into(),
+            command_id: host.command_id.map(|id| id.to_string()),
+            error: host.error,
+            sent_at: Some(NanosUtc::from(host.sent_at).into()),
+            applied_at: host.applied_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+pub struct NewOrgKey<'a> {
+    pub org_id: OrgId,
+    pub name: &'a str,
+    pub key_type: OrgKeyType,
+    pub public_key: &'a str,
+    pub replaces_id: Option<OrgKeyId>,
+    pub created_by: Resource,
+}
+
+impl NewOrgKey<'_> {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<OrgKey, Error> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(Error::InvalidName);
+        }
+        let (public_key, fingerprint) = self.key_type.parse(self.public_key)?;
+
+        diesel::insert_into(org_keys::table)
+            .values((
+                org_keys::org_id.eq(self.org_id),
+                org_keys::name.eq(name),
+                org_keys::key_type.eq(self.key_type),
+                org_keys::public_key.eq(public_key),
+                org_keys::fingerprint.eq(fingerprint),
+                org_keys::replaces_id.eq(self.replaces_id),
+                org_keys::created_by_type.eq(self.created_by.typ()),
+                org_keys::created_by_id.eq(self.created_by.id()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_public_keys() {
+        let ssh = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl  ops@example ";
+        let (key, fingerprint) = OrgKeyType::Ssh.parse(ssh).unwrap();
+        assert_eq!(
+            key,
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl ops@example"
+        );
+        assert!(fingerprint.starts_with("SHA256:"));
+
+        let mismatch =
+            "ssh-rsa AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+        assert!(OrgKeyType::Ssh.parse(mismatch).is_err());
+        assert!(OrgKeyType::Ssh.parse("ssh-dss AAAA").is_err());
+
+        let wireguard = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+        let (key, _) = OrgKeyType::Wireguard.parse(wireguard).unwrap();
+        assert_eq!(key, wireguard);
+        assert!(OrgKeyType::Wireguard.parse("AAAA").is_err());
+    }
+}
@@ -0,0 +1,617 @@
+//! The logging stack that the private hosts of an org ship node logs to.
+//!
+//! An org has at most one log sink, which is sent as a whole to each of its
+//! private hosts in a `HostLogSink` command whenever it changes. Removing the
+//! sink sends a `HostLogSink` without a sink, so hosts stop shipping logs. Each
+//! change bumps the `revision` of the sink, and each `OrgLogSinkHost` tracks
+//! the last revision sent to a host through to success or failure.
+//!
+//! The credentials of a sink are kept in the secret store rather than the
+//! database, so commands only carry them once revealed to a host fetching its
+//! own pending commands.
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display as DisplayDoc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+use crate::auth::resource::{HostId, OrgId, Resource};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::store::Secret;
+use crate::store::secret::SecretKey;
+use crate::util::NanosUtc;
+
+use super::command::{Command, CommandId, CommandType, NewCommand};
+use super::schema::{hosts, org_log_sink_hosts, org_log_sinks, sql_types};
+
+/// The longest endpoint url of a sink.
+const MAX_ENDPOINT_LEN: usize = 2048;
+/// The secret store key of the credentials of an org sink.
+const CREDENTIALS_KEY: &str = "log-sink-credentials";
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find log sink of org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Log sink command error: {0}
+    Command(#[from] super::command::Error),
+    /// Credentials are not for a {0:?} log sink.
+    CredentialsKind(LogSinkKind),
+    /// Failed to delete log sink of org `{0}`: {1}
+    Delete(OrgId, diesel::result::Error),
+    /// Log sink endpoint `{0}` is not a valid https url.
+    Endpoint(String),
+    /// Failed to find log sink hosts of org `{0}`: {1}
+    Hosts(OrgId, diesel::result::Error),
+    /// Missing log sink credentials.
+    MissingCredentials,
+    /// Failed to find private hosts of org `{0}`: {1}
+    OrgHosts(OrgId, diesel::result::Error),
+    /// Failed to parse log sink credentials: {0}
+    ParseCredentials(serde_json::Error),
+    /// Failed to parse OrgId of HostLogSink: {0}
+    ParseOrgId(uuid::Error),
+    /// {0:?} log sinks require credentials.
+    RequiresCredentials(LogSinkKind),
+    /// Log sink credentials secret: {0}
+    Secret(#[from] crate::store::secret::Error),
+    /// Failed to send log sink to host `{0}`: {1}
+    Send(HostId, diesel::result::Error),
+    /// Failed to serialize log sink credentials: {0}
+    SerializeCredentials(serde_json::Error),
+    /// Unknown LogSinkKind.
+    UnknownKind,
+    /// Failed to update log sink host: {0}
+    UpdateHost(diesel::result::Error),
+    /// Failed to update log sink of org `{0}`: {1}
+    Upsert(OrgId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            CredentialsKind(_) | MissingCredentials | RequiresCredentials(_) => {
+                Status::invalid_argument("credentials")
+            }
+            Endpoint(_) => Status::invalid_argument("endpoint"),
+            UnknownKind => Status::invalid_argument("kind"),
+            Command(err) => err.into(),
+            Secret(err) => err.into(),
+            ByOrg(..)
+            | Delete(..)
+            | Hosts(..)
+            | OrgHosts(..)
+            | ParseCredentials(_)
+            | ParseOrgId(_)
+            | Send(..)
+            | SerializeCredentials(_)
+            | UpdateHost(_)
+            | Upsert(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum, Serialize, Deserialize)]
+#[ExistingTypePath = "sql_types::EnumLogSinkKind"]
+#[serde(rename_all = "snake_case")]
+pub enum LogSinkKind {
+    Loki,
+    Datadog,
+    S3,
+}
+
+impl LogSinkKind {
+    /// Loki may be reached without auth, but Datadog and S3 always need keys.
+    pub const fn requires_credentials(self) -> bool {
+        match self {
+            LogSinkKind::Loki => false,
+            LogSinkKind::Datadog | LogSinkKind::S3 => true,
+        }
+    }
+}
+
+impl From<LogSinkKind> for api::LogSinkKind {
+    fn from(kind: LogSinkKind) -> Self {
+        match kind {
+            LogSinkKind::Loki => api::LogSinkKind::Loki,
+            LogSinkKind::Datadog => api::LogSinkKind::Datadog,
+            LogSinkKind::S3 => api::LogSinkKind::S3,
+        }
+    }
+}
+
+impl TryFrom<api::LogSinkKind> for LogSinkKind {
+    type Error = Error;
+
+    fn try_from(kind: api::LogSinkKind) -> Result<Self, Self::Error> {
+        match kind {
+            api::LogSinkKind::Unspecified => Err(Error::UnknownKind),
+            api::LogSinkKind::Loki => Ok(LogSinkKind::Loki),
+            api::LogSinkKind::Datadog => Ok(LogSinkKind::Datadog),
+            api::LogSinkKind::S3 => Ok(LogSinkKind::S3),
+        }
+    }
+}
+
+/// Parse the endpoint of a sink, which must be an https url.
+pub fn parse_endpoint(endpoint: &str) -> Result<Url, Error> {
+    let endpoint = endpoint.trim();
+    let invalid = || Error::Endpoint(endpoint.to_string());
+    if endpoint.len() > MAX_ENDPOINT_LEN {
+        return Err(invalid());
+    }
+
+    let url = Url::parse(endpoint).map_err(|_| invalid())?;
+    if url.scheme() != "https" || url.host().is_none() {
+        return Err(invalid());
+    }
+
+    Ok(url)
+}
+
+/// The credentials of a sink, as kept in the secret store.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LogSinkCredentials {
+    Loki {
+        username: String,
+        password: String,
+    },
+    Datadog {
+        api_key: String,
+    },
+    S3 {
+        access_key_id: String,
+        secret_access_key: String,
+    },
+}
+
+impl LogSinkCredentials {
+    pub const fn kind(&self) -> LogSinkKind {
+        match self {
+            LogSinkCredentials::Loki { .. } => LogSinkKind::Loki,
+            LogSinkCredentials::Datadog { .. } => LogSinkKind::Datadog,
+            LogSinkCredentials::S3 { .. } => LogSinkKind::S3,
+        }
+    }
+
+    /// Read the credentials of the org sink from the secret store.
+    pub async fn read(org_id: OrgId, secret: &Secret, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let key = Self::secret_key()?;
+        let data = secret.get(Resource::from(org_id), &key, conn).await?;
+        serde_json::from_slice(&data).map_err(Error::ParseCredentials)
+    }
+
+    /// Replace the credentials of the org sink in the secret store.
+    pub async fn write(
+        &self,
+        org_id: OrgId,
+        secret: &Secret,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let data = serde_json::to_vec(self).map_err(Error::SerializeCredentials)?;
+        let key = Self::secret_key()?;
+        secret
+            .put(Resource::from(org_id), &key, &data, conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    fn secret_key() -> Result<SecretKey, Error> {
+        SecretKey::new(CREDENTIALS_KEY.to_string()).map_err(Into::into)
+    }
+}
+
+impl TryFrom<api::LogSinkCredentials> for LogSinkCredentials {
+    type Error = Error;
+
+    fn try_from(credentials: api::LogSinkCredentials) -> Result<Self, Self::Error> {
+        use api::log_sink_credentials::Credentials as Creds;
+
+        match credentials.credentials.ok_or(Error::MissingCredentials)? {
+            Creds::Loki(creds) => Ok(LogSinkCredentials::Loki {
+                username: creds.username,
+                password: creds.password,
+            }),
+            Creds::Datadog(creds) => Ok(LogSinkCredentials::Datadog {
+                api_key: creds.api_key,
+            }),
+            Creds::S3(creds) => Ok(LogSinkCredentials::S3 {
+                access_key_id: creds.access_key_id,
+                secret_access_key: creds.secret_access_key,
+            }),
+        }
+    }
+}
+
+impl From<LogSinkCredentials> for api::LogSinkCredentials {
+    fn from(credentials: LogSinkCredentials) -> Self {
+        use api::log_sink_credentials::Credentials as Creds;
+
+        let credentials = match credentials {
+            LogSinkCredentials::Loki { username, password } => {
+                Creds::Loki(api::LokiCredentials { username, password })
+            }
+            LogSinkCredentials::Datadog { api_key } => {
+                Creds::Datadog(api::DatadogCredentials { api_key })
+            }
+            LogSinkCredentials::S3 {
+                access_key_id,
+                secret_access_key,
+            } => Creds::S3(api::S3Credentials {
+                access_key_id,
+                secret_access_key,
+            }),
+        };
+
+        api::LogSinkCredentials {
+            credentials: Some(credentials),
+        }
+    }
+}
+
+/// Fill in the credentials of a `HostLogSink` from the secret store of its org.
+pub async fn reveal_credentials(
+    host_sink: &mut api::HostLogSink,
+    secret: &Secret,
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let Some(config) = host_sink.sink.as_mut() else {
+        return Ok(());
+    };
+    if !config.has_credentials {
+        return Ok(());
+    }
+
+    let org_id = host_sink.org_id.parse().map_err(Error::ParseOrgId)?;
+    config.credentials = Some(LogSinkCredentials::read(org_id, secret, conn).await?.into());
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumOrgLogSinkHostStatus"]
+pub enum OrgLogSinkHostStatus {
+    Sent,
+    Applied,
+    Failed,
+}
+
+impl From<OrgLogSinkHostStatus> for api::OrgLogSinkHostStatus {
+    fn from(status: OrgLogSinkHostStatus) -> Self {
+        match status {
+            OrgLogSinkHostStatus::Sent => api::OrgLogSinkHostStatus::Sent,
+            OrgLogSinkHostStatus::Applied => api::OrgLogSinkHostStatus::Applied,
+            OrgLogSinkHostStatus::Failed => api::OrgLogSinkHostStatus::Failed,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct OrgLogSink {
+    pub org_id: OrgId,
+    pub kind: LogSinkKind,
+    pub endpoint: String,
+    /// Whether credentials for this sink are kept in the secret store.
+    pub has_credentials: bool,
+    pub revision: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl OrgLogSink {
+    /// The current sink of an org, if it has one.
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        org_log_sinks::table
+            .find(org_id)
+            .filter(org_log_sinks::deleted_at.is_null())
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    /// Remove this sink, bumping the revision so hosts are sent its removal.
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        diesel::update(org_log_sinks::table.find(self.org_id))
+            .set((
+                org_log_sinks::has_credentials.eq(false),
+                org_log_sinks::revision.eq(org_log_sinks::revision + 1),
+                org_log_sinks::updated_at.eq(Utc::now()),
+                org_log_sinks::deleted_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Delete(self.org_id, err))
+    }
+
+    /// Send this sink, or its removal, to each private host of the org.
+    ///
+    /// Returns the commands to send once the transaction commits.
+    pub async fn send_to_org(&self, conn: &mut Conn<'_>) -> Result<Vec<Command>, Error> {
+        let host_ids: Vec<HostId> = hosts::table
+            .filter(hosts::org_id.eq(self.org_id))
+            .filter(hosts::deleted_at.is_null())
+            .select(hosts::id)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::OrgHosts(self.org_id, err))?;
+
+        let mut commands = Vec::with_capacity(host_ids.len());
+        for host_id in host_ids {
+            commands.push(self.send(host_id, conn).await?);
+        }
+
+        Ok(commands)
+    }
+
+    /// Send the sink of an org to a new private host, if it has one.
+    pub async fn send_to_host(
+        org_id: OrgId,
+        host_id: HostId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Command>, Error> {
+        match Self::by_org(org_id, conn).await? {
+            Some(sink) => sink.send(host_id, conn).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Create a `HostLogSink` command of this revision, and record it as the
+    /// latest command of the host.
+    async fn send(&self, host_id: HostId, conn: &mut Conn<'_>) -> Result<Command, Error> {
+        let command = NewCommand::host(host_id, CommandType::HostLogSink)?
+            .with_protobuf(&api::HostLogSink::from(self))
+            .create(conn)
+            .await?;
+
+        let row = (
+            org_log_sink_hosts::host_id.eq(host_id),
+            org_log_sink_hosts::org_id.eq(self.org_id),
+            org_log_sink_hosts::revision.eq(self.revision),
+            org_log_sink_hosts::status.eq(OrgLogSinkHostStatus::Sent),
+            org_log_sink_hosts::command_id.eq(command.id),
+            org_log_sink_hosts::error.eq(None::<&str>),
+            org_log_sink_hosts::sent_at.eq(Utc::now()),
+            org_log_sink_hosts::applied_at.eq(None::<DateTime<Utc>>),
+        );
+        diesel::insert_into(org_log_sink_hosts::table)
+            .values(row)
+            .on_conflict(org_log_sink_hosts::host_id)
+            .do_update()
+            .set(row)
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Send(host_id, err))?;
+
+        Ok(command)
+    }
+
+    pub fn into_api(self) -> api::OrgLogSink {
+        api::OrgLogSink {
+            org_id: self.org_id.to_string(),
+            kind: api::LogSinkKind::from(self.kind).into(),
+            endpoint: self.endpoint,
+            has_credentials: self.has_credentials,
+            revision: self.revision,
+            created_at: Some(NanosUtc::from(self.created_at).into()),
+            updated_at: Some(NanosUtc::from(self.updated_at).into()),
+        }
+    }
+}
+
+impl From<&OrgLogSink> for api::HostLogSink {
+    fn from(sink: &OrgLogSink) -> Self {
+        let config = sink.deleted_at.is_none().then(|| api::HostLogSinkConfig {
+            kind: api::LogSinkKind::from(sink.kind).into(),
+            endpoint: sink.endpoint.clone(),
+            has_credentials: sink.has_credentials,
+            credentials: None,
+        });
+
+        api::HostLogSink {
+            org_id: sink.org_id.to_string(),
+            revision: sink.revision,
+            sink: config,
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = org_log_sinks)]
+pub struct NewOrgLogSink {
+    pub org_id: OrgId,
+    pub kind: LogSinkKind,
+    pub endpoint: String,
+    pub has_credentials: bool,
+}
+
+impl NewOrgLogSink {
+    /// Check a new sink for an org, where any new `credentials` must be for
+    /// the same kind of sink.
+    ///
+    /// Without new credentials, the stored credentials of an `existing` sink
+    /// are kept while its kind is unchanged.
+    pub fn new(
+        org_id: OrgId,
+        kind: LogSinkKind,
+        endpoint: &str,
+        credentials: Option<&LogSinkCredentials>,
+        existing: Option<&OrgLogSink>,
+    ) -> Result<Self, Error> {
+        let endpoint = parse_endpoint(endpoint)?;
+        let has_credentials = match credentials {
+            Some(credentials) if credentials.kind() != kind => {
+                return Err(Error::CredentialsKind(kind));
+            }
+            Some(_) => true,
+            None => existing.is_some_and(|sink| sink.kind == kind && sink.has_credentials),
+        };
+        if kind.requires_credentials() && !has_credentials {
+            return Err(Error::RequiresCredentials(kind));
+        }
+
+        Ok(NewOrgLogSink {
+            org_id,
+            kind,
+            endpoint: endpoint.into(),
+            has_credentials,
+        })
+    }
+
+    /// Replace the sink of an org, restoring a removed sink as a new revision.
+    pub async fn upsert(self, conn: &mut Conn<'_>) -> Result<OrgLogSink, Error> {
+        let org_id = self.org_id;
+        diesel::insert_into(org_log_sinks::table)
+            .values(&self)
+            .on_conflict(org_log_sinks::org_id)
+            .do_update()
+            .set((
+                org_log_sinks::kind.eq(self.kind),
+                org_log_sinks::endpoint.eq(&self.endpoint),
+                org_log_sinks::has_credentials.eq(self.has_credentials),
+                org_log_sinks::revision.eq(org_log_sinks::revision + 1),
+                org_log_sinks::updated_at.eq(Utc::now()),
+                org_log_sinks::deleted_at.eq(None::<DateTime<Utc>>),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Upsert(org_id, err))
+    }
+}
+
+/// The log sink state of a single private host.
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct OrgLogSinkHost {
+    pub host_id: HostId,
+    pub org_id: OrgId,
+    /// The revision of the org sink last sent to the host.
+    pub revision: i64,
+    pub status: OrgLogSinkHostStatus,
+    pub command_id: Option<CommandId>,
+    pub error: Option<String>,
+    pub sent_at: DateTime<Utc>,
+    pub applied_at: Option<DateTime<Utc>>,
+}
+
+impl OrgLogSinkHost {
+    /// The live private hosts of an org that were sent its sink.
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        org_log_sink_hosts::table
+            .inner_join(hosts::table)
+            .filter(org_log_sink_hosts::org_id.eq(org_id))
+            .filter(hosts::deleted_at.is_null())
+            .select(OrgLogSinkHost::as_select())
+            .order_by(org_log_sink_hosts::host_id)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Hosts(org_id, err))
+    }
+
+    /// Record that a host has finished its `HostLogSink` command.
+    pub async fn finished(
+        command_id: CommandId,
+        error: Option<&str>,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let sent = org_log_sink_hosts::table
+            .filter(org_log_sink_hosts::command_id.eq(command_id))
+            .filter(org_log_sink_hosts::status.eq(OrgLogSinkHostStatus::Sent));
+
+        let update = diesel::update(sent);
+        let updated = if let Some(error) = error {
+            update
+                .set((
+                    org_log_sink_hosts::status.eq(OrgLogSinkHostStatus::Failed),
+                    org_log_sink_hosts::error.eq(error),
+                ))
+                .execute(conn)
+                .await
+        } else {
+            update
+                .set((
+                    org_log_sink_hosts::status.eq(OrgLogSinkHostStatus::Applied),
+                    org_log_sink_hosts::applied_at.eq(Utc::now()),
+                ))
+                .execute(conn)
+                .await
+        };
+
+        updated.map(|_| ()).map_err(Error::UpdateHost)
+    }
+}
+
+impl From<OrgLogSinkHost> for api::OrgLogSinkHost {
+    fn from(host: OrgLogSinkHost) -> Self {
+        api::OrgLogSinkHost {
+            host_id: host.host_id.to_string(),
+            revision: host.revision,
+            status: api::OrgLogSinkHostStatus::from(host.status).into(),
+            command_id: host.command_id.map(|id| id.to_string()),
+            error: host.error,
+            sent_at: Some(NanosUtc::from(host.sent_at).into()),
+            applied_at: host.applied_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn endpoints_must_be_https() {
+        let url = parse_endpoint(" https://logs.acme.io/loki/api/v1/push ").unwrap();
+        assert_eq!(url.host_str(), Some("logs.acme.io"));
+
+        assert!(parse_endpoint("http://logs.acme.io").is_err());
+        assert!(parse_endpoint("logs.acme.io").is_err());
+        assert!(parse_endpoint("https://").is_err());
+        let long = format!("https://acme.io/{}", "a".repeat(MAX_ENDPOINT_LEN));
+        assert!(parse_endpoint(&long).is_err());
+    }
+
+    #[test]
+    fn credentials_must_match_the_sink() {
+        let org_id: OrgId = Uuid::new_v4().into();
+        let endpoint = "https://http-intake.logs.datadoghq.com";
+        let datadog = LogSinkCredentials::Datadog {
+            api_key: "abc123".to_string(),
+        };
+
+        let sink = NewOrgLogSink::new(org_id, LogSinkKind::Datadog, endpoint, Some(&datadog), None);
+        assert!(sink.unwrap().has_credentials);
+
+        let result = NewOrgLogSink::new(org_id, LogSinkKind::S3, endpoint, Some(&datadog), None);
+        assert!(matches!(
+            result,
+            Err(Error::CredentialsKind(LogSinkKind::S3))
+        ));
+        let result = NewOrgLogSink::new(org_id, LogSinkKind::Datadog, endpoint, None, None);
+        assert!(matches!(result, Err(Error::RequiresCredentials(_))));
+
+        let sink = NewOrgLogSink::new(org_id, LogSinkKind::Loki, endpoint, None, None);
+        assert!(!sink.unwrap().has_credentials);
+    }
+
+    #[test]
+    fn credentials_are_stored_by_kind() {
+        let credentials = LogSinkCredentials::Datadog {
+            api_key: "abc123".to_string(),
+        };
+        let json = serde_json::to_string(&credentials).unwrap();
+        assert_eq!(json, r#"{"kind":"datadog","api_key":"abc123"}"#);
+
+        let parsed: LogSinkCredentials = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.kind(), LogSinkKind::Datadog);
+    }
+}
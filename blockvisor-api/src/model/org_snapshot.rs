@@ -0,0 +1,473 @@
+//! Versioned JSON snapshots of the configuration of an org.
+//!
+//! A snapshot holds the settings, host pools, public keys and nodes of an org,
+//! so that a production setup can be copied into an org of another environment
+//! such as dev or staging. Nodes are recorded by protocol version, image build
+//! and region rather than by id, as ids differ between environments.
+//!
+//! Secret property values are never exported, so an imported node keeps the
+//! default of each secret property until it is set again.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::auth::AuthZ;
+use crate::auth::resource::{NodeId, OrgId, Resource};
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::HashVec;
+
+use super::agent_upgrade::AgentUpgradePolicy;
+use super::command::Command;
+use super::host_pool::{HostPool, HostPoolId, NewHostPool};
+use super::image::{Config, FirewallRule, Image, ImageRule};
+use super::node::PlacementPolicy;
+use super::org::UpdateOrg;
+use super::org_key::{NewOrgKey, OrgKey, OrgKeyType};
+use super::protocol::version::{ProtocolKey, VariantKey, VersionKey};
+use super::region::RegionKey;
+use super::schema::{hosts, images, protocol_versions, regions};
+use super::sql::Version;
+use super::{Node, Org, ProtocolVersion, Region};
+
+/// The latest snapshot format, which older snapshots are read as.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Snapshot host pool error: {0}
+    HostPool(#[from] crate::model::host_pool::Error),
+    /// Snapshot image error: {0}
+    Image(#[from] crate::model::image::Error),
+    /// Snapshot image config error: {0}
+    ImageConfig(#[from] crate::model::image::config::Error),
+    /// Snapshot image rule error: {0}
+    ImageRule(#[from] crate::model::image::rule::Error),
+    /// No image of `{0}` version `{1}` is available.
+    NoImage(String, String),
+    /// Snapshot node `{0}` has no region.
+    NoRegion(String),
+    /// Snapshot node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Failed to find the image build of node `{0}`: {1}
+    NodeImage(NodeId, diesel::result::Error),
+    /// Failed to find the region of node `{0}`: {1}
+    NodeRegion(NodeId, diesel::result::Error),
+    /// Failed to find the version keys of node `{0}`: {1}
+    NodeVersion(NodeId, diesel::result::Error),
+    /// Snapshot org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Snapshot org key error: {0}
+    OrgKey(#[from] crate::model::org_key::Error),
+    /// Failed to parse snapshot: {0}
+    Parse(serde_json::Error),
+    /// Snapshot protocol version error: {0}
+    ProtocolVersion(#[from] crate::model::protocol::version::Error),
+    /// Snapshot region error: {0}
+    Region(#[from] crate::model::region::Error),
+    /// Failed to serialize snapshot: {0}
+    Serialize(serde_json::Error),
+    /// Snapshot version error: {0}
+    Sql(#[from] crate::model::sql::Error),
+    /// Snapshot version {0} is not supported.
+    UnsupportedVersion(u32),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            NodeImage(..) | NodeRegion(..) | NodeVersion(..) | Serialize(_) => {
+                Status::internal("Internal error.")
+            }
+            NoImage(key, version) => {
+                // safety: the key and version are from the client snapshot
+                Status::failed_precondition(format!("No image of {key} {version}."))
+            }
+            NoRegion(name) => {
+                // safety: the name is from the client snapshot
+                Status::failed_precondition(format!("Node {name} has no region."))
+            }
+            Parse(_) => Status::invalid_argument("snapshot"),
+            UnsupportedVersion(_) => Status::failed_precondition("Unsupported snapshot version."),
+            HostPool(err) => err.into(),
+            Image(err) => err.into(),
+            ImageConfig(err) => err.into(),
+            ImageRule(err) => err.into(),
+            Node(err) => err.into(),
+            Org(err) => err.into(),
+            OrgKey(err) => err.into(),
+            ProtocolVersion(err) => err.into(),
+            Region(err) => err.into(),
+            Sql(err) => err.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrgSnapshot {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub settings: OrgSettings,
+    #[serde(default)]
+    pub host_pools: Vec<PoolSnapshot>,
+    #[serde(default)]
+    pub keys: Vec<KeySnapshot>,
+    #[serde(default)]
+    pub nodes: Vec<NodeSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrgSettings {
+    pub agent_upgrade_policy: AgentUpgradePolicy,
+    #[serde(default)]
+    pub disk_quota_bytes: Option<i64>,
+    #[serde(default)]
+    pub disk_auto_approve_bytes: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PoolSnapshot {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeySnapshot {
+    pub name: String,
+    pub key_type: OrgKeyType,
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    pub display_name: String,
+    pub protocol_key: String,
+    pub variant_key: String,
+    pub semantic_version: String,
+    pub build_version: i64,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub pool: Option<String>,
+    #[serde(default)]
+    pub placement: PlacementPolicy,
+    pub auto_upgrade: bool,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The property values that differ from the image defaults.
+    #[serde(default)]
+    pub values: Vec<ValueSnapshot>,
+    /// The firewall rules that differ from the image rules.
+    #[serde(default)]
+    pub rules: Vec<FirewallRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValueSnapshot {
+    pub key: String,
+    pub value: String,
+}
+
+impl OrgSnapshot {
+    /// Take a snapshot of the current configuration of an org.
+    pub async fn export(org: &Org, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let pools = HostPool::by_org_id(org.id, conn).await?;
+        let pool_names: HashMap<HostPoolId, String> = pools
+            .iter()
+            .map(|pool| (pool.id, pool.name.clone()))
+            .collect();
+        let keys = OrgKey::by_org_id(org.id, false, conn).await?;
+
+        let mut nodes = vec![];
+        for node in Node::by_org_id(org.id, conn).await? {
+            let pool = node
+                .scheduler_pool_id
+                .and_then(|id| pool_names.get(&id).cloned());
+            nodes.push(NodeSnapshot::export(node, pool, conn).await?);
+        }
+
+        Ok(OrgSnapshot {
+            version: SNAPSHOT_VERSION,
+            exported_at: Utc::now(),
+            settings: OrgSettings {
+                agent_upgrade_policy: org.agent_upgrade_policy,
+                disk_quota_bytes: org.disk_quota_bytes,
+                disk_auto_approve_bytes: org.disk_auto_approve_bytes,
+            },
+            host_pools: pools
+                .into_iter()
+                .map(|pool| PoolSnapshot {
+                    name: pool.name,
+                    description: pool.description,
+                })
+                .collect(),
+            keys: keys
+                .into_iter()
+                .map(|key| KeySnapshot {
+                    name: key.name,
+                    key_type: key.key_type,
+                    public_key: key.public_key,
+                })
+                .collect(),
+            nodes,
+        })
+    }
+
+    /// Parse a snapshot, rejecting versions newer than `SNAPSHOT_VERSION`.
+    pub fn parse(json: &str) -> Result<Self, Error> {
+        let snapshot: Self = serde_json::from_str(json).map_err(Error::Parse)?;
+        if snapshot.version == 0 || snapshot.version > SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedVersion(snapshot.version));
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(Error::Serialize)
+    }
+
+    /// Restore the settings, host pools and keys of the snapshot into an org.
+    ///
+    /// Pools and keys that the org already has are kept as they are. Returns
+    /// the pool ids by name for the nodes to import, and the `HostKeys`
+    /// commands to send once the transaction commits.
+    pub async fn restore(
+        &self,
+        org_id: OrgId,
+        created_by: Resource,
+        conn: &mut Conn<'_>,
+    ) -> Result<(HashMap<String, HostPoolId>, Vec<Command>), Error> {
+        let update = UpdateOrg {
+            id: org_id,
+            name: None,
+            address_id: None,
+            agent_upgrade_policy: Some(self.settings.agent_upgrade_policy),
+            disk_quota_bytes: Some(self.settings.disk_quota_bytes),
+            disk_auto_approve_bytes: Some(self.settings.disk_auto_approve_bytes),
+            node_display_name_max_len: None,
+            node_note_max_len: None,
+            node_display_name_unique: None,
+            node_display_name_charset: None,
+            require_approval_node_delete: None,
+            require_approval_node_upgrade: None,
+            require_approval_org_transfer: None,
+        };
+        update.update(conn).await?;
+
+        let mut pool_ids: HashMap<String, HostPoolId> = HostPool::by_org_id(org_id, conn)
+            .await?
+            .into_iter()
+            .map(|pool| (pool.name, pool.id))
+            .collect();
+        for pool in &self.host_pools {
+            if pool_ids.contains_key(&pool.name) {
+                continue;
+            }
+            let new_pool = NewHostPool {
+                org_id,
+                name: &pool.name,
+                description: pool.description.as_deref(),
+            };
+            let created = new_pool.create(conn).await?;
+            pool_ids.insert(created.name, created.id);
+        }
+
+        let existing: HashSet<String> = OrgKey::by_org_id(org_id, false, conn)
+            .await?
+            .into_iter()
+            .map(|key| key.public_key)
+            .collect();
+        let mut keys_added = false;
+        for key in &self.keys {
+            if existing.contains(&key.public_key) {
+                continue;
+            }
+            let new_key = NewOrgKey {
+                org_id,
+                name: &key.name,
+                key_type: key.key_type,
+                public_key: &key.public_key,
+                replaces_id: None,
+                created_by,
+            };
+            new_key.create(conn).await?;
+            keys_added = true;
+        }
+
+        let commands = if keys_added {
+            OrgKey::send_to_org(org_id, conn).await?
+        } else {
+            vec![]
+        };
+
+        Ok((pool_ids, commands))
+    }
+}
+
+impl NodeSnapshot {
+    async fn export(node: Node, pool: Option<String>, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let node_id = node.id;
+        let (protocol_key, variant_key): (String, String) = protocol_versions::table
+            .find(node.protocol_version_id)
+            .select((
+                protocol_versions::protocol_key,
+                protocol_versions::variant_key,
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::NodeVersion(node_id, err))?;
+        let build_version: i64 = images::table
+            .find(node.image_id)
+            .select(images::build_version)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::NodeImage(node_id, err))?;
+        let region: Option<String> = hosts::table
+            .inner_join(regions::table)
+            .filter(hosts::id.eq(node.host_id))
+            .select(regions::key)
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::NodeRegion(node_id, err))?;
+
+        let config = Config::by_id(node.config_id, conn).await?.node_config()?;
+        let values = config
+            .image
+            .values
+            .into_iter()
+            .filter(|value| value.has_changed && !value.is_secret)
+            .map(|value| ValueSnapshot {
+                key: value.key.to_string(),
+                value: value.value,
+            })
+            .collect();
+        let image_rules = ImageRule::by_image_id(node.image_id, conn)
+            .await?
+            .into_iter()
+            .to_map_keep_last(|rule| (rule.key.clone(), FirewallRule::from(rule)));
+        let rules = config
+            .firewall
+            .rules
+            .into_iter()
+            .filter(|rule| image_rules.get(&rule.key) != Some(rule))
+            .collect();
+
+        Ok(NodeSnapshot {
+            display_name: node.display_name,
+            protocol_key,
+            variant_key,
+            semantic_version: node.semantic_version.to_string(),
+            build_version,
+            region,
+            pool,
+            placement: node.scheduler_placement,
+            auto_upgrade: node.auto_upgrade,
+            note: node.note,
+            tags: common::Tags::from(node.tags)
+                .tags
+                .into_iter()
+                .map(|tag| tag.name)
+                .collect(),
+            values,
+            rules,
+        })
+    }
+
+    /// The request to create this node in `org_id` of the current environment.
+    ///
+    /// The node uses the same image build if it exists, or otherwise the latest
+    /// build of the same protocol version.
+    pub async fn create_request(
+        &self,
+        org_id: OrgId,
+        pool_ids: &HashMap<String, HostPoolId>,
+        authz: &AuthZ,
+        conn: &mut Conn<'_>,
+    ) -> Result<api::NodeServiceCreateRequest, Error> {
+        let version_key = VersionKey {
+            protocol_key: Cow::Owned(ProtocolKey::new(self.protocol_key.clone())?),
+            variant_key: Cow::Owned(VariantKey::new(self.variant_key.clone())?),
+        };
+        let semantic_version: Version = self.semantic_version.parse()?;
+        let no_image = || {
+            Error::NoImage(
+                format!("{}/{}", self.protocol_key, self.variant_key),
+                self.semantic_version.clone(),
+            )
+        };
+
+        let version = ProtocolVersion::by_key(&version_key, Some(org_id), authz, conn)
+            .await?
+            .into_iter()
+            .find(|version| version.semantic_version == semantic_version)
+            .ok_or_else(no_image)?;
+        let image = match Image::by_build(version.id, Some(org_id), self.build_version, authz, conn)
+            .await
+        {
+            Ok(image) => image,
+            Err(_) => Image::latest_build(version.id, Some(org_id), authz, conn)
+                .await?
+                .ok_or_else(no_image)?,
+        };
+
+        let region_key = self
+            .region
+            .clone()
+            .ok_or_else(|| Error::NoRegion(self.display_name.clone()))?;
+        let region = Region::by_key(&RegionKey::new(region_key)?, conn).await?;
+
+        Ok(api::NodeServiceCreateRequest {
+            org_id: org_id.to_string(),
+            image_id: image.id.to_string(),
+            old_node_id: None,
+            launcher: Some(common::NodeLauncher {
+                launch: Some(common::node_launcher::Launch::ByRegion(common::ByRegion {
+                    region_counts: vec![common::RegionCount {
+                        region_id: region.id.to_string(),
+                        node_count: 1,
+                        resource: None,
+                        similarity: None,
+                    }],
+                })),
+            }),
+            new_values: self
+                .values
+                .iter()
+                .map(|value| api::NewImagePropertyValue {
+                    key: value.key.clone(),
+                    value: value.value.clone(),
+                })
+                .collect(),
+            add_rules: self.rules.iter().cloned().map(Into::into).collect(),
+            tags: Some(common::Tags {
+                tags: self
+                    .tags
+                    .iter()
+                    .map(|name| common::Tag { name: name.clone() })
+                    .collect(),
+            }),
+            placement_policy: common::PlacementPolicy::from(self.placement).into(),
+            pool_id: self
+                .pool
+                .as_ref()
+                .and_then(|name| pool_ids.get(name))
+                .map(ToString::to_string),
+            project_id: None,
+            validate_only: false,
+            backorder: false,
+        })
+    }
+}
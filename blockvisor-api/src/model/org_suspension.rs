@@ -0,0 +1,205 @@
+//! Suspension of orgs by an admin, for billing or abuse reasons.
+//!
+//! A suspended org may not create nodes or endpoints, and its members only keep
+//! the permissions to view the org and settle its billing, so that they can see
+//! the suspension banner but not reach any other org resources. Admin roles are
+//! unaffected.
+//!
+//! Nodes that were running when the org was suspended are optionally stopped
+//! and remembered in `org_suspended_nodes`, so that reinstating the org starts
+//! them again.
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::exists;
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::rbac::{OrgPerm, Perm};
+use crate::auth::resource::{NodeId, OrgId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::Org;
+use super::schema::{org_suspended_nodes, orgs, sql_types};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Org `{0}` is already suspended.
+    AlreadySuspended(OrgId),
+    /// Failed to check whether org `{0}` is suspended: {1}
+    IsSuspended(OrgId, diesel::result::Error),
+    /// Org `{0}` is not suspended.
+    NotSuspended(OrgId),
+    /// Failed to reinstate org `{0}`: {1}
+    Reinstate(OrgId, diesel::result::Error),
+    /// Failed to remember the stopped nodes of org `{0}`: {1}
+    StoppedNodes(OrgId, diesel::result::Error),
+    /// Failed to suspend org `{0}`: {1}
+    Suspend(OrgId, diesel::result::Error),
+    /// Failed to take the stopped nodes of org `{0}`: {1}
+    TakeStopped(OrgId, diesel::result::Error),
+    /// Unknown suspension reason.
+    UnknownReason,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            AlreadySuspended(_) => Status::failed_precondition("Org is already suspended.")
+                .with_reason("ORG_SUSPENDED"),
+            NotSuspended(_) => Status::failed_precondition("Org is not suspended.")
+                .with_reason("ORG_NOT_SUSPENDED"),
+            Reinstate(_, NotFound) | Suspend(_, NotFound) => {
+                Status::not_found("Org not found.").with_reason("ORG_NOT_FOUND")
+            }
+            UnknownReason => Status::invalid_argument("reason"),
+            IsSuspended(..) | Reinstate(..) | StoppedNodes(..) | Suspend(..) | TakeStopped(..) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumSuspensionReason"]
+pub enum SuspensionReason {
+    Billing,
+    Abuse,
+}
+
+impl From<SuspensionReason> for api::OrgSuspensionReason {
+    fn from(reason: SuspensionReason) -> Self {
+        match reason {
+            SuspensionReason::Billing => api::OrgSuspensionReason::Billing,
+            SuspensionReason::Abuse => api::OrgSuspensionReason::Abuse,
+        }
+    }
+}
+
+impl TryFrom<api::OrgSuspensionReason> for SuspensionReason {
+    type Error = Error;
+
+    fn try_from(reason: api::OrgSuspensionReason) -> Result<Self, Self::Error> {
+        match reason {
+            api::OrgSuspensionReason::Unspecified => Err(Error::UnknownReason),
+            api::OrgSuspensionReason::Billing => Ok(SuspensionReason::Billing),
+            api::OrgSuspensionReason::Abuse => Ok(SuspensionReason::Abuse),
+        }
+    }
+}
+
+/// The suspension of an org, as shown to its members.
+pub fn to_api(org: &Org) -> Option<api::OrgSuspension> {
+    let suspended_at = org.suspended_at?;
+    Some(api::OrgSuspension {
+        suspended_at: Some(NanosUtc::from(suspended_at).into()),
+        reason: org
+            .suspension_reason
+            .map(api::OrgSuspensionReason::from)
+            .unwrap_or_default()
+            .into(),
+        banner: org.suspension_banner.clone(),
+    })
+}
+
+/// Whether members of a suspended org keep `perm` from their org roles.
+pub const fn allowed_perm(perm: Perm) -> bool {
+    matches!(
+        perm,
+        Perm::Org(OrgPerm::Get | OrgPerm::List | OrgPerm::RemoveSelf)
+            | Perm::OrgAddress(_)
+            | Perm::OrgBilling(_)
+    )
+}
+
+pub async fn is_suspended(org_id: OrgId, conn: &mut Conn<'_>) -> Result<bool, Error> {
+    diesel::select(exists(
+        orgs::table
+            .find(org_id)
+            .filter(orgs::suspended_at.is_not_null()),
+    ))
+    .get_result(conn)
+    .await
+    .map_err(|err| Error::IsSuspended(org_id, err))
+}
+
+pub async fn suspend(
+    org: &Org,
+    reason: SuspensionReason,
+    banner: Option<&str>,
+    conn: &mut Conn<'_>,
+) -> Result<Org, Error> {
+    if org.suspended_at.is_some() {
+        return Err(Error::AlreadySuspended(org.id));
+    }
+
+    let now = Utc::now();
+    diesel::update(orgs::table.find(org.id))
+        .set((
+            orgs::suspended_at.eq(now),
+            orgs::suspension_reason.eq(reason),
+            orgs::suspension_banner.eq(banner),
+            orgs::updated_at.eq(now),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Suspend(org.id, err))
+}
+
+pub async fn reinstate(org: &Org, conn: &mut Conn<'_>) -> Result<Org, Error> {
+    if org.suspended_at.is_none() {
+        return Err(Error::NotSuspended(org.id));
+    }
+
+    diesel::update(orgs::table.find(org.id))
+        .set((
+            orgs::suspended_at.eq(None::<DateTime<Utc>>),
+            orgs::suspension_reason.eq(None::<SuspensionReason>),
+            orgs::suspension_banner.eq(None::<String>),
+            orgs::updated_at.eq(Utc::now()),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Reinstate(org.id, err))
+}
+
+/// Remember the nodes that were stopped by the suspension of an org.
+pub async fn stopped_nodes(
+    org_id: OrgId,
+    node_ids: &[NodeId],
+    conn: &mut Conn<'_>,
+) -> Result<(), Error> {
+    let rows: Vec<_> = node_ids
+        .iter()
+        .map(|node_id| {
+            (
+                org_suspended_nodes::node_id.eq(*node_id),
+                org_suspended_nodes::org_id.eq(org_id),
+            )
+        })
+        .collect();
+
+    diesel::insert_into(org_suspended_nodes::table)
+        .values(rows)
+        .on_conflict_do_nothing()
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|err| Error::StoppedNodes(org_id, err))
+}
+
+/// Forget and return the nodes that were stopped by the suspension of an org.
+pub async fn take_stopped_nodes(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<NodeId>, Error> {
+    diesel::delete(org_suspended_nodes::table)
+        .filter(org_suspended_nodes::org_id.eq(org_id))
+        .returning(org_suspended_nodes::node_id)
+        .get_results(conn)
+        .await
+        .map_err(|err| Error::TakeStopped(org_id, err))
+}
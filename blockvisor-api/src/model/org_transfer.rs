@@ -0,0 +1,163 @@
+//! A pending transfer of org ownership between two users.
+//!
+//! The transfer is only applied once both the current owner and the new owner
+//! have confirmed it via the link sent to them by email.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::Status;
+
+use super::schema::org_transfers;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to cancel pending transfers for org `{0}`: {1}
+    CancelPending(OrgId, diesel::result::Error),
+    /// Failed to complete org transfer `{0}`: {1}
+    Complete(OrgTransferId, diesel::result::Error),
+    /// Failed to confirm org transfer `{0}`: {1}
+    Confirm(OrgTransferId, diesel::result::Error),
+    /// Failed to create org transfer: {0}
+    Create(diesel::result::Error),
+    /// Failed to find org transfer `{0}`: {1}
+    FindById(OrgTransferId, diesel::result::Error),
+    /// User `{0}` is not a party to org transfer `{1}`.
+    NotParty(UserId, OrgTransferId),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Org transfer already pending.")
+            }
+            FindById(_, NotFound) => Status::not_found("Org transfer not found."),
+            NotParty(..) => Status::forbidden("Access denied."),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Display,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    DieselNewType,
+    Deref,
+    From,
+    FromStr,
+)]
+pub struct OrgTransferId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = org_transfers)]
+pub struct OrgTransfer {
+    pub id: OrgTransferId,
+    pub org_id: OrgId,
+    pub old_owner_id: UserId,
+    pub new_owner_id: UserId,
+    pub old_owner_confirmed_at: Option<DateTime<Utc>>,
+    pub new_owner_confirmed_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrgTransfer {
+    pub async fn by_id(id: OrgTransferId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        org_transfers::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::FindById(id, err))
+    }
+
+    /// Remove any transfer for this org that has not yet completed.
+    pub async fn cancel_pending(org_id: OrgId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let pending = org_transfers::table
+            .filter(org_transfers::org_id.eq(org_id))
+            .filter(org_transfers::completed_at.is_null());
+
+        diesel::delete(pending)
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::CancelPending(org_id, err))
+    }
+
+    /// Record the confirmation of `user_id`, who must be either party.
+    pub async fn confirm(self, user_id: UserId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        let row = org_transfers::table.find(id);
+        let now = Utc::now();
+
+        if user_id == self.old_owner_id {
+            diesel::update(row)
+                .set(org_transfers::old_owner_confirmed_at.eq(now))
+                .get_result(conn)
+                .await
+                .map_err(|err| Error::Confirm(id, err))
+        } else if user_id == self.new_owner_id {
+            diesel::update(row)
+                .set(org_transfers::new_owner_confirmed_at.eq(now))
+                .get_result(conn)
+                .await
+                .map_err(|err| Error::Confirm(id, err))
+        } else {
+            Err(Error::NotParty(user_id, id))
+        }
+    }
+
+    pub async fn complete(self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        diesel::update(org_transfers::table.find(id))
+            .set(org_transfers::completed_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Complete(id, err))
+    }
+
+    /// Both the current and the new owner have confirmed the transfer.
+    pub const fn is_confirmed(&self) -> bool {
+        self.old_owner_confirmed_at.is_some() && self.new_owner_confirmed_at.is_some()
+    }
+
+    pub const fn is_completed(&self) -> bool {
+        self.completed_at.is_some()
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = org_transfers)]
+pub struct NewOrgTransfer {
+    pub org_id: OrgId,
+    pub old_owner_id: UserId,
+    pub new_owner_id: UserId,
+}
+
+impl NewOrgTransfer {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<OrgTransfer, Error> {
+        diesel::insert_into(org_transfers::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -0,0 +1,141 @@
+//! Trial periods of orgs.
+//!
+//! An org on trial has the entitlements of its plan tier, but may run at most
+//! `trial_max_nodes` live nodes. The org owner is reminded by email before the
+//! trial ends, then the org is hibernated: its running nodes are stopped and no
+//! new nodes or endpoints may be created.
+//!
+//! Moving the org to a plan with `OrgService.UpdatePlan` converts the trial,
+//! which wakes a hibernated org.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::resource::OrgId;
+use crate::database::Conn;
+use crate::grpc::Status;
+
+use super::Org;
+use super::plan::PlanTier;
+use super::schema::orgs;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to convert the trial of org `{0}`: {1}
+    Convert(OrgId, diesel::result::Error),
+    /// Failed to find orgs with ending trials: {0}
+    Ending(diesel::result::Error),
+    /// Failed to find orgs with expired trials: {0}
+    Expired(diesel::result::Error),
+    /// Failed to hibernate org `{0}`: {1}
+    Hibernate(OrgId, diesel::result::Error),
+    /// Trial must last at least one day.
+    NoDays,
+    /// Failed to mark the trial of org `{0}` as reminded: {1}
+    Reminded(OrgId, diesel::result::Error),
+    /// Failed to start a trial for org `{0}`: {1}
+    Start(OrgId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            NoDays => Status::invalid_argument("days"),
+            Convert(..) | Ending(_) | Expired(_) | Hibernate(..) | Reminded(..) | Start(..) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+/// Put an org on a trial of `tier` for `days`, with at most `max_nodes` live
+/// nodes.
+///
+/// Starting a trial again extends it, and wakes the org if it was hibernated.
+pub async fn start(
+    org_id: OrgId,
+    tier: PlanTier,
+    days: u32,
+    max_nodes: u32,
+    conn: &mut Conn<'_>,
+) -> Result<Org, Error> {
+    if days == 0 {
+        return Err(Error::NoDays);
+    }
+
+    let now = Utc::now();
+    diesel::update(orgs::table.find(org_id))
+        .set((
+            orgs::plan_tier.eq(tier),
+            orgs::trial_ends_at.eq(now + TimeDelta::days(days.into())),
+            orgs::trial_max_nodes.eq(i64::from(max_nodes)),
+            orgs::trial_reminded_at.eq(None::<DateTime<Utc>>),
+            orgs::hibernated_at.eq(None::<DateTime<Utc>>),
+            orgs::updated_at.eq(now),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Start(org_id, err))
+}
+
+/// End the trial of an org that moved to a plan, and wake it if it was
+/// hibernated.
+pub async fn convert(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Org, Error> {
+    diesel::update(orgs::table.find(org_id))
+        .set((
+            orgs::trial_ends_at.eq(None::<DateTime<Utc>>),
+            orgs::trial_max_nodes.eq(None::<i64>),
+            orgs::trial_reminded_at.eq(None::<DateTime<Utc>>),
+            orgs::hibernated_at.eq(None::<DateTime<Utc>>),
+            orgs::updated_at.eq(Utc::now()),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Convert(org_id, err))
+}
+
+/// Orgs whose trial ends within `remind_before` and have not been reminded.
+pub async fn ending(remind_before: TimeDelta, conn: &mut Conn<'_>) -> Result<Vec<Org>, Error> {
+    let now = Utc::now();
+    orgs::table
+        .filter(orgs::deleted_at.is_null())
+        .filter(orgs::trial_ends_at.gt(now))
+        .filter(orgs::trial_ends_at.le(now + remind_before))
+        .filter(orgs::trial_reminded_at.is_null())
+        .get_results(conn)
+        .await
+        .map_err(Error::Ending)
+}
+
+pub async fn reminded(org_id: OrgId, conn: &mut Conn<'_>) -> Result<(), Error> {
+    diesel::update(orgs::table.find(org_id))
+        .set(orgs::trial_reminded_at.eq(Utc::now()))
+        .execute(conn)
+        .await
+        .map(|_| ())
+        .map_err(|err| Error::Reminded(org_id, err))
+}
+
+/// Orgs whose trial has ended but that are not yet hibernated.
+pub async fn expired(conn: &mut Conn<'_>) -> Result<Vec<Org>, Error> {
+    orgs::table
+        .filter(orgs::deleted_at.is_null())
+        .filter(orgs::trial_ends_at.le(Utc::now()))
+        .filter(orgs::hibernated_at.is_null())
+        .get_results(conn)
+        .await
+        .map_err(Error::Expired)
+}
+
+pub async fn hibernate(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Org, Error> {
+    let now = Utc::now();
+    diesel::update(orgs::table.find(org_id))
+        .set((orgs::hibernated_at.eq(now), orgs::updated_at.eq(now)))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Hibernate(org_id, err))
+}
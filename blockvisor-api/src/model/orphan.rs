@@ -0,0 +1,148 @@
+//! External resources left behind by failed deletes.
+//!
+//! Node and endpoint deletes only warn when an external call fails, so DNS
+//! records and stripe subscription items can outlive the rows that referred
+//! to them. These are found by comparing each external service against the
+//! live rows, ignoring anything younger than `MIN_AGE` that may still be in
+//! the middle of being created.
+//!
+//! Node secrets are not checked, as they are kept in the database and removed
+//! by the `RemoveSecrets` step of `NodeCleanup`.
+
+use std::collections::HashSet;
+
+use chrono::{TimeDelta, Utc};
+use displaydoc::Display;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::auth::resource::OrgId;
+use crate::cloudflare::Dns;
+use crate::cloudflare::api::dns::DnsRecord;
+use crate::database::Conn;
+use crate::encryption::Keyring;
+use crate::grpc::Status;
+use crate::stripe::Subscription;
+use crate::stripe::api::subscription::SubscriptionItem;
+
+use super::endpoint::PoolMember;
+use super::{Endpoint, Node, Org};
+
+/// Resources younger than this may still be in the middle of being created.
+pub const MIN_AGE: TimeDelta = TimeDelta::hours(1);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Orphan cloudflare error: {0}
+    Cloudflare(#[from] crate::cloudflare::Error),
+    /// Orphan endpoint error: {0}
+    Endpoint(#[from] crate::model::endpoint::Error),
+    /// Orphan node error: {0}
+    Node(#[from] crate::model::node::Error),
+    /// Orphan org error: {0}
+    Org(#[from] crate::model::org::Error),
+    /// Orphan endpoint pool error: {0}
+    Pool(#[from] crate::model::endpoint::pool::Error),
+    /// Orphan stripe error: {0}
+    Stripe(#[from] crate::stripe::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Cloudflare(_) | Stripe(_) => Status::internal("Internal error."),
+            Endpoint(err) => err.into(),
+            Node(err) => err.into(),
+            Org(err) => err.into(),
+            Pool(err) => err.into(),
+        }
+    }
+}
+
+/// A stripe subscription item that no node or endpoint refers to.
+#[derive(Debug)]
+pub struct OrphanedItem {
+    pub org_id: OrgId,
+    pub item: SubscriptionItem,
+}
+
+/// Find the node DNS records that no node or endpoint pool refers to.
+pub async fn dns_records(
+    dns: &(dyn Dns + Send + Sync),
+    conn: &mut Conn<'_>,
+) -> Result<Vec<DnsRecord>, Error> {
+    let mut dns_ids = Node::dns_ids(conn).await?;
+    dns_ids.extend(PoolMember::dns_ids(conn).await?);
+    let cutoff = Utc::now() - MIN_AGE;
+
+    let records = dns.list().await?;
+    Ok(records
+        .into_iter()
+        .filter(|record| record.created_on < cutoff && !dns_ids.contains(&record.id))
+        .collect())
+}
+
+/// Find the stripe subscription items that no node or endpoint refers to.
+pub async fn stripe_items(
+    stripe: &(dyn Subscription + Send + Sync),
+    keyring: &Keyring,
+    conn: &mut Conn<'_>,
+) -> Result<Vec<OrphanedItem>, Error> {
+    let mut item_ids: HashSet<_> = Node::stripe_item_ids(conn).await?;
+    item_ids.extend(Endpoint::stripe_item_ids(conn).await?);
+
+    let cutoff = (Utc::now() - MIN_AGE).timestamp();
+    let mut orphans = Vec::new();
+    for org in Org::customers(conn).await? {
+        let Some(customer_id) = org.customer_id(keyring)? else {
+            continue;
+        };
+        let Some(subscription) = stripe.get_subscription_by_customer(&customer_id).await? else {
+            continue;
+        };
+
+        for item in subscription.items.data {
+            let settled = item.created.is_none_or(|created| created.0 < cutoff);
+            if !item.deleted && settled && !item_ids.contains(&item.id) {
+                orphans.push(OrphanedItem {
+                    org_id: org.id,
+                    item,
+                });
+            }
+        }
+    }
+
+    Ok(orphans)
+}
+
+/// Delete orphaned DNS records, returning how many were deleted.
+///
+/// Each record is deleted independently so that one failure doesn't stop
+/// the others.
+pub async fn delete_dns_records(dns: &(dyn Dns + Send + Sync), records: &[DnsRecord]) -> u64 {
+    let mut deleted = 0;
+    for record in records {
+        match dns.delete(&record.id).await {
+            Ok(()) => deleted += 1,
+            Err(err) => warn!("Failed to delete orphaned DNS record {}: {err}", record.id),
+        }
+    }
+    deleted
+}
+
+/// Delete orphaned stripe subscription items, returning how many were deleted.
+pub async fn delete_stripe_items(
+    stripe: &(dyn Subscription + Send + Sync),
+    orphans: &[OrphanedItem],
+) -> u64 {
+    let mut deleted = 0;
+    for orphan in orphans {
+        let item_id = &orphan.item.id;
+        match stripe.delete_subscription_item(item_id).await {
+            Ok(()) => deleted += 1,
+            Err(err) => warn!("Failed to delete orphaned stripe item {item_id}: {err}"),
+        }
+    }
+    deleted
+}
@@ -0,0 +1,246 @@
+//! Partners that refer new users through a referral code.
+//!
+//! A user who signs up with a referral code has their personal org attributed
+//! to the partner, as are the orgs they create afterwards. The revenue of a
+//! partner is the metered cost of the nodes of its attributed orgs, prorated
+//! over the time each node was live within the month.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::OrgId;
+use crate::database::Conn;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::Org;
+use super::node::slo::month_bounds;
+use super::schema::{nodes, orgs, partners};
+use super::sql::{Amount, Currency, Period};
+
+/// The most characters in a referral code.
+const MAX_REFERRAL_CODE_LEN: usize = 32;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to attribute org `{0}` to partner `{1}`: {2}
+    Attribute(OrgId, PartnerId, diesel::result::Error),
+    /// Failed to find partner `{0}`: {1}
+    ById(PartnerId, diesel::result::Error),
+    /// Failed to find partner by referral code: {0}
+    ByReferralCode(diesel::result::Error),
+    /// Failed to create partner: {0}
+    Create(diesel::result::Error),
+    /// Failed to list partners: {0}
+    List(diesel::result::Error),
+    /// Partner revenue month: {0}
+    Month(#[from] super::node::slo::Error),
+    /// Failed to find the orgs of partner `{0}`: {1}
+    Orgs(PartnerId, diesel::result::Error),
+    /// Referral code `{0}` must be 1 to 32 letters, digits or `-`.
+    ReferralCode(String),
+    /// Failed to find the node costs of partner `{0}`: {1}
+    Usage(PartnerId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) => Status::not_found("Partner not found."),
+            ByReferralCode(NotFound) | ReferralCode(_) => Status::invalid_argument("referral_code"),
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Referral code already exists.")
+            }
+            Month(err) => err.into(),
+            Attribute(..) | ById(..) | ByReferralCode(_) | Create(_) | List(_) | Orgs(..)
+            | Usage(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct PartnerId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Partner {
+    pub id: PartnerId,
+    pub name: String,
+    pub referral_code: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Partner {
+    pub async fn by_id(id: PartnerId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        partners::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// Find a partner by its case insensitive referral code.
+    pub async fn by_referral_code(code: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        partners::table
+            .filter(partners::referral_code.eq(code.trim().to_lowercase()))
+            .get_result(conn)
+            .await
+            .map_err(Error::ByReferralCode)
+    }
+
+    pub async fn list(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        partners::table
+            .order_by(partners::name)
+            .get_results(conn)
+            .await
+            .map_err(Error::List)
+    }
+
+    /// Attribute an org to this partner, unless it was already referred.
+    pub async fn attribute(&self, org_id: OrgId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(orgs::table.find(org_id))
+            .filter(orgs::partner_id.is_null())
+            .set((
+                orgs::partner_id.eq(self.id),
+                orgs::referred_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Attribute(org_id, self.id, err))
+    }
+
+    /// The revenue of each org attributed to this partner over the month
+    /// containing `month`, measured up until now.
+    pub async fn revenue(
+        &self,
+        month: NaiveDate,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<OrgRevenue>, Error> {
+        let (start, end) = month_bounds(month)?;
+        let orgs: Vec<Org> = orgs::table
+            .filter(orgs::partner_id.eq(self.id))
+            .order_by(orgs::name)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Orgs(self.id, err))?;
+        let org_ids: Vec<_> = orgs.iter().map(|org| org.id).collect();
+
+        let usage: Vec<(OrgId, Option<Amount>, DateTime<Utc>, Option<DateTime<Utc>>)> =
+            nodes::table
+                .filter(nodes::org_id.eq_any(&org_ids))
+                .filter(nodes::cost.is_not_null())
+                .filter(nodes::created_at.lt(end))
+                .filter(nodes::deleted_at.is_null().or(nodes::deleted_at.gt(start)))
+                .select((
+                    nodes::org_id,
+                    nodes::cost,
+                    nodes::created_at,
+                    nodes::deleted_at,
+                ))
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::Usage(self.id, err))?;
+
+        let month_seconds = (end - start).num_seconds();
+        let until = end.min(Utc::now());
+        let mut amounts: HashMap<OrgId, i64> = HashMap::new();
+        for (org_id, cost, created_at, deleted_at) in usage {
+            let Some(cost) = cost else { continue };
+            let from = created_at.max(start);
+            let to = deleted_at.map_or(until, |deleted| deleted.min(until));
+            let live_seconds = (to - from).num_seconds().max(0);
+            let prorated =
+                i128::from(cost.amount) * i128::from(live_seconds) / i128::from(month_seconds);
+            let total = amounts.entry(org_id).or_default();
+            *total = total.saturating_add(i64::try_from(prorated).unwrap_or(i64::MAX));
+        }
+
+        Ok(orgs
+            .into_iter()
+            .map(|org| OrgRevenue {
+                amount: amounts.get(&org.id).copied().unwrap_or_default(),
+                org,
+            })
+            .collect())
+    }
+}
+
+impl From<Partner> for api::Partner {
+    fn from(partner: Partner) -> Self {
+        api::Partner {
+            partner_id: partner.id.to_string(),
+            name: partner.name,
+            referral_code: partner.referral_code,
+            created_at: Some(NanosUtc::from(partner.created_at).into()),
+        }
+    }
+}
+
+/// The revenue of an attributed org in minor units of `Currency::Usd`.
+#[derive(Clone, Debug)]
+pub struct OrgRevenue {
+    pub org: Org,
+    pub amount: i64,
+}
+
+impl From<OrgRevenue> for api::PartnerOrgRevenue {
+    fn from(revenue: OrgRevenue) -> Self {
+        api::PartnerOrgRevenue {
+            org_id: revenue.org.id.to_string(),
+            org_name: revenue.org.name,
+            referred_at: revenue.org.referred_at.map(NanosUtc::from).map(Into::into),
+            revenue: Some(usd(revenue.amount)),
+        }
+    }
+}
+
+/// A monthly amount in minor units of USD.
+pub fn usd(amount: i64) -> common::BillingAmount {
+    Amount {
+        amount,
+        currency: Currency::Usd,
+        period: Period::Monthly,
+    }
+    .into()
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = partners)]
+pub struct NewPartner {
+    name: String,
+    referral_code: String,
+}
+
+impl NewPartner {
+    pub fn new(name: String, referral_code: &str) -> Result<Self, Error> {
+        let code = referral_code.trim().to_lowercase();
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-';
+        if code.is_empty() || code.len() > MAX_REFERRAL_CODE_LEN || !code.chars().all(valid) {
+            return Err(Error::ReferralCode(code));
+        }
+
+        Ok(NewPartner {
+            name,
+            referral_code: code,
+        })
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Partner, Error> {
+        diesel::insert_into(partners::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -0,0 +1,415 @@
+//! Plan tiers and the entitlements that they give an org.
+//!
+//! Each org is on one plan, which limits the number of its live nodes and
+//! endpoints, the protocols it may run, whether it may register private hosts,
+//! and whether it may brand its emails and status pages. The `Entitlements` of an org are checked when creating nodes, private
+//! hosts and endpoints, and against its current usage before the org moves to
+//! another plan.
+//!
+//! Paid plans are billed as a subscription item for the `stripe_sku` of the
+//! plan. Orgs on trial are further limited, as described in `org_trial`.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::resource::OrgId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::protocol::ProtocolId;
+use super::schema::{hosts, node_endpoints, nodes, orgs, plans, sql_types};
+
+/// The status reason of an action that needs a larger plan.
+const PLAN_LIMIT: &str = "PLAN_LIMIT";
+/// The status reason of an action that needs a hibernated org to pick a plan.
+const TRIAL_ENDED: &str = "TRIAL_ENDED";
+/// The status reason of an action that needs a suspended org to be reinstated.
+const ORG_SUSPENDED: &str = "ORG_SUSPENDED";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Plan `{0:?}` does not include custom branding.
+    Branding(PlanTier),
+    /// Failed to find plan `{0:?}`: {1}
+    ByTier(PlanTier, diesel::result::Error),
+    /// Failed to count endpoints of org `{0}`: {1}
+    EndpointCount(OrgId, diesel::result::Error),
+    /// Plan `{0:?}` allows at most {1} endpoints.
+    Endpoints(PlanTier, i64),
+    /// Failed to find the plan of org `{0}`: {1}
+    ForOrg(OrgId, diesel::result::Error),
+    /// Org `{0}` is hibernated since its trial ended.
+    Hibernated(OrgId),
+    /// Failed to count private hosts of org `{0}`: {1}
+    HostCount(OrgId, diesel::result::Error),
+    /// Failed to list plans: {0}
+    List(diesel::result::Error),
+    /// Failed to count nodes of org `{0}`: {1}
+    NodeCount(OrgId, diesel::result::Error),
+    /// Plan `{0:?}` allows at most {1} nodes.
+    Nodes(PlanTier, i64),
+    /// Plan `{0:?}` does not include private hosts.
+    PrivateHosts(PlanTier),
+    /// Plan `{0:?}` does not include protocol `{1}`.
+    Protocol(PlanTier, ProtocolId),
+    /// Org `{0}` is suspended.
+    Suspended(OrgId),
+    /// Unknown plan tier.
+    UnknownTier,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Branding(_) => {
+                Status::failed_precondition("Custom branding is not included in the plan.")
+                    .with_reason(PLAN_LIMIT)
+            }
+            ByTier(_, NotFound) => Status::not_found("Plan not found."),
+            Endpoints(_, max) => {
+                Status::failed_precondition(format!("Plan limit of {max} endpoints reached."))
+                    .with_reason(PLAN_LIMIT)
+            }
+            Hibernated(_) => {
+                Status::failed_precondition("Org trial has ended.").with_reason(TRIAL_ENDED)
+            }
+            Nodes(_, max) => {
+                Status::failed_precondition(format!("Plan limit of {max} nodes reached."))
+                    .with_reason(PLAN_LIMIT)
+            }
+            PrivateHosts(_) => {
+                Status::failed_precondition("Private hosts are not included in the plan.")
+                    .with_reason(PLAN_LIMIT)
+            }
+            Protocol(..) => Status::failed_precondition("Protocol is not included in the plan.")
+                .with_reason(PLAN_LIMIT),
+            Suspended(_) => {
+                Status::failed_precondition("Org is suspended.").with_reason(ORG_SUSPENDED)
+            }
+            UnknownTier => Status::invalid_argument("tier"),
+            ByTier(..) | EndpointCount(..) | ForOrg(..) | HostCount(..) | List(_)
+            | NodeCount(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumPlanTier"]
+pub enum PlanTier {
+    #[default]
+    Free,
+    Pro,
+    Enterprise,
+}
+
+impl From<PlanTier> for api::PlanTier {
+    fn from(tier: PlanTier) -> Self {
+        match tier {
+            PlanTier::Free => api::PlanTier::Free,
+            PlanTier::Pro => api::PlanTier::Pro,
+            PlanTier::Enterprise => api::PlanTier::Enterprise,
+        }
+    }
+}
+
+impl TryFrom<api::PlanTier> for PlanTier {
+    type Error = Error;
+
+    fn try_from(tier: api::PlanTier) -> Result<Self, Self::Error> {
+        match tier {
+            api::PlanTier::Unspecified => Err(Error::UnknownTier),
+            api::PlanTier::Free => Ok(PlanTier::Free),
+            api::PlanTier::Pro => Ok(PlanTier::Pro),
+            api::PlanTier::Enterprise => Ok(PlanTier::Enterprise),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumSupportLevel"]
+pub enum SupportLevel {
+    Community,
+    Standard,
+    Priority,
+}
+
+impl From<SupportLevel> for api::SupportLevel {
+    fn from(level: SupportLevel) -> Self {
+        match level {
+            SupportLevel::Community => api::SupportLevel::Community,
+            SupportLevel::Standard => api::SupportLevel::Standard,
+            SupportLevel::Priority => api::SupportLevel::Priority,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Plan {
+    pub tier: PlanTier,
+    pub name: String,
+    /// The maximum number of live nodes of an org, if limited.
+    pub max_nodes: Option<i64>,
+    /// The maximum number of unrevoked endpoints of an org, if limited.
+    pub max_endpoints: Option<i64>,
+    /// The protocols that an org may run nodes of, or all if `None`.
+    pub protocol_ids: Option<Vec<Option<ProtocolId>>>,
+    pub private_hosts: bool,
+    pub support_level: SupportLevel,
+    /// The stripe price of a paid plan.
+    pub stripe_sku: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Whether the org may brand its emails and status pages.
+    pub custom_branding: bool,
+}
+
+impl Plan {
+    pub async fn by_tier(tier: PlanTier, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        plans::table
+            .find(tier)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByTier(tier, err))
+    }
+
+    /// All plans, from the smallest tier to the largest.
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        plans::table
+            .order_by(plans::tier)
+            .get_results(conn)
+            .await
+            .map_err(Error::List)
+    }
+
+    pub async fn for_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        orgs::table
+            .find(org_id)
+            .inner_join(plans::table)
+            .select(Plan::as_select())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ForOrg(org_id, err))
+    }
+
+    fn protocol_ids(&self) -> Option<HashSet<ProtocolId>> {
+        self.protocol_ids
+            .as_ref()
+            .map(|ids| ids.iter().flatten().copied().collect())
+    }
+}
+
+impl From<Plan> for api::Plan {
+    fn from(plan: Plan) -> Self {
+        let protocol_ids = plan
+            .protocol_ids()
+            .map(|ids| ids.into_iter().map(|id| id.to_string()).collect())
+            .unwrap_or_default();
+
+        api::Plan {
+            tier: api::PlanTier::from(plan.tier).into(),
+            name: plan.name,
+            max_nodes: plan.max_nodes.and_then(|max| u64::try_from(max).ok()),
+            max_endpoints: plan.max_endpoints.and_then(|max| u64::try_from(max).ok()),
+            protocol_ids,
+            private_hosts: plan.private_hosts,
+            support_level: api::SupportLevel::from(plan.support_level).into(),
+            custom_branding: plan.custom_branding,
+            created_at: Some(NanosUtc::from(plan.created_at).into()),
+            updated_at: Some(NanosUtc::from(plan.updated_at).into()),
+        }
+    }
+}
+
+/// What the plan of an org allows it to create.
+///
+/// Counted limits are checked after creating, so that the transaction is rolled
+/// back if the org went over its plan.
+#[derive(Clone, Debug)]
+pub struct Entitlements {
+    pub org_id: OrgId,
+    pub plan: Plan,
+    /// The most live nodes of an org on trial.
+    pub trial_max_nodes: Option<i64>,
+    pub hibernated: bool,
+    pub suspended: bool,
+}
+
+impl Entitlements {
+    /// The entitlements of an org once it has moved to `plan`.
+    pub const fn new(org_id: OrgId, plan: Plan) -> Self {
+        Entitlements {
+            org_id,
+            plan,
+            trial_max_nodes: None,
+            hibernated: false,
+            suspended: false,
+        }
+    }
+
+    pub async fn for_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let (plan, trial_max_nodes, hibernated_at, suspended_at) = orgs::table
+            .find(org_id)
+            .inner_join(plans::table)
+            .select((
+                Plan::as_select(),
+                orgs::trial_max_nodes,
+                orgs::hibernated_at,
+                orgs::suspended_at,
+            ))
+            .get_result::<(
+                Plan,
+                Option<i64>,
+                Option<DateTime<Utc>>,
+                Option<DateTime<Utc>>,
+            )>(conn)
+            .await
+            .map_err(|err| Error::ForOrg(org_id, err))?;
+
+        Ok(Entitlements {
+            org_id,
+            plan,
+            trial_max_nodes,
+            hibernated: hibernated_at.is_some(),
+            suspended: suspended_at.is_some(),
+        })
+    }
+
+    /// Returns an error if the org was hibernated at the end of its trial, or
+    /// was suspended by an admin.
+    pub const fn check_awake(&self) -> Result<(), Error> {
+        if self.suspended {
+            Err(Error::Suspended(self.org_id))
+        } else if self.hibernated {
+            Err(Error::Hibernated(self.org_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The most live nodes of the org, if limited by its plan or trial.
+    fn max_nodes(&self) -> Option<i64> {
+        match (self.plan.max_nodes, self.trial_max_nodes) {
+            (Some(plan), Some(trial)) => Some(plan.min(trial)),
+            (plan, trial) => plan.or(trial),
+        }
+    }
+
+    pub fn check_protocol(&self, protocol_id: ProtocolId) -> Result<(), Error> {
+        match self.plan.protocol_ids() {
+            Some(ids) if !ids.contains(&protocol_id) => {
+                Err(Error::Protocol(self.plan.tier, protocol_id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub const fn check_private_hosts(&self) -> Result<(), Error> {
+        if self.plan.private_hosts {
+            Ok(())
+        } else {
+            Err(Error::PrivateHosts(self.plan.tier))
+        }
+    }
+
+    pub const fn check_branding(&self) -> Result<(), Error> {
+        if self.plan.custom_branding {
+            Ok(())
+        } else {
+            Err(Error::Branding(self.plan.tier))
+        }
+    }
+
+    /// Check that the live nodes of the org are within the plan.
+    pub async fn check_nodes(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        self.check_added_nodes(0, conn).await
+    }
+
+    /// Check that the live nodes of the org plus `added` nodes are within the
+    /// plan.
+    pub async fn check_added_nodes(&self, added: i64, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let Some(max) = self.max_nodes() else {
+            return Ok(());
+        };
+
+        let count: i64 = nodes::table
+            .filter(nodes::org_id.eq(self.org_id))
+            .filter(nodes::deleted_at.is_null())
+            .select(count_star())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::NodeCount(self.org_id, err))?;
+
+        if count + added > max {
+            Err(Error::Nodes(self.plan.tier, max))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the unrevoked endpoints of the org are within the plan.
+    pub async fn check_endpoints(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let Some(max) = self.plan.max_endpoints else {
+            return Ok(());
+        };
+
+        let count: i64 = node_endpoints::table
+            .filter(node_endpoints::org_id.eq(self.org_id))
+            .filter(node_endpoints::revoked_at.is_null())
+            .select(count_star())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::EndpointCount(self.org_id, err))?;
+
+        if count > max {
+            Err(Error::Endpoints(self.plan.tier, max))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that everything the org already runs is within the plan, before
+    /// it moves to this plan.
+    pub async fn check_usage(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        self.check_nodes(conn).await?;
+        self.check_endpoints(conn).await?;
+
+        if !self.plan.private_hosts {
+            let count: i64 = hosts::table
+                .filter(hosts::org_id.eq(self.org_id))
+                .filter(hosts::deleted_at.is_null())
+                .select(count_star())
+                .get_result(conn)
+                .await
+                .map_err(|err| Error::HostCount(self.org_id, err))?;
+            if count > 0 {
+                return Err(Error::PrivateHosts(self.plan.tier));
+            }
+        }
+
+        if let Some(ids) = self.plan.protocol_ids() {
+            let running: Vec<ProtocolId> = nodes::table
+                .filter(nodes::org_id.eq(self.org_id))
+                .filter(nodes::deleted_at.is_null())
+                .select(nodes::protocol_id)
+                .distinct()
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::NodeCount(self.org_id, err))?;
+            if let Some(id) = running.into_iter().find(|id| !ids.contains(id)) {
+                return Err(Error::Protocol(self.plan.tier, id));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Projects that separate the nodes of an org into environments.
+//!
+//! A project groups nodes of one org, so that teams can keep their staging and
+//! production fleets apart under a single billing org. A project may limit the
+//! number of its live nodes with `node_quota`.
+//!
+//! Users bound to a project as members, or through one of its teams, are the
+//! only org members that may create or delete its nodes. A project without
+//! members or teams is open to the whole org, and users that can manage
+//! projects are never restricted.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::{ForeignKeyViolation, UniqueViolation};
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::AuthZ;
+use crate::auth::rbac::OrgPerm;
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::TeamId;
+use super::schema::{nodes, project_members, project_teams, projects, team_members};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to add member to project `{0}`: {1}
+    AddMember(ProjectId, diesel::result::Error),
+    /// Failed to find project `{0}`: {1}
+    ById(ProjectId, diesel::result::Error),
+    /// Failed to find projects for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Failed to create project: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete project `{0}`: {1}
+    Delete(ProjectId, diesel::result::Error),
+    /// Project name must not be empty.
+    EmptyName,
+    /// Failed to find project members: {0}
+    Members(diesel::result::Error),
+    /// Project node quota must not be negative.
+    NegativeQuota,
+    /// Failed to count nodes of project `{0}`: {1}
+    NodeCount(ProjectId, diesel::result::Error),
+    /// User is not a member of project `{0}`.
+    NotMember(ProjectId),
+    /// Project `{0}` has reached its quota of {1} nodes.
+    Quota(ProjectId, i64),
+    /// Failed to remove member from project `{0}`: {1}
+    RemoveMember(ProjectId, diesel::result::Error),
+    /// Failed to find the teams of project `{0}`: {1}
+    Teams(ProjectId, diesel::result::Error),
+    /// Failed to update project `{0}`: {1}
+    Update(ProjectId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _))
+            | Update(_, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Project already exists.")
+            }
+            AddMember(_, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("User is already a project member.")
+            }
+            AddMember(_, DatabaseError(ForeignKeyViolation, _)) => {
+                Status::not_found("User not found.")
+            }
+            ById(_, NotFound) | Delete(_, NotFound) | Update(_, NotFound) => {
+                Status::not_found("Project not found.")
+            }
+            RemoveMember(_, NotFound) => Status::not_found("User is not a project member."),
+            EmptyName => Status::invalid_argument("name"),
+            NegativeQuota => Status::invalid_argument("node_quota"),
+            NotMember(_) => Status::forbidden("Access denied."),
+            Quota(_, quota) => {
+                Status::failed_precondition(format!("Project node quota of {quota} reached."))
+            }
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ProjectId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Project {
+    pub id: ProjectId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub description: Option<String>,
+    /// The maximum number of live nodes in the project, if limited.
+    pub node_quota: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Project {
+    pub async fn by_id(id: ProjectId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        projects::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        projects::table
+            .filter(projects::org_id.eq(org_id))
+            .order_by(projects::name)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Delete a project, which leaves its nodes in the org without a project.
+    pub async fn delete(id: ProjectId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let deleted = diesel::delete(projects::table.find(id))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Delete(id, err))?;
+
+        if deleted == 0 {
+            Err(Error::Delete(id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The user ids bound to each project.
+    pub async fn member_ids(
+        project_ids: &HashSet<ProjectId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<ProjectId, Vec<UserId>>, Error> {
+        let members: Vec<(ProjectId, UserId)> = project_members::table
+            .filter(project_members::project_id.eq_any(project_ids))
+            .select((project_members::project_id, project_members::user_id))
+            .order_by(project_members::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::Members)?;
+
+        let mut member_ids: HashMap<ProjectId, Vec<UserId>> = HashMap::new();
+        for (project_id, user_id) in members {
+            member_ids.entry(project_id).or_default().push(user_id);
+        }
+        Ok(member_ids)
+    }
+
+    pub async fn add_member(
+        id: ProjectId,
+        user_id: UserId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(project_members::table)
+            .values((
+                project_members::project_id.eq(id),
+                project_members::user_id.eq(user_id),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::AddMember(id, err))
+    }
+
+    pub async fn remove_member(
+        id: ProjectId,
+        user_id: UserId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let removed = diesel::delete(project_members::table.find((id, user_id)))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::RemoveMember(id, err))?;
+
+        if removed == 0 {
+            Err(Error::RemoveMember(id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the authorized resource may change the nodes of this
+    /// project.
+    ///
+    /// Only users are bound to projects, so org and host tokens are never
+    /// restricted.
+    pub async fn authorize(&self, authz: &AuthZ, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let Some(user_id) = authz.resource().user() else {
+            return Ok(());
+        };
+        if authz.has_perm(OrgPerm::UpdateProject) {
+            return Ok(());
+        }
+
+        let members = Self::member_ids(&HashSet::from([self.id]), conn).await?;
+        let team_ids: Vec<TeamId> = project_teams::table
+            .filter(project_teams::project_id.eq(self.id))
+            .select(project_teams::team_id)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Teams(self.id, err))?;
+
+        let user_ids = members.get(&self.id);
+        let open = user_ids.is_none() && team_ids.is_empty();
+        if open || user_ids.is_some_and(|user_ids| user_ids.contains(&user_id)) {
+            return Ok(());
+        }
+
+        let in_team = diesel::select(diesel::dsl::exists(
+            team_members::table
+                .filter(team_members::team_id.eq_any(&team_ids))
+                .filter(team_members::user_id.eq(user_id)),
+        ))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Teams(self.id, err))?;
+
+        if in_team {
+            Ok(())
+        } else {
+            Err(Error::NotMember(self.id))
+        }
+    }
+
+    /// Check that the live nodes of the project are within its node quota.
+    ///
+    /// This is checked after nodes are created, so that the transaction is
+    /// rolled back if they went over the quota.
+    pub async fn check_quota(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        self.check_added_quota(0, conn).await
+    }
+
+    /// Check that the live nodes of the project plus `added` nodes are within
+    /// its node quota.
+    pub async fn check_added_quota(&self, added: i64, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let Some(quota) = self.node_quota else {
+            return Ok(());
+        };
+
+        let count: i64 = nodes::table
+            .filter(nodes::project_id.eq(self.id))
+            .filter(nodes::deleted_at.is_null())
+            .select(count_star())
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::NodeCount(self.id, err))?;
+
+        if count + added > quota {
+            Err(Error::Quota(self.id, quota))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn into_api(self, member_ids: &[UserId]) -> api::Project {
+        api::Project {
+            project_id: self.id.to_string(),
+            org_id: self.org_id.to_string(),
+            name: self.name,
+            description: self.description,
+            node_quota: self.node_quota.and_then(|quota| u64::try_from(quota).ok()),
+            member_ids: member_ids.iter().map(ToString::to_string).collect(),
+            created_at: Some(NanosUtc::from(self.created_at).into()),
+            updated_at: Some(NanosUtc::from(self.updated_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = projects)]
+pub struct NewProject<'p> {
+    pub org_id: OrgId,
+    pub name: &'p str,
+    pub description: Option<&'p str>,
+    pub node_quota: Option<i64>,
+}
+
+impl NewProject<'_> {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Project, Error> {
+        if self.name.trim().is_empty() {
+            return Err(Error::EmptyName);
+        }
+        if self.node_quota.is_some_and(|quota| quota < 0) {
+            return Err(Error::NegativeQuota);
+        }
+
+        diesel::insert_into(projects::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = projects)]
+pub struct UpdateProject<'p> {
+    pub name: Option<&'p str>,
+    pub description: Option<&'p str>,
+    pub node_quota: Option<Option<i64>>,
+}
+
+impl UpdateProject<'_> {
+    pub async fn apply(self, id: ProjectId, conn: &mut Conn<'_>) -> Result<Project, Error> {
+        if self.name.is_some_and(|name| name.trim().is_empty()) {
+            return Err(Error::EmptyName);
+        }
+        if self.node_quota.flatten().is_some_and(|quota| quota < 0) {
+            return Err(Error::NegativeQuota);
+        }
+
+        diesel::update(projects::table.find(id))
+            .set((self, projects::updated_at.eq(Utc::now())))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+}
@@ -0,0 +1,163 @@
+//! Grants of access to org-owned protocols and versions for other orgs.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel::sql_types::{Bool, Nullable};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::OrgId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{protocol_grants, protocol_versions, protocols};
+use crate::util::NanosUtc;
+
+use super::{ProtocolId, VersionId};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find protocol grant `{0}`: {1}
+    ById(ProtocolGrantId, diesel::result::Error),
+    /// Failed to find grants for protocol `{0}`: {1}
+    ByProtocol(ProtocolId, diesel::result::Error),
+    /// Failed to create protocol grant: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete protocol grant `{0}`: {1}
+    Delete(ProtocolGrantId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) => Status::not_found("Protocol grant not found."),
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Protocol grant already exists.")
+            }
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ProtocolGrantId(Uuid);
+
+/// Access for an org to a protocol owned by another org.
+///
+/// A grant without a `protocol_version_id` covers every version of the
+/// protocol, otherwise only that version.
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = protocol_grants)]
+pub struct ProtocolGrant {
+    pub id: ProtocolGrantId,
+    pub org_id: OrgId,
+    pub protocol_id: ProtocolId,
+    pub protocol_version_id: Option<VersionId>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ProtocolGrant {
+    pub async fn by_id(id: ProtocolGrantId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        protocol_grants::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_protocol(
+        protocol_id: ProtocolId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        protocol_grants::table
+            .filter(protocol_grants::protocol_id.eq(protocol_id))
+            .order_by(protocol_grants::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByProtocol(protocol_id, err))
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let id = self.id;
+        diesel::delete(protocol_grants::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+}
+
+impl From<ProtocolGrant> for api::ProtocolGrant {
+    fn from(grant: ProtocolGrant) -> Self {
+        api::ProtocolGrant {
+            grant_id: grant.id.to_string(),
+            org_id: grant.org_id.to_string(),
+            protocol_id: grant.protocol_id.to_string(),
+            protocol_version_id: grant.protocol_version_id.map(|id| id.to_string()),
+            created_at: Some(NanosUtc::from(grant.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = protocol_grants)]
+pub struct NewProtocolGrant {
+    pub org_id: OrgId,
+    pub protocol_id: ProtocolId,
+    pub protocol_version_id: Option<VersionId>,
+}
+
+impl NewProtocolGrant {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<ProtocolGrant, Error> {
+        diesel::insert_into(protocol_grants::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+/// Protocols without an org, owned by one of `org_ids`, or granted to them.
+pub(crate) fn visible_protocols(
+    org_ids: Vec<OrgId>,
+) -> Box<dyn BoxableExpression<protocols::table, Pg, SqlType = Nullable<Bool>>> {
+    let granted = protocol_grants::table
+        .filter(protocol_grants::org_id.eq_any(org_ids.clone()))
+        .select(protocol_grants::protocol_id);
+
+    Box::new(
+        protocols::org_id
+            .eq_any(org_ids)
+            .or(protocols::org_id.is_null())
+            .or(protocols::id.eq_any(granted)),
+    )
+}
+
+/// Versions without an org, owned by one of `org_ids`, or granted to them
+/// either directly or through a grant of the whole protocol.
+pub(crate) fn visible_versions(
+    org_ids: Vec<OrgId>,
+) -> Box<dyn BoxableExpression<protocol_versions::table, Pg, SqlType = Nullable<Bool>>> {
+    let granted_protocols = protocol_grants::table
+        .filter(protocol_grants::org_id.eq_any(org_ids.clone()))
+        .filter(protocol_grants::protocol_version_id.is_null())
+        .select(protocol_grants::protocol_id);
+    let granted_versions = protocol_grants::table
+        .filter(protocol_grants::org_id.eq_any(org_ids.clone()))
+        .select(protocol_grants::protocol_version_id);
+
+    Box::new(
+        protocol_versions::org_id
+            .eq_any(org_ids)
+            .or(protocol_versions::org_id.is_null())
+            .or(protocol_versions::protocol_id.eq_any(granted_protocols))
+            .or(protocol_versions::id.nullable().eq_any(granted_versions)),
+    )
+}
@@ -0,0 +1,326 @@
+//! The latest block of each protocol network.
+//!
+//! A network is a protocol variant (e.g. ethereum `mainnet`), which is shared
+//! by every version of that variant. Its head is refreshed periodically:
+//! - from its `rpc_url` when one is configured, which must answer the
+//!   `eth_blockNumber` JSON-RPC method, or otherwise
+//! - from its reference node, which is the healthy running node on the network
+//!   with the highest reported block.
+//!
+//! A network whose rpc provider fails falls back to its reference node.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text, Uuid as SqlUuid};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use displaydoc::Display;
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::auth::resource::NodeId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{network_heads, protocol_versions, sql_types};
+use crate::util::NanosUtc;
+
+use super::ProtocolId;
+use super::version::{VariantKey, VersionId};
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find network heads: {0}
+    All(diesel::result::Error),
+    /// Failed to find network heads for versions `{0:?}`: {1}
+    ByVersionIds(HashSet<VersionId>, diesel::result::Error),
+    /// Failed to find reference nodes: {0}
+    ReferenceNodes(diesel::result::Error),
+    /// Failed to parse rpc block number `{0}`: {1}
+    RpcHeight(String, std::num::ParseIntError),
+    /// Failed to send rpc request: {0}
+    RpcRequest(reqwest::Error),
+    /// Rpc provider returned an error: {0}
+    RpcResponse(serde_json::Value),
+    /// Failed to set the rpc url of a network: {0}
+    SetRpcUrl(diesel::result::Error),
+    /// Failed to update network head: {0}
+    Update(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            All(_) | ByVersionIds(..) | ReferenceNodes(_) | RpcHeight(..) | RpcRequest(_)
+            | RpcResponse(_) | SetRpcUrl(_) | Update(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// Where the head of a network was last read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumHeadSource"]
+pub enum HeadSource {
+    Rpc,
+    ReferenceNode,
+}
+
+impl From<HeadSource> for api::NetworkHeadSource {
+    fn from(source: HeadSource) -> Self {
+        match source {
+            HeadSource::Rpc => api::NetworkHeadSource::Rpc,
+            HeadSource::ReferenceNode => api::NetworkHeadSource::ReferenceNode,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct NetworkHead {
+    pub protocol_id: ProtocolId,
+    pub variant_key: VariantKey,
+    pub rpc_url: Option<String>,
+    pub block_height: Option<i64>,
+    pub source: Option<HeadSource>,
+    pub reference_node_id: Option<NodeId>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NetworkHead {
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        network_heads::table
+            .order_by((network_heads::protocol_id, network_heads::variant_key))
+            .get_results(conn)
+            .await
+            .map_err(Error::All)
+    }
+
+    /// The head block height of the network of each protocol version.
+    pub async fn by_version_ids(
+        version_ids: &HashSet<VersionId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<VersionId, i64>, Error> {
+        let network = protocol_versions::protocol_id
+            .eq(network_heads::protocol_id)
+            .and(protocol_versions::variant_key.eq(network_heads::variant_key));
+
+        let heights: Vec<(VersionId, Option<i64>)> = network_heads::table
+            .inner_join(protocol_versions::table.on(network))
+            .filter(protocol_versions::id.eq_any(version_ids))
+            .select((protocol_versions::id, network_heads::block_height))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByVersionIds(version_ids.clone(), err))?;
+
+        Ok(heights
+            .into_iter()
+            .filter_map(|(id, height)| height.map(|height| (id, height)))
+            .collect())
+    }
+
+    /// Set or clear the rpc provider of a network.
+    pub async fn set_rpc_url(
+        protocol_id: ProtocolId,
+        variant_key: &VariantKey,
+        rpc_url: Option<&str>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::insert_into(network_heads::table)
+            .values((
+                network_heads::protocol_id.eq(protocol_id),
+                network_heads::variant_key.eq(variant_key),
+                network_heads::rpc_url.eq(rpc_url),
+            ))
+            .on_conflict((network_heads::protocol_id, network_heads::variant_key))
+            .do_update()
+            .set((
+                network_heads::rpc_url.eq(rpc_url),
+                network_heads::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(Error::SetRpcUrl)
+    }
+
+    /// Refresh the head of every network with an rpc provider or a reference
+    /// node, returning the number of networks updated.
+    pub async fn refresh(client: &reqwest::Client, conn: &mut Conn<'_>) -> Result<usize, Error> {
+        let rpc_heads: Vec<Self> = network_heads::table
+            .filter(network_heads::rpc_url.is_not_null())
+            .get_results(conn)
+            .await
+            .map_err(Error::All)?;
+
+        let mut updated = HashSet::new();
+        for head in rpc_heads {
+            let Some(rpc_url) = &head.rpc_url else {
+                continue;
+            };
+            match rpc_height(client, rpc_url).await {
+                Ok(height) => {
+                    let update = UpdateHead {
+                        protocol_id: head.protocol_id,
+                        variant_key: head.variant_key.to_string(),
+                        block_height: height,
+                        source: HeadSource::Rpc,
+                        reference_node_id: None,
+                    };
+                    update.apply(conn).await?;
+                    updated.insert((head.protocol_id, head.variant_key.to_string()));
+                }
+                Err(err) => warn!(
+                    "Failed to read the head of {} {} from rpc: {err}",
+                    head.protocol_id, head.variant_key
+                ),
+            }
+        }
+
+        for reference in ReferenceNode::all(conn).await? {
+            let network: (ProtocolId, String) =
+                (reference.protocol_id.into(), reference.variant_key.clone());
+            if updated.contains(&network) {
+                continue;
+            }
+
+            let update = UpdateHead {
+                protocol_id: network.0,
+                variant_key: reference.variant_key,
+                block_height: reference.block_height,
+                source: HeadSource::ReferenceNode,
+                reference_node_id: Some(reference.node_id.into()),
+            };
+            update.apply(conn).await?;
+            updated.insert(network);
+        }
+
+        Ok(updated.len())
+    }
+}
+
+impl From<NetworkHead> for api::NetworkHead {
+    fn from(head: NetworkHead) -> Self {
+        api::NetworkHead {
+            protocol_id: head.protocol_id.to_string(),
+            variant_key: head.variant_key.to_string(),
+            rpc_url: head.rpc_url,
+            block_height: head
+                .block_height
+                .map(|height| u64::try_from(height).unwrap_or_default()),
+            source: head
+                .source
+                .map(|source| api::NetworkHeadSource::from(source).into()),
+            reference_node_id: head.reference_node_id.map(|id| id.to_string()),
+            updated_at: Some(NanosUtc::from(head.updated_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = network_heads)]
+struct UpdateHead {
+    protocol_id: ProtocolId,
+    variant_key: String,
+    block_height: i64,
+    source: HeadSource,
+    reference_node_id: Option<NodeId>,
+}
+
+impl UpdateHead {
+    async fn apply(self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let block_height = self.block_height;
+        let source = self.source;
+        let reference_node_id = self.reference_node_id;
+
+        diesel::insert_into(network_heads::table)
+            .values(self)
+            .on_conflict((network_heads::protocol_id, network_heads::variant_key))
+            .do_update()
+            .set((
+                network_heads::block_height.eq(block_height),
+                network_heads::source.eq(source),
+                network_heads::reference_node_id.eq(reference_node_id),
+                network_heads::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(Error::Update)
+    }
+}
+
+/// The healthy running node with the highest block on a network.
+#[derive(QueryableByName)]
+struct ReferenceNode {
+    #[diesel(sql_type = SqlUuid)]
+    protocol_id: Uuid,
+    #[diesel(sql_type = Text)]
+    variant_key: String,
+    #[diesel(sql_type = SqlUuid)]
+    node_id: Uuid,
+    #[diesel(sql_type = BigInt)]
+    block_height: i64,
+}
+
+impl ReferenceNode {
+    async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        diesel::sql_query(
+            "
+            select distinct on (nodes.protocol_id, protocol_versions.variant_key)
+                nodes.protocol_id,
+                protocol_versions.variant_key,
+                nodes.id as node_id,
+                nodes.block_height
+            from nodes
+            join protocol_versions on protocol_versions.id = nodes.protocol_version_id
+            where nodes.deleted_at is null
+                and nodes.node_state = 'running'
+                and nodes.protocol_health = 'healthy'
+                and nodes.block_height is not null
+            order by nodes.protocol_id, protocol_versions.variant_key, nodes.block_height desc
+            ",
+        )
+        .load(conn)
+        .await
+        .map_err(Error::ReferenceNodes)
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    result: Option<String>,
+    error: Option<serde_json::Value>,
+}
+
+/// Read the latest block number from a JSON-RPC provider.
+async fn rpc_height(client: &reqwest::Client, rpc_url: &str) -> Result<i64, Error> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "eth_blockNumber",
+        "params": [],
+    });
+
+    let response: RpcResponse = client
+        .post(rpc_url)
+        .json(&request)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(Error::RpcRequest)?
+        .json()
+        .await
+        .map_err(Error::RpcRequest)?;
+
+    match (response.result, response.error) {
+        (Some(result), None) => {
+            let hex = result.trim_start_matches("0x");
+            i64::from_str_radix(hex, 16).map_err(|err| Error::RpcHeight(result, err))
+        }
+        (_, error) => Err(Error::RpcResponse(error.unwrap_or_default())),
+    }
+}
@@ -1,3 +1,12 @@
+pub mod grant;
+pub use grant::{NewProtocolGrant, ProtocolGrant, ProtocolGrantId};
+
+pub mod head;
+pub use head::NetworkHead;
+
+pub mod network;
+pub use network::{NetworkKind, ProtocolNetwork, ProtocolNetworkId};
+
 pub mod stats;
 
 pub mod version;
@@ -10,7 +19,7 @@ use derive_more::{Deref, Display, From, FromStr};
 use diesel::expression::expression_types::NotSelectable;
 use diesel::pg::Pg;
 use diesel::prelude::*;
-use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::DatabaseErrorKind::{ForeignKeyViolation, UniqueViolation};
 use diesel::result::Error::{DatabaseError, NotFound};
 use diesel::sql_types::{Bool, Nullable};
 use diesel_async::RunQueryDsl;
@@ -23,8 +32,8 @@ use uuid::Uuid;
 use crate::auth::AuthZ;
 use crate::auth::rbac::{ProtocolAdminPerm, ProtocolPerm};
 use crate::auth::resource::OrgId;
-use crate::database::{Conn, WriteConn};
-use crate::grpc::{Status, common};
+use crate::database::{CacheKey, Conn, WriteConn};
+use crate::grpc::{Status, api, common};
 use crate::model::sql;
 use crate::util::{SearchOperator, SortOrder};
 
@@ -41,12 +50,18 @@ pub enum Error {
     ByIds(HashSet<ProtocolId>, diesel::result::Error),
     /// Failed to find protocol key `{0}`: {1}
     ByKey(ProtocolKey, diesel::result::Error),
+    /// Failed to list protocol catalog: {0}
+    Catalog(diesel::result::Error),
     /// Failed to create new protocol: {0}
     Create(diesel::result::Error),
+    /// Failed to delete protocol id `{0}`: {1}
+    Delete(ProtocolId, diesel::result::Error),
     /// Protocol pagination: {0}
     Paginate(#[from] crate::model::paginate::Error),
     /// Protocol Region: {0}
     Region(#[from] crate::model::region::Error),
+    /// Unknown ProtocolLifecycle.
+    UnknownLifecycle,
     /// Unknown Visibility.
     UnknownVisibility,
     /// Failed to update protocol id `{0}`: {1}
@@ -63,6 +78,11 @@ impl From<Error> for Status {
             Create(DatabaseError(UniqueViolation, _)) => {
                 Status::already_exists("Protocol already exists.")
             }
+            Delete(_, NotFound) => Status::not_found("Protocol not found."),
+            Delete(_, DatabaseError(ForeignKeyViolation, _)) => {
+                Status::failed_precondition("Protocol still has versions or nodes.")
+            }
+            UnknownLifecycle => Status::invalid_argument("lifecycle"),
             UnknownVisibility => Status::invalid_argument("visibility"),
             Paginate(err) => err.into(),
             Region(err) => err.into(),
@@ -85,6 +105,10 @@ pub struct Protocol {
     pub visibility: Visibility,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
+    pub docs_url: Option<String>,
+    pub logo_url: Option<String>,
+    pub networks: Vec<Option<String>>,
+    pub lifecycle: ProtocolLifecycle,
 }
 
 impl Protocol {
@@ -94,13 +118,26 @@ impl Protocol {
         authz: &AuthZ,
         conn: &mut Conn<'_>,
     ) -> Result<Self, Error> {
-        protocols::table
+        let org_ids = org_id.into_iter().collect();
+        if let Some(protocol) = conn.cached(|cache| &cache.protocols, &id).await {
+            match protocol.is_visible(&org_ids, authz) {
+                Some(true) => return Ok(protocol),
+                Some(false) => return Err(Error::ById(id, NotFound)),
+                None => (),
+            }
+        }
+
+        let protocol: Self = protocols::table
             .find(id)
             .filter(protocols::visibility.eq_any(<&[Visibility]>::from(authz)))
-            .filter(protocols::org_id.eq(org_id).or(protocols::org_id.is_null()))
+            .filter(grant::visible_protocols(org_ids.into_iter().collect()))
             .get_result(conn)
             .await
-            .map_err(|err| Error::ById(id, err))
+            .map_err(|err| Error::ById(id, err))?;
+        conn.fill_cache(|cache| &cache.protocols, [(id, protocol.clone())])
+            .await;
+
+        Ok(protocol)
     }
 
     pub async fn by_ids(
@@ -109,17 +146,35 @@ impl Protocol {
         authz: &AuthZ,
         conn: &mut Conn<'_>,
     ) -> Result<Vec<Self>, Error> {
-        protocols::table
-            .filter(protocols::id.eq_any(ids))
+        let (cached, mut missing) = conn.cached_many(|cache| &cache.protocols, ids).await;
+        let mut visible = Vec::with_capacity(cached.len());
+        for protocol in cached {
+            match protocol.is_visible(org_ids, authz) {
+                Some(true) => visible.push(protocol),
+                Some(false) => (),
+                None => {
+                    missing.insert(protocol.id);
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(visible);
+        }
+
+        let protocols: Vec<Self> = protocols::table
+            .filter(protocols::id.eq_any(&missing))
             .filter(protocols::visibility.eq_any(<&[Visibility]>::from(authz)))
-            .filter(
-                protocols::org_id
-                    .eq_any(org_ids)
-                    .or(protocols::org_id.is_null()),
-            )
+            .filter(grant::visible_protocols(org_ids.iter().copied().collect()))
             .get_results(conn)
             .await
-            .map_err(|err| Error::ByIds(ids.clone(), err))
+            .map_err(|err| Error::ByIds(ids.clone(), err))?;
+        let values = protocols
+            .iter()
+            .map(|protocol| (protocol.id, protocol.clone()));
+        conn.fill_cache(|cache| &cache.protocols, values).await;
+
+        visible.extend(protocols);
+        Ok(visible)
     }
 
     pub async fn by_key(
@@ -131,11 +186,59 @@ impl Protocol {
         protocols::table
             .filter(protocols::key.eq(key))
             .filter(protocols::visibility.eq_any(<&[Visibility]>::from(authz)))
-            .filter(protocols::org_id.eq(org_id).or(protocols::org_id.is_null()))
+            .filter(grant::visible_protocols(org_id.into_iter().collect()))
             .get_result(conn)
             .await
             .map_err(|err| Error::ByKey(key.clone(), err))
     }
+
+    /// The org that owns a protocol, or `None` for protocols without an org.
+    pub async fn owner(id: ProtocolId, conn: &mut Conn<'_>) -> Result<Option<OrgId>, Error> {
+        protocols::table
+            .find(id)
+            .select(protocols::org_id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    /// Public protocols that are still offered, for unauthenticated listings.
+    pub async fn catalog(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        protocols::table
+            .filter(protocols::visibility.eq(Visibility::Public))
+            .filter(protocols::org_id.is_null())
+            .filter(protocols::lifecycle.ne(ProtocolLifecycle::Retired))
+            .order_by(protocols::name.asc())
+            .get_results(conn)
+            .await
+            .map_err(Error::Catalog)
+    }
+
+    pub async fn delete(id: ProtocolId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let deleted = diesel::delete(protocols::table.find(id))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Delete(id, err))?;
+        conn.invalidate(CacheKey::Protocol(id)).await;
+
+        if deleted == 0 {
+            Err(Error::Delete(id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Whether a cached protocol is visible to `org_ids`, or `None` when that
+    /// depends on the grants of another org and must be read from the database.
+    fn is_visible(&self, org_ids: &HashSet<OrgId>, authz: &AuthZ) -> Option<bool> {
+        if !<&[Visibility]>::from(authz).contains(&self.visibility) {
+            Some(false)
+        } else if self.org_id.is_none_or(|org_id| org_ids.contains(&org_id)) {
+            Some(true)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Insertable)]
@@ -146,6 +249,10 @@ pub struct NewProtocol {
     pub name: String,
     pub description: Option<String>,
     pub ticker: Option<String>,
+    pub docs_url: Option<String>,
+    pub logo_url: Option<String>,
+    pub networks: Vec<Option<String>>,
+    pub lifecycle: ProtocolLifecycle,
 }
 
 impl NewProtocol {
@@ -165,16 +272,23 @@ pub struct UpdateProtocol<'u> {
     pub name: Option<&'u str>,
     pub description: Option<&'u str>,
     pub visibility: Option<Visibility>,
+    pub docs_url: Option<&'u str>,
+    pub logo_url: Option<&'u str>,
+    pub networks: Option<Vec<Option<String>>>,
+    pub lifecycle: Option<ProtocolLifecycle>,
 }
 
 impl UpdateProtocol<'_> {
     pub async fn apply(self, conn: &mut Conn<'_>) -> Result<Protocol, Error> {
         let id = self.id;
-        diesel::update(protocols::table.find(id))
+        let protocol = diesel::update(protocols::table.find(id))
             .set((self, protocols::updated_at.eq(Utc::now())))
             .get_result(conn)
             .await
-            .map_err(|err| Error::Update(id, err))
+            .map_err(|err| Error::Update(id, err))?;
+        conn.invalidate(CacheKey::Protocol(id)).await;
+
+        Ok(protocol)
     }
 }
 
@@ -195,11 +309,7 @@ impl ProtocolFilter {
     ) -> Result<(Vec<Protocol>, u64), Error> {
         let mut query = protocols::table
             .filter(protocols::visibility.eq_any(<&[Visibility]>::from(authz)))
-            .filter(
-                protocols::org_id
-                    .eq_any(self.org_ids)
-                    .or(protocols::org_id.is_null()),
-            )
+            .filter(grant::visible_protocols(self.org_ids.into_iter().collect()))
             .into_boxed();
 
         if let Some(search) = self.search {
@@ -338,3 +448,37 @@ impl TryFrom<common::Visibility> for Visibility {
         }
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumProtocolLifecycle"]
+pub enum ProtocolLifecycle {
+    Active,
+    Beta,
+    Deprecated,
+    Retired,
+}
+
+impl From<ProtocolLifecycle> for api::ProtocolLifecycle {
+    fn from(lifecycle: ProtocolLifecycle) -> Self {
+        match lifecycle {
+            ProtocolLifecycle::Active => api::ProtocolLifecycle::Active,
+            ProtocolLifecycle::Beta => api::ProtocolLifecycle::Beta,
+            ProtocolLifecycle::Deprecated => api::ProtocolLifecycle::Deprecated,
+            ProtocolLifecycle::Retired => api::ProtocolLifecycle::Retired,
+        }
+    }
+}
+
+impl TryFrom<api::ProtocolLifecycle> for ProtocolLifecycle {
+    type Error = Error;
+
+    fn try_from(lifecycle: api::ProtocolLifecycle) -> Result<Self, Self::Error> {
+        match lifecycle {
+            api::ProtocolLifecycle::Unspecified => Err(Error::UnknownLifecycle),
+            api::ProtocolLifecycle::Active => Ok(ProtocolLifecycle::Active),
+            api::ProtocolLifecycle::Beta => Ok(ProtocolLifecycle::Beta),
+            api::ProtocolLifecycle::Deprecated => Ok(ProtocolLifecycle::Deprecated),
+            api::ProtocolLifecycle::Retired => Ok(ProtocolLifecycle::Retired),
+        }
+    }
+}
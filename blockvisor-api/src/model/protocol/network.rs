@@ -0,0 +1,196 @@
+//! The registered networks of each protocol.
+//!
+//! A network records whether it is a mainnet, testnet or devnet along with its
+//! chain metadata, so that images and nodes can reference it directly rather
+//! than callers inferring the network from variant keys or image properties.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{protocol_networks, sql_types};
+use crate::util::NanosUtc;
+
+use super::ProtocolId;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find protocol network `{0}`: {1}
+    ById(ProtocolNetworkId, diesel::result::Error),
+    /// Failed to find networks for protocol `{0}`: {1}
+    ByProtocol(ProtocolId, diesel::result::Error),
+    /// Failed to create protocol network: {0}
+    Create(diesel::result::Error),
+    /// Protocol network key must not be empty.
+    EmptyKey,
+    /// Invalid explorer url: {0}
+    ExplorerUrl(url::ParseError),
+    /// Network `{0}` does not belong to protocol `{1}`.
+    Protocol(ProtocolNetworkId, ProtocolId),
+    /// Unknown NetworkKind.
+    UnknownKind,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) => Status::not_found("Protocol network not found."),
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Protocol network already exists.")
+            }
+            EmptyKey => Status::invalid_argument("network_key"),
+            ExplorerUrl(_) => Status::invalid_argument("explorer_url"),
+            Protocol(..) => Status::invalid_argument("network_id"),
+            UnknownKind => Status::invalid_argument("kind"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ProtocolNetworkId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumNetworkKind"]
+pub enum NetworkKind {
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl From<NetworkKind> for api::NetworkKind {
+    fn from(kind: NetworkKind) -> Self {
+        match kind {
+            NetworkKind::Mainnet => api::NetworkKind::Mainnet,
+            NetworkKind::Testnet => api::NetworkKind::Testnet,
+            NetworkKind::Devnet => api::NetworkKind::Devnet,
+        }
+    }
+}
+
+impl TryFrom<api::NetworkKind> for NetworkKind {
+    type Error = Error;
+
+    fn try_from(kind: api::NetworkKind) -> Result<Self, Self::Error> {
+        match kind {
+            api::NetworkKind::Unspecified => Err(Error::UnknownKind),
+            api::NetworkKind::Mainnet => Ok(NetworkKind::Mainnet),
+            api::NetworkKind::Testnet => Ok(NetworkKind::Testnet),
+            api::NetworkKind::Devnet => Ok(NetworkKind::Devnet),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = protocol_networks)]
+pub struct ProtocolNetwork {
+    pub id: ProtocolNetworkId,
+    pub protocol_id: ProtocolId,
+    pub key: String,
+    pub kind: NetworkKind,
+    pub chain_id: Option<String>,
+    pub genesis_hash: Option<String>,
+    pub explorer_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl ProtocolNetwork {
+    pub async fn by_id(id: ProtocolNetworkId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        protocol_networks::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_protocol_id(
+        protocol_id: ProtocolId,
+        kinds: &[NetworkKind],
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = protocol_networks::table
+            .filter(protocol_networks::protocol_id.eq(protocol_id))
+            .into_boxed();
+        if !kinds.is_empty() {
+            query = query.filter(protocol_networks::kind.eq_any(kinds));
+        }
+
+        query
+            .order_by(protocol_networks::key.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByProtocol(protocol_id, err))
+    }
+
+    /// Find network `id`, checking that it is a network of `protocol_id`.
+    pub async fn for_protocol(
+        id: ProtocolNetworkId,
+        protocol_id: ProtocolId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let network = Self::by_id(id, conn).await?;
+        if network.protocol_id == protocol_id {
+            Ok(network)
+        } else {
+            Err(Error::Protocol(id, protocol_id))
+        }
+    }
+}
+
+impl From<ProtocolNetwork> for api::ProtocolNetwork {
+    fn from(network: ProtocolNetwork) -> Self {
+        api::ProtocolNetwork {
+            network_id: network.id.to_string(),
+            protocol_id: network.protocol_id.to_string(),
+            network_key: network.key,
+            kind: api::NetworkKind::from(network.kind).into(),
+            chain_id: network.chain_id,
+            genesis_hash: network.genesis_hash,
+            explorer_url: network.explorer_url,
+            created_at: Some(NanosUtc::from(network.created_at).into()),
+            updated_at: network.updated_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = protocol_networks)]
+pub struct NewProtocolNetwork {
+    pub protocol_id: ProtocolId,
+    pub key: String,
+    pub kind: NetworkKind,
+    pub chain_id: Option<String>,
+    pub genesis_hash: Option<String>,
+    pub explorer_url: Option<String>,
+}
+
+impl NewProtocolNetwork {
+    pub async fn create(mut self, conn: &mut Conn<'_>) -> Result<ProtocolNetwork, Error> {
+        self.key = self.key.trim().to_lowercase();
+        if self.key.is_empty() {
+            return Err(Error::EmptyKey);
+        }
+        if let Some(ref url) = self.explorer_url {
+            Url::parse(url).map_err(Error::ExplorerUrl)?;
+        }
+
+        diesel::insert_into(protocol_networks::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -23,7 +23,7 @@ use crate::model::schema::protocol_versions;
 use crate::model::sql::{ProtocolVersionMetadata, Version};
 use crate::util::{LOWER_KEBAB_CASE, NanosUtc};
 
-use super::{ProtocolId, Visibility};
+use super::{ProtocolId, Visibility, grant};
 
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
@@ -114,11 +114,7 @@ impl ProtocolVersion {
     ) -> Result<Self, Error> {
         protocol_versions::table
             .find(id)
-            .filter(
-                protocol_versions::org_id
-                    .eq(org_id)
-                    .or(protocol_versions::org_id.is_null()),
-            )
+            .filter(grant::visible_versions(org_id.into_iter().collect()))
             .filter(protocol_versions::visibility.eq_any(<&[Visibility]>::from(authz)))
             .get_result(conn)
             .await
@@ -134,11 +130,7 @@ impl ProtocolVersion {
         protocol_versions::table
             .filter(protocol_versions::id.eq_any(ids))
             .filter(protocol_versions::visibility.eq_any(<&[Visibility]>::from(authz)))
-            .filter(
-                protocol_versions::org_id
-                    .eq_any(org_ids)
-                    .or(protocol_versions::org_id.is_null()),
-            )
+            .filter(grant::visible_versions(org_ids.iter().copied().collect()))
             .get_results(conn)
             .await
             .map_err(|err| Error::ByIds(ids.clone(), err))
@@ -153,11 +145,7 @@ impl ProtocolVersion {
         let mut versions: Vec<Self> = protocol_versions::table
             .filter(protocol_versions::protocol_key.eq(&version_key.protocol_key))
             .filter(protocol_versions::variant_key.eq(&version_key.variant_key))
-            .filter(
-                protocol_versions::org_id
-                    .eq(org_id)
-                    .or(protocol_versions::org_id.is_null()),
-            )
+            .filter(grant::visible_versions(org_id.into_iter().collect()))
             .filter(protocol_versions::visibility.eq_any(<&[Visibility]>::from(authz)))
             .get_results(conn)
             .await
@@ -189,11 +177,7 @@ impl ProtocolVersion {
     ) -> Result<Vec<Self>, Error> {
         protocol_versions::table
             .filter(protocol_versions::protocol_id.eq(protocol_id))
-            .filter(
-                protocol_versions::org_id
-                    .eq(org_id)
-                    .or(protocol_versions::org_id.is_null()),
-            )
+            .filter(grant::visible_versions(org_id.into_iter().collect()))
             .filter(protocol_versions::visibility.eq_any(<&[Visibility]>::from(authz)))
             .get_results(conn)
             .await
@@ -208,11 +192,7 @@ impl ProtocolVersion {
     ) -> Result<Vec<Self>, Error> {
         protocol_versions::table
             .filter(protocol_versions::protocol_id.eq_any(protocol_ids))
-            .filter(
-                protocol_versions::org_id
-                    .eq_any(org_ids)
-                    .or(protocol_versions::org_id.is_null()),
-            )
+            .filter(grant::visible_versions(org_ids.iter().copied().collect()))
             .filter(protocol_versions::visibility.eq_any(<&[Visibility]>::from(authz)))
             .get_results(conn)
             .await
@@ -14,10 +14,12 @@ use crate::auth::rbac::OrgRole;
 use crate::auth::rbac::ViewRole;
 use crate::auth::rbac::{Perm, Role};
 use crate::auth::resource::{OrgId, UserId};
-use crate::database::Conn;
+use crate::database::{CacheKey, Conn};
 use crate::grpc::Status;
 
+use super::org_suspension;
 use super::schema::{permissions, role_permissions, roles, user_roles};
+use super::team::Team;
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -25,6 +27,8 @@ pub enum Error {
     CreatePerms(diesel::result::Error),
     /// Failed to create all roles: {0}
     CreateRoles(diesel::result::Error),
+    /// Failed to find non-org roles for user `{0}`: {1}
+    FindNonOrgRoles(UserId, diesel::result::Error),
     /// Failed to find org owners for org `{0}`: {1}
     FindOrgOwners(OrgId, diesel::result::Error),
     /// Failed to find roles for user `{0}` and org `{1}`: {2}
@@ -51,6 +55,10 @@ pub enum Error {
     NothingInserted,
     /// Failed to check if Role `{0}` has Perm `{1}`: {2}
     RoleHasPerm(Role, Perm, diesel::result::Error),
+    /// Failed to check org suspension: {0}
+    Suspension(#[from] crate::model::org_suspension::Error),
+    /// Failed to find team roles: {0}
+    Team(#[from] crate::model::team::Error),
     /// Failed to unlink Role `{0}` from Perm `{1}`: {2}
     UnlinkRolePerm(Role, Perm, diesel::result::Error),
     /// Failed to unlink User `{0}` and Org `{1}` from Role `{2:?}`: {3}
@@ -122,19 +130,21 @@ impl RbacRole {
         R: Into<Role> + Send,
     {
         let (role, perm) = (role.into(), perm.into());
-        diesel::insert_into(role_permissions::table)
+        let inserted = diesel::insert_into(role_permissions::table)
             .values((
                 role_permissions::role.eq(role.to_string()),
                 role_permissions::permission.eq(perm.to_string()),
             ))
             .execute(conn)
             .await
-            .map_err(|err| Error::LinkRolePerm(role, perm, err))
-            .and_then(|inserted| match inserted {
-                0 => Err(Error::NothingInserted),
-                1 => Ok(()),
-                n => Err(Error::UnexpectedInserted(n)),
-            })
+            .map_err(|err| Error::LinkRolePerm(role, perm, err))?;
+        conn.invalidate(CacheKey::RolePerms(role)).await;
+
+        match inserted {
+            0 => Err(Error::NothingInserted),
+            1 => Ok(()),
+            n => Err(Error::UnexpectedInserted(n)),
+        }
     }
 
     #[allow(unused)]
@@ -144,17 +154,19 @@ impl RbacRole {
         R: Into<Role> + Send,
     {
         let (role, perm) = (role.into(), perm.into());
-        diesel::delete(role_permissions::table)
+        let deleted = diesel::delete(role_permissions::table)
             .filter(role_permissions::role.eq(role.to_string()))
             .filter(role_permissions::permission.eq(perm.to_string()))
             .execute(conn)
             .await
-            .map_err(|err| Error::UnlinkRolePerm(role, perm, err))
-            .and_then(|deleted| match deleted {
-                0 => Err(Error::NothingDeleted),
-                1 => Ok(()),
-                n => Err(Error::UnexpectedDeleted(n)),
-            })
+            .map_err(|err| Error::UnlinkRolePerm(role, perm, err))?;
+        conn.invalidate(CacheKey::RolePerms(role)).await;
+
+        match deleted {
+            0 => Err(Error::NothingDeleted),
+            1 => Ok(()),
+            n => Err(Error::UnexpectedDeleted(n)),
+        }
     }
 }
 
@@ -182,7 +194,11 @@ impl RbacPerm {
         R: Into<Role> + Send,
     {
         let role = role.into();
-        role_permissions::table
+        if let Some(perms) = conn.cached(|cache| &cache.role_perms, &role).await {
+            return Ok(perms);
+        }
+
+        let perms = role_permissions::table
             .filter(role_permissions::role.eq(role.to_string()))
             .select(role_permissions::permission)
             .get_results(conn)
@@ -190,27 +206,50 @@ impl RbacPerm {
             .map_err(|err| Error::FindPermsForRole(role, err))?
             .into_iter()
             .map(|perm: String| perm.parse().map_err(Error::ParsePerm))
-            .collect()
+            .collect::<Result<HashSet<Perm>, _>>()?;
+        conn.fill_cache(|cache| &cache.role_perms, [(role, perms.clone())])
+            .await;
+
+        Ok(perms)
     }
 
     pub async fn for_roles(
         roles: &HashSet<Role>,
         conn: &mut Conn<'_>,
     ) -> Result<HashSet<Perm>, Error> {
-        role_permissions::table
-            .filter(role_permissions::role.eq_any(roles.iter().map(ToString::to_string)))
-            .select(role_permissions::permission)
+        let (cached, missing) = conn.cached_many(|cache| &cache.role_perms, roles).await;
+        let mut perms: HashSet<Perm> = cached.into_iter().flatten().collect();
+        if missing.is_empty() {
+            return Ok(perms);
+        }
+
+        let rows: Vec<(String, String)> = role_permissions::table
+            .filter(role_permissions::role.eq_any(missing.iter().map(ToString::to_string)))
+            .select((role_permissions::role, role_permissions::permission))
             .get_results(conn)
             .await
-            .map_err(Error::FindPermsForRoles)?
+            .map_err(Error::FindPermsForRoles)?;
+
+        let mut role_perms: HashMap<Role, HashSet<Perm>> = missing
             .into_iter()
-            .map(|perm: String| perm.parse().map_err(Error::ParsePerm))
-            .collect()
+            .map(|role| (role, HashSet::new()))
+            .collect();
+        for (role, perm) in rows {
+            let role = role.parse().map_err(Error::ParseRole)?;
+            let perm = perm.parse().map_err(Error::ParsePerm)?;
+            role_perms.entry(role).or_default().insert(perm);
+        }
+
+        perms.extend(role_perms.values().flatten().copied());
+        conn.fill_cache(|cache| &cache.role_perms, role_perms).await;
+
+        Ok(perms)
     }
 
     /// Find all role permissions for a user and org.
     ///
-    /// Also includes non org-specific role permissions.
+    /// Also includes non org-specific role permissions. The org role
+    /// permissions of a suspended org are limited to `org_suspension::allowed_perm`.
     pub async fn for_org(
         user_id: UserId,
         org_id: OrgId,
@@ -219,6 +258,9 @@ impl RbacPerm {
     ) -> Result<HashSet<Perm>, Error> {
         let roles = RbacUser::org_roles(user_id, org_id, ensure_member, conn).await?;
         let mut perms = RbacPerm::for_roles(&roles, conn).await?;
+        if org_suspension::is_suspended(org_id, conn).await? {
+            perms.retain(|perm| org_suspension::allowed_perm(*perm));
+        }
 
         perms.extend(RbacUser::perms_for_non_org_roles(user_id, conn).await?);
         Ok(perms)
@@ -228,28 +270,68 @@ impl RbacPerm {
 pub struct RbacUser;
 
 impl RbacUser {
+    /// The roles of a user within an org, including those of its teams.
     pub async fn org_roles(
         user_id: UserId,
         org_id: OrgId,
         ensure_member: bool,
         conn: &mut Conn<'_>,
     ) -> Result<HashSet<Role>, Error> {
-        let roles: Vec<_> = user_roles::table
-            .filter(user_roles::user_id.eq(user_id))
-            .filter(user_roles::org_id.eq(org_id))
-            .select(user_roles::role)
-            .get_results(conn)
-            .await
-            .map_err(|err| Error::FindOrgRoles(user_id, org_id, err))?;
+        let key = (user_id, org_id);
+        let roles = if let Some(roles) = conn.cached(|cache| &cache.org_roles, &key).await {
+            roles
+        } else {
+            let mut roles = user_roles::table
+                .filter(user_roles::user_id.eq(user_id))
+                .filter(user_roles::org_id.eq(org_id))
+                .select(user_roles::role)
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::FindOrgRoles(user_id, org_id, err))?
+                .into_iter()
+                .map(|role: String| role.parse().map_err(Error::ParseRole))
+                .collect::<Result<HashSet<Role>, _>>()?;
+            roles.extend(Team::user_roles(user_id, org_id, conn).await?);
+            conn.fill_cache(|cache| &cache.org_roles, [(key, roles.clone())])
+                .await;
+            roles
+        };
 
         if ensure_member && roles.is_empty() {
             return Err(Error::UserNotInOrg(user_id, org_id));
         }
 
-        roles
+        Ok(roles)
+    }
+
+    /// The roles of a user that are not org-specific.
+    pub async fn non_org_roles(
+        user_id: UserId,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<Role>, Error> {
+        if let Some(roles) = conn.cached(|cache| &cache.user_roles, &user_id).await {
+            return Ok(roles);
+        }
+
+        let non_org_roles = [
+            Role::from(BlockjoyRole::Admin),
+            Role::from(ViewRole::DeveloperPreview),
+        ];
+        let roles = user_roles::table
+            .filter(user_roles::user_id.eq(user_id))
+            .filter(user_roles::role.eq_any(non_org_roles.iter().map(ToString::to_string)))
+            .select(user_roles::role)
+            .distinct()
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::FindNonOrgRoles(user_id, err))?
             .into_iter()
             .map(|role: String| role.parse().map_err(Error::ParseRole))
-            .collect()
+            .collect::<Result<HashSet<Role>, _>>()?;
+        conn.fill_cache(|cache| &cache.user_roles, [(user_id, roles.clone())])
+            .await;
+
+        Ok(roles)
     }
 
     pub async fn org_owners(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<UserId>, Error> {
@@ -267,17 +349,8 @@ impl RbacUser {
         user_id: UserId,
         conn: &mut Conn<'_>,
     ) -> Result<HashSet<Perm>, Error> {
-        let mut perms = hashset! {};
-
-        if Self::has_non_org_role(user_id, BlockjoyRole::Admin, conn).await? {
-            perms.extend(&RbacPerm::for_role(BlockjoyRole::Admin, conn).await?);
-        }
-
-        if Self::has_non_org_role(user_id, ViewRole::DeveloperPreview, conn).await? {
-            perms.extend(&RbacPerm::for_role(ViewRole::DeveloperPreview, conn).await?);
-        }
-
-        Ok(perms)
+        let roles = Self::non_org_roles(user_id, conn).await?;
+        RbacPerm::for_roles(&roles, conn).await
     }
 
     /// Predicate to determine whether the user has some non org-specific role.
@@ -310,7 +383,7 @@ impl RbacUser {
         R: Into<Role> + Send,
     {
         let role = role.into();
-        diesel::insert_into(user_roles::table)
+        let inserted = diesel::insert_into(user_roles::table)
             .values((
                 user_roles::user_id.eq(user_id),
                 user_roles::org_id.eq(org_id),
@@ -318,12 +391,14 @@ impl RbacUser {
             ))
             .execute(conn)
             .await
-            .map_err(|err| Error::LinkUserRole(user_id, org_id, role, err))
-            .and_then(|inserted| match inserted {
-                0 => Err(Error::NothingInserted),
-                1 => Ok(()),
-                n => Err(Error::UnexpectedInserted(n)),
-            })
+            .map_err(|err| Error::LinkUserRole(user_id, org_id, role, err))?;
+        Self::invalidate_roles(user_id, org_id, conn).await;
+
+        match inserted {
+            0 => Err(Error::NothingInserted),
+            1 => Ok(()),
+            n => Err(Error::UnexpectedInserted(n)),
+        }
     }
 
     pub async fn link_roles<I, R>(
@@ -365,14 +440,22 @@ impl RbacUser {
             delete = delete.filter(user_roles::role.eq(role.to_string()));
         }
 
-        delete
+        let deleted = delete
             .execute(conn)
             .await
-            .map_err(|err| Error::UnlinkUserRole(user_id, org_id, role, err))
-            .and_then(|deleted| match deleted {
-                0 => Err(Error::NothingDeleted),
-                _ => Ok(()),
-            })
+            .map_err(|err| Error::UnlinkUserRole(user_id, org_id, role, err))?;
+        Self::invalidate_roles(user_id, org_id, conn).await;
+
+        match deleted {
+            0 => Err(Error::NothingDeleted),
+            _ => Ok(()),
+        }
+    }
+
+    /// Forget the cached roles of a user once its roles in `org_id` are written.
+    pub async fn invalidate_roles(user_id: UserId, org_id: OrgId, conn: &mut Conn<'_>) {
+        conn.invalidate(CacheKey::OrgRoles(user_id, org_id)).await;
+        conn.invalidate(CacheKey::UserRoles(user_id)).await;
     }
 }
 
@@ -0,0 +1,197 @@
+//! Differences between the nodes of a host and the VMs it actually runs.
+//!
+//! Blockvisord periodically reports the ids of every VM on its host. A VM that
+//! is not a live node of the host is recorded as `UnknownVm`, and a live node
+//! without a VM as `MissingNode`, so that an operator can clean up or recreate
+//! it. Nodes younger than `MIN_AGE` are not expected to have a VM yet.
+//!
+//! Each report resolves the open reconciliations that it no longer confirms.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{HostId, NodeId};
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::node::NodeState;
+use super::schema::{host_reconciliations, nodes, sql_types};
+
+/// Nodes younger than this may not have a VM on their host yet.
+pub const MIN_AGE: TimeDelta = TimeDelta::minutes(15);
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find reconciliations of host `{0}`: {1}
+    ByHostId(HostId, diesel::result::Error),
+    /// Failed to create reconciliations for host `{0}`: {1}
+    Create(HostId, diesel::result::Error),
+    /// Failed to find nodes of host `{0}`: {1}
+    HostNodes(HostId, diesel::result::Error),
+    /// Failed to resolve reconciliations of host `{0}`: {1}
+    Resolve(HostId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByHostId(..) | Create(..) | HostNodes(..) | Resolve(..) => {
+                Status::internal("Internal error.")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct ReconciliationId(Uuid);
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumReconciliationKind"]
+pub enum ReconciliationKind {
+    /// The host runs a VM that is not one of its live nodes.
+    UnknownVm,
+    /// The host has no VM for one of its live nodes.
+    MissingNode,
+}
+
+impl From<ReconciliationKind> for api::ReconciliationKind {
+    fn from(kind: ReconciliationKind) -> Self {
+        match kind {
+            ReconciliationKind::UnknownVm => api::ReconciliationKind::UnknownVm,
+            ReconciliationKind::MissingNode => api::ReconciliationKind::MissingNode,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Reconciliation {
+    pub id: ReconciliationId,
+    pub host_id: HostId,
+    pub vm_id: String,
+    pub kind: ReconciliationKind,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Reconciliation {
+    /// The reconciliations of a host, newest first, optionally including the
+    /// resolved ones.
+    pub async fn by_host_id(
+        host_id: HostId,
+        include_resolved: bool,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        let mut query = host_reconciliations::table
+            .filter(host_reconciliations::host_id.eq(host_id))
+            .into_boxed();
+        if !include_resolved {
+            query = query.filter(host_reconciliations::resolved_at.is_null());
+        }
+
+        query
+            .order_by(host_reconciliations::created_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByHostId(host_id, err))
+    }
+
+    /// Compare the VMs that a host reports running against its nodes.
+    ///
+    /// Returns the open reconciliations of the host after the report, along
+    /// with the ones it created.
+    pub async fn report(
+        host_id: HostId,
+        vm_ids: &HashSet<String>,
+        conn: &mut Conn<'_>,
+    ) -> Result<(Vec<Self>, Vec<Self>), Error> {
+        let live: Vec<(NodeId, NodeState, DateTime<Utc>)> = nodes::table
+            .filter(nodes::host_id.eq(host_id))
+            .filter(nodes::deleted_at.is_null())
+            .select((nodes::id, nodes::node_state, nodes::created_at))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::HostNodes(host_id, err))?;
+
+        let cutoff = Utc::now() - MIN_AGE;
+        let node_ids: HashSet<String> = live.iter().map(|(id, ..)| id.to_string()).collect();
+        let expected = live
+            .iter()
+            .filter(|(_, state, created_at)| {
+                *created_at < cutoff && !matches!(state, NodeState::Deleting | NodeState::Deleted)
+            })
+            .map(|(id, ..)| id.to_string());
+
+        let mut found: HashSet<(ReconciliationKind, String)> = vm_ids
+            .iter()
+            .filter(|vm_id| !node_ids.contains(*vm_id))
+            .map(|vm_id| (ReconciliationKind::UnknownVm, vm_id.clone()))
+            .collect();
+        found.extend(
+            expected
+                .filter(|node_id| !vm_ids.contains(node_id))
+                .map(|node_id| (ReconciliationKind::MissingNode, node_id)),
+        );
+
+        let open = Self::by_host_id(host_id, false, conn).await?;
+        let resolved: Vec<_> = open
+            .iter()
+            .filter(|rec| !found.remove(&(rec.kind, rec.vm_id.clone())))
+            .map(|rec| rec.id)
+            .collect();
+        if !resolved.is_empty() {
+            diesel::update(host_reconciliations::table)
+                .filter(host_reconciliations::id.eq_any(resolved))
+                .set(host_reconciliations::resolved_at.eq(Utc::now()))
+                .execute(conn)
+                .await
+                .map_err(|err| Error::Resolve(host_id, err))?;
+        }
+
+        let rows: Vec<_> = found
+            .into_iter()
+            .map(|(kind, vm_id)| {
+                (
+                    host_reconciliations::host_id.eq(host_id),
+                    host_reconciliations::vm_id.eq(vm_id),
+                    host_reconciliations::kind.eq(kind),
+                )
+            })
+            .collect();
+        let created = if rows.is_empty() {
+            vec![]
+        } else {
+            diesel::insert_into(host_reconciliations::table)
+                .values(rows)
+                .get_results(conn)
+                .await
+                .map_err(|err| Error::Create(host_id, err))?
+        };
+
+        let open = Self::by_host_id(host_id, false, conn).await?;
+        Ok((open, created))
+    }
+}
+
+impl From<Reconciliation> for api::HostReconciliation {
+    fn from(rec: Reconciliation) -> Self {
+        api::HostReconciliation {
+            reconciliation_id: rec.id.to_string(),
+            host_id: rec.host_id.to_string(),
+            vm_id: rec.vm_id,
+            kind: api::ReconciliationKind::from(rec.kind).into(),
+            created_at: Some(NanosUtc::from(rec.created_at).into()),
+            resolved_at: rec.resolved_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
@@ -10,7 +10,7 @@ use displaydoc::Display as DisplayDoc;
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::database::Conn;
+use crate::database::{CacheKey, Conn};
 use crate::grpc::{Status, api};
 use crate::util::LOWER_KEBAB_CASE;
 
@@ -18,6 +18,8 @@ use super::schema::regions;
 
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
+    /// Failed to get all regions: {0}
+    All(diesel::result::Error),
     /// Failed to create region: {0}
     Create(diesel::result::Error),
     /// Failed to get regions for id `{0}`: {1}
@@ -78,22 +80,48 @@ pub struct Region {
 
 impl Region {
     pub async fn by_id(id: RegionId, conn: &mut Conn<'_>) -> Result<Self, Error> {
-        regions::table
+        if let Some(region) = conn.cached(|cache| &cache.regions, &id).await {
+            return Ok(region);
+        }
+
+        let region: Self = regions::table
             .find(id)
             .get_result(conn)
             .await
-            .map_err(|err| Error::ById(id, err))
+            .map_err(|err| Error::ById(id, err))?;
+        conn.fill_cache(|cache| &cache.regions, [(id, region.clone())])
+            .await;
+
+        Ok(region)
     }
 
     pub async fn by_ids(
         region_ids: &HashSet<RegionId>,
         conn: &mut Conn<'_>,
     ) -> Result<Vec<Self>, Error> {
+        let (mut cached, missing) = conn.cached_many(|cache| &cache.regions, region_ids).await;
+        if missing.is_empty() {
+            return Ok(cached);
+        }
+
+        let regions: Vec<Self> = regions::table
+            .filter(regions::id.eq_any(&missing))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByIds(region_ids.clone(), err))?;
+        let values = regions.iter().map(|region| (region.id, region.clone()));
+        conn.fill_cache(|cache| &cache.regions, values).await;
+
+        cached.extend(regions);
+        Ok(cached)
+    }
+
+    pub async fn all(conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
         regions::table
-            .filter(regions::id.eq_any(region_ids))
+            .order_by(regions::key)
             .get_results(conn)
             .await
-            .map_err(|err| Error::ByIds(region_ids.clone(), err))
+            .map_err(Error::All)
     }
 
     pub async fn by_key(key: &RegionKey, conn: &mut Conn<'_>) -> Result<Self, Error> {
@@ -145,10 +173,13 @@ pub struct UpdateRegion<'u> {
 impl UpdateRegion<'_> {
     pub async fn apply(self, conn: &mut Conn<'_>) -> Result<Region, Error> {
         let id = self.id;
-        diesel::update(regions::table.find(id))
+        let region = diesel::update(regions::table.find(id))
             .set(self)
             .get_result(conn)
             .await
-            .map_err(|err| Error::Update(id, err))
+            .map_err(|err| Error::Update(id, err))?;
+        conn.invalidate(CacheKey::Region(id)).await;
+
+        Ok(region)
     }
 }
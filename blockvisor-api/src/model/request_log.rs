@@ -0,0 +1,119 @@
+//! The outcome of failed requests, kept for support debugging.
+//!
+//! Requests are identified by their `x-request-id`, which is either sent by the
+//! client or generated by the api and returned in the response metadata.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::schema::request_logs;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find request logs for request `{0}`: {1}
+    ByRequestId(String, diesel::result::Error),
+    /// Failed to create request log: {0}
+    Create(diesel::result::Error),
+    /// Failed to purge request logs: {0}
+    Purge(diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ByRequestId(..) | Create(_) | Purge(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Display,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    DieselNewType,
+    Deref,
+    From,
+    FromStr,
+)]
+pub struct RequestLogId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = request_logs)]
+pub struct RequestLog {
+    pub id: RequestLogId,
+    pub request_id: String,
+    pub method: String,
+    pub grpc_code: i32,
+    pub message: Option<String>,
+    pub duration_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RequestLog {
+    pub async fn by_request_id(request_id: &str, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        request_logs::table
+            .filter(request_logs::request_id.eq(request_id))
+            .order_by(request_logs::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByRequestId(request_id.to_string(), err))
+    }
+
+    /// Delete the request logs created before `cutoff`.
+    pub async fn purge(cutoff: DateTime<Utc>, conn: &mut Conn<'_>) -> Result<usize, Error> {
+        let expired = request_logs::table.filter(request_logs::created_at.lt(cutoff));
+        diesel::delete(expired)
+            .execute(conn)
+            .await
+            .map_err(Error::Purge)
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = request_logs)]
+pub struct NewRequestLog {
+    pub request_id: String,
+    pub method: String,
+    pub grpc_code: i32,
+    pub message: Option<String>,
+    pub duration_ms: i64,
+}
+
+impl NewRequestLog {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<RequestLog, Error> {
+        diesel::insert_into(request_logs::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+impl From<RequestLog> for api::RequestOutcome {
+    fn from(log: RequestLog) -> Self {
+        api::RequestOutcome {
+            method: log.method,
+            grpc_code: log.grpc_code,
+            message: log.message,
+            duration_ms: log.duration_ms,
+            created_at: Some(NanosUtc::from(log.created_at).into()),
+        }
+    }
+}
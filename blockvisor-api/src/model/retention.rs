@@ -0,0 +1,314 @@
+//! Retention of the tables that would otherwise grow without bound.
+//!
+//! Each table is partitioned by month as `{table}_pYYYYMM`, so expired rows are
+//! removed by dropping a whole partition once its last day is past the
+//! retention. A partition with rows of an org or node under legal hold is kept
+//! until the hold is cleared.
+
+use chrono::{DateTime, Months, NaiveDate, NaiveTime, TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Date, Text};
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::config::retention::Config;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::node::slo::month_start;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to clear references to commands in `{0}`: {1}
+    ClearCommandRefs(String, diesel::result::Error),
+    /// Failed to create partition of `{0}` for {1}: {2}
+    CreatePartition(RetainedTable, NaiveDate, diesel::result::Error),
+    /// Failed to drop partition `{0}`: {1}
+    DropPartition(String, diesel::result::Error),
+    /// Failed to check partition `{0}` for legal holds: {1}
+    Held(String, diesel::result::Error),
+    /// Failed to list partitions of `{0}`: {1}
+    ListPartitions(RetainedTable, diesel::result::Error),
+    /// Failed to find the size of `{0}`: {1}
+    TableSize(RetainedTable, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ClearCommandRefs(..) | CreatePartition(..) | DropPartition(..) | Held(..)
+            | ListPartitions(..) | TableSize(..) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, derive_more::Display, PartialEq, Eq)]
+pub enum RetainedTable {
+    #[display("node_logs")]
+    NodeLogs,
+    #[display("commands")]
+    Commands,
+    #[display("node_health_history")]
+    NodeHealthHistory,
+    #[display("audit_logs")]
+    AuditLogs,
+}
+
+impl RetainedTable {
+    pub const ALL: [Self; 4] = [
+        Self::NodeLogs,
+        Self::Commands,
+        Self::NodeHealthHistory,
+        Self::AuditLogs,
+    ];
+
+    pub const fn retention_days(self, config: &Config) -> u32 {
+        match self {
+            Self::NodeLogs => config.node_logs_days,
+            Self::Commands => config.commands_days,
+            Self::NodeHealthHistory => config.node_health_days,
+            Self::AuditLogs => config.audit_logs_days,
+        }
+    }
+
+    pub fn retention(self, config: &Config) -> TimeDelta {
+        TimeDelta::days(i64::from(self.retention_days(config)))
+    }
+
+    /// The monthly partitions of this table, oldest first.
+    ///
+    /// The default partition that catches rows outside of any month is not
+    /// included.
+    pub async fn partitions(self, conn: &mut Conn<'_>) -> Result<Vec<Partition>, Error> {
+        let rows: Vec<PartitionRow> = diesel::sql_query(
+            "
+            select child.relname::text as name
+            from pg_inherits
+            join pg_class child on child.oid = pg_inherits.inhrelid
+            where pg_inherits.inhparent = to_regclass($1)
+            ",
+        )
+        .bind::<Text, _>(self.to_string())
+        .load(conn)
+        .await
+        .map_err(|err| Error::ListPartitions(self, err))?;
+
+        let prefix = format!("{self}_p");
+        let mut partitions: Vec<_> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let suffix = row.name.strip_prefix(&prefix)?;
+                let month = NaiveDate::parse_from_str(&format!("{suffix}01"), "%Y%m%d").ok()?;
+                Some(Partition {
+                    name: row.name,
+                    month,
+                })
+            })
+            .collect();
+        partitions.sort_by_key(|partition| partition.month);
+
+        Ok(partitions)
+    }
+
+    /// Create the partitions for the month of `now` and the month after, so
+    /// that inserts never fall through to the default partition.
+    pub async fn ensure_partitions(
+        self,
+        now: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let this_month = month_start(now.date_naive());
+        let next_month = this_month + Months::new(1);
+        for month in [this_month, next_month] {
+            diesel::sql_query("select create_monthly_partition($1, $2)")
+                .bind::<Text, _>(self.to_string())
+                .bind::<Date, _>(month)
+                .execute(conn)
+                .await
+                .map_err(|err| Error::CreatePartition(self, month, err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop the partitions whose last day is before `cutoff`, returning how
+    /// many were dropped.
+    ///
+    /// Partitions with rows under legal hold are skipped.
+    pub async fn purge(self, cutoff: DateTime<Utc>, conn: &mut Conn<'_>) -> Result<usize, Error> {
+        let mut dropped = 0;
+        for partition in self.partitions(conn).await? {
+            if partition.end() > cutoff {
+                break;
+            }
+            if self.is_held(&partition, conn).await? {
+                continue;
+            }
+
+            // dropping a partition doesn't fire the delete trigger of commands
+            if self == Self::Commands {
+                clear_command_refs(&partition, conn).await?;
+            }
+
+            diesel::sql_query(format!("drop table if exists \"{}\"", partition.name))
+                .execute(conn)
+                .await
+                .map_err(|err| Error::DropPartition(partition.name, err))?;
+            dropped += 1;
+        }
+
+        Ok(dropped)
+    }
+
+    /// Whether `partition` has rows of an org or node under legal hold.
+    async fn is_held(self, partition: &Partition, conn: &mut Conn<'_>) -> Result<bool, Error> {
+        let held_ids = match self {
+            Self::NodeLogs | Self::Commands | Self::NodeHealthHistory => {
+                "node_id in (
+                    select node_id from legal_holds
+                    where cleared_at is null and node_id is not null
+                    union
+                    select nodes.id from nodes
+                    join legal_holds on legal_holds.org_id = nodes.org_id
+                    where legal_holds.cleared_at is null
+                )"
+            }
+            Self::AuditLogs => {
+                "org_id in (
+                    select org_id from legal_holds
+                    where cleared_at is null and org_id is not null
+                )"
+            }
+        };
+
+        let row: HeldRow = diesel::sql_query(format!(
+            "select exists (select 1 from \"{}\" where {held_ids}) as held",
+            partition.name
+        ))
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Held(partition.name.clone(), err))?;
+
+        Ok(row.held)
+    }
+
+    /// The current size of this table and when its oldest rows expire.
+    pub async fn usage(self, config: &Config, conn: &mut Conn<'_>) -> Result<TableUsage, Error> {
+        let size: TableSize = diesel::sql_query(
+            "
+            select
+                coalesce(sum(pg_total_relation_size(oid)), 0)::bigint as total_bytes,
+                coalesce(sum(greatest(reltuples, 0)), 0)::bigint as row_estimate
+            from pg_class
+            where oid = to_regclass($1)
+                or oid in (select inhrelid from pg_inherits where inhparent = to_regclass($1))
+            ",
+        )
+        .bind::<Text, _>(self.to_string())
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::TableSize(self, err))?;
+
+        let retention = self.retention(config);
+        let partitions = self.partitions(conn).await?;
+        let oldest = partitions.first();
+
+        Ok(TableUsage {
+            table: self,
+            retention_days: self.retention_days(config),
+            total_bytes: size.total_bytes,
+            row_estimate: size.row_estimate,
+            partition_count: partitions.len(),
+            oldest_at: oldest.map(Partition::start),
+            next_purge_at: oldest.map(|oldest| oldest.end() + retention),
+        })
+    }
+}
+
+/// A monthly partition named `{table}_pYYYYMM`.
+#[derive(Debug)]
+pub struct Partition {
+    pub name: String,
+    /// The first day of the month covered by this partition.
+    pub month: NaiveDate,
+}
+
+impl Partition {
+    pub fn start(&self) -> DateTime<Utc> {
+        self.month.and_time(NaiveTime::MIN).and_utc()
+    }
+
+    pub fn end(&self) -> DateTime<Utc> {
+        (self.month + Months::new(1))
+            .and_time(NaiveTime::MIN)
+            .and_utc()
+    }
+}
+
+#[derive(Debug)]
+pub struct TableUsage {
+    pub table: RetainedTable,
+    pub retention_days: u32,
+    /// The size of the table and its partitions, including indexes and toast.
+    pub total_bytes: i64,
+    /// The row count as of the last analyze of each partition.
+    pub row_estimate: i64,
+    pub partition_count: usize,
+    pub oldest_at: Option<DateTime<Utc>>,
+    /// When the oldest rows are past the retention, which may be in the past
+    /// until the next run of the retention job.
+    pub next_purge_at: Option<DateTime<Utc>>,
+}
+
+impl From<TableUsage> for api::TableRetention {
+    fn from(usage: TableUsage) -> Self {
+        api::TableRetention {
+            table: usage.table.to_string(),
+            retention_days: usage.retention_days,
+            total_bytes: u64::try_from(usage.total_bytes).unwrap_or_default(),
+            row_estimate: u64::try_from(usage.row_estimate).unwrap_or_default(),
+            partition_count: u32::try_from(usage.partition_count).unwrap_or(u32::MAX),
+            oldest_at: usage.oldest_at.map(NanosUtc::from).map(Into::into),
+            next_purge_at: usage.next_purge_at.map(NanosUtc::from).map(Into::into),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct PartitionRow {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+#[derive(QueryableByName)]
+struct HeldRow {
+    #[diesel(sql_type = Bool)]
+    held: bool,
+}
+
+#[derive(QueryableByName)]
+struct TableSize {
+    #[diesel(sql_type = BigInt)]
+    total_bytes: i64,
+    #[diesel(sql_type = BigInt)]
+    row_estimate: i64,
+}
+
+/// Clear the references to the commands of a partition that is about to be
+/// dropped, as a partitioned table can't be the target of a foreign key.
+async fn clear_command_refs(partition: &Partition, conn: &mut Conn<'_>) -> Result<(), Error> {
+    for table in ["agent_upgrade_hosts", "disk_resizes"] {
+        diesel::sql_query(format!(
+            "update {table} set command_id = null where command_id in (select id from \"{}\")",
+            partition.name
+        ))
+        .execute(conn)
+        .await
+        .map_err(|err| Error::ClearCommandRefs(table.to_string(), err))?;
+    }
+
+    Ok(())
+}
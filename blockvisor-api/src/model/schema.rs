@@ -1,10 +1,66 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_abuse_reason"))]
+    pub struct EnumAbuseReason;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_abuse_resolution"))]
+    pub struct EnumAbuseResolution;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_alert_kind"))]
+    pub struct EnumAlertKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_agent_upgrade_host_status"))]
+    pub struct EnumAgentUpgradeHostStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_agent_upgrade_policy"))]
+    pub struct EnumAgentUpgradePolicy;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_agent_upgrade_status"))]
+    pub struct EnumAgentUpgradeStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_approval_action"))]
+    pub struct EnumApprovalAction;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_approval_status"))]
+    pub struct EnumApprovalStatus;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "blockchain_property_ui_type"))]
     pub struct BlockchainPropertyUiType;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_audit_event"))]
+    pub struct EnumAuditEvent;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_autoscale_action"))]
+    pub struct EnumAutoscaleAction;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_autoscale_metric"))]
+    pub struct EnumAutoscaleMetric;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_backorder_status"))]
+    pub struct EnumBackorderStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_certificate_status"))]
+    pub struct EnumCertificateStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_cloud_provider"))]
+    pub struct EnumCloudProvider;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_command_exit_code"))]
     pub struct EnumCommandExitCode;
@@ -25,6 +81,14 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "enum_container_status"))]
     pub struct EnumContainerStatus;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_disk_resize_status"))]
+    pub struct EnumDiskResizeStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_dns_alias_type"))]
+    pub struct EnumDnsAliasType;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_firewall_action"))]
     pub struct EnumFirewallAction;
@@ -37,18 +101,54 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "enum_firewall_protocol"))]
     pub struct EnumFirewallProtocol;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_head_source"))]
+    pub struct EnumHeadSource;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_health"))]
     pub struct EnumHealth;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_host_ranking"))]
+    pub struct EnumHostRanking;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_host_type_old"))]
     pub struct EnumHostTypeOld;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_image_review_status"))]
+    pub struct EnumImageReviewStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_incident_link_type"))]
+    pub struct EnumIncidentLinkType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_incident_status"))]
+    pub struct EnumIncidentStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_log_sink_kind"))]
+    pub struct EnumLogSinkKind;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_managed_host_status"))]
+    pub struct EnumManagedHostStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_network_kind"))]
+    pub struct EnumNetworkKind;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_next_state"))]
     pub struct EnumNextState;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_node_cleanup"))]
+    pub struct EnumNodeCleanup;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_node_event"))]
     pub struct EnumNodeEvent;
@@ -57,6 +157,14 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "enum_node_log_event"))]
     pub struct EnumNodeLogEvent;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_node_name_charset"))]
+    pub struct EnumNodeNameCharset;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_node_placement"))]
+    pub struct EnumNodePlacement;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_node_resource_affinity"))]
     pub struct EnumNodeResourceAffinity;
@@ -85,6 +193,34 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "enum_node_type"))]
     pub struct EnumNodeType;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_org_key_host_status"))]
+    pub struct EnumOrgKeyHostStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_org_key_type"))]
+    pub struct EnumOrgKeyType;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_org_log_sink_host_status"))]
+    pub struct EnumOrgLogSinkHostStatus;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_plan_tier"))]
+    pub struct EnumPlanTier;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_protocol_lifecycle"))]
+    pub struct EnumProtocolLifecycle;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_push_platform"))]
+    pub struct EnumPushPlatform;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_reconciliation_kind"))]
+    pub struct EnumReconciliationKind;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_resource_type"))]
     pub struct EnumResourceType;
@@ -93,6 +229,18 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "enum_schedule_type"))]
     pub struct EnumScheduleType;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_support_level"))]
+    pub struct EnumSupportLevel;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_suspension_reason"))]
+    pub struct EnumSuspensionReason;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_ticket_provider"))]
+    pub struct EnumTicketProvider;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_token_type"))]
     pub struct EnumTokenType;
@@ -101,6 +249,10 @@ pub mod sql_types {
     #[diesel(postgres_type(name = "enum_ui_type"))]
     pub struct EnumUiType;
 
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "enum_user_export_status"))]
+    pub struct EnumUserExportStatus;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "enum_visibility"))]
     pub struct EnumVisibility;
@@ -124,6 +276,64 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAgentUpgradeHostStatus;
+
+    agent_upgrade_hosts (upgrade_id, host_id) {
+        upgrade_id -> Uuid,
+        host_id -> Uuid,
+        wave -> Int4,
+        status -> EnumAgentUpgradeHostStatus,
+        command_id -> Nullable<Uuid>,
+        error -> Nullable<Text>,
+        sent_at -> Nullable<Timestamptz>,
+        acked_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAgentUpgradeStatus;
+    use super::sql_types::EnumResourceType;
+
+    agent_upgrades (id) {
+        id -> Uuid,
+        version -> Text,
+        wave_size -> Int4,
+        wave_interval_seconds -> Int4,
+        max_failures -> Int4,
+        status -> EnumAgentUpgradeStatus,
+        current_wave -> Int4,
+        halted_reason -> Nullable<Text>,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        wave_sent_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAlertKind;
+
+    alerts (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        node_id -> Nullable<Uuid>,
+        kind -> EnumAlertKind,
+        message -> Text,
+        escalation_level -> Int4,
+        notified_at -> Timestamptz,
+        acknowledged_at -> Nullable<Timestamptz>,
+        acknowledged_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumResourceType;
@@ -141,6 +351,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumApprovalAction;
+    use super::sql_types::EnumApprovalStatus;
+    use super::sql_types::EnumResourceType;
+
+    approvals (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        action -> EnumApprovalAction,
+        request -> Bytea,
+        status -> EnumApprovalStatus,
+        requested_by_type -> EnumResourceType,
+        requested_by_id -> Uuid,
+        reviewed_by_type -> Nullable<EnumResourceType>,
+        reviewed_by_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        reviewed_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     archives (id) {
         id -> Uuid,
@@ -151,6 +383,66 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    artifacts (id) {
+        id -> Uuid,
+        command_id -> Uuid,
+        host_id -> Uuid,
+        node_id -> Nullable<Uuid>,
+        name -> Text,
+        store_key -> Text,
+        created_at -> Timestamptz,
+        uploaded_at -> Nullable<Timestamptz>,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAuditEvent;
+    use super::sql_types::EnumResourceType;
+
+    audit_logs (id, created_at) {
+        id -> Uuid,
+        org_id -> Nullable<Uuid>,
+        event -> EnumAuditEvent,
+        event_data -> Nullable<Jsonb>,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        request_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    backfills (name) {
+        name -> Text,
+        cursor -> Nullable<Uuid>,
+        processed -> Int8,
+        error -> Nullable<Text>,
+        started_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumBackorderStatus;
+
+    backorders (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        user_id -> Uuid,
+        request -> Bytea,
+        status -> EnumBackorderStatus,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumNodeType;
@@ -221,7 +513,7 @@ diesel::table! {
     use super::sql_types::EnumCommandExitCode;
     use super::sql_types::EnumCommandType;
 
-    commands (id) {
+    commands (id, created_at) {
         id -> Uuid,
         host_id -> Uuid,
         exit_message -> Nullable<Text>,
@@ -233,6 +525,9 @@ diesel::table! {
         exit_code -> Nullable<EnumCommandExitCode>,
         command_type -> EnumCommandType,
         protobuf -> Nullable<Bytea>,
+        execute_at -> Nullable<Timestamptz>,
+        dispatched_at -> Nullable<Timestamptz>,
+        depends_on -> Nullable<Uuid>,
     }
 }
 
@@ -253,6 +548,140 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumDiskResizeStatus;
+    use super::sql_types::EnumResourceType;
+
+    disk_resizes (id) {
+        id -> Uuid,
+        node_id -> Uuid,
+        org_id -> Uuid,
+        old_disk_bytes -> Int8,
+        new_disk_bytes -> Int8,
+        used_disk_bytes -> Nullable<Int8>,
+        status -> EnumDiskResizeStatus,
+        command_id -> Nullable<Uuid>,
+        requested_by_type -> EnumResourceType,
+        requested_by_id -> Uuid,
+        reviewed_by_type -> Nullable<EnumResourceType>,
+        reviewed_by_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        reviewed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    endpoint_pool_members (pool_id, node_id) {
+        pool_id -> Uuid,
+        node_id -> Uuid,
+        dns_id -> Nullable<Text>,
+        rotated_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        autoscaled -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumResourceType;
+
+    endpoint_pools (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    endpoint_usage (endpoint_id, hour) {
+        endpoint_id -> Uuid,
+        hour -> Timestamptz,
+        request_count -> Int8,
+        error_count -> Int8,
+        latency_ms_total -> Int8,
+        latency_ms_max -> Int8,
+    }
+}
+
+diesel::table! {
+    escalation_policies (org_id) {
+        org_id -> Uuid,
+        schedule_ids -> Array<Nullable<Uuid>>,
+        ack_timeout_minutes -> Int4,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    feature_flags (id) {
+        id -> Uuid,
+        name -> Text,
+        description -> Nullable<Text>,
+        enabled -> Bool,
+        rollout_percent -> Int4,
+        org_ids -> Array<Nullable<Uuid>>,
+        user_ids -> Array<Nullable<Uuid>>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    freeze_windows (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        start_minute -> Int4,
+        end_minute -> Int4,
+        reason -> Nullable<Text>,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    host_pools (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        description -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumReconciliationKind;
+
+    host_reconciliations (id) {
+        id -> Uuid,
+        host_id -> Uuid,
+        vm_id -> Text,
+        kind -> EnumReconciliationKind,
+        created_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumHostRanking;
+
+    host_weights (ranking) {
+        ranking -> EnumHostRanking,
+        headroom -> Float8,
+        similarity -> Float8,
+        age -> Float8,
+        cost -> Float8,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumScheduleType;
@@ -295,6 +724,12 @@ diesel::table! {
         updated_at -> Nullable<Timestamptz>,
         deleted_at -> Nullable<Timestamptz>,
         cost -> Nullable<Jsonb>,
+        pool_id -> Nullable<Uuid>,
+        gpu_count -> Int8,
+        gpu_model -> Nullable<Text>,
+        nvme_iops -> Nullable<Int8>,
+        network_bandwidth_mbps -> Nullable<Int8>,
+        node_gpu_count -> Int8,
     }
 }
 
@@ -312,8 +747,6 @@ diesel::table! {
         status -> EnumConnectionStatus,
         created_at -> Timestamptz,
         cpu_count -> Int8,
-        mem_size_bytes -> Int8,
-        disk_size_bytes -> Int8,
         os -> Text,
         os_version -> Text,
         ip_gateway -> Inet,
@@ -360,6 +793,7 @@ diesel::table! {
         add_disk_bytes -> Nullable<Int8>,
         display_name -> Nullable<Text>,
         display_group -> Nullable<Text>,
+        validation -> Nullable<Jsonb>,
     }
 }
 
@@ -386,6 +820,7 @@ diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumFirewallAction;
     use super::sql_types::EnumVisibility;
+    use super::sql_types::EnumImageReviewStatus;
 
     images (id) {
         id -> Uuid,
@@ -405,6 +840,15 @@ diesel::table! {
         updated_at -> Nullable<Timestamptz>,
         min_babel_version -> Text,
         dns_scheme -> Nullable<Text>,
+        review_status -> EnumImageReviewStatus,
+        health_rules -> Nullable<Jsonb>,
+        min_gpu_count -> Int8,
+        gpu_model -> Nullable<Text>,
+        min_nvme_iops -> Nullable<Int8>,
+        min_network_bandwidth_mbps -> Nullable<Int8>,
+        dns_settings -> Nullable<Jsonb>,
+        port_map -> Nullable<Jsonb>,
+        network_id -> Nullable<Uuid>,
     }
 }
 
@@ -412,32 +856,257 @@ diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumResourceType;
 
-    invitations (id) {
+    incident_comments (id) {
         id -> Uuid,
-        invited_by -> Uuid,
-        org_id -> Uuid,
-        invitee_email -> Text,
+        incident_id -> Uuid,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        message -> Text,
         created_at -> Timestamptz,
-        accepted_at -> Nullable<Timestamptz>,
-        declined_at -> Nullable<Timestamptz>,
-        invited_by_resource -> EnumResourceType,
     }
 }
 
 diesel::table! {
-    ip_addresses (id) {
-        id -> Uuid,
+    use diesel::sql_types::*;
+    use super::sql_types::EnumIncidentLinkType;
+
+    incident_links (incident_id, link_type, link_id) {
+        incident_id -> Uuid,
+        link_type -> EnumIncidentLinkType,
+        link_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumIncidentStatus;
+    use super::sql_types::EnumResourceType;
+
+    incidents (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        node_id -> Uuid,
+        report_id -> Nullable<Uuid>,
+        title -> Text,
+        status -> EnumIncidentStatus,
+        assignee_id -> Nullable<Uuid>,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        acknowledged_at -> Nullable<Timestamptz>,
+        resolved_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumResourceType;
+
+    invitations (id) {
+        id -> Uuid,
+        invited_by -> Uuid,
+        org_id -> Uuid,
+        invitee_email -> Text,
+        created_at -> Timestamptz,
+        accepted_at -> Nullable<Timestamptz>,
+        declined_at -> Nullable<Timestamptz>,
+        invited_by_resource -> EnumResourceType,
+    }
+}
+
+diesel::table! {
+    ip_addresses (id) {
+        id -> Uuid,
         ip -> Inet,
         host_id -> Uuid,
     }
 }
 
+diesel::table! {
+    legal_holds (id) {
+        id -> Uuid,
+        org_id -> Nullable<Uuid>,
+        node_id -> Nullable<Uuid>,
+        reason -> Text,
+        created_by -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        cleared_at -> Nullable<Timestamptz>,
+        cleared_by -> Nullable<Uuid>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumCloudProvider;
+    use super::sql_types::EnumManagedHostStatus;
+    use super::sql_types::EnumResourceType;
+
+    managed_hosts (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        region_id -> Uuid,
+        provider -> EnumCloudProvider,
+        provider_region -> Text,
+        machine_type -> Text,
+        machine_image -> Text,
+        name -> Text,
+        status -> EnumManagedHostStatus,
+        external_id -> Nullable<Text>,
+        ip_address -> Nullable<Inet>,
+        host_id -> Nullable<Uuid>,
+        error -> Nullable<Text>,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    mesh_networks (org_id) {
+        org_id -> Uuid,
+        subnet -> Cidr,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    mesh_peers (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        host_id -> Uuid,
+        node_id -> Nullable<Uuid>,
+        public_key -> Text,
+        ip -> Inet,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumHeadSource;
+
+    network_heads (protocol_id, variant_key) {
+        protocol_id -> Uuid,
+        variant_key -> Text,
+        rpc_url -> Nullable<Text>,
+        block_height -> Nullable<Int8>,
+        source -> Nullable<EnumHeadSource>,
+        reference_node_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAbuseReason;
+    use super::sql_types::EnumAbuseResolution;
+
+    node_abuse_reports (id) {
+        id -> Uuid,
+        node_id -> Uuid,
+        org_id -> Uuid,
+        reason -> EnumAbuseReason,
+        details -> Text,
+        suspended -> Bool,
+        created_at -> Timestamptz,
+        reviewed_at -> Nullable<Timestamptz>,
+        reviewed_by -> Nullable<Uuid>,
+        resolution -> Nullable<EnumAbuseResolution>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumCertificateStatus;
+
+    node_certificates (id) {
+        id -> Uuid,
+        node_id -> Uuid,
+        names -> Array<Nullable<Text>>,
+        status -> EnumCertificateStatus,
+        not_after -> Nullable<Timestamptz>,
+        last_error -> Nullable<Text>,
+        alerted_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumDnsAliasType;
+    use super::sql_types::EnumResourceType;
+
+    node_dns_aliases (id) {
+        id -> Uuid,
+        node_id -> Uuid,
+        name -> Text,
+        alias_type -> EnumDnsAliasType,
+        dns_id -> Text,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        expires_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumNodeState;
+
+    node_downtimes (id) {
+        id -> Uuid,
+        node_id -> Uuid,
+        node_state -> EnumNodeState,
+        started_at -> Timestamptz,
+        ended_at -> Nullable<Timestamptz>,
+        pushed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumResourceType;
+
+    node_endpoints (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        node_id -> Nullable<Uuid>,
+        label -> Text,
+        token_hash -> Text,
+        token_salt -> Text,
+        allowed_methods -> Array<Nullable<Text>>,
+        rate_limit_rps -> Nullable<Int4>,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        monthly_quota -> Nullable<Int8>,
+        stripe_item_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    node_health_history (node_id, hour) {
+        node_id -> Uuid,
+        hour -> Timestamptz,
+        samples -> Int4,
+        healthy_samples -> Int4,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumNodeEvent;
     use super::sql_types::EnumResourceType;
 
-    node_logs (id) {
+    node_logs (id, created_at) {
         id -> Uuid,
         node_id -> Uuid,
         host_id -> Uuid,
@@ -491,6 +1160,47 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    node_share_accesses (id) {
+        id -> Uuid,
+        share_id -> Uuid,
+        ip_address -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+        accessed_at -> Timestamptz,
+        accessed_minute -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumResourceType;
+
+    node_shares (id) {
+        id -> Uuid,
+        node_id -> Uuid,
+        org_id -> Uuid,
+        label -> Text,
+        token_hash -> Text,
+        token_salt -> Text,
+        expires_at -> Timestamptz,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    node_slo (node_id, month) {
+        node_id -> Uuid,
+        org_id -> Uuid,
+        month -> Date,
+        total_seconds -> Int8,
+        downtime_seconds -> Int8,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumNodeState;
@@ -499,6 +1209,8 @@ diesel::table! {
     use super::sql_types::EnumNodeSimilarityAffinity;
     use super::sql_types::EnumNodeResourceAffinity;
     use super::sql_types::EnumResourceType;
+    use super::sql_types::EnumNodeCleanup;
+    use super::sql_types::EnumNodePlacement;
 
     nodes (id) {
         id -> Uuid,
@@ -542,6 +1254,21 @@ diesel::table! {
         updated_at -> Nullable<Timestamptz>,
         deleted_at -> Nullable<Timestamptz>,
         cost -> Nullable<Jsonb>,
+        cleanup -> Nullable<EnumNodeCleanup>,
+        cleanup_attempts -> Int4,
+        cleanup_error -> Nullable<Text>,
+        cleanup_claimed_until -> Nullable<Timestamptz>,
+        peer_count -> Nullable<Int4>,
+        scheduler_placement -> EnumNodePlacement,
+        scheduler_pool_id -> Nullable<Uuid>,
+        gpu_count -> Int8,
+        dns_record_ids -> Array<Nullable<Text>>,
+        config_drift -> Bool,
+        config_reported_at -> Nullable<Timestamptz>,
+        project_id -> Nullable<Uuid>,
+        cpu_pegged_since -> Nullable<Timestamptz>,
+        cpu_percent -> Nullable<Int4>,
+        network_id -> Nullable<Uuid>,
     }
 }
 
@@ -574,8 +1301,6 @@ diesel::table! {
         block_age -> Nullable<Int8>,
         consensus -> Nullable<Bool>,
         vcpu_count -> Int8,
-        mem_size_bytes -> Int8,
-        disk_size_bytes -> Int8,
         network -> Text,
         created_by -> Nullable<Uuid>,
         #[max_length = 50]
@@ -607,6 +1332,131 @@ diesel::table! {
 }
 
 diesel::table! {
+    oncall_schedules (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        user_ids -> Array<Nullable<Uuid>>,
+        rotation_hours -> Int4,
+        starts_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    org_brandings (org_id) {
+        org_id -> Uuid,
+        sender_name -> Nullable<Text>,
+        primary_color -> Nullable<Text>,
+        accent_color -> Nullable<Text>,
+        logo_key -> Nullable<Text>,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumOrgKeyHostStatus;
+
+    org_key_hosts (key_id, host_id) {
+        key_id -> Uuid,
+        host_id -> Uuid,
+        status -> EnumOrgKeyHostStatus,
+        command_id -> Nullable<Uuid>,
+        error -> Nullable<Text>,
+        sent_at -> Timestamptz,
+        applied_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumOrgKeyType;
+    use super::sql_types::EnumResourceType;
+
+    org_keys (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        key_type -> EnumOrgKeyType,
+        public_key -> Text,
+        fingerprint -> Text,
+        replaces_id -> Nullable<Uuid>,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumOrgLogSinkHostStatus;
+
+    org_log_sink_hosts (host_id) {
+        host_id -> Uuid,
+        org_id -> Uuid,
+        revision -> Int8,
+        status -> EnumOrgLogSinkHostStatus,
+        command_id -> Nullable<Uuid>,
+        error -> Nullable<Text>,
+        sent_at -> Timestamptz,
+        applied_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumLogSinkKind;
+
+    org_log_sinks (org_id) {
+        org_id -> Uuid,
+        kind -> EnumLogSinkKind,
+        endpoint -> Text,
+        has_credentials -> Bool,
+        revision -> Int8,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        deleted_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    org_slo_reports (org_id, month) {
+        org_id -> Uuid,
+        month -> Date,
+        sent_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    org_suspended_nodes (node_id) {
+        node_id -> Uuid,
+        org_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    org_transfers (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        old_owner_id -> Uuid,
+        new_owner_id -> Uuid,
+        old_owner_confirmed_at -> Nullable<Timestamptz>,
+        new_owner_confirmed_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAgentUpgradePolicy;
+    use super::sql_types::EnumPlanTier;
+    use super::sql_types::EnumSuspensionReason;
+    use super::sql_types::EnumNodeNameCharset;
+
     orgs (id) {
         id -> Uuid,
         name -> Text,
@@ -619,6 +1469,38 @@ diesel::table! {
         member_count -> Int4,
         stripe_customer_id -> Nullable<Text>,
         address_id -> Nullable<Uuid>,
+        purged_at -> Nullable<Timestamptz>,
+        agent_upgrade_policy -> EnumAgentUpgradePolicy,
+        disk_quota_bytes -> Nullable<Int8>,
+        disk_auto_approve_bytes -> Nullable<Int8>,
+        plan_tier -> EnumPlanTier,
+        stripe_plan_item_id -> Nullable<Text>,
+        trial_ends_at -> Nullable<Timestamptz>,
+        trial_max_nodes -> Nullable<Int8>,
+        trial_reminded_at -> Nullable<Timestamptz>,
+        hibernated_at -> Nullable<Timestamptz>,
+        partner_id -> Nullable<Uuid>,
+        referred_at -> Nullable<Timestamptz>,
+        suspended_at -> Nullable<Timestamptz>,
+        suspension_reason -> Nullable<EnumSuspensionReason>,
+        suspension_banner -> Nullable<Text>,
+        node_display_name_max_len -> Nullable<Int4>,
+        node_note_max_len -> Nullable<Int4>,
+        node_display_name_unique -> Bool,
+        node_display_name_charset -> EnumNodeNameCharset,
+        require_approval_node_delete -> Bool,
+        require_approval_node_upgrade -> Bool,
+        require_approval_org_transfer -> Bool,
+        stripe_customer_index -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    partners (id) {
+        id -> Uuid,
+        name -> Text,
+        referral_code -> Text,
+        created_at -> Timestamptz,
     }
 }
 
@@ -629,6 +1511,118 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumPlanTier;
+    use super::sql_types::EnumSupportLevel;
+
+    plans (tier) {
+        tier -> EnumPlanTier,
+        name -> Text,
+        max_nodes -> Nullable<Int8>,
+        max_endpoints -> Nullable<Int8>,
+        protocol_ids -> Nullable<Array<Nullable<Uuid>>>,
+        private_hosts -> Bool,
+        support_level -> EnumSupportLevel,
+        stripe_sku -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        custom_branding -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAutoscaleAction;
+
+    pool_autoscale_events (id) {
+        id -> Uuid,
+        pool_id -> Uuid,
+        action -> EnumAutoscaleAction,
+        node_id -> Nullable<Uuid>,
+        metric_value -> Nullable<Float8>,
+        reason -> Text,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumAutoscaleMetric;
+
+    pool_autoscalers (pool_id) {
+        pool_id -> Uuid,
+        user_id -> Uuid,
+        image_id -> Uuid,
+        region_id -> Uuid,
+        min_nodes -> Int4,
+        max_nodes -> Int4,
+        metric -> EnumAutoscaleMetric,
+        scale_up_above -> Float8,
+        scale_down_below -> Float8,
+        cooldown_seconds -> Int4,
+        last_scaled_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    project_members (project_id, user_id) {
+        project_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    project_teams (project_id, team_id) {
+        project_id -> Uuid,
+        team_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    projects (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        description -> Nullable<Text>,
+        node_quota -> Nullable<Int8>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    protocol_grants (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        protocol_id -> Uuid,
+        protocol_version_id -> Nullable<Uuid>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumNetworkKind;
+
+    protocol_networks (id) {
+        id -> Uuid,
+        protocol_id -> Uuid,
+        key -> Text,
+        kind -> EnumNetworkKind,
+        chain_id -> Nullable<Text>,
+        genesis_hash -> Nullable<Text>,
+        explorer_url -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumVisibility;
@@ -652,6 +1646,7 @@ diesel::table! {
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumVisibility;
+    use super::sql_types::EnumProtocolLifecycle;
 
     protocols (id) {
         id -> Uuid,
@@ -663,6 +1658,10 @@ diesel::table! {
         visibility -> EnumVisibility,
         created_at -> Timestamptz,
         updated_at -> Nullable<Timestamptz>,
+        docs_url -> Nullable<Text>,
+        logo_url -> Nullable<Text>,
+        networks -> Array<Nullable<Text>>,
+        lifecycle -> EnumProtocolLifecycle,
     }
 }
 
@@ -675,6 +1674,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    request_logs (id) {
+        id -> Uuid,
+        request_id -> Text,
+        method -> Text,
+        grpc_code -> Int4,
+        message -> Nullable<Text>,
+        duration_ms -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     role_permissions (role, permission) {
         role -> Text,
@@ -690,6 +1701,99 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumResourceType;
+
+    secrets (resource_type, resource_id, key) {
+        resource_type -> EnumResourceType,
+        resource_id -> Uuid,
+        key -> Text,
+        value -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    status_pages (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        slug -> Text,
+        is_public -> Bool,
+        custom_domain -> Nullable<Text>,
+        domain_token -> Nullable<Text>,
+        domain_verified_at -> Nullable<Timestamptz>,
+        node_ids -> Array<Nullable<Uuid>>,
+        node_tags -> Array<Nullable<Text>>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    team_members (team_id, user_id) {
+        team_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    team_roles (team_id, role) {
+        team_id -> Uuid,
+        role -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    teams (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        name -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumResourceType;
+    use super::sql_types::EnumTicketProvider;
+
+    ticket_integrations (id) {
+        id -> Uuid,
+        org_id -> Uuid,
+        provider -> EnumTicketProvider,
+        base_url -> Text,
+        username -> Text,
+        api_token -> Text,
+        project_key -> Nullable<Text>,
+        webhook_hash -> Text,
+        webhook_salt -> Text,
+        created_by_type -> EnumResourceType,
+        created_by_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumIncidentStatus;
+
+    tickets (id) {
+        id -> Uuid,
+        incident_id -> Uuid,
+        integration_id -> Uuid,
+        external_id -> Text,
+        url -> Text,
+        status -> EnumIncidentStatus,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::EnumTokenType;
@@ -707,6 +1811,46 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumPushPlatform;
+
+    user_devices (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        platform -> EnumPushPlatform,
+        push_token -> Text,
+        name -> Text,
+        created_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EnumUserExportStatus;
+
+    user_export_requests (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        status -> EnumUserExportStatus,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        claimed_until -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    user_notification_preferences (user_id) {
+        user_id -> Uuid,
+        node_down -> Bool,
+        payment_failed -> Bool,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     user_roles (user_id, org_id, role) {
         user_id -> Uuid,
@@ -716,6 +1860,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    user_sessions (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        device -> Nullable<Text>,
+        ip_address -> Nullable<Text>,
+        created_at -> Timestamptz,
+        last_seen_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     user_settings (id) {
         id -> Uuid,
@@ -725,6 +1881,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    user_signups (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        ip_address -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Uuid,
@@ -738,12 +1903,25 @@ diesel::table! {
         last_name -> Varchar,
         confirmed_at -> Nullable<Timestamptz>,
         deleted_at -> Nullable<Timestamptz>,
+        password_reset_required -> Bool,
+        support_access_until -> Nullable<Timestamptz>,
     }
 }
 
+diesel::joinable!(agent_upgrade_hosts -> agent_upgrades (upgrade_id));
+diesel::joinable!(agent_upgrade_hosts -> hosts (host_id));
+diesel::joinable!(alerts -> nodes (node_id));
+diesel::joinable!(alerts -> orgs (org_id));
+diesel::joinable!(alerts -> users (acknowledged_by));
 diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(approvals -> orgs (org_id));
 diesel::joinable!(archives -> images (image_id));
 diesel::joinable!(archives -> orgs (org_id));
+diesel::joinable!(artifacts -> hosts (host_id));
+diesel::joinable!(artifacts -> nodes (node_id));
+diesel::joinable!(audit_logs -> orgs (org_id));
+diesel::joinable!(backorders -> orgs (org_id));
+diesel::joinable!(backorders -> users (user_id));
 diesel::joinable!(blockchain_node_types_old -> blockchains_old (blockchain_id));
 diesel::joinable!(blockchain_properties_old -> blockchain_node_types_old (blockchain_node_type_id));
 diesel::joinable!(blockchain_properties_old -> blockchain_versions_old (blockchain_version_id));
@@ -754,6 +1932,18 @@ diesel::joinable!(commands -> hosts (host_id));
 diesel::joinable!(commands -> nodes (node_id));
 diesel::joinable!(configs -> archives (archive_id));
 diesel::joinable!(configs -> images (image_id));
+diesel::joinable!(disk_resizes -> nodes (node_id));
+diesel::joinable!(disk_resizes -> orgs (org_id));
+diesel::joinable!(endpoint_pool_members -> endpoint_pools (pool_id));
+diesel::joinable!(endpoint_pool_members -> nodes (node_id));
+diesel::joinable!(endpoint_pools -> orgs (org_id));
+diesel::joinable!(endpoint_usage -> node_endpoints (endpoint_id));
+diesel::joinable!(escalation_policies -> orgs (org_id));
+diesel::joinable!(freeze_windows -> orgs (org_id));
+diesel::joinable!(freeze_windows -> users (created_by));
+diesel::joinable!(host_pools -> orgs (org_id));
+diesel::joinable!(host_reconciliations -> hosts (host_id));
+diesel::joinable!(hosts -> host_pools (pool_id));
 diesel::joinable!(hosts -> orgs (org_id));
 diesel::joinable!(hosts -> regions (region_id));
 diesel::joinable!(hosts_old -> orgs (org_id));
@@ -762,10 +1952,37 @@ diesel::joinable!(hosts_old -> users (created_by));
 diesel::joinable!(image_properties -> images (image_id));
 diesel::joinable!(image_rules -> images (image_id));
 diesel::joinable!(images -> orgs (org_id));
+diesel::joinable!(images -> protocol_networks (network_id));
 diesel::joinable!(images -> protocol_versions (protocol_version_id));
+diesel::joinable!(incident_comments -> incidents (incident_id));
+diesel::joinable!(incident_links -> incidents (incident_id));
+diesel::joinable!(incidents -> node_reports (report_id));
+diesel::joinable!(incidents -> nodes (node_id));
+diesel::joinable!(incidents -> orgs (org_id));
+diesel::joinable!(incidents -> users (assignee_id));
 diesel::joinable!(invitations -> orgs (org_id));
 diesel::joinable!(invitations -> users (invited_by));
 diesel::joinable!(ip_addresses -> hosts (host_id));
+diesel::joinable!(legal_holds -> nodes (node_id));
+diesel::joinable!(legal_holds -> orgs (org_id));
+diesel::joinable!(managed_hosts -> hosts (host_id));
+diesel::joinable!(managed_hosts -> orgs (org_id));
+diesel::joinable!(managed_hosts -> regions (region_id));
+diesel::joinable!(mesh_networks -> orgs (org_id));
+diesel::joinable!(mesh_peers -> hosts (host_id));
+diesel::joinable!(mesh_peers -> mesh_networks (org_id));
+diesel::joinable!(mesh_peers -> nodes (node_id));
+diesel::joinable!(network_heads -> nodes (reference_node_id));
+diesel::joinable!(network_heads -> protocols (protocol_id));
+diesel::joinable!(node_abuse_reports -> nodes (node_id));
+diesel::joinable!(node_abuse_reports -> orgs (org_id));
+diesel::joinable!(node_abuse_reports -> users (reviewed_by));
+diesel::joinable!(node_certificates -> nodes (node_id));
+diesel::joinable!(node_dns_aliases -> nodes (node_id));
+diesel::joinable!(node_downtimes -> nodes (node_id));
+diesel::joinable!(node_endpoints -> nodes (node_id));
+diesel::joinable!(node_endpoints -> orgs (org_id));
+diesel::joinable!(node_health_history -> nodes (node_id));
 diesel::joinable!(node_logs -> hosts (host_id));
 diesel::joinable!(node_logs -> nodes (node_id));
 diesel::joinable!(node_logs_old -> blockchains_old (blockchain_id));
@@ -773,10 +1990,18 @@ diesel::joinable!(node_logs_old -> orgs (org_id));
 diesel::joinable!(node_properties_old -> blockchain_properties_old (blockchain_property_id));
 diesel::joinable!(node_properties_old -> nodes_old (node_id));
 diesel::joinable!(node_reports -> nodes (node_id));
+diesel::joinable!(node_share_accesses -> node_shares (share_id));
+diesel::joinable!(node_shares -> nodes (node_id));
+diesel::joinable!(node_shares -> orgs (org_id));
+diesel::joinable!(node_slo -> nodes (node_id));
+diesel::joinable!(node_slo -> orgs (org_id));
 diesel::joinable!(nodes -> configs (config_id));
+diesel::joinable!(nodes -> host_pools (scheduler_pool_id));
 diesel::joinable!(nodes -> hosts (host_id));
 diesel::joinable!(nodes -> images (image_id));
 diesel::joinable!(nodes -> orgs (org_id));
+diesel::joinable!(nodes -> projects (project_id));
+diesel::joinable!(nodes -> protocol_networks (network_id));
 diesel::joinable!(nodes -> protocol_versions (protocol_version_id));
 diesel::joinable!(nodes -> protocols (protocol_id));
 diesel::joinable!(nodes -> regions (scheduler_region_id));
@@ -784,48 +2009,156 @@ diesel::joinable!(nodes_old -> blockchains_old (blockchain_id));
 diesel::joinable!(nodes_old -> hosts_old (host_id));
 diesel::joinable!(nodes_old -> orgs (org_id));
 diesel::joinable!(nodes_old -> regions (scheduler_region));
+diesel::joinable!(oncall_schedules -> orgs (org_id));
+diesel::joinable!(org_brandings -> orgs (org_id));
+diesel::joinable!(org_key_hosts -> hosts (host_id));
+diesel::joinable!(org_key_hosts -> org_keys (key_id));
+diesel::joinable!(org_keys -> orgs (org_id));
+diesel::joinable!(org_log_sink_hosts -> hosts (host_id));
+diesel::joinable!(org_log_sink_hosts -> orgs (org_id));
+diesel::joinable!(org_log_sinks -> orgs (org_id));
+diesel::joinable!(org_slo_reports -> orgs (org_id));
+diesel::joinable!(org_suspended_nodes -> nodes (node_id));
+diesel::joinable!(org_suspended_nodes -> orgs (org_id));
+diesel::joinable!(org_transfers -> orgs (org_id));
 diesel::joinable!(orgs -> addresses (address_id));
+diesel::joinable!(orgs -> partners (partner_id));
+diesel::joinable!(orgs -> plans (plan_tier));
+diesel::joinable!(pool_autoscale_events -> endpoint_pools (pool_id));
+diesel::joinable!(pool_autoscalers -> endpoint_pools (pool_id));
+diesel::joinable!(pool_autoscalers -> images (image_id));
+diesel::joinable!(pool_autoscalers -> regions (region_id));
+diesel::joinable!(pool_autoscalers -> users (user_id));
+diesel::joinable!(project_members -> projects (project_id));
+diesel::joinable!(project_members -> users (user_id));
+diesel::joinable!(project_teams -> projects (project_id));
+diesel::joinable!(project_teams -> teams (team_id));
+diesel::joinable!(projects -> orgs (org_id));
+diesel::joinable!(protocol_grants -> orgs (org_id));
+diesel::joinable!(protocol_grants -> protocol_versions (protocol_version_id));
+diesel::joinable!(protocol_grants -> protocols (protocol_id));
+diesel::joinable!(protocol_networks -> protocols (protocol_id));
 diesel::joinable!(protocol_versions -> orgs (org_id));
 diesel::joinable!(protocols -> orgs (org_id));
 diesel::joinable!(role_permissions -> permissions (permission));
 diesel::joinable!(role_permissions -> roles (role));
+diesel::joinable!(status_pages -> orgs (org_id));
+diesel::joinable!(team_members -> teams (team_id));
+diesel::joinable!(team_members -> users (user_id));
+diesel::joinable!(team_roles -> roles (role));
+diesel::joinable!(team_roles -> teams (team_id));
+diesel::joinable!(teams -> orgs (org_id));
+diesel::joinable!(ticket_integrations -> orgs (org_id));
+diesel::joinable!(tickets -> incidents (incident_id));
+diesel::joinable!(tickets -> ticket_integrations (integration_id));
 diesel::joinable!(user_roles -> orgs (org_id));
 diesel::joinable!(user_roles -> roles (role));
 diesel::joinable!(user_roles -> users (user_id));
+diesel::joinable!(user_devices -> users (user_id));
+diesel::joinable!(user_export_requests -> users (user_id));
+diesel::joinable!(user_notification_preferences -> users (user_id));
+diesel::joinable!(user_sessions -> users (user_id));
 diesel::joinable!(user_settings -> users (user_id));
+diesel::joinable!(user_signups -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     addresses,
+    agent_upgrade_hosts,
+    agent_upgrades,
+    alerts,
     api_keys,
+    approvals,
     archives,
+    artifacts,
+    audit_logs,
+    backfills,
+    backorders,
     blockchain_node_types_old,
     blockchain_properties_old,
     blockchain_versions_old,
     blockchains_old,
     commands,
     configs,
+    disk_resizes,
+    endpoint_pool_members,
+    endpoint_pools,
+    endpoint_usage,
+    escalation_policies,
+    feature_flags,
+    freeze_windows,
+    host_pools,
+    host_reconciliations,
+    host_weights,
     hosts,
     hosts_old,
     image_properties,
     image_rules,
     images,
+    incident_comments,
+    incident_links,
+    incidents,
     invitations,
     ip_addresses,
+    legal_holds,
+    managed_hosts,
+    mesh_networks,
+    mesh_peers,
+    network_heads,
+    node_abuse_reports,
+    node_certificates,
+    node_dns_aliases,
+    node_downtimes,
+    node_endpoints,
+    node_health_history,
     node_logs,
     node_logs_old,
     node_properties_old,
     node_reports,
+    node_share_accesses,
+    node_shares,
+    node_slo,
     nodes,
     nodes_old,
+    oncall_schedules,
+    org_brandings,
+    org_key_hosts,
+    org_keys,
+    org_log_sink_hosts,
+    org_log_sinks,
+    org_slo_reports,
+    org_suspended_nodes,
+    org_transfers,
     orgs,
+    partners,
     permissions,
+    plans,
+    pool_autoscale_events,
+    pool_autoscalers,
+    project_members,
+    project_teams,
+    projects,
+    protocol_grants,
+    protocol_networks,
     protocol_versions,
     protocols,
     regions,
+    request_logs,
     role_permissions,
     roles,
+    secrets,
+    status_pages,
+    team_members,
+    team_roles,
+    teams,
+    ticket_integrations,
+    tickets,
     tokens,
+    user_devices,
+    user_export_requests,
+    user_notification_preferences,
     user_roles,
+    user_sessions,
     user_settings,
+    user_signups,
     users,
 );
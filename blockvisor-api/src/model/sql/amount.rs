@@ -128,20 +128,25 @@ impl TryFrom<common::Period> for Period {
     }
 }
 
+impl From<Amount> for common::BillingAmount {
+    fn from(cost: Amount) -> Self {
+        common::BillingAmount {
+            amount: Some(common::Amount {
+                currency: common::Currency::from(cost.currency).into(),
+                amount_minor_units: cost.amount,
+            }),
+            period: common::Period::from(cost.period).into(),
+        }
+    }
+}
+
 impl common::BillingAmount {
     pub fn from_host(host: &Host, authz: &AuthZ) -> Option<Self> {
         if !authz.has_perm(HostAdminPerm::ViewCost) {
             return None;
         }
 
-        let cost = host.cost?;
-        Some(common::BillingAmount {
-            amount: Some(common::Amount {
-                currency: common::Currency::from(cost.currency).into(),
-                amount_minor_units: cost.amount,
-            }),
-            period: common::Period::from(cost.period).into(),
-        })
+        host.cost.map(Into::into)
     }
 
     pub fn from_node(node: &Node, authz: &AuthZ) -> Option<Self> {
@@ -149,13 +154,6 @@ impl common::BillingAmount {
             return None;
         }
 
-        let cost = node.cost?;
-        Some(common::BillingAmount {
-            amount: Some(common::Amount {
-                currency: common::Currency::from(cost.currency).into(),
-                amount_minor_units: cost.amount,
-            }),
-            period: common::Period::from(cost.period).into(),
-        })
+        node.cost.map(Into::into)
     }
 }
@@ -0,0 +1,55 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use diesel::deserialize::{FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{deserialize, serialize};
+
+use crate::encryption::{self, Keyring};
+
+/// A sensitive value stored in a `Text` column as sealed by the `Keyring`.
+///
+/// The value stays sealed when read, and is only available by opening it with
+/// the `Keyring` of the `Context`.
+#[derive(Clone, Debug, PartialEq, Eq, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+pub struct Encrypted<T> {
+    sealed: String,
+    value: PhantomData<T>,
+}
+
+impl<T: fmt::Display> Encrypted<T> {
+    /// Seal `value` with the current key of `keyring`.
+    pub fn seal(value: &T, keyring: &Keyring) -> Result<Self, encryption::Error> {
+        keyring.seal(&value.to_string()).map(|sealed| Encrypted {
+            sealed,
+            value: PhantomData,
+        })
+    }
+}
+
+impl<T: From<String>> Encrypted<T> {
+    /// Open the sealed value with any of the keys of `keyring`.
+    pub fn open(&self, keyring: &Keyring) -> Result<T, encryption::Error> {
+        keyring.open(&self.sealed).map(T::from)
+    }
+}
+
+impl<T> FromSql<Text, Pg> for Encrypted<T> {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        let sealed = <String as FromSql<Text, Pg>>::from_sql(value)?;
+        Ok(Encrypted {
+            sealed,
+            value: PhantomData,
+        })
+    }
+}
+
+impl<T: fmt::Debug> ToSql<Text, Pg> for Encrypted<T> {
+    fn to_sql<'a>(&'a self, out: &mut Output<'a, '_, Pg>) -> serialize::Result {
+        <String as ToSql<Text, Pg>>::to_sql(&self.sealed, &mut out.reborrow())
+    }
+}
@@ -1,6 +1,9 @@
 pub mod amount;
 pub use amount::{Amount, Currency, Period};
 
+pub mod encrypted;
+pub use encrypted::Encrypted;
+
 use std::fmt;
 use std::str::FromStr;
 
@@ -19,7 +22,7 @@ use crate::auth::claims::Granted;
 use crate::auth::rbac::Perm;
 use crate::grpc::{Status, common};
 use crate::model::protocol::VersionMetadata;
-use crate::util::LOWER_KEBAB_CASE;
+use crate::util::{LOWER_KEBAB_CASE, secrets};
 
 define_sql_function!(fn coalesce(x: Nullable<Text>, y: Text) -> Text);
 define_sql_function!(fn greatest<T: SingleValue>(x: T, y: T) -> T);
@@ -43,13 +46,15 @@ pub enum Error {
     TagChars(String),
     /// Tag must be at least 3 characters: {0}
     TagLen(String),
+    /// Tag looks like a seed phrase.
+    TagSecret,
 }
 
 impl From<Error> for Status {
     fn from(err: Error) -> Self {
         use Error::*;
         match err {
-            TagChars(_) | TagLen(_) => Status::invalid_argument("tag"),
+            TagChars(_) | TagLen(_) | TagSecret => Status::invalid_argument("tag"),
             _ => Status::internal("Internal error."),
         }
     }
@@ -154,6 +159,8 @@ impl Tag {
             Err(Error::TagLen(tag))
         } else if !tag.chars().all(|c| LOWER_KEBAB_CASE.contains(c)) {
             Err(Error::TagChars(tag))
+        } else if secrets::contains_secret(&tag) {
+            Err(Error::TagSecret)
         } else {
             Ok(Tag(tag))
         }
@@ -164,6 +171,20 @@ impl Tag {
 #[diesel(sql_type = Array<Nullable<Text>>)]
 pub struct Tags(Vec<Tag>);
 
+impl Tags {
+    /// Add the `added` tags that are missing, then drop the `removed` tags.
+    #[must_use]
+    pub fn edit(mut self, added: &[Tag], removed: &[Tag]) -> Self {
+        for tag in added {
+            if !self.0.contains(tag) {
+                self.0.push(tag.clone());
+            }
+        }
+        self.0.retain(|tag| !removed.contains(tag));
+        self
+    }
+}
+
 impl FromSql<Array<Nullable<Text>>, Pg> for Tags {
     fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
         let tags = <Vec<Option<String>> as FromSql<Array<Nullable<Text>>, Pg>>::from_sql(value)?;
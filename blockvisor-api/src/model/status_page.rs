@@ -0,0 +1,302 @@
+//! Customer-facing status pages showing the health of selected org nodes.
+//!
+//! A page selects nodes either directly by id, or as groups through any of
+//! the node tags. Public pages of active orgs are served without auth at their
+//! slug, or at their custom domain once it points at the api and has been
+//! verified.
+//!
+//! A custom domain is verified by publishing the `domain_token` of the page as
+//! a TXT record at `_blockvisor-status.{domain}`, so that an org can't claim
+//! a domain that it doesn't control.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr, Into};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{NodeId, OrgId};
+use crate::cloudflare::Dns;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::util::{LOWER_KEBAB_CASE, NanosUtc};
+
+use super::Node;
+use super::schema::{nodes, orgs, status_pages};
+
+const DOMAIN_CHARS: &str = "abcdefghijklmnopqrstuvwxyz1234567890-.";
+
+/// The subdomain of a custom domain holding its verification TXT record.
+const DOMAIN_CHALLENGE: &str = "_blockvisor-status";
+
+/// The number of characters of a domain verification token.
+const DOMAIN_TOKEN_LEN: usize = 32;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find status page `{0}`: {1}
+    ById(StatusPageId, diesel::result::Error),
+    /// Failed to find public status page: {0}
+    ByDomain(diesel::result::Error),
+    /// Failed to find status pages for org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Failed to find public status page: {0}
+    BySlug(diesel::result::Error),
+    /// Failed to create status page: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete status page `{0}`: {1}
+    Delete(StatusPageId, diesel::result::Error),
+    /// Invalid custom domain: {0}
+    DomainChars(String),
+    /// Failed to look up domain verification record: {0}
+    DomainLookup(crate::cloudflare::Error),
+    /// Status page `{0}` has no custom domain.
+    DomainMissing(StatusPageId),
+    /// No TXT record at `{0}` matches the domain token.
+    DomainUnverified(String),
+    /// Failed to find nodes of status page `{0}`: {1}
+    Nodes(StatusPageId, diesel::result::Error),
+    /// Status page slug `{0}` must be lower-kebab-case.
+    SlugChars(String),
+    /// Status page slug `{0}` must be at least 3 characters.
+    SlugLen(String),
+    /// Failed to mark the domain of status page `{0}` as verified: {1}
+    VerifyDomain(StatusPageId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Status page slug or domain is already in use.")
+            }
+            ById(_, NotFound) | ByDomain(NotFound) | BySlug(NotFound) => {
+                Status::not_found("Status page not found.")
+            }
+            DomainChars(_) => Status::invalid_argument("custom_domain"),
+            DomainMissing(_) => Status::failed_precondition("Status page has no custom domain."),
+            DomainUnverified(name) => Status::failed_precondition(format!(
+                "No TXT record at `{name}` matches the domain token."
+            )),
+            SlugChars(_) | SlugLen(_) => Status::invalid_argument("slug"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct StatusPageId(Uuid);
+
+#[derive(Clone, Debug, Display, PartialEq, Eq, DieselNewType, Deref, Into)]
+pub struct StatusPageSlug(String);
+
+impl StatusPageSlug {
+    pub fn new(slug: String) -> Result<Self, Error> {
+        if slug.len() < 3 {
+            Err(Error::SlugLen(slug))
+        } else if !slug.chars().all(|c| LOWER_KEBAB_CASE.contains(c)) {
+            Err(Error::SlugChars(slug))
+        } else {
+            Ok(StatusPageSlug(slug))
+        }
+    }
+}
+
+#[derive(Clone, Debug, Display, PartialEq, Eq, DieselNewType, Deref, Into)]
+pub struct CustomDomain(String);
+
+impl CustomDomain {
+    pub fn new(domain: &str) -> Result<Self, Error> {
+        let domain = domain.trim().trim_end_matches('.').to_lowercase();
+        let valid = domain.contains('.')
+            && domain.chars().all(|c| DOMAIN_CHARS.contains(c))
+            && domain
+                .split('.')
+                .all(|label| !label.is_empty() && !label.starts_with('-'));
+
+        if valid {
+            Ok(CustomDomain(domain))
+        } else {
+            Err(Error::DomainChars(domain))
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct StatusPage {
+    pub id: StatusPageId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub slug: StatusPageSlug,
+    pub is_public: bool,
+    pub custom_domain: Option<CustomDomain>,
+    /// The TXT record content that verifies the custom domain.
+    pub domain_token: Option<String>,
+    pub domain_verified_at: Option<DateTime<Utc>>,
+    pub node_ids: Vec<Option<NodeId>>,
+    pub node_tags: Vec<Option<String>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StatusPage {
+    pub async fn by_id(id: StatusPageId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        status_pages::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        status_pages::table
+            .filter(status_pages::org_id.eq(org_id))
+            .order_by(status_pages::name.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    /// A public page of an org that is neither deleted nor suspended.
+    pub async fn public_by_slug(slug: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        status_pages::table
+            .inner_join(orgs::table)
+            .filter(status_pages::slug.eq(slug))
+            .filter(status_pages::is_public)
+            .filter(orgs::deleted_at.is_null())
+            .filter(orgs::suspended_at.is_null())
+            .select(StatusPage::as_select())
+            .get_result(conn)
+            .await
+            .map_err(Error::BySlug)
+    }
+
+    /// A public page of an active org, once its custom domain is verified.
+    pub async fn public_by_domain(domain: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        status_pages::table
+            .inner_join(orgs::table)
+            .filter(status_pages::custom_domain.eq(domain.to_lowercase()))
+            .filter(status_pages::domain_verified_at.is_not_null())
+            .filter(status_pages::is_public)
+            .filter(orgs::deleted_at.is_null())
+            .filter(orgs::suspended_at.is_null())
+            .select(StatusPage::as_select())
+            .get_result(conn)
+            .await
+            .map_err(Error::ByDomain)
+    }
+
+    /// Mark the custom domain as verified if a TXT record at its challenge
+    /// name matches the domain token.
+    pub async fn verify_domain(
+        &self,
+        dns: &(dyn Dns + Send + Sync),
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        let (Some(domain), Some(token)) = (&self.custom_domain, &self.domain_token) else {
+            return Err(Error::DomainMissing(self.id));
+        };
+
+        let name = format!("{DOMAIN_CHALLENGE}.{domain}");
+        let records = dns.lookup_txt(&name).await.map_err(Error::DomainLookup)?;
+        if !records.iter().any(|record| record == token) {
+            return Err(Error::DomainUnverified(name));
+        }
+
+        diesel::update(status_pages::table.find(self.id))
+            .set(status_pages::domain_verified_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::VerifyDomain(self.id, err))
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let id = self.id;
+        diesel::delete(status_pages::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+
+    /// The nodes of the org selected by id or by any of the tags.
+    pub async fn nodes(&self, conn: &mut Conn<'_>) -> Result<Vec<Node>, Error> {
+        let node_ids: Vec<NodeId> = self.node_ids.iter().flatten().copied().collect();
+
+        nodes::table
+            .filter(nodes::org_id.eq(self.org_id))
+            .filter(nodes::deleted_at.is_null())
+            .filter(
+                nodes::id
+                    .eq_any(node_ids)
+                    .or(nodes::tags.overlaps_with(&self.node_tags)),
+            )
+            .order_by(dsl::lower(nodes::display_name).asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Nodes(self.id, err))
+    }
+}
+
+impl From<StatusPage> for api::StatusPage {
+    fn from(page: StatusPage) -> Self {
+        api::StatusPage {
+            status_page_id: page.id.to_string(),
+            org_id: page.org_id.to_string(),
+            name: page.name,
+            slug: page.slug.into(),
+            is_public: page.is_public,
+            custom_domain: page.custom_domain.map(Into::into),
+            domain_token: page.domain_token,
+            domain_verified: page.domain_verified_at.is_some(),
+            node_ids: page
+                .node_ids
+                .iter()
+                .flatten()
+                .map(ToString::to_string)
+                .collect(),
+            node_tags: page.node_tags.into_iter().flatten().collect(),
+            created_at: Some(NanosUtc::from(page.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = status_pages)]
+pub struct NewStatusPage {
+    pub org_id: OrgId,
+    pub name: String,
+    pub slug: StatusPageSlug,
+    pub is_public: bool,
+    pub custom_domain: Option<CustomDomain>,
+    pub domain_token: Option<String>,
+    pub node_ids: Vec<Option<NodeId>>,
+    pub node_tags: Vec<Option<String>>,
+}
+
+impl NewStatusPage {
+    /// A new token for verifying a custom domain.
+    pub fn domain_token() -> String {
+        rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(DOMAIN_TOKEN_LEN)
+            .map(char::from)
+            .collect()
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<StatusPage, Error> {
+        diesel::insert_into(status_pages::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -0,0 +1,427 @@
+//! Teams of org members that share roles and project access.
+//!
+//! A team belongs to one org, and only members of that org may join it. The
+//! org roles granted to a team apply to each of its members as if they were
+//! granted to the user directly, so that large orgs can manage access per team
+//! rather than per user.
+//!
+//! A team bound to a project gives its members the same access to the nodes of
+//! that project as its project members.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::{ForeignKeyViolation, UniqueViolation};
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::rbac::{OrgRole, Role};
+use crate::auth::resource::{OrgId, UserId};
+use crate::database::{CacheKey, Conn};
+use crate::grpc::{Status, api};
+use crate::util::NanosUtc;
+
+use super::ProjectId;
+use super::schema::{project_teams, team_members, team_roles, teams};
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to add member to team `{0}`: {1}
+    AddMember(TeamId, diesel::result::Error),
+    /// Failed to add project to team `{0}`: {1}
+    AddProject(TeamId, diesel::result::Error),
+    /// Failed to find team `{0}`: {1}
+    ById(TeamId, diesel::result::Error),
+    /// Failed to find teams for org `{0}`: {1}
+    ByOrgId(OrgId, diesel::result::Error),
+    /// Failed to create team: {0}
+    Create(diesel::result::Error),
+    /// Failed to delete team `{0}`: {1}
+    Delete(TeamId, diesel::result::Error),
+    /// Team name must not be empty.
+    EmptyName,
+    /// Failed to grant role `{1}` to team `{0}`: {2}
+    GrantRole(TeamId, Role, diesel::result::Error),
+    /// Failed to find team members: {0}
+    Members(diesel::result::Error),
+    /// Failed to parse team role: {0}
+    ParseRole(String),
+    /// Failed to find team projects: {0}
+    Projects(diesel::result::Error),
+    /// Failed to remove member from team `{0}`: {1}
+    RemoveMember(TeamId, diesel::result::Error),
+    /// Failed to remove project from team `{0}`: {1}
+    RemoveProject(TeamId, diesel::result::Error),
+    /// Failed to remove user `{0}` from the teams of org `{1}`: {2}
+    RemoveUser(UserId, OrgId, diesel::result::Error),
+    /// Failed to revoke role `{1}` from team `{0}`: {2}
+    RevokeRole(TeamId, Role, diesel::result::Error),
+    /// Role `{0}` can't be granted to a team.
+    RoleNotGrantable(Role),
+    /// Failed to find team roles: {0}
+    Roles(diesel::result::Error),
+    /// Failed to update team `{0}`: {1}
+    Update(TeamId, diesel::result::Error),
+    /// Failed to find the team roles of user `{0}` in org `{1}`: {2}
+    UserRoles(UserId, OrgId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _))
+            | Update(_, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Team already exists.")
+            }
+            AddMember(_, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("User is already a team member.")
+            }
+            AddMember(_, DatabaseError(ForeignKeyViolation, _)) => {
+                Status::not_found("User not found.")
+            }
+            AddProject(_, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Team already has that project.")
+            }
+            AddProject(_, DatabaseError(ForeignKeyViolation, _)) => {
+                Status::not_found("Project not found.")
+            }
+            GrantRole(_, _, DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Team already has that role.")
+            }
+            ById(_, NotFound) | Delete(_, NotFound) | Update(_, NotFound) => {
+                Status::not_found("Team not found.")
+            }
+            RemoveMember(_, NotFound) => Status::not_found("User is not a team member."),
+            RemoveProject(_, NotFound) => Status::not_found("Team does not have that project."),
+            RevokeRole(_, _, NotFound) => Status::not_found("Team does not have that role."),
+            EmptyName => Status::invalid_argument("name"),
+            RoleNotGrantable(_) => Status::invalid_argument("role"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct TeamId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Team {
+    pub id: TeamId,
+    pub org_id: OrgId,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Team {
+    pub async fn by_id(id: TeamId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        teams::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org_id(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        teams::table
+            .filter(teams::org_id.eq(org_id))
+            .order_by(teams::name)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByOrgId(org_id, err))
+    }
+
+    /// Delete a team, which removes its roles from each of its members.
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let member_ids = Self::member_ids(&HashSet::from([self.id]), conn).await?;
+        let deleted = diesel::delete(teams::table.find(self.id))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::Delete(self.id, err))?;
+        for user_id in member_ids.get(&self.id).into_iter().flatten() {
+            conn.invalidate(CacheKey::OrgRoles(*user_id, self.org_id))
+                .await;
+        }
+
+        if deleted == 0 {
+            Err(Error::Delete(self.id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The user ids of the members of each team.
+    pub async fn member_ids(
+        team_ids: &HashSet<TeamId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<TeamId, Vec<UserId>>, Error> {
+        let members: Vec<(TeamId, UserId)> = team_members::table
+            .filter(team_members::team_id.eq_any(team_ids))
+            .select((team_members::team_id, team_members::user_id))
+            .order_by(team_members::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::Members)?;
+
+        let mut member_ids: HashMap<TeamId, Vec<UserId>> = HashMap::new();
+        for (team_id, user_id) in members {
+            member_ids.entry(team_id).or_default().push(user_id);
+        }
+        Ok(member_ids)
+    }
+
+    /// The roles granted to each team.
+    pub async fn roles(
+        team_ids: &HashSet<TeamId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<TeamId, Vec<Role>>, Error> {
+        let rows: Vec<(TeamId, String)> = team_roles::table
+            .filter(team_roles::team_id.eq_any(team_ids))
+            .select((team_roles::team_id, team_roles::role))
+            .order_by(team_roles::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::Roles)?;
+
+        let mut roles: HashMap<TeamId, Vec<Role>> = HashMap::new();
+        for (team_id, role) in rows {
+            let role = role.parse().map_err(Error::ParseRole)?;
+            roles.entry(team_id).or_default().push(role);
+        }
+        Ok(roles)
+    }
+
+    /// The projects that each team has access to.
+    pub async fn project_ids(
+        team_ids: &HashSet<TeamId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashMap<TeamId, Vec<ProjectId>>, Error> {
+        let rows: Vec<(TeamId, ProjectId)> = project_teams::table
+            .filter(project_teams::team_id.eq_any(team_ids))
+            .select((project_teams::team_id, project_teams::project_id))
+            .order_by(project_teams::created_at)
+            .get_results(conn)
+            .await
+            .map_err(Error::Projects)?;
+
+        let mut project_ids: HashMap<TeamId, Vec<ProjectId>> = HashMap::new();
+        for (team_id, project_id) in rows {
+            project_ids.entry(team_id).or_default().push(project_id);
+        }
+        Ok(project_ids)
+    }
+
+    /// The roles that `user_id` has in `org_id` through its teams.
+    pub async fn user_roles(
+        user_id: UserId,
+        org_id: OrgId,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<Role>, Error> {
+        team_roles::table
+            .inner_join(teams::table.inner_join(team_members::table))
+            .filter(teams::org_id.eq(org_id))
+            .filter(team_members::user_id.eq(user_id))
+            .select(team_roles::role)
+            .distinct()
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::UserRoles(user_id, org_id, err))?
+            .into_iter()
+            .map(|role: String| role.parse().map_err(Error::ParseRole))
+            .collect()
+    }
+
+    pub async fn add_member(&self, user_id: UserId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::insert_into(team_members::table)
+            .values((
+                team_members::team_id.eq(self.id),
+                team_members::user_id.eq(user_id),
+            ))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::AddMember(self.id, err))?;
+        conn.invalidate(CacheKey::OrgRoles(user_id, self.org_id))
+            .await;
+
+        Ok(())
+    }
+
+    pub async fn remove_member(&self, user_id: UserId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let removed = diesel::delete(team_members::table.find((self.id, user_id)))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::RemoveMember(self.id, err))?;
+        conn.invalidate(CacheKey::OrgRoles(user_id, self.org_id))
+            .await;
+
+        if removed == 0 {
+            Err(Error::RemoveMember(self.id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Remove a user that is leaving `org_id` from each of its teams.
+    pub async fn remove_user(
+        user_id: UserId,
+        org_id: OrgId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let org_teams = teams::table
+            .filter(teams::org_id.eq(org_id))
+            .select(teams::id);
+        diesel::delete(team_members::table)
+            .filter(team_members::user_id.eq(user_id))
+            .filter(team_members::team_id.eq_any(org_teams))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::RemoveUser(user_id, org_id, err))?;
+        conn.invalidate(CacheKey::OrgRoles(user_id, org_id)).await;
+
+        Ok(())
+    }
+
+    /// Grant an org role to each member of the team.
+    ///
+    /// Ownership of an org is never shared through a team.
+    pub async fn grant_role(&self, role: Role, conn: &mut Conn<'_>) -> Result<(), Error> {
+        if !matches!(role, Role::Org(OrgRole::Admin | OrgRole::Member)) {
+            return Err(Error::RoleNotGrantable(role));
+        }
+
+        diesel::insert_into(team_roles::table)
+            .values((
+                team_roles::team_id.eq(self.id),
+                team_roles::role.eq(role.to_string()),
+            ))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::GrantRole(self.id, role, err))?;
+        self.invalidate_roles(conn).await
+    }
+
+    pub async fn revoke_role(&self, role: Role, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let revoked = diesel::delete(team_roles::table.find((self.id, role.to_string())))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::RevokeRole(self.id, role, err))?;
+        self.invalidate_roles(conn).await?;
+
+        if revoked == 0 {
+            Err(Error::RevokeRole(self.id, role, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Give the members of the team access to a project of its org.
+    pub async fn add_project(
+        &self,
+        project_id: ProjectId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        diesel::insert_into(project_teams::table)
+            .values((
+                project_teams::project_id.eq(project_id),
+                project_teams::team_id.eq(self.id),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::AddProject(self.id, err))
+    }
+
+    pub async fn remove_project(
+        &self,
+        project_id: ProjectId,
+        conn: &mut Conn<'_>,
+    ) -> Result<(), Error> {
+        let removed = diesel::delete(project_teams::table.find((project_id, self.id)))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::RemoveProject(self.id, err))?;
+
+        if removed == 0 {
+            Err(Error::RemoveProject(self.id, NotFound))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Forget the cached org roles of each team member once the roles of the
+    /// team are written.
+    async fn invalidate_roles(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let member_ids = Self::member_ids(&HashSet::from([self.id]), conn).await?;
+        for user_id in member_ids.get(&self.id).into_iter().flatten() {
+            conn.invalidate(CacheKey::OrgRoles(*user_id, self.org_id))
+                .await;
+        }
+        Ok(())
+    }
+
+    pub fn into_api(
+        self,
+        member_ids: &[UserId],
+        roles: &[Role],
+        project_ids: &[ProjectId],
+    ) -> api::Team {
+        api::Team {
+            team_id: self.id.to_string(),
+            org_id: self.org_id.to_string(),
+            name: self.name,
+            member_ids: member_ids.iter().map(ToString::to_string).collect(),
+            roles: roles.iter().map(ToString::to_string).collect(),
+            project_ids: project_ids.iter().map(ToString::to_string).collect(),
+            created_at: Some(NanosUtc::from(self.created_at).into()),
+            updated_at: Some(NanosUtc::from(self.updated_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = teams)]
+pub struct NewTeam<'t> {
+    pub org_id: OrgId,
+    pub name: &'t str,
+}
+
+impl NewTeam<'_> {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Team, Error> {
+        if self.name.trim().is_empty() {
+            return Err(Error::EmptyName);
+        }
+
+        diesel::insert_into(teams::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
+
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = teams)]
+pub struct UpdateTeam<'t> {
+    pub name: Option<&'t str>,
+}
+
+impl UpdateTeam<'_> {
+    pub async fn apply(self, id: TeamId, conn: &mut Conn<'_>) -> Result<Team, Error> {
+        if self.name.is_some_and(|name| name.trim().is_empty()) {
+            return Err(Error::EmptyName);
+        }
+
+        diesel::update(teams::table.find(id))
+            .set((self, teams::updated_at.eq(Utc::now())))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(id, err))
+    }
+}
@@ -0,0 +1,386 @@
+//! Tickets mirror incidents into an org's own Zendesk or Jira.
+//!
+//! Each org may configure one `TicketIntegration` with the credentials used to
+//! call the provider. A `Ticket` links an incident to the external ticket, and
+//! records the incident status last synced so that changes on either side are
+//! only pushed once. The provider calls back through a webhook authenticated by
+//! a secret that is only returned once on creation, and is only stored as a
+//! hash with a sealed salt.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::DatabaseErrorKind::UniqueViolation;
+use diesel::result::Error::{DatabaseError, NotFound};
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+
+use crate::auth::resource::{OrgId, Resource, ResourceId, ResourceType};
+use crate::auth::token::api_key::{KeyHash, Salt, Secret};
+use crate::database::{Conn, WriteConn};
+use crate::encryption::Keyring;
+use crate::grpc::{Status, api, common};
+use crate::util::NanosUtc;
+
+use super::incident::{Incident, IncidentId, IncidentStatus};
+use super::schema::{incidents, sql_types, ticket_integrations, tickets};
+use super::sql::Encrypted;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find ticket `{0}`: {1}
+    ByExternal(String, diesel::result::Error),
+    /// Failed to find ticket integration `{0}`: {1}
+    ById(TicketIntegrationId, diesel::result::Error),
+    /// Failed to find ticket for incident `{0}`: {1}
+    ByIncident(IncidentId, diesel::result::Error),
+    /// Failed to find ticket integration for org `{0}`: {1}
+    ByOrg(OrgId, diesel::result::Error),
+    /// Failed to create ticket integration: {0}
+    Create(diesel::result::Error),
+    /// Failed to create ticket for incident `{0}`: {1}
+    CreateTicket(IncidentId, diesel::result::Error),
+    /// Failed to delete ticket integration `{0}`: {1}
+    Delete(TicketIntegrationId, diesel::result::Error),
+    /// Ticket integration encryption: {0}
+    Encryption(crate::encryption::Error),
+    /// Jira integrations require a project key.
+    MissingProjectKey,
+    /// Failed to find out of sync tickets: {0}
+    OutOfSync(diesel::result::Error),
+    /// Invalid ticket integration base url: {0}
+    ParseBaseUrl(url::ParseError),
+    /// Failed to find incidents pending a ticket: {0}
+    Pending(diesel::result::Error),
+    /// Unknown TicketProvider.
+    UnknownProvider,
+    /// Failed to update ticket `{0}`: {1}
+    Update(Uuid, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Create(DatabaseError(UniqueViolation, _)) => {
+                Status::already_exists("Org already has a ticket integration.")
+            }
+            ById(_, NotFound) | ByOrg(_, NotFound) | Delete(_, NotFound) => {
+                Status::not_found("Ticket integration not found.")
+            }
+            ByIncident(_, NotFound) | ByExternal(_, NotFound) => {
+                Status::not_found("Ticket not found.")
+            }
+            MissingProjectKey => Status::invalid_argument("project_key"),
+            ParseBaseUrl(_) => Status::invalid_argument("base_url"),
+            UnknownProvider => Status::invalid_argument("provider"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct TicketIntegrationId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumTicketProvider"]
+pub enum TicketProvider {
+    Zendesk,
+    Jira,
+}
+
+impl From<TicketProvider> for api::TicketProvider {
+    fn from(provider: TicketProvider) -> Self {
+        match provider {
+            TicketProvider::Zendesk => api::TicketProvider::Zendesk,
+            TicketProvider::Jira => api::TicketProvider::Jira,
+        }
+    }
+}
+
+impl TryFrom<api::TicketProvider> for TicketProvider {
+    type Error = Error;
+
+    fn try_from(provider: api::TicketProvider) -> Result<Self, Self::Error> {
+        match provider {
+            api::TicketProvider::Unspecified => Err(Error::UnknownProvider),
+            api::TicketProvider::Zendesk => Ok(TicketProvider::Zendesk),
+            api::TicketProvider::Jira => Ok(TicketProvider::Jira),
+        }
+    }
+}
+
+/// The provider credentials of an org. The `api_token` is never returned.
+#[derive(Queryable, Selectable)]
+pub struct TicketIntegration {
+    pub id: TicketIntegrationId,
+    pub org_id: OrgId,
+    pub provider: TicketProvider,
+    pub base_url: String,
+    pub username: String,
+    /// Encrypted at rest, see `crate::encryption`.
+    pub api_token: Encrypted<String>,
+    pub project_key: Option<String>,
+    pub webhook_hash: KeyHash,
+    /// Encrypted at rest, see `crate::encryption`.
+    pub webhook_salt: Encrypted<Salt>,
+    pub created_by_type: ResourceType,
+    pub created_by_id: ResourceId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TicketIntegration {
+    pub async fn by_id(id: TicketIntegrationId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        ticket_integrations::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_org(org_id: OrgId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        ticket_integrations::table
+            .filter(ticket_integrations::org_id.eq(org_id))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByOrg(org_id, err))
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let id = self.id;
+        diesel::delete(ticket_integrations::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+
+    /// Whether `token` is the webhook secret returned on creation.
+    pub fn verify_webhook(&self, token: &str, keyring: &Keyring) -> Result<bool, Error> {
+        let Ok(secret) = Secret::decode(token) else {
+            return Ok(false);
+        };
+        let salt = self.webhook_salt.open(keyring).map_err(Error::Encryption)?;
+
+        Ok(KeyHash::from(&salt, &secret) == self.webhook_hash)
+    }
+
+    pub fn created_by(&self) -> Resource {
+        Resource::new(self.created_by_type, self.created_by_id)
+    }
+}
+
+impl From<TicketIntegration> for api::TicketIntegration {
+    fn from(integration: TicketIntegration) -> Self {
+        let created_by = common::Resource::from(integration.created_by());
+        api::TicketIntegration {
+            integration_id: integration.id.to_string(),
+            org_id: integration.org_id.to_string(),
+            provider: api::TicketProvider::from(integration.provider).into(),
+            base_url: integration.base_url,
+            username: integration.username,
+            project_key: integration.project_key,
+            created_by: Some(created_by),
+            created_at: Some(NanosUtc::from(integration.created_at).into()),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = ticket_integrations)]
+struct InsertIntegration {
+    org_id: OrgId,
+    provider: TicketProvider,
+    base_url: String,
+    username: String,
+    api_token: Encrypted<String>,
+    project_key: Option<String>,
+    webhook_hash: KeyHash,
+    webhook_salt: Encrypted<Salt>,
+    created_by_type: ResourceType,
+    created_by_id: ResourceId,
+}
+
+pub struct NewTicketIntegration {
+    pub org_id: OrgId,
+    pub provider: TicketProvider,
+    pub base_url: String,
+    pub username: String,
+    pub api_token: String,
+    pub project_key: Option<String>,
+    pub created_by: Resource,
+}
+
+impl NewTicketIntegration {
+    pub async fn create(self, write: &mut WriteConn<'_, '_>) -> Result<Created, Error> {
+        let base_url: Url = self.base_url.parse().map_err(Error::ParseBaseUrl)?;
+        let project_key = self.project_key.filter(|key| !key.is_empty());
+        if self.provider == TicketProvider::Jira && project_key.is_none() {
+            return Err(Error::MissingProjectKey);
+        }
+
+        let mut rng = write.ctx.rng.lock().await;
+        let salt = Salt::generate(&mut *rng);
+        let secret = Secret::generate(&mut *rng);
+        drop(rng);
+
+        let keyring = &write.ctx.keyring;
+        let api_token = Encrypted::seal(&self.api_token, keyring).map_err(Error::Encryption)?;
+        let webhook_salt = Encrypted::seal(&salt, keyring).map_err(Error::Encryption)?;
+
+        let insert = InsertIntegration {
+            org_id: self.org_id,
+            provider: self.provider,
+            base_url: base_url.as_str().trim_end_matches('/').to_string(),
+            username: self.username,
+            api_token,
+            project_key,
+            webhook_hash: KeyHash::from(&salt, &secret),
+            webhook_salt,
+            created_by_type: self.created_by.typ(),
+            created_by_id: self.created_by.id(),
+        };
+
+        let integration = diesel::insert_into(ticket_integrations::table)
+            .values(insert)
+            .get_result(write)
+            .await
+            .map_err(Error::Create)?;
+
+        Ok(Created {
+            integration,
+            webhook_token: secret.encode(),
+        })
+    }
+}
+
+/// A new `TicketIntegration` plus the webhook token returned once on creation.
+pub struct Created {
+    pub integration: TicketIntegration,
+    pub webhook_token: String,
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct Ticket {
+    pub id: Uuid,
+    pub incident_id: IncidentId,
+    pub integration_id: TicketIntegrationId,
+    pub external_id: String,
+    pub url: String,
+    pub status: IncidentStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Ticket {
+    pub async fn by_incident(
+        incident_id: IncidentId,
+        conn: &mut Conn<'_>,
+    ) -> Result<Option<Self>, Error> {
+        tickets::table
+            .filter(tickets::incident_id.eq(incident_id))
+            .get_result(conn)
+            .await
+            .optional()
+            .map_err(|err| Error::ByIncident(incident_id, err))
+    }
+
+    pub async fn by_external(
+        integration_id: TicketIntegrationId,
+        external_id: &str,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        tickets::table
+            .filter(tickets::integration_id.eq(integration_id))
+            .filter(tickets::external_id.eq(external_id))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ByExternal(external_id.to_string(), err))
+    }
+
+    /// Incidents opened since their org added an integration without a ticket.
+    pub async fn pending(conn: &mut Conn<'_>) -> Result<Vec<(Incident, TicketIntegration)>, Error> {
+        incidents::table
+            .inner_join(
+                ticket_integrations::table.on(ticket_integrations::org_id
+                    .eq(incidents::org_id)
+                    .and(incidents::created_at.ge(ticket_integrations::created_at))),
+            )
+            .left_join(tickets::table.on(tickets::incident_id.eq(incidents::id)))
+            .filter(tickets::id.is_null())
+            .select((Incident::as_select(), TicketIntegration::as_select()))
+            .get_results(conn)
+            .await
+            .map_err(Error::Pending)
+    }
+
+    /// Tickets whose incident status changed since it was last synced.
+    pub async fn out_of_sync(
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<(Ticket, Incident, TicketIntegration)>, Error> {
+        tickets::table
+            .inner_join(incidents::table)
+            .inner_join(ticket_integrations::table)
+            .filter(tickets::status.ne(incidents::status))
+            .select((
+                Ticket::as_select(),
+                Incident::as_select(),
+                TicketIntegration::as_select(),
+            ))
+            .get_results(conn)
+            .await
+            .map_err(Error::OutOfSync)
+    }
+
+    /// Record `status` as the incident status known to the provider.
+    pub async fn set_status(
+        &self,
+        status: IncidentStatus,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::update(tickets::table.find(self.id))
+            .set((
+                tickets::status.eq(status),
+                tickets::updated_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(self.id, err))
+    }
+}
+
+impl From<Ticket> for api::IncidentTicket {
+    fn from(ticket: Ticket) -> Self {
+        api::IncidentTicket {
+            external_id: ticket.external_id,
+            url: ticket.url,
+            created_at: Some(NanosUtc::from(ticket.created_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tickets)]
+pub struct NewTicket {
+    pub incident_id: IncidentId,
+    pub integration_id: TicketIntegrationId,
+    pub external_id: String,
+    pub url: String,
+    pub status: IncidentStatus,
+}
+
+impl NewTicket {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<Ticket, Error> {
+        let incident_id = self.incident_id;
+        diesel::insert_into(tickets::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::CreateTicket(incident_id, err))
+    }
+}
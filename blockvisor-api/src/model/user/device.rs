@@ -0,0 +1,183 @@
+//! Mobile devices of a user that receive push notifications.
+//!
+//! A push token identifies one app install, so registering a token that is
+//! already known moves it to the registering user.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel::upsert::excluded;
+use diesel_async::RunQueryDsl;
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::UserId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::{sql_types, user_devices};
+use crate::util::NanosUtc;
+
+/// The longest accepted push token.
+const MAX_TOKEN_LEN: usize = 4096;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to find user device `{0}`: {1}
+    ById(UserDeviceId, diesel::result::Error),
+    /// Failed to find devices of user `{0}`: {1}
+    ByUser(UserId, diesel::result::Error),
+    /// Failed to find devices of users: {0}
+    ByUsers(diesel::result::Error),
+    /// Failed to delete user device `{0}`: {1}
+    Delete(UserDeviceId, diesel::result::Error),
+    /// Push token must not be empty or longer than 4096 characters.
+    PushToken,
+    /// Failed to register user device: {0}
+    Register(diesel::result::Error),
+    /// Unknown PushPlatform.
+    UnknownPlatform,
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            ById(_, NotFound) | Delete(_, NotFound) => Status::not_found("Device not found."),
+            PushToken => Status::invalid_argument("push_token"),
+            UnknownPlatform => Status::invalid_argument("platform"),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct UserDeviceId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumPushPlatform"]
+pub enum PushPlatform {
+    Fcm,
+    Apns,
+}
+
+impl From<PushPlatform> for api::PushPlatform {
+    fn from(platform: PushPlatform) -> Self {
+        match platform {
+            PushPlatform::Fcm => api::PushPlatform::Fcm,
+            PushPlatform::Apns => api::PushPlatform::Apns,
+        }
+    }
+}
+
+impl TryFrom<api::PushPlatform> for PushPlatform {
+    type Error = Error;
+
+    fn try_from(platform: api::PushPlatform) -> Result<Self, Self::Error> {
+        match platform {
+            api::PushPlatform::Unspecified => Err(Error::UnknownPlatform),
+            api::PushPlatform::Fcm => Ok(PushPlatform::Fcm),
+            api::PushPlatform::Apns => Ok(PushPlatform::Apns),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct UserDevice {
+    pub id: UserDeviceId,
+    pub user_id: UserId,
+    pub platform: PushPlatform,
+    pub push_token: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl UserDevice {
+    pub async fn by_id(id: UserDeviceId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        user_devices::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::ById(id, err))
+    }
+
+    pub async fn by_user(user_id: UserId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        user_devices::table
+            .filter(user_devices::user_id.eq(user_id))
+            .order_by(user_devices::last_seen_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByUser(user_id, err))
+    }
+
+    pub async fn by_users(
+        user_ids: &HashSet<UserId>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        user_devices::table
+            .filter(user_devices::user_id.eq_any(user_ids))
+            .get_results(conn)
+            .await
+            .map_err(Error::ByUsers)
+    }
+
+    pub async fn delete(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let id = self.id;
+        diesel::delete(user_devices::table.find(id))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(id, err))
+    }
+}
+
+impl From<UserDevice> for api::UserDevice {
+    fn from(device: UserDevice) -> Self {
+        api::UserDevice {
+            device_id: device.id.to_string(),
+            user_id: device.user_id.to_string(),
+            platform: api::PushPlatform::from(device.platform).into(),
+            name: device.name,
+            created_at: Some(NanosUtc::from(device.created_at).into()),
+            last_seen_at: Some(NanosUtc::from(device.last_seen_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_devices)]
+pub struct NewUserDevice {
+    pub user_id: UserId,
+    pub platform: PushPlatform,
+    pub push_token: String,
+    pub name: String,
+}
+
+impl NewUserDevice {
+    /// Register the device, or refresh it when its token is already known.
+    pub async fn register(self, conn: &mut Conn<'_>) -> Result<UserDevice, Error> {
+        if self.push_token.is_empty() || self.push_token.len() > MAX_TOKEN_LEN {
+            return Err(Error::PushToken);
+        }
+
+        diesel::insert_into(user_devices::table)
+            .values(self)
+            .on_conflict(user_devices::push_token)
+            .do_update()
+            .set((
+                user_devices::user_id.eq(excluded(user_devices::user_id)),
+                user_devices::platform.eq(excluded(user_devices::platform)),
+                user_devices::name.eq(excluded(user_devices::name)),
+                user_devices::last_seen_at.eq(Utc::now()),
+            ))
+            .get_result(conn)
+            .await
+            .map_err(Error::Register)
+    }
+}
@@ -0,0 +1,327 @@
+//! A portable export of all data held about a user.
+//!
+//! An export is requested by storing a pending `UserExportRequest`, which
+//! `job::run_user_exports` claims, assembles and emails. A failed export stays
+//! pending and is retried after `RETRY_AFTER`, until it fails `MAX_ATTEMPTS`
+//! times. A claim that is never released (such as after a crash) expires after
+//! `CLAIM_LEASE`, so no request is lost on a restart.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use diesel_derive_enum::DbEnum;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::{Resource, ResourceType, UserId};
+use crate::database::Conn;
+use crate::model::audit::AuditEvent;
+use crate::model::schema::{nodes, orgs, sql_types, user_export_requests, user_roles};
+use crate::model::{ApiKey, AuditLog};
+
+use super::User;
+
+/// Export requests are no longer retried after this many failed attempts.
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// How long after a failed attempt to wait before retrying an export.
+const RETRY_AFTER: TimeDelta = TimeDelta::minutes(5);
+
+/// How long a claim on an export request lasts unless released.
+const CLAIM_LEASE: TimeDelta = TimeDelta::minutes(10);
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// User export api key error: {0}
+    ApiKey(#[from] crate::model::api_key::Error),
+    /// User export audit error: {0}
+    Audit(#[from] crate::model::audit::Error),
+    /// Failed to find export requests of user `{0}`: {1}
+    ByUser(UserId, diesel::result::Error),
+    /// Failed to claim the next pending user export: {0}
+    ClaimNext(diesel::result::Error),
+    /// Failed to create user export request: {0}
+    Create(diesel::result::Error),
+    /// Failed to mark user export `{0}` as done: {1}
+    Done(UserExportId, diesel::result::Error),
+    /// Failed to record failure of user export `{0}`: {1}
+    Failed(UserExportId, diesel::result::Error),
+    /// Failed to find org memberships for user `{0}`: {1}
+    Memberships(UserId, diesel::result::Error),
+    /// Failed to find nodes created by user `{0}`: {1}
+    Nodes(UserId, diesel::result::Error),
+    /// Failed to serialize user export: {0}
+    Serialize(serde_json::Error),
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserExport {
+    pub exported_at: DateTime<Utc>,
+    pub profile: ExportProfile,
+    pub memberships: Vec<ExportMembership>,
+    pub api_keys: Vec<ExportApiKey>,
+    pub audit_events: Vec<ExportAuditEvent>,
+    pub nodes: Vec<ExportNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportProfile {
+    pub id: Uuid,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub created_at: DateTime<Utc>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct ExportMembership {
+    pub org_id: Uuid,
+    pub org_name: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Api key metadata, excluding the key hash and salt.
+#[derive(Debug, Serialize)]
+pub struct ExportApiKey {
+    pub id: Uuid,
+    pub label: String,
+    pub resource_type: ResourceType,
+    pub resource_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportAuditEvent {
+    pub org_id: Option<Uuid>,
+    pub event: AuditEvent,
+    pub event_data: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Queryable)]
+pub struct ExportNode {
+    pub id: Uuid,
+    pub node_name: String,
+    pub display_name: String,
+    pub org_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl UserExport {
+    pub async fn collect(user: &User, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let user_id = user.id;
+
+        let memberships = user_roles::table
+            .inner_join(orgs::table)
+            .filter(user_roles::user_id.eq(user_id))
+            .select((
+                orgs::id,
+                orgs::name,
+                user_roles::role,
+                user_roles::created_at,
+            ))
+            .order_by((orgs::name, user_roles::role))
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Memberships(user_id, err))?;
+
+        let api_keys = ApiKey::by_user_id(user_id, conn)
+            .await?
+            .into_iter()
+            .map(|key| ExportApiKey {
+                id: *key.id,
+                label: key.label,
+                resource_type: key.resource,
+                resource_id: *key.resource_id,
+                created_at: key.created_at,
+            })
+            .collect();
+
+        let audit_events = AuditLog::by_creator(Resource::User(user_id), conn)
+            .await?
+            .into_iter()
+            .map(|log| ExportAuditEvent {
+                org_id: log.org_id.map(|id| *id),
+                event: log.event,
+                event_data: log.event_data,
+                created_at: log.created_at,
+            })
+            .collect();
+
+        let nodes = nodes::table
+            .filter(nodes::created_by_type.eq(ResourceType::User))
+            .filter(nodes::created_by_id.eq(*user_id))
+            .select((
+                nodes::id,
+                nodes::node_name,
+                nodes::display_name,
+                nodes::org_id,
+                nodes::created_at,
+                nodes::deleted_at,
+            ))
+            .order_by(nodes::created_at)
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::Nodes(user_id, err))?;
+
+        Ok(UserExport {
+            exported_at: Utc::now(),
+            profile: ExportProfile {
+                id: *user.id,
+                email: user.email.clone(),
+                first_name: user.first_name.clone(),
+                last_name: user.last_name.clone(),
+                created_at: user.created_at,
+                confirmed_at: user.confirmed_at,
+            },
+            memberships,
+            api_keys,
+            audit_events,
+            nodes,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, Error> {
+        serde_json::to_vec_pretty(self).map_err(Error::Serialize)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Display, Hash, PartialEq, Eq, DieselNewType, Deref, From, FromStr)]
+pub struct UserExportId(Uuid);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DbEnum)]
+#[ExistingTypePath = "sql_types::EnumUserExportStatus"]
+pub enum UserExportStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// A requested export of the data held about a user.
+#[derive(Clone, Debug, Queryable, Selectable)]
+pub struct UserExportRequest {
+    pub id: UserExportId,
+    pub user_id: UserId,
+    pub status: UserExportStatus,
+    pub attempts: i32,
+    /// Why the last attempt to export failed.
+    pub last_error: Option<String>,
+    pub claimed_until: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl UserExportRequest {
+    pub async fn by_user(user_id: UserId, conn: &mut Conn<'_>) -> Result<Vec<Self>, Error> {
+        user_export_requests::table
+            .filter(user_export_requests::user_id.eq(user_id))
+            .order_by(user_export_requests::created_at.asc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::ByUser(user_id, err))
+    }
+
+    /// Claim the pending export request that has waited the longest, skipping
+    /// any request locked by a concurrent claim.
+    pub async fn claim_next(conn: &mut Conn<'_>) -> Result<Option<Self>, Error> {
+        conn.transaction(|conn| {
+            async move {
+                let now = Utc::now();
+                let next: Option<UserExportId> = user_export_requests::table
+                    .filter(user_export_requests::status.eq(UserExportStatus::Pending))
+                    .filter(
+                        user_export_requests::claimed_until
+                            .is_null()
+                            .or(user_export_requests::claimed_until.lt(now)),
+                    )
+                    .order_by(user_export_requests::created_at.asc())
+                    .select(user_export_requests::id)
+                    .for_update()
+                    .skip_locked()
+                    .first(conn)
+                    .await
+                    .optional()?;
+
+                let Some(id) = next else {
+                    return Ok(None);
+                };
+                diesel::update(user_export_requests::table.find(id))
+                    .set((
+                        user_export_requests::attempts.eq(user_export_requests::attempts + 1),
+                        user_export_requests::claimed_until.eq(now + CLAIM_LEASE),
+                    ))
+                    .get_result(conn)
+                    .await
+                    .map(Some)
+            }
+            .scope_boxed()
+        })
+        .await
+        .map_err(Error::ClaimNext)
+    }
+
+    pub async fn done(&self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(user_export_requests::table.find(self.id))
+            .set((
+                user_export_requests::status.eq(UserExportStatus::Done),
+                user_export_requests::last_error.eq(None::<String>),
+                user_export_requests::claimed_until.eq(None::<DateTime<Utc>>),
+                user_export_requests::completed_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Done(self.id, err))
+    }
+
+    /// Record a failed attempt, which is retried after `RETRY_AFTER` unless
+    /// the request has run out of attempts.
+    pub async fn failed(&self, error: &str, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let now = Utc::now();
+        let (status, completed_at) = if self.attempts >= MAX_ATTEMPTS {
+            (UserExportStatus::Failed, Some(now))
+        } else {
+            (UserExportStatus::Pending, None)
+        };
+
+        diesel::update(user_export_requests::table.find(self.id))
+            .set((
+                user_export_requests::status.eq(status),
+                user_export_requests::last_error.eq(error),
+                user_export_requests::claimed_until.eq(now + RETRY_AFTER),
+                user_export_requests::completed_at.eq(completed_at),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Failed(self.id, err))
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_export_requests)]
+pub struct NewUserExportRequest {
+    pub user_id: UserId,
+}
+
+impl NewUserExportRequest {
+    pub const fn new(user_id: UserId) -> Self {
+        NewUserExportRequest { user_id }
+    }
+
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<UserExportRequest, Error> {
+        diesel::insert_into(user_export_requests::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
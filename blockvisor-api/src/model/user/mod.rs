@@ -2,7 +2,7 @@ use std::collections::{HashSet, VecDeque};
 
 use argon2::password_hash::{PasswordHasher, SaltString};
 use argon2::{Algorithm, Argon2, PasswordHash};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use diesel::dsl::LeftJoinQuerySource;
 use diesel::expression::expression_types::NotSelectable;
 use diesel::pg::Pg;
@@ -20,6 +20,7 @@ use validator::Validate;
 
 use crate::auth::rbac::{OrgRole, Role};
 use crate::auth::resource::{OrgId, UserId};
+use crate::config::password::Config as PasswordConfig;
 use crate::database::Conn;
 use crate::grpc::{Status, api};
 use crate::model::sql;
@@ -29,12 +30,27 @@ use super::Paginate;
 use super::org::NewOrg;
 use super::schema::{user_roles, users};
 
+pub mod device;
+pub mod export;
+pub mod notification;
+pub mod password;
+pub mod session;
 pub mod setting;
+pub mod signup;
+
+const ANONYMIZED_EMAIL_DOMAIN: &str = "deleted.blockjoy.com";
+const ANONYMIZED_FIRST_NAME: &str = "Deleted";
+const ANONYMIZED_LAST_NAME: &str = "User";
+
+/// How long a user's consent to support impersonation lasts.
+pub const SUPPORT_ACCESS_DURATION: TimeDelta = TimeDelta::days(1);
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
     /// User is already confirmed.
     AlreadyConfirmed,
+    /// Failed to anonymize user `{0}`: {1}
+    Anonymize(UserId, diesel::result::Error),
     /// Failed to create new user: {0}
     Create(diesel::result::Error),
     /// Failed to confirm user: {0}
@@ -75,8 +91,16 @@ pub enum Error {
     ParseHash(password_hash::Error),
     /// Failed to parse Salt: {0}
     ParseSalt(password_hash::Error),
+    /// User password: {0}
+    Password(#[from] password::Error),
+    /// User must reset their password before logging in.
+    PasswordResetRequired,
     /// User RBAC error: {0}
     Rbac(#[from] crate::model::rbac::Error),
+    /// Failed to require password reset for user `{0}`: {1}
+    RequirePasswordReset(UserId, diesel::result::Error),
+    /// Failed to update support access for user `{0}`: {1}
+    SupportAccess(UserId, diesel::result::Error),
     /// Failed to update user: {0}
     Update(diesel::result::Error),
     /// Failed to update user `{0}`: {1}
@@ -94,7 +118,7 @@ impl From<Error> for Status {
         use Error::*;
         match err {
             Create(DatabaseError(UniqueViolation, _)) => {
-                Status::already_exists("User already exists.")
+                Status::already_exists("User already exists.").with_reason("USER_EXISTS")
             }
             ConfirmNone
             | Delete(NotFound)
@@ -102,11 +126,18 @@ impl From<Error> for Status {
             | FindAll(NotFound)
             | FindByEmail(_, NotFound)
             | FindById(_, NotFound)
-            | FindByIds(_, NotFound) => Status::not_found("User not found."),
-            AlreadyConfirmed => Status::failed_precondition("Already confirmed."),
-            NotConfirmed => Status::failed_precondition("User is not confirmed."),
+            | FindByIds(_, NotFound) => {
+                Status::not_found("User not found.").with_reason("USER_NOT_FOUND")
+            }
+            AlreadyConfirmed => Status::failed_precondition("Already confirmed.")
+                .with_reason("USER_ALREADY_CONFIRMED"),
+            NotConfirmed => Status::failed_precondition("User is not confirmed.")
+                .with_reason("USER_NOT_CONFIRMED"),
+            PasswordResetRequired => Status::failed_precondition("Password reset required.")
+                .with_reason("PASSWORD_RESET_REQUIRED"),
             LoginEmail | VerifyPassword(_) => Status::forbidden("Invalid email or password."),
             Paginate(err) => err.into(),
+            Password(err) => err.into(),
             Org(err) => err.into(),
             Rbac(err) => err.into(),
             _ => Status::internal("Internal error."),
@@ -126,6 +157,8 @@ pub struct User {
     pub last_name: String,
     pub confirmed_at: Option<DateTime<Utc>>,
     pub deleted_at: Option<DateTime<Utc>>,
+    pub password_reset_required: bool,
+    pub support_access_until: Option<DateTime<Utc>>,
 }
 
 impl User {
@@ -218,11 +251,16 @@ impl User {
             .map_err(Error::Update)
     }
 
+    /// Set a new password, clearing any required password reset.
     pub async fn update_password(
         &self,
         password: &str,
+        policy: &PasswordConfig,
         conn: &mut Conn<'_>,
     ) -> Result<Self, Error> {
+        let inputs = [self.email.as_str(), &self.first_name, &self.last_name];
+        password::check_policy(password, &inputs, policy)?;
+
         let salt = SaltString::generate(&mut OsRng);
         let hash = Argon2::default()
             .hash_password(password.as_bytes(), &salt)
@@ -233,12 +271,41 @@ impl User {
             .set((
                 users::hashword.eq(hash.to_string()),
                 users::salt.eq(salt.as_str()),
+                users::password_reset_required.eq(false),
             ))
             .get_result(conn)
             .await
             .map_err(Error::UpdatePassword)
     }
 
+    /// Allow support staff to act as this user until `until`.
+    pub async fn set_support_access(
+        id: UserId,
+        until: Option<DateTime<Utc>>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Self, Error> {
+        diesel::update(users::table.find(id))
+            .set(users::support_access_until.eq(until))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::SupportAccess(id, err))
+    }
+
+    /// Whether the user currently consents to support impersonation.
+    pub fn has_support_access(&self) -> bool {
+        self.support_access_until
+            .is_some_and(|until| until > Utc::now())
+    }
+
+    /// Require the user to reset their password before they can next log in.
+    pub async fn require_password_reset(id: UserId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        diesel::update(users::table.find(id))
+            .set(users::password_reset_required.eq(true))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::RequirePasswordReset(id, err))
+    }
+
     pub async fn login(email: &str, password: &str, conn: &mut Conn<'_>) -> Result<Self, Error> {
         let user = match Self::by_email(email, conn).await {
             Ok(user) => Ok(user),
@@ -248,6 +315,9 @@ impl User {
 
         if User::is_confirmed(user.id, conn).await? {
             user.verify_password(password)?;
+            if user.password_reset_required {
+                return Err(Error::PasswordResetRequired);
+            }
             Ok(user)
         } else {
             Err(Error::NotConfirmed)
@@ -293,6 +363,25 @@ impl User {
             .map_err(Error::Delete)
     }
 
+    /// Remove all personal information from a user and mark them as deleted.
+    ///
+    /// The row itself is kept so that historical references remain valid.
+    pub async fn anonymize(id: UserId, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::update(users::table.find(id))
+            .set((
+                users::email.eq(format!("deleted-{id}@{ANONYMIZED_EMAIL_DOMAIN}")),
+                users::first_name.eq(ANONYMIZED_FIRST_NAME),
+                users::last_name.eq(ANONYMIZED_LAST_NAME),
+                users::hashword.eq(""),
+                users::salt.eq(""),
+                users::deleted_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Anonymize(id, err))
+    }
+
     pub fn name(&self) -> String {
         format!("{} {}", self.first_name, self.last_name)
     }
@@ -435,7 +524,10 @@ impl<'a> NewUser<'a> {
         first_name: &'a str,
         last_name: &'a str,
         password: &'a str,
+        policy: &PasswordConfig,
     ) -> Result<Self, Error> {
+        password::check_policy(password, &[email, first_name, last_name], policy)?;
+
         let salt = SaltString::generate(&mut OsRng);
         let hash = Argon2::default()
             .hash_password(password.as_bytes(), &salt)
@@ -518,6 +610,8 @@ mod tests {
             last_name: "Ballington".to_string(),
             confirmed_at: Some(chrono::Utc::now()),
             deleted_at: None,
+            password_reset_required: false,
+            support_access_until: None,
         };
         user.verify_password("A password that cannot be hacked!1")
             .unwrap();
@@ -0,0 +1,122 @@
+//! Which push notifications a user wants to receive.
+//!
+//! Users without stored preferences receive every notification.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::auth::resource::UserId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::user_notification_preferences;
+use crate::util::NanosUtc;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to find notification preferences of user `{0}`: {1}
+    ByUser(UserId, diesel::result::Error),
+    /// Failed to find notification preferences of users: {0}
+    ByUsers(diesel::result::Error),
+    /// Failed to update notification preferences of user `{0}`: {1}
+    Update(UserId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(_: Error) -> Self {
+        Status::internal("Internal error.")
+    }
+}
+
+/// A kind of notification that a user may opt out of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotificationKind {
+    NodeDown,
+    PaymentFailed,
+}
+
+#[derive(Clone, Debug, Queryable, Selectable, Insertable, AsChangeset)]
+#[diesel(table_name = user_notification_preferences)]
+pub struct NotificationPreferences {
+    pub user_id: UserId,
+    pub node_down: bool,
+    pub payment_failed: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NotificationPreferences {
+    /// The preferences of a user, or the defaults if none were stored.
+    pub async fn by_user(user_id: UserId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        user_notification_preferences::table
+            .find(user_id)
+            .get_result(conn)
+            .await
+            .optional()
+            .map(|prefs| prefs.unwrap_or_else(|| Self::default_for(user_id)))
+            .map_err(|err| Error::ByUser(user_id, err))
+    }
+
+    /// The subset of `user_ids` that want to receive `kind`.
+    pub async fn wanting(
+        user_ids: &HashSet<UserId>,
+        kind: NotificationKind,
+        conn: &mut Conn<'_>,
+    ) -> Result<HashSet<UserId>, Error> {
+        let stored: HashMap<UserId, Self> = user_notification_preferences::table
+            .filter(user_notification_preferences::user_id.eq_any(user_ids))
+            .get_results(conn)
+            .await
+            .map_err(Error::ByUsers)?
+            .into_iter()
+            .map(|prefs: Self| (prefs.user_id, prefs))
+            .collect();
+
+        Ok(user_ids
+            .iter()
+            .filter(|id| stored.get(id).is_none_or(|prefs| prefs.wants(kind)))
+            .copied()
+            .collect())
+    }
+
+    pub async fn upsert(self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let user_id = self.user_id;
+        diesel::insert_into(user_notification_preferences::table)
+            .values(&self)
+            .on_conflict(user_notification_preferences::user_id)
+            .do_update()
+            .set(&self)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Update(user_id, err))
+    }
+
+    pub const fn wants(&self, kind: NotificationKind) -> bool {
+        match kind {
+            NotificationKind::NodeDown => self.node_down,
+            NotificationKind::PaymentFailed => self.payment_failed,
+        }
+    }
+
+    fn default_for(user_id: UserId) -> Self {
+        NotificationPreferences {
+            user_id,
+            node_down: true,
+            payment_failed: true,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+impl From<NotificationPreferences> for api::NotificationPreferences {
+    fn from(prefs: NotificationPreferences) -> Self {
+        api::NotificationPreferences {
+            node_down: prefs.node_down,
+            payment_failed: prefs.payment_failed,
+            updated_at: Some(NanosUtc::from(prefs.updated_at).into()),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Password policy and breach checks for user passwords.
+
+use displaydoc::Display;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use zxcvbn::zxcvbn;
+
+use crate::config::password::Config;
+use crate::grpc::Status;
+
+/// Only this many characters of the password hash are sent for a breach check.
+const BREACH_PREFIX_LEN: usize = 5;
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Password was found in a known data breach.
+    Breached,
+    /// Failed to request breached passwords: {0}
+    BreachRequest(reqwest::Error),
+    /// Failed to read breached passwords response: {0}
+    BreachResponse(reqwest::Error),
+    /// Password is shorter than {0} characters.
+    TooShort(usize),
+    /// Password strength score {0} is below {1}.
+    TooWeak(u8, u8),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            Breached => Status::invalid_argument(
+                "Password has appeared in a data breach. Please choose another.",
+            )
+            .with_reason("PASSWORD_BREACHED"),
+            TooShort(len) => {
+                Status::invalid_argument(format!("Password must be at least {len} characters."))
+                    .with_reason("PASSWORD_TOO_SHORT")
+            }
+            TooWeak(..) => {
+                Status::invalid_argument("Password is too weak.").with_reason("PASSWORD_TOO_WEAK")
+            }
+            BreachRequest(_) | BreachResponse(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+/// Check that a password satisfies the configured length and strength policy.
+///
+/// The `user_inputs` (such as the email and name of the user) are penalized
+/// when they appear in the password.
+pub fn check_policy(password: &str, user_inputs: &[&str], config: &Config) -> Result<(), Error> {
+    if password.chars().count() < config.min_length {
+        return Err(Error::TooShort(config.min_length));
+    }
+
+    let score = u8::from(zxcvbn(password, user_inputs).score());
+    if score < config.min_score {
+        return Err(Error::TooWeak(score, config.min_score));
+    }
+
+    Ok(())
+}
+
+/// Check that a password has not appeared in a known data breach.
+///
+/// This uses the k-anonymity range api of HaveIBeenPwned so only a prefix of
+/// the password hash leaves the service. Does nothing unless enabled in config.
+pub async fn check_breached(password: &str, config: &Config) -> Result<(), Error> {
+    if !config.breach_check {
+        return Ok(());
+    }
+
+    let hash: String = Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect();
+    let (prefix, suffix) = hash.split_at(BREACH_PREFIX_LEN);
+
+    let url = format!("{}/range/{prefix}", config.breach_url.trim_end_matches('/'));
+    let body = reqwest::Client::new()
+        .get(url)
+        .header("Add-Padding", "true")
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(Error::BreachRequest)?
+        .text()
+        .await
+        .map_err(Error::BreachResponse)?;
+
+    // padded responses contain fake suffixes with a count of zero
+    let breached = body
+        .lines()
+        .filter_map(|line| line.trim().split_once(':'))
+        .any(|(found, count)| found.eq_ignore_ascii_case(suffix) && count != "0");
+
+    if breached {
+        Err(Error::Breached)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_policy() {
+        let config = Config::default();
+
+        let err = check_policy("hunter2", &[], &config).unwrap_err();
+        assert!(matches!(err, Error::TooShort(8)));
+
+        let err = check_policy("password123", &[], &config).unwrap_err();
+        assert!(matches!(err, Error::TooWeak(..)));
+
+        check_policy("correct-horse-battery-staple", &[], &config).unwrap();
+    }
+}
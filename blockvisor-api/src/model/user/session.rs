@@ -0,0 +1,174 @@
+//! A login session of a user, tracked through its refresh tokens.
+
+use chrono::{DateTime, Utc};
+use derive_more::{Deref, Display, From, FromStr};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
+use diesel_derive_newtype::DieselNewType;
+use displaydoc::Display as DisplayDoc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::auth::resource::UserId;
+use crate::database::Conn;
+use crate::grpc::{Status, api};
+use crate::model::schema::user_sessions;
+use crate::util::NanosUtc;
+
+#[derive(Debug, DisplayDoc, Error)]
+pub enum Error {
+    /// Failed to create user session: {0}
+    Create(diesel::result::Error),
+    /// Failed to find user session `{0}`: {1}
+    FindById(UserSessionId, diesel::result::Error),
+    /// Failed to find sessions for user `{0}`: {1}
+    FindByUser(UserId, diesel::result::Error),
+    /// Failed to revoke user session `{0}`: {1}
+    Revoke(UserSessionId, diesel::result::Error),
+    /// User session `{0}` has been revoked.
+    Revoked(UserSessionId),
+    /// Failed to revoke sessions of user `{0}`: {1}
+    RevokeForUser(UserId, diesel::result::Error),
+    /// Failed to update user session `{0}`: {1}
+    Touch(UserSessionId, diesel::result::Error),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            FindById(_, NotFound) => Status::not_found("Session not found."),
+            Revoked(_) => Status::unauthorized("Session revoked."),
+            _ => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Display,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    DieselNewType,
+    Deref,
+    From,
+    FromStr,
+    Serialize,
+    Deserialize,
+)]
+pub struct UserSessionId(Uuid);
+
+#[derive(Clone, Debug, Queryable, Selectable)]
+#[diesel(table_name = user_sessions)]
+pub struct UserSession {
+    pub id: UserSessionId,
+    pub user_id: UserId,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl UserSession {
+    pub async fn by_id(id: UserSessionId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        user_sessions::table
+            .find(id)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::FindById(id, err))
+    }
+
+    /// Find the unrevoked sessions of a user that were seen after `since`.
+    pub async fn active_by_user(
+        user_id: UserId,
+        since: DateTime<Utc>,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<Self>, Error> {
+        user_sessions::table
+            .filter(user_sessions::user_id.eq(user_id))
+            .filter(user_sessions::revoked_at.is_null())
+            .filter(user_sessions::last_seen_at.gt(since))
+            .order_by(user_sessions::last_seen_at.desc())
+            .get_results(conn)
+            .await
+            .map_err(|err| Error::FindByUser(user_id, err))
+    }
+
+    /// Record that the session is still in use, failing if it was revoked.
+    pub async fn touch(id: UserSessionId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let active = user_sessions::table
+            .find(id)
+            .filter(user_sessions::revoked_at.is_null());
+
+        diesel::update(active)
+            .set(user_sessions::last_seen_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| match err {
+                NotFound => Error::Revoked(id),
+                err => Error::Touch(id, err),
+            })
+    }
+
+    pub async fn revoke(&self, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let id = self.id;
+        diesel::update(user_sessions::table.find(id))
+            .set(user_sessions::revoked_at.eq(Utc::now()))
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Revoke(id, err))
+    }
+
+    /// Revoke every unrevoked session of a user, so that none of their refresh
+    /// tokens can be used again.
+    pub async fn revoke_for_user(user_id: UserId, conn: &mut Conn<'_>) -> Result<usize, Error> {
+        let active = user_sessions::table
+            .filter(user_sessions::user_id.eq(user_id))
+            .filter(user_sessions::revoked_at.is_null());
+
+        diesel::update(active)
+            .set(user_sessions::revoked_at.eq(Utc::now()))
+            .execute(conn)
+            .await
+            .map_err(|err| Error::RevokeForUser(user_id, err))
+    }
+}
+
+impl From<UserSession> for api::UserSession {
+    fn from(session: UserSession) -> Self {
+        api::UserSession {
+            session_id: session.id.to_string(),
+            user_id: session.user_id.to_string(),
+            device: session.device,
+            ip_address: session.ip_address,
+            created_at: Some(NanosUtc::from(session.created_at).into()),
+            last_seen_at: Some(NanosUtc::from(session.last_seen_at).into()),
+        }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_sessions)]
+pub struct NewUserSession {
+    pub user_id: UserId,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl NewUserSession {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<UserSession, Error> {
+        diesel::insert_into(user_sessions::table)
+            .values(self)
+            .get_result(conn)
+            .await
+            .map_err(Error::Create)
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use derive_more::{Deref, From, FromStr, Into};
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -8,9 +10,17 @@ use uuid::Uuid;
 
 use crate::auth::resource::UserId;
 use crate::database::Conn;
+use crate::email::Language;
 use crate::grpc::Status;
 use crate::model::schema::user_settings;
 
+/// The setting holding a BCP 47 locale such as `en` or `de-CH`.
+pub const LOCALE_KEY: &str = "locale";
+/// The setting holding an IANA timezone such as `Europe/Berlin`.
+pub const TIMEZONE_KEY: &str = "timezone";
+
+const DEFAULT_LOCALE: &str = "en";
+
 #[derive(Debug, Display, Error)]
 pub enum Error {
     /// Failed to create new user setting: {0}
@@ -19,6 +29,10 @@ pub enum Error {
     Delete(diesel::result::Error),
     /// Failed to find user settings for user `{0}`: {1}
     ByUser(UserId, diesel::result::Error),
+    /// Invalid locale: {0}
+    InvalidLocale(String),
+    /// Invalid timezone: {0}
+    InvalidTimezone(String),
 }
 
 impl From<Error> for Status {
@@ -26,6 +40,7 @@ impl From<Error> for Status {
         use Error::*;
         match err {
             Create(_) | Delete(_) | ByUser(_, _) => Status::internal("Internal error."),
+            InvalidLocale(_) | InvalidTimezone(_) => Status::invalid_argument("value"),
         }
     }
 }
@@ -79,6 +94,20 @@ pub struct NewUserSetting<'a> {
 }
 
 impl<'a> NewUserSetting<'a> {
+    /// Create a new setting, checking the value of the locale and timezone.
+    pub fn validated(user_id: UserId, key: String, value: &'a [u8]) -> Result<Self, Error> {
+        let text = || String::from_utf8_lossy(value).into_owned();
+        match key.as_str() {
+            LOCALE_KEY if !is_locale(&text()) => return Err(Error::InvalidLocale(text())),
+            TIMEZONE_KEY if text().parse::<Tz>().is_err() => {
+                return Err(Error::InvalidTimezone(text()));
+            }
+            _ => (),
+        }
+
+        Ok(Self::new(user_id, key, value))
+    }
+
     pub const fn new(user_id: UserId, key: String, value: &'a [u8]) -> Self {
         Self {
             user_id,
@@ -98,3 +127,126 @@ impl<'a> NewUserSetting<'a> {
             .map_err(Error::Create)
     }
 }
+
+/// The locale and timezone used when rendering content for a user.
+///
+/// Users without valid settings get English dates in UTC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserLocale {
+    pub locale: String,
+    pub timezone: Tz,
+}
+
+impl Default for UserLocale {
+    fn default() -> Self {
+        UserLocale {
+            locale: DEFAULT_LOCALE.to_string(),
+            timezone: Tz::UTC,
+        }
+    }
+}
+
+impl UserLocale {
+    pub async fn by_user(user_id: UserId, conn: &mut Conn<'_>) -> Result<Self, Error> {
+        let settings = UserSetting::by_user(user_id, conn).await?;
+        Ok(Self::from_settings(&settings))
+    }
+
+    /// Read the locale and timezone from all settings of a user, ignoring
+    /// values that are no longer valid.
+    pub fn from_settings(settings: &[UserSetting]) -> Self {
+        let value = |key: &str| {
+            settings
+                .iter()
+                .find(|setting| setting.key.as_str() == key)
+                .and_then(|setting| std::str::from_utf8(&setting.value).ok())
+        };
+
+        let mut locale = UserLocale::default();
+        if let Some(value) = value(LOCALE_KEY).filter(|value| is_locale(value)) {
+            locale.locale = value.to_string();
+        }
+        if let Some(timezone) = value(TIMEZONE_KEY).and_then(|value| value.parse().ok()) {
+            locale.timezone = timezone;
+        }
+        locale
+    }
+
+    /// The email language of the locale, or English if it isn't translated.
+    pub fn language(&self) -> Language {
+        match self.primary_language() {
+            "de" => Language::De,
+            "nl" => Language::Nl,
+            _ => Language::En,
+        }
+    }
+
+    /// Format a date in the user's timezone.
+    pub fn date(&self, at: DateTime<Utc>) -> String {
+        let format = match self.primary_language() {
+            "de" => "%-d.%-m.%Y",
+            "nl" => "%-d-%-m-%Y",
+            _ => "%B %-d, %Y",
+        };
+        at.with_timezone(&self.timezone).format(format).to_string()
+    }
+
+    /// Format a date and time in the user's timezone.
+    pub fn date_time(&self, at: DateTime<Utc>) -> String {
+        let time = at.with_timezone(&self.timezone).format("%H:%M %Z");
+        format!("{} {time}", self.date(at))
+    }
+
+    fn primary_language(&self) -> &str {
+        self.locale.split('-').next().unwrap_or(DEFAULT_LOCALE)
+    }
+}
+
+/// Whether `locale` is a language with an optional region, like `en-US`.
+fn is_locale(locale: &str) -> bool {
+    let mut parts = locale.split('-');
+    let language = parts.next().unwrap_or_default();
+    let region = parts.next();
+
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|region| {
+            (region.len() == 2 && region.chars().all(|c| c.is_ascii_uppercase()))
+                || (region.len() == 3 && region.chars().all(|c| c.is_ascii_digit()))
+        })
+        && parts.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn validates_locales() {
+        assert!(is_locale("en"));
+        assert!(is_locale("de-CH"));
+        assert!(is_locale("es-419"));
+        assert!(!is_locale(""));
+        assert!(!is_locale("EN"));
+        assert!(!is_locale("en_US"));
+        assert!(!is_locale("en-us"));
+        assert!(!is_locale("en-US-x"));
+    }
+
+    #[test]
+    fn formats_in_timezone() {
+        let at = Utc.with_ymd_and_hms(2025, 3, 31, 23, 30, 0).unwrap();
+
+        let utc = UserLocale::default();
+        assert_eq!(utc.date_time(at), "March 31, 2025 23:30 UTC");
+
+        let berlin = UserLocale {
+            locale: "de-DE".to_string(),
+            timezone: Tz::Europe__Berlin,
+        };
+        assert_eq!(berlin.language(), Language::De);
+        assert_eq!(berlin.date_time(at), "1.4.2025 01:30 CEST");
+    }
+}
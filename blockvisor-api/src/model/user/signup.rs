@@ -0,0 +1,247 @@
+//! Abuse checks of public sign-ups.
+//!
+//! New users may need to solve a captcha, may not sign up with an email domain
+//! that is blocked or has no MX record, and may only sign up so many times from
+//! one IP address. Each check does nothing unless enabled in config.
+
+use chrono::{TimeDelta, Utc};
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use displaydoc::Display;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::auth::resource::UserId;
+use crate::config::signup::Config;
+use crate::database::Conn;
+use crate::grpc::Status;
+use crate::model::schema::user_signups;
+
+/// The DNS record type of an MX record.
+const MX_RECORD_TYPE: u16 = 15;
+/// The data of a null MX record, for domains that accept no email.
+const NULL_MX: &str = "0 .";
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Email domain `{0}` is blocked.
+    BlockedDomain(String),
+    /// Captcha verification failed.
+    CaptchaFailed,
+    /// Missing captcha token.
+    CaptchaMissing,
+    /// Failed to request captcha verification: {0}
+    CaptchaRequest(reqwest::Error),
+    /// Failed to read captcha verification response: {0}
+    CaptchaResponse(reqwest::Error),
+    /// Failed to count sign-ups from IP address `{0}`: {1}
+    Count(String, diesel::result::Error),
+    /// Failed to record sign-up of user `{0}`: {1}
+    Create(UserId, diesel::result::Error),
+    /// Failed to parse the sign-up IP window: {0}
+    IpWindow(crate::config::Error),
+    /// Email address has no domain.
+    MissingDomain,
+    /// Failed to request MX records: {0}
+    MxRequest(reqwest::Error),
+    /// Failed to read MX records response: {0}
+    MxResponse(reqwest::Error),
+    /// Email domain `{0}` has no MX record.
+    NoMx(String),
+    /// Too many sign-ups from IP address `{0}`.
+    TooMany(String),
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        use Error::*;
+        match err {
+            BlockedDomain(_) | MissingDomain | NoMx(_) => {
+                Status::invalid_argument("Email domain is not allowed.")
+                    .with_reason("EMAIL_DOMAIN_BLOCKED")
+            }
+            CaptchaFailed | CaptchaMissing => {
+                Status::invalid_argument("captcha_token").with_reason("CAPTCHA_FAILED")
+            }
+            TooMany(_) => Status::failed_precondition("Too many sign-ups. Please try again later.")
+                .with_reason("SIGNUP_RATE_LIMITED"),
+            CaptchaRequest(_) | CaptchaResponse(_) | Count(..) | Create(..) | IpWindow(_)
+            | MxRequest(_) | MxResponse(_) => Status::internal("Internal error."),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CaptchaResponse {
+    success: bool,
+}
+
+#[derive(Deserialize)]
+struct DnsResponse {
+    #[serde(rename = "Status")]
+    status: u16,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DnsAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+/// Verify the captcha `token` of a sign-up from `remote_ip`.
+///
+/// Both hCaptcha and Turnstile share the same `siteverify` api.
+pub async fn check_captcha(
+    token: Option<&str>,
+    remote_ip: Option<&str>,
+    config: &Config,
+) -> Result<(), Error> {
+    let Some((provider, secret)) = config.captcha() else {
+        return Ok(());
+    };
+    let token = token
+        .filter(|token| !token.is_empty())
+        .ok_or(Error::CaptchaMissing)?;
+
+    let mut form = vec![("secret", secret), ("response", token)];
+    if let Some(ip) = remote_ip {
+        form.push(("remoteip", ip));
+    }
+
+    let resp: CaptchaResponse = reqwest::Client::new()
+        .post(provider.verify_url())
+        .form(&form)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(Error::CaptchaRequest)?
+        .json()
+        .await
+        .map_err(Error::CaptchaResponse)?;
+
+    if resp.success {
+        Ok(())
+    } else {
+        Err(Error::CaptchaFailed)
+    }
+}
+
+/// Check that the domain of `email` is not blocked and can receive email.
+pub async fn check_email(email: &str, config: &Config) -> Result<(), Error> {
+    let domain = email
+        .trim()
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.to_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .ok_or(Error::MissingDomain)?;
+
+    if config.blocked_domains.matches(&domain) {
+        return Err(Error::BlockedDomain(domain));
+    }
+    if !config.mx_check {
+        return Ok(());
+    }
+
+    let resp: DnsResponse = reqwest::Client::new()
+        .get(&config.dns_url)
+        .query(&[("name", domain.as_str()), ("type", "MX")])
+        .header("Accept", "application/dns-json")
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(Error::MxRequest)?
+        .json()
+        .await
+        .map_err(Error::MxResponse)?;
+
+    let has_mx = resp.status == 0
+        && resp
+            .answer
+            .iter()
+            .any(|answer| answer.record_type == MX_RECORD_TYPE && answer.data.trim() != NULL_MX);
+
+    if has_mx {
+        Ok(())
+    } else {
+        Err(Error::NoMx(domain))
+    }
+}
+
+/// Check that `ip` has not signed up too many times recently.
+pub async fn check_ip(ip: Option<&str>, config: &Config, conn: &mut Conn<'_>) -> Result<(), Error> {
+    let Some(ip) = ip else {
+        return Ok(());
+    };
+    if config.max_per_ip == 0 {
+        return Ok(());
+    }
+
+    let window = TimeDelta::try_from(config.ip_window).map_err(Error::IpWindow)?;
+    let count: i64 = user_signups::table
+        .filter(user_signups::ip_address.eq(ip))
+        .filter(user_signups::created_at.gt(Utc::now() - window))
+        .select(count_star())
+        .get_result(conn)
+        .await
+        .map_err(|err| Error::Count(ip.to_string(), err))?;
+
+    if count >= i64::from(config.max_per_ip) {
+        Err(Error::TooMany(ip.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_signups)]
+pub struct NewUserSignup<'a> {
+    pub user_id: UserId,
+    pub ip_address: &'a str,
+}
+
+impl NewUserSignup<'_> {
+    pub async fn create(self, conn: &mut Conn<'_>) -> Result<(), Error> {
+        let user_id = self.user_id;
+        diesel::insert_into(user_signups::table)
+            .values(self)
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Create(user_id, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_email_blocked_domains() {
+        let config = Config {
+            captcha_provider: None,
+            captcha_secret: None,
+            blocked_domains: "mailinator.com, Guerrillamail.com".parse().unwrap(),
+            mx_check: false,
+            dns_url: String::new(),
+            max_per_ip: 0,
+            ip_window: "1h".parse().unwrap(),
+        };
+
+        let err = check_email("ada@mailinator.com", &config)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BlockedDomain(_)));
+        let err = check_email("ada@eu.guerrillamail.com", &config)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BlockedDomain(_)));
+        let err = check_email("ada", &config).await.unwrap_err();
+        assert!(matches!(err, Error::MissingDomain));
+
+        check_email("ada@notmailinator.com", &config).await.unwrap();
+    }
+}
@@ -6,9 +6,12 @@ use thiserror::Error;
 use uuid::Uuid;
 
 use crate::auth::resource::{HostId, NodeId, OrgId};
+use crate::model::HostPoolId;
+use crate::model::project::ProjectId;
 
 const WILDCARD_CHARS: &[char] = &['#', '+'];
 const UUID_LEN: usize = 36;
+const SCOPED_ORG_PREFIXES: &[&str] = &["/projects/", "/tags/", "/pools/"];
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -39,8 +42,8 @@ pub enum OperationType {
 
 #[derive(Debug)]
 pub enum Topic {
-    /// `/orgs/<uuid>/...`
-    Orgs(OrgId),
+    /// `/orgs/<uuid>` or one of its `OrgTopic` subtopics.
+    Orgs(OrgId, OrgTopic),
     /// `/hosts/<uuid>/...`
     Hosts(HostId),
     /// `/nodes/<uuid>/...`
@@ -51,6 +54,69 @@ pub enum Topic {
     Wildcard(String),
 }
 
+/// The topics within an org.
+///
+/// Besides the topics for each kind of org message, node and host messages are
+/// also published to topics scoped to a project, tag or host pool, so that a
+/// client can subscribe to a subset of the org. These are authorized for the
+/// whole org, except that a project with members is restricted to them.
+///
+/// Any other topic within the org is also authorized for the whole org, but a
+/// malformed scoped topic is rejected.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OrgTopic {
+    /// `/orgs/<uuid>`
+    Org,
+    /// `/orgs/<uuid>/nodes`
+    Nodes,
+    /// `/orgs/<uuid>/endpoints`
+    Endpoints,
+    /// `/orgs/<uuid>/incidents`
+    Incidents,
+    /// `/orgs/<uuid>/projects/<uuid>/nodes`
+    ProjectNodes(ProjectId),
+    /// `/orgs/<uuid>/tags/<tag>/nodes`
+    TagNodes(String),
+    /// `/orgs/<uuid>/pools/<uuid>/hosts`
+    PoolHosts(HostPoolId),
+    /// `/orgs/<uuid>/...`
+    Other,
+}
+
+impl FromStr for OrgTopic {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scoped = |prefix: &str, suffix: &str| {
+            s.strip_prefix(prefix)
+                .and_then(|rest| rest.strip_suffix(suffix))
+                .filter(|scope| !scope.is_empty() && !scope.contains('/'))
+        };
+
+        match s.trim_end_matches('/') {
+            "" => Ok(OrgTopic::Org),
+            "/nodes" => Ok(OrgTopic::Nodes),
+            "/endpoints" => Ok(OrgTopic::Endpoints),
+            "/incidents" => Ok(OrgTopic::Incidents),
+            _ => {
+                if let Some(id) = scoped("/projects/", "/nodes") {
+                    let id = id.parse::<Uuid>().map_err(Error::ParseUuid)?;
+                    Ok(OrgTopic::ProjectNodes(id.into()))
+                } else if let Some(tag) = scoped("/tags/", "/nodes") {
+                    Ok(OrgTopic::TagNodes(tag.into()))
+                } else if let Some(id) = scoped("/pools/", "/hosts") {
+                    let id = id.parse::<Uuid>().map_err(Error::ParseUuid)?;
+                    Ok(OrgTopic::PoolHosts(id.into()))
+                } else if SCOPED_ORG_PREFIXES.iter().any(|p| s.starts_with(p)) {
+                    Err(Error::UnknownTopic(s.into()))
+                } else {
+                    Ok(OrgTopic::Other)
+                }
+            }
+        }
+    }
+}
+
 impl FromStr for Topic {
     type Err = Error;
 
@@ -69,8 +135,9 @@ impl FromStr for Topic {
         if s.contains(WILDCARD_CHARS) {
             Ok(Topic::Wildcard(s.into()))
         } else if let Some(suffix) = s.strip_prefix("/orgs/") {
-            let (id, _) = parse_uuid(suffix)?;
-            Ok(Topic::Orgs(id.into()))
+            let (id, rest) = parse_uuid(suffix)?;
+            let topic = rest.parse().map_err(|_| Error::UnknownTopic(s.into()))?;
+            Ok(Topic::Orgs(id.into(), topic))
         } else if let Some(suffix) = s.strip_prefix("/hosts/") {
             let (id, _) = parse_uuid(suffix)?;
             Ok(Topic::Hosts(id.into()))
@@ -112,6 +179,7 @@ mod tests {
             (format!("/orgs/{uuid}"), true),
             (format!("/orgs/{uuid}/"), true),
             (format!("/orgs/{uuid}/stuff"), true),
+            (format!("/orgs/{uuid}/projects/{uuid}/nodes"), true),
             (format!("/hosts/{uuid}/"), true),
             (format!("/nodes/{uuid}/"), true),
             (format!("/bv/hosts/{uuid}/status"), true),
@@ -130,6 +198,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_scoped_org_topic() {
+        let uuid = Uuid::new_v4();
+        let org_topic = |topic: String| match topic.parse::<Topic>() {
+            Ok(Topic::Orgs(_, org_topic)) => Some(org_topic),
+            _ => None,
+        };
+
+        let tests = [
+            (format!("/orgs/{uuid}/nodes"), Some(OrgTopic::Nodes)),
+            (
+                format!("/orgs/{uuid}/projects/{uuid}/nodes"),
+                Some(OrgTopic::ProjectNodes(uuid.into())),
+            ),
+            (
+                format!("/orgs/{uuid}/tags/mainnet/nodes"),
+                Some(OrgTopic::TagNodes("mainnet".into())),
+            ),
+            (
+                format!("/orgs/{uuid}/pools/{uuid}/hosts"),
+                Some(OrgTopic::PoolHosts(uuid.into())),
+            ),
+            (format!("/orgs/{uuid}/stuff"), Some(OrgTopic::Other)),
+            (format!("/orgs/{uuid}/projects/{uuid}"), None),
+            (format!("/orgs/{uuid}/projects/stuff/nodes"), None),
+            (format!("/orgs/{uuid}/tags/a/b/nodes"), None),
+            (format!("/orgs/{uuid}/pools/{uuid}"), None),
+        ];
+
+        for (topic, expected) in tests {
+            assert_eq!(org_topic(topic.clone()), expected, "{topic}");
+        }
+    }
+
     #[test]
     fn parse_acl_request() {
         let json = r#"{
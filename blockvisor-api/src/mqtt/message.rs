@@ -5,7 +5,12 @@ use thiserror::Error;
 
 use crate::auth::resource::{HostId, NodeId, OrgId};
 use crate::grpc::{api, common};
-use crate::model::{Host, Node, Org, User};
+use crate::model::image::ConfigId;
+use crate::model::incident::IncidentComment;
+use crate::model::node::NodeCertificate;
+use crate::model::project::ProjectId;
+use crate::model::{Endpoint, Host, HostPoolId, Incident, Node, Org, User};
+use crate::util::NanosUtc;
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
@@ -23,6 +28,8 @@ pub enum Message {
     OrgMessage(Box<api::OrgMessage>),
     HostMessage(Box<api::HostMessage>),
     NodeMessage(Box<api::NodeMessage>),
+    EndpointMessage(Box<api::EndpointMessage>),
+    IncidentMessage(Box<api::IncidentMessage>),
 }
 
 impl From<api::Command> for Message {
@@ -49,6 +56,18 @@ impl From<api::NodeMessage> for Message {
     }
 }
 
+impl From<api::EndpointMessage> for Message {
+    fn from(value: api::EndpointMessage) -> Self {
+        Message::EndpointMessage(Box::new(value))
+    }
+}
+
+impl From<api::IncidentMessage> for Message {
+    fn from(value: api::IncidentMessage) -> Self {
+        Message::IncidentMessage(Box::new(value))
+    }
+}
+
 impl Message {
     pub fn encode(&self) -> Vec<u8> {
         match self {
@@ -56,6 +75,8 @@ impl Message {
             Message::OrgMessage(msg) => msg.encode_to_vec(),
             Message::HostMessage(msg) => msg.encode_to_vec(),
             Message::NodeMessage(msg) => msg.encode_to_vec(),
+            Message::EndpointMessage(msg) => msg.encode_to_vec(),
+            Message::IncidentMessage(msg) => msg.encode_to_vec(),
         }
     }
 
@@ -65,6 +86,8 @@ impl Message {
             Message::OrgMessage(msg) => msg.channels(),
             Message::HostMessage(msg) => msg.channels(),
             Message::NodeMessage(msg) => msg.channels(),
+            Message::EndpointMessage(msg) => msg.channels(),
+            Message::IncidentMessage(msg) => msg.channels(),
         }
     }
 }
@@ -181,7 +204,27 @@ impl api::OrgMessage {
 impl api::HostMessage {
     fn channels(&self) -> Result<Vec<String>, Error> {
         let host_id = self.host_id().ok_or(Error::MissingHostId)?;
-        Ok(vec![format!("/hosts/{host_id}")])
+        let mut channels = vec![format!("/hosts/{host_id}")];
+        if let Some((org_id, pool_id)) = self.pool() {
+            channels.push(format!("/orgs/{org_id}/pools/{pool_id}/hosts"));
+        }
+
+        Ok(channels)
+    }
+
+    /// The org and pool of a private host in a pool.
+    fn pool(&self) -> Option<(OrgId, HostPoolId)> {
+        use api::host_message::Message::*;
+        let host = match self.message.as_ref()? {
+            Created(api::HostCreated { host, .. }) | Updated(api::HostUpdated { host, .. }) => {
+                host.as_ref()?
+            }
+            Deleted(_) => return None,
+        };
+
+        let org_id = host.org_id.as_ref()?.parse().ok()?;
+        let pool_id = host.pool_id.as_ref()?.parse().ok()?;
+        Some((org_id, pool_id))
     }
 
     fn host_id(&self) -> Option<HostId> {
@@ -239,11 +282,52 @@ impl api::NodeMessage {
         let host_id = self.host_id().ok_or(Error::MissingHostId)?;
         let node_id = self.node_id().ok_or(Error::MissingNodeId)?;
 
-        Ok(vec![
+        let mut channels = vec![
             format!("/orgs/{org_id}/nodes"),
             format!("/hosts/{host_id}/nodes"),
             format!("/nodes/{node_id}"),
-        ])
+        ];
+        if let Some(project_id) = self.project_id() {
+            channels.push(format!("/orgs/{org_id}/projects/{project_id}/nodes"));
+        }
+        for tag in self.tags() {
+            channels.push(format!("/orgs/{org_id}/tags/{tag}/nodes"));
+        }
+
+        Ok(channels)
+    }
+
+    /// The tags of the node, which only `Created` and `Updated` messages carry.
+    ///
+    /// Tags are lower kebab-case, so they are always valid topic levels.
+    fn tags(&self) -> impl Iterator<Item = &str> {
+        use api::node_message::Message::*;
+        let tags = match self.message.as_ref() {
+            Some(
+                Created(api::NodeCreated { node, .. }) | Updated(api::NodeUpdated { node, .. }),
+            ) => node.as_ref().and_then(|node| node.tags.as_ref()),
+            _ => None,
+        };
+
+        tags.into_iter()
+            .flat_map(|tags| &tags.tags)
+            .map(|tag| tag.name.as_str())
+    }
+
+    /// The project of the node, which only `Created`, `Updated` and `Deleted`
+    /// messages are published to.
+    fn project_id(&self) -> Option<ProjectId> {
+        use api::node_message::Message::*;
+        match self.message.as_ref()? {
+            Created(api::NodeCreated { node, .. }) => {
+                node.as_ref()?.project_id.as_ref()?.parse().ok()
+            }
+            Updated(api::NodeUpdated { node, .. }) => {
+                node.as_ref()?.project_id.as_ref()?.parse().ok()
+            }
+            Deleted(api::NodeDeleted { project_id, .. }) => project_id.as_ref()?.parse().ok(),
+            CertificateExpiring(_) | ConfigDrift(_) => None,
+        }
     }
 
     fn org_id(&self) -> Option<OrgId> {
@@ -252,6 +336,8 @@ impl api::NodeMessage {
             Created(api::NodeCreated { node, .. }) => node.as_ref()?.org_id.parse().ok(),
             Updated(api::NodeUpdated { node, .. }) => node.as_ref()?.org_id.parse().ok(),
             Deleted(api::NodeDeleted { org_id, .. }) => org_id.parse().ok(),
+            CertificateExpiring(api::NodeCertificateExpiring { org_id, .. }) => org_id.parse().ok(),
+            ConfigDrift(api::NodeConfigDrift { org_id, .. }) => org_id.parse().ok(),
         }
     }
 
@@ -261,6 +347,10 @@ impl api::NodeMessage {
             Created(api::NodeCreated { node, .. }) => node.as_ref()?.host_id.parse().ok(),
             Updated(api::NodeUpdated { node, .. }) => node.as_ref()?.host_id.parse().ok(),
             Deleted(api::NodeDeleted { host_id, .. }) => host_id.parse().ok(),
+            CertificateExpiring(api::NodeCertificateExpiring { host_id, .. }) => {
+                host_id.parse().ok()
+            }
+            ConfigDrift(api::NodeConfigDrift { host_id, .. }) => host_id.parse().ok(),
         }
     }
 
@@ -270,6 +360,10 @@ impl api::NodeMessage {
             Created(api::NodeCreated { node, .. }) => node.as_ref()?.node_id.parse().ok(),
             Updated(api::NodeUpdated { node, .. }) => node.as_ref()?.node_id.parse().ok(),
             Deleted(api::NodeDeleted { node_id, .. }) => node_id.parse().ok(),
+            CertificateExpiring(api::NodeCertificateExpiring { node_id, .. }) => {
+                node_id.parse().ok()
+            }
+            ConfigDrift(api::NodeConfigDrift { node_id, .. }) => node_id.parse().ok(),
         }
     }
 
@@ -309,10 +403,171 @@ impl api::NodeMessage {
                 node_id: node.id.to_string(),
                 host_id: node.host_id.to_string(),
                 org_id: node.org_id.to_string(),
+                project_id: node.project_id.map(|id| id.to_string()),
                 deleted_by,
             })),
         }
     }
+
+    /// An alert that the certificate of a node expires soon and has not been
+    /// renewed.
+    pub fn certificate_expiring(node: &Node, certificate: &NodeCertificate) -> Self {
+        api::NodeMessage {
+            message: Some(api::node_message::Message::CertificateExpiring(
+                api::NodeCertificateExpiring {
+                    node_id: node.id.to_string(),
+                    host_id: node.host_id.to_string(),
+                    org_id: node.org_id.to_string(),
+                    names: certificate.names(),
+                    not_after: certificate.not_after.map(NanosUtc::from).map(Into::into),
+                    last_error: certificate.last_error.clone(),
+                },
+            )),
+        }
+    }
+
+    /// An alert that the host of a node reported running a different config
+    /// than the stored one.
+    pub fn config_drift(
+        node: &Node,
+        config_id: ConfigId,
+        expected_hash: String,
+        reported_hash: &str,
+    ) -> Self {
+        api::NodeMessage {
+            message: Some(api::node_message::Message::ConfigDrift(
+                api::NodeConfigDrift {
+                    node_id: node.id.to_string(),
+                    host_id: node.host_id.to_string(),
+                    org_id: node.org_id.to_string(),
+                    config_id: config_id.to_string(),
+                    expected_hash,
+                    reported_hash: reported_hash.to_string(),
+                },
+            )),
+        }
+    }
+}
+
+impl api::EndpointMessage {
+    fn channels(&self) -> Result<Vec<String>, Error> {
+        let org_id = self.org_id().ok_or(Error::MissingOrgId)?;
+        Ok(vec![format!("/orgs/{org_id}/endpoints")])
+    }
+
+    fn org_id(&self) -> Option<OrgId> {
+        use api::endpoint_message::Message::*;
+        match self.message.as_ref()? {
+            Created(api::EndpointCreated { config, .. }) => config.as_ref()?.org_id.parse().ok(),
+            Revoked(api::EndpointRevoked { org_id, .. }) => org_id.parse().ok(),
+            QuotaAlert(api::EndpointQuotaAlert { org_id, .. }) => org_id.parse().ok(),
+        }
+    }
+
+    /// The gateway config of a new endpoint, for the edge to start serving.
+    pub fn created(endpoint: &Endpoint, created_by: common::Resource) -> Self {
+        api::EndpointMessage {
+            message: Some(api::endpoint_message::Message::Created(
+                api::EndpointCreated {
+                    config: Some(api::EndpointConfig {
+                        endpoint_id: endpoint.id.to_string(),
+                        org_id: endpoint.org_id.to_string(),
+                        node_id: endpoint.node_id.map(|id| id.to_string()),
+                        token_hash: endpoint.token_hash.to_string(),
+                        token_salt: endpoint.token_salt.to_string(),
+                        allowed_methods: endpoint.allowed_methods(),
+                        rate_limit_rps: endpoint
+                            .rate_limit_rps
+                            .and_then(|rps| u32::try_from(rps).ok()),
+                    }),
+                    created_by: Some(created_by),
+                },
+            )),
+        }
+    }
+
+    pub fn revoked(endpoint: &Endpoint, revoked_by: common::Resource) -> Self {
+        api::EndpointMessage {
+            message: Some(api::endpoint_message::Message::Revoked(
+                api::EndpointRevoked {
+                    endpoint_id: endpoint.id.to_string(),
+                    org_id: endpoint.org_id.to_string(),
+                    revoked_by: Some(revoked_by),
+                },
+            )),
+        }
+    }
+
+    /// An alert that an endpoint has used `percent` of its monthly quota.
+    pub fn quota_alert(endpoint: &Endpoint, requests: i64, percent: i64) -> Self {
+        api::EndpointMessage {
+            message: Some(api::endpoint_message::Message::QuotaAlert(
+                api::EndpointQuotaAlert {
+                    endpoint_id: endpoint.id.to_string(),
+                    org_id: endpoint.org_id.to_string(),
+                    monthly_quota: endpoint
+                        .monthly_quota
+                        .and_then(|quota| u64::try_from(quota).ok())
+                        .unwrap_or_default(),
+                    quota_used: u64::try_from(requests).unwrap_or_default(),
+                    percent: u32::try_from(percent).unwrap_or_default(),
+                },
+            )),
+        }
+    }
+}
+
+impl api::IncidentMessage {
+    fn channels(&self) -> Result<Vec<String>, Error> {
+        let org_id = self.org_id().ok_or(Error::MissingOrgId)?;
+        Ok(vec![format!("/orgs/{org_id}/incidents")])
+    }
+
+    fn org_id(&self) -> Option<OrgId> {
+        use api::incident_message::Message::*;
+        match self.message.as_ref()? {
+            Created(api::IncidentCreated { incident, .. }) => {
+                incident.as_ref()?.org_id.parse().ok()
+            }
+            Updated(api::IncidentUpdated { incident, .. }) => {
+                incident.as_ref()?.org_id.parse().ok()
+            }
+            Commented(api::IncidentCommented { org_id, .. }) => org_id.parse().ok(),
+        }
+    }
+
+    pub fn created(incident: &Incident, created_by: common::Resource) -> Self {
+        api::IncidentMessage {
+            message: Some(api::incident_message::Message::Created(
+                api::IncidentCreated {
+                    incident: Some(incident.clone().into()),
+                    created_by: Some(created_by),
+                },
+            )),
+        }
+    }
+
+    pub fn updated(incident: &Incident, updated_by: common::Resource) -> Self {
+        api::IncidentMessage {
+            message: Some(api::incident_message::Message::Updated(
+                api::IncidentUpdated {
+                    incident: Some(incident.clone().into()),
+                    updated_by: Some(updated_by),
+                },
+            )),
+        }
+    }
+
+    pub fn commented(incident: &Incident, comment: &IncidentComment) -> Self {
+        api::IncidentMessage {
+            message: Some(api::incident_message::Message::Commented(
+                api::IncidentCommented {
+                    org_id: incident.org_id.to_string(),
+                    comment: Some(comment.clone().into()),
+                },
+            )),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -404,4 +659,38 @@ mod tests {
         let msg = api::NodeMessage::deleted(&node, Some(resource));
         ctx.notifier.send(msg).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn node_channels_include_scoped_topics() {
+        let (_ctx, db) = Context::with_mocked().await.unwrap();
+        let mut conn = db.conn().await;
+
+        let node = db.seed.node.clone();
+        let authz = view_authz(node.id);
+        let project_id = Uuid::new_v4();
+
+        let mut api_node = api::Node::from_model(node.clone(), &authz, &mut conn)
+            .await
+            .unwrap();
+        api_node.project_id = Some(project_id.to_string());
+        api_node.tags = Some(common::Tags {
+            tags: vec![common::Tag {
+                name: "mainnet".to_string(),
+            }],
+        });
+        let resource = common::Resource {
+            resource_type: common::ResourceType::User.into(),
+            resource_id: db.seed.member.id.to_string(),
+        };
+
+        let channels = api::NodeMessage::updated(api_node, resource)
+            .channels()
+            .unwrap();
+        let org_id = node.org_id;
+        assert!(channels.contains(&format!("/orgs/{org_id}/projects/{project_id}/nodes")));
+        assert!(channels.contains(&format!("/orgs/{org_id}/tags/mainnet/nodes")));
+        for channel in channels {
+            channel.parse::<crate::mqtt::handler::Topic>().unwrap();
+        }
+    }
 }
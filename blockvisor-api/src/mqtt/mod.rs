@@ -16,6 +16,8 @@
 //! |               | /hosts/<host_id>/commands                    |
 //! |               | /nodes/<node_id>/commands                    |
 //! |---------------|----------------------------------------------|
+//! | incidents     | /orgs/<org_id>/incidents                     |
+//! |---------------|----------------------------------------------|
 //! ```
 
 pub mod handler;
@@ -29,8 +31,11 @@ pub use notifier::Notifier;
 use displaydoc::Display;
 use rumqttc::v5::AsyncClient;
 use rumqttc::v5::mqttbytes::QoS;
+use rumqttc::v5::mqttbytes::v5::PublishProperties;
 use thiserror::Error;
 
+use crate::util::trace;
+
 pub const CLIENT_CAPACITY: usize = 10;
 pub const CLIENT_QOS: QoS = QoS::AtLeastOnce;
 pub const CLIENT_RETAIN: bool = false;
@@ -39,6 +44,8 @@ pub const CLIENT_RETAIN: bool = false;
 pub enum Error {
     /// Failed to get Message channels: {0}
     Channels(self::message::Error),
+    /// Failed to disconnect: {0}
+    Disconnect(rumqttc::v5::ClientError),
     /// Failed to publish Message: {0}
     Publish(rumqttc::v5::ClientError),
 }
@@ -53,17 +60,33 @@ impl Client {
         Self { client }
     }
 
+    /// Publish `msg` to each of its channels, with the current trace context
+    /// in the user properties so hosts can continue the trace.
     pub async fn send(&mut self, msg: Message) -> Result<(), Error> {
         let payload = msg.encode();
         let channels = msg.channels().map_err(Error::Channels)?;
+        let properties = PublishProperties {
+            user_properties: trace::user_properties(),
+            ..Default::default()
+        };
 
         for channel in channels {
             self.client
-                .publish(&channel, CLIENT_QOS, CLIENT_RETAIN, payload.clone())
+                .publish_with_properties(
+                    &channel,
+                    CLIENT_QOS,
+                    CLIENT_RETAIN,
+                    payload.clone(),
+                    properties.clone(),
+                )
                 .await
                 .map_err(Error::Publish)?;
         }
 
         Ok(())
     }
+
+    pub async fn disconnect(&self) -> Result<(), Error> {
+        self.client.disconnect().await.map_err(Error::Disconnect)
+    }
 }
@@ -1,22 +1,30 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use displaydoc::Display;
 use prost::Message as _;
+use rumqttc::Outgoing;
 use rumqttc::v5::mqttbytes::v5::{Packet, Publish};
 use rumqttc::v5::{AsyncClient, Event, MqttOptions};
 use thiserror::Error;
+use tokio::sync::{Notify, broadcast};
 use tracing::{trace, warn};
 
+#[cfg(any(test, feature = "integration-test"))]
+use crate::chaos::Faults;
 use crate::database::{Database, Pool};
 use crate::grpc::command::host_pending;
-use crate::grpc::common;
+use crate::grpc::{api, common};
 use crate::model::command::NewCommand;
 use crate::model::host::{ConnectionStatus, UpdateHost};
 use crate::model::{Command, CommandType};
 
 use super::{CLIENT_CAPACITY, CLIENT_QOS, Client, Message};
 
+/// How many node messages are buffered for each node watch.
+const WATCH_CAPACITY: usize = 1024;
+
 #[derive(Debug, Display, Error)]
 pub enum Error {
     /// MQTT client error: {0}
@@ -31,12 +39,16 @@ pub enum Error {
     ParseHostId(uuid::Error),
     /// Failed to parse HostStatus: {0}
     ParseHostStatus(prost::DecodeError),
+    /// Failed to parse NodeMessage: {0}
+    ParseNodeMessage(prost::DecodeError),
     /// MQTT failed to get a pool connection: {0}
     PoolConnection(crate::database::Error),
     /// Failed to starting polling for events: {0}
     StartPolling(rumqttc::v5::ConnectionError),
     /// Failed to subscribe to `/bv/hosts/#`: {0}
     SubscribeHosts(rumqttc::v5::ClientError),
+    /// Failed to subscribe to `/nodes/+`: {0}
+    SubscribeNodes(rumqttc::v5::ClientError),
     /// MQTT failed to update host connection status: {0}
     UpdateHostStatus(crate::model::host::Error),
 }
@@ -44,6 +56,11 @@ pub enum Error {
 #[derive(Clone, Debug)]
 pub struct Notifier {
     client: Client,
+    connected: Arc<AtomicBool>,
+    disconnected: Arc<Notify>,
+    nodes: broadcast::Sender<api::NodeMessage>,
+    #[cfg(any(test, feature = "integration-test"))]
+    faults: Arc<Faults>,
 }
 
 impl Notifier {
@@ -54,18 +71,35 @@ impl Notifier {
             .subscribe("$share/blockvisor-api//bv/hosts/#", CLIENT_QOS)
             .await
             .map_err(Error::SubscribeHosts)?;
+        // every instance receives every node message for its watches
+        client
+            .subscribe("/nodes/+", CLIENT_QOS)
+            .await
+            .map_err(Error::SubscribeNodes)?;
 
-        // poll event loop in the foreground until SubAck
-        loop {
+        // poll event loop in the foreground until both SubAcks
+        let connected = Arc::new(AtomicBool::new(false));
+        let mut pending = 2;
+        while pending > 0 {
             match event_loop.poll().await {
-                Ok(Event::Incoming(Packet::SubAck(_))) => break,
+                Ok(Event::Incoming(Packet::ConnAck(_))) => connected.store(true, Ordering::Relaxed),
+                Ok(Event::Incoming(Packet::SubAck(_))) => pending -= 1,
                 Ok(event) => trace!("startup MQTT event: {event:?}"),
                 Err(err) => return Err(Error::StartPolling(err)),
             };
         }
 
         let client = Client::new(client);
-        let notifier = Arc::new(Self { client });
+        let disconnected = Arc::new(Notify::new());
+        let (nodes, _) = broadcast::channel(WATCH_CAPACITY);
+        let notifier = Arc::new(Self {
+            client,
+            connected: connected.clone(),
+            disconnected: disconnected.clone(),
+            nodes,
+            #[cfg(any(test, feature = "integration-test"))]
+            faults: Arc::default(),
+        });
         let mqtt = notifier.clone();
 
         // then continue polling in the background and warn on errors
@@ -75,13 +109,30 @@ impl Notifier {
 
             loop {
                 match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::Publish(packet)))
+                        if packet.topic.starts_with(b"/nodes/") =>
+                    {
+                        if let Err(err) = mqtt.handle_node_packet(&packet) {
+                            warn!("Failed to handle MQTT node event: {err}");
+                        }
+                    }
                     Ok(Event::Incoming(Packet::Publish(packet))) => {
                         if let Err(err) = mqtt.handle_packet(packet, &pool).await {
                             warn!("Failed to handle MQTT host event: {err}");
                         }
                     }
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        connected.store(true, Ordering::Relaxed);
+                    }
+                    // queued messages are published before the disconnect
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => {
+                        connected.store(false, Ordering::Relaxed);
+                        disconnected.notify_one();
+                        break;
+                    }
                     Ok(event) => trace!("incoming MQTT event: {event:?}"),
                     Err(err) => {
+                        connected.store(false, Ordering::Relaxed);
                         warn!("MQTT polling failure: {err}");
                         tokio::time::sleep(Duration::from_secs(1)).await;
                     }
@@ -97,9 +148,47 @@ impl Notifier {
         M: Into<Message> + Send,
     {
         let message = message.into();
+
+        #[cfg(any(test, feature = "integration-test"))]
+        if self.faults.take_mqtt_drop() {
+            warn!("Dropped MQTT message by injected fault.");
+            return Ok(());
+        }
+
         self.client.clone().send(message).await.map_err(Into::into)
     }
 
+    /// The injected faults, shared with the rest of a mocked `Context`.
+    #[cfg(any(test, feature = "integration-test"))]
+    pub fn faults(&self) -> Arc<Faults> {
+        self.faults.clone()
+    }
+
+    /// Whether the connection to the broker is currently up.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Receive each node message published by any api instance from now on.
+    pub fn watch_nodes(&self) -> broadcast::Receiver<api::NodeMessage> {
+        self.nodes.subscribe()
+    }
+
+    /// Publish any queued messages, then disconnect from the broker.
+    pub async fn shutdown(&self) -> Result<(), Error> {
+        self.client.disconnect().await?;
+        self.disconnected.notified().await;
+        Ok(())
+    }
+
+    fn handle_node_packet(&self, packet: &Publish) -> Result<(), Error> {
+        let message =
+            api::NodeMessage::decode(&*packet.payload).map_err(Error::ParseNodeMessage)?;
+        // there is nothing to do when no node is being watched
+        let _ = self.nodes.send(message);
+        Ok(())
+    }
+
     async fn handle_packet(&self, packet: Publish, pool: &Pool) -> Result<(), Error> {
         let status =
             common::HostStatus::decode(&*packet.payload).map_err(Error::ParseHostStatus)?;
@@ -0,0 +1,148 @@
+//! Apple Push Notification service with token based authentication.
+//!
+//! Apple rejects provider tokens older than an hour and throttles refreshing
+//! them more often than every 20 minutes, so each token is reused for a while.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use displaydoc::Display;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::config::push::ApnsConfig;
+
+use super::Message;
+
+/// How long a signed provider token is reused.
+const TOKEN_LIFETIME: TimeDelta = TimeDelta::minutes(50);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to encode APNs provider token: {0:?}
+    Encode(ErrorKind),
+    /// Failed to parse APNs private key: {0:?}
+    PrivateKey(ErrorKind),
+    /// APNs returned `{0}`: {1}
+    Response(StatusCode, String),
+    /// Failed to send APNs request: {0}
+    SendRequest(reqwest::Error),
+    /// The device token is no longer registered.
+    Unregistered,
+    /// Failed to build APNs url: {0}
+    Url(url::ParseError),
+}
+
+#[derive(Serialize)]
+struct ProviderClaims<'a> {
+    iss: &'a str,
+    iat: i64,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    reason: String,
+}
+
+struct ProviderToken {
+    token: String,
+    issued_at: DateTime<Utc>,
+}
+
+pub struct Apns {
+    client: reqwest::Client,
+    base_url: Url,
+    topic: String,
+    team_id: String,
+    header: Header,
+    key: EncodingKey,
+    token: Mutex<Option<ProviderToken>>,
+}
+
+impl Apns {
+    pub fn new(config: &ApnsConfig, client: reqwest::Client) -> Result<Self, Error> {
+        let key = EncodingKey::from_ec_pem(config.private_key.as_bytes())
+            .map_err(|err| Error::PrivateKey(err.into_kind()))?;
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(config.key_id.clone());
+
+        Ok(Apns {
+            client,
+            base_url: config.url.clone(),
+            topic: config.topic.clone(),
+            team_id: config.team_id.clone(),
+            header,
+            key,
+            token: Mutex::new(None),
+        })
+    }
+
+    pub async fn send(&self, device_token: &str, message: &Message) -> Result<(), Error> {
+        let provider_token = self.provider_token().await?;
+        let url = self
+            .base_url
+            .join(&format!("/3/device/{device_token}"))
+            .map_err(Error::Url)?;
+        let body = json!({
+            "aps": {
+                "alert": message,
+                "sound": "default",
+            }
+        });
+
+        let response = self
+            .client
+            .post(url)
+            .bearer_auth(provider_token)
+            .header("apns-topic", &self.topic)
+            .header("apns-push-type", "alert")
+            .header("apns-priority", "10")
+            .json(&body)
+            .send()
+            .await
+            .map_err(Error::SendRequest)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        } else if status == StatusCode::GONE {
+            return Err(Error::Unregistered);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(resp) if status == StatusCode::BAD_REQUEST && resp.reason == "BadDeviceToken" => {
+                Err(Error::Unregistered)
+            }
+            _ => Err(Error::Response(status, body)),
+        }
+    }
+
+    async fn provider_token(&self) -> Result<String, Error> {
+        let mut cached = self.token.lock().await;
+        let now = Utc::now();
+        if let Some(token) = cached
+            .as_ref()
+            .filter(|token| token.issued_at + TOKEN_LIFETIME > now)
+        {
+            return Ok(token.token.clone());
+        }
+
+        let claims = ProviderClaims {
+            iss: &self.team_id,
+            iat: now.timestamp(),
+        };
+        let token = jsonwebtoken::encode(&self.header, &claims, &self.key)
+            .map_err(|err| Error::Encode(err.into_kind()))?;
+        *cached = Some(ProviderToken {
+            token: token.clone(),
+            issued_at: now,
+        });
+
+        Ok(token)
+    }
+}
@@ -0,0 +1,161 @@
+//! Firebase Cloud Messaging via the HTTP v1 API.
+//!
+//! The service account key signs a JWT that is exchanged for a short lived
+//! OAuth2 access token, which is cached until shortly before it expires.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use displaydoc::Display;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::config::push::FcmConfig;
+
+use super::Message;
+
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:jwt-bearer";
+/// Requested lifetime of the signed assertion.
+const ASSERTION_LIFETIME: TimeDelta = TimeDelta::hours(1);
+/// Refresh an access token this long before it expires.
+const REFRESH_MARGIN: TimeDelta = TimeDelta::minutes(5);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to encode FCM assertion: {0:?}
+    Encode(ErrorKind),
+    /// Failed to parse FCM private key: {0:?}
+    PrivateKey(ErrorKind),
+    /// FCM returned `{0}`: {1}
+    Response(StatusCode, String),
+    /// Failed to send FCM request: {0}
+    SendRequest(reqwest::Error),
+    /// Failed to parse FCM token response: {0}
+    TokenResponse(reqwest::Error),
+    /// The device token is no longer registered.
+    Unregistered,
+}
+
+#[derive(Serialize)]
+struct Assertion<'a> {
+    iss: &'a str,
+    scope: &'static str,
+    aud: &'static str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+struct AccessToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+pub struct Fcm {
+    client: reqwest::Client,
+    client_email: String,
+    send_url: String,
+    key: EncodingKey,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl Fcm {
+    pub fn new(config: &FcmConfig, client: reqwest::Client) -> Result<Self, Error> {
+        let key = EncodingKey::from_rsa_pem(config.private_key.as_bytes())
+            .map_err(|err| Error::PrivateKey(err.into_kind()))?;
+        let send_url = format!(
+            "https://fcm.googleapis.com/v1/projects/{}/messages:send",
+            config.project_id
+        );
+
+        Ok(Fcm {
+            client,
+            client_email: config.client_email.clone(),
+            send_url,
+            key,
+            token: Mutex::new(None),
+        })
+    }
+
+    pub async fn send(&self, device_token: &str, message: &Message) -> Result<(), Error> {
+        let access_token = self.access_token().await?;
+        let body = json!({
+            "message": {
+                "token": device_token,
+                "notification": message,
+                "android": { "priority": "high" },
+            }
+        });
+
+        let response = self
+            .client
+            .post(&self.send_url)
+            .bearer_auth(access_token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(Error::SendRequest)?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND => Err(Error::Unregistered),
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(Error::Response(status, body))
+            }
+        }
+    }
+
+    async fn access_token(&self) -> Result<String, Error> {
+        let mut cached = self.token.lock().await;
+        let now = Utc::now();
+        if let Some(token) = cached
+            .as_ref()
+            .filter(|token| token.expires_at - REFRESH_MARGIN > now)
+        {
+            return Ok(token.token.clone());
+        }
+
+        let assertion = Assertion {
+            iss: &self.client_email,
+            scope: SCOPE,
+            aud: TOKEN_URL,
+            iat: now.timestamp(),
+            exp: (now + ASSERTION_LIFETIME).timestamp(),
+        };
+        let jwt = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &assertion, &self.key)
+            .map_err(|err| Error::Encode(err.into_kind()))?;
+
+        let response = self
+            .client
+            .post(TOKEN_URL)
+            .form(&[("grant_type", GRANT_TYPE), ("assertion", jwt.as_str())])
+            .send()
+            .await
+            .map_err(Error::SendRequest)?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Response(status, body));
+        }
+
+        let resp: TokenResponse = response.json().await.map_err(Error::TokenResponse)?;
+        let token = resp.access_token.clone();
+        *cached = Some(AccessToken {
+            token: resp.access_token,
+            expires_at: now + TimeDelta::seconds(resp.expires_in),
+        });
+
+        Ok(token)
+    }
+}
@@ -0,0 +1,174 @@
+//! Mobile push notifications for critical alerts.
+//!
+//! Android devices are reached through FCM and iOS devices through APNs. A
+//! notification is only sent to users whose preferences allow its kind.
+
+pub mod apns;
+pub mod fcm;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use displaydoc::Display;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::auth::resource::UserId;
+use crate::config::push::Config;
+use crate::database::Conn;
+use crate::model::user::device::{PushPlatform, UserDevice};
+use crate::model::user::notification::{NotificationKind, NotificationPreferences};
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tonic::async_trait]
+pub trait Sender {
+    async fn send_push(&self, device: &UserDevice, message: &Message) -> Result<(), Error>;
+}
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// APNs error: {0}
+    Apns(apns::Error),
+    /// Failed to build push client: {0}
+    BuildClient(reqwest::Error),
+    /// Push device error: {0}
+    Device(#[from] crate::model::user::device::Error),
+    /// FCM error: {0}
+    Fcm(fcm::Error),
+    /// No push provider is configured for `{0:?}`.
+    NoProvider(PushPlatform),
+    /// Notification preferences error: {0}
+    Preferences(#[from] crate::model::user::notification::Error),
+    /// The push token is no longer registered with its provider.
+    Unregistered,
+}
+
+/// The content of a push notification.
+#[derive(Clone, Debug, Serialize)]
+pub struct Message {
+    pub title: String,
+    pub body: String,
+}
+
+pub struct Push {
+    sender: Box<dyn Sender + Send + Sync + 'static>,
+}
+
+impl Push {
+    pub fn new(config: &Config) -> Result<Option<Self>, Error> {
+        if config.fcm.is_none() && config.apns.is_none() {
+            return Ok(None);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(CLIENT_TIMEOUT)
+            .build()
+            .map_err(Error::BuildClient)?;
+        let fcm = config
+            .fcm
+            .as_ref()
+            .map(|config| fcm::Fcm::new(config, client.clone()))
+            .transpose()
+            .map_err(Error::Fcm)?;
+        let apns = config
+            .apns
+            .as_ref()
+            .map(|config| apns::Apns::new(config, client))
+            .transpose()
+            .map_err(Error::Apns)?;
+
+        Ok(Some(Push {
+            sender: Box::new(Providers { fcm, apns }),
+        }))
+    }
+
+    #[cfg(any(test, feature = "integration-test"))]
+    pub fn new_mocked() -> Self {
+        Push {
+            sender: Box::new(tests::MockPush),
+        }
+    }
+
+    /// Send `message` to every device of `user_ids` that wants `kind`.
+    ///
+    /// Devices whose token was unregistered are removed. Returns the number of
+    /// devices that were notified.
+    pub async fn notify(
+        &self,
+        user_ids: &HashSet<UserId>,
+        kind: NotificationKind,
+        message: &Message,
+        conn: &mut Conn<'_>,
+    ) -> Result<usize, Error> {
+        let wanting = NotificationPreferences::wanting(user_ids, kind, conn).await?;
+        if wanting.is_empty() {
+            return Ok(0);
+        }
+
+        let mut sent = 0;
+        for device in UserDevice::by_users(&wanting, conn).await? {
+            match self.sender.send_push(&device, message).await {
+                Ok(()) => sent += 1,
+                Err(Error::Unregistered) => device.delete(conn).await?,
+                Err(err) => warn!("Failed to push to device {}: {err}", device.id),
+            }
+        }
+
+        Ok(sent)
+    }
+}
+
+/// Sends each device to the provider of its platform.
+struct Providers {
+    fcm: Option<fcm::Fcm>,
+    apns: Option<apns::Apns>,
+}
+
+#[tonic::async_trait]
+impl Sender for Providers {
+    async fn send_push(&self, device: &UserDevice, message: &Message) -> Result<(), Error> {
+        match device.platform {
+            PushPlatform::Fcm => {
+                let fcm = self
+                    .fcm
+                    .as_ref()
+                    .ok_or(Error::NoProvider(device.platform))?;
+                match fcm.send(&device.push_token, message).await {
+                    Ok(()) => Ok(()),
+                    Err(fcm::Error::Unregistered) => Err(Error::Unregistered),
+                    Err(err) => Err(Error::Fcm(err)),
+                }
+            }
+            PushPlatform::Apns => {
+                let apns = self
+                    .apns
+                    .as_ref()
+                    .ok_or(Error::NoProvider(device.platform))?;
+                match apns.send(&device.push_token, message).await {
+                    Ok(()) => Ok(()),
+                    Err(apns::Error::Unregistered) => Err(Error::Unregistered),
+                    Err(err) => Err(Error::Apns(err)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(any(test, feature = "integration-test"))]
+pub mod tests {
+    use tracing::debug;
+
+    use super::*;
+
+    pub struct MockPush;
+
+    #[tonic::async_trait]
+    impl Sender for MockPush {
+        async fn send_push(&self, device: &UserDevice, _message: &Message) -> Result<(), Error> {
+            debug!("Mocked push to device {}", device.id);
+            Ok(())
+        }
+    }
+}
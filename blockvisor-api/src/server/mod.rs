@@ -1,3 +1,6 @@
+pub mod shutdown;
+pub use shutdown::Shutdown;
+
 use std::net::SocketAddr;
 use std::sync::Arc;
 
@@ -9,6 +12,7 @@ use thiserror::Error;
 use tokio::net::TcpListener;
 use tower::make::Shared;
 use tower::steer::Steer;
+use tracing::{info, warn};
 
 use crate::config::Context;
 use crate::{grpc, http};
@@ -17,8 +21,12 @@ use crate::{grpc, http};
 pub enum Error {
     /// Failed to bind to `{0}`: `{1}`
     Listener(SocketAddr, tokio::io::Error),
+    /// Failed to listen for config reload signals: {0}
+    ReloadSignal(std::io::Error),
     /// Server error: {0}
     Server(std::io::Error),
+    /// Failed to listen for shutdown signals: {0}
+    Signal(std::io::Error),
 }
 
 pub async fn start(context: Arc<Context>) -> Result<(), Error> {
@@ -26,6 +34,11 @@ pub async fn start(context: Arc<Context>) -> Result<(), Error> {
     let listener = TcpListener::bind(&addr)
         .await
         .map_err(|err| Error::Listener(addr, err))?;
+    context.shutdown.on_signal().map_err(Error::Signal)?;
+    context
+        .log
+        .on_reload_signal()
+        .map_err(Error::ReloadSignal)?;
 
     start_with_listener(context, listener).await
 }
@@ -43,9 +56,33 @@ pub async fn start_with_listener(
         if is_grpc_request(req) { 0 } else { 1 }
     });
 
-    axum::serve(listener, Shared::new(service))
-        .await
-        .map_err(Error::Server)
+    // `/health` fails as soon as draining starts, but the listener stays open
+    // until the load balancer has had time to stop routing to it
+    let delay = *context.config.server.deregistration_delay;
+    let shutdown = context.shutdown.clone();
+    let server = axum::serve(listener, Shared::new(service))
+        .with_graceful_shutdown(async move { shutdown.wait_deregistered(delay).await })
+        .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return result.map_err(Error::Server),
+        () = context.shutdown.wait_deregistered(delay) => {}
+    }
+
+    let drain_timeout = *context.config.server.drain_timeout;
+    match tokio::time::timeout(drain_timeout, server).await {
+        Ok(result) => result.map_err(Error::Server)?,
+        Err(_) => warn!("Requests still in flight after {drain_timeout:?} drain timeout"),
+    }
+
+    match tokio::time::timeout(drain_timeout, context.notifier.shutdown()).await {
+        Ok(Ok(())) => info!("Shutdown complete"),
+        Ok(Err(err)) => warn!("Failed to flush MQTT messages: {err}"),
+        Err(_) => warn!("MQTT messages still queued after {drain_timeout:?} drain timeout"),
+    }
+
+    Ok(())
 }
 
 fn is_grpc_request<B>(req: &Request<B>) -> bool {
@@ -55,3 +92,41 @@ fn is_grpc_request<B>(req: &Request<B>) -> bool {
         .filter(|content_type| content_type.starts_with(b"application/grpc"))
         .is_some()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn health_fails_before_the_listener_closes() {
+        let (ctx, _db) = Context::with_mocked().await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(start_with_listener(ctx.clone(), listener));
+
+        let url = format!("http://{addr}/health");
+        assert_eq!(health(&url).await.unwrap().status(), StatusCode::OK);
+
+        // requests are still served while the load balancer deregisters it
+        ctx.shutdown.start();
+        let status = health(&url).await.unwrap().status();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!server.is_finished());
+
+        let delay = *ctx.config.server.deregistration_delay;
+        tokio::time::timeout(delay + Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(health(&url).await.is_err());
+    }
+
+    async fn health(url: &str) -> reqwest::Result<reqwest::Response> {
+        reqwest::Client::new().get(url).send().await
+    }
+}
@@ -0,0 +1,100 @@
+//! Coordinated shutdown for zero-downtime deploys.
+//!
+//! On SIGTERM or ctrl-c the server starts draining:
+//! 1. `/health` reports the server as unavailable so the load balancer stops
+//!    routing new requests to it.
+//! 2. New requests are still served for `server.deregistration_delay`, while
+//!    the load balancer notices the failing health check.
+//! 3. No new connections are accepted, and in-flight requests (along with
+//!    their transactions) have up to `server.drain_timeout` to finish.
+//! 4. MQTT messages queued by those requests are published before the
+//!    notifier disconnects from the broker.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::watch;
+use tracing::info;
+
+/// Whether the server has started shutting down.
+#[derive(Debug)]
+pub struct Shutdown {
+    draining: watch::Sender<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (draining, _) = watch::channel(false);
+        Shutdown { draining }
+    }
+}
+
+impl Shutdown {
+    /// Start draining the server.
+    pub fn start(&self) {
+        self.draining.send_replace(true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Wait until the server starts draining.
+    pub async fn wait(&self) {
+        let mut draining = self.draining.subscribe();
+        // the sender is owned by self so the channel can't close here
+        let _ = draining.wait_for(|draining| *draining).await;
+    }
+
+    /// Wait until the server starts draining and then for `delay`, after
+    /// which the listener may stop accepting connections.
+    pub async fn wait_deregistered(&self, delay: Duration) {
+        self.wait().await;
+        tokio::time::sleep(delay).await;
+    }
+
+    /// Start draining on the first SIGTERM or ctrl-c.
+    pub fn on_signal(self: &Arc<Self>) -> Result<(), std::io::Error> {
+        let mut terminate = signal(SignalKind::terminate())?;
+        let shutdown = self.clone();
+
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = terminate.recv() => info!("Received SIGTERM, draining requests..."),
+                _ = tokio::signal::ctrl_c() => info!("Received ctrl-c, draining requests..."),
+            }
+            shutdown.start();
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn waiters_are_released_once_draining() {
+        let shutdown = Arc::new(Shutdown::default());
+        assert!(!shutdown.is_draining());
+
+        let waiter = shutdown.clone();
+        let waiting = tokio::spawn(async move { waiter.wait().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+
+        shutdown.start();
+        assert!(shutdown.is_draining());
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // waiting after the start returns immediately
+        shutdown.wait().await;
+    }
+}
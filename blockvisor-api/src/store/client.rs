@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::delete_object::DeleteObjectError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
 use aws_sdk_s3::operation::put_object::PutObjectError;
@@ -13,6 +14,8 @@ use url::Url;
 
 #[derive(Debug, Display, Error)]
 pub enum Error {
+    /// Failed to delete key `{0}:{1}`: {2:?}
+    DeleteKey(String, String, SdkError<DeleteObjectError>),
     /// Failed to create presigned download URL for key `{0}`: {1:?}
     DownloadUrl(String, SdkError<GetObjectError>),
     /// Failed to list path `{0}`: {1:?}
@@ -103,6 +106,37 @@ impl Client {
             .map_err(|err| Error::WriteKey(bucket.into(), key.clone(), err))
     }
 
+    /// Write a key with a `Content-Type`, for objects that browsers render.
+    pub(super) async fn write_typed_key(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<(), Error> {
+        let key = key.to_lowercase();
+        self.put_object()
+            .bucket(bucket)
+            .key(&key)
+            .content_type(content_type)
+            .body(data.into())
+            .send()
+            .await
+            .map(|_resp| ())
+            .map_err(|err| Error::WriteKey(bucket.into(), key.clone(), err))
+    }
+
+    pub(super) async fn delete_key(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        let key = key.to_lowercase();
+        self.delete_object()
+            .bucket(bucket)
+            .key(&key)
+            .send()
+            .await
+            .map(|_resp| ())
+            .map_err(|err| Error::DeleteKey(bucket.into(), key.clone(), err))
+    }
+
     pub(super) async fn download_url(
         &self,
         bucket: &str,
@@ -11,6 +11,7 @@ use std::time::Duration;
 use aws_sdk_s3::config::{
     Credentials, Region, RequestChecksumCalculation, ResponseChecksumValidation,
 };
+use chrono::Utc;
 use derive_more::{Deref, Display, Into};
 use diesel_derive_newtype::DieselNewType;
 use displaydoc::Display as DisplayDoc;
@@ -18,6 +19,7 @@ use thiserror::Error;
 use tracing::warn;
 use url::Url;
 
+use crate::auth::resource::{OrgId, UserId};
 use crate::config::store::{BucketConfig, Config};
 use crate::grpc::{Status, api};
 use crate::util::LOWER_KEBAB_CASE;
@@ -28,6 +30,10 @@ pub const CREDENTIALS: &str = "api-credentials-provider";
 pub const BUNDLE_FILE: &str = "bvd-bundle.tgz";
 pub const MANIFEST_BODY: &str = "manifest-body.json";
 pub const MANIFEST_HEADER: &str = "manifest-header.json";
+pub const USER_EXPORT_FILE: &str = "user-export.json";
+
+/// Presigned S3 urls are valid for at most 7 days.
+pub const USER_EXPORT_EXPIRATION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
 
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
@@ -259,6 +265,74 @@ impl Store {
             .map_err(Into::into)
     }
 
+    /// Save a user data export and return a presigned url to download it.
+    pub async fn save_user_export(&self, user_id: UserId, data: Vec<u8>) -> Result<Url, Error> {
+        let key = format!("{user_id}/{}/{USER_EXPORT_FILE}", Utc::now().timestamp());
+        self.client
+            .write_key(&self.bucket.export, &key, data)
+            .await?;
+        self.client
+            .download_url(&self.bucket.export, &key, USER_EXPORT_EXPIRATION)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Return a presigned url for a host to upload a command artifact.
+    pub async fn artifact_upload_url(&self, key: &str) -> Result<Url, Error> {
+        self.client
+            .upload_url(&self.bucket.artifact, key, self.expiration)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Return a presigned url to download an uploaded command artifact.
+    pub async fn artifact_download_url(&self, key: &str) -> Result<Url, Error> {
+        self.client
+            .download_url(&self.bucket.artifact, key, self.expiration)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn delete_artifact(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .delete_key(&self.bucket.artifact, key)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Save a new logo of an org and return its key.
+    ///
+    /// Each logo gets its own key so that cached copies of a replaced logo are
+    /// not served in its place.
+    pub async fn save_logo(
+        &self,
+        org_id: OrgId,
+        data: Vec<u8>,
+        content_type: &str,
+        extension: &str,
+    ) -> Result<String, Error> {
+        let key = format!("{org_id}/{}.{extension}", Utc::now().timestamp_millis());
+        self.client
+            .write_typed_key(&self.bucket.branding, &key, data, content_type)
+            .await?;
+        Ok(key)
+    }
+
+    /// Return a presigned url to download the logo of an org.
+    pub async fn logo_url(&self, key: &str) -> Result<Url, Error> {
+        self.client
+            .download_url(&self.bucket.branding, key, self.expiration)
+            .await
+            .map_err(Into::into)
+    }
+
+    pub async fn delete_logo(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .delete_key(&self.bucket.branding, key)
+            .await
+            .map_err(Into::into)
+    }
+
     pub async fn upload_slots(
         &self,
         store_key: &StoreKey,
@@ -1,30 +1,69 @@
+//! Secret values of each resource, such as provider credentials or the secret
+//! image property values of a node.
+//!
+//! Values are stored sealed in `secrets`, see `crate::encryption`, except for
+//! the keys in `SERVED` that are read from config for every resource.
+
 use std::sync::Arc;
 
+use base64::engine::{Engine as _, general_purpose::STANDARD};
+use chrono::Utc;
 use derive_more::{Deref, Display, Into};
+use diesel::prelude::*;
+use diesel::result::Error::NotFound;
+use diesel_async::RunQueryDsl;
 use displaydoc::Display as DisplayDoc;
 use thiserror::Error;
 
 use crate::auth::resource::Resource;
 use crate::config::secret::Config;
+use crate::database::Conn;
+use crate::encryption::Keyring;
 use crate::grpc::Status;
+use crate::model::schema::secrets;
+use crate::model::sql::Encrypted;
 use crate::util::LOWER_KEBAB_CASE;
 
+/// The secrets currently served from config.
+const SERVED: [&str; 3] = [
+    "cloudflare-cert-key",
+    "grafana-loki-key",
+    "grafana-prometheus-key",
+];
+
 #[derive(Debug, DisplayDoc, Error)]
 pub enum Error {
+    /// Failed to decode secret `{0}`: {1}
+    Decode(SecretKey, base64::DecodeError),
+    /// Failed to delete the secrets of {0:?}: {1}
+    Delete(Resource, diesel::result::Error),
+    /// Secret `{0}` is empty.
+    Empty(&'static str),
+    /// Failed to seal or open secret `{0}`: {1}
+    Encryption(SecretKey, crate::encryption::Error),
+    /// Failed to get secret `{0}`: {1}
+    Get(SecretKey, diesel::result::Error),
+    /// Failed to put secret `{0}`: {1}
+    Put(SecretKey, diesel::result::Error),
     /// SecretKey is not lower-kebab-case: {0}
     SecretKeyChars(String),
     /// SecretKey length `{0}` must be at least 6 characters.
     SecretKeyLen(usize),
-    /// Secret functionality is currently unimplemented.
-    Unimplemented,
+    /// Secret `{0}` is served from config.
+    Served(SecretKey),
 }
 
 impl From<Error> for Status {
     fn from(err: Error) -> Self {
         use Error::*;
         match err {
-            SecretKeyChars(_) | SecretKeyLen(_) => Status::invalid_argument("secret_key"),
-            Unimplemented => Status::internal("Unimplemented."),
+            Get(_, NotFound) => Status::not_found("Secret not found."),
+            SecretKeyChars(_) | SecretKeyLen(_) | Served(_) => {
+                Status::invalid_argument("secret_key")
+            }
+            Decode(..) | Delete(..) | Empty(_) | Encryption(..) | Get(..) | Put(..) => {
+                Status::internal("Internal error.")
+            }
         }
     }
 }
@@ -46,28 +85,99 @@ impl SecretKey {
 
 pub struct Secret {
     config: Arc<Config>,
+    keyring: Arc<Keyring>,
 }
 
 impl Secret {
-    pub const fn new(config: Arc<Config>) -> Self {
-        Secret { config }
+    pub const fn new(config: Arc<Config>, keyring: Arc<Keyring>) -> Self {
+        Secret { config, keyring }
     }
 
-    pub fn get(&self, _resource: Resource, key: &SecretKey) -> Result<Vec<u8>, Error> {
-        match key.0.as_ref() {
-            "cloudflare-cert-key" => Ok(self.config.cloudflare_cert_key.clone().into_bytes()),
-            "grafana-loki-key" => Ok(self.config.grafana_loki_key.clone().into_bytes()),
-            "grafana-prometheus-key" => Ok(self.config.grafana_prometheus_key.clone().into_bytes()),
-            _ => Err(Error::Unimplemented),
+    pub async fn get(
+        &self,
+        resource: Resource,
+        key: &SecretKey,
+        conn: &mut Conn<'_>,
+    ) -> Result<Vec<u8>, Error> {
+        if let Some(value) = self.served(key) {
+            return Ok(value.as_bytes().to_vec());
         }
+
+        let value: Encrypted<String> = secrets::table
+            .find((resource.typ(), resource.id(), key.as_str()))
+            .select(secrets::value)
+            .get_result(conn)
+            .await
+            .map_err(|err| Error::Get(key.clone(), err))?;
+        let value = value
+            .open(&self.keyring)
+            .map_err(|err| Error::Encryption(key.clone(), err))?;
+
+        STANDARD
+            .decode(value)
+            .map_err(|err| Error::Decode(key.clone(), err))
     }
 
-    pub const fn put(
+    /// Write `value` as secret `key` of `resource`, replacing any old value.
+    pub async fn put(
         &self,
-        _resource: Resource,
-        _key: &SecretKey,
-        _value: &[u8],
+        resource: Resource,
+        key: &SecretKey,
+        value: &[u8],
+        conn: &mut Conn<'_>,
     ) -> Result<(), Error> {
-        Err(Error::Unimplemented)
+        if self.served(key).is_some() {
+            return Err(Error::Served(key.clone()));
+        }
+
+        let value = Encrypted::seal(&STANDARD.encode(value), &self.keyring)
+            .map_err(|err| Error::Encryption(key.clone(), err))?;
+        diesel::insert_into(secrets::table)
+            .values((
+                secrets::resource_type.eq(resource.typ()),
+                secrets::resource_id.eq(resource.id()),
+                secrets::key.eq(key.as_str()),
+                secrets::value.eq(&value),
+            ))
+            .on_conflict((secrets::resource_type, secrets::resource_id, secrets::key))
+            .do_update()
+            .set((
+                secrets::value.eq(&value),
+                secrets::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Put(key.clone(), err))
+    }
+
+    /// Remove every secret of `resource`.
+    pub async fn delete_all(&self, resource: Resource, conn: &mut Conn<'_>) -> Result<(), Error> {
+        diesel::delete(secrets::table)
+            .filter(secrets::resource_type.eq(resource.typ()))
+            .filter(secrets::resource_id.eq(resource.id()))
+            .execute(conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| Error::Delete(resource, err))
+    }
+
+    /// Check that every served secret can be read and is non-empty.
+    pub fn probe(&self) -> Result<(), Error> {
+        for key in SERVED {
+            if self.served(key).is_none_or(str::is_empty) {
+                return Err(Error::Empty(key));
+            }
+        }
+        Ok(())
+    }
+
+    fn served(&self, key: &str) -> Option<&str> {
+        match key {
+            "cloudflare-cert-key" => Some(self.config.cloudflare_cert_key.as_str()),
+            "grafana-loki-key" => Some(self.config.grafana_loki_key.as_str()),
+            "grafana-prometheus-key" => Some(self.config.grafana_prometheus_key.as_str()),
+            _ => None,
+        }
     }
 }
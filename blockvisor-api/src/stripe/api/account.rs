@@ -65,3 +65,21 @@ pub struct Account {
     // #[serde(rename = "type")]
     // pub type_: Option<AccountType>,
 }
+
+/// Retrieve the account of the api key, which is used as a reachability check.
+///
+/// For more details see <https://stripe.com/docs/api/accounts/retrieve>
+#[derive(Debug, serde::Serialize)]
+pub struct GetAccount {}
+
+impl super::StripeEndpoint for GetAccount {
+    type Result = Account;
+
+    fn method(&self) -> reqwest::Method {
+        reqwest::Method::GET
+    }
+
+    fn path(&self) -> String {
+        "account".to_string()
+    }
+}
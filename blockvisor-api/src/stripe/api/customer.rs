@@ -305,3 +305,37 @@ impl super::StripeEndpoint for UpdateCustomer<'_> {
         Some(self)
     }
 }
+
+/// Updates the email address that Stripe sends invoices and receipts to.
+#[derive(Debug, serde::Serialize)]
+pub struct UpdateCustomerEmail<'a> {
+    #[serde(skip_serializing)]
+    customer_id: &'a str,
+    email: &'a str,
+}
+
+impl<'a> UpdateCustomerEmail<'a> {
+    pub const fn new(customer_id: &'a str, email: &'a str) -> Self {
+        Self { customer_id, email }
+    }
+}
+
+impl super::StripeEndpoint for UpdateCustomerEmail<'_> {
+    type Result = Customer;
+
+    fn method(&self) -> hyper::Method {
+        hyper::Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("customers/{}", self.customer_id)
+    }
+
+    fn query(&self) -> Option<&Self> {
+        None
+    }
+
+    fn body(&self) -> Option<&Self> {
+        Some(self)
+    }
+}
@@ -30,6 +30,8 @@ pub struct Event {
 
 #[derive(Debug, Default, serde::Deserialize)]
 pub enum EventType {
+    #[serde(rename = "invoice.payment_failed")]
+    InvoicePaymentFailed,
     #[serde(rename = "setup_intent.canceled")]
     SetupIntentCanceled,
     #[serde(rename = "setup_intent.created")]
@@ -54,6 +56,7 @@ pub struct NotificationEventData {
 #[derive(Debug, serde::Deserialize)]
 #[serde(tag = "object", rename_all = "snake_case")]
 pub enum EventObject {
+    Invoice(super::invoice::Invoice),
     SetupIntent(SetupIntent),
     #[serde(other)]
     Other,
@@ -497,3 +497,50 @@ impl super::StripeEndpoint for DeleteSubscriptionItem<'_> {
         format!("subscription_items/{}", self.item_id)
     }
 }
+
+/// A quantity of metered usage reported for a subscription item.
+#[derive(Debug, serde::Deserialize)]
+pub struct UsageRecord {
+    /// Unique identifier for the object.
+    pub id: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct CreateUsageRecord<'a> {
+    #[serde(skip_serializing)]
+    item_id: &'a SubscriptionItemId,
+    quantity: u64,
+    timestamp: super::Timestamp,
+    action: &'static str,
+}
+
+impl<'a> CreateUsageRecord<'a> {
+    pub fn new(
+        item_id: &'a SubscriptionItemId,
+        quantity: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        Self {
+            item_id,
+            quantity,
+            timestamp: timestamp.into(),
+            action: "increment",
+        }
+    }
+}
+
+impl super::StripeEndpoint for CreateUsageRecord<'_> {
+    type Result = UsageRecord;
+
+    fn method(&self) -> reqwest::Method {
+        reqwest::Method::POST
+    }
+
+    fn path(&self) -> String {
+        format!("subscription_items/{}/usage_records", self.item_id)
+    }
+
+    fn body(&self) -> Option<&Self> {
+        Some(self)
+    }
+}
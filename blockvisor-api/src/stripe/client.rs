@@ -1,3 +1,5 @@
+#[cfg(any(test, feature = "integration-test"))]
+use std::sync::Arc;
 use std::time::Duration;
 
 use displaydoc::Display;
@@ -6,6 +8,9 @@ use serde::Serialize;
 use thiserror::Error;
 use url::Url;
 
+#[cfg(any(test, feature = "integration-test"))]
+use crate::chaos::Faults;
+
 use super::api::StripeEndpoint;
 
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
@@ -31,6 +36,8 @@ pub struct Client {
     inner: reqwest::Client,
     endpoint: Url,
     secret: String,
+    #[cfg(any(test, feature = "integration-test"))]
+    faults: Option<Arc<Faults>>,
 }
 
 impl Client {
@@ -45,11 +52,13 @@ impl Client {
             inner,
             endpoint,
             secret: secret.to_owned(),
+            #[cfg(any(test, feature = "integration-test"))]
+            faults: None,
         })
     }
 
     #[cfg(any(test, feature = "integration-test"))]
-    pub fn new_mock(endpoint: Url) -> Result<Self, Error> {
+    pub fn new_mock(endpoint: Url, faults: Arc<Faults>) -> Result<Self, Error> {
         let inner = reqwest::Client::builder()
             .timeout(CLIENT_TIMEOUT)
             .build()
@@ -60,6 +69,7 @@ impl Client {
             inner,
             endpoint,
             secret,
+            faults: Some(faults),
         })
     }
 
@@ -67,6 +77,15 @@ impl Client {
     where
         E: StripeEndpoint + Serialize + std::fmt::Debug,
     {
+        #[cfg(any(test, feature = "integration-test"))]
+        if let Some(delay) = self
+            .faults
+            .as_ref()
+            .and_then(|faults| faults.stripe_delay())
+        {
+            tokio::time::sleep(delay).await;
+        }
+
         let url = self
             .endpoint
             .join(&endpoint.path())
@@ -1,5 +1,6 @@
 pub mod api;
 mod client;
+pub mod webhook;
 
 use std::sync::Arc;
 
@@ -9,10 +10,13 @@ use thiserror::Error;
 
 use crate::auth::resource::{OrgId, UserId};
 use crate::config::stripe::Config;
+use crate::encryption::Keyring;
 use crate::model::{Org, User};
 
 use self::api::subscription::{QuantityModification, SubscriptionItem, SubscriptionItemId};
-use self::api::{address, customer, invoice, payment_method, price, setup_intent, subscription};
+use self::api::{
+    account, address, customer, invoice, payment_method, price, setup_intent, subscription,
+};
 use self::client::Client;
 
 #[tonic::async_trait]
@@ -94,6 +98,14 @@ pub trait Payment {
         item_id: &subscription::SubscriptionItemId,
     ) -> Result<(), Error>;
 
+    /// Adds `quantity` to the metered usage of a subscription item.
+    async fn create_usage_record(
+        &self,
+        item_id: &subscription::SubscriptionItemId,
+        quantity: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), Error>;
+
     async fn get_price(&self, sku: &str) -> Result<price::Price, Error>;
 
     async fn get_address(
@@ -109,21 +121,32 @@ pub trait Payment {
 
     async fn delete_address(&self, customer_id: &str) -> Result<(), Error>;
 
+    /// Updates the billing contact email of a customer.
+    async fn set_email(&self, customer_id: &str, email: &str) -> Result<(), Error>;
+
     async fn get_invoices(&self, customer_id: &str) -> Result<Vec<invoice::Invoice>, Error>;
+
+    /// Returns the stripe account of the api key.
+    async fn get_account(&self) -> Result<account::Account, Error>;
 }
 
 #[tonic::async_trait]
 pub trait Subscription: Payment {
-    async fn add_subscription(&self, org: &Org, sku: &str) -> Result<SubscriptionItem, Error> {
+    async fn add_subscription(
+        &self,
+        org: &Org,
+        keyring: &Keyring,
+        sku: &str,
+    ) -> Result<SubscriptionItem, Error> {
         // If there is no corresponding record in stripe for this org, we cannot continue.
         let stripe_customer_id = org
-            .stripe_customer_id
-            .as_ref()
+            .customer_id(keyring)
+            .map_err(Error::CustomerId)?
             .ok_or_else(|| Error::NoCustomer(org.id))?;
 
         let price = self.get_price(sku).await?;
         if let Some(subscription) = self
-            .get_subscription_by_customer(stripe_customer_id)
+            .get_subscription_by_customer(&stripe_customer_id)
             .await?
         {
             // If there is a subscription, we either need to increment the `quantity` of an existing
@@ -153,7 +176,7 @@ pub trait Subscription: Payment {
             // There wasn't a subscription, so we create it and add the `item` for this node to it
             // straight away.
             let item = self
-                .create_subscription(stripe_customer_id, &price.id)
+                .create_subscription(&stripe_customer_id, &price.id)
                 .await?
                 .items
                 .data
@@ -163,6 +186,31 @@ pub trait Subscription: Payment {
         }
     }
 
+    /// Find the item of the org subscription with the metered price for `sku`.
+    ///
+    /// Metered items are set up by billing, so this returns `None` when the
+    /// org has no subscription or it doesn't include the metered price.
+    async fn find_metered_item(
+        &self,
+        org: &Org,
+        keyring: &Keyring,
+        sku: &str,
+    ) -> Result<Option<SubscriptionItem>, Error> {
+        let Some(stripe_customer_id) = org.customer_id(keyring).map_err(Error::CustomerId)? else {
+            return Ok(None);
+        };
+        let Some(subscription) = self
+            .get_subscription_by_customer(&stripe_customer_id)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let price = self.get_price(sku).await?;
+        self.find_subscription_item(&subscription.id, &price.id)
+            .await
+    }
+
     async fn remove_subscription(&self, item_id: &SubscriptionItemId) -> Result<(), Error> {
         let item = self.get_subscription_item(item_id).await?;
         if item.quantity > 1 {
@@ -200,18 +248,24 @@ pub enum Error {
     CreateClient(client::Error),
     /// Failed to create stripe customer: {0}
     CreateCustomer(client::Error),
+    /// Failed to open the stripe customer id: {0}
+    CustomerId(crate::model::org::Error),
     /// Failed to create stripe setup intent: {0}
     CreateSetupIntent(client::Error),
     /// Failed to create stripe subscription: {0}
     CreateSubscription(client::Error),
     /// Failed to create stripe subscription item: {0}
     CreateSubscriptionItem(client::Error),
+    /// Failed to create stripe usage record: {0}
+    CreateUsageRecord(client::Error),
     /// Failed to delete address: {0}
     DeleteAddress(client::Error),
     /// Failed to delete stripe subscription item: {0}
     DeleteSubscriptionItem(client::Error),
     /// Failed to find subscription items: {0}
     FindSubscriptionItems(client::Error),
+    /// Failed to get account: {0}
+    GetAccount(client::Error),
     /// Failed to get address: {0}
     GetAddress(client::Error),
     /// Failed to get invoices: {0}
@@ -242,6 +296,8 @@ pub enum Error {
     SearchPrices(client::Error),
     /// Failed to set address: {0}
     SetAddress(client::Error),
+    /// Failed to set customer email: {0}
+    SetEmail(client::Error),
     /// Failed to update subscription item: {0}
     UpdateSubscriptionItem(client::Error),
 }
@@ -261,8 +317,12 @@ impl Stripe {
     }
 
     #[cfg(any(test, feature = "integration-test"))]
-    pub fn new_mock(config: Arc<Config>, server_url: url::Url) -> Result<Self, Error> {
-        let client = Client::new_mock(server_url).map_err(Error::CreateClient)?;
+    pub fn new_mock(
+        config: Arc<Config>,
+        server_url: url::Url,
+        faults: Arc<crate::chaos::Faults>,
+    ) -> Result<Self, Error> {
+        let client = Client::new_mock(server_url, faults).map_err(Error::CreateClient)?;
         Ok(Self { config, client })
     }
 }
@@ -453,6 +513,20 @@ impl Payment for Stripe {
         Ok(())
     }
 
+    async fn create_usage_record(
+        &self,
+        item_id: &subscription::SubscriptionItemId,
+        quantity: u64,
+        timestamp: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), Error> {
+        let req = subscription::CreateUsageRecord::new(item_id, quantity, timestamp);
+        self.client
+            .request(&req)
+            .await
+            .map_err(Error::CreateUsageRecord)?;
+        Ok(())
+    }
+
     async fn get_price(&self, sku: &str) -> Result<price::Price, Error> {
         let req = price::SearchPrice::new(sku);
         let mut prices = self
@@ -517,6 +591,12 @@ impl Payment for Stripe {
         Ok(())
     }
 
+    async fn set_email(&self, customer_id: &str, email: &str) -> Result<(), Error> {
+        let req = customer::UpdateCustomerEmail::new(customer_id, email);
+        self.client.request(&req).await.map_err(Error::SetEmail)?;
+        Ok(())
+    }
+
     async fn get_invoices(&self, customer_id: &str) -> Result<Vec<invoice::Invoice>, Error> {
         let req = invoice::ListInvoices::new(customer_id, true);
         let resp = self
@@ -526,12 +606,19 @@ impl Payment for Stripe {
             .map_err(Error::GetInvoices)?;
         Ok(resp.data)
     }
+
+    async fn get_account(&self) -> Result<account::Account, Error> {
+        let req = account::GetAccount {};
+        self.client.request(&req).await.map_err(Error::GetAccount)
+    }
 }
 
 #[cfg(any(test, feature = "integration-test"))]
 pub mod tests {
     use mockito::{Matcher, ServerGuard};
 
+    use crate::chaos::Faults;
+
     use super::*;
 
     pub struct MockStripe {
@@ -654,6 +741,17 @@ pub mod tests {
             self.stripe.delete_subscription_item(item_id).await
         }
 
+        async fn create_usage_record(
+            &self,
+            item_id: &subscription::SubscriptionItemId,
+            quantity: u64,
+            timestamp: chrono::DateTime<chrono::Utc>,
+        ) -> Result<(), Error> {
+            self.stripe
+                .create_usage_record(item_id, quantity, timestamp)
+                .await
+        }
+
         async fn get_price(&self, sku: &str) -> Result<price::Price, Error> {
             self.stripe.get_price(sku).await
         }
@@ -677,17 +775,25 @@ pub mod tests {
             self.stripe.delete_address(customer_id).await
         }
 
+        async fn set_email(&self, customer_id: &str, email: &str) -> Result<(), Error> {
+            self.stripe.set_email(customer_id, email).await
+        }
+
         async fn get_invoices(&self, customer_id: &str) -> Result<Vec<invoice::Invoice>, Error> {
             self.stripe.get_invoices(customer_id).await
         }
+
+        async fn get_account(&self) -> Result<account::Account, Error> {
+            self.stripe.get_account().await
+        }
     }
 
     impl MockStripe {
-        pub async fn new() -> Self {
+        pub async fn new(faults: Arc<Faults>) -> Self {
             let server = mock_server().await;
             let server_url = format!("{}/v1/", server.url()).parse().unwrap();
             let config = Arc::new(mock_config(&server));
-            let stripe = Stripe::new_mock(config, server_url).unwrap();
+            let stripe = Stripe::new_mock(config, server_url, faults).unwrap();
 
             Self { server, stripe }
         }
@@ -710,6 +816,13 @@ pub mod tests {
             .create_async()
             .await;
 
+        server
+            .mock("POST", Matcher::Regex("^/v1/customers/".into()))
+            .with_status(200)
+            .with_body(mock_customer())
+            .create_async()
+            .await;
+
         server
             .mock("POST", "/v1/payment_methods/moneyfaucet/attach")
             .with_status(201)
@@ -779,6 +892,13 @@ pub mod tests {
             .create_async()
             .await;
 
+        server
+            .mock("GET", "/v1/account")
+            .with_status(200)
+            .with_body(mock_account())
+            .create_async()
+            .await;
+
         server
     }
 
@@ -786,9 +906,27 @@ pub mod tests {
         Config {
             secret: Some("stripe_fake_secret".to_owned().into()),
             base_url: format!("{}/v1/", server.url()),
+            disk_sku: None,
+            webhook_secret: None,
         }
     }
 
+    const fn mock_account() -> &'static str {
+        r#"{
+          "id": "acct_1Nv0FGQ9RKHgCVdK",
+          "object": "account",
+          "charges_enabled": true,
+          "country": "US",
+          "created": 1695830751,
+          "default_currency": "usd",
+          "details_submitted": true,
+          "email": "billing@blockjoy.com",
+          "metadata": {},
+          "payouts_enabled": true,
+          "type": "standard"
+        }"#
+    }
+
     const fn mock_setup_intent() -> &'static str {
         r#"{
           "id": "seti_1PIt1LB5ce1jJsfThXFVl6TA",
@@ -0,0 +1,121 @@
+//! Verification of the `Stripe-Signature` header of incoming webhook events.
+//!
+//! Stripe signs `{timestamp}.{body}` with the signing secret of the endpoint
+//! and sends the header as `t={timestamp},v1={signature}`, where the signature
+//! is the hex encoded HMAC-SHA256. Events signed more than `TOLERANCE` away
+//! from now are rejected so that a captured request can't be replayed.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use displaydoc::Display;
+use ring::hmac;
+use thiserror::Error;
+
+/// The header containing the signature of a webhook event.
+pub const SIGNATURE_HEADER: &str = "Stripe-Signature";
+
+/// How far the signed timestamp may be from now.
+const TOLERANCE: TimeDelta = TimeDelta::minutes(5);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Stripe-Signature timestamp `{0}` is outside the tolerance.
+    Expired(i64),
+    /// Stripe-Signature header is malformed.
+    Malformed,
+    /// Stripe-Signature does not match the event.
+    Mismatch,
+    /// Stripe-Signature header is missing.
+    Missing,
+}
+
+/// Verify that `body` was signed by stripe with `secret` at most `TOLERANCE`
+/// away from `now`.
+pub fn verify(
+    secret: &str,
+    header: Option<&str>,
+    body: &str,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let header = header.ok_or(Error::Missing)?;
+
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for entry in header.split(',') {
+        match entry.trim().split_once('=') {
+            Some(("t", value)) => timestamp = Some(value.parse().map_err(|_| Error::Malformed)?),
+            Some(("v1", value)) => signatures.push(decode_hex(value).ok_or(Error::Malformed)?),
+            // other schemes such as `v0` are ignored
+            Some(_) => (),
+            None => return Err(Error::Malformed),
+        }
+    }
+    let timestamp: i64 = timestamp.ok_or(Error::Malformed)?;
+    if signatures.is_empty() {
+        return Err(Error::Malformed);
+    }
+
+    let signed_at = DateTime::from_timestamp(timestamp, 0).ok_or(Error::Malformed)?;
+    if (now - signed_at).abs() > TOLERANCE {
+        return Err(Error::Expired(timestamp));
+    }
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let payload = format!("{timestamp}.{body}");
+    signatures
+        .iter()
+        .any(|signature| hmac::verify(&key, payload.as_bytes(), signature).is_ok())
+        .then_some(())
+        .ok_or(Error::Mismatch)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(any(test, feature = "integration-test"))]
+pub mod tests {
+    use super::*;
+
+    /// The `Stripe-Signature` header of `body` signed with `secret` at `now`.
+    pub fn sign(secret: &str, body: &str, now: DateTime<Utc>) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let timestamp = now.timestamp();
+        let tag = hmac::sign(&key, format!("{timestamp}.{body}").as_bytes());
+        let signature: String = tag
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        format!("t={timestamp},v1={signature}")
+    }
+
+    #[test]
+    fn verify_signed_events() {
+        let now = Utc::now();
+        let body = r#"{"type":"invoice.payment_failed"}"#;
+        let header = sign("whsec_test", body, now);
+
+        verify("whsec_test", Some(&header), body, now).unwrap();
+        let later = now + TimeDelta::minutes(1);
+        verify("whsec_test", Some(&header), body, later).unwrap();
+
+        let other = verify("whsec_other", Some(&header), body, now);
+        assert!(matches!(other, Err(Error::Mismatch)));
+        let tampered = verify("whsec_test", Some(&header), "{}", now);
+        assert!(matches!(tampered, Err(Error::Mismatch)));
+        let replayed = verify("whsec_test", Some(&header), body, now + TimeDelta::hours(1));
+        assert!(matches!(replayed, Err(Error::Expired(_))));
+        let missing = verify("whsec_test", None, body, now);
+        assert!(matches!(missing, Err(Error::Missing)));
+        let malformed = verify("whsec_test", Some("v1=zz"), body, now);
+        assert!(matches!(malformed, Err(Error::Malformed)));
+    }
+}
@@ -0,0 +1,172 @@
+//! Jira issues, created through the v2 REST API.
+//!
+//! Jira workflows name their statuses freely, so statuses are matched by their
+//! category instead: `new`, `indeterminate` or `done`. Status changes arrive
+//! from a Jira `jira:issue_updated` webhook.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::incident::{Incident, IncidentStatus};
+use crate::model::ticket::TicketIntegration;
+
+use super::{Client, Error, ExternalTicket, description, send};
+
+const ISSUE_TYPE: &str = "Task";
+
+#[derive(Serialize)]
+struct IssueRequest<'a> {
+    fields: IssueFields<'a>,
+}
+
+#[derive(Serialize)]
+struct IssueFields<'a> {
+    project: Key<'a>,
+    summary: &'a str,
+    description: String,
+    issuetype: Name,
+}
+
+#[derive(Serialize)]
+struct Key<'a> {
+    key: &'a str,
+}
+
+#[derive(Serialize)]
+struct Name {
+    name: &'static str,
+}
+
+#[derive(Deserialize)]
+struct IssueResponse {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Deserialize)]
+struct Transition {
+    id: String,
+    to: TransitionTo,
+}
+
+#[derive(Deserialize)]
+struct TransitionTo {
+    #[serde(rename = "statusCategory")]
+    status_category: StatusCategory,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusCategory {
+    pub key: String,
+}
+
+#[derive(Serialize)]
+struct TransitionRequest {
+    transition: TransitionId,
+}
+
+#[derive(Serialize)]
+struct TransitionId {
+    id: String,
+}
+
+/// The incoming `jira:issue_updated` webhook body.
+#[derive(Debug, Deserialize)]
+pub struct WebhookEvent {
+    pub issue: WebhookIssue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookIssue {
+    pub key: String,
+    pub fields: WebhookFields,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookFields {
+    pub status: WebhookStatus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookStatus {
+    #[serde(rename = "statusCategory")]
+    pub status_category: StatusCategory,
+}
+
+pub(super) async fn create(
+    client: &Client,
+    integration: &TicketIntegration,
+    incident: &Incident,
+) -> Result<ExternalTicket, Error> {
+    let body = IssueRequest {
+        fields: IssueFields {
+            project: Key {
+                key: integration.project_key.as_deref().unwrap_or_default(),
+            },
+            summary: &incident.title,
+            description: description(incident),
+            issuetype: Name { name: ISSUE_TYPE },
+        },
+    };
+
+    let request = client.post(integration, "/rest/api/2/issue")?.json(&body);
+    let response: IssueResponse = send(request)
+        .await?
+        .json()
+        .await
+        .map_err(Error::ParseResponse)?;
+
+    let url = format!("{}/browse/{}", integration.base_url, response.key);
+    Ok(ExternalTicket {
+        external_id: response.key,
+        url,
+    })
+}
+
+pub(super) async fn update_status(
+    client: &Client,
+    integration: &TicketIntegration,
+    external_id: &str,
+    status: IncidentStatus,
+) -> Result<(), Error> {
+    let path = format!("/rest/api/2/issue/{external_id}/transitions");
+    let transitions: Transitions = send(client.get(integration, &path)?)
+        .await?
+        .json()
+        .await
+        .map_err(Error::ParseResponse)?;
+
+    let category = category_key(status);
+    let transition = transitions
+        .transitions
+        .into_iter()
+        .find(|transition| transition.to.status_category.key == category)
+        .ok_or(Error::NoTransition(status))?;
+
+    let body = TransitionRequest {
+        transition: TransitionId { id: transition.id },
+    };
+    let request = client.post(integration, &path)?.json(&body);
+    send(request).await.map(|_| ())
+}
+
+const fn category_key(status: IncidentStatus) -> &'static str {
+    match status {
+        IncidentStatus::Open => "new",
+        IncidentStatus::Acknowledged => "indeterminate",
+        IncidentStatus::Resolved => "done",
+    }
+}
+
+/// The incident status of a Jira status category.
+pub fn incident_status(category: &StatusCategory) -> Option<IncidentStatus> {
+    match category.key.as_str() {
+        "new" => Some(IncidentStatus::Open),
+        "indeterminate" => Some(IncidentStatus::Acknowledged),
+        "done" => Some(IncidentStatus::Resolved),
+        _ => None,
+    }
+}
@@ -0,0 +1,144 @@
+//! Clients for the Zendesk and Jira APIs used to mirror incidents as tickets.
+//!
+//! Credentials come from each org's `TicketIntegration`, so a single client is
+//! shared across orgs and every request is built against the integration.
+
+pub mod jira;
+pub mod zendesk;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use displaydoc::Display;
+use reqwest::{RequestBuilder, Response};
+use thiserror::Error;
+
+use crate::encryption::Keyring;
+use crate::model::incident::{Incident, IncidentStatus};
+use crate::model::ticket::{TicketIntegration, TicketProvider};
+use crate::util::trace;
+
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Display, Error)]
+pub enum Error {
+    /// Failed to open the ticketing api token: {0}
+    ApiToken(crate::encryption::Error),
+    /// Failed to build ticketing Client: {0}
+    BuildClient(reqwest::Error),
+    /// Jira has no transition to status `{0:?}`.
+    NoTransition(IncidentStatus),
+    /// Failed to parse ticketing response: {0}
+    ParseResponse(reqwest::Error),
+    /// Error code {0} from ticketing provider: {1}
+    ResponseError(reqwest::StatusCode, String),
+    /// Failed to send ticketing request: {0}
+    SendRequest(reqwest::Error),
+}
+
+/// A ticket created with the provider.
+pub struct ExternalTicket {
+    pub external_id: String,
+    pub url: String,
+}
+
+pub struct Client {
+    inner: reqwest::Client,
+    keyring: Arc<Keyring>,
+}
+
+impl Client {
+    pub fn new(keyring: Arc<Keyring>) -> Result<Self, Error> {
+        let inner = reqwest::Client::builder()
+            .timeout(CLIENT_TIMEOUT)
+            .build()
+            .map_err(Error::BuildClient)?;
+
+        Ok(Client { inner, keyring })
+    }
+
+    /// Open a ticket for a new incident.
+    pub async fn create(
+        &self,
+        integration: &TicketIntegration,
+        incident: &Incident,
+    ) -> Result<ExternalTicket, Error> {
+        match integration.provider {
+            TicketProvider::Zendesk => zendesk::create(self, integration, incident).await,
+            TicketProvider::Jira => jira::create(self, integration, incident).await,
+        }
+    }
+
+    /// Move a ticket to the provider status matching the incident `status`.
+    pub async fn update_status(
+        &self,
+        integration: &TicketIntegration,
+        external_id: &str,
+        status: IncidentStatus,
+    ) -> Result<(), Error> {
+        match integration.provider {
+            TicketProvider::Zendesk => {
+                zendesk::update_status(self, integration, external_id, status).await
+            }
+            TicketProvider::Jira => {
+                jira::update_status(self, integration, external_id, status).await
+            }
+        }
+    }
+
+    fn get(&self, integration: &TicketIntegration, path: &str) -> Result<RequestBuilder, Error> {
+        self.request(integration, reqwest::Method::GET, path)
+    }
+
+    fn post(&self, integration: &TicketIntegration, path: &str) -> Result<RequestBuilder, Error> {
+        self.request(integration, reqwest::Method::POST, path)
+    }
+
+    fn put(&self, integration: &TicketIntegration, path: &str) -> Result<RequestBuilder, Error> {
+        self.request(integration, reqwest::Method::PUT, path)
+    }
+
+    fn request(
+        &self,
+        integration: &TicketIntegration,
+        method: reqwest::Method,
+        path: &str,
+    ) -> Result<RequestBuilder, Error> {
+        let url = format!("{}{path}", integration.base_url);
+        // zendesk authenticates api tokens as `{email}/token`
+        let username = match integration.provider {
+            TicketProvider::Zendesk => format!("{}/token", integration.username),
+            TicketProvider::Jira => integration.username.clone(),
+        };
+
+        let api_token = integration
+            .api_token
+            .open(&self.keyring)
+            .map_err(Error::ApiToken)?;
+
+        let request = self
+            .inner
+            .request(method, url)
+            .basic_auth(username, Some(api_token));
+        Ok(trace::inject_headers(request))
+    }
+}
+
+async fn send(request: RequestBuilder) -> Result<Response, Error> {
+    let response = request.send().await.map_err(Error::SendRequest)?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        let body = response.text().await.unwrap_or_default();
+        Err(Error::ResponseError(status, body))
+    }
+}
+
+/// The ticket body describing an incident.
+fn description(incident: &Incident) -> String {
+    format!(
+        "{}\n\nIncident: {}\nNode: {}",
+        incident.title, incident.id, incident.node_id
+    )
+}
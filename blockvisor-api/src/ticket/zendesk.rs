@@ -0,0 +1,120 @@
+//! Zendesk tickets, created through the v2 tickets API.
+//!
+//! Status changes arrive from a Zendesk trigger calling the webhook with a
+//! `{"ticket_id": "{{ticket.id}}", "status": "{{ticket.status}}"}` body.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::incident::{Incident, IncidentStatus};
+use crate::model::ticket::TicketIntegration;
+
+use super::{Client, Error, ExternalTicket, description, send};
+
+#[derive(Serialize)]
+struct TicketRequest<'a> {
+    ticket: TicketBody<'a>,
+}
+
+#[derive(Serialize)]
+struct TicketBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subject: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<Comment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Comment {
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct TicketResponse {
+    ticket: Ticket,
+}
+
+#[derive(Deserialize)]
+struct Ticket {
+    id: u64,
+}
+
+/// The incoming webhook body sent by a Zendesk trigger.
+#[derive(Debug, Deserialize)]
+pub struct WebhookEvent {
+    pub ticket_id: String,
+    pub status: String,
+}
+
+pub(super) async fn create(
+    client: &Client,
+    integration: &TicketIntegration,
+    incident: &Incident,
+) -> Result<ExternalTicket, Error> {
+    let body = TicketRequest {
+        ticket: TicketBody {
+            subject: Some(&incident.title),
+            comment: Some(Comment {
+                body: description(incident),
+            }),
+            external_id: Some(incident.id.to_string()),
+            status: Some(status_name(incident.status)),
+        },
+    };
+
+    let request = client
+        .post(integration, "/api/v2/tickets.json")?
+        .json(&body);
+    let response: TicketResponse = send(request)
+        .await?
+        .json()
+        .await
+        .map_err(Error::ParseResponse)?;
+
+    let id = response.ticket.id;
+    Ok(ExternalTicket {
+        external_id: id.to_string(),
+        url: format!("{}/agent/tickets/{id}", integration.base_url),
+    })
+}
+
+pub(super) async fn update_status(
+    client: &Client,
+    integration: &TicketIntegration,
+    external_id: &str,
+    status: IncidentStatus,
+) -> Result<(), Error> {
+    let body = TicketRequest {
+        ticket: TicketBody {
+            subject: None,
+            comment: None,
+            external_id: None,
+            status: Some(status_name(status)),
+        },
+    };
+
+    let path = format!("/api/v2/tickets/{external_id}.json");
+    let request = client.put(integration, &path)?.json(&body);
+    send(request).await.map(|_| ())
+}
+
+const fn status_name(status: IncidentStatus) -> &'static str {
+    match status {
+        IncidentStatus::Open => "new",
+        IncidentStatus::Acknowledged => "open",
+        IncidentStatus::Resolved => "solved",
+    }
+}
+
+/// The incident status of a Zendesk ticket status.
+pub fn incident_status(status: &str) -> Option<IncidentStatus> {
+    match status.to_lowercase().as_str() {
+        "new" => Some(IncidentStatus::Open),
+        "open" | "pending" | "hold" => Some(IncidentStatus::Acknowledged),
+        "solved" | "closed" => Some(IncidentStatus::Resolved),
+        _ => None,
+    }
+}
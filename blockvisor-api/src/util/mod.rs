@@ -1,9 +1,13 @@
 pub mod search;
 pub use search::{SearchOperator, SortOrder};
 
+pub mod secrets;
+
 pub mod timestamp;
 pub use timestamp::{NanosUtc, SecondsUtc};
 
+pub mod trace;
+
 use std::collections::HashMap;
 use std::hash::Hash;
 
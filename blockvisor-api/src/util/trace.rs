@@ -0,0 +1,74 @@
+//! W3C trace context propagation, so a trace can be followed from a host
+//! request through the API and out over MQTT or outbound webhooks.
+
+use http::HeaderMap;
+use opentelemetry::global;
+use opentelemetry::propagation::Injector;
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use reqwest::RequestBuilder;
+
+/// The current context, continuing the trace sent by the caller of an inbound
+/// request (if any).
+pub fn extract(headers: &HeaderMap) -> opentelemetry::Context {
+    let current = opentelemetry::Context::current();
+    global::get_text_map_propagator(|propagator| {
+        propagator.extract_with_context(&current, &HeaderExtractor(headers))
+    })
+}
+
+/// Add the current trace context to the headers of an outbound request.
+pub fn inject_headers(request: RequestBuilder) -> RequestBuilder {
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject(&mut HeaderInjector(&mut headers));
+    });
+    request.headers(headers)
+}
+
+/// The current trace context as MQTT v5 user properties.
+pub fn user_properties() -> Vec<(String, String)> {
+    let mut properties = UserProperties::default();
+    global::get_text_map_propagator(|propagator| propagator.inject(&mut properties));
+    properties.0
+}
+
+#[derive(Default)]
+struct UserProperties(Vec<(String, String)>);
+
+impl Injector for UserProperties {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.push((key.to_string(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+    use super::*;
+
+    #[test]
+    fn trace_context_is_sent_as_user_properties() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let span = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let _guard = opentelemetry::Context::new()
+            .with_remote_span_context(span)
+            .attach();
+
+        let traceparent = (
+            "traceparent".to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+        );
+        assert!(user_properties().contains(&traceparent));
+    }
+}
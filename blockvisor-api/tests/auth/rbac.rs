@@ -1,9 +1,15 @@
 use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use blockvisor_api::auth::rbac::OrgRole;
+use blockvisor_api::auth::rbac::{OrgPerm, OrgRole, Perm};
 use blockvisor_api::auth::resource::OrgId;
+use blockvisor_api::auth::token::RequestToken;
+use blockvisor_api::database::Conn;
 use blockvisor_api::grpc::api;
 use blockvisor_api::model::rbac::RbacUser;
+use diesel::connection::InstrumentationEvent;
+use diesel_async::AsyncConnection;
 
 use crate::setup::TestServer;
 use crate::setup::helper::traits::{OrgService, SocketRpc};
@@ -17,6 +23,7 @@ async fn org_owner_can_delete_org() {
     // org admin cannot delete org
     let req = api::OrgServiceDeleteRequest {
         org_id: org_id.to_string(),
+        force: false,
     };
     let status = test.send_admin(OrgService::delete, req).await.unwrap_err();
     assert_eq!(status.code(), tonic::Code::PermissionDenied);
@@ -26,8 +33,17 @@ async fn org_owner_can_delete_org() {
         .await
         .unwrap();
 
+    // the seeded org has a running node that must first be stopped
     let req = api::OrgServiceDeleteRequest {
         org_id: org_id.to_string(),
+        force: false,
+    };
+    let status = test.send_admin(OrgService::delete, req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let req = api::OrgServiceDeleteRequest {
+        org_id: org_id.to_string(),
+        force: true,
     };
     let resp = test.send_admin(OrgService::delete, req).await;
     assert!(resp.is_ok());
@@ -68,3 +84,58 @@ async fn blockjoy_admin_can_list_all_orgs() {
     let resp = test.send_super(OrgService::list, req).await.unwrap();
     assert!(org_ids(resp).contains(&org_id));
 }
+
+#[tokio::test]
+async fn cached_roles_reduce_queries_per_request() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let org_id = test.seed().org.id;
+    let token: RequestToken = test.admin_jwt().await.parse().unwrap();
+
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+    conn.set_instrumentation(move |event: InstrumentationEvent<'_>| {
+        if let InstrumentationEvent::StartQuery { .. } = event {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    let count = || queries.load(Ordering::Relaxed);
+
+    // the first request reads the roles and perms of the user
+    let before = count();
+    assert!(authorize(&test, &token, OrgPerm::Get, &mut conn).await);
+    let uncached = count() - before;
+
+    // later requests are answered from the cache
+    let before = count();
+    assert!(authorize(&test, &token, OrgPerm::Get, &mut conn).await);
+    let cached = count() - before;
+    assert!(
+        cached < uncached,
+        "expected fewer than {uncached} queries but was {cached}"
+    );
+
+    // an org admin may not delete the org
+    assert!(!authorize(&test, &token, OrgPerm::Delete, &mut conn).await);
+
+    // linking a role evicts the cached roles of the user
+    RbacUser::link_role(test.seed().admin.id, org_id, OrgRole::Owner, &mut conn)
+        .await
+        .unwrap();
+    assert!(authorize(&test, &token, OrgPerm::Delete, &mut conn).await);
+}
+
+async fn authorize(
+    test: &TestServer,
+    token: &RequestToken,
+    perm: OrgPerm,
+    conn: &mut Conn<'_>,
+) -> bool {
+    let perms = Perm::from(perm).into();
+    let org_id = test.seed().org.id;
+    test.context()
+        .auth
+        .authorize_token(token, perms, org_id.into(), conn)
+        .await
+        .is_ok()
+}
@@ -0,0 +1,93 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::Node;
+use blockvisor_api::model::node::abuse::NewAbuseReport;
+use blockvisor_api::model::node::{AbuseReason, NodeState, UpdateNodeState};
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{AbuseService, NodeService, SocketRpc};
+
+#[tokio::test]
+async fn dismissing_a_report_lifts_the_suspension() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+
+    let mut conn = test.conn().await;
+    let suspend = UpdateNodeState {
+        node_state: Some(NodeState::Suspended),
+        next_state: Some(None),
+        protocol_state: None,
+        protocol_health: None,
+        p2p_address: None,
+    };
+    suspend.force(node.id, &mut conn).await.unwrap();
+    let report = NewAbuseReport {
+        node_id: node.id,
+        org_id: node.org_id,
+        reason: AbuseReason::MiningPort,
+        details: "outbound connection to port 3333".to_string(),
+        suspended: true,
+    };
+    report.create(&mut conn).await.unwrap();
+
+    let req = api::NodeServiceStartRequest {
+        node_id: node.id.to_string(),
+    };
+    let status = test
+        .send_admin(NodeService::start, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let list = api::AbuseServiceListRequest {
+        include_reviewed: false,
+    };
+    let status = test
+        .send_admin(AbuseService::list, list.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let reports = test
+        .send_super(AbuseService::list, list.clone())
+        .await
+        .unwrap()
+        .reports;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].node_id, node.id.to_string());
+    assert!(reports[0].suspended);
+
+    let review = api::AbuseServiceReviewRequest {
+        report_id: reports[0].report_id.clone(),
+        resolution: api::AbuseResolution::Dismissed.into(),
+    };
+    let status = test
+        .send_admin(AbuseService::review, review.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let report = test
+        .send_super(AbuseService::review, review.clone())
+        .await
+        .unwrap()
+        .report
+        .unwrap();
+    assert!(report.reviewed_at.is_some());
+
+    let node = Node::by_id(node.id, &mut conn).await.unwrap();
+    assert_eq!(node.node_state, NodeState::Stopped);
+
+    let status = test
+        .send_super(AbuseService::review, review)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let reports = test
+        .send_super(AbuseService::list, list)
+        .await
+        .unwrap()
+        .reports;
+    assert!(reports.is_empty());
+}
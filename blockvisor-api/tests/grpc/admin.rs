@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use blockvisor_api::grpc::api;
+use blockvisor_api::grpc::middleware::payload_audit::PAYLOAD_AUDIT_FLAG;
+use blockvisor_api::model::AuditLog;
+use blockvisor_api::model::audit::AuditEvent;
+use blockvisor_api::model::feature_flag::UpsertFeatureFlag;
+use tonic::{Code, IntoRequest};
+use uuid::Uuid;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{AdminService, NodeService, OrgService, SocketRpc};
+
+const REQUEST_ID: &str = "support-lookup-test";
+
+#[tokio::test]
+async fn failed_requests_can_be_looked_up_by_id() {
+    let test = TestServer::new().await;
+
+    let jwt = test.admin_jwt().await;
+    let mut req = api::NodeServiceGetRequest {
+        node_id: Uuid::new_v4().to_string(),
+    }
+    .into_request();
+    let auth = format!("Bearer {}", &*jwt).parse().unwrap();
+    req.metadata_mut().insert("authorization", auth);
+    req.metadata_mut()
+        .insert("x-request-id", REQUEST_ID.parse().unwrap());
+
+    let status = test.send_request(NodeService::get, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    assert_eq!(status.metadata().get("x-request-id").unwrap(), REQUEST_ID);
+
+    let lookup = api::AdminServiceLookupRequestRequest {
+        request_id: REQUEST_ID.to_string(),
+    };
+    let status = test
+        .send_member(AdminService::lookup_request, lookup.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // the outcome is recorded in the background
+    let mut outcomes = vec![];
+    for _ in 0..20 {
+        outcomes = test
+            .send_super(AdminService::lookup_request, lookup.clone())
+            .await
+            .unwrap()
+            .outcomes;
+        if !outcomes.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(outcomes.len(), 1);
+    assert_eq!(outcomes[0].method, "/blockjoy.v1.NodeService/Get");
+    assert_eq!(outcomes[0].grpc_code, Code::NotFound as i32);
+}
+
+#[tokio::test]
+async fn payloads_of_flagged_orgs_are_audited() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+
+    let mut conn = test.conn().await;
+    UpsertFeatureFlag {
+        name: PAYLOAD_AUDIT_FLAG.to_string(),
+        description: None,
+        enabled: true,
+        rollout_percent: 0,
+        org_ids: vec![Some(org_id)],
+        user_ids: vec![],
+    }
+    .upsert(&mut conn)
+    .await
+    .unwrap();
+    test.context().flags.invalidate().await;
+
+    let jwt = test.admin_jwt().await;
+    let mut req = api::OrgServiceGetRequest {
+        org_id: org_id.to_string(),
+    }
+    .into_request();
+    let auth = format!("Bearer {}", &*jwt).parse().unwrap();
+    req.metadata_mut().insert("authorization", auth);
+    req.metadata_mut()
+        .insert("x-request-id", "payload-audit-test".parse().unwrap());
+    test.send_request(OrgService::get, req).await.unwrap();
+
+    // the payloads are recorded in the background
+    let mut logs = vec![];
+    for _ in 0..20 {
+        logs = AuditLog::by_request_id("payload-audit-test", &mut conn)
+            .await
+            .unwrap();
+        if !logs.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].event, AuditEvent::RequestPayloadSampled);
+    assert_eq!(logs[0].org_id, Some(org_id));
+
+    let data = logs[0].event_data.as_ref().unwrap();
+    assert_eq!(data["method"], "blockjoy.v1.OrgService/Get");
+    assert_eq!(data["request"]["org_id"], org_id.to_string());
+    assert_eq!(data["response"]["org"]["org_id"], org_id.to_string());
+}
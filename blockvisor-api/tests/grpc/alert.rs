@@ -0,0 +1,119 @@
+use blockvisor_api::database::seed::ORG_ID;
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::alert::{AlertKind, NewAlert};
+use tonic::Code;
+use uuid::Uuid;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{AlertService, SocketRpc};
+
+#[tokio::test]
+async fn acknowledge_an_escalating_alert() {
+    let test = TestServer::new().await;
+    let member_id = test.seed().member.id.to_string();
+
+    let schedule = |user_ids: Vec<String>| api::AlertServiceCreateScheduleRequest {
+        org_id: ORG_ID.to_string(),
+        name: "Primary".to_string(),
+        user_ids,
+        rotation_hours: 24,
+        starts_at: None,
+    };
+
+    // org members can't manage schedules
+    let result = test
+        .send_member(
+            AlertService::create_schedule,
+            schedule(vec![member_id.clone()]),
+        )
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // schedules need users of the org
+    let result = test
+        .send_admin(AlertService::create_schedule, schedule(vec![]))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+    let outsider = Uuid::new_v4().to_string();
+    let result = test
+        .send_admin(AlertService::create_schedule, schedule(vec![outsider]))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    let resp = test
+        .send_admin(
+            AlertService::create_schedule,
+            schedule(vec![member_id.clone()]),
+        )
+        .await
+        .unwrap();
+    let schedule = resp.schedule.unwrap();
+    assert_eq!(
+        schedule.on_call_user_id.as_deref(),
+        Some(member_id.as_str())
+    );
+
+    let policy = api::AlertServiceSetPolicyRequest {
+        org_id: ORG_ID.to_string(),
+        schedule_ids: vec![schedule.schedule_id.clone()],
+        ack_timeout_minutes: 15,
+    };
+    test.send_admin(AlertService::set_policy, policy)
+        .await
+        .unwrap();
+
+    let req = api::AlertServiceGetOncallRequest {
+        org_id: ORG_ID.to_string(),
+    };
+    let resp = test
+        .send_member(AlertService::get_oncall, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.schedules.len(), 1);
+    assert_eq!(
+        resp.policy.unwrap().schedule_ids,
+        vec![schedule.schedule_id]
+    );
+
+    let mut conn = test.conn().await;
+    let alert = NewAlert {
+        org_id: ORG_ID.parse().unwrap(),
+        node_id: None,
+        kind: AlertKind::EndpointQuota,
+        message: "Endpoint reached 100% of its monthly quota.".to_string(),
+    };
+    let alert = alert.create(&mut conn).await.unwrap();
+
+    let list = api::AlertServiceListRequest {
+        org_id: ORG_ID.to_string(),
+        unacknowledged_only: true,
+    };
+    let resp = test
+        .send_member(AlertService::list, list.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.alerts.len(), 1);
+
+    // users outside the org can't acknowledge it
+    let ack = api::AlertServiceAcknowledgeRequest {
+        alert_id: alert.id.to_string(),
+    };
+    let result = test
+        .send_unknown(AlertService::acknowledge, ack.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let resp = test
+        .send_member(AlertService::acknowledge, ack.clone())
+        .await
+        .unwrap();
+    let acked = resp.alert.unwrap();
+    assert_eq!(acked.acknowledged_by.as_deref(), Some(member_id.as_str()));
+
+    // an alert is only acknowledged once
+    let result = test.send_member(AlertService::acknowledge, ack).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    let resp = test.send_member(AlertService::list, list).await.unwrap();
+    assert!(resp.alerts.is_empty());
+}
@@ -2,6 +2,9 @@ use blockvisor_api::auth::rbac::{ApiKeyPerm, NodePerm, OrgPerm, OrgRole, Perm, P
 use blockvisor_api::auth::resource::{OrgId, Resource};
 use blockvisor_api::grpc::api;
 use blockvisor_api::model::org::Org;
+use blockvisor_api::model::schema::api_keys;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
 use tonic::Status;
 
 use crate::setup::TestServer;
@@ -125,6 +128,38 @@ async fn user_can_get_org_with_api_key() {
     assert!(result.is_ok());
 }
 
+#[tokio::test]
+async fn api_key_salts_are_sealed() {
+    let mut test = TestServer::new().await;
+    let perms: &[Perm] = &[OrgPerm::Get.into()];
+
+    let user = rpc::new_seed_user(&mut test).await;
+    let key = rpc::new_api_key(&mut test, &user.jwt, user.user_id, perms).await;
+
+    // the salt is only stored sealed
+    let mut conn = test.conn().await;
+    let salts: Vec<String> = api_keys::table
+        .filter(api_keys::user_id.eq(user.user_id))
+        .select(api_keys::key_salt)
+        .get_results(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(salts.len(), 1);
+    assert!(salts[0].starts_with("enc:v1:"));
+
+    // and is opened with the keyring of the context to validate the key
+    let name = test.rand_string(8).await;
+    let req = api::OrgServiceCreateRequest { name };
+    let created = test
+        .send_with(OrgService::create, req, &user.jwt)
+        .await
+        .unwrap();
+    let req = api::OrgServiceGetRequest {
+        org_id: created.org.unwrap().org_id,
+    };
+    test.send_with(OrgService::get, req, &key).await.unwrap();
+}
+
 #[tokio::test]
 async fn api_key_permissions_are_restricted() {
     let test = TestServer::new().await;
@@ -0,0 +1,98 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::Node;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{ApprovalService, NodeService, OrgService, SocketRpc};
+
+#[tokio::test]
+async fn node_delete_waits_for_another_admin() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+    let node_id = test.seed().node.id;
+
+    let req = api::OrgServiceUpdateRequest {
+        org_id: org_id.clone(),
+        name: None,
+        agent_upgrade_policy: None,
+        disk_quota_bytes: None,
+        disk_auto_approve_bytes: None,
+        node_display_name_max_len: None,
+        node_note_max_len: None,
+        node_display_name_unique: None,
+        node_display_name_charset: None,
+        require_approval_node_delete: Some(true),
+        require_approval_node_upgrade: None,
+        require_approval_org_transfer: None,
+    };
+    test.send_admin(OrgService::update, req).await.unwrap();
+
+    let delete_req = || api::NodeServiceDeleteRequest {
+        node_id: node_id.to_string(),
+    };
+    let approval = test
+        .send_admin(NodeService::delete, delete_req())
+        .await
+        .unwrap()
+        .approval
+        .unwrap();
+    assert_eq!(approval.status(), api::ApprovalStatus::Pending);
+    assert_eq!(approval.action(), api::ApprovalAction::NodeDelete);
+
+    // the requester may not approve their own request
+    let review_req = |approval_id: &str| api::ApprovalServiceReviewRequest {
+        approval_id: approval_id.to_string(),
+        approve: true,
+    };
+    let status = test
+        .send_admin(ApprovalService::review, review_req(&approval.approval_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    // and members may not review at all
+    let status = test
+        .send_member(ApprovalService::review, review_req(&approval.approval_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let mut conn = test.conn().await;
+    Node::by_id(node_id, &mut conn).await.unwrap();
+
+    let req = api::ApprovalServiceListRequest {
+        org_id: org_id.clone(),
+        status: api::ApprovalStatus::Pending.into(),
+    };
+    let approvals = test
+        .send_member(ApprovalService::list, req)
+        .await
+        .unwrap()
+        .approvals;
+    assert_eq!(approvals.len(), 1);
+
+    // only the requester may cancel
+    let cancel_req = || api::ApprovalServiceCancelRequest {
+        approval_id: approval.approval_id.clone(),
+    };
+    let status = test
+        .send_member(ApprovalService::cancel, cancel_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let approval = test
+        .send_admin(ApprovalService::cancel, cancel_req())
+        .await
+        .unwrap()
+        .approval
+        .unwrap();
+    assert_eq!(approval.status(), api::ApprovalStatus::Cancelled);
+
+    // a cancelled approval is no longer pending
+    let status = test
+        .send_admin(ApprovalService::cancel, cancel_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
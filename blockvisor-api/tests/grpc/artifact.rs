@@ -0,0 +1,84 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::Node;
+use blockvisor_api::model::command::{CommandType, NewCommand};
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{ArtifactService, CommandService, SocketRpc};
+
+#[tokio::test]
+async fn uploaded_artifacts_can_be_downloaded() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let node = Node::by_id(test.seed().node.id, &mut conn).await.unwrap();
+    let cmd = NewCommand::node(&node, CommandType::NodeStop)
+        .unwrap()
+        .create(&mut conn)
+        .await
+        .unwrap();
+    let host_jwt = test.public_host_jwt();
+
+    let req = api::ArtifactServiceCreateUploadRequest {
+        command_id: cmd.id.to_string(),
+        name: "../etc/passwd".to_string(),
+    };
+    let status = test
+        .send_with(ArtifactService::create_upload, req, &host_jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = api::ArtifactServiceCreateUploadRequest {
+        command_id: cmd.id.to_string(),
+        name: "debug.tgz".to_string(),
+    };
+    let resp = test
+        .send_with(ArtifactService::create_upload, req, &host_jwt)
+        .await
+        .unwrap();
+    assert!(!resp.upload_url.is_empty());
+    let artifact_id = resp.artifact.unwrap().artifact_id;
+
+    // not downloadable until referenced by the command result
+    let req = api::ArtifactServiceDownloadRequest {
+        artifact_id: artifact_id.clone(),
+    };
+    let status = test
+        .send_member(ArtifactService::download, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let req = api::CommandServiceUpdateRequest {
+        command_id: cmd.id.to_string(),
+        exit_message: None,
+        exit_code: Some(api::CommandExitCode::Ok.into()),
+        retry_hint_seconds: None,
+        config_hash: None,
+        artifact_ids: vec![artifact_id.clone()],
+    };
+    test.send_with(CommandService::update, req, &host_jwt)
+        .await
+        .unwrap();
+
+    let req = api::ArtifactServiceListRequest {
+        command_id: Some(cmd.id.to_string()),
+        node_id: None,
+    };
+    let artifacts = test
+        .send_member(ArtifactService::list, req)
+        .await
+        .unwrap()
+        .artifacts;
+    assert_eq!(artifacts.len(), 1);
+    assert_eq!(artifacts[0].artifact_id, artifact_id);
+    assert_eq!(artifacts[0].name, "debug.tgz");
+
+    let req = api::ArtifactServiceDownloadRequest { artifact_id };
+    let resp = test
+        .send_member(ArtifactService::download, req)
+        .await
+        .unwrap();
+    assert!(!resp.url.is_empty());
+}
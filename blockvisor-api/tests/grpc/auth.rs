@@ -1,14 +1,17 @@
 use blockvisor_api::auth::claims::Claims;
 use blockvisor_api::auth::rbac::AuthPerm;
+use blockvisor_api::auth::resource::Resource;
 use blockvisor_api::auth::token::RequestToken;
 use blockvisor_api::auth::token::refresh::Refresh;
 use blockvisor_api::database::seed::LOGIN_PASSWORD;
 use blockvisor_api::grpc::api;
+use blockvisor_api::model::audit::{AuditEvent, AuditLog};
 use blockvisor_api::model::user::User;
 use tonic::Code;
 
 use crate::setup::TestServer;
-use crate::setup::helper::traits::{AuthService, SocketRpc};
+use crate::setup::helper::rpc;
+use crate::setup::helper::traits::{AuthService, SocketRpc, UserService};
 
 #[tokio::test]
 async fn login_with_username_and_password() {
@@ -89,7 +92,7 @@ async fn ok_with_valid_password_for_update_ui_password() {
     let req = api::AuthServiceUpdateUiPasswordRequest {
         user_id: test.seed().member.id.to_string(),
         old_password: LOGIN_PASSWORD.to_string(),
-        new_password: "hugo-boss".to_string(),
+        new_password: "tailored-wool-suit-jacket".to_string(),
     };
     test.send_member(AuthService::update_ui_password, req)
         .await
@@ -102,7 +105,7 @@ async fn denied_with_invalid_old_password_for_update_ui_password() {
     let req = api::AuthServiceUpdateUiPasswordRequest {
         user_id: test.seed().member.id.to_string(),
         old_password: "some-wrong-pwd".to_string(),
-        new_password: "hugo-boss".to_string(),
+        new_password: "tailored-wool-suit-jacket".to_string(),
     };
     let status = test
         .send_member(AuthService::update_ui_password, req)
@@ -111,6 +114,50 @@ async fn denied_with_invalid_old_password_for_update_ui_password() {
     assert_eq!(status.code(), Code::PermissionDenied);
 }
 
+#[tokio::test]
+async fn login_fails_until_required_password_reset() {
+    let test = TestServer::new().await;
+    let member = &test.seed().member;
+
+    let login_req = |password: &str| api::AuthServiceLoginRequest {
+        email: member.email.clone(),
+        password: password.into(),
+    };
+
+    // only admins may require a password reset
+    let req = api::UserServiceRequirePasswordResetRequest {
+        user_id: member.id.to_string(),
+    };
+    let status = test
+        .send_member(UserService::require_password_reset, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    test.send_super(UserService::require_password_reset, req)
+        .await
+        .unwrap();
+
+    let status = test
+        .send_unauthenticated(AuthService::login, login_req(LOGIN_PASSWORD))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let new_password = "tailored-wool-suit-jacket";
+    let req = api::AuthServiceUpdateUiPasswordRequest {
+        user_id: member.id.to_string(),
+        old_password: LOGIN_PASSWORD.to_string(),
+        new_password: new_password.to_string(),
+    };
+    test.send_member(AuthService::update_ui_password, req)
+        .await
+        .unwrap();
+
+    test.send_unauthenticated(AuthService::login, login_req(new_password))
+        .await
+        .unwrap();
+}
+
 #[tokio::test]
 async fn refresh_works() {
     let test = TestServer::new().await;
@@ -148,3 +195,197 @@ async fn refresh_works_from_cookie() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn revoked_session_cannot_refresh() {
+    let test = TestServer::new().await;
+    let member = &test.seed().member;
+
+    let req = api::AuthServiceLoginRequest {
+        email: member.email.clone(),
+        password: LOGIN_PASSWORD.into(),
+    };
+    let login = test
+        .send_unauthenticated(AuthService::login, req)
+        .await
+        .unwrap();
+
+    let req = api::AuthServiceListSessionsRequest {
+        user_id: member.id.to_string(),
+    };
+    let resp = test
+        .send_member(AuthService::list_sessions, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.sessions.len(), 1);
+
+    // another user cannot revoke the session
+    let req = api::AuthServiceRevokeSessionRequest {
+        session_id: resp.sessions[0].session_id.clone(),
+    };
+    let status = test
+        .send_admin(AuthService::revoke_session, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    test.send_member(AuthService::revoke_session, req)
+        .await
+        .unwrap();
+
+    let req = api::AuthServiceRefreshRequest {
+        token: login.token,
+        refresh: Some(login.refresh),
+    };
+    let status = test
+        .send_unauthenticated(AuthService::refresh, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn impersonation_requires_consent_and_is_audited() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let member = &test.seed().member;
+    let root = &test.seed().root;
+
+    let impersonate_req = || api::AuthServiceImpersonateRequest {
+        user_id: member.id.to_string(),
+        reason: "debugging a failed node".to_string(),
+    };
+
+    // only support admins may impersonate
+    let status = test
+        .send_admin(AuthService::impersonate, impersonate_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // fails until the user has granted support access
+    let status = test
+        .send_super(AuthService::impersonate, impersonate_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let req = api::UserServiceGrantSupportAccessRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::grant_support_access, req)
+        .await
+        .unwrap();
+
+    let resp = test
+        .send_super(AuthService::impersonate, impersonate_req())
+        .await
+        .unwrap();
+
+    let req = api::UserServiceGetRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_with(UserService::get, req, &resp.token)
+        .await
+        .unwrap();
+
+    let events: Vec<_> = AuditLog::by_creator(Resource::User(root.id), &mut conn)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|log| log.event)
+        .collect();
+    assert!(events.contains(&AuditEvent::UserImpersonated));
+    assert!(events.contains(&AuditEvent::ImpersonatedRequest));
+}
+
+#[tokio::test]
+async fn impersonation_cannot_mint_credentials() {
+    let test = TestServer::new().await;
+    let member = &test.seed().member;
+
+    let req = api::UserServiceGrantSupportAccessRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::grant_support_access, req)
+        .await
+        .unwrap();
+
+    let req = api::AuthServiceImpersonateRequest {
+        user_id: member.id.to_string(),
+        reason: "checking api key setup".to_string(),
+    };
+    let token = test
+        .send_super(AuthService::impersonate, req)
+        .await
+        .unwrap()
+        .token;
+
+    let perms = &[AuthPerm::ListPermissions];
+    let status = rpc::create_api_key(&test, &token, "support", Resource::User(member.id), perms)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::AuthServiceUpdateUiPasswordRequest {
+        user_id: member.id.to_string(),
+        old_password: LOGIN_PASSWORD.to_string(),
+        new_password: "tailored-wool-suit-jacket".to_string(),
+    };
+    let status = test
+        .send_with(AuthService::update_ui_password, req, &token)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::UserServiceDeleteAccountRequest {
+        user_id: member.id.to_string(),
+    };
+    let status = test
+        .send_with(UserService::delete_account, req, &token)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn revoking_support_access_stops_live_impersonation() {
+    let test = TestServer::new().await;
+    let member = &test.seed().member;
+
+    let req = api::UserServiceGrantSupportAccessRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::grant_support_access, req)
+        .await
+        .unwrap();
+
+    let req = api::AuthServiceImpersonateRequest {
+        user_id: member.id.to_string(),
+        reason: "debugging a failed node".to_string(),
+    };
+    let token = test
+        .send_super(AuthService::impersonate, req)
+        .await
+        .unwrap()
+        .token;
+
+    let get_req = || api::UserServiceGetRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_with(UserService::get, get_req(), &token)
+        .await
+        .unwrap();
+
+    let req = api::UserServiceRevokeSupportAccessRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::revoke_support_access, req)
+        .await
+        .unwrap();
+
+    let status = test
+        .send_with(UserService::get, get_req(), &token)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
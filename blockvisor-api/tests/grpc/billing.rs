@@ -0,0 +1,51 @@
+use blockvisor_api::database::seed::{IMAGE_ID, ORG_ID};
+use blockvisor_api::grpc::{api, common};
+use blockvisor_api::model::schema::regions;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{BillingService, SocketRpc};
+
+#[tokio::test]
+async fn estimate_prices_nodes_before_creation() {
+    let test = TestServer::new().await;
+    let region_id = test.seed().region.id;
+
+    let req = |node_count| api::BillingServiceEstimateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        region_id: region_id.to_string(),
+        node_count,
+        new_values: vec![],
+    };
+
+    // a region without pricing can't be estimated
+    let result = test.send_admin(BillingService::estimate, req(3)).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    let mut conn = test.conn().await;
+    diesel::update(regions::table.find(region_id))
+        .set(regions::sku_code.eq("USW1"))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    // org members can't estimate the cost of new nodes
+    let result = test.send_member(BillingService::estimate, req(3)).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // at least one node must be estimated
+    let result = test.send_admin(BillingService::estimate, req(0)).await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    let resp = test
+        .send_admin(BillingService::estimate, req(3))
+        .await
+        .unwrap();
+    let amount = |billing: Option<common::BillingAmount>| billing.unwrap().amount.unwrap();
+    assert_eq!(amount(resp.per_node).amount_minor_units, 1000);
+    assert_eq!(amount(resp.added_disk).amount_minor_units, 0);
+    assert_eq!(amount(resp.total).amount_minor_units, 3000);
+}
@@ -4,8 +4,12 @@ use blockvisor_api::auth::rbac::{CommandPerm, Perms, ProtocolPerm};
 use blockvisor_api::auth::resource::NodeId;
 use blockvisor_api::grpc::api;
 use blockvisor_api::model::Node;
-use blockvisor_api::model::command::{Command, CommandType, ExitCode, NewCommand};
-use blockvisor_api::model::node::UpdateNode;
+use blockvisor_api::model::command::{Command, CommandId, CommandType, ExitCode, NewCommand};
+use blockvisor_api::model::image::Config;
+use blockvisor_api::model::node::{UpdateNode, drift};
+use blockvisor_api::util::NanosUtc;
+use chrono::{TimeDelta, Utc};
+use tonic::Code;
 
 use crate::setup::TestServer;
 use crate::setup::helper::traits::{CommandService, SocketRpc};
@@ -32,6 +36,8 @@ async fn node_create_failed() {
         exit_message: Some("hugo boss".to_string()),
         exit_code: Some(api::CommandExitCode::ServiceBroken.into()),
         retry_hint_seconds: Some(10),
+        config_hash: None,
+        artifact_ids: vec![],
     };
 
     test.send_with(CommandService::update, req, &jwt)
@@ -83,3 +89,211 @@ async fn responds_ok_for_pending() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn list_returns_newest_commands_first() {
+    let test = TestServer::new().await;
+
+    let node_id = test.seed().node.id;
+    create_command(&test, node_id, CommandType::NodeStart).await;
+    let newest = create_command(&test, node_id, CommandType::NodeStop).await;
+
+    let perms = Perms::All(hashset! { CommandPerm::List.into() });
+    let jwt = test.org_jwt(perms);
+    let req = api::CommandServiceListRequest {
+        node_id: Some(node_id.to_string()),
+        host_id: None,
+        exit_code: None,
+        created_after: None,
+        limit: Some(1),
+    };
+    let resp = test
+        .send_with(CommandService::list, req, &jwt)
+        .await
+        .unwrap();
+    assert_eq!(resp.commands.len(), 1);
+    assert_eq!(resp.commands[0].command_id, newest.id.to_string());
+}
+
+#[tokio::test]
+async fn node_report_config_sets_drift() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let node = Node::by_id(test.seed().node.id, &mut conn).await.unwrap();
+    let config = Config::by_id(node.config_id, &mut conn).await.unwrap();
+
+    let perms = Perms::All(hashset! { CommandPerm::Update.into(), ProtocolPerm::ViewPublic.into()});
+    let jwt = test.org_jwt(perms);
+
+    for (hash, drifted) in [
+        ("stale".to_string(), true),
+        (drift::config_hash(&config), false),
+    ] {
+        let cmd = NewCommand::node(&node, CommandType::NodeReportConfig)
+            .unwrap()
+            .with_protobuf(&drift::payload(&node))
+            .create(&mut conn)
+            .await
+            .unwrap();
+
+        let req = api::CommandServiceUpdateRequest {
+            command_id: cmd.id.to_string(),
+            exit_message: None,
+            exit_code: Some(api::CommandExitCode::Ok.into()),
+            retry_hint_seconds: None,
+            config_hash: Some(hash),
+            artifact_ids: vec![],
+        };
+        test.send_with(CommandService::update, req, &jwt)
+            .await
+            .unwrap();
+
+        let node = Node::by_id(node.id, &mut conn).await.unwrap();
+        assert_eq!(node.config_drift, drifted);
+        assert!(node.config_reported_at.is_some());
+    }
+}
+
+#[tokio::test]
+async fn scheduled_commands_are_held_until_due() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let node = Node::by_id(test.seed().node.id, &mut conn).await.unwrap();
+    let execute_at = Utc::now() + TimeDelta::hours(1);
+    let cmd = NewCommand::node(&node, CommandType::NodeRestart)
+        .unwrap()
+        .with_execute_at(Some(execute_at))
+        .create(&mut conn)
+        .await
+        .unwrap();
+    assert!(cmd.is_held());
+
+    let pending = Command::host_pending(node.host_id, &mut conn)
+        .await
+        .unwrap();
+    assert!(pending.iter().all(|pending| pending.id != cmd.id));
+    assert!(!Command::has_node_start(node.id, &mut conn).await.unwrap());
+
+    let perms = Perms::All(hashset! {
+        CommandPerm::Cancel.into(),
+        CommandPerm::ListUpcoming.into(),
+        ProtocolPerm::ViewPublic.into(),
+    });
+    let jwt = test.org_jwt(perms);
+    let upcoming_req = || api::CommandServiceListUpcomingRequest {
+        node_id: Some(node.id.to_string()),
+        host_id: None,
+    };
+    let resp = test
+        .send_with(CommandService::list_upcoming, upcoming_req(), &jwt)
+        .await
+        .unwrap();
+    assert_eq!(resp.commands.len(), 1);
+    assert_eq!(resp.commands[0].command_id, cmd.id.to_string());
+    assert_eq!(
+        resp.commands[0].execute_at,
+        Some(NanosUtc::from(cmd.execute_at.unwrap()).into())
+    );
+
+    let cancel_req = || api::CommandServiceCancelRequest {
+        command_id: cmd.id.to_string(),
+    };
+    test.send_with(CommandService::cancel, cancel_req(), &jwt)
+        .await
+        .unwrap();
+    let status = test
+        .send_with(CommandService::cancel, cancel_req(), &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let resp = test
+        .send_with(CommandService::list_upcoming, upcoming_req(), &jwt)
+        .await
+        .unwrap();
+    assert!(resp.commands.is_empty());
+
+    // commands that are not scheduled can't be cancelled
+    let cmd = create_command(&test, node.id, CommandType::NodeStop).await;
+    let req = api::CommandServiceCancelRequest {
+        command_id: cmd.id.to_string(),
+    };
+    let status = test
+        .send_with(CommandService::cancel, req, &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn dependent_commands_follow_their_dependency() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let node = Node::by_id(test.seed().node.id, &mut conn).await.unwrap();
+    let stop = create_command(&test, node.id, CommandType::NodeStop).await;
+    let start = NewCommand::node(&node, CommandType::NodeStart)
+        .unwrap()
+        .after(&stop)
+        .create(&mut conn)
+        .await
+        .unwrap();
+    assert!(start.is_held());
+
+    let pending = Command::host_pending(node.host_id, &mut conn)
+        .await
+        .unwrap();
+    assert!(pending.iter().any(|pending| pending.id == stop.id));
+    assert!(pending.iter().all(|pending| pending.id != start.id));
+
+    let perms = Perms::All(hashset! { CommandPerm::Update.into(), ProtocolPerm::ViewPublic.into()});
+    let jwt = test.org_jwt(perms);
+    let update_req =
+        |id: CommandId, exit_code: api::CommandExitCode| api::CommandServiceUpdateRequest {
+            command_id: id.to_string(),
+            exit_message: None,
+            exit_code: Some(exit_code.into()),
+            retry_hint_seconds: None,
+            config_hash: None,
+            artifact_ids: vec![],
+        };
+
+    // the start is released once the stop succeeds
+    let req = update_req(stop.id, api::CommandExitCode::Ok);
+    test.send_with(CommandService::update, req, &jwt)
+        .await
+        .unwrap();
+    let start = Command::by_id(start.id, &mut conn).await.unwrap();
+    assert!(start.dispatched_at.is_some());
+    let pending = Command::host_pending(node.host_id, &mut conn)
+        .await
+        .unwrap();
+    assert!(pending.iter().any(|pending| pending.id == start.id));
+
+    // and a failed dependency fails the whole chain
+    let stop = create_command(&test, node.id, CommandType::NodeStop).await;
+    let restart = NewCommand::node(&node, CommandType::NodeRestart)
+        .unwrap()
+        .after(&stop)
+        .create(&mut conn)
+        .await
+        .unwrap();
+    let start = NewCommand::node(&node, CommandType::NodeStart)
+        .unwrap()
+        .after(&restart)
+        .create(&mut conn)
+        .await
+        .unwrap();
+
+    let req = update_req(stop.id, api::CommandExitCode::ServiceBroken);
+    test.send_with(CommandService::update, req, &jwt)
+        .await
+        .unwrap();
+    for id in [restart.id, start.id] {
+        let cmd = Command::by_id(id, &mut conn).await.unwrap();
+        assert_eq!(cmd.exit_code, Some(ExitCode::ServiceBroken));
+        assert!(cmd.completed_at.is_some());
+    }
+}
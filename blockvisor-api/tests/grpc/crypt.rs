@@ -220,5 +220,10 @@ pub fn create_node(
         new_values: vec![],
         add_rules: vec![],
         tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
     }
 }
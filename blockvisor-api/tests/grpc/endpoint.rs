@@ -0,0 +1,122 @@
+use blockvisor_api::database::seed::{NODE_ID, ORG_ID};
+use blockvisor_api::grpc::api;
+use blockvisor_api::util::NanosUtc;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{EndpointService, SocketRpc};
+
+#[tokio::test]
+async fn create_list_and_revoke_a_node_endpoint() {
+    let test = TestServer::new().await;
+
+    let req = api::EndpointServiceCreateRequest {
+        org_id: ORG_ID.to_string(),
+        node_id: Some(NODE_ID.to_string()),
+        label: "wallet backend".to_string(),
+        allowed_methods: vec!["eth_call".to_string(), "eth_blockNumber".to_string()],
+        rate_limit_rps: Some(25),
+        monthly_quota: None,
+    };
+
+    // org members can't hand out endpoints
+    let result = test.send_member(EndpointService::create, req.clone()).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // other orgs can't create endpoints for the node
+    let result = test
+        .send_unknown(EndpointService::create, req.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let resp = test.send_admin(EndpointService::create, req).await.unwrap();
+    assert!(resp.token.starts_with("rpc_"));
+    let endpoint = resp.endpoint.unwrap();
+    assert_eq!(endpoint.node_id.as_deref(), Some(NODE_ID));
+    assert_eq!(endpoint.allowed_methods.len(), 2);
+    assert_eq!(endpoint.rate_limit_rps, Some(25));
+    assert!(endpoint.url.ends_with(&endpoint.endpoint_id));
+
+    // org members can see the endpoints of their org
+    let list = || api::EndpointServiceListRequest {
+        org_id: ORG_ID.to_string(),
+        node_id: None,
+    };
+    let resp = test
+        .send_member(EndpointService::list, list())
+        .await
+        .unwrap();
+    assert_eq!(resp.endpoints.len(), 1);
+    assert_eq!(resp.endpoints[0].endpoint_id, endpoint.endpoint_id);
+
+    let req = api::EndpointServiceRevokeRequest {
+        endpoint_id: endpoint.endpoint_id.clone(),
+    };
+    let result = test.send_member(EndpointService::revoke, req.clone()).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+    test.send_admin(EndpointService::revoke, req.clone())
+        .await
+        .unwrap();
+
+    // revoked endpoints are no longer listed or revocable
+    let resp = test
+        .send_member(EndpointService::list, list())
+        .await
+        .unwrap();
+    assert!(resp.endpoints.is_empty());
+    let result = test.send_admin(EndpointService::revoke, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn report_and_query_endpoint_usage() {
+    let test = TestServer::new().await;
+
+    let req = api::EndpointServiceCreateRequest {
+        org_id: ORG_ID.to_string(),
+        node_id: None,
+        label: "indexer".to_string(),
+        allowed_methods: vec![],
+        rate_limit_rps: None,
+        monthly_quota: Some(1000),
+    };
+    let resp = test.send_admin(EndpointService::create, req).await.unwrap();
+    let endpoint_id = resp.endpoint.unwrap().endpoint_id;
+
+    let now = chrono::Utc::now();
+    let report = |request_count, latency_ms_max| api::EndpointUsageReport {
+        endpoint_id: endpoint_id.clone(),
+        timestamp: Some(NanosUtc::from(now).into()),
+        request_count,
+        error_count: 1,
+        latency_ms_total: request_count * 10,
+        latency_ms_max,
+    };
+    let req = api::EndpointServiceReportUsageRequest {
+        reports: vec![report(500, 40), report(400, 90)],
+    };
+
+    // only the gateway can report usage
+    let result = test
+        .send_admin(EndpointService::report_usage, req.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+    test.send_super(EndpointService::report_usage, req)
+        .await
+        .unwrap();
+
+    // reports within the same hour are added to a single rollup
+    let req = api::EndpointServiceUsageRequest {
+        endpoint_id: endpoint_id.clone(),
+        start: None,
+        end: Some(NanosUtc::from(now + chrono::TimeDelta::hours(1)).into()),
+    };
+    let resp = test.send_member(EndpointService::usage, req).await.unwrap();
+    assert_eq!(resp.usage.len(), 1);
+    assert_eq!(resp.usage[0].request_count, 900);
+    assert_eq!(resp.usage[0].error_count, 2);
+    assert_eq!(resp.usage[0].latency_ms_avg, 10);
+    assert_eq!(resp.usage[0].latency_ms_max, 90);
+    assert_eq!(resp.monthly_quota, Some(1000));
+    assert_eq!(resp.quota_used, 900);
+}
@@ -0,0 +1,99 @@
+use blockvisor_api::auth::resource::OrgId;
+use blockvisor_api::database::seed::fixture::{self, MAX_NODES_PER_HOST, OrgFixture};
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::{Host, IpAddress, Node, Org};
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{FixtureService, SocketRpc};
+
+#[tokio::test]
+async fn scenarios_are_seeded_and_reset_deterministically() {
+    let test = TestServer::new().await;
+
+    let seed_req = |scenario: &str| api::FixtureServiceSeedRequest {
+        scenario: scenario.to_string(),
+    };
+    let status = test
+        .send_unauthenticated(FixtureService::seed, seed_req("no-such-scenario"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let seeded = test
+        .send_unauthenticated(FixtureService::seed, seed_req("small-fleet"))
+        .await
+        .unwrap();
+    assert_eq!(seeded.org_ids.len(), 1);
+    assert_eq!(seeded.host_ids.len(), 3);
+    assert_eq!(seeded.node_ids.len(), 30);
+
+    let mut conn = test.conn().await;
+    let org_id: OrgId = seeded.org_ids[0].parse().unwrap();
+    let org = Org::by_id(org_id, &mut conn).await.unwrap();
+    assert_eq!(org.host_count, 3);
+    assert_eq!(org.node_count, 30);
+    let nodes = Node::by_org_id(org_id, &mut conn).await.unwrap();
+    assert_eq!(nodes.len(), 30);
+
+    // nodes are spread evenly across the hosts
+    let host_id = seeded.host_ids[0].parse().unwrap();
+    let host = Host::by_id(host_id, Some(org_id), &mut conn).await.unwrap();
+    assert_eq!(host.node_count, 10);
+
+    // a reset recreates the same rows
+    let req = api::FixtureServiceResetRequest {
+        scenario: "small-fleet".to_string(),
+    };
+    let reset = test
+        .send_unauthenticated(FixtureService::reset, req)
+        .await
+        .unwrap();
+    assert_eq!(reset.org_ids, seeded.org_ids);
+    assert_eq!(reset.host_ids, seeded.host_ids);
+    assert_eq!(reset.node_ids, seeded.node_ids);
+
+    let seeded = test
+        .send_unauthenticated(FixtureService::seed, seed_req("image-groups"))
+        .await
+        .unwrap();
+    assert_eq!(seeded.image_ids.len(), 1);
+}
+
+#[tokio::test]
+async fn org_fixtures_leave_spare_ips_for_new_nodes() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let seed = test.seed();
+
+    let spare = OrgFixture::new("spare-ips").hosts(2).nodes(3).spare_ips(4);
+    let created = spare
+        .create(&seed.region, &seed.protocol, &seed.image, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(created.nodes.len(), 3);
+
+    // each host has its share of the nodes plus the spare ips
+    let host_ids = created.hosts.iter().map(|host| host.id).collect();
+    let ips = IpAddress::for_hosts(&host_ids, &mut conn).await.unwrap();
+    assert_eq!(ips.len(), 2 * (2 + 4));
+    let assigned = IpAddress::assigned_for_hosts(&host_ids, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(assigned.len(), 3);
+
+    // so that more nodes can be placed after seeding
+    let host_id = created.hosts[0].id;
+    let next = IpAddress::next_for_host(host_id, &mut conn).await.unwrap();
+    assert!(next.is_some());
+
+    // but not more than fit in the subnet of a host
+    let full = OrgFixture::new("too-many-ips")
+        .hosts(1)
+        .nodes(1)
+        .spare_ips(MAX_NODES_PER_HOST);
+    let result = full
+        .create(&seed.region, &seed.protocol, &seed.image, &mut conn)
+        .await;
+    assert!(matches!(result, Err(fixture::Error::TooManyNodes(_))));
+}
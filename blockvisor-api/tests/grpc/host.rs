@@ -1,9 +1,14 @@
 use blockvisor_api::auth::resource::HostId;
+use blockvisor_api::database::seed::{DISK_BYTES, IMAGE_ID, MEMORY_BYTES, ORG_ID, PROTOCOL_ID};
+use blockvisor_api::grpc::api::host_service_region_availability_request::Spec;
 use blockvisor_api::grpc::{api, common};
+use blockvisor_api::model::host::{ConnectionStatus, UpdateHost};
 use tonic::Code;
 
 use crate::setup::TestServer;
-use crate::setup::helper::traits::{HostService, NodeService, OrgService, SocketRpc};
+use crate::setup::helper::traits::{
+    CommandService, HostService, NodeService, OrgService, SocketRpc,
+};
 
 #[tokio::test]
 async fn create_a_new_host() {
@@ -25,6 +30,10 @@ async fn create_a_new_host() {
         cpu_cores: 2,
         memory_bytes: 2,
         disk_bytes: 2,
+        gpu_count: 0,
+        gpu_model: None,
+        nvme_iops: None,
+        network_bandwidth_mbps: None,
         tags: None,
     };
 
@@ -71,8 +80,13 @@ async fn update_an_existing_host() {
         cpu_cores: None,
         memory_bytes: None,
         disk_bytes: None,
+        gpu_count: None,
+        gpu_model: None,
+        nvme_iops: None,
+        network_bandwidth_mbps: None,
         update_tags: None,
         cost: None,
+        update_mask: None,
     };
 
     // fails without token
@@ -174,3 +188,362 @@ async fn start_and_stop_a_host() {
     };
     test.send_admin(HostService::restart, req).await.unwrap();
 }
+
+#[tokio::test]
+async fn upgrade_outdated_hosts_in_stages() {
+    let test = TestServer::new().await;
+    let host1 = test.seed().host1.id;
+    let host2 = test.seed().host2.id;
+
+    let mut conn = test.conn().await;
+    let online = UpdateHost::default().with_connection_status(ConnectionStatus::Online);
+    online.clone().apply(host1, &mut conn).await.unwrap();
+    online.apply(host2, &mut conn).await.unwrap();
+
+    let req = api::HostServiceVersionReportRequest { org_id: None };
+    let resp = test
+        .send_super(HostService::version_report, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.total, 2);
+    assert_eq!(resp.versions.len(), 1);
+    assert_eq!(resp.versions[0].bv_version, "0.1.0");
+    assert_eq!(resp.versions[0].online, 2);
+
+    let req = |limit| api::HostServiceUpgradeOutdatedRequest {
+        version: "0.2.0".to_string(),
+        org_id: None,
+        limit,
+    };
+    let status = test
+        .send_admin(HostService::upgrade_outdated, req(None))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // the first stage upgrades a single host
+    let resp = test
+        .send_super(HostService::upgrade_outdated, req(Some(1)))
+        .await
+        .unwrap();
+    assert_eq!(resp.host_ids.len(), 1);
+
+    // the next stage skips the host that is still upgrading
+    let resp2 = test
+        .send_super(HostService::upgrade_outdated, req(None))
+        .await
+        .unwrap();
+    assert_eq!(resp2.host_ids.len(), 1);
+    assert_ne!(resp.host_ids, resp2.host_ids);
+
+    let resp = test
+        .send_super(HostService::upgrade_outdated, req(None))
+        .await
+        .unwrap();
+    assert!(resp.host_ids.is_empty());
+}
+
+#[tokio::test]
+async fn agent_upgrade_halts_on_failure() {
+    use api::host_service_upgrade_agent_request::Target;
+
+    let test = TestServer::new().await;
+    let host1 = test.seed().host1.id;
+    let host2 = test.seed().host2.id;
+
+    let mut conn = test.conn().await;
+    let online = UpdateHost::default().with_connection_status(ConnectionStatus::Online);
+    online.clone().apply(host1, &mut conn).await.unwrap();
+    online.apply(host2, &mut conn).await.unwrap();
+
+    // the private host2 is skipped once its org upgrades manually
+    let req = api::OrgServiceUpdateRequest {
+        org_id: test.seed().org.id.to_string(),
+        name: None,
+        agent_upgrade_policy: Some(api::AgentUpgradePolicy::Manual.into()),
+        disk_quota_bytes: None,
+        disk_auto_approve_bytes: None,
+        node_display_name_max_len: None,
+        node_note_max_len: None,
+        node_display_name_unique: None,
+        node_display_name_charset: None,
+        require_approval_node_delete: None,
+        require_approval_node_upgrade: None,
+        require_approval_org_transfer: None,
+    };
+    test.send_admin(OrgService::update, req).await.unwrap();
+
+    let req = api::HostServiceUpgradeAgentRequest {
+        version: "0.2.0".to_string(),
+        target: Some(Target::Filter(api::AgentUpgradeFilter::default())),
+        wave_size: Some(1),
+        wave_interval_seconds: Some(0),
+        max_failures: Some(0),
+    };
+    let resp = test
+        .send_super(HostService::upgrade_agent, req)
+        .await
+        .unwrap();
+    let upgrade_id = resp.agent_upgrade.unwrap().agent_upgrade_id;
+
+    let req = api::HostServiceGetAgentUpgradeRequest {
+        agent_upgrade_id: upgrade_id.clone(),
+    };
+    let resp = test
+        .send_super(HostService::get_agent_upgrade, req.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.hosts.len(), 1);
+    assert_eq!(resp.hosts[0].host_id, host1.to_string());
+    assert_eq!(resp.hosts[0].status(), api::AgentUpgradeHostStatus::Sent);
+
+    // a single failed host halts the upgrade
+    let req_update = api::CommandServiceUpdateRequest {
+        command_id: resp.hosts[0].command_id.clone().unwrap(),
+        exit_message: Some("bad version".to_string()),
+        exit_code: Some(api::CommandExitCode::InternalError.into()),
+        retry_hint_seconds: None,
+        config_hash: None,
+        artifact_ids: vec![],
+    };
+    test.send_with(CommandService::update, req_update, &test.public_host_jwt())
+        .await
+        .unwrap();
+
+    let resp = test
+        .send_super(HostService::get_agent_upgrade, req)
+        .await
+        .unwrap();
+    let upgrade = resp.agent_upgrade.unwrap();
+    assert_eq!(upgrade.status(), api::AgentUpgradeStatus::Halted);
+    assert_eq!(resp.hosts[0].status(), api::AgentUpgradeHostStatus::Failed);
+    assert_eq!(resp.hosts[0].error.as_deref(), Some("bad version"));
+}
+
+#[tokio::test]
+async fn private_host_pools() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+    let host1 = test.seed().host1.id.to_string();
+    let host2 = test.seed().host2.id.to_string();
+
+    let req = api::HostServiceCreatePoolRequest {
+        org_id: org_id.clone(),
+        name: "rack-1".to_string(),
+        description: None,
+    };
+    let resp = test
+        .send_admin(HostService::create_pool, req.clone())
+        .await
+        .unwrap();
+    let pool_id = resp.pool.unwrap().pool_id;
+
+    // pool names are unique within an org
+    let result = test.send_admin(HostService::create_pool, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::AlreadyExists);
+
+    // public hosts can't be added to a pool
+    let set_pool = |host_id: &str| api::HostServiceSetHostPoolRequest {
+        host_id: host_id.to_string(),
+        pool_id: Some(pool_id.clone()),
+    };
+    let result = test
+        .send_admin(HostService::set_host_pool, set_pool(&host1))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+
+    let resp = test
+        .send_admin(HostService::set_host_pool, set_pool(&host2))
+        .await
+        .unwrap();
+    assert_eq!(resp.host.unwrap().pool_id.as_ref(), Some(&pool_id));
+
+    let req = api::HostServiceListPoolsRequest {
+        org_id: org_id.clone(),
+    };
+    let resp = test
+        .send_admin(HostService::list_pools, req.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.pools.len(), 1);
+    let capacity = resp.pools[0].capacity.as_ref().unwrap();
+    assert_eq!(capacity.host_count, 1);
+    assert_eq!(capacity.cpu_cores, 1);
+
+    // a node in the pool can't be launched on a public host
+    let req = api::NodeServiceCreateRequest {
+        org_id,
+        image_id: test.seed().image.id.to_string(),
+        old_node_id: None,
+        launcher: Some(common::NodeLauncher {
+            launch: Some(common::node_launcher::Launch::ByHost(common::ByHost {
+                host_counts: vec![common::HostCount {
+                    host_id: host1,
+                    node_count: 1,
+                }],
+            })),
+        }),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: Some(pool_id.clone()),
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let result = test.send_admin(NodeService::create, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+
+    // deleting the pool leaves its hosts in the org
+    let req_delete = api::HostServiceDeletePoolRequest {
+        pool_id: pool_id.clone(),
+    };
+    test.send_admin(HostService::delete_pool, req_delete)
+        .await
+        .unwrap();
+
+    let req_get = api::HostServiceGetHostRequest { host_id: host2 };
+    let resp = test
+        .send_admin(HostService::get_host, req_get)
+        .await
+        .unwrap();
+    assert_eq!(resp.host.unwrap().pool_id, None);
+
+    let resp = test.send_admin(HostService::list_pools, req).await.unwrap();
+    assert!(resp.pools.is_empty());
+}
+
+#[tokio::test]
+async fn updated_regions_are_not_served_from_the_cache() {
+    let test = TestServer::new().await;
+    let get_node = || api::NodeServiceGetRequest {
+        node_id: test.seed().node.id.to_string(),
+    };
+
+    // the first read caches the region of the node
+    let resp = test.send_admin(NodeService::get, get_node()).await.unwrap();
+    let region_name = &test.seed().region.display_name;
+    assert_eq!(&resp.node.unwrap().region_name, region_name);
+
+    let req = api::HostServiceUpdateRegionRequest {
+        region_id: test.seed().region.id.to_string(),
+        display_name: Some("Renamed".to_string()),
+        sku_code: None,
+    };
+    test.send_super(HostService::update_region, req)
+        .await
+        .unwrap();
+
+    let resp = test.send_admin(NodeService::get, get_node()).await.unwrap();
+    assert_eq!(resp.node.unwrap().region_name, "Renamed");
+}
+
+#[tokio::test]
+async fn report_inventory_reconciles_unknown_vms() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+    let host_id = node.host_id.to_string();
+    let jwt = test
+        .cipher()
+        .jwt
+        .encode(&test.host_claims_for(node.host_id))
+        .unwrap();
+
+    let report = |vm_ids: Vec<String>| api::HostServiceReportInventoryRequest {
+        host_id: host_id.clone(),
+        vm_ids,
+    };
+
+    let req = report(vec![node.id.to_string(), "stray-vm".to_string()]);
+    let resp = test
+        .send_with(HostService::report_inventory, req, &jwt)
+        .await
+        .unwrap();
+    assert_eq!(resp.reconciliations.len(), 1);
+    let stray = &resp.reconciliations[0];
+    assert_eq!(stray.vm_id, "stray-vm");
+    assert_eq!(stray.kind(), api::ReconciliationKind::UnknownVm);
+
+    // the next report without the stray vm resolves it
+    let req = report(vec![node.id.to_string()]);
+    let resp = test
+        .send_with(HostService::report_inventory, req, &jwt)
+        .await
+        .unwrap();
+    assert!(resp.reconciliations.is_empty());
+
+    let req = api::HostServiceListReconciliationsRequest {
+        host_id: host_id.clone(),
+        include_resolved: true,
+    };
+    let resp = test
+        .send_admin(HostService::list_reconciliations, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.reconciliations.len(), 1);
+    assert!(resp.reconciliations[0].resolved_at.is_some());
+}
+
+#[tokio::test]
+async fn region_availability_counts_schedulable_nodes() {
+    let test = TestServer::new().await;
+    let region_id = test.seed().region.id.to_string();
+
+    let req = |spec| api::HostServiceRegionAvailabilityRequest {
+        org_id: Some(ORG_ID.into()),
+        spec: Some(spec),
+    };
+    let resources = |cpu_cores| {
+        Spec::Resources(api::ResourceSpec {
+            protocol_id: PROTOCOL_ID.into(),
+            cpu_cores,
+            memory_bytes: MEMORY_BYTES as u64,
+            disk_bytes: DISK_BYTES as u64,
+            gpu_count: 0,
+        })
+    };
+    let availability = |resp: api::HostServiceRegionAvailabilityResponse| {
+        resp.regions
+            .into_iter()
+            .find(|info| info.region.as_ref().unwrap().region_id == region_id)
+            .unwrap()
+    };
+
+    // a spec is required
+    let missing = api::HostServiceRegionAvailabilityRequest {
+        org_id: Some(ORG_ID.into()),
+        spec: None,
+    };
+    let result = test
+        .send_member(HostService::region_availability, missing)
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    // the image fits on the public host
+    let result = test
+        .send_member(
+            HostService::region_availability,
+            req(Spec::ImageId(IMAGE_ID.into())),
+        )
+        .await;
+    let info = availability(result.unwrap());
+    assert_eq!(info.valid_hosts, 1);
+    assert!(info.node_capacity >= 1);
+
+    // only one node of 60 cores fits on a host of 100
+    let result = test
+        .send_member(HostService::region_availability, req(resources(60)))
+        .await;
+    let info = availability(result.unwrap());
+    assert_eq!(info.valid_hosts, 1);
+    assert_eq!(info.node_capacity, 1);
+
+    // a full region is still returned, with no capacity
+    let result = test
+        .send_member(HostService::region_availability, req(resources(100)))
+        .await;
+    let info = availability(result.unwrap());
+    assert_eq!(info.valid_hosts, 0);
+    assert_eq!(info.node_capacity, 0);
+}
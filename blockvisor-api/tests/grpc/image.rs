@@ -1,8 +1,9 @@
 use std::collections::{HashMap, HashSet};
 
 use blockvisor_api::database::seed::{
-    ARCHIVE_ID_1, IMAGE_ID, ORG_ID, ORG_IMAGE_ID, ORG_PROTOCOL_KEY, ORG_VARIANT_KEY, PROTOCOL_KEY,
-    PROTOCOL_VERSION_ID, SEMANTIC_VERSION, STORE_KEY_1, STORE_KEY_2, VARIANT_KEY,
+    ARCHIVE_ID_1, IMAGE_ID, ORG_ID, ORG_IMAGE_ID, ORG_PROTOCOL_KEY, ORG_PROTOCOL_VERSION_ID,
+    ORG_VARIANT_KEY, PROTOCOL_KEY, PROTOCOL_VERSION_ID, SEMANTIC_VERSION, STORE_KEY_1, STORE_KEY_2,
+    VARIANT_KEY,
 };
 use blockvisor_api::grpc::{api, common};
 use blockvisor_api::model::image::rule::{FirewallAction, FirewallDirection};
@@ -40,6 +41,10 @@ async fn add_a_new_image() {
         min_cpu_cores: 1,
         min_memory_bytes: 2,
         min_disk_bytes: 3,
+        min_gpu_count: 0,
+        gpu_model: None,
+        min_nvme_iops: None,
+        min_network_bandwidth_mbps: None,
         min_babel_version: "0.0.1".to_string(),
         ramdisks: vec![],
         archive_pointers: vec![
@@ -49,6 +54,10 @@ async fn add_a_new_image() {
             archive_pointer(vec!["prop2", "prop4"], None),
         ],
         dns_scheme: Some("https".to_string()),
+        health_rules: vec![],
+        dns_settings: None,
+        ports: vec![],
+        network_id: None,
     };
 
     // an org admin can't add new images
@@ -90,6 +99,59 @@ async fn add_a_new_image() {
     }
 }
 
+#[tokio::test]
+async fn add_image_exposes_ports() {
+    let test = TestServer::new().await;
+    let req = api::ImageServiceAddImageRequest {
+        protocol_version_id: PROTOCOL_VERSION_ID.into(),
+        org_id: Some(ORG_ID.into()),
+        image_uri: "docker://image".to_string(),
+        description: None,
+        properties: vec![],
+        firewall: Some(common::FirewallConfig {
+            default_in: common::FirewallAction::Drop.into(),
+            default_out: common::FirewallAction::Allow.into(),
+            rules: vec![],
+        }),
+        min_cpu_cores: 1,
+        min_memory_bytes: 2,
+        min_disk_bytes: 3,
+        min_gpu_count: 0,
+        gpu_model: None,
+        min_nvme_iops: None,
+        min_network_bandwidth_mbps: None,
+        min_babel_version: "0.0.1".to_string(),
+        ramdisks: vec![],
+        archive_pointers: vec![archive_pointer(vec![], Some("default-store-key"))],
+        dns_scheme: None,
+        health_rules: vec![],
+        dns_settings: None,
+        ports: vec![common::ImagePort {
+            kind: common::PortKind::P2p.into(),
+            port: 30303,
+            protocol: common::FirewallProtocol::Both.into(),
+            description: Some("devp2p".to_string()),
+        }],
+        network_id: None,
+    };
+    let resp = test.send_super(ImageService::add_image, req).await.unwrap();
+    let image = resp.image.unwrap();
+
+    // members can see the declared ports of the image
+    let ports_req = api::ImageServiceGetPortsRequest {
+        image_id: image.image_id,
+        org_id: Some(ORG_ID.into()),
+    };
+    let ports = test
+        .send_member(ImageService::get_ports, ports_req)
+        .await
+        .unwrap()
+        .ports;
+    assert_eq!(ports.len(), 1);
+    assert_eq!(ports[0].port, 30303);
+    assert_eq!(ports[0].kind(), common::PortKind::P2p);
+}
+
 fn add_image_property<S: Into<String>>(key: S, new_archive: bool) -> api::AddImageProperty {
     api::AddImageProperty {
         key: key.into(),
@@ -105,6 +167,7 @@ fn add_image_property<S: Into<String>>(key: S, new_archive: bool) -> api::AddIma
         add_cpu_cores: None,
         add_memory_bytes: None,
         add_disk_bytes: None,
+        validation: None,
     }
 }
 
@@ -260,6 +323,98 @@ async fn list_archives() {
     assert_eq!(result.unwrap().archives.len(), 0);
 }
 
+#[tokio::test]
+async fn push_a_version_for_review() {
+    let test = TestServer::new().await;
+    let push = |protocol_version_id: &str| api::ImageServicePushVersionRequest {
+        org_id: ORG_ID.into(),
+        protocol_version_id: protocol_version_id.into(),
+        image_uri: "docker://pushed".to_string(),
+        description: None,
+        properties: vec![],
+        firewall: Some(common::FirewallConfig {
+            default_in: common::FirewallAction::Drop.into(),
+            default_out: common::FirewallAction::Allow.into(),
+            rules: vec![],
+        }),
+        min_cpu_cores: 1,
+        min_memory_bytes: 2,
+        min_disk_bytes: 3,
+        min_gpu_count: 0,
+        gpu_model: None,
+        min_nvme_iops: None,
+        min_network_bandwidth_mbps: None,
+        min_babel_version: "0.0.1".to_string(),
+        ramdisks: vec![],
+        archive_pointers: vec![archive_pointer(vec![], Some("pushed-store-key"))],
+        dns_scheme: None,
+        health_rules: vec![],
+        dns_settings: None,
+        ports: vec![],
+        network_id: None,
+    };
+    let get_latest = || api::ImageServiceGetImageRequest {
+        version_key: version_key(ORG_PROTOCOL_KEY, VARIANT_KEY),
+        org_id: Some(ORG_ID.into()),
+        semantic_version: None,
+        build_version: None,
+    };
+
+    // an org member can't push a version
+    let req = push(ORG_PROTOCOL_VERSION_ID);
+    let result = test.send_member(ImageService::push_version, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // an org admin can't push a version of a protocol it doesn't publish
+    let req = push(PROTOCOL_VERSION_ID);
+    let result = test.send_admin(ImageService::push_version, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // an org admin can push a version of its own protocol
+    let req = push(ORG_PROTOCOL_VERSION_ID);
+    let resp = test
+        .send_admin(ImageService::push_version, req)
+        .await
+        .unwrap();
+    let image = resp.image.unwrap();
+    assert_eq!(image.build_version, 2);
+    assert_eq!(image.review_status(), api::ImageReviewStatus::PendingReview);
+
+    // a pending version isn't used yet
+    let result = test
+        .send_member(ImageService::get_image, get_latest())
+        .await;
+    assert_eq!(result.unwrap().image.unwrap().image_id, ORG_IMAGE_ID);
+
+    // an org admin can't review a version
+    let req = api::ImageServiceReviewVersionRequest {
+        image_id: image.image_id.clone(),
+        approve: true,
+    };
+    let result = test
+        .send_admin(ImageService::review_version, req.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // a blockjoy admin can approve a version
+    let resp = test
+        .send_super(ImageService::review_version, req.clone())
+        .await
+        .unwrap();
+    let reviewed = resp.image.unwrap();
+    assert_eq!(reviewed.review_status(), api::ImageReviewStatus::Approved);
+
+    // an approved version can't be reviewed again
+    let result = test.send_super(ImageService::review_version, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    // the approved version is now the latest
+    let result = test
+        .send_member(ImageService::get_image, get_latest())
+        .await;
+    assert_eq!(result.unwrap().image.unwrap().image_id, image.image_id);
+}
+
 #[tokio::test]
 async fn update_existing_archive() {
     let test = TestServer::new().await;
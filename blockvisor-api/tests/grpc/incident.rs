@@ -0,0 +1,138 @@
+use blockvisor_api::grpc::{api, common};
+use tonic::Code;
+use tonic_types::StatusExt;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{IncidentService, NodeService, SocketRpc};
+
+#[tokio::test]
+async fn node_report_opens_an_incident() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+    let member_id = test.seed().member.id;
+
+    let report = api::NodeServiceReportErrorRequest {
+        node_id: node_id.to_string(),
+        created_by: Some(common::Resource {
+            resource_type: common::ResourceType::User.into(),
+            resource_id: member_id.to_string(),
+        }),
+        message: "node fails to sync\nfull stack trace".to_string(),
+    };
+    let report_id = test
+        .send_member(NodeService::report_error, report)
+        .await
+        .unwrap()
+        .report_id;
+
+    let list = api::IncidentServiceListRequest {
+        org_id: Some(test.seed().org.id.to_string()),
+        node_id: Some(node_id.to_string()),
+        assignee_id: None,
+        statuses: vec![api::IncidentStatus::Open.into()],
+    };
+    let incidents = test
+        .send_member(IncidentService::list, list.clone())
+        .await
+        .unwrap()
+        .incidents;
+    assert_eq!(incidents.len(), 1);
+    let incident = &incidents[0];
+    assert_eq!(incident.title, "node fails to sync");
+    assert_eq!(incident.report_id.as_deref(), Some(report_id.as_str()));
+
+    // a report only opens one incident
+    let create = api::IncidentServiceCreateRequest { report_id };
+    let result = test.send_super(IncidentService::create, create).await;
+    assert_eq!(result.unwrap_err().code(), Code::AlreadyExists);
+
+    // only the support team may update an incident
+    let update = |status: api::IncidentStatus| api::IncidentServiceUpdateRequest {
+        incident_id: incident.incident_id.clone(),
+        status: Some(status.into()),
+        assignee_id: Some(test.seed().root.id.to_string()),
+        unassign: false,
+    };
+    let result = test
+        .send_member(
+            IncidentService::update,
+            update(api::IncidentStatus::Resolved),
+        )
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let acked = test
+        .send_super(
+            IncidentService::update,
+            update(api::IncidentStatus::Acknowledged),
+        )
+        .await
+        .unwrap()
+        .incident
+        .unwrap();
+    assert_eq!(acked.status, api::IncidentStatus::Acknowledged as i32);
+    assert!(acked.acknowledged_at.is_some());
+    assert!(acked.assignee_id.is_some());
+
+    let comment = api::IncidentServiceCommentRequest {
+        incident_id: incident.incident_id.clone(),
+        message: "still failing after restart".to_string(),
+    };
+    test.send_member(IncidentService::comment, comment)
+        .await
+        .unwrap();
+
+    let resolved = test
+        .send_super(
+            IncidentService::update,
+            update(api::IncidentStatus::Resolved),
+        )
+        .await
+        .unwrap()
+        .incident
+        .unwrap();
+    assert!(resolved.resolved_at.is_some());
+
+    let get = api::IncidentServiceGetRequest {
+        incident_id: incident.incident_id.clone(),
+    };
+    let resp = test
+        .send_member(IncidentService::get, get.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.comments.len(), 1);
+    assert_eq!(resp.comments[0].message, "still failing after restart");
+
+    let incidents = test
+        .send_member(IncidentService::list, list)
+        .await
+        .unwrap()
+        .incidents;
+    assert!(incidents.is_empty());
+
+    let result = test.send_unknown(IncidentService::get, get).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn invalid_fields_are_reported_as_bad_request() {
+    let test = TestServer::new().await;
+
+    let comment = api::IncidentServiceCommentRequest {
+        incident_id: "not-a-uuid".to_string(),
+        message: "  ".to_string(),
+    };
+    let status = test
+        .send_member(IncidentService::comment, comment)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let bad_request = status.get_details_bad_request().unwrap();
+    let fields: Vec<_> = bad_request
+        .field_violations
+        .iter()
+        .map(|violation| violation.field.as_str())
+        .collect();
+    assert_eq!(fields, ["incident_id", "message"]);
+}
@@ -0,0 +1,511 @@
+use std::time::Duration;
+
+use blockvisor_api::database::seed::{IMAGE_ID, NODE_NAME, ORG_ID, VARIANT_KEY};
+use blockvisor_api::grpc::{api, common};
+use blockvisor_api::job;
+use blockvisor_api::model::node::NodeHealth;
+use blockvisor_api::model::protocol::NetworkHead;
+use blockvisor_api::model::retention::RetainedTable;
+use blockvisor_api::model::schema::nodes;
+use blockvisor_api::model::{Org, orphan};
+use chrono::{TimeDelta, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{InternalAdminService, NodeService, SocketRpc};
+
+#[tokio::test]
+async fn search_nodes_across_orgs() {
+    let test = TestServer::new().await;
+
+    let req = api::InternalAdminServiceSearchNodesRequest {
+        search: NODE_NAME.to_uppercase(),
+        offset: 0,
+        limit: 10,
+    };
+    let status = test
+        .send_member(InternalAdminService::search_nodes, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let resp = test
+        .send_super(InternalAdminService::search_nodes, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.total, 1);
+    assert_eq!(resp.nodes[0].node_id, test.seed().node.id.to_string());
+}
+
+#[tokio::test]
+async fn override_node_state_is_audited() {
+    let test = TestServer::new().await;
+
+    let req = api::InternalAdminServiceOverrideNodeStateRequest {
+        node_id: test.seed().node.id.to_string(),
+        node_state: Some(common::NodeState::Failed.into()),
+        next_state: None,
+        clear_next_state: true,
+        reason: "Host was lost.".to_string(),
+    };
+    let resp = test
+        .send_super(InternalAdminService::override_node_state, req)
+        .await
+        .unwrap();
+    let status = resp.node.unwrap().status.unwrap();
+    assert_eq!(status.state, common::NodeState::Failed as i32);
+    assert_eq!(status.next, None);
+
+    let req = api::InternalAdminServiceOverrideNodeStateRequest {
+        node_id: test.seed().node.id.to_string(),
+        node_state: None,
+        next_state: None,
+        clear_next_state: false,
+        reason: String::new(),
+    };
+    let status = test
+        .send_super(InternalAdminService::override_node_state, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn dry_run_reports_orphaned_dns_records() {
+    let test = TestServer::new().await;
+
+    let req = api::InternalAdminServiceCleanupDnsRequest { dry_run: true };
+    let resp = test
+        .send_super(InternalAdminService::cleanup_dns, req)
+        .await
+        .unwrap();
+
+    assert!(resp.dry_run);
+    assert_eq!(resp.deleted, 0);
+    assert_eq!(resp.orphans.len(), 1);
+    assert_eq!(resp.orphans[0].name, "orphaned-node.base");
+}
+
+#[tokio::test]
+async fn reconcile_orphans_ignores_live_resources() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let ctx = test.context();
+    let dns = ctx.dns.as_ref().as_ref();
+    let stripe = ctx.stripe.as_ref().unwrap().as_ref().as_ref();
+
+    // the day old record and the subscription item of the org are orphans
+    let records = orphan::dns_records(dns, &mut conn).await.unwrap();
+    assert_eq!(records.len(), 1);
+    let items = orphan::stripe_items(stripe, &ctx.keyring, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].org_id, test.seed().org.id);
+
+    // the scheduled job only reports them by default
+    job::reconcile_orphans(ctx).await.unwrap();
+    assert_eq!(orphan::dns_records(dns, &mut conn).await.unwrap().len(), 1);
+
+    // until a live node refers to them
+    diesel::update(nodes::table.find(test.seed().node.id))
+        .set((
+            nodes::dns_id.eq(&records[0].id),
+            nodes::stripe_item_id.eq(items[0].item.id.to_string()),
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let records_left = orphan::dns_records(dns, &mut conn).await.unwrap();
+    assert!(records_left.is_empty());
+    let items_left = orphan::stripe_items(stripe, &ctx.keyring, &mut conn)
+        .await
+        .unwrap();
+    assert!(items_left.is_empty());
+}
+
+#[tokio::test]
+async fn feature_flags_roll_out_to_allowlisted_orgs() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+    let flags = &test.context().flags;
+    assert!(!flags.enabled("rebalancer", Some(org_id), None).await);
+
+    let req = api::InternalAdminServiceSetFeatureFlagRequest {
+        name: "rebalancer".to_string(),
+        description: Some("Move nodes off overloaded hosts.".to_string()),
+        enabled: true,
+        rollout_percent: 0,
+        org_ids: vec![org_id.to_string()],
+        user_ids: vec![],
+    };
+    let status = test
+        .send_admin(InternalAdminService::set_feature_flag, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let resp = test
+        .send_super(InternalAdminService::set_feature_flag, req)
+        .await
+        .unwrap();
+    assert!(resp.flag.unwrap().enabled);
+    assert!(flags.enabled("rebalancer", Some(org_id), None).await);
+    assert!(!flags.enabled("rebalancer", None, None).await);
+
+    let req = api::InternalAdminServiceSetFeatureFlagRequest {
+        name: "rebalancer".to_string(),
+        description: None,
+        enabled: false,
+        rollout_percent: 100,
+        org_ids: vec![],
+        user_ids: vec![],
+    };
+    test.send_super(InternalAdminService::set_feature_flag, req)
+        .await
+        .unwrap();
+    assert!(!flags.enabled("rebalancer", Some(org_id), None).await);
+
+    let req = api::InternalAdminServiceListFeatureFlagsRequest {};
+    let resp = test
+        .send_super(InternalAdminService::list_feature_flags, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.flags.len(), 1);
+    assert_eq!(resp.flags[0].rollout_percent, 100);
+}
+
+#[tokio::test]
+async fn host_weights_fall_back_to_config() {
+    let test = TestServer::new().await;
+
+    let req = api::InternalAdminServiceGetHostWeightsRequest {};
+    let resp = test
+        .send_super(InternalAdminService::get_host_weights, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.active, resp.defaults);
+    assert!(resp.shadow.is_none());
+
+    let weights = |cost| api::HostWeights {
+        headroom: 0.5,
+        similarity: 2.0,
+        age: 0.1,
+        cost,
+    };
+    let req = api::InternalAdminServiceSetHostWeightsRequest {
+        ranking: api::HostRanking::Shadow.into(),
+        weights: Some(weights(f64::NAN)),
+    };
+    let status = test
+        .send_admin(InternalAdminService::set_host_weights, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let status = test
+        .send_super(InternalAdminService::set_host_weights, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let weights = api::HostWeights {
+        cost: 1.0,
+        ..weights
+    };
+    let req = api::InternalAdminServiceSetHostWeightsRequest {
+        ranking: api::HostRanking::Shadow.into(),
+        weights: Some(weights),
+    };
+    let resp = test
+        .send_super(InternalAdminService::set_host_weights, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.weights, Some(weights(1.0)));
+
+    // the shadow ranking runs alongside the active one
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(common::NodeLauncher {
+            launch: Some(common::node_launcher::Launch::ByRegion(common::ByRegion {
+                region_counts: vec![common::RegionCount {
+                    region_id: test.seed().region.id.to_string(),
+                    node_count: 1,
+                    resource: Some(common::ResourceAffinity::MostResources.into()),
+                    similarity: Some(common::SimilarNodeAffinity::Cluster.into()),
+                }],
+            })),
+        }),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let resp = test.send_admin(NodeService::create, req).await.unwrap();
+    assert_eq!(resp.nodes[0].host_id, test.seed().host1.id.to_string());
+
+    let req = api::InternalAdminServiceSetHostWeightsRequest {
+        ranking: api::HostRanking::Shadow.into(),
+        weights: None,
+    };
+    test.send_super(InternalAdminService::set_host_weights, req)
+        .await
+        .unwrap();
+
+    let req = api::InternalAdminServiceGetHostWeightsRequest {};
+    let resp = test
+        .send_super(InternalAdminService::get_host_weights, req)
+        .await
+        .unwrap();
+    assert!(resp.shadow.is_none());
+}
+
+#[tokio::test]
+async fn backfill_recounts_org_nodes() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+
+    let req = api::InternalAdminServiceRunBackfillRequest {
+        name: "unknown-backfill".to_string(),
+        batch_size: None,
+    };
+    let status = test
+        .send_super(InternalAdminService::run_backfill, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let req = api::InternalAdminServiceRunBackfillRequest {
+        name: "org-counts".to_string(),
+        batch_size: Some(1),
+    };
+    test.send_super(InternalAdminService::run_backfill, req)
+        .await
+        .unwrap();
+
+    // the backfill runs in the background once committed
+    let mut completed = false;
+    for _ in 0..20 {
+        let req = api::InternalAdminServiceListBackfillsRequest {};
+        let resp = test
+            .send_super(InternalAdminService::list_backfills, req)
+            .await
+            .unwrap();
+        assert!(resp.tasks.contains(&"org-counts".to_string()));
+        completed = resp.backfills[0].completed_at.is_some();
+        if completed {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(completed);
+
+    let mut conn = test.conn().await;
+    let org = Org::by_id(org_id, &mut conn).await.unwrap();
+    assert_eq!(org.node_count, 1);
+}
+
+#[tokio::test]
+async fn network_head_sets_node_sync_lag() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+
+    let set_req = |rpc_url: Option<&str>| api::InternalAdminServiceSetNetworkHeadRequest {
+        protocol_id: test.seed().protocol.id.to_string(),
+        variant_key: VARIANT_KEY.to_string(),
+        rpc_url: rpc_url.map(ToString::to_string),
+    };
+
+    let req = set_req(Some("not a url"));
+    let status = test
+        .send_super(InternalAdminService::set_network_head, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = set_req(Some("https://rpc.example.com"));
+    let resp = test
+        .send_super(InternalAdminService::set_network_head, req)
+        .await
+        .unwrap();
+    let head = resp.head.unwrap();
+    assert_eq!(head.rpc_url.as_deref(), Some("https://rpc.example.com"));
+    assert_eq!(head.block_height, None);
+
+    // without an rpc provider the head follows the reference node
+    let req = set_req(None);
+    test.send_super(InternalAdminService::set_network_head, req)
+        .await
+        .unwrap();
+
+    let mut conn = test.conn().await;
+    diesel::update(nodes::table.find(node.id))
+        .set((
+            nodes::protocol_health.eq(NodeHealth::Healthy),
+            nodes::block_height.eq(100),
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    NetworkHead::refresh(&reqwest::Client::new(), &mut conn)
+        .await
+        .unwrap();
+
+    let req = api::InternalAdminServiceListNetworkHeadsRequest {};
+    let resp = test
+        .send_super(InternalAdminService::list_network_heads, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.heads.len(), 1);
+    assert_eq!(resp.heads[0].block_height, Some(100));
+    assert_eq!(resp.heads[0].reference_node_id, Some(node.id.to_string()));
+
+    diesel::update(nodes::table.find(node.id))
+        .set(nodes::block_height.eq(90))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let req = api::NodeServiceGetRequest {
+        node_id: node.id.to_string(),
+    };
+    let resp = test.send_admin(NodeService::get, req).await.unwrap();
+    assert_eq!(resp.node.unwrap().sync_lag, Some(10));
+
+    let list_req = |min_blocks_behind| api::NodeServiceListRequest {
+        org_ids: vec![test.seed().org.id.to_string()],
+        min_blocks_behind: Some(min_blocks_behind),
+        limit: 10,
+        ..Default::default()
+    };
+    let resp = test
+        .send_admin(NodeService::list, list_req(10))
+        .await
+        .unwrap();
+    assert_eq!(resp.nodes.len(), 1);
+    let resp = test
+        .send_admin(NodeService::list, list_req(11))
+        .await
+        .unwrap();
+    assert!(resp.nodes.is_empty());
+}
+
+#[tokio::test]
+async fn retention_drops_expired_partitions() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let now = Utc::now();
+    let table = RetainedTable::NodeLogs;
+    table
+        .ensure_partitions(now - TimeDelta::days(800), &mut conn)
+        .await
+        .unwrap();
+    let before = table.partitions(&mut conn).await.unwrap().len();
+
+    // only the partitions that ended before the cutoff are dropped
+    let dropped = table
+        .purge(now - TimeDelta::days(365), &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(dropped, 2);
+    let after = table.partitions(&mut conn).await.unwrap();
+    assert_eq!(after.len(), before - 2);
+    assert!(
+        after
+            .iter()
+            .all(|partition| partition.end() > now - TimeDelta::days(365))
+    );
+
+    let req = api::InternalAdminServiceListTableRetentionRequest {};
+    let status = test
+        .send_member(InternalAdminService::list_table_retention, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::InternalAdminServiceListTableRetentionRequest {};
+    let resp = test
+        .send_super(InternalAdminService::list_table_retention, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.tables.len(), RetainedTable::ALL.len());
+    let node_logs = resp
+        .tables
+        .iter()
+        .find(|retention| retention.table == "node_logs")
+        .unwrap();
+    assert_eq!(node_logs.retention_days, 365);
+    assert_eq!(node_logs.partition_count as usize, after.len());
+    assert!(node_logs.next_purge_at.is_some());
+}
+
+#[tokio::test]
+async fn legal_hold_blocks_node_deletes_until_cleared() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id.to_string();
+
+    let set_req = || api::InternalAdminServiceSetLegalHoldRequest {
+        org_id: None,
+        node_id: Some(node_id.clone()),
+        reason: "Pending litigation.".to_string(),
+    };
+    let status = test
+        .send_admin(InternalAdminService::set_legal_hold, set_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let resp = test
+        .send_super(InternalAdminService::set_legal_hold, set_req())
+        .await
+        .unwrap();
+    let hold = resp.hold.unwrap();
+    assert_eq!(hold.node_id, Some(node_id.clone()));
+
+    // only one active hold per node
+    let status = test
+        .send_super(InternalAdminService::set_legal_hold, set_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    let delete_req = || api::NodeServiceDeleteRequest {
+        node_id: node_id.clone(),
+    };
+    let status = test
+        .send_admin(NodeService::delete, delete_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let req = api::InternalAdminServiceListLegalHoldsRequest {
+        include_cleared: false,
+    };
+    let resp = test
+        .send_super(InternalAdminService::list_legal_holds, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.holds.len(), 1);
+
+    let req = api::InternalAdminServiceClearLegalHoldRequest {
+        hold_id: hold.hold_id,
+        reason: "Case closed.".to_string(),
+    };
+    let resp = test
+        .send_super(InternalAdminService::clear_legal_hold, req)
+        .await
+        .unwrap();
+    assert!(resp.hold.unwrap().cleared_at.is_some());
+
+    test.send_admin(NodeService::delete, delete_req())
+        .await
+        .unwrap();
+}
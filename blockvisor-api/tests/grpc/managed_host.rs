@@ -0,0 +1,126 @@
+use blockvisor_api::grpc::{api, common};
+use blockvisor_api::model::ManagedHost;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{HostService, ManagedHostService, OrgService, SocketRpc};
+
+#[tokio::test]
+async fn managed_hosts_register_from_their_machine() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+
+    let provision_req = |provider: api::CloudProvider| api::ManagedHostServiceProvisionRequest {
+        org_id: org_id.clone(),
+        region_id: test.seed().region.id.to_string(),
+        provider: provider.into(),
+        provider_region: "fsn1".to_string(),
+        machine_type: "ccx33".to_string(),
+        machine_image: "ubuntu-24.04".to_string(),
+        name: "managed-host".to_string(),
+    };
+
+    // members may not provision hosts
+    let req = provision_req(api::CloudProvider::Hetzner);
+    let status = test
+        .send_member(ManagedHostService::provision, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // a provider is required
+    let req = provision_req(api::CloudProvider::Unspecified);
+    let status = test
+        .send_admin(ManagedHostService::provision, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = provision_req(api::CloudProvider::Hetzner);
+    let resp = test
+        .send_admin(ManagedHostService::provision, req)
+        .await
+        .unwrap();
+    let managed_host = resp.managed_host.unwrap();
+    assert_eq!(managed_host.status(), api::ManagedHostStatus::Creating);
+
+    let req = api::ManagedHostServiceListRequest {
+        org_id: org_id.clone(),
+    };
+    let resp = test
+        .send_member(ManagedHostService::list, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.managed_hosts.len(), 1);
+
+    // simulate the provision job creating the machine
+    let id = managed_host.managed_host_id.parse().unwrap();
+    let mut conn = test.conn().await;
+    let created = ManagedHost::by_id(id, &mut conn)
+        .await
+        .unwrap()
+        .created("12345", &mut conn)
+        .await
+        .unwrap();
+    created
+        .set_ip_address("172.168.0.10".parse().unwrap(), &mut conn)
+        .await
+        .unwrap();
+
+    let token_req = api::OrgServiceGetProvisionTokenRequest {
+        org_id: org_id.clone(),
+        user_id: test.seed().admin.id.to_string(),
+    };
+    let provision_token = test
+        .send_admin(OrgService::get_provision_token, token_req)
+        .await
+        .unwrap()
+        .token;
+
+    let req = api::HostServiceCreateHostRequest {
+        provision_token,
+        is_private: true,
+        network_name: "managed-host".to_string(),
+        display_name: None,
+        region_id: test.seed().region.id.to_string(),
+        schedule_type: common::ScheduleType::Automatic.into(),
+        os: "Ubuntu".to_string(),
+        os_version: "24.04".to_string(),
+        bv_version: "0.1.2".to_string(),
+        ip_address: "172.168.0.10".to_string(),
+        ip_gateway: "172.168.0.1".to_string(),
+        ips: vec!["172.168.0.11".to_string()],
+        cpu_cores: 8,
+        memory_bytes: 32,
+        disk_bytes: 240,
+        gpu_count: 0,
+        gpu_model: None,
+        nvme_iops: None,
+        network_bandwidth_mbps: None,
+        tags: None,
+    };
+    let host = test
+        .send_unauthenticated(HostService::create_host, req)
+        .await
+        .unwrap()
+        .host
+        .unwrap();
+
+    // the host registered from the machine is linked to the managed host
+    let ready = ManagedHost::by_id(id, &mut conn).await.unwrap();
+    let ready = api::ManagedHost::from(ready);
+    assert_eq!(ready.status(), api::ManagedHostStatus::Ready);
+    assert_eq!(ready.host_id, Some(host.host_id));
+
+    let req = api::ManagedHostServiceDeprovisionRequest {
+        managed_host_id: managed_host.managed_host_id,
+    };
+    let resp = test
+        .send_admin(ManagedHostService::deprovision, req)
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.managed_host.unwrap().status(),
+        api::ManagedHostStatus::Deleting
+    );
+}
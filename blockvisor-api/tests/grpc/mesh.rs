@@ -0,0 +1,119 @@
+use blockvisor_api::grpc::api;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{MeshService, SocketRpc};
+
+const HOST_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+const NODE_KEY: &str = "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=";
+
+#[tokio::test]
+async fn hosts_and_nodes_join_the_org_mesh() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+    let host1_id = test.seed().host1.id.to_string();
+    let host2_id = test.seed().host2.id.to_string();
+    let node_id = test.seed().node.id.to_string();
+
+    // a public host only joins through the nodes of an org
+    let req = api::MeshServiceJoinRequest {
+        host_id: host1_id.clone(),
+        node_id: None,
+        public_key: NODE_KEY.to_string(),
+    };
+    let jwt = test.public_host_jwt();
+    let status = test
+        .send_with(MeshService::join, req, &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    // the key must be a WireGuard key
+    let req = api::MeshServiceJoinRequest {
+        host_id: host2_id.clone(),
+        node_id: None,
+        public_key: "AAAA".to_string(),
+    };
+    let jwt = test.private_host_jwt();
+    let status = test
+        .send_with(MeshService::join, req, &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = api::MeshServiceJoinRequest {
+        host_id: host2_id.clone(),
+        node_id: None,
+        public_key: HOST_KEY.to_string(),
+    };
+    let host_peer = test
+        .send_with(MeshService::join, req, &jwt)
+        .await
+        .unwrap()
+        .peer
+        .unwrap();
+    assert_eq!(host_peer.ip, "10.200.0.1");
+
+    let req = api::MeshServiceJoinRequest {
+        host_id: host1_id.clone(),
+        node_id: Some(node_id.clone()),
+        public_key: NODE_KEY.to_string(),
+    };
+    let jwt = test.public_host_jwt();
+    let node_peer = test
+        .send_with(MeshService::join, req, &jwt)
+        .await
+        .unwrap()
+        .peer
+        .unwrap();
+    assert_eq!(node_peer.ip, "10.200.0.2");
+    assert_eq!(node_peer.node_id, Some(node_id));
+
+    let req = api::MeshServiceListPeersRequest {
+        org_id: org_id.clone(),
+    };
+    let peers = test
+        .send_member(MeshService::list_peers, req)
+        .await
+        .unwrap()
+        .peers;
+    assert_eq!(peers.len(), 2);
+
+    // each host is configured with the other peers of the org
+    let req = api::MeshServiceGetConfigRequest {
+        host_id: host2_id.clone(),
+    };
+    let jwt = test.private_host_jwt();
+    let mesh = test
+        .send_with(MeshService::get_config, req, &jwt)
+        .await
+        .unwrap()
+        .mesh
+        .unwrap();
+    assert_eq!(mesh.interfaces.len(), 1);
+    assert_eq!(mesh.interfaces[0].address, "10.200.0.1/24");
+    assert_eq!(mesh.interfaces[0].peers.len(), 1);
+    assert_eq!(mesh.interfaces[0].peers[0].public_key, NODE_KEY);
+    assert_eq!(mesh.interfaces[0].peers[0].allowed_ips, ["10.200.0.2/32"]);
+
+    // members may not remove peers
+    let req = api::MeshServiceLeaveRequest {
+        peer_id: node_peer.peer_id.clone(),
+    };
+    let status = test.send_member(MeshService::leave, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::MeshServiceLeaveRequest {
+        peer_id: node_peer.peer_id,
+    };
+    test.send_admin(MeshService::leave, req).await.unwrap();
+
+    let req = api::MeshServiceGetConfigRequest { host_id: host2_id };
+    let mesh = test
+        .send_with(MeshService::get_config, req, &jwt)
+        .await
+        .unwrap()
+        .mesh
+        .unwrap();
+    assert!(mesh.interfaces[0].peers.is_empty());
+}
@@ -1,7 +1,11 @@
 use blockvisor_api::auth::rbac::{MetricsPerm, Perms};
 use blockvisor_api::grpc::{api, common};
 use blockvisor_api::model::Host;
+use blockvisor_api::model::image::{HealthRule, HealthRules};
 use blockvisor_api::model::node::{Node, NodeHealth, NodeState};
+use blockvisor_api::model::schema::images;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
 use uuid::Uuid;
 
 use crate::setup::TestServer;
@@ -25,6 +29,7 @@ async fn responds_ok_for_write_node() {
         height: Some(10),
         block_age: Some(5),
         consensus: Some(false),
+        peer_count: None,
         jobs: vec![common::NodeJob {
             name: "download".to_string(),
             status: 2,
@@ -126,6 +131,7 @@ async fn single_failure_doesnt_abort_all_updates() {
         height: Some(10),
         block_age: Some(5),
         consensus: Some(false),
+        peer_count: None,
         node_status: None,
         jobs: vec![common::NodeJob {
             name: "download".to_string(),
@@ -165,3 +171,52 @@ async fn single_failure_doesnt_abort_all_updates() {
     assert_eq!(progress.current, Some(3));
     assert_eq!(progress.message, None);
 }
+
+#[tokio::test]
+async fn health_rules_override_the_reported_health() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+
+    let mut conn = test.conn().await;
+    let rules = HealthRules(vec![HealthRule::Consensus, HealthRule::MinPeers(3)]);
+    diesel::update(images::table.find(node.image_id))
+        .set(images::health_rules.eq(Some(rules)))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let metrics = |consensus, peer_count| api::MetricsServiceNodeRequest {
+        metrics: vec![api::NodeMetrics {
+            node_id: node.id.to_string(),
+            node_status: Some(common::NodeStatus {
+                state: common::NodeState::Running.into(),
+                next: None,
+                protocol: Some(common::ProtocolStatus {
+                    state: "broadcasting".into(),
+                    health: common::NodeHealth::Healthy.into(),
+                }),
+            }),
+            height: Some(10),
+            block_age: Some(5),
+            consensus,
+            peer_count,
+            jobs: vec![],
+        }],
+    };
+    let jwt = test.org_jwt(Perms::from(MetricsPerm::Node));
+
+    // the host reports healthy but the node has too few peers
+    let req = metrics(Some(true), Some(1));
+    test.send_with(MetricsService::node, req, &jwt)
+        .await
+        .unwrap();
+    let updated = Node::by_id(node.id, &mut conn).await.unwrap();
+    assert_eq!(updated.protocol_health, Some(NodeHealth::Unhealthy));
+
+    let req = metrics(Some(true), Some(8));
+    test.send_with(MetricsService::node, req, &jwt)
+        .await
+        .unwrap();
+    let updated = Node::by_id(node.id, &mut conn).await.unwrap();
+    assert_eq!(updated.protocol_health, Some(NodeHealth::Healthy));
+}
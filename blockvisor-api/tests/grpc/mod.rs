@@ -1,13 +1,35 @@
+mod abuse;
+mod admin;
+mod alert;
 mod api_key;
+mod approval;
+mod artifact;
 mod auth;
+mod billing;
 mod command;
 mod crypt;
 mod discovery;
+mod endpoint;
+mod fixture;
 mod host;
 mod image;
+mod incident;
+mod internal_admin;
 mod invitation;
+mod managed_host;
+mod mesh;
 mod metrics;
 mod node;
+mod node_share;
 mod org;
+mod org_branding;
+mod org_key;
+mod org_log_sink;
+mod partner;
+mod pool;
 mod protocol;
+mod report;
+mod status_page;
+mod team;
+mod ticket;
 mod user;
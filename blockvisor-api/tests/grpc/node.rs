@@ -1,19 +1,36 @@
-use blockvisor_api::auth::rbac::{NodePerm, Perms, ProtocolPerm};
+use std::time::Duration;
+
+use blockvisor_api::auth::claims::Claims;
+use blockvisor_api::auth::rbac::{CommandPerm, NodePerm, Perms, ProtocolPerm};
+use blockvisor_api::auth::resource::NodeId;
 use blockvisor_api::database::seed::{
     ARCHIVE_ID_1, ARCHIVE_ID_2, DISK_BYTES, IMAGE_ID, MEMORY_BYTES, MORE_RESOURCES_KEY, ORG_ID,
 };
-use blockvisor_api::grpc::{api, common};
+use blockvisor_api::database::{self, Database};
+use blockvisor_api::grpc::{api, api_v2, common};
+use blockvisor_api::job;
 use blockvisor_api::model::Node;
-use blockvisor_api::model::command::Command;
-use blockvisor_api::model::schema::commands;
+use blockvisor_api::model::backorder::Backorder;
+use blockvisor_api::model::command::{Command, CommandType, ExitCode};
+use blockvisor_api::model::host::UpdateHost;
+use blockvisor_api::model::node::{NodeCleanup, NodeEvent, NodeLog, NodeState};
+use blockvisor_api::model::schema::{commands, images, nodes, secrets};
 use blockvisor_api::model::sql::Tag;
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
+use futures::StreamExt;
+use opentelemetry::global;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use prost_wkt_types::FieldMask;
 use tonic::Code;
+use tonic_types::StatusExt;
 use uuid::Uuid;
 
+use crate::mqtt;
 use crate::setup::TestServer;
-use crate::setup::helper::traits::{NodeService, SocketRpc};
+use crate::setup::helper::traits::{
+    ArtifactService, CommandService, NodeService, NodeServiceV2, OrgService, SocketRpc,
+};
 
 #[tokio::test]
 async fn create_a_new_node() {
@@ -27,6 +44,11 @@ async fn create_a_new_node() {
         new_values,
         add_rules,
         tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
     };
 
     // an org admin can't create a node with an invalid org_id
@@ -84,6 +106,511 @@ async fn create_a_new_node() {
     assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
 }
 
+#[tokio::test]
+async fn create_the_extra_dns_records_of_an_image() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let settings = r#"{"ttl": 120, "records": [
+        {"srv": {"service": "p2p", "protocol": "tcp", "port": 30303, "priority": 10, "weight": 5}}
+    ]}"#;
+    let query = format!("UPDATE images SET dns_settings = '{settings}' WHERE id = '{IMAGE_ID}';");
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let mut resp = test.send_admin(NodeService::create, req).await.unwrap();
+    let node_id: NodeId = resp.nodes.pop().unwrap().node_id.parse().unwrap();
+
+    // the node keeps the extra record alongside its address record
+    let node = Node::by_id(node_id, &mut conn).await.unwrap();
+    assert_eq!(node.dns_record_ids.len(), 1);
+    assert_eq!(node.dns_records().count(), 2);
+
+    // and the node cleanup removes the extra record too
+    let req = api::NodeServiceDeleteRequest {
+        node_id: node_id.to_string(),
+    };
+    test.send_admin(NodeService::delete, req).await.unwrap();
+
+    let mut cleanup = None;
+    for _ in 0..20 {
+        let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+        cleanup = node.cleanup;
+        if cleanup == Some(NodeCleanup::Complete) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(cleanup, Some(NodeCleanup::Complete));
+}
+
+#[tokio::test]
+async fn property_values_are_validated_on_create() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let query = format!(
+        r#"INSERT INTO image_properties (id, image_id, key, new_archive, default_value, ui_type, validation)
+        VALUES ('{}', '{IMAGE_ID}', 'max-peers', false, '', 'text', '{{"min": 1, "max": 100}}');"#,
+        Uuid::new_v4()
+    );
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+
+    let create_req = |value: &str| api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![property("max-peers", value)],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+
+    // every broken rule is a violation of the property value
+    let status = test
+        .send_admin(NodeService::create, create_req("500"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations.len(), 1);
+    let violation = &bad_request.field_violations[0];
+    assert_eq!(violation.field, "new_values.max-peers");
+    assert_eq!(violation.description, "Must be at most 100.");
+
+    let status = test
+        .send_admin(NodeService::create, create_req("many"))
+        .await
+        .unwrap_err();
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(
+        bad_request.field_violations[0].description,
+        "Must be an integer."
+    );
+
+    // while a valid value creates the node
+    let resp = test
+        .send_admin(NodeService::create, create_req("50"))
+        .await
+        .unwrap();
+    assert_eq!(resp.nodes.len(), 1);
+}
+
+#[tokio::test]
+async fn secret_values_are_only_revealed_to_the_host() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let query = format!(
+        "INSERT INTO image_properties (id, image_id, key, new_archive, default_value, ui_type)
+        VALUES ('{}', '{IMAGE_ID}', 'api-secret', false, '', 'secret');",
+        Uuid::new_v4()
+    );
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+
+    let redacted = |node: &api::Node| {
+        let config = node.config.as_ref().unwrap();
+        let image = config.image.as_ref().unwrap();
+        let value = image.values.iter().find(|v| v.key == "api-secret").unwrap();
+        assert_eq!(value.value, "");
+        assert_eq!(value.secret_path.as_deref(), Some("property-api-secret"));
+    };
+
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![property("api-secret", "hunter2")],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let mut resp = test.send_admin(NodeService::create, req).await.unwrap();
+    let node = resp.nodes.pop().unwrap();
+    redacted(&node);
+
+    // the value is redacted in get and list
+    let req = api::NodeServiceGetRequest {
+        node_id: node.node_id.clone(),
+    };
+    let resp = test.send_admin(NodeService::get, req).await.unwrap();
+    redacted(&resp.node.unwrap());
+
+    let req = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        limit: 10,
+        ..Default::default()
+    };
+    let resp = test.send_admin(NodeService::list, req).await.unwrap();
+    let listed = resp.nodes.iter().find(|n| n.node_id == node.node_id);
+    redacted(listed.unwrap());
+
+    // and stored under the node id once the create has committed
+    let node_id: NodeId = node.node_id.parse().unwrap();
+    let stored: i64 = secrets::table
+        .filter(secrets::resource_id.eq(*node_id))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(stored, 1);
+
+    let pending_value = |resp: api::CommandServicePendingResponse| {
+        resp.commands
+            .into_iter()
+            .find_map(|cmd| match cmd.command? {
+                api::command::Command::Node(api::NodeCommand {
+                    node_id: ref id,
+                    command: Some(api::node_command::Command::Create(create)),
+                    ..
+                }) if *id == node.node_id => create.node,
+                _ => None,
+            })
+            .and_then(|node| node.config?.image)
+            .and_then(|image| image.values.into_iter().find(|v| v.key == "api-secret"))
+            .unwrap()
+            .value
+    };
+
+    let req = || api::CommandServicePendingRequest {
+        host_id: node.host_id.clone(),
+        filter_type: None,
+    };
+
+    // an org user only sees the redacted value of a pending command
+    let perms =
+        Perms::All(hashset! { CommandPerm::Pending.into(), ProtocolPerm::ViewPublic.into()});
+    let jwt = test.org_jwt(perms);
+    let resp = test
+        .send_with(CommandService::pending, req(), &jwt)
+        .await
+        .unwrap();
+    assert_eq!(pending_value(resp), "");
+
+    // while the host of the node sees the secret value
+    let jwt = test.public_host_jwt();
+    let resp = test
+        .send_with(CommandService::pending, req(), &jwt)
+        .await
+        .unwrap();
+    assert_eq!(pending_value(resp), "hunter2");
+
+    // and the secret values are removed by the node cleanup
+    let req = api::NodeServiceDeleteRequest {
+        node_id: node.node_id.clone(),
+    };
+    test.send_admin(NodeService::delete, req).await.unwrap();
+
+    let mut cleanup = None;
+    for _ in 0..20 {
+        let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+        cleanup = node.cleanup;
+        if cleanup == Some(NodeCleanup::Complete) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(cleanup, Some(NodeCleanup::Complete));
+
+    let stored: i64 = secrets::table
+        .filter(secrets::resource_id.eq(*node_id))
+        .count()
+        .get_result(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+}
+
+#[tokio::test]
+async fn validate_only_create_does_not_create_nodes() {
+    let test = TestServer::new().await;
+
+    let list_req = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        limit: 10,
+        ..Default::default()
+    };
+    let before = test
+        .send_admin(NodeService::list, list_req.clone())
+        .await
+        .unwrap();
+
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: true,
+        backorder: false,
+    };
+
+    // a validate-only request returns the planned placement
+    let resp = test
+        .send_admin(NodeService::create, req.clone())
+        .await
+        .unwrap();
+    assert!(resp.nodes.is_empty());
+    assert_eq!(resp.planned.len(), 1);
+    assert_eq!(resp.planned[0].host_id, test.seed().host1.id.to_string());
+
+    // but no node was created
+    let after = test.send_admin(NodeService::list, list_req).await.unwrap();
+    assert_eq!(after.total, before.total);
+
+    // and it still fails on an unknown region
+    let mut bad_req = req;
+    bad_req.launcher = Some(launch_region(Uuid::new_v4(), 1));
+    let result = test.send_admin(NodeService::create, bad_req).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn project_limits_its_nodes() {
+    let test = TestServer::new().await;
+
+    let req = api::OrgServiceCreateProjectRequest {
+        org_id: ORG_ID.into(),
+        name: "staging".to_string(),
+        description: None,
+        node_quota: Some(1),
+    };
+    let resp = test
+        .send_admin(OrgService::create_project, req)
+        .await
+        .unwrap();
+    let project_id = resp.project.unwrap().project_id;
+
+    let create_req = || api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: Some(project_id.clone()),
+        validate_only: false,
+        backorder: false,
+    };
+
+    let resp = test
+        .send_admin(NodeService::create, create_req())
+        .await
+        .unwrap();
+    assert_eq!(resp.nodes[0].project_id, Some(project_id.clone()));
+
+    // the project quota of one node is reached
+    let result = test.send_admin(NodeService::create, create_req()).await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+
+    let req = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        project_ids: vec![project_id],
+        limit: 10,
+        ..Default::default()
+    };
+    let resp = test.send_admin(NodeService::list, req).await.unwrap();
+    assert_eq!(resp.nodes.len(), 1);
+}
+
+#[tokio::test]
+async fn create_is_retried_after_a_conflict() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    // the first node insert fails with a serialization failure, and the
+    // sequence is not rolled back so that the retry succeeds
+    let queries = [
+        "CREATE SEQUENCE node_insert_attempts;",
+        "CREATE FUNCTION conflict_once() RETURNS trigger LANGUAGE plpgsql AS $$
+        BEGIN
+            IF nextval('node_insert_attempts') = 1 THEN
+                RAISE EXCEPTION 'forced conflict' USING ERRCODE = 'serialization_failure';
+            END IF;
+            RETURN NEW;
+        END $$;",
+        "CREATE TRIGGER conflict_once BEFORE INSERT ON nodes
+        FOR EACH ROW EXECUTE FUNCTION conflict_once();",
+    ];
+    for query in queries {
+        diesel::sql_query(query).execute(&mut conn).await.unwrap();
+    }
+
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let mut resp = test.send_admin(NodeService::create, req).await.unwrap();
+    assert_eq!(resp.nodes.len(), 1);
+    let node_id: NodeId = resp.nodes.pop().unwrap().node_id.parse().unwrap();
+
+    let attempts: i64 = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+        "(SELECT last_value FROM node_insert_attempts)",
+    ))
+    .get_result(&mut conn)
+    .await
+    .unwrap();
+    assert_eq!(attempts, 2);
+
+    // dns records are only created by the committed attempt
+    let node = Node::by_id(node_id, &mut conn).await.unwrap();
+    assert!(!node.dns_id.is_empty());
+}
+
+#[tokio::test]
+async fn create_a_node_that_needs_a_gpu() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    diesel::update(images::table.find(Uuid::parse_str(IMAGE_ID).unwrap()))
+        .set((images::min_gpu_count.eq(1), images::gpu_model.eq("H100")))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let create_req = |launcher| api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launcher),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+
+    // no host has a gpu yet
+    let req = create_req(launch_region(test.seed().region.id, 1));
+    let result = test.send_admin(NodeService::create, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+
+    let update = UpdateHost {
+        gpu_count: Some(1),
+        gpu_model: Some(Some("H100")),
+        ..Default::default()
+    };
+    update.apply(test.seed().host1.id, &mut conn).await.unwrap();
+
+    let req = create_req(launch_region(test.seed().region.id, 1));
+    let mut result = test.send_admin(NodeService::create, req).await.unwrap();
+    let node = result.nodes.pop().unwrap();
+    assert_eq!(node.host_id, test.seed().host1.id.to_string());
+    assert_eq!(node.config.unwrap().vm.unwrap().gpu_count, 1);
+
+    // the only gpu of host1 is now taken
+    let req = create_req(launch_host(test.seed().host1.id, 1));
+    let result = test.send_admin(NodeService::create, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn backorder_a_node_until_a_host_has_capacity() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    diesel::update(images::table.find(Uuid::parse_str(IMAGE_ID).unwrap()))
+        .set((images::min_gpu_count.eq(1), images::gpu_model.eq("H100")))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let list_req = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        limit: 10,
+        ..Default::default()
+    };
+    let before = test
+        .send_admin(NodeService::list, list_req.clone())
+        .await
+        .unwrap();
+
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_region(test.seed().region.id, 1)),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: true,
+    };
+
+    // no host has a gpu, so the node is backordered
+    let resp = test.send_admin(NodeService::create, req).await.unwrap();
+    assert!(resp.nodes.is_empty());
+    let backorder = resp.backorder.unwrap();
+    assert_eq!(backorder.status(), api::BackorderStatus::Pending);
+
+    // retrying without capacity leaves it pending
+    job::fulfil_backorders(test.context()).await.unwrap();
+    let pending = Backorder::pending(&mut conn).await.unwrap();
+    assert_eq!(pending.len(), 1);
+
+    let update = UpdateHost {
+        gpu_count: Some(1),
+        gpu_model: Some(Some("H100")),
+        ..Default::default()
+    };
+    update.apply(test.seed().host1.id, &mut conn).await.unwrap();
+
+    // the node is created once a host has room for it
+    job::fulfil_backorders(test.context()).await.unwrap();
+    let pending = Backorder::pending(&mut conn).await.unwrap();
+    assert!(pending.is_empty());
+
+    let after = test.send_admin(NodeService::list, list_req).await.unwrap();
+    assert_eq!(after.total, before.total + 1);
+}
+
 fn launch_host<S: ToString>(host_id: S, node_count: u32) -> common::NodeLauncher {
     common::NodeLauncher {
         launch: Some(common::node_launcher::Launch::ByHost(common::ByHost {
@@ -136,6 +663,7 @@ async fn update_a_node_config() {
             })),
         }),
         cost: None,
+        update_mask: None,
     };
 
     // fails for unknown id
@@ -167,80 +695,836 @@ async fn update_a_node_config() {
 }
 
 #[tokio::test]
-async fn get_an_existing_node() {
-    let test = TestServer::new().await;
-
-    let get_req = |node_id| api::NodeServiceGetRequest { node_id };
-
-    // fails for unknown id
-    let req = get_req(Uuid::new_v4().to_string());
-    let status = test.send_admin(NodeService::get, req).await.unwrap_err();
-    assert_eq!(status.code(), Code::NotFound);
-
-    // ok for an org member
-    let req = get_req(test.seed().node.id.to_string());
-    test.send_admin(NodeService::get, req).await.unwrap();
-
-    validate_commands(&test).await;
-}
-
-#[tokio::test]
-async fn start_and_stop_a_node() {
+async fn update_a_node_config_with_a_mask() {
     let test = TestServer::new().await;
     let node_id = test.seed().node.id;
 
-    let req = api::NodeServiceStartRequest {
-        node_id: node_id.to_string(),
-    };
-    test.send_admin(NodeService::start, req).await.unwrap();
-
-    let req = api::NodeServiceStopRequest {
+    let req = api::NodeServiceUpdateConfigRequest {
         node_id: node_id.to_string(),
+        auto_upgrade: Some(true),
+        new_display_name: Some("masked-node".to_string()),
+        new_note: Some("a note to clear".to_string()),
+        ..Default::default()
     };
-    test.send_admin(NodeService::stop, req).await.unwrap();
+    test.send_admin(NodeService::update_config, req)
+        .await
+        .unwrap();
 
-    let req = api::NodeServiceRestartRequest {
+    // a masked field that is unset is cleared, and other fields are unchanged
+    let req = api::NodeServiceUpdateConfigRequest {
         node_id: node_id.to_string(),
+        auto_upgrade: Some(false),
+        update_mask: Some(FieldMask {
+            paths: vec!["new_display_name".to_string(), "new_note".to_string()],
+        }),
+        ..Default::default()
     };
-    test.send_admin(NodeService::restart, req).await.unwrap();
+    test.send_admin(NodeService::update_config, req)
+        .await
+        .unwrap();
 
-    validate_commands(&test).await;
+    let mut conn = test.conn().await;
+    let node = Node::by_id(node_id, &mut conn).await.unwrap();
+    assert!(node.auto_upgrade);
+    assert_eq!(node.note, None);
+    assert_eq!(node.display_name, node.node_name);
 }
 
 #[tokio::test]
-async fn report_a_node_status() {
+async fn update_a_node_config_with_an_unknown_mask_path() {
     let test = TestServer::new().await;
-    let node = &test.seed().node;
 
-    let report_req = || api::NodeServiceReportStatusRequest {
-        node_id: node.id.to_string(),
-        config_id: node.config_id.to_string(),
-        status: Some(common::NodeStatus {
-            state: common::NodeState::Stopped.into(),
-            next: None,
-            protocol: None,
+    // fails for a path that can't be updated
+    let req = api::NodeServiceUpdateConfigRequest {
+        node_id: test.seed().node.id.to_string(),
+        update_mask: Some(FieldMask {
+            paths: vec!["node_id".to_string()],
         }),
-        p2p_address: None,
+        ..Default::default()
     };
-
-    // fails for an org admin
-    let req = report_req();
     let status = test
-        .send_admin(NodeService::report_status, req)
+        .send_admin(NodeService::update_config, req)
         .await
         .unwrap_err();
-    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
 
-    // ok for org token
-    let perms = Perms::All(hashset! {
-        NodePerm::ReportStatus.into(),
-        ProtocolPerm::ViewPublic.into()
-    });
-    let jwt = test.org_jwt(perms);
-    let req = report_req();
+#[tokio::test]
+async fn update_a_node_config_rejects_secrets() {
+    let test = TestServer::new().await;
+
+    // fails for a note containing a seed phrase
+    let req = api::NodeServiceUpdateConfigRequest {
+        node_id: test.seed().node.id.to_string(),
+        new_note: Some(
+            "abandon ability able about above absent absorb abstract absurd abuse access accident"
+                .to_string(),
+        ),
+        ..Default::default()
+    };
+    let status = test
+        .send_admin(NodeService::update_config, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn update_a_node_within_org_policy() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id.to_string();
+
+    let req = api::OrgServiceUpdateRequest {
+        org_id: test.seed().org.id.to_string(),
+        name: None,
+        agent_upgrade_policy: None,
+        disk_quota_bytes: None,
+        disk_auto_approve_bytes: None,
+        node_display_name_max_len: Some(12),
+        node_note_max_len: Some(8),
+        node_display_name_unique: Some(true),
+        node_display_name_charset: Some(api::NodeNameCharset::DnsLabel.into()),
+        require_approval_node_delete: None,
+        require_approval_node_upgrade: None,
+        require_approval_org_transfer: None,
+    };
+    test.send_admin(OrgService::update, req).await.unwrap();
+
+    let update_req = |node_id: &str, name: &str| api::NodeServiceUpdateConfigRequest {
+        node_id: node_id.to_string(),
+        new_display_name: Some(name.to_string()),
+        ..Default::default()
+    };
+
+    // fails for a name that is too long or has the wrong characters
+    for name in ["eth-node-too-long", "Eth Node"] {
+        let req = update_req(&node_id, name);
+        let status = test
+            .send_admin(NodeService::update_config, req)
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let bad_request = status.get_details_bad_request().unwrap();
+        assert_eq!(bad_request.field_violations[0].field, "display_name");
+    }
+
+    // fails for a note that is too long
+    let req = api::NodeServiceUpdateConfigRequest {
+        node_id: node_id.clone(),
+        new_note: Some("milk, eggs and bread".to_string()),
+        ..Default::default()
+    };
+    let status = test
+        .send_admin(NodeService::update_config, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = update_req(&node_id, "eth-node");
+    test.send_admin(NodeService::update_config, req)
+        .await
+        .unwrap();
+
+    // fails for a name used by another node of the org
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(launch_host(test.seed().host1.id, 1)),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let mut created = test.send_admin(NodeService::create, req).await.unwrap();
+    let other_id = created.nodes.pop().unwrap().node_id;
+
+    let req = update_req(&other_id, "eth-node");
+    let status = test
+        .send_admin(NodeService::update_config, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn resize_a_node() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    let resize_req = |cpu_cores, memory_bytes| api::NodeServiceResizeRequest {
+        node_id: node_id.to_string(),
+        cpu_cores,
+        memory_bytes,
+    };
+
+    // members may not resize nodes
+    let req = resize_req(Some(2), None);
+    let status = test
+        .send_member(NodeService::resize, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // something must be resized
+    let req = resize_req(None, None);
+    let status = test.send_admin(NodeService::resize, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let memory_bytes = u64::try_from(2 * MEMORY_BYTES).unwrap();
+    let req = resize_req(Some(2), Some(memory_bytes));
+    let resp = test.send_admin(NodeService::resize, req).await.unwrap();
+    let vm = resp.node.unwrap().config.unwrap().vm.unwrap();
+    assert_eq!(vm.cpu_cores, 2);
+    assert_eq!(vm.memory_bytes, memory_bytes);
+
+    let mut conn = test.conn().await;
+    let node = Node::by_id(node_id, &mut conn).await.unwrap();
+    assert_eq!(node.cpu_cores, 2);
+    let logs = NodeLog::by_node(&node, &mut conn).await.unwrap();
+    assert!(logs.iter().any(|log| log.event == NodeEvent::Resized));
+
+    // the host of the node only has 100 cpu cores
+    let req = resize_req(Some(102), None);
+    let status = test.send_admin(NodeService::resize, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    validate_commands(&test).await;
+}
+
+#[tokio::test]
+async fn resize_a_node_disk() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    let resize_req = |disk_bytes: i64| api::NodeServiceResizeDiskRequest {
+        node_id: node_id.to_string(),
+        disk_bytes: disk_bytes.try_into().unwrap(),
+        used_disk_bytes: Some((DISK_BYTES - 1).try_into().unwrap()),
+    };
+    let org_req = |quota: Option<i64>, auto_approve: Option<i64>| api::OrgServiceUpdateRequest {
+        org_id: ORG_ID.into(),
+        name: None,
+        agent_upgrade_policy: None,
+        disk_quota_bytes: quota.map(|bytes| bytes.try_into().unwrap()),
+        disk_auto_approve_bytes: auto_approve.map(|bytes| bytes.try_into().unwrap()),
+        node_display_name_max_len: None,
+        node_note_max_len: None,
+        node_display_name_unique: None,
+        node_display_name_charset: None,
+        require_approval_node_delete: None,
+        require_approval_node_upgrade: None,
+        require_approval_org_transfer: None,
+    };
+
+    // members may not resize disks
+    let req = resize_req(2 * DISK_BYTES);
+    let status = test
+        .send_member(NodeService::resize_disk, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // without an auto approve cap the resize waits for review
+    let req = resize_req(2 * DISK_BYTES);
+    let resp = test
+        .send_admin(NodeService::resize_disk, req)
+        .await
+        .unwrap();
+    let resize = resp.resize.unwrap();
+    assert_eq!(resize.status(), api::DiskResizeStatus::Pending);
+
+    // a node has at most one pending resize
+    let req = resize_req(3 * DISK_BYTES);
+    let status = test
+        .send_admin(NodeService::resize_disk, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    let req = api::NodeServiceListDiskResizesRequest {
+        org_id: ORG_ID.into(),
+        status: Some(api::DiskResizeStatus::Pending.into()),
+    };
+    let resp = test
+        .send_member(NodeService::list_disk_resizes, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.resizes.len(), 1);
+
+    let req = api::NodeServiceReviewDiskResizeRequest {
+        resize_id: resize.resize_id,
+        approve: true,
+    };
+    let resp = test
+        .send_admin(NodeService::review_disk_resize, req)
+        .await
+        .unwrap();
+    let resize = resp.resize.unwrap();
+    assert_eq!(resize.status(), api::DiskResizeStatus::Approved);
+    assert!(resize.command_id.is_some());
+
+    let mut conn = test.conn().await;
+    let node = Node::by_id(node_id, &mut conn).await.unwrap();
+    assert_eq!(node.disk_bytes, 2 * DISK_BYTES);
+
+    // resizes within the auto approve cap are applied straight away
+    let req = org_req(None, Some(4 * DISK_BYTES));
+    test.send_admin(OrgService::update, req).await.unwrap();
+    let req = resize_req(3 * DISK_BYTES);
+    let resp = test
+        .send_admin(NodeService::resize_disk, req)
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.resize.unwrap().status(),
+        api::DiskResizeStatus::Approved
+    );
+
+    // only blockjoy sets the disk quota of an org
+    let req = org_req(Some(3 * DISK_BYTES), None);
+    let status = test.send_admin(OrgService::update, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let req = org_req(Some(3 * DISK_BYTES), None);
+    test.send_super(OrgService::update, req).await.unwrap();
+
+    let req = resize_req(4 * DISK_BYTES);
+    let status = test
+        .send_admin(NodeService::resize_disk, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    // the host of the node only has 100 TiB of disk
+    let req = org_req(Some(0), Some(0));
+    test.send_super(OrgService::update, req).await.unwrap();
+    let req = resize_req(102 * DISK_BYTES);
+    let status = test
+        .send_admin(NodeService::resize_disk, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    validate_commands(&test).await;
+}
+
+#[tokio::test]
+async fn bulk_update_the_nodes_of_an_org() {
+    let test = TestServer::new().await;
+    let filter = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        limit: 10,
+        ..Default::default()
+    };
+
+    // a filter is required
+    let req = api::NodeServiceBulkUpdateRequest {
+        add_tags: vec!["housekeeping".to_string()],
+        ..Default::default()
+    };
+    let status = test
+        .send_admin(NodeService::bulk_update, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // an update must change something
+    let req = api::NodeServiceBulkUpdateRequest {
+        filter: Some(filter.clone()),
+        ..Default::default()
+    };
+    let status = test
+        .send_admin(NodeService::bulk_update, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = api::NodeServiceBulkUpdateRequest {
+        filter: Some(filter.clone()),
+        display_name_template: Some("eth-{oops}".to_string()),
+        ..Default::default()
+    };
+    let status = test
+        .send_admin(NodeService::bulk_update, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = api::NodeServiceBulkUpdateRequest {
+        filter: Some(filter.clone()),
+        add_tags: vec!["housekeeping".to_string()],
+        new_note: Some("checked".to_string()),
+        auto_upgrade: Some(false),
+        display_name_template: Some("eth-{index}".to_string()),
+        ..Default::default()
+    };
+    let resp = test
+        .send_admin(NodeService::bulk_update, req)
+        .await
+        .unwrap();
+    assert!(!resp.results.is_empty());
+
+    for (index, result) in resp.results.into_iter().enumerate() {
+        assert_eq!(result.error, None);
+        let node = result.node.unwrap();
+        assert_eq!(node.display_name, format!("eth-{}", index + 1));
+        assert_eq!(node.note.as_deref(), Some("checked"));
+        assert!(!node.auto_upgrade);
+        let tags = node.tags.unwrap().tags;
+        assert!(tags.iter().any(|tag| tag.name == "housekeeping"));
+    }
+
+    // removing a tag leaves the others
+    let req = api::NodeServiceBulkUpdateRequest {
+        filter: Some(filter),
+        remove_tags: vec!["housekeeping".to_string()],
+        ..Default::default()
+    };
+    let resp = test
+        .send_admin(NodeService::bulk_update, req)
+        .await
+        .unwrap();
+    for result in resp.results {
+        let tags = result.node.unwrap().tags.unwrap().tags;
+        assert!(tags.iter().all(|tag| tag.name != "housekeeping"));
+    }
+}
+
+#[tokio::test]
+async fn add_and_remove_a_dns_alias() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id.to_string();
+
+    let add_req = |name: &str| api::NodeServiceAddDnsAliasRequest {
+        node_id: node_id.clone(),
+        name: name.to_string(),
+        alias_type: api::DnsAliasType::Cname.into(),
+    };
+
+    // aliases must be within an allowed zone
+    let req = add_req("rpc.example.org");
+    let status = test
+        .send_admin(NodeService::add_dns_alias, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = add_req("My-RPC.n0des.xyz");
+    let resp = test
+        .send_admin(NodeService::add_dns_alias, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.alias.unwrap().name, "my-rpc.n0des.xyz");
+
+    // the same name can't be used twice
+    let req = add_req("my-rpc.n0des.xyz");
+    let status = test
+        .send_admin(NodeService::add_dns_alias, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    let req = api::NodeServiceGetRequest {
+        node_id: node_id.clone(),
+    };
+    let node = test.send_admin(NodeService::get, req).await.unwrap();
+    assert_eq!(node.node.unwrap().dns_aliases.len(), 1);
+
+    let remove_req = || api::NodeServiceRemoveDnsAliasRequest {
+        node_id: node_id.clone(),
+        name: "my-rpc.n0des.xyz".to_string(),
+    };
+    test.send_admin(NodeService::remove_dns_alias, remove_req())
+        .await
+        .unwrap();
+    let status = test
+        .send_admin(NodeService::remove_dns_alias, remove_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn rename_a_node() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+    let node_id = node.id.to_string();
+
+    let rename_req = |name: &str| api::NodeServiceRenameRequest {
+        node_id: node_id.clone(),
+        new_node_name: Some(name.to_string()),
+    };
+
+    // the new name must be a DNS label
+    let status = test
+        .send_admin(NodeService::rename, rename_req("blue.whale"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let status = test
+        .send_admin(NodeService::rename, rename_req(&node.node_name))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let resp = test
+        .send_admin(NodeService::rename, rename_req("Blue-Whale"))
+        .await
+        .unwrap();
+    let renamed = resp.node.unwrap();
+    assert_eq!(renamed.node_name, "blue-whale");
+    assert!(renamed.dns_name.contains("blue-whale."));
+
+    // the old name is kept as an expiring alias
+    let old_name = format!("{}.", node.node_name);
+    let alias = renamed
+        .dns_aliases
+        .iter()
+        .find(|alias| alias.name.starts_with(&old_name))
+        .unwrap();
+    assert!(alias.expires_at.is_some());
+
+    // a generated name is used without one
+    let req = api::NodeServiceRenameRequest {
+        node_id: node_id.clone(),
+        new_node_name: None,
+    };
+    let resp = test.send_admin(NodeService::rename, req).await.unwrap();
+    assert_ne!(resp.node.unwrap().node_name, "blue-whale");
+}
+
+#[tokio::test]
+async fn request_a_node_certificate() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+    let node_id = node.id.to_string();
+
+    let get_req = || api::NodeServiceGetCertificateRequest {
+        node_id: node_id.clone(),
+    };
+    let status = test
+        .send_admin(NodeService::get_certificate, get_req())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let req = api::NodeServiceAddDnsAliasRequest {
+        node_id: node_id.clone(),
+        name: "rpc.n0des.xyz".to_string(),
+        alias_type: api::DnsAliasType::Cname.into(),
+    };
+    test.send_admin(NodeService::add_dns_alias, req)
+        .await
+        .unwrap();
+
+    // covers the node name and its aliases
+    let req = api::NodeServiceRequestCertificateRequest {
+        node_id: node_id.clone(),
+    };
+    let resp = test
+        .send_admin(NodeService::request_certificate, req)
+        .await
+        .unwrap();
+    let certificate = resp.certificate.unwrap();
+    let node_name = format!("{}.n0des.xyz", node.node_name);
+    assert_eq!(
+        certificate.names,
+        vec![node_name, "rpc.n0des.xyz".to_string()]
+    );
+    assert_eq!(certificate.status(), api::CertificateStatus::Pending);
+
+    let resp = test
+        .send_admin(NodeService::get_certificate, get_req())
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.certificate.unwrap().certificate_id,
+        certificate.certificate_id
+    );
+}
+
+#[tokio::test]
+async fn get_an_existing_node() {
+    let test = TestServer::new().await;
+
+    let get_req = |node_id| api::NodeServiceGetRequest { node_id };
+
+    // fails for unknown id
+    let req = get_req(Uuid::new_v4().to_string());
+    let status = test.send_admin(NodeService::get, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    // ok for an org member
+    let req = get_req(test.seed().node.id.to_string());
+    test.send_admin(NodeService::get, req).await.unwrap();
+
+    validate_commands(&test).await;
+}
+
+#[tokio::test]
+async fn get_an_unknown_node_has_a_reason() {
+    let test = TestServer::new().await;
+
+    let req = api::NodeServiceGetRequest {
+        node_id: Uuid::new_v4().to_string(),
+    };
+    let status = test.send_admin(NodeService::get, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, "NODE_NOT_FOUND");
+    assert_eq!(info.metadata["retryable"], "false");
+}
+
+#[tokio::test]
+async fn errors_carry_reasons_and_field_violations() {
+    let test = TestServer::new().await;
+
+    let get_req = |node_id: &str| api::NodeServiceGetRequest {
+        node_id: node_id.to_string(),
+    };
+
+    // an unparseable id names the offending field
+    let status = test
+        .send_admin(NodeService::get, get_req("not-a-uuid"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, "INVALID_FIELDS");
+    assert_eq!(info.domain, "api.blockjoy.com");
+    assert_eq!(info.metadata["retryable"], "false");
+    let bad_request = status.get_details_bad_request().unwrap();
+    assert_eq!(bad_request.field_violations.len(), 1);
+    assert_eq!(bad_request.field_violations[0].field, "node_id");
+
+    let node_id = test.seed().node.id.to_string();
+
+    // a missing auth header falls back to the reason of the code
+    let status = test
+        .send_unauthenticated(NodeService::get, get_req(&node_id))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, "UNAUTHENTICATED");
+    assert!(status.get_details_bad_request().is_none());
+
+    // while an expired token has its own reason
+    let claims = Claims::from_now(
+        chrono::TimeDelta::minutes(-10),
+        test.seed().admin.id,
+        NodePerm::Get,
+    );
+    let jwt = test.cipher().jwt.encode(&claims).unwrap();
+    let status = test
+        .send_with(NodeService::get, get_req(&node_id), &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, "TOKEN_EXPIRED");
+    assert_eq!(info.metadata["retryable"], "false");
+}
+
+#[tokio::test]
+async fn get_a_node_while_the_pool_is_exhausted() {
+    let test = TestServer::new().await;
+    let max_conns = test.context().config.database.max_conns;
+    let jwt = test.admin_jwt().await;
+
+    // hold every connection until the next acquisition times out
+    let mut held = Vec::new();
+    let err = loop {
+        match test.context().conn().await {
+            Ok(conn) => held.push(conn),
+            Err(err) => break err,
+        }
+    };
+    assert!(matches!(err, database::Error::AcquireTimeout(_)));
+    assert!(held.len() <= usize::try_from(max_conns).unwrap());
+
+    let req = || api::NodeServiceGetRequest {
+        node_id: test.seed().node.id.to_string(),
+    };
+
+    // requests fail as unavailable rather than waiting indefinitely
+    let status = test
+        .send_with(NodeService::get, req(), &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, "DATABASE_BUSY");
+
+    // and succeed again once connections are released
+    drop(held);
+    test.send_with(NodeService::get, req(), &jwt).await.unwrap();
+}
+
+#[tokio::test]
+async fn get_a_node_with_a_hidden_protocol() {
+    let test = TestServer::new().await;
+
+    let req = || api::NodeServiceGetRequest {
+        node_id: test.seed().node.id.to_string(),
+    };
+
+    // not found without visibility of the node protocol
+    let jwt = test.org_jwt(Perms::All(hashset! { NodePerm::Get.into() }));
+    let status = test
+        .send_with(NodeService::get, req(), &jwt)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    // ok once the protocol is visible
+    let perms = Perms::All(hashset! { NodePerm::Get.into(), ProtocolPerm::ViewPublic.into() });
+    let jwt = test.org_jwt(perms);
+    test.send_with(NodeService::get, req(), &jwt).await.unwrap();
+}
+
+#[tokio::test]
+async fn inconsistent_nodes_are_an_internal_error() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let query = format!(
+        "UPDATE hosts SET deleted_at = now() WHERE id = '{}';",
+        test.seed().host1.id
+    );
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+
+    // a node without its host is not reported as missing
+    let req = api::NodeServiceGetRequest {
+        node_id: test.seed().node.id.to_string(),
+    };
+    let status = test.send_admin(NodeService::get, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::Internal);
+
+    // nor silently left out of a list
+    let req = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        limit: 10,
+        ..Default::default()
+    };
+    let status = test.send_admin(NodeService::list, req).await.unwrap_err();
+    assert_eq!(status.code(), Code::Internal);
+}
+
+#[tokio::test]
+async fn start_and_stop_a_node() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    let req = api::NodeServiceStartRequest {
+        node_id: node_id.to_string(),
+        execute_at: None,
+    };
+    test.send_admin(NodeService::start, req).await.unwrap();
+
+    let req = api::NodeServiceStopRequest {
+        node_id: node_id.to_string(),
+        execute_at: None,
+    };
+    test.send_admin(NodeService::stop, req).await.unwrap();
+
+    let req = api::NodeServiceRestartRequest {
+        node_id: node_id.to_string(),
+        execute_at: None,
+    };
+    test.send_admin(NodeService::restart, req).await.unwrap();
+
+    validate_commands(&test).await;
+}
+
+#[tokio::test]
+async fn report_a_node_status() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+
+    let report_req = || api::NodeServiceReportStatusRequest {
+        node_id: node.id.to_string(),
+        config_id: node.config_id.to_string(),
+        status: Some(common::NodeStatus {
+            state: common::NodeState::Stopped.into(),
+            next: None,
+            protocol: None,
+        }),
+        p2p_address: None,
+    };
+
+    // fails for an org admin
+    let req = report_req();
+    let status = test
+        .send_admin(NodeService::report_status, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // ok for org token
+    let perms = Perms::All(hashset! {
+        NodePerm::ReportStatus.into(),
+        ProtocolPerm::ViewPublic.into()
+    });
+    let jwt = test.org_jwt(perms);
+    let req = report_req();
+    test.send_with(NodeService::report_status, req, &jwt)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn reject_a_node_restarting_without_a_command() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+
+    let report_req = |state: common::NodeState| api::NodeServiceReportStatusRequest {
+        node_id: node.id.to_string(),
+        config_id: node.config_id.to_string(),
+        status: Some(common::NodeStatus {
+            state: state.into(),
+            next: None,
+            protocol: None,
+        }),
+        p2p_address: None,
+    };
+
+    let perms = Perms::All(hashset! {
+        NodePerm::ReportStatus.into(),
+        ProtocolPerm::ViewPublic.into()
+    });
+    let jwt = test.org_jwt(perms);
+
+    // a running node can't report starting without a restart command
+    let req = report_req(common::NodeState::Starting);
+    test.send_with(NodeService::report_status, req, &jwt)
+        .await
+        .unwrap();
+
+    let mut conn = test.conn().await;
+    let updated = Node::by_id(node.id, &mut conn).await.unwrap();
+    assert_eq!(updated.node_state, NodeState::Running);
+    let logs = NodeLog::by_node(&updated, &mut conn).await.unwrap();
+    assert!(logs.iter().any(|log| log.event == NodeEvent::StateRejected));
+
+    // but it can once restarted
+    let req = api::NodeServiceRestartRequest {
+        node_id: node.id.to_string(),
+        execute_at: None,
+    };
+    test.send_admin(NodeService::restart, req).await.unwrap();
+
+    let req = report_req(common::NodeState::Starting);
     test.send_with(NodeService::report_status, req, &jwt)
         .await
         .unwrap();
+    let updated = Node::by_id(node.id, &mut conn).await.unwrap();
+    assert_eq!(updated.node_state, NodeState::Starting);
 }
 
 #[tokio::test]
@@ -266,6 +1550,174 @@ async fn delete_an_existing_node() {
     validate_commands(&test).await;
 }
 
+#[tokio::test]
+async fn delete_cleans_up_a_node_in_the_background() {
+    let test = TestServer::new().await;
+
+    let req = api::NodeServiceDeleteRequest {
+        node_id: test.seed().node.id.to_string(),
+    };
+    test.send_admin(NodeService::delete, req).await.unwrap();
+
+    // external state is removed in the background once committed
+    let mut cleanup = None;
+    for _ in 0..20 {
+        let mut conn = test.conn().await;
+        let node = Node::deleted_by_id(test.seed().node.id, &mut conn)
+            .await
+            .unwrap();
+        cleanup = node.cleanup;
+        if cleanup == Some(NodeCleanup::Complete) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(cleanup, Some(NodeCleanup::Complete));
+}
+
+#[tokio::test]
+async fn failed_node_cleanup_is_retried() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    // the next DNS call fails
+    let url = format!("http://{}/v1/chaos", test.socket_addr());
+    let armed: serde_json::Value = reqwest::Client::new()
+        .put(&url)
+        .json(&serde_json::json!({ "fail_dns": 1 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(armed["fail_dns"], 1);
+
+    let req = api::NodeServiceDeleteRequest {
+        node_id: node_id.to_string(),
+    };
+    test.send_admin(NodeService::delete, req).await.unwrap();
+
+    let mut failed = None;
+    for _ in 0..20 {
+        let mut conn = test.conn().await;
+        let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+        if node.cleanup_error.is_some() {
+            failed = Some(node);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let node = failed.expect("cleanup should have failed");
+    assert_eq!(node.cleanup, Some(NodeCleanup::RemoveDns));
+    assert_eq!(node.cleanup_attempts, 1);
+
+    // the retry resumes from the failed step
+    let mut conn = test.conn().await;
+    NodeCleanup::resume(&node, test.context(), &mut conn)
+        .await
+        .unwrap();
+    let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+    assert_eq!(node.cleanup, Some(NodeCleanup::Complete));
+    assert_eq!(node.cleanup_error, None);
+}
+
+#[tokio::test]
+async fn http_delete_cleans_up_the_node() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    let jwt = test.admin_jwt().await;
+    let url = format!("http://{}/v1/node/{node_id}", test.socket_addr());
+    let resp = reqwest::Client::new()
+        .delete(&url)
+        .bearer_auth(&*jwt)
+        .json(&serde_json::json!({ "node_id": node_id.to_string() }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let mut cleanup = None;
+    for _ in 0..20 {
+        let mut conn = test.conn().await;
+        let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+        cleanup = node.cleanup;
+        if cleanup == Some(NodeCleanup::Complete) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(cleanup, Some(NodeCleanup::Complete));
+}
+
+#[tokio::test]
+async fn node_cleanup_is_claimed_once() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    // the cleanup spawned by the delete fails
+    let url = format!("http://{}/v1/chaos", test.socket_addr());
+    reqwest::Client::new()
+        .put(&url)
+        .json(&serde_json::json!({ "fail_dns": 1 }))
+        .send()
+        .await
+        .unwrap();
+    let req = api::NodeServiceDeleteRequest {
+        node_id: node_id.to_string(),
+    };
+    test.send_admin(NodeService::delete, req).await.unwrap();
+
+    let mut failed = None;
+    for _ in 0..20 {
+        let mut conn = test.conn().await;
+        let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+        if node.cleanup_error.is_some() {
+            failed = Some(node);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let node = failed.expect("cleanup should have failed");
+    assert!(node.cleanup_claimed_until.unwrap() > chrono::Utc::now());
+
+    // a failed node stays claimed until it may be retried
+    let mut conn = test.conn().await;
+    let claimed = NodeCleanup::claim(node_id, &mut conn).await.unwrap();
+    assert!(claimed.is_none());
+    let claimed = NodeCleanup::claim_next(&mut conn).await.unwrap();
+    assert!(claimed.is_none());
+
+    // once retryable, only one of two concurrent claims gets the node
+    let past = chrono::Utc::now() - chrono::TimeDelta::hours(1);
+    diesel::update(nodes::table.find(node_id))
+        .set((
+            nodes::deleted_at.eq(past),
+            nodes::cleanup_claimed_until.eq(past),
+        ))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let mut other = test.conn().await;
+    let (first, second) = tokio::join!(
+        NodeCleanup::claim_next(&mut conn),
+        NodeCleanup::claim_next(&mut other),
+    );
+    let claims = [first.unwrap(), second.unwrap()];
+    assert_eq!(claims.iter().flatten().count(), 1);
+    let node = claims.into_iter().flatten().next().unwrap();
+    assert_eq!(node.id, node_id);
+
+    NodeCleanup::resume(&node, test.context(), &mut conn)
+        .await
+        .unwrap();
+    let node = Node::deleted_by_id(node_id, &mut conn).await.unwrap();
+    assert_eq!(node.cleanup, Some(NodeCleanup::Complete));
+    assert_eq!(node.cleanup_claimed_until, None);
+}
+
 async fn validate_commands(test: &TestServer) {
     let mut conn = test.conn().await;
     let commands: Vec<Command> = commands::table
@@ -276,3 +1728,282 @@ async fn validate_commands(test: &TestServer) {
 
     assert!(commands.is_empty());
 }
+
+#[tokio::test]
+async fn watch_streams_node_updates() {
+    let test = TestServer::new().await;
+    let node = &test.seed().node;
+
+    let req = api::NodeServiceWatchRequest {
+        org_ids: vec![test.seed().org.id.to_string()],
+        host_ids: vec![],
+        node_ids: vec![node.id.to_string()],
+        node_states: vec![],
+    };
+    let mut stream = test.send_admin(NodeService::watch, req).await.unwrap();
+
+    let req = api::NodeServiceUpdateConfigRequest {
+        node_id: node.id.to_string(),
+        auto_upgrade: None,
+        new_org_id: None,
+        new_display_name: Some("watched".to_string()),
+        new_note: None,
+        new_values: vec![],
+        new_firewall: None,
+        update_tags: None,
+        cost: None,
+        update_mask: None,
+    };
+    test.send_admin(NodeService::update_config, req)
+        .await
+        .unwrap();
+
+    let resp = tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let Some(api::node_message::Message::Updated(updated)) = resp.event.unwrap().message else {
+        panic!("expected a node update");
+    };
+    assert_eq!(updated.node.unwrap().display_name, "watched");
+    assert_eq!(resp.previous_state, None);
+}
+
+#[tokio::test]
+async fn watch_hides_the_nodes_of_a_restricted_project() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let node = &test.seed().node;
+
+    let req = api::OrgServiceCreateProjectRequest {
+        org_id: ORG_ID.into(),
+        name: "private".to_string(),
+        description: None,
+        node_quota: None,
+    };
+    let resp = test
+        .send_admin(OrgService::create_project, req)
+        .await
+        .unwrap();
+    let project_id = resp.project.unwrap().project_id;
+
+    // only the admin is a member of the project of the node
+    let admin_id = test.seed().admin.id;
+    let query = format!(
+        "INSERT INTO project_members (project_id, user_id) VALUES ('{project_id}', '{admin_id}');"
+    );
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+    let query = format!(
+        "UPDATE nodes SET project_id = '{project_id}' WHERE id = '{}';",
+        node.id
+    );
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+
+    let watch_req = || api::NodeServiceWatchRequest {
+        org_ids: vec![ORG_ID.into()],
+        host_ids: vec![],
+        node_ids: vec![],
+        node_states: vec![],
+    };
+    let mut admin_stream = test
+        .send_admin(NodeService::watch, watch_req())
+        .await
+        .unwrap();
+    let mut member_stream = test
+        .send_member(NodeService::watch, watch_req())
+        .await
+        .unwrap();
+
+    let req = api::NodeServiceUpdateConfigRequest {
+        node_id: node.id.to_string(),
+        new_display_name: Some("private".to_string()),
+        ..Default::default()
+    };
+    test.send_admin(NodeService::update_config, req)
+        .await
+        .unwrap();
+
+    // the project member sees the update
+    let resp = tokio::time::timeout(Duration::from_secs(5), admin_stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(resp.event.is_some());
+
+    // while another org member does not
+    let result = tokio::time::timeout(Duration::from_millis(500), member_stream.message()).await;
+    assert!(result.is_err(), "unexpected event: {result:?}");
+}
+
+#[tokio::test]
+async fn watch_ends_when_the_server_shuts_down() {
+    let test = TestServer::new().await;
+
+    let req = api::NodeServiceWatchRequest {
+        org_ids: vec![ORG_ID.into()],
+        host_ids: vec![],
+        node_ids: vec![],
+        node_states: vec![],
+    };
+    let mut stream = test.send_admin(NodeService::watch, req).await.unwrap();
+
+    test.context().shutdown.start();
+
+    let status = tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unavailable);
+    let info = status.get_details_error_info().unwrap();
+    assert_eq!(info.reason, "SHUTTING_DOWN");
+    assert_eq!(info.metadata["retryable"], "true");
+}
+
+#[tokio::test]
+async fn v2_nodes_keep_every_v1_field() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id.to_string();
+
+    let req = api::NodeServiceAddDnsAliasRequest {
+        node_id: node_id.clone(),
+        name: "my-rpc.n0des.xyz".to_string(),
+        alias_type: api::DnsAliasType::Cname.into(),
+    };
+    test.send_admin(NodeService::add_dns_alias, req)
+        .await
+        .unwrap();
+
+    let req = api::NodeServiceGetRequest {
+        node_id: node_id.clone(),
+    };
+    let v1 = test.send_admin(NodeService::get, req).await.unwrap();
+    let v1 = v1.node.unwrap();
+
+    let req = api_v2::NodeServiceGetRequest { node_id };
+    let v2 = test.send_admin(NodeServiceV2::get, req).await.unwrap();
+    let v2 = v2.node.unwrap();
+    assert_eq!(v2, api_v2::Node::from(v1.clone()));
+
+    let spec = v2.spec.unwrap();
+    assert_eq!(spec.host_pool_id, v1.host_pool_id);
+    assert_eq!(spec.placement_policy, v1.placement_policy);
+    assert_eq!(spec.project_id, v1.project_id);
+    assert_eq!(spec.network_id, v1.network_id);
+    assert_eq!(spec.version_metadata, v1.version_metadata);
+
+    let status = v2.status.unwrap();
+    assert_eq!(status.dns_aliases, v1.dns_aliases);
+    assert_eq!(status.dns_aliases.len(), 1);
+    assert_eq!(status.sync_lag, v1.sync_lag);
+    assert_eq!(status.config_drift, v1.config_drift);
+
+    let req = api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        limit: 10,
+        ..Default::default()
+    };
+    let v1 = test
+        .send_admin(NodeService::list, req.clone())
+        .await
+        .unwrap();
+    let v2 = test.send_admin(NodeServiceV2::list, req).await.unwrap();
+    assert_eq!(v2.total, v1.total);
+    let expected: Vec<_> = v1.nodes.into_iter().map(api_v2::Node::from).collect();
+    assert_eq!(v2.nodes, expected);
+}
+
+#[tokio::test]
+async fn collect_a_debug_bundle() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let node_id = test.seed().node.id;
+
+    let req = api::NodeServiceCollectDebugBundleRequest {
+        node_id: node_id.to_string(),
+        log_lines: Some(500),
+    };
+    let command_id = test
+        .send_member(NodeService::collect_debug_bundle, req)
+        .await
+        .unwrap()
+        .command_id;
+    let cmd = Command::by_id(command_id.parse().unwrap(), &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(cmd.command_type, CommandType::NodeDebugBundle);
+    assert_eq!(cmd.node_id, Some(node_id));
+
+    let host_jwt = test.public_host_jwt();
+    let req = api::ArtifactServiceCreateUploadRequest {
+        command_id: command_id.clone(),
+        name: "debug-bundle.tar.gz".to_string(),
+    };
+    let artifact_id = test
+        .send_with(ArtifactService::create_upload, req, &host_jwt)
+        .await
+        .unwrap()
+        .artifact
+        .unwrap()
+        .artifact_id;
+
+    let req = api::CommandServiceUpdateRequest {
+        command_id: command_id.clone(),
+        exit_message: None,
+        exit_code: Some(api::CommandExitCode::Ok.into()),
+        retry_hint_seconds: None,
+        config_hash: None,
+        artifact_ids: vec![artifact_id.clone()],
+    };
+    test.send_with(CommandService::update, req, &host_jwt)
+        .await
+        .unwrap();
+
+    let cmd = Command::by_id(cmd.id, &mut conn).await.unwrap();
+    assert_eq!(cmd.exit_code, Some(ExitCode::Ok));
+
+    let req = api::ArtifactServiceDownloadRequest { artifact_id };
+    test.send_member(ArtifactService::download, req)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn node_messages_continue_the_trace_of_the_request() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let test = TestServer::new().await;
+    let jwt = test.admin_jwt().await;
+    let node_id = test.seed().node.id;
+    let mut packets = mqtt::topic_messages(&format!("/nodes/{node_id}")).await;
+
+    // the api continues the trace of callers that send a `traceparent` header
+    let trace_id = Uuid::new_v4().simple().to_string();
+    let traceparent = format!("00-{trace_id}-00f067aa0ba902b7-01");
+    let mut req = tonic::Request::new(api::NodeServiceUpdateConfigRequest {
+        node_id: node_id.to_string(),
+        new_note: Some("traced".to_string()),
+        update_mask: Some(FieldMask {
+            paths: vec!["new_note".to_string()],
+        }),
+        ..Default::default()
+    });
+    let header = traceparent.parse().unwrap();
+    req.metadata_mut().insert("traceparent", header);
+    test.send_with(NodeService::update_config, req, &jwt)
+        .await
+        .unwrap();
+
+    // other tests also publish messages for the seed node
+    while let Some(packet) = packets.next().await {
+        let properties = packet.properties.unwrap_or_default();
+        let traced = properties
+            .user_properties
+            .iter()
+            .any(|(key, value)| key == "traceparent" && value.contains(&trace_id));
+        if traced {
+            return;
+        }
+    }
+    panic!("No node message continued trace {trace_id}");
+}
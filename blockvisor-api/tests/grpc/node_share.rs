@@ -0,0 +1,104 @@
+use blockvisor_api::database::seed::NODE_ID;
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::NodeShare;
+use blockvisor_api::util::NanosUtc;
+use chrono::{TimeDelta, Utc};
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{NodeShareService, SocketRpc};
+
+#[tokio::test]
+async fn share_view_and_revoke_a_node() {
+    let test = TestServer::new().await;
+
+    let req = |days| api::NodeShareServiceCreateRequest {
+        node_id: NODE_ID.to_string(),
+        label: "Delegators".to_string(),
+        expires_at: Some(NanosUtc::from(Utc::now() + TimeDelta::days(days)).into()),
+    };
+
+    // org members can't share nodes
+    let result = test.send_member(NodeShareService::create, req(7)).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // shares expire within 90 days
+    let result = test.send_admin(NodeShareService::create, req(120)).await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+    let result = test.send_admin(NodeShareService::create, req(-1)).await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    let resp = test
+        .send_admin(NodeShareService::create, req(7))
+        .await
+        .unwrap();
+    let share = resp.node_share.unwrap();
+    assert!(resp.url.contains(&share.node_share_id));
+    assert!(resp.url.contains("token="));
+
+    // only the token of the share opens it
+    let mut conn = test.conn().await;
+    let found = NodeShare::by_id(share.node_share_id.parse().unwrap(), &mut conn)
+        .await
+        .unwrap();
+    found.verify(&resp.token).unwrap();
+    assert!(found.verify("bm90LXRoZS10b2tlbg").is_err());
+
+    // repeated views from one ip within a minute are recorded once
+    for _ in 0..3 {
+        found
+            .record_access(
+                Some("203.0.113.7".to_string()),
+                Some("curl/8.0".to_string()),
+                &mut conn,
+            )
+            .await
+            .unwrap();
+    }
+
+    let list = api::NodeShareServiceListRequest {
+        node_id: NODE_ID.to_string(),
+    };
+    let resp_list = test
+        .send_member(NodeShareService::list, list.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp_list.node_shares.len(), 1);
+
+    let accesses = api::NodeShareServiceAccessesRequest {
+        node_share_id: share.node_share_id.clone(),
+    };
+    let result = test
+        .send_unknown(NodeShareService::accesses, accesses.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+    let resp_accesses = test
+        .send_member(NodeShareService::accesses, accesses)
+        .await
+        .unwrap();
+    assert_eq!(resp_accesses.accesses.len(), 1);
+    assert_eq!(
+        resp_accesses.accesses[0].ip_address.as_deref(),
+        Some("203.0.113.7")
+    );
+
+    let revoke = api::NodeShareServiceRevokeRequest {
+        node_share_id: share.node_share_id.clone(),
+    };
+    test.send_admin(NodeShareService::revoke, revoke.clone())
+        .await
+        .unwrap();
+    let result = test.send_admin(NodeShareService::revoke, revoke).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    // revoked shares no longer open
+    let revoked = NodeShare::by_id(share.node_share_id.parse().unwrap(), &mut conn)
+        .await
+        .unwrap();
+    assert!(revoked.verify(&resp.token).is_err());
+    let resp_list = test
+        .send_member(NodeShareService::list, list)
+        .await
+        .unwrap();
+    assert!(resp_list.node_shares.is_empty());
+}
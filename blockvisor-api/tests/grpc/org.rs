@@ -1,13 +1,21 @@
 use blockvisor_api::auth::claims::{Claims, Expirable};
-use blockvisor_api::auth::rbac::InvitationPerm;
-use blockvisor_api::auth::resource::Resource;
+use blockvisor_api::auth::rbac::{EmailRole, InvitationPerm, OrgRole};
+use blockvisor_api::auth::resource::{Resource, UserId};
 use blockvisor_api::database::seed;
 use blockvisor_api::grpc::api;
 use blockvisor_api::model::invitation::NewInvitation;
 use blockvisor_api::model::org::Org;
+use blockvisor_api::model::org_trial;
+use blockvisor_api::model::rbac::RbacUser;
+use chrono::{Datelike, TimeDelta, Timelike, Utc};
+use diesel_async::RunQueryDsl;
+use tonic_types::StatusExt;
+use uuid::Uuid;
 
 use crate::setup::TestServer;
-use crate::setup::helper::traits::{InvitationService, OrgService, SocketRpc};
+use crate::setup::helper::traits::{
+    EndpointService, InvitationService, NodeService, OrgService, SocketRpc,
+};
 
 #[tokio::test]
 async fn can_create_new_org() {
@@ -26,16 +34,83 @@ async fn responds_ok_for_get() {
     test.send_admin(OrgService::get, req).await.unwrap();
 }
 
+#[tokio::test]
+async fn summary_counts_org_nodes() {
+    let test = TestServer::new().await;
+    let req = api::OrgServiceSummaryRequest {
+        org_id: test.seed().org.id.to_string(),
+    };
+    let resp = test.send_admin(OrgService::summary, req).await.unwrap();
+
+    let summary = resp.summary.unwrap();
+    assert_eq!(summary.node_count, 1);
+    assert_eq!(summary.node_states.len(), 1);
+    assert_eq!(summary.protocols[0].id, test.seed().protocol.id.to_string());
+    assert_eq!(summary.hosts[0].count, 1);
+    assert_eq!(summary.regions[0].count, 1);
+}
+
 #[tokio::test]
 async fn responds_ok_for_update() {
     let test = TestServer::new().await;
     let req = api::OrgServiceUpdateRequest {
         org_id: test.seed().org.id.to_string(),
         name: Some("new-org-asdf".to_string()),
+        agent_upgrade_policy: None,
+        disk_quota_bytes: None,
+        disk_auto_approve_bytes: None,
+        node_display_name_max_len: None,
+        node_note_max_len: None,
+        node_display_name_unique: None,
+        node_display_name_charset: None,
+        require_approval_node_delete: None,
+        require_approval_node_upgrade: None,
+        require_approval_org_transfer: None,
     };
     test.send_admin(OrgService::update, req).await.unwrap();
 }
 
+#[tokio::test]
+async fn export_and_import_configuration() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+
+    let req = api::OrgServiceExportConfigurationRequest {
+        org_id: org_id.clone(),
+    };
+    let resp = test
+        .send_admin(OrgService::export_configuration, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.version, 1);
+
+    let snapshot: serde_json::Value = serde_json::from_str(&resp.snapshot).unwrap();
+    let nodes = snapshot["nodes"].as_array().unwrap();
+    assert_eq!(nodes.len(), 1);
+    let display_name = nodes[0]["display_name"].as_str().unwrap().to_string();
+
+    let req = api::OrgServiceImportConfigurationRequest {
+        org_id: org_id.clone(),
+        snapshot: resp.snapshot,
+    };
+    let resp = test
+        .send_admin(OrgService::import_configuration, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.nodes.len(), 1);
+    assert_eq!(resp.nodes[0].display_name, display_name);
+
+    let req = api::OrgServiceImportConfigurationRequest {
+        org_id,
+        snapshot: r#"{"version": 99}"#.to_string(),
+    };
+    let status = test
+        .send_admin(OrgService::import_configuration, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+}
+
 #[tokio::test]
 async fn delete_org() {
     let test = TestServer::new().await;
@@ -46,10 +121,44 @@ async fn delete_org() {
 
     let req = api::OrgServiceDeleteRequest {
         org_id: org.org.unwrap().org_id,
+        force: false,
     };
     test.send_admin(OrgService::delete, req).await.unwrap();
 }
 
+#[tokio::test]
+async fn restore_deleted_org() {
+    let test = TestServer::new().await;
+    let req = api::OrgServiceCreateRequest {
+        name: "new-org".to_string(),
+    };
+    let org = test.send_admin(OrgService::create, req).await.unwrap();
+    let org_id = org.org.unwrap().org_id;
+
+    let req = api::OrgServiceDeleteRequest {
+        org_id: org_id.clone(),
+        force: false,
+    };
+    let resp = test.send_admin(OrgService::delete, req).await.unwrap();
+    assert!(resp.purge_at.is_some());
+
+    let req = api::OrgServiceGetRequest {
+        org_id: org_id.clone(),
+    };
+    let status = test.send_admin(OrgService::get, req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::NotFound);
+
+    let req = api::OrgServiceRestoreDeletedRequest {
+        org_id: org_id.clone(),
+    };
+    test.send_admin(OrgService::restore_deleted, req)
+        .await
+        .unwrap();
+
+    let req = api::OrgServiceGetRequest { org_id };
+    test.send_admin(OrgService::get, req).await.unwrap();
+}
+
 #[tokio::test]
 async fn responds_error_for_delete_on_personal_org() {
     let test = TestServer::new().await;
@@ -60,6 +169,7 @@ async fn responds_error_for_delete_on_personal_org() {
 
     let req = api::OrgServiceDeleteRequest {
         org_id: org.id.to_string(),
+        force: false,
     };
     let status = test.send_admin(OrgService::delete, req).await.unwrap_err();
     assert_eq!(status.code(), tonic::Code::PermissionDenied);
@@ -123,3 +233,407 @@ async fn member_count_works() {
         .unwrap();
     assert_eq!(org_resp.member_count, members + 1);
 }
+
+#[tokio::test]
+async fn removed_members_are_denied_on_their_next_request() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+
+    // the member's roles are cached by a first request
+    let req = api::OrgServiceGetRequest {
+        org_id: org_id.clone(),
+    };
+    test.send_member(OrgService::get, req.clone())
+        .await
+        .unwrap();
+
+    let remove = api::OrgServiceRemoveMemberRequest {
+        org_id,
+        user_id: test.seed().member.id.to_string(),
+    };
+    test.send_admin(OrgService::remove_member, remove)
+        .await
+        .unwrap();
+
+    let status = test.send_member(OrgService::get, req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn member_cannot_transfer_ownership() {
+    let test = TestServer::new().await;
+    let req = api::OrgServiceTransferOwnershipRequest {
+        org_id: test.seed().org.id.to_string(),
+        new_owner_id: test.seed().member.id.to_string(),
+    };
+    let status = test
+        .send_member(OrgService::transfer_ownership, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn owner_can_transfer_ownership() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let org_id = test.seed().org.id;
+    let old_owner = test.seed().admin.id;
+    let new_owner = test.seed().member.id;
+    RbacUser::link_role(old_owner, org_id, OrgRole::Owner, &mut conn)
+        .await
+        .unwrap();
+
+    let req = api::OrgServiceTransferOwnershipRequest {
+        org_id: org_id.to_string(),
+        new_owner_id: new_owner.to_string(),
+    };
+    let resp = test
+        .send_admin(OrgService::transfer_ownership, req)
+        .await
+        .unwrap();
+    assert!(resp.approval.is_none());
+
+    // the transfer waits for both parties to confirm
+    let confirmed = confirm_transfer(&test, &resp.transfer_id, old_owner)
+        .await
+        .unwrap();
+    assert!(!confirmed.completed);
+    let owners = RbacUser::org_owners(org_id, &mut conn).await.unwrap();
+    assert_eq!(owners, vec![old_owner]);
+
+    let confirmed = confirm_transfer(&test, &resp.transfer_id, new_owner)
+        .await
+        .unwrap();
+    assert!(confirmed.completed);
+    let owners = RbacUser::org_owners(org_id, &mut conn).await.unwrap();
+    assert_eq!(owners, vec![new_owner]);
+
+    // a completed transfer can't be confirmed again
+    let status = confirm_transfer(&test, &resp.transfer_id, old_owner)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+async fn confirm_transfer(
+    test: &TestServer,
+    transfer_id: &str,
+    user_id: UserId,
+) -> Result<api::OrgServiceConfirmTransferResponse, tonic::Status> {
+    let expires = TimeDelta::minutes(15);
+    let data = hashmap! { "transfer_id".into() => transfer_id.to_string() };
+    let claims = Claims::from_now(expires, user_id, EmailRole::OrgTransfer).with_data(data);
+    let jwt = test.cipher().jwt.encode(&claims).unwrap();
+    let req = api::OrgServiceConfirmTransferRequest {
+        transfer_id: transfer_id.to_string(),
+    };
+    test.send_with(OrgService::confirm_transfer, req, &jwt)
+        .await
+}
+
+#[tokio::test]
+async fn remove_member_reports_invalid_fields() {
+    let test = TestServer::new().await;
+
+    let req = api::OrgServiceRemoveMemberRequest {
+        org_id: "not-a-uuid".to_string(),
+        user_id: "also-not-a-uuid".to_string(),
+    };
+    let status = test
+        .send_admin(OrgService::remove_member, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+    let bad_request = status.get_details_bad_request().unwrap();
+    let fields: Vec<_> = bad_request
+        .field_violations
+        .iter()
+        .map(|violation| violation.field.as_str())
+        .collect();
+    assert_eq!(fields, ["org_id", "user_id"]);
+}
+
+#[tokio::test]
+async fn mqtt_acl_scopes_org_topics() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let org_id = test.seed().org.id;
+    let admin_id = test.seed().admin.id;
+
+    let req = api::OrgServiceCreateProjectRequest {
+        org_id: org_id.to_string(),
+        name: "private".to_string(),
+        description: None,
+        node_quota: None,
+    };
+    let resp = test
+        .send_admin(OrgService::create_project, req)
+        .await
+        .unwrap();
+    let project_id = resp.project.unwrap().project_id;
+
+    let url = format!("http://{}/mqtt/acl", test.socket_addr());
+    let client = reqwest::Client::new();
+    let admin = String::from(test.admin_jwt().await);
+    let member = String::from(test.member_jwt().await);
+    let acl = |username: &str, topic: String| {
+        let body = serde_json::json!({
+            "operation": "2",
+            "username": username,
+            "topic": topic,
+        });
+        client.post(&url).json(&body).send()
+    };
+
+    let tag_nodes = format!("/orgs/{org_id}/tags/mainnet/nodes");
+    assert_eq!(acl(&member, tag_nodes).await.unwrap().status(), 200);
+    let pool_hosts = format!("/orgs/{org_id}/pools/{}/hosts", Uuid::new_v4());
+    assert_eq!(acl(&member, pool_hosts).await.unwrap().status(), 200);
+    let unknown = format!("/orgs/{org_id}/tags/mainnet/hosts");
+    assert!(!acl(&member, unknown).await.unwrap().status().is_success());
+
+    // a project without members is open to the whole org
+    let project_nodes = format!("/orgs/{org_id}/projects/{project_id}/nodes");
+    let resp = acl(&member, project_nodes.clone()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let query = format!(
+        "INSERT INTO project_members (project_id, user_id) VALUES ('{project_id}', '{admin_id}');"
+    );
+    diesel::sql_query(query).execute(&mut conn).await.unwrap();
+
+    let resp = acl(&member, project_nodes.clone()).await.unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = acl(&admin, project_nodes).await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let missing = format!("/orgs/{org_id}/projects/{}/nodes", Uuid::new_v4());
+    assert_eq!(acl(&member, missing).await.unwrap().status(), 401);
+}
+
+#[tokio::test]
+async fn downgrade_plan_checks_current_usage() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+
+    let req = api::OrgServiceListPlansRequest {
+        org_id: org_id.clone(),
+    };
+    let resp = test.send_member(OrgService::list_plans, req).await.unwrap();
+    assert_eq!(resp.plans.len(), 3);
+    assert_eq!(resp.current_tier(), api::PlanTier::Enterprise);
+
+    let req = api::OrgServiceUpdatePlanRequest {
+        org_id: org_id.clone(),
+        tier: api::PlanTier::Free.into(),
+    };
+    let status = test
+        .send_member(OrgService::update_plan, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    // the seed org has a private host, which the free plan does not include
+    let status = test
+        .send_admin(OrgService::update_plan, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn hibernated_trials_wake_on_conversion() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+
+    let req = api::OrgServiceStartTrialRequest {
+        org_id: org_id.to_string(),
+        tier: api::PlanTier::Pro.into(),
+        days: Some(7),
+        max_nodes: None,
+    };
+    let status = test
+        .send_admin(OrgService::start_trial, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let org = test
+        .send_super(OrgService::start_trial, req)
+        .await
+        .unwrap()
+        .org
+        .unwrap();
+    assert_eq!(org.plan_tier(), api::PlanTier::Pro);
+    assert!(org.trial_ends_at.is_some());
+
+    let mut conn = test.conn().await;
+    org_trial::hibernate(org_id, &mut conn).await.unwrap();
+
+    let endpoint = api::EndpointServiceCreateRequest {
+        org_id: org_id.to_string(),
+        node_id: None,
+        label: "trial".to_string(),
+        allowed_methods: vec![],
+        rate_limit_rps: None,
+        monthly_quota: None,
+    };
+    let status = test
+        .send_admin(EndpointService::create, endpoint.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let req = api::OrgServiceUpdatePlanRequest {
+        org_id: org_id.to_string(),
+        tier: api::PlanTier::Pro.into(),
+    };
+    let org = test
+        .send_super(OrgService::update_plan, req)
+        .await
+        .unwrap()
+        .org
+        .unwrap();
+    assert!(org.trial_ends_at.is_none());
+    assert!(org.hibernated_at.is_none());
+
+    test.send_admin(EndpointService::create, endpoint)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn suspended_orgs_block_members_until_reinstated() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+    let node_id = test.seed().node.id;
+
+    let req = api::OrgServiceSuspendRequest {
+        org_id: org_id.to_string(),
+        reason: api::OrgSuspensionReason::Billing.into(),
+        banner: Some("Your last invoice is overdue.".to_string()),
+        stop_nodes: true,
+    };
+    let status = test
+        .send_admin(OrgService::suspend, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let org = test
+        .send_super(OrgService::suspend, req.clone())
+        .await
+        .unwrap()
+        .org
+        .unwrap();
+    let suspension = org.suspension.unwrap();
+    assert_eq!(suspension.reason(), api::OrgSuspensionReason::Billing);
+
+    let status = test.send_super(OrgService::suspend, req).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    // members still see the org and its banner, but nothing else
+    let get_org = api::OrgServiceGetRequest {
+        org_id: org_id.to_string(),
+    };
+    let org = test
+        .send_admin(OrgService::get, get_org.clone())
+        .await
+        .unwrap()
+        .org
+        .unwrap();
+    assert_eq!(
+        org.suspension.unwrap().banner.as_deref(),
+        Some("Your last invoice is overdue.")
+    );
+
+    let get_node = api::NodeServiceGetRequest {
+        node_id: node_id.to_string(),
+    };
+    let status = test
+        .send_admin(NodeService::get, get_node.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let req = api::OrgServiceReinstateRequest {
+        org_id: org_id.to_string(),
+    };
+    let org = test
+        .send_super(OrgService::reinstate, req.clone())
+        .await
+        .unwrap()
+        .org
+        .unwrap();
+    assert!(org.suspension.is_none());
+
+    test.send_admin(NodeService::get, get_node).await.unwrap();
+
+    let status = test
+        .send_super(OrgService::reinstate, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
+
+#[tokio::test]
+async fn freeze_windows_block_node_deletes_without_override() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+
+    // a window from now until two hours later
+    let now = Utc::now();
+    let later = now + TimeDelta::hours(2);
+    let req = api::OrgServiceCreateFreezeWindowRequest {
+        org_id: org_id.clone(),
+        start_day: now.weekday().num_days_from_monday(),
+        start_minute: now.hour() * 60 + now.minute(),
+        end_day: later.weekday().num_days_from_monday(),
+        end_minute: later.hour() * 60 + later.minute(),
+        reason: Some("release week".to_string()),
+    };
+    let status = test
+        .send_member(OrgService::create_freeze_window, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::PermissionDenied);
+
+    let window = test
+        .send_admin(OrgService::create_freeze_window, req)
+        .await
+        .unwrap()
+        .window
+        .unwrap();
+
+    let req = api::OrgServiceListFreezeWindowsRequest {
+        org_id: org_id.clone(),
+    };
+    let windows = test
+        .send_member(OrgService::list_freeze_windows, req)
+        .await
+        .unwrap()
+        .windows;
+    assert_eq!(windows.len(), 1);
+
+    let delete_node = || api::NodeServiceDeleteRequest {
+        node_id: test.seed().node.id.to_string(),
+    };
+    let status = test
+        .send_admin(NodeService::delete, delete_node())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+    let req = api::OrgServiceDeleteFreezeWindowRequest {
+        window_id: window.window_id,
+    };
+    test.send_admin(OrgService::delete_freeze_window, req)
+        .await
+        .unwrap();
+
+    test.send_admin(NodeService::delete, delete_node())
+        .await
+        .unwrap();
+}
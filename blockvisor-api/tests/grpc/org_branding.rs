@@ -0,0 +1,94 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::OrgBranding;
+use blockvisor_api::model::plan::PlanTier;
+use blockvisor_api::model::schema::orgs;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{OrgBrandingService, SocketRpc};
+
+#[tokio::test]
+async fn enterprise_orgs_can_brand_emails_and_status_pages() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+
+    let req = |primary_color: &str| api::OrgBrandingServiceUpdateRequest {
+        org_id: org_id.to_string(),
+        sender_name: Some("Acme Nodes".to_string()),
+        primary_color: Some(primary_color.to_string()),
+        accent_color: None,
+        logo: None,
+        remove_logo: false,
+    };
+
+    // org members can't update the branding
+    let result = test
+        .send_member(OrgBrandingService::update, req("#0055ff"))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let result = test
+        .send_admin(OrgBrandingService::update, req("blue"))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    // svg logos are rejected before anything is stored
+    let svg = api::OrgBrandingServiceUpdateRequest {
+        logo: Some(b"<svg><script>alert(1)</script></svg>".to_vec()),
+        ..req("#0055ff")
+    };
+    let result = test.send_admin(OrgBrandingService::update, svg).await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    let resp = test
+        .send_admin(OrgBrandingService::update, req("#0055FF"))
+        .await
+        .unwrap();
+    let branding = resp.branding.unwrap();
+    assert_eq!(branding.sender_name.as_deref(), Some("Acme Nodes"));
+    assert_eq!(branding.primary_color.as_deref(), Some("#0055ff"));
+    assert_eq!(branding.logo_url, None);
+
+    let get = api::OrgBrandingServiceGetRequest {
+        org_id: org_id.to_string(),
+    };
+    let resp = test
+        .send_member(OrgBrandingService::get, get.clone())
+        .await
+        .unwrap();
+    assert!(resp.enabled);
+    assert_eq!(
+        resp.branding.unwrap().primary_color.as_deref(),
+        Some("#0055ff")
+    );
+
+    let mut conn = test.conn().await;
+    let active = OrgBranding::active(org_id, &mut conn).await.unwrap();
+    assert!(active.is_some());
+
+    // branding is kept but no longer applied on a plan without it
+    diesel::update(orgs::table.find(org_id))
+        .set(orgs::plan_tier.eq(PlanTier::Pro))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let active = OrgBranding::active(org_id, &mut conn).await.unwrap();
+    assert!(active.is_none());
+
+    let resp = test
+        .send_member(OrgBrandingService::get, get)
+        .await
+        .unwrap();
+    assert!(!resp.enabled);
+    assert_eq!(
+        resp.branding.unwrap().sender_name.as_deref(),
+        Some("Acme Nodes")
+    );
+
+    let result = test
+        .send_admin(OrgBrandingService::update, req("#0055ff"))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+}
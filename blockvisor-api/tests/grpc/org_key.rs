@@ -0,0 +1,139 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::org_key::OrgKeyHost;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{OrgKeyService, SocketRpc};
+
+const SSH_KEY: &str =
+    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl ops";
+const WIREGUARD_KEY: &str = "xTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=";
+
+#[tokio::test]
+async fn org_keys_are_sent_to_private_hosts() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+    let host_id = test.seed().host2.id.to_string();
+
+    let create_req =
+        |key_type: api::OrgKeyType, public_key: &str| api::OrgKeyServiceCreateRequest {
+            org_id: org_id.clone(),
+            name: "operator".to_string(),
+            key_type: key_type.into(),
+            public_key: public_key.to_string(),
+        };
+
+    // members may not register keys
+    let req = create_req(api::OrgKeyType::Ssh, SSH_KEY);
+    let status = test
+        .send_member(OrgKeyService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // the key must match its type
+    let req = create_req(api::OrgKeyType::Wireguard, SSH_KEY);
+    let status = test
+        .send_admin(OrgKeyService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = create_req(api::OrgKeyType::Ssh, SSH_KEY);
+    let key = test
+        .send_admin(OrgKeyService::create, req)
+        .await
+        .unwrap()
+        .key
+        .unwrap();
+    assert!(key.fingerprint.starts_with("SHA256:"));
+
+    // the private host of the org is sent the key
+    assert_eq!(key.hosts.len(), 1);
+    assert_eq!(key.hosts[0].host_id, host_id);
+    assert_eq!(key.hosts[0].status(), api::OrgKeyHostStatus::Sent);
+
+    // the same key may only be registered once
+    let req = create_req(api::OrgKeyType::Ssh, SSH_KEY);
+    let status = test
+        .send_admin(OrgKeyService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    // simulate the host applying its keys
+    let mut conn = test.conn().await;
+    let command_id = key.hosts[0].command_id.as_ref().unwrap().parse().unwrap();
+    OrgKeyHost::finished(command_id, None, &mut conn)
+        .await
+        .unwrap();
+
+    let list_req = |include_revoked| api::OrgKeyServiceListRequest {
+        org_id: org_id.clone(),
+        include_revoked,
+    };
+    let keys = test
+        .send_member(OrgKeyService::list, list_req(false))
+        .await
+        .unwrap()
+        .keys;
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].hosts[0].status(), api::OrgKeyHostStatus::Applied);
+
+    // rotating revokes the old key in favour of the new one
+    let req = api::OrgKeyServiceRotateRequest {
+        key_id: key.key_id.clone(),
+        name: None,
+        public_key:
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIB5cX6pCTxR2GHrRd5KcJ9KAkH0Uv8QxBb9S5kTRt0Vd"
+                .to_string(),
+    };
+    let rotated = test
+        .send_admin(OrgKeyService::rotate, req)
+        .await
+        .unwrap()
+        .key
+        .unwrap();
+    assert_eq!(rotated.name, "operator");
+    assert_eq!(rotated.replaces_key_id, Some(key.key_id.clone()));
+
+    let keys = test
+        .send_member(OrgKeyService::list, list_req(true))
+        .await
+        .unwrap()
+        .keys;
+    assert_eq!(keys.len(), 2);
+    assert!(keys[0].revoked_at.is_some());
+    assert_eq!(keys[0].hosts[0].status(), api::OrgKeyHostStatus::Sent);
+
+    // once applied, the host no longer has the revoked key
+    let command_id = rotated.hosts[0]
+        .command_id
+        .as_ref()
+        .unwrap()
+        .parse()
+        .unwrap();
+    OrgKeyHost::finished(command_id, None, &mut conn)
+        .await
+        .unwrap();
+
+    let keys = test
+        .send_member(OrgKeyService::list, list_req(true))
+        .await
+        .unwrap()
+        .keys;
+    assert!(keys[0].hosts.is_empty());
+    assert_eq!(keys[1].hosts[0].status(), api::OrgKeyHostStatus::Applied);
+
+    // a revoked key cannot be revoked again
+    let req = api::OrgKeyServiceRevokeRequest { key_id: key.key_id };
+    let status = test
+        .send_admin(OrgKeyService::revoke, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    let req = create_req(api::OrgKeyType::Wireguard, WIREGUARD_KEY);
+    let resp = test.send_admin(OrgKeyService::create, req).await.unwrap();
+    assert_eq!(resp.key.unwrap().key_type(), api::OrgKeyType::Wireguard);
+}
@@ -0,0 +1,107 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::org_log_sink::OrgLogSinkHost;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{OrgLogSinkService, SocketRpc};
+
+#[tokio::test]
+async fn org_log_sinks_are_sent_to_private_hosts() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+    let host_id = test.seed().host2.id.to_string();
+
+    let set_req = |kind: api::LogSinkKind, endpoint: &str| api::OrgLogSinkServiceSetRequest {
+        org_id: org_id.clone(),
+        kind: kind.into(),
+        endpoint: endpoint.to_string(),
+        credentials: None,
+    };
+    let loki = "https://logs.acme.io/loki/api/v1/push";
+
+    // members may not configure log shipping
+    let req = set_req(api::LogSinkKind::Loki, loki);
+    let status = test
+        .send_member(OrgLogSinkService::set, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // logs are only shipped over https
+    let req = set_req(api::LogSinkKind::Loki, "http://logs.acme.io");
+    let status = test
+        .send_admin(OrgLogSinkService::set, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // datadog needs an api key
+    let req = set_req(
+        api::LogSinkKind::Datadog,
+        "https://http-intake.logs.datadoghq.com",
+    );
+    let status = test
+        .send_admin(OrgLogSinkService::set, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = set_req(api::LogSinkKind::Loki, loki);
+    let resp = test.send_admin(OrgLogSinkService::set, req).await.unwrap();
+    let sink = resp.log_sink.unwrap();
+    assert_eq!(sink.endpoint, loki);
+    assert!(!sink.has_credentials);
+
+    // the private host of the org is sent the sink
+    assert_eq!(resp.hosts.len(), 1);
+    assert_eq!(resp.hosts[0].host_id, host_id);
+    assert_eq!(resp.hosts[0].revision, sink.revision);
+    assert_eq!(resp.hosts[0].status(), api::OrgLogSinkHostStatus::Sent);
+
+    // simulate the host applying its sink
+    let mut conn = test.conn().await;
+    let command_id = resp.hosts[0].command_id.as_ref().unwrap().parse().unwrap();
+    OrgLogSinkHost::finished(command_id, None, &mut conn)
+        .await
+        .unwrap();
+
+    let get_req = api::OrgLogSinkServiceGetRequest {
+        org_id: org_id.clone(),
+    };
+    let resp = test
+        .send_member(OrgLogSinkService::get, get_req.clone())
+        .await
+        .unwrap();
+    assert!(resp.log_sink.is_some());
+    assert_eq!(resp.hosts[0].status(), api::OrgLogSinkHostStatus::Applied);
+
+    // removing the sink sends its removal as a new revision
+    let req = api::OrgLogSinkServiceDeleteRequest {
+        org_id: org_id.clone(),
+    };
+    let resp = test
+        .send_admin(OrgLogSinkService::delete, req.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.hosts[0].revision, sink.revision + 1);
+    assert_eq!(resp.hosts[0].status(), api::OrgLogSinkHostStatus::Sent);
+
+    let command_id = resp.hosts[0].command_id.as_ref().unwrap().parse().unwrap();
+    OrgLogSinkHost::finished(command_id, Some("no route to host"), &mut conn)
+        .await
+        .unwrap();
+
+    let resp = test
+        .send_member(OrgLogSinkService::get, get_req)
+        .await
+        .unwrap();
+    assert!(resp.log_sink.is_none());
+    assert_eq!(resp.hosts[0].status(), api::OrgLogSinkHostStatus::Failed);
+    assert_eq!(resp.hosts[0].error.as_deref(), Some("no route to host"));
+
+    let status = test
+        .send_admin(OrgLogSinkService::delete, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+}
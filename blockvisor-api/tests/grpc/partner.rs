@@ -0,0 +1,65 @@
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::{Org, User};
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{PartnerService, SocketRpc, UserService};
+
+#[tokio::test]
+async fn referred_signups_are_attributed_to_partners() {
+    let test = TestServer::new().await;
+
+    let req = api::PartnerServiceCreateRequest {
+        name: "Validator Co".to_string(),
+        referral_code: "VALIDATOR-CO".to_string(),
+    };
+    let status = test
+        .send_admin(PartnerService::create, req.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let partner = test
+        .send_super(PartnerService::create, req)
+        .await
+        .unwrap()
+        .partner
+        .unwrap();
+    assert_eq!(partner.referral_code, "validator-co");
+
+    let signup = |email: &str, code: &str| api::UserServiceCreateRequest {
+        email: email.to_string(),
+        first_name: "Ada".to_string(),
+        last_name: "Referred".to_string(),
+        password: "tailored-wool-suit-jacket".to_string(),
+        referral_code: Some(code.to_string()),
+        captcha_token: None,
+    };
+
+    let req = signup("ada@referred.com", "unknown-code");
+    let status = test
+        .send_member(UserService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = signup("ada@referred.com", "Validator-Co");
+    test.send_member(UserService::create, req).await.unwrap();
+
+    let mut conn = test.conn().await;
+    let user = User::by_email("ada@referred.com", &mut conn).await.unwrap();
+    let org = Org::find_personal(user.id, &mut conn).await.unwrap();
+    assert_eq!(
+        org.partner_id.map(|id| id.to_string()),
+        Some(partner.partner_id.clone())
+    );
+
+    let req = api::PartnerServiceRevenueRequest {
+        partner_id: partner.partner_id,
+        month: None,
+    };
+    let resp = test.send_super(PartnerService::revenue, req).await.unwrap();
+    assert_eq!(resp.orgs.len(), 1);
+    assert_eq!(resp.orgs[0].org_id, org.id.to_string());
+    assert!(resp.orgs[0].referred_at.is_some());
+}
@@ -0,0 +1,217 @@
+use blockvisor_api::database::seed::{IMAGE_ID, ORG_ID};
+use blockvisor_api::grpc::{api, common};
+use blockvisor_api::job;
+use blockvisor_api::model::node::{NodeHealth, NodeState};
+use blockvisor_api::model::schema::nodes;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tonic::Code;
+use uuid::Uuid;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{NodeService, PoolService, SocketRpc};
+
+#[tokio::test]
+async fn pool_members_rotate_by_health() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let node1 = test.seed().node.id;
+    let node2 = create_node(&test).await;
+
+    let create_req = |name: &str| api::PoolServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        name: name.to_string(),
+        node_ids: vec![node1.to_string(), node2.clone()],
+    };
+
+    // members may not create pools
+    let status = test
+        .send_member(PoolService::create, create_req("rpc.n0des.xyz"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // pool names must be within an allowed zone
+    let status = test
+        .send_admin(PoolService::create, create_req("rpc.example.org"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // only the running node is in rotation
+    let pool = test
+        .send_admin(PoolService::create, create_req("RPC.n0des.xyz"))
+        .await
+        .unwrap()
+        .pool
+        .unwrap();
+    assert_eq!(pool.name, "rpc.n0des.xyz");
+    assert_eq!(in_rotation(&pool), vec![node1.to_string()]);
+
+    diesel::update(nodes::table.find(Uuid::parse_str(&node2).unwrap()))
+        .set(nodes::node_state.eq(NodeState::Running))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    diesel::update(nodes::table.find(node1))
+        .set(nodes::protocol_health.eq(NodeHealth::Unhealthy))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    // the healthy node is rotated in before the unhealthy one is removed
+    job::rotate_endpoint_pools(test.context()).await.unwrap();
+    let req = api::PoolServiceGetRequest {
+        pool_id: pool.pool_id.clone(),
+    };
+    let pool = test
+        .send_member(PoolService::get, req)
+        .await
+        .unwrap()
+        .pool
+        .unwrap();
+    assert_eq!(in_rotation(&pool), vec![node2.clone()]);
+
+    let req = api::PoolServiceUpdateRequest {
+        pool_id: pool.pool_id.clone(),
+        add_node_ids: vec![],
+        remove_node_ids: vec![node1.to_string()],
+    };
+    let pool = test
+        .send_admin(PoolService::update, req)
+        .await
+        .unwrap()
+        .pool
+        .unwrap();
+    assert_eq!(pool.members.len(), 1);
+    assert_eq!(in_rotation(&pool), vec![node2]);
+
+    let req = api::PoolServiceDeleteRequest {
+        pool_id: pool.pool_id.clone(),
+    };
+    test.send_admin(PoolService::delete, req).await.unwrap();
+
+    let req = api::PoolServiceListRequest {
+        org_id: ORG_ID.into(),
+    };
+    let resp = test.send_member(PoolService::list, req).await.unwrap();
+    assert!(resp.pools.is_empty());
+}
+
+async fn create_node(test: &TestServer) -> String {
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(common::NodeLauncher {
+            launch: Some(common::node_launcher::Launch::ByRegion(common::ByRegion {
+                region_counts: vec![common::RegionCount {
+                    region_id: test.seed().region.id.to_string(),
+                    node_count: 1,
+                    resource: None,
+                    similarity: None,
+                }],
+            })),
+        }),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let mut resp = test.send_admin(NodeService::create, req).await.unwrap();
+    resp.nodes.pop().unwrap().node_id
+}
+
+fn in_rotation(pool: &api::EndpointPool) -> Vec<String> {
+    pool.members
+        .iter()
+        .filter(|member| member.in_rotation)
+        .map(|member| member.node_id.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn autoscaler_adds_nodes_up_to_min_nodes() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id.to_string();
+
+    let req = api::PoolServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        name: "rpc.n0des.xyz".to_string(),
+        node_ids: vec![node_id.clone()],
+    };
+    let pool = test
+        .send_admin(PoolService::create, req)
+        .await
+        .unwrap()
+        .pool
+        .unwrap();
+
+    let set_req = |cooldown_seconds| api::PoolServiceSetAutoscalerRequest {
+        pool_id: pool.pool_id.clone(),
+        image_id: IMAGE_ID.into(),
+        region_id: test.seed().region.id.to_string(),
+        min_nodes: 2,
+        max_nodes: 3,
+        metric: api::AutoscaleMetric::CpuPercent.into(),
+        scale_up_above: 80.0,
+        scale_down_below: 20.0,
+        cooldown_seconds,
+    };
+
+    // members may not set up an autoscaler
+    let status = test
+        .send_member(PoolService::set_autoscaler, set_req(300))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // the cooldown must be at least a minute
+    let status = test
+        .send_admin(PoolService::set_autoscaler, set_req(10))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    test.send_admin(PoolService::set_autoscaler, set_req(300))
+        .await
+        .unwrap();
+
+    // the pool is below min_nodes so a node is added
+    job::autoscale_endpoint_pools(test.context()).await.unwrap();
+    let req = api::PoolServiceGetRequest {
+        pool_id: pool.pool_id.clone(),
+    };
+    let pool = test
+        .send_member(PoolService::get, req)
+        .await
+        .unwrap()
+        .pool
+        .unwrap();
+    assert_eq!(pool.members.len(), 2);
+    let added = pool
+        .members
+        .iter()
+        .find(|member| member.autoscaled)
+        .unwrap();
+    assert_ne!(added.node_id, node_id);
+
+    // the cooldown stops a second node from being added straight away
+    job::autoscale_endpoint_pools(test.context()).await.unwrap();
+    let req = api::PoolServiceGetAutoscalerRequest {
+        pool_id: pool.pool_id.clone(),
+    };
+    let resp = test
+        .send_member(PoolService::get_autoscaler, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.events.len(), 1);
+    let event = &resp.events[0];
+    assert_eq!(event.action(), api::AutoscaleAction::ScaleUp);
+    assert_eq!(event.node_id.as_ref(), Some(&added.node_id));
+    assert!(event.error.is_none());
+}
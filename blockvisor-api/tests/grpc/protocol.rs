@@ -1,14 +1,17 @@
 use blockvisor_api::database::seed::{
-    ORG_ID, ORG_PROTOCOL_ID, ORG_PROTOCOL_KEY, ORG_PROTOCOL_VERSION_ID, ORG_SEMANTIC_VERSION,
-    PROTOCOL_ID, PROTOCOL_KEY, PROTOCOL_VERSION_ID, VARIANT_KEY,
+    IMAGE_ID, ORG_ID, ORG_PROTOCOL_ID, ORG_PROTOCOL_KEY, ORG_PROTOCOL_VERSION_ID,
+    ORG_SEMANTIC_VERSION, PROTOCOL_ID, PROTOCOL_KEY, PROTOCOL_VERSION_ID, VARIANT_KEY,
 };
 use blockvisor_api::grpc::api::protocol_service_get_protocol_request::Protocol as ApiProtocol;
 use blockvisor_api::grpc::{api, common};
+use blockvisor_api::model::schema::images;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
 use tonic::Code;
 use uuid::Uuid;
 
 use crate::setup::TestServer;
-use crate::setup::helper::traits::{ProtocolService, SocketRpc};
+use crate::setup::helper::traits::{NodeService, OrgService, ProtocolService, SocketRpc};
 
 #[tokio::test]
 async fn add_a_new_protocol() {
@@ -20,6 +23,10 @@ async fn add_a_new_protocol() {
         org_id: None,
         description: None,
         ticker: Some(ticker.to_string()),
+        docs_url: None,
+        logo_url: None,
+        networks: vec!["mainnet".to_string()],
+        lifecycle: None,
     };
 
     // an org admin can't add new protocols
@@ -39,6 +46,8 @@ async fn add_a_new_protocol() {
     assert!(protocol.org_id.is_none());
     assert!(protocol.description.is_none());
     assert_eq!(protocol.ticker.unwrap(), ticker);
+    assert_eq!(protocol.networks, vec!["mainnet"]);
+    assert_eq!(protocol.lifecycle(), api::ProtocolLifecycle::Active);
 
     // an org member can't view a private visibility protocol
     let req = api::ProtocolServiceGetProtocolRequest {
@@ -99,6 +108,50 @@ async fn add_a_new_version() {
     assert_eq!(resp.version.unwrap().semantic_version, version);
 }
 
+#[tokio::test]
+async fn delete_a_protocol_without_versions() {
+    let test = TestServer::new().await;
+    let req = api::ProtocolServiceAddProtocolRequest {
+        key: "sui".to_string(),
+        name: "Sui".to_string(),
+        org_id: None,
+        description: None,
+        ticker: None,
+        docs_url: None,
+        logo_url: None,
+        networks: vec![],
+        lifecycle: None,
+    };
+    let resp = test
+        .send_super(ProtocolService::add_protocol, req)
+        .await
+        .unwrap();
+    let protocol_id = resp.protocol.unwrap().protocol_id;
+
+    // an org admin can't delete protocols
+    let req = api::ProtocolServiceDeleteProtocolRequest {
+        protocol_id: protocol_id.clone(),
+    };
+    let result = test
+        .send_admin(ProtocolService::delete_protocol, req.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // a blockjoy admin can delete protocols
+    test.send_super(ProtocolService::delete_protocol, req.clone())
+        .await
+        .unwrap();
+    let result = test.send_super(ProtocolService::delete_protocol, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    // a protocol with versions can't be deleted
+    let req = api::ProtocolServiceDeleteProtocolRequest {
+        protocol_id: PROTOCOL_ID.to_string(),
+    };
+    let result = test.send_super(ProtocolService::delete_protocol, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+}
+
 #[tokio::test]
 async fn get_an_existing_protocol() {
     let test = TestServer::new().await;
@@ -178,6 +231,151 @@ async fn get_latest_version() {
     assert_eq!(version.semantic_version, ORG_SEMANTIC_VERSION);
 }
 
+#[tokio::test]
+async fn grant_an_org_access_to_a_protocol() {
+    let test = TestServer::new().await;
+    let req = api::OrgServiceCreateRequest {
+        name: "other-org".to_string(),
+    };
+    let resp = test.send_unknown(OrgService::create, req).await.unwrap();
+    let other_org_id = resp.org.unwrap().org_id;
+
+    let get = || api::ProtocolServiceGetProtocolRequest {
+        protocol: Some(ApiProtocol::ProtocolId(ORG_PROTOCOL_ID.to_string())),
+        org_id: Some(other_org_id.clone()),
+    };
+
+    // another org can't see an org protocol without a grant
+    let result = test
+        .send_unknown(ProtocolService::get_protocol, get())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    // an org member of the publisher can't grant access, and is told the
+    // same as for a protocol that doesn't exist
+    let req = api::ProtocolServiceGrantAccessRequest {
+        protocol_id: ORG_PROTOCOL_ID.to_string(),
+        org_id: other_org_id.clone(),
+        protocol_version_id: None,
+    };
+    let denied = test
+        .send_member(ProtocolService::grant_access, req.clone())
+        .await
+        .unwrap_err();
+    let mut missing_req = req.clone();
+    missing_req.protocol_id = Uuid::new_v4().to_string();
+    let missing = test
+        .send_member(ProtocolService::grant_access, missing_req)
+        .await
+        .unwrap_err();
+    assert_eq!(denied.code(), Code::NotFound);
+    assert_eq!(denied.code(), missing.code());
+    assert_eq!(denied.message(), missing.message());
+
+    // granting access to an org that doesn't exist is not found
+    let mut no_org_req = req.clone();
+    no_org_req.org_id = Uuid::new_v4().to_string();
+    let result = test
+        .send_admin(ProtocolService::grant_access, no_org_req)
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+
+    // an org admin of the publisher can grant access
+    let resp = test
+        .send_admin(ProtocolService::grant_access, req)
+        .await
+        .unwrap();
+    let grant = resp.grant.unwrap();
+    assert_eq!(grant.org_id, other_org_id);
+
+    let resp = test
+        .send_unknown(ProtocolService::get_protocol, get())
+        .await
+        .unwrap();
+    let protocol = resp.protocol.unwrap();
+    assert_eq!(protocol.protocol_id, ORG_PROTOCOL_ID);
+    assert_eq!(protocol.versions.len(), 1);
+
+    // the publisher can list which orgs have access
+    let req = api::ProtocolServiceListGrantsRequest {
+        protocol_id: ORG_PROTOCOL_ID.to_string(),
+    };
+    let resp = test
+        .send_admin(ProtocolService::list_grants, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.grants.len(), 1);
+
+    // revoking the grant hides the protocol again
+    let req = api::ProtocolServiceRevokeAccessRequest {
+        grant_id: grant.grant_id,
+    };
+    test.send_admin(ProtocolService::revoke_access, req)
+        .await
+        .unwrap();
+    let result = test
+        .send_unknown(ProtocolService::get_protocol, get())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn list_the_public_catalog() {
+    let test = TestServer::new().await;
+
+    // the catalog is available without authentication
+    let req = api::ProtocolServiceListCatalogRequest {};
+    let resp = test
+        .send_unauthenticated(ProtocolService::list_catalog, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.protocols.len(), 1);
+    assert_eq!(resp.protocols[0].protocol_id, PROTOCOL_ID);
+
+    let update = |lifecycle: api::ProtocolLifecycle| api::ProtocolServiceUpdateProtocolRequest {
+        protocol_id: PROTOCOL_ID.to_string(),
+        name: None,
+        description: None,
+        visibility: None,
+        docs_url: Some("https://docs.example.com".to_string()),
+        logo_url: Some("https://example.com/logo.png".to_string()),
+        networks: Some(api::ProtocolNetworks {
+            networks: vec!["mainnet".to_string(), "testnet".to_string()],
+        }),
+        lifecycle: Some(lifecycle.into()),
+    };
+
+    // catalog metadata is returned after an update
+    let req = update(api::ProtocolLifecycle::Beta);
+    test.send_super(ProtocolService::update_protocol, req)
+        .await
+        .unwrap();
+    let req = api::ProtocolServiceListCatalogRequest {};
+    let resp = test
+        .send_unauthenticated(ProtocolService::list_catalog, req)
+        .await
+        .unwrap();
+    let protocol = &resp.protocols[0];
+    assert_eq!(
+        protocol.docs_url.as_deref(),
+        Some("https://docs.example.com")
+    );
+    assert_eq!(protocol.networks, vec!["mainnet", "testnet"]);
+    assert_eq!(protocol.lifecycle(), api::ProtocolLifecycle::Beta);
+
+    // retired protocols are removed from the catalog
+    let req = update(api::ProtocolLifecycle::Retired);
+    test.send_super(ProtocolService::update_protocol, req)
+        .await
+        .unwrap();
+    let req = api::ProtocolServiceListCatalogRequest {};
+    let resp = test
+        .send_unauthenticated(ProtocolService::list_catalog, req)
+        .await
+        .unwrap();
+    assert!(resp.protocols.is_empty());
+}
+
 #[tokio::test]
 async fn list_existing_protocols() {
     let test = TestServer::new().await;
@@ -242,6 +440,102 @@ async fn list_existing_versions() {
     assert_eq!(versions[0].protocol_version_id, ORG_PROTOCOL_VERSION_ID);
 }
 
+#[tokio::test]
+async fn list_protocol_networks() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let add_req = |key: &str, kind: api::NetworkKind| api::ProtocolServiceAddNetworkRequest {
+        protocol_id: PROTOCOL_ID.into(),
+        network_key: key.to_string(),
+        kind: kind.into(),
+        chain_id: None,
+        genesis_hash: None,
+        explorer_url: Some("https://etherscan.io".to_string()),
+    };
+
+    // an org admin can't add networks
+    let req = add_req("mainnet", api::NetworkKind::Mainnet);
+    let result = test.send_admin(ProtocolService::add_network, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let mainnet = test
+        .send_super(
+            ProtocolService::add_network,
+            add_req("Mainnet", api::NetworkKind::Mainnet),
+        )
+        .await
+        .unwrap()
+        .network
+        .unwrap();
+    assert_eq!(mainnet.network_key, "mainnet");
+
+    let mut req = add_req(VARIANT_KEY, api::NetworkKind::Testnet);
+    req.chain_id = Some("11155111".to_string());
+    req.explorer_url = Some("not a url".to_string());
+    let result = test
+        .send_super(ProtocolService::add_network, req.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+    req.explorer_url = None;
+    let sepolia = test
+        .send_super(ProtocolService::add_network, req.clone())
+        .await
+        .unwrap()
+        .network
+        .unwrap();
+    let result = test.send_super(ProtocolService::add_network, req).await;
+    assert_eq!(result.unwrap_err().code(), Code::AlreadyExists);
+
+    // org members can list networks by kind
+    let list_req = |kinds: Vec<api::NetworkKind>| api::ProtocolServiceListNetworksRequest {
+        protocol_id: PROTOCOL_ID.into(),
+        org_id: None,
+        kinds: kinds.into_iter().map(Into::into).collect(),
+    };
+    let resp = test
+        .send_member(ProtocolService::list_networks, list_req(vec![]))
+        .await
+        .unwrap();
+    assert_eq!(resp.networks.len(), 2);
+    let resp = test
+        .send_member(
+            ProtocolService::list_networks,
+            list_req(vec![api::NetworkKind::Testnet]),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.networks.len(), 1);
+    assert_eq!(resp.networks[0].chain_id.as_deref(), Some("11155111"));
+
+    // nodes are on the network of their image
+    let network_id: Uuid = sepolia.network_id.parse().unwrap();
+    diesel::update(images::table.find(Uuid::parse_str(IMAGE_ID).unwrap()))
+        .set(images::network_id.eq(network_id))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let node_id = create_node(&test).await;
+
+    let nodes_req = |kind: api::NetworkKind| api::NodeServiceListRequest {
+        org_ids: vec![ORG_ID.into()],
+        network_kinds: vec![kind.into()],
+        limit: 10,
+        ..Default::default()
+    };
+    let resp = test
+        .send_admin(NodeService::list, nodes_req(api::NetworkKind::Testnet))
+        .await
+        .unwrap();
+    assert_eq!(resp.nodes.len(), 1);
+    assert_eq!(resp.nodes[0].node_id, node_id);
+    assert_eq!(resp.nodes[0].network_id.as_ref(), Some(&sepolia.network_id));
+    let resp = test
+        .send_admin(NodeService::list, nodes_req(api::NetworkKind::Mainnet))
+        .await
+        .unwrap();
+    assert!(resp.nodes.is_empty());
+}
+
 #[tokio::test]
 async fn update_an_existing_protocol() {
     let test = TestServer::new().await;
@@ -262,6 +556,10 @@ async fn update_an_existing_protocol() {
         name: None,
         description: None,
         visibility: Some(common::Visibility::Private.into()),
+        docs_url: None,
+        logo_url: None,
+        networks: None,
+        lifecycle: None,
     };
     let result = test.send_admin(ProtocolService::update_protocol, req).await;
     assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
@@ -272,6 +570,10 @@ async fn update_an_existing_protocol() {
         name: None,
         description: None,
         visibility: Some(common::Visibility::Private.into()),
+        docs_url: None,
+        logo_url: None,
+        networks: None,
+        lifecycle: None,
     };
     let result = test.send_super(ProtocolService::update_protocol, req).await;
     let protocol = result.unwrap().protocol.unwrap();
@@ -337,3 +639,31 @@ async fn update_existing_version() {
     let result = test.send_member(ProtocolService::get_latest, req).await;
     assert_eq!(result.unwrap_err().code(), Code::NotFound);
 }
+
+async fn create_node(test: &TestServer) -> String {
+    let req = api::NodeServiceCreateRequest {
+        org_id: ORG_ID.into(),
+        image_id: IMAGE_ID.into(),
+        old_node_id: None,
+        launcher: Some(common::NodeLauncher {
+            launch: Some(common::node_launcher::Launch::ByRegion(common::ByRegion {
+                region_counts: vec![common::RegionCount {
+                    region_id: test.seed().region.id.to_string(),
+                    node_count: 1,
+                    resource: None,
+                    similarity: None,
+                }],
+            })),
+        }),
+        new_values: vec![],
+        add_rules: vec![],
+        tags: None,
+        placement_policy: common::PlacementPolicy::Unspecified.into(),
+        pool_id: None,
+        project_id: None,
+        validate_only: false,
+        backorder: false,
+    };
+    let mut resp = test.send_admin(NodeService::create, req).await.unwrap();
+    resp.nodes.pop().unwrap().node_id
+}
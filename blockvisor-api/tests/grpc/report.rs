@@ -0,0 +1,77 @@
+use blockvisor_api::auth::rbac::{MetricsPerm, Perms};
+use blockvisor_api::database::seed::ORG_ID;
+use blockvisor_api::grpc::{api, common};
+use blockvisor_api::model::node::NodeDowntime;
+use chrono::{TimeDelta, Utc};
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{MetricsService, ReportService, SocketRpc};
+
+#[tokio::test]
+async fn node_downtime_is_reported_as_monthly_uptime() {
+    let test = TestServer::new().await;
+    let node_id = test.seed().node.id;
+
+    let metrics = |state: common::NodeState| api::MetricsServiceNodeRequest {
+        metrics: vec![api::NodeMetrics {
+            node_id: node_id.to_string(),
+            node_status: Some(common::NodeStatus {
+                state: state.into(),
+                next: None,
+                protocol: None,
+            }),
+            height: None,
+            block_age: None,
+            consensus: None,
+            peer_count: None,
+            jobs: vec![],
+        }],
+    };
+    let jwt = test.org_jwt(Perms::from(MetricsPerm::Node));
+
+    // a failed node opens a downtime interval until it is running again
+    test.send_with(
+        MetricsService::node,
+        metrics(common::NodeState::Failed),
+        &jwt,
+    )
+    .await
+    .unwrap();
+    let mut conn = test.conn().await;
+    let (start, end) = (Utc::now() - TimeDelta::hours(1), Utc::now());
+    let downtimes = NodeDowntime::overlapping(&[node_id], start, end, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(downtimes.len(), 1);
+    assert!(downtimes[0].ended_at.is_none());
+
+    test.send_with(
+        MetricsService::node,
+        metrics(common::NodeState::Running),
+        &jwt,
+    )
+    .await
+    .unwrap();
+    let downtimes = NodeDowntime::overlapping(&[node_id], start, Utc::now(), &mut conn)
+        .await
+        .unwrap();
+    assert!(downtimes[0].ended_at.is_some());
+
+    let req = api::ReportServiceUptimeRequest {
+        org_id: ORG_ID.to_string(),
+        month: None,
+        node_ids: vec![],
+    };
+    let result = test.send_unknown(ReportService::uptime, req.clone()).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let resp = test.send_member(ReportService::uptime, req).await.unwrap();
+    let node = resp
+        .nodes
+        .iter()
+        .find(|node| node.node_id == node_id.to_string())
+        .unwrap();
+    assert!(node.downtime_seconds <= node.total_seconds);
+    assert!(resp.uptime_percent.is_some());
+}
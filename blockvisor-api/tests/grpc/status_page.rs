@@ -0,0 +1,165 @@
+use blockvisor_api::database::seed::{NODE_ID, ORG_ID};
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::StatusPage;
+use blockvisor_api::model::schema::{orgs, status_pages};
+use chrono::Utc;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tonic::Code;
+use uuid::Uuid;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{SocketRpc, StatusPageService};
+
+#[tokio::test]
+async fn create_view_and_delete_a_status_page() {
+    let test = TestServer::new().await;
+
+    let req = |slug: &str| api::StatusPageServiceCreateRequest {
+        org_id: ORG_ID.to_string(),
+        name: "Validator status".to_string(),
+        slug: slug.to_string(),
+        is_public: true,
+        custom_domain: Some("Status.Example.com".to_string()),
+        node_ids: vec![NODE_ID.to_string()],
+        node_tags: vec![],
+    };
+
+    // org members can't create status pages
+    let result = test
+        .send_member(StatusPageService::create, req("validators"))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    let result = test
+        .send_admin(StatusPageService::create, req("Not A Slug"))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    let resp = test
+        .send_admin(StatusPageService::create, req("validators"))
+        .await
+        .unwrap();
+    let page = resp.status_page.unwrap();
+    assert_eq!(page.custom_domain.as_deref(), Some("status.example.com"));
+
+    // slugs are unique across orgs
+    let result = test
+        .send_admin(StatusPageService::create, req("validators"))
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::AlreadyExists);
+
+    let get = api::StatusPageServiceGetRequest {
+        status_page_id: page.status_page_id.clone(),
+    };
+    let result = test.send_unknown(StatusPageService::get, get.clone()).await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+    let resp = test.send_member(StatusPageService::get, get).await.unwrap();
+    let view = resp.view.unwrap();
+    assert_eq!(view.name, "Validator status");
+    assert_eq!(view.nodes.len(), 1);
+
+    // public pages are found by slug
+    let mut conn = test.conn().await;
+    let found = StatusPage::public_by_slug("validators", &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(found.id.to_string(), page.status_page_id);
+
+    let list = api::StatusPageServiceListRequest {
+        org_id: ORG_ID.to_string(),
+    };
+    let resp = test
+        .send_member(StatusPageService::list, list.clone())
+        .await
+        .unwrap();
+    assert_eq!(resp.status_pages.len(), 1);
+
+    let delete = api::StatusPageServiceDeleteRequest {
+        status_page_id: page.status_page_id,
+    };
+    test.send_admin(StatusPageService::delete, delete)
+        .await
+        .unwrap();
+    let resp = test
+        .send_member(StatusPageService::list, list)
+        .await
+        .unwrap();
+    assert!(resp.status_pages.is_empty());
+}
+
+fn create_req(slug: &str) -> api::StatusPageServiceCreateRequest {
+    api::StatusPageServiceCreateRequest {
+        org_id: ORG_ID.to_string(),
+        name: "Validator status".to_string(),
+        slug: slug.to_string(),
+        is_public: true,
+        custom_domain: Some("status.example.com".to_string()),
+        node_ids: vec![NODE_ID.to_string()],
+        node_tags: vec![],
+    }
+}
+
+#[tokio::test]
+async fn custom_domains_are_only_served_once_verified() {
+    let test = TestServer::new().await;
+
+    let resp = test
+        .send_admin(StatusPageService::create, create_req("validators"))
+        .await
+        .unwrap();
+    let page = resp.status_page.unwrap();
+    assert_eq!(page.domain_token.as_ref().map(String::len), Some(32));
+    assert!(!page.domain_verified);
+
+    let mut conn = test.conn().await;
+    let result = StatusPage::public_by_domain("status.example.com", &mut conn).await;
+    assert!(result.is_err());
+
+    // the mock resolver has no TXT record holding the token
+    let verify = api::StatusPageServiceVerifyDomainRequest {
+        status_page_id: page.status_page_id.clone(),
+    };
+    let result = test
+        .send_member(StatusPageService::verify_domain, verify.clone())
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+    let result = test
+        .send_admin(StatusPageService::verify_domain, verify)
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::FailedPrecondition);
+
+    let page_id: Uuid = page.status_page_id.parse().unwrap();
+    diesel::update(status_pages::table.find(page_id))
+        .set(status_pages::domain_verified_at.eq(Utc::now()))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let found = StatusPage::public_by_domain("STATUS.example.com", &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(found.id.to_string(), page.status_page_id);
+}
+
+#[tokio::test]
+async fn public_pages_of_suspended_orgs_are_hidden() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id;
+
+    test.send_admin(StatusPageService::create, create_req("validators"))
+        .await
+        .unwrap();
+
+    let mut conn = test.conn().await;
+    StatusPage::public_by_slug("validators", &mut conn)
+        .await
+        .unwrap();
+
+    diesel::update(orgs::table.find(org_id))
+        .set(orgs::suspended_at.eq(Utc::now()))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    let result = StatusPage::public_by_slug("validators", &mut conn).await;
+    assert!(result.is_err());
+}
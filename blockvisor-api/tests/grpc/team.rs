@@ -0,0 +1,91 @@
+use blockvisor_api::grpc::api;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{SocketRpc, TeamService};
+
+#[tokio::test]
+async fn team_roles_apply_to_each_member() {
+    let test = TestServer::new().await;
+    let org_id = test.seed().org.id.to_string();
+    let member_id = test.seed().member.id.to_string();
+
+    let create_req = |name: &str| api::TeamServiceCreateRequest {
+        org_id: org_id.clone(),
+        name: name.to_string(),
+    };
+
+    // members may not create teams
+    let status = test
+        .send_member(TeamService::create, create_req("operators"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let team = test
+        .send_admin(TeamService::create, create_req("operators"))
+        .await
+        .unwrap()
+        .team
+        .unwrap();
+
+    // team names are unique within an org
+    let status = test
+        .send_admin(TeamService::create, create_req("operators"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+
+    let req = api::TeamServiceAddMemberRequest {
+        team_id: team.team_id.clone(),
+        user_id: member_id.clone(),
+    };
+    test.send_admin(TeamService::add_member, req).await.unwrap();
+
+    // ownership of an org is never granted through a team
+    let grant_req = |role: &str| api::TeamServiceGrantRoleRequest {
+        team_id: team.team_id.clone(),
+        role: role.to_string(),
+    };
+    let status = test
+        .send_admin(TeamService::grant_role, grant_req("org-owner"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    test.send_admin(TeamService::grant_role, grant_req("org-admin"))
+        .await
+        .unwrap();
+
+    // the member now has the admin permissions of the team
+    test.send_member(TeamService::create, create_req("support"))
+        .await
+        .unwrap();
+
+    let req = api::TeamServiceGetRequest {
+        team_id: team.team_id.clone(),
+    };
+    let team = test
+        .send_member(TeamService::get, req)
+        .await
+        .unwrap()
+        .team
+        .unwrap();
+    assert_eq!(team.member_ids, vec![member_id.clone()]);
+    assert_eq!(team.roles, vec!["org-admin".to_string()]);
+
+    // and loses them again with the role
+    let req = api::TeamServiceRevokeRoleRequest {
+        team_id: team.team_id.clone(),
+        role: "org-admin".to_string(),
+    };
+    test.send_admin(TeamService::revoke_role, req)
+        .await
+        .unwrap();
+
+    let status = test
+        .send_member(TeamService::create, create_req("billing"))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
@@ -0,0 +1,113 @@
+use blockvisor_api::database::seed::ORG_ID;
+use blockvisor_api::grpc::api;
+use blockvisor_api::model::schema::ticket_integrations;
+use blockvisor_api::model::{Ticket, TicketIntegration};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tonic::Code;
+
+use crate::setup::TestServer;
+use crate::setup::helper::traits::{NodeService, SocketRpc, TicketService};
+
+#[tokio::test]
+async fn ticket_integrations_pick_up_new_incidents() {
+    let test = TestServer::new().await;
+
+    let req = |provider: api::TicketProvider| api::TicketServiceCreateIntegrationRequest {
+        org_id: ORG_ID.to_string(),
+        provider: provider.into(),
+        base_url: "https://example.atlassian.net/".to_string(),
+        username: "support@example.com".to_string(),
+        api_token: "secret-api-token".to_string(),
+        project_key: None,
+    };
+
+    // org members can't configure ticketing
+    let result = test
+        .send_member(
+            TicketService::create_integration,
+            req(api::TicketProvider::Zendesk),
+        )
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::PermissionDenied);
+
+    // jira issues need a project to be filed under
+    let result = test
+        .send_admin(
+            TicketService::create_integration,
+            req(api::TicketProvider::Jira),
+        )
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+    let mut jira = req(api::TicketProvider::Jira);
+    jira.project_key = Some("OPS".to_string());
+    let resp = test
+        .send_admin(TicketService::create_integration, jira.clone())
+        .await
+        .unwrap();
+    let integration = resp.integration.unwrap();
+    assert_eq!(integration.base_url, "https://example.atlassian.net");
+
+    let result = test
+        .send_admin(TicketService::create_integration, jira)
+        .await;
+    assert_eq!(result.unwrap_err().code(), Code::AlreadyExists);
+
+    let mut conn = test.conn().await;
+    let id = integration.integration_id.parse().unwrap();
+    let stored = TicketIntegration::by_id(id, &mut conn).await.unwrap();
+    let keyring = &test.context().keyring;
+    assert!(stored.verify_webhook(&resp.webhook_token, keyring).unwrap());
+    assert!(!stored.verify_webhook("not-the-token", keyring).unwrap());
+
+    // the api token and webhook salt are only stored encrypted
+    let api_token = stored.api_token.open(keyring).unwrap();
+    assert_eq!(api_token, "secret-api-token");
+    let (sealed, salt): (String, String) = ticket_integrations::table
+        .find(id)
+        .select((
+            ticket_integrations::api_token,
+            ticket_integrations::webhook_salt,
+        ))
+        .get_result(&mut conn)
+        .await
+        .unwrap();
+    assert!(sealed.starts_with("enc:v1:"));
+    assert!(!sealed.contains("secret-api-token"));
+    assert!(salt.starts_with("enc:v1:"));
+
+    // incidents opened after the integration are pending a ticket
+    let report = api::NodeServiceReportErrorRequest {
+        node_id: test.seed().node.id.to_string(),
+        created_by: None,
+        message: "disk full".to_string(),
+    };
+    test.send_admin(NodeService::report_error, report)
+        .await
+        .unwrap();
+    let pending = Ticket::pending(&mut conn).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].0.title, "disk full");
+
+    let get = api::TicketServiceGetIntegrationRequest {
+        org_id: ORG_ID.to_string(),
+    };
+    let resp = test
+        .send_admin(TicketService::get_integration, get.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        resp.integration.unwrap().project_key.as_deref(),
+        Some("OPS")
+    );
+
+    let delete = api::TicketServiceDeleteIntegrationRequest {
+        integration_id: integration.integration_id,
+    };
+    test.send_admin(TicketService::delete_integration, delete)
+        .await
+        .unwrap();
+    let result = test.send_admin(TicketService::get_integration, get).await;
+    assert_eq!(result.unwrap_err().code(), Code::NotFound);
+}
@@ -1,11 +1,24 @@
+use std::collections::HashSet;
+
 use blockvisor_api::auth::resource::UserId;
+use blockvisor_api::database::seed::LOGIN_PASSWORD;
 use blockvisor_api::grpc::{api, common};
-use blockvisor_api::model::User;
+use blockvisor_api::model::user::device::UserDevice;
+use blockvisor_api::model::user::export::{
+    MAX_ATTEMPTS, UserExport, UserExportRequest, UserExportStatus,
+};
+use blockvisor_api::model::user::notification::NotificationKind;
+use blockvisor_api::model::user::session::UserSession;
+use blockvisor_api::model::{Org, User};
+use blockvisor_api::push::Message;
+use blockvisor_api::stripe::webhook::SIGNATURE_HEADER;
+use blockvisor_api::stripe::webhook::tests::sign;
+use chrono::Utc;
 use tonic::Code;
 use uuid::Uuid;
 
 use crate::setup::TestServer;
-use crate::setup::helper::traits::{SocketRpc, UserService};
+use crate::setup::helper::traits::{AuthService, SocketRpc, UserService};
 
 #[tokio::test]
 async fn create_a_new_user() {
@@ -15,7 +28,9 @@ async fn create_a_new_user() {
         email: email.to_string(),
         first_name: "Hugo".to_string(),
         last_name: "Boss".to_string(),
-        password: "abcde12345".to_string(),
+        password: "tailored-wool-suit-jacket".to_string(),
+        referral_code: None,
+        captcha_token: None,
     };
 
     // fails for existing email
@@ -31,6 +46,56 @@ async fn create_a_new_user() {
     test.send_member(UserService::create, req).await.unwrap();
 }
 
+#[tokio::test]
+async fn create_a_user_with_a_blocked_email_domain() {
+    let test = TestServer::new().await;
+
+    // fails for a blocked email domain
+    let req = api::UserServiceCreateRequest {
+        email: "hugo@mailinator.com".to_string(),
+        first_name: "Hugo".to_string(),
+        last_name: "Boss".to_string(),
+        password: "tailored-wool-suit-jacket".to_string(),
+        referral_code: None,
+        captcha_token: None,
+    };
+    let status = test
+        .send_member(UserService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn create_a_user_with_a_weak_password() {
+    let test = TestServer::new().await;
+
+    let create_req = |password: &str| api::UserServiceCreateRequest {
+        email: "hugo@boss.com".to_string(),
+        first_name: "Hugo".to_string(),
+        last_name: "Boss".to_string(),
+        password: password.to_string(),
+        referral_code: None,
+        captcha_token: None,
+    };
+
+    // fails for a short password
+    let req = create_req("hugo");
+    let status = test
+        .send_member(UserService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // fails for a weak password
+    let req = create_req("abcde12345");
+    let status = test
+        .send_member(UserService::create, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
 #[tokio::test]
 async fn confirm_a_new_user() {
     let test = TestServer::new().await;
@@ -97,6 +162,7 @@ async fn update_an_existing_user() {
         user_id: user_id.to_string(),
         first_name: Some("Hugo".to_string()),
         last_name: Some("Boss".to_string()),
+        update_mask: None,
     };
 
     // cannot update unconfirmed user
@@ -164,3 +230,430 @@ async fn list_users() {
         .unwrap();
     assert_eq!(resp.users.len(), 0, "{resp:?}");
 }
+
+#[tokio::test]
+async fn export_user_data() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let member = &test.seed().member;
+
+    // cannot request an export for another user
+    let req = api::UserServiceExportDataRequest {
+        user_id: test.seed().admin.id.to_string(),
+    };
+    let status = test
+        .send_member(UserService::export_data, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::UserServiceExportDataRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::export_data, req)
+        .await
+        .unwrap();
+
+    // the export is stored as pending for the export job
+    let requests = UserExportRequest::by_user(member.id, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].status, UserExportStatus::Pending);
+
+    let export = UserExport::collect(member, &mut conn).await.unwrap();
+    assert_eq!(export.profile.email, member.email);
+    assert!(
+        export
+            .memberships
+            .iter()
+            .any(|m| m.org_id == *test.seed().org.id)
+    );
+    assert!(export.to_json().is_ok());
+}
+
+#[tokio::test]
+async fn failed_export_requests_are_retried() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let member = &test.seed().member;
+
+    let req = api::UserServiceExportDataRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::export_data, req)
+        .await
+        .unwrap();
+
+    // a claimed request is not claimed again
+    let request = UserExportRequest::claim_next(&mut conn)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.attempts, 1);
+    assert!(
+        UserExportRequest::claim_next(&mut conn)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // a failed attempt stays pending to be retried
+    request
+        .failed("store unavailable", &mut conn)
+        .await
+        .unwrap();
+    let requests = UserExportRequest::by_user(member.id, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(requests[0].status, UserExportStatus::Pending);
+    assert_eq!(requests[0].last_error.as_deref(), Some("store unavailable"));
+
+    // the last attempt fails the request
+    let mut request = requests[0].clone();
+    request.attempts = MAX_ATTEMPTS;
+    request
+        .failed("store unavailable", &mut conn)
+        .await
+        .unwrap();
+    let requests = UserExportRequest::by_user(member.id, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(requests[0].status, UserExportStatus::Failed);
+    assert!(requests[0].completed_at.is_some());
+}
+
+#[tokio::test]
+async fn delete_account_anonymizes_user() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let member = &test.seed().member;
+
+    // cannot delete the account of another user
+    let req = api::UserServiceDeleteAccountRequest {
+        user_id: test.seed().admin.id.to_string(),
+    };
+    let status = test
+        .send_member(UserService::delete_account, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::UserServiceDeleteAccountRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::delete_account, req)
+        .await
+        .unwrap();
+
+    assert!(User::by_id(member.id, &mut conn).await.is_err());
+    assert!(User::by_email(&member.email, &mut conn).await.is_err());
+}
+
+#[tokio::test]
+async fn delete_account_refuses_personal_org_with_nodes() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let member = &test.seed().member;
+
+    let personal = Org::find_personal(member.id, &mut conn).await.unwrap();
+    Org::add_node(personal.id, &mut conn).await.unwrap();
+
+    let req = api::UserServiceDeleteAccountRequest {
+        user_id: member.id.to_string(),
+    };
+    let status = test
+        .send_member(UserService::delete_account, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::FailedPrecondition);
+
+    assert!(User::by_id(member.id, &mut conn).await.is_ok());
+    assert!(Org::by_id(personal.id, &mut conn).await.is_ok());
+}
+
+#[tokio::test]
+async fn delete_account_revokes_sessions() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+    let member = &test.seed().member;
+
+    let req = api::AuthServiceLoginRequest {
+        email: member.email.clone(),
+        password: LOGIN_PASSWORD.into(),
+    };
+    test.send_unauthenticated(AuthService::login, req)
+        .await
+        .unwrap();
+    let req = api::AuthServiceListSessionsRequest {
+        user_id: member.id.to_string(),
+    };
+    let resp = test
+        .send_member(AuthService::list_sessions, req)
+        .await
+        .unwrap();
+    let session_id = resp.sessions[0].session_id.parse().unwrap();
+
+    let req = api::UserServiceDeleteAccountRequest {
+        user_id: member.id.to_string(),
+    };
+    test.send_member(UserService::delete_account, req)
+        .await
+        .unwrap();
+
+    let session = UserSession::by_id(session_id, &mut conn).await.unwrap();
+    assert!(session.revoked_at.is_some());
+}
+
+#[tokio::test]
+async fn register_and_remove_devices() {
+    let test = TestServer::new().await;
+    let member = &test.seed().member;
+
+    let register_req = |user_id: &str| api::UserServiceRegisterDeviceRequest {
+        user_id: user_id.to_string(),
+        platform: api::PushPlatform::Fcm.into(),
+        push_token: "fcm-token".to_string(),
+        name: "Pixel".to_string(),
+    };
+
+    // cannot register a device for another user
+    let req = register_req(&test.seed().admin.id.to_string());
+    let status = test
+        .send_member(UserService::register_device, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    // the platform must be set
+    let mut req = register_req(&member.id.to_string());
+    req.platform = api::PushPlatform::Unspecified.into();
+    let status = test
+        .send_member(UserService::register_device, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // registering the same token again refreshes the existing device
+    let req = register_req(&member.id.to_string());
+    let first = test
+        .send_member(UserService::register_device, req)
+        .await
+        .unwrap()
+        .device
+        .unwrap();
+    let req = register_req(&member.id.to_string());
+    let second = test
+        .send_member(UserService::register_device, req)
+        .await
+        .unwrap()
+        .device
+        .unwrap();
+    assert_eq!(first.device_id, second.device_id);
+
+    let req = api::UserServiceListDevicesRequest {
+        user_id: member.id.to_string(),
+    };
+    let resp = test
+        .send_member(UserService::list_devices, req)
+        .await
+        .unwrap();
+    assert_eq!(resp.devices.len(), 1);
+
+    let req = api::UserServiceRemoveDeviceRequest {
+        device_id: first.device_id.clone(),
+    };
+    let status = test
+        .send_admin(UserService::remove_device, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let req = api::UserServiceRemoveDeviceRequest {
+        device_id: first.device_id,
+    };
+    test.send_member(UserService::remove_device, req)
+        .await
+        .unwrap();
+
+    let mut conn = test.conn().await;
+    let devices = UserDevice::by_user(member.id, &mut conn).await.unwrap();
+    assert!(devices.is_empty());
+}
+
+#[tokio::test]
+async fn notification_preferences_filter_pushes() {
+    let test = TestServer::new().await;
+    let member = &test.seed().member;
+
+    let req = api::UserServiceRegisterDeviceRequest {
+        user_id: member.id.to_string(),
+        platform: api::PushPlatform::Apns.into(),
+        push_token: "apns-token".to_string(),
+        name: "iPhone".to_string(),
+    };
+    test.send_member(UserService::register_device, req)
+        .await
+        .unwrap();
+
+    // every notification is enabled by default
+    let req = api::UserServiceGetNotificationPreferencesRequest {
+        user_id: member.id.to_string(),
+    };
+    let prefs = test
+        .send_member(UserService::get_notification_preferences, req)
+        .await
+        .unwrap()
+        .preferences
+        .unwrap();
+    assert!(prefs.node_down);
+    assert!(prefs.payment_failed);
+
+    let req = api::UserServiceUpdateNotificationPreferencesRequest {
+        user_id: member.id.to_string(),
+        node_down: Some(false),
+        payment_failed: None,
+    };
+    let prefs = test
+        .send_member(UserService::update_notification_preferences, req)
+        .await
+        .unwrap()
+        .preferences
+        .unwrap();
+    assert!(!prefs.node_down);
+    assert!(prefs.payment_failed);
+
+    let push = test.context().push.as_ref().unwrap();
+    let user_ids = HashSet::from([member.id]);
+    let message = Message {
+        title: "title".to_string(),
+        body: "body".to_string(),
+    };
+    let mut conn = test.conn().await;
+
+    let sent = push
+        .notify(&user_ids, NotificationKind::NodeDown, &message, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(sent, 0);
+    let sent = push
+        .notify(
+            &user_ids,
+            NotificationKind::PaymentFailed,
+            &message,
+            &mut conn,
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent, 1);
+}
+
+#[tokio::test]
+async fn payment_failed_webhook_must_be_signed() {
+    let test = TestServer::new().await;
+    let mut conn = test.conn().await;
+
+    let keyring = &test.context().keyring;
+    let org = Org::by_id(test.seed().org.id, &mut conn).await.unwrap();
+    let org = org
+        .set_customer_id("cus_failed", keyring, &mut conn)
+        .await
+        .unwrap();
+    assert!(org.stripe_customer_index.is_some());
+    let customer_id = org.customer_id(keyring).unwrap().unwrap();
+    assert_eq!(customer_id.as_str(), "cus_failed");
+    let found = Org::by_customer_id("cus_failed", keyring, &mut conn)
+        .await
+        .unwrap();
+    assert_eq!(found.unwrap().id, org.id);
+    let other = Org::by_customer_id("cus_other", keyring, &mut conn)
+        .await
+        .unwrap();
+    assert!(other.is_none());
+
+    let body = serde_json::json!({
+        "id": "evt_failed",
+        "created": Utc::now().timestamp(),
+        "livemode": false,
+        "pending_webhooks": 1,
+        "type": "invoice.payment_failed",
+        "data": { "object": { "object": "invoice", "customer": "cus_failed" } },
+    })
+    .to_string();
+    let url = format!(
+        "http://{}/v1/stripe/invoice_payment_failed",
+        test.socket_addr()
+    );
+    let client = reqwest::Client::new();
+    let post = |signature: Option<String>| {
+        let mut req = client.post(&url).body(body.clone());
+        if let Some(signature) = signature {
+            req = req.header(SIGNATURE_HEADER, signature);
+        }
+        req.send()
+    };
+
+    let unsigned = post(None).await.unwrap();
+    assert_eq!(unsigned.status(), 401);
+    let forged = post(Some(sign("whsec_other", &body, Utc::now())));
+    assert_eq!(forged.await.unwrap().status(), 401);
+    let stale = post(Some(sign(
+        "whsec_test",
+        &body,
+        Utc::now() - chrono::TimeDelta::hours(1),
+    )));
+    assert_eq!(stale.await.unwrap().status(), 401);
+
+    let signed = post(Some(sign("whsec_test", &body, Utc::now())));
+    let resp = signed.await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let json: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(json["message"], "payment failure notified");
+}
+
+#[tokio::test]
+async fn locale_and_timezone_settings() {
+    let test = TestServer::new().await;
+    let user_id = test.seed().member.id.to_string();
+
+    let update_req = |key: &str, value: &str| api::UserServiceUpdateSettingsRequest {
+        user_id: user_id.clone(),
+        key: key.to_string(),
+        value: value.as_bytes().to_vec(),
+    };
+    let get_req = || api::UserServiceGetRequest {
+        user_id: user_id.clone(),
+    };
+
+    // defaults to english in UTC
+    let resp = test.send_member(UserService::get, get_req()).await.unwrap();
+    assert_eq!(resp.locale, "en");
+    assert_eq!(resp.timezone, "UTC");
+
+    let req = update_req("timezone", "Mars/Olympus_Mons");
+    let status = test
+        .send_member(UserService::update_settings, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = update_req("locale", "german");
+    let status = test
+        .send_member(UserService::update_settings, req)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let req = update_req("timezone", "Europe/Berlin");
+    test.send_member(UserService::update_settings, req)
+        .await
+        .unwrap();
+    let req = update_req("locale", "de-DE");
+    test.send_member(UserService::update_settings, req)
+        .await
+        .unwrap();
+
+    let resp = test.send_member(UserService::get, get_req()).await.unwrap();
+    assert_eq!(resp.locale, "de-DE");
+    assert_eq!(resp.timezone, "Europe/Berlin");
+}
@@ -15,7 +15,8 @@ pub async fn new_seed_user(test: &mut TestServer) -> SeedUser {
     let email = test.rand_email().await;
     let conn = &mut test.conn().await;
 
-    let user = NewUser::new(&email, "Test", &email, seed::LOGIN_PASSWORD).unwrap();
+    let policy = &test.context().config.password;
+    let user = NewUser::new(&email, "Test", &email, seed::LOGIN_PASSWORD, policy).unwrap();
     let created = user.create(conn).await.unwrap();
     let user_id = created.id;
     User::confirm(user_id, conn).await.unwrap();
@@ -13,23 +13,55 @@ pub trait GrpcClient<T> {
 }
 
 grpc_clients! [
+    abuse => Abuse,
+    admin => Admin,
+    alert => Alert,
     api_key => ApiKey,
+    approval => Approval,
     archive => Archive,
+    artifact => Artifact,
     auth => Auth,
+    billing => Billing,
     protocol => Protocol,
     bundle => Bundle,
     command => Command,
     crypt => Crypt,
     discovery => Discovery,
+    endpoint => Endpoint,
+    fixture => Fixture,
     host => Host,
     image => Image,
+    incident => Incident,
+    internal_admin => InternalAdmin,
     invitation => Invitation,
+    managed_host => ManagedHost,
+    mesh => Mesh,
     metrics => Metrics,
     node => Node,
+    node_share => NodeShare,
     org => Org,
+    org_branding => OrgBranding,
+    org_key => OrgKey,
+    org_log_sink => OrgLogSink,
+    partner => Partner,
+    pool => Pool,
+    report => Report,
+    status_page => StatusPage,
+    team => Team,
+    ticket => Ticket,
     user => User
 ];
 
+#[allow(dead_code)]
+pub type NodeServiceV2 =
+    blockvisor_api::grpc::api_v2::node_service_client::NodeServiceClient<Channel>;
+
+impl GrpcClient<Channel> for NodeServiceV2 {
+    fn create(channel: Channel) -> Self {
+        Self::new(channel)
+    }
+}
+
 pub trait SocketRpc {
     fn socket_addr(&self) -> SocketAddr;
 